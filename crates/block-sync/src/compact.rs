@@ -0,0 +1,184 @@
+//! Compact block announcements. A new block is gossiped as its header, proof and a short
+//! id per transaction; peers rebuild the block from transactions already in their pool
+//! and light clients follow the chain from the headers alone. The first transaction, the
+//! producer's coinbase, is never in anyone's pool and is sent in full, as BIP152 does.
+//! The producer's seal, which consensus needs to import the block, travels with it.
+
+use crate::error::BlockSyncError;
+use crate::{Block, BlockHeader, BlockProof, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Bytes of a transaction hash kept in its short id
+pub const SHORT_ID_LEN: usize = 6;
+
+/// Truncated transaction hash, salted with the block hash
+pub type ShortId = [u8; SHORT_ID_LEN];
+
+/// Transaction sent in full inside a compact block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefilledTransaction {
+    /// Position in the block
+    pub index: u32,
+    pub transaction: Transaction,
+}
+
+/// Block announcement without transaction bodies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactBlock {
+    pub header: BlockHeader,
+    pub proof: BlockProof,
+    /// Short id of each transaction not prefilled, in block order
+    pub short_ids: Vec<ShortId>,
+    /// Transactions sent in full, in block order
+    pub prefilled: Vec<PrefilledTransaction>,
+    /// Producer's seal of the block, opaque to the relay; empty when announced without one
+    pub seal: Vec<u8>,
+}
+
+/// Short id of `tx_hash` in the block hashing to `block_hash`. Salting with the block hash
+/// means a collision crafted against one block does not carry over to the next.
+pub fn short_id(block_hash: &[u8; 32], tx_hash: &[u8; 32]) -> ShortId {
+    let hash = pow::cn_fast_hash(&[block_hash.as_slice(), tx_hash].concat());
+    let mut id = [0u8; SHORT_ID_LEN];
+    id.copy_from_slice(&hash[..SHORT_ID_LEN]);
+    id
+}
+
+impl CompactBlock {
+    /// Announcement of `block`
+    pub fn from_block(block: &Block) -> Result<Self, BlockSyncError> {
+        let block_hash = block.header.hash()?;
+        let (coinbase, rest) = match block.transactions.split_first() {
+            Some((coinbase, rest)) => (Some(coinbase), rest),
+            None => (None, &[][..]),
+        };
+        Ok(Self {
+            header: block.header.clone(),
+            proof: block.proof.clone(),
+            short_ids: rest.iter().map(|tx| short_id(&block_hash, &tx.hash)).collect(),
+            prefilled: coinbase
+                .map(|tx| PrefilledTransaction {
+                    index: 0,
+                    transaction: tx.clone(),
+                })
+                .into_iter()
+                .collect(),
+            seal: Vec::new(),
+        })
+    }
+
+    /// Number of transactions in the block
+    pub fn len(&self) -> usize {
+        self.short_ids.len() + self.prefilled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fill each position of the block with its prefilled transaction or the one in `known`
+    /// with a matching short id, leaving `None` where no transaction matched
+    pub fn match_transactions<'a>(
+        &self,
+        known: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Result<Vec<Option<Transaction>>, BlockSyncError> {
+        let block_hash = self.header.hash()?;
+        let by_id: HashMap<ShortId, &Transaction> =
+            known.into_iter().map(|tx| (short_id(&block_hash, &tx.hash), tx)).collect();
+
+        let mut short_ids = self.short_ids.iter();
+        let mut prefilled = self.prefilled.iter().peekable();
+        let mut slots = Vec::with_capacity(self.len());
+        for index in 0..self.len() {
+            match prefilled.next_if(|prefilled| prefilled.index as usize == index) {
+                Some(prefilled) => slots.push(Some(prefilled.transaction.clone())),
+                None => {
+                    let id = short_ids.next().ok_or_else(|| {
+                        BlockSyncError::EncodingError("prefilled transactions are out of order".to_string())
+                    })?;
+                    slots.push(by_id.get(id).map(|tx| (*tx).clone()));
+                }
+            }
+        }
+        Ok(slots)
+    }
+
+    /// Assemble the block once every position is filled. Fails with `MissingTransactions`
+    /// listing the positions that still have to be fetched.
+    pub fn complete(&self, slots: Vec<Option<Transaction>>) -> Result<Block, BlockSyncError> {
+        let missing: Vec<usize> = slots.iter().enumerate().filter(|(_, tx)| tx.is_none()).map(|(i, _)| i).collect();
+        if !missing.is_empty() {
+            return Err(BlockSyncError::MissingTransactions(missing));
+        }
+        Ok(Block {
+            header: self.header.clone(),
+            transactions: slots.into_iter().flatten().collect(),
+            proof: self.proof.clone(),
+        })
+    }
+
+    /// Rebuild the full block from `known` transactions
+    pub fn reconstruct<'a>(&self, known: impl IntoIterator<Item = &'a Transaction>) -> Result<Block, BlockSyncError> {
+        self.complete(self.match_transactions(known)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Canonical, ProofType};
+
+    fn transaction(byte: u8) -> Transaction {
+        Transaction {
+            hash: [byte; 32],
+            sender: vec![byte],
+            nonce: 0,
+            gas_limit: 21_000,
+            data: Vec::new(),
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee: 1,
+            timestamp: 1,
+        }
+    }
+
+    #[test]
+    fn test_compact_block_round_trip() {
+        let block = Block {
+            header: BlockHeader {
+                height: 5,
+                prev_hash: [0x11; 32],
+                merkle_root: [0x22; 32],
+                timestamp: 1_000,
+                nonce: 3,
+                difficulty: 10,
+                nullifier_root: [0u8; 32],
+            },
+            transactions: vec![transaction(1), transaction(2)],
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![9],
+            },
+        };
+        let compact = CompactBlock::from_block(&block).unwrap();
+        assert_eq!(compact.short_ids.len(), 1);
+        assert_eq!(compact.prefilled[0].transaction.hash, [1; 32]);
+        assert!(compact.to_canonical_bytes().len() < block.to_canonical_bytes().len());
+
+        let decoded = CompactBlock::from_canonical_bytes(&compact.to_canonical_bytes()).unwrap();
+        let pool = [transaction(3), transaction(2)];
+        let rebuilt = decoded.reconstruct(&pool).unwrap();
+        assert_eq!(rebuilt.to_canonical_bytes(), block.to_canonical_bytes());
+
+        assert!(matches!(
+            decoded.reconstruct(&pool[..1]),
+            Err(BlockSyncError::MissingTransactions(missing)) if missing == vec![1]
+        ));
+    }
+}
@@ -31,4 +31,16 @@ pub enum BlockSyncError {
     
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+    
+    #[error("Encoding error: {0}")]
+    EncodingError(String),
+    
+    #[error("Unsupported encoding version {0}")]
+    UnsupportedVersion(u8),
+    
+    #[error("Missing {} transactions of a compact block", .0.len())]
+    MissingTransactions(Vec<usize>),
+    
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
 }
\ No newline at end of file
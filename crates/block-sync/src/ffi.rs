@@ -37,6 +37,7 @@ impl FuegoBlockParser {
                 timestamp: 1234567890,
                 nonce: 0,
                 difficulty: 1,
+                nullifier_root: [0u8; 32],
             };
             
             let block = Block {
@@ -72,6 +73,7 @@ impl FuegoBlockParser {
             timestamp: 1234567890,
             nonce: 0,
             difficulty: 1,
+            nullifier_root: [0u8; 32],
         };
         
         let block = Block {
@@ -143,6 +145,7 @@ mod tests {
             timestamp: 1234567890,
             nonce: 0,
             difficulty: 1,
+            nullifier_root: [0u8; 32],
         };
         
         let block = Block {
@@ -1,11 +1,19 @@
 use anyhow::Result;
+use hashing::{Domain, Hasher};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+pub mod codec;
+pub mod compact;
 pub mod error;
 pub mod ffi;
+pub mod ordering;
+pub mod signatures;
 pub mod validation;
 
+pub use codec::Canonical;
+pub use compact::{CompactBlock, PrefilledTransaction, ShortId};
+pub use ordering::OrderingPolicy;
 use error::BlockSyncError;
 
 /// Block structure for COLD L3
@@ -25,13 +33,15 @@ pub struct BlockHeader {
     pub timestamp: u64,
     pub nonce: u64,
     pub difficulty: u64,
+    /// Root of the spent-nullifier set after this block
+    #[serde(default)]
+    pub nullifier_root: [u8; 32],
 }
 
 impl BlockHeader {
+    /// Hash of the canonical encoding committed into the parent chain by merge mining
     pub fn hash(&self) -> Result<[u8; 32], BlockSyncError> {
-        // TODO: proper hashing via ffi-cryptonote
-        // For now, return a placeholder
-        Ok([0u8; 32])
+        Ok(hashing::hash(Domain::MergeMining, &self.to_canonical_bytes()))
     }
     
     pub fn verify(&self) -> Result<bool, BlockSyncError> {
@@ -51,11 +61,133 @@ impl BlockHeader {
 /// Transaction structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
+    /// Id derived from the rest of the transaction by `id`; pools, receipts and blocks
+    /// refuse a transaction whose hash is anything else
     pub hash: [u8; 32],
+    /// Account paying for the outputs, and for the fee unless `fee_payer` sponsors it
+    #[serde(default)]
+    pub sender: Vec<u8>,
+    /// Sender's transaction count, which must match its account nonce
+    #[serde(default)]
+    pub nonce: u64,
+    /// Most gas the transaction may consume; `fee` is paid pro rata to the gas used
+    #[serde(default)]
+    pub gas_limit: u64,
+    /// Call data, charged per byte as intrinsic gas
+    #[serde(default)]
+    pub data: Vec<u8>,
+    /// Nullifiers of the private notes this transaction spends
+    #[serde(default)]
+    pub nullifiers: Vec<[u8; 32]>,
+    /// Stealth outputs spent from the shielded pool, each hidden among decoys
+    #[serde(default)]
+    pub ring_inputs: Vec<RingInput>,
     pub inputs: Vec<TxInput>,
     pub outputs: Vec<TxOutput>,
     pub fee: u64,
     pub timestamp: u64,
+    /// Network the transaction is valid on, committed to by its signatures
+    #[serde(default)]
+    pub chain_id: u64,
+    /// Account sponsoring the fee in place of the sender
+    #[serde(default)]
+    pub fee_payer: Option<FeePayer>,
+    /// Bridged asset the outputs transfer, or HEAT when unset; the fee is always paid in HEAT
+    #[serde(default)]
+    pub asset: Option<[u8; 32]>,
+}
+
+impl Transaction {
+    /// Id of the transaction: its full canonical encoding, signatures and fee payer
+    /// included, with the hash field itself zeroed
+    pub fn id(&self) -> [u8; 32] {
+        let mut unhashed = self.clone();
+        unhashed.hash = [0u8; 32];
+        hashing::hash(Domain::TxId, &unhashed.to_canonical_bytes())
+    }
+
+    /// The transaction with its hash set to its id, once every other field is final
+    pub fn with_id(mut self) -> Self {
+        self.hash = self.id();
+        self
+    }
+
+    /// Whether the hash field holds the transaction's id
+    pub fn has_valid_id(&self) -> bool {
+        self.hash == self.id()
+    }
+
+    /// The transaction as its signatures see it: input and ring signatures emptied, the id
+    /// derived from them zeroed and the fee payer, which signs after the inputs, left out
+    fn unsigned(&self) -> Transaction {
+        let mut unsigned = self.clone();
+        unsigned.hash = [0u8; 32];
+        unsigned.fee_payer = None;
+        for input in &mut unsigned.inputs {
+            input.signature.clear();
+        }
+        for input in &mut unsigned.ring_inputs {
+            input.signature.clear();
+        }
+        unsigned
+    }
+
+    /// Message the transaction's signatures sign: the canonical encoding of everything but
+    /// the signatures, so no field can change under them, bound to its chain id so a
+    /// transaction signed for one network cannot be replayed on another
+    pub fn signing_hash(&self) -> [u8; 32] {
+        Hasher::new(Domain::TxSigning)
+            .u64(self.chain_id)
+            .bytes(&self.unsigned().to_canonical_bytes())
+            .finish()
+    }
+
+    /// Message a fee payer signs to sponsor this transaction from `account`
+    pub fn fee_payer_hash(&self, account: &[u8]) -> [u8; 32] {
+        Hasher::new(Domain::FeePayer)
+            .u64(self.chain_id)
+            .bytes(&self.unsigned().to_canonical_bytes())
+            .bytes(account)
+            .finish()
+    }
+
+    /// Account the fee is charged to
+    pub fn fee_account(&self) -> &[u8] {
+        match &self.fee_payer {
+            Some(payer) => &payer.account,
+            None => &self.sender,
+        }
+    }
+}
+
+/// Hashing of a block's transaction tree
+pub struct TxTreeHasher;
+
+impl merkle::MerkleHasher for TxTreeHasher {
+    fn leaf(content_hash: &[u8; 32]) -> [u8; 32] {
+        Hasher::new(Domain::TxLeaf).fixed(content_hash).finish()
+    }
+
+    fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        Hasher::new(Domain::TxNode).fixed(left).fixed(right).finish()
+    }
+}
+
+/// Merkle root of a block's transactions, committed to by its header. Each leaf hashes the
+/// transaction's full canonical encoding, hash and signatures included, so no field of a
+/// transaction can change without changing the root
+pub fn merkle_root(transactions: &[Transaction]) -> [u8; 32] {
+    let contents: Vec<[u8; 32]> =
+        transactions.iter().map(|tx| hashing::hash(Domain::TxContent, &tx.to_canonical_bytes())).collect();
+    merkle::root::<TxTreeHasher>(&contents)
+}
+
+/// Sponsor of a transaction's fee
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeePayer {
+    pub account: Vec<u8>,
+    /// The payer's ed25519 key followed by its signature of the transaction's fee payer hash
+    pub signature: Vec<u8>,
 }
 
 /// Transaction input
@@ -66,12 +198,30 @@ pub struct TxInput {
     pub signature: Vec<u8>,
 }
 
+/// Spend of one stealth output from a ring of outputs of the same amount
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RingInput {
+    pub amount: u64,
+    /// One-time keys of the real output and its decoys
+    pub ring: Vec<[u8; 32]>,
+    /// Linkable tag of the real output, recorded as a nullifier
+    pub key_image: [u8; 32],
+    /// Ring signature over the transaction's signing hash
+    pub signature: Vec<u8>,
+    /// Real output's one-time key masked under the sender's outgoing viewing key
+    #[serde(default)]
+    pub audit_tag: Option<[u8; 32]>,
+}
+
 /// Transaction output
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TxOutput {
     pub amount: u64,
     pub address: Vec<u8>,
     pub commitment: [u8; 32],
+    /// Sender's ephemeral key when `address` is a stealth one-time key
+    #[serde(default)]
+    pub ephemeral_key: Option<[u8; 32]>,
 }
 
 /// Block proof structure
@@ -263,6 +413,7 @@ mod tests {
             timestamp: 1234567890,
             nonce: 0,
             difficulty: 1,
+            nullifier_root: [0u8; 32],
         };
         
         let block = Block {
@@ -292,6 +443,7 @@ mod tests {
             timestamp: 1234567890,
             nonce: 0,
             difficulty: 1,
+            nullifier_root: [0u8; 32],
         };
         
         assert!(header.verify().unwrap());
@@ -303,6 +455,7 @@ mod tests {
             timestamp: 1234567890,
             nonce: 0,
             difficulty: 1,
+            nullifier_root: [0u8; 32],
         };
         
         assert!(invalid_header.verify().is_err());
@@ -0,0 +1,131 @@
+//! Order of the transactions within a block. Under the sequencer policy the block producer
+//! orders them as it likes. Under the deterministic policy each transaction is ranked by a
+//! hash of its id and the parent block hash, so the producer still picks which transactions
+//! a block carries but not where they go, and cannot wrap its own around someone else's.
+//! A sender's transactions keep running in nonce order: they take the slots their ranks
+//! give them, lowest nonce first.
+
+use crate::{Block, Transaction};
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Rule a block's transaction order must follow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderingPolicy {
+    /// Whatever order the block producer chose
+    #[default]
+    Sequencer,
+    /// Ranked by `order_key`, each sender's transactions in nonce order
+    Deterministic,
+}
+
+impl OrderingPolicy {
+    /// Put `transactions` of a block building on `prev_hash` in the order the policy requires
+    pub fn apply(&self, prev_hash: &[u8; 32], transactions: Vec<Transaction>) -> Vec<Transaction> {
+        match self {
+            OrderingPolicy::Sequencer => transactions,
+            OrderingPolicy::Deterministic => deterministic_order(prev_hash, transactions),
+        }
+    }
+
+    /// Position of the first transaction of `block` out of the required order
+    pub fn first_misplaced(&self, block: &Block) -> Option<usize> {
+        if matches!(self, OrderingPolicy::Sequencer) {
+            return None;
+        }
+        let expected = self.apply(&block.header.prev_hash, block.transactions.clone());
+        block
+            .transactions
+            .iter()
+            .zip(&expected)
+            .position(|(found, expected)| found.hash != expected.hash)
+    }
+}
+
+/// Rank of `tx` in a block building on `prev_hash`; lower goes first
+pub fn order_key(prev_hash: &[u8; 32], tx: &Transaction) -> [u8; 32] {
+    Hasher::new(Domain::TxOrder).fixed(prev_hash).fixed(&tx.hash).finish()
+}
+
+/// Sort `transactions` by `order_key`, then refill each sender's slots in nonce order
+pub fn deterministic_order(prev_hash: &[u8; 32], mut transactions: Vec<Transaction>) -> Vec<Transaction> {
+    transactions.sort_by_cached_key(|tx| order_key(prev_hash, tx));
+    let senders: Vec<Vec<u8>> = transactions.iter().map(|tx| tx.sender.clone()).collect();
+    let mut queues: HashMap<Vec<u8>, VecDeque<Transaction>> = HashMap::new();
+    for tx in transactions {
+        queues.entry(tx.sender.clone()).or_default().push_back(tx);
+    }
+    for queue in queues.values_mut() {
+        queue.make_contiguous().sort_by_key(|tx| tx.nonce);
+    }
+    senders
+        .iter()
+        .map(|sender| queues.get_mut(sender).and_then(VecDeque::pop_front).expect("one transaction per slot"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockHeader, BlockProof, ProofType};
+
+    fn transaction(id: u8, sender: u8, nonce: u64) -> Transaction {
+        Transaction {
+            hash: [id; 32],
+            sender: vec![sender],
+            nonce,
+            gas_limit: 21_000,
+            data: Vec::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee: 21_000,
+            timestamp: 1,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        }
+    }
+
+    #[test]
+    fn test_deterministic_order_is_fixed_by_the_parent() {
+        let prev_hash = [9u8; 32];
+        let transactions: Vec<Transaction> = (0..8u8).map(|id| transaction(id, id % 3, id as u64)).collect();
+        let ordered = OrderingPolicy::Deterministic.apply(&prev_hash, transactions.clone());
+        let mut reversed = transactions.clone();
+        reversed.reverse();
+        let hashes = |txs: &[Transaction]| txs.iter().map(|tx| tx.hash).collect::<Vec<_>>();
+        assert_eq!(hashes(&OrderingPolicy::Deterministic.apply(&prev_hash, reversed)), hashes(&ordered));
+        assert_eq!(hashes(&OrderingPolicy::Sequencer.apply(&prev_hash, transactions.clone())), hashes(&transactions));
+
+        // Each sender's transactions still run lowest nonce first
+        for sender in 0..3u8 {
+            let nonces: Vec<u64> = ordered.iter().filter(|tx| tx.sender == [sender]).map(|tx| tx.nonce).collect();
+            assert!(nonces.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", nonces);
+        }
+
+        let mut block = Block {
+            header: BlockHeader {
+                height: 1,
+                prev_hash,
+                merkle_root: [0u8; 32],
+                timestamp: 1,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions: ordered,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: Vec::new(),
+            },
+        };
+        assert_eq!(OrderingPolicy::Deterministic.first_misplaced(&block), None);
+        block.transactions.swap(0, 1);
+        assert_eq!(OrderingPolicy::Deterministic.first_misplaced(&block), Some(0));
+        assert_eq!(OrderingPolicy::Sequencer.first_misplaced(&block), None);
+    }
+}
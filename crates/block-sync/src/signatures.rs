@@ -0,0 +1,263 @@
+//! Signatures of transaction inputs. Each input carries the ed25519 key that owns the output
+//! it spends followed by that key's signature of the transaction's signing hash, which covers
+//! every field but the signatures. Inputs are therefore signed once the transaction is
+//! otherwise complete, with their signatures left empty until then. A fee payer
+//! sponsoring the transaction signs its fee payer hash the same way. A block's
+//! signatures are verified together in one batch; only when the batch fails are they checked
+//! one by one, to name the transaction that broke it.
+
+use crate::error::BlockSyncError;
+use crate::Transaction;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+/// Bytes of an input signature: the public key, then the signature
+pub const INPUT_SIGNATURE_LEN: usize = 32 + 64;
+
+/// Signature of `tx` by `key`, for one of its inputs
+pub fn sign_input(key: &SigningKey, tx: &Transaction) -> Vec<u8> {
+    let mut bytes = key.verifying_key().to_bytes().to_vec();
+    bytes.extend_from_slice(&key.sign(&tx.signing_hash()).to_bytes());
+    bytes
+}
+
+/// Signature of `tx` by `key` as its fee payer, sponsoring it from `account`
+pub fn sign_fee_payer(key: &SigningKey, tx: &Transaction, account: &[u8]) -> Vec<u8> {
+    let mut bytes = key.verifying_key().to_bytes().to_vec();
+    bytes.extend_from_slice(&key.sign(&tx.fee_payer_hash(account)).to_bytes());
+    bytes
+}
+
+/// Split a signature field into its key and signature
+fn split_signature(bytes: &[u8]) -> Result<(VerifyingKey, Signature), String> {
+    if bytes.len() != INPUT_SIGNATURE_LEN {
+        return Err(format!("{} bytes, not {}", bytes.len(), INPUT_SIGNATURE_LEN));
+    }
+    let (key, signature) = bytes.split_at(32);
+    let key = VerifyingKey::from_bytes(key.try_into().expect("split at 32 bytes")).map_err(|e| e.to_string())?;
+    Ok((key, Signature::from_slice(signature).map_err(|e| e.to_string())?))
+}
+
+/// Who signed one message of a transaction
+enum SignedBy {
+    Input(usize),
+    FeePayer,
+}
+
+/// One signature a transaction carries, with the message it signs
+struct SignedMessage {
+    by: SignedBy,
+    message: [u8; 32],
+    key: VerifyingKey,
+    signature: Signature,
+}
+
+impl SignedMessage {
+    fn parse(tx: &Transaction, by: SignedBy, message: [u8; 32], bytes: &[u8]) -> Result<Self, BlockSyncError> {
+        let (key, signature) = split_signature(bytes)
+            .map_err(|reason| BlockSyncError::InvalidSignature(format!("{}: {}", describe(tx, &by), reason)))?;
+        Ok(Self {
+            by,
+            message,
+            key,
+            signature,
+        })
+    }
+
+    fn verify(&self, tx: &Transaction) -> Result<(), BlockSyncError> {
+        self.key.verify(&self.message, &self.signature).map_err(|_| {
+            BlockSyncError::InvalidSignature(format!("{} does not verify", describe(tx, &self.by)))
+        })
+    }
+}
+
+fn describe(tx: &Transaction, by: &SignedBy) -> String {
+    match by {
+        SignedBy::Input(index) => format!("input {} of {}", index, hex::encode(tx.hash)),
+        SignedBy::FeePayer => format!("fee payer of {}", hex::encode(tx.hash)),
+    }
+}
+
+/// The fee payer's signature of `tx`, if it has a fee payer
+fn fee_payer_signature(tx: &Transaction) -> Result<Option<SignedMessage>, BlockSyncError> {
+    tx.fee_payer
+        .as_ref()
+        .map(|payer| SignedMessage::parse(tx, SignedBy::FeePayer, tx.fee_payer_hash(&payer.account), &payer.signature))
+        .transpose()
+}
+
+/// Every signature `tx` carries: one per input, then the fee payer's
+fn signed_messages(tx: &Transaction) -> Result<Vec<SignedMessage>, BlockSyncError> {
+    let message = tx.signing_hash();
+    let mut signed = tx
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| SignedMessage::parse(tx, SignedBy::Input(index), message, &input.signature))
+        .collect::<Result<Vec<_>, _>>()?;
+    signed.extend(fee_payer_signature(tx)?);
+    Ok(signed)
+}
+
+/// Verify every input signature of `tx`, and its fee payer's, on its own
+pub fn verify_transaction(tx: &Transaction) -> Result<(), BlockSyncError> {
+    signed_messages(tx)?.iter().try_for_each(|signed| signed.verify(tx))
+}
+
+/// Verify only the fee payer's signature of `tx`, for sponsoring a transaction whose inputs
+/// may not be signed yet
+pub fn verify_fee_payer(tx: &Transaction) -> Result<(), BlockSyncError> {
+    fee_payer_signature(tx)?.map_or(Ok(()), |signed| signed.verify(tx))
+}
+
+/// Keys whose valid signatures the inputs of `tx` carry, in input order
+pub fn input_signers(tx: &Transaction) -> Result<Vec<[u8; 32]>, BlockSyncError> {
+    let message = tx.signing_hash();
+    tx.inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let signed = SignedMessage::parse(tx, SignedBy::Input(index), message, &input.signature)?;
+            signed.verify(tx)?;
+            Ok(signed.key.to_bytes())
+        })
+        .collect()
+}
+
+/// Verify the input and fee payer signatures of every transaction in one batch, falling back to checking
+/// them one by one when the batch fails; those single checks decide. The error names the
+/// first offending transaction and its position.
+pub fn verify_batch(transactions: &[Transaction]) -> Result<(), (usize, BlockSyncError)> {
+    let mut messages = Vec::new();
+    let mut keys = Vec::new();
+    let mut signatures = Vec::new();
+    for (tx_index, tx) in transactions.iter().enumerate() {
+        for signed in signed_messages(tx).map_err(|e| (tx_index, e))? {
+            messages.push(signed.message);
+            keys.push(signed.key);
+            signatures.push(signed.signature);
+        }
+    }
+    if signatures.is_empty() {
+        return Ok(());
+    }
+    let messages: Vec<&[u8]> = messages.iter().map(|message| message.as_slice()).collect();
+    if ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok() {
+        return Ok(());
+    }
+    transactions
+        .iter()
+        .enumerate()
+        .try_for_each(|(tx_index, tx)| verify_transaction(tx).map_err(|e| (tx_index, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FeePayer, TxInput, TxOutput};
+
+    fn signed(index: u8) -> Transaction {
+        let mut tx = Transaction {
+            hash: [index; 32],
+            sender: vec![index],
+            nonce: 0,
+            gas_limit: 21_000,
+            data: Vec::new(),
+            inputs: Vec::new(),
+            outputs: vec![TxOutput {
+                amount: 1,
+                address: vec![0xb0],
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 21_000,
+            timestamp: 1,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        };
+        tx.inputs.push(TxInput {
+            prev_tx_hash: [0u8; 32],
+            output_index: 0,
+            signature: Vec::new(),
+        });
+        tx.inputs[0].signature = sign_input(&SigningKey::from_bytes(&[index; 32]), &tx);
+        tx
+    }
+
+    #[test]
+    fn test_signatures_cover_the_transaction_content() {
+        assert!(verify_transaction(&signed(1)).is_ok());
+
+        // Changing any field under the original signature breaks it
+        let mut inflated = signed(1);
+        inflated.outputs[0].amount += 1;
+        assert!(verify_transaction(&inflated).is_err());
+        let mut redirected = signed(1);
+        redirected.outputs[0].address = vec![0xb1];
+        assert!(verify_transaction(&redirected).is_err());
+
+        // The hash is the id derived from everything else, signatures included, so it is not signed
+        let mut renamed = signed(1);
+        renamed.hash = [0xee; 32];
+        assert!(verify_transaction(&renamed).is_ok());
+        assert!(!renamed.has_valid_id());
+        let identified = signed(1).with_id();
+        assert!(verify_transaction(&identified).is_ok() && identified.has_valid_id());
+        assert_ne!(signed(1).id(), signed(2).id());
+
+        // Signatures are not part of what they sign, so a fee payer may join afterwards
+        let payer = SigningKey::from_bytes(&[0xfe; 32]);
+        let mut sponsored = signed(1);
+        let signature = sign_fee_payer(&payer, &sponsored, &[0xfe]);
+        sponsored.fee_payer = Some(FeePayer { account: vec![0xfe], signature });
+        assert!(verify_transaction(&sponsored).is_ok());
+        sponsored.fee = 1;
+        assert!(verify_transaction(&sponsored).is_err());
+    }
+
+    #[test]
+    fn test_batch_verification_names_the_offending_transaction() {
+        let mut transactions: Vec<Transaction> = (1..=16).map(signed).collect();
+        assert!(verify_batch(&transactions).is_ok());
+        assert!(verify_batch(&[]).is_ok());
+
+        // A signature replayed onto another transaction fails the batch and is then found
+        transactions[9].inputs[0].signature = transactions[3].inputs[0].signature.clone();
+        let (tx_index, error) = verify_batch(&transactions).unwrap_err();
+        assert_eq!(tx_index, 9);
+        assert!(error.to_string().contains("does not verify"), "{}", error);
+        assert!(verify_transaction(&transactions[3]).is_ok());
+
+        // So does a transaction whose output was raised under its copied original signature
+        let mut tampered: Vec<Transaction> = (1..=16).map(signed).collect();
+        tampered[5].outputs[0].amount += 1_000_000;
+        let (tx_index, error) = verify_batch(&tampered).unwrap_err();
+        assert_eq!(tx_index, 5);
+        assert!(error.to_string().contains("does not verify"), "{}", error);
+
+        // And one signed for another chain
+        let mut replayed = signed(20);
+        replayed.chain_id = 2;
+        assert_eq!(verify_batch(&[signed(21), replayed]).unwrap_err().0, 1);
+
+        // Malformed signatures are refused before any curve arithmetic
+        let mut truncated = signed(22);
+        truncated.inputs[0].signature.truncate(64);
+        let (tx_index, error) = verify_batch(&[signed(23), signed(24), truncated]).unwrap_err();
+        assert_eq!(tx_index, 2);
+        assert!(error.to_string().contains("64 bytes"), "{}", error);
+
+        // A fee payer's signature covers the account it pays from
+        let payer = SigningKey::from_bytes(&[0xfe; 32]);
+        let mut sponsored = signed(25);
+        let signature = sign_fee_payer(&payer, &sponsored, &[0xfe]);
+        sponsored.fee_payer = Some(FeePayer { account: vec![0xfe], signature });
+        assert!(verify_batch(&[signed(26), sponsored.clone()]).is_ok());
+        sponsored.fee_payer.as_mut().unwrap().account = vec![0xff];
+        let (tx_index, error) = verify_batch(&[signed(26), sponsored]).unwrap_err();
+        assert_eq!(tx_index, 1);
+        assert!(error.to_string().starts_with("Invalid signature: fee payer of "), "{}", error);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::BlockSyncError;
 use crate::{Block, Transaction, BlockProof, ProofType};
+use pow::{CryptoNight, MergeMinedProof};
 
 /// Block validation utilities
 pub struct BlockValidator;
@@ -24,9 +25,42 @@ impl BlockValidator {
             return Ok(false);
         }
         
+        // Merge-mined blocks carry an AuxPoW binding them to Fuego work
+        if !Self::validate_merge_mining(block)? {
+            return Ok(false);
+        }
+        
         Ok(true)
     }
     
+    /// Validate the AuxPoW of a merge-mined block. Every block must be merge-mined, so a block
+    /// without PoW proof data fails.
+    pub fn validate_merge_mining(block: &Block) -> Result<bool, BlockSyncError> {
+        if !matches!(block.proof.proof_type, ProofType::PoW) || block.proof.proof_data.is_empty() {
+            println!("Rejecting block {}: no AuxPoW", block.header.height);
+            return Ok(false);
+        }
+        
+        let proof: MergeMinedProof = match serde_json::from_slice(&block.proof.proof_data) {
+            Ok(proof) => proof,
+            Err(_) => return Ok(false),
+        };
+        
+        let aux_hash = block.header.hash()?;
+        match proof.aux_pow.verify_work(
+            &proof.parent_header,
+            &aux_hash,
+            block.header.difficulty,
+            &CryptoNight::upx2(),
+        ) {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                println!("Rejecting merge-mined block {}: {}", block.header.height, e);
+                Ok(false)
+            }
+        }
+    }
+    
     /// Validate a transaction
     pub async fn validate_transaction(tx: &Transaction) -> Result<bool, BlockSyncError> {
         // Basic transaction validation
@@ -124,7 +158,7 @@ mod tests {
     use crate::{BlockHeader, TxInput, TxOutput};
     
     #[tokio::test]
-    async fn test_block_validation() {
+    async fn test_block_without_auxpow_is_rejected() {
         let header = BlockHeader {
             height: 1,
             prev_hash: [0u8; 32],
@@ -132,6 +166,7 @@ mod tests {
             timestamp: 1234567890,
             nonce: 0,
             difficulty: 1,
+            nullifier_root: [0u8; 32],
         };
         
         let block = Block {
@@ -143,13 +178,67 @@ mod tests {
             },
         };
         
-        assert!(BlockValidator::validate_block(&block).await.unwrap());
+        assert!(!BlockValidator::validate_block(&block).await.unwrap());
+        
+        // A PoS proof carries no parent work either
+        let mut block = block;
+        block.proof.proof_type = ProofType::PoS;
+        block.proof.proof_data = vec![1u8; 64];
+        assert!(!BlockValidator::validate_merge_mining(&block).unwrap());
+    }
+    
+    fn merge_mined_header() -> BlockHeader {
+        BlockHeader {
+            height: 1,
+            prev_hash: [0u8; 32],
+            merkle_root: [1u8; 32],
+            timestamp: 1234567890,
+            nonce: 0,
+            difficulty: 1,
+            nullifier_root: [0u8; 32],
+        }
+    }
+    
+    fn merge_mined_block(proof: &MergeMinedProof) -> Block {
+        Block {
+            header: merge_mined_header(),
+            transactions: vec![],
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: serde_json::to_vec(proof).unwrap(),
+            },
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_merge_mined_block_validation() {
+        let aux_hash = merge_mined_header().hash().unwrap();
+        
+        let mut parent_header = pow::ParentBlockHeader::default();
+        let coinbase = pow::auxpow::bare_coinbase(1);
+        let aux_pow = pow::AuxPow::create(aux_hash, coinbase, &[[2u8; 32]], &mut parent_header).unwrap();
+        let proof = MergeMinedProof { parent_header, aux_pow };
+        assert!(BlockValidator::validate_block(&merge_mined_block(&proof)).await.unwrap());
+        
+        // A coinbase that does not commit to this block is rejected
+        let mut forged = proof.clone();
+        forged.aux_pow.coinbase_tx = pow::auxpow::bare_coinbase(2);
+        assert!(!BlockValidator::validate_block(&merge_mined_block(&forged)).await.unwrap());
+        
+        // Undecodable proof data is rejected
+        let mut block = merge_mined_block(&proof);
+        block.proof.proof_data = vec![0xff];
+        assert!(!BlockValidator::validate_block(&block).await.unwrap());
     }
     
     #[tokio::test]
     async fn test_transaction_validation() {
         let tx = Transaction {
             hash: [0u8; 32],
+            sender: Vec::new(),
+            nonce: 0,
+            gas_limit: 0,
+            data: Vec::new(),
             inputs: vec![TxInput {
                 prev_tx_hash: [0u8; 32],
                 output_index: 0,
@@ -159,9 +248,15 @@ mod tests {
                 amount: 100,
                 address: vec![1u8; 32],
                 commitment: [0u8; 32],
+                ephemeral_key: None,
             }],
             fee: 1,
             timestamp: 1234567890,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
         };
         
         assert!(BlockValidator::validate_transaction(&tx).await.unwrap());
@@ -171,10 +266,19 @@ mod tests {
     async fn test_invalid_transaction() {
         let tx = Transaction {
             hash: [0u8; 32],
+            sender: Vec::new(),
+            nonce: 0,
+            gas_limit: 0,
+            data: Vec::new(),
             inputs: vec![],
             outputs: vec![],
             fee: 0,
             timestamp: 1234567890,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
         };
         
         assert!(!BlockValidator::validate_transaction(&tx).await.unwrap());
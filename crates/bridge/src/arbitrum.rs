@@ -1,5 +1,7 @@
 use crate::error::BridgeError;
+use crate::settlement::{self, BatchFinality, CommittedBatch, SettlementBackend, SettlementLayer};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time::{Duration, Instant};
@@ -32,7 +34,12 @@ pub enum SubmissionStatus {
 pub struct ArbitrumClient {
     rpc_url: String,
     contract_address: String,
-    submissions: Arc<RwLock<std::collections::HashMap<[u8; 32], SubmissionResult>>>,
+    /// How long a committed batch can be challenged with a fraud proof
+    challenge_period: Duration,
+    submissions: Arc<RwLock<HashMap<[u8; 32], SubmissionResult>>>,
+    withdrawal_roots: Arc<RwLock<HashMap<u64, [u8; 32]>>>,
+    batches: Arc<RwLock<HashMap<u64, CommittedBatch>>>,
+    deposits: Arc<RwLock<u64>>,
     last_submission_time: Arc<RwLock<Instant>>,
 }
 
@@ -42,11 +49,20 @@ impl ArbitrumClient {
         Ok(Self {
             rpc_url,
             contract_address,
-            submissions: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            challenge_period: Duration::from_secs(7 * 24 * 3600),
+            submissions: Arc::new(RwLock::new(HashMap::new())),
+            withdrawal_roots: Arc::new(RwLock::new(HashMap::new())),
+            batches: Arc::new(RwLock::new(HashMap::new())),
+            deposits: Arc::new(RwLock::new(0)),
             last_submission_time: Arc::new(RwLock::new(Instant::now())),
         })
     }
     
+    pub fn with_challenge_period(mut self, challenge_period: Duration) -> Self {
+        self.challenge_period = challenge_period;
+        self
+    }
+    
     /// Submit a proof to Arbitrum
     pub async fn submit_proof(&self, submission: ProofSubmission) -> Result<(), BridgeError> {
         // In a real implementation, this would make an RPC call to Arbitrum
@@ -74,6 +90,31 @@ impl ArbitrumClient {
         Ok(())
     }
     
+    /// Commit the Merkle root of withdrawal batch `batch_index` to the bridge contract
+    pub async fn submit_withdrawal_root(&self, batch_index: u64, root: [u8; 32]) -> Result<(), BridgeError> {
+        // In a real implementation, this would call the bridge contract on Arbitrum
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        
+        let mut roots = self.withdrawal_roots.write().await;
+        if roots.get(&batch_index).is_some_and(|committed| *committed != root) {
+            return Err(BridgeError::ArbitrumError(format!(
+                "Withdrawal batch {} already has a different root",
+                batch_index
+            )));
+        }
+        roots.insert(batch_index, root);
+        *self.last_submission_time.write().await = Instant::now();
+        
+        println!("Withdrawal root committed to Arbitrum: batch {}", batch_index);
+        
+        Ok(())
+    }
+    
+    /// Get the withdrawal root committed for `batch_index`
+    pub async fn get_withdrawal_root(&self, batch_index: u64) -> Option<[u8; 32]> {
+        self.withdrawal_roots.read().await.get(&batch_index).copied()
+    }
+    
     /// Get submission result
     pub async fn get_submission_result(&self, header_hash: &[u8; 32]) -> Option<SubmissionResult> {
         self.submissions.read().await.get(header_hash).cloned()
@@ -107,6 +148,81 @@ impl ArbitrumClient {
     }
 }
 
+impl SettlementBackend for ArbitrumClient {
+    fn layer(&self) -> SettlementLayer {
+        SettlementLayer::Arbitrum
+    }
+    
+    async fn commit_batch(&self, number: u64, commitment: [u8; 32]) -> Result<(), BridgeError> {
+        settlement::record_commitment(&mut *self.batches.write().await, number, commitment)
+    }
+    
+    async fn submit_proof(&self, submission: ProofSubmission) -> Result<(), BridgeError> {
+        ArbitrumClient::submit_proof(self, submission).await
+    }
+    
+    async fn prove_batch(&self, number: u64, _proof: &[u8]) -> Result<(), BridgeError> {
+        Err(BridgeError::SettlementError(format!(
+            "Arbitrum batch {} is settled optimistically and takes no validity proof",
+            number
+        )))
+    }
+    
+    /// Open a challenge of the batch, which holds its finality until it is resolved
+    async fn submit_fraud_proof(&self, number: u64, proof: &[u8]) -> Result<(), BridgeError> {
+        let mut batches = self.batches.write().await;
+        let batch = batches
+            .get_mut(&number)
+            .ok_or_else(|| BridgeError::SettlementError(format!("Batch {} was never committed", number)))?;
+        if batch.committed_at.elapsed() >= self.challenge_period {
+            return Err(BridgeError::SettlementError(format!("Challenge period of batch {} is over", number)));
+        }
+        if proof.is_empty() || batch.finality != BatchFinality::Committed {
+            return Err(BridgeError::SettlementError(format!("Fraud proof against batch {} is void", number)));
+        }
+        batch.finality = BatchFinality::Disputed;
+        println!("Batch {} challenged on Arbitrum", number);
+        Ok(())
+    }
+    
+    async fn resolve_fraud_proof(&self, number: u64, upheld: Option<String>) -> Result<(), BridgeError> {
+        let mut batches = self.batches.write().await;
+        let batch = batches
+            .get_mut(&number)
+            .filter(|batch| batch.finality == BatchFinality::Disputed)
+            .ok_or_else(|| BridgeError::SettlementError(format!("Batch {} is not disputed", number)))?;
+        batch.finality = match upheld {
+            Some(reason) => BatchFinality::Challenged(reason),
+            None => BatchFinality::Committed,
+        };
+        println!("Challenge of batch {} resolved on Arbitrum: {:?}", number, batch.finality);
+        Ok(())
+    }
+    
+    async fn deposit(&self, recipient: &[u8], amount: u64) -> Result<[u8; 32], BridgeError> {
+        let mut deposits = self.deposits.write().await;
+        let id = settlement::deposit_id(SettlementLayer::Arbitrum, *deposits, recipient, amount);
+        *deposits += 1;
+        Ok(id)
+    }
+    
+    async fn withdraw(&self, batch_index: u64, root: [u8; 32]) -> Result<(), BridgeError> {
+        self.submit_withdrawal_root(batch_index, root).await
+    }
+    
+    async fn finality(&self, number: u64) -> BatchFinality {
+        match self.batches.read().await.get(&number) {
+            None => BatchFinality::Unknown,
+            Some(batch) if batch.finality == BatchFinality::Committed
+                && batch.committed_at.elapsed() >= self.challenge_period =>
+            {
+                BatchFinality::Finalized
+            }
+            Some(batch) => batch.finality.clone(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,80 @@
+//! Signatures by which the bridge authorizes the mints it derives.
+//!
+//! A mint executes only when enough of the chain's attesters signed it, so it is backed by
+//! what the block carries rather than by whatever a node has recorded. The attester signs
+//! a mint as its one input, over the same signing hash user transactions are signed over.
+
+use crate::error::BridgeError;
+use block_sync::signatures::sign_input;
+use block_sync::{Transaction, TxInput};
+use ed25519_dalek::SigningKey;
+
+/// Key attesting the mints this node derives, for one chain
+#[derive(Clone)]
+pub struct MintAttester {
+    key: SigningKey,
+    chain_id: u64,
+}
+
+impl MintAttester {
+    pub fn new(key: SigningKey, chain_id: u64) -> Self {
+        Self { key, chain_id }
+    }
+
+    /// Attester holding the hex ed25519 secret `secret`
+    pub fn from_hex(secret: &str, chain_id: u64) -> Result<Self, BridgeError> {
+        let secret = hex::decode(secret.trim_start_matches("0x"))
+            .ok()
+            .and_then(|secret| <[u8; 32]>::try_from(secret).ok())
+            .ok_or_else(|| BridgeError::ConfigError("Mint attester key must be 32 hex bytes".to_string()))?;
+        Ok(Self::new(SigningKey::from_bytes(&secret), chain_id))
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    /// `mint` bound to this attester's chain and signed by it
+    pub fn attest(&self, mut mint: Transaction) -> Transaction {
+        mint.chain_id = self.chain_id;
+        mint.inputs = vec![TxInput { prev_tx_hash: [0u8; 32], output_index: 0, signature: Vec::new() }];
+        mint.inputs[0].signature = sign_input(&self.key, &mint);
+        mint.with_id()
+    }
+}
+
+/// `mint` as `attester` signs it, or unsigned without one; no chain executes an unsigned mint
+pub(crate) fn attested(attester: Option<&MintAttester>, mint: Transaction) -> Transaction {
+    match attester {
+        Some(attester) => attester.attest(mint),
+        None => mint,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deposits::Deposit;
+    use block_sync::signatures::input_signers;
+
+    #[test]
+    fn test_attested_mints_are_signed_for_the_chain() {
+        let deposit = Deposit {
+            l1_block: 7,
+            l1_tx_hash: [1u8; 32],
+            log_index: 0,
+            depositor: vec![0x11; 20],
+            recipient: vec![0xb0],
+            amount: 700,
+            token: None,
+        };
+        let attester = MintAttester::from_hex(&hex::encode([0xa7u8; 32]), 3).unwrap();
+        let mint = attester.attest(deposit.mint_transaction(0, None));
+        assert_eq!(mint.chain_id, 3);
+        assert!(mint.has_valid_id());
+        assert_eq!(input_signers(&mint).unwrap(), vec![attester.public_key()]);
+        assert_eq!(attested(Some(&attester), deposit.mint_transaction(0, None)).hash, mint.hash);
+        assert!(input_signers(&attested(None, deposit.mint_transaction(0, None))).unwrap().is_empty());
+        assert!(MintAttester::from_hex("a7", 3).is_err());
+    }
+}
@@ -0,0 +1,593 @@
+use crate::deposits::{decode_hex, parse_quantity, L1Client};
+use crate::error::BridgeError;
+use block_sync::Block;
+use execution::MINT_ADDRESS;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha3::{Digest, Keccak256};
+use state_db::RocksStateDB;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::{Duration, Instant};
+
+const BUILDER_KEY: &[u8] = b"bridge/batches/builder";
+const BATCH_PREFIX: &[u8] = b"bridge/batches/batch/";
+
+/// Batch builder configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchBuilderConfig {
+    /// Blocks sealed into one batch
+    pub batch_size: usize,
+    /// Longest a partial batch waits for more blocks
+    pub batch_timeout: Duration,
+}
+
+impl Default for BatchBuilderConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            batch_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+/// A sealed C0DL3 block waiting to be batched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchBlock {
+    pub height: u64,
+    pub hash: [u8; 32],
+    pub transactions: u64,
+    /// Hashes of the L1-originated mints the block executed
+    pub priority_ops: Vec<[u8; 32]>,
+}
+
+/// L1 transaction that committed a batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchSubmission {
+    pub tx_hash: [u8; 32],
+    pub nonce: u64,
+    pub gas_price: u64,
+    pub submitted_at: u64,
+}
+
+/// Consecutive C0DL3 blocks committed to L1 as one unit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L1Batch {
+    pub number: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    /// Hash chain over the block hashes, in height order
+    pub blocks_hash: [u8; 32],
+    /// State root committed at `last_block`
+    pub state_root: [u8; 32],
+    /// Hash chain over the priority operations, in execution order
+    pub priority_ops_hash: [u8; 32],
+    pub priority_ops: u64,
+    pub transactions: u64,
+    pub submission: Option<BatchSubmission>,
+}
+
+impl L1Batch {
+    /// Hash the L1 contract stores for the batch
+    pub fn commitment(&self) -> [u8; 32] {
+        Keccak256::digest(self.calldata_words()).into()
+    }
+
+    /// ABI-encoded arguments of `commitBatch(uint256,uint256,uint256,bytes32,bytes32,bytes32)`
+    fn calldata_words(&self) -> Vec<u8> {
+        let mut words = Vec::with_capacity(6 * 32);
+        for number in [self.number, self.first_block, self.last_block] {
+            words.extend_from_slice(&[0u8; 24]);
+            words.extend_from_slice(&number.to_be_bytes());
+        }
+        words.extend_from_slice(&self.blocks_hash);
+        words.extend_from_slice(&self.state_root);
+        words.extend_from_slice(&self.priority_ops_hash);
+        words
+    }
+
+    /// Calldata of the commitment transaction
+    pub fn commit_calldata(&self) -> Vec<u8> {
+        let selector = Keccak256::digest(b"commitBatch(uint256,uint256,uint256,bytes32,bytes32,bytes32)");
+        [&selector[..4], self.calldata_words().as_slice()].concat()
+    }
+}
+
+fn chain_hash(previous: &[u8; 32], item: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(previous);
+    hasher.update(item);
+    hasher.finalize().into()
+}
+
+fn batch_key(number: u64) -> Vec<u8> {
+    [BATCH_PREFIX, number.to_be_bytes().as_slice()].concat()
+}
+
+/// Get the sealed batch `number`
+pub fn get_batch(state: &RocksStateDB, number: u64) -> Result<Option<L1Batch>, BridgeError> {
+    match state.get_sync(&batch_key(number))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Progress of the builder, persisted with every change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct BuilderState {
+    next_batch: u64,
+    /// First batch whose commitment has not been sent
+    next_unsubmitted: u64,
+    /// Height the next block must have, once any block was seen
+    next_block: Option<u64>,
+    pending: Vec<BatchBlock>,
+}
+
+/// Groups sealed C0DL3 blocks into L1 batches.
+///
+/// Blocks must arrive in height order after their state was committed. A batch is sealed
+/// once it holds `batch_size` blocks or its first block has waited `batch_timeout`.
+pub struct BatchBuilder {
+    config: BatchBuilderConfig,
+    db: Arc<RwLock<RocksStateDB>>,
+    state: BuilderState,
+    opened_at: Option<Instant>,
+}
+
+impl BatchBuilder {
+    /// Create a builder resuming the pending blocks stored in `db`
+    pub async fn with_state_db(config: BatchBuilderConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, BridgeError> {
+        if config.batch_size == 0 {
+            return Err(BridgeError::ConfigError("Batch size must be positive".to_string()));
+        }
+        let state: BuilderState = match db.read().await.get_sync(BUILDER_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => BuilderState::default(),
+        };
+        let opened_at = (!state.pending.is_empty()).then(Instant::now);
+        Ok(Self {
+            config,
+            db,
+            state,
+            opened_at,
+        })
+    }
+
+    /// Add the next sealed block, returning the batch it completed
+    pub async fn add_block(&mut self, block: &Block) -> Result<Option<L1Batch>, BridgeError> {
+        let height = block.header.height;
+        if self.state.next_block.is_some_and(|expected| height != expected) {
+            return Err(BridgeError::InvalidHeader);
+        }
+        let summary = BatchBlock {
+            height,
+            hash: block.header.hash()?,
+            transactions: block.transactions.len() as u64,
+            priority_ops: block
+                .transactions
+                .iter()
+                .filter(|tx| tx.sender == MINT_ADDRESS)
+                .map(|tx| tx.hash)
+                .collect(),
+        };
+
+        let mut state = self.state.clone();
+        state.pending.push(summary);
+        state.next_block = Some(height + 1);
+        if state.pending.len() >= self.config.batch_size {
+            return self.seal(state).await.map(Some);
+        }
+        self.save(&state, Vec::new()).await?;
+        self.state = state;
+        self.opened_at.get_or_insert_with(Instant::now);
+        Ok(None)
+    }
+
+    /// Seal the pending blocks if the oldest has waited out the batch timeout
+    pub async fn seal_if_expired(&mut self) -> Result<Option<L1Batch>, BridgeError> {
+        match self.opened_at {
+            Some(opened_at) if opened_at.elapsed() >= self.config.batch_timeout => {
+                self.seal(self.state.clone()).await.map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn seal(&mut self, mut state: BuilderState) -> Result<L1Batch, BridgeError> {
+        let blocks = std::mem::take(&mut state.pending);
+        let (first, last) = match (blocks.first(), blocks.last()) {
+            (Some(first), Some(last)) => (first.height, last.height),
+            _ => return Err(BridgeError::Unknown("Cannot seal an empty batch".to_string())),
+        };
+        let state_root = self
+            .db
+            .read()
+            .await
+            .root_at(last)?
+            .ok_or_else(|| BridgeError::StateError(format!("Block {} has no committed state", last)))?;
+
+        let mut batch = L1Batch {
+            number: state.next_batch,
+            first_block: first,
+            last_block: last,
+            blocks_hash: [0u8; 32],
+            state_root,
+            priority_ops_hash: [0u8; 32],
+            priority_ops: 0,
+            transactions: 0,
+            submission: None,
+        };
+        for block in &blocks {
+            batch.blocks_hash = chain_hash(&batch.blocks_hash, &block.hash);
+            for op in &block.priority_ops {
+                batch.priority_ops_hash = chain_hash(&batch.priority_ops_hash, op);
+            }
+            batch.priority_ops += block.priority_ops.len() as u64;
+            batch.transactions += block.transactions;
+        }
+        state.next_batch += 1;
+
+        self.save(&state, vec![(batch_key(batch.number), serde_json::to_vec(&batch)?)]).await?;
+        self.state = state;
+        self.opened_at = None;
+        Ok(batch)
+    }
+
+    /// Sealed batches whose commitment has not been sent, oldest first
+    pub async fn unsubmitted(&self) -> Result<Vec<L1Batch>, BridgeError> {
+        let db = self.db.read().await;
+        let mut batches = Vec::new();
+        for number in self.state.next_unsubmitted..self.state.next_batch {
+            batches.push(
+                get_batch(&db, number)?
+                    .ok_or_else(|| BridgeError::StateError(format!("Batch {} is missing", number)))?,
+            );
+        }
+        Ok(batches)
+    }
+
+    /// Record the L1 transaction that committed the oldest unsubmitted batch
+    pub async fn record_submission(&mut self, number: u64, submission: BatchSubmission) -> Result<(), BridgeError> {
+        if number != self.state.next_unsubmitted || number >= self.state.next_batch {
+            return Err(BridgeError::ProofSubmissionError(format!(
+                "Batch {} is not the next to submit",
+                number
+            )));
+        }
+        let mut batch = get_batch(&*self.db.read().await, number)?
+            .ok_or_else(|| BridgeError::StateError(format!("Batch {} is missing", number)))?;
+        batch.submission = Some(submission);
+
+        let mut state = self.state.clone();
+        state.next_unsubmitted += 1;
+        self.save(&state, vec![(batch_key(number), serde_json::to_vec(&batch)?)]).await?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Number of blocks waiting for a batch
+    pub fn pending_blocks(&self) -> usize {
+        self.state.pending.len()
+    }
+
+    /// Number the next sealed batch will get
+    pub fn next_batch(&self) -> u64 {
+        self.state.next_batch
+    }
+
+    async fn save(&self, state: &BuilderState, mut entries: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), BridgeError> {
+        entries.push((BUILDER_KEY.to_vec(), serde_json::to_vec(state)?));
+        self.db.write().await.write_batch_sync(&entries)?;
+        Ok(())
+    }
+}
+
+/// Transaction carrying a batch commitment to the L1 bridge contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentTransaction {
+    pub from: String,
+    pub to: String,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub gas_price: u64,
+    pub data: Vec<u8>,
+}
+
+/// L1 access needed to send commitment transactions
+pub trait CommitmentTransport: Send + Sync {
+    /// Next nonce of `address`, counting transactions still in the mempool
+    fn pending_nonce(&self, address: &str) -> impl Future<Output = Result<u64, BridgeError>> + Send;
+
+    /// Current gas price in wei
+    fn gas_price(&self) -> impl Future<Output = Result<u64, BridgeError>> + Send;
+
+    /// Send `transaction`, returning its hash
+    fn send_transaction(
+        &self,
+        transaction: &CommitmentTransaction,
+    ) -> impl Future<Output = Result<[u8; 32], BridgeError>> + Send;
+}
+
+impl CommitmentTransport for L1Client {
+    async fn pending_nonce(&self, address: &str) -> Result<u64, BridgeError> {
+        let result = self.call("eth_getTransactionCount", json!([address, "pending"])).await?;
+        parse_quantity(result.as_str().unwrap_or_default())
+    }
+
+    async fn gas_price(&self) -> Result<u64, BridgeError> {
+        let result = self.call("eth_gasPrice", json!([])).await?;
+        parse_quantity(result.as_str().unwrap_or_default())
+    }
+
+    /// Sent through `eth_sendTransaction`, so the L1 node signs for the `from` account
+    async fn send_transaction(&self, transaction: &CommitmentTransaction) -> Result<[u8; 32], BridgeError> {
+        let request = json!([{
+            "from": transaction.from,
+            "to": transaction.to,
+            "nonce": format!("0x{:x}", transaction.nonce),
+            "gas": format!("0x{:x}", transaction.gas_limit),
+            "gasPrice": format!("0x{:x}", transaction.gas_price),
+            "data": format!("0x{}", hex::encode(&transaction.data)),
+        }]);
+        let result = self.call("eth_sendTransaction", request).await?;
+        decode_hex(result.as_str().unwrap_or_default())?
+            .try_into()
+            .map_err(|_| BridgeError::ArbitrumError("eth_sendTransaction: invalid hash".to_string()))
+    }
+}
+
+/// Commitment submission settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitterConfig {
+    /// L1 account sending commitments
+    pub from: String,
+    /// Bridge contract receiving commitments
+    pub to: String,
+    pub gas_limit: u64,
+    /// Highest gas price paid; commitments wait while the L1 is pricier
+    pub max_gas_price: u64,
+}
+
+impl Default for SubmitterConfig {
+    fn default() -> Self {
+        Self {
+            from: "0x0000000000000000000000000000000000000000".to_string(),
+            to: "0x0000000000000000000000000000000000000000".to_string(),
+            gas_limit: 300_000,
+            max_gas_price: 100_000_000_000,
+        }
+    }
+}
+
+/// Sends batch commitments with locally tracked nonces
+pub struct CommitmentSubmitter<T: CommitmentTransport> {
+    config: SubmitterConfig,
+    transport: T,
+    next_nonce: Option<u64>,
+}
+
+impl<T: CommitmentTransport> CommitmentSubmitter<T> {
+    pub fn new(config: SubmitterConfig, transport: T) -> Self {
+        Self {
+            config,
+            transport,
+            next_nonce: None,
+        }
+    }
+
+    /// Send the commitment of `batch`.
+    ///
+    /// The nonce is fetched from L1 once and then counted locally; any failed send
+    /// drops it so the next attempt resynchronises with the L1 mempool.
+    #[tracing::instrument(name = "bridge.commit_batch", skip_all, fields(batch = batch.number))]
+    pub async fn submit(&mut self, batch: &L1Batch) -> Result<BatchSubmission, BridgeError> {
+        let gas_price = self.transport.gas_price().await?;
+        if gas_price > self.config.max_gas_price {
+            return Err(BridgeError::ProofSubmissionError(format!(
+                "Gas price {} exceeds the cap {}",
+                gas_price, self.config.max_gas_price
+            )));
+        }
+        let nonce = match self.next_nonce {
+            Some(nonce) => nonce,
+            None => self.transport.pending_nonce(&self.config.from).await?,
+        };
+        let transaction = CommitmentTransaction {
+            from: self.config.from.clone(),
+            to: self.config.to.clone(),
+            nonce,
+            gas_limit: self.config.gas_limit,
+            gas_price,
+            data: batch.commit_calldata(),
+        };
+
+        match self.transport.send_transaction(&transaction).await {
+            Ok(tx_hash) => {
+                self.next_nonce = Some(nonce + 1);
+                Ok(BatchSubmission {
+                    tx_hash,
+                    nonce,
+                    gas_price,
+                    submitted_at: std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                })
+            }
+            Err(e) => {
+                self.next_nonce = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get the transport
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_sync::{BlockHeader, BlockProof, ProofType, Transaction};
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    fn block(height: u64, senders: &[&[u8]]) -> Block {
+        let transactions = senders
+            .iter()
+            .enumerate()
+            .map(|(i, sender)| Transaction {
+                hash: [height as u8 * 16 + i as u8; 32],
+                sender: sender.to_vec(),
+                nonce: 0,
+                gas_limit: 0,
+                data: Vec::new(),
+                inputs: vec![],
+                outputs: vec![],
+                fee: 0,
+                timestamp: height,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
+            })
+            .collect();
+        Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+                nonce: 0,
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        }
+    }
+
+    async fn committed_state(path: &std::path::Path, heights: u64) -> Arc<RwLock<RocksStateDB>> {
+        let mut db = RocksStateDB::new(path).unwrap();
+        for height in 1..=heights {
+            db.put_sync(b"height", &height.to_be_bytes()).unwrap();
+            db.commit_sync(height).unwrap();
+        }
+        Arc::new(RwLock::new(db))
+    }
+
+    #[derive(Default)]
+    struct MockTransport {
+        gas_price: u64,
+        fail_next: Mutex<bool>,
+        sent: Mutex<Vec<CommitmentTransaction>>,
+    }
+
+    impl CommitmentTransport for MockTransport {
+        async fn pending_nonce(&self, _address: &str) -> Result<u64, BridgeError> {
+            Ok(self.sent.lock().unwrap().len() as u64 + 7)
+        }
+
+        async fn gas_price(&self) -> Result<u64, BridgeError> {
+            Ok(self.gas_price)
+        }
+
+        async fn send_transaction(&self, transaction: &CommitmentTransaction) -> Result<[u8; 32], BridgeError> {
+            if std::mem::take(&mut *self.fail_next.lock().unwrap()) {
+                return Err(BridgeError::NetworkError("connection reset".to_string()));
+            }
+            self.sent.lock().unwrap().push(transaction.clone());
+            Ok([transaction.nonce as u8; 32])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_seal_by_size_and_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = committed_state(temp_dir.path(), 3).await;
+        let config = BatchBuilderConfig {
+            batch_size: 2,
+            batch_timeout: Duration::from_millis(20),
+        };
+        let mut builder = BatchBuilder::with_state_db(config.clone(), db.clone()).await.unwrap();
+
+        assert!(builder.add_block(&block(1, &[MINT_ADDRESS, &[0xa1]])).await.unwrap().is_none());
+        let first = builder.add_block(&block(2, &[MINT_ADDRESS])).await.unwrap().unwrap();
+        assert_eq!((first.number, first.first_block, first.last_block), (0, 1, 2));
+        assert_eq!((first.transactions, first.priority_ops), (3, 2));
+        assert_eq!(first.state_root, db.read().await.root_at(2).unwrap().unwrap());
+        let ops_hash = chain_hash(&chain_hash(&[0u8; 32], &[16; 32]), &[32; 32]);
+        assert_eq!(first.priority_ops_hash, ops_hash);
+        assert!(matches!(builder.add_block(&block(4, &[])).await, Err(BridgeError::InvalidHeader)));
+
+        // A restart keeps the partial batch, which seals once the timeout passes
+        builder.add_block(&block(3, &[])).await.unwrap();
+        let mut builder = BatchBuilder::with_state_db(config, db.clone()).await.unwrap();
+        assert_eq!(builder.pending_blocks(), 1);
+        assert!(builder.seal_if_expired().await.unwrap().is_none());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let second = builder.seal_if_expired().await.unwrap().unwrap();
+        assert_eq!((second.number, second.first_block, second.last_block), (1, 3, 3));
+        assert_eq!(second.priority_ops_hash, [0u8; 32]);
+        assert_eq!(get_batch(&*db.read().await, 1).unwrap(), Some(second));
+    }
+
+    #[tokio::test]
+    async fn test_submitter_manages_nonces_and_gas_price() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = committed_state(temp_dir.path(), 2).await;
+        let mut builder = BatchBuilder::with_state_db(
+            BatchBuilderConfig {
+                batch_size: 1,
+                ..Default::default()
+            },
+            db,
+        )
+        .await
+        .unwrap();
+        builder.add_block(&block(1, &[])).await.unwrap();
+        builder.add_block(&block(2, &[])).await.unwrap();
+        let batches = builder.unsubmitted().await.unwrap();
+        assert_eq!(batches.len(), 2);
+
+        let transport = MockTransport {
+            gas_price: 50,
+            ..Default::default()
+        };
+        let config = SubmitterConfig {
+            max_gas_price: 60,
+            ..Default::default()
+        };
+        let mut submitter = CommitmentSubmitter::new(config, transport);
+        let first = submitter.submit(&batches[0]).await.unwrap();
+        assert_eq!((first.nonce, first.gas_price), (7, 50));
+        builder.record_submission(0, first.clone()).await.unwrap();
+        assert!(builder.record_submission(0, first).await.is_err());
+
+        // A failed send resynchronises the nonce from L1 on the next attempt
+        *submitter.transport().fail_next.lock().unwrap() = true;
+        assert!(submitter.submit(&batches[1]).await.is_err());
+        let second = submitter.submit(&batches[1]).await.unwrap();
+        assert_eq!(second.nonce, 8);
+        builder.record_submission(1, second).await.unwrap();
+        assert!(builder.unsubmitted().await.unwrap().is_empty());
+
+        let sent = submitter.transport().sent.lock().unwrap().clone();
+        assert_eq!(sent[1].data, batches[1].commit_calldata());
+        assert_eq!(sent[1].data.len(), 4 + 6 * 32);
+
+        let pricey = MockTransport {
+            gas_price: 61,
+            ..Default::default()
+        };
+        let mut submitter = CommitmentSubmitter::new(SubmitterConfig { max_gas_price: 60, ..Default::default() }, pricey);
+        assert!(submitter.submit(&batches[0]).await.is_err());
+    }
+}
@@ -0,0 +1,446 @@
+//! HEAT minted against XFG burned on Fuego.
+//!
+//! A burn pays XFG to an unspendable output key and names its C0DL3 recipient in a
+//! `tx_extra` tag. Its proof carries the whole Fuego transaction, the header of the block
+//! holding it and the branch from the transaction hash to the header's transaction root.
+//! The Fuego daemon must report that header's id on its main chain under enough blocks,
+//! and the header's CN-UPX/2 hash must meet the block's difficulty. Each burn mints once:
+//! its transaction hash is recorded with the mint.
+
+use crate::attestation::{attested, MintAttester};
+use crate::error::BridgeError;
+use crate::transfers::{self, BridgeTransfer, TransferKind, TransferStatus};
+use block_sync::{Canonical, Transaction, TxOutput};
+use execution::XFG_MINT_ADDRESS;
+use fuego_integration::{FuegoRpcClient, FuegoRpcConfig};
+use fuego_types::{TxInput, TxOutputTarget};
+use pow::auxpow::{Hash, MerkleBranch};
+use pow::{check_hash, CryptoNight, ParentBlockHeader, PowHasher};
+use serde::{Deserialize, Serialize};
+use state_db::supply::{mint_record_key, MintSource};
+use state_db::RocksStateDB;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const NEXT_NONCE_KEY: &[u8] = b"xfg/next_nonce";
+/// Nonce of the mint made for a burn, keyed by the Fuego transaction hash
+const BURN_PREFIX: &[u8] = b"xfg/burn/";
+
+/// `tx_extra` tag naming the C0DL3 recipient of a burn: tag, length, address bytes
+pub const TX_EXTRA_HEAT_BURN_TAG: u8 = 0xcb;
+
+/// XFG burn verification configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct XfgBurnConfig {
+    pub fuego: FuegoRpcConfig,
+    /// Fuego blocks a burn must be buried under before it is minted
+    pub confirmations: u64,
+    /// Least difficulty accepted for the block holding a burn, whatever the daemon reports
+    pub min_difficulty: u64,
+    /// Hex output key no one can spend from; burns pay XFG to it. Unset, no burn verifies.
+    pub burn_output_key: String,
+}
+
+impl Default for XfgBurnConfig {
+    fn default() -> Self {
+        Self {
+            fuego: FuegoRpcConfig::default(),
+            confirmations: 20,
+            min_difficulty: 10_000,
+            burn_output_key: String::new(),
+        }
+    }
+}
+
+/// Evidence that a Fuego transaction burning XFG is in a mined block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BurnProof {
+    /// Height of the Fuego block holding the burn
+    pub height: u64,
+    pub header: ParentBlockHeader,
+    /// Branch from the transaction hash to the header's transaction root
+    pub tx_branch: MerkleBranch,
+    /// The whole serialized transaction, whose fast hash is its id
+    pub tx_blob: Vec<u8>,
+}
+
+/// A verified burn
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XfgBurn {
+    pub tx_hash: Hash,
+    pub height: u64,
+    /// Atomic units paid to the burn output key, minted 1:1 as HEAT
+    pub amount: u64,
+    pub recipient: Vec<u8>,
+}
+
+impl XfgBurn {
+    /// Transaction minting this burn as the `nonce`-th XFG burn mint
+    pub fn mint_transaction(&self, nonce: u64) -> Transaction {
+        Transaction {
+            hash: [0u8; 32],
+            sender: XFG_MINT_ADDRESS.to_vec(),
+            nonce,
+            gas_limit: 0,
+            data: Vec::new(),
+            inputs: Vec::new(),
+            outputs: vec![TxOutput {
+                amount: self.amount,
+                address: self.recipient.clone(),
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 0,
+            // Every node must derive the same mint from the same burn
+            timestamp: self.height,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            // The attester binds the mint to its chain when it signs it
+            chain_id: 0,
+            fee_payer: None,
+            asset: None,
+        }
+        .with_id()
+    }
+}
+
+/// What a burn needs from a transaction
+struct BurnOutputs {
+    tx_hash: Hash,
+    burned: u64,
+    recipient: Option<Vec<u8>>,
+}
+
+fn invalid(reason: String) -> BridgeError {
+    BridgeError::InvalidBurnProof(reason)
+}
+
+/// Sum the outputs paid to `burn_key` and find the recipient tag in the extra field
+fn parse_burn(blob: &[u8], burn_key: &[u8; 32]) -> Result<BurnOutputs, BridgeError> {
+    let tx = fuego_types::Transaction::parse(blob).map_err(|e| invalid(e.to_string()))?;
+    if tx.prefix.inputs.iter().any(|input| matches!(input, TxInput::Gen { .. })) {
+        return Err(invalid("coinbase transactions cannot burn".to_string()));
+    }
+
+    let mut burned = 0u64;
+    for output in &tx.prefix.outputs {
+        if output.target == TxOutputTarget::Key(*burn_key) {
+            burned = burned
+                .checked_add(output.amount)
+                .ok_or_else(|| invalid("burned amount overflows".to_string()))?;
+        }
+    }
+
+    let extra = &tx.prefix.extra;
+    let recipient = (0..extra.len()).find_map(|offset| {
+        let tag = &extra[offset..];
+        let (&TX_EXTRA_HEAT_BURN_TAG, rest) = tag.split_first()? else {
+            return None;
+        };
+        let (&len, address) = rest.split_first()?;
+        if !(1..=32).contains(&len) {
+            return None;
+        }
+        address.get(..len as usize).map(<[u8]>::to_vec)
+    });
+    Ok(BurnOutputs {
+        tx_hash: tx.hash,
+        burned,
+        recipient,
+    })
+}
+
+/// Burn statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BurnStats {
+    pub burns_minted: u64,
+    pub xfg_minted: u64,
+    pub proofs_rejected: u64,
+}
+
+/// Verifies burn proofs against the Fuego daemon and queues one mint per burn
+pub struct XfgBurnMinter {
+    config: XfgBurnConfig,
+    burn_key: Option<[u8; 32]>,
+    client: FuegoRpcClient,
+    hasher: CryptoNight,
+    db: Arc<RwLock<RocksStateDB>>,
+    next_nonce: u64,
+    attester: Option<MintAttester>,
+    stats: BurnStats,
+}
+
+impl XfgBurnMinter {
+    /// Create a minter that resumes after the burn mints recorded in `db`
+    pub async fn with_state_db(config: XfgBurnConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, BridgeError> {
+        let burn_key = match config.burn_output_key.as_str() {
+            "" => None,
+            key => Some(
+                hex::decode(key.trim_start_matches("0x"))
+                    .ok()
+                    .and_then(|key| <[u8; 32]>::try_from(key).ok())
+                    .ok_or_else(|| BridgeError::ConfigError("Burn output key must be 32 hex bytes".to_string()))?,
+            ),
+        };
+        let client = FuegoRpcClient::new(config.fuego.clone()).map_err(|e| BridgeError::FuegoError(e.to_string()))?;
+        let next_nonce = match db.read().await.get_sync(NEXT_NONCE_KEY)? {
+            Some(bytes) => u64::from_be_bytes(
+                bytes
+                    .try_into()
+                    .map_err(|_| BridgeError::StateError("Corrupt XFG mint nonce".to_string()))?,
+            ),
+            None => 0,
+        };
+        Ok(Self {
+            config,
+            burn_key,
+            client,
+            hasher: CryptoNight::upx2(),
+            db,
+            next_nonce,
+            attester: None,
+            stats: BurnStats::default(),
+        })
+    }
+
+    /// Sign the mints queued from now on with `attester`
+    pub fn set_attester(&mut self, attester: MintAttester) {
+        self.attester = Some(attester);
+    }
+
+    /// Check `proof` against the Fuego chain and return the burn it proves
+    pub async fn verify(&self, proof: &BurnProof) -> Result<XfgBurn, BridgeError> {
+        let burn_key = self
+            .burn_key
+            .ok_or_else(|| BridgeError::ConfigError("No burn output key is configured".to_string()))?;
+        let outputs = parse_burn(&proof.tx_blob, &burn_key)?;
+        if outputs.burned == 0 {
+            return Err(invalid("transaction pays nothing to the burn key".to_string()));
+        }
+        let recipient = outputs
+            .recipient
+            .ok_or_else(|| invalid("transaction names no HEAT recipient".to_string()))?;
+
+        let tx_hash = outputs.tx_hash;
+        if proof.tx_branch.root(&tx_hash).map_err(|e| invalid(e.to_string()))? != proof.header.merkle_root {
+            return Err(invalid("transaction is not in the block".to_string()));
+        }
+
+        let block_id = hex::encode(proof.header.id());
+        let header = self
+            .client
+            .get_block_header_by_height(proof.height)
+            .await
+            .map_err(|e| BridgeError::FuegoError(e.to_string()))?;
+        if header.orphan_status || header.hash != block_id {
+            return Err(invalid(format!("block {} is not on the Fuego main chain", block_id)));
+        }
+        if header.depth < self.config.confirmations {
+            return Err(invalid(format!(
+                "block {} has {} of {} confirmations",
+                block_id, header.depth, self.config.confirmations
+            )));
+        }
+        let difficulty = header.difficulty.max(self.config.min_difficulty);
+        if !check_hash(&self.hasher.hash(&proof.header.hashing_blob()), difficulty) {
+            return Err(invalid(format!("block {} does not meet difficulty {}", block_id, difficulty)));
+        }
+
+        Ok(XfgBurn {
+            tx_hash,
+            height: proof.height,
+            amount: outputs.burned,
+            recipient,
+        })
+    }
+
+    /// Verify `proof` and record the mint of its burn, unless the burn was minted before
+    pub async fn queue_mint(&mut self, proof: &BurnProof) -> Result<Transaction, BridgeError> {
+        let burn = match self.verify(proof).await {
+            Ok(burn) => burn,
+            Err(e) => {
+                self.stats.proofs_rejected += 1;
+                return Err(e);
+            }
+        };
+
+        let mut db = self.db.write().await;
+        let burn_key = [BURN_PREFIX, burn.tx_hash.as_slice()].concat();
+        if db.get_sync(&burn_key)?.is_some() {
+            self.stats.proofs_rejected += 1;
+            return Err(invalid(format!("burn {} is already minted", hex::encode(burn.tx_hash))));
+        }
+        let mint = attested(self.attester.as_ref(), burn.mint_transaction(self.next_nonce));
+        let transfer = BridgeTransfer::new(mint.hash, TransferKind::XfgBurn, burn.recipient.clone(), burn.amount)
+            .with_mint_nonce(mint.nonce);
+        let mut entries = vec![
+            (mint_record_key(MintSource::XfgBurn, mint.nonce), mint.to_canonical_bytes()),
+            (burn_key, mint.nonce.to_be_bytes().to_vec()),
+            (NEXT_NONCE_KEY.to_vec(), (mint.nonce + 1).to_be_bytes().to_vec()),
+        ];
+        entries.extend(transfers::transitions(&db, [(transfer, TransferStatus::Pending, None)])?);
+        db.write_batch_sync(&entries)?;
+        drop(db);
+
+        println!(
+            "Queued mint of {} HEAT for XFG burn {} at Fuego height {}",
+            burn.amount,
+            hex::encode(burn.tx_hash),
+            burn.height
+        );
+        self.next_nonce += 1;
+        self.stats.burns_minted += 1;
+        self.stats.xfg_minted += burn.amount;
+        Ok(mint)
+    }
+
+    /// Burn mints the committed state has not executed yet, in nonce order
+    pub async fn pending_mints(&self) -> Result<Vec<Transaction>, BridgeError> {
+        let db = self.db.read().await;
+        let executed = db.get_account(XFG_MINT_ADDRESS)?.nonce;
+        let mut mints = Vec::new();
+        for nonce in executed..self.next_nonce {
+            let bytes = db
+                .get_sync(&mint_record_key(MintSource::XfgBurn, nonce))?
+                .ok_or_else(|| BridgeError::StateError(format!("XFG mint {} is missing", nonce)))?;
+            mints.push(Transaction::from_canonical_bytes(&bytes)?);
+        }
+        Ok(mints)
+    }
+
+    pub fn get_stats(&self) -> BurnStats {
+        self.stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pow::auxpow::tree_hash;
+    use pow::cn_fast_hash;
+    use serde_json::json;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BURN_KEY: [u8; 32] = [0xbb; 32];
+
+    /// One key input, a burn output, a change output and the recipient tag
+    fn burn_tx(burned: u64, recipient: &[u8]) -> Vec<u8> {
+        let mut blob = vec![1, 0, 1, 0x02, 0xe8, 0x07, 1, 5];
+        blob.extend_from_slice(&[0x11; 32]);
+        blob.extend_from_slice(&[2, (burned as u8) & 0x7f, 0x02]);
+        blob.extend_from_slice(&BURN_KEY);
+        blob.extend_from_slice(&[0x10, 0x02]);
+        blob.extend_from_slice(&[0x22; 32]);
+        let mut extra = vec![0x01];
+        extra.extend_from_slice(&[0x33; 32]);
+        extra.extend_from_slice(&[TX_EXTRA_HEAT_BURN_TAG, recipient.len() as u8]);
+        extra.extend_from_slice(recipient);
+        blob.push(extra.len() as u8);
+        blob.extend_from_slice(&extra);
+        // Ring signatures follow the prefix
+        blob.extend_from_slice(&[0x44; 64]);
+        blob
+    }
+
+    fn proof(tx_blob: Vec<u8>) -> BurnProof {
+        let hashes = vec![[0xc0; 32], cn_fast_hash(&tx_blob), [0xc1; 32]];
+        let header = ParentBlockHeader {
+            major_version: 1,
+            timestamp: 1_700_000_000,
+            prev_id: [0xaa; 32],
+            merkle_root: tree_hash(&hashes),
+            tx_count: hashes.len() as u64,
+            ..Default::default()
+        };
+        BurnProof {
+            height: 500,
+            header,
+            tx_branch: MerkleBranch::build(&hashes, 1).unwrap(),
+            tx_blob,
+        }
+    }
+
+    async fn fuego(header: &ParentBlockHeader, depth: u64, difficulty: u64) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "getblockheaderbyheight", "params": { "height": 500 } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": {
+                    "block_header": {
+                        "major_version": 1, "minor_version": 0, "timestamp": header.timestamp,
+                        "prev_hash": hex::encode(header.prev_id), "nonce": 0, "orphan_status": false,
+                        "height": 500, "depth": depth, "hash": hex::encode(header.id()),
+                        "difficulty": difficulty, "reward": 0,
+                    },
+                    "status": "OK",
+                },
+            })))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn config(url: String, min_difficulty: u64) -> XfgBurnConfig {
+        XfgBurnConfig {
+            fuego: FuegoRpcConfig {
+                url,
+                max_retries: 0,
+                timeout: Duration::from_secs(2),
+                ..Default::default()
+            },
+            confirmations: 10,
+            min_difficulty,
+            burn_output_key: hex::encode(BURN_KEY),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verified_burns_mint_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let burn = proof(burn_tx(100, &[0xb0]));
+        let server = fuego(&burn.header, 30, 1).await;
+        let mut minter = XfgBurnMinter::with_state_db(config(server.uri(), 1), db.clone()).await.unwrap();
+
+        let mint = minter.queue_mint(&burn).await.unwrap();
+        assert_eq!((mint.sender.as_slice(), mint.nonce), (XFG_MINT_ADDRESS, 0));
+        assert_eq!((mint.outputs[0].amount, mint.outputs[0].address.clone()), (100, vec![0xb0]));
+        assert!(matches!(minter.queue_mint(&burn).await, Err(BridgeError::InvalidBurnProof(_))));
+
+        // The mint is authorized for execution, and survives a restart
+        let record = db.read().await.get_sync(&mint_record_key(MintSource::XfgBurn, 0)).unwrap();
+        assert_eq!(record, Some(mint.to_canonical_bytes()));
+        let mut restarted = XfgBurnMinter::with_state_db(config(server.uri(), 1), db.clone()).await.unwrap();
+        assert_eq!(restarted.pending_mints().await.unwrap().len(), 1);
+        assert!(matches!(restarted.queue_mint(&burn).await, Err(BridgeError::InvalidBurnProof(_))));
+
+        // Burns outside the block, without work or too shallow are refused
+        let mut moved = proof(burn_tx(100, &[0xb0]));
+        moved.tx_branch = MerkleBranch::build(&[[0xc0; 32], [0xc1; 32], [0xc2; 32]], 1).unwrap();
+        assert!(minter.verify(&moved).await.is_err());
+        let unworked = XfgBurnMinter::with_state_db(config(server.uri(), u64::MAX), db.clone()).await.unwrap();
+        assert!(unworked.verify(&burn).await.is_err());
+        let shallow = fuego(&burn.header, 3, 1).await;
+        let shallow = XfgBurnMinter::with_state_db(config(shallow.uri(), 1), db).await.unwrap();
+        assert!(shallow.verify(&burn).await.is_err());
+        assert_eq!(minter.get_stats().burns_minted, 1);
+    }
+
+    #[test]
+    fn test_parse_burn_outputs() {
+        let outputs = parse_burn(&burn_tx(100, &[0xb0, 0xb1]), &BURN_KEY).unwrap();
+        assert_eq!((outputs.burned, outputs.recipient), (100, Some(vec![0xb0, 0xb1])));
+        assert_eq!(parse_burn(&burn_tx(100, &[0xb0]), &[0u8; 32]).unwrap().burned, 0);
+
+        let mut coinbase = burn_tx(100, &[0xb0]);
+        coinbase[3] = 0xff;
+        assert!(parse_burn(&coinbase, &BURN_KEY).is_err());
+        assert!(parse_burn(&burn_tx(100, &[0xb0])[..50], &BURN_KEY).is_err());
+    }
+}
@@ -0,0 +1,524 @@
+//! Interactive fraud-proof challenges against batches settled optimistically on Arbitrum.
+//!
+//! A challenger disputes the state root a committed batch ends in. The defender, the node
+//! that committed the batch, then bisects the disputed blocks by posting the state root
+//! at their midpoint, and the challenger says which half it still disputes, until one
+//! block is left. The defender must prove that block's transition in one step; a party
+//! that lets its move time out loses.
+//!
+//! Challenges live in the state database, where watchtowers follow and play them over
+//! RPC. The bridge referees them: it opens each one on the settlement layer, times out
+//! stalled parties and applies every outcome to the challenged batch.
+//!
+//! A challenger is an ed25519 key. It signs the challenge it opens and every half it
+//! chooses, so nobody who can reach the RPC can play its side of the game for it.
+
+use crate::batches::{self, L1Batch};
+use crate::error::BridgeError;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use state_db::RocksStateDB;
+
+const CHALLENGE_PREFIX: &[u8] = b"bridge/challenges/";
+const NEXT_ID_KEY: &[u8] = b"bridge/next_challenge";
+
+/// Side of a challenge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Party {
+    /// Stands by the batch as committed
+    Defender,
+    /// Disputes the state root the batch ends in
+    Challenger,
+}
+
+/// Where a challenge is in its game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeStatus {
+    /// Waiting for the settlement layer to accept it
+    Opened,
+    /// The defender must post the state root at the midpoint of the disputed blocks
+    AwaitingBisection,
+    /// The challenger must say whether it agrees with the midpoint
+    AwaitingChoice,
+    /// One block is left, whose transition the defender must prove
+    AwaitingOneStepProof,
+    /// The batch stands
+    DefenderWon,
+    /// The batch was shown wrong
+    ChallengerWon,
+    /// The settlement layer refused the challenge
+    Rejected,
+}
+
+impl ChallengeStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChallengeStatus::Opened => "opened",
+            ChallengeStatus::AwaitingBisection => "awaiting_bisection",
+            ChallengeStatus::AwaitingChoice => "awaiting_choice",
+            ChallengeStatus::AwaitingOneStepProof => "awaiting_one_step_proof",
+            ChallengeStatus::DefenderWon => "defender_won",
+            ChallengeStatus::ChallengerWon => "challenger_won",
+            ChallengeStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Opened,
+            Self::AwaitingBisection,
+            Self::AwaitingChoice,
+            Self::AwaitingOneStepProof,
+            Self::DefenderWon,
+            Self::ChallengerWon,
+            Self::Rejected,
+        ]
+        .into_iter()
+        .find(|status| status.name() == name)
+    }
+
+    /// Party whose move it is, if the game is being played
+    pub fn to_move(&self) -> Option<Party> {
+        match self {
+            ChallengeStatus::AwaitingBisection | ChallengeStatus::AwaitingOneStepProof => Some(Party::Defender),
+            ChallengeStatus::AwaitingChoice => Some(Party::Challenger),
+            _ => None,
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, ChallengeStatus::DefenderWon | ChallengeStatus::ChallengerWon | ChallengeStatus::Rejected)
+    }
+}
+
+/// One bisection of the disputed blocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisectionRound {
+    pub agreed_step: u64,
+    pub disputed_step: u64,
+    /// State root the defender posted at the midpoint
+    pub midpoint_state: [u8; 32],
+    /// Whether the challenger agreed with it, once it chose
+    pub challenger_agreed: Option<bool>,
+}
+
+/// A challenge of a batch and every move made in it.
+///
+/// Step `k` of a batch is the state after its first `k` blocks, so step 0 is the state the
+/// previous batch ended in and the last step the state root the batch committed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    pub id: u64,
+    pub batch: u64,
+    /// Height of the batch's first block, which step 1 executes
+    pub first_block: u64,
+    /// Public key of the challenger, which signs its moves
+    pub challenger: Vec<u8>,
+    /// State root the challenger claims the batch ends in
+    pub claimed_state_root: [u8; 32],
+    pub status: ChallengeStatus,
+    /// Last step both parties agree on, and its state root
+    pub agreed_step: u64,
+    pub agreed_state: [u8; 32],
+    /// First step the challenger disputes, and the defender's state root for it
+    pub disputed_step: u64,
+    pub disputed_state: [u8; 32],
+    pub rounds: Vec<BisectionRound>,
+    /// Unix time of the last move; the party to move loses once the move timeout has passed
+    pub last_move_at: u64,
+    pub resolution: Option<String>,
+    /// Whether the outcome was applied to the settlement layer
+    pub settled: bool,
+}
+
+impl Challenge {
+    /// Step whose state root the defender posts next
+    pub fn midpoint(&self) -> u64 {
+        self.agreed_step + (self.disputed_step - self.agreed_step) / 2
+    }
+
+    /// Height of the block executed by the step after the agreed one, once one block is left
+    pub fn disputed_block(&self) -> u64 {
+        self.first_block + self.agreed_step
+    }
+
+    fn expect(&self, status: ChallengeStatus) -> Result<(), BridgeError> {
+        if self.status != status {
+            return Err(BridgeError::ChallengeError(format!(
+                "Challenge {} is {}, not {}",
+                self.id,
+                self.status.name(),
+                status.name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Wait for the next bisection, or the one-step proof once one block is left
+    fn next_round(&mut self, now: u64) {
+        self.status = match self.disputed_step - self.agreed_step {
+            1 => ChallengeStatus::AwaitingOneStepProof,
+            _ => ChallengeStatus::AwaitingBisection,
+        };
+        self.last_move_at = now;
+    }
+
+    fn resolve(&mut self, winner: Party, resolution: String) {
+        self.status = match winner {
+            Party::Defender => ChallengeStatus::DefenderWon,
+            Party::Challenger => ChallengeStatus::ChallengerWon,
+        };
+        self.resolution = Some(resolution);
+    }
+
+    /// The settlement layer accepted the challenge, so the game starts
+    pub fn accept(&mut self, now: u64) -> Result<(), BridgeError> {
+        self.expect(ChallengeStatus::Opened)?;
+        self.next_round(now);
+        Ok(())
+    }
+
+    /// The settlement layer refused the challenge
+    pub fn reject(&mut self, reason: String) -> Result<(), BridgeError> {
+        self.expect(ChallengeStatus::Opened)?;
+        self.status = ChallengeStatus::Rejected;
+        self.resolution = Some(reason);
+        Ok(())
+    }
+
+    /// The defender posts its state root at the midpoint
+    pub fn bisect(&mut self, midpoint_state: [u8; 32], now: u64) -> Result<(), BridgeError> {
+        self.expect(ChallengeStatus::AwaitingBisection)?;
+        self.rounds.push(BisectionRound {
+            agreed_step: self.agreed_step,
+            disputed_step: self.disputed_step,
+            midpoint_state,
+            challenger_agreed: None,
+        });
+        self.status = ChallengeStatus::AwaitingChoice;
+        self.last_move_at = now;
+        Ok(())
+    }
+
+    /// The challenger agrees with the midpoint, disputing the upper half, or not, disputing
+    /// the lower half. `signature` is the challenger's signature of the choice's
+    /// `choice_message`.
+    pub fn choose(&mut self, agrees: bool, signature: &[u8], now: u64) -> Result<(), BridgeError> {
+        self.expect(ChallengeStatus::AwaitingChoice)?;
+        let round = self.rounds.len() as u64 - 1;
+        verify_challenger(&self.challenger, &choice_message(self.id, round, agrees), signature)
+            .map_err(|e| BridgeError::ChallengeError(format!("Only the challenger of challenge {} can choose: {}", self.id, e)))?;
+        let midpoint = self.midpoint();
+        let round = self.rounds.last_mut().expect("a bisection precedes every choice");
+        round.challenger_agreed = Some(agrees);
+        if agrees {
+            (self.agreed_step, self.agreed_state) = (midpoint, round.midpoint_state);
+        } else {
+            (self.disputed_step, self.disputed_state) = (midpoint, round.midpoint_state);
+        }
+        self.next_round(now);
+        Ok(())
+    }
+
+    /// The defender proves the one disputed block. A proof that does not take the agreed
+    /// state to the defender's disputed one loses the game.
+    pub fn prove_step(&mut self, witness: &[u8]) -> Result<(), BridgeError> {
+        self.expect(ChallengeStatus::AwaitingOneStepProof)?;
+        let block = self.disputed_block();
+        if one_step(&self.agreed_state, witness) == self.disputed_state {
+            self.resolve(Party::Defender, format!("One-step proof of block {} verified", block));
+        } else {
+            self.resolve(Party::Challenger, format!("One-step proof of block {} failed", block));
+        }
+        Ok(())
+    }
+
+    /// End the game against the party to move if it let `timeout` seconds pass, returning
+    /// whether it did
+    pub fn expire(&mut self, now: u64, timeout: u64) -> bool {
+        let Some(party) = self.status.to_move() else {
+            return false;
+        };
+        if now < self.last_move_at.saturating_add(timeout) {
+            return false;
+        }
+        let winner = match party {
+            Party::Defender => Party::Challenger,
+            Party::Challenger => Party::Defender,
+        };
+        self.resolve(winner, format!("{:?} missed its move deadline", party));
+        true
+    }
+}
+
+/// State after executing one block from `state`, as the simulated one-step prover checks
+/// it; the real prover re-executes the block the witness holds
+pub fn one_step(state: &[u8; 32], witness: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(state);
+    hasher.update(witness);
+    hasher.finalize().into()
+}
+
+/// What a challenger signs to dispute the state root of `batch`, claiming `claimed_state_root`
+pub fn open_message(batch: u64, claimed_state_root: &[u8; 32]) -> [u8; 32] {
+    Hasher::new(Domain::ChallengeMove).bytes(b"open").u64(batch).fixed(claimed_state_root).finish()
+}
+
+/// What a challenger signs to answer bisection round `round` of challenge `id`, counting
+/// rounds from zero
+pub fn choice_message(id: u64, round: u64, agrees: bool) -> [u8; 32] {
+    Hasher::new(Domain::ChallengeMove).bytes(b"choose").u64(id).u64(round).u64(agrees as u64).finish()
+}
+
+/// Check that `signature` is `challenger`'s ed25519 signature of `message`
+fn verify_challenger(challenger: &[u8], message: &[u8; 32], signature: &[u8]) -> Result<(), String> {
+    let key = <[u8; 32]>::try_from(challenger)
+        .ok()
+        .and_then(|key| VerifyingKey::from_bytes(&key).ok())
+        .ok_or_else(|| "the challenger is not an ed25519 public key".to_string())?;
+    let signature = Signature::from_slice(signature).map_err(|_| "the signature is malformed".to_string())?;
+    key.verify(message, &signature).map_err(|_| "the signature does not verify".to_string())
+}
+
+fn challenge_key(id: u64) -> Vec<u8> {
+    [CHALLENGE_PREFIX, id.to_be_bytes().as_slice()].concat()
+}
+
+pub(crate) fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Get the challenge with `id`
+pub fn get_challenge(state: &RocksStateDB, id: u64) -> Result<Option<Challenge>, BridgeError> {
+    match state.get_sync(&challenge_key(id))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Get the challenges of `batch` and in `status`, either filter being optional, oldest first
+pub fn list_challenges(
+    state: &RocksStateDB,
+    batch: Option<u64>,
+    status: Option<ChallengeStatus>,
+) -> Result<Vec<Challenge>, BridgeError> {
+    let mut challenges = Vec::new();
+    for (_, bytes) in state.scan_prefix_sync(CHALLENGE_PREFIX)? {
+        let challenge: Challenge = serde_json::from_slice(&bytes)?;
+        if batch.is_none_or(|batch| challenge.batch == batch)
+            && status.is_none_or(|status| challenge.status == status)
+        {
+            challenges.push(challenge);
+        }
+    }
+    Ok(challenges)
+}
+
+/// Challenges whose outcome the settlement layer has not seen yet
+pub(crate) fn unsettled(state: &RocksStateDB) -> Result<Vec<Challenge>, BridgeError> {
+    Ok(list_challenges(state, None, None)?.into_iter().filter(|challenge| !challenge.settled).collect())
+}
+
+pub(crate) fn save(state: &mut RocksStateDB, challenge: &Challenge) -> Result<(), BridgeError> {
+    state.write_batch_sync(&[(challenge_key(challenge.id), serde_json::to_vec(challenge)?)])?;
+    Ok(())
+}
+
+/// Dispute the state root committed batch `batch` ends in, claiming `claimed_state_root`,
+/// with `signature` the challenger's signature of their `open_message`. The bridge opens
+/// the challenge on the settlement layer on its next pass.
+pub fn open_challenge(
+    state: &mut RocksStateDB,
+    batch: u64,
+    challenger: Vec<u8>,
+    claimed_state_root: [u8; 32],
+    signature: &[u8],
+) -> Result<Challenge, BridgeError> {
+    verify_challenger(&challenger, &open_message(batch, &claimed_state_root), signature)
+        .map_err(|e| BridgeError::ChallengeError(format!("Challenge of batch {} is not signed: {}", batch, e)))?;
+    let committed = batches::get_batch(state, batch)?
+        .filter(|committed| committed.submission.is_some())
+        .ok_or_else(|| BridgeError::ChallengeError(format!("Batch {} is not committed", batch)))?;
+    let agreed_state = match batch.checked_sub(1) {
+        Some(previous) => batches::get_batch(state, previous)?.map_or([0u8; 32], |previous| previous.state_root),
+        None => [0u8; 32],
+    };
+    open_against(state, &committed, agreed_state, challenger, claimed_state_root)
+}
+
+/// Dispute `committed`, whose blocks start from `agreed_state`, claiming it ends in
+/// `claimed_state_root`. Nothing is signed here: it is how the node's own watchtower opens
+/// challenges under its configured key.
+pub fn open_against(
+    state: &mut RocksStateDB,
+    committed: &L1Batch,
+    agreed_state: [u8; 32],
+    challenger: Vec<u8>,
+    claimed_state_root: [u8; 32],
+) -> Result<Challenge, BridgeError> {
+    if committed.state_root == claimed_state_root {
+        return Err(BridgeError::ChallengeError(format!(
+            "Batch {} already ends in the claimed state root",
+            committed.number
+        )));
+    }
+    if challenger.is_empty() {
+        return Err(BridgeError::ChallengeError("Challenger address is empty".to_string()));
+    }
+    let id = match state.get_sync(NEXT_ID_KEY)? {
+        Some(bytes) => serde_json::from_slice(&bytes)?,
+        None => 0u64,
+    };
+    let challenge = Challenge {
+        id,
+        batch: committed.number,
+        first_block: committed.first_block,
+        challenger,
+        claimed_state_root,
+        status: ChallengeStatus::Opened,
+        agreed_step: 0,
+        agreed_state,
+        disputed_step: committed.last_block - committed.first_block + 1,
+        disputed_state: committed.state_root,
+        rounds: Vec::new(),
+        last_move_at: now(),
+        resolution: None,
+        settled: false,
+    };
+    state.write_batch_sync(&[
+        (challenge_key(id), serde_json::to_vec(&challenge)?),
+        (NEXT_ID_KEY.to_vec(), serde_json::to_vec(&(id + 1))?),
+    ])?;
+    Ok(challenge)
+}
+
+/// Make a move in challenge `id` and store the result
+fn play(
+    state: &mut RocksStateDB,
+    id: u64,
+    play: impl FnOnce(&mut Challenge) -> Result<(), BridgeError>,
+) -> Result<Challenge, BridgeError> {
+    let mut challenge =
+        get_challenge(state, id)?.ok_or_else(|| BridgeError::ChallengeError(format!("Unknown challenge {}", id)))?;
+    play(&mut challenge)?;
+    save(state, &challenge)?;
+    Ok(challenge)
+}
+
+/// Post the defender's state root at the midpoint of challenge `id`
+pub fn bisect(state: &mut RocksStateDB, id: u64, midpoint_state: [u8; 32]) -> Result<Challenge, BridgeError> {
+    play(state, id, |challenge| challenge.bisect(midpoint_state, now()))
+}
+
+/// Record whether the challenger of challenge `id` agrees with the defender's midpoint,
+/// as `signature` shows
+pub fn choose(state: &mut RocksStateDB, id: u64, agrees: bool, signature: &[u8]) -> Result<Challenge, BridgeError> {
+    play(state, id, |challenge| challenge.choose(agrees, signature, now()))
+}
+
+/// Submit the defender's one-step proof of the block left in challenge `id`
+pub fn prove_step(state: &mut RocksStateDB, id: u64, witness: &[u8]) -> Result<Challenge, BridgeError> {
+    play(state, id, |challenge| challenge.prove_step(witness))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batches::BatchSubmission;
+    use ed25519_dalek::{Signer, SigningKey};
+    use tempfile::TempDir;
+
+    fn commit_batch(db: &mut RocksStateDB, number: u64, first_block: u64, last_block: u64, state_root: [u8; 32]) {
+        let batch = L1Batch {
+            number,
+            first_block,
+            last_block,
+            blocks_hash: [0; 32],
+            state_root,
+            priority_ops_hash: [0; 32],
+            priority_ops: 0,
+            transactions: 0,
+            submission: Some(BatchSubmission { tx_hash: [1; 32], nonce: 0, gas_price: 1, submitted_at: 0 }),
+        };
+        let key = [b"bridge/batches/batch/".as_slice(), &number.to_be_bytes()].concat();
+        db.write_batch_sync(&[(key, serde_json::to_vec(&batch).unwrap())]).unwrap();
+    }
+
+    #[test]
+    fn test_bisection_narrows_to_one_block_and_the_one_step_proof_decides() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+        // The defender's execution of blocks 10..=17, each step hashing in the block height
+        let mut states = vec![[5u8; 32]];
+        for height in 10..18u64 {
+            states.push(one_step(states.last().unwrap(), &height.to_be_bytes()));
+        }
+        commit_batch(&mut db, 0, 2, 9, states[0]);
+        commit_batch(&mut db, 1, 10, 17, states[8]);
+
+        let challenger = SigningKey::from_bytes(&[0xc1; 32]);
+        let impostor = SigningKey::from_bytes(&[0xc2; 32]);
+        let public = challenger.verifying_key().to_bytes().to_vec();
+        let open = |db: &mut RocksStateDB, batch: u64, root: [u8; 32], key: &SigningKey| {
+            let signature = key.sign(&open_message(batch, &root)).to_bytes();
+            open_challenge(db, batch, public.clone(), root, &signature)
+        };
+        assert!(open(&mut db, 2, [9; 32], &challenger).is_err());
+        assert!(open(&mut db, 1, states[8], &challenger).is_err());
+        assert!(open(&mut db, 1, [9; 32], &impostor).is_err());
+        let challenge = open(&mut db, 1, [9; 32], &challenger).unwrap();
+        assert_eq!((challenge.agreed_step, challenge.disputed_step, challenge.agreed_state), (0, 8, states[0]));
+        assert!(bisect(&mut db, challenge.id, [0; 32]).is_err());
+
+        let mut challenge = get_challenge(&db, 0).unwrap().unwrap();
+        challenge.accept(now()).unwrap();
+        save(&mut db, &challenge).unwrap();
+
+        // The challenger computed the same first five blocks, then diverged
+        let mut rounds = 0;
+        while get_challenge(&db, 0).unwrap().unwrap().status == ChallengeStatus::AwaitingBisection {
+            let midpoint = get_challenge(&db, 0).unwrap().unwrap().midpoint() as usize;
+            bisect(&mut db, 0, states[midpoint]).unwrap();
+            let agrees = midpoint <= 5;
+            let forged = impostor.sign(&choice_message(0, rounds, agrees)).to_bytes();
+            assert!(choose(&mut db, 0, agrees, &forged).is_err());
+            // A signature from an earlier round, or of the other half, is no choice now
+            let replayed = challenger.sign(&choice_message(0, rounds + 1, agrees)).to_bytes();
+            assert!(choose(&mut db, 0, agrees, &replayed).is_err());
+            let flipped = challenger.sign(&choice_message(0, rounds, !agrees)).to_bytes();
+            assert!(choose(&mut db, 0, agrees, &flipped).is_err());
+            choose(&mut db, 0, agrees, &challenger.sign(&choice_message(0, rounds, agrees)).to_bytes()).unwrap();
+            rounds += 1;
+        }
+        let challenge = get_challenge(&db, 0).unwrap().unwrap();
+        assert_eq!(rounds, 3);
+        assert_eq!(challenge.status, ChallengeStatus::AwaitingOneStepProof);
+        assert_eq!((challenge.agreed_step, challenge.disputed_step, challenge.disputed_block()), (5, 6, 15));
+
+        // Only a proof of the block the defender executed wins
+        let mut lost = challenge.clone();
+        lost.prove_step(&16u64.to_be_bytes()).unwrap();
+        assert_eq!(lost.status, ChallengeStatus::ChallengerWon);
+        let challenge = prove_step(&mut db, 0, &15u64.to_be_bytes()).unwrap();
+        assert_eq!(challenge.status, ChallengeStatus::DefenderWon);
+        assert!(prove_step(&mut db, 0, &15u64.to_be_bytes()).is_err());
+        assert_eq!(list_challenges(&db, Some(1), Some(ChallengeStatus::DefenderWon)).unwrap().len(), 1);
+        assert_eq!(unsettled(&db).unwrap().len(), 1);
+
+        // A party that stops playing loses
+        let mut stalled = open(&mut db, 1, [9; 32], &challenger).unwrap();
+        assert_eq!(stalled.id, 1);
+        stalled.accept(100).unwrap();
+        assert!(!stalled.expire(150, 60));
+        assert!(stalled.expire(160, 60));
+        assert_eq!(stalled.status, ChallengeStatus::ChallengerWon);
+    }
+}
@@ -0,0 +1,825 @@
+use crate::attestation::{attested, MintAttester};
+use crate::error::BridgeError;
+use crate::transfers::{self, BridgeTransfer, TransferKind, TransferStatus};
+use block_sync::{Canonical, Transaction, TxOutput};
+use execution::{TokenInfo, MINT_ADDRESS};
+use fuego_integration::socks_proxy;
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use state_db::supply::{mint_record_key, MintSource};
+use state_db::RocksStateDB;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+
+const CURSOR_KEY: &[u8] = b"bridge/deposit_cursor";
+const CHECKPOINTS_KEY: &[u8] = b"bridge/deposit_checkpoints";
+const INCIDENTS_KEY: &[u8] = b"bridge/reorg_incidents";
+/// Nonce of the mint made for a deposit, keyed by the deposit's id
+const MINTED_PREFIX: &[u8] = b"bridge/minted/";
+
+/// Reorg incidents kept for inspection
+const MAX_INCIDENTS: usize = 64;
+
+/// Deposit monitor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositMonitorConfig {
+    /// JSON-RPC endpoint of the L1 the bridge contract lives on
+    pub rpc_url: String,
+    /// Bridge contract emitting `Deposit` events
+    pub contract_address: String,
+    /// Blocks an event must be buried under before it is minted
+    pub confirmations: u64,
+    /// First L1 block to scan, normally the bridge contract's deployment block
+    pub start_block: u64,
+    /// Widest block range requested in one `eth_getLogs` call
+    pub max_block_range: u64,
+    /// Scanned ranges whose last block hash is kept to find the fork point after an L1 reorg
+    pub reorg_checkpoints: usize,
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+    /// Tokens the bridge contract carries; deposits of other tokens are skipped
+    pub tokens: Vec<TokenInfo>,
+    /// SOCKS5 proxy the L1 endpoint is reached through
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+impl Default for DepositMonitorConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: "http://localhost:8545".to_string(),
+            contract_address: "0x0000000000000000000000000000000000000000".to_string(),
+            confirmations: 12,
+            start_block: 0,
+            max_block_range: 1_000,
+            reorg_checkpoints: 64,
+            poll_interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(30),
+            tokens: Vec::new(),
+            proxy: None,
+        }
+    }
+}
+
+/// Topic of `Deposit(address indexed depositor, address indexed recipient, uint256 amount)`
+pub fn deposit_topic() -> [u8; 32] {
+    Keccak256::digest(b"Deposit(address,address,uint256)").into()
+}
+
+/// Topic of `TokenDeposit(address indexed token, address indexed depositor, address indexed recipient,
+/// uint256 amount)`
+pub fn token_deposit_topic() -> [u8; 32] {
+    Keccak256::digest(b"TokenDeposit(address,address,address,uint256)").into()
+}
+
+/// A confirmed deposit into the L1 bridge contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deposit {
+    pub l1_block: u64,
+    pub l1_tx_hash: [u8; 32],
+    pub log_index: u64,
+    pub depositor: Vec<u8>,
+    /// C0DL3 account credited by the mint
+    pub recipient: Vec<u8>,
+    pub amount: u64,
+    /// L1 contract of the token deposited, or `None` for HEAT
+    #[serde(default)]
+    pub token: Option<Vec<u8>>,
+}
+
+impl Deposit {
+    /// Decode a `Deposit` or `TokenDeposit` log as returned by `eth_getLogs`
+    pub fn from_log(log: &Value) -> Result<Self, BridgeError> {
+        let field = |name: &str| {
+            log.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| BridgeError::ArbitrumError(format!("Log is missing {}", name)))
+        };
+        let topics: Vec<Vec<u8>> = log
+            .get("topics")
+            .and_then(Value::as_array)
+            .ok_or_else(|| BridgeError::ArbitrumError("Log is missing topics".to_string()))?
+            .iter()
+            .map(|topic| decode_hex(topic.as_str().unwrap_or_default()))
+            .collect::<Result<_, _>>()?;
+        if topics.iter().any(|topic| topic.len() != 32) {
+            return Err(BridgeError::ArbitrumError("Log is not a Deposit event".to_string()));
+        }
+        let token = match topics.len() {
+            3 if topics[0] == deposit_topic() => None,
+            4 if topics[0] == token_deposit_topic() => Some(topics[1][12..].to_vec()),
+            _ => return Err(BridgeError::ArbitrumError("Log is not a Deposit event".to_string())),
+        };
+        let (depositor, recipient) = (&topics[topics.len() - 2], &topics[topics.len() - 1]);
+        let data = decode_hex(field("data")?)?;
+        if data.len() != 32 {
+            return Err(BridgeError::ArbitrumError("Deposit data is not one word".to_string()));
+        }
+        if data[..24].iter().any(|byte| *byte != 0) {
+            return Err(BridgeError::ArbitrumError("Deposit amount exceeds u64".to_string()));
+        }
+        let l1_tx_hash = decode_hex(field("transactionHash")?)?
+            .try_into()
+            .map_err(|_| BridgeError::ArbitrumError("Invalid transaction hash".to_string()))?;
+
+        Ok(Self {
+            l1_block: parse_quantity(field("blockNumber")?)?,
+            l1_tx_hash,
+            log_index: parse_quantity(field("logIndex")?)?,
+            depositor: depositor[12..].to_vec(),
+            recipient: recipient[12..].to_vec(),
+            amount: u64::from_be_bytes(data[24..].try_into().unwrap()),
+            token,
+        })
+    }
+
+    /// Id of this deposit on L1, whatever the nonce of its mint
+    pub fn deposit_id(&self) -> [u8; 32] {
+        Hasher::new(Domain::DepositMint).fixed(&self.l1_tx_hash).u64(self.log_index).finish()
+    }
+
+    /// Transaction minting this deposit as the `nonce`-th bridge mint. A token deposit mints
+    /// `token`, whose info the mint carries so the first mint of a token registers it.
+    pub fn mint_transaction(&self, nonce: u64, token: Option<&TokenInfo>) -> Transaction {
+        Transaction {
+            hash: [0u8; 32],
+            sender: MINT_ADDRESS.to_vec(),
+            nonce,
+            gas_limit: 0,
+            data: token.map_or_else(Vec::new, |info| serde_json::to_vec(info).expect("token info serializes")),
+            inputs: Vec::new(),
+            outputs: vec![TxOutput {
+                amount: self.amount,
+                address: self.recipient.clone(),
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 0,
+            // Every node must derive the same mint from the same deposit
+            timestamp: self.l1_block,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            // The attester binds the mint to its chain when it signs it
+            chain_id: 0,
+            fee_payer: None,
+            asset: token.map(TokenInfo::asset_id),
+        }
+        .with_id()
+    }
+}
+
+/// Progress through the L1 event history, persisted with the mints it produced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositCursor {
+    /// Next L1 block to scan
+    pub next_block: u64,
+    /// Nonce of the next mint transaction
+    pub next_nonce: u64,
+}
+
+/// L1 block whose hash was recorded when the range ending at it was scanned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockCheckpoint {
+    pub number: u64,
+    pub hash: [u8; 32],
+}
+
+/// An L1 reorg that orphaned blocks the deposit monitor had already scanned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReorgIncident {
+    /// Unix time the reorg was detected
+    pub detected_at: u64,
+    pub l1_head: u64,
+    /// Newest scanned block still on the canonical chain; scanning resumes after it
+    pub common_ancestor: u64,
+    /// Newest scanned block that was orphaned
+    pub orphaned_block: u64,
+    /// Mints not yet executed that were dropped, to be re-derived from the new chain
+    pub rolled_back_mints: u64,
+    /// Hashes of mints the state had already executed for deposits in orphaned blocks
+    pub irreversible_mints: Vec<[u8; 32]>,
+}
+
+/// Deposit monitor statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepositStats {
+    pub total_deposits: u64,
+    pub total_minted: u64,
+    /// Deposit logs that could not be decoded and were skipped
+    pub rejected_logs: u64,
+    pub last_l1_head: u64,
+    pub reorgs_detected: u64,
+    pub mints_rolled_back: u64,
+    /// Executed mints whose deposits were orphaned by a reorg
+    pub irreversible_mints: u64,
+}
+
+/// Minimal Ethereum JSON-RPC client for the calls the monitor needs
+pub struct L1Client {
+    url: String,
+    http: reqwest::Client,
+    request_id: AtomicU64,
+}
+
+impl L1Client {
+    /// Client for the endpoint at `url`, reached through `proxy` when set
+    pub fn new(url: String, timeout: Duration, proxy: Option<&str>) -> Result<Self, BridgeError> {
+        let mut http = reqwest::Client::builder().timeout(timeout);
+        if let Some(proxy) = proxy {
+            let proxy = socks_proxy(proxy, &url).map_err(|e| BridgeError::NetworkError(e.to_string()))?;
+            http = http.proxy(proxy);
+        }
+        let http = http.build().map_err(|e| BridgeError::NetworkError(e.to_string()))?;
+        Ok(Self {
+            url,
+            http,
+            request_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Get the number of the latest L1 block
+    pub async fn block_number(&self) -> Result<u64, BridgeError> {
+        let result = self.call("eth_blockNumber", json!([])).await?;
+        parse_quantity(result.as_str().unwrap_or_default())
+    }
+
+    /// Get the logs of `address` whose first topic is one of `topics` in `from..=to`
+    pub async fn get_logs(
+        &self,
+        address: &str,
+        topics: &[[u8; 32]],
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<Value>, BridgeError> {
+        let topics: Vec<String> = topics.iter().map(|topic| format!("0x{}", hex::encode(topic))).collect();
+        let filter = json!([{
+            "address": address,
+            "topics": [topics],
+            "fromBlock": format!("0x{:x}", from),
+            "toBlock": format!("0x{:x}", to),
+        }]);
+        match self.call("eth_getLogs", filter).await? {
+            Value::Array(logs) => Ok(logs),
+            _ => Err(BridgeError::ArbitrumError("eth_getLogs: result is not an array".to_string())),
+        }
+    }
+
+    /// Get the hash of block `number`, or `None` if the L1 has no such block
+    pub async fn block_hash(&self, number: u64) -> Result<Option<[u8; 32]>, BridgeError> {
+        let block = self.call("eth_getBlockByNumber", json!([format!("0x{:x}", number), false])).await?;
+        if block.is_null() {
+            return Ok(None);
+        }
+        let hash = decode_hex(block.get("hash").and_then(Value::as_str).unwrap_or_default())?;
+        hash.try_into()
+            .map(Some)
+            .map_err(|_| BridgeError::ArbitrumError(format!("Block {} has an invalid hash", number)))
+    }
+
+    pub(crate) async fn call(&self, method: &str, params: Value) -> Result<Value, BridgeError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.request_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+        let response = self
+            .http
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| BridgeError::NetworkError(e.to_string()))?;
+        let mut body: Value = response
+            .json()
+            .await
+            .map_err(|e| BridgeError::ArbitrumError(format!("{}: {}", method, e)))?;
+        if let Some(error) = body.get("error").filter(|e| !e.is_null()) {
+            let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+            return Err(BridgeError::ArbitrumError(format!("{}: {}", method, message)));
+        }
+        body.get_mut("result")
+            .map(Value::take)
+            .ok_or_else(|| BridgeError::ArbitrumError(format!("{}: missing result", method)))
+    }
+}
+
+/// Ingests confirmed L1 deposits as mint transactions.
+///
+/// Only blocks at least `confirmations` deep are scanned; the cursor and the mints of
+/// each scanned range are written in one batch so a restart neither skips nor repeats a
+/// deposit. Should the L1 still reorganise scanned blocks, the hash recorded for each
+/// range no longer matches: the monitor rewinds to the newest range still canonical,
+/// drops the mints the state has not executed and rescans.
+pub struct DepositMonitor {
+    config: DepositMonitorConfig,
+    client: L1Client,
+    db: Arc<RwLock<RocksStateDB>>,
+    cursor: DepositCursor,
+    checkpoints: Vec<BlockCheckpoint>,
+    attester: Option<MintAttester>,
+    stats: DepositStats,
+}
+
+impl DepositMonitor {
+    /// Create a monitor resuming from the cursor stored in `db`
+    pub async fn with_state_db(config: DepositMonitorConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, BridgeError> {
+        if config.max_block_range == 0 {
+            return Err(BridgeError::ConfigError("Deposit block range must be positive".to_string()));
+        }
+        let client = L1Client::new(config.rpc_url.clone(), config.timeout, config.proxy.as_deref())?;
+        let (cursor, checkpoints) = {
+            let db = db.read().await;
+            let cursor = match db.get_sync(CURSOR_KEY)? {
+                Some(bytes) => serde_json::from_slice(&bytes)?,
+                None => DepositCursor {
+                    next_block: config.start_block,
+                    next_nonce: 0,
+                },
+            };
+            let checkpoints = match db.get_sync(CHECKPOINTS_KEY)? {
+                Some(bytes) => serde_json::from_slice(&bytes)?,
+                None => Vec::new(),
+            };
+            (cursor, checkpoints)
+        };
+        Ok(Self {
+            config,
+            client,
+            db,
+            cursor,
+            checkpoints,
+            attester: None,
+            stats: DepositStats::default(),
+        })
+    }
+
+    /// Sign the mints derived from now on with `attester`
+    pub fn set_attester(&mut self, attester: MintAttester) {
+        self.attester = Some(attester);
+    }
+
+    /// Scan every newly confirmed L1 block and store a mint for each deposit found
+    pub async fn poll(&mut self) -> Result<Vec<Deposit>, BridgeError> {
+        let head = self.client.block_number().await?;
+        self.stats.last_l1_head = head;
+        self.check_reorg(head).await?;
+        let Some(confirmed) = head.checked_sub(self.config.confirmations) else {
+            return Ok(Vec::new());
+        };
+
+        let topics = [deposit_topic(), token_deposit_topic()];
+        let mut deposits = Vec::new();
+        while self.cursor.next_block <= confirmed {
+            let from = self.cursor.next_block;
+            let to = confirmed.min(from.saturating_add(self.config.max_block_range - 1));
+            let logs = self.client.get_logs(&self.config.contract_address, &topics, from, to).await?;
+
+            let mut found = Vec::with_capacity(logs.len());
+            for log in &logs {
+                match Deposit::from_log(log) {
+                    Ok(Deposit { token: Some(token), .. }) if self.bridged_token(&token).is_none() => {
+                        println!("Skipping deposit of unbridged token {}", hex::encode(token));
+                        self.stats.rejected_logs += 1;
+                    }
+                    Ok(deposit) => found.push(deposit),
+                    Err(e) => {
+                        println!("Skipping deposit log: {}", e);
+                        self.stats.rejected_logs += 1;
+                    }
+                }
+            }
+            found.sort_by_key(|deposit| (deposit.l1_block, deposit.log_index));
+            let hash = self
+                .client
+                .block_hash(to)
+                .await?
+                .ok_or_else(|| BridgeError::ArbitrumError(format!("L1 block {} is missing", to)))?;
+
+            let mut cursor = DepositCursor {
+                next_block: to + 1,
+                next_nonce: self.cursor.next_nonce,
+            };
+            let mut checkpoints = self.checkpoints.clone();
+            checkpoints.push(BlockCheckpoint { number: to, hash });
+            let excess = checkpoints.len().saturating_sub(self.config.reorg_checkpoints.max(1));
+            checkpoints.drain(..excess);
+
+            let mut db = self.db.write().await;
+            // Deposits re-included after a reorg keep the mint the state already executed
+            let mut fresh = Vec::with_capacity(found.len());
+            for deposit in found {
+                let token = deposit.token.as_deref().and_then(|token| self.bridged_token(token));
+                if !minted(&db, &deposit, token, self.attester.as_ref(), self.cursor.next_nonce)? {
+                    fresh.push(deposit);
+                }
+            }
+            let found = fresh;
+            let mut entries = Vec::with_capacity(2 * found.len() + 2);
+            let mut changes = Vec::with_capacity(found.len());
+            for deposit in &found {
+                let token = deposit.token.as_deref().and_then(|token| self.bridged_token(token));
+                let mint = attested(self.attester.as_ref(), deposit.mint_transaction(cursor.next_nonce, token));
+                let key = mint_record_key(MintSource::BridgeDeposit, cursor.next_nonce);
+                entries.push((key, mint.to_canonical_bytes()));
+                entries.push((minted_key(&deposit.deposit_id()), cursor.next_nonce.to_be_bytes().to_vec()));
+                let recipient = deposit.recipient.clone();
+                let transfer = BridgeTransfer::new(mint.hash, TransferKind::Deposit, recipient, deposit.amount)
+                    .with_mint_nonce(cursor.next_nonce);
+                changes.push((transfer, TransferStatus::Pending, None));
+                cursor.next_nonce += 1;
+            }
+            entries.extend(transfers::transitions(&db, changes)?);
+            entries.push((CURSOR_KEY.to_vec(), serde_json::to_vec(&cursor)?));
+            entries.push((CHECKPOINTS_KEY.to_vec(), serde_json::to_vec(&checkpoints)?));
+            db.write_batch_sync(&entries)?;
+            drop(db);
+
+            self.cursor = cursor;
+            self.checkpoints = checkpoints;
+            self.stats.total_deposits += found.len() as u64;
+            deposits.extend(found);
+        }
+        Ok(deposits)
+    }
+
+    /// Compare the newest checkpoint with the L1 and, if it was orphaned, roll back to
+    /// the newest checkpoint still on the canonical chain
+    async fn check_reorg(&mut self, head: u64) -> Result<(), BridgeError> {
+        let Some(newest) = self.checkpoints.last().copied() else {
+            return Ok(());
+        };
+        if self.client.block_hash(newest.number).await? == Some(newest.hash) {
+            return Ok(());
+        }
+
+        let mut ancestor = None;
+        for (index, checkpoint) in self.checkpoints.iter().enumerate().rev().skip(1) {
+            if self.client.block_hash(checkpoint.number).await? == Some(checkpoint.hash) {
+                ancestor = Some((index, checkpoint.number));
+                break;
+            }
+        }
+        let Some((index, ancestor)) = ancestor else {
+            self.stats.reorgs_detected += 1;
+            return Err(BridgeError::StateError(format!(
+                "L1 reorg reaches below block {}, the oldest of {} checkpoints",
+                self.checkpoints[0].number,
+                self.checkpoints.len()
+            )));
+        };
+
+        let mut db = self.db.write().await;
+        let executed = db.get_account(MINT_ADDRESS)?.nonce.min(self.cursor.next_nonce);
+        // Mints follow L1 order, so those of orphaned blocks are the newest ones
+        let mut first_orphaned = self.cursor.next_nonce;
+        while first_orphaned > 0 && read_mint(&db, first_orphaned - 1)?.timestamp > ancestor {
+            first_orphaned -= 1;
+        }
+        let irreversible_mints = (first_orphaned..executed)
+            .map(|nonce| read_mint(&db, nonce).map(|mint| mint.hash))
+            .collect::<Result<Vec<_>, _>>()?;
+        let next_nonce = first_orphaned.max(executed);
+
+        let incident = ReorgIncident {
+            detected_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            l1_head: head,
+            common_ancestor: ancestor,
+            orphaned_block: newest.number,
+            rolled_back_mints: self.cursor.next_nonce - next_nonce,
+            irreversible_mints,
+        };
+        let cursor = DepositCursor {
+            next_block: ancestor + 1,
+            next_nonce,
+        };
+        let checkpoints = self.checkpoints[..=index].to_vec();
+        let mut incidents = get_reorg_incidents(&db)?;
+        incidents.push(incident.clone());
+        let excess = incidents.len().saturating_sub(MAX_INCIDENTS);
+        incidents.drain(..excess);
+        let mut changes = Vec::new();
+        for nonce in next_nonce..self.cursor.next_nonce {
+            let mint = read_mint(&db, nonce)?;
+            let transfer = BridgeTransfer::new(mint.hash, TransferKind::Deposit, Vec::new(), 0);
+            let detail = format!("L1 blocks {}..={} were orphaned", ancestor + 1, newest.number);
+            changes.push((transfer, TransferStatus::RolledBack, Some(detail)));
+        }
+        // Clear the rolled-back mints so block validation no longer accepts them
+        let mut entries: Vec<_> = (next_nonce..self.cursor.next_nonce)
+            .map(|nonce| (mint_record_key(MintSource::BridgeDeposit, nonce), Vec::new()))
+            .collect();
+        entries.extend(transfers::transitions(&db, changes)?);
+        entries.push((CURSOR_KEY.to_vec(), serde_json::to_vec(&cursor)?));
+        entries.push((CHECKPOINTS_KEY.to_vec(), serde_json::to_vec(&checkpoints)?));
+        entries.push((INCIDENTS_KEY.to_vec(), serde_json::to_vec(&incidents)?));
+        db.write_batch_sync(&entries)?;
+        drop(db);
+
+        println!(
+            "⚠ L1 reorg orphaned scanned blocks {}..={}: rolled back {} mints, {} already executed",
+            ancestor + 1,
+            newest.number,
+            incident.rolled_back_mints,
+            incident.irreversible_mints.len()
+        );
+        self.cursor = cursor;
+        self.checkpoints = checkpoints;
+        self.stats.reorgs_detected += 1;
+        self.stats.mints_rolled_back += incident.rolled_back_mints;
+        self.stats.irreversible_mints += incident.irreversible_mints.len() as u64;
+        Ok(())
+    }
+
+    /// Mint transactions the committed state has not executed yet, in nonce order
+    pub async fn pending_mints(&self) -> Result<Vec<Transaction>, BridgeError> {
+        let db = self.db.read().await;
+        let executed = db.get_account(MINT_ADDRESS)?.nonce;
+        let mut mints = Vec::new();
+        for nonce in executed..self.cursor.next_nonce {
+            mints.push(read_mint(&db, nonce)?);
+        }
+        Ok(mints)
+    }
+
+    /// The bridged token whose L1 contract is `l1_origin`
+    fn bridged_token(&self, l1_origin: &[u8]) -> Option<&TokenInfo> {
+        self.config.tokens.iter().find(|token| token.l1_origin == l1_origin)
+    }
+
+    /// Get the persisted scan position
+    pub fn cursor(&self) -> DepositCursor {
+        self.cursor
+    }
+
+    /// Get deposit statistics
+    pub fn get_stats(&self) -> DepositStats {
+        DepositStats {
+            total_minted: self.cursor.next_nonce,
+            ..self.stats.clone()
+        }
+    }
+
+    /// Get the monitor configuration
+    pub fn config(&self) -> &DepositMonitorConfig {
+        &self.config
+    }
+}
+
+fn minted_key(deposit_id: &[u8; 32]) -> Vec<u8> {
+    [MINTED_PREFIX, deposit_id.as_slice()].concat()
+}
+
+fn read_mint(db: &RocksStateDB, nonce: u64) -> Result<Transaction, BridgeError> {
+    let bytes = db
+        .get_sync(&mint_record_key(MintSource::BridgeDeposit, nonce))?
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| BridgeError::StateError(format!("Mint {} is missing", nonce)))?;
+    Ok(Transaction::from_canonical_bytes(&bytes)?)
+}
+
+/// Hash of the mint from `source` holding `nonce`, if it is recorded and was not rolled back
+pub(crate) fn mint_hash_at(db: &RocksStateDB, source: MintSource, nonce: u64) -> Result<Option<[u8; 32]>, BridgeError> {
+    match db.get_sync(&mint_record_key(source, nonce))? {
+        Some(bytes) if !bytes.is_empty() => Ok(Some(Transaction::from_canonical_bytes(&bytes)?.hash)),
+        _ => Ok(None),
+    }
+}
+
+/// Whether the mint of `deposit` holds a nonce below `next_nonce`; a rollback may have
+/// dropped it or reused its nonce
+fn minted(
+    db: &RocksStateDB,
+    deposit: &Deposit,
+    token: Option<&TokenInfo>,
+    attester: Option<&MintAttester>,
+    next_nonce: u64,
+) -> Result<bool, BridgeError> {
+    let Some(nonce) = db.get_sync(&minted_key(&deposit.deposit_id()))? else {
+        return Ok(false);
+    };
+    let nonce = u64::from_be_bytes(
+        nonce
+            .try_into()
+            .map_err(|_| BridgeError::StateError("Corrupt mint index".to_string()))?,
+    );
+    if nonce >= next_nonce {
+        return Ok(false);
+    }
+    Ok(read_mint(db, nonce)?.hash == attested(attester, deposit.mint_transaction(nonce, token)).hash)
+}
+
+/// Get the recorded L1 reorg incidents, oldest first
+pub fn get_reorg_incidents(state: &RocksStateDB) -> Result<Vec<ReorgIncident>, BridgeError> {
+    match state.get_sync(INCIDENTS_KEY)? {
+        Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub(crate) fn decode_hex(value: &str) -> Result<Vec<u8>, BridgeError> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|e| BridgeError::ArbitrumError(format!("Invalid hex {}: {}", value, e)))
+}
+
+pub(crate) fn parse_quantity(value: &str) -> Result<u64, BridgeError> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|e| BridgeError::ArbitrumError(format!("Invalid quantity {}: {}", value, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    fn rpc_result(result: Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({ "jsonrpc": "2.0", "id": 0, "result": result }))
+    }
+
+    fn deposit_log(block: u64, log_index: u64, recipient: u8, amount: u64) -> Value {
+        let mut data = [0u8; 32];
+        data[24..].copy_from_slice(&amount.to_be_bytes());
+        json!({
+            "blockNumber": format!("0x{:x}", block),
+            "transactionHash": format!("0x{}", hex::encode([block as u8; 32])),
+            "logIndex": format!("0x{:x}", log_index),
+            "topics": [
+                format!("0x{}", hex::encode(deposit_topic())),
+                format!("0x{}", hex::encode([0xd0u8; 32])),
+                format!("0x{:064x}", recipient),
+            ],
+            "data": format!("0x{}", hex::encode(data)),
+            "removed": false,
+        })
+    }
+
+    /// Answers `eth_getBlockByNumber` with hashes that differ on `branch` from block `fork_from` on
+    struct Chain {
+        fork_from: u64,
+        branch: u8,
+    }
+
+    impl Respond for Chain {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let body: Value = serde_json::from_slice(&request.body).unwrap();
+            let number = parse_quantity(body["params"][0].as_str().unwrap()).unwrap();
+            let mut hash = [if number >= self.fork_from { self.branch } else { 0 }; 32];
+            hash[24..].copy_from_slice(&number.to_be_bytes());
+            rpc_result(json!({ "hash": format!("0x{}", hex::encode(hash)) }))
+        }
+    }
+
+    async fn mount_head(server: &MockServer, head: u64) {
+        mount_branch(server, head, u64::MAX, 0).await;
+    }
+
+    async fn mount_branch(server: &MockServer, head: u64, fork_from: u64, branch: u8) {
+        server.reset().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "eth_blockNumber" })))
+            .respond_with(rpc_result(json!(format!("0x{:x}", head))))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "eth_getBlockByNumber" })))
+            .respond_with(Chain { fork_from, branch })
+            .mount(server)
+            .await;
+    }
+
+    async fn mount_logs(server: &MockServer, from: u64, to: u64, logs: Value) {
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_getLogs",
+                "params": [{ "fromBlock": format!("0x{:x}", from), "toBlock": format!("0x{:x}", to) }],
+            })))
+            .respond_with(rpc_result(logs))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    fn test_config(url: String) -> DepositMonitorConfig {
+        DepositMonitorConfig {
+            rpc_url: url,
+            confirmations: 5,
+            start_block: 100,
+            max_block_range: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_deposit_log_decoding() {
+        let deposit = Deposit::from_log(&deposit_log(7, 2, 0xb0, 500)).unwrap();
+        assert_eq!((deposit.l1_block, deposit.log_index, deposit.amount), (7, 2, 500));
+        assert_eq!(deposit.recipient, [vec![0u8; 19], vec![0xb0]].concat());
+        assert_eq!(deposit.depositor, vec![0xd0; 20]);
+
+        let mint = deposit.mint_transaction(3, None);
+        assert_eq!((mint.sender.as_slice(), mint.nonce, mint.fee), (MINT_ADDRESS, 3, 0));
+        assert_eq!(mint.outputs[0].amount, 500);
+        assert_eq!(mint.hash, deposit.mint_transaction(3, None).hash);
+        assert!(mint.has_valid_id());
+
+        let mut oversized = deposit_log(7, 2, 0xb0, 500);
+        oversized["data"] = json!(format!("0x01{}", "00".repeat(31)));
+        assert!(Deposit::from_log(&oversized).is_err());
+        let mut foreign = deposit_log(7, 2, 0xb0, 500);
+        foreign["topics"][0] = json!(format!("0x{}", "11".repeat(32)));
+        assert!(Deposit::from_log(&foreign).is_err());
+
+        // A TokenDeposit names the L1 token between the event and the depositor
+        let mut token_log = deposit_log(7, 3, 0xb0, 500);
+        token_log["topics"][0] = json!(format!("0x{}", hex::encode(token_deposit_topic())));
+        token_log["topics"].as_array_mut().unwrap().insert(1, json!(format!("0x{:064x}", 0x70)));
+        let deposit = Deposit::from_log(&token_log).unwrap();
+        let l1_origin = [vec![0u8; 19], vec![0x70]].concat();
+        assert_eq!((deposit.token.as_ref(), deposit.depositor.as_slice()), (Some(&l1_origin), [0xd0; 20].as_slice()));
+        let info = TokenInfo { l1_origin, symbol: "USDC".to_string(), decimals: 6 };
+        let mint = deposit.mint_transaction(0, Some(&info));
+        assert_eq!(mint.asset, Some(info.asset_id()));
+        assert_eq!(serde_json::from_slice::<TokenInfo>(&mint.data).unwrap(), info);
+    }
+
+    #[tokio::test]
+    async fn test_poll_waits_for_confirmations_and_resumes_from_cursor() {
+        let server = MockServer::start().await;
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+
+        // Nothing below the start block is confirmed yet
+        mount_head(&server, 104).await;
+        let mut monitor = DepositMonitor::with_state_db(test_config(server.uri()), db.clone()).await.unwrap();
+        assert!(monitor.poll().await.unwrap().is_empty());
+        assert_eq!(monitor.cursor().next_block, 100);
+
+        // Blocks 100..=114 are confirmed at head 119, scanned in ranges of ten
+        mount_head(&server, 119).await;
+        mount_logs(&server, 100, 109, json!([deposit_log(105, 1, 0xb1, 20), deposit_log(105, 0, 0xb0, 10)])).await;
+        mount_logs(&server, 110, 114, json!([deposit_log(112, 0, 0xb2, 30)])).await;
+        let deposits = monitor.poll().await.unwrap();
+        let amounts: Vec<u64> = deposits.iter().map(|deposit| deposit.amount).collect();
+        assert_eq!(amounts, vec![10, 20, 30]);
+        assert_eq!(monitor.cursor(), DepositCursor { next_block: 115, next_nonce: 3 });
+        server.verify().await;
+
+        // A restarted monitor picks up where the last one stopped
+        let restarted = DepositMonitor::with_state_db(test_config(server.uri()), db.clone()).await.unwrap();
+        assert_eq!(restarted.cursor(), monitor.cursor());
+        let mints = restarted.pending_mints().await.unwrap();
+        let nonces: Vec<u64> = mints.iter().map(|mint| mint.nonce).collect();
+        assert_eq!(nonces, vec![0, 1, 2]);
+        assert_eq!(mints[2].outputs[0].amount, 30);
+        assert_eq!(restarted.get_stats().total_minted, 3);
+    }
+
+    #[tokio::test]
+    async fn test_reorg_rolls_back_unexecuted_mints() {
+        let server = MockServer::start().await;
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+
+        mount_head(&server, 119).await;
+        mount_logs(&server, 100, 109, json!([deposit_log(105, 0, 0xb0, 10)])).await;
+        mount_logs(&server, 110, 114, json!([deposit_log(112, 0, 0xb2, 30), deposit_log(113, 0, 0xb3, 40)])).await;
+        let mut monitor = DepositMonitor::with_state_db(test_config(server.uri()), db.clone()).await.unwrap();
+        assert_eq!(monitor.poll().await.unwrap().len(), 3);
+
+        // The state has executed the mints of blocks 105 and 112
+        {
+            let mut state = db.write().await;
+            let mut account = state.get_account(MINT_ADDRESS).unwrap();
+            account.nonce = 2;
+            state.put_account(MINT_ADDRESS, &account).unwrap();
+        }
+
+        // Blocks from 111 on are replaced; 112's deposit is re-included, 113's is gone and 115 is new
+        mount_branch(&server, 121, 111, 1).await;
+        mount_logs(&server, 110, 116, json!([deposit_log(112, 0, 0xb2, 30), deposit_log(115, 0, 0xb5, 50)])).await;
+        let deposits = monitor.poll().await.unwrap();
+        assert_eq!(deposits.iter().map(|deposit| deposit.amount).collect::<Vec<_>>(), vec![50]);
+        assert_eq!(monitor.cursor(), DepositCursor { next_block: 117, next_nonce: 3 });
+        server.verify().await;
+
+        let incidents = get_reorg_incidents(&*db.read().await).unwrap();
+        assert_eq!(incidents.len(), 1);
+        let incident = &incidents[0];
+        assert_eq!((incident.common_ancestor, incident.orphaned_block, incident.rolled_back_mints), (109, 114, 1));
+        let executed = Deposit::from_log(&deposit_log(112, 0, 0xb2, 30)).unwrap();
+        assert_eq!(incident.irreversible_mints, vec![executed.mint_transaction(1, None).hash]);
+        let stats = monitor.get_stats();
+        assert_eq!((stats.reorgs_detected, stats.mints_rolled_back, stats.irreversible_mints), (1, 1, 1));
+
+        let mints = monitor.pending_mints().await.unwrap();
+        assert_eq!((mints.len(), mints[0].nonce, mints[0].outputs[0].amount), (1, 2, 50));
+    }
+}
@@ -11,9 +11,18 @@ pub enum BridgeError {
     #[error("Arbitrum client error: {0}")]
     ArbitrumError(String),
     
+    #[error("Settlement layer error: {0}")]
+    SettlementError(String),
+    
+    #[error("Invalid challenge move: {0}")]
+    ChallengeError(String),
+    
     #[error("Fuego verification error: {0}")]
     FuegoError(String),
     
+    #[error("Invalid burn proof: {0}")]
+    InvalidBurnProof(String),
+    
     #[error("Relayer error: {0}")]
     RelayerError(String),
     
@@ -32,6 +41,9 @@ pub enum BridgeError {
     #[error("IO error: {0}")]
     IoError(String),
     
+    #[error("State error: {0}")]
+    StateError(String),
+    
     #[error("Timeout error: {0}")]
     TimeoutError(String),
     
@@ -61,4 +73,10 @@ impl From<block_sync::error::BlockSyncError> for BridgeError {
     fn from(err: block_sync::error::BlockSyncError) -> Self {
         BridgeError::FuegoError(err.to_string())
     }
-}
\ No newline at end of file
+}
+
+impl From<state_db::error::StateDBError> for BridgeError {
+    fn from(err: state_db::error::StateDBError) -> Self {
+        BridgeError::StateError(err.to_string())
+    }
+}
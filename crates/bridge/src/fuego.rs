@@ -140,6 +140,7 @@ mod tests {
                 .unwrap()
                 .as_secs(),
             difficulty: 1000,
+            nullifier_root: [0u8; 32],
             nonce: 0,
         };
         
@@ -164,6 +165,7 @@ mod tests {
                 .unwrap()
                 .as_secs(),
             difficulty: 1000,
+            nullifier_root: [0u8; 32],
             nonce: 0,
         };
         
@@ -187,6 +189,7 @@ mod tests {
                 .unwrap()
                 .as_secs(),
             difficulty: 1000,
+            nullifier_root: [0u8; 32],
             nonce: 0,
         };
         
@@ -1,43 +1,136 @@
 use anyhow::Result;
-use block_sync::{Block, BlockHeader};
+use block_sync::{Block, BlockHeader, Transaction};
+use execution::{Receipt, TokenInfo};
 use serde::{Deserialize, Serialize};
+use state_db::{MintSource, RocksStateDB};
 use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use tokio::time::{Duration, Instant};
 
 pub mod error;
 pub mod arbitrum;
+pub mod attestation;
+pub mod batches;
+pub mod burns;
+pub mod challenges;
+pub mod deposits;
 pub mod fuego;
 pub mod relayer;
+pub mod settlement;
+pub mod submission;
+pub mod transfers;
+pub mod watchtower;
+pub mod withdrawals;
+pub mod zksync;
 
 use error::BridgeError;
-use arbitrum::{ArbitrumClient, ProofSubmission};
+use arbitrum::ProofSubmission;
+use attestation::MintAttester;
+use batches::{BatchBuilder, BatchBuilderConfig, CommitmentSubmitter, L1Batch, SubmitterConfig};
+use burns::{BurnProof, XfgBurnConfig, XfgBurnMinter};
+use challenges::{Challenge, ChallengeStatus};
+use deposits::{Deposit, DepositMonitor, DepositMonitorConfig, L1Client};
 use fuego::{FuegoHeaderVerifier, HeaderVerification};
 use relayer::{Relayer, RelayerConfig};
+use settlement::{Settlement, SettlementBackend, SettlementLayer};
+use submission::{L1Signer, ProofSubmissionConfig, ProofSubmitter, SubmissionEvent};
+use transfers::{BridgeTransfer, TransferKind, TransferStatus};
+use watchtower::{BatchMismatch, Watchtower, WatchtowerConfig};
+use withdrawals::{WithdrawalBatch, WithdrawalConfig, WithdrawalQueue};
+
+/// Proofs the bridge is sending or following, keyed by header hash
+const PROOF_PREFIX: &[u8] = b"bridge/proofs/";
 
 /// Bridge configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BridgeConfig {
+    /// Layer batches, proofs and withdrawal roots are settled on
+    pub settlement: SettlementLayer,
+    /// JSON-RPC endpoint of the settlement layer, whichever `settlement` names
     pub arbitrum_rpc_url: String,
+    /// Bridge contract on the settlement layer
     pub arbitrum_contract_address: String,
+    /// How long a batch settled optimistically can be challenged
+    pub challenge_period: Duration,
+    /// How long a party to a fraud-proof challenge has for each move before it loses
+    pub challenge_move_timeout: Duration,
     pub fuego_rpc_url: String,
     pub relayer_interval: Duration,
     pub max_headers_per_batch: usize,
     pub proof_timeout: Duration,
     pub enable_auto_relay: bool,
+    /// L1 blocks a deposit must be buried under before it is minted
+    pub l1_confirmations: u64,
+    /// First L1 block scanned for deposits
+    pub deposit_start_block: u64,
+    pub deposit_poll_interval: Duration,
+    /// Most withdrawals committed to L1 under one root
+    pub max_withdrawals_per_batch: usize,
+    /// C0DL3 blocks committed to L1 per batch
+    pub batch_size: usize,
+    /// Longest a partial batch waits before it is committed
+    pub batch_timeout: Duration,
+    /// L1 account sending batch commitments
+    pub commitment_sender: String,
+    pub commitment_gas_limit: u64,
+    /// Highest L1 gas price, in wei, paid for a commitment
+    pub max_gas_price: u64,
+    /// EIP-155 chain id of the L1
+    pub l1_chain_id: u64,
+    /// Hex secp256k1 key signing proof submissions; proofs are only simulated without it
+    pub l1_signer_key: Option<String>,
+    /// Chain id of this chain, which mints are signed for
+    #[serde(default)]
+    pub chain_id: u64,
+    /// Hex ed25519 key attesting the mints this node derives. Without it mints are queued
+    /// unsigned, and the chain refuses them.
+    #[serde(default)]
+    pub mint_attester_key: Option<String>,
+    /// Verification of XFG burns on Fuego that HEAT is minted against
+    pub xfg_burns: XfgBurnConfig,
+    /// Checking of posted batches when the bridge runs as a watchtower
+    pub watchtower: WatchtowerConfig,
+    /// L1 tokens the bridge contract carries besides HEAT
+    #[serde(default)]
+    pub bridged_tokens: Vec<TokenInfo>,
+    /// SOCKS5 proxy, e.g. `socks5://127.0.0.1:9050` for Tor, the L1 endpoint is reached through
+    #[serde(default)]
+    pub proxy: Option<String>,
 }
 
 impl Default for BridgeConfig {
     fn default() -> Self {
         Self {
+            settlement: SettlementLayer::Arbitrum,
             arbitrum_rpc_url: "http://localhost:8545".to_string(),
             arbitrum_contract_address: "0x0000000000000000000000000000000000000000".to_string(),
+            challenge_period: Duration::from_secs(7 * 24 * 3600),
+            challenge_move_timeout: Duration::from_secs(24 * 3600),
             fuego_rpc_url: "http://localhost:8080".to_string(),
             relayer_interval: Duration::from_secs(60),
             max_headers_per_batch: 10,
             proof_timeout: Duration::from_secs(300),
             enable_auto_relay: true,
+            l1_confirmations: 12,
+            deposit_start_block: 0,
+            deposit_poll_interval: Duration::from_secs(15),
+            max_withdrawals_per_batch: 1_024,
+            batch_size: 100,
+            batch_timeout: Duration::from_secs(300),
+            commitment_sender: "0x0000000000000000000000000000000000000000".to_string(),
+            commitment_gas_limit: 300_000,
+            max_gas_price: 100_000_000_000,
+            l1_chain_id: 42161,
+            l1_signer_key: None,
+            chain_id: 1,
+            mint_attester_key: None,
+            xfg_burns: XfgBurnConfig::default(),
+            watchtower: WatchtowerConfig::default(),
+            bridged_tokens: Vec::new(),
+            proxy: None,
         }
     }
 }
@@ -78,20 +171,83 @@ pub struct BridgeStats {
     pub total_proofs_failed: u64,
     pub last_header_height: u64,
     pub last_proof_timestamp: u64,
+    pub total_deposits_ingested: u64,
+    /// Latest L1 block number seen by the deposit monitor
+    pub last_l1_block: u64,
+    pub total_withdrawal_batches: u64,
+    pub total_batches_committed: u64,
+    /// L1 reorgs that orphaned blocks already scanned for deposits
+    pub l1_reorgs: u64,
+    /// Unexecuted mints dropped because their deposits were orphaned
+    pub mints_rolled_back: u64,
+    /// Verified XFG burns queued for minting
+    pub xfg_burns_minted: u64,
+    /// Posted batches a watchtower checked against local execution
+    pub watchtower_batches_checked: u64,
+    /// Posted batches whose state root differed from the local one
+    pub watchtower_mismatches: u64,
+}
+
+/// Items waiting in each bridge queue
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BridgeQueueDepths {
+    pub pending_proofs: usize,
+    pub pending_mints: usize,
+    pub pending_withdrawals: usize,
+    /// Sealed blocks not yet part of an L1 batch
+    pub pending_batch_blocks: usize,
+}
+
+/// Background loops of a running bridge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeTask {
+    /// Polls L1 for deposits and rolls back mints orphaned by reorgs
+    DepositMonitor,
+    /// Seals batches and commits them to L1
+    BatchCommitter,
+    /// Follows submitted proofs until they confirm or revert
+    ProofTracker,
+    /// Plays fraud-proof challenges out on the settlement layer
+    ChallengeReferee,
+    /// Checks posted batches against local execution
+    Watchtower,
+}
+
+impl BridgeTask {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BridgeTask::DepositMonitor => "deposit_monitor",
+            BridgeTask::BatchCommitter => "batch_committer",
+            BridgeTask::ProofTracker => "proof_tracker",
+            BridgeTask::ChallengeReferee => "challenge_referee",
+            BridgeTask::Watchtower => "watchtower",
+        }
+    }
 }
 
 /// Bridge engine implementing Fuego to Arbitrum L3 bridging
 pub struct Bridge {
     config: BridgeConfig,
-    arbitrum_client: ArbitrumClient,
+    settlement: Arc<Settlement>,
     fuego_verifier: FuegoHeaderVerifier,
     relayer: Relayer,
     state: Arc<RwLock<BridgeState>>,
     stats: Arc<RwLock<BridgeStats>>,
     pending_proofs: Arc<RwLock<HashMap<[u8; 32], BridgeProof>>>,
     submitted_proofs: Arc<RwLock<HashMap<[u8; 32], BridgeProof>>>,
+    deposits: Option<Arc<RwLock<DepositMonitor>>>,
+    burns: Option<Arc<RwLock<XfgBurnMinter>>>,
+    withdrawals: Option<Arc<RwLock<WithdrawalQueue>>>,
+    batches: Option<Arc<RwLock<BatchBuilder>>>,
+    submitter: Option<Arc<RwLock<CommitmentSubmitter<L1Client>>>>,
+    proof_submitter: Option<Arc<RwLock<ProofSubmitter<L1Client>>>>,
+    watchtower: Option<Arc<RwLock<Watchtower>>>,
+    /// State database holding the lifecycle of every transfer
+    db: Option<Arc<RwLock<RocksStateDB>>>,
     message_tx: mpsc::Sender<BridgeMessage>,
     message_rx: mpsc::Receiver<BridgeMessage>,
+    /// The caller runs the background loops itself rather than `start` spawning them
+    external_tasks: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,6 +256,11 @@ pub enum BridgeMessage {
     ProofSubmitted([u8; 32]),
     ProofConfirmed([u8; 32]),
     ProofFailed([u8; 32], String),
+    DepositsIngested(Vec<Deposit>),
+    WithdrawalsCommitted(u64, [u8; 32]),
+    BatchCommitted(u64, [u8; 32]),
+    ChallengeResolved(u64, ChallengeStatus),
+    BatchMismatch(BatchMismatch),
     BridgeError(String),
 }
 
@@ -108,9 +269,11 @@ impl Bridge {
     pub fn new(config: BridgeConfig) -> Result<Self, BridgeError> {
         let (message_tx, message_rx) = mpsc::channel(1000);
         
-        let arbitrum_client = ArbitrumClient::new(
+        let settlement = Settlement::new(
+            config.settlement,
             config.arbitrum_rpc_url.clone(),
             config.arbitrum_contract_address.clone(),
+            config.challenge_period,
         )?;
         
         let fuego_verifier = FuegoHeaderVerifier::new(config.fuego_rpc_url.clone())?;
@@ -124,7 +287,7 @@ impl Bridge {
         
         Ok(Self {
             config,
-            arbitrum_client,
+            settlement: Arc::new(settlement),
             fuego_verifier,
             relayer,
             state: Arc::new(RwLock::new(BridgeState::Initializing)),
@@ -135,14 +298,146 @@ impl Bridge {
                 total_proofs_failed: 0,
                 last_header_height: 0,
                 last_proof_timestamp: 0,
+                total_deposits_ingested: 0,
+                last_l1_block: 0,
+                total_withdrawal_batches: 0,
+                total_batches_committed: 0,
+                l1_reorgs: 0,
+                mints_rolled_back: 0,
+                xfg_burns_minted: 0,
+                watchtower_batches_checked: 0,
+                watchtower_mismatches: 0,
             })),
             pending_proofs: Arc::new(RwLock::new(HashMap::new())),
             submitted_proofs: Arc::new(RwLock::new(HashMap::new())),
+            deposits: None,
+            burns: None,
+            withdrawals: None,
+            batches: None,
+            submitter: None,
+            proof_submitter: None,
+            watchtower: None,
+            db: None,
             message_tx,
             message_rx,
+            external_tasks: false,
         })
     }
     
+    /// Ingest L1 deposits, queue withdrawals and batch blocks, keeping their progress in `db`,
+    /// and pick up the transfers that were in flight when the bridge last stopped
+    pub async fn attach_state_db(&mut self, db: Arc<RwLock<RocksStateDB>>) -> Result<(), BridgeError> {
+        self.db = Some(db.clone());
+        let monitor_config = DepositMonitorConfig {
+            rpc_url: self.config.arbitrum_rpc_url.clone(),
+            contract_address: self.config.arbitrum_contract_address.clone(),
+            confirmations: self.config.l1_confirmations,
+            start_block: self.config.deposit_start_block,
+            poll_interval: self.config.deposit_poll_interval,
+            timeout: self.config.proof_timeout,
+            tokens: self.config.bridged_tokens.clone(),
+            proxy: self.config.proxy.clone(),
+            ..Default::default()
+        };
+        let attester = match &self.config.mint_attester_key {
+            Some(key) => Some(MintAttester::from_hex(key, self.config.chain_id)?),
+            None => None,
+        };
+        let mut deposits = DepositMonitor::with_state_db(monitor_config, db.clone()).await?;
+        
+        let mut burns_config = self.config.xfg_burns.clone();
+        burns_config.fuego.proxy = burns_config.fuego.proxy.or_else(|| self.config.proxy.clone());
+        let mut burns = XfgBurnMinter::with_state_db(burns_config, db.clone()).await?;
+        if let Some(attester) = attester {
+            println!("✓ Attesting mints as {}", hex::encode(attester.public_key()));
+            deposits.set_attester(attester.clone());
+            burns.set_attester(attester);
+        }
+        self.deposits = Some(Arc::new(RwLock::new(deposits)));
+        self.burns = Some(Arc::new(RwLock::new(burns)));
+        
+        let withdrawal_config = WithdrawalConfig {
+            max_batch_size: self.config.max_withdrawals_per_batch,
+        };
+        self.withdrawals = Some(Arc::new(RwLock::new(WithdrawalQueue::with_state_db(withdrawal_config, db.clone()).await?)));
+        
+        let batch_config = BatchBuilderConfig {
+            batch_size: self.config.batch_size,
+            batch_timeout: self.config.batch_timeout,
+        };
+        self.batches = Some(Arc::new(RwLock::new(BatchBuilder::with_state_db(batch_config, db.clone()).await?)));
+        
+        let submitter_config = SubmitterConfig {
+            from: self.config.commitment_sender.clone(),
+            to: self.config.arbitrum_contract_address.clone(),
+            gas_limit: self.config.commitment_gas_limit,
+            max_gas_price: self.config.max_gas_price,
+        };
+        let transport = self.l1_client()?;
+        self.submitter = Some(Arc::new(RwLock::new(CommitmentSubmitter::new(submitter_config, transport))));
+        
+        if let Some(key) = &self.config.l1_signer_key {
+            let proof_config = ProofSubmissionConfig {
+                chain_id: self.config.l1_chain_id,
+                contract_address: self.config.arbitrum_contract_address.clone(),
+                gas_limit: self.config.commitment_gas_limit,
+                max_gas_price: self.config.max_gas_price,
+                ..Default::default()
+            };
+            let signer = L1Signer::from_hex(key, self.config.l1_chain_id)?;
+            let transport = self.l1_client()?;
+            let proof_submitter = ProofSubmitter::with_state_db(proof_config, signer, transport, db).await?;
+            self.proof_submitter = Some(Arc::new(RwLock::new(proof_submitter)));
+        }
+        self.recover().await
+    }
+    
+    /// Client for the settlement layer's JSON-RPC endpoint
+    fn l1_client(&self) -> Result<L1Client, BridgeError> {
+        L1Client::new(self.config.arbitrum_rpc_url.clone(), self.config.proof_timeout, self.config.proxy.as_deref())
+    }
+
+    /// Check the batches other nodes post to L1 against the state in `db` instead of
+    /// bridging: nothing is minted, batched or submitted, and mismatches are challenged
+    pub async fn attach_watchtower(&mut self, db: Arc<RwLock<RocksStateDB>>) -> Result<(), BridgeError> {
+        let l1 = self.l1_client()?;
+        let contract_address = self.config.arbitrum_contract_address.clone();
+        let watchtower =
+            Watchtower::with_state_db(self.config.watchtower.clone(), l1, contract_address, db.clone()).await?;
+        self.watchtower = Some(Arc::new(RwLock::new(watchtower)));
+        self.db = Some(db);
+        Ok(())
+    }
+    
+    /// Complete the mints executed while the bridge was down and reload the proofs it was
+    /// still sending or following. Running it again finds nothing more to do.
+    async fn recover(&self) -> Result<(), BridgeError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let mut db = db.write().await;
+        let completed = transfers::reconcile(&mut db)?;
+        let mut reloaded = 0;
+        for transfer in transfers::open(&db)? {
+            if transfer.kind != TransferKind::Proof {
+                continue;
+            }
+            let Some(bytes) = db.get_sync(&proof_key(&transfer.id))? else {
+                continue;
+            };
+            let proofs = match transfer.status {
+                TransferStatus::Submitted => &self.submitted_proofs,
+                _ => &self.pending_proofs,
+            };
+            proofs.write().await.insert(transfer.id, serde_json::from_slice(&bytes)?);
+            reloaded += 1;
+        }
+        if completed + reloaded > 0 {
+            println!("Recovered bridge transfers: {} mints completed, {} proofs reloaded", completed, reloaded);
+        }
+        Ok(())
+    }
+    
     /// Start the bridge
     pub async fn start(&mut self) -> Result<(), BridgeError> {
         *self.state.write().await = BridgeState::Running;
@@ -150,6 +445,15 @@ impl Bridge {
         // Start relayer
         self.relayer.start().await?;
         
+        // Start the background loops unless the caller supervises them
+        if !self.external_tasks {
+            for task in self.tasks() {
+                if let Some(run) = self.task(task) {
+                    tokio::spawn(run);
+                }
+            }
+        }
+        
         // Start message processing loop
         let message_rx = std::mem::replace(&mut self.message_rx, mpsc::channel(1000).1);
         let state = self.state.clone();
@@ -160,6 +464,63 @@ impl Bridge {
         Ok(())
     }
     
+    /// Leave the background loops to the caller, which runs them through `task`
+    pub fn set_external_tasks(&mut self, external: bool) {
+        self.external_tasks = external;
+    }
+    
+    /// Background loops the bridge has the components for
+    pub fn tasks(&self) -> Vec<BridgeTask> {
+        let mut tasks = Vec::new();
+        if self.deposits.is_some() {
+            tasks.push(BridgeTask::DepositMonitor);
+        }
+        if self.batches.is_some() && self.submitter.is_some() {
+            tasks.push(BridgeTask::BatchCommitter);
+        }
+        if self.proof_submitter.is_some() {
+            tasks.push(BridgeTask::ProofTracker);
+        }
+        if self.db.is_some() {
+            tasks.push(BridgeTask::ChallengeReferee);
+        }
+        if self.watchtower.is_some() {
+            tasks.push(BridgeTask::Watchtower);
+        }
+        tasks
+    }
+    
+    /// Future running `task` until the bridge stops, or `None` when it is not configured
+    pub fn task(&self, task: BridgeTask) -> Option<Pin<Box<dyn Future<Output = ()> + Send>>> {
+        let state = self.state.clone();
+        let stats = self.stats.clone();
+        let message_tx = self.message_tx.clone();
+        match task {
+            BridgeTask::DepositMonitor => {
+                let (monitor, db) = (self.deposits.clone()?, self.db.clone()?);
+                Some(Box::pin(Self::monitor_events(monitor, db, state, stats, message_tx)))
+            }
+            BridgeTask::BatchCommitter => {
+                let (batches, submitter) = (self.batches.clone()?, self.submitter.clone()?);
+                let settlement = self.settlement.clone();
+                Some(Box::pin(Self::commit_batches(batches, submitter, settlement, state, stats, message_tx)))
+            }
+            BridgeTask::ProofTracker => {
+                let (proof_submitter, db) = (self.proof_submitter.clone()?, self.db.clone()?);
+                Some(Box::pin(Self::track_proofs(proof_submitter, db, state, stats, message_tx)))
+            }
+            BridgeTask::ChallengeReferee => {
+                let (settlement, db) = (self.settlement.clone(), self.db.clone()?);
+                let move_timeout = self.config.challenge_move_timeout;
+                Some(Box::pin(Self::referee_challenges(settlement, db, move_timeout, state, message_tx)))
+            }
+            BridgeTask::Watchtower => {
+                let (watchtower, settlement) = (self.watchtower.clone()?, self.settlement.clone());
+                Some(Box::pin(Self::watch_batches(watchtower, settlement, state, stats, message_tx)))
+            }
+        }
+    }
+    
     /// Stop the bridge
     pub async fn stop(&mut self) -> Result<(), BridgeError> {
         *self.state.write().await = BridgeState::Stopping;
@@ -199,8 +560,13 @@ impl Bridge {
         }
     }
     
-    /// Submit proof to Arbitrum
-    pub async fn submit_to_arbitrum(&self, proof: &BridgeProof) -> Result<(), BridgeError> {
+    /// Submit proof to the settlement layer
+    #[tracing::instrument(
+        name = "bridge.submit_to_settlement",
+        skip_all,
+        fields(layer = self.settlement.layer().name())
+    )]
+    pub async fn submit_to_settlement(&self, proof: &BridgeProof) -> Result<(), BridgeError> {
         let status = self.state.read().await;
         if !matches!(*status, BridgeState::Running) {
             return Err(BridgeError::BridgeNotRunning);
@@ -214,7 +580,15 @@ impl Bridge {
             timestamp: proof.submission_timestamp,
         };
         
-        let result = self.arbitrum_client.submit_proof(submission).await;
+        let result = match &self.proof_submitter {
+            Some(proof_submitter) => proof_submitter
+                .write()
+                .await
+                .submit(submission.header_hash, &submission.proof_data)
+                .await
+                .map(|_| ()),
+            None => self.settlement.submit_proof(submission).await,
+        };
         
         match result {
             Ok(_) => {
@@ -227,6 +601,7 @@ impl Bridge {
                 
                 // Move proof to submitted state
                 let header_hash = proof.fuego_header.hash()?;
+                record_proof(self.db.as_ref(), header_hash, TransferStatus::Submitted, None, None).await?;
                 {
                     let mut pending = self.pending_proofs.write().await;
                     if let Some(proof) = pending.remove(&header_hash) {
@@ -256,6 +631,11 @@ impl Bridge {
         }
     }
     
+    /// Backend of the layer the bridge settles on
+    pub fn settlement(&self) -> &Settlement {
+        &self.settlement
+    }
+    
     /// Get bridge state
     pub async fn get_bridge_state(&self) -> BridgeState {
         self.state.read().await.clone()
@@ -266,11 +646,105 @@ impl Bridge {
         self.stats.read().await.clone()
     }
     
+    /// Mint transactions for confirmed deposits and verified burns that the state has not
+    /// executed yet, deposits first
+    pub async fn pending_mints(&self) -> Result<Vec<Transaction>, BridgeError> {
+        let mut mints = match &self.deposits {
+            Some(monitor) => monitor.read().await.pending_mints().await?,
+            None => Vec::new(),
+        };
+        if let Some(burns) = &self.burns {
+            mints.extend(burns.read().await.pending_mints().await?);
+        }
+        Ok(mints)
+    }
+    
+    /// Queue the HEAT mint a verified XFG burn backs. Deposits are not queued here: the
+    /// deposit monitor mints them once they are confirmed on L1.
+    pub async fn queue_heat_mint(&self, source: MintSource, proof: &BurnProof) -> Result<Transaction, BridgeError> {
+        if source != MintSource::XfgBurn {
+            return Err(BridgeError::ConfigError("Bridge deposits are minted by the deposit monitor".to_string()));
+        }
+        let burns = self
+            .burns
+            .as_ref()
+            .ok_or_else(|| BridgeError::ConfigError("State database not attached".to_string()))?;
+        let mint = burns.write().await.queue_mint(proof).await?;
+        self.stats.write().await.xfg_burns_minted += 1;
+        Ok(mint)
+    }
+    
+    /// Add a block whose state has been committed to the next L1 batch
+    pub async fn add_sealed_block(&self, block: &Block) -> Result<Option<L1Batch>, BridgeError> {
+        let batches = self
+            .batches
+            .as_ref()
+            .ok_or_else(|| BridgeError::ConfigError("State database not attached".to_string()))?;
+        batches.write().await.add_block(block).await
+    }
+    
+    /// Queue the withdrawals burned by an executed block
+    pub async fn record_withdrawals(&self, receipts: &[Receipt]) -> Result<usize, BridgeError> {
+        let withdrawals = self
+            .withdrawals
+            .as_ref()
+            .ok_or_else(|| BridgeError::ConfigError("State database not attached".to_string()))?;
+        withdrawals.write().await.ingest(receipts).await
+    }
+    
+    /// Seal the queued withdrawals into a batch and commit its root to L1
+    pub async fn commit_withdrawal_batch(&self) -> Result<Option<WithdrawalBatch>, BridgeError> {
+        let status = self.state.read().await;
+        if !matches!(*status, BridgeState::Running) {
+            return Err(BridgeError::BridgeNotRunning);
+        }
+        drop(status);
+        
+        let withdrawals = self
+            .withdrawals
+            .as_ref()
+            .ok_or_else(|| BridgeError::ConfigError("State database not attached".to_string()))?;
+        let mut queue = withdrawals.write().await;
+        // A batch whose commit failed is retried before anything new is sealed
+        let batch = match queue.uncommitted_batch().await? {
+            Some(batch) => Some(batch),
+            None => queue.seal_batch().await?,
+        };
+        let Some(mut batch) = batch else {
+            return Ok(None);
+        };
+        
+        self.settlement.withdraw(batch.index, batch.root).await?;
+        queue.mark_committed(batch.index).await?;
+        batch.committed = true;
+        
+        self.stats.write().await.total_withdrawal_batches += 1;
+        let _ = self.message_tx.send(BridgeMessage::WithdrawalsCommitted(batch.index, batch.root)).await;
+        
+        Ok(Some(batch))
+    }
+    
     /// Get pending proofs count
     pub async fn get_pending_proofs_count(&self) -> usize {
         self.pending_proofs.read().await.len()
     }
     
+    /// Depth of the proof, mint, withdrawal and batch queues
+    pub async fn queue_depths(&self) -> Result<BridgeQueueDepths, BridgeError> {
+        let mut depths = BridgeQueueDepths {
+            pending_proofs: self.pending_proofs.read().await.len(),
+            pending_mints: self.pending_mints().await?.len(),
+            ..Default::default()
+        };
+        if let Some(withdrawals) = &self.withdrawals {
+            depths.pending_withdrawals = withdrawals.read().await.pending().len();
+        }
+        if let Some(batches) = &self.batches {
+            depths.pending_batch_blocks = batches.read().await.pending_blocks();
+        }
+        Ok(depths)
+    }
+    
     /// Get submitted proofs count
     pub async fn get_submitted_proofs_count(&self) -> usize {
         self.submitted_proofs.read().await.len()
@@ -299,6 +773,7 @@ impl Bridge {
         
         // Store pending proof
         let header_hash = block.header.hash()?;
+        record_proof(self.db.as_ref(), header_hash, TransferStatus::Pending, None, Some(&proof)).await?;
         self.pending_proofs.write().await.insert(header_hash, proof.clone());
         
         Ok(proof)
@@ -326,6 +801,231 @@ impl Bridge {
         Ok(proof_data)
     }
     
+    /// Poll the L1 for confirmed deposits while the bridge is running
+    async fn monitor_events(
+        monitor: Arc<RwLock<DepositMonitor>>,
+        db: Arc<RwLock<RocksStateDB>>,
+        state: Arc<RwLock<BridgeState>>,
+        stats: Arc<RwLock<BridgeStats>>,
+        message_tx: mpsc::Sender<BridgeMessage>,
+    ) {
+        let poll_interval = monitor.read().await.config().poll_interval;
+        while matches!(*state.read().await, BridgeState::Running) {
+            let mut monitor_guard = monitor.write().await;
+            let result = monitor_guard.poll().await;
+            let monitor_stats = monitor_guard.get_stats();
+            drop(monitor_guard);
+            {
+                let mut stats = stats.write().await;
+                stats.l1_reorgs = monitor_stats.reorgs_detected;
+                stats.mints_rolled_back = monitor_stats.mints_rolled_back;
+            }
+            
+            match result {
+                Ok(deposits) => {
+                    {
+                        let mut stats = stats.write().await;
+                        stats.total_deposits_ingested += deposits.len() as u64;
+                        stats.last_l1_block = monitor_stats.last_l1_head;
+                    }
+                    if !deposits.is_empty() {
+                        let _ = message_tx.send(BridgeMessage::DepositsIngested(deposits)).await;
+                    }
+                }
+                // L1 outages are retried on the next poll
+                Err(e) => println!("Deposit polling failed: {}", e),
+            }
+            if let Err(e) = transfers::reconcile(&mut *db.write().await) {
+                println!("Mint reconciliation failed: {}", e);
+            }
+            
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+    
+    /// Seal expired batches and send every unsubmitted commitment while the bridge is running
+    async fn commit_batches(
+        batches: Arc<RwLock<BatchBuilder>>,
+        submitter: Arc<RwLock<CommitmentSubmitter<L1Client>>>,
+        settlement: Arc<Settlement>,
+        state: Arc<RwLock<BridgeState>>,
+        stats: Arc<RwLock<BridgeStats>>,
+        message_tx: mpsc::Sender<BridgeMessage>,
+    ) {
+        while matches!(*state.read().await, BridgeState::Running) {
+            let unsubmitted = {
+                let mut builder = batches.write().await;
+                if let Err(e) = builder.seal_if_expired().await {
+                    println!("Batch sealing failed: {}", e);
+                }
+                builder.unsubmitted().await.unwrap_or_default()
+            };
+            
+            // Commitments go out in batch order, so stop at the first failure
+            for batch in unsubmitted {
+                let submission = match submitter.write().await.submit(&batch).await {
+                    Ok(submission) => submission,
+                    Err(e) => {
+                        println!("Batch {} commitment failed: {}", batch.number, e);
+                        break;
+                    }
+                };
+                let tx_hash = submission.tx_hash;
+                if let Err(e) = batches.write().await.record_submission(batch.number, submission).await {
+                    println!("Batch {} commitment not recorded: {}", batch.number, e);
+                    break;
+                }
+                // Finality of the batch is followed on the settlement layer from here on
+                if let Err(e) = settlement.commit_batch(batch.number, batch.commitment()).await {
+                    println!("Batch {} not tracked on {}: {}", batch.number, settlement.layer().name(), e);
+                }
+                stats.write().await.total_batches_committed += 1;
+                let _ = message_tx.send(BridgeMessage::BatchCommitted(batch.number, tx_hash)).await;
+            }
+            
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+    
+    /// Follow signed proof submissions to confirmation while the bridge is running
+    async fn track_proofs(
+        proof_submitter: Arc<RwLock<ProofSubmitter<L1Client>>>,
+        db: Arc<RwLock<RocksStateDB>>,
+        state: Arc<RwLock<BridgeState>>,
+        stats: Arc<RwLock<BridgeStats>>,
+        message_tx: mpsc::Sender<BridgeMessage>,
+    ) {
+        while matches!(*state.read().await, BridgeState::Running) {
+            let events = match proof_submitter.write().await.poll().await {
+                Ok(events) => events,
+                Err(e) => {
+                    println!("Proof tracking failed: {}", e);
+                    Vec::new()
+                }
+            };
+            for event in events {
+                match event {
+                    SubmissionEvent::Confirmed(proof_id) => {
+                        if let Err(e) = record_proof(Some(&db), proof_id, TransferStatus::Completed, None, None).await {
+                            println!("Proof {:?} confirmation not recorded: {}", proof_id, e);
+                        }
+                        stats.write().await.total_proofs_confirmed += 1;
+                        let _ = message_tx.send(BridgeMessage::ProofConfirmed(proof_id)).await;
+                    }
+                    SubmissionEvent::Reverted(proof_id) => {
+                        let detail = Some("Reverted on L1".to_string());
+                        if let Err(e) = record_proof(Some(&db), proof_id, TransferStatus::Failed, detail, None).await {
+                            println!("Proof {:?} revert not recorded: {}", proof_id, e);
+                        }
+                        stats.write().await.total_proofs_failed += 1;
+                        let _ = message_tx
+                            .send(BridgeMessage::ProofFailed(proof_id, "Reverted on L1".to_string()))
+                            .await;
+                    }
+                    SubmissionEvent::Replaced(proof_id, gas_price) => {
+                        println!("Proof {:?} resubmitted at gas price {}", proof_id, gas_price);
+                    }
+                }
+            }
+            
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+    
+    /// Check posted batches while the bridge is running
+    async fn watch_batches(
+        watchtower: Arc<RwLock<Watchtower>>,
+        settlement: Arc<Settlement>,
+        state: Arc<RwLock<BridgeState>>,
+        stats: Arc<RwLock<BridgeStats>>,
+        message_tx: mpsc::Sender<BridgeMessage>,
+    ) {
+        let poll_interval = watchtower.read().await.config().poll_interval;
+        while matches!(*state.read().await, BridgeState::Running) {
+            let mut watchtower_guard = watchtower.write().await;
+            let result = watchtower_guard.poll(&settlement).await;
+            let watchtower_stats = watchtower_guard.get_stats();
+            drop(watchtower_guard);
+            {
+                let mut stats = stats.write().await;
+                stats.watchtower_batches_checked = watchtower_stats.batches_checked;
+                stats.watchtower_mismatches = watchtower_stats.mismatches;
+            }
+            
+            match result {
+                Ok(mismatches) => {
+                    for mismatch in mismatches {
+                        let _ = message_tx.send(BridgeMessage::BatchMismatch(mismatch)).await;
+                    }
+                }
+                // L1 outages are retried on the next poll
+                Err(e) => println!("Batch watching failed: {}", e),
+            }
+            
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+    
+    /// Referee fraud-proof challenges while the bridge is running
+    async fn referee_challenges(
+        settlement: Arc<Settlement>,
+        db: Arc<RwLock<RocksStateDB>>,
+        move_timeout: Duration,
+        state: Arc<RwLock<BridgeState>>,
+        message_tx: mpsc::Sender<BridgeMessage>,
+    ) {
+        while matches!(*state.read().await, BridgeState::Running) {
+            match Self::referee_pass(&settlement, &db, move_timeout).await {
+                Ok(finished) => {
+                    for challenge in finished {
+                        let _ = message_tx.send(BridgeMessage::ChallengeResolved(challenge.id, challenge.status)).await;
+                    }
+                }
+                Err(e) => println!("Challenge refereeing failed: {}", e),
+            }
+            
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+    
+    /// Open new challenges on the settlement layer, end those whose party to move let
+    /// `move_timeout` pass and apply finished ones to their batch, returning the finished ones
+    async fn referee_pass(
+        settlement: &Settlement,
+        db: &RwLock<RocksStateDB>,
+        move_timeout: Duration,
+    ) -> Result<Vec<Challenge>, BridgeError> {
+        // Moves made over RPC wait until the pass is done
+        let mut db = db.write().await;
+        let mut finished = Vec::new();
+        for unsettled in challenges::unsettled(&db)? {
+            let mut challenge = unsettled.clone();
+            if challenge.status == ChallengeStatus::Opened {
+                match settlement.submit_fraud_proof(challenge.batch, &challenge.claimed_state_root).await {
+                    Ok(()) => challenge.accept(challenges::now())?,
+                    Err(e) => challenge.reject(e.to_string())?,
+                }
+            }
+            challenge.expire(challenges::now(), move_timeout.as_secs());
+            if challenge.status.is_final() {
+                if challenge.status != ChallengeStatus::Rejected {
+                    let upheld = (challenge.status == ChallengeStatus::ChallengerWon)
+                        .then(|| challenge.resolution.clone().unwrap_or_default());
+                    // Batches committed before a restart are no longer tracked by the client
+                    if let Err(e) = settlement.resolve_fraud_proof(challenge.batch, upheld).await {
+                        println!("Challenge {} not applied to batch {}: {}", challenge.id, challenge.batch, e);
+                    }
+                }
+                challenge.settled = true;
+                finished.push(challenge.clone());
+            }
+            if challenge != unsettled {
+                challenges::save(&mut db, &challenge)?;
+            }
+        }
+        Ok(finished)
+    }
+    
     /// Process bridge messages
     async fn process_messages(
         mut message_rx: mpsc::Receiver<BridgeMessage>,
@@ -345,6 +1045,21 @@ impl Bridge {
                 BridgeMessage::ProofFailed(header_hash, error) => {
                     println!("Proof failed: {:?}, error: {}", header_hash, error);
                 }
+                BridgeMessage::DepositsIngested(deposits) => {
+                    println!("Deposits ingested: {}", deposits.len());
+                }
+                BridgeMessage::WithdrawalsCommitted(batch_index, root) => {
+                    println!("Withdrawal batch {} committed: {:?}", batch_index, root);
+                }
+                BridgeMessage::BatchCommitted(batch_number, tx_hash) => {
+                    println!("Batch {} committed in L1 transaction {:?}", batch_number, tx_hash);
+                }
+                BridgeMessage::ChallengeResolved(id, status) => {
+                    println!("Challenge {} resolved: {}", id, status.name());
+                }
+                BridgeMessage::BatchMismatch(mismatch) => {
+                    println!("Batch {} does not match local execution", mismatch.batch);
+                }
                 BridgeMessage::BridgeError(error) => {
                     println!("Bridge error: {}", error);
                     *state.write().await = BridgeState::Error(error);
@@ -354,6 +1069,31 @@ impl Bridge {
     }
 }
 
+fn proof_key(header_hash: &[u8; 32]) -> Vec<u8> {
+    [PROOF_PREFIX, header_hash.as_slice()].concat()
+}
+
+/// Move proof `header_hash` to `status` in `db`, storing `proof` alongside when given
+async fn record_proof(
+    db: Option<&Arc<RwLock<RocksStateDB>>>,
+    header_hash: [u8; 32],
+    status: TransferStatus,
+    detail: Option<String>,
+    proof: Option<&BridgeProof>,
+) -> Result<(), BridgeError> {
+    let Some(db) = db else {
+        return Ok(());
+    };
+    let mut db = db.write().await;
+    let transfer = BridgeTransfer::new(header_hash, TransferKind::Proof, Vec::new(), 0);
+    let mut entries = transfers::transitions(&db, [(transfer, status, detail)])?;
+    if let Some(proof) = proof {
+        entries.push((proof_key(&header_hash), serde_json::to_vec(proof)?));
+    }
+    db.write_batch_sync(&entries)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,10 +1110,15 @@ mod tests {
                     .unwrap()
                     .as_secs(),
                 difficulty: 1000,
+                nullifier_root: [0u8; 32],
                 nonce: 0,
             },
             transactions: vec![Transaction {
                 hash: [1u8; 32],
+                sender: Vec::new(),
+                nonce: 0,
+                gas_limit: 0,
+                data: Vec::new(),
                 inputs: vec![TxInput {
                     prev_tx_hash: [0u8; 32],
                     output_index: 0,
@@ -383,9 +1128,15 @@ mod tests {
                     amount: 100,
                     address: vec![1u8; 32],
                     commitment: [0u8; 32],
+                    ephemeral_key: None,
                 }],
                 fee: 10,
                 timestamp: 1234567890,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
             }],
             proof: block_sync::BlockProof {
                 proof_type: block_sync::ProofType::PoW,
@@ -464,4 +1215,118 @@ mod tests {
         // Stop bridge
         bridge.stop().await.unwrap();
     }
-}
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_in_flight_proofs_survive_a_restart() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let block = create_test_block();
+        let header_hash = block.header.hash().unwrap();
+        {
+            let mut bridge = Bridge::new(BridgeConfig::default()).unwrap();
+            bridge.attach_state_db(db.clone()).await.unwrap();
+            bridge.start().await.unwrap();
+            bridge.create_bridge_proof(&block).await.unwrap();
+            bridge.create_bridge_proof(&block).await.unwrap();
+        }
+        
+        // Attaching the same database again, as after a crash, reloads the proof once
+        for _ in 0..2 {
+            let mut bridge = Bridge::new(BridgeConfig::default()).unwrap();
+            bridge.attach_state_db(db.clone()).await.unwrap();
+            assert_eq!(bridge.get_pending_proofs_count().await, 1);
+        }
+        let pending = transfers::list_transfers(&*db.read().await, Some(TransferStatus::Pending), None).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].id, pending[0].kind, pending[0].history.len()), (header_hash, TransferKind::Proof, 1));
+    }
+    
+    #[tokio::test]
+    async fn test_referee_opens_times_out_and_settles_challenges() {
+        use batches::BatchSubmission;
+        use settlement::BatchFinality;
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap());
+        let bridge = Bridge::new(BridgeConfig::default()).unwrap();
+        let batch = L1Batch {
+            number: 0,
+            first_block: 1,
+            last_block: 4,
+            blocks_hash: [0u8; 32],
+            state_root: [3u8; 32],
+            priority_ops_hash: [0u8; 32],
+            priority_ops: 0,
+            transactions: 0,
+            submission: Some(BatchSubmission { tx_hash: [1u8; 32], nonce: 0, gas_price: 1, submitted_at: 0 }),
+        };
+        let key = [b"bridge/batches/batch/".as_slice(), &0u64.to_be_bytes()].concat();
+        db.write().await.write_batch_sync(&[(key, serde_json::to_vec(&batch).unwrap())]).unwrap();
+        bridge.settlement().commit_batch(0, batch.commitment()).await.unwrap();
+        
+        // The first pass opens the challenge on the settlement layer
+        challenges::open_against(&mut *db.write().await, &batch, [0u8; 32], vec![0xc1], [4u8; 32]).unwrap();
+        let timeout = Duration::from_secs(3_600);
+        assert!(Bridge::referee_pass(bridge.settlement(), &db, timeout).await.unwrap().is_empty());
+        let challenge = challenges::get_challenge(&*db.read().await, 0).unwrap().unwrap();
+        assert_eq!(challenge.status, ChallengeStatus::AwaitingBisection);
+        assert_eq!(bridge.settlement().finality(0).await, BatchFinality::Disputed);
+        
+        // A defender that does not bisect in time loses the batch
+        let finished = Bridge::referee_pass(bridge.settlement(), &db, Duration::ZERO).await.unwrap();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].status, ChallengeStatus::ChallengerWon);
+        assert!(matches!(bridge.settlement().finality(0).await, BatchFinality::Challenged(_)));
+        assert!(challenges::get_challenge(&*db.read().await, 0).unwrap().unwrap().settled);
+        
+        // The batch is no longer open to challenge
+        challenges::open_against(&mut *db.write().await, &batch, [0u8; 32], vec![0xc1], [4u8; 32]).unwrap();
+        let finished = Bridge::referee_pass(bridge.settlement(), &db, timeout).await.unwrap();
+        assert_eq!(finished[0].status, ChallengeStatus::Rejected);
+        assert!(Bridge::referee_pass(bridge.settlement(), &db, timeout).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_withdrawal_batches_are_committed_to_l1() {
+        use execution::receipt::{address_topic, withdrawal_topic};
+        use execution::{Log, ReceiptStatus, WITHDRAWAL_ADDRESS};
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut bridge = Bridge::new(BridgeConfig::default()).unwrap();
+        bridge.attach_state_db(db.clone()).await.unwrap();
+        bridge.start().await.unwrap();
+        assert!(bridge.commit_withdrawal_batch().await.unwrap().is_none());
+        
+        let receipt = Receipt {
+            tx_hash: [7u8; 32],
+            block_height: 1,
+            tx_index: 0,
+            status: ReceiptStatus::Success,
+            gas_used: 0,
+            cumulative_gas_used: 0,
+            fee: 0,
+            contract_address: None,
+            revert_reason: None,
+            logs: vec![Log {
+                address: WITHDRAWAL_ADDRESS.to_vec(),
+                topics: vec![withdrawal_topic(), address_topic(&[0xa1; 20]), address_topic(&[0x1e; 20])],
+                data: 900u64.to_be_bytes().to_vec(),
+                block_height: 1,
+                tx_hash: [7u8; 32],
+                tx_index: 0,
+                log_index: 0,
+            }],
+        };
+        assert_eq!(bridge.record_withdrawals(&[receipt]).await.unwrap(), 1);
+        
+        let batch = bridge.commit_withdrawal_batch().await.unwrap().unwrap();
+        assert!(batch.committed);
+        assert_eq!(bridge.settlement().withdrawal_root(0).await, Some(batch.root));
+        let proof = withdrawals::get_withdrawal_proof(&*db.read().await, &[7u8; 32], 0).unwrap().unwrap();
+        assert!(proof.committed);
+        assert_eq!(bridge.get_bridge_stats().await.total_withdrawal_batches, 1);
+        
+        bridge.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,276 @@
+//! The layer C0DL3 settles on, behind one interface so the rest of the node does not care
+//! which it is.
+//!
+//! Arbitrum settles optimistically: a committed batch is final once its challenge period
+//! passes without a fraud proof. zkSync checks a validity proof of each batch instead,
+//! which finalizes it at once and leaves nothing to challenge.
+
+use crate::arbitrum::{ArbitrumClient, ProofSubmission};
+use crate::error::BridgeError;
+use crate::zksync::ZkSyncClient;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::future::Future;
+use tokio::time::Duration;
+
+/// Layer the bridge settles on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettlementLayer {
+    #[default]
+    Arbitrum,
+    ZkSync,
+}
+
+impl SettlementLayer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettlementLayer::Arbitrum => "arbitrum",
+            SettlementLayer::ZkSync => "zksync",
+        }
+    }
+}
+
+/// Where a committed batch stands on the settlement layer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchFinality {
+    /// The batch was never committed
+    Unknown,
+    /// Committed but not final: inside its challenge period, or not yet proven
+    Committed,
+    /// Final; its state can no longer be reverted
+    Finalized,
+    /// A fraud-proof challenge against the batch is being played out
+    Disputed,
+    /// A fraud proof against the batch was upheld
+    Challenged(String),
+}
+
+/// A batch commitment as the settlement contract stores it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CommittedBatch {
+    pub(crate) commitment: [u8; 32],
+    pub(crate) committed_at: tokio::time::Instant,
+    pub(crate) finality: BatchFinality,
+}
+
+/// Operations every settlement layer provides
+pub trait SettlementBackend {
+    fn layer(&self) -> SettlementLayer;
+
+    /// Commit batch `number`, whose contents hash to `commitment`
+    fn commit_batch(&self, number: u64, commitment: [u8; 32]) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    /// Submit a proof of a Fuego header
+    fn submit_proof(&self, submission: ProofSubmission) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    /// Prove committed batch `number` valid
+    fn prove_batch(&self, number: u64, proof: &[u8]) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    /// Challenge committed batch `number` with a fraud proof
+    fn submit_fraud_proof(&self, number: u64, proof: &[u8]) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    /// End the challenge of batch `number`, with the reason the batch was shown wrong if it was
+    fn resolve_fraud_proof(
+        &self,
+        number: u64,
+        upheld: Option<String>,
+    ) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    /// Deposit `amount` for the C0DL3 account `recipient`, returning the deposit id
+    fn deposit(&self, recipient: &[u8], amount: u64) -> impl Future<Output = Result<[u8; 32], BridgeError>> + Send;
+
+    /// Commit the root of withdrawal batch `batch_index`, after which its withdrawals can be claimed
+    fn withdraw(&self, batch_index: u64, root: [u8; 32]) -> impl Future<Output = Result<(), BridgeError>> + Send;
+
+    /// Where committed batch `number` stands
+    fn finality(&self, number: u64) -> impl Future<Output = BatchFinality> + Send;
+}
+
+/// Id of the `nonce`-th deposit made through a backend
+pub(crate) fn deposit_id(layer: SettlementLayer, nonce: u64, recipient: &[u8], amount: u64) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(layer.name());
+    hasher.update(nonce.to_be_bytes());
+    hasher.update(recipient);
+    hasher.update(amount.to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// Record `commitment` as batch `number`, refusing to replace a different one
+pub(crate) fn record_commitment(
+    batches: &mut std::collections::HashMap<u64, CommittedBatch>,
+    number: u64,
+    commitment: [u8; 32],
+) -> Result<(), BridgeError> {
+    match batches.get(&number) {
+        Some(batch) if batch.commitment != commitment => {
+            Err(BridgeError::SettlementError(format!("Batch {} already has a different commitment", number)))
+        }
+        Some(_) => Ok(()),
+        None => {
+            batches.insert(
+                number,
+                CommittedBatch {
+                    commitment,
+                    committed_at: tokio::time::Instant::now(),
+                    finality: BatchFinality::Committed,
+                },
+            );
+            Ok(())
+        }
+    }
+}
+
+/// The backend a bridge was configured with
+pub enum Settlement {
+    Arbitrum(ArbitrumClient),
+    ZkSync(ZkSyncClient),
+}
+
+impl Settlement {
+    /// Connect to `layer` at `rpc_url`; `challenge_period` only applies to optimistic layers
+    pub fn new(
+        layer: SettlementLayer,
+        rpc_url: String,
+        contract_address: String,
+        challenge_period: Duration,
+    ) -> Result<Self, BridgeError> {
+        Ok(match layer {
+            SettlementLayer::Arbitrum => {
+                let client = ArbitrumClient::new(rpc_url, contract_address)?;
+                Settlement::Arbitrum(client.with_challenge_period(challenge_period))
+            }
+            SettlementLayer::ZkSync => Settlement::ZkSync(ZkSyncClient::new(rpc_url, contract_address)?),
+        })
+    }
+
+    /// Get the withdrawal root committed for `batch_index`
+    pub async fn withdrawal_root(&self, batch_index: u64) -> Option<[u8; 32]> {
+        match self {
+            Settlement::Arbitrum(client) => client.get_withdrawal_root(batch_index).await,
+            Settlement::ZkSync(client) => client.get_withdrawal_root(batch_index).await,
+        }
+    }
+}
+
+impl SettlementBackend for Settlement {
+    fn layer(&self) -> SettlementLayer {
+        match self {
+            Settlement::Arbitrum(client) => client.layer(),
+            Settlement::ZkSync(client) => client.layer(),
+        }
+    }
+
+    async fn commit_batch(&self, number: u64, commitment: [u8; 32]) -> Result<(), BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.commit_batch(number, commitment).await,
+            Settlement::ZkSync(client) => client.commit_batch(number, commitment).await,
+        }
+    }
+
+    async fn submit_proof(&self, submission: ProofSubmission) -> Result<(), BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.submit_proof(submission).await,
+            Settlement::ZkSync(client) => client.submit_proof(submission).await,
+        }
+    }
+
+    async fn prove_batch(&self, number: u64, proof: &[u8]) -> Result<(), BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.prove_batch(number, proof).await,
+            Settlement::ZkSync(client) => client.prove_batch(number, proof).await,
+        }
+    }
+
+    async fn submit_fraud_proof(&self, number: u64, proof: &[u8]) -> Result<(), BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.submit_fraud_proof(number, proof).await,
+            Settlement::ZkSync(client) => client.submit_fraud_proof(number, proof).await,
+        }
+    }
+
+    async fn resolve_fraud_proof(&self, number: u64, upheld: Option<String>) -> Result<(), BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.resolve_fraud_proof(number, upheld).await,
+            Settlement::ZkSync(client) => client.resolve_fraud_proof(number, upheld).await,
+        }
+    }
+
+    async fn deposit(&self, recipient: &[u8], amount: u64) -> Result<[u8; 32], BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.deposit(recipient, amount).await,
+            Settlement::ZkSync(client) => client.deposit(recipient, amount).await,
+        }
+    }
+
+    async fn withdraw(&self, batch_index: u64, root: [u8; 32]) -> Result<(), BridgeError> {
+        match self {
+            Settlement::Arbitrum(client) => client.withdraw(batch_index, root).await,
+            Settlement::ZkSync(client) => client.withdraw(batch_index, root).await,
+        }
+    }
+
+    async fn finality(&self, number: u64) -> BatchFinality {
+        match self {
+            Settlement::Arbitrum(client) => client.finality(number).await,
+            Settlement::ZkSync(client) => client.finality(number).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(layer: SettlementLayer, challenge_period: Duration) -> Settlement {
+        let contract = "0x1234567890123456789012345678901234567890".to_string();
+        Settlement::new(layer, "http://localhost:8545".to_string(), contract, challenge_period).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batches_finalize_by_challenge_period_or_validity_proof() {
+        // Optimistic batches are final once their challenge period has passed
+        let arbitrum = backend(SettlementLayer::Arbitrum, Duration::ZERO);
+        assert_eq!(arbitrum.finality(0).await, BatchFinality::Unknown);
+        arbitrum.commit_batch(0, [1; 32]).await.unwrap();
+        assert!(arbitrum.commit_batch(0, [2; 32]).await.is_err());
+        assert_eq!(arbitrum.finality(0).await, BatchFinality::Finalized);
+        assert!(arbitrum.submit_fraud_proof(0, b"fraud").await.is_err());
+        assert!(arbitrum.prove_batch(0, b"proof").await.is_err());
+
+        // and can be challenged until then
+        let arbitrum = backend(SettlementLayer::Arbitrum, Duration::from_secs(3_600));
+        arbitrum.commit_batch(0, [1; 32]).await.unwrap();
+        assert_eq!(arbitrum.finality(0).await, BatchFinality::Committed);
+        arbitrum.submit_fraud_proof(0, b"fraud").await.unwrap();
+        assert_eq!(arbitrum.finality(0).await, BatchFinality::Disputed);
+        assert!(arbitrum.submit_fraud_proof(0, b"fraud").await.is_err());
+        arbitrum.resolve_fraud_proof(0, None).await.unwrap();
+        assert_eq!(arbitrum.finality(0).await, BatchFinality::Committed);
+        arbitrum.submit_fraud_proof(0, b"fraud").await.unwrap();
+        arbitrum.resolve_fraud_proof(0, Some("bad block".to_string())).await.unwrap();
+        assert_eq!(arbitrum.finality(0).await, BatchFinality::Challenged("bad block".to_string()));
+
+        // Validity-proven batches are final once proven and cannot be challenged
+        let zksync = backend(SettlementLayer::ZkSync, Duration::from_secs(3_600));
+        assert_eq!(zksync.layer(), SettlementLayer::ZkSync);
+        assert!(zksync.prove_batch(0, b"proof").await.is_err());
+        zksync.commit_batch(0, [1; 32]).await.unwrap();
+        assert_eq!(zksync.finality(0).await, BatchFinality::Committed);
+        assert!(zksync.prove_batch(0, b"").await.is_err());
+        zksync.prove_batch(0, b"proof").await.unwrap();
+        assert_eq!(zksync.finality(0).await, BatchFinality::Finalized);
+        assert!(zksync.submit_fraud_proof(0, b"fraud").await.is_err());
+        assert!(zksync.resolve_fraud_proof(0, None).await.is_err());
+
+        // Deposits and withdrawal roots behave the same on both
+        for settlement in [arbitrum, zksync] {
+            let first = settlement.deposit(&[0xa1; 20], 100).await.unwrap();
+            assert_ne!(settlement.deposit(&[0xa1; 20], 100).await.unwrap(), first);
+            settlement.withdraw(3, [9; 32]).await.unwrap();
+            assert!(settlement.withdraw(3, [8; 32]).await.is_err());
+            assert_eq!(settlement.withdrawal_root(3).await, Some([9; 32]));
+        }
+    }
+}
@@ -0,0 +1,686 @@
+use crate::batches::CommitmentTransport;
+use crate::deposits::{decode_hex, parse_quantity, L1Client};
+use crate::error::BridgeError;
+use k256::ecdsa::SigningKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use state_db::RocksStateDB;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+
+const RECORD_PREFIX: &[u8] = b"bridge/proof_submissions/record/";
+const STATS_KEY: &[u8] = b"bridge/proof_submissions/stats";
+const NONCE_KEY: &[u8] = b"bridge/proof_submissions/next_nonce";
+const IN_FLIGHT_KEY: &[u8] = b"bridge/proof_submissions/in_flight";
+
+/// In-flight submissions by nonce, with the calldata replacements resend
+type InFlight = BTreeMap<u64, (SubmissionRecord, Vec<u8>)>;
+
+/// Proof submission configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofSubmissionConfig {
+    /// EIP-155 chain id of the L1
+    pub chain_id: u64,
+    /// Bridge contract verifying proofs
+    pub contract_address: String,
+    pub gas_limit: u64,
+    /// Highest gas price, in wei, a submission is bumped to
+    pub max_gas_price: u64,
+    /// Percentage a stuck transaction's gas price is raised by when it is replaced
+    pub fee_bump_percent: u64,
+    /// How long a transaction may stay unmined before it is replaced
+    pub resubmit_after: Duration,
+    /// Blocks on top of the inclusion block before a submission counts as confirmed
+    pub confirmations: u64,
+}
+
+impl Default for ProofSubmissionConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: 42161,
+            contract_address: "0x0000000000000000000000000000000000000000".to_string(),
+            gas_limit: 500_000,
+            max_gas_price: 100_000_000_000,
+            fee_bump_percent: 12,
+            resubmit_after: Duration::from_secs(60),
+            confirmations: 3,
+        }
+    }
+}
+
+/// Legacy (EIP-155) Ethereum transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LegacyTransaction {
+    pub nonce: u64,
+    pub gas_price: u64,
+    pub gas_limit: u64,
+    pub to: [u8; 20],
+    pub value: u64,
+    pub data: Vec<u8>,
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    match bytes.len() {
+        1 if bytes[0] < 0x80 => bytes.to_vec(),
+        len => [rlp_length(len, 0x80), bytes.to_vec()].concat(),
+    }
+}
+
+fn rlp_uint(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    rlp_bytes(&bytes[start..])
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [rlp_length(payload.len(), 0xc0), payload].concat()
+}
+
+fn rlp_length(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let bytes = (len as u64).to_be_bytes();
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    [vec![offset + 55 + (bytes.len() - start) as u8], bytes[start..].to_vec()].concat()
+}
+
+impl LegacyTransaction {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.nonce),
+            rlp_uint(self.gas_price),
+            rlp_uint(self.gas_limit),
+            rlp_bytes(&self.to),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+        ]
+    }
+
+    /// Hash signed under EIP-155 replay protection
+    pub fn signing_hash(&self, chain_id: u64) -> [u8; 32] {
+        let mut fields = self.fields();
+        fields.extend([rlp_uint(chain_id), rlp_uint(0), rlp_uint(0)]);
+        Keccak256::digest(rlp_list(&fields)).into()
+    }
+}
+
+/// Signs L1 transactions with a secp256k1 key
+pub struct L1Signer {
+    key: SigningKey,
+    chain_id: u64,
+}
+
+impl L1Signer {
+    /// Create a signer from a hex-encoded 32-byte private key
+    pub fn from_hex(secret: &str, chain_id: u64) -> Result<Self, BridgeError> {
+        let bytes = decode_hex(secret)?;
+        let key = SigningKey::from_slice(&bytes).map_err(|_| BridgeError::ConfigError("Invalid L1 signing key".to_string()))?;
+        Ok(Self { key, chain_id })
+    }
+
+    /// Ethereum address of the key
+    pub fn address(&self) -> [u8; 20] {
+        let public_key = self.key.verifying_key().to_encoded_point(false);
+        let hash = Keccak256::digest(&public_key.as_bytes()[1..]);
+        hash[12..].try_into().unwrap()
+    }
+
+    /// Hex address with the `0x` prefix, as L1 RPCs expect
+    pub fn address_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.address()))
+    }
+
+    /// Sign `transaction`, returning the raw encoding and its transaction hash
+    pub fn sign(&self, transaction: &LegacyTransaction) -> Result<(Vec<u8>, [u8; 32]), BridgeError> {
+        let (signature, recovery_id) = self
+            .key
+            .sign_prehash_recoverable(&transaction.signing_hash(self.chain_id))
+            .map_err(|e| BridgeError::ProofSubmissionError(e.to_string()))?;
+        let v = self.chain_id * 2 + 35 + recovery_id.to_byte() as u64;
+        let (r, s) = signature.split_bytes();
+        let strip = |bytes: &[u8]| bytes[bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len())..].to_vec();
+
+        let mut fields = transaction.fields();
+        fields.extend([rlp_uint(v), rlp_bytes(&strip(&r)), rlp_bytes(&strip(&s))]);
+        let raw = rlp_list(&fields);
+        let hash = Keccak256::digest(&raw).into();
+        Ok((raw, hash))
+    }
+}
+
+/// Inclusion of an L1 transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L1Receipt {
+    pub block_number: u64,
+    pub gas_used: u64,
+    pub effective_gas_price: u64,
+    pub success: bool,
+}
+
+/// L1 access needed to send signed transactions and follow them to confirmation
+pub trait SignedTransport: CommitmentTransport {
+    /// Latest L1 block number
+    fn latest_block(&self) -> impl Future<Output = Result<u64, BridgeError>> + Send;
+
+    /// Broadcast a signed transaction
+    fn send_raw_transaction(&self, raw: &[u8]) -> impl Future<Output = Result<[u8; 32], BridgeError>> + Send;
+
+    /// Receipt of `tx_hash`, once it is mined
+    fn transaction_receipt(
+        &self,
+        tx_hash: &[u8; 32],
+    ) -> impl Future<Output = Result<Option<L1Receipt>, BridgeError>> + Send;
+}
+
+impl SignedTransport for L1Client {
+    async fn latest_block(&self) -> Result<u64, BridgeError> {
+        self.block_number().await
+    }
+
+    async fn send_raw_transaction(&self, raw: &[u8]) -> Result<[u8; 32], BridgeError> {
+        let result = self
+            .call("eth_sendRawTransaction", json!([format!("0x{}", hex::encode(raw))]))
+            .await?;
+        decode_hex(result.as_str().unwrap_or_default())?
+            .try_into()
+            .map_err(|_| BridgeError::ArbitrumError("eth_sendRawTransaction: invalid hash".to_string()))
+    }
+
+    async fn transaction_receipt(&self, tx_hash: &[u8; 32]) -> Result<Option<L1Receipt>, BridgeError> {
+        let receipt = self
+            .call("eth_getTransactionReceipt", json!([format!("0x{}", hex::encode(tx_hash))]))
+            .await?;
+        if receipt.is_null() {
+            return Ok(None);
+        }
+        let quantity = |name: &str| parse_quantity(receipt.get(name).and_then(Value::as_str).unwrap_or_default());
+        Ok(Some(L1Receipt {
+            block_number: quantity("blockNumber")?,
+            gas_used: quantity("gasUsed")?,
+            effective_gas_price: quantity("effectiveGasPrice")?,
+            success: quantity("status")? == 1,
+        }))
+    }
+}
+
+/// One broadcast of a submission; replacements reuse the nonce at a higher price
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionAttempt {
+    pub tx_hash: [u8; 32],
+    pub gas_price: u64,
+    pub sent_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubmissionState {
+    /// Broadcast and waiting for inclusion or confirmations
+    InFlight,
+    Confirmed { block_number: u64 },
+    /// Mined but reverted by the contract
+    Reverted { block_number: u64 },
+}
+
+/// History and cost of one proof submission
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionRecord {
+    pub proof_id: [u8; 32],
+    pub nonce: u64,
+    pub attempts: Vec<SubmissionAttempt>,
+    pub state: SubmissionState,
+    /// Transaction that was mined, if any
+    pub included_tx: Option<[u8; 32]>,
+    pub gas_used: u64,
+    /// Wei paid for the mined transaction
+    pub cost: u128,
+}
+
+/// Aggregate submission costs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionCostStats {
+    pub submitted: u64,
+    pub confirmed: u64,
+    pub reverted: u64,
+    /// Stuck transactions replaced at a higher gas price
+    pub replacements: u64,
+    pub total_gas_used: u64,
+    pub total_cost: u128,
+}
+
+impl SubmissionCostStats {
+    /// Mean gas of a mined proof submission
+    pub fn average_gas_used(&self) -> u64 {
+        match self.confirmed + self.reverted {
+            0 => 0,
+            mined => self.total_gas_used / mined,
+        }
+    }
+}
+
+/// Change to a submission noticed while polling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmissionEvent {
+    Confirmed([u8; 32]),
+    Reverted([u8; 32]),
+    Replaced([u8; 32], u64),
+}
+
+fn record_key(proof_id: &[u8; 32]) -> Vec<u8> {
+    [RECORD_PREFIX, proof_id.as_slice()].concat()
+}
+
+/// Get the submission record of `proof_id`
+pub fn get_submission(state: &RocksStateDB, proof_id: &[u8; 32]) -> Result<Option<SubmissionRecord>, BridgeError> {
+    match state.get_sync(&record_key(proof_id))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Get the aggregate proof submission costs
+pub fn get_cost_stats(state: &RocksStateDB) -> Result<SubmissionCostStats, BridgeError> {
+    match state.get_sync(STATS_KEY)? {
+        Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        None => Ok(SubmissionCostStats::default()),
+    }
+}
+
+/// Calldata of `submitProof(bytes32 proofId, bytes proof)`
+pub fn submit_proof_calldata(proof_id: &[u8; 32], proof: &[u8]) -> Vec<u8> {
+    let selector = Keccak256::digest(b"submitProof(bytes32,bytes)");
+    let mut data = selector[..4].to_vec();
+    data.extend_from_slice(proof_id);
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&64u64.to_be_bytes());
+    data.extend_from_slice(&word);
+    word[24..].copy_from_slice(&(proof.len() as u64).to_be_bytes());
+    data.extend_from_slice(&word);
+    data.extend_from_slice(proof);
+    data.resize(data.len() + (32 - proof.len() % 32) % 32, 0);
+    data
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Signs proof submissions, follows them to confirmation and replaces stuck ones.
+///
+/// Nonces are assigned locally in submission order, starting from the L1 pending nonce,
+/// and every record is persisted so a restart keeps following in-flight transactions.
+pub struct ProofSubmitter<T: SignedTransport> {
+    config: ProofSubmissionConfig,
+    signer: L1Signer,
+    transport: T,
+    db: Arc<RwLock<RocksStateDB>>,
+    to: [u8; 20],
+    in_flight: InFlight,
+    next_nonce: Option<u64>,
+    stats: SubmissionCostStats,
+}
+
+impl<T: SignedTransport> ProofSubmitter<T> {
+    pub async fn with_state_db(
+        config: ProofSubmissionConfig,
+        signer: L1Signer,
+        transport: T,
+        db: Arc<RwLock<RocksStateDB>>,
+    ) -> Result<Self, BridgeError> {
+        let to = decode_hex(&config.contract_address)?
+            .try_into()
+            .map_err(|_| BridgeError::ConfigError("Contract address must be 20 bytes".to_string()))?;
+        if config.fee_bump_percent < 10 {
+            return Err(BridgeError::ConfigError(
+                "L1 nodes only accept replacements paying at least 10% more".to_string(),
+            ));
+        }
+        let (stats, next_nonce, in_flight) = {
+            let store = db.read().await;
+            let next_nonce = match store.get_sync(NONCE_KEY)? {
+                Some(bytes) => Some(serde_json::from_slice(&bytes)?),
+                None => None,
+            };
+            let in_flight = match store.get_sync(IN_FLIGHT_KEY)? {
+                Some(bytes) => serde_json::from_slice(&bytes)?,
+                None => InFlight::new(),
+            };
+            (get_cost_stats(&store)?, next_nonce, in_flight)
+        };
+        Ok(Self {
+            config,
+            signer,
+            transport,
+            db,
+            to,
+            in_flight,
+            next_nonce,
+            stats,
+        })
+    }
+
+    /// Sign and broadcast a proof submission, returning its transaction hash
+    #[tracing::instrument(name = "bridge.send_proof", skip_all, fields(proof = %hex::encode(proof_id)))]
+    pub async fn submit(&mut self, proof_id: [u8; 32], proof: &[u8]) -> Result<[u8; 32], BridgeError> {
+        let gas_price = self.transport.gas_price().await?.min(self.config.max_gas_price);
+        let nonce = match self.next_nonce {
+            Some(nonce) => nonce,
+            None => self.transport.pending_nonce(&self.signer.address_hex()).await?,
+        };
+        let data = submit_proof_calldata(&proof_id, proof);
+        let tx_hash = match self.broadcast(nonce, gas_price, &data).await {
+            Ok(tx_hash) => tx_hash,
+            Err(e) => {
+                // Resynchronise with the L1 mempool on the next submission
+                self.next_nonce = None;
+                return Err(e);
+            }
+        };
+
+        let record = SubmissionRecord {
+            proof_id,
+            nonce,
+            attempts: vec![SubmissionAttempt {
+                tx_hash,
+                gas_price,
+                sent_at: now(),
+            }],
+            state: SubmissionState::InFlight,
+            included_tx: None,
+            gas_used: 0,
+            cost: 0,
+        };
+        self.next_nonce = Some(nonce + 1);
+        self.stats.submitted += 1;
+        self.in_flight.insert(nonce, (record.clone(), data));
+        self.save(&[record]).await?;
+        Ok(tx_hash)
+    }
+
+    async fn broadcast(&self, nonce: u64, gas_price: u64, data: &[u8]) -> Result<[u8; 32], BridgeError> {
+        let transaction = LegacyTransaction {
+            nonce,
+            gas_price,
+            gas_limit: self.config.gas_limit,
+            to: self.to,
+            value: 0,
+            data: data.to_vec(),
+        };
+        let (raw, tx_hash) = self.signer.sign(&transaction)?;
+        self.transport.send_raw_transaction(&raw).await?;
+        Ok(tx_hash)
+    }
+
+    /// Check every in-flight submission, confirming mined ones and replacing stuck ones
+    pub async fn poll(&mut self) -> Result<Vec<SubmissionEvent>, BridgeError> {
+        let head = self.transport.latest_block().await?;
+        let mut events = Vec::new();
+        let mut changed = Vec::new();
+        let nonces: Vec<u64> = self.in_flight.keys().copied().collect();
+        for nonce in nonces {
+            let (mut record, data) = self.in_flight.remove(&nonce).unwrap();
+            let result = self.check(head, &mut record, &data).await;
+            let settled = record.state != SubmissionState::InFlight;
+            match result {
+                Ok(Some(event)) => {
+                    events.push(event);
+                    changed.push(record.clone());
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    self.in_flight.insert(nonce, (record, data));
+                    self.save(&changed).await?;
+                    return Err(e);
+                }
+            }
+            if !settled {
+                self.in_flight.insert(nonce, (record, data));
+            }
+        }
+        if !changed.is_empty() {
+            self.save(&changed).await?;
+        }
+        Ok(events)
+    }
+
+    /// Settle `record` once its transaction is confirmed, or replace it when stuck
+    async fn check(&mut self, head: u64, record: &mut SubmissionRecord, data: &[u8]) -> Result<Option<SubmissionEvent>, BridgeError> {
+        // Any of the attempts may be the one that got mined
+        let mut mined = None;
+        for attempt in &record.attempts {
+            if let Some(receipt) = self.transport.transaction_receipt(&attempt.tx_hash).await? {
+                mined = Some((attempt.tx_hash, receipt));
+                break;
+            }
+        }
+        if let Some((tx_hash, receipt)) = mined {
+            if head.saturating_sub(receipt.block_number) + 1 < self.config.confirmations {
+                return Ok(None);
+            }
+            record.included_tx = Some(tx_hash);
+            record.gas_used = receipt.gas_used;
+            record.cost = receipt.gas_used as u128 * receipt.effective_gas_price as u128;
+            self.stats.total_gas_used += receipt.gas_used;
+            self.stats.total_cost += record.cost;
+            return Ok(Some(if receipt.success {
+                record.state = SubmissionState::Confirmed {
+                    block_number: receipt.block_number,
+                };
+                self.stats.confirmed += 1;
+                SubmissionEvent::Confirmed(record.proof_id)
+            } else {
+                record.state = SubmissionState::Reverted {
+                    block_number: receipt.block_number,
+                };
+                self.stats.reverted += 1;
+                SubmissionEvent::Reverted(record.proof_id)
+            }));
+        }
+
+        let last = record.attempts.last().unwrap().clone();
+        if now().saturating_sub(last.sent_at) < self.config.resubmit_after.as_secs() {
+            return Ok(None);
+        }
+        let bumped = (last.gas_price as u128 * (100 + self.config.fee_bump_percent) as u128 / 100)
+            .min(self.config.max_gas_price as u128) as u64;
+        if bumped <= last.gas_price {
+            // Already at the cap; wait for the L1 to clear
+            return Ok(None);
+        }
+        let gas_price = bumped.max(self.transport.gas_price().await?.min(self.config.max_gas_price));
+        let tx_hash = self.broadcast(record.nonce, gas_price, data).await?;
+        record.attempts.push(SubmissionAttempt {
+            tx_hash,
+            gas_price,
+            sent_at: now(),
+        });
+        self.stats.replacements += 1;
+        Ok(Some(SubmissionEvent::Replaced(record.proof_id, gas_price)))
+    }
+
+    /// Persist `records` with the stats, nonce and in-flight set in one batch
+    async fn save(&self, records: &[SubmissionRecord]) -> Result<(), BridgeError> {
+        let mut entries = Vec::with_capacity(records.len() + 3);
+        for record in records {
+            entries.push((record_key(&record.proof_id), serde_json::to_vec(record)?));
+        }
+        entries.push((STATS_KEY.to_vec(), serde_json::to_vec(&self.stats)?));
+        entries.push((IN_FLIGHT_KEY.to_vec(), serde_json::to_vec(&self.in_flight)?));
+        if let Some(next_nonce) = self.next_nonce {
+            entries.push((NONCE_KEY.to_vec(), serde_json::to_vec(&next_nonce)?));
+        }
+        self.db.write().await.write_batch_sync(&entries)?;
+        Ok(())
+    }
+
+    /// Number of submissions waiting for confirmation
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Get aggregate submission costs
+    pub fn get_stats(&self) -> SubmissionCostStats {
+        self.stats.clone()
+    }
+
+    /// Get the signing account
+    pub fn signer(&self) -> &L1Signer {
+        &self.signer
+    }
+
+    /// Get the transport
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batches::CommitmentTransaction;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_eip155_signing_vector() {
+        // Example transaction from EIP-155
+        let signer = L1Signer::from_hex(&"46".repeat(32), 1).unwrap();
+        let transaction = LegacyTransaction {
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: [0x35; 20],
+            value: 1_000_000_000_000_000_000,
+            data: Vec::new(),
+        };
+        assert_eq!(
+            hex::encode(transaction.signing_hash(1)),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        let (raw, _) = signer.sign(&transaction).unwrap();
+        assert_eq!(
+            hex::encode(raw),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a0\
+             28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb7033\
+             04b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(signer.address_hex(), "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+    }
+
+    #[derive(Default)]
+    struct MockL1 {
+        gas_price: Mutex<u64>,
+        head: Mutex<u64>,
+        sent: Mutex<Vec<Vec<u8>>>,
+        mined: Mutex<HashMap<[u8; 32], L1Receipt>>,
+    }
+
+    impl CommitmentTransport for MockL1 {
+        async fn pending_nonce(&self, _address: &str) -> Result<u64, BridgeError> {
+            Ok(4)
+        }
+
+        async fn gas_price(&self) -> Result<u64, BridgeError> {
+            Ok(*self.gas_price.lock().unwrap())
+        }
+
+        async fn send_transaction(&self, _transaction: &CommitmentTransaction) -> Result<[u8; 32], BridgeError> {
+            Err(BridgeError::Unknown("unsigned".to_string()))
+        }
+    }
+
+    impl SignedTransport for MockL1 {
+        async fn latest_block(&self) -> Result<u64, BridgeError> {
+            Ok(*self.head.lock().unwrap())
+        }
+
+        async fn send_raw_transaction(&self, raw: &[u8]) -> Result<[u8; 32], BridgeError> {
+            self.sent.lock().unwrap().push(raw.to_vec());
+            Ok(Keccak256::digest(raw).into())
+        }
+
+        async fn transaction_receipt(&self, tx_hash: &[u8; 32]) -> Result<Option<L1Receipt>, BridgeError> {
+            Ok(self.mined.lock().unwrap().get(tx_hash).cloned())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submissions_are_replaced_then_confirmed_with_costs() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let config = ProofSubmissionConfig {
+            contract_address: format!("0x{}", "ab".repeat(20)),
+            max_gas_price: 1_200,
+            resubmit_after: Duration::ZERO,
+            confirmations: 2,
+            ..Default::default()
+        };
+        let l1 = MockL1::default();
+        *l1.gas_price.lock().unwrap() = 1_000;
+        let signer = L1Signer::from_hex(&"46".repeat(32), 42161).unwrap();
+        let mut submitter = ProofSubmitter::with_state_db(config.clone(), signer, l1, db.clone()).await.unwrap();
+
+        let first = submitter.submit([1u8; 32], &[0xaa; 40]).await.unwrap();
+        submitter.submit([2u8; 32], &[0xbb; 8]).await.unwrap();
+        let record = get_submission(&*db.read().await, &[2u8; 32]).unwrap().unwrap();
+        assert_eq!((record.nonce, record.state.clone()), (5, SubmissionState::InFlight));
+
+        // Nothing is mined, so both are replaced at 12% more until the gas price cap
+        let events = submitter.poll().await.unwrap();
+        assert_eq!(events[0], SubmissionEvent::Replaced([1u8; 32], 1_120));
+        let events = submitter.poll().await.unwrap();
+        assert_eq!(events[0], SubmissionEvent::Replaced([1u8; 32], 1_200));
+        assert!(submitter.poll().await.unwrap().is_empty());
+        assert_eq!(submitter.get_stats().replacements, 4);
+
+        // The original transaction of the first proof is mined and then confirmed
+        let receipt = L1Receipt {
+            block_number: 10,
+            gas_used: 60_000,
+            effective_gas_price: 1_000,
+            success: true,
+        };
+        submitter.transport().mined.lock().unwrap().insert(first, receipt);
+        *submitter.transport().head.lock().unwrap() = 10;
+        assert!(submitter.poll().await.unwrap().is_empty());
+        *submitter.transport().head.lock().unwrap() = 11;
+        assert_eq!(submitter.poll().await.unwrap(), vec![SubmissionEvent::Confirmed([1u8; 32])]);
+        assert_eq!(submitter.in_flight(), 1);
+
+        let store = db.read().await;
+        let record = get_submission(&store, &[1u8; 32]).unwrap().unwrap();
+        assert_eq!(record.state, SubmissionState::Confirmed { block_number: 10 });
+        assert_eq!((record.included_tx, record.cost), (Some(first), 60_000_000));
+        assert_eq!(record.attempts.len(), 3);
+        let stats = get_cost_stats(&store).unwrap();
+        assert_eq!((stats.submitted, stats.confirmed, stats.average_gas_used()), (2, 1, 60_000));
+        drop(store);
+
+        // A restarted submitter keeps counting nonces from the last submission
+        let signer = L1Signer::from_hex(&"46".repeat(32), 42161).unwrap();
+        let mut restarted = ProofSubmitter::with_state_db(config, signer, MockL1::default(), db.clone()).await.unwrap();
+        assert_eq!(restarted.in_flight(), 1);
+        restarted.submit([3u8; 32], &[]).await.unwrap();
+        assert_eq!(restarted.get_stats().submitted, 3);
+        let record = get_submission(&*db.read().await, &[3u8; 32]).unwrap().unwrap();
+        assert_eq!(record.nonce, 6);
+    }
+
+    #[test]
+    fn test_submit_proof_calldata_layout() {
+        let data = submit_proof_calldata(&[7u8; 32], &[0xcc; 33]);
+        assert_eq!(data.len(), 4 + 32 * 3 + 64);
+        assert_eq!(data[4 + 63], 64);
+        assert_eq!(data[4 + 95], 33);
+        assert_eq!(&data[4 + 96..4 + 129], &[0xcc; 33]);
+        assert!(data[4 + 129..].iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,284 @@
+//! Lifecycle of every transfer through the bridge.
+//!
+//! Each deposit, burn mint, withdrawal and proof has a record whose status changes are
+//! written in the same batch as the bridge state that causes them, so a restart finds
+//! exactly what was in flight. The few steps that finish outside the bridge, such as a
+//! block executing a mint, are picked up by `reconcile`, which only ever moves a record
+//! forward and can run any number of times.
+
+use crate::deposits;
+use crate::error::BridgeError;
+use execution::{MINT_ADDRESS, XFG_MINT_ADDRESS};
+use serde::{Deserialize, Serialize};
+use state_db::supply::MintSource;
+use state_db::RocksStateDB;
+
+/// Keys and values to write in one batch
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+const TRANSFER_PREFIX: &[u8] = b"bridge/transfers/";
+/// Ids of the transfers not yet in a final status
+const OPEN_KEY: &[u8] = b"bridge/open_transfers";
+
+/// What moves through the bridge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    /// HEAT minted for an L1 deposit
+    Deposit,
+    /// HEAT minted for XFG burned on Fuego
+    XfgBurn,
+    /// HEAT burned on C0DL3 to be claimed on L1
+    Withdrawal,
+    /// A Fuego header proof sent to L1
+    Proof,
+}
+
+/// Where a transfer is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    /// Waiting for its mint to execute, its batch to be sealed or its proof to be sent
+    Pending,
+    /// Handed to L1: a withdrawal sealed into a batch or a proof sent
+    Submitted,
+    /// Its mint executed, its withdrawal root was committed or its proof confirmed
+    Completed,
+    /// Its proof was rejected or reverted
+    Failed,
+    /// Its deposit was orphaned by an L1 reorg before the mint executed
+    RolledBack,
+}
+
+impl TransferStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransferStatus::Pending => "pending",
+            TransferStatus::Submitted => "submitted",
+            TransferStatus::Completed => "completed",
+            TransferStatus::Failed => "failed",
+            TransferStatus::RolledBack => "rolled_back",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Pending, Self::Submitted, Self::Completed, Self::Failed, Self::RolledBack]
+            .into_iter()
+            .find(|status| status.name() == name)
+    }
+
+    /// Whether nothing more will happen to the transfer. A rolled-back deposit is only
+    /// recorded again if the new L1 chain includes it.
+    pub fn is_final(&self) -> bool {
+        !matches!(self, TransferStatus::Pending | TransferStatus::Submitted)
+    }
+}
+
+/// One status change of a transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferTransition {
+    pub status: TransferStatus,
+    /// Unix time of the change
+    pub at: u64,
+    pub detail: Option<String>,
+}
+
+/// A transfer and every status it went through
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BridgeTransfer {
+    /// Mint transaction hash, withdrawal id or proven header hash
+    pub id: [u8; 32],
+    pub kind: TransferKind,
+    pub status: TransferStatus,
+    /// C0DL3 account credited by a mint or debited by a withdrawal; empty for proofs
+    pub address: Vec<u8>,
+    pub amount: u64,
+    /// Nonce of the mint transaction, for deposits and burns
+    pub mint_nonce: Option<u64>,
+    pub history: Vec<TransferTransition>,
+}
+
+impl BridgeTransfer {
+    /// A transfer not recorded yet; `transitions` gives it its first status
+    pub fn new(id: [u8; 32], kind: TransferKind, address: Vec<u8>, amount: u64) -> Self {
+        Self {
+            id,
+            kind,
+            status: TransferStatus::Pending,
+            address,
+            amount,
+            mint_nonce: None,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn with_mint_nonce(mut self, nonce: u64) -> Self {
+        self.mint_nonce = Some(nonce);
+        self
+    }
+
+    /// Unix time the transfer was first recorded
+    pub fn created_at(&self) -> u64 {
+        self.history.first().map_or(0, |transition| transition.at)
+    }
+}
+
+fn transfer_key(id: &[u8; 32]) -> Vec<u8> {
+    [TRANSFER_PREFIX, id.as_slice()].concat()
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Get the transfer with `id`
+pub fn get_transfer(state: &RocksStateDB, id: &[u8; 32]) -> Result<Option<BridgeTransfer>, BridgeError> {
+    match state.get_sync(&transfer_key(id))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Get the transfers in `status` and involving `address`, either filter being optional,
+/// oldest first
+pub fn list_transfers(
+    state: &RocksStateDB,
+    status: Option<TransferStatus>,
+    address: Option<&[u8]>,
+) -> Result<Vec<BridgeTransfer>, BridgeError> {
+    let mut transfers = Vec::new();
+    for (_, bytes) in state.scan_prefix_sync(TRANSFER_PREFIX)? {
+        let transfer: BridgeTransfer = serde_json::from_slice(&bytes)?;
+        if status.is_none_or(|status| transfer.status == status)
+            && address.is_none_or(|address| transfer.address == address)
+        {
+            transfers.push(transfer);
+        }
+    }
+    transfers.sort_by_key(|transfer| (transfer.created_at(), transfer.id));
+    Ok(transfers)
+}
+
+fn open_transfers(state: &RocksStateDB) -> Result<Vec<[u8; 32]>, BridgeError> {
+    match state.get_sync(OPEN_KEY)? {
+        Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Writes moving each transfer to its status, for the caller to add to the batch holding
+/// the change behind them. A transfer already in its status is left as it is, so
+/// replaying a step after a crash records nothing twice.
+pub(crate) fn transitions(
+    state: &RocksStateDB,
+    changes: impl IntoIterator<Item = (BridgeTransfer, TransferStatus, Option<String>)>,
+) -> Result<Entries, BridgeError> {
+    let mut open = open_transfers(state)?;
+    let mut entries = Vec::new();
+    let at = now();
+    for (transfer, status, detail) in changes {
+        let mut record = match get_transfer(state, &transfer.id)? {
+            Some(record) if record.status == status => continue,
+            // A deposit included again after a reorg gets a new mint nonce
+            Some(record) => BridgeTransfer {
+                mint_nonce: transfer.mint_nonce.or(record.mint_nonce),
+                ..record
+            },
+            None => transfer,
+        };
+        record.status = status;
+        record.history.push(TransferTransition { status, at, detail });
+        open.retain(|id| *id != record.id);
+        if !status.is_final() {
+            open.push(record.id);
+        }
+        entries.push((transfer_key(&record.id), serde_json::to_vec(&record)?));
+    }
+    if !entries.is_empty() {
+        entries.push((OPEN_KEY.to_vec(), serde_json::to_vec(&open)?));
+    }
+    Ok(entries)
+}
+
+/// Transfers not yet in a final status
+pub fn open(state: &RocksStateDB) -> Result<Vec<BridgeTransfer>, BridgeError> {
+    open_transfers(state)?
+        .iter()
+        .map(|id| {
+            get_transfer(state, id)?
+                .ok_or_else(|| BridgeError::StateError(format!("Transfer {} is missing", hex::encode(id))))
+        })
+        .collect()
+}
+
+/// Complete the pending mints the state has executed since they were recorded, returning
+/// how many were completed
+pub fn reconcile(state: &mut RocksStateDB) -> Result<usize, BridgeError> {
+    let executed_deposits = state.get_account(MINT_ADDRESS)?.nonce;
+    let executed_burns = state.get_account(XFG_MINT_ADDRESS)?.nonce;
+    let mut completed = Vec::new();
+    for transfer in open(state)? {
+        let (Some(nonce), TransferStatus::Pending) = (transfer.mint_nonce, transfer.status) else {
+            continue;
+        };
+        // A mint counts once the state has executed its nonce and the nonce still holds it
+        let (source, executed) = match transfer.kind {
+            TransferKind::Deposit => (MintSource::BridgeDeposit, executed_deposits),
+            TransferKind::XfgBurn => (MintSource::XfgBurn, executed_burns),
+            _ => continue,
+        };
+        if nonce < executed && deposits::mint_hash_at(state, source, nonce)? == Some(transfer.id) {
+            completed.push((transfer, TransferStatus::Completed, None));
+        }
+    }
+    let count = completed.len();
+    if count > 0 {
+        let entries = transitions(state, completed)?;
+        state.write_batch_sync(&entries)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_transitions_are_idempotent_and_listed_by_status_and_address() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+        let deposit = BridgeTransfer::new([1; 32], TransferKind::Deposit, vec![0xa1], 50);
+        let withdrawal = BridgeTransfer::new([2; 32], TransferKind::Withdrawal, vec![0xa2], 70);
+
+        let entries = transitions(
+            &db,
+            [(deposit.clone(), TransferStatus::Pending, None), (withdrawal.clone(), TransferStatus::Pending, None)],
+        )
+        .unwrap();
+        db.write_batch_sync(&entries).unwrap();
+        // Replaying the same step after a restart changes nothing
+        assert!(transitions(&db, [(deposit.clone(), TransferStatus::Pending, None)]).unwrap().is_empty());
+
+        let sealed = transitions(&db, [(withdrawal.clone(), TransferStatus::Submitted, Some("batch 0".to_string()))]);
+        db.write_batch_sync(&sealed.unwrap()).unwrap();
+        let entries = transitions(&db, [(withdrawal, TransferStatus::Completed, None)]).unwrap();
+        db.write_batch_sync(&entries).unwrap();
+
+        let record = get_transfer(&db, &[2; 32]).unwrap().unwrap();
+        let statuses: Vec<_> = record.history.iter().map(|transition| transition.status).collect();
+        assert_eq!(statuses, [TransferStatus::Pending, TransferStatus::Submitted, TransferStatus::Completed]);
+        assert_eq!(record.history[1].detail.as_deref(), Some("batch 0"));
+        assert_eq!(open(&db).unwrap(), vec![get_transfer(&db, &[1; 32]).unwrap().unwrap()]);
+
+        assert_eq!(list_transfers(&db, None, None).unwrap().len(), 2);
+        let pending = list_transfers(&db, Some(TransferStatus::Pending), None).unwrap();
+        assert_eq!(pending.iter().map(|transfer| transfer.id).collect::<Vec<_>>(), vec![[1; 32]]);
+        assert_eq!(list_transfers(&db, None, Some(&[0xa2])).unwrap()[0].amount, 70);
+        assert!(list_transfers(&db, Some(TransferStatus::Pending), Some(&[0xa2])).unwrap().is_empty());
+        assert_eq!(TransferStatus::from_name("rolled_back"), Some(TransferStatus::RolledBack));
+    }
+}
@@ -0,0 +1,402 @@
+//! Independent checking of the batches committed to L1.
+//!
+//! A watchtower does not produce blocks. It follows the `BatchCommitted` events of the
+//! bridge contract and holds each posted state root against the root its own execution
+//! of the same blocks reached. A batch the local chain has not reached yet is checked
+//! once it has. Every mismatch is counted, posted to the configured webhook and, on an
+//! optimistic settlement layer, disputed with a fraud-proof challenge.
+
+use crate::batches::{BatchSubmission, L1Batch};
+use crate::challenges;
+use crate::deposits::{decode_hex, parse_quantity, L1Client};
+use crate::error::BridgeError;
+use crate::settlement::{Settlement, SettlementBackend, SettlementLayer};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use state_db::RocksStateDB;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+
+const CURSOR_KEY: &[u8] = b"bridge/watchtower/cursor";
+
+/// Watchtower configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchtowerConfig {
+    /// URL receiving a JSON alert for every mismatching batch
+    pub webhook_url: Option<String>,
+    /// Hex ed25519 public key challenges are opened under, which must sign their later
+    /// moves; mismatches are only alerted without it
+    pub challenger: Option<String>,
+    /// Blocks a commitment must be buried under before it is checked
+    pub confirmations: u64,
+    /// First L1 block to scan, normally the bridge contract's deployment block
+    pub start_block: u64,
+    /// Widest block range requested in one `eth_getLogs` call
+    pub max_block_range: u64,
+    pub poll_interval: Duration,
+}
+
+impl Default for WatchtowerConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            challenger: None,
+            confirmations: 12,
+            start_block: 0,
+            max_block_range: 1_000,
+            poll_interval: Duration::from_secs(15),
+        }
+    }
+}
+
+/// Topic of `BatchCommitted(uint256 indexed number, uint256 firstBlock, uint256 lastBlock,
+/// bytes32 blocksHash, bytes32 stateRoot, bytes32 priorityOpsHash)`
+pub fn batch_committed_topic() -> [u8; 32] {
+    Keccak256::digest(b"BatchCommitted(uint256,uint256,uint256,bytes32,bytes32,bytes32)").into()
+}
+
+/// Decode a `BatchCommitted` log as returned by `eth_getLogs`, with the L1 block holding it
+pub fn posted_batch(log: &Value) -> Result<(u64, L1Batch), BridgeError> {
+    let field = |name: &str| {
+        log.get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| BridgeError::ArbitrumError(format!("Log is missing {}", name)))
+    };
+    let topics: Vec<Vec<u8>> = log
+        .get("topics")
+        .and_then(Value::as_array)
+        .ok_or_else(|| BridgeError::ArbitrumError("Log is missing topics".to_string()))?
+        .iter()
+        .map(|topic| decode_hex(topic.as_str().unwrap_or_default()))
+        .collect::<Result<_, _>>()?;
+    if topics.len() != 2 || topics.iter().any(|topic| topic.len() != 32) || topics[0] != batch_committed_topic() {
+        return Err(BridgeError::ArbitrumError("Log is not a BatchCommitted event".to_string()));
+    }
+    let data = decode_hex(field("data")?)?;
+    if data.len() != 5 * 32 {
+        return Err(BridgeError::ArbitrumError("BatchCommitted data is not five words".to_string()));
+    }
+    let word = |index: usize| -> [u8; 32] { data[index * 32..(index + 1) * 32].try_into().unwrap() };
+    let number = |word: &[u8]| -> Result<u64, BridgeError> {
+        if word[..24].iter().any(|byte| *byte != 0) {
+            return Err(BridgeError::ArbitrumError("Batch number exceeds u64".to_string()));
+        }
+        Ok(u64::from_be_bytes(word[24..].try_into().unwrap()))
+    };
+    let tx_hash = decode_hex(field("transactionHash")?)?
+        .try_into()
+        .map_err(|_| BridgeError::ArbitrumError("Invalid transaction hash".to_string()))?;
+
+    let batch = L1Batch {
+        number: number(&topics[1])?,
+        first_block: number(&word(0))?,
+        last_block: number(&word(1))?,
+        blocks_hash: word(2),
+        state_root: word(3),
+        priority_ops_hash: word(4),
+        priority_ops: 0,
+        transactions: 0,
+        submission: Some(BatchSubmission { tx_hash, nonce: 0, gas_price: 0, submitted_at: 0 }),
+    };
+    if batch.last_block < batch.first_block {
+        return Err(BridgeError::ArbitrumError(format!("Batch {} ends before it starts", batch.number)));
+    }
+    Ok((parse_quantity(field("blockNumber")?)?, batch))
+}
+
+/// Progress of the watchtower, persisted after every poll
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchCursor {
+    pub next_l1_block: u64,
+    pub next_batch: u64,
+    /// State root posted for the batch before `next_batch`
+    pub last_state_root: [u8; 32],
+}
+
+/// A posted batch whose state root differs from the local one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchMismatch {
+    pub batch: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    pub posted_state_root: [u8; 32],
+    pub local_state_root: [u8; 32],
+    /// Challenge opened against the batch, if any
+    pub challenge: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchtowerStats {
+    pub batches_checked: u64,
+    pub mismatches: u64,
+    pub challenges_opened: u64,
+    pub alerts_failed: u64,
+}
+
+/// Checks the batches committed to L1 against local execution
+pub struct Watchtower {
+    config: WatchtowerConfig,
+    contract_address: String,
+    l1: L1Client,
+    http: reqwest::Client,
+    db: Arc<RwLock<RocksStateDB>>,
+    cursor: WatchCursor,
+    stats: WatchtowerStats,
+}
+
+impl Watchtower {
+    /// Create a watchtower resuming from the cursor stored in `db`
+    pub async fn with_state_db(
+        config: WatchtowerConfig,
+        l1: L1Client,
+        contract_address: String,
+        db: Arc<RwLock<RocksStateDB>>,
+    ) -> Result<Self, BridgeError> {
+        let cursor = match db.read().await.get_sync(CURSOR_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => WatchCursor {
+                next_l1_block: config.start_block,
+                ..Default::default()
+            },
+        };
+        Ok(Self {
+            config,
+            contract_address,
+            l1,
+            http: reqwest::Client::new(),
+            db,
+            cursor,
+            stats: WatchtowerStats::default(),
+        })
+    }
+
+    /// Check the batches committed in newly confirmed L1 blocks, returning the mismatches.
+    /// Every posted batch is also mirrored to `settlement`, so challenges can be opened there.
+    pub async fn poll(&mut self, settlement: &Settlement) -> Result<Vec<BatchMismatch>, BridgeError> {
+        let head = self.l1.block_number().await?;
+        let Some(confirmed) = head.checked_sub(self.config.confirmations) else {
+            return Ok(Vec::new());
+        };
+        if confirmed < self.cursor.next_l1_block {
+            return Ok(Vec::new());
+        }
+        let to = confirmed.min(self.cursor.next_l1_block.saturating_add(self.config.max_block_range.max(1) - 1));
+        let logs = self
+            .l1
+            .get_logs(&self.contract_address, &[batch_committed_topic()], self.cursor.next_l1_block, to)
+            .await?;
+        let mut posted = logs.iter().map(posted_batch).collect::<Result<Vec<_>, _>>()?;
+        posted.sort_by_key(|(_, batch)| batch.number);
+
+        let mut cursor = self.cursor.clone();
+        cursor.next_l1_block = to + 1;
+        let mut mismatches = Vec::new();
+        for (l1_block, batch) in posted {
+            // Rescanned after waiting for the local chain
+            if batch.number < cursor.next_batch {
+                continue;
+            }
+            if batch.number > cursor.next_batch {
+                return Err(BridgeError::StateError(format!(
+                    "Batch {} was posted before batch {}",
+                    batch.number, cursor.next_batch
+                )));
+            }
+            let Some(local_state_root) = self.db.read().await.root_at(batch.last_block)? else {
+                // Check it again once the local chain reaches its last block
+                cursor.next_l1_block = l1_block;
+                break;
+            };
+            if let Err(e) = settlement.commit_batch(batch.number, batch.commitment()).await {
+                println!("Batch {} not tracked on {}: {}", batch.number, settlement.layer().name(), e);
+            }
+            self.stats.batches_checked += 1;
+            if local_state_root != batch.state_root {
+                let challenge = self.dispute(settlement, &batch, &cursor.last_state_root, local_state_root).await;
+                let mismatch = BatchMismatch {
+                    batch: batch.number,
+                    first_block: batch.first_block,
+                    last_block: batch.last_block,
+                    posted_state_root: batch.state_root,
+                    local_state_root,
+                    challenge,
+                };
+                println!(
+                    "Batch {} posted state root {} but local execution reached {}",
+                    batch.number,
+                    hex::encode(batch.state_root),
+                    hex::encode(local_state_root)
+                );
+                self.stats.mismatches += 1;
+                self.alert(&mismatch).await;
+                mismatches.push(mismatch);
+            }
+            cursor.next_batch += 1;
+            cursor.last_state_root = batch.state_root;
+        }
+
+        self.db.write().await.write_batch_sync(&[(CURSOR_KEY.to_vec(), serde_json::to_vec(&cursor)?)])?;
+        self.cursor = cursor;
+        Ok(mismatches)
+    }
+
+    /// Open a challenge of `batch` claiming `local_state_root`, when the settlement layer is
+    /// optimistic and a challenger is configured
+    async fn dispute(
+        &mut self,
+        settlement: &Settlement,
+        batch: &L1Batch,
+        agreed_state: &[u8; 32],
+        local_state_root: [u8; 32],
+    ) -> Option<u64> {
+        if settlement.layer() != SettlementLayer::Arbitrum {
+            return None;
+        }
+        let challenger = decode_hex(self.config.challenger.as_deref()?).ok()?;
+        let mut db = self.db.write().await;
+        match challenges::open_against(&mut db, batch, *agreed_state, challenger, local_state_root) {
+            Ok(challenge) => {
+                self.stats.challenges_opened += 1;
+                Some(challenge.id)
+            }
+            Err(e) => {
+                println!("Challenge of batch {} not opened: {}", batch.number, e);
+                None
+            }
+        }
+    }
+
+    /// Post `mismatch` to the webhook, if one is configured
+    async fn alert(&mut self, mismatch: &BatchMismatch) {
+        let Some(url) = &self.config.webhook_url else {
+            return;
+        };
+        let alert = json!({
+            "event": "batch_mismatch",
+            "batch": mismatch.batch,
+            "firstBlock": mismatch.first_block,
+            "lastBlock": mismatch.last_block,
+            "postedStateRoot": hex::encode(mismatch.posted_state_root),
+            "localStateRoot": hex::encode(mismatch.local_state_root),
+            "challenge": mismatch.challenge,
+        });
+        let result = self.http.post(url).json(&alert).send().await.and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            println!("Watchtower alert for batch {} not delivered: {}", mismatch.batch, e);
+            self.stats.alerts_failed += 1;
+        }
+    }
+
+    pub fn cursor(&self) -> &WatchCursor {
+        &self.cursor
+    }
+
+    pub fn get_stats(&self) -> WatchtowerStats {
+        self.stats.clone()
+    }
+
+    pub fn config(&self) -> &WatchtowerConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn rpc_result(result: Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({ "jsonrpc": "2.0", "id": 0, "result": result }))
+    }
+
+    fn batch_log(l1_block: u64, number: u64, first_block: u64, last_block: u64, state_root: [u8; 32]) -> Value {
+        let mut data = Vec::new();
+        for value in [first_block, last_block] {
+            data.extend_from_slice(&[0u8; 24]);
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&[0xbb; 32]);
+        data.extend_from_slice(&state_root);
+        data.extend_from_slice(&[0u8; 32]);
+        json!({
+            "blockNumber": format!("0x{:x}", l1_block),
+            "transactionHash": format!("0x{}", hex::encode([l1_block as u8; 32])),
+            "logIndex": "0x0",
+            "topics": [format!("0x{}", hex::encode(batch_committed_topic())), format!("0x{:064x}", number)],
+            "data": format!("0x{}", hex::encode(data)),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_mismatching_batches_are_alerted_and_challenged() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "eth_blockNumber" })))
+            .respond_with(rpc_result(json!("0x20")))
+            .mount(&server)
+            .await;
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut roots = Vec::new();
+        for height in 0..=4u64 {
+            let mut state = db.write().await;
+            state.put_sync(b"height", &height.to_be_bytes()).unwrap();
+            roots.push(state.commit_sync(height).unwrap());
+        }
+        let (root_2, root_4) = (roots[2], roots[4]);
+
+        // Batch 0 matches local execution, batch 1 does not and batch 2 is ahead of it
+        let logs = json!([
+            batch_log(0x11, 1, 3, 4, [0xee; 32]),
+            batch_log(0x10, 0, 0, 2, root_2),
+            batch_log(0x12, 2, 5, 6, [0xdd; 32]),
+        ]);
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "eth_getLogs" })))
+            .respond_with(rpc_result(logs))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "event": "batch_mismatch", "batch": 1 })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = WatchtowerConfig {
+            webhook_url: Some(format!("{}/alerts", server.uri())),
+            challenger: Some("c1".to_string()),
+            confirmations: 2,
+            ..Default::default()
+        };
+        let l1 = L1Client::new(server.uri(), Duration::from_secs(5), None).unwrap();
+        let mut watchtower = Watchtower::with_state_db(config, l1, "0xbridge".to_string(), db.clone()).await.unwrap();
+        let settlement = Settlement::new(
+            SettlementLayer::Arbitrum,
+            server.uri(),
+            "0xbridge".to_string(),
+            Duration::from_secs(3_600),
+        )
+        .unwrap();
+
+        let mismatches = watchtower.poll(&settlement).await.unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!((mismatches[0].batch, mismatches[0].local_state_root), (1, root_4));
+        assert_eq!(mismatches[0].challenge, Some(0));
+        let challenge = challenges::get_challenge(&*db.read().await, 0).unwrap().unwrap();
+        assert_eq!((challenge.batch, challenge.agreed_state, challenge.disputed_step), (1, root_2, 2));
+
+        // Batch 2 waits for block 6, so its L1 block is scanned again
+        assert_eq!(watchtower.cursor().next_batch, 2);
+        assert_eq!(watchtower.cursor().next_l1_block, 0x12);
+        let stats = watchtower.get_stats();
+        assert_eq!((stats.batches_checked, stats.mismatches), (2, 1));
+        assert_eq!((stats.challenges_opened, stats.alerts_failed), (1, 0));
+        server.verify().await;
+    }
+}
@@ -0,0 +1,399 @@
+use crate::error::BridgeError;
+use crate::transfers::{self, BridgeTransfer, TransferKind, TransferStatus};
+use execution::receipt::withdrawal_topic;
+use execution::{Log, Receipt, ReceiptStatus, WITHDRAWAL_ADDRESS};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use state_db::RocksStateDB;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const PENDING_KEY: &[u8] = b"bridge/withdrawals/pending";
+const NEXT_BATCH_KEY: &[u8] = b"bridge/withdrawals/next_batch";
+const BATCH_PREFIX: &[u8] = b"bridge/withdrawals/batch/";
+const LOCATION_PREFIX: &[u8] = b"bridge/withdrawals/location/";
+
+/// Withdrawal queue configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalConfig {
+    /// Most withdrawals sealed under one root
+    pub max_batch_size: usize,
+}
+
+impl Default for WithdrawalConfig {
+    fn default() -> Self {
+        Self { max_batch_size: 1_024 }
+    }
+}
+
+/// HEAT or a bridged token burned on C0DL3 and claimable from the L1 bridge contract
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Withdrawal {
+    pub block_height: u64,
+    pub tx_hash: [u8; 32],
+    pub log_index: u32,
+    pub sender: Vec<u8>,
+    pub l1_recipient: Vec<u8>,
+    pub amount: u64,
+    /// L1 contract of the token withdrawn, or `None` for HEAT
+    #[serde(default)]
+    pub token: Option<Vec<u8>>,
+}
+
+impl Withdrawal {
+    /// Decode a withdrawal log emitted by the executor
+    pub fn from_log(log: &Log) -> Option<Self> {
+        // A token withdrawal logs the token's L1 contract as a fourth topic
+        let topics = log.topics.len();
+        if log.address != WITHDRAWAL_ADDRESS || !(3..=4).contains(&topics) || log.topics[0] != withdrawal_topic() {
+            return None;
+        }
+        let amount = u64::from_be_bytes(log.data.as_slice().try_into().ok()?);
+        Some(Self {
+            block_height: log.block_height,
+            tx_hash: log.tx_hash,
+            log_index: log.log_index,
+            sender: log.topics[1][12..].to_vec(),
+            l1_recipient: log.topics[2][12..].to_vec(),
+            amount,
+            token: log.topics.get(3).map(|topic| topic[12..].to_vec()),
+        })
+    }
+
+    /// Identifier the L1 contract marks as claimed
+    pub fn id(&self) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(self.tx_hash);
+        hasher.update(self.log_index.to_be_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Lifecycle record of this withdrawal
+    pub fn transfer(&self) -> BridgeTransfer {
+        BridgeTransfer::new(self.id(), TransferKind::Withdrawal, self.sender.clone(), self.amount)
+    }
+
+    /// Merkle leaf: `keccak256(abi.encodePacked(id, recipient, uint256(amount)))`, or
+    /// `keccak256(abi.encodePacked(id, recipient, token, uint256(amount)))` for a token
+    pub fn leaf(&self) -> [u8; 32] {
+        let mut amount = [0u8; 32];
+        amount[24..].copy_from_slice(&self.amount.to_be_bytes());
+        let mut hasher = Keccak256::new();
+        hasher.update(self.id());
+        hasher.update(&self.l1_recipient);
+        if let Some(token) = &self.token {
+            hasher.update(token);
+        }
+        hasher.update(amount);
+        hasher.finalize().into()
+    }
+}
+
+/// Withdrawals sealed under one Merkle root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WithdrawalBatch {
+    pub index: u64,
+    pub root: [u8; 32],
+    pub withdrawals: Vec<Withdrawal>,
+    /// Whether the root has been committed to the L1 bridge contract
+    pub committed: bool,
+}
+
+/// Everything a user needs to claim a withdrawal on L1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WithdrawalProof {
+    pub batch_index: u64,
+    pub root: [u8; 32],
+    pub committed: bool,
+    pub withdrawal: Withdrawal,
+    pub leaf: [u8; 32],
+    pub leaf_index: u64,
+    /// Sibling hashes from the leaf up to the root
+    pub siblings: Vec<[u8; 32]>,
+}
+
+/// Hashing of the withdrawal tree: Keccak-256 over a `0x00` prefix and the leaf, or a
+/// `0x01` prefix and both children, as the L1 contract checks claims
+pub struct WithdrawalTreeHasher;
+
+impl merkle::MerkleHasher for WithdrawalTreeHasher {
+    fn leaf(leaf: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update([0x00]);
+        hasher.update(leaf);
+        hasher.finalize().into()
+    }
+
+    fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update([0x01]);
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize().into()
+    }
+}
+
+/// Check that `leaf` sits at `index` of the withdrawal tree with `root`, as the L1 contract does
+pub fn verify_merkle_proof(leaf: &[u8; 32], index: u64, siblings: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    merkle::verify::<WithdrawalTreeHasher>(leaf, index, siblings, root)
+}
+
+fn batch_key(index: u64) -> Vec<u8> {
+    [BATCH_PREFIX, index.to_be_bytes().as_slice()].concat()
+}
+
+fn location_key(tx_hash: &[u8; 32], log_index: u32) -> Vec<u8> {
+    [LOCATION_PREFIX, tx_hash.as_slice(), log_index.to_be_bytes().as_slice()].concat()
+}
+
+/// Get the sealed withdrawal batch `index`
+pub fn get_batch(state: &RocksStateDB, index: u64) -> Result<Option<WithdrawalBatch>, BridgeError> {
+    match state.get_sync(&batch_key(index))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Get the claim proof of the withdrawal logged by `tx_hash` at block log index `log_index`,
+/// or `None` until the withdrawal has been sealed into a batch
+pub fn get_withdrawal_proof(
+    state: &RocksStateDB,
+    tx_hash: &[u8; 32],
+    log_index: u32,
+) -> Result<Option<WithdrawalProof>, BridgeError> {
+    let Some(bytes) = state.get_sync(&location_key(tx_hash, log_index))? else {
+        return Ok(None);
+    };
+    let (batch_index, leaf_index): (u64, u64) = serde_json::from_slice(&bytes)?;
+    let batch = get_batch(state, batch_index)?
+        .ok_or_else(|| BridgeError::StateError(format!("Withdrawal batch {} is missing", batch_index)))?;
+    let leaves: Vec<[u8; 32]> = batch.withdrawals.iter().map(Withdrawal::leaf).collect();
+    let withdrawal = batch
+        .withdrawals
+        .get(leaf_index as usize)
+        .cloned()
+        .ok_or_else(|| BridgeError::StateError(format!("Withdrawal batch {} is truncated", batch_index)))?;
+
+    Ok(Some(WithdrawalProof {
+        batch_index,
+        root: batch.root,
+        committed: batch.committed,
+        leaf: leaves[leaf_index as usize],
+        leaf_index,
+        siblings: merkle::proof::<WithdrawalTreeHasher>(&leaves, leaf_index as usize).unwrap_or_default(),
+        withdrawal,
+    }))
+}
+
+/// Collects withdrawals from executed blocks and seals them into batches for L1
+pub struct WithdrawalQueue {
+    config: WithdrawalConfig,
+    db: Arc<RwLock<RocksStateDB>>,
+    pending: Vec<Withdrawal>,
+    next_batch: u64,
+}
+
+impl WithdrawalQueue {
+    /// Create a queue restoring unsealed withdrawals from `db`
+    pub async fn with_state_db(config: WithdrawalConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, BridgeError> {
+        if config.max_batch_size == 0 {
+            return Err(BridgeError::ConfigError("Withdrawal batch size must be positive".to_string()));
+        }
+        let (pending, next_batch) = {
+            let store = db.read().await;
+            let pending = match store.get_sync(PENDING_KEY)? {
+                Some(bytes) => serde_json::from_slice(&bytes)?,
+                None => Vec::new(),
+            };
+            let next_batch = match store.get_sync(NEXT_BATCH_KEY)? {
+                Some(bytes) => serde_json::from_slice(&bytes)?,
+                None => 0,
+            };
+            (pending, next_batch)
+        };
+        Ok(Self {
+            config,
+            db,
+            pending,
+            next_batch,
+        })
+    }
+
+    /// Queue the withdrawals of successful transactions in `receipts`
+    pub async fn ingest(&mut self, receipts: &[Receipt]) -> Result<usize, BridgeError> {
+        let withdrawals: Vec<Withdrawal> = receipts
+            .iter()
+            .filter(|receipt| receipt.status == ReceiptStatus::Success)
+            .flat_map(|receipt| receipt.logs.iter().filter_map(Withdrawal::from_log))
+            .collect();
+        if withdrawals.is_empty() {
+            return Ok(0);
+        }
+        let mut pending = self.pending.clone();
+        pending.extend(withdrawals.iter().cloned());
+        let mut db = self.db.write().await;
+        let changes = withdrawals.iter().map(|w| (w.transfer(), TransferStatus::Pending, None));
+        let mut entries = transfers::transitions(&db, changes)?;
+        entries.push((PENDING_KEY.to_vec(), serde_json::to_vec(&pending)?));
+        db.write_batch_sync(&entries)?;
+        drop(db);
+        self.pending = pending;
+        Ok(withdrawals.len())
+    }
+
+    /// Seal up to `max_batch_size` pending withdrawals under a new root
+    pub async fn seal_batch(&mut self) -> Result<Option<WithdrawalBatch>, BridgeError> {
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        let take = self.pending.len().min(self.config.max_batch_size);
+        let withdrawals = self.pending[..take].to_vec();
+        let remaining = &self.pending[take..];
+        let leaves: Vec<[u8; 32]> = withdrawals.iter().map(Withdrawal::leaf).collect();
+        let batch = WithdrawalBatch {
+            index: self.next_batch,
+            root: merkle::root::<WithdrawalTreeHasher>(&leaves),
+            withdrawals,
+            committed: false,
+        };
+
+        let mut entries = Vec::with_capacity(batch.withdrawals.len() + 3);
+        for (leaf_index, withdrawal) in batch.withdrawals.iter().enumerate() {
+            entries.push((
+                location_key(&withdrawal.tx_hash, withdrawal.log_index),
+                serde_json::to_vec(&(batch.index, leaf_index as u64))?,
+            ));
+        }
+        entries.push((batch_key(batch.index), serde_json::to_vec(&batch)?));
+        entries.push((PENDING_KEY.to_vec(), serde_json::to_vec(remaining)?));
+        entries.push((NEXT_BATCH_KEY.to_vec(), serde_json::to_vec(&(batch.index + 1))?));
+        let mut db = self.db.write().await;
+        let detail = format!("batch {}", batch.index);
+        let changes = batch.withdrawals.iter().map(|w| (w.transfer(), TransferStatus::Submitted, Some(detail.clone())));
+        entries.extend(transfers::transitions(&db, changes)?);
+        db.write_batch_sync(&entries)?;
+        drop(db);
+
+        self.pending.drain(..take);
+        self.next_batch += 1;
+        Ok(Some(batch))
+    }
+
+    /// The latest sealed batch if its root has not been committed to L1 yet
+    pub async fn uncommitted_batch(&self) -> Result<Option<WithdrawalBatch>, BridgeError> {
+        let Some(latest) = self.next_batch.checked_sub(1) else {
+            return Ok(None);
+        };
+        Ok(get_batch(&*self.db.read().await, latest)?.filter(|batch| !batch.committed))
+    }
+
+    /// Record that the root of batch `index` was committed to L1
+    pub async fn mark_committed(&self, index: u64) -> Result<(), BridgeError> {
+        let mut db = self.db.write().await;
+        let mut batch = get_batch(&db, index)?
+            .ok_or_else(|| BridgeError::StateError(format!("Withdrawal batch {} does not exist", index)))?;
+        batch.committed = true;
+        let changes = batch.withdrawals.iter().map(|w| (w.transfer(), TransferStatus::Completed, None));
+        let mut entries = transfers::transitions(&db, changes)?;
+        entries.push((batch_key(index), serde_json::to_vec(&batch)?));
+        db.write_batch_sync(&entries)?;
+        Ok(())
+    }
+
+    /// Withdrawals waiting for a batch
+    pub fn pending(&self) -> &[Withdrawal] {
+        &self.pending
+    }
+
+    /// Index the next sealed batch will get
+    pub fn next_batch(&self) -> u64 {
+        self.next_batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use execution::receipt::address_topic;
+    use tempfile::TempDir;
+
+    fn withdrawal_receipt(tx: u8, amounts: &[u64], status: ReceiptStatus) -> Receipt {
+        let logs = amounts
+            .iter()
+            .enumerate()
+            .map(|(log_index, amount)| Log {
+                address: WITHDRAWAL_ADDRESS.to_vec(),
+                topics: vec![withdrawal_topic(), address_topic(&[0xa1; 20]), address_topic(&[tx; 20])],
+                data: amount.to_be_bytes().to_vec(),
+                block_height: 1,
+                tx_hash: [tx; 32],
+                tx_index: 0,
+                log_index: log_index as u32,
+            })
+            .collect();
+        Receipt {
+            tx_hash: [tx; 32],
+            block_height: 1,
+            tx_index: 0,
+            status,
+            gas_used: 0,
+            cumulative_gas_used: 0,
+            fee: 0,
+            contract_address: None,
+            revert_reason: None,
+            logs,
+        }
+    }
+
+    #[test]
+    fn test_withdrawal_tree_prefixes_leaves_and_nodes() {
+        let leaves = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let root = merkle::root::<WithdrawalTreeHasher>(&leaves);
+        let keccak = |parts: &[&[u8]]| -> [u8; 32] {
+            let mut hasher = Keccak256::new();
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize().into()
+        };
+        let left = keccak(&[&[0x01], &keccak(&[&[0x00], &leaves[0]]), &keccak(&[&[0x00], &leaves[1]])]);
+        let right = keccak(&[&[0x01], &keccak(&[&[0x00], &leaves[2]]), &[0u8; 32]]);
+        assert_eq!(root, keccak(&[&[0x01], &left, &right]));
+
+        let siblings = merkle::proof::<WithdrawalTreeHasher>(&leaves, 2).unwrap();
+        assert!(verify_merkle_proof(&leaves[2], 2, &siblings, &root));
+        assert!(!verify_merkle_proof(&right, 1, &siblings[1..], &root));
+    }
+
+    #[tokio::test]
+    async fn test_queue_seals_batches_and_serves_proofs() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let config = WithdrawalConfig { max_batch_size: 2 };
+        let mut queue = WithdrawalQueue::with_state_db(config.clone(), db.clone()).await.unwrap();
+
+        let receipts = vec![
+            withdrawal_receipt(1, &[10, 20], ReceiptStatus::Success),
+            withdrawal_receipt(2, &[30], ReceiptStatus::Success),
+            withdrawal_receipt(3, &[40], ReceiptStatus::Failed),
+        ];
+        assert_eq!(queue.ingest(&receipts).await.unwrap(), 3);
+
+        let first = queue.seal_batch().await.unwrap().unwrap();
+        assert_eq!((first.index, first.withdrawals.len()), (0, 2));
+
+        // Unsealed withdrawals survive a restart
+        let mut queue = WithdrawalQueue::with_state_db(config, db.clone()).await.unwrap();
+        assert_eq!((queue.pending().len(), queue.next_batch()), (1, 1));
+        let second = queue.seal_batch().await.unwrap().unwrap();
+        assert_eq!(second.withdrawals[0].amount, 30);
+        assert!(queue.seal_batch().await.unwrap().is_none());
+        assert_eq!(queue.uncommitted_batch().await.unwrap(), Some(second));
+        queue.mark_committed(0).await.unwrap();
+
+        let proof = get_withdrawal_proof(&*db.read().await, &[1; 32], 1).unwrap().unwrap();
+        assert_eq!((proof.batch_index, proof.leaf_index, proof.committed), (0, 1, true));
+        assert_eq!(proof.withdrawal.amount, 20);
+        assert_eq!(proof.withdrawal.l1_recipient, vec![1; 20]);
+        assert!(verify_merkle_proof(&proof.leaf, proof.leaf_index, &proof.siblings, &first.root));
+        assert!(get_withdrawal_proof(&*db.read().await, &[3; 32], 0).unwrap().is_none());
+    }
+}
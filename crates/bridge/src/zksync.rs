@@ -0,0 +1,125 @@
+use crate::arbitrum::{ProofSubmission, SubmissionResult, SubmissionStatus};
+use crate::error::BridgeError;
+use crate::settlement::{self, BatchFinality, CommittedBatch, SettlementBackend, SettlementLayer};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+
+/// zkSync client settling C0DL3 batches with validity proofs
+pub struct ZkSyncClient {
+    rpc_url: String,
+    contract_address: String,
+    submissions: Arc<RwLock<HashMap<[u8; 32], SubmissionResult>>>,
+    withdrawal_roots: Arc<RwLock<HashMap<u64, [u8; 32]>>>,
+    batches: Arc<RwLock<HashMap<u64, CommittedBatch>>>,
+    deposits: Arc<RwLock<u64>>,
+}
+
+impl ZkSyncClient {
+    pub fn new(rpc_url: String, contract_address: String) -> Result<Self, BridgeError> {
+        Ok(Self {
+            rpc_url,
+            contract_address,
+            submissions: Arc::new(RwLock::new(HashMap::new())),
+            withdrawal_roots: Arc::new(RwLock::new(HashMap::new())),
+            batches: Arc::new(RwLock::new(HashMap::new())),
+            deposits: Arc::new(RwLock::new(0)),
+        })
+    }
+
+    /// Get the withdrawal root committed for `batch_index`
+    pub async fn get_withdrawal_root(&self, batch_index: u64) -> Option<[u8; 32]> {
+        self.withdrawal_roots.read().await.get(&batch_index).copied()
+    }
+
+    /// Get submission result
+    pub async fn get_submission_result(&self, header_hash: &[u8; 32]) -> Option<SubmissionResult> {
+        self.submissions.read().await.get(header_hash).cloned()
+    }
+
+    pub fn get_contract_address(&self) -> &str {
+        &self.contract_address
+    }
+
+    pub fn get_rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+}
+
+impl SettlementBackend for ZkSyncClient {
+    fn layer(&self) -> SettlementLayer {
+        SettlementLayer::ZkSync
+    }
+
+    async fn commit_batch(&self, number: u64, commitment: [u8; 32]) -> Result<(), BridgeError> {
+        settlement::record_commitment(&mut *self.batches.write().await, number, commitment)
+    }
+
+    async fn submit_proof(&self, submission: ProofSubmission) -> Result<(), BridgeError> {
+        // In a real implementation, this would call the diamond proxy on zkSync
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let result = SubmissionResult {
+            transaction_hash: [0u8; 32],
+            block_number: 0,
+            gas_used: 0,
+            status: SubmissionStatus::Confirmed,
+        };
+        self.submissions.write().await.insert(submission.header_hash, result);
+        println!("Proof submitted to zkSync: {:?}", submission.header_hash);
+        Ok(())
+    }
+
+    /// The proof is taken as verified once it is accepted; a real contract checks it on chain
+    async fn prove_batch(&self, number: u64, proof: &[u8]) -> Result<(), BridgeError> {
+        let mut batches = self.batches.write().await;
+        let batch = batches
+            .get_mut(&number)
+            .ok_or_else(|| BridgeError::SettlementError(format!("Batch {} was never committed", number)))?;
+        if proof.is_empty() {
+            return Err(BridgeError::SettlementError(format!("Validity proof of batch {} is empty", number)));
+        }
+        batch.finality = BatchFinality::Finalized;
+        println!("Batch {} proven on zkSync", number);
+        Ok(())
+    }
+
+    async fn submit_fraud_proof(&self, number: u64, _proof: &[u8]) -> Result<(), BridgeError> {
+        Err(BridgeError::SettlementError(format!(
+            "zkSync batch {} is settled by a validity proof and cannot be challenged",
+            number
+        )))
+    }
+
+    async fn resolve_fraud_proof(&self, number: u64, _upheld: Option<String>) -> Result<(), BridgeError> {
+        Err(BridgeError::SettlementError(format!("zkSync batch {} was never challenged", number)))
+    }
+
+    async fn deposit(&self, recipient: &[u8], amount: u64) -> Result<[u8; 32], BridgeError> {
+        let mut deposits = self.deposits.write().await;
+        let id = settlement::deposit_id(SettlementLayer::ZkSync, *deposits, recipient, amount);
+        *deposits += 1;
+        Ok(id)
+    }
+
+    async fn withdraw(&self, batch_index: u64, root: [u8; 32]) -> Result<(), BridgeError> {
+        let mut roots = self.withdrawal_roots.write().await;
+        if roots.get(&batch_index).is_some_and(|committed| *committed != root) {
+            return Err(BridgeError::SettlementError(format!(
+                "Withdrawal batch {} already has a different root",
+                batch_index
+            )));
+        }
+        roots.insert(batch_index, root);
+        println!("Withdrawal root committed to zkSync: batch {}", batch_index);
+        Ok(())
+    }
+
+    async fn finality(&self, number: u64) -> BatchFinality {
+        self.batches
+            .read()
+            .await
+            .get(&number)
+            .map_or(BatchFinality::Unknown, |batch| batch.finality.clone())
+    }
+}
@@ -1,7 +1,13 @@
 use clap::Parser;
-use net_p2p::start_network;
+use net_p2p::{
+    start_network, transport::load_swarm_key, BandwidthLimits, NetworkConfig, Socks5Proxy, TimingPrivacyConfig,
+    TransportSecurity,
+};
 use libp2p::Multiaddr;
-use std::future;
+use std::time::Duration;
+
+/// How long the network gets to close its connections after a shutdown signal
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Parser)]
 #[command(name = "coldl3d", version = "0.1.0", about = "COLD L3 Node Daemon")]
@@ -9,16 +15,131 @@ struct Args {
     /// Multiaddr to listen on, e.g. /ip4/0.0.0.0/tcp/4001
     #[arg(long, default_value = "/ip4/0.0.0.0/tcp/4001")]
     listen: String,
+
+    /// Disable AutoNAT reachability probing
+    #[arg(long)]
+    no_autonat: bool,
+
+    /// Disable circuit relay usage (also disables hole punching)
+    #[arg(long)]
+    no_relay: bool,
+
+    /// Disable DCUtR hole punching
+    #[arg(long)]
+    no_hole_punching: bool,
+
+    /// Disable mDNS discovery on the local network
+    #[arg(long)]
+    no_mdns: bool,
+
+    /// Bootstrap peer multiaddr to dial on startup (repeatable)
+    #[arg(long = "bootstrap")]
+    bootstrap: Vec<String>,
+
+    /// DNS seed domain publishing `dnsaddr=` TXT records (repeatable)
+    #[arg(long = "dns-seed")]
+    dns_seeds: Vec<String>,
+
+    /// Security handshake for direct connections: noise or tls
+    #[arg(long, default_value = "noise")]
+    transport_security: String,
+
+    /// Path to a swarm key file; only peers holding the same key can connect
+    #[arg(long)]
+    swarm_key: Option<String>,
+
+    /// Publish transactions directly instead of relaying them along a Dandelion++ stem
+    #[arg(long)]
+    no_dandelion: bool,
+
+    /// Longest random delay in milliseconds before broadcasting a local transaction
+    #[arg(long, default_value_t = 2000)]
+    max_broadcast_delay_ms: u64,
+
+    /// Release outgoing transactions together every this many milliseconds
+    #[arg(long)]
+    batch_epoch_ms: Option<u64>,
+
+    /// Most bytes per second sent to a single peer before it is throttled
+    #[arg(long)]
+    peer_upload_limit: Option<u64>,
+
+    /// Most bytes per second accepted from a single peer before it is throttled
+    #[arg(long)]
+    peer_download_limit: Option<u64>,
+
+    /// Dial every peer through this SOCKS5 proxy, e.g. socks5://127.0.0.1:9050 for Tor;
+    /// also disables mDNS and hole punching
+    #[arg(long)]
+    proxy: Option<String>,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
     let addr: Multiaddr = args.listen.parse().expect("invalid multiaddr");
+    let bootstrap_peers = args
+        .bootstrap
+        .iter()
+        .map(|a| a.parse().expect("invalid bootstrap multiaddr"))
+        .collect();
+    let transport_security: TransportSecurity = args
+        .transport_security
+        .parse()
+        .expect("invalid transport security");
+    let private_network_key = args
+        .swarm_key
+        .as_ref()
+        .map(|path| load_swarm_key(path).expect("failed to load swarm key"));
+    let proxy: Option<Socks5Proxy> = args.proxy.as_ref().map(|url| url.parse().expect("invalid proxy"));
 
-    let (peer_id, _events) = start_network(addr).await;
-    println!("Node started with PeerId: {peer_id}");
+    let config = NetworkConfig {
+        listen_addr: addr,
+        enable_autonat: !args.no_autonat,
+        enable_relay: !args.no_relay,
+        enable_hole_punching: !args.no_relay && !args.no_hole_punching && proxy.is_none(),
+        enable_mdns: !args.no_mdns && proxy.is_none(),
+        bootstrap_peers,
+        dns_seeds: args.dns_seeds,
+        transport_security,
+        private_network_key,
+        timing_privacy: TimingPrivacyConfig {
+            max_broadcast_delay: Duration::from_millis(args.max_broadcast_delay_ms),
+            min_broadcast_delay: Duration::from_millis(args.max_broadcast_delay_ms.min(100)),
+            batch_epoch: args.batch_epoch_ms.map(Duration::from_millis),
+            enable_dandelion: !args.no_dandelion,
+            ..Default::default()
+        },
+        bandwidth: BandwidthLimits {
+            upload_bytes_per_sec: args.peer_upload_limit,
+            download_bytes_per_sec: args.peer_download_limit,
+            ..Default::default()
+        },
+        proxy,
+        ..Default::default()
+    };
 
-    // Block forever (placeholder)
-    future::pending::<()>().await;
-} 
\ No newline at end of file
+    let handle = start_network(config).await.expect("failed to start network");
+    println!("Node started with PeerId: {}", handle.peer_id);
+
+    shutdown_signal().await;
+    println!("Shutting down...");
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, handle.shutdown()).await.is_err() {
+        eprintln!("Network did not stop within {:?}", SHUTDOWN_TIMEOUT);
+    }
+}
+
+/// Wait for Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
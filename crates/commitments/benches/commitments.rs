@@ -0,0 +1,22 @@
+use commitments::note_tree::hash_children;
+use commitments::CommitmentEngine;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn bench_commitments(c: &mut Criterion) {
+    let engine = CommitmentEngine::new();
+    let data = [0x5au8; 256];
+    c.bench_function("commitments/heat", |b| {
+        b.iter(|| engine.calculate_heat_commitment(black_box(&data)).unwrap())
+    });
+    c.bench_function("commitments/yield", |b| {
+        b.iter(|| engine.calculate_yield_commitment(black_box(&data)).unwrap())
+    });
+
+    let (left, right) = ([0x11u8; 32], [0x22u8; 32]);
+    c.bench_function("note_tree/hash_children", |b| {
+        b.iter(|| hash_children(black_box(&left), black_box(&right)))
+    });
+}
+
+criterion_group!(benches, bench_commitments);
+criterion_main!(benches);
@@ -13,4 +13,13 @@ pub enum CommitmentError {
     
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+}
+
+impl From<state_db::error::StateDBError> for CommitmentError {
+    fn from(err: state_db::error::StateDBError) -> Self {
+        CommitmentError::StorageError(err.to_string())
+    }
 }
\ No newline at end of file
@@ -1,12 +1,18 @@
 use anyhow::Result;
-use blake2::{Blake2b, Digest};
 use serde::{Deserialize, Serialize};
 
 pub mod error;
 pub mod heat;
+pub mod note_tree;
 pub mod yield_commitment;
 
 use error::CommitmentError;
+use zk_proofs::{field_to_bytes, HashDomain, Poseidon, ZkFriendlyHash};
+
+/// Hash of the data a HEAT or Yield commitment was made over
+pub fn data_hash(data: &[u8]) -> [u8; 32] {
+    field_to_bytes(&Poseidon::hash_bytes(HashDomain::AuxData, data))
+}
 
 /// Commitment engine as specified in the outline
 pub struct CommitmentEngine {
@@ -67,11 +73,7 @@ impl HeatCommitment {
     }
     
     pub fn verify(&self, data: &[u8]) -> bool {
-        let mut hasher = Blake2b::new();
-        hasher.update(data);
-        let calculated_hash: [u8; 64] = hasher.finalize().into();
-        
-        self.data_hash == <[u8; 32]>::try_from(&calculated_hash[..32]).unwrap()
+        self.data_hash == data_hash(data)
     }
 }
 
@@ -95,11 +97,7 @@ impl YieldCommitment {
     }
     
     pub fn verify(&self, data: &[u8]) -> bool {
-        let mut hasher = Blake2b::new();
-        hasher.update(data);
-        let calculated_hash: [u8; 64] = hasher.finalize().into();
-        
-        self.data_hash == <[u8; 32]>::try_from(&calculated_hash[..32]).unwrap()
+        self.data_hash == data_hash(data)
     }
 }
 
@@ -146,14 +144,11 @@ mod tests {
     #[test]
     fn test_heat_commitment_struct() {
         let test_data = b"test_heat_struct_data";
-        let mut hasher = Blake2b::new();
-        hasher.update(test_data);
-        let data_hash: [u8; 64] = hasher.finalize().into();
-        
+
         let commitment = HeatCommitment::new(
             [1u8; 32],
             1234567890,
-            data_hash[..32].try_into().unwrap(),
+            data_hash(test_data),
         );
         
         assert!(commitment.verify(test_data));
@@ -163,15 +158,12 @@ mod tests {
     #[test]
     fn test_yield_commitment_struct() {
         let test_data = b"test_yield_struct_data";
-        let mut hasher = Blake2b::new();
-        hasher.update(test_data);
-        let data_hash: [u8; 64] = hasher.finalize().into();
-        
+
         let commitment = YieldCommitment::new(
             [2u8; 32],
             1234567890,
             1000,
-            data_hash[..32].try_into().unwrap(),
+            data_hash(test_data),
         );
         
         assert!(commitment.verify(test_data));
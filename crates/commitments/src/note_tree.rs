@@ -0,0 +1,344 @@
+use crate::error::CommitmentError;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+use std::collections::{BTreeMap, HashMap};
+use zk_proofs::poseidon::HASH_VERSION;
+use zk_proofs::{field_from_hash, field_to_bytes, HashDomain, Poseidon, ZkFriendlyHash};
+
+/// Depth of the note commitment tree, which holds 2^32 notes
+pub const NOTE_TREE_DEPTH: usize = 32;
+
+const META_KEY: &[u8] = b"note_tree/meta";
+const TRACKED_KEY: &[u8] = b"note_tree/tracked";
+const NODE_PREFIX: &[u8] = b"note_tree/node/";
+const POSITION_PREFIX: &[u8] = b"note_tree/position/";
+
+/// Size and frontier of the tree, enough to append without reading stored nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TreeMeta {
+    /// `HASH_VERSION` the nodes were hashed with; trees from before versioning read as 0
+    #[serde(default)]
+    hash_version: u64,
+    depth: usize,
+    size: u64,
+    root: [u8; 32],
+    /// Latest left node at each level, paired with the next right node appended there
+    frontier: Vec<[u8; 32]>,
+}
+
+fn node_key(level: usize, index: u64) -> Vec<u8> {
+    let mut key = NODE_PREFIX.to_vec();
+    key.push(level as u8);
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+fn position_key(commitment: &[u8; 32]) -> Vec<u8> {
+    [POSITION_PREFIX, commitment.as_slice()].concat()
+}
+
+/// Hash two children into their parent, the same way the state-transition circuit does.
+/// Nodes are read as little-endian field elements, reduced if not canonical.
+pub fn hash_children(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    field_to_bytes(&Poseidon::hash2(HashDomain::MerkleNode, field_from_hash(left), field_from_hash(right)))
+}
+
+/// Root of an empty subtree at each level, from the leaves up to the root
+fn empty_roots(depth: usize) -> Vec<[u8; 32]> {
+    let mut roots = vec![[0u8; 32]];
+    for level in 0..depth {
+        roots.push(hash_children(&roots[level], &roots[level]));
+    }
+    roots
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(bytes: Option<Vec<u8>>) -> Result<Option<T>, CommitmentError> {
+    Ok(bytes.map(|bytes| serde_json::from_slice(&bytes)).transpose()?)
+}
+
+/// Membership witness of a note commitment: its position and the siblings on its path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteWitness {
+    pub commitment: [u8; 32],
+    pub index: u64,
+    /// Sibling at each level, from the leaves up
+    pub siblings: Vec<[u8; 32]>,
+    /// Tree root the witness was taken at
+    pub root: [u8; 32],
+}
+
+impl NoteWitness {
+    /// Root implied by the commitment and its path
+    pub fn compute_root(&self) -> [u8; 32] {
+        self.siblings
+            .iter()
+            .enumerate()
+            .fold(self.commitment, |node, (level, sibling)| {
+                if (self.index >> level) & 1 == 0 {
+                    hash_children(&node, sibling)
+                } else {
+                    hash_children(sibling, &node)
+                }
+            })
+    }
+
+    /// Check that the commitment is in the tree with `root`
+    pub fn verify(&self, root: &[u8; 32]) -> bool {
+        self.compute_root() == *root
+    }
+}
+
+/// Read the current witness of `commitment` from the stored tree
+pub fn get_witness(state: &RocksStateDB, commitment: &[u8; 32]) -> Result<Option<NoteWitness>, CommitmentError> {
+    let Some(meta) = decode::<TreeMeta>(state.get_sync(META_KEY)?)? else {
+        return Ok(None);
+    };
+    let Some(index) = state
+        .get_sync(&position_key(commitment))?
+        .and_then(|bytes| <[u8; 8]>::try_from(bytes.as_slice()).ok())
+        .map(u64::from_be_bytes)
+    else {
+        return Ok(None);
+    };
+
+    let empty = empty_roots(meta.depth);
+    let siblings = (0..meta.depth)
+        .map(|level| {
+            let stored = state.get_sync(&node_key(level, (index >> level) ^ 1))?;
+            Ok(match stored {
+                Some(bytes) => bytes
+                    .try_into()
+                    .map_err(|_| CommitmentError::StorageError("Malformed tree node".to_string()))?,
+                None => empty[level],
+            })
+        })
+        .collect::<Result<Vec<_>, CommitmentError>>()?;
+    Ok(Some(NoteWitness {
+        commitment: *commitment,
+        index,
+        siblings,
+        root: meta.root,
+    }))
+}
+
+/// Append-only Merkle tree of note commitments, stored in the state database.
+///
+/// Appends only touch the cached frontier; every node written is also stored so
+/// witnesses can be served for any note, and the witnesses of tracked notes are
+/// kept current as commitments are appended.
+pub struct NoteCommitmentTree {
+    meta: TreeMeta,
+    empty: Vec<[u8; 32]>,
+    tracked: HashMap<[u8; 32], NoteWitness>,
+}
+
+impl NoteCommitmentTree {
+    /// Load the stored tree, or start an empty one of `depth`
+    pub fn load(state: &RocksStateDB, depth: usize) -> Result<Self, CommitmentError> {
+        if depth == 0 || depth > 63 {
+            return Err(CommitmentError::InvalidData(format!("Unsupported tree depth {}", depth)));
+        }
+        let empty = empty_roots(depth);
+        let meta = match decode::<TreeMeta>(state.get_sync(META_KEY)?)? {
+            Some(meta) if meta.depth != depth => {
+                return Err(CommitmentError::InvalidData(format!(
+                    "Stored tree has depth {}, expected {}",
+                    meta.depth, depth
+                )))
+            }
+            Some(meta) if meta.hash_version != HASH_VERSION => {
+                return Err(CommitmentError::InvalidData(format!(
+                    "Stored tree was hashed with version {}, expected {}; rebuild it from the chain",
+                    meta.hash_version, HASH_VERSION
+                )))
+            }
+            Some(meta) => meta,
+            None => TreeMeta {
+                hash_version: HASH_VERSION,
+                depth,
+                size: 0,
+                root: empty[depth],
+                frontier: empty[..depth].to_vec(),
+            },
+        };
+        let tracked = decode::<Vec<NoteWitness>>(state.get_sync(TRACKED_KEY)?)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|witness| (witness.commitment, witness))
+            .collect();
+        Ok(Self { meta, empty, tracked })
+    }
+
+    /// Current root
+    pub fn root(&self) -> [u8; 32] {
+        self.meta.root
+    }
+
+    /// Number of commitments appended
+    pub fn size(&self) -> u64 {
+        self.meta.size
+    }
+
+    /// Append one commitment and return its index
+    pub fn append(&mut self, state: &mut RocksStateDB, commitment: [u8; 32]) -> Result<u64, CommitmentError> {
+        self.append_all(state, &[commitment])
+    }
+
+    /// Append commitments in order in one write and return the index of the first
+    pub fn append_all(&mut self, state: &mut RocksStateDB, commitments: &[[u8; 32]]) -> Result<u64, CommitmentError> {
+        let first = self.meta.size;
+        if first + commitments.len() as u64 > 1u64 << self.meta.depth {
+            return Err(CommitmentError::InvalidData("Note commitment tree is full".to_string()));
+        }
+        for (i, commitment) in commitments.iter().enumerate() {
+            if commitments[..i].contains(commitment) || state.get_sync(&position_key(commitment))?.is_some() {
+                return Err(CommitmentError::InvalidData(format!(
+                    "Commitment {} is already in the tree",
+                    hex::encode(commitment)
+                )));
+            }
+        }
+
+        let mut meta = self.meta.clone();
+        let mut tracked = self.tracked.clone();
+        let mut nodes = BTreeMap::new();
+        for commitment in commitments {
+            let index = meta.size;
+            nodes.insert(position_key(commitment), index.to_be_bytes().to_vec());
+
+            // Walk up from the new leaf, pairing with the frontier or an empty subtree
+            let mut path = Vec::with_capacity(meta.depth);
+            let mut node = *commitment;
+            for level in 0..meta.depth {
+                path.push(node);
+                nodes.insert(node_key(level, index >> level), node.to_vec());
+                if (index >> level) & 1 == 0 {
+                    meta.frontier[level] = node;
+                    node = hash_children(&node, &self.empty[level]);
+                } else {
+                    node = hash_children(&meta.frontier[level], &node);
+                }
+            }
+            nodes.insert(node_key(meta.depth, 0), node.to_vec());
+            meta.root = node;
+            meta.size += 1;
+
+            // The new leaf changes exactly one sibling of each earlier note: the subtree
+            // under the level where their paths meet
+            for witness in tracked.values_mut() {
+                let level = (u64::BITS - 1 - (witness.index ^ index).leading_zeros()) as usize;
+                witness.siblings[level] = path[level];
+                witness.root = meta.root;
+            }
+        }
+
+        let mut entries: Vec<(Vec<u8>, Vec<u8>)> = nodes.into_iter().collect();
+        entries.push((META_KEY.to_vec(), serde_json::to_vec(&meta)?));
+        if !tracked.is_empty() {
+            entries.push((TRACKED_KEY.to_vec(), serde_json::to_vec(&tracked.values().collect::<Vec<_>>())?));
+        }
+        state.write_batch_sync(&entries)?;
+        self.meta = meta;
+        self.tracked = tracked;
+        Ok(first)
+    }
+
+    /// Keep the witness of `commitment` up to date from now on, as a wallet does for its notes
+    pub fn track(&mut self, state: &mut RocksStateDB, commitment: &[u8; 32]) -> Result<NoteWitness, CommitmentError> {
+        let witness = get_witness(state, commitment)?.ok_or_else(|| {
+            CommitmentError::InvalidData(format!("Commitment {} is not in the tree", hex::encode(commitment)))
+        })?;
+        self.tracked.insert(*commitment, witness.clone());
+        self.save_tracked(state)?;
+        Ok(witness)
+    }
+
+    /// Stop updating the witness of `commitment`, e.g. once the note is spent
+    pub fn untrack(&mut self, state: &mut RocksStateDB, commitment: &[u8; 32]) -> Result<(), CommitmentError> {
+        if self.tracked.remove(commitment).is_some() {
+            self.save_tracked(state)?;
+        }
+        Ok(())
+    }
+
+    /// Current witness of a tracked commitment
+    pub fn witness(&self, commitment: &[u8; 32]) -> Option<&NoteWitness> {
+        self.tracked.get(commitment)
+    }
+
+    fn save_tracked(&self, state: &mut RocksStateDB) -> Result<(), CommitmentError> {
+        let tracked: Vec<&NoteWitness> = self.tracked.values().collect();
+        state.write_batch_sync(&[(TRACKED_KEY.to_vec(), serde_json::to_vec(&tracked)?)])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commitment(i: u8) -> [u8; 32] {
+        [i + 1; 32]
+    }
+
+    #[test]
+    fn test_appends_serve_and_update_witnesses() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut tree = NoteCommitmentTree::load(&state, 4).unwrap();
+        let empty_root = tree.root();
+
+        assert_eq!(tree.append(&mut state, commitment(0)).unwrap(), 0);
+        assert_eq!(tree.append_all(&mut state, &[commitment(1), commitment(2)]).unwrap(), 1);
+        let tracked = tree.track(&mut state, &commitment(1)).unwrap();
+        assert!(tracked.verify(&tree.root()));
+        assert_ne!(tree.root(), empty_root);
+
+        for i in 3..11 {
+            tree.append(&mut state, commitment(i)).unwrap();
+            // Tracked witnesses follow every append and match the stored tree
+            let witness = tree.witness(&commitment(1)).unwrap();
+            assert!(witness.verify(&tree.root()));
+            assert_eq!(Some(witness), get_witness(&state, &commitment(1)).unwrap().as_ref());
+        }
+        for i in 0..11 {
+            let witness = get_witness(&state, &commitment(i)).unwrap().unwrap();
+            assert_eq!(witness.index, i as u64);
+            assert!(witness.verify(&tree.root()));
+        }
+        assert!(!tracked.verify(&tree.root()));
+        assert!(get_witness(&state, &[0xee; 32]).unwrap().is_none());
+
+        // Duplicates are refused, and the tree survives a reload
+        assert!(tree.append(&mut state, commitment(4)).is_err());
+        let reloaded = NoteCommitmentTree::load(&state, 4).unwrap();
+        assert_eq!((reloaded.root(), reloaded.size()), (tree.root(), 11));
+        assert_eq!(reloaded.witness(&commitment(1)), tree.witness(&commitment(1)));
+        assert!(NoteCommitmentTree::load(&state, 5).is_err());
+
+        // A full tree takes no more notes
+        tree.append_all(&mut state, &(11..16).map(commitment).collect::<Vec<_>>()).unwrap();
+        assert!(tree.append(&mut state, commitment(16)).is_err());
+        assert!(get_witness(&state, &commitment(15)).unwrap().unwrap().verify(&tree.root()));
+    }
+
+    #[test]
+    fn test_witnesses_open_the_circuit_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut tree = NoteCommitmentTree::load(&state, 4).unwrap();
+        tree.append_all(&mut state, &(0..5).map(commitment).collect::<Vec<_>>()).unwrap();
+
+        // The state-transition circuit recomputes the same root from a witness
+        let witness = get_witness(&state, &commitment(3)).unwrap().unwrap();
+        let siblings: Vec<_> = witness.siblings.iter().map(field_from_hash).collect();
+        let root = zk_proofs::state_transition::merkle_root(field_from_hash(&witness.commitment), 3, &siblings);
+        assert_eq!(field_to_bytes(&root), tree.root());
+
+        // A tree hashed before versioning has to be rebuilt
+        let meta = TreeMeta { hash_version: 0, ..tree.meta.clone() };
+        state.write_batch_sync(&[(META_KEY.to_vec(), serde_json::to_vec(&meta).unwrap())]).unwrap();
+        assert!(NoteCommitmentTree::load(&state, 4).is_err());
+    }
+}
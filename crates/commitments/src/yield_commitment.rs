@@ -1,5 +1,5 @@
 use crate::error::CommitmentError;
-use blake2::{Blake2b, Digest};
+use zk_proofs::{field_to_bytes, HashDomain, Poseidon, ZkFriendlyHash};
 
 /// Yield commitment calculator
 pub struct YieldCommitmentCalculator {
@@ -20,26 +20,9 @@ impl YieldCommitmentCalculator {
     
     /// Calculate Yield commitment
     pub fn calculate(&self, data: &[u8]) -> Result<[u8; 32], CommitmentError> {
-        // Create a Blake2b hasher
-        let mut hasher = Blake2b::new();
-        
-        // Add yield rate to the hash
-        hasher.update(&self.yield_rate.to_le_bytes());
-        
-        // Add the data
-        hasher.update(data);
-        
-        // Add a "YIELD" prefix to distinguish from other commitments
-        hasher.update(b"YIELD");
-        
-        // Finalize and get the hash
-        let result: [u8; 64] = hasher.finalize().into();
-        
-        // Convert to [u8; 32]
-        let mut commitment = [0u8; 32];
-        commitment.copy_from_slice(&result[..32]);
-        
-        Ok(commitment)
+        // The domain tag keeps HEAT and Yield commitments of the same data apart
+        let input = [self.yield_rate.to_le_bytes().as_slice(), data].concat();
+        Ok(field_to_bytes(&Poseidon::hash_bytes(HashDomain::YieldCommitment, &input)))
     }
     
     /// Set the yield rate
@@ -0,0 +1,914 @@
+use crate::error::ConsensusError;
+use crate::limits::BlockLimits;
+use crate::time;
+use crate::validation::{BlockContext, BlockRejection, BlockValidator, StateTransition};
+use crate::validators::{DoubleSignEvidence, ValidatorSet};
+use crate::{BlockProposal, ConsensusConfig};
+use block_sync::{Block, BlockHeader, Canonical};
+use hashing::Domain;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Canonical encoding of a header, signed by validators
+pub fn header_signing_bytes(header: &BlockHeader) -> Vec<u8> {
+    header.to_canonical_bytes()
+}
+
+/// Block id used for chain linkage and checkpoints
+pub fn header_id(header: &BlockHeader) -> [u8; 32] {
+    hashing::hash(Domain::Block, &header_signing_bytes(header))
+}
+
+/// Scale `difficulty` by how far `actual_span` was from `expected_span`, limited to
+/// a factor of `max_adjustment` either way
+pub fn retarget(difficulty: u64, actual_span: u64, expected_span: u64, max_adjustment: u64) -> u64 {
+    let actual = actual_span.clamp(
+        (expected_span / max_adjustment).max(1),
+        expected_span.saturating_mul(max_adjustment).max(1),
+    );
+    let next = difficulty as u128 * expected_span as u128 / actual as u128;
+    next.clamp(1, u64::MAX as u128) as u64
+}
+
+/// A finalized block: it and its ancestors can no longer be reorganized
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub hash: [u8; 32],
+    pub timestamp: u64,
+}
+
+/// Current best block of an engine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainHead {
+    pub height: u64,
+    pub hash: [u8; 32],
+}
+
+/// Result of importing a block proposal
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportOutcome {
+    pub height: u64,
+    pub hash: [u8; 32],
+    /// Number of canonical blocks the import disconnected
+    pub reorg_depth: u64,
+    /// Blocks that joined the canonical chain, oldest first and ending with this one; empty
+    /// when the block was kept on a branch that does not beat the canonical chain
+    pub connected: Vec<[u8; 32]>,
+    /// Checkpoints created by this import, oldest first
+    pub checkpoints: Vec<Checkpoint>,
+    /// Key of the proposer, which the block's fees are paid to
+    pub fee_recipient: [u8; 32],
+}
+
+/// Block validity and fork choice rules, swappable behind `Consensus`
+pub trait ConsensusEngine: Send + Sync {
+    /// Engine name
+    fn name(&self) -> &str;
+
+    /// Difficulty required of the block at `height` on the current chain
+    fn expected_difficulty(&self, height: u64) -> Result<u64, ConsensusError>;
+
+    /// Check a proposal against the current chain without importing it
+    fn validate_proposal(&self, proposal: &BlockProposal) -> Result<(), ConsensusError>;
+
+    /// Validate and import a proposal, switching the chain to its branch if that branch wins
+    /// the fork choice
+    fn import_proposal(&mut self, proposal: &BlockProposal) -> Result<ImportOutcome, ConsensusError>;
+
+    /// Current best block
+    fn head(&self) -> Option<ChainHead>;
+
+    /// Most recent finality checkpoint
+    fn latest_checkpoint(&self) -> Option<Checkpoint>;
+
+    /// Checkpoint describing the canonical block at `height`
+    fn canonical_checkpoint(&self, height: u64) -> Option<Checkpoint>;
+
+    /// Finalize a canonical block decided outside the engine, e.g. by attestations
+    fn add_checkpoint(&mut self, checkpoint: Checkpoint) -> Result<(), ConsensusError>;
+
+    /// Replace the set of validators allowed to sign blocks
+    fn set_validators(&mut self, validators: ValidatorSet);
+
+    /// Start an empty engine from `headers`, genesis first: blocks decided before it ran,
+    /// which are taken as final without being checked again
+    fn restore_chain(&mut self, headers: &[BlockHeader]) -> Result<(), ConsensusError>;
+
+    /// Drain misbehavior evidence gathered while importing proposals
+    fn take_evidence(&mut self) -> Vec<DoubleSignEvidence> {
+        Vec::new()
+    }
+
+    /// Seconds to add to the local clock when judging how far ahead a block is
+    fn set_time_offset(&mut self, _offset: i64) {}
+
+    /// Dry-run blocks' transactions through `state` before importing them
+    fn set_state_transition(&mut self, _state: Arc<dyn StateTransition>) {}
+
+    /// Nullifier root the header of `block` must carry on the state it builds on
+    fn nullifier_root(&self, _block: &Block) -> Result<[u8; 32], ConsensusError> {
+        Ok([0u8; 32])
+    }
+}
+
+/// Hybrid engine configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HybridEngineConfig {
+    pub initial_difficulty: u64,
+    /// Target seconds between blocks
+    pub target_block_time: u64,
+    /// Blocks between difficulty adjustments
+    pub retarget_interval: u64,
+    /// Maximum factor difficulty can change by per adjustment
+    pub max_adjustment: u64,
+    /// Blocks between finality checkpoints
+    pub checkpoint_interval: u64,
+    /// Confirmations a block needs before it can become a checkpoint
+    pub finality_depth: u64,
+    /// How far in the future a block timestamp may be, in seconds
+    pub max_future_drift: u64,
+    /// Size and weight every block must stay within
+    #[serde(default)]
+    pub limits: BlockLimits,
+}
+
+impl Default for HybridEngineConfig {
+    fn default() -> Self {
+        Self {
+            initial_difficulty: 1000,
+            target_block_time: 10,
+            retarget_interval: 60,
+            max_adjustment: 4,
+            checkpoint_interval: 100,
+            finality_depth: 2,
+            max_future_drift: 120,
+            limits: BlockLimits::default(),
+        }
+    }
+}
+
+impl From<&ConsensusConfig> for HybridEngineConfig {
+    fn from(config: &ConsensusConfig) -> Self {
+        Self {
+            initial_difficulty: config.pow_difficulty,
+            target_block_time: config.block_time.as_secs().max(1),
+            checkpoint_interval: config.checkpoint_interval,
+            finality_depth: config.min_finality,
+            max_future_drift: config.max_future_drift,
+            limits: config.block_limits(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChainEntry {
+    hash: [u8; 32],
+    timestamp: u64,
+    difficulty: u64,
+    /// Total difficulty of the chain up to and including this block
+    work: u128,
+}
+
+impl ChainEntry {
+    /// Whether a chain ending in this block should replace one ending in `head`: the chain
+    /// with the most work wins, the lowest block id breaking ties
+    fn beats(&self, head: &ChainEntry) -> bool {
+        self.work > head.work || (self.work == head.work && self.hash < head.hash)
+    }
+}
+
+/// Valid block off the canonical chain, kept in case its branch overtakes it
+#[derive(Debug, Clone)]
+struct SideEntry {
+    height: u64,
+    parent: [u8; 32],
+    entry: ChainEntry,
+}
+
+/// Blocks below a new block: the canonical chain up to `fork`, then the side branch
+/// it was built on
+struct Branch {
+    fork: usize,
+    side: Vec<ChainEntry>,
+}
+
+impl Branch {
+    /// The canonical chain up to `height`
+    fn canonical(height: usize) -> Self {
+        Branch { fork: height, side: Vec::new() }
+    }
+
+    fn len(&self) -> usize {
+        self.fork + self.side.len()
+    }
+}
+
+/// Merge-mined PoW blocks, each signed by a staked validator, finalized by periodic checkpoints.
+/// The canonical chain is the one with the most cumulative difficulty; valid blocks on other
+/// branches above the latest checkpoint are kept so a branch that overtakes it can replace it.
+pub struct HybridEngine {
+    config: HybridEngineConfig,
+    validators: ValidatorSet,
+    /// Canonical chain, indexed by height
+    chain: Vec<ChainEntry>,
+    /// Unfinalized blocks off the canonical chain, by id
+    side_blocks: HashMap<[u8; 32], SideEntry>,
+    checkpoints: Vec<Checkpoint>,
+    /// First signed header seen from each validator at each unfinalized height
+    signed_headers: BTreeMap<u64, HashMap<u64, (BlockHeader, Vec<u8>)>>,
+    evidence: Vec<DoubleSignEvidence>,
+    /// Network time offset applied to the local clock
+    time_offset: i64,
+    validator: BlockValidator,
+}
+
+impl HybridEngine {
+    /// Create a new hybrid engine
+    pub fn new(config: HybridEngineConfig, validators: ValidatorSet) -> Result<Self, ConsensusError> {
+        if config.retarget_interval < 2 || config.checkpoint_interval == 0 || config.max_adjustment == 0 {
+            return Err(ConsensusError::ConfigError(
+                "Retarget interval must be at least 2, checkpoint interval and max adjustment positive".to_string(),
+            ));
+        }
+        if config.initial_difficulty == 0 {
+            return Err(ConsensusError::ConfigError("Initial difficulty must be positive".to_string()));
+        }
+        config.limits.validate()?;
+
+        Ok(Self {
+            validator: BlockValidator::new(config.limits.clone(), config.max_future_drift),
+            config,
+            validators,
+            chain: Vec::new(),
+            side_blocks: HashMap::new(),
+            checkpoints: Vec::new(),
+            signed_headers: BTreeMap::new(),
+            evidence: Vec::new(),
+            time_offset: 0,
+        })
+    }
+
+    /// Validators allowed to sign blocks
+    pub fn validators(&self) -> &ValidatorSet {
+        &self.validators
+    }
+
+    /// All checkpoints, oldest first
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Append a header already known to be final to the canonical chain, without checking it,
+    /// and make it the only checkpoint. It has to extend the tip.
+    pub fn extend_final(&mut self, header: &BlockHeader) -> Result<(), ConsensusError> {
+        let parent = self.chain.last();
+        if header.height != self.chain.len() as u64 || parent.is_some_and(|parent| parent.hash != header.prev_hash) {
+            return Err(BlockRejection::UnknownParent(header.height).into());
+        }
+        let entry = ChainEntry {
+            hash: header_id(header),
+            timestamp: header.timestamp,
+            difficulty: header.difficulty,
+            work: parent.map_or(0, |parent| parent.work) + header.difficulty as u128,
+        };
+        self.checkpoints = vec![Checkpoint {
+            height: header.height,
+            hash: entry.hash,
+            timestamp: entry.timestamp,
+        }];
+        self.chain.push(entry);
+        Ok(())
+    }
+
+    /// What the branch a block with `header` builds on requires of it, rejecting headers that
+    /// conflict with finality, repeat a known block or have no known parent
+    pub fn block_context(&self, header: &BlockHeader) -> Result<BlockContext, ConsensusError> {
+        Ok(self.branch_context(header)?.1)
+    }
+
+    /// The branch below a block with `header` and what it requires of the block
+    fn branch_context(&self, header: &BlockHeader) -> Result<(Branch, BlockContext), ConsensusError> {
+        let height = header.height;
+        if self.finalized_height().is_some_and(|finalized| height <= finalized) {
+            return Err(BlockRejection::ConflictsWithFinalized(height).into());
+        }
+        let hash = header_id(header);
+        let canonical = self.chain.get(height as usize).is_some_and(|entry| entry.hash == hash);
+        if canonical || self.side_blocks.contains_key(&hash) {
+            return Err(BlockRejection::AlreadyKnown(height).into());
+        }
+        let branch = self.branch(height, header.prev_hash)?;
+        let context = BlockContext {
+            parent_hash: height.checked_sub(1).map(|parent| self.entry(&branch, parent as usize).hash),
+            median_time_past: self.branch_median_time_past(&branch),
+            adjusted_time: time::local_time().saturating_add_signed(self.time_offset),
+            expected_difficulty: self.next_difficulty(&branch),
+        };
+        Ok((branch, context))
+    }
+
+    /// Blocks below one at `height` on `parent`, following side branches back to the canonical chain
+    fn branch(&self, height: u64, parent: [u8; 32]) -> Result<Branch, BlockRejection> {
+        let mut side = Vec::new();
+        let (mut below, mut hash) = (height, parent);
+        while below > 0 {
+            if self.chain.get(below as usize - 1).is_some_and(|entry| entry.hash == hash) {
+                break;
+            }
+            match self.side_blocks.get(&hash) {
+                Some(block) if block.height + 1 == below => {
+                    side.push(block.entry.clone());
+                    hash = block.parent;
+                    below -= 1;
+                }
+                _ => return Err(BlockRejection::UnknownParent(height)),
+            }
+        }
+        side.reverse();
+        Ok(Branch { fork: below as usize, side })
+    }
+
+    /// Block at `height` of `branch`
+    fn entry<'a>(&'a self, branch: &'a Branch, height: usize) -> &'a ChainEntry {
+        match height.checked_sub(branch.fork) {
+            Some(offset) => &branch.side[offset],
+            None => &self.chain[height],
+        }
+    }
+
+    /// Median timestamp of the canonical blocks before `height`, which a block at `height` must exceed
+    pub fn median_time_past(&self, height: u64) -> Option<u64> {
+        self.branch_median_time_past(&Branch::canonical((height as usize).min(self.chain.len())))
+    }
+
+    fn branch_median_time_past(&self, branch: &Branch) -> Option<u64> {
+        let start = branch.len().saturating_sub(time::MEDIAN_TIME_SPAN);
+        let timestamps: Vec<u64> = (start..branch.len()).map(|height| self.entry(branch, height).timestamp).collect();
+        time::median_time_past(&timestamps)
+    }
+
+    /// Difficulty required of the next block on `branch`
+    fn next_difficulty(&self, branch: &Branch) -> u64 {
+        let height = branch.len();
+        if height == 0 {
+            return self.config.initial_difficulty;
+        }
+
+        let interval = self.config.retarget_interval as usize;
+        let previous = self.entry(branch, height - 1);
+        if !height.is_multiple_of(interval) {
+            return previous.difficulty;
+        }
+
+        // Blocks height - interval ..= height - 1 span interval - 1 block times
+        let first = self.entry(branch, height - interval);
+        let actual_span = previous.timestamp.saturating_sub(first.timestamp);
+        let expected_span = self.config.target_block_time * (interval as u64 - 1);
+        retarget(previous.difficulty, actual_span, expected_span, self.config.max_adjustment)
+    }
+
+    /// Check `proposal` against what its branch requires, returning who its fees are paid to
+    fn check_proposal(&self, proposal: &BlockProposal, context: &BlockContext) -> Result<[u8; 32], ConsensusError> {
+        let block = &proposal.block;
+        // The block's fees are paid to its proposer, which must be staked before the block is executed
+        let fee_recipient = match self.validators.get(proposal.proposer) {
+            Some(validator) if self.validators.is_active(validator.id) => validator.public_key,
+            _ => {
+                let reason = format!("Validator {} is not staked", proposal.proposer);
+                return Err(BlockRejection::InvalidSignature(reason).into());
+            }
+        };
+        self.validator.validate(block, context, &fee_recipient)?;
+
+        // Validator signature
+        self.validators
+            .verify(proposal.proposer, &header_signing_bytes(&block.header), &proposal.signature)
+            .map_err(|e| BlockRejection::InvalidSignature(e.to_string()))?;
+        Ok(fee_recipient)
+    }
+
+    /// Drop side blocks at or below a newly finalized height
+    fn prune_side_blocks(&mut self, finalized: u64) {
+        self.side_blocks.retain(|_, block| block.height > finalized);
+    }
+
+    fn finalized_height(&self) -> Option<u64> {
+        self.checkpoints.last().map(|checkpoint| checkpoint.height)
+    }
+
+    /// Remember the proposer's signed header, recording evidence if it signed a different one before
+    fn record_signature(&mut self, proposal: &BlockProposal) {
+        let header = &proposal.block.header;
+        let signing_bytes = header_signing_bytes(header);
+        if self.validators.verify(proposal.proposer, &signing_bytes, &proposal.signature).is_err() {
+            return;
+        }
+
+        let signed = self.signed_headers.entry(header.height).or_default();
+        match signed.get(&proposal.proposer) {
+            Some((first_header, first_signature)) if header_id(first_header) != header_id(header) => {
+                println!("Validator {} signed two blocks at height {}", proposal.proposer, header.height);
+                self.evidence.push(DoubleSignEvidence {
+                    validator_id: proposal.proposer,
+                    height: header.height,
+                    first_header: first_header.clone(),
+                    first_signature: first_signature.clone(),
+                    second_header: header.clone(),
+                    second_signature: proposal.signature.clone(),
+                });
+            }
+            Some(_) => {}
+            None => {
+                signed.insert(proposal.proposer, (header.clone(), proposal.signature.clone()));
+            }
+        }
+    }
+}
+
+impl ConsensusEngine for HybridEngine {
+    fn name(&self) -> &str {
+        "pow-validator-hybrid"
+    }
+
+    fn expected_difficulty(&self, height: u64) -> Result<u64, ConsensusError> {
+        if height > self.chain.len() as u64 {
+            return Err(ConsensusError::BlockValidationFailed(format!("Unknown parent for height {}", height)));
+        }
+        Ok(self.next_difficulty(&Branch::canonical(height as usize)))
+    }
+
+    fn validate_proposal(&self, proposal: &BlockProposal) -> Result<(), ConsensusError> {
+        let (_, context) = self.branch_context(&proposal.block.header)?;
+        self.check_proposal(proposal, &context).map(|_| ())
+    }
+
+    fn import_proposal(&mut self, proposal: &BlockProposal) -> Result<ImportOutcome, ConsensusError> {
+        let header = &proposal.block.header;
+        let (branch, context) = self.branch_context(header)?;
+        let fee_recipient = self.check_proposal(proposal, &context)?;
+        // Only blocks that pass validation count as signed, so a header nobody could import is no evidence
+        self.record_signature(proposal);
+        let height = header.height;
+        let hash = header_id(header);
+        let parent_work = height.checked_sub(1).map_or(0, |parent| self.entry(&branch, parent as usize).work);
+        let entry = ChainEntry {
+            hash,
+            timestamp: header.timestamp,
+            difficulty: header.difficulty,
+            work: parent_work + header.difficulty as u128,
+        };
+
+        if self.chain.last().is_some_and(|head| !entry.beats(head)) {
+            println!("Keeping block {} on a side branch, it does not beat the canonical chain", height);
+            self.side_blocks.insert(hash, SideEntry { height, parent: header.prev_hash, entry });
+            return Ok(ImportOutcome {
+                height,
+                hash,
+                reorg_depth: 0,
+                connected: Vec::new(),
+                checkpoints: Vec::new(),
+                fee_recipient,
+            });
+        }
+
+        // Blocks leaving the chain are kept, so it can switch back if their branch overtakes again
+        let reorg_depth = (self.chain.len() - branch.fork) as u64;
+        if reorg_depth > 0 {
+            println!("Reorganizing {} blocks at height {}", reorg_depth, branch.fork);
+        }
+        let mut parent = branch.fork.checked_sub(1).map_or(header.prev_hash, |below| self.chain[below].hash);
+        for (offset, disconnected) in self.chain.split_off(branch.fork).into_iter().enumerate() {
+            let next = disconnected.hash;
+            let height = (branch.fork + offset) as u64;
+            self.side_blocks.insert(next, SideEntry { height, parent, entry: disconnected });
+            parent = next;
+        }
+        let mut connected = Vec::new();
+        for entry in branch.side.into_iter().chain(std::iter::once(entry)) {
+            self.side_blocks.remove(&entry.hash);
+            connected.push(entry.hash);
+            self.chain.push(entry);
+        }
+
+        // Every interval boundary the import pushes finality_depth below the head becomes a checkpoint,
+        // including boundaries skipped by a reorg that connected several blocks at once
+        let mut checkpoints = Vec::new();
+        if let Some(deepest) = height.checked_sub(self.config.finality_depth) {
+            let interval = self.config.checkpoint_interval;
+            let first = self.finalized_height().map_or(1, |finalized| finalized + 1).next_multiple_of(interval);
+            for candidate in (first..=deepest).step_by(interval as usize) {
+                let entry = &self.chain[candidate as usize];
+                let new_checkpoint = Checkpoint {
+                    height: candidate,
+                    hash: entry.hash,
+                    timestamp: entry.timestamp,
+                };
+                println!("Finalized checkpoint at height {}", candidate);
+                self.checkpoints.push(new_checkpoint.clone());
+                checkpoints.push(new_checkpoint);
+            }
+        }
+        if let Some(finalized) = self.finalized_height().filter(|_| !checkpoints.is_empty()) {
+            self.signed_headers = self.signed_headers.split_off(&(finalized + 1));
+            self.prune_side_blocks(finalized);
+        }
+
+        Ok(ImportOutcome {
+            height,
+            hash,
+            reorg_depth,
+            connected,
+            checkpoints,
+            fee_recipient,
+        })
+    }
+
+    fn head(&self) -> Option<ChainHead> {
+        self.chain.last().map(|entry| ChainHead {
+            height: self.chain.len() as u64 - 1,
+            hash: entry.hash,
+        })
+    }
+
+    fn latest_checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoints.last().cloned()
+    }
+
+    fn canonical_checkpoint(&self, height: u64) -> Option<Checkpoint> {
+        self.chain.get(height as usize).map(|entry| Checkpoint {
+            height,
+            hash: entry.hash,
+            timestamp: entry.timestamp,
+        })
+    }
+
+    fn add_checkpoint(&mut self, checkpoint: Checkpoint) -> Result<(), ConsensusError> {
+        if self.canonical_checkpoint(checkpoint.height).as_ref() != Some(&checkpoint) {
+            return Err(ConsensusError::BlockValidationFailed(format!(
+                "Checkpoint at height {} is not on the canonical chain",
+                checkpoint.height
+            )));
+        }
+        if self.finalized_height().is_none_or(|finalized| checkpoint.height > finalized) {
+            self.signed_headers = self.signed_headers.split_off(&(checkpoint.height + 1));
+            self.prune_side_blocks(checkpoint.height);
+            self.checkpoints.push(checkpoint);
+        }
+        Ok(())
+    }
+
+    fn set_validators(&mut self, validators: ValidatorSet) {
+        self.validators = validators;
+    }
+
+    fn restore_chain(&mut self, headers: &[BlockHeader]) -> Result<(), ConsensusError> {
+        if !self.chain.is_empty() {
+            return Err(ConsensusError::ConfigError("Only an empty engine can restore a chain".to_string()));
+        }
+        for header in headers {
+            if let Err(e) = self.extend_final(header) {
+                self.chain.clear();
+                self.checkpoints.clear();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn take_evidence(&mut self) -> Vec<DoubleSignEvidence> {
+        std::mem::take(&mut self.evidence)
+    }
+
+    fn set_time_offset(&mut self, offset: i64) {
+        self.time_offset = offset;
+    }
+
+    fn set_state_transition(&mut self, state: Arc<dyn StateTransition>) {
+        self.validator.set_state_transition(state);
+    }
+
+    fn nullifier_root(&self, block: &Block) -> Result<[u8; 32], ConsensusError> {
+        Ok(self.validator.nullifier_root(block)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::validators::Validator;
+    use block_sync::{Block, BlockProof, ProofType, Transaction};
+    use ed25519_dalek::{Signer, SigningKey};
+    use pow::auxpow::bare_coinbase;
+    use pow::{AuxPow, CryptoNight, MergeMinedProof, ParentBlockHeader};
+
+    pub(crate) fn test_validators(key: &SigningKey) -> ValidatorSet {
+        let mut validators = ValidatorSet::new(100);
+        validators
+            .insert(Validator {
+                id: 1,
+                public_key: key.verifying_key().to_bytes(),
+                stake: 1000,
+            })
+            .unwrap();
+        validators
+    }
+
+    /// Block at `height` on `prev` with a merge-mined proof meeting difficulty 1
+    pub(crate) fn mined_proposal(key: &SigningKey, height: u64, prev_hash: [u8; 32], timestamp: u64) -> BlockProposal {
+        mined_proposal_with(key, height, prev_hash, timestamp, vec![])
+    }
+
+    /// Block like `mined_proposal` carrying `transactions` under its merkle root
+    pub(crate) fn mined_proposal_with(
+        key: &SigningKey,
+        height: u64,
+        prev_hash: [u8; 32],
+        timestamp: u64,
+        transactions: Vec<Transaction>,
+    ) -> BlockProposal {
+        mined_proposal_at(key, height, prev_hash, timestamp, transactions, 1)
+    }
+
+    /// Block like `mined_proposal_with` whose parent header is ground until it meets `difficulty`
+    fn mined_proposal_at(
+        key: &SigningKey,
+        height: u64,
+        prev_hash: [u8; 32],
+        timestamp: u64,
+        transactions: Vec<Transaction>,
+        difficulty: u64,
+    ) -> BlockProposal {
+        let header = BlockHeader {
+            height,
+            prev_hash,
+            merkle_root: crate::merkle_root(&transactions),
+            timestamp,
+            nonce: 0,
+            difficulty,
+            nullifier_root: [0u8; 32],
+        };
+        let mut parent_header = ParentBlockHeader {
+            major_version: 1,
+            timestamp,
+            ..Default::default()
+        };
+        let aux_hash = header.hash().unwrap();
+        let aux_pow = AuxPow::create(aux_hash, bare_coinbase(height), &[], &mut parent_header).unwrap();
+        let hasher = CryptoNight::upx2();
+        while difficulty > 1 && aux_pow.verify_work(&parent_header, &aux_hash, difficulty, &hasher).is_err() {
+            parent_header.nonce += 1;
+        }
+        let proof = MergeMinedProof { parent_header, aux_pow };
+
+        let signature = key.sign(&header_signing_bytes(&header)).to_bytes().to_vec();
+        BlockProposal {
+            block: Block {
+                header,
+                transactions,
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: serde_json::to_vec(&proof).unwrap(),
+                },
+            },
+            proposer: 1,
+            timestamp,
+            signature,
+        }
+    }
+
+    pub(crate) fn test_engine_config() -> HybridEngineConfig {
+        HybridEngineConfig {
+            initial_difficulty: 1,
+            retarget_interval: 1000,
+            checkpoint_interval: 2,
+            finality_depth: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hybrid_engine_imports_and_checkpoints() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+
+        let mut prev = [0u8; 32];
+        let mut ids = Vec::new();
+        for height in 0..4 {
+            let outcome = engine.import_proposal(&mined_proposal(&key, height, prev, 1_000 + height)).unwrap();
+            assert_eq!(outcome.reorg_depth, 0);
+            // Block 2 is finalized once block 3 confirms it
+            assert_eq!(outcome.checkpoints.len(), usize::from(height == 3));
+            prev = outcome.hash;
+            ids.push(outcome.hash);
+        }
+        assert_eq!(engine.head().unwrap(), ChainHead { height: 3, hash: ids[3] });
+        assert_eq!(engine.latest_checkpoint().unwrap().height, 2);
+
+        // A competing branch with more work reorganizes the unfinalized tip
+        let competing = engine.import_proposal(&mined_proposal(&key, 3, ids[2], 2_000)).unwrap();
+        let outcome = engine.import_proposal(&mined_proposal(&key, 4, competing.hash, 2_001)).unwrap();
+        assert_eq!(competing.reorg_depth + outcome.reorg_depth, 1);
+        assert_eq!(engine.head().unwrap(), ChainHead { height: 4, hash: outcome.hash });
+        assert_eq!(engine.canonical_checkpoint(3).unwrap().hash, competing.hash);
+
+        // Nothing at or below the checkpoint can be replaced
+        let err = engine.import_proposal(&mined_proposal(&key, 2, ids[1], 2_000)).unwrap_err();
+        assert!(err.to_string().contains("finalized"));
+    }
+
+    #[test]
+    fn test_fork_choice_follows_the_most_work() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let config = HybridEngineConfig { checkpoint_interval: 100, ..test_engine_config() };
+        let mut engine = HybridEngine::new(config, test_validators(&key)).unwrap();
+        let mut ids = Vec::new();
+        for height in 0..6 {
+            let prev = ids.last().copied().unwrap_or([0u8; 32]);
+            ids.push(engine.import_proposal(&mined_proposal(&key, height, prev, 1_000 + height)).unwrap().hash);
+        }
+
+        // A valid block below the tip is kept aside instead of replacing the blocks above it
+        let side = engine.import_proposal(&mined_proposal(&key, 2, ids[1], 2_000)).unwrap();
+        assert_eq!((side.reorg_depth, side.connected.len()), (0, 0));
+        assert_eq!(engine.head().unwrap(), ChainHead { height: 5, hash: ids[5] });
+        assert_eq!(engine.canonical_checkpoint(2).unwrap().hash, ids[2]);
+        let err = engine.import_proposal(&mined_proposal(&key, 2, ids[1], 2_000)).unwrap_err();
+        assert!(err.to_string().contains("already known"), "{}", err);
+
+        // The side branch grows until it has more work, then replaces the canonical blocks
+        let mut branch = vec![side.hash];
+        for height in 3..=6 {
+            let parent = branch[branch.len() - 1];
+            let outcome = engine.import_proposal(&mined_proposal(&key, height, parent, 2_000 + height)).unwrap();
+            if height < 5 {
+                assert!(outcome.connected.is_empty());
+                assert_eq!(engine.head().unwrap().hash, ids[5]);
+            }
+            branch.push(outcome.hash);
+        }
+        assert_eq!(engine.head().unwrap(), ChainHead { height: 6, hash: branch[4] });
+        assert_eq!(engine.canonical_checkpoint(2).unwrap().hash, branch[0]);
+
+        // The old blocks were kept too, so the chain can switch back to them
+        let back = engine.import_proposal(&mined_proposal(&key, 6, ids[5], 3_000)).unwrap();
+        let outcome = engine.import_proposal(&mined_proposal(&key, 7, back.hash, 3_001)).unwrap();
+        assert_eq!(back.reorg_depth + outcome.reorg_depth, 5);
+        assert_eq!(engine.head().unwrap(), ChainHead { height: 7, hash: outcome.hash });
+        assert_eq!(engine.canonical_checkpoint(5).unwrap().hash, ids[5]);
+    }
+
+    #[test]
+    fn test_reorg_checkpoints_every_boundary_it_connects() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let config = HybridEngineConfig { retarget_interval: 2, ..test_engine_config() };
+        let mut engine = HybridEngine::new(config, test_validators(&key)).unwrap();
+
+        // Fast blocks retarget block 2 to difficulty 5, leaving a short chain with nothing final yet
+        let genesis = engine.import_proposal(&mined_proposal(&key, 0, [0u8; 32], 1_000)).unwrap();
+        let first = engine.import_proposal(&mined_proposal(&key, 1, genesis.hash, 1_001)).unwrap();
+        assert_eq!(engine.expected_difficulty(2).unwrap(), 5);
+        let head = engine.import_proposal(&mined_proposal_at(&key, 2, first.hash, 1_002, vec![], 5)).unwrap();
+        assert!(head.checkpoints.is_empty());
+
+        // A slow branch stays at difficulty 1 and builds up to height 5 without overtaking it
+        let mut parent = genesis.hash;
+        for (height, timestamp) in [(1, 1_100), (2, 1_200), (3, 1_300), (4, 1_400), (5, 1_401)] {
+            let outcome = engine.import_proposal(&mined_proposal(&key, height, parent, timestamp)).unwrap();
+            assert!(outcome.connected.is_empty());
+            parent = outcome.hash;
+        }
+
+        // Then a heavy block connects all six at once, pushing both boundaries 2 and 4 past finality depth
+        let outcome = engine.import_proposal(&mined_proposal_at(&key, 6, parent, 1_402, vec![], 5)).unwrap();
+        assert_eq!(outcome.connected.len(), 6);
+        let heights: Vec<u64> = outcome.checkpoints.iter().map(|checkpoint| checkpoint.height).collect();
+        assert_eq!(heights, vec![2, 4]);
+        assert_eq!(engine.latest_checkpoint().unwrap().height, 4);
+        assert_eq!(engine.canonical_checkpoint(2).unwrap(), outcome.checkpoints[0]);
+    }
+
+    #[test]
+    fn test_restored_chain_is_final_and_built_on() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut stored = Vec::new();
+        for height in 0..3 {
+            let prev = stored.last().map_or([0u8; 32], header_id);
+            stored.push(mined_proposal(&key, height, prev, 1_000 + height).block.header);
+        }
+        let mut engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+        let mut gap = stored.clone();
+        gap.remove(1);
+        assert!(engine.restore_chain(&gap).is_err());
+        engine.restore_chain(&stored).unwrap();
+        assert!(engine.restore_chain(&stored).is_err());
+
+        // New blocks build on the stored tip, which cannot be reorganized away
+        let tip = header_id(&stored[2]);
+        assert_eq!(engine.head().unwrap(), ChainHead { height: 2, hash: tip });
+        assert_eq!(engine.latest_checkpoint().unwrap().hash, tip);
+        assert_eq!(engine.expected_difficulty(3).unwrap(), 1);
+        engine.import_proposal(&mined_proposal(&key, 3, tip, 1_003)).unwrap();
+        let err = engine.import_proposal(&mined_proposal(&key, 2, header_id(&stored[1]), 2_000)).unwrap_err();
+        assert!(err.to_string().contains("finalized"), "{}", err);
+    }
+
+    #[test]
+    fn test_hybrid_engine_rejections() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+
+        // Signed by a key outside the validator set
+        let outsider = SigningKey::from_bytes(&[8u8; 32]);
+        let err = engine.validate_proposal(&mined_proposal(&outsider, 0, [0u8; 32], 1_000)).unwrap_err();
+        assert!(err.to_string().contains("Bad signature"));
+
+        let mut unknown = mined_proposal(&key, 0, [0u8; 32], 1_000);
+        unknown.proposer = 2;
+        assert!(engine.validate_proposal(&unknown).unwrap_err().to_string().contains("not staked"));
+
+        let mut no_pow = mined_proposal(&key, 0, [0u8; 32], 1_000);
+        no_pow.block.proof.proof_data.clear();
+        assert!(engine.validate_proposal(&no_pow).unwrap_err().to_string().contains("proof of work"));
+
+        let mut wrong_difficulty = mined_proposal(&key, 0, [0u8; 32], 1_000);
+        wrong_difficulty.block.header.difficulty = 5;
+        assert!(engine.validate_proposal(&wrong_difficulty).unwrap_err().to_string().contains("Difficulty"));
+
+        let small = HybridEngineConfig {
+            limits: BlockLimits {
+                max_block_bytes: 256,
+                max_transaction_bytes: 256,
+                ..Default::default()
+            },
+            ..test_engine_config()
+        };
+        let small = HybridEngine::new(small, test_validators(&key)).unwrap();
+        let oversize = small.validate_proposal(&mined_proposal(&key, 0, [0u8; 32], 1_000)).unwrap_err();
+        assert!(oversize.to_string().contains("over the limit"));
+
+        assert!(engine.validate_proposal(&mined_proposal(&key, 1, [0u8; 32], 1_000)).is_err());
+        assert!(engine.validate_proposal(&mined_proposal(&key, 0, [0u8; 32], 1_000)).is_ok());
+    }
+
+    #[test]
+    fn test_timestamp_rules() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+        let mut prev = [0u8; 32];
+        for (height, timestamp) in [1_000, 1_010, 1_020].into_iter().enumerate() {
+            prev = engine.import_proposal(&mined_proposal(&key, height as u64, prev, timestamp)).unwrap().hash;
+        }
+        assert_eq!(engine.median_time_past(3), Some(1_010));
+
+        // Earlier than the parent is fine as long as it is after the median time past
+        let err = engine.validate_proposal(&mined_proposal(&key, 3, prev, 1_010)).unwrap_err();
+        assert!(err.to_string().contains("median time past"));
+        assert!(engine.validate_proposal(&mined_proposal(&key, 3, prev, 1_011)).is_ok());
+
+        let ahead = time::local_time() + 10_000;
+        let err = engine.validate_proposal(&mined_proposal(&key, 3, prev, ahead)).unwrap_err();
+        assert!(err.to_string().contains("future"));
+        engine.set_time_offset(10_000);
+        assert!(engine.validate_proposal(&mined_proposal(&key, 3, prev, ahead)).is_ok());
+    }
+
+    #[test]
+    fn test_double_sign_evidence() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+        let genesis = engine.import_proposal(&mined_proposal(&key, 0, [0u8; 32], 1_000)).unwrap();
+        engine.import_proposal(&mined_proposal(&key, 1, genesis.hash, 1_001)).unwrap();
+        assert!(engine.take_evidence().is_empty());
+
+        // A signed competing header whose block fails validation is not evidence
+        let mut no_pow = mined_proposal(&key, 1, genesis.hash, 1_002);
+        no_pow.block.proof.proof_data.clear();
+        assert!(engine.import_proposal(&no_pow).is_err());
+        assert!(engine.take_evidence().is_empty());
+
+        // The same validator signs a competing block 1
+        engine.import_proposal(&mined_proposal(&key, 1, genesis.hash, 1_002)).unwrap();
+        let evidence = engine.take_evidence();
+        assert_eq!(evidence.len(), 1);
+        assert_eq!(evidence[0].validator_id, 1);
+        assert_eq!(evidence[0].height, 1);
+        evidence[0].verify(&key.verifying_key().to_bytes()).unwrap();
+        assert!(evidence[0].verify(&SigningKey::from_bytes(&[8u8; 32]).verifying_key().to_bytes()).is_err());
+        assert!(engine.take_evidence().is_empty());
+    }
+
+    #[test]
+    fn test_retarget() {
+        assert_eq!(retarget(1000, 100, 100, 4), 1000);
+        // Blocks twice as fast double the difficulty
+        assert_eq!(retarget(1000, 50, 100, 4), 2000);
+        assert_eq!(retarget(1000, 200, 100, 4), 500);
+        // Adjustments are clamped
+        assert_eq!(retarget(1000, 0, 100, 4), 4000);
+        assert_eq!(retarget(1000, 10_000, 100, 4), 250);
+        assert_eq!(retarget(1, 10_000, 100, 4), 1);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::validation::BlockRejection;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,6 +11,9 @@ pub enum ConsensusError {
     
     #[error("Block validation failed: {0}")]
     BlockValidationFailed(String),
+
+    #[error("Block rejected at {} stage: {0}", .0.stage())]
+    BlockRejected(BlockRejection),
     
     #[error("HotStuff consensus error: {0}")]
     HotStuffError(String),
@@ -29,6 +33,9 @@ pub enum ConsensusError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
     
+    #[error("Signer error: {0}")]
+    SignerError(String),
+    
     #[error("Serialization error: {0}")]
     SerializationError(String),
     
@@ -42,6 +49,12 @@ pub enum ConsensusError {
     Unknown(String),
 }
 
+impl From<BlockRejection> for ConsensusError {
+    fn from(rejection: BlockRejection) -> Self {
+        ConsensusError::BlockRejected(rejection)
+    }
+}
+
 impl From<std::io::Error> for ConsensusError {
     fn from(err: std::io::Error) -> Self {
         ConsensusError::IoError(err.to_string())
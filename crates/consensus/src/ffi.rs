@@ -124,7 +124,10 @@ impl FuegoHash {
         
         #[cfg(not(feature = "ffi"))]
         {
-            self.hash(header_data)
+            // Fuego block headers are mined with CN-UPX/2
+            use pow::PowHasher;
+
+            Ok(pow::CryptoNight::upx2().hash(header_data))
         }
     }
     
@@ -194,6 +197,7 @@ mod tests {
         
         let hash = result.unwrap();
         assert_eq!(hash.len(), 32);
+        assert_eq!(hash, pow::CryptoNight::upx2().digest(header_data));
     }
     
     #[test]
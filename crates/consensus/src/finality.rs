@@ -0,0 +1,292 @@
+use crate::engine::Checkpoint;
+use block_sync::Canonical;
+use crate::error::ConsensusError;
+use crate::validators::ValidatorSet;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const CHECKPOINT_PREFIX: &str = "finality/checkpoint/";
+const HEAD_KEY: &[u8] = b"finality/head";
+
+/// Domain separator so attestation signatures cannot be replayed as block signatures
+const ATTESTATION_DOMAIN: &[u8] = b"C0DL3-attestation";
+
+/// Basis points in one whole
+const BPS: u128 = 10_000;
+
+/// Attestation signatures for one height, by block hash and validator
+type HeightAttestations = HashMap<[u8; 32], HashMap<u64, Vec<u8>>>;
+
+/// Message a validator signs to attest to the block `hash` at `height`
+pub fn attestation_signing_bytes(height: u64, hash: &[u8; 32]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(ATTESTATION_DOMAIN.len() + 40);
+    bytes.extend_from_slice(ATTESTATION_DOMAIN);
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes.extend_from_slice(hash);
+    bytes
+}
+
+/// A validator's vote that a block belongs in the final chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub validator_id: u64,
+    pub height: u64,
+    pub hash: [u8; 32],
+    pub signature: Vec<u8>,
+}
+
+impl Canonical for Attestation {}
+
+/// Finality gadget configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalityConfig {
+    /// Blocks that must be built on top of a block before it can be final
+    pub confirmation_depth: u64,
+    /// Share of active stake that must attest, in basis points
+    pub quorum_bps: u64,
+}
+
+impl Default for FinalityConfig {
+    fn default() -> Self {
+        Self {
+            confirmation_depth: 6,
+            quorum_bps: 6667,
+        }
+    }
+}
+
+/// Collects validator attestations and tracks the finalized head, persisting
+/// finalized checkpoints in a StateDB when one is attached
+pub struct FinalityGadget {
+    config: FinalityConfig,
+    validators: ValidatorSet,
+    attestations: BTreeMap<u64, HeightAttestations>,
+    finalized_head: Option<Checkpoint>,
+    db: Option<Arc<RwLock<RocksStateDB>>>,
+}
+
+impl FinalityGadget {
+    /// Create an in-memory finality gadget
+    pub fn new(config: FinalityConfig) -> Result<Self, ConsensusError> {
+        if config.quorum_bps == 0 || config.quorum_bps > BPS as u64 {
+            return Err(ConsensusError::ConfigError("Quorum must be between 1 and 10000 bps".to_string()));
+        }
+        Ok(Self {
+            config,
+            validators: ValidatorSet::default(),
+            attestations: BTreeMap::new(),
+            finalized_head: None,
+            db: None,
+        })
+    }
+
+    /// Load the finalized head from `db` and store new checkpoints in it
+    pub async fn with_state_db(config: FinalityConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, ConsensusError> {
+        let mut gadget = Self::new(config)?;
+        let head = db
+            .read()
+            .await
+            .get_sync(HEAD_KEY)
+            .map_err(|e| ConsensusError::StateError(e.to_string()))?;
+        if let Some(head) = head {
+            gadget.finalized_head = Some(serde_json::from_slice(&head)?);
+        }
+        gadget.db = Some(db);
+        Ok(gadget)
+    }
+
+    /// Blocks that must be built on top of a block before it can be final
+    pub fn confirmation_depth(&self) -> u64 {
+        self.config.confirmation_depth
+    }
+
+    /// Replace the validators whose attestations count
+    pub fn set_validators(&mut self, validators: ValidatorSet) {
+        self.validators = validators;
+    }
+
+    /// Record a validator attestation after checking its signature
+    pub fn add_attestation(&mut self, attestation: &Attestation) -> Result<(), ConsensusError> {
+        if self.finalized_head.as_ref().is_some_and(|head| attestation.height <= head.height) {
+            return Err(ConsensusError::InvalidBlockProposal(format!(
+                "Height {} is already final",
+                attestation.height
+            )));
+        }
+        let message = attestation_signing_bytes(attestation.height, &attestation.hash);
+        self.validators
+            .verify(attestation.validator_id, &message, &attestation.signature)?;
+
+        self.attestations
+            .entry(attestation.height)
+            .or_default()
+            .entry(attestation.hash)
+            .or_default()
+            .insert(attestation.validator_id, attestation.signature.clone());
+        Ok(())
+    }
+
+    /// Stake of active validators attesting to `hash` at `height`
+    pub fn attested_stake(&self, height: u64, hash: &[u8; 32]) -> u64 {
+        self.attestations
+            .get(&height)
+            .and_then(|by_hash| by_hash.get(hash))
+            .map(|votes| {
+                votes
+                    .keys()
+                    .filter(|id| self.validators.is_active(**id))
+                    .filter_map(|id| self.validators.get(*id))
+                    .map(|validator| validator.stake)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Check if attestations to `hash` at `height` reach the stake quorum
+    pub fn has_quorum(&self, height: u64, hash: &[u8; 32]) -> bool {
+        let total = self.validators.total_stake() as u128;
+        total > 0 && self.attested_stake(height, hash) as u128 * BPS >= total * self.config.quorum_bps as u128
+    }
+
+    /// Heights with pending attestations, highest first
+    pub fn attested_heights(&self) -> Vec<u64> {
+        self.attestations.keys().rev().copied().collect()
+    }
+
+    /// Check if a block at `height` has enough confirmations below `head_height`
+    pub fn is_confirmed(&self, height: u64, head_height: u64) -> bool {
+        height + self.config.confirmation_depth <= head_height
+    }
+
+    /// Record a new finalized checkpoint, storing it in the StateDB
+    pub async fn record_finalized(&mut self, checkpoint: Checkpoint) -> Result<(), ConsensusError> {
+        if self.finalized_head.as_ref().is_some_and(|head| checkpoint.height <= head.height) {
+            return Ok(());
+        }
+        if let Some(db) = &self.db {
+            let data = serde_json::to_vec(&checkpoint)?;
+            let key = format!("{}{:020}", CHECKPOINT_PREFIX, checkpoint.height);
+            db.write()
+                .await
+                .write_batch_sync(&[(key.into_bytes(), data.clone()), (HEAD_KEY.to_vec(), data)])
+                .map_err(|e| ConsensusError::StateError(e.to_string()))?;
+        }
+
+        self.attestations = self.attestations.split_off(&(checkpoint.height + 1));
+        self.finalized_head = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Load the stored checkpoint at `height`
+    pub async fn get_checkpoint(&self, height: u64) -> Result<Option<Checkpoint>, ConsensusError> {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return Ok(self.finalized_head.clone().filter(|head| head.height == height)),
+        };
+        let key = format!("{}{:020}", CHECKPOINT_PREFIX, height);
+        let data = db
+            .read()
+            .await
+            .get_sync(key.as_bytes())
+            .map_err(|e| ConsensusError::StateError(e.to_string()))?;
+        Ok(match data {
+            Some(data) => Some(serde_json::from_slice(&data)?),
+            None => None,
+        })
+    }
+
+    /// Highest finalized block
+    pub fn finalized_head(&self) -> Option<Checkpoint> {
+        self.finalized_head.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validators::Validator;
+    use ed25519_dalek::{Signer, SigningKey};
+    use tempfile::TempDir;
+
+    fn keys() -> Vec<SigningKey> {
+        (1..=3u8).map(|seed| SigningKey::from_bytes(&[seed; 32])).collect()
+    }
+
+    fn gadget(keys: &[SigningKey]) -> FinalityGadget {
+        let mut validators = ValidatorSet::new(1);
+        for (index, key) in keys.iter().enumerate() {
+            validators
+                .insert(Validator {
+                    id: index as u64 + 1,
+                    public_key: key.verifying_key().to_bytes(),
+                    stake: 100,
+                })
+                .unwrap();
+        }
+        let mut gadget = FinalityGadget::new(FinalityConfig {
+            confirmation_depth: 2,
+            quorum_bps: 6666,
+        })
+        .unwrap();
+        gadget.set_validators(validators);
+        gadget
+    }
+
+    fn attest(key: &SigningKey, validator_id: u64, height: u64, hash: [u8; 32]) -> Attestation {
+        Attestation {
+            validator_id,
+            height,
+            hash,
+            signature: key.sign(&attestation_signing_bytes(height, &hash)).to_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_quorum_and_confirmations() {
+        let keys = keys();
+        let mut gadget = gadget(&keys);
+        let hash = [5u8; 32];
+
+        gadget.add_attestation(&attest(&keys[0], 1, 10, hash)).unwrap();
+        assert!(!gadget.has_quorum(10, &hash));
+        // A vote for another block does not count towards this one
+        gadget.add_attestation(&attest(&keys[1], 2, 10, [6u8; 32])).unwrap();
+        assert!(!gadget.has_quorum(10, &hash));
+        gadget.add_attestation(&attest(&keys[2], 3, 10, hash)).unwrap();
+        assert!(gadget.has_quorum(10, &hash));
+        assert_eq!(gadget.attested_stake(10, &hash), 200);
+
+        // Signatures must come from the claimed validator
+        assert!(gadget.add_attestation(&attest(&keys[0], 2, 11, hash)).is_err());
+
+        assert!(!gadget.is_confirmed(10, 11));
+        assert!(gadget.is_confirmed(10, 12));
+    }
+
+    #[tokio::test]
+    async fn test_finalized_head_is_stored() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let keys = keys();
+        let checkpoint = Checkpoint {
+            height: 10,
+            hash: [5u8; 32],
+            timestamp: 1_000,
+        };
+        {
+            let mut gadget = FinalityGadget::with_state_db(FinalityConfig::default(), db.clone()).await.unwrap();
+            gadget.set_validators(self::gadget(&keys).validators.clone());
+            gadget.record_finalized(checkpoint.clone()).await.unwrap();
+            // Attestations at or below the finalized head are refused
+            assert!(gadget.add_attestation(&attest(&keys[0], 1, 10, [5u8; 32])).is_err());
+        }
+
+        let gadget = FinalityGadget::with_state_db(FinalityConfig::default(), db).await.unwrap();
+        assert_eq!(gadget.finalized_head(), Some(checkpoint.clone()));
+        assert_eq!(gadget.get_checkpoint(10).await.unwrap(), Some(checkpoint));
+        assert_eq!(gadget.get_checkpoint(9).await.unwrap(), None);
+    }
+}
@@ -1,20 +1,39 @@
 use anyhow::Result;
-use block_sync::{Block, BlockHeader, Transaction};
+use block_sync::{Block, BlockHeader, Canonical, Transaction};
+use ed25519_dalek::SigningKey;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::Duration;
+use txpool::selection::SelectionConfig;
+use txpool::TxPool;
 
+pub mod engine;
 pub mod error;
+pub mod finality;
 pub mod hotstuff;
+pub mod limits;
 pub mod pow_mining;
+pub mod signer;
 pub mod ffi;
+pub mod time;
+pub mod validation;
+pub mod validators;
 
+use engine::{
+    header_signing_bytes, ChainHead, Checkpoint, ConsensusEngine, HybridEngine, HybridEngineConfig, ImportOutcome,
+};
 use error::ConsensusError;
+use finality::{Attestation, FinalityConfig, FinalityGadget};
+pub use block_sync::{merkle_root, TxTreeHasher};
+pub use limits::{BlockLimits, BlockWeight};
+pub use time::{NetworkTime, NetworkTimeStatus};
+pub use validation::{BlockContext, BlockRejection, BlockValidator, StateTransition, ValidationStage};
+use validators::{DoubleSignEvidence, ValidatorSet};
 use hotstuff::{HotStuffConsensus, ConsensusMessage};
 use pow_mining::{PoWMiner, MiningConfig};
-use ffi::FuegoHash;
+use signer::{LocalSigner, RemoteSigner};
 
 /// Consensus configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,10 +41,26 @@ pub struct ConsensusConfig {
     pub node_id: u64,
     pub total_nodes: u64,
     pub block_time: Duration,
+    /// Most transactions in one block
     pub max_block_size: usize,
     pub min_finality: u64,
     pub pow_difficulty: u64,
     pub enable_merge_mining: bool,
+    /// Blocks between finality checkpoints
+    pub checkpoint_interval: u64,
+    /// Gas, byte and weight limits of blocks; the transaction count comes from `max_block_size`
+    #[serde(default)]
+    pub limits: BlockLimits,
+    /// How far ahead of network-adjusted time a block timestamp may be, in seconds
+    #[serde(default = "default_max_future_drift")]
+    pub max_future_drift: u64,
+    /// How block production picks pooled transactions
+    #[serde(default)]
+    pub selection: SelectionConfig,
+}
+
+fn default_max_future_drift() -> u64 {
+    120
 }
 
 impl Default for ConsensusConfig {
@@ -38,6 +73,20 @@ impl Default for ConsensusConfig {
             min_finality: 2,
             pow_difficulty: 1000,
             enable_merge_mining: true,
+            checkpoint_interval: 100,
+            limits: BlockLimits::default(),
+            max_future_drift: default_max_future_drift(),
+            selection: SelectionConfig::default(),
+        }
+    }
+}
+
+impl ConsensusConfig {
+    /// Limits blocks are built and validated against
+    pub fn block_limits(&self) -> BlockLimits {
+        BlockLimits {
+            max_transactions: self.max_block_size,
+            ..self.limits.clone()
         }
     }
 }
@@ -61,22 +110,82 @@ pub struct BlockProposal {
     pub signature: Vec<u8>,
 }
 
+/// What a proposal adds to its block, relayed alongside the block so peers can import it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalSeal {
+    pub proposer: u64,
+    pub timestamp: u64,
+    pub signature: Vec<u8>,
+}
+
+impl Canonical for ProposalSeal {}
+
+impl BlockProposal {
+    /// Proposal of `block` under `seal`
+    pub fn from_seal(block: Block, seal: ProposalSeal) -> Self {
+        Self {
+            block,
+            proposer: seal.proposer,
+            timestamp: seal.timestamp,
+            signature: seal.signature,
+        }
+    }
+
+    pub fn seal(&self) -> ProposalSeal {
+        ProposalSeal {
+            proposer: self.proposer,
+            timestamp: self.timestamp,
+            signature: self.signature.clone(),
+        }
+    }
+}
+
+/// A finalized block as it is handed out for execution
+#[derive(Debug, Clone)]
+pub struct FinalBlock {
+    pub block: Block,
+    /// Key of the proposer, which the block's fees are paid to
+    pub fee_recipient: [u8; 32],
+    /// Seal the block was imported with, for the record of who proposed it
+    pub seal: ProposalSeal,
+}
+
+/// Key an imported block's fees are paid to and the seal it was proposed under
+type Proposer = ([u8; 32], ProposalSeal);
+
 /// Consensus engine implementing HotStuff BFT with PoW merge-mining
 pub struct Consensus {
     config: ConsensusConfig,
     hotstuff: HotStuffConsensus,
     pow_miner: Option<PoWMiner>,
-    fuego_hash: FuegoHash,
+    engine: Arc<RwLock<Box<dyn ConsensusEngine>>>,
+    finality: Arc<RwLock<FinalityGadget>>,
+    network_time: Arc<RwLock<NetworkTime>>,
+    signer: Option<Arc<dyn RemoteSigner>>,
     status: Arc<RwLock<ConsensusStatus>>,
-    finalized_blocks: Arc<RwLock<Vec<Block>>>,
-    block_proposals: Arc<RwLock<HashMap<[u8; 32], BlockProposal>>>,
+    finalized_blocks: Arc<RwLock<Vec<FinalBlock>>>,
+    /// Imported blocks above the latest checkpoint
+    unfinalized_blocks: Arc<RwLock<Vec<Block>>>,
+    /// Fee recipient and seal of each imported block above the latest checkpoint, by id
+    proposers: Arc<RwLock<HashMap<[u8; 32], Proposer>>>,
+    /// Imported blocks above the latest checkpoint that are off the canonical chain, by id
+    side_blocks: Arc<RwLock<HashMap<[u8; 32], Block>>>,
+    /// Pool that gets back the transactions of blocks a reorg disconnects
+    tx_pool: Option<Arc<RwLock<TxPool>>>,
     message_tx: mpsc::Sender<ConsensusMessage>,
     message_rx: mpsc::Receiver<ConsensusMessage>,
+    evidence_tx: broadcast::Sender<DoubleSignEvidence>,
 }
 
 impl Consensus {
-    /// Create a new consensus instance
+    /// Create a new consensus instance using the hybrid PoW + validator engine
     pub fn new(config: ConsensusConfig) -> Result<Self, ConsensusError> {
+        let engine = HybridEngine::new(HybridEngineConfig::from(&config), ValidatorSet::default())?;
+        Self::with_engine(config, Box::new(engine))
+    }
+
+    /// Create a new consensus instance driven by `engine`
+    pub fn with_engine(config: ConsensusConfig, engine: Box<dyn ConsensusEngine>) -> Result<Self, ConsensusError> {
         let (message_tx, message_rx) = mpsc::channel(1000);
         
         let hotstuff = HotStuffConsensus::new(config.clone())?;
@@ -89,18 +198,28 @@ impl Consensus {
             None
         };
         
-        let fuego_hash = FuegoHash::new()?;
+        let finality = FinalityGadget::new(FinalityConfig {
+            confirmation_depth: config.min_finality,
+            ..Default::default()
+        })?;
         
         Ok(Self {
             config,
             hotstuff,
             pow_miner,
-            fuego_hash,
+            engine: Arc::new(RwLock::new(engine)),
+            finality: Arc::new(RwLock::new(finality)),
+            network_time: Arc::new(RwLock::new(NetworkTime::new(time::MAX_CLOCK_OFFSET))),
+            signer: None,
             status: Arc::new(RwLock::new(ConsensusStatus::Starting)),
             finalized_blocks: Arc::new(RwLock::new(Vec::new())),
-            block_proposals: Arc::new(RwLock::new(HashMap::new())),
+            unfinalized_blocks: Arc::new(RwLock::new(Vec::new())),
+            proposers: Arc::new(RwLock::new(HashMap::new())),
+            side_blocks: Arc::new(RwLock::new(HashMap::new())),
+            tx_pool: None,
             message_tx,
             message_rx,
+            evidence_tx: broadcast::channel(64).0,
         })
     }
     
@@ -142,108 +261,336 @@ impl Consensus {
         Ok(())
     }
     
-    /// Propose a new block
-    pub async fn propose_block(&mut self, transactions: Vec<Transaction>) -> Result<(), ConsensusError> {
-        let status = self.status.read().await;
-        if !matches!(*status, ConsensusStatus::Running) {
-            return Err(ConsensusError::ConsensusNotRunning);
+    /// Set the key this node signs its block proposals with
+    pub fn set_signing_key(&mut self, signing_key: SigningKey) {
+        self.signer = Some(Arc::new(LocalSigner::new(signing_key)));
+    }
+
+    /// Sign proposals and attestations through `signer`, keeping the key off this host
+    pub fn set_signer(&mut self, signer: Arc<dyn RemoteSigner>) {
+        self.signer = Some(signer);
+    }
+
+    /// Public key of this node's validator signer, if it has one
+    pub fn validator_public_key(&self) -> Option<[u8; 32]> {
+        self.signer.as_ref().map(|signer| signer.public_key())
+    }
+
+    /// Sign a header with this node's validator key, if it has one
+    pub async fn sign_header(&self, header: &BlockHeader) -> Result<Vec<u8>, ConsensusError> {
+        match &self.signer {
+            Some(signer) => Ok(signer.sign(&header_signing_bytes(header)).await?.to_vec()),
+            None => Ok(vec![]),
         }
-        drop(status);
-        
-        // Create block header
-        let header = BlockHeader {
-            height: 0, // Will be set properly
-            prev_hash: self.get_latest_block_hash().await?,
-            merkle_root: self.calculate_merkle_root(&transactions)?,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            difficulty: self.config.pow_difficulty,
-            nonce: 0,
-        };
-        
-        // Create block
-        let block = Block {
-            header,
-            transactions,
-            proof: block_sync::BlockProof {
-                proof_type: block_sync::ProofType::PoW,
-                proof_data: vec![], // Will be set by PoW mining
-            },
-        };
-        
-        // Create proposal
-        let proposal = BlockProposal {
-            block: block.clone(),
+    }
+
+    /// `block` proposed by this node, signed with its validator key
+    pub async fn seal_proposal(&self, block: Block) -> Result<BlockProposal, ConsensusError> {
+        Ok(BlockProposal {
+            signature: self.sign_header(&block.header).await?,
+            block,
             proposer: self.config.node_id,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
-            signature: vec![], // TODO: Implement signing
+        })
+    }
+
+    /// Start the engine from `headers`, genesis first, e.g. the blocks a node executed before
+    /// it restarted; they are final
+    pub async fn restore_chain(&self, headers: &[BlockHeader]) -> Result<(), ConsensusError> {
+        self.engine.write().await.restore_chain(headers)
+    }
+
+    /// Import a block proposal through the consensus engine
+    pub async fn import_proposal(&self, proposal: &BlockProposal) -> Result<ImportOutcome, ConsensusError> {
+        let mut engine = self.engine.write().await;
+        let result = engine.import_proposal(proposal);
+        for evidence in engine.take_evidence() {
+            let _ = self.evidence_tx.send(evidence);
+        }
+        drop(engine);
+        let outcome = result?;
+        self.proposers.write().await.insert(outcome.hash, (outcome.fee_recipient, proposal.seal()));
+        if outcome.connected.is_empty() {
+            self.side_blocks.write().await.insert(outcome.hash, proposal.block.clone());
+            return Ok(outcome);
+        }
+
+        let (connected, disconnected) = {
+            let mut side_blocks = self.side_blocks.write().await;
+            let mut unfinalized = self.unfinalized_blocks.write().await;
+            let fork = outcome.height + 1 - outcome.connected.len() as u64;
+            let split = unfinalized
+                .iter()
+                .position(|block| block.header.height >= fork)
+                .unwrap_or(unfinalized.len());
+            let disconnected = unfinalized.split_off(split);
+            for block in &disconnected {
+                side_blocks.insert(engine::header_id(&block.header), block.clone());
+            }
+            let (branch, _) = outcome.connected.split_at(outcome.connected.len() - 1);
+            let mut connected: Vec<Block> = branch.iter().filter_map(|hash| side_blocks.remove(hash)).collect();
+            connected.push(proposal.block.clone());
+            unfinalized.extend(connected.iter().cloned());
+            (connected, disconnected)
         };
-        
-        // Store proposal
-        let block_hash = block.header.hash()?;
-        self.block_proposals.write().await.insert(block_hash, proposal);
-        
-        // Submit to HotStuff consensus
-        self.hotstuff.propose_block(block).await?;
-        
-        Ok(())
+        self.update_pool(&connected, disconnected).await;
+
+        for checkpoint in &outcome.checkpoints {
+            self.finality.write().await.record_finalized(checkpoint.clone()).await?;
+            self.finalize_blocks(checkpoint).await;
+        }
+        self.try_finalize().await?;
+        Ok(outcome)
     }
-    
-    /// Get finalized blocks
-    pub async fn get_finalized_blocks(&self) -> Vec<Block> {
-        self.finalized_blocks.read().await.clone()
+
+    /// Submit a validator attestation, returning the new finalized checkpoint if it completed a quorum
+    pub async fn submit_attestation(&self, attestation: &Attestation) -> Result<Option<Checkpoint>, ConsensusError> {
+        self.finality.write().await.add_attestation(attestation)?;
+        self.try_finalize().await
     }
-    
-    /// Get consensus status
-    pub async fn get_status(&self) -> ConsensusStatus {
-        self.status.read().await.clone()
+
+    /// Attest to the canonical block at `height` with this node's validator key
+    pub async fn create_attestation(&self, height: u64) -> Result<Attestation, ConsensusError> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or_else(|| ConsensusError::ConfigError("No validator key configured".to_string()))?;
+        let block = self
+            .engine
+            .read()
+            .await
+            .canonical_checkpoint(height)
+            .ok_or_else(|| ConsensusError::BlockValidationFailed(format!("No block at height {}", height)))?;
+        let message = finality::attestation_signing_bytes(height, &block.hash);
+        Ok(Attestation {
+            validator_id: self.config.node_id,
+            height,
+            hash: block.hash,
+            signature: signer.sign(&message).await?.to_vec(),
+        })
     }
-    
-    /// Get latest block hash
-    async fn get_latest_block_hash(&self) -> Result<[u8; 32], ConsensusError> {
-        let blocks = self.finalized_blocks.read().await;
-        if let Some(latest) = blocks.last() {
-            Ok(latest.header.hash()?)
-        } else {
-            // Genesis block hash
-            Ok([0u8; 32])
+
+    /// Finalize the highest confirmed block with an attestation quorum
+    async fn try_finalize(&self) -> Result<Option<Checkpoint>, ConsensusError> {
+        let mut finality = self.finality.write().await;
+        let engine = self.engine.read().await;
+        let head = match engine.head() {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+
+        let checkpoint = finality
+            .attested_heights()
+            .into_iter()
+            .filter(|height| finality.is_confirmed(*height, head.height))
+            .filter_map(|height| engine.canonical_checkpoint(height))
+            .find(|candidate| finality.has_quorum(candidate.height, &candidate.hash));
+        drop(engine);
+
+        let checkpoint = match checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(None),
+        };
+        self.engine.write().await.add_checkpoint(checkpoint.clone())?;
+        finality.record_finalized(checkpoint.clone()).await?;
+        drop(finality);
+
+        println!("Block {} finalized by validator attestations", checkpoint.height);
+        self.finalize_blocks(&checkpoint).await;
+        Ok(Some(checkpoint))
+    }
+
+    /// Move imported blocks up to `checkpoint` into the finalized list
+    async fn finalize_blocks(&self, checkpoint: &Checkpoint) {
+        let mut unfinalized = self.unfinalized_blocks.write().await;
+        let split = unfinalized
+            .iter()
+            .position(|block| block.header.height > checkpoint.height)
+            .unwrap_or(unfinalized.len());
+        let finalized: Vec<Block> = unfinalized.drain(..split).collect();
+        drop(unfinalized);
+        let mut proposers = self.proposers.write().await;
+        self.side_blocks.write().await.retain(|id, block| {
+            let kept = block.header.height > checkpoint.height;
+            if !kept {
+                proposers.remove(id);
+            }
+            kept
+        });
+        if let Some(tx_pool) = &self.tx_pool {
+            let mut tx_pool = tx_pool.write().await;
+            for block in &finalized {
+                tx_pool.forget_included(&block.transactions);
+            }
         }
+        let finalized = finalized.into_iter().map(|block| {
+            let id = engine::header_id(&block.header);
+            let (fee_recipient, seal) = proposers.remove(&id).expect("imported blocks have a proposer");
+            FinalBlock { block, fee_recipient, seal }
+        });
+        self.finalized_blocks.write().await.extend(finalized);
     }
-    
-    /// Calculate Merkle root for transactions
-    fn calculate_merkle_root(&self, transactions: &[Transaction]) -> Result<[u8; 32], ConsensusError> {
-        if transactions.is_empty() {
-            return Ok([0u8; 32]);
+
+    /// Take the transactions of the `connected` blocks out of the pool and return those of the
+    /// blocks they replaced, except any `connected` includes itself
+    async fn update_pool(&self, connected: &[Block], disconnected: Vec<Block>) {
+        let Some(tx_pool) = &self.tx_pool else { return };
+        let mut tx_pool = tx_pool.write().await;
+        for block in connected {
+            tx_pool.remove_included(&block.transactions).await;
         }
-        
-        let mut hashes: Vec<[u8; 32]> = transactions
+        if disconnected.is_empty() {
+            return;
+        }
+
+        let included: HashSet<[u8; 32]> =
+            connected.iter().flat_map(|block| block.transactions.iter()).map(|tx| tx.hash).collect();
+        let returned: Vec<Transaction> = disconnected
             .iter()
-            .map(|tx| tx.hash)
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| !included.contains(&tx.hash))
+            .cloned()
             .collect();
-        
-        // Build Merkle tree
-        while hashes.len() > 1 {
-            let mut new_hashes = Vec::new();
-            for chunk in hashes.chunks(2) {
-                let combined = if chunk.len() == 2 {
-                    let mut combined = Vec::new();
-                    combined.extend_from_slice(&chunk[0]);
-                    combined.extend_from_slice(&chunk[1]);
-                    self.fuego_hash.hash(&combined)?
-                } else {
-                    chunk[0]
-                };
-                new_hashes.push(combined);
+        let candidates = returned.len();
+        let reinjected = tx_pool.reinject(returned).await;
+        println!(
+            "Returned {} of {} transactions from {} disconnected blocks to the pool",
+            reinjected,
+            candidates,
+            disconnected.len()
+        );
+    }
+
+    /// Import proposals from `proposals` until the channel closes or consensus stops
+    pub async fn run_consensus(&self, mut proposals: mpsc::Receiver<BlockProposal>) -> Result<(), ConsensusError> {
+        println!("Running {} consensus engine", self.engine.read().await.name());
+        while let Some(proposal) = proposals.recv().await {
+            if !matches!(*self.status.read().await, ConsensusStatus::Running) {
+                return Err(ConsensusError::ConsensusNotRunning);
+            }
+
+            let finalized_before = self.finalized_blocks.read().await.len();
+            match self.import_proposal(&proposal).await {
+                Ok(_) => {
+                    self.record_peer_time(&proposal).await;
+                    let finalized: Vec<Block> = self.finalized_blocks.read().await[finalized_before..]
+                        .iter()
+                        .map(|finalized| finalized.block.clone())
+                        .collect();
+                    for block in finalized {
+                        let _ = self.message_tx.try_send(ConsensusMessage::BlockFinalized(block));
+                    }
+                }
+                Err(e) => {
+                    let hash = engine::header_id(&proposal.block.header);
+                    let _ = self.message_tx.try_send(ConsensusMessage::BlockRejected(hash, e.to_string()));
+                }
             }
-            hashes = new_hashes;
         }
-        
-        Ok(hashes[0])
+        Ok(())
+    }
+
+    /// Sample the clock of the validator behind an accepted proposal and pass the adjusted
+    /// offset on to the engine
+    async fn record_peer_time(&self, proposal: &BlockProposal) {
+        if proposal.proposer == self.config.node_id {
+            return;
+        }
+        let offset = {
+            let mut network_time = self.network_time.write().await;
+            network_time.add_sample(proposal.proposer, proposal.timestamp, time::local_time());
+            network_time.offset()
+        };
+        self.engine.write().await.set_time_offset(offset);
+    }
+
+    /// Run the state transition stage of block validation through `state`
+    pub async fn set_state_transition(&self, state: Arc<dyn StateTransition>) {
+        self.engine.write().await.set_state_transition(state);
+    }
+
+    /// Receive double-sign evidence for slashing
+    pub fn subscribe_evidence(&self) -> broadcast::Receiver<DoubleSignEvidence> {
+        self.evidence_tx.subscribe()
+    }
+
+    /// Replace the validators allowed to sign blocks, e.g. after staking changes
+    pub async fn update_validators(&self, validators: ValidatorSet) {
+        self.engine.write().await.set_validators(validators.clone());
+        self.finality.write().await.set_validators(validators);
+    }
+
+    /// Keep `tx_pool` in step with the chain: included transactions leave it and those of
+    /// disconnected blocks return to it
+    pub fn attach_tx_pool(&mut self, tx_pool: Arc<RwLock<TxPool>>) {
+        self.tx_pool = Some(tx_pool);
+    }
+
+    /// Use `finality`, e.g. one backed by a StateDB, as the finality gadget
+    pub fn set_finality(&mut self, finality: FinalityGadget) {
+        self.finality = Arc::new(RwLock::new(finality));
+    }
+
+    /// Finality gadget, shared with the RPC server
+    pub fn finality(&self) -> Arc<RwLock<FinalityGadget>> {
+        self.finality.clone()
+    }
+
+    /// Network-adjusted clock, shared with the RPC server
+    pub fn network_time(&self) -> Arc<RwLock<NetworkTime>> {
+        self.network_time.clone()
+    }
+
+    /// Highest finalized block, by checkpoint interval or attestation quorum
+    pub async fn get_finalized_head(&self) -> Option<Checkpoint> {
+        self.finality.read().await.finalized_head()
+    }
+
+    /// The engine's current head, if it has imported a block
+    pub async fn head(&self) -> Option<ChainHead> {
+        self.engine.read().await.head()
+    }
+
+    /// The engine's current head with the difficulty it expects of the block built on it
+    pub async fn head_with_difficulty(&self) -> Result<Option<(ChainHead, u64)>, ConsensusError> {
+        let engine = self.engine.read().await;
+        match engine.head() {
+            Some(head) => {
+                let difficulty = engine.expected_difficulty(head.height + 1)?;
+                Ok(Some((head, difficulty)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Height of the engine's current head, if it has imported a block
+    pub async fn head_height(&self) -> Option<u64> {
+        self.engine.read().await.head().map(|head| head.height)
+    }
+
+    /// Get the most recent finality checkpoint
+    pub async fn get_latest_checkpoint(&self) -> Option<Checkpoint> {
+        self.engine.read().await.latest_checkpoint()
+    }
+
+    /// Get finalized blocks
+    pub async fn get_finalized_blocks(&self) -> Vec<Block> {
+        self.finalized_blocks.read().await.iter().map(|finalized| finalized.block.clone()).collect()
+    }
+
+    /// Finalized blocks above `height`, oldest first
+    pub async fn final_blocks_above(&self, height: u64) -> Vec<FinalBlock> {
+        let finalized = self.finalized_blocks.read().await;
+        let start = finalized.partition_point(|finalized| finalized.block.header.height <= height);
+        finalized[start..].to_vec()
+    }
+    
+    /// Get consensus status
+    pub async fn get_status(&self) -> ConsensusStatus {
+        self.status.read().await.clone()
     }
     
     /// Process consensus messages
@@ -276,7 +623,11 @@ mod tests {
     
     fn create_test_transaction() -> Transaction {
         Transaction {
-            hash: [1u8; 32],
+            hash: [0u8; 32],
+            sender: Vec::new(),
+            nonce: 0,
+            gas_limit: 0,
+            data: Vec::new(),
             inputs: vec![TxInput {
                 prev_tx_hash: [0u8; 32],
                 output_index: 0,
@@ -286,10 +637,17 @@ mod tests {
                 amount: 100,
                 address: vec![1u8; 32],
                 commitment: [0u8; 32],
+                ephemeral_key: None,
             }],
             fee: 10,
             timestamp: 1234567890,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
         }
+        .with_id()
     }
     
     #[tokio::test]
@@ -322,21 +680,131 @@ mod tests {
     }
     
     #[tokio::test]
-    async fn test_block_proposal() {
-        let config = ConsensusConfig::default();
-        let mut consensus = Consensus::new(config).unwrap();
-        
-        // Start consensus
+    async fn test_attestation_quorum_finalizes_blocks() {
+        use engine::tests::{mined_proposal, test_validators};
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let engine_config = HybridEngineConfig {
+            initial_difficulty: 1,
+            checkpoint_interval: 1000,
+            ..Default::default()
+        };
+        let engine = HybridEngine::new(engine_config, ValidatorSet::default()).unwrap();
+        let config = ConsensusConfig {
+            node_id: 1,
+            min_finality: 1,
+            ..Default::default()
+        };
+        let mut consensus = Consensus::with_engine(config, Box::new(engine)).unwrap();
+        consensus.set_signing_key(key.clone());
+        consensus.update_validators(test_validators(&key)).await;
+
+        let mut ids = vec![];
+        let mut prev = [0u8; 32];
+        for height in 0..3 {
+            prev = consensus.import_proposal(&mined_proposal(&key, height, prev, 1_000 + height)).await.unwrap().hash;
+            ids.push(prev);
+        }
+        assert!(consensus.get_finalized_head().await.is_none());
+
+        // The head has no confirmations yet, so attesting to it does not finalize it
+        let attestation = consensus.create_attestation(2).await.unwrap();
+        assert_eq!(consensus.submit_attestation(&attestation).await.unwrap(), None);
+
+        let attestation = consensus.create_attestation(1).await.unwrap();
+        let checkpoint = consensus.submit_attestation(&attestation).await.unwrap().unwrap();
+        assert_eq!(checkpoint.height, 1);
+        assert_eq!(checkpoint.hash, ids[1]);
+        assert_eq!(consensus.get_finalized_head().await.unwrap().height, 1);
+        assert_eq!(consensus.get_finalized_blocks().await.len(), 2);
+
+        // Block 2 becomes final once block 3 confirms it; a proposal relayed as its block and
+        // seal imports as the proposal itself
+        let proposal = mined_proposal(&key, 3, prev, 1_003);
+        let seal = ProposalSeal::from_canonical_bytes(&proposal.seal().to_canonical_bytes()).unwrap();
+        let relayed = BlockProposal::from_seal(proposal.block.clone(), seal);
+        prev = consensus.import_proposal(&relayed).await.unwrap().hash;
+        assert_eq!(consensus.get_finalized_head().await.unwrap().height, 2);
+
+        // Final blocks are handed out for execution with the key their fees are paid to
+        let executable = consensus.final_blocks_above(0).await;
+        let heights: Vec<_> = executable.iter().map(|final_block| final_block.block.header.height).collect();
+        assert_eq!(heights, vec![1, 2]);
+        assert!(executable.iter().all(|final_block| final_block.fee_recipient == key.verifying_key().to_bytes()));
+        assert_eq!(executable[1].seal, mined_proposal(&key, 2, ids[1], 1_002).seal());
+
+        // No reorg past the finalized head
+        let err = consensus.import_proposal(&mined_proposal(&key, 2, ids[1], 2_000)).await.unwrap_err();
+        assert!(err.to_string().contains("finalized"));
+        assert_ne!(prev, [0u8; 32]);
+    }
+
+    #[tokio::test]
+    async fn test_run_consensus_finalizes_checkpoints() {
+        use engine::tests::{mined_proposal, test_engine_config, test_validators};
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+        let mut consensus = Consensus::with_engine(ConsensusConfig::default(), Box::new(engine)).unwrap();
         consensus.start_consensus().await.unwrap();
-        
-        // Create test transaction
-        let tx = create_test_transaction();
-        
-        // Propose block
-        let result = consensus.propose_block(vec![tx]).await;
-        assert!(result.is_ok());
-        
-        // Stop consensus
+
+        let (proposal_tx, proposal_rx) = mpsc::channel(16);
+        let mut prev = [0u8; 32];
+        for height in 0..4 {
+            let proposal = mined_proposal(&key, height, prev, 1_000 + height);
+            prev = engine::header_id(&proposal.block.header);
+            proposal_tx.send(proposal).await.unwrap();
+        }
+        // Rejected: not signed by a validator
+        let mut unsigned = mined_proposal(&key, 4, prev, 2_000);
+        unsigned.signature.clear();
+        proposal_tx.send(unsigned).await.unwrap();
+        drop(proposal_tx);
+
+        consensus.run_consensus(proposal_rx).await.unwrap();
+        assert_eq!(consensus.get_latest_checkpoint().await.unwrap().height, 2);
+        let finalized = consensus.get_finalized_blocks().await;
+        assert_eq!(finalized.iter().map(|block| block.header.height).collect::<Vec<_>>(), vec![0, 1, 2]);
+
         consensus.stop_consensus().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_reorg_returns_disconnected_transactions_to_the_pool() {
+        use engine::tests::{mined_proposal, mined_proposal_with, test_engine_config, test_validators};
+        use txpool::fee::SimpleFeeAlgorithm;
+        use txpool::priority::SimplePriorityCalculator;
+
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let engine = HybridEngine::new(test_engine_config(), test_validators(&key)).unwrap();
+        let mut consensus = Consensus::with_engine(ConsensusConfig::default(), Box::new(engine)).unwrap();
+        let pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 10);
+        let pool = Arc::new(RwLock::new(pool));
+        consensus.attach_tx_pool(pool.clone());
+
+        let txs: Vec<Transaction> = (1..=3u8)
+            .map(|i| Transaction { nonce: i as u64, ..create_test_transaction() }.with_id())
+            .collect();
+        for tx in &txs {
+            pool.write().await.add_transaction(tx.clone()).await.unwrap();
+        }
+
+        let genesis = consensus.import_proposal(&mined_proposal(&key, 0, [0u8; 32], 1_000)).await.unwrap();
+        let block = mined_proposal_with(&key, 1, genesis.hash, 1_001, txs[..2].to_vec());
+        consensus.import_proposal(&block).await.unwrap();
+        let pooled: Vec<[u8; 32]> = pool.read().await.transactions().iter().map(|tx| tx.hash).collect();
+        assert_eq!(pooled, vec![txs[2].hash]);
+
+        // The competing branch includes only the second transaction, so the first goes back
+        let competing = mined_proposal_with(&key, 1, genesis.hash, 1_002, vec![txs[1].clone()]);
+        let first = consensus.import_proposal(&competing).await.unwrap();
+        let tip = mined_proposal(&key, 2, first.hash, 1_003);
+        let outcome = consensus.import_proposal(&tip).await.unwrap();
+        assert_eq!(first.reorg_depth + outcome.reorg_depth, 1);
+        assert_eq!(consensus.engine.read().await.head().unwrap().hash, outcome.hash);
+        let pool = pool.read().await;
+        assert!(pool.get_transaction(&txs[0].hash).is_some());
+        assert!(pool.get_transaction(&txs[1].hash).is_none());
+        assert_eq!(pool.get_stats().total_transactions, 2);
+    }
 }
\ No newline at end of file
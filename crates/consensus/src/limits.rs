@@ -0,0 +1,302 @@
+//! Block size and weight limits. A block's weight is the bytes of its canonical encoding
+//! plus the cost of verifying what it carries: its proof of work and every signature,
+//! ring member and nullifier of its transactions. Templates are filled up to the limits
+//! and blocks beyond any of them are rejected before their proof of work is checked.
+
+use crate::error::ConsensusError;
+use block_sync::{Block, Canonical, OrderingPolicy, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// Limits every block on a chain must respect
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockLimits {
+    /// Most transactions in one block
+    pub max_transactions: usize,
+    /// Most gas the transactions of one block may reserve through their gas limits
+    pub max_block_gas: u64,
+    /// Largest canonical encoding of one block
+    pub max_block_bytes: u64,
+    /// Largest canonical encoding of one transaction
+    pub max_transaction_bytes: u64,
+    /// Most weight one block may carry
+    pub max_block_weight: u64,
+    /// Weight of verifying a merge-mined proof of work
+    pub pow_weight: u64,
+    /// Weight of verifying one input signature
+    pub signature_weight: u64,
+    /// Weight of verifying one member of a ring signature
+    pub ring_member_weight: u64,
+    /// Weight of checking one nullifier against the spent set
+    pub nullifier_weight: u64,
+    /// Fill templates with bridge and system transactions of the priority-ops lane first
+    pub priority_ops_enabled: bool,
+    /// Gas of each template kept for priority ops; user transactions may use what they leave,
+    /// and ops beyond it wait for a later block
+    pub priority_ops_gas: u64,
+    /// Require every transaction input to carry a valid ed25519 signature of its transaction
+    pub verify_input_signatures: bool,
+    /// Order a block's transactions must follow
+    pub transaction_ordering: OrderingPolicy,
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self {
+            max_transactions: 1000,
+            max_block_gas: 30_000_000,
+            max_block_bytes: 2 * 1024 * 1024,
+            max_transaction_bytes: 128 * 1024,
+            max_block_weight: 4 * 1024 * 1024,
+            pow_weight: 10_000,
+            signature_weight: 500,
+            ring_member_weight: 250,
+            nullifier_weight: 100,
+            priority_ops_enabled: false,
+            priority_ops_gas: 3_000_000,
+            verify_input_signatures: false,
+            transaction_ordering: OrderingPolicy::Sequencer,
+        }
+    }
+}
+
+/// Measured size of a block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockWeight {
+    pub bytes: u64,
+    pub gas: u64,
+    pub weight: u64,
+}
+
+impl BlockLimits {
+    pub fn validate(&self) -> Result<(), ConsensusError> {
+        if self.max_transactions == 0 || self.max_block_gas == 0 || self.max_transaction_bytes == 0 {
+            return Err(ConsensusError::ConfigError(
+                "Block transaction, gas and transaction size limits must be positive".to_string(),
+            ));
+        }
+        if self.max_block_bytes < self.max_transaction_bytes || self.max_block_weight < self.max_block_bytes {
+            return Err(ConsensusError::ConfigError(
+                "A block must fit its largest transaction and weigh at least its byte limit".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Weight of a transaction: its encoded bytes plus the cost of verifying it
+    pub fn transaction_weight(&self, tx: &Transaction) -> u64 {
+        (tx.to_canonical_bytes().len() as u64).saturating_add(self.verification_weight(tx))
+    }
+
+    /// Cost of verifying a transaction's signatures, ring members and nullifiers
+    fn verification_weight(&self, tx: &Transaction) -> u64 {
+        let ring_members: u64 = tx.ring_inputs.iter().map(|input| input.ring.len() as u64).sum();
+        self.signature_weight
+            .saturating_mul(tx.inputs.len() as u64 + tx.fee_payer.is_some() as u64)
+            .saturating_add(self.ring_member_weight.saturating_mul(ring_members))
+            .saturating_add(self.nullifier_weight.saturating_mul(tx.nullifiers.len() as u64))
+    }
+
+    /// Check a transaction on its own, returning its weight
+    pub fn check_transaction(&self, tx: &Transaction) -> Result<u64, ConsensusError> {
+        let bytes = tx.to_canonical_bytes().len() as u64;
+        if bytes > self.max_transaction_bytes {
+            return Err(ConsensusError::BlockValidationFailed(format!(
+                "Transaction {} is {} bytes, over the limit of {}",
+                hex::encode(tx.hash),
+                bytes,
+                self.max_transaction_bytes
+            )));
+        }
+        Ok(self.transaction_weight(tx))
+    }
+
+    /// Measure `block` and reject it if it breaks any limit
+    pub fn check_block(&self, block: &Block) -> Result<BlockWeight, ConsensusError> {
+        if block.transactions.len() > self.max_transactions {
+            return Err(ConsensusError::BlockValidationFailed(format!(
+                "Block has {} transactions, over the limit of {}",
+                block.transactions.len(),
+                self.max_transactions
+            )));
+        }
+
+        let bytes = block.to_canonical_bytes().len() as u64;
+        let mut weight = BlockWeight {
+            bytes,
+            gas: 0,
+            weight: bytes.saturating_add(self.pow_weight),
+        };
+        for tx in &block.transactions {
+            self.check_transaction(tx)?;
+            weight.gas = weight.gas.saturating_add(tx.gas_limit);
+            weight.weight = weight.weight.saturating_add(self.verification_weight(tx));
+        }
+
+        let over = if bytes > self.max_block_bytes {
+            Some(format!("{} bytes, over the limit of {}", bytes, self.max_block_bytes))
+        } else if weight.gas > self.max_block_gas {
+            Some(format!("{} gas, over the limit of {}", weight.gas, self.max_block_gas))
+        } else if weight.weight > self.max_block_weight {
+            Some(format!("weight {}, over the limit of {}", weight.weight, self.max_block_weight))
+        } else {
+            None
+        };
+        match over {
+            Some(over) => Err(ConsensusError::BlockValidationFailed(format!("Block has {}", over))),
+            None => Ok(weight),
+        }
+    }
+
+    /// Take transactions from `candidates`, best first, while they fit in a block. A candidate
+    /// that would break a limit is skipped so smaller ones behind it can still fill the block.
+    /// With the lane enabled `priority_ops` go first, within the gas reserved for them;
+    /// otherwise they are simply the first candidates.
+    pub fn select_transactions(
+        &self,
+        priority_ops: Vec<Transaction>,
+        candidates: Vec<Transaction>,
+    ) -> Vec<Transaction> {
+        // Room for the header, the proof and the length prefixes around the transactions
+        const BLOCK_OVERHEAD: u64 = 1024;
+
+        let lanes = if self.priority_ops_enabled {
+            [(priority_ops, self.priority_ops_gas.min(self.max_block_gas)), (candidates, self.max_block_gas)]
+        } else {
+            [(Vec::new(), 0), (priority_ops.into_iter().chain(candidates).collect(), self.max_block_gas)]
+        };
+        let mut used = BlockWeight {
+            bytes: BLOCK_OVERHEAD,
+            gas: 0,
+            weight: BLOCK_OVERHEAD + self.pow_weight,
+        };
+        let mut selected = Vec::new();
+        for (lane, gas_limit) in lanes {
+            for tx in lane {
+                if selected.len() == self.max_transactions {
+                    return selected;
+                }
+                let bytes = tx.to_canonical_bytes().len() as u64;
+                if bytes > self.max_transaction_bytes {
+                    continue;
+                }
+                let next = BlockWeight {
+                    bytes: used.bytes.saturating_add(bytes),
+                    gas: used.gas.saturating_add(tx.gas_limit),
+                    weight: used.weight.saturating_add(self.transaction_weight(&tx)),
+                };
+                let fits =
+                    next.bytes <= self.max_block_bytes && next.gas <= gas_limit && next.weight <= self.max_block_weight;
+                if !fits {
+                    continue;
+                }
+                used = next;
+                selected.push(tx);
+            }
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_sync::{BlockHeader, BlockProof, ProofType, RingInput};
+
+    fn transaction(byte: u8, gas_limit: u64, data: usize) -> Transaction {
+        Transaction {
+            hash: [byte; 32],
+            sender: vec![byte],
+            nonce: 0,
+            gas_limit,
+            data: vec![0; data],
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee: 1,
+            timestamp: 1,
+        }
+    }
+
+    fn block(transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                height: 1,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![0; 64],
+            },
+        }
+    }
+
+    #[test]
+    fn test_limits_bound_templates_and_blocks() {
+        let limits = BlockLimits {
+            max_transactions: 3,
+            max_block_gas: 100_000,
+            max_block_bytes: 8 * 1024,
+            max_transaction_bytes: 4 * 1024,
+            max_block_weight: 16 * 1024,
+            ..Default::default()
+        };
+        limits.validate().unwrap();
+
+        // Ring members and nullifiers weigh more than their bytes
+        let mut ring = transaction(1, 21_000, 0);
+        ring.ring_inputs.push(RingInput {
+            amount: 1,
+            ring: vec![[0u8; 32]; 11],
+            key_image: [0u8; 32],
+            signature: Vec::new(),
+            audit_tag: None,
+        });
+        let bytes = ring.to_canonical_bytes().len() as u64;
+        assert_eq!(limits.transaction_weight(&ring), bytes + 11 * limits.ring_member_weight);
+
+        // Oversized, over-gas and surplus candidates are left out of the template
+        let candidates = vec![
+            transaction(1, 21_000, 0),
+            transaction(2, 21_000, 5_000),
+            transaction(3, 90_000, 0),
+            transaction(4, 21_000, 3_000),
+            transaction(5, 21_000, 0),
+            transaction(6, 21_000, 0),
+        ];
+        let selected = limits.select_transactions(Vec::new(), candidates.clone());
+        assert_eq!(selected.iter().map(|tx| tx.hash[0]).collect::<Vec<_>>(), vec![1, 4, 5]);
+        let weight = limits.check_block(&block(selected)).unwrap();
+        assert_eq!(weight.gas, 63_000);
+
+        // Priority ops go first within their reserved gas, then users fill what they left
+        let lane_limits = BlockLimits {
+            max_transactions: 4,
+            priority_ops_enabled: true,
+            priority_ops_gas: 50_000,
+            ..limits.clone()
+        };
+        let ops = vec![transaction(7, 21_000, 0), transaction(8, 21_000, 0), transaction(9, 21_000, 0)];
+        let selected = lane_limits.select_transactions(ops, candidates[4..].to_vec());
+        assert_eq!(selected.iter().map(|tx| tx.hash[0]).collect::<Vec<_>>(), vec![7, 8, 5, 6]);
+
+        for (transactions, reason) in [
+            (candidates[..4].to_vec(), "transactions"),
+            (vec![candidates[1].clone()], "bytes"),
+            (vec![candidates[0].clone(), candidates[2].clone()], "gas"),
+        ] {
+            let err = limits.check_block(&block(transactions)).unwrap_err();
+            assert!(err.to_string().contains(reason), "{} should mention {}", err, reason);
+        }
+    }
+}
@@ -234,10 +234,15 @@ mod tests {
                     .unwrap()
                     .as_secs(),
                 difficulty: 1000,
+                nullifier_root: [0u8; 32],
                 nonce: 0,
             },
             transactions: vec![Transaction {
                 hash: [1u8; 32],
+                sender: Vec::new(),
+                nonce: 0,
+                gas_limit: 0,
+                data: Vec::new(),
                 inputs: vec![TxInput {
                     prev_tx_hash: [0u8; 32],
                     output_index: 0,
@@ -247,9 +252,15 @@ mod tests {
                     amount: 100,
                     address: vec![1u8; 32],
                     commitment: [0u8; 32],
+                    ephemeral_key: None,
                 }],
                 fee: 10,
                 timestamp: 1234567890,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
             }],
             proof: block_sync::BlockProof {
                 proof_type: block_sync::ProofType::PoW,
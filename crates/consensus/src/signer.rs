@@ -0,0 +1,387 @@
+//! Signers for validator and wallet keys.
+//!
+//! Everything that signs with a validator or wallet key goes through [`RemoteSigner`],
+//! so the key itself can live in a Ledger device or behind a remote signing service
+//! instead of on the node host. Signatures from outside the process are checked
+//! against the expected public key before they are used.
+
+use crate::error::ConsensusError;
+use async_trait::async_trait;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+/// Ledger APDU class of the C0DL3 app
+pub const LEDGER_CLA: u8 = 0xe0;
+const INS_GET_PUBLIC_KEY: u8 = 0x02;
+const INS_SIGN: u8 = 0x04;
+/// First APDU of a signing request, carrying the derivation path
+const P1_FIRST: u8 = 0x00;
+const P1_CONTINUE: u8 = 0x80;
+const P2_MORE: u8 = 0x80;
+const P2_LAST: u8 = 0x00;
+const SW_OK: u16 = 0x9000;
+const SW_REJECTED: u16 = 0x6985;
+/// Largest data field of a short APDU
+const MAX_APDU_DATA: usize = 255;
+/// SLIP-44 coin type the C0DL3 Ledger app derives keys under
+pub const LEDGER_COIN_TYPE: u32 = 0xc0d1;
+
+/// Where a node's validator key is held, when not loaded as a local key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignerConfig {
+    /// HTTP signing service holding the key
+    Remote {
+        url: String,
+        /// Hex-encoded ed25519 public key the service signs for
+        public_key: String,
+        timeout_secs: u64,
+    },
+    /// Ledger device reached through an APDU bridge, such as Speculos or a HID proxy
+    Ledger { address: String, account: u32 },
+}
+
+/// Something that holds an ed25519 key and signs with it on request
+#[async_trait]
+pub trait RemoteSigner: Send + Sync {
+    fn public_key(&self) -> [u8; 32];
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], ConsensusError>;
+}
+
+/// Build the signer described by `config`
+pub async fn connect_signer(config: &SignerConfig) -> Result<Arc<dyn RemoteSigner>, ConsensusError> {
+    match config {
+        SignerConfig::Remote { url, public_key, timeout_secs } => {
+            let public_key = hex::decode(public_key.trim_start_matches("0x"))
+                .ok()
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .ok_or_else(|| ConsensusError::ConfigError("Remote signer key must be 32 hex bytes".to_string()))?;
+            Ok(Arc::new(HttpSigner::new(url, public_key, Duration::from_secs(*timeout_secs))?))
+        }
+        SignerConfig::Ledger { address, account } => {
+            let signer = LedgerSigner::connect(TcpLedgerTransport::new(address), *account).await?;
+            Ok(Arc::new(signer))
+        }
+    }
+}
+
+fn check_signature(public_key: &[u8; 32], message: &[u8], signature: &[u8]) -> Result<[u8; 64], ConsensusError> {
+    let signature = <[u8; 64]>::try_from(signature)
+        .map_err(|_| ConsensusError::SignerError(format!("Signature has {} bytes", signature.len())))?;
+    VerifyingKey::from_bytes(public_key)
+        .and_then(|key| key.verify(message, &Signature::from_bytes(&signature)))
+        .map_err(|_| ConsensusError::SignerError("Signer returned an invalid signature".to_string()))?;
+    Ok(signature)
+}
+
+/// Key held in process memory
+pub struct LocalSigner {
+    key: SigningKey,
+}
+
+impl LocalSigner {
+    pub fn new(key: SigningKey) -> Self {
+        Self { key }
+    }
+}
+
+#[async_trait]
+impl RemoteSigner for LocalSigner {
+    fn public_key(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], ConsensusError> {
+        Ok(self.key.sign(message).to_bytes())
+    }
+}
+
+/// Signing service reached over HTTP. Requests are `{"publicKey", "message"}` and
+/// responses `{"signature"}`, all hex-encoded.
+pub struct HttpSigner {
+    http: reqwest::Client,
+    url: String,
+    public_key: [u8; 32],
+}
+
+impl HttpSigner {
+    pub fn new(url: &str, public_key: [u8; 32], timeout: Duration) -> Result<Self, ConsensusError> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| ConsensusError::ConfigError(e.to_string()))?;
+        Ok(Self {
+            http,
+            url: url.to_string(),
+            public_key,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    signature: String,
+}
+
+#[async_trait]
+impl RemoteSigner for HttpSigner {
+    fn public_key(&self) -> [u8; 32] {
+        self.public_key
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], ConsensusError> {
+        let response = self
+            .http
+            .post(&self.url)
+            .json(&serde_json::json!({
+                "publicKey": hex::encode(self.public_key),
+                "message": hex::encode(message),
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ConsensusError::SignerError(format!("Remote signer request failed: {}", e)))?;
+        let body: SignResponse = response
+            .json()
+            .await
+            .map_err(|e| ConsensusError::SignerError(format!("Malformed remote signer response: {}", e)))?;
+        let signature = hex::decode(body.signature.trim_start_matches("0x"))
+            .map_err(|e| ConsensusError::SignerError(format!("Malformed remote signature: {}", e)))?;
+        check_signature(&self.public_key, message, &signature)
+    }
+}
+
+/// Carries APDUs to a Ledger device and returns the response data followed by the status word
+#[async_trait]
+pub trait LedgerTransport: Send + Sync {
+    async fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, ConsensusError>;
+}
+
+/// APDU bridge speaking the Speculos TCP framing: a 4-byte big-endian length before
+/// each command, and before each response's data, which is followed by the status word
+pub struct TcpLedgerTransport {
+    address: String,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl TcpLedgerTransport {
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            stream: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl LedgerTransport for TcpLedgerTransport {
+    async fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, ConsensusError> {
+        let mut stream = self.stream.lock().await;
+        if stream.is_none() {
+            let connected = TcpStream::connect(&self.address)
+                .await
+                .map_err(|e| ConsensusError::SignerError(format!("Cannot reach Ledger at {}: {}", self.address, e)))?;
+            *stream = Some(connected);
+        }
+        let connection = stream.as_mut().unwrap();
+        let result = async {
+            connection.write_all(&(apdu.len() as u32).to_be_bytes()).await?;
+            connection.write_all(apdu).await?;
+            let mut length = [0u8; 4];
+            connection.read_exact(&mut length).await?;
+            let mut response = vec![0u8; u32::from_be_bytes(length) as usize + 2];
+            connection.read_exact(&mut response).await?;
+            Ok::<_, std::io::Error>(response)
+        }
+        .await;
+        // Reconnect on the next exchange rather than reuse a stream left mid-frame
+        if result.is_err() {
+            *stream = None;
+        }
+        result.map_err(|e| ConsensusError::SignerError(format!("Ledger exchange failed: {}", e)))
+    }
+}
+
+/// Key held by the C0DL3 app on a Ledger device, derived at `m/44'/coin'/account'/0'/0'`.
+/// Every signature needs confirmation on the device.
+pub struct LedgerSigner<T: LedgerTransport> {
+    transport: T,
+    path: Vec<u32>,
+    public_key: [u8; 32],
+}
+
+impl<T: LedgerTransport> LedgerSigner<T> {
+    /// Read the public key for `account` from the device
+    pub async fn connect(transport: T, account: u32) -> Result<Self, ConsensusError> {
+        const HARDENED: u32 = 0x8000_0000;
+        let path = vec![44 | HARDENED, LEDGER_COIN_TYPE | HARDENED, account | HARDENED, HARDENED, HARDENED];
+        let response = Self::command(&transport, INS_GET_PUBLIC_KEY, P1_FIRST, P2_LAST, &encode_path(&path)).await?;
+        let public_key = <[u8; 32]>::try_from(response.as_slice())
+            .map_err(|_| ConsensusError::SignerError(format!("Ledger returned a {}-byte public key", response.len())))?;
+        Ok(Self {
+            transport,
+            path,
+            public_key,
+        })
+    }
+
+    async fn command(transport: &T, ins: u8, p1: u8, p2: u8, data: &[u8]) -> Result<Vec<u8>, ConsensusError> {
+        let mut apdu = vec![LEDGER_CLA, ins, p1, p2, data.len() as u8];
+        apdu.extend_from_slice(data);
+        let mut response = transport.exchange(&apdu).await?;
+        if response.len() < 2 {
+            return Err(ConsensusError::SignerError("Ledger response is missing its status word".to_string()));
+        }
+        let status = u16::from_be_bytes([response[response.len() - 2], response[response.len() - 1]]);
+        response.truncate(response.len() - 2);
+        match status {
+            SW_OK => Ok(response),
+            SW_REJECTED => Err(ConsensusError::SignerError("Signing was rejected on the Ledger".to_string())),
+            status => Err(ConsensusError::SignerError(format!("Ledger returned status {:04x}", status))),
+        }
+    }
+}
+
+fn encode_path(path: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![path.len() as u8];
+    for index in path {
+        bytes.extend_from_slice(&index.to_be_bytes());
+    }
+    bytes
+}
+
+#[async_trait]
+impl<T: LedgerTransport> RemoteSigner for LedgerSigner<T> {
+    fn public_key(&self) -> [u8; 32] {
+        self.public_key
+    }
+
+    async fn sign(&self, message: &[u8]) -> Result<[u8; 64], ConsensusError> {
+        let chunks: Vec<&[u8]> = message.chunks(MAX_APDU_DATA).collect();
+        let p2 = if chunks.is_empty() { P2_LAST } else { P2_MORE };
+        let mut response = Self::command(&self.transport, INS_SIGN, P1_FIRST, p2, &encode_path(&self.path)).await?;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let p2 = if i + 1 == chunks.len() { P2_LAST } else { P2_MORE };
+            response = Self::command(&self.transport, INS_SIGN, P1_CONTINUE, p2, chunk).await?;
+        }
+        check_signature(&self.public_key, message, &response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Device that signs with a fixed key, or rejects every signature
+    struct FakeLedger {
+        key: SigningKey,
+        reject: bool,
+        message: std::sync::Mutex<Vec<u8>>,
+    }
+
+    #[async_trait]
+    impl LedgerTransport for FakeLedger {
+        async fn exchange(&self, apdu: &[u8]) -> Result<Vec<u8>, ConsensusError> {
+            assert_eq!(apdu[0], LEDGER_CLA);
+            assert_eq!(apdu[4] as usize, apdu.len() - 5);
+            let (p1, p2, data) = (apdu[2], apdu[3], &apdu[5..]);
+            let mut response = match (apdu[1], p1) {
+                (INS_GET_PUBLIC_KEY, _) => self.key.verifying_key().to_bytes().to_vec(),
+                (INS_SIGN, P1_FIRST) => {
+                    assert_eq!(data[0], 5);
+                    self.message.lock().unwrap().clear();
+                    Vec::new()
+                }
+                (INS_SIGN, _) => {
+                    self.message.lock().unwrap().extend_from_slice(data);
+                    if p2 == P2_MORE {
+                        Vec::new()
+                    } else if self.reject {
+                        return Ok(SW_REJECTED.to_be_bytes().to_vec());
+                    } else {
+                        self.key.sign(&self.message.lock().unwrap()).to_bytes().to_vec()
+                    }
+                }
+                _ => panic!("unexpected APDU"),
+            };
+            response.extend_from_slice(&SW_OK.to_be_bytes());
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ledger_signer_signs_long_messages_and_reports_rejection() {
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let device = FakeLedger { key: key.clone(), reject: false, message: Default::default() };
+        let signer = LedgerSigner::connect(device, 0).await.unwrap();
+        assert_eq!(signer.public_key(), key.verifying_key().to_bytes());
+
+        let message = vec![0x5a; 600];
+        let signature = signer.sign(&message).await.unwrap();
+        assert_eq!(signature, key.sign(&message).to_bytes());
+
+        let device = FakeLedger { key, reject: true, message: Default::default() };
+        let signer = LedgerSigner::connect(device, 0).await.unwrap();
+        let err = signer.sign(b"header").await.unwrap_err();
+        assert!(err.to_string().contains("rejected"));
+    }
+
+    /// Serve one HTTP signing request, answering with the signature of `key`
+    async fn serve_signature(listener: &TcpListener, key: &SigningKey) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        let body = loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ").map(str::to_string))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                if body.len() >= length {
+                    break body.to_string();
+                }
+            }
+        };
+        let request: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let message = hex::decode(request["message"].as_str().unwrap()).unwrap();
+        let reply = serde_json::json!({ "signature": hex::encode(key.sign(&message).to_bytes()) }).to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+            reply.len(),
+            reply
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_signer_checks_returned_signatures() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = SignerConfig::Remote {
+            url: format!("http://{}/sign", listener.local_addr().unwrap()),
+            public_key: hex::encode(SigningKey::from_bytes(&[4u8; 32]).verifying_key().to_bytes()),
+            timeout_secs: 5,
+        };
+        let signer = connect_signer(&config).await.unwrap();
+
+        let key = SigningKey::from_bytes(&[4u8; 32]);
+        let (signature, _) = tokio::join!(signer.sign(b"block"), serve_signature(&listener, &key));
+        assert_eq!(signature.unwrap(), key.sign(b"block").to_bytes());
+
+        // A service signing with some other key is caught
+        let impostor = SigningKey::from_bytes(&[5u8; 32]);
+        let (signature, _) = tokio::join!(signer.sign(b"block"), serve_signature(&listener, &impostor));
+        assert!(matches!(signature, Err(ConsensusError::SignerError(_))));
+    }
+}
@@ -0,0 +1,129 @@
+//! Block time rules. A block must be later than the median timestamp of the blocks before
+//! it and no further ahead of the node's network-adjusted clock than the allowed drift. The
+//! adjusted clock is local time shifted by the median offset of the validators' clocks, as
+//! sampled from the proposals they send.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Blocks whose timestamps make up the median time past
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// Largest peer clock offset applied to the local clock, in seconds
+pub const MAX_CLOCK_OFFSET: u64 = 70 * 60;
+
+/// Samples needed before the network offset is applied
+const MIN_SAMPLES: usize = 5;
+
+/// Median of the timestamps of the blocks preceding a new one, which it must be later than
+pub fn median_time_past(timestamps: &[u64]) -> Option<u64> {
+    let recent = &timestamps[timestamps.len().saturating_sub(MEDIAN_TIME_SPAN)..];
+    if recent.is_empty() {
+        return None;
+    }
+    let mut sorted = recent.to_vec();
+    sorted.sort_unstable();
+    Some(sorted[sorted.len() / 2])
+}
+
+/// Seconds since the Unix epoch on the local clock
+pub fn local_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Snapshot of the network-adjusted clock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkTimeStatus {
+    pub local_time: u64,
+    pub adjusted_time: u64,
+    /// Seconds added to local time
+    pub offset: i64,
+    pub samples: usize,
+}
+
+/// Local clock corrected by the median offset of peers' clocks
+#[derive(Debug, Clone)]
+pub struct NetworkTime {
+    /// Latest offset observed from each validator, in seconds
+    offsets: HashMap<u64, i64>,
+    /// Offsets larger than this are not applied, since the local clock is then more
+    /// likely wrong than every peer's
+    max_offset: u64,
+}
+
+impl NetworkTime {
+    pub fn new(max_offset: u64) -> Self {
+        Self {
+            offsets: HashMap::new(),
+            max_offset,
+        }
+    }
+
+    /// Record `peer_time` reported by `validator_id` when the local clock read `local_time`
+    pub fn add_sample(&mut self, validator_id: u64, peer_time: u64, local_time: u64) {
+        let offset = peer_time as i64 - local_time as i64;
+        self.offsets.insert(validator_id, offset);
+    }
+
+    /// Median peer offset, or 0 with too few samples or an implausibly large median
+    pub fn offset(&self) -> i64 {
+        if self.offsets.len() < MIN_SAMPLES {
+            return 0;
+        }
+        let mut offsets: Vec<i64> = self.offsets.values().copied().collect();
+        offsets.sort_unstable();
+        let median = offsets[offsets.len() / 2];
+        if median.unsigned_abs() > self.max_offset {
+            return 0;
+        }
+        median
+    }
+
+    /// Current network-adjusted time
+    pub fn adjusted_time(&self) -> u64 {
+        local_time().saturating_add_signed(self.offset())
+    }
+
+    pub fn status(&self) -> NetworkTimeStatus {
+        let local_time = local_time();
+        NetworkTimeStatus {
+            local_time,
+            adjusted_time: local_time.saturating_add_signed(self.offset()),
+            offset: self.offset(),
+            samples: self.offsets.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median_time_past_and_network_offset() {
+        assert_eq!(median_time_past(&[]), None);
+        assert_eq!(median_time_past(&[5, 1, 3]), Some(3));
+        // Only the last eleven blocks count
+        let timestamps: Vec<u64> = (0..20).map(|i| 100 + i).collect();
+        assert_eq!(median_time_past(&timestamps), Some(114));
+
+        let mut time = NetworkTime::new(MAX_CLOCK_OFFSET);
+        for validator in 0..4 {
+            time.add_sample(validator, 1_030, 1_000);
+        }
+        assert_eq!(time.offset(), 0);
+        time.add_sample(4, 990, 1_000);
+        assert_eq!(time.offset(), 30);
+
+        // A validator's latest sample replaces its earlier one
+        for validator in 0..2 {
+            time.add_sample(validator, 1_000 + 10_000, 1_000);
+        }
+        assert_eq!(time.offset(), 30);
+        time.add_sample(2, 11_000, 1_000);
+        assert_eq!(time.offset(), 0);
+    }
+}
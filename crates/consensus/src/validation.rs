@@ -0,0 +1,425 @@
+//! Staged block validation shared by block import, templates and RPC submission. Checks run
+//! cheapest first: the block on its own, then against the chain it extends, then its proof
+//! of work, then its transactions' signatures, and last the state transition it makes. A
+//! failure names the stage and the rule that rejected the block.
+
+use crate::error::ConsensusError;
+use crate::limits::{BlockLimits, BlockWeight};
+use block_sync::{Block, BlockHeader, ProofType};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Stages of block validation, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationStage {
+    /// The block on its own: header fields, sizes, weights and transaction order
+    Syntactic,
+    /// The block against its parent: linkage, timestamps, difficulty and signer
+    Contextual,
+    /// The merge-mined proof of work
+    ProofOfWork,
+    /// The input signatures of the block's transactions, verified as one batch
+    Signatures,
+    /// Applying the block's transactions to the parent state
+    StateTransition,
+}
+
+impl fmt::Display for ValidationStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValidationStage::Syntactic => "syntactic",
+            ValidationStage::Contextual => "contextual",
+            ValidationStage::ProofOfWork => "pow",
+            ValidationStage::Signatures => "signatures",
+            ValidationStage::StateTransition => "state",
+        })
+    }
+}
+
+/// Why a block was rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BlockRejection {
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+
+    #[error("{0}")]
+    LimitExceeded(String),
+
+    #[error("Transaction {0} is out of the required order")]
+    MisorderedTransactions(usize),
+
+    #[error("Merkle root does not commit to the block's transactions")]
+    MerkleRootMismatch,
+
+    #[error("Transaction {0} has a hash other than its id")]
+    TransactionIdMismatch(usize),
+
+    #[error("Block {0} conflicts with a finalized checkpoint")]
+    ConflictsWithFinalized(u64),
+
+    #[error("Unknown parent for height {0}")]
+    UnknownParent(u64),
+
+    #[error("Block {0} already known")]
+    AlreadyKnown(u64),
+
+    #[error("Timestamp {timestamp} not after median time past {median}")]
+    TimestampTooOld { timestamp: u64, median: u64 },
+
+    #[error("Timestamp {timestamp} too far in the future of {now}")]
+    TimestampInFuture { timestamp: u64, now: u64 },
+
+    #[error("Difficulty {found} does not match required {expected}")]
+    WrongDifficulty { found: u64, expected: u64 },
+
+    #[error("{0}")]
+    InvalidSignature(String),
+
+    #[error("Missing proof of work")]
+    MissingProofOfWork,
+
+    #[error("Invalid proof of work")]
+    InvalidProofOfWork,
+
+    #[error("Transaction {tx_index}: {reason}")]
+    InvalidInputSignature { tx_index: usize, reason: String },
+
+    #[error("State transition failed: {0}")]
+    StateTransition(String),
+}
+
+impl BlockRejection {
+    /// Stage whose check rejected the block
+    pub fn stage(&self) -> ValidationStage {
+        match self {
+            BlockRejection::InvalidHeader(_)
+            | BlockRejection::LimitExceeded(_)
+            | BlockRejection::MisorderedTransactions(_)
+            | BlockRejection::MerkleRootMismatch
+            | BlockRejection::TransactionIdMismatch(_) => ValidationStage::Syntactic,
+            BlockRejection::MissingProofOfWork | BlockRejection::InvalidProofOfWork => ValidationStage::ProofOfWork,
+            BlockRejection::InvalidInputSignature { .. } => ValidationStage::Signatures,
+            BlockRejection::StateTransition(_) => ValidationStage::StateTransition,
+            _ => ValidationStage::Contextual,
+        }
+    }
+
+    /// Stable name of the broken rule
+    pub fn code(&self) -> &'static str {
+        match self {
+            BlockRejection::InvalidHeader(_) => "invalid_header",
+            BlockRejection::LimitExceeded(_) => "limit_exceeded",
+            BlockRejection::MisorderedTransactions(_) => "misordered_transactions",
+            BlockRejection::MerkleRootMismatch => "merkle_root_mismatch",
+            BlockRejection::TransactionIdMismatch(_) => "transaction_id_mismatch",
+            BlockRejection::ConflictsWithFinalized(_) => "conflicts_with_finalized",
+            BlockRejection::UnknownParent(_) => "unknown_parent",
+            BlockRejection::AlreadyKnown(_) => "already_known",
+            BlockRejection::TimestampTooOld { .. } => "timestamp_too_old",
+            BlockRejection::TimestampInFuture { .. } => "timestamp_in_future",
+            BlockRejection::WrongDifficulty { .. } => "wrong_difficulty",
+            BlockRejection::InvalidSignature(_) => "invalid_signature",
+            BlockRejection::MissingProofOfWork => "missing_pow",
+            BlockRejection::InvalidProofOfWork => "invalid_pow",
+            BlockRejection::InvalidInputSignature { .. } => "invalid_input_signature",
+            BlockRejection::StateTransition(_) => "state_transition",
+        }
+    }
+}
+
+/// Dry run of a block's transactions against its parent state
+pub trait StateTransition: Send + Sync {
+    /// Check `block` applies to the state it builds on, its fees paid to `fee_recipient`,
+    /// without committing anything
+    fn check_block(&self, block: &Block, fee_recipient: &[u8]) -> Result<(), String>;
+
+    /// Root of the spent-nullifier set once `block` spends its nullifiers on the state it builds on
+    fn nullifier_root(&self, block: &Block) -> Result<[u8; 32], String>;
+}
+
+/// What the chain a block extends requires of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockContext {
+    /// Hash of the canonical block below, `None` for genesis
+    pub parent_hash: Option<[u8; 32]>,
+    /// Median timestamp of the blocks below, `None` for genesis
+    pub median_time_past: Option<u64>,
+    /// Network-adjusted time now
+    pub adjusted_time: u64,
+    pub expected_difficulty: u64,
+}
+
+/// Block checks, run stage by stage
+#[derive(Clone)]
+pub struct BlockValidator {
+    limits: BlockLimits,
+    /// How far ahead of adjusted time a block timestamp may be, in seconds
+    max_future_drift: u64,
+    state: Option<Arc<dyn StateTransition>>,
+}
+
+impl BlockValidator {
+    pub fn new(limits: BlockLimits, max_future_drift: u64) -> Self {
+        Self {
+            limits,
+            max_future_drift,
+            state: None,
+        }
+    }
+
+    /// Run the state transition stage through `state`; without one the stage is skipped
+    pub fn set_state_transition(&mut self, state: Arc<dyn StateTransition>) {
+        self.state = Some(state);
+    }
+
+    pub fn limits(&self) -> &BlockLimits {
+        &self.limits
+    }
+
+    /// Check the block on its own, including its transaction order, that its header's
+    /// merkle root commits to exactly these transactions and that each carries its own id,
+    /// returning its measured size
+    pub fn check_syntax(&self, block: &Block) -> Result<BlockWeight, BlockRejection> {
+        block
+            .header
+            .verify()
+            .map_err(|e| BlockRejection::InvalidHeader(e.to_string()))?;
+        let weight = self.limits.check_block(block).map_err(|e| match e {
+            ConsensusError::BlockValidationFailed(reason) => BlockRejection::LimitExceeded(reason),
+            other => BlockRejection::LimitExceeded(other.to_string()),
+        })?;
+        if let Some(tx_index) = self.limits.transaction_ordering.first_misplaced(block) {
+            return Err(BlockRejection::MisorderedTransactions(tx_index));
+        }
+        if block.header.merkle_root != crate::merkle_root(&block.transactions) {
+            return Err(BlockRejection::MerkleRootMismatch);
+        }
+        if let Some(tx_index) = block.transactions.iter().position(|tx| !tx.has_valid_id()) {
+            return Err(BlockRejection::TransactionIdMismatch(tx_index));
+        }
+        Ok(weight)
+    }
+
+    /// Check the header against the chain it extends
+    pub fn check_context(&self, header: &BlockHeader, context: &BlockContext) -> Result<(), BlockRejection> {
+        if context.parent_hash.is_some_and(|parent| parent != header.prev_hash) {
+            return Err(BlockRejection::UnknownParent(header.height));
+        }
+        if let Some(median) = context.median_time_past {
+            if header.timestamp <= median {
+                return Err(BlockRejection::TimestampTooOld {
+                    timestamp: header.timestamp,
+                    median,
+                });
+            }
+        }
+        if header.timestamp > context.adjusted_time.saturating_add(self.max_future_drift) {
+            return Err(BlockRejection::TimestampInFuture {
+                timestamp: header.timestamp,
+                now: context.adjusted_time,
+            });
+        }
+        if header.difficulty != context.expected_difficulty {
+            return Err(BlockRejection::WrongDifficulty {
+                found: header.difficulty,
+                expected: context.expected_difficulty,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the merge-mined proof of work meets the header's difficulty
+    pub fn check_work(&self, block: &Block) -> Result<(), BlockRejection> {
+        if !matches!(block.proof.proof_type, ProofType::PoW) || block.proof.proof_data.is_empty() {
+            return Err(BlockRejection::MissingProofOfWork);
+        }
+        match block_sync::validation::BlockValidator::validate_merge_mining(block) {
+            Ok(true) => Ok(()),
+            _ => Err(BlockRejection::InvalidProofOfWork),
+        }
+    }
+
+    /// Check the input signatures of the block's transactions, if the chain requires them,
+    /// naming the first transaction whose signature fails
+    pub fn check_signatures(&self, block: &Block) -> Result<(), BlockRejection> {
+        if !self.limits.verify_input_signatures {
+            return Ok(());
+        }
+        block_sync::signatures::verify_batch(&block.transactions).map_err(|(tx_index, e)| {
+            BlockRejection::InvalidInputSignature {
+                tx_index,
+                reason: e.to_string(),
+            }
+        })
+    }
+
+    /// Check the block's transactions apply to its parent state, paying its fees to `fee_recipient`
+    pub fn check_state(&self, block: &Block, fee_recipient: &[u8]) -> Result<(), BlockRejection> {
+        match &self.state {
+            Some(state) => state.check_block(block, fee_recipient).map_err(BlockRejection::StateTransition),
+            None => Ok(()),
+        }
+    }
+
+    /// Nullifier root `block`'s header must carry for the state stage to accept it. Without a
+    /// state transition the stage is skipped and the root is left empty.
+    pub fn nullifier_root(&self, block: &Block) -> Result<[u8; 32], BlockRejection> {
+        match &self.state {
+            Some(state) => state.nullifier_root(block).map_err(BlockRejection::StateTransition),
+            None => Ok([0u8; 32]),
+        }
+    }
+
+    /// Run every stage for a block paying its fees to `fee_recipient`, stopping at the first rejection
+    pub fn validate(
+        &self,
+        block: &Block,
+        context: &BlockContext,
+        fee_recipient: &[u8],
+    ) -> Result<BlockWeight, BlockRejection> {
+        let weight = self.check_syntax(block)?;
+        self.check_context(&block.header, context)?;
+        self.check_work(block)?;
+        self.check_signatures(block)?;
+        self.check_state(block, fee_recipient)?;
+        Ok(weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::{mined_proposal, mined_proposal_with};
+    use block_sync::signatures::sign_input;
+    use block_sync::{OrderingPolicy, Transaction, TxInput};
+    use ed25519_dalek::SigningKey;
+
+    const PROPOSER: &[u8] = &[0x07; 32];
+
+    struct RejectAll;
+
+    impl StateTransition for RejectAll {
+        fn check_block(&self, _block: &Block, _fee_recipient: &[u8]) -> Result<(), String> {
+            Err("nonce 3 does not match account nonce 2".to_string())
+        }
+
+        fn nullifier_root(&self, _block: &Block) -> Result<[u8; 32], String> {
+            Err("no state".to_string())
+        }
+    }
+
+    #[test]
+    fn test_stages_report_where_a_block_failed() {
+        let block = mined_proposal(&SigningKey::from_bytes(&[7u8; 32]), 1, [1u8; 32], 1_000).block;
+        let context = BlockContext {
+            parent_hash: Some([1u8; 32]),
+            median_time_past: Some(990),
+            adjusted_time: 1_000,
+            expected_difficulty: 1,
+        };
+        let mut validator = BlockValidator::new(BlockLimits::default(), 120);
+        assert!(validator.validate(&block, &context, PROPOSER).is_ok());
+
+        let mut no_pow = block.clone();
+        no_pow.proof.proof_data.clear();
+        let mut zero_time = block.clone();
+        zero_time.header.timestamp = 0;
+        let cases = [
+            (zero_time, context.clone(), ValidationStage::Syntactic, "invalid_header"),
+            (
+                block.clone(),
+                BlockContext {
+                    parent_hash: Some([2u8; 32]),
+                    ..context.clone()
+                },
+                ValidationStage::Contextual,
+                "unknown_parent",
+            ),
+            (
+                block.clone(),
+                BlockContext {
+                    median_time_past: Some(1_000),
+                    ..context.clone()
+                },
+                ValidationStage::Contextual,
+                "timestamp_too_old",
+            ),
+            (no_pow, context.clone(), ValidationStage::ProofOfWork, "missing_pow"),
+        ];
+        for (block, context, stage, code) in cases {
+            let rejection = validator.validate(&block, &context, PROPOSER).unwrap_err();
+            assert_eq!((rejection.stage(), rejection.code()), (stage, code), "{}", rejection);
+        }
+
+        // Input signatures are checked as one batch once the chain requires them
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        let producer = SigningKey::from_bytes(&[7u8; 32]);
+        let mine = |transactions| mined_proposal_with(&producer, 1, [1u8; 32], 1_000, transactions);
+        let transactions: Vec<Transaction> = (0..3u8)
+            .map(|index| {
+                let mut tx = Transaction {
+                    hash: [index; 32],
+                    sender: vec![index],
+                    nonce: 0,
+                    gas_limit: 21_000,
+                    data: Vec::new(),
+                    inputs: vec![TxInput { prev_tx_hash: [0u8; 32], output_index: 0, signature: Vec::new() }],
+                    outputs: Vec::new(),
+                    fee: 21_000,
+                    timestamp: 1,
+                    nullifiers: Vec::new(),
+                    ring_inputs: Vec::new(),
+                    chain_id: 1,
+                    fee_payer: None,
+                    asset: None,
+                };
+                tx.inputs[0].signature = sign_input(&key, &tx);
+                tx.with_id()
+            })
+            .collect();
+        let signed = mine(transactions.clone()).block;
+        let strict = BlockValidator::new(BlockLimits { verify_input_signatures: true, ..Default::default() }, 120);
+        assert!(strict.validate(&signed, &context, PROPOSER).is_ok());
+
+        // A transaction changed under a mined header no longer matches its merkle root
+        let mut tampered = signed.clone();
+        tampered.transactions[1].hash = [0xff; 32];
+        let rejection = validator.validate(&tampered, &context, PROPOSER).unwrap_err();
+        assert_eq!((rejection.stage(), rejection.code()), (ValidationStage::Syntactic, "merkle_root_mismatch"));
+        tampered.transactions[1].hash = transactions[1].hash;
+        tampered.transactions[1].fee += 1;
+        let rejection = strict.validate(&tampered, &context, PROPOSER).unwrap_err();
+        assert_eq!(rejection.code(), "merkle_root_mismatch");
+
+        // Committed to and mined again, a made-up hash is not the transaction's id
+        let mut renamed = transactions.clone();
+        renamed[1].hash = [0xff; 32];
+        let rejection = validator.validate(&mine(renamed).block, &context, PROPOSER).unwrap_err();
+        assert_eq!((rejection.stage(), rejection.code()), (ValidationStage::Syntactic, "transaction_id_mismatch"));
+
+        // And with its id derived again, the changed transaction still fails its signature
+        let mut changed = transactions.clone();
+        changed[1].fee += 1;
+        changed[1] = changed[1].clone().with_id();
+        let tampered = mine(changed).block;
+        assert!(validator.validate(&tampered, &context, PROPOSER).is_ok());
+        let rejection = strict.validate(&tampered, &context, PROPOSER).unwrap_err();
+        assert_eq!((rejection.stage(), rejection.code()), (ValidationStage::Signatures, "invalid_input_signature"));
+        assert!(rejection.to_string().starts_with("Transaction 1: "), "{}", rejection);
+
+        // Under deterministic ordering the producer cannot choose where transactions go
+        let limits = BlockLimits { transaction_ordering: OrderingPolicy::Deterministic, ..Default::default() };
+        let deterministic = BlockValidator::new(limits, 120);
+        let mut ordered = OrderingPolicy::Deterministic.apply(&signed.header.prev_hash, transactions);
+        assert!(deterministic.validate(&mine(ordered.clone()).block, &context, PROPOSER).is_ok());
+        ordered.reverse();
+        let rejection = deterministic.validate(&mine(ordered).block, &context, PROPOSER).unwrap_err();
+        assert_eq!((rejection.stage(), rejection.code()), (ValidationStage::Syntactic, "misordered_transactions"));
+
+        validator.set_state_transition(Arc::new(RejectAll));
+        let rejection = validator.validate(&block, &context, PROPOSER).unwrap_err();
+        assert_eq!(rejection.stage(), ValidationStage::StateTransition);
+        assert!(rejection.to_string().contains("account nonce"));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::engine::{header_id, header_signing_bytes};
+use crate::error::ConsensusError;
+use block_sync::BlockHeader;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A block-signing validator and its stake
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validator {
+    pub id: u64,
+    /// Ed25519 public key
+    pub public_key: [u8; 32],
+    pub stake: u64,
+}
+
+/// Validators allowed to sign blocks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidatorSet {
+    validators: HashMap<u64, Validator>,
+    /// Minimum stake required to sign
+    pub min_stake: u64,
+}
+
+impl ValidatorSet {
+    /// Create an empty validator set
+    pub fn new(min_stake: u64) -> Self {
+        Self {
+            validators: HashMap::new(),
+            min_stake,
+        }
+    }
+
+    /// Add or replace a validator
+    pub fn insert(&mut self, validator: Validator) -> Result<(), ConsensusError> {
+        VerifyingKey::from_bytes(&validator.public_key)
+            .map_err(|e| ConsensusError::ConfigError(format!("Invalid key for validator {}: {}", validator.id, e)))?;
+        self.validators.insert(validator.id, validator);
+        Ok(())
+    }
+
+    /// Remove a validator
+    pub fn remove(&mut self, id: u64) -> Option<Validator> {
+        self.validators.remove(&id)
+    }
+
+    /// Get a validator by id
+    pub fn get(&self, id: u64) -> Option<&Validator> {
+        self.validators.get(&id)
+    }
+
+    /// Check if a validator has enough stake to sign
+    pub fn is_active(&self, id: u64) -> bool {
+        self.get(id).is_some_and(|validator| validator.stake >= self.min_stake && validator.stake > 0)
+    }
+
+    /// Total stake of the active validators
+    pub fn total_stake(&self) -> u64 {
+        self.validators
+            .values()
+            .filter(|validator| self.is_active(validator.id))
+            .map(|validator| validator.stake)
+            .sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.validators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty()
+    }
+
+    /// Verify that active validator `id` signed `message`
+    pub fn verify(&self, id: u64, message: &[u8], signature: &[u8]) -> Result<(), ConsensusError> {
+        if !self.is_active(id) {
+            return Err(ConsensusError::InvalidBlockProposal(format!("Validator {} is not staked", id)));
+        }
+        let validator = &self.validators[&id];
+        let key = VerifyingKey::from_bytes(&validator.public_key)
+            .map_err(|e| ConsensusError::InvalidBlockProposal(e.to_string()))?;
+        let signature = Signature::from_slice(signature)
+            .map_err(|e| ConsensusError::InvalidBlockProposal(format!("Malformed signature: {}", e)))?;
+        key.verify(message, &signature)
+            .map_err(|_| ConsensusError::InvalidBlockProposal(format!("Bad signature from validator {}", id)))
+    }
+}
+
+/// Proof that a validator signed two different blocks at the same height
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoubleSignEvidence {
+    pub validator_id: u64,
+    pub height: u64,
+    pub first_header: BlockHeader,
+    pub first_signature: Vec<u8>,
+    pub second_header: BlockHeader,
+    pub second_signature: Vec<u8>,
+}
+
+impl DoubleSignEvidence {
+    /// Check the evidence against the validator's public key
+    pub fn verify(&self, public_key: &[u8; 32]) -> Result<(), ConsensusError> {
+        if self.first_header.height != self.height || self.second_header.height != self.height {
+            return Err(ConsensusError::InvalidBlockProposal("Evidence headers are not at the same height".to_string()));
+        }
+        if header_id(&self.first_header) == header_id(&self.second_header) {
+            return Err(ConsensusError::InvalidBlockProposal("Evidence headers are identical".to_string()));
+        }
+
+        let key = VerifyingKey::from_bytes(public_key).map_err(|e| ConsensusError::InvalidBlockProposal(e.to_string()))?;
+        for (header, signature) in [
+            (&self.first_header, &self.first_signature),
+            (&self.second_header, &self.second_signature),
+        ] {
+            let signature = Signature::from_slice(signature)
+                .map_err(|e| ConsensusError::InvalidBlockProposal(format!("Malformed signature: {}", e)))?;
+            key.verify(&header_signing_bytes(header), &signature)
+                .map_err(|_| ConsensusError::InvalidBlockProposal("Evidence signature does not verify".to_string()))?;
+        }
+        Ok(())
+    }
+}
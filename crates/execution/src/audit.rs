@@ -0,0 +1,159 @@
+//! Audit reports: an account's stealth transfers over a block range, signed with its
+//! scan key, and the checks an auditor runs on them against the chain.
+
+use crate::error::ExecutionError;
+use crate::receipt::{get_ring_spends, get_stealth_outputs, StealthOutputRecord};
+use hashing::Domain;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+use std::collections::HashSet;
+use zk_proofs::{verify_disclosure_signature, ViewingKey};
+
+/// Ring input that spent one of the reporting account's outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisclosedSpend {
+    pub block_height: u64,
+    pub tx_hash: [u8; 32],
+    pub input_index: u32,
+    pub amount: u64,
+    pub key_image: [u8; 32],
+    /// Ring member the input really spent
+    pub one_time_key: [u8; 32],
+}
+
+/// Incoming and outgoing stealth transfers of one address in `from_block..=to_block`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditReport {
+    pub scan_public: [u8; 32],
+    pub spend_public: [u8; 32],
+    pub from_block: u64,
+    pub to_block: u64,
+    pub incoming: Vec<StealthOutputRecord>,
+    pub outgoing: Vec<DisclosedSpend>,
+    /// Scan-key signature over the rest of the report
+    pub signature: Vec<u8>,
+}
+
+impl AuditReport {
+    /// Collect and sign the transfers `viewing_key` can see in `from_block..=to_block`.
+    /// The range is clamped to the latest committed block.
+    pub fn build(
+        state: &RocksStateDB,
+        viewing_key: &ViewingKey,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Self, ExecutionError> {
+        let to_block = to_block.min(state.latest_version().unwrap_or(0));
+        let view_key = viewing_key.view_key();
+        let incoming = get_stealth_outputs(state, from_block, to_block)?
+            .into_iter()
+            .filter(|output| {
+                <[u8; 32]>::try_from(output.one_time_key.as_slice())
+                    .is_ok_and(|one_time_key| view_key.owns(output.output_index, &output.ephemeral_key, &one_time_key))
+            })
+            .collect();
+        // Only the holder of the outgoing key can make a tag unmask to a ring member
+        let outgoing = get_ring_spends(state, from_block, to_block)?
+            .into_iter()
+            .filter_map(|spend| {
+                let one_time_key = viewing_key.spent_output(&spend.key_image, &spend.audit_tag?);
+                spend.ring.contains(&one_time_key).then_some(DisclosedSpend {
+                    block_height: spend.block_height,
+                    tx_hash: spend.tx_hash,
+                    input_index: spend.input_index,
+                    amount: spend.amount,
+                    key_image: spend.key_image,
+                    one_time_key,
+                })
+            })
+            .collect();
+
+        let mut report = Self {
+            scan_public: viewing_key.scan_public(),
+            spend_public: viewing_key.spend_public(),
+            from_block,
+            to_block,
+            incoming,
+            outgoing,
+            signature: Vec::new(),
+        };
+        report.signature = viewing_key.sign(&report.digest()?).to_vec();
+        Ok(report)
+    }
+
+    /// Hash of everything the signature covers
+    pub fn digest(&self) -> Result<[u8; 32], ExecutionError> {
+        let body = (
+            self.scan_public,
+            self.spend_public,
+            self.from_block,
+            self.to_block,
+            &self.incoming,
+            &self.outgoing,
+        );
+        Ok(hashing::hash(Domain::AuditReport, &serde_json::to_vec(&body)?))
+    }
+
+    pub fn verify_signature(&self) -> bool {
+        self.digest()
+            .is_ok_and(|digest| verify_disclosure_signature(&self.scan_public, &digest, &self.signature))
+    }
+}
+
+/// Check a report on the auditor's side: it is signed by the address it describes and
+/// every transfer in it happened on this chain. Given the account's viewing key, also
+/// check that the report leaves nothing out.
+pub fn check_audit_report(
+    state: &RocksStateDB,
+    report: &AuditReport,
+    viewing_key: Option<&ViewingKey>,
+) -> Result<(), ExecutionError> {
+    let invalid = |reason: String| Err(ExecutionError::InvalidAuditReport(reason));
+    if !report.verify_signature() {
+        return invalid("signature does not match the scan key".to_string());
+    }
+
+    let outputs = get_stealth_outputs(state, report.from_block, report.to_block)?;
+    for output in &report.incoming {
+        if !outputs.contains(output) {
+            return invalid(format!("no output {} in block {}", hex::encode(&output.one_time_key), output.block_height));
+        }
+    }
+    let spends = get_ring_spends(state, report.from_block, report.to_block)?;
+    for disclosed in &report.outgoing {
+        let found = spends.iter().any(|spend| {
+            spend.block_height == disclosed.block_height
+                && spend.tx_hash == disclosed.tx_hash
+                && spend.input_index == disclosed.input_index
+                && spend.amount == disclosed.amount
+                && spend.key_image == disclosed.key_image
+                && spend.ring.contains(&disclosed.one_time_key)
+        });
+        if !found {
+            return invalid(format!("no spend with key image {}", hex::encode(disclosed.key_image)));
+        }
+    }
+    let key_images: HashSet<_> = report.outgoing.iter().map(|spend| spend.key_image).collect();
+    if key_images.len() != report.outgoing.len() {
+        return invalid("a spend is listed twice".to_string());
+    }
+
+    if let Some(viewing_key) = viewing_key {
+        if viewing_key.scan_public() != report.scan_public || viewing_key.spend_public() != report.spend_public {
+            return invalid("viewing key belongs to another address".to_string());
+        }
+        let complete = AuditReport::build(state, viewing_key, report.from_block, report.to_block)?;
+        if complete.incoming != report.incoming || complete.outgoing != report.outgoing {
+            return invalid(format!(
+                "report lists {} incoming and {} outgoing transfers, the viewing key finds {} and {}",
+                report.incoming.len(),
+                report.outgoing.len(),
+                complete.incoming.len(),
+                complete.outgoing.len()
+            ));
+        }
+    }
+    Ok(())
+}
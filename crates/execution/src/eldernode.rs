@@ -0,0 +1,203 @@
+//! Eldernode service registry. Operators register by staking HEAT with the registry
+//! account, keep their registration live with heartbeats and deregister to get the stake
+//! back. A configured share of every block's fees is split among the live Eldernodes,
+//! and the split is committed with the block so who served it can be queried later.
+
+use crate::error::ExecutionError;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+
+/// Account holding Eldernode stakes; transactions paying it carry a registry command
+pub const ELDERNODE_REGISTRY_ADDRESS: &[u8] = &[0xed; 20];
+
+const ELDERNODE_PREFIX: &[u8] = b"eldernode/node/";
+const ELDERNODE_INDEX_KEY: &[u8] = b"eldernode/index";
+const SERVED_PREFIX: &[u8] = b"eldernode/served/";
+
+/// Basis points in one whole
+const BPS: u128 = 10_000;
+
+/// Eldernode registry and fee routing rules; every node of a network must agree on them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EldernodeConfig {
+    /// Stake an Eldernode must keep registered to serve blocks
+    pub min_stake: u64,
+    /// Share of each block's fees routed to live Eldernodes, in basis points
+    pub fee_share_bps: u64,
+    /// Blocks an Eldernode stays live after registering or its last heartbeat
+    pub heartbeat_window: u64,
+}
+
+impl Default for EldernodeConfig {
+    fn default() -> Self {
+        Self {
+            min_stake: 800_000,
+            fee_share_bps: 1_000,
+            heartbeat_window: 100,
+        }
+    }
+}
+
+/// What a transaction paying the registry asks of it, encoded in one byte of transaction data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EldernodeCommand {
+    /// Register the sender, staking the amount paid
+    Register,
+    /// Mark the sender live, adding the amount paid to its stake
+    Heartbeat,
+    /// Remove the sender and return its stake
+    Deregister,
+}
+
+impl EldernodeCommand {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x01] => Some(Self::Register),
+            [0x02] => Some(Self::Heartbeat),
+            [0x03] => Some(Self::Deregister),
+            _ => None,
+        }
+    }
+
+    pub fn to_data(self) -> Vec<u8> {
+        match self {
+            Self::Register => vec![0x01],
+            Self::Heartbeat => vec![0x02],
+            Self::Deregister => vec![0x03],
+        }
+    }
+}
+
+/// A registered Eldernode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Eldernode {
+    pub address: Vec<u8>,
+    /// HEAT held by the registry for this Eldernode
+    pub stake: u64,
+    pub registered_at: u64,
+    /// Height of the registration or latest heartbeat
+    pub last_heartbeat: u64,
+    pub blocks_served: u64,
+    pub fees_earned: u64,
+}
+
+impl Eldernode {
+    /// Whether the Eldernode serves the block at `height`
+    pub fn is_active(&self, config: &EldernodeConfig, height: u64) -> bool {
+        self.stake >= config.min_stake && height.saturating_sub(self.last_heartbeat) <= config.heartbeat_window
+    }
+}
+
+/// An Eldernode's part of one block's fees
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EldernodeShare {
+    pub address: Vec<u8>,
+    pub fee: u64,
+}
+
+/// State key of the Eldernode registered by `address`
+pub fn eldernode_key(address: &[u8]) -> Vec<u8> {
+    [ELDERNODE_PREFIX, address].concat()
+}
+
+pub(crate) fn eldernode_index_key() -> Vec<u8> {
+    ELDERNODE_INDEX_KEY.to_vec()
+}
+
+/// State key of the Eldernodes that served the block at `height`
+pub fn served_key(height: u64) -> Vec<u8> {
+    [SERVED_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+/// Split `fees * fee_share_bps` among `active` Eldernodes pro rata to stake. The rounding
+/// remainder goes to the largest stake, the lowest address breaking ties.
+pub fn fee_shares(config: &EldernodeConfig, fees: u64, active: &[Eldernode]) -> Vec<EldernodeShare> {
+    let routed = (fees as u128 * config.fee_share_bps.min(BPS as u64) as u128 / BPS) as u64;
+    let total: u128 = active.iter().map(|node| node.stake as u128).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut shares: Vec<EldernodeShare> = active
+        .iter()
+        .map(|node| EldernodeShare {
+            address: node.address.clone(),
+            fee: (routed as u128 * node.stake as u128 / total) as u64,
+        })
+        .collect();
+    let remainder = routed - shares.iter().map(|share| share.fee).sum::<u64>();
+    if let Some(largest) = active
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.stake.cmp(&b.stake).then_with(|| b.address.cmp(&a.address)))
+        .map(|(index, _)| index)
+    {
+        shares[largest].fee += remainder;
+    }
+    shares
+}
+
+/// The Eldernode registered by `address`, if any; deregistered ones leave an empty record
+pub fn get_eldernode(state: &RocksStateDB, address: &[u8]) -> Result<Option<Eldernode>, ExecutionError> {
+    state
+        .get_sync(&eldernode_key(address))?
+        .filter(|bytes| !bytes.is_empty())
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Every registered Eldernode, ordered by address
+pub fn get_eldernodes(state: &RocksStateDB) -> Result<Vec<Eldernode>, ExecutionError> {
+    let index: Vec<Vec<u8>> = match state.get_sync(ELDERNODE_INDEX_KEY)? {
+        Some(bytes) => serde_json::from_slice(&bytes)?,
+        None => return Ok(Vec::new()),
+    };
+    index
+        .iter()
+        .map(|address| {
+            get_eldernode(state, address)?
+                .ok_or_else(|| ExecutionError::StateError(format!("Missing Eldernode {}", hex::encode(address))))
+        })
+        .collect()
+}
+
+/// The Eldernodes that served the block at `height` and their fee shares
+pub fn get_served(state: &RocksStateDB, height: u64) -> Result<Vec<EldernodeShare>, ExecutionError> {
+    match state.get_sync(&served_key(height))? {
+        Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(address: u8, stake: u64) -> Eldernode {
+        Eldernode {
+            address: vec![address],
+            stake,
+            registered_at: 1,
+            last_heartbeat: 1,
+            blocks_served: 0,
+            fees_earned: 0,
+        }
+    }
+
+    #[test]
+    fn test_fee_shares_are_pro_rata_and_deterministic() {
+        let config = EldernodeConfig { fee_share_bps: 5_000, ..Default::default() };
+        // 101 of 202 fees, split 3:1 with the remainder to the larger stake
+        let shares = fee_shares(&config, 202, &[node(1, 100), node(2, 300)]);
+        assert_eq!(shares.iter().map(|share| share.fee).collect::<Vec<_>>(), vec![25, 76]);
+        // Equal stakes break the tie by the lower address
+        let shares = fee_shares(&config, 3, &[node(1, 100), node(2, 100)]);
+        assert_eq!(shares.iter().map(|share| share.fee).collect::<Vec<_>>(), vec![1, 0]);
+        assert!(fee_shares(&config, 100, &[]).is_empty());
+
+        assert!(node(1, 800_000).is_active(&EldernodeConfig::default(), 101));
+        assert!(!node(1, 800_000).is_active(&EldernodeConfig::default(), 102));
+        assert!(!node(1, 799_999).is_active(&EldernodeConfig::default(), 2));
+    }
+}
@@ -0,0 +1,161 @@
+//! Block subsidy emission. Each network fixes how much new HEAT a block may mint for its
+//! producer, either halving at a fixed interval or decaying smoothly every epoch, and
+//! block execution rejects blocks that claim more.
+
+use crate::error::ExecutionError;
+use serde::{Deserialize, Serialize};
+
+/// Basis points in one whole
+const BPS: u128 = 10_000;
+
+/// Fixed-point scale of decay factors
+const ONE: u128 = 1_000_000_000_000_000_000;
+
+/// Network whose emission rules apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl std::str::FromStr for Network {
+    type Err = ExecutionError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            _ => Err(ExecutionError::ConfigError(format!("Unknown network {}", name))),
+        }
+    }
+}
+
+/// How the subsidy falls with height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmissionCurve {
+    /// Halve the subsidy every `interval` blocks
+    Halving { interval: u64 },
+    /// Cut the subsidy by `decay_bps` at the start of every `epoch_length` blocks
+    Decay { epoch_length: u64, decay_bps: u64 },
+}
+
+/// Block subsidy as a function of height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmissionSchedule {
+    /// Subsidy of the first blocks
+    pub initial_subsidy: u64,
+    pub curve: EmissionCurve,
+    /// The subsidy never falls below this
+    pub tail_subsidy: u64,
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        Self::for_network(Network::Mainnet)
+    }
+}
+
+impl EmissionSchedule {
+    /// The schedule `network` launched with
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Mainnet => Self {
+                initial_subsidy: 5_000_000_000,
+                curve: EmissionCurve::Halving { interval: 2_100_000 },
+                tail_subsidy: 0,
+            },
+            Network::Testnet => Self {
+                initial_subsidy: 5_000_000_000,
+                curve: EmissionCurve::Halving { interval: 210_000 },
+                tail_subsidy: 0,
+            },
+            Network::Devnet => Self {
+                initial_subsidy: 5_000_000_000,
+                curve: EmissionCurve::Decay {
+                    epoch_length: 1_000,
+                    decay_bps: 100,
+                },
+                tail_subsidy: 1_000_000,
+            },
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ExecutionError> {
+        match self.curve {
+            EmissionCurve::Halving { interval: 0 } => {
+                Err(ExecutionError::ConfigError("Halving interval must be positive".to_string()))
+            }
+            EmissionCurve::Decay { epoch_length: 0, .. } => {
+                Err(ExecutionError::ConfigError("Decay epoch length must be positive".to_string()))
+            }
+            EmissionCurve::Decay { decay_bps, .. } if decay_bps as u128 > BPS => {
+                Err(ExecutionError::ConfigError("Decay cannot exceed 10000 bps".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Most new HEAT the block at `height` may mint
+    pub fn subsidy(&self, height: u64) -> u64 {
+        let curve = match self.curve {
+            EmissionCurve::Halving { interval } => {
+                let halvings = height / interval.max(1);
+                if halvings >= 64 {
+                    0
+                } else {
+                    self.initial_subsidy >> halvings
+                }
+            }
+            EmissionCurve::Decay { epoch_length, decay_bps } => {
+                let epochs = height / epoch_length.max(1);
+                let retained = (BPS - (decay_bps as u128).min(BPS)) * (ONE / BPS);
+                (self.initial_subsidy as u128 * fixed_pow(retained, epochs) / ONE) as u64
+            }
+        };
+        curve.max(self.tail_subsidy)
+    }
+}
+
+/// `base^exponent` in fixed point, rounding down after every multiplication
+fn fixed_pow(mut base: u128, mut exponent: u64) -> u128 {
+    let mut result = ONE;
+    while exponent > 0 && result > 0 {
+        if exponent & 1 == 1 {
+            result = result * base / ONE;
+        }
+        base = base * base / ONE;
+        exponent >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsidy_halves_or_decays_to_the_tail() {
+        let mainnet = EmissionSchedule::for_network(Network::Mainnet);
+        assert_eq!(mainnet.subsidy(1), 5_000_000_000);
+        assert_eq!(mainnet.subsidy(2_099_999), 5_000_000_000);
+        assert_eq!(mainnet.subsidy(2_100_000), 2_500_000_000);
+        assert_eq!(mainnet.subsidy(u64::MAX), 0);
+
+        let devnet = EmissionSchedule::for_network(Network::Devnet);
+        assert_eq!(devnet.subsidy(999), 5_000_000_000);
+        assert_eq!(devnet.subsidy(1_000), 4_950_000_000);
+        assert_eq!(devnet.subsidy(2_000), 4_900_500_000);
+        assert_eq!(devnet.subsidy(u64::MAX), 1_000_000);
+
+        let broken = EmissionSchedule {
+            curve: EmissionCurve::Halving { interval: 0 },
+            ..mainnet
+        };
+        assert!(broken.validate().is_err());
+    }
+}
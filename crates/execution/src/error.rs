@@ -0,0 +1,40 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum ExecutionError {
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(String),
+
+    #[error("Invalid block: {0}")]
+    InvalidBlock(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Invalid log filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("State error: {0}")]
+    StateError(String),
+
+    #[error("Invalid audit report: {0}")]
+    InvalidAuditReport(String),
+
+    #[error("Invalid view key: {0}")]
+    InvalidViewKey(String),
+
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+}
+
+impl From<state_db::error::StateDBError> for ExecutionError {
+    fn from(err: state_db::error::StateDBError) -> Self {
+        ExecutionError::StateError(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ExecutionError {
+    fn from(err: serde_json::Error) -> Self {
+        ExecutionError::SerializationError(err.to_string())
+    }
+}
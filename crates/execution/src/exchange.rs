@@ -0,0 +1,312 @@
+//! Deposit addresses for exchanges. An account derives a deposit address per user: a public
+//! address from the account and an index, and once the account has registered its stealth
+//! view key, the stealth subaddress of the same index for private deposits. The node keeps
+//! an index from every derived address back to its account and index, outside the versioned
+//! state, and finds the payments to them in executed blocks.
+
+use crate::error::ExecutionError;
+use crate::receipt::{address_topic, get_logs, get_stealth_outputs, transfer_topic, LogFilter};
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+use zk_proofs::ViewKey;
+
+const DEPOSIT_ADDRESS_PREFIX: &[u8] = b"exchange/address/";
+const STEALTH_DEPOSIT_PREFIX: &[u8] = b"exchange/stealth/";
+const VIEW_KEY_PREFIX: &[u8] = b"exchange/view_key/";
+const NEXT_INDEX_PREFIX: &[u8] = b"exchange/next_index/";
+
+/// Public deposit address `index` of `account`
+pub fn deposit_address(account: &[u8], index: u64) -> Vec<u8> {
+    Hasher::new(Domain::DepositAddress).bytes(account).u64(index).finish()[12..].to_vec()
+}
+
+/// Stealth keys an account scans for private deposits with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositViewKey {
+    pub scan_secret: [u8; 32],
+    pub spend_public: [u8; 32],
+}
+
+impl DepositViewKey {
+    pub fn view_key(&self) -> Result<ViewKey, ExecutionError> {
+        ViewKey::from_bytes(&self.scan_secret, &self.spend_public)
+            .map_err(|e| ExecutionError::InvalidViewKey(e.to_string()))
+    }
+}
+
+/// A deposit address derived by an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositAddress {
+    pub account: Vec<u8>,
+    pub index: u64,
+    /// Public address paid by transparent deposits
+    pub address: Vec<u8>,
+    /// Stealth subaddress paid by private deposits, once the account has a view key
+    pub stealth_address: Option<Vec<u8>>,
+}
+
+/// A payment to a deposit address in an executed block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectedDeposit {
+    pub account: Vec<u8>,
+    pub index: u64,
+    pub block_height: u64,
+    pub tx_hash: [u8; 32],
+    /// Position of a public deposit's transfer log within its block, or of a private
+    /// deposit's output within its transaction
+    pub position: u32,
+    pub amount: u64,
+    /// Bridged token deposited, or `None` for HEAT
+    pub asset: Option<[u8; 32]>,
+    /// Paid to the stealth subaddress rather than the public address
+    pub private: bool,
+    /// Blocks from the deposit's to the latest, both included
+    pub confirmations: u64,
+}
+
+fn address_key(address: &[u8]) -> Vec<u8> {
+    [DEPOSIT_ADDRESS_PREFIX, address_topic(address).as_slice()].concat()
+}
+
+fn stealth_key(spend_public: &[u8; 32]) -> Vec<u8> {
+    [STEALTH_DEPOSIT_PREFIX, spend_public.as_slice()].concat()
+}
+
+fn view_key_key(account: &[u8]) -> Vec<u8> {
+    [VIEW_KEY_PREFIX, account].concat()
+}
+
+fn next_index_key(account: &[u8]) -> Vec<u8> {
+    [NEXT_INDEX_PREFIX, account].concat()
+}
+
+fn next_index(state: &RocksStateDB, account: &[u8]) -> Result<u64, ExecutionError> {
+    match state.get_sync(&next_index_key(account))? {
+        Some(bytes) => <[u8; 8]>::try_from(bytes.as_slice())
+            .map(u64::from_be_bytes)
+            .map_err(|_| ExecutionError::StateError("Malformed deposit address index".to_string())),
+        None => Ok(0),
+    }
+}
+
+/// The view key `account` registered for private deposits, if any
+pub fn get_view_key(state: &RocksStateDB, account: &[u8]) -> Result<Option<DepositViewKey>, ExecutionError> {
+    state
+        .get_sync(&view_key_key(account))?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// The deposit address record of `address`, if it was derived by an account
+pub fn get_deposit_address(state: &RocksStateDB, address: &[u8]) -> Result<Option<DepositAddress>, ExecutionError> {
+    state
+        .get_sync(&address_key(address))?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Index entries of deposit address `index` of `account`, with its stealth subaddress when
+/// the account has a view key
+fn address_entries(
+    account: &[u8],
+    index: u64,
+    view_key: Option<&ViewKey>,
+) -> Result<(DepositAddress, Vec<(Vec<u8>, Vec<u8>)>), ExecutionError> {
+    let subaddress = view_key.map(|view_key| view_key.subaddress(index));
+    let record = DepositAddress {
+        account: account.to_vec(),
+        index,
+        address: deposit_address(account, index),
+        stealth_address: subaddress.map(|subaddress| subaddress.to_bytes().to_vec()),
+    };
+    let value = serde_json::to_vec(&record)?;
+    let mut entries = vec![(address_key(&record.address), value.clone())];
+    if let Some(subaddress) = subaddress {
+        entries.push((stealth_key(&subaddress.spend_public), value));
+    }
+    Ok((record, entries))
+}
+
+/// Register the view key `account` scans for private deposits with, giving the addresses it
+/// has already derived their stealth subaddresses
+pub fn register_view_key(state: &mut RocksStateDB, account: &[u8], key: DepositViewKey) -> Result<(), ExecutionError> {
+    let view_key = key.view_key()?;
+    let mut entries = vec![(view_key_key(account), serde_json::to_vec(&key)?)];
+    for index in 0..next_index(state, account)? {
+        entries.extend(address_entries(account, index, Some(&view_key))?.1);
+    }
+    state.write_batch_sync(&entries)?;
+    Ok(())
+}
+
+/// Derive the next deposit address of `account` and index it
+pub fn new_deposit_address(state: &mut RocksStateDB, account: &[u8]) -> Result<DepositAddress, ExecutionError> {
+    let index = next_index(state, account)?;
+    let view_key = get_view_key(state, account)?.map(|key| key.view_key()).transpose()?;
+    let (record, mut entries) = address_entries(account, index, view_key.as_ref())?;
+    entries.push((next_index_key(account), (index + 1).to_be_bytes().to_vec()));
+    state.write_batch_sync(&entries)?;
+    Ok(record)
+}
+
+/// Deposits to derived addresses in blocks `from_block..=to_block`, in block order
+pub fn scan_deposits(state: &RocksStateDB, from_block: u64, to_block: u64) -> Result<Vec<DetectedDeposit>, ExecutionError> {
+    let Some(latest) = state.latest_version() else {
+        return Ok(Vec::new());
+    };
+    let confirmations = |height: u64| latest.saturating_sub(height) + 1;
+    let filter = LogFilter {
+        from_block: Some(from_block),
+        to_block: Some(to_block),
+        topics: vec![Some(vec![transfer_topic()])],
+        ..Default::default()
+    };
+
+    let mut deposits = Vec::new();
+    for log in get_logs(state, &filter)? {
+        let Some(recipient) = log.topics.get(2) else { continue };
+        let Some(bytes) = state.get_sync(&[DEPOSIT_ADDRESS_PREFIX, recipient.as_slice()].concat())? else {
+            continue;
+        };
+        let record: DepositAddress = serde_json::from_slice(&bytes)?;
+        deposits.push(DetectedDeposit {
+            account: record.account,
+            index: record.index,
+            block_height: log.block_height,
+            tx_hash: log.tx_hash,
+            position: log.log_index,
+            amount: log.data.as_slice().try_into().map_or(0, u64::from_be_bytes),
+            asset: log.topics.get(3).copied(),
+            private: false,
+            confirmations: confirmations(log.block_height),
+        });
+    }
+
+    let view_keys = state
+        .scan_prefix_sync(VIEW_KEY_PREFIX)?
+        .into_iter()
+        .map(|(_, bytes)| serde_json::from_slice::<DepositViewKey>(&bytes)?.view_key())
+        .collect::<Result<Vec<_>, _>>()?;
+    if !view_keys.is_empty() {
+        for output in get_stealth_outputs(state, from_block, to_block)? {
+            let Ok(one_time_key) = <[u8; 32]>::try_from(output.one_time_key.as_slice()) else {
+                continue;
+            };
+            for view_key in &view_keys {
+                let Some(spend_key) = view_key.output_spend_key(output.output_index, &output.ephemeral_key, &one_time_key)
+                else {
+                    continue;
+                };
+                if let Some(bytes) = state.get_sync(&stealth_key(&spend_key))? {
+                    let record: DepositAddress = serde_json::from_slice(&bytes)?;
+                    deposits.push(DetectedDeposit {
+                        account: record.account,
+                        index: record.index,
+                        block_height: output.block_height,
+                        tx_hash: output.tx_hash,
+                        position: output.output_index,
+                        amount: output.amount,
+                        asset: None,
+                        private: true,
+                        confirmations: confirmations(output.block_height),
+                    });
+                    break;
+                }
+            }
+        }
+    }
+    deposits.sort_by_key(|deposit| deposit.block_height);
+    Ok(deposits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockExecutor, ExecutionConfig};
+    use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+    use state_db::account::GenesisAccount;
+    use state_db::Genesis;
+    use tempfile::TempDir;
+    use zk_proofs::StealthKeys;
+
+    const EXCHANGE: &[u8] = &[0xe0; 20];
+
+    fn payment(nonce: u64, output: TxOutput) -> Transaction {
+        Transaction {
+            hash: [nonce as u8 + 1; 32],
+            sender: vec![0xa1],
+            nonce,
+            gas_limit: 200_000,
+            data: Vec::new(),
+            inputs: vec![],
+            outputs: vec![output],
+            fee: 200_000,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        }
+    }
+
+    fn block(height: u64, transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_deposits_to_public_and_stealth_addresses_are_found() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut genesis = Genesis::default();
+        genesis.alloc.insert("a1".to_string(), GenesisAccount { balance: 1_000_000 });
+        state.apply_genesis(&genesis).unwrap();
+
+        // The address derived before the view key was registered gets a subaddress with it
+        let first = new_deposit_address(&mut state, EXCHANGE).unwrap();
+        assert_eq!((first.index, first.stealth_address.as_ref()), (0, None));
+        let keys = StealthKeys::from_seed(b"exchange");
+        let view_key = DepositViewKey { scan_secret: keys.scan_secret(), spend_public: keys.address().spend_public };
+        register_view_key(&mut state, EXCHANGE, view_key).unwrap();
+        let first = get_deposit_address(&state, &first.address).unwrap().unwrap();
+        assert_eq!(first.stealth_address, Some(keys.subaddress(0).to_bytes().to_vec()));
+        let second = new_deposit_address(&mut state, EXCHANGE).unwrap();
+        assert_eq!((second.index, second.address.clone()), (1, deposit_address(EXCHANGE, 1)));
+
+        let public = TxOutput { amount: 500, address: first.address, commitment: [0u8; 32], ephemeral_key: None };
+        let stealth = keys.subaddress(1).derive_output(0, &mut rand::rngs::OsRng).unwrap();
+        let private = TxOutput {
+            amount: 700,
+            address: stealth.one_time_key.to_vec(),
+            commitment: [0u8; 32],
+            ephemeral_key: Some(stealth.ephemeral_key),
+        };
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        executor.process_block(&mut state, &block(1, vec![payment(0, public), payment(1, private)]), &[0xfe]).unwrap();
+        executor.process_block(&mut state, &block(2, vec![]), &[0xfe]).unwrap();
+
+        let deposits = scan_deposits(&state, 0, 10).unwrap();
+        let found: Vec<_> = deposits.iter().map(|d| (d.index, d.amount, d.private, d.confirmations)).collect();
+        assert_eq!(found, vec![(0, 500, false, 2), (1, 700, true, 2)]);
+        assert!(deposits.iter().all(|deposit| deposit.account == EXCHANGE && deposit.block_height == 1));
+        assert!(scan_deposits(&state, 2, 2).unwrap().is_empty());
+    }
+}
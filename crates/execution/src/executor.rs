@@ -0,0 +1,2491 @@
+use crate::eldernode::{
+    eldernode_index_key, eldernode_key, fee_shares, served_key, Eldernode, EldernodeCommand, EldernodeConfig,
+    EldernodeShare, ELDERNODE_REGISTRY_ADDRESS,
+};
+use crate::emission::EmissionSchedule;
+use crate::error::ExecutionError;
+use crate::fee_market::{block_fees_key, split_fee, BlockFees, FeeMarketConfig};
+use crate::gas::{charged_fee, GasMeter, GasSchedule, GasStep, OutOfGas};
+use crate::receipt::{
+    address_topic, body_entry, header_entry, receipt_entries, transfer_topic, withdrawal_topic, Log, Receipt,
+    ReceiptStatus, RingSpendRecord, StealthOutputRecord,
+};
+use crate::shielded::{check_ring_input, decode_amount, stealth_output_key, SHIELDED_POOL_ADDRESS};
+use crate::token::{decode_balance, token_balance_key, token_key, Token, TokenInfo};
+use block_sync::{Block, Transaction};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use state_db::account::{account_key, code_key, GENESIS_VERSION};
+#[cfg(feature = "wasm")]
+use state_db::account::storage_key;
+use state_db::error::StateDBError;
+use state_db::merkle::EMPTY_HASH;
+use state_db::nullifier::{accumulate_nullifiers, nullifier_key, NULLIFIER_ROOT_KEY};
+use state_db::supply::{MintSource, SUPPLY_KEY};
+use state_db::{Account, MerkleRoot, RocksStateDB, StateView, SupplyLedger};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Sender of the transactions minting bridged L1 deposits; mints need no balance and pay no fee
+pub const MINT_ADDRESS: &[u8] = &[0u8; 20];
+
+/// Sender of the transactions minting HEAT against XFG burned on Fuego; mints are
+/// ordered by its own nonce
+pub const XFG_MINT_ADDRESS: &[u8] = &[0x01; 20];
+
+/// Sender of the transaction paying the block subsidy; it must come first in its block,
+/// carry the block height as its nonce and claim no more than the emission schedule allows
+pub const COINBASE_ADDRESS: &[u8] = &[0x02; 20];
+
+/// Outputs paid to this address are burned and withdrawn to the 20-byte L1 account in the
+/// transaction data
+pub const WITHDRAWAL_ADDRESS: &[u8] = &[0xff; 20];
+
+/// Block execution configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionConfig {
+    /// Most gas one block may use; each transaction must fit its gas limit in what is left
+    pub block_gas_limit: u64,
+    pub gas: GasSchedule,
+    /// Members every ring input must have: the real output plus `ring_size - 1` decoys
+    pub ring_size: usize,
+    #[serde(default)]
+    pub eldernode: EldernodeConfig,
+    /// Block subsidy schedule of the network
+    #[serde(default)]
+    pub emission: EmissionSchedule,
+    /// Chain id user transactions must be signed for
+    #[serde(default = "mainnet_chain_id")]
+    pub chain_id: u64,
+    /// Check signatures and execute transactions speculatively across the rayon pool,
+    /// re-executing in order those that read what an earlier transaction wrote
+    #[serde(default = "parallel_by_default")]
+    pub parallel_execution: bool,
+    #[serde(default)]
+    pub fee_market: FeeMarketConfig,
+    #[serde(default)]
+    pub mint_attestation: MintAttestation,
+}
+
+/// Bridge keys that authorize mints; every node of a network must agree on them.
+///
+/// A mint carries the attesters' signatures of it as its input signatures, and executes
+/// only when `threshold` distinct attesters signed it. Without attesters nothing mints.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MintAttestation {
+    /// Hex ed25519 public keys of the attesters
+    pub attesters: Vec<String>,
+    /// Distinct attesters each mint must be signed by
+    pub threshold: usize,
+}
+
+impl MintAttestation {
+    /// Attestation by the one key `attester`
+    pub fn single(attester: [u8; 32]) -> Self {
+        Self { attesters: vec![hex::encode(attester)], threshold: 1 }
+    }
+
+    pub fn validate(&self) -> Result<(), ExecutionError> {
+        if self.attesters.iter().any(|key| !hex::decode(key).is_ok_and(|key| key.len() == 32)) {
+            return Err(ExecutionError::ConfigError("Mint attesters must be hex 32-byte keys".to_string()));
+        }
+        if !self.attesters.is_empty() && !(1..=self.attesters.len()).contains(&self.threshold) {
+            return Err(ExecutionError::ConfigError(format!(
+                "Mint threshold {} is not between 1 and the {} attesters",
+                self.threshold,
+                self.attesters.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// How many distinct attesters are among `signers`
+    fn attested_by(&self, signers: &[[u8; 32]]) -> usize {
+        let signers: HashSet<&[u8; 32]> = signers.iter().collect();
+        self.attesters
+            .iter()
+            .filter_map(|key| hex::decode(key).ok()?.try_into().ok())
+            .filter(|key: &[u8; 32]| signers.contains(key))
+            .count()
+    }
+}
+
+fn mainnet_chain_id() -> u64 {
+    1
+}
+
+fn parallel_by_default() -> bool {
+    true
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            block_gas_limit: 30_000_000,
+            gas: GasSchedule::default(),
+            ring_size: 11,
+            eldernode: EldernodeConfig::default(),
+            emission: EmissionSchedule::default(),
+            chain_id: mainnet_chain_id(),
+            parallel_execution: parallel_by_default(),
+            fee_market: FeeMarketConfig::default(),
+            mint_attestation: MintAttestation::default(),
+        }
+    }
+}
+
+/// Execution statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionStats {
+    pub blocks_executed: u64,
+    pub transactions_executed: u64,
+    pub failed_transactions: u64,
+    pub gas_used: u64,
+    pub fees_collected: u64,
+    /// Base fees burned, or paid to the treasury
+    pub base_fees: u64,
+    pub heat_minted: u64,
+    pub heat_burned: u64,
+    /// Fees routed to Eldernodes
+    pub eldernode_fees: u64,
+    /// Speculative executions thrown away because an earlier transaction of the block wrote
+    /// what they read
+    pub reexecuted_transactions: u64,
+}
+
+/// Result of executing one block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockExecution {
+    pub height: u64,
+    pub state_root: MerkleRoot,
+    /// Root of the spent-nullifier set, as committed in the block header
+    pub nullifier_root: MerkleRoot,
+    pub transactions: usize,
+    pub gas_used: u64,
+    /// Fees paid to the validator: everything charged above the base fee
+    pub fees: u64,
+    /// Base fee per unit of gas the block charged
+    pub base_fee: u64,
+    /// Base fees burned, or paid to the treasury
+    pub base_fees: u64,
+    /// Eldernodes that served the block and their part of its fees, paid out of the validator's
+    pub eldernodes: Vec<EldernodeShare>,
+    pub receipts: Vec<Receipt>,
+}
+
+/// State written by a block so far
+#[derive(Clone, Default)]
+struct Changes {
+    accounts: BTreeMap<Vec<u8>, Account>,
+    /// Contract storage by state key
+    storage: BTreeMap<Vec<u8>, Vec<u8>>,
+    code: BTreeMap<[u8; 32], Vec<u8>>,
+}
+
+impl Changes {
+    /// Take on the writes of a later transaction
+    fn merge(&mut self, later: Changes) {
+        self.accounts.extend(later.accounts);
+        self.storage.extend(later.storage);
+        self.code.extend(later.code);
+    }
+
+    /// Add every change to `writes` under its state key
+    fn write_to(&self, writes: &mut StateWrites) -> Result<(), ExecutionError> {
+        for (address, account) in &self.accounts {
+            writes.insert(account_key(address), serde_json::to_vec(account)?);
+        }
+        for (key, value) in &self.storage {
+            writes.insert(key.clone(), value.clone());
+        }
+        for (code_hash, code) in &self.code {
+            writes.insert(code_key(code_hash), code.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Keys a speculative execution read from the parent state
+#[derive(Default)]
+struct Reads {
+    accounts: HashSet<Vec<u8>>,
+    storage: HashSet<Vec<u8>>,
+    code: HashSet<[u8; 32]>,
+}
+
+impl Reads {
+    /// Whether `changes` wrote anything read here, so the reads may be stale
+    fn conflict(&self, changes: &Changes) -> bool {
+        self.accounts.iter().any(|address| changes.accounts.contains_key(address))
+            || self.storage.iter().any(|key| changes.storage.contains_key(key))
+            || self.code.iter().any(|code_hash| changes.code.contains_key(code_hash))
+    }
+}
+
+/// A transaction executed alone on the parent state of its block
+struct Speculation {
+    result: Result<Receipt, ExecutionError>,
+    changes: Changes,
+    reads: Reads,
+}
+
+/// State a block executes on: the live StateDB, an earlier version of it, or a version with
+/// the writes of blocks not committed yet on top
+pub trait ParentState: Sync {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError>;
+}
+
+impl ParentState for RocksStateDB {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
+        self.get_sync(key)
+    }
+}
+
+/// Versioned state as committed at one version
+impl ParentState for StateView<'_> {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
+        StateView::get(self, key)
+    }
+}
+
+/// Versioned writes of a block, by state key
+pub type StateWrites = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// A committed state with the writes of blocks built on it that were checked but not
+/// committed, such as those of a side branch, applied on top in order
+pub struct PendingState<'a> {
+    base: &'a dyn ParentState,
+    writes: StateWrites,
+}
+
+impl<'a> PendingState<'a> {
+    pub fn new(base: &'a dyn ParentState) -> Self {
+        Self {
+            base,
+            writes: StateWrites::new(),
+        }
+    }
+
+    /// Apply the writes of the next block
+    pub fn apply(&mut self, writes: &StateWrites) {
+        self.writes.extend(writes.iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+}
+
+impl ParentState for PendingState<'_> {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
+        match self.writes.get(key) {
+            Some(value) => Ok(Some(value.clone())),
+            None => self.base.get(key),
+        }
+    }
+}
+
+/// Root of the spent-nullifier set in `parent`
+fn parent_nullifier_root(parent: &dyn ParentState) -> Result<MerkleRoot, ExecutionError> {
+    match parent.get(NULLIFIER_ROOT_KEY)? {
+        Some(bytes) => <[u8; 32]>::try_from(bytes.as_slice())
+            .map_err(|_| ExecutionError::StateError("Malformed nullifier root".to_string())),
+        None => Ok(EMPTY_HASH),
+    }
+}
+
+/// Nullifiers `block` spends, in block order. Key images of ring inputs are the nullifiers
+/// of the stealth outputs they spend.
+fn block_nullifiers(block: &Block) -> Vec<[u8; 32]> {
+    block
+        .transactions
+        .iter()
+        .flat_map(|tx| {
+            tx.nullifiers
+                .iter()
+                .copied()
+                .chain(tx.ring_inputs.iter().map(|input| input.key_image))
+        })
+        .collect()
+}
+
+/// A block executed on its parent state, none of whose effects are written yet
+struct ExecutedBlock {
+    changes: Changes,
+    supply: SupplyLedger,
+    nullifiers: Vec<[u8; 32]>,
+    nullifier_root: MerkleRoot,
+    receipts: Vec<Receipt>,
+    gas_used: u64,
+    fees: u64,
+    base_fee: u64,
+    base_fees: u64,
+    eldernodes: Vec<EldernodeShare>,
+    eldernode_fees: u64,
+    minted: u64,
+    burned: u64,
+    reexecuted: u64,
+}
+
+impl ExecutedBlock {
+    /// Everything the block at `height` writes to the versioned state
+    fn writes(&self, height: u64) -> Result<StateWrites, ExecutionError> {
+        let mut writes = StateWrites::new();
+        self.changes.write_to(&mut writes)?;
+        writes.insert(SUPPLY_KEY.to_vec(), serde_json::to_vec(&self.supply)?);
+        if !self.nullifiers.is_empty() {
+            for nullifier in &self.nullifiers {
+                writes.insert(nullifier_key(nullifier), height.to_be_bytes().to_vec());
+            }
+            writes.insert(NULLIFIER_ROOT_KEY.to_vec(), self.nullifier_root.to_vec());
+        }
+        Ok(writes)
+    }
+}
+
+/// State touched by a block, written to the StateDB only if every transaction is valid
+pub(crate) struct AccountOverlay<'a> {
+    state: &'a dyn ParentState,
+    changes: Changes,
+    /// Keys read from `state`, when executing speculatively
+    reads: Option<RefCell<Reads>>,
+    /// Gas charged by the current transaction, when tracing
+    pub(crate) gas_steps: Option<Vec<GasStep>>,
+}
+
+impl<'a> AccountOverlay<'a> {
+    pub(crate) fn new(state: &'a dyn ParentState) -> Self {
+        Self {
+            state,
+            changes: Changes::default(),
+            reads: None,
+            gas_steps: None,
+        }
+    }
+
+    /// An overlay recording every key it reads from `state`
+    fn tracking(state: &'a dyn ParentState) -> Self {
+        Self {
+            reads: Some(RefCell::default()),
+            ..Self::new(state)
+        }
+    }
+
+    fn record(&self, read: impl FnOnce(&mut Reads)) {
+        if let Some(reads) = &self.reads {
+            read(&mut reads.borrow_mut());
+        }
+    }
+
+    pub(crate) fn account(&mut self, address: &[u8]) -> Result<&mut Account, ExecutionError> {
+        if !self.changes.accounts.contains_key(address) {
+            self.record(|reads| {
+                reads.accounts.insert(address.to_vec());
+            });
+            let account = match self.state.get(&account_key(address))? {
+                Some(bytes) => serde_json::from_slice(&bytes)?,
+                None => Account::default(),
+            };
+            self.changes.accounts.insert(address.to_vec(), account);
+        }
+        Ok(self.changes.accounts.get_mut(address).expect("account was just loaded"))
+    }
+
+    /// Balance of every account written so far
+    pub(crate) fn balances(&self) -> BTreeMap<Vec<u8>, u64> {
+        self.changes
+            .accounts
+            .iter()
+            .map(|(address, account)| (address.clone(), account.balance))
+            .collect()
+    }
+
+    /// Balance of `address` in the parent state, whatever was written since
+    pub(crate) fn parent_balance(&self, address: &[u8]) -> Result<u64, ExecutionError> {
+        match self.state.get(&account_key(address))? {
+            Some(bytes) => Ok(serde_json::from_slice::<Account>(&bytes)?.balance),
+            None => Ok(0),
+        }
+    }
+
+    /// Contract code by hash
+    #[cfg(feature = "wasm")]
+    pub(crate) fn code(&self, code_hash: &[u8; 32]) -> Result<Option<Vec<u8>>, ExecutionError> {
+        match self.changes.code.get(code_hash) {
+            Some(code) => Ok(Some(code.clone())),
+            None => {
+                self.record(|reads| {
+                    reads.code.insert(*code_hash);
+                });
+                Ok(self.state.get(&code_key(code_hash))?)
+            }
+        }
+    }
+
+    /// Storage slot `key` of the contract at `address`
+    #[cfg(feature = "wasm")]
+    pub(crate) fn storage(&self, address: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, ExecutionError> {
+        let slot = storage_key(address, key);
+        match self.changes.storage.get(&slot) {
+            Some(value) => Ok(Some(value.clone())),
+            None => {
+                let stored = self.state.get(&slot)?;
+                self.record(|reads| {
+                    reads.storage.insert(slot);
+                });
+                Ok(stored)
+            }
+        }
+    }
+
+    /// Write a storage slot of the contract at `address`, advancing its storage root
+    #[cfg(feature = "wasm")]
+    pub(crate) fn put_storage(&mut self, address: &[u8], key: Vec<u8>, value: Vec<u8>) -> Result<(), ExecutionError> {
+        self.account(address)?.record_storage_write(&key, &value);
+        self.changes.storage.insert(storage_key(address, &key), value);
+        Ok(())
+    }
+
+    /// Amount of the stealth output paid to `one_time_key`
+    fn stealth_output(&self, one_time_key: &[u8]) -> Result<Option<u64>, ExecutionError> {
+        let key = stealth_output_key(one_time_key);
+        match self.changes.storage.get(&key) {
+            Some(value) => decode_amount(Some(value.clone())),
+            None => {
+                let stored = self.state.get(&key)?;
+                self.record(|reads| {
+                    reads.storage.insert(key);
+                });
+                decode_amount(stored)
+            }
+        }
+    }
+
+    /// The Eldernode registered by `address`
+    fn eldernode(&self, address: &[u8]) -> Result<Option<Eldernode>, ExecutionError> {
+        let key = eldernode_key(address);
+        match self.changes.storage.get(&key) {
+            Some(bytes) if bytes.is_empty() => Ok(None),
+            Some(bytes) => Ok(Some(serde_json::from_slice(bytes)?)),
+            None => {
+                let stored = self.state.get(&key)?.filter(|bytes| !bytes.is_empty());
+                self.record(|reads| {
+                    reads.storage.insert(key);
+                });
+                Ok(stored.map(|bytes| serde_json::from_slice(&bytes)).transpose()?)
+            }
+        }
+    }
+
+    /// Addresses of every registered Eldernode, in order
+    fn eldernode_index(&self) -> Result<Vec<Vec<u8>>, ExecutionError> {
+        let key = eldernode_index_key();
+        match self.changes.storage.get(&key) {
+            Some(bytes) => Ok(serde_json::from_slice(bytes)?),
+            None => {
+                let stored = self.state.get(&key)?;
+                self.record(|reads| {
+                    reads.storage.insert(key);
+                });
+                match stored {
+                    Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+                    None => Ok(Vec::new()),
+                }
+            }
+        }
+    }
+
+    /// Write `node`, adding it to the index if it is new
+    fn put_eldernode(&mut self, node: &Eldernode) -> Result<(), ExecutionError> {
+        let mut index = self.eldernode_index()?;
+        if let Err(position) = index.binary_search(&node.address) {
+            index.insert(position, node.address.clone());
+            self.changes.storage.insert(eldernode_index_key(), serde_json::to_vec(&index)?);
+        }
+        self.changes.storage.insert(eldernode_key(&node.address), serde_json::to_vec(node)?);
+        Ok(())
+    }
+
+    /// Remove the Eldernode registered by `address`, leaving an empty record
+    fn remove_eldernode(&mut self, address: &[u8]) -> Result<(), ExecutionError> {
+        let mut index = self.eldernode_index()?;
+        index.retain(|registered| registered != address);
+        self.changes.storage.insert(eldernode_index_key(), serde_json::to_vec(&index)?);
+        self.changes.storage.insert(eldernode_key(address), Vec::new());
+        Ok(())
+    }
+
+    /// The registered token `asset`
+    fn token(&self, asset: &[u8; 32]) -> Result<Option<Token>, ExecutionError> {
+        let key = token_key(asset);
+        match self.changes.storage.get(&key) {
+            Some(bytes) => Ok(Some(serde_json::from_slice(bytes)?)),
+            None => {
+                let stored = self.state.get(&key)?;
+                self.record(|reads| {
+                    reads.storage.insert(key);
+                });
+                Ok(stored.map(|bytes| serde_json::from_slice(&bytes)).transpose()?)
+            }
+        }
+    }
+
+    fn put_token(&mut self, token: &Token) -> Result<(), ExecutionError> {
+        self.changes.storage.insert(token_key(&token.asset), serde_json::to_vec(token)?);
+        Ok(())
+    }
+
+    /// Balance of `asset` held by `address`
+    fn token_balance(&self, asset: &[u8; 32], address: &[u8]) -> Result<u64, ExecutionError> {
+        let key = token_balance_key(asset, address);
+        match self.changes.storage.get(&key) {
+            Some(value) => decode_balance(Some(value.clone())),
+            None => {
+                let stored = self.state.get(&key)?;
+                self.record(|reads| {
+                    reads.storage.insert(key);
+                });
+                decode_balance(stored)
+            }
+        }
+    }
+
+    fn put_token_balance(&mut self, asset: &[u8; 32], address: &[u8], balance: u64) {
+        self.changes.storage.insert(token_balance_key(asset, address), balance.to_be_bytes().to_vec());
+    }
+
+    #[cfg(feature = "wasm")]
+    fn put_code(&mut self, code: &[u8]) -> [u8; 32] {
+        let code_hash = hashing::hash(hashing::Domain::ContractCode, code);
+        self.changes.code.insert(code_hash, code.to_vec());
+        code_hash
+    }
+}
+
+/// Why the effects of a transaction were reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Revert {
+    OutOfGas,
+    /// A contract failed or could not run, or an output could not be paid
+    Trap(String),
+}
+
+impl From<OutOfGas> for Revert {
+    fn from(_: OutOfGas) -> Self {
+        Revert::OutOfGas
+    }
+}
+
+impl fmt::Display for Revert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revert::OutOfGas => write!(f, "out of gas"),
+            Revert::Trap(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Effects of a transaction that ran to completion
+struct TxOutcome {
+    logs: Vec<Log>,
+    contract_address: Option<Vec<u8>>,
+}
+
+/// Applies blocks to the account state.
+///
+/// Execution depends only on the parent state, the block and the validator, so every
+/// node replaying a block commits the same state root and receipts.
+pub struct BlockExecutor {
+    config: ExecutionConfig,
+    stats: ExecutionStats,
+    #[cfg(feature = "wasm")]
+    contracts: crate::wasm::ContractRuntime,
+}
+
+impl BlockExecutor {
+    pub fn new(config: ExecutionConfig) -> Result<Self, ExecutionError> {
+        if config.block_gas_limit < config.gas.tx_base {
+            return Err(ExecutionError::ConfigError(
+                "Block gas limit cannot fit a single transaction".to_string(),
+            ));
+        }
+        config.emission.validate()?;
+        config.mint_attestation.validate()?;
+        Ok(Self {
+            config,
+            stats: ExecutionStats::default(),
+            #[cfg(feature = "wasm")]
+            contracts: crate::wasm::ContractRuntime::new()?,
+        })
+    }
+
+    /// Checks of `tx` that read nothing the block writes: its chain, its ring signatures and
+    /// the shape of its outputs and data
+    fn check_transaction(&self, state: &dyn ParentState, tx: &Transaction) -> Result<(), ExecutionError> {
+        // Only the coinbase is not signed; everything else, mints included, must be signed for this chain
+        let system = system_sender(&tx.sender);
+        if tx.sender != COINBASE_ADDRESS && tx.chain_id != self.config.chain_id {
+            return Err(ExecutionError::InvalidBlock(format!(
+                "Transaction {} is for chain {}, not {}",
+                hex::encode(tx.hash),
+                tx.chain_id,
+                self.config.chain_id
+            )));
+        }
+        for input in &tx.ring_inputs {
+            check_ring_input(state, self.config.ring_size, &tx.signing_hash(), input).map_err(|reason| {
+                ExecutionError::InvalidBlock(format!("Ring input of {}: {}", hex::encode(tx.hash), reason))
+            })?;
+        }
+
+        let invalid = |reason: &str| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), reason));
+        if tx.sender.is_empty() {
+            return Err(invalid("no sender"));
+        }
+        if tx.outputs.iter().any(|output| output.address.is_empty()) {
+            return Err(invalid("output has no address"));
+        }
+        if tx
+            .outputs
+            .iter()
+            .any(|output| output.ephemeral_key.is_some() && output.address.len() != 32)
+        {
+            return Err(invalid("stealth output address is not a 32-byte one-time key"));
+        }
+        if let Some(payer) = &tx.fee_payer {
+            if system || payer.account.is_empty() || payer.account == tx.sender {
+                return Err(invalid("fee payer must be an account other than the sender of a fee-paying transaction"));
+            }
+        }
+        if tx.asset.is_some() {
+            if tx.sender == COINBASE_ADDRESS || tx.sender == XFG_MINT_ADDRESS {
+                return Err(invalid("only bridge deposits mint tokens"));
+            }
+            if !tx.ring_inputs.is_empty()
+                || tx
+                    .outputs
+                    .iter()
+                    .any(|output| output.ephemeral_key.is_some() || output.address == ELDERNODE_REGISTRY_ADDRESS)
+            {
+                return Err(invalid("tokens only move between public accounts"));
+            }
+        }
+        if system {
+            return Ok(());
+        }
+        if tx.outputs.iter().any(|output| output.address == WITHDRAWAL_ADDRESS) && tx.data.len() != 20 {
+            return Err(invalid("withdrawal data is not a 20-byte L1 address"));
+        }
+        if tx.outputs.iter().any(|output| output.address == ELDERNODE_REGISTRY_ADDRESS)
+            && (tx.outputs.len() != 1 || EldernodeCommand::parse(&tx.data).is_none())
+        {
+            return Err(invalid("Eldernode registry call needs one output and a command"));
+        }
+        Ok(())
+    }
+
+    /// Execute `tx`, which has passed `check_transaction`, charging its fee pro rata to the
+    /// gas used, to its fee payer if it has one. The receipt's fee is left for the caller to
+    /// pay out with `pay_fee`.
+    ///
+    /// Transactions that could never be included are errors. A transaction that runs out
+    /// of gas or traps still pays its whole fee and consumes its nonce, but its effects are reverted.
+    pub(crate) fn execute_transaction(
+        &self,
+        accounts: &mut AccountOverlay,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Receipt, ExecutionError> {
+        let invalid = |reason: String| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), reason));
+        if let Some(source) = mint_source(&tx.sender) {
+            return self.execute_mint(accounts, source, height, tx_index, tx);
+        }
+        if tx.sender == COINBASE_ADDRESS {
+            return self.execute_coinbase(accounts, height, tx_index, tx);
+        }
+        let mut meter = match accounts.gas_steps {
+            Some(_) => GasMeter::traced(tx.gas_limit),
+            None => GasMeter::new(tx.gas_limit),
+        };
+        let intrinsic = self.config.gas.intrinsic_gas(tx);
+        if meter.charge_for("intrinsic", intrinsic).is_err() {
+            return Err(invalid(format!(
+                "gas limit {} is below the intrinsic gas {}",
+                tx.gas_limit, intrinsic
+            )));
+        }
+        let value = tx
+            .outputs
+            .iter()
+            .try_fold(0u64, |total, output| total.checked_add(output.amount))
+            .ok_or_else(|| invalid("output amounts overflow".to_string()))?;
+        // Token outputs are paid from the sender's token balance, leaving only the fee in HEAT
+        let heat_value = if tx.asset.is_some() { 0 } else { value };
+        let upfront = heat_value
+            .checked_add(tx.fee)
+            .ok_or_else(|| invalid("output amounts overflow".to_string()))?;
+
+        // Ring inputs withdraw from the shielded pool to the sender, whatever the outcome
+        let withdrawn = tx
+            .ring_inputs
+            .iter()
+            .try_fold(0u64, |total, input| total.checked_add(input.amount))
+            .ok_or_else(|| invalid("ring input amounts overflow".to_string()))?;
+        if withdrawn > 0 {
+            accounts
+                .account(SHIELDED_POOL_ADDRESS)?
+                .debit(withdrawn)
+                .map_err(|e| invalid(e.to_string()))?;
+            accounts
+                .account(&tx.sender)?
+                .credit(withdrawn)
+                .map_err(|e| invalid(e.to_string()))?;
+        }
+
+        // A fee payer covers the fee, leaving the sender only the value it sends
+        let sender_cost = match &tx.fee_payer {
+            Some(payer) => {
+                let balance = accounts.account(&payer.account)?.balance;
+                if balance < tx.fee {
+                    return Err(invalid(format!("fee payer balance {} cannot cover {}", balance, tx.fee)));
+                }
+                heat_value
+            }
+            None => upfront,
+        };
+        if let Some(asset) = &tx.asset {
+            if accounts.token(asset)?.is_none() {
+                return Err(invalid("asset is not a registered token".to_string()));
+            }
+            let balance = accounts.token_balance(asset, &tx.sender)?;
+            if balance < value {
+                return Err(invalid(format!("token balance {} cannot cover {}", balance, value)));
+            }
+        }
+        let sender = accounts.account(&tx.sender)?;
+        if tx.nonce != sender.nonce {
+            return Err(invalid(format!("nonce {} does not match account nonce {}", tx.nonce, sender.nonce)));
+        }
+        if sender.balance < sender_cost {
+            return Err(invalid(format!("balance {} cannot cover {}", sender.balance, sender_cost)));
+        }
+        sender.nonce += 1;
+        accounts
+            .account(tx.fee_account())?
+            .debit(tx.fee)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        let checkpoint = accounts.changes.clone();
+        let (status, outcome, revert_reason) = match self.run_transaction(accounts, &mut meter, height, tx_index, tx)? {
+            Ok(outcome) => (ReceiptStatus::Success, outcome, None),
+            Err(revert) => {
+                accounts.changes = checkpoint;
+                let outcome = TxOutcome {
+                    logs: Vec::new(),
+                    contract_address: None,
+                };
+                (ReceiptStatus::Failed, outcome, Some(revert.to_string()))
+            }
+        };
+        // A failed transaction forfeits its whole gas limit
+        let gas_used = match status {
+            ReceiptStatus::Success => meter.used(),
+            ReceiptStatus::Failed => tx.gas_limit,
+        };
+        let fee = charged_fee(tx.fee, gas_used, tx.gas_limit);
+        if let Some(steps) = &mut accounts.gas_steps {
+            steps.extend(meter.take_steps());
+        }
+        accounts
+            .account(tx.fee_account())?
+            .credit(tx.fee - fee)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        Ok(Receipt {
+            tx_hash: tx.hash,
+            block_height: height,
+            tx_index,
+            status,
+            gas_used,
+            cumulative_gas_used: gas_used,
+            fee,
+            contract_address: outcome.contract_address,
+            revert_reason,
+            logs: outcome.logs,
+        })
+    }
+
+    /// Base fee per unit of gas of block `height`, set by its parent; zero without a fee market
+    pub(crate) fn base_fee(&self, parent: &dyn ParentState, height: u64) -> Result<u64, ExecutionError> {
+        if !self.config.fee_market.enabled {
+            return Ok(0);
+        }
+        match parent.get(&block_fees_key(height - 1))? {
+            Some(bytes) => Ok(serde_json::from_slice::<BlockFees>(&bytes)?.next_base_fee),
+            None => Ok(self.config.fee_market.initial_base_fee),
+        }
+    }
+
+    /// Pay out the fee `receipt` charged `tx`: the base fee part is burned, or paid to the
+    /// treasury, and the tip goes to `validator`. Returns both parts.
+    pub(crate) fn pay_fee(
+        &self,
+        accounts: &mut AccountOverlay,
+        validator: &[u8],
+        base_fee: u64,
+        tx: &Transaction,
+        receipt: &Receipt,
+    ) -> Result<(u64, u64), ExecutionError> {
+        if system_sender(&tx.sender) {
+            return Ok((0, 0));
+        }
+        let (base, tip) = split_fee(receipt.fee, base_fee, receipt.gas_used);
+        let invalid = |e: StateDBError| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), e));
+        if let Some(treasury) = &self.config.fee_market.treasury {
+            accounts.account(treasury)?.credit(base).map_err(invalid)?;
+        }
+        accounts.account(validator)?.credit(tip).map_err(invalid)?;
+        Ok((base, tip))
+    }
+
+    /// Credit the outputs of a mint, which is ordered by the nonce of its minter account
+    fn execute_mint(
+        &self,
+        accounts: &mut AccountOverlay,
+        source: MintSource,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Receipt, ExecutionError> {
+        let invalid = |reason: String| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), reason));
+        if tx.fee != 0 || tx.gas_limit != 0 {
+            return Err(invalid("mint carries a fee or gas limit".to_string()));
+        }
+        if !tx.ring_inputs.is_empty() {
+            return Err(invalid("mint spends ring inputs".to_string()));
+        }
+        let minter = accounts.account(&tx.sender)?;
+        if tx.nonce != minter.nonce {
+            return Err(invalid(format!("mint nonce {} does not match {}", tx.nonce, minter.nonce)));
+        }
+        minter.nonce += 1;
+        // Only mints the chain's bridge attesters signed for a confirmed deposit or verified burn
+        // may execute, whatever any one node has recorded
+        let signers = block_sync::signatures::input_signers(tx).map_err(|e| invalid(e.to_string()))?;
+        let attestation = &self.config.mint_attestation;
+        let attested = attestation.attested_by(&signers);
+        if attestation.attesters.is_empty() || attested < attestation.threshold {
+            return Err(invalid(format!(
+                "{:?} mint {} is attested by {} of the {} attesters it needs",
+                source,
+                tx.nonce,
+                attested,
+                attestation.threshold.max(1)
+            )));
+        }
+        match tx.asset {
+            Some(asset) => Self::mint_tokens(accounts, asset, height, tx_index, tx),
+            None => Self::credit_new_heat(accounts, height, tx_index, tx),
+        }
+    }
+
+    /// Credit the outputs of a bridge mint of `asset`, registering the token from the token
+    /// info in the mint's data if this is its first mint
+    fn mint_tokens(
+        accounts: &mut AccountOverlay,
+        asset: [u8; 32],
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Receipt, ExecutionError> {
+        let invalid = |reason: &str| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), reason));
+        let mut token = match accounts.token(&asset)? {
+            Some(token) => token,
+            None => {
+                let info: TokenInfo =
+                    serde_json::from_slice(&tx.data).map_err(|_| invalid("first mint of a token has no token info"))?;
+                if info.asset_id() != asset {
+                    return Err(invalid("token info does not match the asset"));
+                }
+                Token { asset, info, supply: 0 }
+            }
+        };
+        let mut logs = Vec::with_capacity(tx.outputs.len());
+        for output in &tx.outputs {
+            token.supply = token
+                .supply
+                .checked_add(output.amount)
+                .ok_or_else(|| invalid("token supply overflows"))?;
+            // No balance exceeds the supply, so it cannot overflow either
+            let balance = accounts.token_balance(&asset, &output.address)?;
+            accounts.put_token_balance(&asset, &output.address, balance + output.amount);
+            logs.push(Log {
+                address: tx.sender.clone(),
+                topics: vec![transfer_topic(), address_topic(&tx.sender), address_topic(&output.address), asset],
+                data: output.amount.to_be_bytes().to_vec(),
+                block_height: height,
+                tx_hash: tx.hash,
+                tx_index,
+                log_index: 0,
+            });
+        }
+        accounts.put_token(&token)?;
+        Ok(Receipt {
+            tx_hash: tx.hash,
+            block_height: height,
+            tx_index,
+            status: ReceiptStatus::Success,
+            gas_used: 0,
+            cumulative_gas_used: 0,
+            fee: 0,
+            contract_address: None,
+            revert_reason: None,
+            logs,
+        })
+    }
+
+    /// Credit the outputs of the block's coinbase, whose total `process_block` has checked
+    /// against the subsidy
+    fn execute_coinbase(
+        &self,
+        accounts: &mut AccountOverlay,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Receipt, ExecutionError> {
+        let invalid = |reason: String| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), reason));
+        if tx.fee != 0 || tx.gas_limit != 0 {
+            return Err(invalid("coinbase carries a fee or gas limit".to_string()));
+        }
+        if !tx.ring_inputs.is_empty() || tx.outputs.iter().any(|output| output.ephemeral_key.is_some()) {
+            return Err(invalid("coinbase has private inputs or outputs".to_string()));
+        }
+        if tx.nonce != height {
+            return Err(invalid(format!("coinbase nonce {} is not the block height {}", tx.nonce, height)));
+        }
+        Self::credit_new_heat(accounts, height, tx_index, tx)
+    }
+
+    /// Credit the outputs of a mint or coinbase out of nothing, logging each as a transfer
+    /// from the sender
+    fn credit_new_heat(
+        accounts: &mut AccountOverlay,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Receipt, ExecutionError> {
+        let mut logs = Vec::with_capacity(tx.outputs.len());
+        for output in &tx.outputs {
+            accounts
+                .account(&output.address)?
+                .credit(output.amount)
+                .map_err(|e| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), e)))?;
+            logs.push(Log {
+                address: tx.sender.clone(),
+                topics: vec![transfer_topic(), address_topic(&tx.sender), address_topic(&output.address)],
+                data: output.amount.to_be_bytes().to_vec(),
+                block_height: height,
+                tx_hash: tx.hash,
+                tx_index,
+                log_index: 0,
+            });
+        }
+        Ok(Receipt {
+            tx_hash: tx.hash,
+            block_height: height,
+            tx_index,
+            status: ReceiptStatus::Success,
+            gas_used: 0,
+            cumulative_gas_used: 0,
+            fee: 0,
+            contract_address: None,
+            revert_reason: None,
+            logs,
+        })
+    }
+
+    /// Deploy the contract in `tx` or carry out its transfers
+    fn run_transaction(
+        &self,
+        accounts: &mut AccountOverlay,
+        meter: &mut GasMeter,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Result<TxOutcome, Revert>, ExecutionError> {
+        #[cfg(feature = "wasm")]
+        {
+            if tx.outputs.is_empty() && tx.data.starts_with(crate::wasm::WASM_MAGIC) {
+                return self.deploy(accounts, meter, tx);
+            }
+        }
+        Ok(self
+            .transfer_outputs(accounts, meter, height, tx_index, tx)?
+            .map(|logs| TxOutcome {
+                logs,
+                contract_address: None,
+            }))
+    }
+
+    /// Store the code in `tx.data` at the address derived from the sender and nonce
+    #[cfg(feature = "wasm")]
+    fn deploy(
+        &self,
+        accounts: &mut AccountOverlay,
+        meter: &mut GasMeter,
+        tx: &Transaction,
+    ) -> Result<Result<TxOutcome, Revert>, ExecutionError> {
+        if let Err(e) = meter.charge_for("code", self.config.gas.code_byte.saturating_mul(tx.data.len() as u64)) {
+            return Ok(Err(e.into()));
+        }
+        if !self.contracts.validate(&tx.data) {
+            return Ok(Err(Revert::Trap("invalid contract code".to_string())));
+        }
+        let address = crate::wasm::contract_address(&tx.sender, tx.nonce);
+        let code_hash = accounts.put_code(&tx.data);
+        let account = accounts.account(&address)?;
+        if account.is_contract() {
+            return Ok(Err(Revert::Trap("contract address is taken".to_string())));
+        }
+        account.code_hash = Some(code_hash);
+        Ok(Ok(TxOutcome {
+            logs: Vec::new(),
+            contract_address: Some(address),
+        }))
+    }
+
+    /// Move the outputs of `tx` from the sender to the recipients, logging one transfer per
+    /// output and calling recipients that are contracts. Outputs to `WITHDRAWAL_ADDRESS` are
+    /// burned and logged as withdrawals instead.
+    ///
+    /// Reverts are returned as the inner error so the caller can roll back.
+    fn transfer_outputs(
+        &self,
+        accounts: &mut AccountOverlay,
+        meter: &mut GasMeter,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Result<Vec<Log>, Revert>, ExecutionError> {
+        if let Some(asset) = tx.asset {
+            return self.transfer_tokens(accounts, meter, asset, height, tx_index, tx);
+        }
+        let invalid = |e: state_db::error::StateDBError| {
+            ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), e))
+        };
+        let mut logs = Vec::with_capacity(tx.outputs.len());
+        for output in &tx.outputs {
+            accounts.account(&tx.sender)?.debit(output.amount).map_err(invalid)?;
+            if output.address == WITHDRAWAL_ADDRESS {
+                let data = output.amount.to_be_bytes().to_vec();
+                if let Err(e) = meter.charge_for("log", self.config.gas.log_gas(data.len())) {
+                    return Ok(Err(e.into()));
+                }
+                logs.push(Log {
+                    address: WITHDRAWAL_ADDRESS.to_vec(),
+                    topics: vec![withdrawal_topic(), address_topic(&tx.sender), address_topic(&tx.data)],
+                    data,
+                    block_height: height,
+                    tx_hash: tx.hash,
+                    tx_index,
+                    log_index: 0,
+                });
+                continue;
+            }
+            if output.ephemeral_key.is_some() {
+                // The pool holds the value; the one-time key only records that it is owed
+                if accounts.stealth_output(&output.address)?.is_some() {
+                    return Ok(Err(Revert::Trap("one-time key is already used".to_string())));
+                }
+                if let Err(e) = meter.charge_for("new_account", self.config.gas.new_account) {
+                    return Ok(Err(e.into()));
+                }
+                accounts
+                    .changes
+                    .storage
+                    .insert(stealth_output_key(&output.address), output.amount.to_be_bytes().to_vec());
+                accounts
+                    .account(SHIELDED_POOL_ADDRESS)?
+                    .credit(output.amount)
+                    .map_err(invalid)?;
+            } else {
+                let recipient = accounts.account(&output.address)?;
+                if *recipient == Account::default() {
+                    if let Err(e) = meter.charge_for("new_account", self.config.gas.new_account) {
+                        return Ok(Err(e.into()));
+                    }
+                }
+                recipient.credit(output.amount).map_err(invalid)?;
+            }
+
+            let data = output.amount.to_be_bytes().to_vec();
+            if let Err(e) = meter.charge_for("log", self.config.gas.log_gas(data.len())) {
+                return Ok(Err(e.into()));
+            }
+            logs.push(Log {
+                address: tx.sender.clone(),
+                topics: vec![transfer_topic(), address_topic(&tx.sender), address_topic(&output.address)],
+                data,
+                block_height: height,
+                tx_hash: tx.hash,
+                tx_index,
+                log_index: 0,
+            });
+            if output.address == ELDERNODE_REGISTRY_ADDRESS {
+                match self.eldernode_call(accounts, meter, height, tx_index, tx)? {
+                    Ok(refund) => logs.extend(refund),
+                    Err(revert) => return Ok(Err(revert)),
+                }
+            }
+
+            #[cfg(feature = "wasm")]
+            {
+                if output.ephemeral_key.is_none() && accounts.account(&output.address)?.is_contract() {
+                    let call = crate::wasm::CallContext {
+                        contract: &output.address,
+                        caller: &tx.sender,
+                        value: output.amount,
+                        input: &tx.data,
+                    };
+                    match self.contracts.call(accounts, meter, &self.config.gas, call)? {
+                        Ok(contract_logs) => logs.extend(contract_logs.into_iter().map(|log| Log {
+                            address: output.address.clone(),
+                            topics: log.topics,
+                            data: log.data,
+                            block_height: height,
+                            tx_hash: tx.hash,
+                            tx_index,
+                            log_index: 0,
+                        })),
+                        Err(revert) => return Ok(Err(revert)),
+                    }
+                }
+            }
+        }
+        Ok(Ok(logs))
+    }
+
+    /// Move the outputs of a token transaction between token balances. Outputs paid to the
+    /// withdrawal address burn the token and log its withdrawal with its L1 contract.
+    fn transfer_tokens(
+        &self,
+        accounts: &mut AccountOverlay,
+        meter: &mut GasMeter,
+        asset: [u8; 32],
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Result<Vec<Log>, Revert>, ExecutionError> {
+        let invalid = |reason: String| ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), reason));
+        let mut token = accounts
+            .token(&asset)?
+            .ok_or_else(|| invalid("asset is not a registered token".to_string()))?;
+        let mut logs = Vec::with_capacity(tx.outputs.len());
+        for output in &tx.outputs {
+            let balance = accounts.token_balance(&asset, &tx.sender)?;
+            let remaining = balance
+                .checked_sub(output.amount)
+                .ok_or_else(|| invalid(format!("token balance {} is below {}", balance, output.amount)))?;
+            accounts.put_token_balance(&asset, &tx.sender, remaining);
+
+            let (address, topics) = if output.address == WITHDRAWAL_ADDRESS {
+                token.supply = token.supply.saturating_sub(output.amount);
+                let topics = vec![
+                    withdrawal_topic(),
+                    address_topic(&tx.sender),
+                    address_topic(&tx.data),
+                    address_topic(&token.info.l1_origin),
+                ];
+                (WITHDRAWAL_ADDRESS.to_vec(), topics)
+            } else {
+                let balance = accounts.token_balance(&asset, &output.address)?;
+                // A first balance of the token costs as much as a new account
+                if balance == 0 {
+                    if let Err(e) = meter.charge_for("new_account", self.config.gas.new_account) {
+                        return Ok(Err(e.into()));
+                    }
+                }
+                accounts.put_token_balance(&asset, &output.address, balance + output.amount);
+                let topics = vec![transfer_topic(), address_topic(&tx.sender), address_topic(&output.address), asset];
+                (tx.sender.clone(), topics)
+            };
+            let data = output.amount.to_be_bytes().to_vec();
+            if let Err(e) = meter.charge_for("log", self.config.gas.log_gas(data.len())) {
+                return Ok(Err(e.into()));
+            }
+            logs.push(Log {
+                address,
+                topics,
+                data,
+                block_height: height,
+                tx_hash: tx.hash,
+                tx_index,
+                log_index: 0,
+            });
+        }
+        accounts.put_token(&token)?;
+        Ok(Ok(logs))
+    }
+
+    /// Apply the registry command in the data of `tx`, whose one output paid the registry.
+    /// A deregistration returns the stake and logs the refund.
+    fn eldernode_call(
+        &self,
+        accounts: &mut AccountOverlay,
+        meter: &mut GasMeter,
+        height: u64,
+        tx_index: u32,
+        tx: &Transaction,
+    ) -> Result<Result<Option<Log>, Revert>, ExecutionError> {
+        let invalid = |e: state_db::error::StateDBError| {
+            ExecutionError::InvalidTransaction(format!("{}: {}", hex::encode(tx.hash), e))
+        };
+        if let Err(e) = meter.charge_for("storage_write", self.config.gas.storage_write) {
+            return Ok(Err(e.into()));
+        }
+        let amount = tx.outputs[0].amount;
+        let config = &self.config.eldernode;
+        let command = EldernodeCommand::parse(&tx.data).ok_or_else(|| {
+            ExecutionError::InvalidTransaction(format!("{}: registry call has no command", hex::encode(tx.hash)))
+        })?;
+        let registered = accounts.eldernode(&tx.sender)?;
+        let mut node = match (command, registered) {
+            (EldernodeCommand::Register, Some(_)) => {
+                return Ok(Err(Revert::Trap("Eldernode is already registered".to_string())));
+            }
+            (EldernodeCommand::Register, None) => {
+                if amount < config.min_stake {
+                    return Ok(Err(Revert::Trap(format!(
+                        "Eldernode stake {} below minimum {}",
+                        amount, config.min_stake
+                    ))));
+                }
+                accounts.put_eldernode(&Eldernode {
+                    address: tx.sender.clone(),
+                    stake: amount,
+                    registered_at: height,
+                    last_heartbeat: height,
+                    blocks_served: 0,
+                    fees_earned: 0,
+                })?;
+                return Ok(Ok(None));
+            }
+            (_, None) => return Ok(Err(Revert::Trap("sender is not a registered Eldernode".to_string()))),
+            (_, Some(node)) => node,
+        };
+        node.stake = match node.stake.checked_add(amount) {
+            Some(stake) => stake,
+            None => return Ok(Err(Revert::Trap("Eldernode stake overflows".to_string()))),
+        };
+        if command == EldernodeCommand::Heartbeat {
+            node.last_heartbeat = height;
+            accounts.put_eldernode(&node)?;
+            return Ok(Ok(None));
+        }
+
+        let data = node.stake.to_be_bytes().to_vec();
+        if let Err(e) = meter.charge_for("log", self.config.gas.log_gas(data.len())) {
+            return Ok(Err(e.into()));
+        }
+        accounts
+            .account(ELDERNODE_REGISTRY_ADDRESS)?
+            .debit(node.stake)
+            .map_err(invalid)?;
+        accounts.account(&tx.sender)?.credit(node.stake).map_err(invalid)?;
+        accounts.remove_eldernode(&tx.sender)?;
+        Ok(Ok(Some(Log {
+            address: ELDERNODE_REGISTRY_ADDRESS.to_vec(),
+            topics: vec![
+                transfer_topic(),
+                address_topic(ELDERNODE_REGISTRY_ADDRESS),
+                address_topic(&tx.sender),
+            ],
+            data,
+            block_height: height,
+            tx_hash: tx.hash,
+            tx_index,
+            log_index: 0,
+        })))
+    }
+
+    /// Execute every transaction in `block`, paying fees above the base fee to `validator` less
+    /// the share routed to live Eldernodes, and commit the resulting state as the block height with a receipt
+    /// per transaction in the same write.
+    ///
+    /// An invalid transaction, or a coinbase claiming more than the emission schedule allows
+    /// at this height, rejects the whole block and leaves the state untouched.
+    pub fn process_block(
+        &mut self,
+        state: &mut RocksStateDB,
+        block: &Block,
+        validator: &[u8],
+    ) -> Result<BlockExecution, ExecutionError> {
+        let height = block.header.height;
+        let executed = self.execute_block(&*state, block, validator)?;
+        for (key, value) in executed.writes(height)? {
+            state.put_sync(&key, &value)?;
+        }
+        let receipts = executed.receipts;
+        let stealth_outputs: Vec<StealthOutputRecord> = block
+            .transactions
+            .iter()
+            .zip(&receipts)
+            .filter(|(_, receipt)| receipt.status == ReceiptStatus::Success)
+            .flat_map(|(tx, _)| {
+                tx.outputs.iter().enumerate().filter_map(move |(output_index, output)| {
+                    Some(StealthOutputRecord {
+                        block_height: height,
+                        tx_hash: tx.hash,
+                        output_index: output_index as u32,
+                        amount: output.amount,
+                        ephemeral_key: output.ephemeral_key?,
+                        one_time_key: output.address.clone(),
+                    })
+                })
+            })
+            .collect();
+        let ring_spends: Vec<RingSpendRecord> = block
+            .transactions
+            .iter()
+            .zip(&receipts)
+            .filter(|(_, receipt)| receipt.status == ReceiptStatus::Success)
+            .flat_map(|(tx, _)| {
+                tx.ring_inputs.iter().enumerate().map(move |(input_index, input)| RingSpendRecord {
+                    block_height: height,
+                    tx_hash: tx.hash,
+                    input_index: input_index as u32,
+                    amount: input.amount,
+                    key_image: input.key_image,
+                    ring: input.ring.clone(),
+                    audit_tag: input.audit_tag,
+                })
+            })
+            .collect();
+        let mut entries = receipt_entries(height, &receipts, &stealth_outputs, &ring_spends)?;
+        entries.push(header_entry(&block.header));
+        entries.push(body_entry(block, validator));
+        let state_root = state.commit_with_sync(height, &entries)?;
+
+        self.stats.blocks_executed += 1;
+        self.stats.transactions_executed += block.transactions.len() as u64;
+        self.stats.failed_transactions += receipts
+            .iter()
+            .filter(|receipt| receipt.status == ReceiptStatus::Failed)
+            .count() as u64;
+        self.stats.gas_used += executed.gas_used;
+        self.stats.fees_collected += executed.fees;
+        self.stats.base_fees += executed.base_fees;
+        self.stats.heat_minted += executed.minted;
+        self.stats.heat_burned += executed.burned;
+        self.stats.eldernode_fees += executed.eldernode_fees;
+        self.stats.reexecuted_transactions += executed.reexecuted;
+        Ok(BlockExecution {
+            height,
+            state_root,
+            nullifier_root: executed.nullifier_root,
+            transactions: block.transactions.len(),
+            gas_used: executed.gas_used,
+            fees: executed.fees,
+            base_fee: executed.base_fee,
+            base_fees: executed.base_fees,
+            eldernodes: executed.eldernodes,
+            receipts,
+        })
+    }
+
+    /// Execute `block` on `parent` as `process_block` would, committing nothing, and return
+    /// what it writes so that blocks built on it, such as those of a side branch, can be
+    /// checked on top of it in turn
+    pub fn check_block(
+        &self,
+        parent: &dyn ParentState,
+        block: &Block,
+        validator: &[u8],
+    ) -> Result<StateWrites, ExecutionError> {
+        self.execute_block(parent, block, validator)?.writes(block.header.height)
+    }
+
+    /// Root of the spent-nullifier set once `block` spends its nullifiers on `parent`
+    pub fn nullifier_root(&self, parent: &dyn ParentState, block: &Block) -> Result<MerkleRoot, ExecutionError> {
+        Ok(accumulate_nullifiers(&parent_nullifier_root(parent)?, &block_nullifiers(block)))
+    }
+
+    /// Execute every transaction of `block` on `parent`, checking the block as a whole, and
+    /// return its effects without writing any of them
+    fn execute_block(
+        &self,
+        parent: &dyn ParentState,
+        block: &Block,
+        validator: &[u8],
+    ) -> Result<ExecutedBlock, ExecutionError> {
+        let height = block.header.height;
+        if height == GENESIS_VERSION {
+            return Err(ExecutionError::InvalidBlock(
+                "Genesis state comes from the allocation file".to_string(),
+            ));
+        }
+        if validator.is_empty() {
+            return Err(ExecutionError::InvalidBlock("Validator address is empty".to_string()));
+        }
+
+        // Signatures dominate the cost of a block, and each transaction's are checked on its own.
+        // In parallel the first failure in block order is reported, as it is sequentially.
+        let check = |tx: &Transaction| self.check_transaction(parent, tx);
+        if self.config.parallel_execution {
+            block.transactions.par_iter().map(check).collect::<Vec<_>>().into_iter().collect::<Result<(), _>>()?;
+        } else {
+            block.transactions.iter().try_for_each(check)?;
+        }
+
+        // A private note may be spent once: across the chain and within this block
+        let nullifiers = block_nullifiers(block);
+        let mut seen = HashSet::with_capacity(nullifiers.len());
+        for nullifier in &nullifiers {
+            if !seen.insert(nullifier) || parent.get(&nullifier_key(nullifier))?.is_some() {
+                return Err(ExecutionError::InvalidBlock(format!(
+                    "Nullifier {} is already spent",
+                    hex::encode(nullifier)
+                )));
+            }
+        }
+        let nullifier_root = accumulate_nullifiers(&parent_nullifier_root(parent)?, &nullifiers);
+        if nullifier_root != block.header.nullifier_root {
+            return Err(ExecutionError::InvalidBlock(format!(
+                "Nullifier root {} does not match the header's {}",
+                hex::encode(nullifier_root),
+                hex::encode(block.header.nullifier_root)
+            )));
+        }
+
+        // Only the first transaction may claim the block subsidy, and no more than the schedule allows
+        let subsidy = self.config.emission.subsidy(height);
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            if tx.sender != COINBASE_ADDRESS {
+                continue;
+            }
+            if tx_index != 0 {
+                return Err(ExecutionError::InvalidBlock(format!(
+                    "Coinbase is transaction {}, not the first",
+                    tx_index
+                )));
+            }
+            let claimed = tx
+                .outputs
+                .iter()
+                .try_fold(0u64, |total, output| total.checked_add(output.amount))
+                .ok_or_else(|| ExecutionError::InvalidBlock("Coinbase outputs overflow".to_string()))?;
+            if claimed > subsidy {
+                return Err(ExecutionError::InvalidBlock(format!(
+                    "Block claims a reward of {} but the subsidy at height {} is {}",
+                    claimed, height, subsidy
+                )));
+            }
+        }
+
+        // Every fee must cover the base fee over the transaction's whole gas limit
+        let base_fee = self.base_fee(parent, height)?;
+        for tx in block.transactions.iter().filter(|tx| !system_sender(&tx.sender)) {
+            if tx.fee < base_fee.saturating_mul(tx.gas_limit) {
+                return Err(ExecutionError::InvalidBlock(format!(
+                    "Transaction {} pays a fee of {}, below the base fee {} per unit of gas",
+                    hex::encode(tx.hash),
+                    tx.fee,
+                    base_fee
+                )));
+            }
+        }
+
+        // Execute every transaction alone on the parent state; in block order, those that read
+        // nothing an earlier transaction wrote keep their result and the rest run again
+        let speculations: Vec<Speculation> = if self.config.parallel_execution && block.transactions.len() > 1 {
+            block
+                .transactions
+                .par_iter()
+                .enumerate()
+                .map(|(tx_index, tx)| {
+                    let mut overlay = AccountOverlay::tracking(parent);
+                    let result = self.execute_transaction(&mut overlay, height, tx_index as u32, tx);
+                    Speculation {
+                        result,
+                        changes: overlay.changes,
+                        reads: overlay.reads.map(RefCell::into_inner).unwrap_or_default(),
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut speculations = speculations.into_iter();
+        let mut reexecuted = 0u64;
+
+        let mut overlay = AccountOverlay::new(parent);
+        let mut fees = 0u64;
+        let mut base_fees = 0u64;
+        let mut gas_used = 0u64;
+        let mut receipts: Vec<Receipt> = Vec::with_capacity(block.transactions.len());
+        let mut log_index = 0u32;
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            if gas_used.saturating_add(tx.gas_limit) > self.config.block_gas_limit {
+                return Err(ExecutionError::InvalidBlock(format!(
+                    "Transaction {} exceeds the block gas limit {}",
+                    tx_index, self.config.block_gas_limit
+                )));
+            }
+            let mut receipt = match speculations.next() {
+                Some(speculation) if !speculation.reads.conflict(&overlay.changes) => {
+                    let receipt = speculation.result?;
+                    overlay.changes.merge(speculation.changes);
+                    receipt
+                }
+                speculation => {
+                    reexecuted += u64::from(speculation.is_some());
+                    self.execute_transaction(&mut overlay, height, tx_index as u32, tx)?
+                }
+            };
+            let (base, tip) = self.pay_fee(&mut overlay, validator, base_fee, tx, &receipt)?;
+            base_fees = base_fees
+                .checked_add(base)
+                .ok_or_else(|| ExecutionError::InvalidBlock("Block fees overflow".to_string()))?;
+            fees = fees
+                .checked_add(tip)
+                .ok_or_else(|| ExecutionError::InvalidBlock("Block fees overflow".to_string()))?;
+            gas_used += receipt.gas_used;
+            receipt.cumulative_gas_used = gas_used;
+            for log in &mut receipt.logs {
+                log.log_index = log_index;
+                log_index += 1;
+            }
+            receipts.push(receipt);
+        }
+
+        // Live Eldernodes serve the block and take their share of its fees from the validator's
+        let block_invalid = |e: state_db::error::StateDBError| ExecutionError::InvalidBlock(e.to_string());
+        let mut active = Vec::new();
+        for address in overlay.eldernode_index()? {
+            if let Some(node) = overlay.eldernode(&address)? {
+                if node.is_active(&self.config.eldernode, height) {
+                    active.push(node);
+                }
+            }
+        }
+        let eldernodes = fee_shares(&self.config.eldernode, fees, &active);
+        let eldernode_fees: u64 = eldernodes.iter().map(|share| share.fee).sum();
+        overlay.account(validator)?.debit(eldernode_fees).map_err(block_invalid)?;
+        for (mut node, share) in active.into_iter().zip(&eldernodes) {
+            overlay.account(&node.address)?.credit(share.fee).map_err(block_invalid)?;
+            node.blocks_served += 1;
+            node.fees_earned = node.fees_earned.saturating_add(share.fee);
+            overlay.put_eldernode(&node)?;
+        }
+        if !eldernodes.is_empty() {
+            overlay.changes.storage.insert(served_key(height), serde_json::to_vec(&eldernodes)?);
+        }
+        if self.config.fee_market.enabled {
+            let gas_target = self.config.fee_market.gas_target(self.config.block_gas_limit);
+            let record = BlockFees {
+                height,
+                base_fee,
+                gas_used,
+                gas_target,
+                base_fees,
+                tips: fees,
+                next_base_fee: self.config.fee_market.next_base_fee(base_fee, gas_used, gas_target),
+            };
+            overlay.changes.storage.insert(block_fees_key(height), serde_json::to_vec(&record)?);
+        }
+
+        let (bridge_minted, xfg_minted, rewarded, withdrawn) = supply_changes(&block.transactions, &receipts);
+        let fees_burned = if self.config.fee_market.treasury.is_some() { 0 } else { base_fees };
+        let burned = withdrawn.saturating_add(fees_burned);
+        let minted = bridge_minted.saturating_add(xfg_minted).saturating_add(rewarded);
+        let mut supply = match parent.get(SUPPLY_KEY)? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => SupplyLedger::default(),
+        };
+        supply
+            .record_mint(MintSource::BridgeDeposit, bridge_minted)
+            .and_then(|()| supply.record_mint(MintSource::XfgBurn, xfg_minted))
+            .and_then(|()| supply.record_subsidy(rewarded))
+            .and_then(|()| supply.record_withdrawal(withdrawn))
+            .and_then(|()| supply.record_fee_burn(fees_burned))
+            .map_err(|e| ExecutionError::InvalidBlock(format!("Supply ledger rejects the block: {}", e)))?;
+        // Balances may only grow by what was minted and shrink by what was burned
+        let mut balance_change = 0i128;
+        for (address, account) in &overlay.changes.accounts {
+            balance_change += i128::from(account.balance) - i128::from(overlay.parent_balance(address)?);
+        }
+        if balance_change != i128::from(minted) - i128::from(burned) {
+            return Err(ExecutionError::InvalidBlock(format!(
+                "Balances changed by {} but {} was minted and {} burned",
+                balance_change, minted, burned
+            )));
+        }
+
+        Ok(ExecutedBlock {
+            changes: overlay.changes,
+            supply,
+            nullifiers,
+            nullifier_root,
+            receipts,
+            gas_used,
+            fees,
+            base_fee,
+            base_fees,
+            eldernodes,
+            eldernode_fees,
+            minted,
+            burned,
+            reexecuted,
+        })
+    }
+
+    /// Get execution statistics
+    pub fn get_stats(&self) -> ExecutionStats {
+        self.stats.clone()
+    }
+}
+
+/// Source of the mints `sender` makes, if it is a minter
+fn mint_source(sender: &[u8]) -> Option<MintSource> {
+    if sender == MINT_ADDRESS {
+        Some(MintSource::BridgeDeposit)
+    } else if sender == XFG_MINT_ADDRESS {
+        Some(MintSource::XfgBurn)
+    } else {
+        None
+    }
+}
+
+/// Whether `sender` mints or pays the coinbase, sending unsigned transactions without fees
+pub(crate) fn system_sender(sender: &[u8]) -> bool {
+    mint_source(sender).is_some() || sender == COINBASE_ADDRESS
+}
+
+/// HEAT minted for bridge deposits, minted for XFG burns, paid as block subsidy and burned
+/// by the successful `transactions`, whose receipts are `receipts`; tokens are not counted
+fn supply_changes(transactions: &[Transaction], receipts: &[Receipt]) -> (u64, u64, u64, u64) {
+    let amount = |log: &Log| log.data.as_slice().try_into().map_or(0, u64::from_be_bytes);
+    let logs = || {
+        transactions
+            .iter()
+            .zip(receipts)
+            .filter(|(tx, receipt)| tx.asset.is_none() && receipt.status == ReceiptStatus::Success)
+            .flat_map(|(_, receipt)| &receipt.logs)
+    };
+    let total = |address: &[u8]| {
+        logs()
+            .filter(|log| log.address == address)
+            .fold(0u64, |total, log| total.saturating_add(amount(log)))
+    };
+    (
+        total(MINT_ADDRESS),
+        total(XFG_MINT_ADDRESS),
+        total(COINBASE_ADDRESS),
+        total(WITHDRAWAL_ADDRESS),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt::{
+        get_headers, get_logs, get_receipt, get_stealth_outputs, LogFilter, MAX_HEADER_RANGE, MAX_LOG_RANGE,
+    };
+    use crate::shielded::get_stealth_output;
+    use block_sync::signatures::sign_input;
+    use block_sync::{BlockHeader, BlockProof, FeePayer, ProofType, RingInput, TxInput, TxOutput};
+    use ed25519_dalek::SigningKey;
+    use state_db::account::GenesisAccount;
+    use state_db::Genesis;
+    use tempfile::TempDir;
+    use zk_proofs::{RingSignature, StealthKeys};
+
+    const ALICE: &[u8] = &[0xa1];
+    const BOB: &[u8] = &[0xb0];
+    const VALIDATOR: &[u8] = &[0xfe];
+    /// With the fee equal to the gas limit, one unit of fee buys one unit of gas
+    const GAS_LIMIT: u64 = 100_000;
+
+    fn transfer(nonce: u64, amount: u64, gas_limit: u64) -> Transaction {
+        Transaction {
+            hash: [nonce as u8; 32],
+            sender: ALICE.to_vec(),
+            nonce,
+            gas_limit,
+            data: Vec::new(),
+            inputs: vec![],
+            outputs: vec![TxOutput {
+                amount,
+                address: BOB.to_vec(),
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: gas_limit,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        }
+    }
+
+    /// The bridge attester the mint tests' chain trusts
+    fn attester() -> SigningKey {
+        SigningKey::from_bytes(&[0xa7; 32])
+    }
+
+    fn attested_config() -> ExecutionConfig {
+        let mint_attestation = MintAttestation::single(attester().verifying_key().to_bytes());
+        ExecutionConfig { mint_attestation, ..Default::default() }
+    }
+
+    /// `mint` signed by each of `keys`, one input apiece
+    fn attest(mut mint: Transaction, keys: &[&SigningKey]) -> Transaction {
+        mint.inputs = vec![TxInput { prev_tx_hash: [0u8; 32], output_index: 0, signature: Vec::new() }; keys.len()];
+        for (index, key) in keys.iter().enumerate() {
+            mint.inputs[index].signature = sign_input(key, &mint);
+        }
+        mint
+    }
+
+    fn block(height: u64, transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        }
+    }
+
+    fn genesis_state(path: &std::path::Path) -> RocksStateDB {
+        let mut state = RocksStateDB::new(path).unwrap();
+        let mut genesis = Genesis::default();
+        genesis.alloc.insert(hex::encode(ALICE), GenesisAccount { balance: 1_000_000 });
+        state.apply_genesis(&genesis).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_transfers_charge_gas_and_pay_the_validator() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        let schedule = GasSchedule::default();
+        let first_gas = schedule.tx_base + schedule.new_account + schedule.log_gas(8);
+        let second_gas = schedule.tx_base + schedule.log_gas(8);
+
+        let transfers = block(1, vec![transfer(0, 100, GAS_LIMIT), transfer(1, 200, GAS_LIMIT)]);
+        let result = executor.process_block(&mut state, &transfers, VALIDATOR).unwrap();
+        assert_eq!(result.gas_used, first_gas + second_gas);
+        assert_eq!(result.fees, first_gas + second_gas);
+        assert_eq!(state.root_at(1).unwrap(), Some(result.state_root));
+        let alice = state.get_account(ALICE).unwrap();
+        assert_eq!((alice.balance, alice.nonce), (1_000_000 - 300 - result.fees, 2));
+        assert_eq!(state.get_account(BOB).unwrap().balance, 300);
+        assert_eq!(state.get_account(VALIDATOR).unwrap().balance, result.fees);
+        assert_eq!(executor.get_stats().transactions_executed, 2);
+
+        let receipt = get_receipt(&state, &[1u8; 32]).unwrap().unwrap();
+        assert_eq!(receipt, result.receipts[1]);
+        assert_eq!((receipt.tx_index, receipt.status), (1, ReceiptStatus::Success));
+        assert_eq!((receipt.gas_used, receipt.fee), (second_gas, second_gas));
+        assert_eq!(receipt.cumulative_gas_used, first_gas + second_gas);
+        assert_eq!(receipt.logs[0].log_index, 1);
+        assert_eq!(receipt.logs[0].data, 200u64.to_be_bytes());
+        assert!(get_receipt(&state, &[9u8; 32]).unwrap().is_none());
+
+        // Replaying the block on the same parent state reaches the same root
+        let replay_dir = TempDir::new().unwrap();
+        let mut replay = genesis_state(replay_dir.path());
+        let mut replay_executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        let replayed = replay_executor.process_block(&mut replay, &transfers, VALIDATOR).unwrap();
+        assert_eq!(replayed, result);
+    }
+
+    #[test]
+    fn test_fee_payer_covers_the_fee_of_a_sponsored_transaction() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        executor.process_block(&mut state, &block(1, vec![transfer(0, 500, GAS_LIMIT)]), VALIDATOR).unwrap();
+        let alice_balance = state.get_account(ALICE).unwrap().balance;
+
+        // Bob sends back all he has while Alice pays the fee
+        let mut sponsored = transfer(0, 500, GAS_LIMIT);
+        sponsored.hash = [0x50; 32];
+        sponsored.sender = BOB.to_vec();
+        sponsored.outputs[0].address = ALICE.to_vec();
+        sponsored.fee_payer = Some(FeePayer {
+            account: ALICE.to_vec(),
+            signature: Vec::new(),
+        });
+        let result = executor.process_block(&mut state, &block(2, vec![sponsored.clone()]), VALIDATOR).unwrap();
+        let bob = state.get_account(BOB).unwrap();
+        assert_eq!((bob.balance, bob.nonce), (0, 1));
+        assert_eq!(state.get_account(ALICE).unwrap().balance, alice_balance + 500 - result.fees);
+
+        // The sender cannot sponsor itself
+        sponsored.nonce = 1;
+        sponsored.fee_payer.as_mut().unwrap().account = BOB.to_vec();
+        assert!(executor.process_block(&mut state, &block(3, vec![sponsored]), VALIDATOR).is_err());
+    }
+
+    #[test]
+    fn test_stealth_outputs_are_indexed_when_paid() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        let stealth = |nonce: u64, one_time_key: u8, gas_limit: u64| {
+            let mut tx = transfer(nonce, 100, gas_limit);
+            tx.outputs[0].address = vec![one_time_key; 32];
+            tx.outputs[0].ephemeral_key = Some([0xe0; 32]);
+            tx
+        };
+
+        // The second transaction runs out of gas, so its output is never paid
+        let paid = block(1, vec![stealth(0, 0x0e, GAS_LIMIT), stealth(1, 0x0f, 30_000), transfer(2, 5, GAS_LIMIT)]);
+        executor.process_block(&mut state, &paid, VALIDATOR).unwrap();
+        let outputs = get_stealth_outputs(&state, 0, 10).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!((outputs[0].block_height, outputs[0].tx_hash), (1, [0u8; 32]));
+        assert_eq!((outputs[0].output_index, outputs[0].amount), (0, 100));
+        assert_eq!(outputs[0].ephemeral_key, [0xe0; 32]);
+        // The shielded pool holds the value until a ring input spends it
+        assert_eq!(state.get_account(&outputs[0].one_time_key).unwrap(), Account::default());
+        assert_eq!(state.get_account(SHIELDED_POOL_ADDRESS).unwrap().balance, 100);
+        assert_eq!(get_stealth_output(&state, &outputs[0].one_time_key).unwrap(), Some(100));
+        assert!(get_stealth_outputs(&state, 2, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ring_inputs_spend_from_the_shielded_pool_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let config = ExecutionConfig { ring_size: 3, ..ExecutionConfig::default() };
+        let mut executor = BlockExecutor::new(config).unwrap();
+        let mut rng = rand::rngs::OsRng;
+
+        let owners: Vec<StealthKeys> = (0..3).map(|_| StealthKeys::generate(&mut rng)).collect();
+        let outputs: Vec<_> = owners
+            .iter()
+            .map(|owner| owner.address().derive_output(0, &mut rng).unwrap())
+            .collect();
+        let payments = outputs
+            .iter()
+            .enumerate()
+            .map(|(nonce, output)| {
+                let mut tx = transfer(nonce as u64, 200_000, GAS_LIMIT);
+                tx.outputs[0].address = output.one_time_key.to_vec();
+                tx.outputs[0].ephemeral_key = Some(output.ephemeral_key);
+                tx
+            })
+            .collect();
+        executor.process_block(&mut state, &block(1, payments), VALIDATOR).unwrap();
+
+        let mut ring: Vec<[u8; 32]> = outputs.iter().map(|output| output.one_time_key).collect();
+        ring.sort();
+        let spend = |hash: [u8; 32], nonce: u64, ring: Vec<[u8; 32]>| {
+            let secret = owners[1].one_time_secret(0, &outputs[1]).unwrap();
+            let real_index = ring.iter().position(|key| *key == outputs[1].one_time_key).unwrap();
+            let mut tx = Transaction {
+                hash,
+                sender: vec![0xc0],
+                nonce,
+                ring_inputs: vec![RingInput {
+                    amount: 200_000,
+                    ring: ring.clone(),
+                    key_image: zk_proofs::key_image(&secret).unwrap(),
+                    signature: Vec::new(),
+                    audit_tag: None,
+                }],
+                ..transfer(0, 50, GAS_LIMIT)
+            };
+            let signature = RingSignature::sign(&tx.signing_hash(), &ring, real_index, &secret, &mut rand::rngs::OsRng);
+            tx.ring_inputs[0].signature = signature.unwrap().to_bytes();
+            tx
+        };
+        let spent = spend([0xc0; 32], 0, ring.clone());
+        let key_image = spent.ring_inputs[0].key_image;
+        let mut withdrawal = block(2, vec![spent]);
+        withdrawal.header.nullifier_root = accumulate_nullifiers(&[0u8; 32], &[key_image]);
+        let result = executor.process_block(&mut state, &withdrawal, VALIDATOR).unwrap();
+        assert_eq!(state.get_account(&[0xc0]).unwrap().balance, 200_000 - 50 - result.fees);
+        assert_eq!(state.get_account(SHIELDED_POOL_ADDRESS).unwrap().balance, 400_000);
+        assert_eq!(state.nullifier_height(&key_image).unwrap(), Some(2));
+
+        // The same output cannot be spent again, even hidden in another signature
+        let mut respent = block(3, vec![spend([0xc1; 32], 1, ring.clone())]);
+        respent.header.nullifier_root = accumulate_nullifiers(&withdrawal.header.nullifier_root, &[key_image]);
+        assert!(executor.process_block(&mut state, &respent, VALIDATOR).is_err());
+
+        // Rings must have the configured size, and signatures must cover the transaction
+        let without_decoy = ring.iter().copied().filter(|key| *key != outputs[0].one_time_key).collect();
+        let small = spend([0xc2; 32], 1, without_decoy);
+        assert!(executor.process_block(&mut state, &block(3, vec![small]), VALIDATOR).is_err());
+        // A spend signed for one chain cannot be replayed on another
+        let mut replayed = spend([0xc5; 32], 1, ring.clone());
+        replayed.chain_id = 2;
+        assert!(executor.process_block(&mut state, &block(3, vec![replayed]), VALIDATOR).is_err());
+        let mut forged = spend([0xc3; 32], 1, ring.clone());
+        forged.hash = [0xc4; 32];
+        assert!(executor.process_block(&mut state, &block(3, vec![forged]), VALIDATOR).is_err());
+        // Nor can the amounts change under the original signature
+        let mut inflated = spend([0xc6; 32], 1, ring);
+        inflated.outputs[0].amount += 1_000;
+        let err = executor.process_block(&mut state, &block(3, vec![inflated]), VALIDATOR).unwrap_err();
+        assert!(err.to_string().contains("invalid ring signature"), "{}", err);
+    }
+
+    #[test]
+    fn test_parallel_execution_matches_sequential_and_benchmarks_the_speedup() {
+        const SPENDS: usize = 8;
+        let mut rng = rand::rngs::OsRng;
+        let owners: Vec<StealthKeys> = (0..SPENDS).map(|_| StealthKeys::generate(&mut rng)).collect();
+        let outputs: Vec<_> = owners
+            .iter()
+            .map(|owner| owner.address().derive_output(0, &mut rng).unwrap())
+            .collect();
+        // Fund the stealth outputs to spend, and senders whose transfers touch nothing in common
+        let mut funding: Vec<Transaction> = outputs
+            .iter()
+            .enumerate()
+            .map(|(nonce, output)| {
+                let mut tx = transfer(nonce as u64, 200_000, GAS_LIMIT);
+                tx.outputs[0].address = output.one_time_key.to_vec();
+                tx.outputs[0].ephemeral_key = Some(output.ephemeral_key);
+                tx
+            })
+            .collect();
+        for sender in 0..SPENDS as u8 {
+            let mut tx = transfer((SPENDS + sender as usize) as u64, 200_000, GAS_LIMIT);
+            tx.outputs[0].address = vec![0xd0 + sender];
+            funding.push(tx);
+        }
+
+        let mut ring: Vec<[u8; 32]> = outputs.iter().map(|output| output.one_time_key).collect();
+        ring.sort();
+        let mut transactions = Vec::new();
+        for (index, (owner, output)) in owners.iter().zip(&outputs).enumerate() {
+            let hash = [0xc0 + index as u8; 32];
+            let secret = owner.one_time_secret(0, output).unwrap();
+            let real_index = ring.iter().position(|key| *key == output.one_time_key).unwrap();
+            let mut spend = Transaction {
+                hash,
+                sender: vec![0xc0 + index as u8],
+                nonce: 0,
+                ring_inputs: vec![RingInput {
+                    amount: 200_000,
+                    ring: ring.clone(),
+                    key_image: zk_proofs::key_image(&secret).unwrap(),
+                    signature: Vec::new(),
+                    audit_tag: None,
+                }],
+                ..transfer(0, 50, GAS_LIMIT)
+            };
+            let signature = RingSignature::sign(&spend.signing_hash(), &ring, real_index, &secret, &mut rng).unwrap();
+            spend.ring_inputs[0].signature = signature.to_bytes();
+            transactions.push(spend);
+            let mut independent = transfer(0, 1_000, GAS_LIMIT);
+            independent.hash = [0xd0 + index as u8; 32];
+            independent.sender = vec![0xd0 + index as u8];
+            independent.outputs[0].address = vec![0xe0 + index as u8];
+            transactions.push(independent);
+        }
+        let key_images: Vec<[u8; 32]> = transactions
+            .iter()
+            .flat_map(|tx| tx.ring_inputs.iter().map(|input| input.key_image))
+            .collect();
+        let mut spending = block(2, transactions);
+        spending.header.nullifier_root = accumulate_nullifiers(&[0u8; 32], &key_images);
+
+        let run = |parallel_execution: bool| {
+            let temp_dir = TempDir::new().unwrap();
+            let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+            let mut genesis = Genesis::default();
+            genesis.alloc.insert(hex::encode(ALICE), GenesisAccount { balance: 10_000_000 });
+            state.apply_genesis(&genesis).unwrap();
+            let config = ExecutionConfig { ring_size: SPENDS, parallel_execution, ..ExecutionConfig::default() };
+            let mut executor = BlockExecutor::new(config).unwrap();
+            executor.process_block(&mut state, &block(1, funding.clone()), VALIDATOR).unwrap();
+            let started = std::time::Instant::now();
+            let execution = executor.process_block(&mut state, &spending, VALIDATOR).unwrap();
+            (execution, started.elapsed(), executor.get_stats())
+        };
+        let (sequential, sequential_time, _) = run(false);
+        let (parallel, parallel_time, stats) = run(true);
+        println!(
+            "{} transactions: sequential {:?}, parallel {:?}, speedup {:.2}x",
+            spending.transactions.len(),
+            sequential_time,
+            parallel_time,
+            sequential_time.as_secs_f64() / parallel_time.as_secs_f64()
+        );
+
+        // Every node commits the same state whichever way it executes
+        assert_eq!(parallel, sequential);
+        assert!(parallel.receipts.iter().all(|receipt| receipt.status == ReceiptStatus::Success));
+        // Funding comes from one sender, so all but its first transaction ran again. Spends all
+        // draw on the shielded pool, while transfers between their own accounts kept their result.
+        assert_eq!(stats.reexecuted_transactions, (2 * SPENDS as u64 - 1) + (SPENDS as u64 - 1));
+    }
+
+    #[test]
+    fn test_out_of_gas_reverts_transfers_but_charges_the_fee() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+
+        // Covers the intrinsic gas but not funding a new account
+        let result = executor
+            .process_block(&mut state, &block(1, vec![transfer(0, 100, 30_000)]), VALIDATOR)
+            .unwrap();
+        let receipt = &result.receipts[0];
+        assert_eq!(receipt.status, ReceiptStatus::Failed);
+        assert_eq!((receipt.gas_used, receipt.fee), (30_000, 30_000));
+        assert!(receipt.logs.is_empty());
+        let alice = state.get_account(ALICE).unwrap();
+        assert_eq!((alice.balance, alice.nonce), (970_000, 1));
+        assert_eq!(state.get_account(BOB).unwrap(), Account::default());
+        assert_eq!(state.get_account(VALIDATOR).unwrap().balance, 30_000);
+        assert_eq!(executor.get_stats().failed_transactions, 1);
+
+        // Below the intrinsic gas the transaction cannot be included at all
+        let too_low = block(2, vec![transfer(1, 100, 20_000)]);
+        assert!(matches!(
+            executor.process_block(&mut state, &too_low, VALIDATOR),
+            Err(ExecutionError::InvalidTransaction(_))
+        ));
+
+        let mut executor = BlockExecutor::new(ExecutionConfig {
+            block_gas_limit: 120_000,
+            ..Default::default()
+        })
+        .unwrap();
+        let over_limit = block(2, vec![transfer(1, 1, GAS_LIMIT), transfer(2, 1, GAS_LIMIT)]);
+        assert!(matches!(
+            executor.process_block(&mut state, &over_limit, VALIDATOR),
+            Err(ExecutionError::InvalidBlock(_))
+        ));
+        assert_eq!(state.latest_version(), Some(1));
+    }
+
+    #[test]
+    fn test_log_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        for (height, transactions) in [
+            (1, vec![transfer(0, 1, GAS_LIMIT)]),
+            (2, vec![]),
+            (3, vec![transfer(1, 2, GAS_LIMIT)]),
+        ] {
+            executor.process_block(&mut state, &block(height, transactions), VALIDATOR).unwrap();
+        }
+
+        let all = LogFilter {
+            from_block: Some(1),
+            ..Default::default()
+        };
+        let logs = get_logs(&state, &all).unwrap();
+        assert_eq!(logs.iter().map(|log| log.block_height).collect::<Vec<_>>(), vec![1, 3]);
+        // Without a range only the latest block is scanned
+        assert_eq!(get_logs(&state, &LogFilter::default()).unwrap(), logs[1..]);
+
+        let to_bob = LogFilter {
+            from_block: Some(1),
+            addresses: vec![ALICE.to_vec()],
+            topics: vec![Some(vec![transfer_topic()]), None, Some(vec![address_topic(BOB)])],
+            ..Default::default()
+        };
+        assert_eq!(get_logs(&state, &to_bob).unwrap().len(), 2);
+        let from_bob = LogFilter {
+            from_block: Some(1),
+            topics: vec![None, Some(vec![address_topic(BOB)])],
+            ..Default::default()
+        };
+        assert!(get_logs(&state, &from_bob).unwrap().is_empty());
+
+        // Ranges are clamped to the committed head before the width check
+        let beyond_head = LogFilter {
+            from_block: Some(0),
+            to_block: Some(MAX_LOG_RANGE + 5),
+            ..Default::default()
+        };
+        assert_eq!(get_logs(&state, &beyond_head).unwrap().len(), 2);
+
+        // Headers are kept for light clients, up to the head
+        let headers = get_headers(&state, 2, 5).unwrap();
+        assert_eq!(headers.iter().map(|header| header.height).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(get_headers(&state, 0, MAX_HEADER_RANGE + 1).is_err());
+    }
+
+    #[test]
+    fn test_invalid_transaction_rejects_block() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+
+        // The second transfer overdraws, so the first must not apply either
+        let overdraw = block(1, vec![transfer(0, 500_000, GAS_LIMIT), transfer(1, 500_000, GAS_LIMIT)]);
+        assert!(matches!(
+            executor.process_block(&mut state, &overdraw, VALIDATOR),
+            Err(ExecutionError::InvalidTransaction(_))
+        ));
+        assert_eq!(state.get_account(ALICE).unwrap().balance, 1_000_000);
+        assert_eq!(state.latest_version(), Some(GENESIS_VERSION));
+
+        // Replayed nonces are refused
+        executor
+            .process_block(&mut state, &block(1, vec![transfer(0, 10, GAS_LIMIT)]), VALIDATOR)
+            .unwrap();
+        let replayed = block(2, vec![transfer(0, 10, GAS_LIMIT)]);
+        assert!(executor.process_block(&mut state, &replayed, VALIDATOR).is_err());
+        assert!(executor.process_block(&mut state, &block(0, vec![]), VALIDATOR).is_err());
+        assert!(executor.process_block(&mut state, &block(2, vec![]), &[]).is_err());
+
+        let overflow = block(2, vec![transfer(1, u64::MAX, GAS_LIMIT)]);
+        assert!(executor.process_block(&mut state, &overflow, VALIDATOR).is_err());
+    }
+
+    #[test]
+    fn test_dry_runs_check_blocks_on_any_parent_without_committing() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        let first = block(1, vec![transfer(0, 100, GAS_LIMIT)]);
+        let writes = executor.check_block(&state, &first, VALIDATOR).unwrap();
+        assert_eq!(state.latest_version(), Some(GENESIS_VERSION));
+        executor.process_block(&mut state, &first, VALIDATOR).unwrap();
+        assert_eq!(state.get_sync(&account_key(ALICE)).unwrap().as_ref(), writes.get(&account_key(ALICE)));
+
+        // A side branch off genesis is checked on the genesis version with its own blocks on top
+        let genesis = state.state_at(GENESIS_VERSION).unwrap();
+        let fork = block(1, vec![transfer(0, 800_000, GAS_LIMIT)]);
+        let mut branch = PendingState::new(&genesis);
+        branch.apply(&executor.check_block(&genesis, &fork, VALIDATOR).unwrap());
+        assert!(executor.check_block(&branch, &block(2, vec![transfer(1, 1, GAS_LIMIT)]), VALIDATOR).is_ok());
+        let overdraw = block(2, vec![transfer(1, 100_000, GAS_LIMIT)]);
+        assert!(executor.check_block(&branch, &overdraw, VALIDATOR).is_err());
+        assert!(executor.check_block(&state, &overdraw, VALIDATOR).is_ok());
+        assert!(executor.check_block(&genesis, &block(1, vec![transfer(1, 1, GAS_LIMIT)]), VALIDATOR).is_err());
+        assert_eq!(executor.nullifier_root(&branch, &overdraw).unwrap(), EMPTY_HASH);
+        assert_eq!(executor.get_stats().blocks_executed, 1);
+    }
+
+    #[test]
+    fn test_mints_credit_recipients_in_nonce_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(attested_config()).unwrap();
+        let unsigned = |nonce: u64| Transaction {
+            sender: MINT_ADDRESS.to_vec(),
+            fee: 0,
+            gas_limit: 0,
+            ..transfer(nonce, 700, 0)
+        };
+        let mint = |nonce: u64| attest(unsigned(nonce), &[&attester()]);
+
+        // Mints need a bridge attester's signature, of exactly what they mint
+        assert!(executor.process_block(&mut state, &block(1, vec![unsigned(0)]), VALIDATOR).is_err());
+        let forged = attest(unsigned(0), &[&SigningKey::from_bytes(&[0xf0; 32])]);
+        assert!(executor.process_block(&mut state, &block(1, vec![forged]), VALIDATOR).is_err());
+        let inflated = Transaction { outputs: transfer(0, 7_000, 0).outputs, ..mint(0) };
+        assert!(executor.process_block(&mut state, &block(1, vec![inflated]), VALIDATOR).is_err());
+
+        // The signature binds the mint to its source and its chain
+        let unbacked = Transaction { sender: XFG_MINT_ADDRESS.to_vec(), ..mint(0) };
+        assert!(executor.process_block(&mut state, &block(1, vec![unbacked]), VALIDATOR).is_err());
+        let other_chain = attest(Transaction { chain_id: 2, ..unsigned(0) }, &[&attester()]);
+        assert!(executor.process_block(&mut state, &block(1, vec![other_chain]), VALIDATOR).is_err());
+        let xfg_mint = attest(Transaction { sender: XFG_MINT_ADDRESS.to_vec(), ..unsigned(0) }, &[&attester()]);
+
+        let result = executor.process_block(&mut state, &block(1, vec![mint(0), xfg_mint]), VALIDATOR).unwrap();
+        assert_eq!((result.gas_used, result.fees), (0, 0));
+        let supply = state.get_supply().unwrap();
+        assert_eq!((supply.minted, supply.bridged_in, supply.xfg_burn_minted), (1_400, 700, 700));
+        assert_eq!(supply.circulating().unwrap(), 1_001_400);
+        assert_eq!(state.get_account(BOB).unwrap().balance, 1_400);
+        assert_eq!(state.get_account(MINT_ADDRESS).unwrap().nonce, 1);
+        assert_eq!(result.receipts[0].logs[0].topics[1], address_topic(MINT_ADDRESS));
+
+        // A deposit cannot be minted twice, and mints cannot pay fees
+        assert!(executor.process_block(&mut state, &block(2, vec![mint(0)]), VALIDATOR).is_err());
+        let paid = attest(Transaction { fee: 1, ..unsigned(1) }, &[&attester()]);
+        assert!(executor.process_block(&mut state, &block(2, vec![paid]), VALIDATOR).is_err());
+    }
+
+    #[test]
+    fn test_mints_need_the_threshold_of_distinct_attesters() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let second = SigningKey::from_bytes(&[0xa8; 32]);
+        let keys = [attester(), second.clone()];
+        let attesters = keys.iter().map(|key| hex::encode(key.verifying_key().to_bytes())).collect::<Vec<_>>();
+        let config = |threshold: usize| ExecutionConfig {
+            mint_attestation: MintAttestation { attesters: attesters.clone(), threshold },
+            ..Default::default()
+        };
+        assert!(BlockExecutor::new(config(0)).is_err());
+        assert!(BlockExecutor::new(config(3)).is_err());
+        let mut executor = BlockExecutor::new(config(2)).unwrap();
+        let mint = Transaction { sender: MINT_ADDRESS.to_vec(), fee: 0, gas_limit: 0, ..transfer(0, 700, 0) };
+
+        let once = attest(mint.clone(), &[&attester()]);
+        assert!(executor.process_block(&mut state, &block(1, vec![once]), VALIDATOR).is_err());
+        let twice = attest(mint.clone(), &[&attester(), &attester()]);
+        assert!(executor.process_block(&mut state, &block(1, vec![twice]), VALIDATOR).is_err());
+        let both = attest(mint, &[&attester(), &second]);
+        executor.process_block(&mut state, &block(1, vec![both]), VALIDATOR).unwrap();
+        assert_eq!(state.get_account(BOB).unwrap().balance, 700);
+    }
+
+    #[test]
+    fn test_coinbase_is_capped_by_the_emission_schedule() {
+        use crate::emission::EmissionCurve;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let emission = EmissionSchedule {
+            initial_subsidy: 1_000,
+            curve: EmissionCurve::Halving { interval: 2 },
+            tail_subsidy: 0,
+        };
+        let mut executor = BlockExecutor::new(ExecutionConfig { emission, ..Default::default() }).unwrap();
+        let coinbase = |height: u64, amount: u64| Transaction {
+            sender: COINBASE_ADDRESS.to_vec(),
+            fee: 0,
+            gas_limit: 0,
+            ..transfer(height, amount, 0)
+        };
+
+        executor.process_block(&mut state, &block(1, vec![coinbase(1, 1_000)]), VALIDATOR).unwrap();
+        // The subsidy halved at height 2
+        assert!(executor.process_block(&mut state, &block(2, vec![coinbase(2, 501)]), VALIDATOR).is_err());
+        let late = block(2, vec![transfer(0, 10, GAS_LIMIT), coinbase(2, 500)]);
+        assert!(executor.process_block(&mut state, &late, VALIDATOR).is_err());
+        assert!(executor.process_block(&mut state, &block(2, vec![coinbase(1, 500)]), VALIDATOR).is_err());
+        executor.process_block(&mut state, &block(2, vec![coinbase(2, 500)]), VALIDATOR).unwrap();
+
+        assert_eq!(state.get_account(BOB).unwrap().balance, 1_500);
+        let supply = state.get_supply().unwrap();
+        assert_eq!((supply.block_rewards, supply.circulating().unwrap()), (1_500, 1_001_500));
+    }
+
+    #[test]
+    fn test_base_fee_is_burned_and_follows_block_fullness() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let config = ExecutionConfig {
+            block_gas_limit: 2 * GAS_LIMIT,
+            fee_market: FeeMarketConfig {
+                enabled: true,
+                initial_base_fee: 2,
+                elasticity: 4,
+                ..Default::default()
+            },
+            ..ExecutionConfig::default()
+        };
+        let mut executor = BlockExecutor::new(config.clone()).unwrap();
+        // Paying three units of fee per unit of gas leaves a tip of one over the base fee of two
+        let paying = |nonce: u64, fee_per_gas: u64| Transaction {
+            fee: fee_per_gas * GAS_LIMIT,
+            ..transfer(nonce, 100, GAS_LIMIT)
+        };
+        let full = block(1, vec![paying(0, 3), paying(1, 3)]);
+        let result = executor.process_block(&mut state, &full, VALIDATOR).unwrap();
+        assert_eq!((result.base_fee, result.base_fees, result.fees), (2, 2 * result.gas_used, result.gas_used));
+        assert_eq!(state.get_account(VALIDATOR).unwrap().balance, result.fees);
+        let supply = state.get_supply().unwrap();
+        assert_eq!(supply.fees_burned, result.base_fees);
+        assert_eq!(supply.circulating().unwrap(), 1_000_000 - result.base_fees);
+
+        // The block used more than a quarter of its gas limit, so the base fee rises
+        let fees = crate::fee_market::get_block_fees(&state, 1).unwrap().unwrap();
+        assert_eq!((fees.gas_target, fees.next_base_fee), (GAS_LIMIT / 2, 3));
+        let err = executor.process_block(&mut state, &block(2, vec![paying(2, 2)]), VALIDATOR).unwrap_err();
+        assert!(err.to_string().contains("below the base fee"), "{}", err);
+
+        // With a treasury the base fee is paid to it instead of burned
+        const TREASURY: &[u8] = &[0x7e];
+        let mut executor = BlockExecutor::new(ExecutionConfig {
+            fee_market: FeeMarketConfig {
+                treasury: Some(TREASURY.to_vec()),
+                ..config.fee_market
+            },
+            ..config
+        })
+        .unwrap();
+        let result = executor.process_block(&mut state, &block(2, vec![paying(2, 3)]), VALIDATOR).unwrap();
+        assert_eq!((result.base_fee, result.fees), (3, 0));
+        assert_eq!(state.get_account(TREASURY).unwrap().balance, result.base_fees);
+        assert_eq!(state.get_supply().unwrap().fees_burned, supply.fees_burned);
+        let history = crate::fee_market::get_fee_history(&state, 0, 10).unwrap();
+        assert_eq!(history.iter().map(|fees| fees.base_fee).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_withdrawals_burn_and_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        let l1_recipient = vec![0x1e; 20];
+        let mut withdrawal = transfer(0, 5_000, GAS_LIMIT);
+        withdrawal.outputs[0].address = WITHDRAWAL_ADDRESS.to_vec();
+        withdrawal.data = l1_recipient.clone();
+
+        let result = executor.process_block(&mut state, &block(1, vec![withdrawal.clone()]), VALIDATOR).unwrap();
+        let alice = state.get_account(ALICE).unwrap();
+        assert_eq!(alice.balance, 1_000_000 - 5_000 - result.fees);
+        assert_eq!(state.get_account(WITHDRAWAL_ADDRESS).unwrap(), Account::default());
+        let log = &result.receipts[0].logs[0];
+        assert_eq!(log.topics, vec![withdrawal_topic(), address_topic(ALICE), address_topic(&l1_recipient)]);
+        assert_eq!(log.data, 5_000u64.to_be_bytes());
+        let supply = state.get_supply().unwrap();
+        assert_eq!((supply.burned, supply.bridged_out, supply.circulating().unwrap()), (5_000, 5_000, 995_000));
+        assert_eq!(executor.get_stats().heat_burned, 5_000);
+
+        // The L1 recipient must be a 20-byte address
+        withdrawal.nonce = 1;
+        withdrawal.data = vec![0x1e; 32];
+        assert!(executor.process_block(&mut state, &block(2, vec![withdrawal]), VALIDATOR).is_err());
+    }
+
+    #[test]
+    fn test_bridged_tokens_mint_move_and_withdraw_apart_from_heat() {
+        use crate::token::{get_token, get_token_balance};
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(attested_config()).unwrap();
+        let info = TokenInfo { l1_origin: vec![0x70; 20], symbol: "USDC".to_string(), decimals: 6 };
+        let asset = info.asset_id();
+        let mint = Transaction {
+            sender: MINT_ADDRESS.to_vec(),
+            fee: 0,
+            gas_limit: 0,
+            data: serde_json::to_vec(&info).unwrap(),
+            asset: Some(asset),
+            ..transfer(0, 700, 0)
+        };
+        let mint = attest(mint, &[&attester()]);
+        executor.process_block(&mut state, &block(1, vec![mint]), VALIDATOR).unwrap();
+        assert_eq!(get_token(&state, &asset).unwrap().unwrap().supply, 700);
+        assert_eq!(get_token_balance(&state, &asset, BOB).unwrap(), 700);
+        assert_eq!(state.get_account(BOB).unwrap().balance, 0);
+        assert_eq!(state.get_supply().unwrap().minted, 0);
+
+        // Bob moves tokens to Alice and withdraws some to L1, paying fees in HEAT she sent him
+        let heat = transfer(0, 2 * GAS_LIMIT, GAS_LIMIT);
+        executor.process_block(&mut state, &block(2, vec![heat]), VALIDATOR).unwrap();
+        let send = |nonce: u64, amount: u64, to: &[u8]| {
+            let mut tx = transfer(nonce, amount, GAS_LIMIT);
+            tx.hash = [0xb0 + nonce as u8; 32];
+            tx.sender = BOB.to_vec();
+            tx.outputs[0].address = to.to_vec();
+            tx.asset = Some(asset);
+            tx
+        };
+        let overdrawn = send(0, 701, ALICE);
+        assert!(executor.process_block(&mut state, &block(3, vec![overdrawn]), VALIDATOR).is_err());
+        let mut withdrawal = send(1, 200, WITHDRAWAL_ADDRESS);
+        withdrawal.data = vec![0x1e; 20];
+        let moves = block(3, vec![send(0, 300, ALICE), withdrawal]);
+        let result = executor.process_block(&mut state, &moves, VALIDATOR).unwrap();
+        assert_eq!(get_token_balance(&state, &asset, ALICE).unwrap(), 300);
+        assert_eq!(get_token_balance(&state, &asset, BOB).unwrap(), 200);
+        assert_eq!(get_token(&state, &asset).unwrap().unwrap().supply, 500);
+        assert_eq!(state.get_account(BOB).unwrap().balance, 2 * GAS_LIMIT - result.fees);
+        assert_eq!(result.receipts[1].logs[0].topics[3], address_topic(&info.l1_origin));
+        let supply = state.get_supply().unwrap();
+        assert_eq!((supply.burned, supply.circulating().unwrap()), (0, 1_000_000));
+    }
+
+    #[test]
+    fn test_eldernodes_register_serve_blocks_and_deregister() {
+        use crate::eldernode::{get_eldernode, get_eldernodes, get_served};
+        use EldernodeCommand::{Deregister, Heartbeat, Register};
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let eldernode = EldernodeConfig { min_stake: 1_000, fee_share_bps: 5_000, heartbeat_window: 2 };
+        let mut executor = BlockExecutor::new(ExecutionConfig { eldernode, ..Default::default() }).unwrap();
+        let call = |nonce: u64, command: EldernodeCommand, amount: u64| {
+            let mut tx = transfer(nonce, amount, GAS_LIMIT);
+            tx.outputs[0].address = ELDERNODE_REGISTRY_ADDRESS.to_vec();
+            tx.data = command.to_data();
+            tx
+        };
+
+        // Registering needs the minimum stake, and the new Eldernode serves its block
+        let registration = block(1, vec![call(0, Register, 999), call(1, Register, 1_000)]);
+        let result = executor.process_block(&mut state, &registration, VALIDATOR).unwrap();
+        assert_eq!(result.receipts[0].status, ReceiptStatus::Failed);
+        let share = EldernodeShare { address: ALICE.to_vec(), fee: result.fees / 2 };
+        assert_eq!(result.eldernodes, vec![share]);
+        assert_eq!(state.get_account(VALIDATOR).unwrap().balance, result.fees - result.fees / 2);
+        assert_eq!(state.get_account(ELDERNODE_REGISTRY_ADDRESS).unwrap().balance, 1_000);
+
+        // Heartbeats add stake and keep the Eldernode live for the window
+        executor.process_block(&mut state, &block(2, vec![call(2, Heartbeat, 500)]), VALIDATOR).unwrap();
+        let node = get_eldernode(&state, ALICE).unwrap().unwrap();
+        assert_eq!((node.stake, node.last_heartbeat, node.blocks_served), (1_500, 2, 2));
+        assert!(executor.process_block(&mut state, &block(5, vec![]), VALIDATOR).unwrap().eldernodes.is_empty());
+        assert_eq!(get_served(&state, 2).unwrap()[0].address, ALICE);
+        assert!(get_served(&state, 5).unwrap().is_empty());
+
+        // A registry call carries one output and a command
+        let mut malformed = call(3, Heartbeat, 0);
+        malformed.outputs.push(transfer(3, 1, GAS_LIMIT).outputs[0].clone());
+        assert!(executor.process_block(&mut state, &block(6, vec![malformed]), VALIDATOR).is_err());
+
+        let result = executor.process_block(&mut state, &block(6, vec![call(3, Deregister, 0)]), VALIDATOR).unwrap();
+        assert_eq!(result.receipts[0].logs[1].data, 1_500u64.to_be_bytes());
+        assert_eq!(state.get_account(ELDERNODE_REGISTRY_ADDRESS).unwrap().balance, 0);
+        assert!(get_eldernode(&state, ALICE).unwrap().is_none());
+        assert!(get_eldernodes(&state).unwrap().is_empty());
+        assert_eq!(executor.get_stats().eldernode_fees, node.fees_earned);
+    }
+
+    #[test]
+    fn test_spent_nullifiers_are_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = genesis_state(temp_dir.path());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        let spend = |nonce: u64, nullifier: u8| Transaction {
+            nullifiers: vec![[nullifier; 32]],
+            ..transfer(nonce, 10, GAS_LIMIT)
+        };
+        let with_root = |mut block: Block, root: MerkleRoot| {
+            block.header.nullifier_root = root;
+            block
+        };
+
+        let root = accumulate_nullifiers(&[0u8; 32], &[[1u8; 32], [2u8; 32]]);
+        let first = with_root(block(1, vec![spend(0, 1), spend(1, 2)]), root);
+        let result = executor.process_block(&mut state, &first, VALIDATOR).unwrap();
+        assert_eq!(result.nullifier_root, root);
+        assert_eq!(state.nullifier_root().unwrap(), root);
+
+        // Spent earlier, repeated within the block, or committed under the wrong root
+        let respent = with_root(block(2, vec![spend(2, 1)]), accumulate_nullifiers(&root, &[[1u8; 32]]));
+        assert!(executor.process_block(&mut state, &respent, VALIDATOR).is_err());
+        let repeated = accumulate_nullifiers(&root, &[[3u8; 32], [3u8; 32]]);
+        let repeated = with_root(block(2, vec![spend(2, 3), spend(3, 3)]), repeated);
+        assert!(executor.process_block(&mut state, &repeated, VALIDATOR).is_err());
+        assert!(executor.process_block(&mut state, &block(2, vec![spend(2, 3)]), VALIDATOR).is_err());
+
+        // Blocks without private spends carry the root forward
+        let empty = with_root(block(2, vec![]), root);
+        assert_eq!(executor.process_block(&mut state, &empty, VALIDATOR).unwrap().nullifier_root, root);
+    }
+}
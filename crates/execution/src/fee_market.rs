@@ -0,0 +1,141 @@
+//! EIP-1559 style fee market. Each block has a base fee per unit of gas, set by its parent:
+//! it rises when the parent used more than the gas target and falls when it used less, by
+//! at most 1/`max_change_denominator` a block. A transaction's fee over its gas limit must
+//! cover the base fee. Of what it is charged for the gas it used, the base fee part is
+//! burned, or paid to the treasury when one is configured, and the rest is the validator's tip.
+
+use crate::error::ExecutionError;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+
+const FEE_MARKET_PREFIX: &[u8] = b"fees/block/";
+
+/// Most blocks `get_fee_history` returns at once
+pub const MAX_FEE_HISTORY: u64 = 1024;
+
+/// Base fee rules; every node of a network must agree on them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeMarketConfig {
+    /// Charge a base fee; without one the validator is paid every fee
+    pub enabled: bool,
+    /// Base fee of the first block charged one
+    pub initial_base_fee: u64,
+    /// The base fee never falls below this
+    pub min_base_fee: u64,
+    /// Gas target of a block: its gas limit divided by this
+    pub elasticity: u64,
+    /// The base fee moves by at most itself divided by this each block
+    pub max_change_denominator: u64,
+    /// Account paid the base fee instead of burning it
+    pub treasury: Option<Vec<u8>>,
+}
+
+impl Default for FeeMarketConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_base_fee: 1,
+            min_base_fee: 1,
+            elasticity: 2,
+            max_change_denominator: 8,
+            treasury: None,
+        }
+    }
+}
+
+impl FeeMarketConfig {
+    /// Gas a block of `block_gas_limit` aims to use
+    pub fn gas_target(&self, block_gas_limit: u64) -> u64 {
+        (block_gas_limit / self.elasticity.max(1)).max(1)
+    }
+
+    /// Base fee of the block after one with `base_fee` that used `gas_used` of `gas_target`
+    pub fn next_base_fee(&self, base_fee: u64, gas_used: u64, gas_target: u64) -> u64 {
+        let denominator = self.max_change_denominator.max(1) as u128;
+        let change = |gas: u64| base_fee as u128 * gas as u128 / gas_target.max(1) as u128 / denominator;
+        let next = if gas_used > gas_target {
+            base_fee.saturating_add(change(gas_used - gas_target).max(1) as u64)
+        } else {
+            base_fee.saturating_sub(change(gas_target - gas_used) as u64)
+        };
+        next.max(self.min_base_fee)
+    }
+}
+
+/// Base fee charged by one block and where its fees went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockFees {
+    pub height: u64,
+    pub base_fee: u64,
+    pub gas_used: u64,
+    pub gas_target: u64,
+    /// Base fees burned, or paid to the treasury
+    pub base_fees: u64,
+    /// Fees above the base fee, paid to the validator
+    pub tips: u64,
+    /// Base fee of the block after this one
+    pub next_base_fee: u64,
+}
+
+/// Split a fee of `fee` charged for `gas_used` into its base fee part and the tip
+pub fn split_fee(fee: u64, base_fee: u64, gas_used: u64) -> (u64, u64) {
+    let base = base_fee.saturating_mul(gas_used).min(fee);
+    (base, fee - base)
+}
+
+/// State key of the fees of the block at `height`
+pub fn block_fees_key(height: u64) -> Vec<u8> {
+    [FEE_MARKET_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+/// Fees of the block at `height`, if it charged a base fee
+pub fn get_block_fees(state: &RocksStateDB, height: u64) -> Result<Option<BlockFees>, ExecutionError> {
+    state
+        .get_sync(&block_fees_key(height))?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Fees of up to `count` blocks from `from_block`, skipping blocks that charged no base fee
+pub fn get_fee_history(state: &RocksStateDB, from_block: u64, count: u64) -> Result<Vec<BlockFees>, ExecutionError> {
+    if count > MAX_FEE_HISTORY {
+        return Err(ExecutionError::InvalidFilter(format!(
+            "{} blocks exceeds the limit of {}",
+            count, MAX_FEE_HISTORY
+        )));
+    }
+    let Some(latest) = state.latest_version() else {
+        return Ok(Vec::new());
+    };
+    (from_block..from_block.saturating_add(count).min(latest.saturating_add(1)))
+        .filter_map(|height| get_block_fees(state, height).transpose())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_fee_follows_parent_fullness() {
+        let config = FeeMarketConfig {
+            enabled: true,
+            min_base_fee: 7,
+            ..Default::default()
+        };
+        let target = config.gas_target(30_000_000);
+        assert_eq!(target, 15_000_000);
+        // A full block raises the fee by an eighth, an empty one lowers it by an eighth
+        assert_eq!(config.next_base_fee(800, 30_000_000, target), 900);
+        assert_eq!(config.next_base_fee(800, 0, target), 700);
+        assert_eq!(config.next_base_fee(800, target, target), 800);
+        // Small fees still rise, and never fall below the floor
+        assert_eq!(config.next_base_fee(7, target + 1, target), 8);
+        assert_eq!(config.next_base_fee(7, 0, target), 7);
+
+        assert_eq!(split_fee(1_000, 3, 200), (600, 400));
+        assert_eq!(split_fee(1_000, 10, 200), (1_000, 0));
+    }
+}
@@ -0,0 +1,167 @@
+use block_sync::Transaction;
+use serde::{Deserialize, Serialize};
+
+/// Gas prices of the operations a transaction can perform
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasSchedule {
+    /// Charged once for every transaction
+    pub tx_base: u64,
+    /// Charged per byte of transaction data
+    pub data_byte: u64,
+    /// Charged when a transfer funds an account that was empty
+    pub new_account: u64,
+    /// Charged per emitted log
+    pub log: u64,
+    /// Charged per byte of log data
+    pub log_data_byte: u64,
+    /// Charged per byte of deployed contract code
+    pub code_byte: u64,
+    /// Charged per contract storage read
+    pub storage_read: u64,
+    /// Charged per contract storage write
+    pub storage_write: u64,
+    /// Charged per byte of a stored value
+    pub storage_byte: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            tx_base: 21_000,
+            data_byte: 68,
+            new_account: 25_000,
+            log: 375,
+            log_data_byte: 8,
+            code_byte: 200,
+            storage_read: 200,
+            storage_write: 5_000,
+            storage_byte: 16,
+        }
+    }
+}
+
+impl GasSchedule {
+    /// Gas a transaction costs before it executes anything
+    pub fn intrinsic_gas(&self, tx: &Transaction) -> u64 {
+        self.tx_base
+            .saturating_add(self.data_byte.saturating_mul(tx.data.len() as u64))
+    }
+
+    /// Gas of one log carrying `data_len` bytes
+    pub fn log_gas(&self, data_len: usize) -> u64 {
+        self.log.saturating_add(self.log_data_byte.saturating_mul(data_len as u64))
+    }
+}
+
+/// The transaction ran past its gas limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfGas;
+
+/// One charge recorded by a tracing meter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasStep {
+    /// What was charged for, e.g. `intrinsic` or `log`
+    pub op: String,
+    pub gas: u64,
+    /// Gas used by the transaction once this step was charged
+    pub cumulative_gas: u64,
+}
+
+/// Gas consumed by one transaction against its limit
+#[derive(Debug, Clone)]
+pub struct GasMeter {
+    limit: u64,
+    used: u64,
+    /// Charges so far, when tracing
+    steps: Option<Vec<GasStep>>,
+}
+
+impl GasMeter {
+    pub fn new(limit: u64) -> Self {
+        Self { limit, used: 0, steps: None }
+    }
+
+    /// A meter recording every charge as a step
+    pub fn traced(limit: u64) -> Self {
+        Self {
+            steps: Some(Vec::new()),
+            ..Self::new(limit)
+        }
+    }
+
+    /// Consume `amount`, failing without consuming anything once the limit would be exceeded
+    pub fn charge(&mut self, amount: u64) -> Result<(), OutOfGas> {
+        match self.used.checked_add(amount) {
+            Some(used) if used <= self.limit => {
+                self.used = used;
+                Ok(())
+            }
+            _ => Err(OutOfGas),
+        }
+    }
+
+    /// Consume `amount` for `op`, recording it as a step when tracing
+    pub fn charge_for(&mut self, op: &str, amount: u64) -> Result<(), OutOfGas> {
+        self.charge(amount)?;
+        if let Some(steps) = &mut self.steps {
+            steps.push(GasStep {
+                op: op.to_string(),
+                gas: amount,
+                cumulative_gas: self.used,
+            });
+        }
+        Ok(())
+    }
+
+    /// The steps recorded so far, leaving none behind; empty unless tracing
+    pub fn take_steps(&mut self) -> Vec<GasStep> {
+        self.steps.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Gas consumed so far
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Gas still available
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used
+    }
+}
+
+/// Share of `max_fee` owed for `gas_used` out of `gas_limit`; the rest is refunded
+pub fn charged_fee(max_fee: u64, gas_used: u64, gas_limit: u64) -> u64 {
+    if gas_limit == 0 {
+        return max_fee;
+    }
+    // gas_used <= gas_limit, so the quotient never exceeds max_fee
+    (max_fee as u128 * gas_used as u128 / gas_limit as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_meter_and_fees() {
+        let mut meter = GasMeter::new(100);
+        meter.charge(60).unwrap();
+        assert_eq!(meter.charge(41), Err(OutOfGas));
+        assert_eq!((meter.used(), meter.remaining()), (60, 40));
+        meter.charge(40).unwrap();
+        assert_eq!(meter.charge(u64::MAX), Err(OutOfGas));
+        assert!(meter.take_steps().is_empty());
+
+        let mut meter = GasMeter::traced(100);
+        meter.charge_for("intrinsic", 60).unwrap();
+        assert_eq!(meter.charge_for("log", 41), Err(OutOfGas));
+        meter.charge_for("log", 30).unwrap();
+        let steps = meter.take_steps();
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[1].op.as_str(), steps[1].gas, steps[1].cumulative_gas), ("log", 30, 90));
+
+        assert_eq!(charged_fee(1_000, 25, 100), 250);
+        assert_eq!(charged_fee(u64::MAX, 7, 7), u64::MAX);
+        assert_eq!(charged_fee(10, 1, 3), 3);
+    }
+}
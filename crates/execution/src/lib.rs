@@ -0,0 +1,34 @@
+//! Deterministic block execution over the account state: gas-metered balance
+//! transfers, nonces and fees, committed to the StateDB as one version per block
+//! together with the receipts and logs of its transactions.
+
+pub mod audit;
+pub mod eldernode;
+pub mod emission;
+pub mod error;
+pub mod exchange;
+pub mod executor;
+pub mod fee_market;
+pub mod gas;
+pub mod receipt;
+pub mod shielded;
+pub mod token;
+pub mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use audit::{check_audit_report, AuditReport, DisclosedSpend};
+pub use eldernode::{Eldernode, EldernodeCommand, EldernodeConfig, EldernodeShare, ELDERNODE_REGISTRY_ADDRESS};
+pub use emission::{EmissionCurve, EmissionSchedule, Network};
+pub use error::ExecutionError;
+pub use exchange::{DepositAddress, DepositViewKey, DetectedDeposit};
+pub use executor::{
+    BlockExecution, BlockExecutor, ExecutionConfig, ExecutionStats, MintAttestation, ParentState, PendingState,
+    StateWrites, COINBASE_ADDRESS, MINT_ADDRESS, WITHDRAWAL_ADDRESS, XFG_MINT_ADDRESS,
+};
+pub use fee_market::{BlockFees, FeeMarketConfig};
+pub use gas::{GasMeter, GasSchedule};
+pub use receipt::{Log, LogFilter, Receipt, ReceiptStatus, RingSpendRecord, StealthOutputRecord};
+pub use shielded::SHIELDED_POOL_ADDRESS;
+pub use token::{Token, TokenInfo};
+pub use trace::{BalanceChange, TransactionTrace};
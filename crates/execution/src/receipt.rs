@@ -0,0 +1,318 @@
+use crate::error::ExecutionError;
+use block_sync::{Block, BlockHeader, Canonical};
+use hashing::Domain;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+
+const RECEIPT_PREFIX: &[u8] = b"receipt/";
+const BLOCK_LOGS_PREFIX: &[u8] = b"logs/";
+const BLOCK_STEALTH_OUTPUTS_PREFIX: &[u8] = b"stealth_outputs/";
+const BLOCK_RING_SPENDS_PREFIX: &[u8] = b"ring_spends/";
+const BLOCK_HEADER_PREFIX: &[u8] = b"header/";
+const BLOCK_BODY_PREFIX: &[u8] = b"body/";
+
+/// Unversioned keys and values written alongside a block
+type StateEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Widest block range a single log query may scan
+pub const MAX_LOG_RANGE: u64 = 10_000;
+
+/// Most headers a single header query may return
+pub const MAX_HEADER_RANGE: u64 = 2_000;
+
+/// Outcome of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    Success,
+    Failed,
+}
+
+/// Event emitted while executing a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    /// Account that emitted the event
+    pub address: Vec<u8>,
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
+    pub block_height: u64,
+    pub tx_hash: [u8; 32],
+    pub tx_index: u32,
+    /// Position of the log within its block
+    pub log_index: u32,
+}
+
+/// Result of an executed transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub tx_hash: [u8; 32],
+    pub block_height: u64,
+    pub tx_index: u32,
+    pub status: ReceiptStatus,
+    pub gas_used: u64,
+    /// Gas used by this and every earlier transaction of the block
+    pub cumulative_gas_used: u64,
+    /// Fee paid to the validator
+    pub fee: u64,
+    /// Address of the contract a deployment created
+    pub contract_address: Option<Vec<u8>>,
+    /// Why a failed transaction was reverted
+    pub revert_reason: Option<String>,
+    pub logs: Vec<Log>,
+}
+
+/// Output paid to a stealth one-time key, indexed so wallets can scan for their funds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StealthOutputRecord {
+    pub block_height: u64,
+    pub tx_hash: [u8; 32],
+    /// Position of the output within its transaction, which the one-time key commits to
+    pub output_index: u32,
+    pub amount: u64,
+    pub ephemeral_key: [u8; 32],
+    /// Account the output credited
+    pub one_time_key: Vec<u8>,
+}
+
+/// Ring input of a successful transaction, indexed so viewing keys can find their spends
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RingSpendRecord {
+    pub block_height: u64,
+    pub tx_hash: [u8; 32],
+    /// Position of the input within its transaction
+    pub input_index: u32,
+    pub amount: u64,
+    pub key_image: [u8; 32],
+    pub ring: Vec<[u8; 32]>,
+    pub audit_tag: Option<[u8; 32]>,
+}
+
+/// Topic of native HEAT transfer logs
+pub fn transfer_topic() -> [u8; 32] {
+    hashing::hash(Domain::EventTopic, b"Transfer(address,address,uint64)")
+}
+
+/// Topic of logs recording HEAT burned for withdrawal to L1
+pub fn withdrawal_topic() -> [u8; 32] {
+    hashing::hash(Domain::EventTopic, b"Withdrawal(address,address,uint64)")
+}
+
+/// Topic form of an address: left-padded to 32 bytes, or hashed when longer
+pub fn address_topic(address: &[u8]) -> [u8; 32] {
+    if address.len() > 32 {
+        return hashing::hash(Domain::AddressTopic, address);
+    }
+    let mut topic = [0u8; 32];
+    topic[32 - address.len()..].copy_from_slice(address);
+    topic
+}
+
+pub(crate) fn receipt_key(tx_hash: &[u8; 32]) -> Vec<u8> {
+    [RECEIPT_PREFIX, tx_hash.as_slice()].concat()
+}
+
+pub(crate) fn block_logs_key(height: u64) -> Vec<u8> {
+    [BLOCK_LOGS_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+fn block_stealth_outputs_key(height: u64) -> Vec<u8> {
+    [BLOCK_STEALTH_OUTPUTS_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+fn block_ring_spends_key(height: u64) -> Vec<u8> {
+    [BLOCK_RING_SPENDS_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+fn block_header_key(height: u64) -> Vec<u8> {
+    [BLOCK_HEADER_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+/// Unversioned StateDB entry holding the canonical encoding of an executed block's header
+pub(crate) fn header_entry(header: &BlockHeader) -> (Vec<u8>, Vec<u8>) {
+    (block_header_key(header.height), header.to_canonical_bytes())
+}
+
+fn block_body_key(height: u64) -> Vec<u8> {
+    [BLOCK_BODY_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+/// Unversioned StateDB entry holding an executed block and the validator paid its fees: the
+/// validator's length as two bytes, the validator, then the block's canonical encoding
+pub(crate) fn body_entry(block: &Block, validator: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let length = (validator.len() as u16).to_be_bytes();
+    let value = [length.as_slice(), validator, &block.to_canonical_bytes()].concat();
+    (block_body_key(block.header.height), value)
+}
+
+/// Unversioned StateDB entries recording `receipts`, the logs of block `height`, its
+/// stealth outputs and its ring spends
+pub(crate) fn receipt_entries(
+    height: u64,
+    receipts: &[Receipt],
+    stealth_outputs: &[StealthOutputRecord],
+    ring_spends: &[RingSpendRecord],
+) -> Result<StateEntries, ExecutionError> {
+    let mut entries = Vec::with_capacity(receipts.len() + 3);
+    for receipt in receipts {
+        entries.push((receipt_key(&receipt.tx_hash), serde_json::to_vec(receipt)?));
+    }
+    let logs: Vec<&Log> = receipts.iter().flat_map(|receipt| &receipt.logs).collect();
+    entries.push((block_logs_key(height), serde_json::to_vec(&logs)?));
+    if !stealth_outputs.is_empty() {
+        entries.push((block_stealth_outputs_key(height), serde_json::to_vec(stealth_outputs)?));
+    }
+    if !ring_spends.is_empty() {
+        entries.push((block_ring_spends_key(height), serde_json::to_vec(ring_spends)?));
+    }
+    Ok(entries)
+}
+
+/// Log query in the shape of `eth_getLogs`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogFilter {
+    /// First block to scan; defaults to `to_block`
+    pub from_block: Option<u64>,
+    /// Last block to scan; defaults to the latest committed block
+    pub to_block: Option<u64>,
+    /// Emitting accounts to match; empty matches any account
+    pub addresses: Vec<Vec<u8>>,
+    /// Topics by position, each matching any of its alternatives; `None` matches anything
+    pub topics: Vec<Option<Vec<[u8; 32]>>>,
+}
+
+impl LogFilter {
+    /// Whether `log` passes the address and topic criteria
+    pub fn matches(&self, log: &Log) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+        self.topics.iter().enumerate().all(|(position, alternatives)| match alternatives {
+            None => true,
+            Some(alternatives) => log
+                .topics
+                .get(position)
+                .is_some_and(|topic| alternatives.contains(topic)),
+        })
+    }
+}
+
+/// Get the receipt of the transaction `tx_hash`, if it was executed
+pub fn get_receipt(state: &RocksStateDB, tx_hash: &[u8; 32]) -> Result<Option<Receipt>, ExecutionError> {
+    match state.get_sync(&receipt_key(tx_hash))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Get the logs matching `filter`, in block order
+pub fn get_logs(state: &RocksStateDB, filter: &LogFilter) -> Result<Vec<Log>, ExecutionError> {
+    let Some(latest) = state.latest_version() else {
+        return Ok(Vec::new());
+    };
+    let to_block = filter.to_block.unwrap_or(latest).min(latest);
+    let from_block = filter.from_block.unwrap_or(to_block);
+    if from_block > to_block {
+        return Ok(Vec::new());
+    }
+    if to_block - from_block >= MAX_LOG_RANGE {
+        return Err(ExecutionError::InvalidFilter(format!(
+            "Block range {}..={} exceeds {} blocks",
+            from_block, to_block, MAX_LOG_RANGE
+        )));
+    }
+
+    let mut logs = Vec::new();
+    for height in from_block..=to_block {
+        if let Some(bytes) = state.get_sync(&block_logs_key(height))? {
+            let block_logs: Vec<Log> = serde_json::from_slice(&bytes)?;
+            logs.extend(block_logs.into_iter().filter(|log| filter.matches(log)));
+        }
+    }
+    Ok(logs)
+}
+
+/// Get the headers of the executed blocks among `count` heights from `from_block` on
+pub fn get_headers(state: &RocksStateDB, from_block: u64, count: u64) -> Result<Vec<BlockHeader>, ExecutionError> {
+    if count > MAX_HEADER_RANGE {
+        return Err(ExecutionError::InvalidFilter(format!(
+            "{} headers exceeds the limit of {}",
+            count, MAX_HEADER_RANGE
+        )));
+    }
+    let Some(latest) = state.latest_version() else {
+        return Ok(Vec::new());
+    };
+
+    let mut headers = Vec::new();
+    for height in from_block..from_block.saturating_add(count).min(latest.saturating_add(1)) {
+        if let Some(bytes) = state.get_sync(&block_header_key(height))? {
+            let header = BlockHeader::from_canonical_bytes(&bytes)
+                .map_err(|e| ExecutionError::SerializationError(e.to_string()))?;
+            headers.push(header);
+        }
+    }
+    Ok(headers)
+}
+
+/// Get executed block `height` with the validator its fees were paid to
+pub fn get_block(state: &RocksStateDB, height: u64) -> Result<Option<(Block, Vec<u8>)>, ExecutionError> {
+    let Some(bytes) = state.get_sync(&block_body_key(height))? else {
+        return Ok(None);
+    };
+    let corrupt = || ExecutionError::SerializationError(format!("Body of block {} is corrupt", height));
+    let (length, rest) = bytes.split_first_chunk::<2>().ok_or_else(corrupt)?;
+    let length = u16::from_be_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(corrupt());
+    }
+    let (validator, block) = rest.split_at(length);
+    let block = Block::from_canonical_bytes(block).map_err(|e| ExecutionError::SerializationError(e.to_string()))?;
+    Ok(Some((block, validator.to_vec())))
+}
+
+/// Get the stealth outputs paid in blocks `from_block..=to_block`, in block order
+pub fn get_stealth_outputs(
+    state: &RocksStateDB,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<StealthOutputRecord>, ExecutionError> {
+    get_block_records(state, block_stealth_outputs_key, from_block, to_block)
+}
+
+/// Get the ring inputs spent in blocks `from_block..=to_block`, in block order
+pub fn get_ring_spends(
+    state: &RocksStateDB,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<RingSpendRecord>, ExecutionError> {
+    get_block_records(state, block_ring_spends_key, from_block, to_block)
+}
+
+fn get_block_records<T: DeserializeOwned>(
+    state: &RocksStateDB,
+    block_key: fn(u64) -> Vec<u8>,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<T>, ExecutionError> {
+    let Some(latest) = state.latest_version() else {
+        return Ok(Vec::new());
+    };
+    let to_block = to_block.min(latest);
+    if from_block > to_block {
+        return Ok(Vec::new());
+    }
+    if to_block - from_block >= MAX_LOG_RANGE {
+        return Err(ExecutionError::InvalidFilter(format!(
+            "Block range {}..={} exceeds {} blocks",
+            from_block, to_block, MAX_LOG_RANGE
+        )));
+    }
+
+    let mut records = Vec::new();
+    for height in from_block..=to_block {
+        if let Some(bytes) = state.get_sync(&block_key(height))? {
+            records.extend(serde_json::from_slice::<Vec<T>>(&bytes)?);
+        }
+    }
+    Ok(records)
+}
@@ -0,0 +1,71 @@
+//! Shielded pool for stealth outputs. Outputs paid to one-time keys are held by the
+//! pool account and recorded by key; a ring input withdraws one of them without
+//! revealing which, and its key image joins the nullifier set so it cannot be spent twice.
+
+use crate::error::ExecutionError;
+use crate::executor::ParentState;
+use block_sync::RingInput;
+use state_db::RocksStateDB;
+use std::collections::HashSet;
+use zk_proofs::RingSignature;
+
+/// Account holding the value of every unspent stealth output
+pub const SHIELDED_POOL_ADDRESS: &[u8] = &[0xee; 20];
+
+const STEALTH_OUTPUT_PREFIX: &[u8] = b"stealth_output/";
+
+/// State key recording the amount of the stealth output paid to `one_time_key`
+pub fn stealth_output_key(one_time_key: &[u8]) -> Vec<u8> {
+    [STEALTH_OUTPUT_PREFIX, one_time_key].concat()
+}
+
+/// Amount of the stealth output paid to `one_time_key`, if there is one
+pub fn get_stealth_output(state: &RocksStateDB, one_time_key: &[u8]) -> Result<Option<u64>, ExecutionError> {
+    decode_amount(state.get_sync(&stealth_output_key(one_time_key))?)
+}
+
+pub(crate) fn decode_amount(bytes: Option<Vec<u8>>) -> Result<Option<u64>, ExecutionError> {
+    bytes
+        .map(|bytes| {
+            <[u8; 8]>::try_from(bytes.as_slice())
+                .map(u64::from_be_bytes)
+                .map_err(|_| ExecutionError::StateError("Malformed stealth output".to_string()))
+        })
+        .transpose()
+}
+
+/// Check that `input` spends one of `ring_size` existing outputs of its amount, signed over
+/// `signing_hash`
+pub(crate) fn check_ring_input(
+    state: &dyn ParentState,
+    ring_size: usize,
+    signing_hash: &[u8; 32],
+    input: &RingInput,
+) -> Result<(), String> {
+    if input.ring.len() != ring_size {
+        return Err(format!("ring has {} members, expected {}", input.ring.len(), ring_size));
+    }
+    if input.ring.iter().collect::<HashSet<_>>().len() != input.ring.len() {
+        return Err("ring repeats a member".to_string());
+    }
+    for member in &input.ring {
+        let stored = state.get(&stealth_output_key(member)).map_err(|e| e.to_string())?;
+        match decode_amount(stored).map_err(|e| e.to_string())? {
+            Some(amount) if amount == input.amount => {}
+            Some(amount) => {
+                return Err(format!(
+                    "ring member {} holds {}, not {}",
+                    hex::encode(member),
+                    amount,
+                    input.amount
+                ))
+            }
+            None => return Err(format!("ring member {} is not a stealth output", hex::encode(member))),
+        }
+    }
+    let signature = RingSignature::from_bytes(input.key_image, &input.signature).map_err(|e| e.to_string())?;
+    if !signature.verify(signing_hash, &input.ring) {
+        return Err("invalid ring signature".to_string());
+    }
+    Ok(())
+}
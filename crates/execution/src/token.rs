@@ -0,0 +1,76 @@
+//! Registry of tokens bridged from L1 next to native HEAT. A token is registered by the
+//! first bridge mint of it, which carries the token's L1 contract, symbol and decimals,
+//! and is identified by an asset id derived from that contract. Token balances are kept
+//! per asset and account apart from HEAT balances; transactions naming an asset move
+//! token balances with their outputs while still paying their fee in HEAT.
+
+use crate::error::ExecutionError;
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+
+const TOKEN_PREFIX: &[u8] = b"token/info/";
+const TOKEN_BALANCE_PREFIX: &[u8] = b"token/balance/";
+
+/// What the bridge knows of a token it carries from L1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    /// Address of the token contract on L1
+    pub l1_origin: Vec<u8>,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl TokenInfo {
+    /// Asset id of the token on C0DL3
+    pub fn asset_id(&self) -> [u8; 32] {
+        asset_id(&self.l1_origin)
+    }
+}
+
+/// A registered token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub asset: [u8; 32],
+    pub info: TokenInfo,
+    /// Units minted by deposits less those burned by withdrawals
+    pub supply: u64,
+}
+
+/// Asset id of the token bridged from the L1 contract at `l1_origin`
+pub fn asset_id(l1_origin: &[u8]) -> [u8; 32] {
+    Hasher::new(Domain::BridgedAsset).bytes(l1_origin).finish()
+}
+
+/// State key of the registered token `asset`
+pub fn token_key(asset: &[u8; 32]) -> Vec<u8> {
+    [TOKEN_PREFIX, asset.as_slice()].concat()
+}
+
+/// State key of the balance of `asset` held by `address`
+pub fn token_balance_key(asset: &[u8; 32], address: &[u8]) -> Vec<u8> {
+    [TOKEN_BALANCE_PREFIX, asset.as_slice(), address].concat()
+}
+
+/// The registered token `asset`, if any
+pub fn get_token(state: &RocksStateDB, asset: &[u8; 32]) -> Result<Option<Token>, ExecutionError> {
+    state
+        .get_sync(&token_key(asset))?
+        .map(|bytes| serde_json::from_slice(&bytes))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Balance of `asset` held by `address`
+pub fn get_token_balance(state: &RocksStateDB, asset: &[u8; 32], address: &[u8]) -> Result<u64, ExecutionError> {
+    decode_balance(state.get_sync(&token_balance_key(asset, address))?)
+}
+
+pub(crate) fn decode_balance(bytes: Option<Vec<u8>>) -> Result<u64, ExecutionError> {
+    match bytes {
+        Some(bytes) => <[u8; 8]>::try_from(bytes.as_slice())
+            .map(u64::from_be_bytes)
+            .map_err(|_| ExecutionError::StateError("Malformed token balance".to_string())),
+        None => Ok(0),
+    }
+}
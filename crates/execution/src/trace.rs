@@ -0,0 +1,177 @@
+//! Traces of executed transactions, rebuilt by running a stored block again on its parent state.
+//!
+//! Nothing is recorded while blocks are processed. Tracing loads the block kept with its
+//! receipts and executes its transactions in order over a view of the previous version,
+//! metering each charge and diffing balances around every transaction.
+
+use crate::error::ExecutionError;
+use crate::executor::{AccountOverlay, BlockExecutor};
+use crate::gas::GasStep;
+use crate::receipt::{get_block, Receipt};
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+
+/// Balance of an account before and after a transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub address: Vec<u8>,
+    pub before: u64,
+    pub after: u64,
+}
+
+/// What executing one transaction did, step by step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionTrace {
+    pub receipt: Receipt,
+    /// Every gas charge in the order it was made
+    pub gas_steps: Vec<GasStep>,
+    /// Accounts whose balance changed, including the validator's tip
+    pub balance_changes: Vec<BalanceChange>,
+}
+
+impl BlockExecutor {
+    /// Trace every transaction of block `height`, or `None` if this node has not executed it.
+    /// The version before the block must not have been pruned.
+    pub fn trace_block(
+        &self,
+        state: &RocksStateDB,
+        height: u64,
+    ) -> Result<Option<Vec<TransactionTrace>>, ExecutionError> {
+        let Some((block, validator)) = get_block(state, height)? else {
+            return Ok(None);
+        };
+        let parent = state.state_at(height - 1)?;
+        let base_fee = self.base_fee(&parent, height)?;
+        let mut overlay = AccountOverlay::new(&parent);
+        let mut traces = Vec::with_capacity(block.transactions.len());
+        let mut cumulative_gas_used = 0u64;
+        let mut log_index = 0u32;
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            let before = overlay.balances();
+            overlay.gas_steps = Some(Vec::new());
+            let mut receipt = self.execute_transaction(&mut overlay, height, tx_index as u32, tx)?;
+            self.pay_fee(&mut overlay, &validator, base_fee, tx, &receipt)?;
+            cumulative_gas_used += receipt.gas_used;
+            receipt.cumulative_gas_used = cumulative_gas_used;
+            for log in &mut receipt.logs {
+                log.log_index = log_index;
+                log_index += 1;
+            }
+
+            let mut balance_changes = Vec::new();
+            for (address, after) in overlay.balances() {
+                let before = match before.get(&address) {
+                    Some(balance) => *balance,
+                    None => overlay.parent_balance(&address)?,
+                };
+                if before != after {
+                    balance_changes.push(BalanceChange { address, before, after });
+                }
+            }
+            traces.push(TransactionTrace {
+                receipt,
+                gas_steps: overlay.gas_steps.take().unwrap_or_default(),
+                balance_changes,
+            });
+        }
+        Ok(Some(traces))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::ExecutionConfig;
+    use crate::gas::GasSchedule;
+    use crate::receipt::get_receipt;
+    use crate::BlockExecutor;
+    use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+    use state_db::account::GenesisAccount;
+    use state_db::{Genesis, RocksStateDB};
+    use tempfile::TempDir;
+
+    const ALICE: &[u8] = &[0xa1];
+    const BOB: &[u8] = &[0xb0];
+    const VALIDATOR: &[u8] = &[0xfe];
+
+    fn transfer(nonce: u64, amount: u64) -> Transaction {
+        Transaction {
+            hash: [nonce as u8; 32],
+            sender: ALICE.to_vec(),
+            nonce,
+            gas_limit: 100_000,
+            data: Vec::new(),
+            inputs: vec![],
+            outputs: vec![TxOutput {
+                amount,
+                address: BOB.to_vec(),
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 100_000,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        }
+    }
+
+    fn block(height: u64, transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_trace_block_replays_receipts_with_gas_steps_and_balance_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut genesis = Genesis::default();
+        genesis.alloc.insert(hex::encode(ALICE), GenesisAccount { balance: 1_000_000 });
+        state.apply_genesis(&genesis).unwrap();
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        executor.process_block(&mut state, &block(1, vec![transfer(0, 100), transfer(1, 200)]), VALIDATOR).unwrap();
+        // A later block moves the balances on without changing the traces of block 1
+        executor.process_block(&mut state, &block(2, vec![transfer(2, 300)]), VALIDATOR).unwrap();
+
+        let traces = executor.trace_block(&state, 1).unwrap().unwrap();
+        assert_eq!(traces.len(), 2);
+        for trace in &traces {
+            assert_eq!(Some(&trace.receipt), get_receipt(&state, &trace.receipt.tx_hash).unwrap().as_ref());
+            assert_eq!(trace.gas_steps.last().unwrap().cumulative_gas, trace.receipt.gas_used);
+        }
+        let schedule = GasSchedule::default();
+        let ops: Vec<&str> = traces[0].gas_steps.iter().map(|step| step.op.as_str()).collect();
+        assert_eq!(ops, ["intrinsic", "new_account", "log"]);
+        assert_eq!(traces[0].gas_steps[0].gas, schedule.tx_base);
+
+        let second = &traces[1];
+        let fee = second.receipt.fee;
+        let changes: Vec<(&[u8], u64, u64)> = second
+            .balance_changes
+            .iter()
+            .map(|change| (change.address.as_slice(), change.before, change.after))
+            .collect();
+        let alice = 1_000_000 - 100 - traces[0].receipt.fee;
+        let validator = traces[0].receipt.fee;
+        assert_eq!(
+            changes,
+            [(ALICE, alice, alice - 200 - fee), (BOB, 100, 300), (VALIDATOR, validator, validator + fee)]
+        );
+        assert_eq!(executor.trace_block(&state, 3).unwrap(), None);
+    }
+}
@@ -0,0 +1,416 @@
+//! Contract runtime on wasmtime, enabled with the `wasm` feature.
+//!
+//! A transaction without outputs whose data is a WASM module deploys a contract.
+//! Transfers to a contract account call its exported `call` function with the
+//! transaction data as input. Contracts import their host API from `env`:
+//!
+//! - `input_len() -> i32` and `input(out_ptr)` read the call data
+//! - `storage_get(key_ptr, key_len, out_ptr, out_cap) -> i32` returns the value length, or -1
+//! - `storage_put(key_ptr, key_len, value_ptr, value_len)`
+//! - `caller(out_ptr, out_cap) -> i32` returns the caller address length
+//! - `value() -> i64` is the HEAT amount sent with the call
+//! - `emit_log(topics_ptr, topic_count, data_ptr, data_len)` takes 32-byte topics
+//!
+//! Gas is metered as wasm fuel one to one, and host calls burn fuel at the
+//! [`GasSchedule`] prices.
+
+use crate::error::ExecutionError;
+use crate::executor::{AccountOverlay, Revert};
+use crate::gas::{GasMeter, GasSchedule};
+use hashing::{Domain, Hasher};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use wasmtime::{Caller, Config, Engine, Linker, Memory, Module, Store, Trap};
+
+/// Leading bytes of every WASM module
+pub const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Most topics one contract log may carry
+const MAX_LOG_TOPICS: u32 = 4;
+
+/// Address of the contract deployed by `sender` with transaction nonce `nonce`
+pub fn contract_address(sender: &[u8], nonce: u64) -> Vec<u8> {
+    Hasher::new(Domain::ContractAddress).bytes(sender).u64(nonce).finish()[12..].to_vec()
+}
+
+/// Log emitted by a contract, before it is placed in a block
+pub(crate) struct ContractLog {
+    pub topics: Vec<[u8; 32]>,
+    pub data: Vec<u8>,
+}
+
+/// Context of one contract call
+pub(crate) struct CallContext<'t> {
+    pub contract: &'t [u8],
+    pub caller: &'t [u8],
+    pub value: u64,
+    pub input: &'t [u8],
+}
+
+struct HostState<'o, 's, 't> {
+    overlay: &'o mut AccountOverlay<'s>,
+    call: CallContext<'t>,
+    schedule: GasSchedule,
+    logs: Vec<ContractLog>,
+    /// State failure that must abort the block rather than revert the call
+    fault: Option<ExecutionError>,
+}
+
+/// Compiles and runs contracts with deterministic settings
+pub struct ContractRuntime {
+    engine: Engine,
+    modules: Mutex<HashMap<[u8; 32], Module>>,
+}
+
+impl ContractRuntime {
+    pub fn new() -> Result<Self, ExecutionError> {
+        let mut config = Config::new();
+        config
+            .consume_fuel(true)
+            .cranelift_nan_canonicalization(true)
+            .wasm_relaxed_simd(false);
+        let engine = Engine::new(&config).map_err(|e| ExecutionError::ConfigError(e.to_string()))?;
+        Ok(Self {
+            engine,
+            modules: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether `code` is a module this runtime accepts
+    pub fn validate(&self, code: &[u8]) -> bool {
+        Module::validate(&self.engine, code).is_ok()
+    }
+
+    fn module(&self, code_hash: [u8; 32], code: &[u8]) -> Result<Module, Revert> {
+        let mut modules = self.modules.lock().expect("module cache poisoned");
+        if let Some(module) = modules.get(&code_hash) {
+            return Ok(module.clone());
+        }
+        let module = Module::new(&self.engine, code).map_err(|e| Revert::Trap(format!("invalid contract code: {}", e)))?;
+        modules.insert(code_hash, module.clone());
+        Ok(module)
+    }
+
+    /// Run the `call` export of the contract at `call.contract`, charging `meter`.
+    ///
+    /// Storage writes go to `overlay`; the caller reverts them when the call fails.
+    pub(crate) fn call(
+        &self,
+        overlay: &mut AccountOverlay,
+        meter: &mut GasMeter,
+        schedule: &GasSchedule,
+        call: CallContext,
+    ) -> Result<Result<Vec<ContractLog>, Revert>, ExecutionError> {
+        let code_hash = match overlay.account(call.contract)?.code_hash {
+            Some(code_hash) => code_hash,
+            None => return Ok(Err(Revert::Trap("account has no code".to_string()))),
+        };
+        let code = overlay
+            .code(&code_hash)?
+            .ok_or_else(|| ExecutionError::StateError(format!("Missing code {}", hex::encode(code_hash))))?;
+        let module = match self.module(code_hash, &code) {
+            Ok(module) => module,
+            Err(revert) => return Ok(Err(revert)),
+        };
+
+        let fuel = meter.remaining();
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                overlay,
+                call,
+                schedule: schedule.clone(),
+                logs: Vec::new(),
+                fault: None,
+            },
+        );
+        store
+            .set_fuel(fuel)
+            .map_err(|e| ExecutionError::ConfigError(e.to_string()))?;
+
+        let result = Self::link(&self.engine)
+            .and_then(|linker| linker.instantiate(&mut store, &module))
+            .and_then(|instance| instance.get_typed_func::<(), ()>(&mut store, "call"))
+            .and_then(|entry| entry.call(&mut store, ()));
+        let remaining = store.get_fuel().unwrap_or(0);
+        let state = store.into_data();
+        if let Some(fault) = state.fault {
+            return Err(fault);
+        }
+        // Fuel never exceeds the meter's remaining gas, so this cannot fail
+        let _ = meter.charge_for("contract", fuel - remaining);
+
+        Ok(match result {
+            Ok(()) => Ok(state.logs),
+            Err(e) if e.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) => Err(Revert::OutOfGas),
+            Err(e) => Err(Revert::Trap(format!("contract trapped: {}", e))),
+        })
+    }
+
+    fn link<'o, 's, 't>(engine: &Engine) -> wasmtime::Result<Linker<HostState<'o, 's, 't>>> {
+        let mut linker = Linker::new(engine);
+        linker.func_wrap("env", "input_len", |caller: Caller<'_, HostState>| -> i32 {
+            caller.data().call.input.len() as i32
+        })?;
+        linker.func_wrap("env", "input", |mut caller: Caller<'_, HostState>, out: i32| {
+            let input = caller.data().call.input.to_vec();
+            write_memory(&mut caller, out, &input)
+        })?;
+        linker.func_wrap(
+            "env",
+            "storage_get",
+            |mut caller: Caller<'_, HostState>, key: i32, key_len: i32, out: i32, out_cap: i32| -> wasmtime::Result<i32> {
+                let gas = caller.data().schedule.storage_read;
+                charge(&mut caller, gas)?;
+                let key = read_memory(&mut caller, key, key_len)?;
+                let state = caller.data_mut();
+                let value = match state.overlay.storage(state.call.contract, &key) {
+                    Ok(value) => value,
+                    Err(e) => return Err(fault(state, e)),
+                };
+                match value {
+                    Some(value) => {
+                        let len = value.len().min(out_cap.max(0) as usize);
+                        write_memory(&mut caller, out, &value[..len])?;
+                        Ok(value.len() as i32)
+                    }
+                    None => Ok(-1),
+                }
+            },
+        )?;
+        linker.func_wrap(
+            "env",
+            "storage_put",
+            |mut caller: Caller<'_, HostState>, key: i32, key_len: i32, value: i32, value_len: i32| {
+                let schedule = &caller.data().schedule;
+                let gas = schedule
+                    .storage_write
+                    .saturating_add(schedule.storage_byte.saturating_mul(value_len.max(0) as u64));
+                charge(&mut caller, gas)?;
+                let key = read_memory(&mut caller, key, key_len)?;
+                let value = read_memory(&mut caller, value, value_len)?;
+                let state = caller.data_mut();
+                let contract = state.call.contract;
+                state
+                    .overlay
+                    .put_storage(contract, key, value)
+                    .map_err(|e| fault(state, e))
+            },
+        )?;
+        linker.func_wrap(
+            "env",
+            "caller",
+            |mut caller: Caller<'_, HostState>, out: i32, out_cap: i32| -> wasmtime::Result<i32> {
+                let address = caller.data().call.caller.to_vec();
+                let len = address.len().min(out_cap.max(0) as usize);
+                write_memory(&mut caller, out, &address[..len])?;
+                Ok(address.len() as i32)
+            },
+        )?;
+        linker.func_wrap("env", "value", |caller: Caller<'_, HostState>| -> i64 {
+            caller.data().call.value as i64
+        })?;
+        linker.func_wrap(
+            "env",
+            "emit_log",
+            |mut caller: Caller<'_, HostState>, topics: i32, topic_count: i32, data: i32, data_len: i32| {
+                if topic_count < 0 || topic_count as u32 > MAX_LOG_TOPICS {
+                    return Err(wasmtime::Error::msg("too many log topics"));
+                }
+                let gas = caller.data().schedule.log_gas(data_len.max(0) as usize);
+                charge(&mut caller, gas)?;
+                let topics = read_memory(&mut caller, topics, topic_count * 32)?
+                    .chunks_exact(32)
+                    .map(|topic| topic.try_into().expect("chunks are 32 bytes"))
+                    .collect();
+                let data = read_memory(&mut caller, data, data_len)?;
+                caller.data_mut().logs.push(ContractLog { topics, data });
+                Ok(())
+            },
+        )?;
+        Ok(linker)
+    }
+}
+
+/// Record a state failure and trap so the call stops
+fn fault(state: &mut HostState, e: ExecutionError) -> wasmtime::Error {
+    let message = e.to_string();
+    state.fault = Some(e);
+    wasmtime::Error::msg(message)
+}
+
+fn charge(caller: &mut Caller<'_, HostState>, gas: u64) -> wasmtime::Result<()> {
+    let fuel = caller.get_fuel()?;
+    if fuel < gas {
+        caller.set_fuel(0)?;
+        return Err(Trap::OutOfFuel.into());
+    }
+    caller.set_fuel(fuel - gas)
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("contract does not export its memory"))
+}
+
+fn read_memory(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    if ptr < 0 || len < 0 {
+        return Err(wasmtime::Error::msg("negative memory range"));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    memory(caller)?.read(&*caller, ptr as usize, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_memory(caller: &mut Caller<'_, HostState>, ptr: i32, bytes: &[u8]) -> wasmtime::Result<()> {
+    if ptr < 0 {
+        return Err(wasmtime::Error::msg("negative memory offset"));
+    }
+    memory(caller)?.write(&mut *caller, ptr as usize, bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{BlockExecutor, ExecutionConfig};
+    use crate::receipt::ReceiptStatus;
+    use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+    use state_db::account::GenesisAccount;
+    use state_db::{Genesis, RocksStateDB};
+    use tempfile::TempDir;
+
+    const ALICE: &[u8] = &[0xa1];
+    const VALIDATOR: &[u8] = &[0xfe];
+
+    /// Adds the call value to a stored total and logs the new total; traps on any input
+    const ACCUMULATOR: &str = r#"
+        (module
+          (import "env" "input_len" (func $input_len (result i32)))
+          (import "env" "storage_get" (func $storage_get (param i32 i32 i32 i32) (result i32)))
+          (import "env" "storage_put" (func $storage_put (param i32 i32 i32 i32)))
+          (import "env" "value" (func $value (result i64)))
+          (import "env" "emit_log" (func $emit_log (param i32 i32 i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "total")
+          (func (export "call")
+            (if (i32.ne (call $input_len) (i32.const 0)) (then unreachable))
+            (drop (call $storage_get (i32.const 0) (i32.const 5) (i32.const 64) (i32.const 8)))
+            (i64.store (i32.const 64) (i64.add (i64.load (i32.const 64)) (call $value)))
+            (call $storage_put (i32.const 0) (i32.const 5) (i32.const 64) (i32.const 8))
+            (call $emit_log (i32.const 96) (i32.const 1) (i32.const 64) (i32.const 8))))
+    "#;
+
+    fn transaction(nonce: u64, outputs: Vec<TxOutput>, data: Vec<u8>, gas_limit: u64) -> Transaction {
+        Transaction {
+            hash: [nonce as u8; 32],
+            sender: ALICE.to_vec(),
+            nonce,
+            gas_limit,
+            data,
+            inputs: vec![],
+            outputs,
+            fee: gas_limit,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        }
+    }
+
+    fn call(nonce: u64, contract: &[u8], amount: u64, input: &[u8], gas_limit: u64) -> Transaction {
+        let output = TxOutput {
+            amount,
+            address: contract.to_vec(),
+            commitment: [0u8; 32],
+            ephemeral_key: None,
+        };
+        transaction(nonce, vec![output], input.to_vec(), gas_limit)
+    }
+
+    fn block(height: u64, transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_deploy_and_call_contract() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut genesis = Genesis::default();
+        genesis.alloc.insert(hex::encode(ALICE), GenesisAccount { balance: 10_000_000 });
+        state.apply_genesis(&genesis).unwrap();
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+
+        let code = wat::parse_str(ACCUMULATOR).unwrap();
+        let deploy = transaction(0, vec![], code.clone(), 1_000_000);
+        let deployed = executor.process_block(&mut state, &block(1, vec![deploy]), VALIDATOR).unwrap();
+        let contract = contract_address(ALICE, 0);
+        assert_eq!(deployed.receipts[0].status, ReceiptStatus::Success);
+        assert_eq!(deployed.receipts[0].contract_address, Some(contract.clone()));
+        let code_hash = state.get_account(&contract).unwrap().code_hash.unwrap();
+        assert_eq!(state.get_code(&code_hash).unwrap(), Some(code));
+
+        let calls = vec![
+            call(1, &contract, 40, b"", 200_000),
+            call(2, &contract, 2, b"", 200_000),
+            // Traps, so neither the value nor the storage write applies
+            call(3, &contract, 100, b"fail", 200_000),
+            // Runs out of fuel at the storage write
+            call(4, &contract, 100, b"", 26_000),
+        ];
+        let result = executor.process_block(&mut state, &block(2, calls), VALIDATOR).unwrap();
+        let statuses: Vec<_> = result.receipts.iter().map(|receipt| receipt.status).collect();
+        assert_eq!(
+            statuses,
+            vec![ReceiptStatus::Success, ReceiptStatus::Success, ReceiptStatus::Failed, ReceiptStatus::Failed]
+        );
+        assert!(result.receipts[2].revert_reason.as_ref().unwrap().starts_with("contract trapped"));
+        assert_eq!(result.receipts[3].revert_reason.as_deref(), Some("out of gas"));
+
+        let logs = &result.receipts[1].logs;
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[1].address, contract);
+        assert_eq!(logs[1].data, 42u64.to_le_bytes());
+        assert_eq!(state.get_storage(&contract, b"total").unwrap(), Some(42u64.to_le_bytes().to_vec()));
+        let account = state.get_account(&contract).unwrap();
+        assert_eq!(account.balance, 42);
+        assert!(account.storage_root.is_some());
+    }
+
+    #[test]
+    fn test_invalid_code_fails_deployment() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut genesis = Genesis::default();
+        genesis.alloc.insert(hex::encode(ALICE), GenesisAccount { balance: 1_000_000 });
+        state.apply_genesis(&genesis).unwrap();
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+
+        let garbage = [WASM_MAGIC, &[0xff; 8]].concat();
+        let result = executor
+            .process_block(&mut state, &block(1, vec![transaction(0, vec![], garbage, 100_000)]), VALIDATOR)
+            .unwrap();
+        assert_eq!(result.receipts[0].status, ReceiptStatus::Failed);
+        assert_eq!(result.receipts[0].contract_address, None);
+        assert!(!state.get_account(&contract_address(ALICE, 0)).unwrap().is_contract());
+    }
+}
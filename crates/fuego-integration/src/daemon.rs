@@ -0,0 +1,336 @@
+use crate::error::FuegoError;
+use crate::rpc_client::{set_nonce, BlockTemplate, FuegoRpcClient, FuegoRpcConfig};
+use pow::{check_hash, CryptoNight};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::{Duration, Instant};
+
+/// Fuego mining daemon configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FuegoDaemonConfig {
+    pub rpc: FuegoRpcConfig,
+    pub wallet_address: String,
+    /// Extra nonce space reserved in the coinbase for the merge-mining tag
+    pub reserve_size: u32,
+    /// How often the tip is checked while working on a template
+    pub poll_interval: Duration,
+    /// Nonces tried per batch, split across `threads` blocking tasks
+    pub batch_size: u32,
+    /// Blocking tasks hashing each batch in parallel
+    pub threads: u32,
+    /// Nonces tried on one template before fetching a fresh one
+    pub max_nonce_attempts: u32,
+}
+
+impl Default for FuegoDaemonConfig {
+    fn default() -> Self {
+        Self {
+            rpc: FuegoRpcConfig::default(),
+            wallet_address: String::new(),
+            reserve_size: 60,
+            poll_interval: Duration::from_secs(5),
+            batch_size: 64,
+            threads: 1,
+            max_nonce_attempts: u32::MAX,
+        }
+    }
+}
+
+/// A Fuego block found and accepted by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuegoBlockSolution {
+    pub height: u64,
+    pub nonce: u32,
+    pub pow_hash: [u8; 32],
+    pub difficulty: u64,
+}
+
+/// Fuego mining statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FuegoMiningStats {
+    pub templates_fetched: u64,
+    pub stale_templates: u64,
+    pub blocks_submitted: u64,
+    pub blocks_rejected: u64,
+    pub total_hashes: u64,
+    pub hash_rate: u64,
+    pub last_template_height: u64,
+}
+
+/// Mines Fuego blocks on templates from a real Fuego daemon
+pub struct FuegoDaemon {
+    config: Arc<RwLock<FuegoDaemonConfig>>,
+    client: Arc<FuegoRpcClient>,
+    running: Arc<RwLock<bool>>,
+    stats: Arc<RwLock<FuegoMiningStats>>,
+}
+
+impl FuegoDaemon {
+    /// Create a new Fuego daemon connection
+    pub fn new(config: FuegoDaemonConfig) -> Result<Self, FuegoError> {
+        let client = Arc::new(FuegoRpcClient::new(config.rpc.clone())?);
+        Ok(Self {
+            config: Arc::new(RwLock::new(config)),
+            client,
+            running: Arc::new(RwLock::new(false)),
+            stats: Arc::new(RwLock::new(FuegoMiningStats::default())),
+        })
+    }
+
+    /// Start mining against the daemon
+    pub async fn start(&mut self) -> Result<(), FuegoError> {
+        *self.running.write().await = true;
+
+        let config = self.config.clone();
+        let client = self.client.clone();
+        let running = self.running.clone();
+        let stats = self.stats.clone();
+
+        tokio::spawn(async move {
+            while *running.read().await {
+                // Tuning changes take effect from the next template
+                let config = config.read().await.clone();
+                match Self::mine_block(&config, &client, &running, &stats).await {
+                    Ok(Some(solution)) => {
+                        println!("Fuego block {} accepted (nonce {})", solution.height, solution.nonce);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!("Fuego mining error: {}", e);
+                        tokio::time::sleep(config.poll_interval).await;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Stop mining
+    pub async fn stop(&mut self) -> Result<(), FuegoError> {
+        *self.running.write().await = false;
+        Ok(())
+    }
+
+    /// Fetch a template, search it for a solution and submit the block.
+    /// Returns `None` if the template went stale or the nonce budget ran out.
+    pub async fn mine_fuego_block(&self) -> Result<Option<FuegoBlockSolution>, FuegoError> {
+        let config = self.config.read().await.clone();
+        Self::mine_block(&config, &self.client, &self.running, &self.stats).await
+    }
+
+    /// Change the hashing threads and the tip poll interval while mining continues
+    pub async fn set_tuning(&self, threads: u32, poll_interval: Duration) {
+        let mut config = self.config.write().await;
+        config.threads = threads.max(1);
+        config.poll_interval = poll_interval;
+    }
+
+    /// Get the configuration currently mined with
+    pub async fn config(&self) -> FuegoDaemonConfig {
+        self.config.read().await.clone()
+    }
+
+    async fn mine_block(
+        config: &FuegoDaemonConfig,
+        client: &FuegoRpcClient,
+        running: &RwLock<bool>,
+        stats: &RwLock<FuegoMiningStats>,
+    ) -> Result<Option<FuegoBlockSolution>, FuegoError> {
+        let template = client.get_block_template(config.reserve_size, &config.wallet_address).await?;
+        {
+            let mut stats = stats.write().await;
+            stats.templates_fetched += 1;
+            stats.last_template_height = template.height;
+        }
+
+        let hashing_blob = template.hashing_bytes()?;
+        let start_time = Instant::now();
+        let mut last_poll = Instant::now();
+        let mut hashes = 0u64;
+        let mut nonce = 0u32;
+
+        while nonce < config.max_nonce_attempts && *running.read().await {
+            let count = config.batch_size.min(config.max_nonce_attempts - nonce);
+            let found = Self::search(&hashing_blob, nonce, count, template.difficulty, config.threads).await?;
+            hashes += count as u64;
+            {
+                let mut stats = stats.write().await;
+                stats.total_hashes += count as u64;
+                stats.hash_rate = hashes * 1000 / (start_time.elapsed().as_millis() as u64).max(1);
+            }
+
+            if let Some((solution_nonce, pow_hash)) = found {
+                return Self::submit(client, stats, &template, solution_nonce, pow_hash).await.map(Some);
+            }
+            nonce += count;
+
+            // Abandon the template once the daemon has moved to a new tip
+            if last_poll.elapsed() >= config.poll_interval {
+                last_poll = Instant::now();
+                if client.get_block_count().await? > template.height {
+                    stats.write().await.stale_templates += 1;
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Hash `count` nonces starting at `start`, split into contiguous ranges across
+    /// `threads` blocking tasks. The lowest solving nonce wins.
+    async fn search(
+        blob: &[u8],
+        start: u32,
+        count: u32,
+        difficulty: u64,
+        threads: u32,
+    ) -> Result<Option<(u32, [u8; 32])>, FuegoError> {
+        let chunk = count.div_ceil(threads.clamp(1, count.max(1))).max(1);
+        let mut searches = Vec::new();
+        let mut first = start;
+        while first < start + count {
+            let len = chunk.min(start + count - first);
+            let blob = blob.to_vec();
+            searches.push(tokio::task::spawn_blocking(move || Self::search_range(blob, first, len, difficulty)));
+            first += len;
+        }
+
+        let mut found = None;
+        for search in searches {
+            let result = search.await.map_err(|e| FuegoError::MiningError(e.to_string()))??;
+            found = found.or(result);
+        }
+        Ok(found)
+    }
+
+    fn search_range(
+        mut blob: Vec<u8>,
+        start: u32,
+        count: u32,
+        difficulty: u64,
+    ) -> Result<Option<(u32, [u8; 32])>, FuegoError> {
+        let hasher = CryptoNight::upx2();
+        let mut scratchpad = vec![0u8; hasher.params().memory];
+        for nonce in start..start + count {
+            set_nonce(&mut blob, nonce)?;
+            let hash = hasher.digest_with_scratchpad(&blob, &mut scratchpad);
+            if check_hash(&hash, difficulty) {
+                return Ok(Some((nonce, hash)));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn submit(
+        client: &FuegoRpcClient,
+        stats: &RwLock<FuegoMiningStats>,
+        template: &BlockTemplate,
+        nonce: u32,
+        pow_hash: [u8; 32],
+    ) -> Result<FuegoBlockSolution, FuegoError> {
+        let mut block = template.template_bytes()?;
+        set_nonce(&mut block, nonce)?;
+
+        match client.submit_block(&block).await {
+            Ok(()) => {
+                stats.write().await.blocks_submitted += 1;
+                Ok(FuegoBlockSolution {
+                    height: template.height,
+                    nonce,
+                    pow_hash,
+                    difficulty: template.difficulty,
+                })
+            }
+            Err(e) => {
+                stats.write().await.blocks_rejected += 1;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get the RPC client
+    pub fn client(&self) -> Arc<FuegoRpcClient> {
+        self.client.clone()
+    }
+
+    /// Get mining statistics
+    pub async fn get_stats(&self) -> FuegoMiningStats {
+        self.stats.read().await.clone()
+    }
+
+    /// Check if the daemon is mining
+    pub async fn is_running(&self) -> bool {
+        *self.running.read().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn template_blob() -> String {
+        let mut blob = vec![0x01, 0x00, 0x01];
+        blob.extend_from_slice(&[0u8; 32]);
+        blob.extend_from_slice(&[0u8; 4]);
+        blob.extend_from_slice(&[0x02; 8]);
+        hex::encode(blob)
+    }
+
+    #[tokio::test]
+    async fn test_mine_fuego_block_submits_solution() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "getblocktemplate" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0", "id": 0,
+                "result": {
+                    "blocktemplate_blob": template_blob(),
+                    "difficulty": 1,
+                    "height": 100,
+                    "reserved_offset": 40,
+                    "status": "OK",
+                },
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "submitblock" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0", "id": 1, "result": { "status": "OK" },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = FuegoDaemonConfig {
+            rpc: FuegoRpcConfig { url: server.uri(), ..Default::default() },
+            batch_size: 4,
+            ..Default::default()
+        };
+        let daemon = FuegoDaemon::new(config).unwrap();
+        *daemon.running.write().await = true;
+        daemon.set_tuning(2, Duration::from_secs(1)).await;
+        assert_eq!(daemon.config().await.threads, 2);
+
+        // Difficulty 1 accepts every nonce, and the lowest across both threads wins
+        let solution = daemon.mine_fuego_block().await.unwrap().unwrap();
+        assert_eq!(solution.height, 100);
+        assert_eq!(solution.nonce, 0);
+
+        let mut blob = hex::decode(template_blob()).unwrap();
+        set_nonce(&mut blob, 0).unwrap();
+        assert_eq!(solution.pow_hash, CryptoNight::upx2().digest(&blob));
+
+        let stats = daemon.get_stats().await;
+        assert_eq!(stats.templates_fetched, 1);
+        assert_eq!(stats.blocks_submitted, 1);
+        assert_eq!(stats.total_hashes, 4);
+    }
+}
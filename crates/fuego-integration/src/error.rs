@@ -0,0 +1,26 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum FuegoError {
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
+    #[error("RPC error: {0}")]
+    RpcError(String),
+
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Daemon unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("Mining error: {0}")]
+    MiningError(String),
+}
+
+impl FuegoError {
+    /// Whether the request may succeed if retried
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, FuegoError::TransportError(_) | FuegoError::Unavailable(_))
+    }
+}
@@ -0,0 +1,14 @@
+//! Integration with the Fuego daemon: a typed RPC client, a daemon driver
+//! that mines real Fuego block templates and a supervisor for the fuegod process.
+
+pub mod daemon;
+pub mod error;
+pub mod rpc_client;
+pub mod supervisor;
+
+pub use daemon::{FuegoBlockSolution, FuegoDaemon, FuegoDaemonConfig, FuegoMiningStats};
+pub use error::FuegoError;
+pub use rpc_client::{
+    socks_proxy, BlockTemplate, ConnectionHealth, FuegoBlockHeader, FuegoInfo, FuegoRpcClient, FuegoRpcConfig,
+};
+pub use supervisor::{FuegoProcessState, FuegoProcessStatus, FuegoSupervisor, FuegoSupervisorConfig};
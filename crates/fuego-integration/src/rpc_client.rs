@@ -0,0 +1,497 @@
+use crate::error::FuegoError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::{Duration, Instant};
+
+/// Fuego daemon RPC configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FuegoRpcConfig {
+    pub url: String,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failures after which the daemon is reported unhealthy
+    pub failure_threshold: u32,
+    /// SOCKS5 proxy, e.g. `socks5://127.0.0.1:9050` for Tor, the daemon is reached through
+    pub proxy: Option<String>,
+}
+
+impl Default for FuegoRpcConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:18180".to_string(),
+            timeout: Duration::from_secs(10),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+            failure_threshold: 3,
+            proxy: None,
+        }
+    }
+}
+
+/// Connection health of the Fuego daemon as seen by the client
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConnectionHealth {
+    pub is_healthy: bool,
+    pub consecutive_failures: u32,
+    pub total_requests: u64,
+    pub total_failures: u64,
+    /// Unix time of the last successful request
+    pub last_success: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_latency_ms: u64,
+}
+
+/// Block template returned by `getblocktemplate`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTemplate {
+    pub blocktemplate_blob: String,
+    /// Hashing blob, when the daemon provides it; otherwise the template blob is hashed
+    #[serde(default)]
+    pub blockhashing_blob: Option<String>,
+    pub difficulty: u64,
+    pub height: u64,
+    pub reserved_offset: u64,
+}
+
+impl BlockTemplate {
+    /// Decoded template blob
+    pub fn template_bytes(&self) -> Result<Vec<u8>, FuegoError> {
+        hex::decode(&self.blocktemplate_blob)
+            .map_err(|e| FuegoError::InvalidResponse(format!("Bad template blob: {}", e)))
+    }
+
+    /// Decoded hashing blob
+    pub fn hashing_bytes(&self) -> Result<Vec<u8>, FuegoError> {
+        let blob = self.blockhashing_blob.as_ref().unwrap_or(&self.blocktemplate_blob);
+        hex::decode(blob).map_err(|e| FuegoError::InvalidResponse(format!("Bad hashing blob: {}", e)))
+    }
+}
+
+/// SOCKS5 proxy for an HTTP client. Host names go to the proxy unresolved, and `isolation`
+/// is sent as the username so Tor carries this client's requests on a circuit of its own.
+/// Endpoints on this host are still reached directly.
+pub fn socks_proxy(url: &str, isolation: &str) -> reqwest::Result<reqwest::Proxy> {
+    let url = match url.strip_prefix("socks5://") {
+        Some(addr) => format!("socks5h://{}", addr),
+        None => url.to_string(),
+    };
+    let local = reqwest::NoProxy::from_string("localhost,127.0.0.1,::1");
+    Ok(reqwest::Proxy::all(url)?.basic_auth(isolation, "c0dl3").no_proxy(local))
+}
+
+/// Offset of the 4-byte nonce in a block or hashing blob: it follows the
+/// major version, minor version and timestamp varints and the previous block id
+pub fn nonce_offset(blob: &[u8]) -> Result<usize, FuegoError> {
+    let mut offset = 0;
+    for field in ["major_version", "minor_version", "timestamp"] {
+        let (_, read) = pow::auxpow::read_varint(&blob[offset..])
+            .ok_or_else(|| FuegoError::InvalidResponse(format!("Truncated {} in block blob", field)))?;
+        offset += read;
+    }
+    offset += 32;
+    if blob.len() < offset + 4 {
+        return Err(FuegoError::InvalidResponse("Block blob too short for nonce".to_string()));
+    }
+    Ok(offset)
+}
+
+/// Write `nonce` into a block or hashing blob
+pub fn set_nonce(blob: &mut [u8], nonce: u32) -> Result<(), FuegoError> {
+    let offset = nonce_offset(blob)?;
+    blob[offset..offset + 4].copy_from_slice(&nonce.to_le_bytes());
+    Ok(())
+}
+
+/// Fuego block header as returned by the `getblockheader*` methods
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuegoBlockHeader {
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub timestamp: u64,
+    pub prev_hash: String,
+    pub nonce: u32,
+    pub orphan_status: bool,
+    pub height: u64,
+    pub depth: u64,
+    pub hash: String,
+    pub difficulty: u64,
+    pub reward: u64,
+}
+
+/// Daemon information returned by `/getinfo`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuegoInfo {
+    pub height: u64,
+    pub difficulty: u64,
+    #[serde(default)]
+    pub tx_count: u64,
+    #[serde(default)]
+    pub tx_pool_size: u64,
+    #[serde(default)]
+    pub incoming_connections_count: u64,
+    #[serde(default)]
+    pub outgoing_connections_count: u64,
+    #[serde(default)]
+    pub synced: bool,
+}
+
+#[derive(Deserialize)]
+struct BlockCountResponse {
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct BlockHeaderResponse {
+    block_header: FuegoBlockHeader,
+}
+
+/// JSON-RPC client for the Fuego daemon
+pub struct FuegoRpcClient {
+    config: FuegoRpcConfig,
+    http: reqwest::Client,
+    health: Arc<RwLock<ConnectionHealth>>,
+    request_id: AtomicU64,
+}
+
+impl FuegoRpcClient {
+    /// Create a new Fuego RPC client
+    pub fn new(config: FuegoRpcConfig) -> Result<Self, FuegoError> {
+        let mut http = reqwest::Client::builder().timeout(config.timeout);
+        if let Some(proxy) = &config.proxy {
+            http = http.proxy(socks_proxy(proxy, &config.url).map_err(|e| FuegoError::TransportError(e.to_string()))?);
+        }
+        let http = http.build().map_err(|e| FuegoError::TransportError(e.to_string()))?;
+
+        Ok(Self {
+            config,
+            http,
+            health: Arc::new(RwLock::new(ConnectionHealth::default())),
+            request_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Get a block template paying to `wallet_address` with `reserve_size` bytes of extra nonce space
+    pub async fn get_block_template(&self, reserve_size: u32, wallet_address: &str) -> Result<BlockTemplate, FuegoError> {
+        self.call(
+            "getblocktemplate",
+            json!({ "reserve_size": reserve_size, "wallet_address": wallet_address }),
+        )
+        .await
+    }
+
+    /// Submit a solved block blob
+    pub async fn submit_block(&self, block_blob: &[u8]) -> Result<(), FuegoError> {
+        let _: Value = self.call("submitblock", json!([hex::encode(block_blob)])).await?;
+        Ok(())
+    }
+
+    /// Get the number of blocks in the daemon's main chain
+    pub async fn get_block_count(&self) -> Result<u64, FuegoError> {
+        let response: BlockCountResponse = self.call("getblockcount", json!({})).await?;
+        Ok(response.count)
+    }
+
+    /// Get the header of the block at `height`
+    pub async fn get_block_header_by_height(&self, height: u64) -> Result<FuegoBlockHeader, FuegoError> {
+        let response: BlockHeaderResponse = self.call("getblockheaderbyheight", json!({ "height": height })).await?;
+        Ok(response.block_header)
+    }
+
+    /// Get the header of the block with `hash`
+    pub async fn get_block_header_by_hash(&self, hash: &str) -> Result<FuegoBlockHeader, FuegoError> {
+        let response: BlockHeaderResponse = self.call("getblockheaderbyhash", json!({ "hash": hash })).await?;
+        Ok(response.block_header)
+    }
+
+    /// Get the header of the current tip
+    pub async fn get_last_block_header(&self) -> Result<FuegoBlockHeader, FuegoError> {
+        let response: BlockHeaderResponse = self.call("getlastblockheader", json!({})).await?;
+        Ok(response.block_header)
+    }
+
+    /// Get general daemon information
+    pub async fn get_info(&self) -> Result<FuegoInfo, FuegoError> {
+        let url = format!("{}/getinfo", self.config.url.trim_end_matches('/'));
+        self.with_retry(|| async {
+            let response = self.http.get(&url).send().await.map_err(Self::transport_error)?;
+            let body = Self::read_body(response).await?;
+            Self::check_status(&body)?;
+            serde_json::from_value(body).map_err(|e| FuegoError::InvalidResponse(e.to_string()))
+        })
+        .await
+    }
+
+    /// Get the current connection health
+    pub async fn health(&self) -> ConnectionHealth {
+        self.health.read().await.clone()
+    }
+
+    /// Get the RPC configuration
+    pub fn config(&self) -> &FuegoRpcConfig {
+        &self.config
+    }
+
+    /// Call a JSON-RPC method on `/json_rpc`
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, FuegoError> {
+        let url = format!("{}/json_rpc", self.config.url.trim_end_matches('/'));
+        self.with_retry(|| async {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": self.request_id.fetch_add(1, Ordering::Relaxed),
+                "method": method,
+                "params": params,
+            });
+            let response = self.http.post(&url).json(&request).send().await.map_err(Self::transport_error)?;
+            let mut body = Self::read_body(response).await?;
+
+            if let Some(error) = body.get("error").filter(|e| !e.is_null()) {
+                let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+                return Err(FuegoError::RpcError(format!("{}: {}", method, message)));
+            }
+            let result = body
+                .get_mut("result")
+                .map(Value::take)
+                .ok_or_else(|| FuegoError::InvalidResponse(format!("{}: missing result", method)))?;
+            Self::check_status(&result)?;
+            serde_json::from_value(result).map_err(|e| FuegoError::InvalidResponse(format!("{}: {}", method, e)))
+        })
+        .await
+    }
+
+    /// Run `request` with exponential backoff on retryable errors, tracking connection health
+    async fn with_retry<T, F, Fut>(&self, request: F) -> Result<T, FuegoError>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = Result<T, FuegoError>>,
+    {
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            let start_time = Instant::now();
+            let result = request().await;
+            self.record(&result, start_time.elapsed()).await;
+
+            match result {
+                Err(e) if e.is_retryable() && attempt < self.config.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.config.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn record<T>(&self, result: &Result<T, FuegoError>, latency: Duration) {
+        let mut health = self.health.write().await;
+        health.total_requests += 1;
+        health.last_latency_ms = latency.as_millis() as u64;
+        match result {
+            Ok(_) => {
+                health.consecutive_failures = 0;
+                health.last_success = Some(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                );
+            }
+            // A daemon answering with an RPC error is still reachable
+            Err(FuegoError::RpcError(e)) => health.last_error = Some(e.clone()),
+            Err(e) => {
+                health.consecutive_failures += 1;
+                health.total_failures += 1;
+                health.last_error = Some(e.to_string());
+            }
+        }
+        health.is_healthy = health.consecutive_failures < self.config.failure_threshold && health.last_success.is_some();
+    }
+
+    async fn read_body(response: reqwest::Response) -> Result<Value, FuegoError> {
+        let status = response.status();
+        if status.is_server_error() {
+            return Err(FuegoError::Unavailable(format!("HTTP {}", status)));
+        }
+        if !status.is_success() {
+            return Err(FuegoError::RpcError(format!("HTTP {}", status)));
+        }
+        response.json().await.map_err(|e| FuegoError::InvalidResponse(e.to_string()))
+    }
+
+    /// Fuego reports `BUSY` while the core is syncing and an error string otherwise
+    fn check_status(result: &Value) -> Result<(), FuegoError> {
+        match result.get("status").and_then(Value::as_str) {
+            None | Some("OK") => Ok(()),
+            Some("BUSY") => Err(FuegoError::Unavailable("Daemon is busy".to_string())),
+            Some(status) => Err(FuegoError::RpcError(status.to_string())),
+        }
+    }
+
+    fn transport_error(e: reqwest::Error) -> FuegoError {
+        FuegoError::TransportError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_config(url: String) -> FuegoRpcConfig {
+        FuegoRpcConfig {
+            url,
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(20),
+            ..Default::default()
+        }
+    }
+
+    fn rpc_result(result: Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({ "jsonrpc": "2.0", "id": 0, "result": result }))
+    }
+
+    #[tokio::test]
+    async fn test_typed_methods() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/json_rpc"))
+            .and(body_partial_json(json!({ "method": "getblockcount" })))
+            .respond_with(rpc_result(json!({ "count": 42, "status": "OK" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "getblocktemplate", "params": { "reserve_size": 60 } })))
+            .respond_with(rpc_result(json!({
+                "blocktemplate_blob": "0102",
+                "difficulty": 1000,
+                "height": 42,
+                "reserved_offset": 10,
+                "status": "OK",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "getblockheaderbyheight", "params": { "height": 41 } })))
+            .respond_with(rpc_result(json!({
+                "block_header": {
+                    "major_version": 7, "minor_version": 0, "timestamp": 1700000000,
+                    "prev_hash": "aa", "nonce": 5, "orphan_status": false, "height": 41,
+                    "depth": 1, "hash": "bb", "difficulty": 1000, "reward": 10,
+                },
+                "status": "OK",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/getinfo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "height": 42, "difficulty": 1000, "status": "OK", "synced": true,
+            })))
+            .mount(&server)
+            .await;
+
+        let client = FuegoRpcClient::new(test_config(server.uri())).unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+
+        let template = client.get_block_template(60, "fire").await.unwrap();
+        assert_eq!(template.height, 42);
+        assert_eq!(template.hashing_bytes().unwrap(), vec![1, 2]);
+
+        let header = client.get_block_header_by_height(41).await.unwrap();
+        assert_eq!(header.nonce, 5);
+
+        let info = client.get_info().await.unwrap();
+        assert!(info.synced);
+
+        let health = client.health().await;
+        assert!(health.is_healthy);
+        assert_eq!(health.total_requests, 4);
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_health_tracking() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(rpc_result(json!({ "count": 7, "status": "OK" })))
+            .mount(&server)
+            .await;
+
+        // Two 503s are retried before the call succeeds
+        let client = FuegoRpcClient::new(test_config(server.uri())).unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 7);
+        let health = client.health().await;
+        assert_eq!(health.total_failures, 2);
+        assert_eq!(health.consecutive_failures, 0);
+        assert!(health.is_healthy);
+
+        // RPC errors are not retried
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0", "id": 0, "error": { "code": -7, "message": "Block not accepted" },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = FuegoRpcClient::new(test_config(server.uri())).unwrap();
+        assert!(matches!(client.submit_block(&[1, 2]).await, Err(FuegoError::RpcError(_))));
+
+        // An unreachable daemon becomes unhealthy once retries are exhausted
+        let client = FuegoRpcClient::new(test_config("http://127.0.0.1:1".to_string())).unwrap();
+        assert!(matches!(client.get_block_count().await, Err(FuegoError::TransportError(_))));
+        let health = client.health().await;
+        assert_eq!(health.consecutive_failures, 3);
+        assert!(!health.is_healthy);
+    }
+
+    #[tokio::test]
+    async fn test_only_remote_daemons_are_proxied() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(rpc_result(json!({ "count": 7, "status": "OK" })))
+            .mount(&server)
+            .await;
+        let proxied = |url: String| FuegoRpcConfig {
+            proxy: Some("socks5://127.0.0.1:1".to_string()),
+            max_retries: 0,
+            ..test_config(url)
+        };
+
+        // Nothing listens on the proxy port, so only a daemon on this host can be reached
+        let local = FuegoRpcClient::new(proxied(server.uri())).unwrap();
+        assert_eq!(local.get_block_count().await.unwrap(), 7);
+        let remote = FuegoRpcClient::new(proxied("http://fuego.example:18180".to_string())).unwrap();
+        assert!(matches!(remote.get_block_count().await, Err(FuegoError::TransportError(_))));
+    }
+
+    #[test]
+    fn test_set_nonce() {
+        // major 1, minor 0, timestamp 300 (2-byte varint), prev id, nonce, trailing data
+        let mut blob = vec![0x01, 0x00, 0xac, 0x02];
+        blob.extend_from_slice(&[0xaa; 32]);
+        blob.extend_from_slice(&[0u8; 4]);
+        blob.push(0xff);
+
+        assert_eq!(nonce_offset(&blob).unwrap(), 36);
+        set_nonce(&mut blob, 0x01020304).unwrap();
+        assert_eq!(&blob[36..40], &[4, 3, 2, 1]);
+        assert_eq!(blob[40], 0xff);
+
+        assert!(nonce_offset(&blob[..38]).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use crate::error::FuegoError;
+use crate::rpc_client::{FuegoRpcClient, FuegoRpcConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+/// Fuego daemon process supervisor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FuegoSupervisorConfig {
+    pub fuego_binary_path: PathBuf,
+    pub fuego_data_dir: PathBuf,
+    /// Extra command line arguments passed to fuegod
+    pub args: Vec<String>,
+    /// RPC endpoint used for health checks
+    pub rpc: FuegoRpcConfig,
+    pub health_check_interval: Duration,
+    /// Consecutive failed health checks after which a running daemon is considered hung and restarted
+    pub max_failed_health_checks: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many restarts; `None` restarts forever
+    pub max_restarts: Option<u32>,
+    /// Time allowed for a clean exit before the process is killed
+    pub shutdown_timeout: Duration,
+    /// Number of recent output lines kept
+    pub log_lines: usize,
+}
+
+impl Default for FuegoSupervisorConfig {
+    fn default() -> Self {
+        Self {
+            fuego_binary_path: PathBuf::from("fuegod"),
+            fuego_data_dir: PathBuf::from("./data/fuego"),
+            args: Vec::new(),
+            rpc: FuegoRpcConfig::default(),
+            health_check_interval: Duration::from_secs(15),
+            max_failed_health_checks: 8,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: None,
+            shutdown_timeout: Duration::from_secs(30),
+            log_lines: 200,
+        }
+    }
+}
+
+/// Lifecycle state of the supervised daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FuegoProcessState {
+    Stopped,
+    Starting,
+    Running,
+    Restarting,
+    Failed,
+}
+
+/// Supervised daemon status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuegoProcessStatus {
+    pub state: FuegoProcessState,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    pub rpc_healthy: bool,
+    pub last_exit: Option<String>,
+    pub uptime_seconds: u64,
+}
+
+impl Default for FuegoProcessStatus {
+    fn default() -> Self {
+        Self {
+            state: FuegoProcessState::Stopped,
+            pid: None,
+            restarts: 0,
+            rpc_healthy: false,
+            last_exit: None,
+            uptime_seconds: 0,
+        }
+    }
+}
+
+/// Spawns fuegod, restarts it when it crashes or hangs and stops it cleanly
+pub struct FuegoSupervisor {
+    config: FuegoSupervisorConfig,
+    client: Arc<FuegoRpcClient>,
+    status: Arc<RwLock<FuegoProcessStatus>>,
+    logs: Arc<RwLock<VecDeque<String>>>,
+    running: Arc<RwLock<bool>>,
+    shutdown: Arc<Notify>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl FuegoSupervisor {
+    /// Create a new supervisor
+    pub fn new(config: FuegoSupervisorConfig) -> Result<Self, FuegoError> {
+        // Health probes should fail fast rather than retry
+        let client = FuegoRpcClient::new(FuegoRpcConfig { max_retries: 0, ..config.rpc.clone() })?;
+        Ok(Self {
+            config,
+            client: Arc::new(client),
+            status: Arc::new(RwLock::new(FuegoProcessStatus::default())),
+            logs: Arc::new(RwLock::new(VecDeque::new())),
+            running: Arc::new(RwLock::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            task: None,
+        })
+    }
+
+    /// Launch the daemon and start supervising it
+    pub async fn start(&mut self) -> Result<(), FuegoError> {
+        if self.task.is_some() {
+            return Err(FuegoError::Unavailable("Supervisor already started".to_string()));
+        }
+        std::fs::create_dir_all(&self.config.fuego_data_dir)
+            .map_err(|e| FuegoError::Unavailable(format!("Cannot create data dir: {}", e)))?;
+        *self.running.write().await = true;
+
+        let config = self.config.clone();
+        let client = self.client.clone();
+        let status = self.status.clone();
+        let logs = self.logs.clone();
+        let running = self.running.clone();
+        let shutdown = self.shutdown.clone();
+
+        self.task = Some(tokio::spawn(async move {
+            Self::supervise(config, client, status, logs, running, shutdown).await;
+        }));
+        Ok(())
+    }
+
+    /// Shut the daemon down and wait for it to exit
+    pub async fn stop(&mut self) -> Result<(), FuegoError> {
+        *self.running.write().await = false;
+        self.shutdown.notify_one();
+        if let Some(task) = self.task.take() {
+            task.await.map_err(|e| FuegoError::Unavailable(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn supervise(
+        config: FuegoSupervisorConfig,
+        client: Arc<FuegoRpcClient>,
+        status: Arc<RwLock<FuegoProcessStatus>>,
+        logs: Arc<RwLock<VecDeque<String>>>,
+        running: Arc<RwLock<bool>>,
+        shutdown: Arc<Notify>,
+    ) {
+        let mut backoff = config.initial_backoff;
+
+        while *running.read().await {
+            status.write().await.state = FuegoProcessState::Starting;
+
+            let exit = match Self::spawn_daemon(&config, &logs) {
+                Ok(child) => {
+                    let pid = child.id();
+                    println!("Started fuegod (pid {:?})", pid);
+                    status.write().await.pid = pid;
+                    Self::monitor(&config, &client, &status, &shutdown, child, &mut backoff).await
+                }
+                Err(e) => Some(e.to_string()),
+            };
+
+            let Some(exit) = exit else {
+                break;
+            };
+            println!("fuegod exited: {}", exit);
+            {
+                let mut status = status.write().await;
+                status.pid = None;
+                status.rpc_healthy = false;
+                status.last_exit = Some(exit);
+                status.restarts += 1;
+                if config.max_restarts.is_some_and(|max| status.restarts > max) {
+                    println!("fuegod restart limit reached, giving up");
+                    status.state = FuegoProcessState::Failed;
+                    *running.write().await = false;
+                    return;
+                }
+                status.state = FuegoProcessState::Restarting;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.notified() => break,
+            }
+            backoff = (backoff * 2).min(config.max_backoff);
+        }
+
+        let mut status = status.write().await;
+        status.state = FuegoProcessState::Stopped;
+        status.pid = None;
+        status.rpc_healthy = false;
+    }
+
+    fn spawn_daemon(config: &FuegoSupervisorConfig, logs: &Arc<RwLock<VecDeque<String>>>) -> Result<Child, FuegoError> {
+        let mut child = Command::new(&config.fuego_binary_path)
+            .args(&config.args)
+            .arg("--data-dir")
+            .arg(&config.fuego_data_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| FuegoError::Unavailable(format!("Failed to spawn {:?}: {}", config.fuego_binary_path, e)))?;
+
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(Self::capture_output(stdout, logs.clone(), config.log_lines));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(Self::capture_output(stderr, logs.clone(), config.log_lines));
+        }
+        Ok(child)
+    }
+
+    /// Watch a running daemon. Returns the exit reason, or `None` after a requested shutdown.
+    async fn monitor(
+        config: &FuegoSupervisorConfig,
+        client: &FuegoRpcClient,
+        status: &RwLock<FuegoProcessStatus>,
+        shutdown: &Notify,
+        mut child: Child,
+        backoff: &mut Duration,
+    ) -> Option<String> {
+        let started = Instant::now();
+        let mut stdin = child.stdin.take();
+        let mut health_timer = tokio::time::interval(config.health_check_interval);
+        health_timer.tick().await;
+        let mut failed_checks = 0;
+
+        loop {
+            tokio::select! {
+                result = child.wait() => {
+                    return Some(match result {
+                        Ok(exit_status) => exit_status.to_string(),
+                        Err(e) => e.to_string(),
+                    });
+                }
+                _ = shutdown.notified() => {
+                    Self::shutdown_daemon(&mut child, stdin.take(), config.shutdown_timeout).await;
+                    return None;
+                }
+                _ = health_timer.tick() => {
+                    let healthy = client.get_info().await.is_ok();
+                    let mut status = status.write().await;
+                    status.rpc_healthy = healthy;
+                    status.uptime_seconds = started.elapsed().as_secs();
+                    if healthy {
+                        failed_checks = 0;
+                        *backoff = config.initial_backoff;
+                        status.state = FuegoProcessState::Running;
+                    } else {
+                        failed_checks += 1;
+                        if failed_checks >= config.max_failed_health_checks {
+                            println!("fuegod RPC unresponsive after {} checks, restarting", failed_checks);
+                            let _ = child.start_kill();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ask fuegod to exit through its console, killing it if it does not exit in time
+    async fn shutdown_daemon(child: &mut Child, stdin: Option<ChildStdin>, timeout: Duration) {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(b"exit\n").await;
+            let _ = stdin.flush().await;
+        }
+        match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(_) => println!("fuegod stopped"),
+            Err(_) => {
+                println!("fuegod did not exit within {:?}, killing it", timeout);
+                let _ = child.kill().await;
+            }
+        }
+    }
+
+    async fn capture_output<R: AsyncRead + Unpin>(output: R, logs: Arc<RwLock<VecDeque<String>>>, max_lines: usize) {
+        let mut lines = BufReader::new(output).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let mut logs = logs.write().await;
+            if logs.len() >= max_lines {
+                logs.pop_front();
+            }
+            logs.push_back(line);
+        }
+    }
+
+    /// Get the daemon status
+    pub async fn get_status(&self) -> FuegoProcessStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Get the most recent daemon output lines
+    pub async fn recent_logs(&self) -> Vec<String> {
+        self.logs.read().await.iter().cloned().collect()
+    }
+
+    /// Check if the supervisor is running
+    pub async fn is_running(&self) -> bool {
+        *self.running.read().await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell_config(script: &str, data_dir: &std::path::Path) -> FuegoSupervisorConfig {
+        FuegoSupervisorConfig {
+            fuego_binary_path: PathBuf::from("sh"),
+            fuego_data_dir: data_dir.to_path_buf(),
+            args: vec!["-c".to_string(), script.to_string()],
+            rpc: FuegoRpcConfig { url: "http://127.0.0.1:1".to_string(), ..Default::default() },
+            health_check_interval: Duration::from_secs(60),
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+            shutdown_timeout: Duration::from_secs(5),
+            ..Default::default()
+        }
+    }
+
+    async fn wait_for<F: std::future::Future<Output = bool>>(mut condition: impl FnMut() -> F) {
+        for _ in 0..200 {
+            if condition().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn test_restarts_crashed_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FuegoSupervisorConfig {
+            max_restarts: Some(2),
+            ..shell_config("echo booting; exit 3", dir.path())
+        };
+        let mut supervisor = FuegoSupervisor::new(config).unwrap();
+        supervisor.start().await.unwrap();
+
+        wait_for(|| async { supervisor.get_status().await.state == FuegoProcessState::Failed }).await;
+        let status = supervisor.get_status().await;
+        assert_eq!(status.restarts, 3);
+        assert!(status.last_exit.unwrap().contains('3'));
+        assert!(!supervisor.is_running().await);
+        assert!(supervisor.recent_logs().await.iter().all(|line| line == "booting"));
+
+        supervisor.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_clean_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("stopped");
+        let script = format!("echo ready; read cmd; echo \"$cmd\" > {}", marker.display());
+        let mut supervisor = FuegoSupervisor::new(shell_config(&script, dir.path())).unwrap();
+        supervisor.start().await.unwrap();
+
+        wait_for(|| async { supervisor.recent_logs().await.contains(&"ready".to_string()) }).await;
+        assert!(supervisor.get_status().await.pid.is_some());
+
+        supervisor.stop().await.unwrap();
+        let status = supervisor.get_status().await;
+        assert_eq!(status.state, FuegoProcessState::Stopped);
+        assert_eq!(status.restarts, 0);
+        assert_eq!(std::fs::read_to_string(marker).unwrap().trim(), "exit");
+    }
+}
@@ -0,0 +1,164 @@
+//! Fuego blocks: the header, the coinbase and the hashes of the other transactions.
+
+use crate::error::ParseError;
+use crate::reader::Reader;
+use crate::transaction::Transaction;
+use crate::Hash;
+use pow::auxpow::tree_hash;
+use pow::ParentBlockHeader;
+
+/// Header fields serialized at the start of every block and hashing blob
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub timestamp: u64,
+    pub prev_id: Hash,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    fn read(reader: &mut Reader) -> Result<Self, ParseError> {
+        Ok(Self {
+            major_version: reader.varint_as("major version")?,
+            minor_version: reader.varint_as("minor version")?,
+            timestamp: reader.varint("timestamp")?,
+            prev_id: reader.hash("previous block id")?,
+            nonce: u32::from_le_bytes(reader.array("nonce")?),
+        })
+    }
+
+    /// Hashing view of this header over a transaction tree
+    pub fn with_transactions(&self, merkle_root: Hash, tx_count: u64) -> ParentBlockHeader {
+        ParentBlockHeader {
+            major_version: self.major_version,
+            minor_version: self.minor_version,
+            timestamp: self.timestamp,
+            prev_id: self.prev_id,
+            nonce: self.nonce,
+            merkle_root,
+            tx_count,
+        }
+    }
+}
+
+/// A whole block as fuegod serializes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub miner_tx: Transaction,
+    /// Ids of the other transactions, in block order
+    pub tx_hashes: Vec<Hash>,
+}
+
+impl Block {
+    /// Parse a serialized block, such as a `getblocktemplate` blob
+    pub fn parse(blob: &[u8]) -> Result<Self, ParseError> {
+        let mut reader = Reader::new(blob);
+        let header = BlockHeader::read(&mut reader)?;
+        let miner_tx = Transaction::read(&mut reader)?;
+        if !miner_tx.is_coinbase() {
+            return Err(ParseError::Invalid("coinbase: not a single coinbase input".to_string()));
+        }
+        let mut tx_hashes = Vec::new();
+        for _ in 0..reader.count(32, "transaction hashes")? {
+            tx_hashes.push(reader.hash("transaction hash")?);
+        }
+        reader.finish("block")?;
+        Ok(Self {
+            header,
+            miner_tx,
+            tx_hashes,
+        })
+    }
+
+    /// Ids of all the block's transactions, coinbase first
+    pub fn transaction_hashes(&self) -> Vec<Hash> {
+        std::iter::once(self.miner_tx.hash).chain(self.tx_hashes.iter().copied()).collect()
+    }
+
+    /// CryptoNote tree hash of the block's transactions
+    pub fn merkle_root(&self) -> Hash {
+        tree_hash(&self.transaction_hashes())
+    }
+
+    /// Header as hashed for the block id and proof of work
+    pub fn hashing_header(&self) -> ParentBlockHeader {
+        self.header.with_transactions(self.merkle_root(), 1 + self.tx_hashes.len() as u64)
+    }
+
+    /// Block id, as fuegod reports it
+    pub fn id(&self) -> Hash {
+        self.hashing_header().id()
+    }
+}
+
+/// Parse a block hashing blob: the header, the transaction tree root and the transaction count
+pub fn parse_hashing_blob(blob: &[u8]) -> Result<ParentBlockHeader, ParseError> {
+    let mut reader = Reader::new(blob);
+    let header = BlockHeader::read(&mut reader)?;
+    let merkle_root = reader.hash("transaction root")?;
+    let tx_count = reader.varint("transaction count")?;
+    reader.finish("hashing blob")?;
+    Ok(header.with_transactions(merkle_root, tx_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::tests::GENESIS_COINBASE_TX_HEX;
+    use pow::auxpow::MergeMiningTag;
+    use pow::cn_fast_hash;
+
+    /// Block whose coinbase is fuegod's genesis coinbase with a merge mining tag appended to its extra
+    fn block_blob(tx_hashes: &[Hash]) -> (Vec<u8>, Vec<u8>) {
+        let mut coinbase = hex::decode(GENESIS_COINBASE_TX_HEX).unwrap();
+        let tag = MergeMiningTag { depth: 0, nonce: 0, merkle_root: [0x77; 32] }.to_extra();
+        let extra_len = coinbase.len() - 34;
+        coinbase[extra_len] += tag.len() as u8;
+        coinbase.extend_from_slice(&tag);
+
+        let mut blob = vec![1, 0, 0x80, 0xe1, 0xeb, 0x17];
+        blob.extend_from_slice(&[0xaa; 32]);
+        blob.extend_from_slice(&70u32.to_le_bytes());
+        blob.extend_from_slice(&coinbase);
+        blob.push(tx_hashes.len() as u8);
+        tx_hashes.iter().for_each(|hash| blob.extend_from_slice(hash));
+        (blob, coinbase)
+    }
+
+    #[test]
+    fn test_parse_block_and_compute_its_id() {
+        let tx_hashes = [[1u8; 32], [2u8; 32]];
+        let (blob, coinbase) = block_blob(&tx_hashes);
+        let block = Block::parse(&blob).unwrap();
+        assert_eq!(
+            block.header,
+            BlockHeader { major_version: 1, minor_version: 0, timestamp: 50_000_000, prev_id: [0xaa; 32], nonce: 70 }
+        );
+        assert_eq!(block.miner_tx.hash, cn_fast_hash(&coinbase));
+        assert_eq!(block.miner_tx.prefix.merge_mining_tag().unwrap().merkle_root, [0x77; 32]);
+        assert_eq!(block.merkle_root(), tree_hash(&[cn_fast_hash(&coinbase), tx_hashes[0], tx_hashes[1]]));
+
+        // The hashing blob miners get parses back to the same header and id
+        let hashing = block.hashing_header();
+        assert_eq!(hashing.tx_count, 3);
+        assert_eq!(parse_hashing_blob(&hashing.hashing_blob()).unwrap(), hashing);
+        assert_eq!(block.id(), hashing.id());
+
+        assert!(Block::parse(&blob[..blob.len() - 1]).is_err());
+        assert!(matches!(parse_hashing_blob(&blob), Err(ParseError::TrailingBytes(_, _))));
+    }
+
+    #[test]
+    fn test_block_coinbase_must_be_a_coinbase() {
+        let (blob, _) = block_blob(&[]);
+        let mut spend = blob[..42].to_vec();
+        spend.extend_from_slice(&[1, 0, 1, 0x02, 1, 1, 0]);
+        spend.extend_from_slice(&[0x11; 32]);
+        spend.extend_from_slice(&[0, 0]);
+        spend.extend_from_slice(&[0x44; 64]);
+        spend.push(0);
+        assert!(matches!(Block::parse(&spend), Err(ParseError::Invalid(_))));
+    }
+}
@@ -0,0 +1,16 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Truncated {0}")]
+    Truncated(String),
+
+    #[error("Invalid {0}")]
+    Invalid(String),
+
+    #[error("Unsupported {0}")]
+    Unsupported(String),
+
+    #[error("{0} bytes left after the {1}")]
+    TrailingBytes(usize, String),
+}
@@ -0,0 +1,18 @@
+//! Binary parsing of Fuego (CryptoNote) blocks and transactions, as fuegod serializes them.
+//!
+//! Blocks parse into their header, coinbase and transaction ids, from which the
+//! transaction tree root, hashing blob and block id are computed the way fuegod does.
+//! Transactions parse into their prefix, including deposit terms, and their signatures.
+//! Parsing is strict: trailing bytes and padded varints are refused, so every parsed value
+//! has exactly one encoding and one hash.
+
+pub mod block;
+pub mod error;
+mod reader;
+pub mod transaction;
+
+pub use block::{parse_hashing_blob, Block, BlockHeader};
+pub use error::ParseError;
+pub use transaction::{parse_extra, ExtraField, Transaction, TransactionPrefix, TxInput, TxOutput, TxOutputTarget};
+
+pub type Hash = [u8; 32];
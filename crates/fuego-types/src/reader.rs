@@ -0,0 +1,85 @@
+use crate::error::ParseError;
+use crate::Hash;
+use pow::auxpow::read_varint;
+
+/// Reads CryptoNote binary serialization front to back
+pub(crate) struct Reader<'a> {
+    blob: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(blob: &'a [u8]) -> Self {
+        Self { blob, offset: 0 }
+    }
+
+    /// Bytes read so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Bytes read since offset `start`
+    pub fn since(&self, start: usize) -> &'a [u8] {
+        &self.blob[start..self.offset]
+    }
+
+    /// Varint, refusing the padded encodings fuegod would not write
+    pub fn varint(&mut self, field: &str) -> Result<u64, ParseError> {
+        let rest = &self.blob[self.offset..];
+        let (value, read) = read_varint(rest).ok_or_else(|| ParseError::Truncated(field.to_string()))?;
+        if read > 1 && rest[read - 1] == 0 {
+            return Err(ParseError::Invalid(format!("{}: padded varint", field)));
+        }
+        self.offset += read;
+        Ok(value)
+    }
+
+    /// Varint of a field fuegod holds in a narrower integer
+    pub fn varint_as<T: TryFrom<u64>>(&mut self, field: &str) -> Result<T, ParseError> {
+        let value = self.varint(field)?;
+        T::try_from(value).map_err(|_| ParseError::Invalid(format!("{}: {} out of range", field, value)))
+    }
+
+    pub fn bytes(&mut self, len: usize, field: &str) -> Result<&'a [u8], ParseError> {
+        let bytes = self
+            .blob
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or_else(|| ParseError::Truncated(field.to_string()))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub fn byte(&mut self, field: &str) -> Result<u8, ParseError> {
+        Ok(self.bytes(1, field)?[0])
+    }
+
+    pub fn array<const N: usize>(&mut self, field: &str) -> Result<[u8; N], ParseError> {
+        Ok(self.bytes(N, field)?.try_into().expect("length was checked"))
+    }
+
+    pub fn hash(&mut self, field: &str) -> Result<Hash, ParseError> {
+        self.array(field)
+    }
+
+    /// Number of items in a vector, each taking at least `min_size` bytes
+    pub fn count(&mut self, min_size: usize, field: &str) -> Result<usize, ParseError> {
+        let count = self.varint(field)?;
+        let remaining = (self.blob.len() - self.offset) as u64;
+        if count.saturating_mul(min_size as u64) > remaining {
+            return Err(ParseError::Truncated(format!("{} ({} items)", field, count)));
+        }
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offset == self.blob.len()
+    }
+
+    /// Fail unless `what` took up the whole blob
+    pub fn finish(&self, what: &str) -> Result<(), ParseError> {
+        match self.blob.len() - self.offset {
+            0 => Ok(()),
+            left => Err(ParseError::TrailingBytes(left, what.to_string())),
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Fuego transactions: the prefix, its signatures and the `tx_extra` fields.
+
+use crate::error::ParseError;
+use crate::reader::Reader;
+use crate::Hash;
+use pow::auxpow::MergeMiningTag;
+use pow::cn_fast_hash;
+
+const TXIN_GEN: u8 = 0xff;
+const TXIN_TO_KEY: u8 = 0x02;
+const TXIN_MULTISIG: u8 = 0x03;
+const TXOUT_TO_KEY: u8 = 0x02;
+const TXOUT_MULTISIG: u8 = 0x03;
+
+const TX_EXTRA_TAG_PADDING: u8 = 0x00;
+const TX_EXTRA_TAG_PUBKEY: u8 = 0x01;
+const TX_EXTRA_NONCE: u8 = 0x02;
+const TX_EXTRA_MERGE_MINING_TAG: u8 = 0x03;
+const TX_EXTRA_MESSAGE_TAG: u8 = 0x04;
+const TX_EXTRA_TTL: u8 = 0x05;
+const TX_EXTRA_PADDING_MAX_COUNT: usize = 255;
+
+/// One ring or multisignature signature
+pub type Signature = [u8; 64];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxInput {
+    /// Coinbase input minting the reward of block `height`
+    Gen { height: u32 },
+    /// Ring input spending one of the outputs at `output_indexes`, stored relative to each other
+    Key {
+        amount: u64,
+        output_indexes: Vec<u32>,
+        key_image: Hash,
+    },
+    /// Spend of a multisignature output, or of a deposit when `term` is set
+    Multisig {
+        amount: u64,
+        signature_count: u8,
+        output_index: u32,
+        term: u32,
+    },
+}
+
+impl TxInput {
+    /// Signatures the input is signed with
+    fn signature_count(&self) -> usize {
+        match self {
+            TxInput::Gen { .. } => 0,
+            TxInput::Key { output_indexes, .. } => output_indexes.len(),
+            TxInput::Multisig { signature_count, .. } => *signature_count as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxOutputTarget {
+    /// One-time output key
+    Key(Hash),
+    /// Multisignature output, locked as a deposit for `term` blocks when set
+    Multisig {
+        keys: Vec<Hash>,
+        required_signatures: u8,
+        term: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOutput {
+    pub amount: u64,
+    pub target: TxOutputTarget,
+}
+
+/// Everything a transaction's signatures sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionPrefix {
+    pub version: u8,
+    pub unlock_time: u64,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+    pub extra: Vec<u8>,
+}
+
+impl TransactionPrefix {
+    fn read(reader: &mut Reader) -> Result<Self, ParseError> {
+        let version = reader.varint_as("transaction version")?;
+        let unlock_time = reader.varint("unlock time")?;
+
+        let mut inputs = Vec::new();
+        for _ in 0..reader.count(2, "inputs")? {
+            let input = match reader.byte("input type")? {
+                TXIN_GEN => TxInput::Gen {
+                    height: reader.varint_as("coinbase height")?,
+                },
+                TXIN_TO_KEY => {
+                    let amount = reader.varint("input amount")?;
+                    let mut output_indexes = Vec::new();
+                    for _ in 0..reader.count(1, "output indexes")? {
+                        output_indexes.push(reader.varint_as("output index")?);
+                    }
+                    TxInput::Key {
+                        amount,
+                        output_indexes,
+                        key_image: reader.hash("key image")?,
+                    }
+                }
+                TXIN_MULTISIG => TxInput::Multisig {
+                    amount: reader.varint("input amount")?,
+                    signature_count: reader.varint_as("signature count")?,
+                    output_index: reader.varint_as("output index")?,
+                    term: reader.varint_as("input term")?,
+                },
+                other => return Err(ParseError::Unsupported(format!("input type {:#04x}", other))),
+            };
+            inputs.push(input);
+        }
+
+        let mut outputs = Vec::new();
+        for _ in 0..reader.count(2, "outputs")? {
+            let amount = reader.varint("output amount")?;
+            let target = match reader.byte("output type")? {
+                TXOUT_TO_KEY => TxOutputTarget::Key(reader.hash("output key")?),
+                TXOUT_MULTISIG => {
+                    let mut keys = Vec::new();
+                    for _ in 0..reader.count(32, "multisignature keys")? {
+                        keys.push(reader.hash("multisignature key")?);
+                    }
+                    TxOutputTarget::Multisig {
+                        keys,
+                        required_signatures: reader.varint_as("required signatures")?,
+                        term: reader.varint_as("output term")?,
+                    }
+                }
+                other => return Err(ParseError::Unsupported(format!("output type {:#04x}", other))),
+            };
+            outputs.push(TxOutput { amount, target });
+        }
+
+        let extra_len = reader.count(1, "extra")?;
+        let extra = reader.bytes(extra_len, "extra")?.to_vec();
+        Ok(Self {
+            version,
+            unlock_time,
+            inputs,
+            outputs,
+            extra,
+        })
+    }
+
+    /// The fields of `extra` up to the first one fuegod could not read either
+    pub fn extra_fields(&self) -> Vec<ExtraField> {
+        parse_extra(&self.extra)
+    }
+
+    /// Transaction public key outputs are derived from
+    pub fn public_key(&self) -> Option<Hash> {
+        self.extra_fields().into_iter().find_map(|field| match field {
+            ExtraField::PublicKey(key) => Some(key),
+            _ => None,
+        })
+    }
+
+    /// Merge mining commitment, when this is a parent coinbase
+    pub fn merge_mining_tag(&self) -> Option<MergeMiningTag> {
+        self.extra_fields().into_iter().find_map(|field| match field {
+            ExtraField::MergeMiningTag(tag) => Some(tag),
+            _ => None,
+        })
+    }
+}
+
+/// A whole transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub prefix: TransactionPrefix,
+    /// Signatures of each input, in input order
+    pub signatures: Vec<Vec<Signature>>,
+    /// Transaction id: the fast hash of the whole serialized transaction
+    pub hash: Hash,
+    /// Hash the signatures sign
+    pub prefix_hash: Hash,
+}
+
+impl Transaction {
+    /// Parse a serialized transaction, which must be the whole of `blob`
+    pub fn parse(blob: &[u8]) -> Result<Self, ParseError> {
+        let mut reader = Reader::new(blob);
+        let transaction = Self::read(&mut reader)?;
+        reader.finish("transaction")?;
+        Ok(transaction)
+    }
+
+    /// Read a transaction starting at the reader's position
+    pub(crate) fn read(reader: &mut Reader) -> Result<Self, ParseError> {
+        let start = reader.offset();
+        let prefix = TransactionPrefix::read(reader)?;
+        let prefix_hash = cn_fast_hash(reader.since(start));
+
+        let mut signatures = Vec::new();
+        for input in &prefix.inputs {
+            let mut input_signatures = Vec::new();
+            for _ in 0..input.signature_count() {
+                input_signatures.push(reader.array("signature")?);
+            }
+            signatures.push(input_signatures);
+        }
+
+        Ok(Self {
+            hash: cn_fast_hash(reader.since(start)),
+            prefix_hash,
+            prefix,
+            signatures,
+        })
+    }
+
+    /// Whether this is a block's coinbase transaction
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.prefix.inputs.as_slice(), [TxInput::Gen { .. }])
+    }
+}
+
+/// A field of `tx_extra`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtraField {
+    /// Zero bytes filling the rest of the field
+    Padding(usize),
+    PublicKey(Hash),
+    /// Free-form data, such as a payment id or a pool's extra nonce
+    Nonce(Vec<u8>),
+    MergeMiningTag(MergeMiningTag),
+    /// Encrypted message to a recipient
+    Message(Vec<u8>),
+    /// Time after which an unconfirmed transaction leaves the pool
+    Ttl(u64),
+}
+
+/// Parse `extra` the way fuegod does, keeping the fields before the first one it cannot
+/// read. Fields with other tags (such as bridge tags) end the parse as well.
+pub fn parse_extra(extra: &[u8]) -> Vec<ExtraField> {
+    let mut reader = Reader::new(extra);
+    let mut fields = Vec::new();
+    while !reader.is_empty() {
+        match read_extra_field(&mut reader) {
+            Ok(field) => fields.push(field),
+            Err(_) => break,
+        }
+    }
+    fields
+}
+
+fn read_extra_field(reader: &mut Reader) -> Result<ExtraField, ParseError> {
+    let field = match reader.byte("extra tag")? {
+        TX_EXTRA_TAG_PADDING => {
+            let mut size = 1;
+            while !reader.is_empty() {
+                if reader.byte("padding")? != 0 {
+                    return Err(ParseError::Invalid("extra padding".to_string()));
+                }
+                size += 1;
+            }
+            if size > TX_EXTRA_PADDING_MAX_COUNT {
+                return Err(ParseError::Invalid("extra padding".to_string()));
+            }
+            ExtraField::Padding(size)
+        }
+        TX_EXTRA_TAG_PUBKEY => ExtraField::PublicKey(reader.hash("extra public key")?),
+        TX_EXTRA_NONCE => {
+            let size = reader.byte("extra nonce size")? as usize;
+            ExtraField::Nonce(reader.bytes(size, "extra nonce")?.to_vec())
+        }
+        TX_EXTRA_MERGE_MINING_TAG => {
+            let size = reader.count(1, "merge mining tag")?;
+            let mut tag = Reader::new(reader.bytes(size, "merge mining tag")?);
+            let packed = tag.varint("merge mining depth")?;
+            let merkle_root = tag.hash("merge mining root")?;
+            tag.finish("merge mining tag")?;
+            let tag = MergeMiningTag::from_packed(packed, merkle_root)
+                .ok_or_else(|| ParseError::Invalid("merge mining nonce out of range".to_string()))?;
+            ExtraField::MergeMiningTag(tag)
+        }
+        TX_EXTRA_MESSAGE_TAG => {
+            let size = reader.count(1, "extra message")?;
+            ExtraField::Message(reader.bytes(size, "extra message")?.to_vec())
+        }
+        TX_EXTRA_TTL => {
+            let size = reader.count(1, "extra ttl")?;
+            let mut ttl = Reader::new(reader.bytes(size, "extra ttl")?);
+            ExtraField::Ttl(ttl.varint("extra ttl")?)
+        }
+        other => return Err(ParseError::Unsupported(format!("extra tag {:#04x}", other))),
+    };
+    Ok(field)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// `GENESIS_COINBASE_TX_HEX` from fuegod's CryptoNoteConfig.h
+    pub(crate) const GENESIS_COINBASE_TX_HEX: &str = "013c01ff0001b4bcc29101029b2e4c0281c0b02e7c53291a94d1d0\
+        cbff8883f8024f5142ee494ffbbd0880712101bd4e0bf284c04d004fd016a21405046e8267ef81328cabf3017c4c24b273b25a";
+
+    #[test]
+    fn test_parse_genesis_coinbase() {
+        let blob = hex::decode(GENESIS_COINBASE_TX_HEX).unwrap();
+        let tx = Transaction::parse(&blob).unwrap();
+        assert!(tx.is_coinbase());
+        assert_eq!((tx.prefix.version, tx.prefix.unlock_time), (1, 60));
+        assert_eq!(tx.prefix.inputs, vec![TxInput::Gen { height: 0 }]);
+        assert_eq!(tx.prefix.outputs.len(), 1);
+        assert_eq!(tx.prefix.outputs[0].amount, 305_176_116);
+        assert!(matches!(tx.prefix.outputs[0].target, TxOutputTarget::Key(key) if key[..2] == [0x9b, 0x2e]));
+        assert_eq!(tx.prefix.public_key().map(|key| key[..2].to_vec()), Some(vec![0xbd, 0x4e]));
+        assert_eq!(tx.signatures, vec![Vec::<Signature>::new()]);
+        assert_eq!(tx.hash, cn_fast_hash(&blob));
+
+        assert!(matches!(Transaction::parse(&blob[..blob.len() - 1]), Err(ParseError::Truncated(_))));
+        let mut longer = blob.clone();
+        longer.push(0);
+        assert!(matches!(Transaction::parse(&longer), Err(ParseError::TrailingBytes(1, _))));
+    }
+
+    #[test]
+    fn test_parse_deposit_spend_and_extra_fields() {
+        // One ring input and one deposit input, a key output and a deposit output
+        let mut blob = vec![1, 0, 2, TXIN_TO_KEY, 0xe8, 0x07, 2, 5, 3];
+        blob.extend_from_slice(&[0x11; 32]);
+        blob.extend_from_slice(&[TXIN_MULTISIG, 100, 1, 7, 0xa0, 0x1f]);
+        blob.extend_from_slice(&[2, 0x10, TXOUT_TO_KEY]);
+        blob.extend_from_slice(&[0x22; 32]);
+        blob.extend_from_slice(&[0x20, TXOUT_MULTISIG, 1]);
+        blob.extend_from_slice(&[0x33; 32]);
+        blob.extend_from_slice(&[1, 0xa0, 0x1f]);
+        let mut extra = MergeMiningTag { depth: 2, nonce: 0, merkle_root: [0x55; 32] }.to_extra();
+        extra.extend_from_slice(&[TX_EXTRA_NONCE, 2, 0xaa, 0xbb, TX_EXTRA_TTL, 1, 9, 0xcb, 1, 0xb0]);
+        blob.push(extra.len() as u8);
+        blob.extend_from_slice(&extra);
+        blob.extend_from_slice(&[0x44; 64 * 3]);
+
+        let tx = Transaction::parse(&blob).unwrap();
+        assert_eq!(
+            tx.prefix.inputs[1],
+            TxInput::Multisig { amount: 100, signature_count: 1, output_index: 7, term: 4000 }
+        );
+        assert_eq!(
+            tx.prefix.outputs[1].target,
+            TxOutputTarget::Multisig { keys: vec![[0x33; 32]], required_signatures: 1, term: 4000 }
+        );
+        assert_eq!(tx.signatures.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(tx.prefix_hash, cn_fast_hash(&blob[..blob.len() - 64 * 3]));
+
+        // The unknown bridge tag ends the fields fuegod understands
+        assert_eq!(
+            tx.prefix.extra_fields(),
+            vec![
+                ExtraField::MergeMiningTag(MergeMiningTag { depth: 2, nonce: 0, merkle_root: [0x55; 32] }),
+                ExtraField::Nonce(vec![0xaa, 0xbb]),
+                ExtraField::Ttl(9),
+            ]
+        );
+
+        // Padded varints give a second encoding of the same transaction, so they are refused
+        let mut padded = blob.clone();
+        padded.splice(1..2, [0x80, 0x00]);
+        assert!(matches!(Transaction::parse(&padded), Err(ParseError::Invalid(_))));
+    }
+}
@@ -0,0 +1,216 @@
+//! Domain-separated, versioned hashing for every protocol hash.
+//!
+//! Each hash starts from a tag naming what is hashed and the version of its encoding,
+//! e.g. `c0dl3/block/v1`, so a value hashed for one purpose can never pass for another.
+//! Fields are encoded at a fixed width: integers big-endian, fixed-size values as they
+//! are, and variable-length bytes behind their u64 length, so two different field lists
+//! never encode to the same bytes. Changing what a domain hashes means bumping its tag.
+//!
+//! Hashes whose format is defined elsewhere (Ethereum's Keccak, CryptoNote's proof of
+//! work and merge-mining trees) do not go through this module.
+
+use blake2::{Blake2b512, Digest};
+
+/// What a hash is computed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Domain {
+    /// Block id used for chain linkage, from the header's canonical bytes
+    Block,
+    /// Header hash committed into the parent chain's merge-mining tree
+    MergeMining,
+    /// Message a transaction's signatures sign
+    TxSigning,
+    /// Full encoding of a transaction a block's transaction tree commits to
+    TxContent,
+    /// Id of a transaction, from its full encoding with the id field left zero
+    TxId,
+    /// Leaf of a block's transaction Merkle tree
+    TxLeaf,
+    /// Interior node of a block's transaction Merkle tree
+    TxNode,
+    /// Rank of a transaction under deterministic block ordering
+    TxOrder,
+    /// Message a fee payer signs to sponsor a transaction
+    FeePayer,
+    /// Hash identifying a network's genesis
+    Genesis,
+    /// Genesis allocation committed to by the genesis header
+    GenesisAlloc,
+    /// State key placed in the state tree
+    StateKey,
+    /// State value placed in the state tree
+    StateValue,
+    /// Leaf of the state tree
+    StateLeaf,
+    /// Internal node of the state tree
+    StateNode,
+    /// Running root of a contract's storage writes
+    StorageRoot,
+    /// Contract code
+    ContractCode,
+    /// Running root of spent nullifiers
+    NullifierRoot,
+    /// Checksum of a state snapshot chunk
+    SnapshotChunk,
+    /// Address of a deployed contract
+    ContractAddress,
+    /// Log topic naming an event
+    EventTopic,
+    /// Log topic of an address too long to be padded into one
+    AddressTopic,
+    /// What a viewing key's audit report signature covers
+    AuditReport,
+    /// Transaction minting an L1 deposit
+    DepositMint,
+    /// Transaction minting a Fuego burn
+    BurnMint,
+    /// Asset id of a token bridged from L1
+    BridgedAsset,
+    /// What a challenger signs to open a fraud-proof challenge or make a move in it
+    ChallengeMove,
+    /// Identifier of a zero-knowledge proof
+    Proof,
+    /// Identifier of a verifying key
+    VerifyingKey,
+    /// Identifier of a relayed transaction payload
+    RelayedTransaction,
+    /// Point a one-time key's key image is taken against
+    RingKeyImage,
+    /// Message, ring and key image a ring signature commits to
+    RingPrefix,
+    /// Challenge linking one ring member to the next
+    RingChallenge,
+    /// Stealth address derivations
+    Stealth,
+    /// Mask of an outgoing audit tag
+    Disclosure,
+    /// Deposit address derived from an account
+    DepositAddress,
+    /// Checksum of a file in a state database backup
+    BackupFile,
+}
+
+impl Domain {
+    /// Tag the hash starts from
+    pub fn tag(self) -> &'static str {
+        match self {
+            Domain::Block => "c0dl3/block/v1",
+            Domain::MergeMining => "c0dl3/merge-mining/v1",
+            Domain::TxSigning => "c0dl3/tx/signing/v1",
+            Domain::TxContent => "c0dl3/tx/content/v1",
+            Domain::TxId => "c0dl3/tx/id/v1",
+            Domain::TxLeaf => "c0dl3/tx/leaf/v1",
+            Domain::TxNode => "c0dl3/tx/node/v1",
+            Domain::TxOrder => "c0dl3/tx/order/v1",
+            Domain::FeePayer => "c0dl3/tx/fee-payer/v1",
+            Domain::Genesis => "c0dl3/genesis/v1",
+            Domain::GenesisAlloc => "c0dl3/genesis/alloc/v1",
+            Domain::StateKey => "c0dl3/state/key/v1",
+            Domain::StateValue => "c0dl3/state/value/v1",
+            Domain::StateLeaf => "c0dl3/state/leaf/v1",
+            Domain::StateNode => "c0dl3/state/node/v1",
+            Domain::StorageRoot => "c0dl3/state/storage/v1",
+            Domain::ContractCode => "c0dl3/state/code/v1",
+            Domain::NullifierRoot => "c0dl3/state/nullifiers/v1",
+            Domain::SnapshotChunk => "c0dl3/snapshot/chunk/v1",
+            Domain::ContractAddress => "c0dl3/contract/address/v1",
+            Domain::EventTopic => "c0dl3/log/event/v1",
+            Domain::AddressTopic => "c0dl3/log/address/v1",
+            Domain::AuditReport => "c0dl3/audit/report/v1",
+            Domain::DepositMint => "c0dl3/bridge/deposit/v1",
+            Domain::BurnMint => "c0dl3/bridge/burn/v1",
+            Domain::BridgedAsset => "c0dl3/bridge/asset/v1",
+            Domain::ChallengeMove => "c0dl3/bridge/challenge/v1",
+            Domain::Proof => "c0dl3/zk/proof/v1",
+            Domain::VerifyingKey => "c0dl3/zk/verifying-key/v1",
+            Domain::RelayedTransaction => "c0dl3/p2p/transaction/v1",
+            Domain::RingKeyImage => "c0dl3/ring/key-image/v1",
+            Domain::RingPrefix => "c0dl3/ring/prefix/v1",
+            Domain::RingChallenge => "c0dl3/ring/challenge/v1",
+            Domain::Stealth => "c0dl3/stealth/v1",
+            Domain::Disclosure => "c0dl3/disclosure/outgoing/v1",
+            Domain::DepositAddress => "c0dl3/exchange/deposit-address/v1",
+            Domain::BackupFile => "c0dl3/backup/file/v1",
+        }
+    }
+}
+
+/// Hash of one domain's fields, built up field by field
+#[derive(Clone)]
+pub struct Hasher(Blake2b512);
+
+impl Hasher {
+    pub fn new(domain: Domain) -> Self {
+        Self(Blake2b512::new()).bytes(domain.tag().as_bytes())
+    }
+
+    /// Append an integer as 8 big-endian bytes
+    pub fn u64(mut self, value: u64) -> Self {
+        self.0.update(value.to_be_bytes());
+        self
+    }
+
+    /// Append a fixed-size value, such as a hash or a key, as it is
+    pub fn fixed<const N: usize>(mut self, value: &[u8; N]) -> Self {
+        self.0.update(value);
+        self
+    }
+
+    /// Append variable-length bytes behind their length
+    pub fn bytes(mut self, value: &[u8]) -> Self {
+        self.0.update((value.len() as u64).to_be_bytes());
+        self.0.update(value);
+        self
+    }
+
+    /// 32-byte hash
+    pub fn finish(self) -> [u8; 32] {
+        self.finish_wide()[..32].try_into().unwrap()
+    }
+
+    /// 64-byte hash, for reducing into a scalar or a group element without bias
+    pub fn finish_wide(self) -> [u8; 64] {
+        self.0.finalize().into()
+    }
+}
+
+/// Hash a single variable-length value under `domain`
+pub fn hash(domain: Domain, value: &[u8]) -> [u8; 32] {
+    Hasher::new(domain).bytes(value).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domains_and_field_boundaries_separate_hashes() {
+        assert_ne!(hash(Domain::StateKey, b"balance"), hash(Domain::StateValue, b"balance"));
+        assert_eq!(hash(Domain::StateKey, b"balance"), Hasher::new(Domain::StateKey).bytes(b"balance").finish());
+
+        // Moving bytes from one field into the next changes the hash
+        let split = |at: usize| {
+            let value = b"c0dl3/state";
+            Hasher::new(Domain::ContractCode).bytes(&value[..at]).bytes(&value[at..]).finish()
+        };
+        assert_ne!(split(5), split(6));
+        assert_ne!(Hasher::new(Domain::Block).u64(1).finish(), Hasher::new(Domain::Block).fixed(&[1u8; 8]).finish());
+    }
+
+    #[test]
+    fn test_every_tag_is_distinct_and_versioned() {
+        let domains = [
+            Domain::Block, Domain::MergeMining, Domain::TxSigning, Domain::TxContent, Domain::TxLeaf, Domain::TxNode,
+            Domain::TxOrder, Domain::FeePayer, Domain::Genesis, Domain::GenesisAlloc, Domain::StateKey, Domain::StateValue,
+            Domain::StateLeaf, Domain::StateNode, Domain::StorageRoot, Domain::ContractCode, Domain::NullifierRoot,
+            Domain::SnapshotChunk, Domain::ContractAddress, Domain::EventTopic, Domain::AddressTopic,
+            Domain::AuditReport, Domain::DepositMint, Domain::BurnMint, Domain::BridgedAsset, Domain::Proof,
+            Domain::VerifyingKey, Domain::RelayedTransaction, Domain::RingKeyImage, Domain::RingPrefix,
+            Domain::RingChallenge, Domain::Stealth, Domain::Disclosure, Domain::DepositAddress,
+            Domain::BackupFile, Domain::TxId, Domain::ChallengeMove,
+        ];
+        let tags: std::collections::HashSet<&str> = domains.iter().map(|domain| domain.tag()).collect();
+        assert_eq!(tags.len(), domains.len());
+        assert!(tags.iter().all(|tag| tag.starts_with("c0dl3/") && tag.ends_with("/v1")));
+    }
+}
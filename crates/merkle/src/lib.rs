@@ -0,0 +1,130 @@
+//! Binary Merkle trees over 32-byte leaves, with inclusion proofs.
+//!
+//! Leaves and interior nodes are hashed differently (see `MerkleHasher`), so an interior
+//! node can never be presented as a leaf and a proof cannot be cut short. A level with an
+//! odd number of nodes pairs its last node with `EMPTY_NODE` rather than with itself:
+//! `[a, b, c]` and `[a, b, c, c]` have different roots, and every proof in a tree of `n`
+//! leaves holds exactly `ceil(log2(n))` siblings.
+//!
+//! Fixed-format trees (CryptoNote's `tree_hash`, the Poseidon note tree and the sparse
+//! state tree) do not go through this module.
+
+/// Hash of a leaf or a node
+pub type Hash = [u8; 32];
+
+/// Root of an empty tree, and the sibling of the last node of an odd level
+pub const EMPTY_NODE: Hash = [0u8; 32];
+
+/// How one tree hashes its leaves and interior nodes; the two must never collide
+pub trait MerkleHasher {
+    /// Hash a leaf into the bottom level
+    fn leaf(leaf: &Hash) -> Hash;
+
+    /// Hash two children into their parent
+    fn node(left: &Hash, right: &Hash) -> Hash;
+}
+
+/// Parent level of `level`, pairing an odd last node with `EMPTY_NODE`
+fn parent_level<H: MerkleHasher>(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| H::node(&pair[0], pair.get(1).unwrap_or(&EMPTY_NODE)))
+        .collect()
+}
+
+/// Root of the tree over `leaves`; `EMPTY_NODE` when there are none
+pub fn root<H: MerkleHasher>(leaves: &[Hash]) -> Hash {
+    let mut level: Vec<Hash> = leaves.iter().map(H::leaf).collect();
+    if level.is_empty() {
+        return EMPTY_NODE;
+    }
+    while level.len() > 1 {
+        level = parent_level::<H>(&level);
+    }
+    level[0]
+}
+
+/// Sibling hashes from leaf `index` up to the root, or `None` if there is no such leaf
+pub fn proof<H: MerkleHasher>(leaves: &[Hash], mut index: usize) -> Option<Vec<Hash>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut siblings = Vec::new();
+    let mut level: Vec<Hash> = leaves.iter().map(H::leaf).collect();
+    while level.len() > 1 {
+        siblings.push(level.get(index ^ 1).copied().unwrap_or(EMPTY_NODE));
+        level = parent_level::<H>(&level);
+        index /= 2;
+    }
+    Some(siblings)
+}
+
+/// Check that `leaf` sits at `index` of the tree with `root`
+pub fn verify<H: MerkleHasher>(leaf: &Hash, mut index: u64, siblings: &[Hash], root: &Hash) -> bool {
+    // Every index past the tree's width would otherwise verify with the same path
+    if siblings.len() < 64 && index >> siblings.len() != 0 {
+        return false;
+    }
+    let mut node = H::leaf(leaf);
+    for sibling in siblings {
+        node = if index.is_multiple_of(2) { H::node(&node, sibling) } else { H::node(sibling, &node) };
+        index /= 2;
+    }
+    node == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashing::{Domain, Hasher};
+
+    struct TestHasher;
+
+    impl MerkleHasher for TestHasher {
+        fn leaf(leaf: &Hash) -> Hash {
+            Hasher::new(Domain::TxLeaf).fixed(leaf).finish()
+        }
+
+        fn node(left: &Hash, right: &Hash) -> Hash {
+            Hasher::new(Domain::TxNode).fixed(left).fixed(right).finish()
+        }
+    }
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| [i + 1; 32]).collect()
+    }
+
+    #[test]
+    fn test_proofs_verify_for_every_leaf() {
+        for count in 1..=9u8 {
+            let leaves = leaves(count);
+            let root = root::<TestHasher>(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let siblings = proof::<TestHasher>(&leaves, index).unwrap();
+                assert_eq!(siblings.len(), (count as usize).next_power_of_two().trailing_zeros() as usize);
+                assert!(verify::<TestHasher>(leaf, index as u64, &siblings, &root));
+                assert!(!verify::<TestHasher>(&[0xee; 32], index as u64, &siblings, &root));
+                assert!(!verify::<TestHasher>(leaf, index as u64 + (1 << siblings.len()), &siblings, &root));
+            }
+            assert!(proof::<TestHasher>(&leaves, count as usize).is_none());
+        }
+        assert_eq!(root::<TestHasher>(&[]), EMPTY_NODE);
+    }
+
+    #[test]
+    fn test_second_preimages_are_rejected() {
+        let leaves = leaves(4);
+        let full_root = root::<TestHasher>(&leaves);
+
+        // An interior node does not verify as a leaf one level up
+        let left = TestHasher::node(&TestHasher::leaf(&leaves[0]), &TestHasher::leaf(&leaves[1]));
+        let right = TestHasher::node(&TestHasher::leaf(&leaves[2]), &TestHasher::leaf(&leaves[3]));
+        assert_eq!(TestHasher::node(&left, &right), full_root);
+        assert!(!verify::<TestHasher>(&left, 0, &[right], &full_root));
+        assert_ne!(root::<TestHasher>(&[left, right]), full_root);
+
+        // Repeating the last leaf of an odd level changes the root
+        let repeated = [leaves[0], leaves[1], leaves[2], leaves[2]];
+        assert_ne!(root::<TestHasher>(&leaves[..3]), root::<TestHasher>(&repeated));
+    }
+}
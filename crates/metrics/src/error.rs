@@ -0,0 +1,10 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MetricsError {
+    #[error("Metrics server error: {0}")]
+    ServerError(String),
+
+    #[error("Encoding error: {0}")]
+    EncodingError(String),
+}
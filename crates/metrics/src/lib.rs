@@ -0,0 +1,368 @@
+//! Prometheus metrics shared by the node's subsystems.
+//!
+//! Subsystems record into one `Metrics` registry, either directly as events happen
+//! or through the node's sampler, and `MetricsServer` exposes it at `/metrics` in
+//! the OpenMetrics text format.
+
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub mod error;
+
+pub use error::MetricsError;
+
+/// Largest request head the server reads before answering
+const MAX_REQUEST_BYTES: usize = 4096;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct QueueLabels {
+    queue: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ProofLabels {
+    priority: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct TaskLabels {
+    task: String,
+}
+
+/// Metrics registry for one node
+pub struct Metrics {
+    registry: Registry,
+    txpool_transactions: Gauge,
+    txpool_evictions: Counter,
+    block_height: Gauge,
+    connected_peers: Gauge,
+    proof_generation_seconds: Family<ProofLabels, Histogram>,
+    prover_queued_jobs: Gauge,
+    mining_hashrate: Gauge,
+    state_db_estimated_keys: Gauge,
+    state_db_sst_bytes: Gauge,
+    state_db_memtable_bytes: Gauge,
+    state_db_cache_hits: Counter,
+    state_db_cache_misses: Counter,
+    state_db_cache_hit_ratio: Gauge<f64, AtomicU64>,
+    bridge_queue_depth: Family<QueueLabels, Gauge>,
+    bridge_l1_reorgs: Counter,
+    bridge_mints_rolled_back: Counter,
+    watchtower_batches_checked: Counter,
+    watchtower_batch_mismatches: Counter,
+    task_up: Family<TaskLabels, Gauge>,
+    task_restarts: Family<TaskLabels, Counter>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("codl3");
+        let txpool_transactions = Gauge::default();
+        registry.register("txpool_transactions", "Transactions waiting in the pool", txpool_transactions.clone());
+        let txpool_evictions = Counter::default();
+        registry.register(
+            "txpool_evictions",
+            "Transactions evicted to make room for higher priority ones",
+            txpool_evictions.clone(),
+        );
+        let block_height = Gauge::default();
+        registry.register("block_height", "Latest committed block", block_height.clone());
+        let connected_peers = Gauge::default();
+        registry.register("connected_peers", "Peers currently connected", connected_peers.clone());
+        let proof_generation_seconds: Family<ProofLabels, Histogram> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.01, 2.0, 14)));
+        registry.register(
+            "proof_generation_seconds",
+            "Time spent generating each proof",
+            proof_generation_seconds.clone(),
+        );
+        let prover_queued_jobs = Gauge::default();
+        registry.register("prover_queued_jobs", "Proving jobs waiting for a worker", prover_queued_jobs.clone());
+        let mining_hashrate = Gauge::default();
+        registry.register("mining_hashrate", "Hashes per second of the local miner", mining_hashrate.clone());
+        let state_db_estimated_keys = Gauge::default();
+        registry.register("state_db_estimated_keys", "Estimated keys in RocksDB", state_db_estimated_keys.clone());
+        let state_db_sst_bytes = Gauge::default();
+        registry.register("state_db_sst_bytes", "Size of the live RocksDB SST files", state_db_sst_bytes.clone());
+        let state_db_memtable_bytes = Gauge::default();
+        registry.register("state_db_memtable_bytes", "Size of the RocksDB memtables", state_db_memtable_bytes.clone());
+        let state_db_cache_hits = Counter::default();
+        registry.register("state_db_cache_hits", "Account reads answered from memory", state_db_cache_hits.clone());
+        let state_db_cache_misses = Counter::default();
+        registry.register("state_db_cache_misses", "Account reads that went to RocksDB", state_db_cache_misses.clone());
+        let state_db_cache_hit_ratio = Gauge::<f64, AtomicU64>::default();
+        registry.register(
+            "state_db_cache_hit_ratio",
+            "Share of account reads answered from memory",
+            state_db_cache_hit_ratio.clone(),
+        );
+        let bridge_queue_depth = Family::<QueueLabels, Gauge>::default();
+        registry.register("bridge_queue_depth", "Items waiting in each bridge queue", bridge_queue_depth.clone());
+        let bridge_l1_reorgs = Counter::default();
+        registry.register(
+            "bridge_l1_reorgs",
+            "L1 reorgs that orphaned blocks already scanned for deposits",
+            bridge_l1_reorgs.clone(),
+        );
+        let bridge_mints_rolled_back = Counter::default();
+        registry.register(
+            "bridge_mints_rolled_back",
+            "Unexecuted deposit mints dropped after an L1 reorg",
+            bridge_mints_rolled_back.clone(),
+        );
+        let watchtower_batches_checked = Counter::default();
+        registry.register(
+            "watchtower_batches_checked",
+            "Posted batches the watchtower compared with its own state",
+            watchtower_batches_checked.clone(),
+        );
+        let watchtower_batch_mismatches = Counter::default();
+        registry.register(
+            "watchtower_batch_mismatches",
+            "Posted batches whose state root differed from the watchtower's",
+            watchtower_batch_mismatches.clone(),
+        );
+        let task_up = Family::<TaskLabels, Gauge>::default();
+        registry.register("task_up", "Whether each supervised task is running", task_up.clone());
+        let task_restarts = Family::<TaskLabels, Counter>::default();
+        registry.register("task_restarts", "Restarts of each supervised task", task_restarts.clone());
+
+        Self {
+            registry,
+            txpool_transactions,
+            txpool_evictions,
+            block_height,
+            connected_peers,
+            proof_generation_seconds,
+            prover_queued_jobs,
+            mining_hashrate,
+            state_db_estimated_keys,
+            state_db_sst_bytes,
+            state_db_memtable_bytes,
+            state_db_cache_hits,
+            state_db_cache_misses,
+            state_db_cache_hit_ratio,
+            bridge_queue_depth,
+            bridge_l1_reorgs,
+            bridge_mints_rolled_back,
+            watchtower_batches_checked,
+            watchtower_batch_mismatches,
+            task_up,
+            task_restarts,
+        }
+    }
+
+    /// Record the pool size and its running eviction total
+    pub fn set_txpool(&self, transactions: usize, evictions: u64) {
+        self.txpool_transactions.set(transactions as i64);
+        self.txpool_evictions.inc_by(evictions.saturating_sub(self.txpool_evictions.get()));
+    }
+
+    pub fn set_block_height(&self, height: u64) {
+        self.block_height.set(height as i64);
+    }
+
+    pub fn set_connected_peers(&self, peers: usize) {
+        self.connected_peers.set(peers as i64);
+    }
+
+    /// Record how long a proof of `priority` took to generate
+    pub fn observe_proof_time(&self, priority: &str, elapsed: Duration) {
+        self.proof_generation_seconds
+            .get_or_create(&ProofLabels { priority: priority.to_string() })
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn set_prover_queue(&self, queued: usize) {
+        self.prover_queued_jobs.set(queued as i64);
+    }
+
+    pub fn set_mining_hashrate(&self, hashrate: u64) {
+        self.mining_hashrate.set(hashrate as i64);
+    }
+
+    pub fn set_state_db(&self, estimated_keys: u64, sst_bytes: u64, memtable_bytes: u64) {
+        self.state_db_estimated_keys.set(estimated_keys as i64);
+        self.state_db_sst_bytes.set(sst_bytes as i64);
+        self.state_db_memtable_bytes.set(memtable_bytes as i64);
+    }
+
+    /// Record the account cache's running hit and miss totals
+    pub fn set_state_db_cache(&self, hits: u64, misses: u64) {
+        self.state_db_cache_hits.inc_by(hits.saturating_sub(self.state_db_cache_hits.get()));
+        self.state_db_cache_misses.inc_by(misses.saturating_sub(self.state_db_cache_misses.get()));
+        let reads = hits + misses;
+        self.state_db_cache_hit_ratio.set(if reads == 0 { 0.0 } else { hits as f64 / reads as f64 });
+    }
+
+    pub fn set_bridge_queue(&self, queue: &str, depth: usize) {
+        self.bridge_queue_depth.get_or_create(&QueueLabels { queue: queue.to_string() }).set(depth as i64);
+    }
+
+    /// Record the bridge's running totals of L1 reorgs and rolled back mints
+    pub fn set_bridge_reorgs(&self, reorgs: u64, mints_rolled_back: u64) {
+        self.bridge_l1_reorgs.inc_by(reorgs.saturating_sub(self.bridge_l1_reorgs.get()));
+        self.bridge_mints_rolled_back
+            .inc_by(mints_rolled_back.saturating_sub(self.bridge_mints_rolled_back.get()));
+    }
+
+    /// Record the watchtower's running totals of checked and mismatching batches
+    pub fn set_watchtower(&self, checked: u64, mismatches: u64) {
+        self.watchtower_batches_checked
+            .inc_by(checked.saturating_sub(self.watchtower_batches_checked.get()));
+        self.watchtower_batch_mismatches
+            .inc_by(mismatches.saturating_sub(self.watchtower_batch_mismatches.get()));
+    }
+
+    /// Record whether a supervised task is running and its running restart total
+    pub fn set_task(&self, task: &str, running: bool, restarts: u64) {
+        let labels = TaskLabels { task: task.to_string() };
+        self.task_up.get_or_create(&labels).set(running as i64);
+        let counter = self.task_restarts.get_or_create(&labels);
+        counter.inc_by(restarts.saturating_sub(counter.get()));
+    }
+
+    /// Render every metric in the OpenMetrics text format
+    pub fn encode(&self) -> Result<String, MetricsError> {
+        let mut output = String::new();
+        encode(&mut output, &self.registry).map_err(|e| MetricsError::EncodingError(e.to_string()))?;
+        Ok(output)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serves `GET /metrics` over plain HTTP
+pub struct MetricsServer {
+    listener: TcpListener,
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsServer {
+    pub async fn bind(addr: &str, metrics: Arc<Metrics>) -> Result<Self, MetricsError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| MetricsError::ServerError(format!("Failed to bind {}: {}", addr, e)))?;
+        Ok(Self { listener, metrics })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, MetricsError> {
+        self.listener.local_addr().map_err(|e| MetricsError::ServerError(e.to_string()))
+    }
+
+    /// Answer scrapes until the task is dropped
+    pub async fn run(self) {
+        loop {
+            let stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Metrics server accept failed: {}", e);
+                    continue;
+                }
+            };
+            let metrics = self.metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, &metrics).await {
+                    eprintln!("Metrics request failed: {}", e);
+                }
+            });
+        }
+    }
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> Result<(), MetricsError> {
+    let io_error = |e: std::io::Error| MetricsError::ServerError(e.to_string());
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let read = stream.read(&mut buffer).await.map_err(io_error)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request_line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = metrics.encode()?;
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                CONTENT_TYPE,
+                body.len(),
+                body
+            )
+        }
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await.map_err(io_error)?;
+    stream.shutdown().await.map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_serves_recorded_values() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.set_txpool(12, 3);
+        metrics.set_txpool(10, 5);
+        metrics.set_block_height(42);
+        metrics.observe_proof_time("block", Duration::from_millis(300));
+        metrics.set_prover_queue(2);
+        metrics.set_bridge_queue("withdrawals", 7);
+        metrics.set_bridge_reorgs(2, 5);
+        metrics.set_watchtower(4, 1);
+        metrics.set_task("network", false, 3);
+        metrics.set_state_db_cache(3, 1);
+
+        let server = MetricsServer::bind("127.0.0.1:0", metrics).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let response = get(addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("codl3_txpool_transactions 10"));
+        assert!(response.contains("codl3_txpool_evictions_total 5"));
+        assert!(response.contains("codl3_block_height 42"));
+        assert!(response.contains("codl3_proof_generation_seconds_count{priority=\"block\"} 1"));
+        assert!(response.contains("codl3_prover_queued_jobs 2"));
+        assert!(response.contains("codl3_bridge_queue_depth{queue=\"withdrawals\"} 7"));
+        assert!(response.contains("codl3_bridge_l1_reorgs_total 2"));
+        assert!(response.contains("codl3_watchtower_batch_mismatches_total 1"));
+        assert!(response.contains("codl3_task_up{task=\"network\"} 0"));
+        assert!(response.contains("codl3_task_restarts_total{task=\"network\"} 3"));
+        assert!(response.contains("codl3_state_db_cache_hits_total 3"));
+        assert!(response.contains("codl3_state_db_cache_hit_ratio 0.75"));
+        assert!(response.trim_end().ends_with("# EOF"));
+
+        assert!(get(addr, "/other").await.starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Pluggable hash backends for the miner.
+//!
+//! A [`HashBackend`] creates one [`HashWorker`] per mining thread. Workers hash whole
+//! batches of nonces at once, which is the natural interface for GPU or FPGA hashers;
+//! the miner re-checks every reported solution with the CPU reference implementation.
+//! Backends are registered by name in a [`BackendRegistry`] and selected through
+//! `CODL3MiningConfig::backend`.
+
+use crate::error::MiningError;
+use pow::{check_hash, CryptoNight};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Name of the built-in CPU backend
+pub const CPU_BACKEND: &str = "cpu";
+
+/// A source of CN-UPX/2 hashing power
+pub trait HashBackend: Send + Sync {
+    /// Backend name as used in configuration
+    fn name(&self) -> &str;
+
+    /// Create the hashing state for mining thread `thread`
+    fn create_worker(&self, thread: usize) -> Result<Box<dyn HashWorker>, MiningError>;
+}
+
+/// Per-thread hashing state of a backend
+pub trait HashWorker: Send {
+    /// Hash `count` nonces from `start`, writing each little-endian nonce at `nonce_offset`
+    /// of `blob`. Nonces whose hash meets `difficulty` are pushed to `solutions`.
+    /// Returns the number of hashes computed.
+    fn hash_batch(
+        &mut self,
+        blob: &mut [u8],
+        nonce_offset: usize,
+        start: u32,
+        count: u32,
+        difficulty: u64,
+        solutions: &mut Vec<(u32, [u8; 32])>,
+    ) -> Result<u64, MiningError>;
+}
+
+/// Reference CPU backend
+pub struct CpuBackend;
+
+impl HashBackend for CpuBackend {
+    fn name(&self) -> &str {
+        CPU_BACKEND
+    }
+
+    fn create_worker(&self, _thread: usize) -> Result<Box<dyn HashWorker>, MiningError> {
+        let hasher = CryptoNight::upx2();
+        Ok(Box::new(CpuWorker {
+            scratchpad: vec![0u8; hasher.params().memory],
+            hasher,
+        }))
+    }
+}
+
+/// CPU worker reusing one scratchpad for every hash
+struct CpuWorker {
+    hasher: CryptoNight,
+    scratchpad: Vec<u8>,
+}
+
+impl HashWorker for CpuWorker {
+    fn hash_batch(
+        &mut self,
+        blob: &mut [u8],
+        nonce_offset: usize,
+        start: u32,
+        count: u32,
+        difficulty: u64,
+        solutions: &mut Vec<(u32, [u8; 32])>,
+    ) -> Result<u64, MiningError> {
+        if blob.len() < nonce_offset + 4 {
+            return Err(MiningError::InvalidWork("Nonce offset outside blob".to_string()));
+        }
+
+        let end = start.saturating_add(count);
+        for nonce in start..end {
+            blob[nonce_offset..nonce_offset + 4].copy_from_slice(&nonce.to_le_bytes());
+            let hash = self.hasher.digest_with_scratchpad(blob, &mut self.scratchpad);
+            if check_hash(&hash, difficulty) {
+                solutions.push((nonce, hash));
+            }
+        }
+        Ok((end - start) as u64)
+    }
+}
+
+/// Backend options from configuration, interpreted by each backend
+pub type BackendOptions = HashMap<String, String>;
+
+type BackendFactory = Arc<dyn Fn(&BackendOptions) -> Result<Arc<dyn HashBackend>, MiningError> + Send + Sync>;
+
+/// Named hash backend factories
+#[derive(Clone)]
+pub struct BackendRegistry {
+    factories: HashMap<String, BackendFactory>,
+}
+
+impl BackendRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Register a backend factory under `name`, replacing any previous one
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&BackendOptions) -> Result<Arc<dyn HashBackend>, MiningError> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Arc::new(factory));
+    }
+
+    /// Instantiate the backend registered as `name`
+    pub fn create(&self, name: &str, options: &BackendOptions) -> Result<Arc<dyn HashBackend>, MiningError> {
+        let factory = self.factories.get(name).ok_or_else(|| {
+            MiningError::InvalidWork(format!("Unknown hash backend '{}', available: {:?}", name, self.names()))
+        })?;
+        factory(options)
+    }
+
+    /// Names of all registered backends, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.factories.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+impl Default for BackendRegistry {
+    /// Registry with the built-in CPU backend
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(CPU_BACKEND, |_| Ok(Arc::new(CpuBackend) as Arc<dyn HashBackend>));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_backend_matches_reference() {
+        let backend = BackendRegistry::default().create(CPU_BACKEND, &BackendOptions::new()).unwrap();
+        assert_eq!(backend.name(), "cpu");
+        let mut worker = backend.create_worker(0).unwrap();
+
+        let mut blob = vec![0u8; 76];
+        let mut solutions = Vec::new();
+        let hashed = worker.hash_batch(&mut blob, 39, 7, 2, 1, &mut solutions).unwrap();
+        assert_eq!(hashed, 2);
+        assert_eq!(solutions.len(), 2);
+
+        let mut expected = vec![0u8; 76];
+        expected[39..43].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(solutions[1], (8, CryptoNight::upx2().digest(&expected)));
+
+        assert!(worker.hash_batch(&mut blob, 80, 0, 1, 1, &mut solutions).is_err());
+    }
+
+    #[test]
+    fn test_unknown_backend() {
+        let registry = BackendRegistry::default();
+        let err = registry.create("opencl", &BackendOptions::new()).err().unwrap();
+        assert!(err.to_string().contains("opencl"));
+        assert_eq!(registry.names(), vec!["cpu".to_string()]);
+    }
+}
@@ -0,0 +1,447 @@
+use crate::error::MiningError;
+use crate::job_manager::{JobManager, ParentWork};
+use crate::work::{FoundBlock, EXTRA_NONCE_SIZE};
+use block_sync::Block;
+use fuego_integration::{BlockTemplate, FuegoRpcClient, FuegoRpcConfig};
+use pow::auxpow::{write_varint, Hash, MergeMiningTag};
+use pow::{MergeMinedProof, ParentBlockHeader};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::time::Duration;
+
+/// Merge mining coordinator configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MergeMiningConfig {
+    pub rpc: FuegoRpcConfig,
+    /// Fuego address the parent coinbase pays to
+    pub wallet_address: String,
+    /// How often fuegod is asked for a new template
+    pub poll_interval: Duration,
+}
+
+impl Default for MergeMiningConfig {
+    fn default() -> Self {
+        Self {
+            rpc: FuegoRpcConfig::default(),
+            wallet_address: String::new(),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// What became of the blocks one chain was offered by merged shares
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChainShareStats {
+    /// Shares whose hash met this chain's difficulty
+    pub found: u64,
+    /// Blocks accepted by fuegod or sealed for C0DL3
+    pub submitted: u64,
+    /// Blocks lost to rejection, an expired template or a closed channel
+    pub failed: u64,
+}
+
+/// Merge mining statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeMiningStats {
+    pub templates_fetched: u64,
+    pub parent_updates: u64,
+    pub fuego: ChainShareStats,
+    pub c0dl3: ChainShareStats,
+    /// Found blocks dropped because the coordinator fell behind the miners
+    pub missed: u64,
+    /// Block-solving shares that reached a chain
+    pub used_shares: u64,
+    /// Block-solving shares that were found but reached neither chain
+    pub wasted_shares: u64,
+    /// `used_shares` over all block-solving shares, 1.0 before any are found
+    pub efficiency: f64,
+    pub last_fuego_height: u64,
+}
+
+/// Drives merged mining: keeps the job manager on the current Fuego template and hands
+/// every solved share to the chain(s) whose difficulty it meets
+pub struct MergeMiningCoordinator {
+    config: MergeMiningConfig,
+    client: Arc<FuegoRpcClient>,
+    job_manager: Arc<JobManager>,
+    sealed_tx: mpsc::Sender<Block>,
+    /// Height and previous block of the template being mined on
+    parent_tip: RwLock<Option<(u64, Hash)>>,
+    stats: RwLock<MergeMiningStats>,
+}
+
+impl MergeMiningCoordinator {
+    /// Create a coordinator feeding `job_manager`; sealed C0DL3 blocks are sent on `sealed_tx`
+    pub fn new(
+        config: MergeMiningConfig,
+        job_manager: Arc<JobManager>,
+        sealed_tx: mpsc::Sender<Block>,
+    ) -> Result<Self, MiningError> {
+        let client = Arc::new(FuegoRpcClient::new(config.rpc.clone())?);
+        Ok(Self {
+            config,
+            client,
+            job_manager,
+            sealed_tx,
+            parent_tip: RwLock::new(None),
+            stats: RwLock::new(MergeMiningStats::default()),
+        })
+    }
+
+    /// Fetch a Fuego template and switch the job manager to it if the Fuego tip moved.
+    /// Returns whether new parent work was set.
+    pub async fn refresh_template(&self) -> Result<bool, MiningError> {
+        let template = self
+            .client
+            .get_block_template(EXTRA_NONCE_SIZE as u32, &self.config.wallet_address)
+            .await?;
+        let parent = parent_work(&template)?;
+        let tip = (parent.fuego_height, parent.parent_header.prev_id);
+
+        let mut stats = self.stats.write().await;
+        stats.templates_fetched += 1;
+        let mut parent_tip = self.parent_tip.write().await;
+        if *parent_tip == Some(tip) {
+            return Ok(false);
+        }
+        *parent_tip = Some(tip);
+        stats.parent_updates += 1;
+        stats.last_fuego_height = parent.fuego_height;
+        self.job_manager.set_parent_work(parent);
+        Ok(true)
+    }
+
+    /// Submit a solved Fuego block to fuegod, or seal a solved C0DL3 block
+    pub async fn handle_block(&self, found: FoundBlock) -> Result<(), MiningError> {
+        let is_fuego = matches!(found, FoundBlock::Fuego { .. });
+        self.chain_stats(is_fuego, |chain| chain.found += 1).await;
+        let result = match found {
+            FoundBlock::Fuego {
+                height,
+                parent_header,
+                coinbase_tx,
+                other_tx_hashes,
+                ..
+            } => self.submit_fuego(height, &parent_header, &coinbase_tx, &other_tx_hashes).await,
+            FoundBlock::C0dl3 {
+                job_id, height, proof, ..
+            } => self.seal_c0dl3(&job_id, height, &proof).await,
+        };
+        match &result {
+            Ok(()) => self.chain_stats(is_fuego, |chain| chain.submitted += 1).await,
+            Err(_) => self.chain_stats(is_fuego, |chain| chain.failed += 1).await,
+        }
+        result
+    }
+
+    async fn submit_fuego(
+        &self,
+        height: u64,
+        parent_header: &ParentBlockHeader,
+        coinbase_tx: &[u8],
+        other_tx_hashes: &[Hash],
+    ) -> Result<(), MiningError> {
+        let blob = fuego_block_blob(parent_header, coinbase_tx, other_tx_hashes);
+        self.client.submit_block(&blob).await?;
+        println!("Merge-mined Fuego block {} submitted", height);
+        // Our own block moved the Fuego tip; mine on top of it straight away
+        if let Err(e) = self.refresh_template().await {
+            println!("Failed to fetch Fuego template: {}", e);
+        }
+        Ok(())
+    }
+
+    async fn seal_c0dl3(&self, job_id: &str, height: u64, proof: &MergeMinedProof) -> Result<(), MiningError> {
+        let block = self.job_manager.seal_block(job_id, proof).await?;
+        self.sealed_tx
+            .send(block)
+            .await
+            .map_err(|_| MiningError::InvalidWork("Sealed block receiver closed".to_string()))?;
+        println!("Merge-mined C0DL3 block {} sealed", height);
+        Ok(())
+    }
+
+    async fn chain_stats(&self, is_fuego: bool, update: impl FnOnce(&mut ChainShareStats)) {
+        let mut stats = self.stats.write().await;
+        update(if is_fuego { &mut stats.fuego } else { &mut stats.c0dl3 });
+    }
+
+    /// Poll fuegod for templates and route found blocks until `running` turns false
+    pub async fn run(self: Arc<Self>, mut blocks: broadcast::Receiver<FoundBlock>, mut running: watch::Receiver<bool>) {
+        let mut poll = tokio::time::interval(self.config.poll_interval);
+        while *running.borrow() {
+            tokio::select! {
+                _ = poll.tick() => {
+                    if let Err(e) = self.refresh_template().await {
+                        println!("Failed to fetch Fuego template: {}", e);
+                    }
+                }
+                found = blocks.recv() => match found {
+                    Ok(found) => {
+                        if let Err(e) = self.handle_block(found).await {
+                            println!("Failed to submit merge-mined block: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        println!("Merge mining coordinator missed {} found blocks", missed);
+                        self.stats.write().await.missed += missed;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = running.changed() => {}
+            }
+        }
+    }
+
+    /// Get merge mining statistics
+    pub async fn get_stats(&self) -> MergeMiningStats {
+        let mut stats = self.stats.read().await.clone();
+        stats.used_shares = stats.fuego.submitted + stats.c0dl3.submitted;
+        stats.wasted_shares = stats.fuego.failed + stats.c0dl3.failed + stats.missed;
+        let total = stats.used_shares + stats.wasted_shares;
+        stats.efficiency = if total == 0 { 1.0 } else { stats.used_shares as f64 / total as f64 };
+        stats
+    }
+}
+
+/// Parent work from a `getblocktemplate` response. The coinbase's `tx_extra` is lengthened to
+/// cover the merge mining tag the job appends, so fuegod reads the tag as part of the extra.
+/// A nonzero `reserved_offset` must point at `EXTRA_NONCE_SIZE` bytes inside the extra.
+pub fn parent_work(template: &BlockTemplate) -> Result<ParentWork, MiningError> {
+    let blob = template.template_bytes()?;
+    let block = fuego_types::Block::parse(&blob)?;
+    let parent_header = block.hashing_header();
+
+    // The coinbase sits between the header and the list of other transaction hashes
+    let start = header_bytes(&parent_header).len();
+    let end = blob.len() - tx_hashes_bytes(&block.tx_hashes).len();
+    let coinbase = &blob[start..end];
+
+    // A coinbase carries no signatures, so its extra runs to the end
+    let extra = &block.miner_tx.prefix.extra;
+    if !coinbase.ends_with(extra) {
+        return Err(MiningError::InvalidWork("Template coinbase does not end with its extra".to_string()));
+    }
+    let mut extra_len = Vec::new();
+    write_varint(&mut extra_len, extra.len() as u64);
+    let tag_len = MergeMiningTag { depth: 0, nonce: 0, merkle_root: [0u8; 32] }.to_extra().len();
+
+    let mut coinbase_tx = coinbase[..coinbase.len() - extra.len() - extra_len.len()].to_vec();
+    write_varint(&mut coinbase_tx, (extra.len() + tag_len) as u64);
+
+    // The lengthened varint may shift the extra, so the reservation is kept relative to it
+    let extra_at = end - extra.len();
+    let reserved_offset = match usize::try_from(template.reserved_offset).unwrap_or(usize::MAX) {
+        0 => None,
+        offset if offset >= extra_at && offset.saturating_add(EXTRA_NONCE_SIZE) <= end => {
+            Some(coinbase_tx.len() + offset - extra_at)
+        }
+        offset => {
+            return Err(MiningError::InvalidWork(format!("Reserved offset {} is outside the coinbase extra", offset)))
+        }
+    };
+    coinbase_tx.extend_from_slice(extra);
+
+    Ok(ParentWork {
+        parent_header,
+        coinbase_tx,
+        reserved_offset,
+        other_tx_hashes: block.tx_hashes,
+        fuego_height: template.height,
+        fuego_difficulty: template.difficulty,
+    })
+}
+
+/// Serialized Fuego block, as `submitblock` takes it
+pub fn fuego_block_blob(parent_header: &ParentBlockHeader, coinbase_tx: &[u8], other_tx_hashes: &[Hash]) -> Vec<u8> {
+    let mut blob = header_bytes(parent_header);
+    blob.extend_from_slice(coinbase_tx);
+    blob.extend_from_slice(&tx_hashes_bytes(other_tx_hashes));
+    blob
+}
+
+/// Header fields at the start of a block blob
+fn header_bytes(header: &ParentBlockHeader) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, header.major_version as u64);
+    write_varint(&mut out, header.minor_version as u64);
+    write_varint(&mut out, header.timestamp);
+    out.extend_from_slice(&header.prev_id);
+    out.extend_from_slice(&header.nonce.to_le_bytes());
+    out
+}
+
+fn tx_hashes_bytes(hashes: &[Hash]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, hashes.len() as u64);
+    hashes.iter().for_each(|hash| out.extend_from_slice(hash));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_manager::{ChainTip, JobManagerConfig};
+    use crate::work::MergedJob;
+    use consensus::{BlockLimits, BlockValidator};
+    use serde_json::json;
+    use txpool::fee::SimpleFeeAlgorithm;
+    use txpool::priority::SimplePriorityCalculator;
+    use txpool::TxPool;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Blob offset of the extra nonce reserved in `template_blob`
+    const RESERVED_OFFSET: u64 = 120;
+
+    /// Template blob with a one-output coinbase whose extra holds a public key and the reserved
+    /// extra nonce
+    fn template_blob(tx_hashes: &[Hash]) -> Vec<u8> {
+        let mut blob = vec![1, 0, 0x80, 0xe1, 0xeb, 0x17];
+        blob.extend_from_slice(&[0xaa; 32]);
+        blob.extend_from_slice(&0u32.to_le_bytes());
+        // Version, unlock time, gen input at height 100, one key output
+        blob.extend_from_slice(&[1, 0x8a, 0x01, 1, 0xff, 100, 1, 0x90, 0x4e, 0x02]);
+        blob.extend_from_slice(&[0x33; 32]);
+        blob.extend_from_slice(&[43, 0x01]);
+        blob.extend_from_slice(&[0x44; 32]);
+        blob.extend_from_slice(&[0x02, EXTRA_NONCE_SIZE as u8]);
+        blob.extend_from_slice(&[0; EXTRA_NONCE_SIZE]);
+        blob.extend_from_slice(&tx_hashes_bytes(tx_hashes));
+        blob
+    }
+
+    fn test_template(tx_hashes: &[Hash]) -> BlockTemplate {
+        BlockTemplate {
+            blocktemplate_blob: hex::encode(template_blob(tx_hashes)),
+            blockhashing_blob: None,
+            difficulty: 1,
+            height: 100,
+            reserved_offset: RESERVED_OFFSET,
+        }
+    }
+
+    fn test_job_manager() -> Arc<JobManager> {
+        let pool = Arc::new(RwLock::new(TxPool::new(
+            Box::new(SimpleFeeAlgorithm::new(1)),
+            Box::new(SimplePriorityCalculator::new()),
+            100,
+        )));
+        let validator = BlockValidator::new(BlockLimits::default(), 15);
+        let job_manager = JobManager::new(JobManagerConfig::default(), pool, validator, vec![0xfe; 32]).unwrap();
+        job_manager.update_tip(ChainTip { height: 0, hash: [0u8; 32], difficulty: 1 });
+        Arc::new(job_manager)
+    }
+
+    #[test]
+    fn test_tagged_template_serializes_a_valid_fuego_block() {
+        let other = [[1u8; 32], [2u8; 32]];
+        let parent = parent_work(&test_template(&other)).unwrap();
+        assert_eq!(parent.other_tx_hashes, other);
+
+        let work = crate::work::MergedWork {
+            parent_header: parent.parent_header,
+            coinbase_tx: parent.coinbase_tx,
+            reserved_offset: parent.reserved_offset,
+            other_tx_hashes: parent.other_tx_hashes,
+            fuego_height: parent.fuego_height,
+            fuego_difficulty: parent.fuego_difficulty,
+            aux_hash: [9u8; 32],
+            c0dl3_height: 1,
+            c0dl3_difficulty: 1,
+        };
+        let job = MergedJob::new("1".to_string(), work).unwrap();
+
+        // fuegod parses the tag out of the coinbase extra and agrees on the block id
+        let header = job.header_with_nonce(7);
+        let blob = fuego_block_blob(&header, &job.aux_pow.coinbase_tx, &other);
+        let block = fuego_types::Block::parse(&blob).unwrap();
+        assert_eq!(block.miner_tx.prefix.merge_mining_tag().unwrap().merkle_root, [9u8; 32]);
+        assert_eq!(block.miner_tx.prefix.public_key(), Some([0x44; 32]));
+        assert_eq!(block.hashing_header(), header);
+        assert_eq!(block.id(), header.id());
+
+        // A session's extra nonce lands in the reserved bytes and gives it a parent block of its own
+        let session = job.with_extra_nonce(0x0102).unwrap();
+        let header = session.header_with_nonce(7);
+        let blob = fuego_block_blob(&header, &session.aux_pow.coinbase_tx, &other);
+        let block = fuego_types::Block::parse(&blob).unwrap();
+        let extra_nonce = fuego_types::ExtraField::Nonce(0x0102u64.to_le_bytes().to_vec());
+        assert!(block.miner_tx.prefix.extra_fields().contains(&extra_nonce));
+        assert_eq!(block.miner_tx.prefix.merge_mining_tag().unwrap().merkle_root, [9u8; 32]);
+        assert_eq!(block.id(), header.id());
+        assert_ne!(header.id(), job.header_with_nonce(7).id());
+
+        // A reservation outside the coinbase extra is refused
+        let mut template = test_template(&other);
+        template.reserved_offset = 50;
+        assert!(matches!(parent_work(&template), Err(MiningError::InvalidWork(_))));
+    }
+
+    #[tokio::test]
+    async fn test_share_is_submitted_to_both_chains() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "getblocktemplate", "params": { "reserve_size": 8 } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0", "id": 0,
+                "result": {
+                    "blocktemplate_blob": test_template(&[]).blocktemplate_blob,
+                    "difficulty": 1,
+                    "height": 100,
+                    "reserved_offset": RESERVED_OFFSET,
+                    "status": "OK",
+                },
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "submitblock" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0", "id": 1, "result": { "status": "OK" },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let job_manager = test_job_manager();
+        let (sealed_tx, mut sealed_rx) = mpsc::channel(4);
+        let config = MergeMiningConfig {
+            rpc: FuegoRpcConfig { url: server.uri(), ..Default::default() },
+            ..Default::default()
+        };
+        let coordinator = MergeMiningCoordinator::new(config, job_manager.clone(), sealed_tx).unwrap();
+        assert!(coordinator.refresh_template().await.unwrap());
+        assert!(!coordinator.refresh_template().await.unwrap());
+
+        job_manager.refresh().await.unwrap();
+        let job = job_manager.current_job().await.unwrap();
+        let mut found = job.found_blocks(3, &job.pow_hash(3));
+        assert_eq!(found.len(), 2);
+        let late = found[1].clone();
+        for block in found.drain(..) {
+            coordinator.handle_block(block).await.unwrap();
+        }
+
+        let sealed = sealed_rx.recv().await.unwrap();
+        assert_eq!(sealed.header.height, 1);
+        assert!(!sealed.proof.proof_data.is_empty());
+        assert_eq!(coordinator.get_stats().await.efficiency, 1.0);
+
+        // A solution for a template that has since expired is counted as wasted
+        let FoundBlock::C0dl3 { height, aux_hash, proof, pow_hash, .. } = late else {
+            panic!("expected a C0DL3 block");
+        };
+        let expired = FoundBlock::C0dl3 { job_id: "expired".to_string(), height, aux_hash, proof, pow_hash };
+        assert!(matches!(coordinator.handle_block(expired).await, Err(MiningError::JobNotFound(_))));
+
+        let stats = coordinator.get_stats().await;
+        assert_eq!((stats.fuego.found, stats.fuego.submitted), (1, 1));
+        assert_eq!((stats.c0dl3.found, stats.c0dl3.submitted, stats.c0dl3.failed), (2, 1, 1));
+        assert_eq!((stats.used_shares, stats.wasted_shares), (2, 1));
+        assert!((stats.efficiency - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.last_fuego_height, 100);
+    }
+}
@@ -0,0 +1,40 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum MiningError {
+    #[error("Stratum error: {0}")]
+    StratumError(String),
+
+    #[error("Invalid share: {0}")]
+    InvalidShare(String),
+
+    #[error("Job not found: {0}")]
+    JobNotFound(String),
+
+    #[error("Invalid work: {0}")]
+    InvalidWork(String),
+
+    #[error("Fuego daemon error: {0}")]
+    FuegoError(String),
+
+    #[error("Ledger error: {0}")]
+    LedgerError(String),
+}
+
+impl From<pow::PowError> for MiningError {
+    fn from(err: pow::PowError) -> Self {
+        MiningError::InvalidWork(err.to_string())
+    }
+}
+
+impl From<fuego_integration::FuegoError> for MiningError {
+    fn from(err: fuego_integration::FuegoError) -> Self {
+        MiningError::FuegoError(err.to_string())
+    }
+}
+
+impl From<fuego_types::ParseError> for MiningError {
+    fn from(err: fuego_types::ParseError) -> Self {
+        MiningError::InvalidWork(format!("Fuego block template: {}", err))
+    }
+}
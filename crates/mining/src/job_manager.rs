@@ -0,0 +1,726 @@
+use crate::error::MiningError;
+use crate::work::{MergedJob, MergedWork};
+use block_sync::{merkle_root, Block, BlockHeader, BlockProof, ProofType};
+use consensus::engine::header_id;
+use consensus::BlockValidator;
+use pow::auxpow::Hash;
+use pow::{MergeMinedProof, ParentBlockHeader};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, RwLock};
+use tokio::time::Duration;
+use txpool::selection::SelectionConfig;
+use txpool::TxPool;
+
+/// Job manager configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobManagerConfig {
+    /// Rebuild the template once transactions outside it carry at least this much in fees
+    pub fee_refresh_threshold: u64,
+    /// How often the pool is checked for new transactions
+    pub poll_interval: Duration,
+    /// Nonces per work range handed to a miner thread
+    pub range_size: u32,
+    /// Number of recent templates kept for sealing late solutions
+    pub template_history: usize,
+    /// How pooled transactions are picked for the template
+    pub selection: SelectionConfig,
+}
+
+impl Default for JobManagerConfig {
+    fn default() -> Self {
+        Self {
+            fee_refresh_threshold: 1000,
+            poll_interval: Duration::from_secs(1),
+            range_size: 0x1000,
+            template_history: 4,
+            selection: SelectionConfig::default(),
+        }
+    }
+}
+
+/// Current C0DL3 chain tip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainTip {
+    pub height: u64,
+    pub hash: Hash,
+    /// Difficulty consensus expects of the block built on this tip
+    pub difficulty: u64,
+}
+
+/// Fuego side of merged work: the parent block template being mined on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParentWork {
+    pub parent_header: ParentBlockHeader,
+    pub coinbase_tx: Vec<u8>,
+    /// Offset in `coinbase_tx` of the extra nonce fuegod reserved, if any
+    pub reserved_offset: Option<usize>,
+    pub other_tx_hashes: Vec<Hash>,
+    pub fuego_height: u64,
+    pub fuego_difficulty: u64,
+}
+
+/// A range of nonces on one job
+#[derive(Debug, Clone)]
+pub struct WorkRange {
+    pub job: Arc<MergedJob>,
+    /// Job generation; the range is abandoned once the manager moves past it
+    pub generation: u64,
+    pub start_nonce: u32,
+    pub count: u32,
+}
+
+/// Why the template was rebuilt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefreshReason {
+    Initial,
+    NewTip,
+    NewParent,
+    NewTransactions,
+}
+
+/// Job manager statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobManagerStats {
+    pub templates_built: u64,
+    pub tip_refreshes: u64,
+    pub parent_refreshes: u64,
+    pub transaction_refreshes: u64,
+    pub ranges_dispatched: u64,
+    /// Times dispatching paused because downstream proving fell behind
+    pub backpressure_pauses: u64,
+    pub current_job: Option<String>,
+    pub template_transactions: usize,
+}
+
+struct Template {
+    job: Arc<MergedJob>,
+    block: Block,
+    tip: ChainTip,
+    parent: ParentWork,
+    tx_hashes: HashSet<Hash>,
+}
+
+/// Builds block templates from the txpool and hands out nonce ranges to miner threads
+pub struct JobManager {
+    config: JobManagerConfig,
+    tx_pool: Arc<RwLock<TxPool>>,
+    /// Checks each template as consensus will check the mined block, under the same limits
+    validator: BlockValidator,
+    /// Who the template's fees are paid to
+    fee_recipient: Vec<u8>,
+    tip: watch::Sender<Option<ChainTip>>,
+    parent: watch::Sender<Option<ParentWork>>,
+    generation: watch::Sender<u64>,
+    backpressure: watch::Sender<bool>,
+    /// Cleared while this node may not produce blocks, e.g. while another holds the lease
+    producing: watch::Sender<bool>,
+    templates: RwLock<VecDeque<Template>>,
+    stats: RwLock<JobManagerStats>,
+    next_job_id: AtomicU64,
+}
+
+impl JobManager {
+    /// Create a new job manager whose templates pass `validator` paying their fees to `fee_recipient`
+    pub fn new(
+        config: JobManagerConfig,
+        tx_pool: Arc<RwLock<TxPool>>,
+        validator: BlockValidator,
+        fee_recipient: Vec<u8>,
+    ) -> Result<Self, MiningError> {
+        if config.range_size == 0 {
+            return Err(MiningError::InvalidWork("Range size must be positive".to_string()));
+        }
+        Ok(Self {
+            config,
+            tx_pool,
+            validator,
+            fee_recipient,
+            tip: watch::Sender::new(None),
+            parent: watch::Sender::new(None),
+            generation: watch::Sender::new(0),
+            backpressure: watch::Sender::new(false),
+            producing: watch::Sender::new(true),
+            templates: RwLock::new(VecDeque::new()),
+            stats: RwLock::new(JobManagerStats::default()),
+            next_job_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Record a new C0DL3 tip; work on the old tip is abandoned at the next refresh
+    pub fn update_tip(&self, tip: ChainTip) {
+        self.tip.send_replace(Some(tip));
+    }
+
+    /// Record new Fuego parent work
+    pub fn set_parent_work(&self, parent: ParentWork) {
+        self.parent.send_replace(Some(parent));
+    }
+
+    /// Pause handing out work while `full` is set, e.g. while the proof queue is full
+    pub fn set_backpressure(&self, full: bool) {
+        self.backpressure.send_if_modified(|current| std::mem::replace(current, full) != full);
+    }
+
+    /// Follow a backpressure signal until its sender goes away
+    pub async fn follow_backpressure(self: Arc<Self>, mut signal: watch::Receiver<bool>) {
+        loop {
+            let full = *signal.borrow_and_update();
+            self.set_backpressure(full);
+            if signal.changed().await.is_err() {
+                break;
+            }
+        }
+        self.set_backpressure(false);
+    }
+
+    /// Build templates and hand out work only while `producing` is set. Clearing it drops the
+    /// templates built so far once `run` sees it, so their solutions can no longer be sealed.
+    pub fn set_producing(&self, producing: bool) {
+        self.producing.send_if_modified(|current| std::mem::replace(current, producing) != producing);
+    }
+
+    /// Whether templates are built and work handed out
+    pub fn is_producing(&self) -> bool {
+        *self.producing.borrow()
+    }
+
+    /// Watch the job generation, bumped whenever the template is rebuilt
+    pub fn subscribe_generation(&self) -> watch::Receiver<u64> {
+        self.generation.subscribe()
+    }
+
+    /// Current job, if a template has been built
+    pub async fn current_job(&self) -> Option<Arc<MergedJob>> {
+        self.templates.read().await.back().map(|template| template.job.clone())
+    }
+
+    /// Rebuild the template if the tip, the parent work or the pool changed enough
+    pub async fn refresh(&self) -> Result<Option<RefreshReason>, MiningError> {
+        if !self.is_producing() {
+            return Ok(None);
+        }
+        let (Some(tip), Some(parent)) = (*self.tip.borrow(), self.parent.borrow().clone()) else {
+            return Ok(None);
+        };
+        // Priority ops get the gas reserved for them ahead of the transactions the selection
+        // strategy picks, and both are cut to the block's gas, size and weight limits
+        let strategy = self.config.selection.strategy();
+        let limits = self.validator.limits();
+        let transactions = {
+            let tx_pool = self.tx_pool.read().await;
+            let priority_ops = tx_pool.priority_ops(limits.max_transactions);
+            let candidates = tx_pool.select_transactions(&*strategy, limits.max_transactions);
+            limits.select_transactions(priority_ops, candidates)
+        };
+
+        let reason = match self.templates.read().await.back() {
+            None => Some(RefreshReason::Initial),
+            Some(current) if current.tip != tip => Some(RefreshReason::NewTip),
+            Some(current) if current.parent != parent => Some(RefreshReason::NewParent),
+            Some(current) => {
+                let new_fees: u64 = transactions
+                    .iter()
+                    .filter(|tx| !current.tx_hashes.contains(&tx.hash))
+                    .map(|tx| tx.fee)
+                    .sum();
+                (new_fees >= self.config.fee_refresh_threshold).then_some(RefreshReason::NewTransactions)
+            }
+        };
+        let Some(reason) = reason else {
+            return Ok(None);
+        };
+        let transactions = limits.transaction_ordering.apply(&tip.hash, transactions);
+
+        let header = BlockHeader {
+            height: tip.height + 1,
+            prev_hash: tip.hash,
+            merkle_root: merkle_root(&transactions),
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            nonce: 0,
+            difficulty: tip.difficulty,
+            nullifier_root: [0u8; 32],
+        };
+        let mut block = Block {
+            header,
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        };
+        block.header.nullifier_root = self
+            .validator
+            .nullifier_root(&block)
+            .map_err(|rejection| MiningError::InvalidWork(format!("Template rejected: {}", rejection)))?;
+
+        // Only the proof of work and the proposer's signature are left for the mined block
+        self.validator
+            .check_syntax(&block)
+            .and_then(|_| self.validator.check_state(&block, &self.fee_recipient))
+            .map_err(|rejection| MiningError::InvalidWork(format!("Template rejected: {}", rejection)))?;
+        let header = &block.header;
+        let aux_hash = header.hash().map_err(|e| MiningError::InvalidWork(e.to_string()))?;
+
+        let work = MergedWork {
+            parent_header: parent.parent_header.clone(),
+            coinbase_tx: parent.coinbase_tx.clone(),
+            reserved_offset: parent.reserved_offset,
+            other_tx_hashes: parent.other_tx_hashes.clone(),
+            fuego_height: parent.fuego_height,
+            fuego_difficulty: parent.fuego_difficulty,
+            aux_hash,
+            c0dl3_height: header.height,
+            c0dl3_difficulty: header.difficulty,
+        };
+        let job_id = self.next_job_id.fetch_add(1, Ordering::Relaxed).to_string();
+        let job = Arc::new(MergedJob::new(job_id.clone(), work)?);
+
+        let template = Template {
+            job,
+            tx_hashes: block.transactions.iter().map(|tx| tx.hash).collect(),
+            block,
+            tip,
+            parent,
+        };
+
+        {
+            let mut stats = self.stats.write().await;
+            stats.templates_built += 1;
+            match reason {
+                RefreshReason::NewTip => stats.tip_refreshes += 1,
+                RefreshReason::NewParent => stats.parent_refreshes += 1,
+                RefreshReason::NewTransactions => stats.transaction_refreshes += 1,
+                RefreshReason::Initial => {}
+            }
+            stats.current_job = Some(job_id);
+            stats.template_transactions = template.block.transactions.len();
+        }
+        {
+            let mut templates = self.templates.write().await;
+            templates.push_back(template);
+            while templates.len() > self.config.template_history.max(1) {
+                templates.pop_front();
+            }
+        }
+        self.generation.send_modify(|generation| *generation += 1);
+
+        Ok(Some(reason))
+    }
+
+    /// Attach a merge-mining proof to the template block of `job_id`
+    pub async fn seal_block(&self, job_id: &str, proof: &MergeMinedProof) -> Result<Block, MiningError> {
+        let templates = self.templates.read().await;
+        let template = templates
+            .iter()
+            .find(|template| template.job.job_id == job_id)
+            .ok_or_else(|| MiningError::JobNotFound(job_id.to_string()))?;
+
+        let mut block = template.block.clone();
+        block.proof.proof_data =
+            serde_json::to_vec(proof).map_err(|e| MiningError::InvalidWork(e.to_string()))?;
+        Ok(block)
+    }
+
+    /// Merge-mining hash of the recent template whose block has id `block_id`, which is what
+    /// miners record the blocks they solve under
+    pub async fn aux_hash_of(&self, block_id: &Hash) -> Option<Hash> {
+        let templates = self.templates.read().await;
+        templates
+            .iter()
+            .find(|template| header_id(&template.block.header) == *block_id)
+            .map(|template| template.job.work.aux_hash)
+    }
+
+    /// Refresh templates and keep every miner thread supplied with work ranges.
+    /// Miners report finished ranges on `done`; the loop ends when `running` turns false.
+    pub async fn run(
+        self: Arc<Self>,
+        miners: Vec<mpsc::Sender<WorkRange>>,
+        mut done: mpsc::Receiver<usize>,
+        mut running: watch::Receiver<bool>,
+    ) {
+        let mut tip = self.tip.subscribe();
+        let mut parent = self.parent.subscribe();
+        let mut backpressure = self.backpressure.subscribe();
+        let mut producing = self.producing.subscribe();
+        let mut poll = tokio::time::interval(self.config.poll_interval);
+        let mut generation = *self.generation.borrow();
+        let mut next_nonce = 0u32;
+
+        while *running.borrow() {
+            match self.refresh().await {
+                Ok(Some(reason)) => {
+                    println!("Rebuilt mining template ({:?})", reason);
+                    generation = *self.generation.borrow();
+                    next_nonce = 0;
+                    // Fresh work for everyone; anything still queued is dropped as stale by the miners
+                    for miner in 0..miners.len() {
+                        self.dispatch(&miners, miner, generation, &mut next_nonce).await;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => println!("Failed to build mining template: {}", e),
+            }
+
+            tokio::select! {
+                Some(miner) = done.recv() => {
+                    self.dispatch(&miners, miner, generation, &mut next_nonce).await;
+                }
+                _ = tip.changed() => {}
+                _ = parent.changed() => {}
+                _ = backpressure.changed() => {
+                    if *backpressure.borrow_and_update() {
+                        self.stats.write().await.backpressure_pauses += 1;
+                    } else {
+                        // Threads that went idle while paused need new ranges
+                        for miner in 0..miners.len() {
+                            self.dispatch(&miners, miner, generation, &mut next_nonce).await;
+                        }
+                    }
+                }
+                _ = producing.changed() => {
+                    // Work already handed out is abandoned; production resumes on a new template
+                    if !*producing.borrow_and_update() {
+                        self.templates.write().await.clear();
+                    }
+                }
+                _ = poll.tick() => {}
+                _ = running.changed() => {}
+            }
+        }
+    }
+
+    async fn dispatch(&self, miners: &[mpsc::Sender<WorkRange>], miner: usize, generation: u64, next_nonce: &mut u32) {
+        if *self.backpressure.borrow() || !self.is_producing() {
+            return;
+        }
+        let Some(job) = self.current_job().await else {
+            return;
+        };
+        let Some(sender) = miners.get(miner) else {
+            return;
+        };
+        if *next_nonce == u32::MAX {
+            // Nonce space exhausted; wait for the next template
+            return;
+        }
+
+        let count = self.config.range_size.min(u32::MAX - *next_nonce);
+        let range = WorkRange {
+            job,
+            generation,
+            start_nonce: *next_nonce,
+            count,
+        };
+        if sender.try_send(range).is_ok() {
+            *next_nonce += count;
+            self.stats.write().await.ranges_dispatched += 1;
+        }
+    }
+
+    /// Get job manager statistics
+    pub async fn get_stats(&self) -> JobManagerStats {
+        self.stats.read().await.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_sync::{Transaction, TxInput, TxOutput};
+    use consensus::{BlockLimits, StateTransition};
+    use pow::auxpow::bare_coinbase;
+    use txpool::fee::SimpleFeeAlgorithm;
+    use txpool::priority::SimplePriorityCalculator;
+
+    fn test_pool() -> Arc<RwLock<TxPool>> {
+        Arc::new(RwLock::new(TxPool::new(
+            Box::new(SimpleFeeAlgorithm::new(1)),
+            Box::new(SimplePriorityCalculator::new()),
+            100,
+        )))
+    }
+
+    fn test_tx(id: u8, fee: u64) -> Transaction {
+        Transaction {
+            hash: [0u8; 32],
+            sender: Vec::new(),
+            nonce: 0,
+            gas_limit: 0,
+            data: Vec::new(),
+            inputs: vec![TxInput {
+                prev_tx_hash: [id; 32],
+                output_index: 0,
+                signature: vec![1u8; 64],
+            }],
+            outputs: vec![TxOutput {
+                amount: 100,
+                address: vec![1u8; 32],
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee,
+            timestamp: 1234567890,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        }
+        .with_id()
+    }
+
+    fn test_validator() -> BlockValidator {
+        BlockValidator::new(BlockLimits::default(), 15)
+    }
+
+    fn test_manager(config: JobManagerConfig, pool: Arc<RwLock<TxPool>>) -> JobManager {
+        JobManager::new(config, pool, test_validator(), vec![0xfe; 32]).unwrap()
+    }
+
+    /// State transition refusing blocks that pay anyone but `0xfe..` or carry a fee above 1000
+    struct FeeCap;
+
+    impl StateTransition for FeeCap {
+        fn check_block(&self, block: &Block, fee_recipient: &[u8]) -> Result<(), String> {
+            if fee_recipient != [0xfe; 32] {
+                return Err("unknown fee recipient".to_string());
+            }
+            match block.transactions.iter().find(|tx| tx.fee > 1000) {
+                Some(tx) => Err(format!("fee of {} is too high", hex::encode(tx.hash))),
+                None => Ok(()),
+            }
+        }
+
+        fn nullifier_root(&self, block: &Block) -> Result<[u8; 32], String> {
+            Ok(pow::auxpow::tree_hash(&[block.header.prev_hash]))
+        }
+    }
+
+    fn test_tip(height: u64, hash: u8, difficulty: u64) -> ChainTip {
+        ChainTip {
+            height,
+            hash: [hash; 32],
+            difficulty,
+        }
+    }
+
+    fn test_parent(fuego_height: u64) -> ParentWork {
+        ParentWork {
+            parent_header: ParentBlockHeader {
+                major_version: 1,
+                timestamp: 1_700_000_000,
+                ..Default::default()
+            },
+            coinbase_tx: bare_coinbase(1),
+            reserved_offset: None,
+            other_tx_hashes: vec![],
+            fuego_height,
+            fuego_difficulty: u64::MAX,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_template_refresh_triggers() {
+        let pool = test_pool();
+        pool.write().await.add_transaction(test_tx(1, 100)).await.unwrap();
+        let config = JobManagerConfig {
+            fee_refresh_threshold: 500,
+            ..Default::default()
+        };
+        let manager = test_manager(config, pool.clone());
+
+        // No tip or Fuego work yet
+        assert_eq!(manager.refresh().await.unwrap(), None);
+        manager.update_tip(test_tip(0, 0, 1000));
+        assert_eq!(manager.refresh().await.unwrap(), None);
+
+        manager.set_parent_work(test_parent(10));
+        assert_eq!(manager.refresh().await.unwrap(), Some(RefreshReason::Initial));
+        assert_eq!(manager.refresh().await.unwrap(), None);
+        let job = manager.current_job().await.unwrap();
+        assert_eq!((job.work.c0dl3_height, job.work.c0dl3_difficulty), (1, 1000));
+
+        // A cheap transaction does not justify new work, a fee-heavy one does
+        pool.write().await.add_transaction(test_tx(2, 100)).await.unwrap();
+        assert_eq!(manager.refresh().await.unwrap(), None);
+        pool.write().await.add_transaction(test_tx(3, 400)).await.unwrap();
+        assert_eq!(manager.refresh().await.unwrap(), Some(RefreshReason::NewTransactions));
+        assert_eq!(manager.get_stats().await.template_transactions, 3);
+
+        // The difficulty comes with the tip, as consensus expects it of the next block
+        manager.update_tip(test_tip(1, 5, 2000));
+        assert_eq!(manager.refresh().await.unwrap(), Some(RefreshReason::NewTip));
+        let work = manager.current_job().await.unwrap().work.clone();
+        assert_eq!((work.c0dl3_height, work.c0dl3_difficulty), (2, 2000));
+
+        manager.set_parent_work(test_parent(11));
+        assert_eq!(manager.refresh().await.unwrap(), Some(RefreshReason::NewParent));
+        assert_eq!(*manager.subscribe_generation().borrow(), 4);
+
+        // Solutions on recent templates can still be sealed
+        let proof = MergeMinedProof {
+            parent_header: job.parent_header.clone(),
+            aux_pow: job.aux_pow.clone(),
+        };
+        let block = manager.seal_block(&job.job_id, &proof).await.unwrap();
+        assert_eq!(block.header.height, 1);
+
+        // The header commits to the transaction root consensus checks, `consensus::merkle_root`
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(block.header.merkle_root, merkle_root(&block.transactions));
+        assert_ne!(block.header.merkle_root, pow::auxpow::tree_hash(&[block.transactions[0].hash]));
+        assert_eq!(serde_json::from_slice::<MergeMinedProof>(&block.proof.proof_data).unwrap(), proof);
+    }
+
+    #[tokio::test]
+    async fn test_dispatches_disjoint_ranges() {
+        let config = JobManagerConfig {
+            range_size: 100,
+            ..Default::default()
+        };
+        let manager = Arc::new(test_manager(config, test_pool()));
+        manager.update_tip(test_tip(0, 0, 1000));
+        manager.set_parent_work(test_parent(10));
+
+        let (miner_a, mut ranges_a) = mpsc::channel(1);
+        let (miner_b, mut ranges_b) = mpsc::channel(1);
+        let (done_tx, done_rx) = mpsc::channel(8);
+        let (running_tx, running_rx) = watch::channel(true);
+        let task = tokio::spawn(manager.clone().run(vec![miner_a, miner_b], done_rx, running_rx));
+
+        let a = ranges_a.recv().await.unwrap();
+        let b = ranges_b.recv().await.unwrap();
+        assert_eq!((a.start_nonce, b.start_nonce), (0, 100));
+        assert_eq!(a.job.job_id, b.job.job_id);
+
+        done_tx.send(0).await.unwrap();
+        assert_eq!(ranges_a.recv().await.unwrap().start_nonce, 200);
+
+        // A new tip restarts the nonce space on a new job
+        manager.update_tip(test_tip(1, 5, 1000));
+        let next = ranges_a.recv().await.unwrap();
+        assert_eq!(next.start_nonce, 0);
+        assert!(next.generation > a.generation);
+        assert_eq!(ranges_b.recv().await.unwrap().start_nonce, 100);
+
+        // Finished ranges are not replaced while backpressure is on
+        let (full_tx, full_rx) = watch::channel(true);
+        tokio::spawn(manager.clone().follow_backpressure(full_rx));
+        while manager.get_stats().await.backpressure_pauses == 0 {
+            tokio::task::yield_now().await;
+        }
+        done_tx.send(0).await.unwrap();
+        let idle = tokio::time::timeout(Duration::from_millis(50), ranges_a.recv()).await;
+        assert!(idle.is_err());
+        full_tx.send(false).unwrap();
+        assert_eq!(ranges_a.recv().await.unwrap().start_nonce, 200);
+
+        // Nor while the node may not produce blocks, which drops the templates built so far
+        manager.set_producing(false);
+        while manager.current_job().await.is_some() {
+            tokio::task::yield_now().await;
+        }
+        manager.update_tip(test_tip(2, 6, 1000));
+        done_tx.send(0).await.unwrap();
+        let idle = tokio::time::timeout(Duration::from_millis(50), ranges_a.recv()).await;
+        assert!(idle.is_err());
+        assert!(manager.current_job().await.is_none());
+        manager.set_producing(true);
+        let resumed = ranges_a.recv().await.unwrap();
+        assert_eq!((resumed.job.work.c0dl3_height, resumed.start_nonce), (3, 0));
+
+        running_tx.send(false).unwrap();
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_templates_pass_the_block_validator() {
+        let pool = test_pool();
+        let limits = BlockLimits {
+            max_transactions: 2,
+            ..Default::default()
+        };
+        let mut validator = BlockValidator::new(limits, 15);
+        validator.set_state_transition(Arc::new(FeeCap));
+        let config = JobManagerConfig::default();
+        let manager = JobManager::new(config.clone(), pool.clone(), validator.clone(), vec![0xfe; 32]).unwrap();
+        manager.update_tip(test_tip(0, 0, 1000));
+        manager.set_parent_work(test_parent(10));
+
+        // The template is filled to the consensus limits and checked on its parent state
+        for id in 1..=3 {
+            pool.write().await.add_transaction(test_tx(id, 100 * id as u64)).await.unwrap();
+        }
+        assert_eq!(manager.refresh().await.unwrap(), Some(RefreshReason::Initial));
+        assert_eq!(manager.get_stats().await.template_transactions, 2);
+
+        // Its header commits to the nullifier root of its post-state
+        let job = manager.current_job().await.unwrap();
+        let proof = MergeMinedProof {
+            parent_header: job.parent_header.clone(),
+            aux_pow: job.aux_pow.clone(),
+        };
+        let block = manager.seal_block(&job.job_id, &proof).await.unwrap();
+        assert_eq!(block.header.nullifier_root, pow::auxpow::tree_hash(&[block.header.prev_hash]));
+        assert_eq!(job.work.aux_hash, block.header.hash().unwrap());
+
+        // A template the state transition refuses is never handed out
+        pool.write().await.add_transaction(test_tx(4, 5_000)).await.unwrap();
+        let error = manager.refresh().await.unwrap_err();
+        assert!(error.to_string().contains("Template rejected"), "{}", error);
+        assert_eq!(manager.get_stats().await.templates_built, 1);
+
+        // Nor one paying its fees to someone the chain does not pay
+        let stranger = JobManager::new(config, test_pool(), validator, vec![0xee; 32]).unwrap();
+        stranger.update_tip(test_tip(0, 0, 1000));
+        stranger.set_parent_work(test_parent(10));
+        assert!(stranger.refresh().await.is_err());
+        assert!(stranger.current_job().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_templates_keep_to_the_block_limits() {
+        let pool = test_pool();
+        pool.write().await.set_priority_accounts(vec![vec![0xb0]]);
+        let limits = BlockLimits {
+            max_block_gas: 63_000,
+            priority_ops_enabled: true,
+            priority_ops_gas: 21_000,
+            ..Default::default()
+        };
+        let validator = BlockValidator::new(limits, 15);
+        let manager = JobManager::new(JobManagerConfig::default(), pool.clone(), validator, vec![0xfe; 32]).unwrap();
+        manager.update_tip(test_tip(0, 0, 1000));
+        manager.set_parent_work(test_parent(10));
+
+        let with_gas = |id: u8, fee: u64, address: u8| {
+            let mut tx = test_tx(id, fee);
+            tx.gas_limit = 21_000;
+            tx.outputs[0].address = vec![address];
+            tx.with_id()
+        };
+        for id in 1..=3 {
+            pool.write().await.add_transaction(with_gas(id, 100, 0xb0)).await.unwrap();
+            pool.write().await.add_transaction(with_gas(10 + id, 1_000, 0xb1)).await.unwrap();
+        }
+
+        // Priority ops fill only the gas reserved for them, user transactions the rest of the block
+        manager.refresh().await.unwrap();
+        let job = manager.current_job().await.unwrap();
+        let proof = MergeMinedProof {
+            parent_header: job.parent_header.clone(),
+            aux_pow: job.aux_pow.clone(),
+        };
+        let block = manager.seal_block(&job.job_id, &proof).await.unwrap();
+        let ops = block.transactions.iter().filter(|tx| tx.outputs[0].address == [0xb0]).count();
+        assert_eq!((block.transactions.len(), ops), (3, 1));
+    }
+}
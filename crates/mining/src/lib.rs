@@ -0,0 +1,24 @@
+//! Mining subsystem: merged C0DL3 + Fuego work, the job manager that builds it from
+//! the txpool, the coordinator that binds it to fuegod's templates, the in-process miner
+//! with pluggable hash backends and the Stratum server for external miners, with PPLNS
+//! payout accounting for pools.
+
+pub mod backend;
+pub mod coordinator;
+pub mod error;
+pub mod job_manager;
+pub mod miner;
+pub mod pplns;
+pub mod stale;
+pub mod stratum;
+pub mod work;
+
+pub use backend::{BackendOptions, BackendRegistry, CpuBackend, HashBackend, HashWorker};
+pub use coordinator::{ChainShareStats, MergeMiningConfig, MergeMiningCoordinator, MergeMiningStats};
+pub use error::MiningError;
+pub use job_manager::{ChainTip, JobManager, JobManagerConfig, JobManagerStats, ParentWork, RefreshReason, WorkRange};
+pub use miner::{CODL3Miner, CODL3MiningConfig, CODL3MiningStats, ThreadStats};
+pub use pplns::{BlockPayout, PayoutChain, PplnsConfig, PplnsLedger, PplnsShare};
+pub use stale::{StaleBlock, StaleTracker};
+pub use stratum::{StratumConfig, StratumServer, StratumStats, WorkerStats};
+pub use work::{FoundBlock, MergedJob, MergedWork};
@@ -0,0 +1,575 @@
+use crate::backend::{BackendOptions, BackendRegistry, HashBackend, HashWorker, CPU_BACKEND};
+use crate::error::MiningError;
+use crate::job_manager::{ChainTip, JobManager, JobManagerConfig, WorkRange};
+use crate::stale::{StaleBlock, StaleTracker};
+use crate::work::{FoundBlock, MergedJob};
+use consensus::BlockValidator;
+use pow::check_hash;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle as ThreadHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::task::JoinHandle;
+use txpool::TxPool;
+
+/// Interval over which each thread measures its hashrate
+const HASHRATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// In-process C0DL3 miner configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CODL3MiningConfig {
+    /// Number of dedicated OS mining threads
+    pub threads: usize,
+    /// Nonces hashed between checks for stop and new work
+    pub batch_size: u32,
+    /// Hash backend, by registry name
+    pub backend: String,
+    pub backend_options: BackendOptions,
+    pub job_manager: JobManagerConfig,
+}
+
+impl Default for CODL3MiningConfig {
+    fn default() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            batch_size: 16,
+            backend: CPU_BACKEND.to_string(),
+            backend_options: BackendOptions::new(),
+            job_manager: JobManagerConfig::default(),
+        }
+    }
+}
+
+/// Statistics of one mining thread
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreadStats {
+    pub thread: usize,
+    pub hashes: u64,
+    /// Hashes per second over the last measurement window
+    pub hashrate: u64,
+    pub ranges_completed: u64,
+    pub stale_ranges: u64,
+}
+
+/// In-process miner statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CODL3MiningStats {
+    pub backend: String,
+    pub total_hashes: u64,
+    /// Sum of the per-thread hashrates
+    pub hashrate: u64,
+    pub ranges_completed: u64,
+    pub stale_ranges: u64,
+    pub c0dl3_blocks: u64,
+    pub fuego_blocks: u64,
+    /// Backend solutions that failed the CPU reference check
+    pub invalid_results: u64,
+    /// Mined C0DL3 blocks that lost to a competing block
+    pub stale_blocks: u64,
+    pub threads: Vec<ThreadStats>,
+}
+
+/// Lock-free counters updated from the hashing threads
+#[derive(Default)]
+struct ThreadCounters {
+    hashes: AtomicU64,
+    hashrate: AtomicU64,
+    ranges_completed: AtomicU64,
+    stale_ranges: AtomicU64,
+    c0dl3_blocks: AtomicU64,
+    fuego_blocks: AtomicU64,
+    invalid_results: AtomicU64,
+}
+
+/// Everything a mining thread needs
+struct MinerThread {
+    index: usize,
+    batch_size: u32,
+    ranges: mpsc::Receiver<WorkRange>,
+    done: mpsc::Sender<usize>,
+    generation: watch::Receiver<u64>,
+    stop: Arc<AtomicBool>,
+    counters: Arc<ThreadCounters>,
+    block_tx: broadcast::Sender<FoundBlock>,
+    stale_tracker: Arc<RwLock<StaleTracker>>,
+    worker: Box<dyn HashWorker>,
+}
+
+/// Mines merged work from the job manager on dedicated OS threads
+pub struct CODL3Miner {
+    config: CODL3MiningConfig,
+    job_manager: Arc<JobManager>,
+    backend: Arc<dyn HashBackend>,
+    running: watch::Sender<bool>,
+    stop: Arc<AtomicBool>,
+    counters: Arc<RwLock<Vec<Arc<ThreadCounters>>>>,
+    block_tx: broadcast::Sender<FoundBlock>,
+    stale_tracker: Arc<RwLock<StaleTracker>>,
+    threads: Vec<ThreadHandle<()>>,
+    manager_task: Option<JoinHandle<()>>,
+}
+
+impl CODL3Miner {
+    /// Create a new miner building templates from `tx_pool` that pass `validator`, paying
+    /// their fees to `fee_recipient`, with the built-in backends
+    pub fn new(
+        config: CODL3MiningConfig,
+        tx_pool: Arc<RwLock<TxPool>>,
+        validator: BlockValidator,
+        fee_recipient: Vec<u8>,
+    ) -> Result<Self, MiningError> {
+        Self::with_registry(config, tx_pool, validator, fee_recipient, &BackendRegistry::default())
+    }
+
+    /// Create a new miner, resolving `config.backend` in `registry`
+    pub fn with_registry(
+        config: CODL3MiningConfig,
+        tx_pool: Arc<RwLock<TxPool>>,
+        validator: BlockValidator,
+        fee_recipient: Vec<u8>,
+        registry: &BackendRegistry,
+    ) -> Result<Self, MiningError> {
+        let job_manager = Arc::new(JobManager::new(config.job_manager.clone(), tx_pool, validator, fee_recipient)?);
+        Self::with_job_manager(config, job_manager, registry)
+    }
+
+    /// Create a new miner for work from `job_manager`, which others may share, in place of
+    /// one configured by `config.job_manager`
+    pub fn with_job_manager(
+        config: CODL3MiningConfig,
+        job_manager: Arc<JobManager>,
+        registry: &BackendRegistry,
+    ) -> Result<Self, MiningError> {
+        if config.threads == 0 || config.batch_size == 0 {
+            return Err(MiningError::InvalidWork("Thread count and batch size must be positive".to_string()));
+        }
+        let backend = registry.create(&config.backend, &config.backend_options)?;
+        let (block_tx, _) = broadcast::channel(64);
+
+        Ok(Self {
+            config,
+            job_manager,
+            backend,
+            running: watch::Sender::new(false),
+            stop: Arc::new(AtomicBool::new(false)),
+            counters: Arc::new(RwLock::new(Vec::new())),
+            block_tx,
+            stale_tracker: Arc::new(RwLock::new(StaleTracker::default())),
+            threads: Vec::new(),
+            manager_task: None,
+        })
+    }
+
+    /// Start the job manager and the mining threads
+    pub async fn start(&mut self) -> Result<(), MiningError> {
+        if *self.running.borrow() {
+            return Err(MiningError::InvalidWork("Miner already running".to_string()));
+        }
+        let mut workers = (0..self.config.threads)
+            .map(|index| self.backend.create_worker(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.running.send_replace(true);
+        self.stop.store(false, Ordering::Relaxed);
+
+        let (done_tx, done_rx) = mpsc::channel(self.config.threads * 2);
+        let mut miners = Vec::with_capacity(self.config.threads);
+        let mut counters = Vec::with_capacity(self.config.threads);
+
+        for (index, worker) in workers.drain(..).enumerate() {
+            let (range_tx, range_rx) = mpsc::channel(1);
+            miners.push(range_tx);
+            let thread_counters = Arc::new(ThreadCounters::default());
+            counters.push(thread_counters.clone());
+
+            let thread = MinerThread {
+                index,
+                batch_size: self.config.batch_size,
+                ranges: range_rx,
+                done: done_tx.clone(),
+                generation: self.job_manager.subscribe_generation(),
+                stop: self.stop.clone(),
+                counters: thread_counters,
+                block_tx: self.block_tx.clone(),
+                stale_tracker: self.stale_tracker.clone(),
+                worker,
+            };
+            let handle = std::thread::Builder::new()
+                .name(format!("codl3-miner-{}", index))
+                .spawn(move || thread.run())
+                .map_err(|e| MiningError::InvalidWork(format!("Failed to spawn mining thread: {}", e)))?;
+            self.threads.push(handle);
+        }
+        *self.counters.write().await = counters;
+
+        let job_manager = self.job_manager.clone();
+        let running = self.running.subscribe();
+        self.manager_task = Some(tokio::spawn(job_manager.run(miners, done_rx, running)));
+
+        println!("CODL3 miner started with {} {} threads", self.config.threads, self.backend.name());
+        Ok(())
+    }
+
+    /// Signal the mining threads to stop and wait for them to exit
+    pub async fn stop(&mut self) -> Result<(), MiningError> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.running.send_replace(false);
+
+        // Dropping the job manager's senders releases threads waiting for work
+        if let Some(task) = self.manager_task.take() {
+            task.await.map_err(|e| MiningError::InvalidWork(e.to_string()))?;
+        }
+        let threads: Vec<_> = self.threads.drain(..).collect();
+        tokio::task::spawn_blocking(move || {
+            for thread in threads {
+                let _ = thread.join();
+            }
+        })
+        .await
+        .map_err(|e| MiningError::InvalidWork(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Receive blocks solved by this miner
+    pub fn subscribe_blocks(&self) -> broadcast::Receiver<FoundBlock> {
+        self.block_tx.subscribe()
+    }
+
+    /// The job manager feeding this miner, for tip updates and sealing solved blocks
+    pub fn job_manager(&self) -> Arc<JobManager> {
+        self.job_manager.clone()
+    }
+
+    /// Report a new canonical C0DL3 tip, refreshing work and detecting stale mined blocks.
+    /// The tip's hash is the block id; a block mined here is matched by its merge-mining hash.
+    pub async fn notify_tip(&self, tip: ChainTip) -> Vec<StaleBlock> {
+        let canonical = self.job_manager.aux_hash_of(&tip.hash).await.unwrap_or(tip.hash);
+        let stale = self.stale_tracker.write().await.record_canonical(tip.height, canonical);
+        self.job_manager.update_tip(tip);
+        stale
+    }
+
+    /// Tracker of mined blocks that went stale, shared with the RPC server
+    pub fn stale_tracker(&self) -> Arc<RwLock<StaleTracker>> {
+        self.stale_tracker.clone()
+    }
+
+    /// Get mining statistics, aggregated over all threads
+    pub async fn get_stats(&self) -> CODL3MiningStats {
+        let mut stats = CODL3MiningStats {
+            backend: self.backend.name().to_string(),
+            stale_blocks: self.stale_tracker.read().await.stale_blocks(),
+            ..Default::default()
+        };
+        for (thread, counters) in self.counters.read().await.iter().enumerate() {
+            let thread_stats = ThreadStats {
+                thread,
+                hashes: counters.hashes.load(Ordering::Relaxed),
+                hashrate: counters.hashrate.load(Ordering::Relaxed),
+                ranges_completed: counters.ranges_completed.load(Ordering::Relaxed),
+                stale_ranges: counters.stale_ranges.load(Ordering::Relaxed),
+            };
+            stats.total_hashes += thread_stats.hashes;
+            stats.hashrate += thread_stats.hashrate;
+            stats.ranges_completed += thread_stats.ranges_completed;
+            stats.stale_ranges += thread_stats.stale_ranges;
+            stats.c0dl3_blocks += counters.c0dl3_blocks.load(Ordering::Relaxed);
+            stats.fuego_blocks += counters.fuego_blocks.load(Ordering::Relaxed);
+            stats.invalid_results += counters.invalid_results.load(Ordering::Relaxed);
+            stats.threads.push(thread_stats);
+        }
+        stats
+    }
+
+    /// Check if the miner is running
+    pub fn is_running(&self) -> bool {
+        *self.running.borrow()
+    }
+}
+
+impl MinerThread {
+    fn run(mut self) {
+        let mut solutions = Vec::new();
+        let mut window_start = Instant::now();
+        let mut window_hashes = 0u64;
+
+        // Ranges stop arriving once the job manager exits
+        while let Some(range) = self.ranges.blocking_recv() {
+            let job = &range.job;
+            let target = job.work.fuego_difficulty.min(job.work.c0dl3_difficulty);
+            let mut blob = job.hashing_blob(0);
+            let offset = job.nonce_offset();
+            let end = range.start_nonce + range.count;
+            let mut nonce = range.start_nonce;
+            let mut stale = false;
+
+            while nonce < end {
+                if self.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if *self.generation.borrow() != range.generation {
+                    stale = true;
+                    break;
+                }
+
+                let count = self.batch_size.min(end - nonce);
+                let hashed = match self.worker.hash_batch(&mut blob, offset, nonce, count, target, &mut solutions) {
+                    Ok(hashed) => hashed,
+                    Err(e) => {
+                        println!("Miner thread {} hashing failed: {}", self.index, e);
+                        return;
+                    }
+                };
+                for (solved_nonce, hash) in solutions.drain(..) {
+                    self.submit(job, solved_nonce, &hash, target);
+                }
+                nonce += count;
+
+                self.counters.hashes.fetch_add(hashed, Ordering::Relaxed);
+                window_hashes += hashed;
+                let elapsed = window_start.elapsed();
+                if elapsed >= HASHRATE_WINDOW {
+                    let hashrate = (window_hashes as f64 / elapsed.as_secs_f64()) as u64;
+                    self.counters.hashrate.store(hashrate, Ordering::Relaxed);
+                    window_start = Instant::now();
+                    window_hashes = 0;
+                }
+            }
+
+            let counter = if stale { &self.counters.stale_ranges } else { &self.counters.ranges_completed };
+            counter.fetch_add(1, Ordering::Relaxed);
+            if self.done.blocking_send(self.index).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn submit(&self, job: &MergedJob, nonce: u32, hash: &[u8; 32], target: u64) {
+        // Backends are untrusted: check every solution against the reference hash
+        let reference = job.pow_hash(nonce);
+        if &reference != hash || !check_hash(&reference, target) {
+            println!("Miner thread {} backend returned an invalid solution for nonce {}", self.index, nonce);
+            self.counters.invalid_results.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        for block in job.found_blocks(nonce, hash) {
+            match &block {
+                FoundBlock::Fuego { .. } => {
+                    self.counters.fuego_blocks.fetch_add(1, Ordering::Relaxed);
+                }
+                FoundBlock::C0dl3 { height, aux_hash, .. } => {
+                    println!("Miner thread {} found C0DL3 block {}", self.index, height);
+                    self.counters.c0dl3_blocks.fetch_add(1, Ordering::Relaxed);
+                    self.stale_tracker.blocking_write().record_mined(*height, *aux_hash);
+                }
+            }
+            let _ = self.block_tx.send(block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_manager::ParentWork;
+    use consensus::BlockLimits;
+    use pow::auxpow::bare_coinbase;
+    use pow::ParentBlockHeader;
+    use txpool::fee::SimpleFeeAlgorithm;
+    use txpool::priority::SimplePriorityCalculator;
+
+    fn test_pool() -> Arc<RwLock<TxPool>> {
+        Arc::new(RwLock::new(TxPool::new(
+            Box::new(SimpleFeeAlgorithm::new(1)),
+            Box::new(SimplePriorityCalculator::new()),
+            100,
+        )))
+    }
+
+    fn test_validator() -> BlockValidator {
+        BlockValidator::new(BlockLimits::default(), 15)
+    }
+
+    fn test_parent() -> ParentWork {
+        ParentWork {
+            parent_header: ParentBlockHeader {
+                major_version: 1,
+                timestamp: 1_700_000_000,
+                ..Default::default()
+            },
+            coinbase_tx: bare_coinbase(1),
+            reserved_offset: None,
+            other_tx_hashes: vec![],
+            fuego_height: 10,
+            fuego_difficulty: u64::MAX,
+        }
+    }
+
+    fn test_config(threads: usize) -> CODL3MiningConfig {
+        CODL3MiningConfig {
+            threads,
+            batch_size: 2,
+            job_manager: JobManagerConfig {
+                range_size: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Give `miner` work on the genesis tip at `difficulty`
+    fn supply_work(miner: &CODL3Miner, difficulty: u64) {
+        miner.job_manager().update_tip(ChainTip { height: 0, hash: [0u8; 32], difficulty });
+        miner.job_manager().set_parent_work(test_parent());
+    }
+
+    fn test_miner(threads: usize, difficulty: u64) -> CODL3Miner {
+        let miner = CODL3Miner::new(test_config(threads), test_pool(), test_validator(), vec![0xfe; 32]).unwrap();
+        supply_work(&miner, difficulty);
+        miner
+    }
+
+    #[tokio::test]
+    async fn test_miner_finds_and_seals_blocks() {
+        let mut miner = test_miner(2, 1);
+        let mut blocks = miner.subscribe_blocks();
+        miner.start().await.unwrap();
+        assert!(miner.is_running());
+
+        // Difficulty 1 is met by the first hash
+        let (height, proof) = match blocks.recv().await.unwrap() {
+            FoundBlock::C0dl3 { height, proof, .. } => (height, proof),
+            other => panic!("unexpected block {:?}", other),
+        };
+        assert_eq!(height, 1);
+
+        let job_id = miner.job_manager().current_job().await.unwrap().job_id.clone();
+        let block = miner.job_manager().seal_block(&job_id, &proof).await.unwrap();
+        assert!(!block.proof.proof_data.is_empty());
+
+        miner.stop().await.unwrap();
+        assert!(!miner.is_running());
+
+        // A competing block wins height 1, so our block goes stale
+        let competing = ChainTip { height: 1, hash: [0xee; 32], difficulty: 1 };
+        let stale = miner.notify_tip(competing).await;
+        assert!(!stale.is_empty());
+        assert_eq!(stale[0].competing_hash, hex::encode([0xee; 32]));
+
+        let stats = miner.get_stats().await;
+        assert!(stats.total_hashes >= 1);
+        assert!(stats.c0dl3_blocks >= 1);
+        assert_eq!(stats.stale_blocks, stale.len() as u64);
+        assert_eq!(stats.threads.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_own_block_on_the_tip_is_accepted() {
+        let mut miner = test_miner(1, 1);
+        let mut blocks = miner.subscribe_blocks();
+        miner.start().await.unwrap();
+        let (job_id, proof) = match blocks.recv().await.unwrap() {
+            FoundBlock::C0dl3 { job_id, proof, .. } => (job_id, proof),
+            other => panic!("unexpected block {:?}", other),
+        };
+        let block = miner.job_manager().seal_block(&job_id, &proof).await.unwrap();
+        miner.stop().await.unwrap();
+
+        // Consensus names its tip by block id, not by the hash the block was mined under
+        let tip = ChainTip { height: 1, hash: consensus::engine::header_id(&block.header), difficulty: 1 };
+        assert!(miner.notify_tip(tip).await.is_empty());
+        assert_eq!(miner.stale_tracker().read().await.accepted_blocks(), 1);
+    }
+
+    /// Claims every nonce solves the block
+    struct LyingBackend;
+
+    struct LyingWorker;
+
+    impl HashBackend for LyingBackend {
+        fn name(&self) -> &str {
+            "lying"
+        }
+
+        fn create_worker(&self, _thread: usize) -> Result<Box<dyn HashWorker>, MiningError> {
+            Ok(Box::new(LyingWorker))
+        }
+    }
+
+    impl HashWorker for LyingWorker {
+        fn hash_batch(
+            &mut self,
+            _blob: &mut [u8],
+            _nonce_offset: usize,
+            start: u32,
+            count: u32,
+            _difficulty: u64,
+            solutions: &mut Vec<(u32, [u8; 32])>,
+        ) -> Result<u64, MiningError> {
+            solutions.extend((start..start + count).map(|nonce| (nonce, [0u8; 32])));
+            Ok(count as u64)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_backend_solutions_are_verified() {
+        let mut registry = BackendRegistry::default();
+        registry.register("lying", |_| Ok(Arc::new(LyingBackend) as Arc<dyn HashBackend>));
+
+        let config = CODL3MiningConfig {
+            backend: "lying".to_string(),
+            ..test_config(1)
+        };
+        // Only the built-in backends are known without the registry
+        assert!(CODL3Miner::new(config.clone(), test_pool(), test_validator(), vec![0xfe; 32]).is_err());
+
+        let job_manager = JobManager::new(config.job_manager.clone(), test_pool(), test_validator(), vec![0xfe; 32]);
+        let mut miner = CODL3Miner::with_job_manager(config, Arc::new(job_manager.unwrap()), &registry).unwrap();
+        supply_work(&miner, u64::MAX);
+        miner.start().await.unwrap();
+        for _ in 0..600 {
+            if miner.get_stats().await.invalid_results > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        miner.stop().await.unwrap();
+
+        let stats = miner.get_stats().await;
+        assert_eq!(stats.backend, "lying");
+        assert!(stats.invalid_results > 0);
+        assert_eq!(stats.c0dl3_blocks, 0);
+    }
+
+    #[tokio::test]
+    async fn test_threads_partition_nonces_and_stop() {
+        let mut miner = test_miner(3, u64::MAX);
+        miner.start().await.unwrap();
+
+        // Every thread receives its own ranges and hashes them
+        for _ in 0..600 {
+            let stats = miner.get_stats().await;
+            if stats.threads.iter().all(|thread| thread.ranges_completed > 0) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let ranges = miner.job_manager().get_stats().await.ranges_dispatched;
+        miner.stop().await.unwrap();
+
+        let stats = miner.get_stats().await;
+        assert!(stats.threads.iter().all(|thread| thread.ranges_completed > 0));
+        assert_eq!(stats.c0dl3_blocks, 0);
+        // No range is hashed twice: each completed range accounts for exactly 4 nonces
+        assert!(stats.total_hashes <= ranges * 4);
+        assert!(stats.total_hashes >= stats.ranges_completed * 4);
+
+        // Nothing keeps hashing after stop
+        let total = stats.total_hashes;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(miner.get_stats().await.total_hashes, total);
+    }
+}
@@ -0,0 +1,304 @@
+//! PPLNS ("pay per last N shares") accounting for pools running the Stratum server.
+//! Each block's reward, less the pool fee, is split over the most recent shares worth
+//! `window` difficulty in total, in proportion to the difficulty of each share.
+
+use crate::error::MiningError;
+use crate::work::FoundBlock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+
+/// PPLNS configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PplnsConfig {
+    /// Total share difficulty each block reward is spread over (the "N")
+    pub window: u64,
+    /// Pool fee, in basis points of each block reward
+    pub fee_bps: u64,
+    /// Reward credited for a C0DL3 block; Fuego rewards are read from the coinbase
+    pub c0dl3_block_reward: u64,
+    /// File the ledger is kept in; the ledger lives in memory only when unset
+    pub ledger_path: Option<PathBuf>,
+    /// Block payouts kept in the history
+    pub max_history: usize,
+}
+
+impl Default for PplnsConfig {
+    fn default() -> Self {
+        Self {
+            window: 10_000_000,
+            fee_bps: 100,
+            c0dl3_block_reward: 0,
+            ledger_path: None,
+            max_history: 1000,
+        }
+    }
+}
+
+/// Chain a pool block was found on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayoutChain {
+    Fuego,
+    C0dl3,
+}
+
+/// An accepted share, weighted by its difficulty
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PplnsShare {
+    /// Payout account: the login the worker authenticated with
+    pub account: String,
+    pub difficulty: u64,
+    pub timestamp: u64,
+}
+
+/// Rewards credited for one pool block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockPayout {
+    pub chain: PayoutChain,
+    pub height: u64,
+    /// Account whose share solved the block
+    pub found_by: String,
+    pub reward: u64,
+    /// Pool fee, including rounding dust from the split
+    pub fee: u64,
+    /// Share difficulty the reward was split over
+    pub window_difficulty: u64,
+    /// Amount credited to each account
+    pub credits: BTreeMap<String, u64>,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LedgerState {
+    shares: VecDeque<PplnsShare>,
+    payouts: VecDeque<BlockPayout>,
+    balances: BTreeMap<String, u64>,
+    pool_fees: u64,
+}
+
+/// Share window, payout history and account balances of a pool
+#[derive(Debug, Clone)]
+pub struct PplnsLedger {
+    config: PplnsConfig,
+    state: LedgerState,
+    /// Total difficulty of `state.shares`
+    share_difficulty: u64,
+}
+
+impl PplnsLedger {
+    /// Open the ledger at `config.ledger_path`, starting empty if there is none yet
+    pub fn open(config: PplnsConfig) -> Result<Self, MiningError> {
+        if config.window == 0 {
+            return Err(MiningError::LedgerError("PPLNS window must be positive".to_string()));
+        }
+        if config.fee_bps > 10_000 {
+            return Err(MiningError::LedgerError("Pool fee cannot exceed the block reward".to_string()));
+        }
+        let state = match &config.ledger_path {
+            Some(path) if path.exists() => {
+                let bytes = std::fs::read(path).map_err(|e| MiningError::LedgerError(e.to_string()))?;
+                serde_json::from_slice(&bytes).map_err(|e| MiningError::LedgerError(e.to_string()))?
+            }
+            _ => LedgerState::default(),
+        };
+        let share_difficulty = state.shares.iter().map(|share| share.difficulty).sum();
+        Ok(Self {
+            config,
+            state,
+            share_difficulty,
+        })
+    }
+
+    /// Record an accepted share of `difficulty` for `account`
+    pub fn record_share(&mut self, account: &str, difficulty: u64) {
+        self.state.shares.push_back(PplnsShare {
+            account: account.to_string(),
+            difficulty,
+            timestamp: unix_time(),
+        });
+        self.share_difficulty += difficulty;
+
+        // Keep just enough shares to fill the window
+        while let Some(oldest) = self.state.shares.front() {
+            if self.share_difficulty - oldest.difficulty < self.config.window {
+                break;
+            }
+            self.share_difficulty -= oldest.difficulty;
+            self.state.shares.pop_front();
+        }
+    }
+
+    /// Split the reward of a block found by `found_by` over the share window and persist the ledger
+    pub fn record_block(&mut self, block: &FoundBlock, found_by: &str) -> Result<BlockPayout, MiningError> {
+        let (chain, height, reward) = match block {
+            FoundBlock::Fuego { height, coinbase_tx, .. } => {
+                let coinbase = fuego_types::Transaction::parse(coinbase_tx)?;
+                let reward = coinbase.prefix.outputs.iter().fold(0u64, |sum, output| sum.saturating_add(output.amount));
+                (PayoutChain::Fuego, *height, reward)
+            }
+            FoundBlock::C0dl3 { height, .. } => (PayoutChain::C0dl3, *height, self.config.c0dl3_block_reward),
+        };
+        let mut fee = (reward as u128 * self.config.fee_bps as u128 / 10_000) as u64;
+        let shared = reward - fee;
+
+        // Newest shares first; the oldest counted share may only partly fit the window
+        let mut weights: BTreeMap<String, u64> = BTreeMap::new();
+        let mut window_difficulty = 0u64;
+        for share in self.state.shares.iter().rev() {
+            let weight = share.difficulty.min(self.config.window - window_difficulty);
+            *weights.entry(share.account.clone()).or_default() += weight;
+            window_difficulty += weight;
+            if window_difficulty == self.config.window {
+                break;
+            }
+        }
+        if window_difficulty == 0 {
+            weights.insert(found_by.to_string(), 1);
+            window_difficulty = 1;
+        }
+
+        let mut credits = BTreeMap::new();
+        let mut credited = 0u64;
+        for (account, weight) in weights {
+            let amount = (shared as u128 * weight as u128 / window_difficulty as u128) as u64;
+            if amount > 0 {
+                *self.state.balances.entry(account.clone()).or_default() += amount;
+                credits.insert(account, amount);
+                credited += amount;
+            }
+        }
+        fee += shared - credited;
+        self.state.pool_fees += fee;
+
+        let payout = BlockPayout {
+            chain,
+            height,
+            found_by: found_by.to_string(),
+            reward,
+            fee,
+            window_difficulty,
+            credits,
+            timestamp: unix_time(),
+        };
+        println!("Pool block {:?} {} credited {} to {} accounts", chain, height, reward - fee, payout.credits.len());
+        self.state.payouts.push_back(payout.clone());
+        while self.state.payouts.len() > self.config.max_history {
+            self.state.payouts.pop_front();
+        }
+        self.save()?;
+        Ok(payout)
+    }
+
+    /// Write the ledger to `config.ledger_path`, replacing the previous copy at once
+    pub fn save(&self) -> Result<(), MiningError> {
+        let Some(path) = &self.config.ledger_path else {
+            return Ok(());
+        };
+        let bytes = serde_json::to_vec(&self.state).map_err(|e| MiningError::LedgerError(e.to_string()))?;
+        let staging = path.with_extension("tmp");
+        std::fs::write(&staging, bytes).map_err(|e| MiningError::LedgerError(e.to_string()))?;
+        std::fs::rename(&staging, path).map_err(|e| MiningError::LedgerError(e.to_string()))
+    }
+
+    /// Most recent block payouts, newest first
+    pub fn payouts(&self, limit: usize) -> Vec<BlockPayout> {
+        self.state.payouts.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Total credited to each account
+    pub fn balances(&self) -> &BTreeMap<String, u64> {
+        &self.state.balances
+    }
+
+    /// Total kept by the pool as fees
+    pub fn pool_fees(&self) -> u64 {
+        self.state.pool_fees
+    }
+
+    /// Shares currently in the window and their total difficulty
+    pub fn window(&self) -> (usize, u64) {
+        (self.state.shares.len(), self.share_difficulty)
+    }
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pow::auxpow::bare_coinbase;
+    use pow::{AuxPow, MergeMinedProof, ParentBlockHeader};
+
+    fn c0dl3_block(height: u64) -> FoundBlock {
+        let mut parent_header = ParentBlockHeader::default();
+        let aux_pow = AuxPow::create([1u8; 32], bare_coinbase(1), &[], &mut parent_header).unwrap();
+        FoundBlock::C0dl3 {
+            job_id: "1".to_string(),
+            height,
+            aux_hash: [1u8; 32],
+            proof: MergeMinedProof { parent_header, aux_pow },
+            pow_hash: [0u8; 32],
+        }
+    }
+
+    fn test_config() -> PplnsConfig {
+        PplnsConfig {
+            window: 100,
+            fee_bps: 1000,
+            c0dl3_block_reward: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reward_is_split_over_the_last_shares() {
+        let mut ledger = PplnsLedger::open(test_config()).unwrap();
+        ledger.record_share("old", 50);
+        ledger.record_share("alice", 40);
+        ledger.record_share("bob", 20);
+        ledger.record_share("alice", 30);
+        // Only 10 of the oldest share's 50 still fit the window
+        assert_eq!(ledger.window(), (4, 140));
+
+        let payout = ledger.record_block(&c0dl3_block(7), "bob").unwrap();
+        assert_eq!((payout.chain, payout.height, payout.reward), (PayoutChain::C0dl3, 7, 1000));
+        assert_eq!(payout.window_difficulty, 100);
+        // 900 after the fee, split 70:20:10
+        assert_eq!(payout.credits["alice"], 630);
+        assert_eq!(payout.credits["bob"], 180);
+        assert_eq!(payout.credits["old"], 90);
+        assert_eq!(payout.fee, 100);
+
+        ledger.record_share("carol", 100);
+        let payout = ledger.record_block(&c0dl3_block(8), "carol").unwrap();
+        assert_eq!(payout.credits.keys().collect::<Vec<_>>(), vec!["carol"]);
+        assert_eq!(ledger.balances()["carol"], 900);
+        assert_eq!(ledger.pool_fees(), 200);
+        assert_eq!(ledger.payouts(1)[0].height, 8);
+    }
+
+    #[test]
+    fn test_ledger_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PplnsConfig {
+            ledger_path: Some(dir.path().join("pplns.json")),
+            ..test_config()
+        };
+        let mut ledger = PplnsLedger::open(config.clone()).unwrap();
+        ledger.record_share("alice", 30);
+        ledger.record_block(&c0dl3_block(3), "alice").unwrap();
+        ledger.record_share("bob", 10);
+        ledger.save().unwrap();
+
+        let reopened = PplnsLedger::open(config).unwrap();
+        assert_eq!(reopened.balances(), ledger.balances());
+        assert_eq!(reopened.payouts(10), ledger.payouts(10));
+        assert_eq!(reopened.window(), (2, 40));
+    }
+}
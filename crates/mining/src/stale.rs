@@ -0,0 +1,139 @@
+use pow::auxpow::Hash;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// A locally mined block that lost to a competing block at the same height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaleBlock {
+    pub height: u64,
+    /// Hash of our block, hex encoded
+    pub hash: String,
+    /// Hash of the block that won the height, hex encoded
+    pub competing_hash: String,
+    /// Unix time the block was found to be stale
+    pub detected_at: u64,
+}
+
+/// Tracks locally mined blocks until the canonical chain decides their height
+#[derive(Debug, Clone)]
+pub struct StaleTracker {
+    pending: BTreeMap<u64, Vec<Hash>>,
+    recent: VecDeque<StaleBlock>,
+    stale_blocks: u64,
+    accepted_blocks: u64,
+    max_recent: usize,
+    /// Pending blocks this far below the newest canonical height are forgotten
+    max_pending_depth: u64,
+}
+
+impl StaleTracker {
+    /// Create a tracker remembering the `max_recent` latest stale blocks
+    pub fn new(max_recent: usize) -> Self {
+        Self {
+            pending: BTreeMap::new(),
+            recent: VecDeque::new(),
+            stale_blocks: 0,
+            accepted_blocks: 0,
+            max_recent,
+            max_pending_depth: 100,
+        }
+    }
+
+    /// Record a block mined locally
+    pub fn record_mined(&mut self, height: u64, hash: Hash) {
+        let hashes = self.pending.entry(height).or_default();
+        if !hashes.contains(&hash) {
+            hashes.push(hash);
+        }
+    }
+
+    /// Record the canonical block at `height`, returning local blocks it made stale
+    pub fn record_canonical(&mut self, height: u64, canonical_hash: Hash) -> Vec<StaleBlock> {
+        let mut stale = Vec::new();
+        for hash in self.pending.remove(&height).unwrap_or_default() {
+            if hash == canonical_hash {
+                self.accepted_blocks += 1;
+                continue;
+            }
+            let block = StaleBlock {
+                height,
+                hash: hex::encode(hash),
+                competing_hash: hex::encode(canonical_hash),
+                detected_at: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            };
+            println!("Mined block {} at height {} went stale", block.hash, height);
+            self.stale_blocks += 1;
+            self.recent.push_back(block.clone());
+            while self.recent.len() > self.max_recent {
+                self.recent.pop_front();
+            }
+            stale.push(block);
+        }
+
+        // Forget blocks whose height was never reported
+        let horizon = height.saturating_sub(self.max_pending_depth);
+        self.pending = self.pending.split_off(&horizon);
+        stale
+    }
+
+    /// Most recent stale blocks, newest first
+    pub fn recent(&self, limit: usize) -> Vec<StaleBlock> {
+        self.recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Total blocks that went stale
+    pub fn stale_blocks(&self) -> u64 {
+        self.stale_blocks
+    }
+
+    /// Total mined blocks that made it into the canonical chain
+    pub fn accepted_blocks(&self) -> u64 {
+        self.accepted_blocks
+    }
+
+    /// Mined blocks whose height has not been decided yet
+    pub fn pending_blocks(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+}
+
+impl Default for StaleTracker {
+    fn default() -> Self {
+        Self::new(50)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_tracking() {
+        let mut tracker = StaleTracker::new(2);
+        tracker.record_mined(10, [1u8; 32]);
+        tracker.record_mined(11, [2u8; 32]);
+        tracker.record_mined(12, [3u8; 32]);
+        assert_eq!(tracker.pending_blocks(), 3);
+
+        assert!(tracker.record_canonical(10, [1u8; 32]).is_empty());
+        let stale = tracker.record_canonical(11, [9u8; 32]);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].hash, hex::encode([2u8; 32]));
+        assert_eq!(stale[0].competing_hash, hex::encode([9u8; 32]));
+        tracker.record_canonical(12, [8u8; 32]);
+
+        assert_eq!(tracker.accepted_blocks(), 1);
+        assert_eq!(tracker.stale_blocks(), 2);
+        let recent = tracker.recent(10);
+        assert_eq!(recent.iter().map(|block| block.height).collect::<Vec<_>>(), vec![12, 11]);
+
+        // Old undecided blocks are eventually dropped
+        tracker.record_mined(13, [4u8; 32]);
+        tracker.record_canonical(500, [0u8; 32]);
+        assert_eq!(tracker.pending_blocks(), 0);
+        assert_eq!(tracker.stale_blocks(), 2);
+    }
+}
@@ -0,0 +1,778 @@
+//! CryptoNote-style Stratum server (the `login`/`submit`/`job` protocol spoken by xmrig
+//! and friends) serving merged C0DL3 + Fuego work to external miners.
+
+use crate::error::MiningError;
+use crate::pplns::{PplnsConfig, PplnsLedger};
+use crate::work::{FoundBlock, MergedJob, MergedWork};
+use futures::{SinkExt, StreamExt};
+use pow::check_hash;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tokio_util::codec::{Framed, LinesCodec};
+
+/// Stratum server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StratumConfig {
+    pub listen_addr: String,
+    /// Difficulty of shares requested from workers
+    pub share_difficulty: u64,
+    pub max_workers: usize,
+    /// Window over which worker hashrate is estimated
+    pub hashrate_window: Duration,
+    /// Number of recent jobs still accepting shares
+    pub job_history: usize,
+    pub max_line_length: usize,
+    /// PPLNS payout accounting, for servers run as a pool
+    pub pplns: Option<PplnsConfig>,
+}
+
+impl Default for StratumConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: "0.0.0.0:3333".to_string(),
+            share_difficulty: 5000,
+            max_workers: 1024,
+            hashrate_window: Duration::from_secs(600),
+            job_history: 4,
+            max_line_length: 8192,
+            pplns: None,
+        }
+    }
+}
+
+/// Per-worker share accounting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkerStats {
+    pub worker_id: String,
+    pub login: String,
+    pub agent: String,
+    pub address: String,
+    pub accepted_shares: u64,
+    pub rejected_shares: u64,
+    pub stale_shares: u64,
+    pub fuego_blocks: u64,
+    pub c0dl3_blocks: u64,
+    /// Estimated hashes per second
+    pub hashrate: u64,
+    pub connected_at: u64,
+    pub last_share: Option<u64>,
+    #[serde(skip)]
+    share_log: VecDeque<(Instant, u64)>,
+    #[serde(skip)]
+    connected: Option<Instant>,
+}
+
+impl WorkerStats {
+    fn record_share(&mut self, difficulty: u64, window: Duration) {
+        self.accepted_shares += 1;
+        self.last_share = Some(unix_time());
+        self.share_log.push_back((Instant::now(), difficulty));
+        self.refresh_hashrate(window);
+    }
+
+    /// Each accepted share represents `difficulty` hashes on average
+    fn refresh_hashrate(&mut self, window: Duration) {
+        while self.share_log.front().is_some_and(|(at, _)| at.elapsed() > window) {
+            self.share_log.pop_front();
+        }
+        let elapsed = self
+            .connected
+            .map_or(window, |connected| connected.elapsed().min(window))
+            .as_secs()
+            .max(1);
+        let work: u64 = self.share_log.iter().map(|(_, difficulty)| difficulty).sum();
+        self.hashrate = work / elapsed;
+    }
+}
+
+/// Stratum server statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StratumStats {
+    pub connected_workers: usize,
+    pub accepted_shares: u64,
+    pub rejected_shares: u64,
+    pub fuego_blocks: u64,
+    pub c0dl3_blocks: u64,
+    pub current_job: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StratumRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct LoginParams {
+    login: String,
+    #[serde(default)]
+    agent: String,
+}
+
+#[derive(Deserialize)]
+struct SubmitParams {
+    id: String,
+    job_id: String,
+    nonce: String,
+}
+
+/// A logged in connection. Its extra nonce gives it a parent block no other session mines.
+struct Session {
+    worker_id: String,
+    extra_nonce: u64,
+}
+
+struct StratumState {
+    config: StratumConfig,
+    jobs: RwLock<VecDeque<Arc<MergedJob>>>,
+    /// Worker and nonce of every share submitted to each job
+    submitted: RwLock<HashMap<String, HashSet<(String, u32)>>>,
+    workers: Arc<RwLock<HashMap<String, WorkerStats>>>,
+    stats: RwLock<StratumStats>,
+    job_tx: broadcast::Sender<Arc<MergedJob>>,
+    block_tx: broadcast::Sender<FoundBlock>,
+    pplns: Option<Arc<RwLock<PplnsLedger>>>,
+    next_worker_id: AtomicU64,
+    next_job_id: AtomicU64,
+}
+
+/// Stratum server for external merge-miners
+pub struct StratumServer {
+    state: Arc<StratumState>,
+    shutdown: Option<watch::Sender<bool>>,
+    local_addr: Option<SocketAddr>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl StratumServer {
+    /// Create a new Stratum server
+    pub fn new(config: StratumConfig) -> Result<Self, MiningError> {
+        if config.share_difficulty == 0 {
+            return Err(MiningError::StratumError("Share difficulty must be positive".to_string()));
+        }
+        let (job_tx, _) = broadcast::channel(16);
+        let (block_tx, _) = broadcast::channel(64);
+        let pplns = config
+            .pplns
+            .clone()
+            .map(|pplns| PplnsLedger::open(pplns).map(|ledger| Arc::new(RwLock::new(ledger))))
+            .transpose()?;
+
+        Ok(Self {
+            state: Arc::new(StratumState {
+                config,
+                jobs: RwLock::new(VecDeque::new()),
+                submitted: RwLock::new(HashMap::new()),
+                workers: Arc::new(RwLock::new(HashMap::new())),
+                stats: RwLock::new(StratumStats::default()),
+                job_tx,
+                block_tx,
+                pplns,
+                next_worker_id: AtomicU64::new(1),
+                next_job_id: AtomicU64::new(1),
+            }),
+            shutdown: None,
+            local_addr: None,
+            task: None,
+        })
+    }
+
+    /// Bind the listener and start accepting miners
+    pub async fn start(&mut self) -> Result<(), MiningError> {
+        let listener = TcpListener::bind(&self.state.config.listen_addr)
+            .await
+            .map_err(|e| MiningError::StratumError(format!("Failed to bind {}: {}", self.state.config.listen_addr, e)))?;
+        self.local_addr = listener.local_addr().ok();
+        println!("Stratum server listening on {:?}", self.local_addr);
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        self.shutdown = Some(shutdown_tx);
+        let state = self.state.clone();
+        self.task = Some(tokio::spawn(Self::accept_loop(state, listener, shutdown_rx)));
+        Ok(())
+    }
+
+    /// Stop the server and disconnect all workers
+    pub async fn stop(&mut self) -> Result<(), MiningError> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(true);
+        }
+        if let Some(task) = self.task.take() {
+            task.await.map_err(|e| MiningError::StratumError(e.to_string()))?;
+        }
+        // Shares since the last block would otherwise be lost with the window
+        if let Some(ledger) = &self.state.pplns {
+            ledger.read().await.save()?;
+        }
+        Ok(())
+    }
+
+    /// Publish new merged work to all workers. Returns the job id. The work must reserve an
+    /// extra nonce in its coinbase for the sessions to fill.
+    pub async fn set_work(&self, work: MergedWork) -> Result<String, MiningError> {
+        let job_id = self.state.next_job_id.fetch_add(1, Ordering::Relaxed).to_string();
+        self.set_job(Arc::new(MergedJob::new(job_id.clone(), work)?)).await?;
+        Ok(job_id)
+    }
+
+    /// Publish a job built elsewhere, e.g. by a job manager, keeping its id so the blocks
+    /// found on it can be sealed by whoever built it
+    pub async fn set_job(&self, job: Arc<MergedJob>) -> Result<(), MiningError> {
+        job.with_extra_nonce(0)?;
+        let job_id = job.job_id.clone();
+
+        {
+            let mut jobs = self.state.jobs.write().await;
+            let mut submitted = self.state.submitted.write().await;
+            jobs.push_back(job.clone());
+            submitted.insert(job_id.clone(), HashSet::new());
+            while jobs.len() > self.state.config.job_history.max(1) {
+                if let Some(old) = jobs.pop_front() {
+                    submitted.remove(&old.job_id);
+                }
+            }
+        }
+        self.state.stats.write().await.current_job = Some(job_id);
+
+        let _ = self.state.job_tx.send(job);
+        Ok(())
+    }
+
+    /// Receive blocks solved by worker shares
+    pub fn subscribe_blocks(&self) -> broadcast::Receiver<FoundBlock> {
+        self.state.block_tx.subscribe()
+    }
+
+    /// Shared per-worker statistics, for the RPC layer
+    pub fn worker_stats(&self) -> Arc<RwLock<HashMap<String, WorkerStats>>> {
+        self.state.workers.clone()
+    }
+
+    /// PPLNS ledger, when the server runs as a pool
+    pub fn pplns_ledger(&self) -> Option<Arc<RwLock<PplnsLedger>>> {
+        self.state.pplns.clone()
+    }
+
+    /// Get statistics for all connected workers
+    pub async fn get_workers(&self) -> Vec<WorkerStats> {
+        let mut workers = self.state.workers.write().await;
+        workers
+            .values_mut()
+            .map(|worker| {
+                worker.refresh_hashrate(self.state.config.hashrate_window);
+                worker.clone()
+            })
+            .collect()
+    }
+
+    /// Get server statistics
+    pub async fn get_stats(&self) -> StratumStats {
+        let mut stats = self.state.stats.read().await.clone();
+        stats.connected_workers = self.state.workers.read().await.len();
+        stats
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    async fn accept_loop(state: Arc<StratumState>, listener: TcpListener, mut shutdown: watch::Receiver<bool>) {
+        let mut connections = Vec::new();
+        let mut refresh = tokio::time::interval(Duration::from_secs(10));
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, addr)) => {
+                        let state = state.clone();
+                        let shutdown = shutdown.clone();
+                        connections.push(tokio::spawn(Self::handle_connection(state, stream, addr, shutdown)));
+                        connections.retain(|task: &JoinHandle<()>| !task.is_finished());
+                    }
+                    Err(e) => println!("Stratum accept error: {}", e),
+                },
+                _ = refresh.tick() => {
+                    let mut workers = state.workers.write().await;
+                    for worker in workers.values_mut() {
+                        worker.refresh_hashrate(state.config.hashrate_window);
+                    }
+                }
+                _ = shutdown.changed() => break,
+            }
+        }
+
+        for connection in connections {
+            let _ = connection.await;
+        }
+    }
+
+    async fn handle_connection(
+        state: Arc<StratumState>,
+        stream: TcpStream,
+        addr: SocketAddr,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut framed = Framed::new(stream, LinesCodec::new_with_max_length(state.config.max_line_length));
+        let mut jobs = state.job_tx.subscribe();
+        let mut session: Option<Session> = None;
+
+        loop {
+            let message = tokio::select! {
+                line = framed.next() => match line {
+                    Some(Ok(line)) => Some(Self::handle_request(&state, &line, &mut session, addr).await),
+                    _ => break,
+                },
+                job = jobs.recv() => match (job, &session) {
+                    (Ok(job), Some(session)) => Self::job_json(&state, &job, session).ok().map(|params| json!({
+                        "jsonrpc": "2.0",
+                        "method": "job",
+                        "params": params,
+                    })),
+                    (Ok(_), None) | (Err(broadcast::error::RecvError::Lagged(_)), _) => None,
+                    (Err(broadcast::error::RecvError::Closed), _) => break,
+                },
+                _ = shutdown.changed() => break,
+            };
+
+            if let Some(message) = message {
+                if framed.send(message.to_string()).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        if let Some(session) = session {
+            state.workers.write().await.remove(&session.worker_id);
+        }
+    }
+
+    async fn handle_request(state: &StratumState, line: &str, session: &mut Option<Session>, addr: SocketAddr) -> Value {
+        let request: StratumRequest = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Self::error_response(Value::Null, &format!("Malformed request: {}", e)),
+        };
+
+        let result = match request.method.as_str() {
+            "login" => Self::login(state, request.params, session, addr).await,
+            "submit" => Self::submit(state, request.params, session.as_ref()).await,
+            "getjob" => match (Self::current_job(state).await, session.as_ref()) {
+                (Some(job), Some(session)) => Self::job_json(state, &job, session),
+                (_, None) => Err(MiningError::StratumError("Unauthenticated".to_string())),
+                (None, _) => Err(MiningError::JobNotFound("No work available".to_string())),
+            },
+            "keepalived" => Ok(json!({ "status": "KEEPALIVED" })),
+            method => Err(MiningError::StratumError(format!("Unknown method {}", method))),
+        };
+
+        match result {
+            Ok(result) => json!({ "id": request.id, "jsonrpc": "2.0", "error": null, "result": result }),
+            Err(e) => Self::error_response(request.id, &e.to_string()),
+        }
+    }
+
+    async fn login(
+        state: &StratumState,
+        params: Value,
+        session: &mut Option<Session>,
+        addr: SocketAddr,
+    ) -> Result<Value, MiningError> {
+        let params: LoginParams =
+            serde_json::from_value(params).map_err(|e| MiningError::StratumError(format!("Invalid login: {}", e)))?;
+
+        let session = match session {
+            Some(session) => session,
+            None => {
+                let mut workers = state.workers.write().await;
+                if workers.len() >= state.config.max_workers {
+                    return Err(MiningError::StratumError("Too many workers".to_string()));
+                }
+                // Worker ids are never reused, so they double as the sessions' extra nonces
+                let extra_nonce = state.next_worker_id.fetch_add(1, Ordering::Relaxed);
+                let id = extra_nonce.to_string();
+                workers.insert(
+                    id.clone(),
+                    WorkerStats {
+                        worker_id: id.clone(),
+                        login: params.login,
+                        agent: params.agent,
+                        address: addr.to_string(),
+                        connected_at: unix_time(),
+                        connected: Some(Instant::now()),
+                        ..Default::default()
+                    },
+                );
+                session.insert(Session { worker_id: id, extra_nonce })
+            }
+        };
+
+        let job = match Self::current_job(state).await {
+            Some(job) => Some(Self::job_json(state, &job, session)?),
+            None => None,
+        };
+        Ok(json!({ "id": session.worker_id, "job": job, "status": "OK", "extensions": [] }))
+    }
+
+    async fn submit(state: &StratumState, params: Value, session: Option<&Session>) -> Result<Value, MiningError> {
+        let session = session.ok_or_else(|| MiningError::StratumError("Unauthenticated".to_string()))?;
+        let worker_id = session.worker_id.as_str();
+        let params: SubmitParams =
+            serde_json::from_value(params).map_err(|e| MiningError::InvalidShare(format!("Invalid submit: {}", e)))?;
+        if params.id != worker_id {
+            return Err(MiningError::StratumError("Unauthenticated".to_string()));
+        }
+
+        let result = Self::process_share(state, session, &params.job_id, &params.nonce).await;
+
+        let mut workers = state.workers.write().await;
+        let mut stats = state.stats.write().await;
+        let worker = workers
+            .get_mut(worker_id)
+            .ok_or_else(|| MiningError::StratumError("Unknown worker".to_string()))?;
+        match &result {
+            Ok((difficulty, found)) => {
+                worker.record_share(*difficulty, state.config.hashrate_window);
+                stats.accepted_shares += 1;
+                for block in found {
+                    match block {
+                        FoundBlock::Fuego { height, .. } => {
+                            println!("Worker {} found Fuego block {}", worker_id, height);
+                            worker.fuego_blocks += 1;
+                            stats.fuego_blocks += 1;
+                        }
+                        FoundBlock::C0dl3 { height, .. } => {
+                            println!("Worker {} found C0DL3 block {}", worker_id, height);
+                            worker.c0dl3_blocks += 1;
+                            stats.c0dl3_blocks += 1;
+                        }
+                    }
+                    let _ = state.block_tx.send(block.clone());
+                }
+                if let Some(ledger) = &state.pplns {
+                    let mut ledger = ledger.write().await;
+                    ledger.record_share(&worker.login, *difficulty);
+                    for block in found {
+                        if let Err(e) = ledger.record_block(block, &worker.login) {
+                            println!("Failed to credit pool block: {}", e);
+                        }
+                    }
+                }
+            }
+            Err(MiningError::JobNotFound(_)) => worker.stale_shares += 1,
+            Err(_) => {
+                worker.rejected_shares += 1;
+                stats.rejected_shares += 1;
+            }
+        }
+
+        result.map(|_| json!({ "status": "OK" }))
+    }
+
+    /// Validate a share against the session's own parent block, returning its difficulty and
+    /// any blocks it solves
+    async fn process_share(
+        state: &StratumState,
+        session: &Session,
+        job_id: &str,
+        nonce: &str,
+    ) -> Result<(u64, Vec<FoundBlock>), MiningError> {
+        let nonce_bytes: [u8; 4] = hex::decode(nonce)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| MiningError::InvalidShare("Malformed nonce".to_string()))?;
+        let nonce = u32::from_le_bytes(nonce_bytes);
+
+        let job = state
+            .jobs
+            .read()
+            .await
+            .iter()
+            .find(|job| job.job_id == job_id)
+            .ok_or_else(|| MiningError::JobNotFound("Block expired".to_string()))?
+            .with_extra_nonce(session.extra_nonce)?;
+
+        {
+            let mut submitted = state.submitted.write().await;
+            let shares = submitted
+                .get_mut(job_id)
+                .ok_or_else(|| MiningError::JobNotFound("Block expired".to_string()))?;
+            if !shares.insert((session.worker_id.clone(), nonce)) {
+                return Err(MiningError::InvalidShare("Duplicate share".to_string()));
+            }
+        }
+
+        let job = Arc::new(job);
+        let hash_job = job.clone();
+        let pow_hash = tokio::task::spawn_blocking(move || hash_job.pow_hash(nonce))
+            .await
+            .map_err(|e| MiningError::StratumError(e.to_string()))?;
+
+        let difficulty = job.share_difficulty(state.config.share_difficulty);
+        if !check_hash(&pow_hash, difficulty) {
+            return Err(MiningError::InvalidShare("Low difficulty share".to_string()));
+        }
+
+        Ok((difficulty, job.found_blocks(nonce, &pow_hash)))
+    }
+
+    async fn current_job(state: &StratumState) -> Option<Arc<MergedJob>> {
+        state.jobs.read().await.back().cloned()
+    }
+
+    /// `job` as sent to `session`, hashing the session's own parent block
+    fn job_json(state: &StratumState, job: &MergedJob, session: &Session) -> Result<Value, MiningError> {
+        let job = job.with_extra_nonce(session.extra_nonce)?;
+        Ok(json!({
+            "blob": hex::encode(job.hashing_blob(0)),
+            "job_id": job.job_id,
+            "target": target_hex(job.share_difficulty(state.config.share_difficulty)),
+            "algo": "cn/upx2",
+            "height": job.work.fuego_height,
+        }))
+    }
+
+    fn error_response(id: Value, message: &str) -> Value {
+        json!({ "id": id, "jsonrpc": "2.0", "error": { "code": -1, "message": message } })
+    }
+}
+
+/// Compact 32-bit little-endian share target understood by CryptoNote miners
+pub fn target_hex(difficulty: u64) -> String {
+    let target = (u32::MAX as u64 / difficulty.max(1)) as u32;
+    hex::encode(target.to_le_bytes())
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::work::EXTRA_NONCE_SIZE;
+    use pow::auxpow::{bare_coinbase, write_varint};
+    use pow::ParentBlockHeader;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    fn test_work(difficulty: u64) -> MergedWork {
+        // `bare_coinbase` with an extra nonce reserved ahead of the room for the tag
+        let mut coinbase_tx = bare_coinbase(1);
+        let tag_len = coinbase_tx.pop().unwrap() as usize;
+        write_varint(&mut coinbase_tx, (2 + EXTRA_NONCE_SIZE + tag_len) as u64);
+        coinbase_tx.extend_from_slice(&[0x02, EXTRA_NONCE_SIZE as u8]);
+        let reserved_offset = coinbase_tx.len();
+        coinbase_tx.extend_from_slice(&[0; EXTRA_NONCE_SIZE]);
+
+        MergedWork {
+            parent_header: ParentBlockHeader {
+                major_version: 1,
+                timestamp: 1_700_000_000,
+                ..Default::default()
+            },
+            coinbase_tx,
+            reserved_offset: Some(reserved_offset),
+            other_tx_hashes: vec![],
+            fuego_height: 10,
+            fuego_difficulty: difficulty,
+            aux_hash: [9u8; 32],
+            c0dl3_height: 20,
+            c0dl3_difficulty: difficulty,
+        }
+    }
+
+    struct TestMiner {
+        reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+        writer: tokio::net::tcp::OwnedWriteHalf,
+    }
+
+    impl TestMiner {
+        async fn connect(addr: SocketAddr) -> Self {
+            let (read, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+            Self { reader: BufReader::new(read), writer }
+        }
+
+        async fn call(&mut self, request: Value) -> Value {
+            self.writer.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+            self.read().await
+        }
+
+        async fn read(&mut self) -> Value {
+            let mut line = String::new();
+            tokio::time::timeout(Duration::from_secs(30), self.reader.read_line(&mut line))
+                .await
+                .unwrap()
+                .unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        async fn submit(&mut self, worker_id: &Value, job_id: &Value, nonce: &str) -> Value {
+            self.call(json!({
+                "id": 2, "method": "submit",
+                "params": { "id": worker_id, "job_id": job_id, "nonce": nonce, "result": "" },
+            }))
+            .await
+        }
+    }
+
+    #[test]
+    fn test_target_hex() {
+        assert_eq!(target_hex(1), "ffffffff");
+        assert_eq!(target_hex(0x100), "ffffff00");
+    }
+
+    #[tokio::test]
+    async fn test_stratum_shares_and_blocks() {
+        let config = StratumConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            share_difficulty: 1,
+            pplns: Some(PplnsConfig {
+                c0dl3_block_reward: 1000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut server = StratumServer::new(config).unwrap();
+        server.start().await.unwrap();
+        let mut blocks = server.subscribe_blocks();
+        let first_job = server.set_work(test_work(1)).await.unwrap();
+
+        let mut miner = TestMiner::connect(server.local_addr().unwrap()).await;
+        let login = miner
+            .call(json!({ "id": 1, "method": "login", "params": { "login": "fire", "pass": "x", "agent": "test" } }))
+            .await;
+        let worker_id = login["result"]["id"].clone();
+        let job = login["result"]["job"].clone();
+        assert_eq!(job["job_id"], first_job);
+        assert_eq!(job["target"], "ffffffff");
+
+        // Difficulty 1 makes every share a block on both chains
+        let accepted = miner.submit(&worker_id, &job["job_id"], "00000000").await;
+        assert_eq!(accepted["result"]["status"], "OK");
+        assert!(matches!(blocks.recv().await.unwrap(), FoundBlock::Fuego { height: 10, .. }));
+        match blocks.recv().await.unwrap() {
+            FoundBlock::C0dl3 { proof, aux_hash, .. } => {
+                proof
+                    .aux_pow
+                    .verify_work(&proof.parent_header, &aux_hash, 1, &pow::CryptoNight::upx2())
+                    .unwrap();
+            }
+            other => panic!("unexpected block {:?}", other),
+        }
+
+        let duplicate = miner.submit(&worker_id, &job["job_id"], "00000000").await;
+        assert_eq!(duplicate["error"]["message"], "Invalid share: Duplicate share");
+        let stale = miner.submit(&worker_id, &json!("999"), "01000000").await;
+        assert!(stale["error"]["message"].as_str().unwrap().contains("Block expired"));
+
+        // New work is pushed to logged in workers
+        let second_job = server.set_work(test_work(u64::MAX)).await.unwrap();
+        let notification = miner.read().await;
+        assert_eq!(notification["method"], "job");
+        assert_eq!(notification["params"]["job_id"], second_job);
+
+        let workers = server.get_workers().await;
+        assert_eq!(workers.len(), 1);
+        assert_eq!(workers[0].login, "fire");
+        assert_eq!(workers[0].accepted_shares, 1);
+        assert_eq!(workers[0].rejected_shares, 1);
+        assert_eq!(workers[0].stale_shares, 1);
+        assert_eq!(workers[0].c0dl3_blocks, 1);
+        assert!(workers[0].hashrate > 0);
+
+        let stats = server.get_stats().await;
+        assert_eq!(stats.fuego_blocks, 1);
+        assert_eq!(stats.current_job, Some(second_job));
+
+        // The test coinbase pays nothing, so only the C0DL3 block credits the worker
+        let ledger = server.pplns_ledger().unwrap();
+        {
+            let ledger = ledger.read().await;
+            assert_eq!(ledger.window(), (1, 1));
+            assert_eq!(ledger.balances()["fire"], 990);
+            assert_eq!(ledger.payouts(10).len(), 2);
+        }
+
+        server.stop().await.unwrap();
+        assert!(server.get_workers().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_workers_mine_their_own_parent_blocks() {
+        let config = StratumConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            share_difficulty: 1,
+            ..Default::default()
+        };
+        let mut server = StratumServer::new(config).unwrap();
+        server.start().await.unwrap();
+
+        // Work without a reserved extra nonce would hand every worker the same blob
+        let mut shared = test_work(u64::MAX);
+        shared.reserved_offset = None;
+        assert!(matches!(server.set_work(shared).await, Err(MiningError::InvalidWork(_))));
+        server.set_work(test_work(u64::MAX)).await.unwrap();
+
+        let addr = server.local_addr().unwrap();
+        let mut miners = [TestMiner::connect(addr).await, TestMiner::connect(addr).await];
+        let mut logins = Vec::new();
+        for (miner, login) in miners.iter_mut().zip(["fire", "ice"]) {
+            logins.push(miner.call(json!({ "id": 1, "method": "login", "params": { "login": login } })).await);
+        }
+        let (first, second) = (&logins[0]["result"], &logins[1]["result"]);
+        assert_eq!(first["job"]["job_id"], second["job"]["job_id"]);
+        assert_ne!(first["job"]["blob"], second["job"]["blob"]);
+
+        // The same nonce is a different share for each worker, but only once per worker
+        let job_id = &first["job"]["job_id"];
+        for (miner, login) in miners.iter_mut().zip(&logins) {
+            let accepted = miner.submit(&login["result"]["id"], job_id, "2a000000").await;
+            assert_eq!(accepted["result"]["status"], "OK");
+        }
+        let duplicate = miners[1].submit(&second["id"], job_id, "2a000000").await;
+        assert_eq!(duplicate["error"]["message"], "Invalid share: Duplicate share");
+
+        let stats = server.get_stats().await;
+        assert_eq!((stats.accepted_shares, stats.rejected_shares), (2, 1));
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_low_difficulty_share_rejected() {
+        let config = StratumConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            share_difficulty: u64::MAX,
+            ..Default::default()
+        };
+        let mut server = StratumServer::new(config).unwrap();
+        server.start().await.unwrap();
+        server.set_work(test_work(u64::MAX)).await.unwrap();
+
+        let mut miner = TestMiner::connect(server.local_addr().unwrap()).await;
+        let unauthenticated = miner.submit(&json!("1"), &json!("1"), "00000000").await;
+        assert!(unauthenticated["error"].is_object());
+
+        let login = miner.call(json!({ "id": 1, "method": "login", "params": { "login": "fire" } })).await;
+        let worker_id = login["result"]["id"].clone();
+        let rejected = miner.submit(&worker_id, &login["result"]["job"]["job_id"], "00000000").await;
+        assert_eq!(rejected["error"]["message"], "Invalid share: Low difficulty share");
+        assert_eq!(server.get_stats().await.rejected_shares, 1);
+
+        server.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::MiningError;
+use pow::auxpow::Hash;
+use pow::{check_hash, AuxPow, CryptoNight, MergeMinedProof, ParentBlockHeader};
+use serde::{Deserialize, Serialize};
+
+/// Bytes of the coinbase extra nonce reserved in Fuego templates, which each stratum session
+/// fills with its own extra nonce so no two sessions hash the same parent block
+pub const EXTRA_NONCE_SIZE: usize = 8;
+
+/// Merged C0DL3 + Fuego work supplied by the block producer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedWork {
+    /// Fuego header; `merkle_root`, `tx_count` and `nonce` are filled in when the job is built
+    pub parent_header: ParentBlockHeader,
+    /// Fuego coinbase without the merge mining tag
+    pub coinbase_tx: Vec<u8>,
+    /// Offset in `coinbase_tx` of the `EXTRA_NONCE_SIZE` bytes fuegod reserved, if any
+    pub reserved_offset: Option<usize>,
+    /// Hashes of the other transactions in the Fuego block template
+    pub other_tx_hashes: Vec<Hash>,
+    pub fuego_height: u64,
+    pub fuego_difficulty: u64,
+    /// Hash of the C0DL3 block being merge-mined
+    pub aux_hash: Hash,
+    pub c0dl3_height: u64,
+    pub c0dl3_difficulty: u64,
+}
+
+/// A unit of merged work handed to miners
+#[derive(Debug, Clone)]
+pub struct MergedJob {
+    pub job_id: String,
+    pub work: MergedWork,
+    /// Parent header committing to the tagged coinbase
+    pub parent_header: ParentBlockHeader,
+    pub aux_pow: AuxPow,
+}
+
+/// A block solved by a share
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FoundBlock {
+    /// The share meets Fuego difficulty; submit the parent block to the Fuego daemon
+    Fuego {
+        height: u64,
+        parent_header: ParentBlockHeader,
+        coinbase_tx: Vec<u8>,
+        other_tx_hashes: Vec<Hash>,
+        pow_hash: Hash,
+    },
+    /// The share meets C0DL3 difficulty; the proof goes into the C0DL3 block
+    C0dl3 {
+        /// Job whose template block the proof seals
+        job_id: String,
+        height: u64,
+        aux_hash: Hash,
+        proof: MergeMinedProof,
+        pow_hash: Hash,
+    },
+}
+
+impl MergedJob {
+    /// Commit the C0DL3 block into the Fuego coinbase and build the job
+    pub fn new(job_id: String, work: MergedWork) -> Result<Self, MiningError> {
+        let mut parent_header = work.parent_header.clone();
+        parent_header.nonce = 0;
+        let coinbase_tx = work.coinbase_tx.clone();
+        let aux_pow = AuxPow::create(work.aux_hash, coinbase_tx, &work.other_tx_hashes, &mut parent_header)?;
+
+        Ok(Self {
+            job_id,
+            work,
+            parent_header,
+            aux_pow,
+        })
+    }
+
+    /// The same job with `extra_nonce` written into the coinbase's reserved extra nonce,
+    /// giving a parent block of its own to a stratum session
+    pub fn with_extra_nonce(&self, extra_nonce: u64) -> Result<Self, MiningError> {
+        let mut work = self.work.clone();
+        let reserved = work
+            .reserved_offset
+            .map(|offset| offset..offset + EXTRA_NONCE_SIZE)
+            .filter(|reserved| reserved.end <= work.coinbase_tx.len())
+            .ok_or_else(|| MiningError::InvalidWork("No extra nonce reserved in the coinbase".to_string()))?;
+        work.coinbase_tx[reserved].copy_from_slice(&extra_nonce.to_le_bytes());
+        Self::new(self.job_id.clone(), work)
+    }
+
+    /// Parent header with `nonce` applied
+    pub fn header_with_nonce(&self, nonce: u32) -> ParentBlockHeader {
+        ParentBlockHeader {
+            nonce,
+            ..self.parent_header.clone()
+        }
+    }
+
+    /// Hashing blob miners work on
+    pub fn hashing_blob(&self, nonce: u32) -> Vec<u8> {
+        self.header_with_nonce(nonce).hashing_blob()
+    }
+
+    /// Offset of the 4-byte nonce in the hashing blob, so miners can patch it in place
+    pub fn nonce_offset(&self) -> usize {
+        let blob = self.hashing_blob(0);
+        let mut offset = 0;
+        for _ in 0..3 {
+            // Version and timestamp varints, always well formed in our own blob
+            offset += pow::auxpow::read_varint(&blob[offset..]).map_or(1, |(_, read)| read);
+        }
+        offset + 32
+    }
+
+    /// Share difficulty for this job: never above either chain's difficulty so no block is missed
+    pub fn share_difficulty(&self, requested: u64) -> u64 {
+        requested
+            .min(self.work.fuego_difficulty)
+            .min(self.work.c0dl3_difficulty)
+            .max(1)
+    }
+
+    /// CN-UPX/2 hash of the parent block for `nonce`
+    pub fn pow_hash(&self, nonce: u32) -> Hash {
+        CryptoNight::upx2().digest(&self.hashing_blob(nonce))
+    }
+
+    /// Blocks on either chain solved by `nonce`, given its PoW hash. Each chain's target is
+    /// checked on its own, so a hash meeting only one of them still yields that chain's block.
+    pub fn found_blocks(&self, nonce: u32, pow_hash: &Hash) -> Vec<FoundBlock> {
+        let parent_header = self.header_with_nonce(nonce);
+        let mut found = Vec::new();
+
+        if check_hash(pow_hash, self.work.fuego_difficulty) {
+            found.push(FoundBlock::Fuego {
+                height: self.work.fuego_height,
+                parent_header: parent_header.clone(),
+                coinbase_tx: self.aux_pow.coinbase_tx.clone(),
+                other_tx_hashes: self.work.other_tx_hashes.clone(),
+                pow_hash: *pow_hash,
+            });
+        }
+
+        if check_hash(pow_hash, self.work.c0dl3_difficulty) {
+            // A bad proof must not cost the Fuego block solved by the same hash
+            if let Err(e) = self.aux_pow.verify(&parent_header, &self.work.aux_hash) {
+                println!("Job {} cannot prove C0DL3 block {}: {}", self.job_id, self.work.c0dl3_height, e);
+                return found;
+            }
+            found.push(FoundBlock::C0dl3 {
+                job_id: self.job_id.clone(),
+                height: self.work.c0dl3_height,
+                aux_hash: self.work.aux_hash,
+                proof: MergeMinedProof {
+                    parent_header,
+                    aux_pow: self.aux_pow.clone(),
+                },
+                pow_hash: *pow_hash,
+            });
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pow::auxpow::bare_coinbase;
+
+    fn test_work(fuego_difficulty: u64, c0dl3_difficulty: u64) -> MergedWork {
+        MergedWork {
+            parent_header: ParentBlockHeader {
+                major_version: 1,
+                timestamp: 1_700_000_000,
+                ..Default::default()
+            },
+            coinbase_tx: bare_coinbase(2),
+            reserved_offset: None,
+            other_tx_hashes: vec![[7u8; 32]],
+            fuego_height: 10,
+            fuego_difficulty,
+            aux_hash: [9u8; 32],
+            c0dl3_height: 20,
+            c0dl3_difficulty,
+        }
+    }
+
+    #[test]
+    fn test_found_blocks_verify_on_both_chains() {
+        let job = MergedJob::new("1".to_string(), test_work(1, 1)).unwrap();
+        let hash = job.pow_hash(5);
+        let found = job.found_blocks(5, &hash);
+        assert_eq!(found.len(), 2);
+
+        match &found[1] {
+            FoundBlock::C0dl3 { proof, .. } => {
+                assert_eq!(proof.parent_header.nonce, 5);
+                proof
+                    .aux_pow
+                    .verify_work(&proof.parent_header, &[9u8; 32], 1, &CryptoNight::upx2())
+                    .unwrap();
+            }
+            other => panic!("unexpected block {:?}", other),
+        }
+
+        // Unreachable difficulties find nothing
+        let job = MergedJob::new("2".to_string(), test_work(u64::MAX, u64::MAX)).unwrap();
+        assert!(job.found_blocks(5, &job.pow_hash(5)).is_empty());
+        assert_eq!(job.share_difficulty(1000), 1000);
+
+        let offset = job.nonce_offset();
+        assert_eq!(&job.hashing_blob(0x01020304)[offset..offset + 4], &[4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_hash_meeting_one_target_is_still_used() {
+        let job = MergedJob::new("1".to_string(), test_work(1, u64::MAX)).unwrap();
+        let found = job.found_blocks(5, &job.pow_hash(5));
+        assert!(matches!(found.as_slice(), [FoundBlock::Fuego { height: 10, .. }]));
+
+        let job = MergedJob::new("2".to_string(), test_work(u64::MAX, 1)).unwrap();
+        let found = job.found_blocks(5, &job.pow_hash(5));
+        assert!(matches!(found.as_slice(), [FoundBlock::C0dl3 { height: 20, .. }]));
+
+        // A job whose proof no longer commits to its aux hash keeps the Fuego block
+        let mut job = MergedJob::new("3".to_string(), test_work(1, 1)).unwrap();
+        job.work.aux_hash = [8u8; 32];
+        let found = job.found_blocks(5, &job.pow_hash(5));
+        assert!(matches!(found.as_slice(), [FoundBlock::Fuego { .. }]));
+    }
+}
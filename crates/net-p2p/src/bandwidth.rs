@@ -0,0 +1,286 @@
+//! Per-peer bandwidth accounting and throttling.
+//!
+//! Payload bytes exchanged with each connected peer are counted per protocol. Within
+//! each accounting window a peer may receive at most the upload cap and send at most
+//! the download cap; a peer over either cap is deprioritized for the rest of the window,
+//! and disconnected once it has exceeded a cap for several windows in a row.
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::error::NetworkError;
+
+/// Per-peer caps on the payload bytes exchanged each second
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BandwidthLimits {
+    /// Bytes per second this node sends to a single peer; `None` leaves uploads uncapped
+    pub upload_bytes_per_sec: Option<u64>,
+    /// Bytes per second this node accepts from a single peer; `None` leaves downloads uncapped
+    pub download_bytes_per_sec: Option<u64>,
+    /// Length of the window traffic is checked against the caps over
+    pub window_secs: u64,
+    /// Windows in a row a peer may exceed a cap before it is disconnected
+    pub disconnect_after: u32,
+}
+
+impl Default for BandwidthLimits {
+    fn default() -> Self {
+        Self {
+            upload_bytes_per_sec: None,
+            download_bytes_per_sec: None,
+            window_secs: 10,
+            disconnect_after: 3,
+        }
+    }
+}
+
+impl BandwidthLimits {
+    pub fn validate(&self) -> Result<(), NetworkError> {
+        if self.window_secs == 0 {
+            return Err(NetworkError::ConfigError("bandwidth window must be longer than zero".to_string()));
+        }
+        if self.disconnect_after == 0 {
+            return Err(NetworkError::ConfigError(
+                "peers must be allowed at least one window over the bandwidth caps".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+
+    fn window_cap(&self, bytes_per_sec: Option<u64>) -> Option<u64> {
+        bytes_per_sec.map(|rate| rate.saturating_mul(self.window_secs))
+    }
+}
+
+/// Protocol family traffic is attributed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Gossiped transactions and blocks, and Dandelion stem relays
+    Gossip,
+    /// Compact block relay requests and responses
+    Sync,
+    /// Peer discovery exchanges
+    Dht,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Bytes exchanged with a peer over one protocol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Traffic {
+    pub sent: u64,
+    pub received: u64,
+}
+
+impl Traffic {
+    fn add(&mut self, direction: Direction, bytes: u64) {
+        match direction {
+            Direction::Sent => self.sent = self.sent.saturating_add(bytes),
+            Direction::Received => self.received = self.received.saturating_add(bytes),
+        }
+    }
+}
+
+/// Counters of a connected peer, as reported by `net_peerStats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerStats {
+    pub gossip: Traffic,
+    pub sync: Traffic,
+    pub dht: Traffic,
+    /// Whether the peer exceeded a cap in the current or the last window
+    pub throttled: bool,
+    /// Windows in a row the peer has exceeded a cap
+    pub violations: u32,
+}
+
+impl PeerStats {
+    pub fn total(&self) -> Traffic {
+        let protocols = [self.gossip, self.sync, self.dht];
+        Traffic {
+            sent: protocols.iter().map(|traffic| traffic.sent).sum(),
+            received: protocols.iter().map(|traffic| traffic.received).sum(),
+        }
+    }
+
+    fn protocol_mut(&mut self, protocol: Protocol) -> &mut Traffic {
+        match protocol {
+            Protocol::Gossip => &mut self.gossip,
+            Protocol::Sync => &mut self.sync,
+            Protocol::Dht => &mut self.dht,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerBandwidth {
+    stats: PeerStats,
+    window: Traffic,
+}
+
+/// Bandwidth counters of every connected peer
+#[derive(Debug, Default)]
+pub struct BandwidthTracker {
+    limits: BandwidthLimits,
+    peers: HashMap<PeerId, PeerBandwidth>,
+    /// Peers disconnected for exceeding the caps
+    disconnected: u64,
+}
+
+impl BandwidthTracker {
+    pub fn new(limits: BandwidthLimits) -> Self {
+        Self {
+            limits,
+            peers: HashMap::new(),
+            disconnected: 0,
+        }
+    }
+
+    pub fn limits(&self) -> &BandwidthLimits {
+        &self.limits
+    }
+
+    /// Count `bytes` exchanged with `peer`, throttling it as soon as it passes a cap
+    pub fn record(&mut self, peer: PeerId, protocol: Protocol, direction: Direction, bytes: usize) {
+        let upload_cap = self.limits.window_cap(self.limits.upload_bytes_per_sec);
+        let download_cap = self.limits.window_cap(self.limits.download_bytes_per_sec);
+        let peer = self.peers.entry(peer).or_default();
+        peer.stats.protocol_mut(protocol).add(direction, bytes as u64);
+        peer.window.add(direction, bytes as u64);
+        if Self::over_caps(&peer.window, upload_cap, download_cap) {
+            peer.stats.throttled = true;
+        }
+    }
+
+    /// Close the current window and return the peers to disconnect for exceeding a cap
+    /// too many windows in a row
+    pub fn end_window(&mut self) -> Vec<PeerId> {
+        let upload_cap = self.limits.window_cap(self.limits.upload_bytes_per_sec);
+        let download_cap = self.limits.window_cap(self.limits.download_bytes_per_sec);
+        let mut disconnect = Vec::new();
+        for (peer_id, peer) in &mut self.peers {
+            if Self::over_caps(&peer.window, upload_cap, download_cap) {
+                peer.stats.violations += 1;
+                peer.stats.throttled = true;
+                if peer.stats.violations >= self.limits.disconnect_after {
+                    disconnect.push(*peer_id);
+                }
+            } else {
+                peer.stats.violations = 0;
+                peer.stats.throttled = false;
+            }
+            peer.window = Traffic::default();
+        }
+        self.disconnected += disconnect.len() as u64;
+        disconnect
+    }
+
+    /// Whether `peer` should be passed over while it exceeds a cap
+    pub fn is_throttled(&self, peer: &PeerId) -> bool {
+        self.peers.get(peer).is_some_and(|peer| peer.stats.throttled)
+    }
+
+    /// Forget a peer once its last connection closes
+    pub fn remove(&mut self, peer: &PeerId) {
+        self.peers.remove(peer);
+    }
+
+    /// Counters of every connected peer, the busiest first
+    pub fn stats(&self) -> Vec<(PeerId, PeerStats)> {
+        let mut stats: Vec<(PeerId, PeerStats)> =
+            self.peers.iter().map(|(peer_id, peer)| (*peer_id, peer.stats.clone())).collect();
+        stats.sort_by_key(|(_, stats)| {
+            let total = stats.total();
+            std::cmp::Reverse(total.sent.saturating_add(total.received))
+        });
+        stats
+    }
+
+    /// Peers disconnected for exceeding the caps since the network started
+    pub fn disconnected(&self) -> u64 {
+        self.disconnected
+    }
+
+    fn over_caps(window: &Traffic, upload_cap: Option<u64>, download_cap: Option<u64>) -> bool {
+        upload_cap.is_some_and(|cap| window.sent > cap) || download_cap.is_some_and(|cap| window.received > cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> BandwidthLimits {
+        BandwidthLimits {
+            upload_bytes_per_sec: Some(100),
+            download_bytes_per_sec: Some(50),
+            window_secs: 1,
+            disconnect_after: 2,
+        }
+    }
+
+    #[test]
+    fn test_traffic_is_counted_per_protocol() {
+        let peer = PeerId::random();
+        let mut tracker = BandwidthTracker::new(BandwidthLimits::default());
+        tracker.record(peer, Protocol::Gossip, Direction::Received, 10);
+        tracker.record(peer, Protocol::Sync, Direction::Sent, 20);
+        tracker.record(peer, Protocol::Sync, Direction::Received, 5);
+        tracker.record(peer, Protocol::Dht, Direction::Received, 1);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.len(), 1);
+        let (_, stats) = &stats[0];
+        assert_eq!(stats.gossip, Traffic { sent: 0, received: 10 });
+        assert_eq!(stats.sync, Traffic { sent: 20, received: 5 });
+        assert_eq!(stats.total(), Traffic { sent: 20, received: 16 });
+
+        // Uncapped peers are never throttled
+        assert!(tracker.end_window().is_empty());
+        assert!(!tracker.is_throttled(&peer));
+
+        tracker.remove(&peer);
+        assert!(tracker.stats().is_empty());
+    }
+
+    #[test]
+    fn test_peers_over_the_caps_are_throttled_then_disconnected() {
+        let (heavy, light) = (PeerId::random(), PeerId::random());
+        let mut tracker = BandwidthTracker::new(limits());
+
+        tracker.record(heavy, Protocol::Sync, Direction::Sent, 101);
+        tracker.record(light, Protocol::Gossip, Direction::Received, 50);
+        assert!(tracker.is_throttled(&heavy));
+        assert!(!tracker.is_throttled(&light));
+        assert!(tracker.end_window().is_empty());
+
+        // A quiet window lifts the throttle and resets the violations
+        assert!(tracker.end_window().is_empty());
+        assert!(!tracker.is_throttled(&heavy));
+
+        tracker.record(heavy, Protocol::Gossip, Direction::Received, 51);
+        assert!(tracker.end_window().is_empty());
+        tracker.record(heavy, Protocol::Gossip, Direction::Received, 51);
+        assert_eq!(tracker.end_window(), vec![heavy]);
+        assert_eq!(tracker.disconnected(), 1);
+        assert_eq!(tracker.stats()[0].0, heavy);
+    }
+
+    #[test]
+    fn test_limits_are_validated() {
+        assert!(BandwidthLimits::default().validate().is_ok());
+        assert!(BandwidthLimits { window_secs: 0, ..Default::default() }.validate().is_err());
+        assert!(BandwidthLimits { disconnect_after: 0, ..Default::default() }.validate().is_err());
+    }
+}
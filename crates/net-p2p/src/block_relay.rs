@@ -0,0 +1,331 @@
+//! Compact block relay, after BIP152. Blocks are announced on the block topic as their
+//! header, proof and short transaction ids. A receiver rebuilds the block from its
+//! transaction pool, asks the peer that relayed the announcement for the transactions it
+//! lacks, and downloads the full block from the producer if that does not complete it.
+//! Completed blocks are handed on with the seal their producer announced.
+
+use block_sync::error::BlockSyncError;
+use block_sync::{Block, Canonical, CompactBlock, Transaction};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Request-response protocol fetching missing transactions and full blocks
+pub const BLOCK_RELAY_PROTOCOL: &str = "/c0dl3/blockrelay/1.0.0";
+
+/// Recent blocks kept to answer peers' requests
+const RECENT_BLOCKS: usize = 64;
+
+/// Request sent to a peer that announced a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RelayRequest {
+    /// Transactions of the block at the given positions
+    Transactions { block_hash: [u8; 32], indexes: Vec<u32> },
+    /// The whole block
+    Block { block_hash: [u8; 32] },
+}
+
+/// Answer to a [`RelayRequest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayResponse {
+    /// The requested transactions, in the order asked for
+    Transactions { block_hash: [u8; 32], transactions: Vec<Transaction> },
+    Block(Block),
+    /// The peer does not have the block
+    NotFound { block_hash: [u8; 32] },
+}
+
+impl Canonical for RelayRequest {}
+impl Canonical for RelayResponse {}
+
+/// What the swarm should do after a relay event
+#[derive(Debug)]
+pub enum RelayAction {
+    /// The block is complete, with its announced seal
+    Complete(Block, Vec<u8>),
+    /// Send `request` to the peer
+    Request(PeerId, RelayRequest),
+}
+
+/// Announced block still being assembled
+struct PendingBlock {
+    compact: CompactBlock,
+    slots: Vec<Option<Transaction>>,
+    /// Peer that relayed the announcement, asked for missing transactions
+    relayer: PeerId,
+    /// Peer that produced the block, asked for it in full
+    producer: PeerId,
+    full_block_requested: bool,
+}
+
+/// Blocks being reconstructed and recent blocks served to peers
+#[derive(Default)]
+pub struct BlockRelay {
+    recent: HashMap<[u8; 32], Block>,
+    order: VecDeque<[u8; 32]>,
+    pending: HashMap<[u8; 32], PendingBlock>,
+}
+
+impl BlockRelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep a block this node produced or received so peers can fetch it, and return its
+    /// compact form announcing it under `seal`
+    pub fn insert(&mut self, block: Block, seal: Vec<u8>) -> Result<CompactBlock, BlockSyncError> {
+        let compact = CompactBlock { seal, ..CompactBlock::from_block(&block)? };
+        self.remember(block.header.hash()?, block);
+        Ok(compact)
+    }
+
+    /// Handle an announcement relayed by `relayer` and produced by `producer`, rebuilding
+    /// the block from `pool` where possible
+    pub fn on_announcement(
+        &mut self,
+        relayer: PeerId,
+        producer: PeerId,
+        compact: CompactBlock,
+        pool: &[Transaction],
+    ) -> Result<Option<RelayAction>, BlockSyncError> {
+        let block_hash = compact.header.hash()?;
+        if self.recent.contains_key(&block_hash) || self.pending.contains_key(&block_hash) {
+            return Ok(None);
+        }
+        let slots = compact.match_transactions(pool)?;
+        let mut pending = PendingBlock {
+            compact,
+            slots,
+            relayer,
+            producer,
+            full_block_requested: false,
+        };
+        let action = self.advance(block_hash, &mut pending);
+        if !matches!(action, RelayAction::Complete(..)) {
+            self.pending.insert(block_hash, pending);
+        }
+        Ok(Some(action))
+    }
+
+    /// Handle a peer's answer to one of our requests
+    pub fn on_response(&mut self, response: RelayResponse) -> Option<RelayAction> {
+        match response {
+            RelayResponse::Transactions { block_hash, transactions } => {
+                let mut pending = self.pending.remove(&block_hash)?;
+                let missing = pending.slots.iter_mut().filter(|slot| slot.is_none());
+                for (slot, transaction) in missing.zip(transactions) {
+                    *slot = Some(transaction);
+                }
+                if pending.slots.iter().any(Option::is_none) {
+                    return self.fall_back(block_hash, pending);
+                }
+                let action = self.advance(block_hash, &mut pending);
+                if !matches!(action, RelayAction::Complete(..)) {
+                    self.pending.insert(block_hash, pending);
+                }
+                Some(action)
+            }
+            RelayResponse::Block(block) => {
+                let block_hash = block.header.hash().ok()?;
+                let pending = self.pending.remove(&block_hash)?;
+                self.remember(block_hash, block.clone());
+                Some(RelayAction::Complete(block, pending.compact.seal))
+            }
+            RelayResponse::NotFound { block_hash } => self.on_failure(block_hash),
+        }
+    }
+
+    /// Handle a request for `block_hash` that failed or went unanswered
+    pub fn on_failure(&mut self, block_hash: [u8; 32]) -> Option<RelayAction> {
+        let pending = self.pending.remove(&block_hash)?;
+        self.fall_back(block_hash, pending)
+    }
+
+    /// Answer a peer's request from the recent blocks
+    pub fn on_request(&self, request: &RelayRequest) -> RelayResponse {
+        match request {
+            RelayRequest::Transactions { block_hash, indexes } => {
+                let transactions: Option<Vec<Transaction>> = self.recent.get(block_hash).and_then(|block| {
+                    indexes
+                        .iter()
+                        .map(|index| block.transactions.get(*index as usize).cloned())
+                        .collect()
+                });
+                match transactions {
+                    Some(transactions) => RelayResponse::Transactions {
+                        block_hash: *block_hash,
+                        transactions,
+                    },
+                    None => RelayResponse::NotFound { block_hash: *block_hash },
+                }
+            }
+            RelayRequest::Block { block_hash } => match self.recent.get(block_hash) {
+                Some(block) => RelayResponse::Block(block.clone()),
+                None => RelayResponse::NotFound { block_hash: *block_hash },
+            },
+        }
+    }
+
+    /// Blocks still being assembled
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Complete the block if every transaction is known and matches its short id, or ask the
+    /// relayer for the missing ones
+    fn advance(&mut self, block_hash: [u8; 32], pending: &mut PendingBlock) -> RelayAction {
+        let missing: Vec<u32> = (0..pending.slots.len() as u32)
+            .filter(|index| pending.slots[*index as usize].is_none())
+            .collect();
+        if !missing.is_empty() {
+            return RelayAction::Request(
+                pending.relayer,
+                RelayRequest::Transactions {
+                    block_hash,
+                    indexes: missing,
+                },
+            );
+        }
+
+        // A transaction answering for the wrong short id means the relayer is confused or lying
+        let block = pending.compact.complete(pending.slots.clone());
+        let verified = block.as_ref().ok().and_then(|block| CompactBlock::from_block(block).ok());
+        match (block, verified) {
+            (Ok(block), Some(rebuilt)) if rebuilt.short_ids == pending.compact.short_ids => {
+                self.remember(block_hash, block.clone());
+                RelayAction::Complete(block, pending.compact.seal.clone())
+            }
+            _ => {
+                pending.full_block_requested = true;
+                RelayAction::Request(pending.producer, RelayRequest::Block { block_hash })
+            }
+        }
+    }
+
+    /// Download the whole block after reconstruction failed, unless that already failed too
+    fn fall_back(&mut self, block_hash: [u8; 32], mut pending: PendingBlock) -> Option<RelayAction> {
+        if pending.full_block_requested {
+            println!("Giving up on block {}: no peer served it", hex(&block_hash));
+            return None;
+        }
+        pending.full_block_requested = true;
+        let producer = pending.producer;
+        self.pending.insert(block_hash, pending);
+        Some(RelayAction::Request(producer, RelayRequest::Block { block_hash }))
+    }
+
+    fn remember(&mut self, block_hash: [u8; 32], block: Block) {
+        if self.recent.insert(block_hash, block).is_none() {
+            self.order.push_back(block_hash);
+        }
+        while self.order.len() > RECENT_BLOCKS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.recent.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_sync::{BlockHeader, BlockProof, ProofType};
+
+    fn transaction(byte: u8) -> Transaction {
+        Transaction {
+            hash: [byte; 32],
+            sender: vec![byte],
+            nonce: 0,
+            gas_limit: 21_000,
+            data: Vec::new(),
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            fee: 1,
+            timestamp: 1,
+        }
+    }
+
+    fn block(nonce: u64) -> Block {
+        Block {
+            header: BlockHeader {
+                height: 1,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000,
+                nonce,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions: vec![transaction(1), transaction(2), transaction(3)],
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_missing_transactions_then_full_block_fallback() {
+        let (relayer, producer) = (PeerId::random(), PeerId::random());
+        let mut sender = BlockRelay::new();
+        let mut receiver = BlockRelay::new();
+
+        // The coinbase comes prefilled and the pool has one of the others
+        let compact = sender.insert(block(0), vec![0x5e; 8]).unwrap();
+        let wire = CompactBlock::from_canonical_bytes(&compact.to_canonical_bytes()).unwrap();
+        let request = match receiver.on_announcement(relayer, producer, wire, &[transaction(3)]).unwrap() {
+            Some(RelayAction::Request(peer, request)) => {
+                assert_eq!(peer, relayer);
+                request
+            }
+            other => panic!("expected a transaction request, got {:?}", other),
+        };
+        assert!(matches!(&request, RelayRequest::Transactions { indexes, .. } if indexes == &vec![1]));
+        let response = RelayResponse::from_canonical_bytes(&sender.on_request(&request).to_canonical_bytes()).unwrap();
+        match receiver.on_response(response) {
+            Some(RelayAction::Complete(block, seal)) => {
+                assert_eq!(block.transactions.len(), 3);
+                assert_eq!(seal, vec![0x5e; 8]);
+            }
+            other => panic!("expected a complete block, got {:?}", other),
+        }
+        assert_eq!(receiver.pending(), 0);
+
+        // A relayer answering with the wrong transaction sends us to the producer for the block
+        let compact = sender.insert(block(1), vec![0x5e; 8]).unwrap();
+        receiver.on_announcement(relayer, producer, compact.clone(), &[]).unwrap();
+        let block_hash = compact.header.hash().unwrap();
+        let bogus = RelayResponse::Transactions {
+            block_hash,
+            transactions: vec![transaction(9), transaction(8)],
+        };
+        let request = match receiver.on_response(bogus) {
+            Some(RelayAction::Request(peer, request)) => {
+                assert_eq!(peer, producer);
+                request
+            }
+            other => panic!("expected a block request, got {:?}", other),
+        };
+        // The full block carries no seal of its own; the announced one still comes with it
+        match receiver.on_response(sender.on_request(&request)) {
+            Some(RelayAction::Complete(_, seal)) => assert_eq!(seal, vec![0x5e; 8]),
+            other => panic!("expected a complete block, got {:?}", other),
+        }
+
+        // Once the full block cannot be fetched either, the announcement is dropped
+        receiver.on_announcement(relayer, producer, CompactBlock::from_block(&block(2)).unwrap(), &[]).unwrap();
+        let block_hash = block(2).header.hash().unwrap();
+        assert!(matches!(receiver.on_failure(block_hash), Some(RelayAction::Request(..))));
+        assert!(receiver.on_failure(block_hash).is_none());
+        assert_eq!(receiver.pending(), 0);
+    }
+}
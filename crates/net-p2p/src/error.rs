@@ -0,0 +1,16 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum NetworkError {
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
+    #[error("Behaviour error: {0}")]
+    BehaviourError(String),
+
+    #[error("Listen error: {0}")]
+    ListenError(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+}
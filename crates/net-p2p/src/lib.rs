@@ -1,61 +1,1309 @@
 use libp2p::{
-    core::upgrade,
+    autonat,
+    dcutr,
     gossipsub::{self, Behaviour as GossipsubBehaviour, Config as GossipsubConfig, IdentTopic, MessageAuthenticity, IdentityTransform, AllowAllSubscriptionFilter},
+    identify,
     identity,
+    mdns,
     noise,
-    swarm::{Swarm, SwarmEvent},
-    tcp,
+    pnet::PreSharedKey,
+    relay,
+    request_response::{self, ProtocolSupport},
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
+    Swarm,
     yamux,
-    Multiaddr, PeerId, Transport,
+    StreamProtocol,
 };
-use tokio::sync::mpsc;
-use tokio::task;
+pub use libp2p::{Multiaddr, PeerId};
+use block_sync::{Block, Canonical, CompactBlock};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::{self, JoinHandle};
+use tokio_util::sync::CancellationToken;
 use futures_util::StreamExt;
+use txpool::TxPool;
+
+pub mod bandwidth;
+pub mod error;
+pub mod peers;
+pub mod block_relay;
+pub mod proxy;
+pub mod timing;
+pub mod transport;
+
+use bandwidth::{Direction, Protocol};
+pub use bandwidth::{BandwidthLimits, BandwidthTracker, PeerStats};
+use error::NetworkError;
+pub use peers::PeerControl;
+pub use proxy::Socks5Proxy;
+use peers::{BanList, PeerCommand};
+use block_relay::{BlockRelay, RelayAction, RelayRequest, RelayResponse, BLOCK_RELAY_PROTOCOL};
+pub use timing::TimingPrivacyConfig;
+use timing::{BroadcastScheduler, DandelionRouter, Route};
+pub use transport::TransportSecurity;
 
 pub type EventSender = mpsc::UnboundedSender<gossipsub::Event>;
 
-/// Start the P2P networking layer. Returns the local [`PeerId`] and a sender for swarm events.
-pub async fn start_network(listen_addr: Multiaddr) -> (PeerId, EventSender) {
+/// Protocol version advertised through identify
+pub const PROTOCOL_VERSION: &str = "/c0dl3/1.0.0";
+
+/// Identify protocol version of a node following the chain with `genesis_hash`
+pub fn protocol_version(genesis_hash: Option<&[u8; 32]>) -> String {
+    match genesis_hash {
+        Some(hash) => {
+            let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{}/{}", PROTOCOL_VERSION, hex)
+        }
+        None => PROTOCOL_VERSION.to_string(),
+    }
+}
+
+/// Gossip topic shared by all C0DL3 nodes
+pub const GOSSIP_TOPIC: &str = "coldl3-gossip";
+
+/// Gossip topic of compact block announcements, which light clients can follow on its own
+pub const BLOCK_TOPIC: &str = "coldl3-blocks";
+
+/// Gossip topic of validators' attestations, which finalize blocks wherever they arrive
+pub const ATTESTATION_TOPIC: &str = "coldl3-attestations";
+
+/// Request-response protocol carrying Dandelion stem transactions
+pub const STEM_PROTOCOL: &str = "/c0dl3/dandelion/1.0.0";
+
+/// How often delayed and embargoed transactions are checked for release
+const TIMING_TICK: Duration = Duration::from_millis(50);
+
+/// P2P network configuration
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    pub listen_addr: Multiaddr,
+    /// Probe our own reachability through remote peers (AutoNAT)
+    pub enable_autonat: bool,
+    /// Allow reservations on and dialing through circuit relays
+    pub enable_relay: bool,
+    /// Upgrade relayed connections to direct ones via DCUtR hole punching
+    pub enable_hole_punching: bool,
+    /// Discover peers on the local network via mDNS
+    pub enable_mdns: bool,
+    /// Peers dialed on startup and whenever the node has no connections
+    pub bootstrap_peers: Vec<Multiaddr>,
+    /// Domains whose `_dnsaddr` TXT records list bootstrap multiaddrs
+    pub dns_seeds: Vec<String>,
+    /// How often to re-dial bootstrap peers while isolated
+    pub bootstrap_interval: Duration,
+    /// Security handshake for direct connections
+    pub transport_security: TransportSecurity,
+    /// Swarm key for a permissioned network; only peers holding the same key can connect
+    pub private_network_key: Option<PreSharedKey>,
+    /// Broadcast delays, batching and Dandelion++ relaying for transactions
+    pub timing_privacy: TimingPrivacyConfig,
+    /// Genesis of the chain this node follows; peers advertising another one are disconnected
+    pub genesis_hash: Option<[u8; 32]>,
+    /// Per-peer upload and download caps
+    pub bandwidth: BandwidthLimits,
+    /// Dial peers through this SOCKS5 proxy, e.g. Tor, instead of connecting directly
+    pub proxy: Option<Socks5Proxy>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: "/ip4/0.0.0.0/tcp/4001".parse().expect("valid default multiaddr"),
+            enable_autonat: true,
+            enable_relay: true,
+            enable_hole_punching: true,
+            enable_mdns: true,
+            bootstrap_peers: Vec::new(),
+            dns_seeds: Vec::new(),
+            bootstrap_interval: Duration::from_secs(30),
+            transport_security: TransportSecurity::Noise,
+            private_network_key: None,
+            timing_privacy: TimingPrivacyConfig::default(),
+            genesis_hash: None,
+            bandwidth: BandwidthLimits::default(),
+            proxy: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Addresses to dial when bootstrapping: configured peers plus one `/dnsaddr` per DNS seed
+    pub fn bootstrap_addrs(&self) -> Result<Vec<Multiaddr>, NetworkError> {
+        let mut addrs = self.bootstrap_peers.clone();
+        for seed in &self.dns_seeds {
+            let addr: Multiaddr = format!("/dnsaddr/{}", seed)
+                .parse()
+                .map_err(|e| NetworkError::ConfigError(format!("invalid DNS seed {}: {}", seed, e)))?;
+            addrs.push(addr);
+        }
+        Ok(addrs)
+    }
+}
+
+/// Reachability of the local node as determined by AutoNAT
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NatStatus {
+    Unknown,
+    Public(String),
+    Private,
+}
+
+impl From<autonat::NatStatus> for NatStatus {
+    fn from(status: autonat::NatStatus) -> Self {
+        match status {
+            autonat::NatStatus::Public(addr) => NatStatus::Public(addr.to_string()),
+            autonat::NatStatus::Private => NatStatus::Private,
+            autonat::NatStatus::Unknown => NatStatus::Unknown,
+        }
+    }
+}
+
+/// Address discovery state shared with the RPC layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkInfo {
+    pub peer_id: String,
+    pub listen_addrs: Vec<String>,
+    /// Addresses remote peers report seeing us on, not yet confirmed
+    pub observed_addrs: Vec<String>,
+    /// Addresses confirmed reachable from the outside
+    pub external_addrs: Vec<String>,
+    pub nat_status: NatStatus,
+    pub relay_enabled: bool,
+    pub hole_punching_enabled: bool,
+    pub direct_upgrades: u64,
+    pub connected_peers: usize,
+    /// Peers found through mDNS
+    pub discovered_peers: usize,
+    pub bootstrap_attempts: u64,
+    pub transport_security: TransportSecurity,
+    /// Fingerprint of the swarm key when running a private network
+    pub private_network: Option<String>,
+    #[serde(default)]
+    pub dandelion_enabled: bool,
+    /// Transactions handed to a stem relay
+    #[serde(default)]
+    pub transactions_stemmed: u64,
+    /// Transactions published through gossip
+    #[serde(default)]
+    pub transactions_fluffed: u64,
+    /// Peers currently refused by an operator ban
+    #[serde(default)]
+    pub banned_peers: usize,
+    /// Peers disconnected for advertising another genesis
+    #[serde(default)]
+    pub wrong_chain_peers: u64,
+    /// Compact blocks published through gossip
+    #[serde(default)]
+    pub blocks_announced: u64,
+    /// Announced blocks rebuilt from the pool and fetched transactions
+    #[serde(default)]
+    pub blocks_reconstructed: u64,
+    /// Announced blocks downloaded in full after reconstruction failed
+    #[serde(default)]
+    pub full_blocks_downloaded: u64,
+    /// Attestations of this node published through gossip
+    #[serde(default)]
+    pub attestations_published: u64,
+    /// Attestations received from peers
+    #[serde(default)]
+    pub attestations_received: u64,
+    /// Peers disconnected for exceeding the bandwidth caps
+    #[serde(default)]
+    pub bandwidth_disconnects: u64,
+}
+
+impl NetworkInfo {
+    fn new(peer_id: PeerId, config: &NetworkConfig) -> Self {
+        Self {
+            peer_id: peer_id.to_string(),
+            listen_addrs: Vec::new(),
+            observed_addrs: Vec::new(),
+            external_addrs: Vec::new(),
+            nat_status: NatStatus::Unknown,
+            relay_enabled: config.enable_relay,
+            hole_punching_enabled: config.enable_hole_punching,
+            direct_upgrades: 0,
+            connected_peers: 0,
+            discovered_peers: 0,
+            bootstrap_attempts: 0,
+            transport_security: config.transport_security,
+            private_network: config
+                .private_network_key
+                .map(|key| key.fingerprint().to_string()),
+            dandelion_enabled: config.timing_privacy.enable_dandelion,
+            transactions_stemmed: 0,
+            transactions_fluffed: 0,
+            banned_peers: 0,
+            wrong_chain_peers: 0,
+            blocks_announced: 0,
+            blocks_reconstructed: 0,
+            full_blocks_downloaded: 0,
+            attestations_published: 0,
+            attestations_received: 0,
+            bandwidth_disconnects: 0,
+        }
+    }
+}
+
+/// Handle to a running network
+pub struct NetworkHandle {
+    pub peer_id: PeerId,
+    pub events: EventSender,
+    pub info: Arc<RwLock<NetworkInfo>>,
+    /// Operator control over peers, e.g. for the admin RPC
+    pub peers: PeerControl,
+    /// Bytes exchanged with each connected peer
+    pub bandwidth: Arc<RwLock<BandwidthTracker>>,
+    /// Blocks received from peers with the seals announced for them, in the order they were completed
+    pub blocks: mpsc::UnboundedReceiver<(Block, Vec<u8>)>,
+    /// Attestations received from peers, as they were published
+    pub attestations: mpsc::UnboundedReceiver<Vec<u8>>,
+    transactions: mpsc::UnboundedSender<Vec<u8>>,
+    announcements: mpsc::UnboundedSender<(Block, Vec<u8>)>,
+    local_attestations: mpsc::UnboundedSender<Vec<u8>>,
+    shutdown: CancellationToken,
+    swarm_task: JoinHandle<()>,
+}
+
+impl NetworkHandle {
+    /// Broadcast a transaction created by this node, with timing privacy applied
+    pub fn broadcast_transaction(&self, transaction: Vec<u8>) -> Result<(), NetworkError> {
+        self.transactions
+            .send(transaction)
+            .map_err(|_| NetworkError::TransportError("network task has stopped".to_string()))
+    }
+
+    /// Publish an attestation this node signed
+    pub fn broadcast_attestation(&self, attestation: Vec<u8>) -> Result<(), NetworkError> {
+        self.local_attestations
+            .send(attestation)
+            .map_err(|_| NetworkError::TransportError("network task has stopped".to_string()))
+    }
+
+    /// Announce a block this node produced as a compact block under the producer's `seal`,
+    /// serving its transactions to peers that cannot rebuild it
+    pub fn announce_block(&self, block: Block, seal: Vec<u8>) -> Result<(), NetworkError> {
+        self.announcements
+            .send((block, seal))
+            .map_err(|_| NetworkError::TransportError("network task has stopped".to_string()))
+    }
+
+    /// Stop the swarm and wait for it to close its connections and listeners
+    pub async fn shutdown(self) {
+        self.shutdown.cancel();
+        if let Err(e) = self.swarm_task.await {
+            println!("Network task ended abnormally: {}", e);
+        }
+    }
+}
+
+/// Transaction broadcast state owned by the swarm task
+struct TimingPrivacy {
+    scheduler: BroadcastScheduler,
+    router: DandelionRouter,
+    /// Stem transactions awaiting acknowledgement, published directly if the relay fails
+    in_flight: HashMap<request_response::OutboundRequestId, Vec<u8>>,
+    /// Peers we dialed, preferred as stem relays since an attacker cannot choose them for us
+    outbound: HashSet<PeerId>,
+    rng: StdRng,
+}
+
+/// Compact block relay state owned by the swarm task
+struct BlockRelayTask {
+    relay: BlockRelay,
+    /// Pool announced blocks are rebuilt from
+    tx_pool: Option<Arc<RwLock<TxPool>>>,
+    /// Outstanding relay requests and the block each is for
+    in_flight: HashMap<request_response::OutboundRequestId, [u8; 32]>,
+    completed: mpsc::UnboundedSender<(Block, Vec<u8>)>,
+}
+
+/// Combined network behaviour of a C0DL3 node
+#[derive(NetworkBehaviour)]
+pub struct C0DL3Behaviour {
+    pub gossipsub: GossipsubBehaviour<IdentityTransform, AllowAllSubscriptionFilter>,
+    pub identify: identify::Behaviour,
+    pub autonat: Toggle<autonat::Behaviour>,
+    pub relay_client: Toggle<relay::client::Behaviour>,
+    pub dcutr: Toggle<dcutr::Behaviour>,
+    pub mdns: Toggle<mdns::tokio::Behaviour>,
+    pub stem: Toggle<request_response::cbor::Behaviour<Vec<u8>, ()>>,
+    /// Canonically encoded relay requests and responses
+    pub block_relay: request_response::cbor::Behaviour<Vec<u8>, Vec<u8>>,
+}
+
+/// Combine the behaviours of a node running with `config`
+fn build_behaviour(
+    key: &identity::Keypair,
+    relay_client: relay::client::Behaviour,
+    config: &NetworkConfig,
+) -> Result<C0DL3Behaviour, Box<dyn std::error::Error + Send + Sync>> {
+    // Gossipsub
+    let mut gossipsub: GossipsubBehaviour<IdentityTransform, AllowAllSubscriptionFilter> =
+        GossipsubBehaviour::new(MessageAuthenticity::Signed(key.clone()), GossipsubConfig::default())?;
+    gossipsub.subscribe(&IdentTopic::new(GOSSIP_TOPIC))?;
+    gossipsub.subscribe(&IdentTopic::new(BLOCK_TOPIC))?;
+    gossipsub.subscribe(&IdentTopic::new(ATTESTATION_TOPIC))?;
+
+    let local_peer_id = key.public().to_peer_id();
+    let identify = identify::Behaviour::new(identify::Config::new(
+        protocol_version(config.genesis_hash.as_ref()),
+        key.public(),
+    ));
+    let autonat = config
+        .enable_autonat
+        .then(|| autonat::Behaviour::new(local_peer_id, autonat::Config::default()));
+    let relay_client = config.enable_relay.then_some(relay_client);
+    let dcutr = config.enable_hole_punching.then(|| dcutr::Behaviour::new(local_peer_id));
+    let mdns = if config.enable_mdns {
+        Some(mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)?)
+    } else {
+        None
+    };
+
+    let stem = config.timing_privacy.enable_dandelion.then(|| {
+        request_response::cbor::Behaviour::new(
+            [(StreamProtocol::new(STEM_PROTOCOL), ProtocolSupport::Full)],
+            request_response::Config::default(),
+        )
+    });
+
+    let block_relay = request_response::cbor::Behaviour::new(
+        [(StreamProtocol::new(BLOCK_RELAY_PROTOCOL), ProtocolSupport::Full)],
+        request_response::Config::default(),
+    );
+
+    Ok(C0DL3Behaviour {
+        gossipsub,
+        identify,
+        autonat: autonat.into(),
+        relay_client: relay_client.into(),
+        dcutr: dcutr.into(),
+        mdns: mdns.into(),
+        stem: stem.into(),
+        block_relay,
+    })
+}
+
+/// Start the P2P networking layer. Returns a [`NetworkHandle`] with the local [`PeerId`],
+/// a sender for gossip events and the shared address discovery state.
+pub async fn start_network(config: NetworkConfig) -> Result<NetworkHandle, NetworkError> {
+    start_network_with_pool(config, None).await
+}
+
+/// Start the P2P networking layer, rebuilding announced blocks from the transactions in
+/// `tx_pool`. Without a pool every announced transaction is fetched from peers.
+pub async fn start_network_with_pool(
+    config: NetworkConfig,
+    tx_pool: Option<Arc<RwLock<TxPool>>>,
+) -> Result<NetworkHandle, NetworkError> {
     // Generate keypair for this node
     let local_key = identity::Keypair::generate_ed25519();
     let peer_id = PeerId::from(local_key.public());
 
-    // Build transport (tcp + noise + yamux)
-    let transport = tcp::tokio::Transport::default()
-        .upgrade(upgrade::Version::V1)
-        .authenticate(noise::Config::new(&local_key).unwrap())
-        .multiplex(yamux::Config::default())
-        .boxed();
-    // If DNS is needed and available, you can wrap with DNS here.
-    // let transport = libp2p_dns::TokioDnsConfig::system(transport).unwrap();
+    // Hole punching only makes sense on top of relayed connections
+    if config.enable_hole_punching && !config.enable_relay {
+        return Err(NetworkError::ConfigError(
+            "hole punching requires relay support to be enabled".to_string(),
+        ));
+    }
+    config.timing_privacy.validate()?;
+    config.bandwidth.validate()?;
+    // Each of these would reveal the node's address or look names up outside the proxy
+    if config.proxy.is_some() && (config.enable_mdns || config.enable_hole_punching || !config.dns_seeds.is_empty()) {
+        return Err(NetworkError::ConfigError(
+            "mDNS, hole punching and DNS seeds must be disabled when dialing through a proxy".to_string(),
+        ));
+    }
 
-    // Gossipsub
-    let gossipsub_config = GossipsubConfig::default();
-    let mut gossipsub: GossipsubBehaviour<IdentityTransform, AllowAllSubscriptionFilter> =
-        GossipsubBehaviour::new(MessageAuthenticity::Signed(local_key.clone()), gossipsub_config).unwrap();
-    gossipsub.subscribe(&IdentTopic::new("coldl3-gossip")).unwrap();
+    let bootstrap_addrs = config.bootstrap_addrs()?;
+
+    // Build swarm (tcp [+ pnet] + noise/tls + yamux, with dns and an optional relay client transport)
+    let builder = libp2p::SwarmBuilder::with_existing_identity(local_key)
+        .with_tokio()
+        .with_other_transport(|key| {
+            transport::build_transport(
+                key,
+                config.transport_security,
+                config.private_network_key,
+                config.proxy.clone(),
+            )
+        })
+        .map_err(|e| NetworkError::TransportError(e.to_string()))?;
+    let mut swarm = match config.proxy {
+        // Host names are left for the proxy to resolve, so no lookup leaves this host
+        Some(_) => builder
+            .with_relay_client(noise::Config::new, yamux::Config::default)
+            .map_err(|e| NetworkError::TransportError(e.to_string()))?
+            .with_behaviour(|key, relay_client| build_behaviour(key, relay_client, &config))
+            .map_err(|e| NetworkError::BehaviourError(e.to_string()))?
+            .build(),
+        None => builder
+            .with_dns()
+            .map_err(|e| NetworkError::TransportError(e.to_string()))?
+            // Circuits are tunnelled over already secured connections; Noise secures them end to end
+            .with_relay_client(noise::Config::new, yamux::Config::default)
+            .map_err(|e| NetworkError::TransportError(e.to_string()))?
+            .with_behaviour(|key, relay_client| build_behaviour(key, relay_client, &config))
+            .map_err(|e| NetworkError::BehaviourError(e.to_string()))?
+            .build(),
+    };
 
-    // Build swarm
-    let mut swarm = Swarm::new(transport, gossipsub, peer_id, libp2p::swarm::Config::with_tokio_executor());
-    swarm.listen_on(listen_addr).unwrap();
+    swarm
+        .listen_on(config.listen_addr.clone())
+        .map_err(|e| NetworkError::ListenError(e.to_string()))?;
+
+    let info = Arc::new(RwLock::new(NetworkInfo::new(peer_id, &config)));
 
     // Channel to bubble up events
     let (tx, mut rx) = mpsc::unbounded_channel();
     let tx_events = tx.clone();
+    let swarm_info = info.clone();
+    let bootstrap_interval = config.bootstrap_interval;
+    let local_protocol = protocol_version(config.genesis_hash.as_ref());
+    let (transactions, mut local_transactions) = mpsc::unbounded_channel();
+    let (announcements, mut local_blocks) = mpsc::unbounded_channel();
+    let (completed, blocks) = mpsc::unbounded_channel();
+    let (local_attestations, mut outbound_attestations) = mpsc::unbounded_channel::<Vec<u8>>();
+    let (received_attestations, attestations) = mpsc::unbounded_channel();
+    let (peer_commands, mut pending_peer_commands) = mpsc::unbounded_channel();
+    let mut bans = BanList::default();
+    let bandwidth = Arc::new(RwLock::new(BandwidthTracker::new(config.bandwidth.clone())));
+    let swarm_bandwidth = bandwidth.clone();
+    let bandwidth_window = config.bandwidth.window();
+    let mut privacy = TimingPrivacy {
+        scheduler: BroadcastScheduler::new(config.timing_privacy.clone(), Instant::now()),
+        router: DandelionRouter::new(config.timing_privacy.clone()),
+        in_flight: HashMap::new(),
+        outbound: HashSet::new(),
+        rng: StdRng::from_entropy(),
+    };
+    let mut relay = BlockRelayTask {
+        relay: BlockRelay::new(),
+        tx_pool,
+        in_flight: HashMap::new(),
+        completed,
+    };
 
-    task::spawn(async move {
+    let shutdown = CancellationToken::new();
+    let swarm_shutdown = shutdown.clone();
+    let swarm_task = task::spawn(async move {
+        let mut bootstrap_timer = tokio::time::interval(bootstrap_interval);
+        let mut timing_timer = tokio::time::interval(TIMING_TICK);
+        let mut bandwidth_timer = tokio::time::interval(bandwidth_window);
         loop {
-            match swarm.select_next_some().await {
-                SwarmEvent::Behaviour(event) => {
-                    let _ = tx_events.send(event);
+            tokio::select! {
+                event = swarm.select_next_some() => {
+                    if let SwarmEvent::ConnectionEstablished { peer_id, .. } = &event {
+                        if bans.is_banned(peer_id, Instant::now()) {
+                            let _ = swarm.disconnect_peer_id(*peer_id);
+                        }
+                    }
+                    handle_swarm_event(
+                        &mut swarm,
+                        event,
+                        &tx_events,
+                        &swarm_info,
+                        &swarm_bandwidth,
+                        &mut privacy,
+                        &mut relay,
+                        &received_attestations,
+                        &local_protocol,
+                    )
+                    .await;
+                }
+                Some(command) = pending_peer_commands.recv() => {
+                    apply_peer_command(&mut swarm, &mut bans, &swarm_info, command).await;
+                }
+                _ = bootstrap_timer.tick() => {
+                    // The first tick fires immediately, so this also performs the initial dial
+                    if swarm.connected_peers().next().is_none() {
+                        dial_bootstrap_peers(&mut swarm, &bootstrap_addrs, &swarm_info).await;
+                    }
+                }
+                Some(transaction) = local_transactions.recv() => {
+                    privacy.scheduler.schedule(transaction, Instant::now(), &mut privacy.rng);
+                }
+                Some((block, seal)) = local_blocks.recv() => {
+                    announce_block(&mut swarm, &swarm_info, &swarm_bandwidth, &mut relay, block, seal).await;
+                }
+                Some(attestation) = outbound_attestations.recv() => {
+                    match publish(&mut swarm, &swarm_bandwidth, ATTESTATION_TOPIC, attestation).await {
+                        Ok(()) => swarm_info.write().await.attestations_published += 1,
+                        Err(e) => println!("Failed to publish attestation: {}", e),
+                    }
                 }
-                _ => {}
+                _ = timing_timer.tick() => {
+                    release_transactions(&mut swarm, &swarm_info, &swarm_bandwidth, &mut privacy).await;
+                }
+                _ = bandwidth_timer.tick() => {
+                    disconnect_over_cap_peers(&mut swarm, &swarm_info, &swarm_bandwidth).await;
+                }
+                _ = swarm_shutdown.cancelled() => break,
             }
         }
+        // Transactions still waiting for their release time are dropped with the swarm
+        if privacy.scheduler.pending() > 0 {
+            println!("Dropping {} unreleased transactions on shutdown", privacy.scheduler.pending());
+        }
+        // Dropping the swarm closes every connection and listener
+        swarm_info.write().await.connected_peers = 0;
     });
 
     // Drain rx so channel stays alive (can be replaced with proper handler later)
     task::spawn(async move { while let Some(_e) = rx.recv().await {} });
 
-    (peer_id, tx)
+    Ok(NetworkHandle {
+        peer_id,
+        events: tx,
+        info,
+        peers: PeerControl::new(peer_commands),
+        bandwidth,
+        blocks,
+        attestations,
+        transactions,
+        announcements,
+        local_attestations,
+        shutdown,
+        swarm_task,
+    })
+}
+
+/// Carry out an operator's request to dial, drop, ban or unban a peer
+async fn apply_peer_command(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    bans: &mut BanList,
+    info: &Arc<RwLock<NetworkInfo>>,
+    command: PeerCommand,
+) {
+    match command {
+        PeerCommand::Dial(addr) => {
+            if let Err(e) = swarm.dial(addr.clone()) {
+                println!("Failed to dial {}: {}", addr, e);
+            }
+        }
+        PeerCommand::Disconnect(peer) => {
+            let _ = swarm.disconnect_peer_id(peer);
+        }
+        PeerCommand::Ban(peer, duration) => {
+            println!("Banning peer {} for {:?}", peer, duration);
+            bans.ban(peer, Instant::now() + duration);
+            let _ = swarm.disconnect_peer_id(peer);
+        }
+        PeerCommand::Unban(peer) => {
+            bans.unban(&peer);
+        }
+    }
+    info.write().await.banned_peers = bans.active(Instant::now());
+}
+
+/// Send out local transactions whose delay has passed and stemmed ones whose embargo expired
+async fn release_transactions(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    privacy: &mut TimingPrivacy,
+) {
+    let now = Instant::now();
+    for transaction in privacy.scheduler.take_due(now, &mut privacy.rng) {
+        forward_transaction(swarm, info, bandwidth, privacy, transaction, None).await;
+    }
+    for transaction in privacy.router.expired_embargoes(now) {
+        println!("Stem embargo expired, publishing transaction ourselves");
+        fluff_transaction(swarm, info, bandwidth, transaction).await;
+    }
+}
+
+/// Close the bandwidth window, disconnecting peers that kept exceeding the caps
+async fn disconnect_over_cap_peers(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+) {
+    let over_cap = bandwidth.write().await.end_window();
+    for peer in &over_cap {
+        println!("Disconnecting peer {} for exceeding the bandwidth caps", peer);
+        let _ = swarm.disconnect_peer_id(*peer);
+    }
+    if !over_cap.is_empty() {
+        info.write().await.bandwidth_disconnects += over_cap.len() as u64;
+    }
+}
+
+/// Publish `data` on `topic`, counting it as sent to every peer subscribed to the topic
+async fn publish(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    topic: &str,
+    data: Vec<u8>,
+) -> Result<(), gossipsub::PublishError> {
+    let topic = IdentTopic::new(topic);
+    let len = data.len();
+    swarm.behaviour_mut().gossipsub.publish(topic.clone(), data)?;
+    let hash = topic.hash();
+    let mut bandwidth = bandwidth.write().await;
+    for (peer, topics) in swarm.behaviour().gossipsub.all_peers() {
+        if topics.contains(&&hash) {
+            bandwidth.record(*peer, Protocol::Gossip, Direction::Sent, len);
+        }
+    }
+    Ok(())
+}
+
+/// Relay a transaction along the stem or publish it, as the Dandelion router decides
+async fn forward_transaction(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    privacy: &mut TimingPrivacy,
+    transaction: Vec<u8>,
+    from: Option<PeerId>,
+) {
+    // Peers over their bandwidth caps are passed over as stem relays
+    let peers: Vec<PeerId> = {
+        let bandwidth = bandwidth.read().await;
+        swarm.connected_peers().filter(|peer| !bandwidth.is_throttled(peer)).copied().collect()
+    };
+    let outbound: Vec<PeerId> = peers.iter().filter(|peer| privacy.outbound.contains(peer)).copied().collect();
+    let peers = if outbound.is_empty() { peers } else { outbound };
+    let route = privacy
+        .router
+        .route(&transaction, from, &peers, Instant::now(), &mut privacy.rng);
+    match (route, swarm.behaviour_mut().stem.as_mut()) {
+        (Route::Stem(relay), Some(stem)) => {
+            let len = transaction.len();
+            let request_id = stem.send_request(&relay, transaction.clone());
+            privacy.in_flight.insert(request_id, transaction);
+            bandwidth.write().await.record(relay, Protocol::Gossip, Direction::Sent, len);
+            info.write().await.transactions_stemmed += 1;
+        }
+        _ => fluff_transaction(swarm, info, bandwidth, transaction).await,
+    }
+}
+
+/// Publish a transaction through gossip
+async fn fluff_transaction(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    transaction: Vec<u8>,
+) {
+    match publish(swarm, bandwidth, GOSSIP_TOPIC, transaction).await {
+        Ok(()) => info.write().await.transactions_fluffed += 1,
+        Err(e) => println!("Failed to publish transaction: {}", e),
+    }
+}
+
+/// Publish a block as a compact block. Blocks skip the Dandelion stem since their producer
+/// is public.
+async fn announce_block(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    relay: &mut BlockRelayTask,
+    block: Block,
+    seal: Vec<u8>,
+) {
+    let compact = match relay.relay.insert(block, seal) {
+        Ok(compact) => compact,
+        Err(e) => {
+            println!("Failed to build compact block: {}", e);
+            return;
+        }
+    };
+    match publish(swarm, bandwidth, BLOCK_TOPIC, compact.to_canonical_bytes()).await {
+        Ok(()) => info.write().await.blocks_announced += 1,
+        Err(e) => println!("Failed to announce block: {}", e),
+    }
+}
+
+/// Rebuild a compact block announced through gossip
+async fn receive_announcement(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    relay: &mut BlockRelayTask,
+    relayer: PeerId,
+    message: &gossipsub::Message,
+) {
+    let compact = match CompactBlock::from_canonical_bytes(&message.data) {
+        Ok(compact) => compact,
+        Err(e) => {
+            println!("Ignoring malformed compact block from {}: {}", relayer, e);
+            return;
+        }
+    };
+    let pool = match &relay.tx_pool {
+        Some(tx_pool) => tx_pool.read().await.transactions(),
+        None => Vec::new(),
+    };
+    let producer = message.source.unwrap_or(relayer);
+    match relay.relay.on_announcement(relayer, producer, compact, &pool) {
+        Ok(Some(action)) => {
+            if let RelayAction::Complete(..) = &action {
+                info.write().await.blocks_reconstructed += 1;
+            }
+            apply_relay_action(swarm, bandwidth, relay, action).await;
+        }
+        Ok(None) => {}
+        Err(e) => println!("Ignoring compact block from {}: {}", relayer, e),
+    }
+}
+
+/// Send a relay request or hand a completed block to the node
+async fn apply_relay_action(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    relay: &mut BlockRelayTask,
+    action: RelayAction,
+) {
+    match action {
+        RelayAction::Request(peer, request) => {
+            let block_hash = match &request {
+                RelayRequest::Transactions { block_hash, .. } | RelayRequest::Block { block_hash } => *block_hash,
+            };
+            let request = request.to_canonical_bytes();
+            bandwidth.write().await.record(peer, Protocol::Sync, Direction::Sent, request.len());
+            let request_id = swarm.behaviour_mut().block_relay.send_request(&peer, request);
+            relay.in_flight.insert(request_id, block_hash);
+        }
+        RelayAction::Complete(block, seal) => {
+            let _ = relay.completed.send((block, seal));
+        }
+    }
+}
+
+/// Serve a peer's relay request or process the answer to one of ours
+async fn handle_relay_event(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    relay: &mut BlockRelayTask,
+    event: request_response::Event<Vec<u8>, Vec<u8>>,
+) {
+    match event {
+        request_response::Event::Message {
+            peer,
+            message: request_response::Message::Request { request, channel, .. },
+        } => {
+            let mut bandwidth = bandwidth.write().await;
+            bandwidth.record(peer, Protocol::Sync, Direction::Received, request.len());
+            // Dropping the channel leaves a peer over its caps unanswered until the window ends
+            if bandwidth.is_throttled(&peer) {
+                return;
+            }
+            let response = match RelayRequest::from_canonical_bytes(&request) {
+                Ok(request) => relay.relay.on_request(&request).to_canonical_bytes(),
+                Err(e) => {
+                    println!("Ignoring malformed relay request from {}: {}", peer, e);
+                    return;
+                }
+            };
+            bandwidth.record(peer, Protocol::Sync, Direction::Sent, response.len());
+            let _ = swarm.behaviour_mut().block_relay.send_response(channel, response);
+        }
+        request_response::Event::Message {
+            peer,
+            message: request_response::Message::Response { request_id, response },
+        } => {
+            bandwidth.write().await.record(peer, Protocol::Sync, Direction::Received, response.len());
+            let Some(block_hash) = relay.in_flight.remove(&request_id) else {
+                return;
+            };
+            let action = match RelayResponse::from_canonical_bytes(&response) {
+                Ok(response) => {
+                    let full_block = matches!(response, RelayResponse::Block(_));
+                    let action = relay.relay.on_response(response);
+                    if let Some(RelayAction::Complete(..)) = &action {
+                        let mut info = info.write().await;
+                        if full_block {
+                            info.full_blocks_downloaded += 1;
+                        } else {
+                            info.blocks_reconstructed += 1;
+                        }
+                    }
+                    action
+                }
+                Err(e) => {
+                    println!("Malformed relay response from {}: {}", peer, e);
+                    relay.relay.on_failure(block_hash)
+                }
+            };
+            if let Some(action) = action {
+                apply_relay_action(swarm, bandwidth, relay, action).await;
+            }
+        }
+        request_response::Event::OutboundFailure { peer, request_id, error } => {
+            if let Some(block_hash) = relay.in_flight.remove(&request_id) {
+                println!("Relay request to {} failed: {}", peer, error);
+                if let Some(action) = relay.relay.on_failure(block_hash) {
+                    apply_relay_action(swarm, bandwidth, relay, action).await;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Dial every bootstrap address, recording the attempt
+async fn dial_bootstrap_peers(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    bootstrap_addrs: &[Multiaddr],
+    info: &Arc<RwLock<NetworkInfo>>,
+) {
+    if bootstrap_addrs.is_empty() {
+        return;
+    }
+
+    for addr in bootstrap_addrs {
+        if let Err(e) = swarm.dial(addr.clone()) {
+            println!("Failed to dial bootstrap peer {}: {}", addr, e);
+        }
+    }
+    info.write().await.bootstrap_attempts += 1;
+}
+
+/// Approximate payload size of an identify message
+fn identify_size(info: &identify::Info) -> usize {
+    info.public_key.encode_protobuf().len()
+        + info.protocol_version.len()
+        + info.agent_version.len()
+        + info.listen_addrs.iter().map(Multiaddr::len).sum::<usize>()
+        + info.protocols.iter().map(|protocol| protocol.as_ref().len()).sum::<usize>()
+        + info.observed_addr.len()
+}
+
+/// Update address discovery state and forward gossip events
+#[allow(clippy::too_many_arguments)]
+async fn handle_swarm_event(
+    swarm: &mut Swarm<C0DL3Behaviour>,
+    event: SwarmEvent<C0DL3BehaviourEvent>,
+    tx_events: &EventSender,
+    info: &Arc<RwLock<NetworkInfo>>,
+    bandwidth: &Arc<RwLock<BandwidthTracker>>,
+    privacy: &mut TimingPrivacy,
+    relay: &mut BlockRelayTask,
+    attestations: &mpsc::UnboundedSender<Vec<u8>>,
+    local_protocol: &str,
+) {
+    match event {
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Gossipsub(event)) => {
+            if let gossipsub::Event::Message { message, propagation_source, .. } = &event {
+                let len = message.data.len();
+                bandwidth.write().await.record(*propagation_source, Protocol::Gossip, Direction::Received, len);
+                if message.topic == IdentTopic::new(BLOCK_TOPIC).hash() {
+                    receive_announcement(swarm, info, bandwidth, relay, *propagation_source, message).await;
+                } else if message.topic == IdentTopic::new(ATTESTATION_TOPIC).hash() {
+                    info.write().await.attestations_received += 1;
+                    let _ = attestations.send(message.data.clone());
+                } else {
+                    privacy.router.seen_in_gossip(&message.data);
+                }
+            }
+            let _ = tx_events.send(event);
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::BlockRelay(event)) => {
+            handle_relay_event(swarm, info, bandwidth, relay, event).await;
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Stem(request_response::Event::Message { peer, message })) => {
+            match message {
+                request_response::Message::Request { request, channel, .. } => {
+                    bandwidth.write().await.record(peer, Protocol::Gossip, Direction::Received, request.len());
+                    if let Some(stem) = swarm.behaviour_mut().stem.as_mut() {
+                        let _ = stem.send_response(channel, ());
+                    }
+                    forward_transaction(swarm, info, bandwidth, privacy, request, Some(peer)).await;
+                }
+                request_response::Message::Response { request_id, .. } => {
+                    privacy.in_flight.remove(&request_id);
+                }
+            }
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Stem(request_response::Event::OutboundFailure {
+            peer,
+            request_id,
+            error,
+        })) => {
+            // A failed stem hop falls back to publishing, as Dandelion++ does on relay failure
+            if let Some(transaction) = privacy.in_flight.remove(&request_id) {
+                println!("Stem relay to {} failed: {}", peer, error);
+                privacy.router.seen_in_gossip(&transaction);
+                fluff_transaction(swarm, info, bandwidth, transaction).await;
+            }
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Identify(identify::Event::Received {
+            peer_id,
+            info: peer_info,
+            ..
+        })) => {
+            bandwidth.write().await.record(peer_id, Protocol::Dht, Direction::Received, identify_size(&peer_info));
+            if peer_info.protocol_version != local_protocol {
+                println!("Disconnecting peer {} on another chain: {}", peer_id, peer_info.protocol_version);
+                let _ = swarm.disconnect_peer_id(peer_id);
+                info.write().await.wrong_chain_peers += 1;
+                return;
+            }
+            let observed = peer_info.observed_addr.to_string();
+            let mut info = info.write().await;
+            if !info.observed_addrs.contains(&observed) {
+                info.observed_addrs.push(observed);
+            }
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Autonat(autonat::Event::StatusChanged { new, .. })) => {
+            info.write().await.nat_status = new.into();
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Dcutr(event)) if event.result.is_ok() => {
+            info.write().await.direct_upgrades += 1;
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Mdns(mdns::Event::Discovered(peers))) => {
+            for (peer, addr) in peers {
+                swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer);
+                if let Err(e) = swarm.dial(addr.clone()) {
+                    println!("Failed to dial mDNS peer {} at {}: {}", peer, addr, e);
+                }
+                info.write().await.discovered_peers += 1;
+            }
+        }
+        SwarmEvent::Behaviour(C0DL3BehaviourEvent::Mdns(mdns::Event::Expired(peers))) => {
+            for (peer, _addr) in peers {
+                swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer);
+            }
+        }
+        SwarmEvent::ConnectionEstablished { peer_id, endpoint, num_established, .. } => {
+            if endpoint.is_dialer() {
+                privacy.outbound.insert(peer_id);
+            }
+            if num_established.get() == 1 {
+                info.write().await.connected_peers += 1;
+            }
+        }
+        SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. } => {
+            privacy.router.peer_disconnected(&peer_id);
+            privacy.outbound.remove(&peer_id);
+            bandwidth.write().await.remove(&peer_id);
+            let mut info = info.write().await;
+            info.connected_peers = info.connected_peers.saturating_sub(1);
+        }
+        SwarmEvent::NewListenAddr { address, .. } => {
+            info.write().await.listen_addrs.push(address.to_string());
+        }
+        SwarmEvent::ExpiredListenAddr { address, .. } => {
+            let address = address.to_string();
+            info.write().await.listen_addrs.retain(|a| *a != address);
+        }
+        SwarmEvent::ExternalAddrConfirmed { address } => {
+            let address = address.to_string();
+            let mut info = info.write().await;
+            if !info.external_addrs.contains(&address) {
+                info.external_addrs.push(address);
+            }
+        }
+        SwarmEvent::ExternalAddrExpired { address } => {
+            let address = address.to_string();
+            info.write().await.external_addrs.retain(|a| *a != address);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_enables_nat_traversal() {
+        let config = NetworkConfig::default();
+        assert!(config.enable_autonat);
+        assert!(config.enable_relay);
+        assert!(config.enable_hole_punching);
+    }
+
+    #[test]
+    fn test_nat_status_conversion() {
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        assert_eq!(
+            NatStatus::from(autonat::NatStatus::Public(addr)),
+            NatStatus::Public("/ip4/1.2.3.4/tcp/4001".to_string())
+        );
+        assert_eq!(NatStatus::from(autonat::NatStatus::Private), NatStatus::Private);
+    }
+
+    #[test]
+    fn test_bootstrap_addrs_include_dns_seeds() {
+        let config = NetworkConfig {
+            bootstrap_peers: vec!["/ip4/10.0.0.1/tcp/4001".parse().unwrap()],
+            dns_seeds: vec!["seed.c0dl3.network".to_string()],
+            ..Default::default()
+        };
+
+        let addrs = config.bootstrap_addrs().unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(addrs[1].to_string(), "/dnsaddr/seed.c0dl3.network");
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_peer_is_dialed() {
+        let listener = start_network(NetworkConfig {
+            listen_addr: "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            enable_mdns: false,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let mut listen_addr = None;
+        for _ in 0..50 {
+            listen_addr = listener.info.read().await.listen_addrs.first().cloned();
+            if listen_addr.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let dialer = start_network(NetworkConfig {
+            listen_addr: "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            enable_mdns: false,
+            bootstrap_peers: vec![listen_addr.unwrap().parse().unwrap()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        for _ in 0..200 {
+            if dialer.info.read().await.connected_peers > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let info = dialer.info.read().await;
+        assert!(info.bootstrap_attempts >= 1);
+        assert_eq!(info.connected_peers, 1);
+    }
+
+    async fn wait_for_listen_addr(handle: &NetworkHandle) -> Multiaddr {
+        for _ in 0..50 {
+            if let Some(addr) = handle.info.read().await.listen_addrs.first() {
+                return addr.parse().unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("network did not start listening");
+    }
+
+    async fn connects_to(listener: &NetworkConfig, dialer: NetworkConfig) -> bool {
+        let listener = start_network(listener.clone()).await.unwrap();
+        let addr = wait_for_listen_addr(&listener).await;
+        let dialer = start_network(NetworkConfig {
+            bootstrap_peers: vec![addr],
+            ..dialer
+        })
+        .await
+        .unwrap();
+
+        for _ in 0..100 {
+            if dialer.info.read().await.connected_peers > 0 {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        false
+    }
+
+    fn local_config() -> NetworkConfig {
+        NetworkConfig {
+            listen_addr: "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            enable_mdns: false,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_private_network_rejects_peers_without_key() {
+        let key = PreSharedKey::new([7u8; 32]);
+        let private = NetworkConfig {
+            private_network_key: Some(key),
+            ..local_config()
+        };
+
+        assert!(connects_to(&private, private.clone()).await);
+        assert!(!connects_to(&private, local_config()).await);
+    }
+
+    #[tokio::test]
+    async fn test_tls_transport_connects() {
+        let tls = NetworkConfig {
+            transport_security: TransportSecurity::Tls,
+            ..local_config()
+        };
+        assert!(connects_to(&tls, tls.clone()).await);
+    }
+
+    #[tokio::test]
+    async fn test_peers_on_another_genesis_are_disconnected() {
+        let listener = start_network(NetworkConfig {
+            genesis_hash: Some([1u8; 32]),
+            ..local_config()
+        })
+        .await
+        .unwrap();
+        let addr = wait_for_listen_addr(&listener).await;
+        let dialer = start_network(NetworkConfig {
+            genesis_hash: Some([2u8; 32]),
+            bootstrap_peers: vec![addr],
+            ..local_config()
+        })
+        .await
+        .unwrap();
+
+        // Whichever side identifies the other first hangs up
+        for _ in 0..200 {
+            if dialer.info.read().await.wrong_chain_peers > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(dialer.info.read().await.wrong_chain_peers + listener.info.read().await.wrong_chain_peers > 0);
+        assert_eq!(
+            protocol_version(Some(&[0xab; 32])),
+            format!("/c0dl3/1.0.0/{}", "ab".repeat(32))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_transaction_is_stemmed_then_fluffed() {
+        let immediate = TimingPrivacyConfig {
+            min_broadcast_delay: Duration::ZERO,
+            max_broadcast_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let fluffer = start_network(NetworkConfig {
+            timing_privacy: TimingPrivacyConfig { fluff_probability: 1.0, ..immediate.clone() },
+            ..local_config()
+        })
+        .await
+        .unwrap();
+        let addr = wait_for_listen_addr(&fluffer).await;
+        let sender = start_network(NetworkConfig {
+            bootstrap_peers: vec![addr],
+            timing_privacy: immediate,
+            ..local_config()
+        })
+        .await
+        .unwrap();
+        for _ in 0..100 {
+            if sender.info.read().await.connected_peers > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Let gossipsub exchange subscriptions before publishing
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        sender.broadcast_transaction(b"private transfer".to_vec()).unwrap();
+        for _ in 0..200 {
+            if fluffer.info.read().await.transactions_fluffed > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(sender.info.read().await.transactions_stemmed, 1);
+        assert_eq!(sender.info.read().await.transactions_fluffed, 0);
+        assert_eq!(fluffer.info.read().await.transactions_fluffed, 1);
+
+        // Shutting the sender down closes its connection to the fluffer
+        tokio::time::timeout(Duration::from_secs(5), sender.shutdown()).await.unwrap();
+        for _ in 0..200 {
+            if fluffer.info.read().await.connected_peers == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(fluffer.info.read().await.connected_peers, 0);
+    }
+
+    #[tokio::test]
+    async fn test_attestations_reach_peers() {
+        let mut listener = start_network(local_config()).await.unwrap();
+        let addr = wait_for_listen_addr(&listener).await;
+        let attester = start_network(NetworkConfig {
+            bootstrap_peers: vec![addr],
+            ..local_config()
+        })
+        .await
+        .unwrap();
+        for _ in 0..100 {
+            if attester.info.read().await.connected_peers > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Let gossipsub exchange subscriptions before publishing
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        attester.broadcast_attestation(b"attestation".to_vec()).unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), listener.attestations.recv()).await;
+        assert_eq!(received.unwrap().unwrap(), b"attestation".to_vec());
+        assert_eq!(attester.info.read().await.attestations_published, 1);
+        assert_eq!(listener.info.read().await.attestations_received, 1);
+    }
+
+    #[tokio::test]
+    async fn test_timing_privacy_config_is_validated() {
+        let config = NetworkConfig {
+            timing_privacy: TimingPrivacyConfig { fluff_probability: 1.5, ..Default::default() },
+            ..local_config()
+        };
+        assert!(matches!(start_network(config).await, Err(NetworkError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_requires_direct_connections_disabled() {
+        let proxied = NetworkConfig {
+            proxy: Some("socks5://127.0.0.1:9050".parse().unwrap()),
+            ..local_config()
+        };
+        assert!(matches!(start_network(proxied.clone()).await, Err(NetworkError::ConfigError(_))));
+
+        let handle = start_network(NetworkConfig { enable_hole_punching: false, ..proxied }).await.unwrap();
+        assert!(!wait_for_listen_addr(&handle).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hole_punching_requires_relay() {
+        let config = NetworkConfig {
+            listen_addr: "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            enable_relay: false,
+            enable_hole_punching: true,
+            ..Default::default()
+        };
+        assert!(matches!(start_network(config).await, Err(NetworkError::ConfigError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_start_network_reports_listen_addrs() {
+        let config = NetworkConfig {
+            listen_addr: "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            ..Default::default()
+        };
+        let handle = start_network(config).await.unwrap();
+
+        // Give the swarm a moment to bind
+        for _ in 0..50 {
+            if !handle.info.read().await.listen_addrs.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let info = handle.info.read().await;
+        assert_eq!(info.peer_id, handle.peer_id.to_string());
+        assert!(!info.listen_addrs.is_empty());
+        assert_eq!(info.nat_status, NatStatus::Unknown);
+    }
 }
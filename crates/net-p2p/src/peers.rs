@@ -0,0 +1,111 @@
+//! Operator control over the node's connections: dialing and dropping peers on
+//! request, and refusing connections from banned peers until their ban expires.
+
+use libp2p::{Multiaddr, PeerId};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::error::NetworkError;
+
+/// Request from a [`PeerControl`] to the swarm task
+#[derive(Debug)]
+pub(crate) enum PeerCommand {
+    Dial(Multiaddr),
+    Disconnect(PeerId),
+    Ban(PeerId, Duration),
+    Unban(PeerId),
+}
+
+/// Cloneable handle for adding, removing and banning peers of a running network
+#[derive(Debug, Clone)]
+pub struct PeerControl {
+    commands: mpsc::UnboundedSender<PeerCommand>,
+}
+
+impl PeerControl {
+    pub(crate) fn new(commands: mpsc::UnboundedSender<PeerCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Dial `addr`, even if it belongs to a banned peer
+    pub fn add_peer(&self, addr: Multiaddr) -> Result<(), NetworkError> {
+        self.send(PeerCommand::Dial(addr))
+    }
+
+    /// Close every connection to `peer`; it may reconnect unless banned
+    pub fn remove_peer(&self, peer: PeerId) -> Result<(), NetworkError> {
+        self.send(PeerCommand::Disconnect(peer))
+    }
+
+    /// Disconnect `peer` and refuse its connections for `duration`
+    pub fn ban_peer(&self, peer: PeerId, duration: Duration) -> Result<(), NetworkError> {
+        self.send(PeerCommand::Ban(peer, duration))
+    }
+
+    pub fn unban_peer(&self, peer: PeerId) -> Result<(), NetworkError> {
+        self.send(PeerCommand::Unban(peer))
+    }
+
+    fn send(&self, command: PeerCommand) -> Result<(), NetworkError> {
+        self.commands
+            .send(command)
+            .map_err(|_| NetworkError::TransportError("network task has stopped".to_string()))
+    }
+}
+
+/// Peers refused until their ban expires
+#[derive(Debug, Default)]
+pub struct BanList {
+    bans: HashMap<PeerId, Instant>,
+}
+
+impl BanList {
+    /// Ban `peer` until `until`, replacing any earlier ban
+    pub fn ban(&mut self, peer: PeerId, until: Instant) {
+        self.bans.insert(peer, until);
+    }
+
+    /// Lift the ban on `peer`, returning whether it was banned
+    pub fn unban(&mut self, peer: &PeerId) -> bool {
+        self.bans.remove(peer).is_some()
+    }
+
+    pub fn is_banned(&mut self, peer: &PeerId, now: Instant) -> bool {
+        self.expire(now);
+        self.bans.contains_key(peer)
+    }
+
+    /// Number of bans still in force at `now`
+    pub fn active(&mut self, now: Instant) -> usize {
+        self.expire(now);
+        self.bans.len()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.bans.retain(|_, until| *until > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bans_expire() {
+        let now = Instant::now();
+        let (banned, other) = (PeerId::random(), PeerId::random());
+        let mut bans = BanList::default();
+        bans.ban(banned, now + Duration::from_secs(60));
+        assert!(bans.is_banned(&banned, now));
+        assert!(!bans.is_banned(&other, now));
+        assert_eq!(bans.active(now), 1);
+
+        assert!(!bans.is_banned(&banned, now + Duration::from_secs(60)));
+        assert_eq!(bans.active(now), 0);
+
+        bans.ban(other, now + Duration::from_secs(60));
+        assert!(bans.unban(&other));
+        assert!(!bans.unban(&other));
+    }
+}
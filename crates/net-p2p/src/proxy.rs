@@ -0,0 +1,317 @@
+//! Outbound connections through a SOCKS5 proxy such as Tor.
+//!
+//! Dials go to the proxy, which connects to the peer on our behalf, so peers and observers
+//! between us and the proxy never see both ends of a connection. Host names, including
+//! `.onion` addresses, are handed to the proxy unresolved so no DNS lookup leaks. Each
+//! peer is dialed with its own SOCKS credentials, which Tor takes as a request for a
+//! separate circuit, so one exit cannot link our connections to different peers.
+//! Listening is unaffected and still happens on plain TCP.
+
+use futures_util::future::BoxFuture;
+use libp2p::{
+    core::transport::{ListenerId, TransportError, TransportEvent},
+    multiaddr::Protocol,
+    tcp, Multiaddr, Transport,
+};
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::error::NetworkError;
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const CONNECT: u8 = 1;
+
+/// Address of a SOCKS5 proxy, given as `socks5://host:port`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    /// `host:port` of the proxy itself
+    pub addr: String,
+}
+
+impl FromStr for Socks5Proxy {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let addr = s
+            .strip_prefix("socks5://")
+            .or_else(|| s.strip_prefix("socks5h://"))
+            .ok_or_else(|| NetworkError::ConfigError(format!("proxy {} is not a socks5:// URL", s)))?
+            .trim_end_matches('/');
+        match addr.rsplit_once(':').map(|(host, port)| (host, port.parse::<u16>())) {
+            Some((host, Ok(_))) if !host.is_empty() => Ok(Socks5Proxy { addr: addr.to_string() }),
+            _ => Err(NetworkError::ConfigError(format!("proxy {} is not a host:port address", s))),
+        }
+    }
+}
+
+impl fmt::Display for Socks5Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "socks5://{}", self.addr)
+    }
+}
+
+/// Destination the proxy is asked to connect to
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Host {
+    Ip(IpAddr),
+    /// Resolved by the proxy
+    Domain(String),
+}
+
+/// Host and port of a `/ip4`, `/ip6`, `/dns*` or `/onion3` TCP address, optionally ending in `/p2p`
+fn socks_target(addr: &Multiaddr) -> Option<(Host, u16)> {
+    let mut protocols = addr.iter();
+    let target = match protocols.next()? {
+        Protocol::Onion3(onion) => {
+            // Displayed as `/onion3/<base32 service id>:<port>`
+            let display = Protocol::Onion3(onion.clone()).to_string();
+            let (service, _) = display.strip_prefix("/onion3/")?.rsplit_once(':')?;
+            (Host::Domain(format!("{}.onion", service)), onion.port())
+        }
+        host => {
+            let host = match host {
+                Protocol::Ip4(ip) => Host::Ip(ip.into()),
+                Protocol::Ip6(ip) => Host::Ip(ip.into()),
+                Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) => Host::Domain(name.to_string()),
+                _ => return None,
+            };
+            match protocols.next()? {
+                Protocol::Tcp(port) => (host, port),
+                _ => return None,
+            }
+        }
+    };
+    match (protocols.next(), protocols.next()) {
+        (None, _) | (Some(Protocol::P2p(_)), None) => Some(target),
+        _ => None,
+    }
+}
+
+/// Open a connection to `host:port` through the proxy at `proxy`, authenticating with
+/// `username` and `password` when the proxy accepts credentials
+async fn socks5_connect(proxy: &str, host: &Host, port: u16, username: &str, password: &str) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy).await?;
+    stream.set_nodelay(true)?;
+
+    stream.write_all(&[SOCKS_VERSION, 2, USERNAME_PASSWORD, NO_AUTHENTICATION]).await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    match choice {
+        [SOCKS_VERSION, USERNAME_PASSWORD] => {
+            let (username, password) = (truncate(username), truncate(password));
+            let mut auth = vec![1, username.len() as u8];
+            auth.extend_from_slice(username);
+            auth.push(password.len() as u8);
+            auth.extend_from_slice(password);
+            stream.write_all(&auth).await?;
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await?;
+            if status[1] != 0 {
+                return Err(socks_error("proxy rejected the stream credentials"));
+            }
+        }
+        [SOCKS_VERSION, NO_AUTHENTICATION] => {}
+        _ => return Err(socks_error("proxy offered no supported authentication method")),
+    }
+
+    let mut request = vec![SOCKS_VERSION, CONNECT, 0];
+    match host {
+        Host::Ip(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Host::Ip(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Host::Domain(name) => {
+            let len = u8::try_from(name.len()).map_err(|_| socks_error("host name is too long for SOCKS5"))?;
+            request.extend_from_slice(&[3, len]);
+            request.extend_from_slice(name.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(socks_error(&format!("proxy could not connect: {}", reply_message(reply[1]))));
+    }
+    // Skip the address the proxy bound, then its port
+    let bound = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        _ => return Err(socks_error("proxy replied with an unknown address type")),
+    };
+    let mut skipped = vec![0u8; bound + 2];
+    stream.read_exact(&mut skipped).await?;
+    Ok(stream)
+}
+
+/// SOCKS5 credentials are at most 255 bytes
+fn truncate(credential: &str) -> &[u8] {
+    &credential.as_bytes()[..credential.len().min(u8::MAX as usize)]
+}
+
+fn socks_error(message: &str) -> io::Error {
+    io::Error::other(message.to_string())
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+/// TCP transport that listens directly but dials every peer through a SOCKS5 proxy
+pub struct ProxiedTcp {
+    tcp: tcp::tokio::Transport,
+    proxy: Socks5Proxy,
+    /// Password sent with every dial, so circuits are not shared with an earlier run
+    session: String,
+}
+
+impl ProxiedTcp {
+    pub fn new(tcp: tcp::tokio::Transport, proxy: Socks5Proxy) -> Self {
+        Self {
+            tcp,
+            proxy,
+            session: format!("{:016x}", rand::random::<u64>()),
+        }
+    }
+}
+
+impl Transport for ProxiedTcp {
+    type Output = tcp::tokio::TcpStream;
+    type Error = io::Error;
+    type ListenerUpgrade = <tcp::tokio::Transport as Transport>::ListenerUpgrade;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(&mut self, id: ListenerId, addr: Multiaddr) -> Result<(), TransportError<Self::Error>> {
+        self.tcp.listen_on(id, addr)
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.tcp.remove_listener(id)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let Some((host, port)) = socks_target(&addr) else {
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        };
+        let proxy = self.proxy.addr.clone();
+        // The peer's address is the username, isolating its stream from every other peer's
+        let (username, password) = (addr.to_string(), self.session.clone());
+        Ok(Box::pin(async move {
+            let stream = socks5_connect(&proxy, &host, port, &username, &password).await?;
+            Ok(tcp::tokio::TcpStream(stream))
+        }))
+    }
+
+    fn dial_as_listener(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        // Hole punching would connect directly, revealing our address to the peer
+        Err(TransportError::Other(socks_error(&format!(
+            "cannot dial {} as a listener through a proxy",
+            addr
+        ))))
+    }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        Pin::new(&mut self.get_mut().tcp).poll(cx)
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.tcp.address_translation(listen, observed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_proxy_url_is_parsed() {
+        let proxy: Socks5Proxy = "socks5://127.0.0.1:9050".parse().unwrap();
+        assert_eq!(proxy.addr, "127.0.0.1:9050");
+        assert_eq!(proxy.to_string(), "socks5://127.0.0.1:9050");
+        assert_eq!("socks5h://tor:9050/".parse::<Socks5Proxy>().unwrap().addr, "tor:9050");
+        assert!("http://127.0.0.1:9050".parse::<Socks5Proxy>().is_err());
+        assert!("socks5://127.0.0.1".parse::<Socks5Proxy>().is_err());
+    }
+
+    #[test]
+    fn test_socks_targets() {
+        let target = |addr: &str| socks_target(&addr.parse().unwrap());
+        assert_eq!(target("/ip4/10.0.0.1/tcp/4001"), Some((Host::Ip("10.0.0.1".parse().unwrap()), 4001)));
+        assert_eq!(
+            target("/dns4/seed.c0dl3.network/tcp/4001/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN"),
+            Some((Host::Domain("seed.c0dl3.network".to_string()), 4001))
+        );
+        assert_eq!(
+            target("/onion3/vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd:1234"),
+            Some((Host::Domain("vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd.onion".to_string()), 1234))
+        );
+        assert_eq!(target("/ip4/10.0.0.1/udp/4001/quic-v1"), None);
+        assert_eq!(target("/dnsaddr/seed.c0dl3.network"), None);
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_proxy_sends_isolation_credentials() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut client, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 4];
+            client.read_exact(&mut greeting).await.unwrap();
+            client.write_all(&[SOCKS_VERSION, USERNAME_PASSWORD]).await.unwrap();
+
+            let mut header = [0u8; 2];
+            client.read_exact(&mut header).await.unwrap();
+            let mut username = vec![0u8; header[1] as usize];
+            client.read_exact(&mut username).await.unwrap();
+            let password_len = client.read_u8().await.unwrap();
+            let mut password = vec![0u8; password_len as usize];
+            client.read_exact(&mut password).await.unwrap();
+            client.write_all(&[1, 0]).await.unwrap();
+
+            let mut request = [0u8; 5];
+            client.read_exact(&mut request).await.unwrap();
+            let mut name = vec![0u8; request[4] as usize + 2];
+            client.read_exact(&mut name).await.unwrap();
+            client.write_all(&[SOCKS_VERSION, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await.unwrap();
+            client.write_all(b"hello").await.unwrap();
+            (String::from_utf8(username).unwrap(), request[3], name)
+        });
+
+        let host = Host::Domain("example.onion".to_string());
+        let mut stream = socks5_connect(&proxy, &host, 80, "/onion3/peer", "session").await.unwrap();
+        let mut greeting = [0u8; 5];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(&greeting, b"hello");
+
+        let (username, address_type, name) = server.await.unwrap();
+        assert_eq!(username, "/onion3/peer");
+        assert_eq!(address_type, 3);
+        assert_eq!(&name[..13], b"example.onion");
+        assert_eq!(&name[13..], &80u16.to_be_bytes());
+    }
+}
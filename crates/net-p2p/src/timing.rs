@@ -0,0 +1,330 @@
+//! Network-layer timing privacy for transaction broadcast.
+//!
+//! Local transactions wait a random delay, optionally aligned to a batching epoch, so
+//! their release time says little about when they were created. They then travel a
+//! Dandelion++ stem: each node forwards stem transactions to one of two relays chosen
+//! per epoch, until a node in fluff mode publishes them through gossip. A stemmed
+//! transaction not seen in gossip before its embargo expires is fluffed by the node
+//! that relayed it, so a dropped stem cannot censor it. Each node adds a random
+//! exponential delay to its embargo, so the node that fluffs first says little about
+//! its place on the stem, and a transaction that comes back along the stem is fluffed
+//! at once rather than circling until the embargo ends.
+
+use hashing::Domain;
+use libp2p::PeerId;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::error::NetworkError;
+
+/// Number of stem relays a node picks per Dandelion epoch
+const STEM_RELAYS: usize = 2;
+
+/// Longest random embargo delay, as a multiple of its mean
+const MAX_EMBARGO_JITTER: f64 = 10.0;
+
+/// Timing protection applied to transactions this node broadcasts or relays
+#[derive(Debug, Clone)]
+pub struct TimingPrivacyConfig {
+    /// Shortest random delay before a local transaction leaves the node
+    pub min_broadcast_delay: Duration,
+    /// Longest random delay before a local transaction leaves the node
+    pub max_broadcast_delay: Duration,
+    /// Release delayed transactions together at multiples of this interval
+    pub batch_epoch: Option<Duration>,
+    /// Relay transactions along a Dandelion++ stem before publishing them
+    pub enable_dandelion: bool,
+    /// Chance that the node publishes, rather than relays, stem transactions during an epoch
+    pub fluff_probability: f64,
+    /// How long the choice of relays and of stem or fluff mode lasts
+    pub dandelion_epoch: Duration,
+    /// Publish a relayed transaction ourselves if gossip has not delivered it by then
+    pub embargo_timeout: Duration,
+    /// Mean of the random exponential delay added to each embargo
+    pub embargo_jitter: Duration,
+}
+
+impl Default for TimingPrivacyConfig {
+    fn default() -> Self {
+        Self {
+            min_broadcast_delay: Duration::from_millis(100),
+            max_broadcast_delay: Duration::from_secs(2),
+            batch_epoch: None,
+            enable_dandelion: true,
+            fluff_probability: 0.1,
+            dandelion_epoch: Duration::from_secs(600),
+            embargo_timeout: Duration::from_secs(30),
+            embargo_jitter: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TimingPrivacyConfig {
+    pub fn validate(&self) -> Result<(), NetworkError> {
+        if self.min_broadcast_delay > self.max_broadcast_delay {
+            return Err(NetworkError::ConfigError(
+                "minimum broadcast delay exceeds the maximum".to_string(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.fluff_probability) {
+            return Err(NetworkError::ConfigError(format!(
+                "fluff probability {} is not between 0 and 1",
+                self.fluff_probability
+            )));
+        }
+        if self.batch_epoch.is_some_and(|epoch| epoch.is_zero()) || self.dandelion_epoch.is_zero() {
+            return Err(NetworkError::ConfigError("epochs must be longer than zero".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Identifier of a transaction payload, used to match stemmed and gossiped copies
+pub fn transaction_id(transaction: &[u8]) -> [u8; 32] {
+    hashing::hash(Domain::RelayedTransaction, transaction)
+}
+
+/// Holds local transactions until their randomized release time
+pub struct BroadcastScheduler {
+    config: TimingPrivacyConfig,
+    started: Instant,
+    pending: Vec<(Instant, Vec<u8>)>,
+}
+
+impl BroadcastScheduler {
+    pub fn new(config: TimingPrivacyConfig, now: Instant) -> Self {
+        Self {
+            config,
+            started: now,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Queue `transaction` and return when it will be released
+    pub fn schedule<R: Rng>(&mut self, transaction: Vec<u8>, now: Instant, rng: &mut R) -> Instant {
+        let delay = rng.gen_range(self.config.min_broadcast_delay..=self.config.max_broadcast_delay);
+        let mut release = now + delay;
+        if let Some(epoch) = self.config.batch_epoch {
+            // Round up to the next epoch boundary so a batch leaves at once
+            let elapsed = release.duration_since(self.started).as_nanos();
+            let epochs = elapsed.div_ceil(epoch.as_nanos()) as u32;
+            release = self.started + epoch * epochs;
+        }
+        self.pending.push((release, transaction));
+        release
+    }
+
+    /// Remove and return the transactions due by `now`, in a random order
+    pub fn take_due<R: Rng>(&mut self, now: Instant, rng: &mut R) -> Vec<Vec<u8>> {
+        let (due, pending): (Vec<_>, Vec<_>) = self.pending.drain(..).partition(|(release, _)| *release <= now);
+        self.pending = pending;
+        let mut due: Vec<Vec<u8>> = due.into_iter().map(|(_, transaction)| transaction).collect();
+        due.shuffle(rng);
+        due
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Where a transaction goes next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// Relay it privately to this peer
+    Stem(PeerId),
+    /// Publish it through gossip
+    Fluff,
+}
+
+/// Dandelion++ routing state for one node
+pub struct DandelionRouter {
+    config: TimingPrivacyConfig,
+    epoch_ends: Option<Instant>,
+    fluff_mode: bool,
+    relays: Vec<PeerId>,
+    /// Relay assigned to each inbound peer this epoch; `None` is the local node
+    assignments: HashMap<Option<PeerId>, PeerId>,
+    /// Stemmed transactions awaiting gossip, with the time to publish them ourselves
+    embargoes: HashMap<[u8; 32], (Instant, Vec<u8>)>,
+}
+
+impl DandelionRouter {
+    pub fn new(config: TimingPrivacyConfig) -> Self {
+        Self {
+            config,
+            epoch_ends: None,
+            fluff_mode: false,
+            relays: Vec::new(),
+            assignments: HashMap::new(),
+            embargoes: HashMap::new(),
+        }
+    }
+
+    fn start_epoch<R: Rng>(&mut self, peers: &[PeerId], now: Instant, rng: &mut R) {
+        self.epoch_ends = Some(now + self.config.dandelion_epoch);
+        self.fluff_mode = rng.gen_bool(self.config.fluff_probability);
+        self.relays = peers.choose_multiple(rng, STEM_RELAYS).copied().collect();
+        self.assignments.clear();
+    }
+
+    /// Embargo deadline of a transaction stemmed at `now`
+    fn embargo_deadline<R: Rng>(&self, now: Instant, rng: &mut R) -> Instant {
+        let mean = self.config.embargo_jitter.as_secs_f64();
+        let jitter = (-mean * (1.0 - rng.gen::<f64>()).ln()).min(mean * MAX_EMBARGO_JITTER);
+        now + self.config.embargo_timeout + Duration::from_secs_f64(jitter)
+    }
+
+    /// Decide where `transaction`, received from `from` or created locally when `None`, goes next
+    pub fn route<R: Rng>(
+        &mut self,
+        transaction: &[u8],
+        from: Option<PeerId>,
+        peers: &[PeerId],
+        now: Instant,
+        rng: &mut R,
+    ) -> Route {
+        if !self.config.enable_dandelion {
+            return Route::Fluff;
+        }
+        // A transaction we already stemmed has looped back along the stem
+        let id = transaction_id(transaction);
+        if from.is_some() && self.embargoes.remove(&id).is_some() {
+            return Route::Fluff;
+        }
+        // Relays are re-picked when the epoch ends or when every relay has gone away
+        if self.epoch_ends.is_none_or(|ends| now >= ends) || self.relays.is_empty() {
+            self.start_epoch(peers, now, rng);
+        }
+        // Our own transactions are always stemmed, whatever mode the epoch is in
+        if from.is_some() && self.fluff_mode {
+            return Route::Fluff;
+        }
+        let candidates: Vec<PeerId> = self.relays.iter().copied().filter(|relay| Some(*relay) != from).collect();
+        let relay = match self.assignments.get(&from) {
+            Some(relay) if candidates.contains(relay) => *relay,
+            _ => match candidates.choose(rng) {
+                Some(relay) => *relay,
+                None => return Route::Fluff,
+            },
+        };
+        self.assignments.insert(from, relay);
+        let deadline = self.embargo_deadline(now, rng);
+        self.embargoes.insert(id, (deadline, transaction.to_vec()));
+        Route::Stem(relay)
+    }
+
+    /// Gossip delivered a transaction, so any embargo on it is lifted
+    pub fn seen_in_gossip(&mut self, transaction: &[u8]) {
+        self.embargoes.remove(&transaction_id(transaction));
+    }
+
+    /// Remove and return stemmed transactions whose embargo expired by `now`
+    pub fn expired_embargoes(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        let expired: Vec<[u8; 32]> = self
+            .embargoes
+            .iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|id| self.embargoes.remove(&id).map(|(_, transaction)| transaction))
+            .collect()
+    }
+
+    /// Stop relaying through a peer that disconnected
+    pub fn peer_disconnected(&mut self, peer: &PeerId) {
+        self.relays.retain(|relay| relay != peer);
+        self.assignments.retain(|from, relay| relay != peer && *from != Some(*peer));
+    }
+
+    pub fn fluff_mode(&self) -> bool {
+        self.fluff_mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_scheduler_delays_and_batches_transactions() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let start = Instant::now();
+        let config = TimingPrivacyConfig {
+            min_broadcast_delay: Duration::from_millis(100),
+            max_broadcast_delay: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let mut scheduler = BroadcastScheduler::new(config.clone(), start);
+        let release = scheduler.schedule(vec![1], start, &mut rng);
+        assert!(release >= start + Duration::from_millis(100) && release <= start + Duration::from_millis(500));
+        assert!(scheduler.take_due(start + Duration::from_millis(99), &mut rng).is_empty());
+        assert_eq!(scheduler.take_due(start + Duration::from_millis(500), &mut rng), vec![vec![1]]);
+
+        let mut batched = BroadcastScheduler::new(
+            TimingPrivacyConfig { batch_epoch: Some(Duration::from_secs(1)), ..config },
+            start,
+        );
+        for transaction in 0..5u8 {
+            let release = batched.schedule(vec![transaction], start + Duration::from_millis(10), &mut rng);
+            assert_eq!(release, start + Duration::from_secs(1));
+        }
+        assert_eq!(batched.take_due(start + Duration::from_secs(1), &mut rng).len(), 5);
+        assert_eq!(batched.pending(), 0);
+    }
+
+    #[test]
+    fn test_dandelion_stems_then_fluffs_after_embargo() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let now = Instant::now();
+        let peers: Vec<PeerId> = (0..5).map(|_| PeerId::random()).collect();
+        let config = TimingPrivacyConfig {
+            fluff_probability: 0.0,
+            embargo_jitter: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut router = DandelionRouter::new(config.clone());
+
+        // Every transaction from one inbound peer takes the same relay during an epoch
+        let Route::Stem(relay) = router.route(b"a", Some(peers[0]), &peers, now, &mut rng) else {
+            panic!("expected a stem route");
+        };
+        assert_ne!(relay, peers[0]);
+        assert_eq!(router.route(b"b", Some(peers[0]), &peers, now, &mut rng), Route::Stem(relay));
+
+        router.seen_in_gossip(b"a");
+        assert!(router.expired_embargoes(now).is_empty());
+        assert_eq!(router.expired_embargoes(now + config.embargo_timeout), vec![b"b".to_vec()]);
+
+        // A fluffing node publishes relayed transactions but still stems its own
+        let mut fluffing = DandelionRouter::new(TimingPrivacyConfig { fluff_probability: 1.0, ..config });
+        assert_eq!(fluffing.route(b"c", Some(peers[1]), &peers, now, &mut rng), Route::Fluff);
+        assert!(matches!(fluffing.route(b"d", None, &peers, now, &mut rng), Route::Stem(_)));
+        assert_eq!(DandelionRouter::new(config).route(b"e", None, &[], now, &mut rng), Route::Fluff);
+    }
+
+    #[test]
+    fn test_dandelion_fluffs_looped_transactions_and_jitters_embargoes() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let now = Instant::now();
+        let peers: Vec<PeerId> = (0..5).map(|_| PeerId::random()).collect();
+        let config = TimingPrivacyConfig { fluff_probability: 0.0, ..Default::default() };
+        let mut router = DandelionRouter::new(config.clone());
+
+        assert!(matches!(router.route(b"a", None, &peers, now, &mut rng), Route::Stem(_)));
+        assert_eq!(router.route(b"a", Some(peers[2]), &peers, now, &mut rng), Route::Fluff);
+        // The loop already fluffed it, so no embargo is left to expire
+        let latest = now + config.embargo_timeout + config.embargo_jitter * MAX_EMBARGO_JITTER as u32;
+        assert!(router.expired_embargoes(latest).is_empty());
+
+        let deadlines: Vec<Instant> = (0..20).map(|_| router.embargo_deadline(now, &mut rng)).collect();
+        assert!(deadlines.iter().all(|deadline| *deadline >= now + config.embargo_timeout && *deadline <= latest));
+        assert!(deadlines.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+}
@@ -0,0 +1,115 @@
+use futures_util::future::Either;
+use libp2p::{
+    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade, Transport},
+    identity,
+    noise,
+    pnet::{PnetConfig, PreSharedKey},
+    tcp,
+    tls,
+    yamux,
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::NetworkError;
+use crate::proxy::{ProxiedTcp, Socks5Proxy};
+
+/// Handshake used to authenticate and encrypt direct connections
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransportSecurity {
+    #[default]
+    Noise,
+    Tls,
+}
+
+impl FromStr for TransportSecurity {
+    type Err = NetworkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "noise" => Ok(TransportSecurity::Noise),
+            "tls" => Ok(TransportSecurity::Tls),
+            other => Err(NetworkError::ConfigError(format!("unknown transport security: {}", other))),
+        }
+    }
+}
+
+/// Parse a swarm key in the standard `/key/swarm/psk/1.0.0/` format
+pub fn parse_swarm_key(contents: &str) -> Result<PreSharedKey, NetworkError> {
+    contents
+        .trim()
+        .parse()
+        .map_err(|e| NetworkError::ConfigError(format!("invalid swarm key: {}", e)))
+}
+
+/// Load a swarm key from disk
+pub fn load_swarm_key(path: impl AsRef<Path>) -> Result<PreSharedKey, NetworkError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| NetworkError::ConfigError(format!("failed to read swarm key {}: {}", path.display(), e)))?;
+    parse_swarm_key(&contents)
+}
+
+/// Build the TCP transport. With a pre-shared key every connection is wrapped in the
+/// private network handshake first, so peers without the swarm key cannot even negotiate
+/// a security protocol. With a proxy, peers are dialed through it.
+pub fn build_transport(
+    keypair: &identity::Keypair,
+    security: TransportSecurity,
+    private_network_key: Option<PreSharedKey>,
+    proxy: Option<Socks5Proxy>,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn std::error::Error + Send + Sync>> {
+    let tcp = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true));
+    let tcp = match proxy {
+        Some(proxy) => ProxiedTcp::new(tcp, proxy).boxed(),
+        None => tcp.boxed(),
+    };
+
+    let base = match private_network_key {
+        Some(psk) => tcp
+            .and_then(move |socket, _| PnetConfig::new(psk).handshake(socket))
+            .map(|socket, _| Either::Left(socket))
+            .boxed(),
+        None => tcp.map(|socket, _| Either::Right(socket)).boxed(),
+    };
+
+    let upgraded = base.upgrade(upgrade::Version::V1Lazy);
+    let transport = match security {
+        TransportSecurity::Noise => upgraded
+            .authenticate(noise::Config::new(keypair)?)
+            .multiplex(yamux::Config::default())
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+            .boxed(),
+        TransportSecurity::Tls => upgraded
+            .authenticate(tls::Config::new(keypair)?)
+            .multiplex(yamux::Config::default())
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+            .boxed(),
+    };
+
+    Ok(transport)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWARM_KEY: &str = "/key/swarm/psk/1.0.0/\n/base16/\n\
+        6189c5cf0b87fb800c1a9feeda73c6ab5e998db48fb9e6a978575c770ceef683";
+
+    #[test]
+    fn test_parse_swarm_key() {
+        let key = parse_swarm_key(SWARM_KEY).unwrap();
+        assert_eq!(key.to_string().trim(), SWARM_KEY);
+        assert!(parse_swarm_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_transport_security_from_str() {
+        assert_eq!("noise".parse::<TransportSecurity>().unwrap(), TransportSecurity::Noise);
+        assert_eq!("TLS".parse::<TransportSecurity>().unwrap(), TransportSecurity::Tls);
+        assert!("plaintext".parse::<TransportSecurity>().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Node side of the admin RPC namespace: mining control and validator key rotation.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use consensus::Consensus;
+use ed25519_dalek::SigningKey;
+use fuego_integration::FuegoDaemon;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Validator key written by key rotation, relative to the data directory
+pub const VALIDATOR_KEY_FILE: &str = "validator.key";
+
+/// Load the validator key saved by an earlier rotation, if there is one
+pub fn load_validator_key(data_dir: &Path) -> Result<Option<SigningKey>> {
+    let path = data_dir.join(VALIDATOR_KEY_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = hex::decode(std::fs::read_to_string(&path)?.trim())?;
+    let secret: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("{} does not hold a 32-byte key", path.display()))?;
+    Ok(Some(SigningKey::from_bytes(&secret)))
+}
+
+/// Write `key` so only the node's user can read it, replacing any earlier key at once
+fn save_validator_key(data_dir: &Path, key: &SigningKey) -> Result<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let path = data_dir.join(VALIDATOR_KEY_FILE);
+    let staging = path.with_extension("key.tmp");
+    std::fs::write(&staging, hex::encode(key.to_bytes()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staging, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&staging, &path)?;
+    Ok(())
+}
+
+/// Mining and validator key operations exposed through `admin_*` RPC methods
+pub struct NodeAdminHandle {
+    data_dir: PathBuf,
+    /// The key lives in a remote signer or on a Ledger and cannot be rotated from here
+    external_signer: bool,
+    consensus: Arc<RwLock<Consensus>>,
+    fuego_daemon: Option<Arc<RwLock<FuegoDaemon>>>,
+}
+
+impl NodeAdminHandle {
+    pub fn new(
+        data_dir: PathBuf,
+        external_signer: bool,
+        consensus: Arc<RwLock<Consensus>>,
+        fuego_daemon: Option<Arc<RwLock<FuegoDaemon>>>,
+    ) -> Self {
+        Self {
+            data_dir,
+            external_signer,
+            consensus,
+            fuego_daemon,
+        }
+    }
+
+    fn fuego_daemon(&self) -> Result<&Arc<RwLock<FuegoDaemon>>, String> {
+        self.fuego_daemon
+            .as_ref()
+            .ok_or_else(|| "Fuego mining is not configured".to_string())
+    }
+}
+
+#[async_trait]
+impl rpc::NodeAdmin for NodeAdminHandle {
+    async fn start_mining(&self) -> Result<(), String> {
+        let daemon = self.fuego_daemon()?;
+        if daemon.read().await.is_running().await {
+            return Ok(());
+        }
+        daemon.write().await.start().await.map_err(|e| e.to_string())?;
+        println!("✓ Fuego daemon mining started by admin");
+        Ok(())
+    }
+
+    async fn stop_mining(&self) -> Result<(), String> {
+        self.fuego_daemon()?.write().await.stop().await.map_err(|e| e.to_string())?;
+        println!("✓ Fuego daemon mining stopped by admin");
+        Ok(())
+    }
+
+    /// The new key signs from the next proposal on. Stake bonded to the old key stays
+    /// with it, so the operator re-registers the new key with the staking module.
+    async fn rotate_validator_key(&self) -> Result<[u8; 32], String> {
+        if self.external_signer {
+            return Err("validator key is held by an external signer; rotate it there".to_string());
+        }
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        let key = SigningKey::from_bytes(&secret);
+        save_validator_key(&self.data_dir, &key).map_err(|e| e.to_string())?;
+
+        let public_key = key.verifying_key().to_bytes();
+        self.consensus.write().await.set_signing_key(key);
+        println!("✓ Validator key rotated to {}", hex::encode(public_key));
+        Ok(public_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::ConsensusConfig;
+    use rpc::NodeAdmin;
+
+    #[tokio::test]
+    async fn test_rotate_validator_key() {
+        let data_dir = tempfile::TempDir::new().unwrap();
+        let consensus = Arc::new(RwLock::new(Consensus::new(ConsensusConfig::default()).unwrap()));
+        let admin = NodeAdminHandle::new(data_dir.path().to_path_buf(), false, consensus.clone(), None);
+        assert!(load_validator_key(data_dir.path()).unwrap().is_none());
+
+        let public_key = admin.rotate_validator_key().await.unwrap();
+        assert_eq!(consensus.read().await.validator_public_key(), Some(public_key));
+        let saved = load_validator_key(data_dir.path()).unwrap().unwrap();
+        assert_eq!(saved.verifying_key().to_bytes(), public_key);
+        assert_ne!(admin.rotate_validator_key().await.unwrap(), public_key);
+
+        assert!(admin.start_mining().await.is_err());
+        let external = NodeAdminHandle::new(data_dir.path().to_path_buf(), true, consensus, None);
+        assert!(external.rotate_validator_key().await.is_err());
+    }
+}
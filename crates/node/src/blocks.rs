@@ -0,0 +1,356 @@
+//! Blocks and attestations reaching the node from peers, and the execution of blocks.
+//!
+//! A relayed block comes with its proposer's seal and is imported through consensus as that
+//! proposal, which checks it on its parent state before fork choice takes it. The state
+//! database keeps no undo history, so only final blocks are executed into it, in order; the
+//! blocks above them are checked on their branch's pending writes until they are final too.
+//! Each executed block's seal is kept beside it, so a replay can check who proposed it.
+//! Attestations from validators elsewhere count towards finality here as they do there.
+
+use anyhow::{anyhow, Result};
+use block_sync::{Block, Canonical, Transaction};
+use consensus::engine::ImportOutcome;
+use consensus::finality::Attestation;
+use consensus::{BlockProposal, Consensus, FinalBlock, ProposalSeal};
+use execution::BlockExecutor;
+use state_db::account::GENESIS_VERSION;
+use state_db::RocksStateDB;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+use txpool::TxPool;
+
+use crate::{sleep_unless_shutdown, NodeMessage};
+
+/// Unversioned StateDB records of the seal each executed block was proposed under, by height
+const SEAL_PREFIX: &[u8] = b"seal/";
+
+fn seal_key(height: u64) -> Vec<u8> {
+    [SEAL_PREFIX, height.to_be_bytes().as_slice()].concat()
+}
+
+/// Keep `seal` as the one executed block `height` was proposed under
+pub fn store_seal(state: &mut RocksStateDB, height: u64, seal: &ProposalSeal) -> Result<()> {
+    Ok(state.write_batch_sync(&[(seal_key(height), seal.to_canonical_bytes())])?)
+}
+
+/// Seal executed block `height` was proposed under, if one was kept
+pub fn stored_seal(state: &RocksStateDB, height: u64) -> Result<Option<ProposalSeal>> {
+    let Some(bytes) = state.get_sync(&seal_key(height))? else {
+        return Ok(None);
+    };
+    let seal = ProposalSeal::from_canonical_bytes(&bytes)
+        .map_err(|e| anyhow!("Seal of block {} is corrupt: {}", height, e))?;
+    Ok(Some(seal))
+}
+
+/// Decode a relayed block and its seal and import them as the proposal they make up
+pub async fn import_relayed(consensus: &RwLock<Consensus>, block: &[u8], seal: &[u8]) -> Result<ImportOutcome> {
+    let block = Block::from_canonical_bytes(block)?;
+    let seal = ProposalSeal::from_canonical_bytes(seal).map_err(|e| anyhow!("Block without a valid seal: {}", e))?;
+    let proposal = BlockProposal::from_seal(block, seal);
+    Ok(consensus.read().await.import_proposal(&proposal).await?)
+}
+
+/// Handle messages from the node's other tasks until shutdown or a `Shutdown` message, raising
+/// `sync_target` to the height of each block imported from peers and submitting the
+/// attestations of peers to consensus
+pub async fn run_messages(
+    messages: Arc<Mutex<tokio::sync::mpsc::Receiver<NodeMessage>>>,
+    consensus: Arc<RwLock<Consensus>>,
+    tx_pool: Arc<RwLock<TxPool>>,
+    sync_target: Arc<AtomicU64>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let mut messages = messages.lock().await;
+    loop {
+        let message = tokio::select! {
+            message = messages.recv() => message,
+            _ = shutdown.cancelled() => None,
+        };
+        match message {
+            Some(NodeMessage::BlockReceived(block, seal)) => match import_relayed(&consensus, &block, &seal).await {
+                Ok(outcome) if outcome.connected.is_empty() => {
+                    println!("Kept relayed block {} on a side branch", outcome.height);
+                }
+                Ok(outcome) => {
+                    println!("Imported relayed block {} ({})", outcome.height, hex::encode(outcome.hash));
+                    sync_target.fetch_max(outcome.height, Ordering::Relaxed);
+                }
+                Err(e) => eprintln!("Rejected relayed block: {}", e),
+            },
+            Some(NodeMessage::AttestationReceived(bytes)) => {
+                let submitted = match Attestation::from_canonical_bytes(&bytes) {
+                    Ok(attestation) => {
+                        let consensus = consensus.read().await;
+                        consensus.submit_attestation(&attestation).await.map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+                match submitted {
+                    Ok(Some(checkpoint)) => println!("Finalized block {} on peers' attestations", checkpoint.height),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Rejected attestation: {}", e),
+                }
+            }
+            Some(NodeMessage::TransactionReceived(bytes)) => {
+                let added = match Transaction::from_canonical_bytes(&bytes) {
+                    Ok(tx) => tx_pool.write().await.add_transaction(tx).await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                if let Err(e) = added {
+                    eprintln!("Rejected relayed transaction: {}", e);
+                }
+            }
+            Some(NodeMessage::Shutdown) | None => return Ok(()),
+        }
+    }
+}
+
+/// Execute blocks into `state_db` as consensus finalizes them, checking every `interval`, and
+/// keep the seal of each
+pub async fn run_execution(
+    mut executor: BlockExecutor,
+    consensus: Arc<RwLock<Consensus>>,
+    state_db: Arc<RwLock<RocksStateDB>>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    while sleep_unless_shutdown(&shutdown, interval).await {
+        let executed = state_db.read().await.latest_version().unwrap_or(GENESIS_VERSION);
+        let blocks = consensus.read().await.final_blocks_above(executed).await;
+        for FinalBlock { block, fee_recipient, seal } in blocks {
+            let mut state = state_db.write().await;
+            executor.process_block(&mut state, &block, &fee_recipient)?;
+            store_seal(&mut state, block.header.height, &seal)?;
+            println!("Executed block {} ({} transactions)", block.header.height, block.transactions.len());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{admin, ColdL3Node, NodeConfig, NodeMessage, NodeRole};
+    use block_sync::{Block, BlockHeader, BlockProof, Canonical, ProofType};
+    use consensus::engine::{header_id, header_signing_bytes};
+    use consensus::validators::{Validator, ValidatorSet};
+    use consensus::BlockProposal;
+    use ed25519_dalek::{Signer, SigningKey};
+    use execution::Network;
+    use pow::auxpow::bare_coinbase;
+    use pow::{AuxPow, MergeMinedProof, ParentBlockHeader};
+    use tokio::time::{Duration, Instant};
+
+    /// Empty block on `parent` merge-mined at difficulty 1 and signed by validator 0's `key`
+    fn mined_proposal(key: &SigningKey, parent: &BlockHeader) -> BlockProposal {
+        let header = BlockHeader {
+            height: parent.height + 1,
+            prev_hash: header_id(parent),
+            merkle_root: consensus::merkle_root(&[]),
+            timestamp: parent.timestamp + 2,
+            nonce: 0,
+            difficulty: 1,
+            nullifier_root: [0u8; 32],
+        };
+        let mut parent_header = ParentBlockHeader::default();
+        let aux_pow = AuxPow::create(header.hash().unwrap(), bare_coinbase(header.height), &[], &mut parent_header);
+        let proof = MergeMinedProof { parent_header, aux_pow: aux_pow.unwrap() };
+        let signature = key.sign(&header_signing_bytes(&header)).to_bytes().to_vec();
+        BlockProposal {
+            block: Block {
+                header,
+                transactions: vec![],
+                proof: BlockProof { proof_type: ProofType::PoW, proof_data: serde_json::to_vec(&proof).unwrap() },
+            },
+            proposer: 0,
+            timestamp: parent.timestamp + 2,
+            signature,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_relayed_blocks_are_imported_and_executed_once_final() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = NodeConfig {
+            role: NodeRole::Validator,
+            data_dir: temp_dir.path().to_str().unwrap().to_string(),
+            network: Network::Devnet,
+            enable_rpc: false,
+            enable_p2p: false,
+            enable_bridge: false,
+            ..Default::default()
+        };
+        std::fs::write(temp_dir.path().join(admin::VALIDATOR_KEY_FILE), hex::encode([7u8; 32])).unwrap();
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut node = ColdL3Node::new(config).await.unwrap();
+        // Staked under the node's validator id, which its attestations are signed as
+        node.staking.write().await.stake(0, key.verifying_key().to_bytes(), 10_000).await.unwrap();
+        node.start().await.unwrap();
+
+        // A block without a seal is dropped, the sealed ones are imported as proposals
+        let mut parent = node.chain.genesis_header();
+        let unsealed = mined_proposal(&key, &parent).block.to_canonical_bytes();
+        node.message_tx.send(NodeMessage::BlockReceived(unsealed, vec![])).await.unwrap();
+        let mut ids = vec![];
+        for _ in 0..4 {
+            let proposal = mined_proposal(&key, &parent);
+            let message = NodeMessage::BlockReceived(
+                proposal.block.to_canonical_bytes(),
+                proposal.seal().to_canonical_bytes(),
+            );
+            node.message_tx.send(message).await.unwrap();
+            ids.push(header_id(&proposal.block.header));
+            parent = proposal.block.header;
+        }
+
+        // This validator attests to the confirmed blocks, and those it finalizes are executed
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            let executed = node.state_db.read().await.latest_version();
+            if executed.is_some_and(|height| height >= 1) {
+                break;
+            }
+            assert!(Instant::now() < deadline, "no relayed block was executed");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let head = node.consensus.read().await.head_with_difficulty().await.unwrap().unwrap().0;
+        assert_eq!((head.height, head.hash), (4, ids[3]));
+        let (block, fee_recipient) = execution::receipt::get_block(&*node.state_db.read().await, 1).unwrap().unwrap();
+        assert_eq!(header_id(&block.header), ids[0]);
+        assert_eq!(fee_recipient, key.verifying_key().to_bytes().to_vec());
+        let seal = super::stored_seal(&*node.state_db.read().await, 1).unwrap().unwrap();
+        assert_eq!(seal, mined_proposal(&key, &node.chain.genesis_header()).seal());
+        node.stop().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_each_validator_proposes_and_attests_under_its_own_id() {
+        let keys = [SigningKey::from_bytes(&[7u8; 32]), SigningKey::from_bytes(&[8u8; 32])];
+        let mut validators = ValidatorSet::default();
+        let mut nodes = vec![];
+        let mut temp_dirs = vec![];
+        for (id, key) in keys.iter().enumerate() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let config = NodeConfig {
+                role: NodeRole::Validator,
+                validator_id: id as u64,
+                data_dir: temp_dir.path().to_str().unwrap().to_string(),
+                network: Network::Devnet,
+                enable_rpc: false,
+                enable_p2p: false,
+                enable_bridge: false,
+                ..Default::default()
+            };
+            std::fs::write(temp_dir.path().join(admin::VALIDATOR_KEY_FILE), hex::encode(key.to_bytes())).unwrap();
+            nodes.push(ColdL3Node::new(config).await.unwrap());
+            temp_dirs.push(temp_dir);
+            let public_key = key.verifying_key().to_bytes();
+            validators.insert(Validator { id: id as u64, public_key, stake: 10_000 }).unwrap();
+        }
+        for node in &nodes {
+            node.consensus.read().await.update_validators(validators.clone()).await;
+        }
+
+        // The validators take turns proposing, and each proposal is accepted by both nodes
+        let mut parent = nodes[0].chain.genesis_header();
+        for height in 1..=3u64 {
+            let proposer = &nodes[height as usize % 2];
+            let block = mined_proposal(&keys[0], &parent).block;
+            let proposal = proposer.consensus.read().await.seal_proposal(block).await.unwrap();
+            assert_eq!(proposal.proposer, height % 2);
+            for node in &nodes {
+                let outcome = node.consensus.read().await.import_proposal(&proposal).await.unwrap();
+                assert_eq!(outcome.fee_recipient, keys[height as usize % 2].verifying_key().to_bytes());
+            }
+            parent = proposal.block.header;
+        }
+
+        // Block 1 is final once both have attested to it, each under its own id
+        let attestations = [
+            nodes[0].consensus.read().await.create_attestation(1).await.unwrap(),
+            nodes[1].consensus.read().await.create_attestation(1).await.unwrap(),
+        ];
+        assert_eq!((attestations[0].validator_id, attestations[1].validator_id), (0, 1));
+        for node in &nodes {
+            let consensus = node.consensus.read().await;
+            assert_eq!(consensus.submit_attestation(&attestations[0]).await.unwrap(), None);
+            let checkpoint = consensus.submit_attestation(&attestations[1]).await.unwrap().unwrap();
+            assert_eq!(checkpoint.height, 1);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_a_validator_node_finalizes_blocks_on_a_sequencer() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut nodes = vec![];
+        let mut temp_dirs = vec![];
+        for role in [NodeRole::Sequencer, NodeRole::Validator] {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let config = NodeConfig {
+                role,
+                data_dir: temp_dir.path().to_str().unwrap().to_string(),
+                network: Network::Devnet,
+                p2p_port: 0,
+                enable_rpc: false,
+                enable_bridge: false,
+                ..Default::default()
+            };
+            if role == NodeRole::Validator {
+                std::fs::write(temp_dir.path().join(admin::VALIDATOR_KEY_FILE), hex::encode(key.to_bytes())).unwrap();
+            }
+            let node = ColdL3Node::new(config).await.unwrap();
+            node.staking.write().await.stake(0, key.verifying_key().to_bytes(), 10_000).await.unwrap();
+            nodes.push(node);
+            temp_dirs.push(temp_dir);
+        }
+        let sequencer_info = nodes[0].network.as_ref().unwrap().info.clone();
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let addr = loop {
+            let listen_addrs = sequencer_info.read().await.listen_addrs.clone();
+            if let Some(addr) = listen_addrs.iter().find(|addr| addr.contains("127.0.0.1")) {
+                break addr.parse().unwrap();
+            }
+            assert!(Instant::now() < deadline, "the sequencer did not start listening");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        nodes[1].network.as_ref().unwrap().peers.add_peer(addr).unwrap();
+        for node in &mut nodes {
+            node.start().await.unwrap();
+        }
+        while sequencer_info.read().await.connected_peers == 0 {
+            assert!(Instant::now() < deadline, "the validator did not connect to the sequencer");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        // Let gossipsub exchange subscriptions before anything is attested
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // Both follow the same chain; only the validator attests to it
+        let mut parent = nodes[0].chain.genesis_header();
+        for _ in 0..4 {
+            let proposal = mined_proposal(&key, &parent);
+            for node in &nodes {
+                let message = NodeMessage::BlockReceived(
+                    proposal.block.to_canonical_bytes(),
+                    proposal.seal().to_canonical_bytes(),
+                );
+                node.message_tx.send(message).await.unwrap();
+            }
+            parent = proposal.block.header;
+        }
+        loop {
+            if let Some(head) = nodes[0].consensus.read().await.get_finalized_head().await {
+                assert!(head.height >= 1);
+                break;
+            }
+            assert!(Instant::now() < deadline, "the sequencer finalized nothing");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(sequencer_info.read().await.attestations_received >= 1);
+        for node in &mut nodes {
+            node.stop().await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Chain parameters of each network. A node follows exactly one chain, picked by `--network`
+//! or a custom spec file, and everything that must agree across its peers lives here: the
+//! genesis block and allocation, difficulty and block time, emission and the merge-mining tag.
+//! A data directory records the genesis block it was initialized with, so a node cannot
+//! start on one chain's data while configured for another.
+
+use crate::config::ConfigError;
+use anyhow::bail;
+use block_sync::{Block, BlockHeader, BlockProof, Canonical, ProofType};
+use consensus::{BlockLimits, ConsensusConfig};
+use execution::{EmissionSchedule, ExecutionConfig, FeeMarketConfig, MintAttestation, Network};
+use hashing::{Domain, Hasher};
+use net_p2p::{Multiaddr, NetworkConfig};
+use serde::{Deserialize, Serialize};
+use state_db::{Genesis, RocksStateDB};
+use std::path::Path;
+use std::time::Duration;
+
+/// Genesis block of an initialized data directory, relative to it
+pub const GENESIS_BLOCK_FILE: &str = "genesis.json";
+
+/// Proof-of-work difficulty rules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyParams {
+    pub initial_difficulty: u64,
+    /// Blocks between difficulty adjustments
+    pub retarget_interval: u64,
+    /// Maximum factor difficulty can change by per adjustment
+    pub max_adjustment: u64,
+}
+
+impl Default for DifficultyParams {
+    fn default() -> Self {
+        Self {
+            initial_difficulty: 1000,
+            retarget_interval: 60,
+            max_adjustment: 4,
+        }
+    }
+}
+
+/// Genesis block a data directory was initialized with and the chain it starts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisRecord {
+    pub chain: String,
+    pub chain_id: u64,
+    /// Hex-encoded genesis hash
+    pub genesis_hash: String,
+    pub block: Block,
+}
+
+/// Everything that identifies a chain and must match across its nodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+    pub name: String,
+    pub chain_id: u64,
+    /// Network whose preset this spec starts from
+    pub network: Network,
+    /// Timestamp of the genesis block
+    pub genesis_timestamp: u64,
+    /// Initial HEAT balances
+    #[serde(default)]
+    pub genesis: Genesis,
+    #[serde(default)]
+    pub difficulty: DifficultyParams,
+    /// Target seconds between blocks
+    pub block_time_secs: u64,
+    /// Size and weight limits of blocks
+    #[serde(default)]
+    pub block_limits: BlockLimits,
+    pub emission: EmissionSchedule,
+    /// Base fee rules; no base fee is charged unless enabled
+    #[serde(default)]
+    pub fee_market: FeeMarketConfig,
+    /// Bridge attesters whose signatures authorize mints; nothing mints without them
+    #[serde(default)]
+    pub mint_attestation: MintAttestation,
+    /// Multiaddrs dialed to join the network
+    #[serde(default)]
+    pub bootstrap_peers: Vec<String>,
+    /// Tag identifying this chain in merge-mined Fuego coinbases
+    pub aux_pow_tag: String,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self::for_network(Network::Mainnet)
+    }
+}
+
+impl ChainSpec {
+    /// The spec `network` launched with
+    pub fn for_network(network: Network) -> Self {
+        let (name, chain_id, genesis_timestamp, block_time_secs, initial_difficulty) = match network {
+            Network::Mainnet => ("mainnet", 1, 1_735_689_600, 10, 1000),
+            Network::Testnet => ("testnet", 2, 1_735_689_600, 10, 100),
+            Network::Devnet => ("devnet", 3, 1_735_689_600, 2, 1),
+        };
+        Self {
+            name: name.to_string(),
+            chain_id,
+            network,
+            genesis_timestamp,
+            genesis: Genesis::default(),
+            difficulty: DifficultyParams {
+                initial_difficulty,
+                ..Default::default()
+            },
+            block_time_secs,
+            block_limits: BlockLimits::default(),
+            emission: EmissionSchedule::for_network(network),
+            fee_market: FeeMarketConfig::default(),
+            mint_attestation: MintAttestation::default(),
+            bootstrap_peers: Vec::new(),
+            aux_pow_tag: format!("c0dl3-{}", name),
+        }
+    }
+
+    /// Load a custom chain spec JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path).map_err(|e| ConfigError::ReadError(format!("{}: {}", path.display(), e)))?;
+        let spec: Self = serde_json::from_slice(&contents)
+            .map_err(|e| ConfigError::ParseError(format!("{}: {}", path.display(), e)))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let problem = if self.name.trim().is_empty() {
+            Some("name is empty".to_string())
+        } else if self.block_time_secs == 0 {
+            Some("block_time_secs is 0".to_string())
+        } else if self.difficulty.initial_difficulty == 0 || self.difficulty.max_adjustment == 0 {
+            Some("difficulty.initial_difficulty and difficulty.max_adjustment must be above 0".to_string())
+        } else if self.difficulty.retarget_interval < 2 {
+            Some("difficulty.retarget_interval must be at least 2".to_string())
+        } else if self.aux_pow_tag.is_empty() {
+            Some("aux_pow_tag is empty".to_string())
+        } else if let Some(peer) = self.bootstrap_peers.iter().find(|peer| peer.parse::<Multiaddr>().is_err()) {
+            Some(format!("bootstrap peer {} is not a multiaddr", peer))
+        } else if let Err(e) = self.block_limits.validate() {
+            Some(format!("block_limits: {}", e))
+        } else if let Err(e) = self.emission.validate() {
+            Some(format!("emission: {}", e))
+        } else if let Err(e) = self.mint_attestation.validate() {
+            Some(format!("mint_attestation: {}", e))
+        } else if let Err(e) = self.genesis.total_supply().and_then(|_| self.genesis.accounts()) {
+            Some(format!("genesis: {}", e))
+        } else {
+            None
+        };
+        match problem {
+            Some(problem) => Err(ConfigError::ValidationError(format!("chain spec {}: {}", self.name, problem))),
+            None => Ok(()),
+        }
+    }
+
+    /// Header of block 0, committing to the genesis allocation
+    pub fn genesis_header(&self) -> BlockHeader {
+        let alloc = serde_json::to_vec(&self.genesis.alloc).expect("genesis allocation serializes");
+        BlockHeader {
+            height: 0,
+            prev_hash: [0u8; 32],
+            merkle_root: hashing::hash(Domain::GenesisAlloc, &alloc),
+            timestamp: self.genesis_timestamp,
+            nonce: 0,
+            difficulty: self.difficulty.initial_difficulty,
+            nullifier_root: [0u8; 32],
+        }
+    }
+
+    /// Hash identifying the chain: its id and genesis header. Peers advertising another
+    /// genesis hash are on a different chain.
+    pub fn genesis_hash(&self) -> [u8; 32] {
+        let header = self.genesis_header().to_canonical_bytes();
+        Hasher::new(Domain::Genesis).u64(self.chain_id).bytes(&header).finish()
+    }
+
+    /// Block 0: the genesis header, no transactions and no proof
+    pub fn genesis_block(&self) -> Block {
+        Block {
+            header: self.genesis_header(),
+            transactions: Vec::new(),
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: Vec::new(),
+            },
+        }
+    }
+
+    /// Write the genesis block into `data_dir` and the genesis allocation into `state`,
+    /// neither of which may hold a chain yet
+    pub fn init_data_dir(&self, data_dir: &Path, state: &mut RocksStateDB) -> anyhow::Result<GenesisRecord> {
+        if let Some(record) = read_genesis_record(data_dir)? {
+            bail!("{} already holds the {} chain (genesis {})", data_dir.display(), record.chain, record.genesis_hash);
+        }
+        if state.latest_version().is_some() {
+            bail!("{} already holds state", data_dir.display());
+        }
+        if !self.genesis.alloc.is_empty() {
+            state.apply_genesis(&self.genesis)?;
+        }
+        self.write_genesis_record(data_dir)
+    }
+
+    /// Fail unless `data_dir` was initialized for this chain. A data directory without any
+    /// state yet is initialized as `node init` would; one holding state without a genesis
+    /// record, such as an imported snapshot, is assumed to be on this chain.
+    pub fn open_data_dir(&self, data_dir: &Path, state: &mut RocksStateDB) -> anyhow::Result<()> {
+        let genesis_hash = hex::encode(self.genesis_hash());
+        match read_genesis_record(data_dir)? {
+            Some(record) if record.genesis_hash == genesis_hash => Ok(()),
+            Some(record) => bail!(
+                "{} was initialized for {} (chain id {}, genesis {}) but the node is configured for {} \
+                 (chain id {}, genesis {}); pick the matching --network or use another data directory",
+                data_dir.display(),
+                record.chain,
+                record.chain_id,
+                record.genesis_hash,
+                self.name,
+                self.chain_id,
+                genesis_hash
+            ),
+            None if state.latest_version().is_none() => self.init_data_dir(data_dir, state).map(|_| ()),
+            None => self.write_genesis_record(data_dir).map(|_| ()),
+        }
+    }
+
+    fn write_genesis_record(&self, data_dir: &Path) -> anyhow::Result<GenesisRecord> {
+        let record = GenesisRecord {
+            chain: self.name.clone(),
+            chain_id: self.chain_id,
+            genesis_hash: hex::encode(self.genesis_hash()),
+            block: self.genesis_block(),
+        };
+        std::fs::create_dir_all(data_dir)?;
+        std::fs::write(data_dir.join(GENESIS_BLOCK_FILE), serde_json::to_vec_pretty(&record)?)?;
+        Ok(record)
+    }
+
+    /// P2P settings listening on `p2p_port`, dialing this chain's bootstrap peers and only
+    /// keeping peers with the same genesis
+    pub fn network_config(&self, p2p_port: u16) -> NetworkConfig {
+        NetworkConfig {
+            listen_addr: format!("/ip4/0.0.0.0/tcp/{}", p2p_port).parse().expect("valid listen multiaddr"),
+            bootstrap_peers: self.bootstrap_peers.iter().filter_map(|peer| peer.parse().ok()).collect(),
+            genesis_hash: Some(self.genesis_hash()),
+            ..Default::default()
+        }
+    }
+
+    /// Consensus settings following this chain's block time and difficulty
+    pub fn consensus_config(&self) -> ConsensusConfig {
+        ConsensusConfig {
+            block_time: Duration::from_secs(self.block_time_secs),
+            pow_difficulty: self.difficulty.initial_difficulty,
+            max_block_size: self.block_limits.max_transactions,
+            limits: self.block_limits.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Execution settings following this chain's id and emission
+    pub fn execution_config(&self) -> ExecutionConfig {
+        ExecutionConfig {
+            emission: self.emission.clone(),
+            chain_id: self.chain_id,
+            fee_market: self.fee_market.clone(),
+            mint_attestation: self.mint_attestation.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The genesis block `data_dir` was initialized with, if it has been
+pub fn read_genesis_record(data_dir: &Path) -> anyhow::Result<Option<GenesisRecord>> {
+    let path = data_dir.join(GENESIS_BLOCK_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&std::fs::read(path)?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state_db::account::GenesisAccount;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_networks_have_distinct_genesis() {
+        let hashes: Vec<[u8; 32]> = [Network::Mainnet, Network::Testnet, Network::Devnet]
+            .into_iter()
+            .map(|network| ChainSpec::for_network(network).genesis_hash())
+            .collect();
+        assert_ne!(hashes[0], hashes[1]);
+        assert_ne!(hashes[1], hashes[2]);
+        assert_eq!(ChainSpec::default().genesis_hash(), hashes[0]);
+
+        // Funding any account changes the genesis
+        let mut funded = ChainSpec::for_network(Network::Devnet);
+        funded.genesis.alloc.insert("aa".repeat(20), GenesisAccount { balance: 1 });
+        assert_ne!(funded.genesis_hash(), hashes[2]);
+    }
+
+    #[test]
+    fn test_custom_spec_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("chain.json");
+        let mut spec = ChainSpec::for_network(Network::Devnet);
+        spec.name = "local".to_string();
+        spec.chain_id = 1337;
+        std::fs::write(&path, serde_json::to_vec(&spec).unwrap()).unwrap();
+        assert_eq!(ChainSpec::from_file(&path).unwrap(), spec);
+        assert_eq!(spec.consensus_config().block_time, Duration::from_secs(2));
+
+        spec.bootstrap_peers = vec!["/ip4/10.0.0.1/tcp/30303".to_string()];
+        let network = spec.network_config(30400);
+        assert_eq!(network.listen_addr.to_string(), "/ip4/0.0.0.0/tcp/30400");
+        assert_eq!(network.bootstrap_peers.len(), 1);
+        assert_eq!(network.genesis_hash, Some(spec.genesis_hash()));
+        spec.bootstrap_peers.push("10.0.0.2:30303".to_string());
+        assert!(spec.validate().unwrap_err().to_string().contains("not a multiaddr"));
+
+        spec.block_time_secs = 0;
+        std::fs::write(&path, serde_json::to_vec(&spec).unwrap()).unwrap();
+        assert!(matches!(ChainSpec::from_file(&path), Err(ConfigError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_data_dir_is_bound_to_its_genesis() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+        let mut devnet = ChainSpec::for_network(Network::Devnet);
+        devnet.genesis.alloc.insert("aa".repeat(20), GenesisAccount { balance: 500 });
+
+        let record = devnet.init_data_dir(temp_dir.path(), &mut state).unwrap();
+        assert_eq!(record.genesis_hash, hex::encode(devnet.genesis_hash()));
+        assert_eq!(state.get_account(&[0xaa; 20]).unwrap().balance, 500);
+        assert!(devnet.init_data_dir(temp_dir.path(), &mut state).is_err());
+
+        devnet.open_data_dir(temp_dir.path(), &mut state).unwrap();
+        let error = ChainSpec::for_network(Network::Testnet)
+            .open_data_dir(temp_dir.path(), &mut state)
+            .unwrap_err();
+        assert!(error.to_string().contains("initialized for devnet"));
+    }
+}
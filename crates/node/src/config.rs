@@ -0,0 +1,449 @@
+//! Loading `NodeConfig` from a TOML file and `CODL3_*` environment variables, and
+//! checking the result before the node starts.
+//!
+//! Settings are layered: defaults, then the config file, then the environment, then
+//! command line flags, which `main` applies last. An environment variable names a
+//! setting by its path with `__` between levels, e.g. `CODL3_P2P_PORT=30304` or
+//! `CODL3_STAKING__MIN_STAKE=5000`.
+
+use crate::reload::parse_log_level;
+use crate::{ChainSpec, NodeConfig};
+use consensus::signer::SignerConfig;
+use state_db::{Genesis, StorageMode};
+use std::path::Path;
+use thiserror::Error;
+use toml::{Table, Value};
+
+/// Prefix of environment variables that override config settings
+pub const ENV_PREFIX: &str = "CODL3_";
+
+/// Settings never printed or reported, only whether they are set
+pub(crate) const SECRET_SETTINGS: &[&str] = &["admin_token"];
+
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// Configuration loading and validation errors
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config: {0}")]
+    ReadError(String),
+    #[error("Invalid config file: {0}")]
+    ParseError(String),
+    #[error("Invalid environment override: {0}")]
+    EnvError(String),
+    #[error("Invalid command line flag: {0}")]
+    FlagError(String),
+    #[error("Invalid configuration: {0}")]
+    ValidationError(String),
+}
+
+impl NodeConfig {
+    /// Defaults overlaid with `path`, when given, and then with the process environment
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env(std::env::vars())
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::ReadError(format!("{}: {}", path.display(), e)))?;
+        Self::from_toml(&contents).map_err(|e| match e {
+            ConfigError::ParseError(reason) => ConfigError::ParseError(format!("{}: {}", path.display(), reason)),
+            other => other,
+        })
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        toml::from_str(contents).map_err(|e| ConfigError::ParseError(e.to_string()))
+    }
+
+    /// Override settings from `CODL3_*` variables in `vars`; other variables are ignored
+    pub fn apply_env(self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Self, ConfigError> {
+        let mut table = Table::try_from(&self).map_err(|e| ConfigError::EnvError(e.to_string()))?;
+        let mut vars: Vec<(String, String)> =
+            vars.into_iter().filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+        vars.sort();
+
+        for (name, raw) in vars {
+            let path: Vec<String> = name[ENV_PREFIX.len()..].split("__").map(str::to_lowercase).collect();
+            if path.iter().any(String::is_empty) {
+                return Err(ConfigError::EnvError(format!("{} does not name a setting", name)));
+            }
+            // Values are read as TOML scalars where they parse as one, so `true` and `30304`
+            // keep their types, falling back to a plain string for fields that want one
+            let mut last_error = None;
+            let candidates = [parse_env_value(&raw), Value::String(raw.clone())];
+            for value in candidates {
+                let mut candidate = table.clone();
+                set_path(&mut candidate, &path, value);
+                match candidate.clone().try_into::<NodeConfig>() {
+                    Ok(_) => {
+                        table = candidate;
+                        last_error = None;
+                        break;
+                    }
+                    Err(e) => last_error = last_error.or(Some(e.message().to_string())),
+                }
+            }
+            if let Some(reason) = last_error {
+                return Err(ConfigError::EnvError(format!("{}={}: {}", name, raw, reason)));
+            }
+        }
+        table.try_into().map_err(|e: toml::de::Error| ConfigError::EnvError(e.to_string()))
+    }
+
+    /// The configuration as a TOML document, as printed by `node config print-effective`.
+    /// Secrets such as `admin_token` are redacted.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        let mut table = Table::try_from(self).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        for secret in SECRET_SETTINGS {
+            if let Some(value) = table.get_mut(*secret) {
+                *value = Value::String(REDACTED.to_string());
+            }
+        }
+        toml::to_string_pretty(&table).map_err(|e| ConfigError::ParseError(e.to_string()))
+    }
+
+    /// The chain spec file when one is configured, otherwise the preset of `network`, with
+    /// the allocation of `genesis_file` when one is given
+    pub fn chain_spec(&self) -> Result<ChainSpec, ConfigError> {
+        let mut spec = match &self.chain_spec {
+            Some(path) => ChainSpec::from_file(path)?,
+            None => ChainSpec::for_network(self.network),
+        };
+        if let Some(genesis_file) = &self.genesis_file {
+            spec.genesis = Genesis::from_file(genesis_file)
+                .map_err(|e| ConfigError::ReadError(format!("{}: {}", genesis_file, e)))?;
+            spec.validate()?;
+        }
+        Ok(spec)
+    }
+
+    /// Check the settings fit together, reporting every problem found
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.data_dir.trim().is_empty() {
+            problems.push("data_dir is empty".to_string());
+        }
+        if let Err(ConfigError::ValidationError(problem)) = parse_log_level(&self.log_level) {
+            problems.push(problem);
+        }
+
+        let mut listeners: Vec<(&str, u16)> = Vec::new();
+        if self.enable_p2p {
+            listeners.push(("p2p_port", self.p2p_port));
+            if self.max_peers == 0 {
+                problems.push("max_peers is 0 but p2p is enabled".to_string());
+            }
+            if self.p2p_bandwidth.window_secs == 0 || self.p2p_bandwidth.disconnect_after == 0 {
+                problems.push("p2p_bandwidth.window_secs and disconnect_after must be above 0".to_string());
+            }
+        }
+        if let Some(Err(e)) = self.proxy.as_ref().map(|proxy| proxy.parse::<net_p2p::Socks5Proxy>()) {
+            problems.push(e.to_string());
+        }
+        let addresses = [
+            ("rpc_addr", self.enable_rpc.then_some(&self.rpc_addr)),
+            ("metrics_addr", self.metrics_addr.as_ref()),
+            ("health_addr", self.health_addr.as_ref()),
+            (
+                "mining.stratum.listen_addr",
+                self.mining.as_ref().and_then(|mining| mining.stratum.as_ref()).map(|stratum| &stratum.listen_addr),
+            ),
+        ];
+        for (name, addr) in addresses {
+            let Some(addr) = addr else { continue };
+            match addr.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok()) {
+                Some(port) => listeners.push((name, port)),
+                None => problems.push(format!("{} \"{}\" is not a host:port address", name, addr)),
+            }
+        }
+        // Port 0 asks the OS for a free port, so it never conflicts
+        for (i, (name, port)) in listeners.iter().enumerate() {
+            if let Some((other, _)) = listeners[i + 1..].iter().find(|(_, other)| *port != 0 && other == port) {
+                problems.push(format!("{} and {} both use port {}", name, other, port));
+            }
+        }
+
+        if self.tx_pool_size == 0 {
+            problems.push("tx_pool_size is 0".to_string());
+        }
+        if let Some(max_fee) = self.max_fee.filter(|max_fee| *max_fee < self.min_fee) {
+            problems.push(format!("max_fee {} is below min_fee {}", max_fee, self.min_fee));
+        }
+        let limits = &self.tx_pool_limits;
+        if limits.max_per_sender == 0 || limits.expiry_secs == 0 || limits.max_fee_multiplier == 0 {
+            problems
+                .push("tx_pool_limits.max_per_sender, expiry_secs and max_fee_multiplier must be above 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&limits.congestion_threshold) {
+            problems.push("tx_pool_limits.congestion_threshold must be between 0 and 1".to_string());
+        }
+        if self.tasks.initial_backoff_ms == 0 || self.tasks.initial_backoff_ms > self.tasks.max_backoff_ms {
+            problems.push("tasks.initial_backoff_ms must be above 0 and at most tasks.max_backoff_ms".to_string());
+        }
+        if self.metrics_addr.is_some() && self.metrics_interval_secs == 0 {
+            problems.push("metrics_interval_secs is 0 but metrics are enabled".to_string());
+        }
+        if let Some(token) = &self.admin_token {
+            if token.len() < 16 {
+                problems.push("admin_token is shorter than 16 characters".to_string());
+            }
+            if !self.enable_rpc {
+                problems.push("admin_token is set but enable_rpc is false".to_string());
+            }
+        }
+        if self.rpc_limits.enabled && (self.rpc_limits.requests_per_second == 0 || self.rpc_limits.burst == 0) {
+            problems.push("rpc_limits.requests_per_second and rpc_limits.burst must be above 0".to_string());
+        }
+        if self.rpc_limits.max_body_bytes == 0 {
+            problems.push("rpc_limits.max_body_bytes is 0".to_string());
+        }
+        if self.rpc_tls_cert.is_some() != self.rpc_tls_key.is_some() {
+            problems.push("rpc_tls_cert and rpc_tls_key must be set together".to_string());
+        }
+        if self.rpc_jwt_secret.is_some() && !self.enable_rpc {
+            problems.push("rpc_jwt_secret is set but enable_rpc is false".to_string());
+        }
+        if let Some(fuego) = &self.fuego {
+            if !self.role.produces_blocks() {
+                let role = self.role.name();
+                problems.push(format!("[fuego] mining is set but the {} role does not produce blocks", role));
+            }
+            if fuego.wallet_address.trim().is_empty() {
+                problems.push("fuego.wallet_address is empty but mining is enabled by the [fuego] section".to_string());
+            }
+            if fuego.batch_size == 0 {
+                problems.push("fuego.batch_size is 0".to_string());
+            }
+            if fuego.threads == 0 {
+                problems.push("fuego.threads is 0".to_string());
+            }
+        }
+        if let Some(mining) = &self.mining {
+            if !self.role.produces_blocks() {
+                let role = self.role.name();
+                problems.push(format!("[mining] is set but the {} role does not produce blocks", role));
+            }
+            if mining.merge_mining.wallet_address.trim().is_empty() {
+                problems.push("mining.merge_mining.wallet_address is empty".to_string());
+            }
+            if mining.miner.is_none() && mining.stratum.is_none() {
+                problems.push("[mining] is set but neither mining.miner nor mining.stratum is".to_string());
+            }
+            if mining.miner.as_ref().is_some_and(|miner| miner.threads == 0 || miner.batch_size == 0) {
+                problems.push("mining.miner.threads and batch_size must be above 0".to_string());
+            }
+        }
+        if let Some(supervisor) = &self.fuego_supervisor {
+            if supervisor.fuego_binary_path.as_os_str().is_empty() {
+                problems.push("fuego_supervisor.fuego_binary_path is empty".to_string());
+            }
+        }
+        if self.staking.double_sign_slash_bps > 10_000 {
+            problems.push(format!(
+                "staking.double_sign_slash_bps is {}, more than 10000 (100%)",
+                self.staking.double_sign_slash_bps
+            ));
+        }
+        match self.chain_spec() {
+            Ok(chain) if self.role.produces_blocks() => {
+                let block_time = chain.consensus_config().block_time.as_secs();
+                if self.sequencer.lease_duration_secs <= block_time {
+                    problems.push(format!(
+                        "sequencer.lease_duration_secs is {}, not longer than the {}s block time",
+                        self.sequencer.lease_duration_secs, block_time
+                    ));
+                }
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(e.to_string()),
+        }
+        if let Err(e) = self.rewards.validate() {
+            problems.push(format!("rewards: {}", e));
+        }
+        if self.state_db.mode == (StorageMode::Pruned { retention: 0 }) {
+            problems.push("state_db.mode keeps 0 versions; use a retention of at least 1".to_string());
+        }
+        if self.state_db.snapshot_chunk_entries == 0 {
+            problems.push("state_db.snapshot_chunk_entries is 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.logging.sample_ratio) {
+            problems.push("logging.sample_ratio must be between 0 and 1".to_string());
+        }
+        if let Some(endpoint) = &self.logging.otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                problems.push(format!("logging.otlp_endpoint \"{}\" is not an http(s) URL", endpoint));
+            }
+        }
+        let notifications = &self.notifications;
+        for webhook in &notifications.webhooks {
+            if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                problems.push(format!("notifications webhook \"{}\" is not an http(s) URL", webhook.url));
+            }
+        }
+        if !notifications.webhooks.is_empty() && (notifications.max_attempts == 0 || notifications.timeout_secs == 0) {
+            problems.push("notifications.max_attempts and notifications.timeout_secs must be above 0".to_string());
+        }
+        match &self.validator_signer {
+            Some(SignerConfig::Remote { url, public_key, .. }) => {
+                if url.trim().is_empty() {
+                    problems.push("validator_signer.url is empty".to_string());
+                }
+                if !hex::decode(public_key).is_ok_and(|key| key.len() == 32) {
+                    problems.push("validator_signer.public_key is not a hex-encoded 32-byte key".to_string());
+                }
+            }
+            Some(SignerConfig::Ledger { address, .. }) if address.trim().is_empty() => {
+                problems.push("validator_signer.address is empty".to_string());
+            }
+            _ => {}
+        }
+        if let Some(key) = &self.mint_attester_key {
+            if !hex::decode(key.trim_start_matches("0x")).is_ok_and(|key| key.len() == 32) {
+                problems.push("mint_attester_key is not a hex-encoded 32-byte key".to_string());
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::ValidationError(problems.join("; ")))
+        }
+    }
+}
+
+fn parse_env_value(raw: &str) -> Value {
+    format!("value = {}", raw)
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+/// Set `path` in `table`, creating intermediate tables, so an override can enable an
+/// optional section such as `[fuego]`
+fn set_path(table: &mut Table, path: &[String], value: Value) {
+    let (key, parents) = path.split_last().expect("path is not empty");
+    let mut current = table;
+    for parent in parents {
+        let entry = current.entry(parent.clone()).or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        current = entry.as_table_mut().expect("entry is a table");
+    }
+    current.insert(key.clone(), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_file_then_env_overrides() {
+        let file = r#"
+            p2p_port = 30400
+            max_peers = 10
+
+            [staking]
+            min_stake = 2000
+
+            [rewards.vesting.validators]
+            cliff_epochs = 1
+            duration_epochs = 4
+        "#;
+        let config = NodeConfig::from_toml(file).unwrap();
+        assert_eq!(config.p2p_port, 30400);
+        assert_eq!(config.staking.min_stake, 2000);
+        assert_eq!(config.staking.unbonding_period, 1000);
+        assert_eq!(config.rewards.vesting[&rewards::RewardClass::Validators].duration_epochs, 4);
+        assert_eq!(config.rpc_addr, "127.0.0.1:8545");
+
+        let config = config
+            .apply_env(env(&[
+                ("CODL3_MAX_PEERS", "25"),
+                ("CODL3_ENABLE_BRIDGE", "false"),
+                ("CODL3_SETTLEMENT", "zksync"),
+                ("CODL3_ROLE", "sequencer"),
+                ("CODL3_SEQUENCER__LEASE_DURATION_SECS", "45"),
+                ("CODL3_WATCHTOWER__CHALLENGER", "0xc0ffee"),
+                ("CODL3_METRICS_ADDR", "127.0.0.1:9615"),
+                ("CODL3_FUEGO__WALLET_ADDRESS", "fire1"),
+                ("CODL3_FUEGO__RPC__URL", "http://10.0.0.2:18180"),
+                ("CODL3_ADMIN_TOKEN", "0123456789abcdef"),
+                ("CODL3_RPC_LIMITS__BURST", "100"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+        assert_eq!(config.p2p_port, 30400);
+        assert_eq!(config.max_peers, 25);
+        assert_eq!(config.rpc_limits.burst, 100);
+        assert_eq!(config.rpc_limits.method_costs["eth_getLogs"], 10);
+        assert!(!config.enable_bridge);
+        assert_eq!(config.settlement, bridge::settlement::SettlementLayer::ZkSync);
+        assert_eq!(config.role, crate::NodeRole::Sequencer);
+        assert_eq!(config.sequencer.lease_duration_secs, 45);
+        assert_eq!(config.watchtower.challenger.as_deref(), Some("0xc0ffee"));
+        assert_eq!(config.metrics_addr.as_deref(), Some("127.0.0.1:9615"));
+        let fuego = config.fuego.as_ref().unwrap();
+        assert_eq!(fuego.wallet_address, "fire1");
+        assert_eq!(fuego.rpc.url, "http://10.0.0.2:18180");
+        config.validate().unwrap();
+
+        // The effective config round-trips through TOML, without revealing secrets
+        assert_eq!(config.admin_token.as_deref(), Some("0123456789abcdef"));
+        assert!(!config.to_toml().unwrap().contains("0123456789abcdef"));
+        let printed = NodeConfig::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(printed.to_toml().unwrap(), config.to_toml().unwrap());
+
+        assert!(matches!(NodeConfig::from_toml("p2p_prot = 1"), Err(ConfigError::ParseError(_))));
+        let bad = NodeConfig::default().apply_env(env(&[("CODL3_MAX_PEERS", "many")]));
+        assert!(matches!(bad, Err(ConfigError::EnvError(reason)) if reason.contains("CODL3_MAX_PEERS")));
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let config = NodeConfig {
+            rpc_addr: "127.0.0.1:30303".to_string(),
+            health_addr: Some("localhost".to_string()),
+            fuego: Some(Default::default()),
+            mining: Some(Default::default()),
+            rpc_tls_cert: Some("rpc.crt".to_string()),
+            proxy: Some("127.0.0.1:9050".to_string()),
+            rewards: rewards::RewardsConfig { epoch_length: 0, ..Default::default() },
+            notifications: crate::NotificationConfig {
+                webhooks: vec![crate::notifier::WebhookConfig {
+                    url: "hooks.example.com".to_string(),
+                    secret: None,
+                    events: Vec::new(),
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let Err(ConfigError::ValidationError(reason)) = config.validate() else {
+            panic!("expected a validation error");
+        };
+        assert!(reason.contains("p2p_port and rpc_addr both use port 30303"));
+        assert!(reason.contains("health_addr \"localhost\" is not a host:port address"));
+        assert!(reason.contains("fuego.wallet_address is empty"));
+        assert!(reason.contains("[fuego] mining is set but the full role does not produce blocks"));
+        assert!(reason.contains("[mining] is set but neither mining.miner nor mining.stratum is"));
+        assert!(reason.contains("mining.merge_mining.wallet_address is empty"));
+        assert!(reason.contains("rpc_tls_cert and rpc_tls_key must be set together"));
+        assert!(reason.contains("proxy 127.0.0.1:9050 is not a socks5:// URL"));
+        assert!(reason.contains("rewards: Configuration error: Epoch length must be positive"));
+        assert!(reason.contains("notifications webhook \"hooks.example.com\" is not an http(s) URL"));
+        NodeConfig::default().validate().unwrap();
+    }
+}
@@ -1,17 +1,57 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
-use tokio::task::JoinHandle;
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-use block_sync::BlockSync;
+use block_sync::{BlockHeader, Canonical};
+use bridge::settlement::SettlementLayer;
+use bridge::watchtower::WatchtowerConfig;
 use bridge::{Bridge, BridgeConfig};
-use commitments::CommitmentEngine;
-use consensus::{Consensus, ConsensusConfig};
+use consensus::finality::{Attestation, FinalityConfig, FinalityGadget};
+use consensus::signer::{connect_signer, SignerConfig};
+use consensus::{BlockProposal, Consensus, ConsensusConfig};
 use encryption::{EncryptionEngine, EncryptionConfig};
-use rpc::{RPCServer, RPCServerConfig};
-use state_db::RocksStateDB;
-use txpool::{TxPool, fee::SimpleFeeAlgorithm, priority::SimplePriorityCalculator};
+use execution::receipt::{get_headers, MAX_HEADER_RANGE};
+use execution::{BlockExecutor, Network, TokenInfo, ELDERNODE_REGISTRY_ADDRESS, MINT_ADDRESS, XFG_MINT_ADDRESS};
+use fuego_integration::{FuegoDaemon, FuegoDaemonConfig, FuegoSupervisor, FuegoSupervisorConfig};
+use metrics::{Metrics, MetricsServer};
+use net_p2p::{BandwidthLimits, NetworkConfig, NetworkHandle};
+use rpc::{AdminConfig, HealthServer, RPCServer, RPCServerConfig, RateLimitConfig, RpcHttpServer};
+use rewards::{RewardsConfig, RewardsEngine};
+use staking::{StakingConfig, ValidatorStaking};
+use state_db::{RocksStateDB, StateDBConfig};
+use txpool::{PoolLimits, TxPool, priority::SimplePriorityCalculator, selection::SelectionConfig};
+use zk_proofs::{ProverService, ProverServiceConfig};
+
+pub mod admin;
+pub mod blocks;
+pub mod chain_spec;
+pub mod config;
+pub mod merge_mining;
+pub mod notifier;
+pub mod reindex;
+pub mod reload;
+pub mod roles;
+pub mod supervisor;
+pub mod telemetry;
+pub mod transition;
+
+pub use chain_spec::ChainSpec;
+pub use config::ConfigError;
+pub use merge_mining::{MiningConfig, MiningPipeline};
+pub use notifier::{NotificationConfig, Notifier};
+pub use reload::{init_logging, ConfigReloader, ReloadReport};
+pub use roles::{FileLeaseStore, LeaseStore, NodeRole, SequencerConfig};
+pub use supervisor::{RestartPolicy, SupervisedTasks, SupervisorConfig, TaskState, TaskStatus, TaskSupervisor};
+pub use telemetry::{LoggingConfig, Telemetry};
+pub use transition::ExecutorTransition;
+
+/// Pooled transactions saved at shutdown, relative to the data directory
+const TX_POOL_FILE: &str = "txpool.bin";
 
 /// Node status information
 #[derive(Debug, Clone)]
@@ -25,30 +65,141 @@ pub struct NodeStatus {
     pub uptime_seconds: u64,
 }
 
-/// Node configuration
-#[derive(Debug, Clone)]
+/// Node configuration; fields missing from a config file keep their defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct NodeConfig {
+    /// `sequencer`, `validator`, `full`, `light` or `watchtower`; decides which tasks run
+    pub role: NodeRole,
+    /// Production lease of the sequencer role
+    pub sequencer: SequencerConfig,
     pub data_dir: String,
     pub rpc_addr: String,
     pub p2p_port: u16,
     pub max_peers: usize,
+    /// Per-peer upload and download caps; peers that keep exceeding them are disconnected
+    pub p2p_bandwidth: BandwidthLimits,
+    /// `socks5://host:port` proxy, e.g. Tor, that P2P dials and Fuego and L1 requests go through
+    pub proxy: Option<String>,
     pub tx_pool_size: usize,
+    /// Lowest fee a pooled transaction may be asked to pay
+    pub min_fee: u64,
+    /// Cap on the fee asked of a pooled transaction; `None` leaves it uncapped
+    pub max_fee: Option<u64>,
+    /// Per-sender caps, congestion fee floor and expiry of pooled transactions
+    pub tx_pool_limits: PoolLimits,
+    /// How the sequencer picks pooled transactions for its blocks
+    pub tx_selection: SelectionConfig,
+    /// Level of tracing output: off, error, warn, info, debug or trace
+    pub log_level: String,
+    /// Export of tracing spans to an OpenTelemetry collector
+    pub logging: LoggingConfig,
+    /// Enables the `admin_` RPC namespace for callers presenting this token
+    pub admin_token: Option<String>,
+    /// Per-IP rate limits, method costs and body size cap for the public RPC server
+    pub rpc_limits: RateLimitConfig,
+    /// PEM certificate chain; with `rpc_tls_key`, RPC is served over TLS
+    pub rpc_tls_cert: Option<String>,
+    pub rpc_tls_key: Option<String>,
+    /// Hex-encoded 32-byte secret file; privileged RPC methods then accept JWTs signed with it
+    pub rpc_jwt_secret: Option<String>,
     pub enable_rpc: bool,
     pub enable_p2p: bool,
     pub enable_bridge: bool,
+    /// Layer the bridge settles on: `arbitrum` or `zksync`
+    pub settlement: SettlementLayer,
+    /// Where a watchtower alerts on mismatching batches and who it challenges them as
+    pub watchtower: WatchtowerConfig,
+    /// L1 tokens the bridge mints and burns besides HEAT
+    pub bridged_tokens: Vec<TokenInfo>,
+    /// Hex ed25519 key signing the mints this node's bridge derives; it must be one of the
+    /// chain spec's mint attesters for them to execute
+    pub mint_attester_key: Option<String>,
+    /// Mine Fuego templates from this daemon when set
+    pub fuego: Option<FuegoDaemonConfig>,
+    /// Merge mine C0DL3 blocks with Fuego when set; sequencers only
+    pub mining: Option<MiningConfig>,
+    /// Proving threads and job queue; mining pauses while the queue is full
+    pub prover: ProverServiceConfig,
+    /// Launch and supervise a local fuegod when set
+    pub fuego_supervisor: Option<FuegoSupervisorConfig>,
+    pub staking: StakingConfig,
+    /// How block rewards are split between miner, validators, liquidity providers and treasury
+    pub rewards: RewardsConfig,
+    /// State history retention; `StorageMode::Archive` keeps every version
+    pub state_db: StateDBConfig,
+    /// Chain to follow when no `chain_spec` file is given
+    pub network: Network,
+    /// Custom chain spec JSON file, overriding `network`
+    pub chain_spec: Option<String>,
+    /// Genesis allocation replacing the chain spec's
+    pub genesis_file: Option<String>,
+    /// Remote signer or Ledger holding the validator key
+    pub validator_signer: Option<SignerConfig>,
+    /// Id the validator key is staked under; proposals and attestations are signed as it
+    pub validator_id: u64,
+    /// Serve Prometheus metrics at `/metrics` on this address when set
+    pub metrics_addr: Option<String>,
+    /// How often subsystem stats are copied into the metrics
+    pub metrics_interval_secs: u64,
+    /// Serve `/health/live` and `/health/ready` on this address when set
+    pub health_addr: Option<String>,
+    /// How long stopping waits for tasks to finish before aborting them
+    pub shutdown_timeout_secs: u64,
+    /// Restart backoff and limits for the node's long-running tasks
+    pub tasks: SupervisorConfig,
+    /// Webhooks node events are posted to
+    pub notifications: NotificationConfig,
 }
 
 impl Default for NodeConfig {
     fn default() -> Self {
         Self {
+            role: NodeRole::Full,
+            sequencer: SequencerConfig::default(),
             data_dir: "./data".to_string(),
             rpc_addr: "127.0.0.1:8545".to_string(),
             p2p_port: 30303,
             max_peers: 50,
+            p2p_bandwidth: BandwidthLimits::default(),
+            proxy: None,
             tx_pool_size: 10000,
+            min_fee: 1,
+            max_fee: None,
+            tx_pool_limits: PoolLimits::default(),
+            tx_selection: SelectionConfig::default(),
+            log_level: "info".to_string(),
+            logging: LoggingConfig::default(),
+            admin_token: None,
+            rpc_limits: RateLimitConfig::default(),
+            rpc_tls_cert: None,
+            rpc_tls_key: None,
+            rpc_jwt_secret: None,
             enable_rpc: true,
             enable_p2p: true,
             enable_bridge: true,
+            settlement: SettlementLayer::Arbitrum,
+            watchtower: WatchtowerConfig::default(),
+            bridged_tokens: Vec::new(),
+            mint_attester_key: None,
+            fuego: None,
+            mining: None,
+            prover: ProverServiceConfig::default(),
+            fuego_supervisor: None,
+            staking: StakingConfig::default(),
+            rewards: RewardsConfig::default(),
+            state_db: StateDBConfig::default(),
+            network: Network::Mainnet,
+            chain_spec: None,
+            genesis_file: None,
+            validator_signer: None,
+            validator_id: 0,
+            metrics_addr: None,
+            metrics_interval_secs: 5,
+            health_addr: None,
+            shutdown_timeout_secs: 30,
+            tasks: SupervisorConfig::default(),
+            notifications: NotificationConfig::default(),
         }
     }
 }
@@ -56,72 +207,321 @@ impl Default for NodeConfig {
 /// Message types for inter-module communication
 #[derive(Debug)]
 pub enum NodeMessage {
-    BlockReceived(Vec<u8>),
+    /// Canonical bytes of a block from a peer and of the proposal seal it came with
+    BlockReceived(Vec<u8>, Vec<u8>),
+    /// Canonical bytes of a transaction from a peer
     TransactionReceived(Vec<u8>),
+    /// Canonical bytes of a validator's attestation from a peer
+    AttestationReceived(Vec<u8>),
     Shutdown,
 }
 
 /// The main COLD L3 Node that orchestrates all subsystems
 pub struct ColdL3Node {
     config: NodeConfig,
+    chain: ChainSpec,
     status: Arc<RwLock<NodeStatus>>,
     message_tx: mpsc::Sender<NodeMessage>,
-    message_rx: mpsc::Receiver<NodeMessage>,
+    /// Taken by the message task, and by its restarts after a failure
+    message_rx: Arc<Mutex<mpsc::Receiver<NodeMessage>>>,
     
     // Subsystems
-    state_db: Arc<RocksStateDB>,
-    commitment_engine: Arc<CommitmentEngine>,
-    block_sync: Arc<BlockSync>,
-    tx_pool: Arc<TxPool>,
+    state_db: Arc<RwLock<RocksStateDB>>,
+    staking: Arc<RwLock<ValidatorStaking>>,
+    rewards: Arc<RwLock<RewardsEngine>>,
+    tx_pool: Arc<RwLock<TxPool>>,
     consensus: Arc<RwLock<Consensus>>,
     bridge: Arc<RwLock<Bridge>>,
     encryption: Arc<EncryptionEngine>,
+    /// Running P2P network, handed to its task when the node starts
+    network: Option<NetworkHandle>,
+    /// Merge mining pipeline, handed to its task when the node starts
+    mining: Option<MiningPipeline>,
+    /// Set while this sequencer holds the production lease, which mining follows
+    production: Arc<watch::Sender<bool>>,
+    prover: Arc<ProverService>,
+    /// Highest block height imported from peers
+    sync_target: Arc<AtomicU64>,
     rpc_server: Option<Arc<RPCServer>>,
+    fuego_daemon: Option<Arc<RwLock<FuegoDaemon>>>,
+    fuego_supervisor: Option<Arc<RwLock<FuegoSupervisor>>>,
+    metrics: Arc<Metrics>,
+    reloader: Arc<ConfigReloader>,
     
-    // Task handles
-    tasks: Vec<JoinHandle<Result<()>>>,
+    // Long-running tasks, all stopped through `shutdown`
+    shutdown: CancellationToken,
+    supervisor: TaskSupervisor,
+}
+
+/// Sleep for `period`, returning false instead once shutdown has been requested
+async fn sleep_unless_shutdown(shutdown: &CancellationToken, period: Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(period) => true,
+        _ = shutdown.cancelled() => false,
+    }
+}
+
+/// Headers of the executed chain, genesis first
+fn executed_headers(state: &RocksStateDB, genesis: BlockHeader) -> Result<Vec<BlockHeader>> {
+    let mut headers = vec![genesis];
+    loop {
+        let page = get_headers(state, headers.len() as u64, MAX_HEADER_RANGE)?;
+        if page.is_empty() {
+            return Ok(headers);
+        }
+        headers.extend(page);
+    }
+}
+
+/// Wait for Ctrl+C or SIGTERM
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
 }
 
 impl ColdL3Node {
     /// Create a new COLD L3 Node instance
     pub async fn new(config: NodeConfig) -> Result<Self> {
+        config.validate()?;
         let (message_tx, message_rx) = mpsc::channel(1000);
+        let chain = config.chain_spec()?;
+        let genesis_hash = hex::encode(chain.genesis_hash());
+        println!("✓ Following {} (chain id {}, genesis {})", chain.name, chain.chain_id, genesis_hash);
         
         // Initialize state database
         let state_db_path = Path::new(&config.data_dir).join("state");
-        let state_db = Arc::new(RocksStateDB::new(&state_db_path)?);
+        let mut state = RocksStateDB::with_config(&state_db_path, config.state_db.clone())?;
+        chain.open_data_dir(Path::new(&config.data_dir), &mut state)?;
+        let state_db = Arc::new(RwLock::new(state));
         
-        // Initialize commitment engine
-        let commitment_engine = Arc::new(CommitmentEngine::new());
+        // Load validator stakes from the state database
+        let staking = Arc::new(RwLock::new(
+            ValidatorStaking::with_state_db(config.staking.clone(), state_db.clone()).await?,
+        ));
         
-        // Initialize block sync
-        let block_sync = Arc::new(BlockSync::new()?);
+        // Load reward accounting from the state database
+        let rewards = Arc::new(RwLock::new(
+            RewardsEngine::with_state_db(config.rewards.clone(), state_db.clone()).await?,
+        ));
         
         // Initialize transaction pool
-        let fee_algorithm = Box::new(SimpleFeeAlgorithm::new(1));
+        let fee_algorithm = reload::fee_algorithm(&config);
         let priority_calculator = Box::new(SimplePriorityCalculator::new());
-        let tx_pool = Arc::new(TxPool::new(fee_algorithm, priority_calculator, config.tx_pool_size));
+        let mut tx_pool = TxPool::new(fee_algorithm, priority_calculator, config.tx_pool_size);
+        tx_pool.set_chain_id(chain.chain_id);
+        tx_pool.set_verify_signatures(chain.block_limits.verify_input_signatures);
+        tx_pool.set_limits(config.tx_pool_limits.clone());
+        if chain.block_limits.priority_ops_enabled {
+            // Bridge mints and validator registry operations get their own lane
+            let accounts = [MINT_ADDRESS, XFG_MINT_ADDRESS, ELDERNODE_REGISTRY_ADDRESS];
+            tx_pool.set_priority_accounts(accounts.iter().map(|account| account.to_vec()).collect());
+        }
+        let tx_pool_file = Path::new(&config.data_dir).join(TX_POOL_FILE);
+        if tx_pool_file.exists() {
+            let restored = tx_pool.load(&tx_pool_file).await?;
+            println!("✓ Restored {} pooled transactions", restored);
+        }
+        let tx_pool = Arc::new(RwLock::new(tx_pool));
         
         // Initialize consensus
-        let consensus_config = ConsensusConfig::default();
-        let consensus = Arc::new(RwLock::new(Consensus::new(consensus_config)?));
+        let consensus_config = ConsensusConfig {
+            node_id: config.validator_id,
+            selection: config.tx_selection.clone(),
+            ..chain.consensus_config()
+        };
+        let finality_config = FinalityConfig {
+            confirmation_depth: consensus_config.min_finality,
+            ..Default::default()
+        };
+        let mut consensus = Consensus::new(consensus_config)?;
+        consensus.set_finality(FinalityGadget::with_state_db(finality_config, state_db.clone()).await?);
+        consensus.attach_tx_pool(tx_pool.clone());
+        if !config.role.signs() {
+            // Only sequencers and validators sign anything
+        } else if let Some(signer_config) = &config.validator_signer {
+            let signer = connect_signer(signer_config).await?;
+            println!("✓ Validator key {} held by external signer", hex::encode(signer.public_key()));
+            consensus.set_signer(signer);
+        } else if let Some(key) = admin::load_validator_key(Path::new(&config.data_dir))? {
+            println!("✓ Validator key {} loaded", hex::encode(key.verifying_key().to_bytes()));
+            consensus.set_signing_key(key);
+        }
+        // Proposals are executed on the state they build on before they are accepted
+        let transition = Arc::new(ExecutorTransition::new(
+            BlockExecutor::new(chain.execution_config())?,
+            state_db.clone(),
+            &chain.genesis_header(),
+        ));
+        consensus.set_state_transition(transition.clone()).await;
+        // Blocks executed before a restart are final; new ones are built on the last of them
+        let headers = executed_headers(&*state_db.read().await, chain.genesis_header())?;
+        consensus.restore_chain(&headers).await?;
+        let consensus = Arc::new(RwLock::new(consensus));
+        
+        // Proving jobs run on their own threads, timed in the node's metrics
+        let metrics = Arc::new(Metrics::new());
+        let prover = Arc::new(ProverService::new(config.prover.clone())?);
+        prover.attach_metrics(metrics.clone());
+        
+        // Merge mining, whose sealed blocks are imported through consensus while the lease is held
+        let production = Arc::new(watch::Sender::new(false));
+        let mining = match &config.mining {
+            Some(mining_config) if config.role.produces_blocks() => {
+                let fee_recipient = consensus
+                    .read()
+                    .await
+                    .validator_public_key()
+                    .ok_or_else(|| anyhow::anyhow!("Mining needs a validator key to sign the blocks it seals"))?;
+                let chain_config = ConsensusConfig {
+                    selection: config.tx_selection.clone(),
+                    ..chain.consensus_config()
+                };
+                let mut pipeline = MiningPipeline::new(
+                    mining_config,
+                    &chain_config,
+                    tx_pool.clone(),
+                    transition.clone(),
+                    fee_recipient.to_vec(),
+                )?;
+                pipeline.follow_backpressure(prover.subscribe_backpressure());
+                pipeline.follow_lease(production.subscribe());
+                println!("✓ Merge mining with fuegod at {}", mining_config.merge_mining.rpc.url);
+                Some(pipeline)
+            }
+            _ => None,
+        };
         
         // Initialize bridge
-        let bridge_config = BridgeConfig::default();
-        let bridge = Arc::new(RwLock::new(Bridge::new(bridge_config)?));
+        let bridge_config = BridgeConfig {
+            settlement: config.settlement,
+            watchtower: config.watchtower.clone(),
+            bridged_tokens: config.bridged_tokens.clone(),
+            chain_id: chain.chain_id,
+            mint_attester_key: config.mint_attester_key.clone(),
+            proxy: config.proxy.clone(),
+            ..Default::default()
+        };
+        let l1_rpc_url = bridge_config.arbitrum_rpc_url.clone();
+        let mut bridge = Bridge::new(bridge_config)?;
+        bridge.set_external_tasks(true);
+        if config.role == NodeRole::Watchtower {
+            bridge.attach_watchtower(state_db.clone()).await?;
+            println!("✓ Watchtower checking batches posted on {}", config.settlement.name());
+        } else if config.enable_bridge && config.role.produces_blocks() {
+            bridge.attach_state_db(state_db.clone()).await?;
+            println!("✓ Bridge settling on {}", config.settlement.name());
+        }
+        let bridge = Arc::new(RwLock::new(bridge));
         
         // Initialize encryption engine
         let encryption_config = EncryptionConfig::default();
         let encryption = Arc::new(EncryptionEngine::new(encryption_config)?);
         
+        // Join the chain's P2P network, rebuilding announced blocks from the pool
+        let network = if config.enable_p2p {
+            let mut network_config = NetworkConfig {
+                bandwidth: config.p2p_bandwidth.clone(),
+                ..chain.network_config(config.p2p_port)
+            };
+            if let Some(proxy) = &config.proxy {
+                // Only dials through the proxy, so nothing announces or reveals this host's address
+                network_config.proxy = Some(proxy.parse()?);
+                network_config.enable_mdns = false;
+                network_config.enable_hole_punching = false;
+                println!("✓ P2P dials go through {}", proxy);
+            }
+            let network = net_p2p::start_network_with_pool(network_config, Some(tx_pool.clone())).await?;
+            println!("✓ P2P network started as peer {}", network.peer_id);
+            Some(network)
+        } else {
+            None
+        };
+        
+        // Initialize Fuego daemon connection if configured
+        let fuego_daemon = match &config.fuego {
+            Some(fuego_config) => {
+                let mut fuego_config = fuego_config.clone();
+                fuego_config.rpc.proxy = fuego_config.rpc.proxy.or_else(|| config.proxy.clone());
+                Some(Arc::new(RwLock::new(FuegoDaemon::new(fuego_config)?)))
+            }
+            None => None,
+        };
+        
+        let supervisor = TaskSupervisor::new(config.tasks.clone());
+        
+        // Settings that can change while running, on SIGHUP or admin_reloadConfig
+        let reloader = Arc::new(ConfigReloader::new(config.clone(), tx_pool.clone(), fuego_daemon.clone()));
+        
+        // Highest block imported from peers, which the readiness probe measures sync lag against
+        let sync_target = Arc::new(AtomicU64::new(0));
+        
         // Initialize RPC server if enabled
         let rpc_server = if config.enable_rpc {
-            let rpc_config = RPCServerConfig::default();
-            Some(Arc::new(RPCServer::new(rpc_config)?))
+            let mut rpc_config = RPCServerConfig::default();
+            // Readiness probes the external endpoints this node actually uses
+            rpc_config.health.fuego_rpc_url = config.fuego.as_ref().map(|fuego| fuego.rpc.url.clone());
+            rpc_config.health.l1_rpc_url = config.enable_bridge.then_some(l1_rpc_url);
+            rpc_config.http_addr = config.rpc_addr.clone();
+            rpc_config.admin = AdminConfig {
+                enabled: config.admin_token.is_some() || config.rpc_jwt_secret.is_some(),
+                token: config.admin_token.clone(),
+            };
+            rpc_config.rate_limit = config.rpc_limits.clone();
+            rpc_config.tls_cert = config.rpc_tls_cert.as_ref().map(PathBuf::from);
+            rpc_config.tls_key = config.rpc_tls_key.as_ref().map(PathBuf::from);
+            rpc_config.jwt_secret = config.rpc_jwt_secret.as_ref().map(PathBuf::from);
+            let mut rpc_server = RPCServer::new(rpc_config)?;
+            rpc_server.attach_finality(consensus.read().await.finality());
+            rpc_server.attach_network_time(consensus.read().await.network_time());
+            rpc_server.attach_consensus(consensus.clone());
+            rpc_server.attach_state_db(state_db.clone());
+            rpc_server.attach_executor(Arc::new(BlockExecutor::new(chain.execution_config())?));
+            rpc_server.attach_prover(prover.clone());
+            rpc_server.attach_sync_target(sync_target.clone());
+            if let Some(pipeline) = &mining {
+                if let Some(workers) = pipeline.worker_stats() {
+                    rpc_server.attach_mining(workers);
+                }
+                if let Some(stale_tracker) = pipeline.stale_tracker() {
+                    rpc_server.attach_stale_blocks(stale_tracker);
+                }
+                if let Some(pplns) = pipeline.pplns_ledger() {
+                    rpc_server.attach_pplns(pplns);
+                }
+            }
+            if let Some(network) = &network {
+                rpc_server.attach_network(network.info.clone());
+                rpc_server.attach_bandwidth(network.bandwidth.clone());
+                rpc_server.attach_peer_control(network.peers.clone());
+            }
+            rpc_server.attach_config_reload(reloader.clone());
+            rpc_server.attach_task_health(Arc::new(supervisor.tasks()));
+            rpc_server.attach_node_admin(Arc::new(admin::NodeAdminHandle::new(
+                PathBuf::from(&config.data_dir),
+                config.validator_signer.is_some(),
+                consensus.clone(),
+                fuego_daemon.clone(),
+            )));
+            Some(Arc::new(rpc_server))
         } else {
             None
         };
         
+        let fuego_supervisor = match &config.fuego_supervisor {
+            Some(supervisor_config) => Some(Arc::new(RwLock::new(FuegoSupervisor::new(supervisor_config.clone())?))),
+            None => None,
+        };
+        
         let status = Arc::new(RwLock::new(NodeStatus {
             is_running: false,
             block_height: 0,
@@ -134,24 +534,37 @@ impl ColdL3Node {
         
         Ok(Self {
             config,
+            chain,
             status,
             message_tx,
-            message_rx,
+            message_rx: Arc::new(Mutex::new(message_rx)),
             state_db,
-            commitment_engine,
-            block_sync,
+            staking,
+            rewards,
             tx_pool,
             consensus,
             bridge,
             encryption,
+            network,
+            mining,
+            production,
             rpc_server,
-            tasks: Vec::new(),
+            fuego_daemon,
+            fuego_supervisor,
+            metrics,
+            prover,
+            sync_target,
+            reloader,
+            shutdown: CancellationToken::new(),
+            supervisor,
         })
     }
     
     /// Start the node and all its subsystems
     pub async fn start(&mut self) -> Result<()> {
-        println!("Starting COLD L3 Node...");
+        println!("Starting COLD L3 Node as a {} node...", self.config.role.name());
+        self.shutdown = CancellationToken::new();
+        self.supervisor.reset(self.shutdown.clone()).await;
         
         // Update status
         {
@@ -166,6 +579,7 @@ impl ColdL3Node {
             let mut consensus = self.consensus.write().await;
             consensus.start_consensus().await?;
         }
+        staking::sync_validators(&self.staking, &self.consensus).await;
         println!("✓ Consensus started");
         
         // Start bridge
@@ -175,10 +589,62 @@ impl ColdL3Node {
         }
         println!("✓ Bridge started");
         
-        // Start RPC server if enabled
-        if let Some(_rpc_server) = &self.rpc_server {
-            // In a real implementation, this would start the actual RPC server
-            println!("✓ RPC server initialized (mock)");
+        // Launch the local Fuego daemon before anything talks to it
+        if let Some(fuego_supervisor) = &self.fuego_supervisor {
+            fuego_supervisor.write().await.start().await?;
+            println!("✓ Fuego daemon launched");
+        }
+        
+        // Start Fuego mining if configured
+        if let Some(fuego_daemon) = &self.fuego_daemon {
+            fuego_daemon.write().await.start().await?;
+            println!("✓ Fuego daemon mining started");
+        }
+        
+        // Serve metrics before the sampler starts filling them
+        if let Some(metrics_addr) = &self.config.metrics_addr {
+            let server = MetricsServer::bind(metrics_addr, self.metrics.clone()).await?;
+            println!("✓ Metrics served at http://{}/metrics", server.local_addr()?);
+            let shutdown = self.shutdown.clone();
+            let task = async move {
+                tokio::select! {
+                    _ = server.run() => {}
+                    _ = shutdown.cancelled() => {}
+                }
+                Ok(())
+            };
+            self.supervisor.spawn_once("metrics_server", task).await;
+        }
+        
+        // Serve health probes for orchestration systems
+        if let (Some(health_addr), Some(rpc_server)) = (&self.config.health_addr, &self.rpc_server) {
+            let server = HealthServer::bind(health_addr, rpc_server.clone()).await?;
+            println!("✓ Health probes served at http://{}/health", server.local_addr()?);
+            let shutdown = self.shutdown.clone();
+            let task = async move {
+                tokio::select! {
+                    _ = server.run() => {}
+                    _ = shutdown.cancelled() => {}
+                }
+                Ok(())
+            };
+            self.supervisor.spawn_once("health_server", task).await;
+        }
+        
+        // Serve JSON-RPC, over TLS when a certificate is configured
+        if let Some(rpc_server) = &self.rpc_server {
+            let server = RpcHttpServer::bind(&self.config.rpc_addr, rpc_server.clone()).await?;
+            let scheme = if server.is_tls() { "https" } else { "http" };
+            println!("✓ RPC server listening at {}://{}", scheme, server.local_addr()?);
+            let shutdown = self.shutdown.clone();
+            let task = async move {
+                tokio::select! {
+                    _ = server.run() => {}
+                    _ = shutdown.cancelled() => {}
+                }
+                Ok(())
+            };
+            self.supervisor.spawn_once("rpc_server", task).await;
         }
         
         // Spawn subsystem tasks
@@ -207,8 +673,21 @@ impl ColdL3Node {
             status.bridge_state = "stopping".to_string();
         }
         
-        // Send shutdown message
+        // Send shutdown message and ask every task to finish
         let _ = self.message_tx.send(NodeMessage::Shutdown).await;
+        self.shutdown.cancel();
+        
+        // Stop Fuego mining
+        if let Some(fuego_daemon) = &self.fuego_daemon {
+            fuego_daemon.write().await.stop().await?;
+            println!("✓ Fuego daemon mining stopped");
+        }
+        
+        // Shut down the local Fuego daemon
+        if let Some(fuego_supervisor) = &self.fuego_supervisor {
+            fuego_supervisor.write().await.stop().await?;
+            println!("✓ Fuego daemon stopped");
+        }
         
         // Stop bridge
         {
@@ -224,12 +703,16 @@ impl ColdL3Node {
         }
         println!("✓ Consensus stopped");
         
-        // Wait for all tasks to complete
-        for task in self.tasks.drain(..) {
-            if let Err(e) = task.await {
-                eprintln!("Task error: {:?}", e);
-            }
-        }
+        // Wait for all tasks to complete, aborting any still running at the deadline
+        let deadline = Instant::now() + Duration::from_secs(self.config.shutdown_timeout_secs);
+        self.supervisor.stop(deadline).await;
+        
+        // Persist what would otherwise be lost on exit
+        let tx_pool_file = Path::new(&self.config.data_dir).join(TX_POOL_FILE);
+        let saved = self.tx_pool.read().await.save(&tx_pool_file)?;
+        println!("✓ Saved {} pooled transactions", saved);
+        self.state_db.read().await.flush()?;
+        println!("✓ State database flushed");
         
         // Update status
         {
@@ -247,90 +730,422 @@ impl ColdL3Node {
         self.status.read().await.clone()
     }
     
-    /// Spawn all subsystem tasks
+    /// Metrics registry shared with the node's subsystems
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+    
+    /// Parameters of the chain this node follows
+    pub fn chain_spec(&self) -> &ChainSpec {
+        &self.chain
+    }
+    
+    /// Block reward accounting shared with block production
+    pub fn rewards(&self) -> Arc<RwLock<RewardsEngine>> {
+        self.rewards.clone()
+    }
+    
+    /// Applies config reloads to the running subsystems
+    pub fn config_reloader(&self) -> Arc<ConfigReloader> {
+        self.reloader.clone()
+    }
+    
+    /// Reload the config and log which settings changed
+    pub async fn reload_config(&self) {
+        match self.reloader.reload().await {
+            Ok(report) => println!("{}", report),
+            Err(e) => println!("Config reload failed, keeping the running config: {}", e),
+        }
+    }
+    
+    /// Spawn all subsystem tasks under the supervisor
     async fn spawn_subsystem_tasks(&mut self) -> Result<()> {
-        let _message_tx = self.message_tx.clone();
         let status = self.status.clone();
+        let shutdown = self.shutdown.clone();
         
-        // Block sync task
-        let _block_sync = self.block_sync.clone();
-        let task = tokio::spawn(async move {
-            println!("Block sync task started");
-            // TODO: Implement actual block syncing logic
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-                // Simulate block sync activity
-            }
-        });
-        self.tasks.push(task);
-        
-        // Transaction pool task
-        let _tx_pool = self.tx_pool.clone();
-        let task = tokio::spawn(async move {
-            println!("Transaction pool task started");
-            // TODO: Implement transaction processing logic
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                // Simulate transaction processing
-            }
-        });
-        self.tasks.push(task);
-        
-        // State database task
-        let _state_db = self.state_db.clone();
-        let task = tokio::spawn(async move {
-            println!("State database task started");
-            // TODO: Implement state management logic
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-                // Simulate state management
-            }
-        });
-        self.tasks.push(task);
-        
-        // Commitment engine task
-        let _commitment_engine = self.commitment_engine.clone();
-        let task = tokio::spawn(async move {
-            println!("Commitment engine task started");
-            // TODO: Implement commitment calculations
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-                // Simulate commitment calculations
-            }
-        });
-        self.tasks.push(task);
+        // Block execution task: apply blocks to the state database once consensus finalizes them
+        let execution_config = self.chain.execution_config();
+        let consensus = self.consensus.clone();
+        let state_db = self.state_db.clone();
+        let task_shutdown = shutdown.clone();
+        self.supervisor
+            .spawn("block_execution", RestartPolicy::OnFailure, move || {
+                let executor = BlockExecutor::new(execution_config.clone());
+                let task = executor.map(|executor| {
+                    blocks::run_execution(
+                        executor,
+                        consensus.clone(),
+                        state_db.clone(),
+                        Duration::from_secs(1),
+                        task_shutdown.clone(),
+                    )
+                });
+                async move {
+                    println!("Block execution task started");
+                    task?.await
+                }
+            })
+            .await;
         
-        // Message processing task
-        let task = tokio::spawn(async move {
-            println!("Message processing task started");
-            // TODO: Implement message processing logic
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                // Process messages from other tasks
-            }
-        });
-        self.tasks.push(task);
+        // Blocks mined and attestations signed here go out to peers through the network task
+        let (announce_tx, mut announcements) = mpsc::channel::<BlockProposal>(16);
+        let (attest_tx, mut attestations) = mpsc::channel::<Attestation>(16);
+        
+        // Network task: pass blocks completed by the relay and attestations from peers on to the
+        // node, publish the blocks and attestations made here and keep the peer count current,
+        // closing the swarm on shutdown. The swarm cannot be rebuilt here, so the task is not
+        // restarted.
+        if let Some(mut network) = self.network.take() {
+            let message_tx = self.message_tx.clone();
+            let status = self.status.clone();
+            let task_shutdown = shutdown.clone();
+            let task = async move {
+                println!("Network task started");
+                let mut ticker = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    tokio::select! {
+                        Some((block, seal)) = network.blocks.recv() => {
+                            let _ = message_tx.try_send(NodeMessage::BlockReceived(block.to_canonical_bytes(), seal));
+                        }
+                        Some(attestation) = network.attestations.recv() => {
+                            let _ = message_tx.try_send(NodeMessage::AttestationReceived(attestation));
+                        }
+                        Some(attestation) = attestations.recv() => {
+                            if let Err(e) = network.broadcast_attestation(attestation.to_canonical_bytes()) {
+                                eprintln!("Failed to publish attestation: {}", e);
+                            }
+                        }
+                        Some(proposal) = announcements.recv() => {
+                            let seal = proposal.seal().to_canonical_bytes();
+                            if let Err(e) = network.announce_block(proposal.block, seal) {
+                                eprintln!("Failed to announce block: {}", e);
+                            }
+                        }
+                        _ = ticker.tick() => {
+                            status.write().await.connected_peers = network.info.read().await.connected_peers;
+                        }
+                        _ = task_shutdown.cancelled() => break,
+                    }
+                }
+                network.shutdown().await;
+                Ok(())
+            };
+            self.supervisor.spawn_once("network", task).await;
+        }
+        
+        // Mining task: merge mine blocks on the consensus head and import those sealed for it.
+        // The miner, Stratum listener and coordinator are built once, so it is not restarted.
+        if let Some(pipeline) = self.mining.take() {
+            let consensus = self.consensus.clone();
+            let task_shutdown = shutdown.clone();
+            let task = async move {
+                println!("Mining task started");
+                pipeline.run(consensus, announce_tx, task_shutdown).await
+            };
+            self.supervisor.spawn_once("mining", task).await;
+        }
+        
+        // Transaction pool task: drop transactions that stayed pooled too long
+        if self.config.role.keeps_pool() {
+            let tx_pool = self.tx_pool.clone();
+            let task_shutdown = shutdown.clone();
+            self.supervisor
+                .spawn("tx_pool", RestartPolicy::OnFailure, move || {
+                    let tx_pool = tx_pool.clone();
+                    let task_shutdown = task_shutdown.clone();
+                    async move {
+                        println!("Transaction pool task started");
+                        while sleep_unless_shutdown(&task_shutdown, Duration::from_secs(5)).await {
+                            let expired = tx_pool.write().await.expire_stale().await;
+                            if expired > 0 {
+                                println!("Expired {} stale pooled transactions", expired);
+                            }
+                        }
+                        Ok(())
+                    }
+                })
+                .await;
+        }
+        
+        // State database task: compact history left behind by pruning
+        let state_db = self.state_db.clone();
+        let task_shutdown = shutdown.clone();
+        self.supervisor
+            .spawn("state_db_compaction", RestartPolicy::OnFailure, move || {
+                let state_db = state_db.clone();
+                let task_shutdown = task_shutdown.clone();
+                async move {
+                    println!("State database task started");
+                    RocksStateDB::spawn_compaction(state_db, task_shutdown).await.await?;
+                    Ok(())
+                }
+            })
+            .await;
+
+        // Backup task: checkpoint the state database on the configured interval
+        if self.config.state_db.backup.interval.is_some() {
+            let state_db = self.state_db.clone();
+            let task_shutdown = shutdown.clone();
+            self.supervisor
+                .spawn("state_db_backups", RestartPolicy::OnFailure, move || {
+                    let state_db = state_db.clone();
+                    let task_shutdown = task_shutdown.clone();
+                    async move {
+                        if let Some(backups) = RocksStateDB::spawn_backups(state_db, task_shutdown).await {
+                            backups.await?;
+                        }
+                        Ok(())
+                    }
+                })
+                .await;
+        }
+        
+        // Slashing task: punish double signing reported by consensus
+        let staking = self.staking.clone();
+        let consensus = self.consensus.clone();
+        let task_shutdown = shutdown.clone();
+        self.supervisor
+            .spawn("slashing", RestartPolicy::OnFailure, move || {
+                let staking = staking.clone();
+                let consensus = consensus.clone();
+                let task_shutdown = task_shutdown.clone();
+                async move {
+                    println!("Slashing task started");
+                    staking::run_slashing(staking, consensus, task_shutdown).await;
+                    Ok(())
+                }
+            })
+            .await;
+        
+        // Production lease task: sequencers take turns holding the lease their mining follows
+        if self.config.role.produces_blocks() {
+            // Standby sequencers may share the validator key, so the lease goes to the node id
+            let data_dir = Path::new(&self.config.data_dir);
+            let holder = roles::node_id(data_dir)?;
+            let sequencer = self.config.sequencer.clone();
+            let leases: Arc<dyn LeaseStore> = Arc::new(FileLeaseStore::new(sequencer.lease_path(data_dir)));
+            let production = self.production.clone();
+            let interval = self.chain.consensus_config().block_time;
+            let task_shutdown = shutdown.clone();
+            self.supervisor
+                .spawn("production_lease", RestartPolicy::OnFailure, move || {
+                    let task = roles::run_production_lease(
+                        sequencer.clone(),
+                        holder.clone(),
+                        leases.clone(),
+                        production.clone(),
+                        interval,
+                        task_shutdown.clone(),
+                    );
+                    async move {
+                        println!("Production lease task started");
+                        task.await
+                    }
+                })
+                .await;
+        }
+        
+        // Attestation task: validators sign the blocks sequencers build
+        if self.config.role == NodeRole::Validator {
+            let consensus = self.consensus.clone();
+            let interval = self.chain.consensus_config().block_time;
+            let task_shutdown = shutdown.clone();
+            self.supervisor
+                .spawn("attestation", RestartPolicy::OnFailure, move || {
+                    let task = roles::run_attestation(
+                        consensus.clone(),
+                        attest_tx.clone(),
+                        interval,
+                        task_shutdown.clone(),
+                    );
+                    async move {
+                        println!("Attestation task started");
+                        task.await
+                    }
+                })
+                .await;
+        }
+        
+        // Bridge tasks: L1 deposit monitoring, batch commitment and proof tracking
+        let tasks = self.bridge.read().await.tasks();
+        for bridge_task in tasks {
+            let bridge = self.bridge.clone();
+            let task_shutdown = shutdown.clone();
+            self.supervisor
+                .spawn(bridge_task.name(), RestartPolicy::OnFailure, move || {
+                    let bridge = bridge.clone();
+                    let task_shutdown = task_shutdown.clone();
+                    async move {
+                        let Some(run) = bridge.read().await.task(bridge_task) else {
+                            return Ok(());
+                        };
+                        tokio::select! {
+                            _ = run => {}
+                            _ = task_shutdown.cancelled() => {}
+                        }
+                        Ok(())
+                    }
+                })
+                .await;
+        }
+        
+        // Message task: import blocks and pool transactions received from peers
+        let messages = self.message_rx.clone();
+        let sync_target = self.sync_target.clone();
+        let consensus = self.consensus.clone();
+        let tx_pool = self.tx_pool.clone();
+        let task_shutdown = shutdown.clone();
+        self.supervisor
+            .spawn("messages", RestartPolicy::OnFailure, move || {
+                let task = blocks::run_messages(
+                    messages.clone(),
+                    consensus.clone(),
+                    tx_pool.clone(),
+                    sync_target.clone(),
+                    task_shutdown.clone(),
+                );
+                async move {
+                    println!("Message processing task started");
+                    task.await
+                }
+            })
+            .await;
+        
+        // Metrics task: copy subsystem and task stats into the gauges
+        let metrics = self.metrics.clone();
+        let tx_pool = self.tx_pool.clone();
+        let state_db = self.state_db.clone();
+        let prover = self.prover.clone();
+        let bridge = self.bridge.clone();
+        let fuego_daemon = self.fuego_daemon.clone();
+        let metrics_status = self.status.clone();
+        let supervised = self.supervisor.tasks();
+        let interval = Duration::from_secs(self.config.metrics_interval_secs.max(1));
+        let task_shutdown = shutdown.clone();
+        self.supervisor
+            .spawn("metrics", RestartPolicy::OnFailure, move || {
+                let metrics = metrics.clone();
+                let tx_pool = tx_pool.clone();
+                let state_db = state_db.clone();
+                let prover = prover.clone();
+                let bridge = bridge.clone();
+                let fuego_daemon = fuego_daemon.clone();
+                let metrics_status = metrics_status.clone();
+                let supervised = supervised.clone();
+                let task_shutdown = task_shutdown.clone();
+                async move {
+                    loop {
+                        let pool = tx_pool.read().await.get_stats();
+                        metrics.set_txpool(pool.total_transactions, pool.evictions);
+                        metrics.set_connected_peers(metrics_status.read().await.connected_peers);
+                        {
+                            let state = state_db.read().await;
+                            metrics.set_block_height(state.latest_version().unwrap_or(0));
+                            match state.storage_stats() {
+                                Ok(stats) => {
+                                    metrics.set_state_db(stats.estimated_keys, stats.sst_bytes, stats.memtable_bytes)
+                                }
+                                Err(e) => eprintln!("Failed to read state database stats: {}", e),
+                            }
+                            if let Some(cache) = state.cache_stats() {
+                                metrics.set_state_db_cache(cache.hits, cache.misses);
+                            }
+                        }
+                        metrics.set_prover_queue(prover.get_stats().queued);
+                        let bridge_stats = bridge.read().await.get_bridge_stats().await;
+                        metrics.set_bridge_reorgs(bridge_stats.l1_reorgs, bridge_stats.mints_rolled_back);
+                        let checked = bridge_stats.watchtower_batches_checked;
+                        metrics.set_watchtower(checked, bridge_stats.watchtower_mismatches);
+                        match bridge.read().await.queue_depths().await {
+                            Ok(depths) => {
+                                metrics.set_bridge_queue("proofs", depths.pending_proofs);
+                                metrics.set_bridge_queue("mints", depths.pending_mints);
+                                metrics.set_bridge_queue("withdrawals", depths.pending_withdrawals);
+                                metrics.set_bridge_queue("batch_blocks", depths.pending_batch_blocks);
+                            }
+                            Err(e) => eprintln!("Failed to read bridge queues: {}", e),
+                        }
+                        if let Some(fuego_daemon) = &fuego_daemon {
+                            metrics.set_mining_hashrate(fuego_daemon.read().await.get_stats().await.hash_rate);
+                        }
+                        for task in supervised.snapshot().await {
+                            metrics.set_task(&task.name, task.state == TaskState::Running, task.restarts as u64);
+                        }
+                        if !sleep_unless_shutdown(&task_shutdown, interval).await {
+                            return Ok(());
+                        }
+                    }
+                }
+            })
+            .await;
+        
+        // Notifications task: post node events to the operator's webhooks
+        if !self.config.notifications.webhooks.is_empty() {
+            let notifier = Arc::new(Notifier::new(self.config.notifications.clone())?);
+            let sources = notifier::EventSources {
+                consensus: self.consensus.clone(),
+                bridge: self.bridge.clone(),
+                fuego_daemon: self.fuego_daemon.clone(),
+                state_db: self.state_db.clone(),
+            };
+            let produces_blocks = self.config.role.produces_blocks();
+            let task_shutdown = shutdown.clone();
+            self.supervisor
+                .spawn("notifications", RestartPolicy::OnFailure, move || {
+                    let task = notifier::run_notifications(
+                        notifier.clone(),
+                        sources.clone(),
+                        produces_blocks,
+                        task_shutdown.clone(),
+                    );
+                    async move {
+                        println!("Notifications task started");
+                        task.await
+                    }
+                })
+                .await;
+        }
         
         // Status update task
-        let task = tokio::spawn(async move {
-            let start_time = std::time::Instant::now();
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                let mut status = status.write().await;
-                status.uptime_seconds = start_time.elapsed().as_secs();
-            }
-        });
-        self.tasks.push(task);
+        let start_time = std::time::Instant::now();
+        let task_shutdown = shutdown.clone();
+        self.supervisor
+            .spawn("status", RestartPolicy::OnFailure, move || {
+                let status = status.clone();
+                let task_shutdown = task_shutdown.clone();
+                async move {
+                    while sleep_unless_shutdown(&task_shutdown, Duration::from_secs(1)).await {
+                        let mut status = status.write().await;
+                        status.uptime_seconds = start_time.elapsed().as_secs();
+                    }
+                    Ok(())
+                }
+            })
+            .await;
         
         Ok(())
     }
     
     /// Run the main event loop
     pub async fn run(&mut self) -> Result<()> {
-        println!("Node is running. Press Ctrl+C to stop.");
+        println!("Node is running. Press Ctrl+C or send SIGTERM to stop, or send SIGHUP to reload the config.");
         
-        // Wait for shutdown signal
-        tokio::signal::ctrl_c().await?;
+        // Wait for shutdown signal, reloading the config on every SIGHUP meanwhile
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut hangup = signal(SignalKind::hangup())?;
+            loop {
+                tokio::select! {
+                    result = &mut shutdown => break result?,
+                    _ = hangup.recv() => self.reload_config().await,
+                }
+            }
+        }
+        #[cfg(not(unix))]
+        shutdown.await?;
         
         // Stop the node
         self.stop().await?;
@@ -345,16 +1160,28 @@ mod tests {
     
     #[tokio::test]
     async fn test_node_creation() {
-        let config = NodeConfig::default();
+        let config = NodeConfig {
+            enable_p2p: false,
+            ..Default::default()
+        };
         let node = ColdL3Node::new(config).await;
         assert!(node.is_ok());
     }
     
     #[tokio::test]
     async fn test_node_start_stop() {
-        let config = NodeConfig::default();
+        let config = NodeConfig {
+            rpc_addr: "127.0.0.1:0".to_string(),
+            p2p_port: 0,
+            ..Default::default()
+        };
         let mut node = ColdL3Node::new(config).await.unwrap();
         
+        // The RPC server reports the P2P network the node joined
+        let rpc_server = node.rpc_server.clone().unwrap();
+        let info = rpc_server.get_network_info().await.unwrap();
+        assert!(!info["peer_id"].as_str().unwrap().is_empty());
+        
         // Start the node
         assert!(node.start().await.is_ok());
         
@@ -369,4 +1196,136 @@ mod tests {
         let status = node.get_status().await;
         assert!(!status.is_running);
     }
+    
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sequencer_merge_mines_on_the_restored_chain() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = NodeConfig {
+            role: NodeRole::Sequencer,
+            data_dir: temp_dir.path().to_str().unwrap().to_string(),
+            network: Network::Devnet,
+            enable_rpc: false,
+            enable_p2p: false,
+            enable_bridge: false,
+            mining: Some(MiningConfig {
+                merge_mining: mining::MergeMiningConfig {
+                    wallet_address: "fire".to_string(),
+                    ..Default::default()
+                },
+                stratum: Some(mining::StratumConfig {
+                    listen_addr: "127.0.0.1:0".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        
+        // Sealed blocks are signed as proposals, so mining needs the validator key
+        assert!(ColdL3Node::new(config.clone()).await.is_err());
+        std::fs::write(temp_dir.path().join(admin::VALIDATOR_KEY_FILE), hex::encode([7u8; 32])).unwrap();
+        let mut node = ColdL3Node::new(config).await.unwrap();
+        assert!(node.mining.is_some());
+        
+        // Consensus picks up the executed chain, here genesis alone, for templates to build on
+        let (head, difficulty) = node.consensus.read().await.head_with_difficulty().await.unwrap().unwrap();
+        assert_eq!((head.height, head.hash), (0, consensus::engine::header_id(&node.chain.genesis_header())));
+        assert!(difficulty > 0);
+        
+        // A standby builds no templates while another sequencer holds the lease
+        let leases = FileLeaseStore::new(temp_dir.path().join(roles::LEASE_FILE));
+        let other = roles::acquire_lease(&leases, "other", roles::unix_now(), 3_600).unwrap();
+        let job_manager = node.mining.as_ref().unwrap().job_manager();
+        node.start().await.unwrap();
+        let tasks = node.supervisor.tasks().snapshot().await;
+        assert!(tasks.iter().any(|task| task.name == "mining" && task.state == TaskState::Running));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while job_manager.is_producing() {
+            assert!(Instant::now() < deadline, "a standby kept producing");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!job_manager.is_producing() && job_manager.current_job().await.is_none());
+
+        // Mining resumes once the lease lapses to this node
+        roles::release_lease(&leases, "other", roles::unix_now()).unwrap();
+        assert!(other.is_some());
+        while !job_manager.is_producing() {
+            assert!(Instant::now() < deadline, "mining did not resume with the lease");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        node.stop().await.unwrap();
+        assert!(!*node.production.borrow());
+        assert!(leases.load().unwrap().unwrap().expires_at <= roles::unix_now());
+    }
+    
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mining_stats_are_served_and_a_full_proof_queue_pauses_mining() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join(admin::VALIDATOR_KEY_FILE), hex::encode([7u8; 32])).unwrap();
+        let config = NodeConfig {
+            role: NodeRole::Sequencer,
+            data_dir: temp_dir.path().to_str().unwrap().to_string(),
+            network: Network::Devnet,
+            rpc_addr: "127.0.0.1:0".to_string(),
+            enable_p2p: false,
+            enable_bridge: false,
+            mining: Some(MiningConfig {
+                merge_mining: mining::MergeMiningConfig {
+                    wallet_address: "fire".to_string(),
+                    ..Default::default()
+                },
+                miner: Some(mining::CODL3MiningConfig {
+                    threads: 1,
+                    ..Default::default()
+                }),
+                stratum: Some(mining::StratumConfig {
+                    listen_addr: "127.0.0.1:0".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            prover: ProverServiceConfig {
+                workers: 1,
+                queue_capacity: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut node = ColdL3Node::new(config).await.unwrap();
+        let job_manager = node.mining.as_ref().unwrap().job_manager();
+        node.start().await.unwrap();
+        
+        // The miner's stale blocks, the Stratum workers and proof jobs are served over RPC;
+        // PPLNS accounts are not kept without a pool config
+        let rpc_server = node.rpc_server.clone().unwrap();
+        let stale = rpc_server.get_mining_stale_blocks(10).await.unwrap();
+        assert_eq!(stale["stale_blocks"], 0);
+        assert_eq!(rpc_server.get_mining_workers().await.unwrap()["total_hashrate"], 0);
+        assert!(rpc_server.get_mining_payouts(10).await.is_err());
+        assert_eq!(rpc_server.proof_status(1).await.unwrap(), serde_json::Value::Null);
+        
+        // One job holds the only worker and one fills the queue, so mining pauses
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let job = |release: Option<std::sync::mpsc::Receiver<()>>| {
+            move || {
+                if let Some(release) = release {
+                    let _ = release.recv();
+                }
+                Err(zk_proofs::ZkProofError::InvalidWitness("test job".to_string()))
+            }
+        };
+        node.prover.submit(zk_proofs::ProofPriority::Block, job(Some(release_rx))).unwrap();
+        while node.prover.get_stats().running == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        node.prover.submit(zk_proofs::ProofPriority::Transaction, job(None)).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while job_manager.get_stats().await.backpressure_pauses == 0 {
+            assert!(Instant::now() < deadline, "mining did not pause on a full proof queue");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        release_tx.send(()).unwrap();
+        node.stop().await.unwrap();
+    }
 }
\ No newline at end of file
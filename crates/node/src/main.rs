@@ -1,20 +1,236 @@
 // node/src/main.rs
 
-use node::{ColdL3Node, NodeConfig};
+use consensus::finality::{FinalityConfig, FinalityGadget};
+use consensus::signer::SignerConfig;
+use node::{init_logging, ColdL3Node, ConfigError, NodeConfig};
+use state_db::{BackupManifest, RocksStateDB, StorageMode};
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const SNAPSHOT_USAGE: &str = "usage: node snapshot export <dir> [version] | node snapshot import <dir> [root]";
+const CONFIG_USAGE: &str = "usage: node config print-effective";
+/// Flags that may take their value as the next argument, e.g. `--role watchtower`
+const VALUE_FLAGS: [&str; 3] = ["--config", "--role", "--proxy"];
+const REPLAY_USAGE: &str = "usage: node verify-chain | node reindex";
+const BACKUP_USAGE: &str =
+    "usage: node backup create | node backup list | node backup verify <dir> | node backup restore <dir>";
+const INIT_USAGE: &str = "usage: node init [--network=<name> | --chain-spec=<file>] [--genesis=<allocation file>]";
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load configuration
-    let config = NodeConfig::default();
+async fn main() -> Result<(), Box<dyn Error>> {
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    let config_index = raw_args.iter().position(|arg| arg == "--config");
+    let config_path = flag("--config=").or_else(|| config_index.and_then(|i| raw_args.get(i + 1)).cloned());
+    let config = load_config(config_path.as_deref())?;
+
+    let args: Vec<String> = raw_args
+        .iter()
+        .enumerate()
+        .filter(|(i, arg)| !arg.starts_with("--") && (*i == 0 || !VALUE_FLAGS.contains(&raw_args[i - 1].as_str())))
+        .map(|(_, arg)| arg.clone())
+        .collect();
+    match args.first().map(String::as_str) {
+        Some("snapshot") => return run_snapshot_command(&config, &args[1..]).await,
+        Some("config") => return run_config_command(&config, &args[1..]),
+        Some("init") if args.len() == 1 => return run_init_command(&config),
+        Some("init") => return Err(INIT_USAGE.into()),
+        Some("verify-chain") if args.len() == 1 => return run_replay_command(&config, false),
+        Some("reindex") if args.len() == 1 => return run_replay_command(&config, true),
+        Some("verify-chain" | "reindex") => return Err(REPLAY_USAGE.into()),
+        Some("backup") => return run_backup_command(&config, &args[1..]),
+        _ => {}
+    }
+    let (log_level, telemetry) = init_logging(&config.log_level, &config.logging)?;
     
     // Create and start the node
     let mut node = ColdL3Node::new(config).await?;
     
+    // SIGHUP and admin_reloadConfig re-read the same file, environment and flags
+    let reloader = node.config_reloader();
+    reloader.attach_log_level(log_level);
+    reloader.set_loader(Box::new(move || load_config(config_path.as_deref())));
+    
     // Start the node
     node.start().await?;
     
     // Run the main event loop
     node.run().await?;
     
+    // Send the spans still waiting to be exported
+    if let Some(telemetry) = telemetry {
+        telemetry.shutdown();
+    }
+    Ok(())
+}
+
+fn flag(name: &str) -> Option<String> {
+    std::env::args().find_map(|arg| arg.strip_prefix(name).map(str::to_string))
+}
+
+/// Value of a flag given as `--name value`
+fn flag_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+/// Layer the configuration: defaults, then the --config file, then CODL3_* variables, then flags
+fn load_config(config_path: Option<&str>) -> Result<NodeConfig, ConfigError> {
+    let mut config = NodeConfig::load(config_path.map(Path::new))?;
+
+    // Check the batches other nodes post to L1 instead of producing blocks
+    if let Some(role) = flag("--role=").or_else(|| flag_value("--role")) {
+        config.role = role.parse().map_err(|e| ConfigError::FlagError(format!("--role: {}", e)))?;
+    }
+
+    // Keep every state version instead of pruning old history
+    if std::env::args().any(|arg| arg == "--archive") {
+        config.state_db.mode = StorageMode::Archive;
+    }
+
+    // Follow mainnet, testnet, devnet or a custom chain spec file
+    if let Some(network) = flag("--network=") {
+        config.network = network.parse().map_err(|e| ConfigError::FlagError(format!("--network: {}", e)))?;
+    }
+    if let Some(chain_spec) = flag("--chain-spec=") {
+        config.chain_spec = Some(chain_spec);
+    }
+
+    // Replace the chain spec's genesis allocation with the balances in a file
+    if let Some(genesis_file) = flag("--genesis=") {
+        config.genesis_file = Some(genesis_file);
+    }
+
+    // Keep the validator key in a remote signer or on a Ledger instead of on this host
+    if let Some(url) = flag("--remote-signer=") {
+        let public_key = flag("--remote-signer-key=")
+            .ok_or_else(|| ConfigError::FlagError("--remote-signer needs --remote-signer-key=<hex>".to_string()))?;
+        config.validator_signer = Some(SignerConfig::Remote { url, public_key, timeout_secs: 10 });
+    } else if let Some(address) = flag("--ledger=") {
+        let account = flag("--ledger-account=")
+            .map(|account| account.parse())
+            .transpose()
+            .map_err(|e| ConfigError::FlagError(format!("--ledger-account: {}", e)))?
+            .unwrap_or(0);
+        config.validator_signer = Some(SignerConfig::Ledger { address, account });
+    }
+
+    // Expose Prometheus metrics, e.g. --metrics=127.0.0.1:9615
+    if let Some(addr) = flag("--metrics=") {
+        config.metrics_addr = Some(addr);
+    }
+    // Serve liveness and readiness probes, e.g. --health=127.0.0.1:8547
+    if let Some(addr) = flag("--health=") {
+        config.health_addr = Some(addr);
+    }
+    // Dial peers and reach Fuego and L1 through Tor or another proxy, e.g. --proxy socks5://127.0.0.1:9050
+    if let Some(proxy) = flag("--proxy=").or_else(|| flag_value("--proxy")) {
+        config.proxy = Some(proxy);
+    }
+    Ok(config)
+}
+
+/// Print the configuration after the file, environment and flags are applied, then check it
+fn run_config_command(config: &NodeConfig, args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args.first().map(String::as_str) {
+        Some("print-effective") => {
+            print!("{}", config.to_toml()?);
+            config.validate()?;
+        }
+        _ => return Err(CONFIG_USAGE.into()),
+    }
+    Ok(())
+}
+
+/// Write the genesis block and initial state of the configured chain into the data directory
+fn run_init_command(config: &NodeConfig) -> Result<(), Box<dyn Error>> {
+    let chain = config.chain_spec()?;
+    let data_dir = Path::new(&config.data_dir);
+    let mut state = RocksStateDB::with_config(data_dir.join("state"), config.state_db.clone())?;
+    let record = chain.init_data_dir(data_dir, &mut state)?;
+    println!("Initialized {} for {} (chain id {})", data_dir.display(), record.chain, record.chain_id);
+    println!("Genesis hash: {}", record.genesis_hash);
+    Ok(())
+}
+
+/// Replay every stored block from genesis and report the first divergence; `reindex` then
+/// swaps the rebuilt state database in if there was none
+fn run_replay_command(config: &NodeConfig, rebuild: bool) -> Result<(), Box<dyn Error>> {
+    let chain = config.chain_spec()?;
+    let data_dir = Path::new(&config.data_dir);
+    let report = if rebuild {
+        node::reindex::reindex(&chain, data_dir, &config.state_db)?
+    } else {
+        node::reindex::verify_chain(&chain, data_dir, &config.state_db)?
+    };
+    println!("Checked {} of {} blocks", report.blocks_checked, report.latest.unwrap_or(0));
+    if let Some(divergence) = report.divergence {
+        return Err(format!("Chain diverges: {}", divergence).into());
+    }
+    if rebuild {
+        println!("Reindexed {}", data_dir.display());
+    }
+    Ok(())
+}
+
+/// Take, list, check or restore checkpoints of the state database
+fn run_backup_command(config: &NodeConfig, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let state_db_path = Path::new(&config.data_dir).join("state");
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("create"), None) => {
+            let state_db = RocksStateDB::with_config(&state_db_path, config.state_db.clone())?;
+            let (path, manifest) = state_db.create_backup()?;
+            println!("Backed up {} files to {}", manifest.files.len(), path.display());
+        }
+        (Some("list"), None) => {
+            // A relative backup directory sits beside the database, as `RocksStateDB::backup_dir` resolves it
+            let backup_dir = Path::new(&config.data_dir).join(&config.state_db.backup.dir);
+            for (path, manifest) in state_db::backup::list_backups(&backup_dir)? {
+                let root = manifest.root.map_or("none".to_string(), hex::encode);
+                let version = manifest.version.map_or("none".to_string(), |version| version.to_string());
+                println!("{}  version {}  root {}", path.display(), version, root);
+            }
+        }
+        (Some("verify"), Some(dir)) => {
+            BackupManifest::load(dir)?.verify(dir)?;
+            println!("Backup {} is intact", dir);
+        }
+        (Some("restore"), Some(dir)) => {
+            state_db::backup::restore_backup(dir, &state_db_path, config.state_db.clone())?;
+        }
+        _ => return Err(BACKUP_USAGE.into()),
+    }
+    Ok(())
+}
+
+/// Export the state at a finalized height, or import a snapshot into a fresh data directory
+async fn run_snapshot_command(config: &NodeConfig, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let state_db_path = Path::new(&config.data_dir).join("state");
+    let state_db = Arc::new(RwLock::new(RocksStateDB::with_config(&state_db_path, config.state_db.clone())?));
+
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("export"), Some(dir)) => {
+            let version = match args.get(2) {
+                Some(version) => version.parse()?,
+                None => {
+                    FinalityGadget::with_state_db(FinalityConfig::default(), state_db.clone())
+                        .await?
+                        .finalized_head()
+                        .ok_or("no finalized block to snapshot")?
+                        .height
+                }
+            };
+            let manifest = state_db.read().await.export_snapshot(version, dir)?;
+            println!("Snapshot root: {}", hex::encode(manifest.root));
+        }
+        (Some("import"), Some(dir)) => {
+            let root = match args.get(2) {
+                Some(root) => Some(<[u8; 32]>::try_from(hex::decode(root)?).map_err(|_| "root must be 32 bytes")?),
+                None => None,
+            };
+            state_db.write().await.import_snapshot(dir, root)?;
+        }
+        _ => return Err(SNAPSHOT_USAGE.into()),
+    }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,277 @@
+//! Merge-mined block production of a sequencer.
+//!
+//! One job manager builds templates on the consensus head, at the difficulty consensus expects
+//! of the next block. The in-process miner and the workers of the Stratum server mine them, and
+//! the coordinator binds them to fuegod's templates and routes what they solve. Sealed C0DL3
+//! blocks are signed with the validator key and imported through consensus like any proposal.
+//! A sequencer following the production lease builds templates and imports what is sealed
+//! only while it holds the lease.
+
+use anyhow::Result;
+use block_sync::Block;
+use consensus::engine::ImportOutcome;
+use consensus::error::ConsensusError;
+use consensus::{BlockProposal, BlockValidator, Consensus, ConsensusConfig, StateTransition};
+use mining::{
+    BackendRegistry, CODL3Miner, CODL3MiningConfig, ChainTip, FoundBlock, JobManager, JobManagerConfig,
+    MergeMiningConfig, MergeMiningCoordinator, PplnsLedger, StaleTracker, StratumConfig, StratumServer, WorkerStats,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+use txpool::TxPool;
+
+/// How often the consensus head is checked for a new tip to mine on
+const TIP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Merge mining configuration of a sequencer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MiningConfig {
+    /// fuegod the parent templates come from and solved Fuego blocks go to
+    pub merge_mining: MergeMiningConfig,
+    /// Template refresh and work ranges; transactions are picked by the node's `tx_selection`
+    pub job_manager: JobManagerConfig,
+    /// Mine on local threads when set; its `job_manager` is replaced by the one above
+    pub miner: Option<CODL3MiningConfig>,
+    /// Serve the work to external miners when set, keeping PPLNS accounts if it sets `pplns`
+    pub stratum: Option<StratumConfig>,
+}
+
+/// The mining subsystems of a sequencer, built with the node and run by its mining task
+pub struct MiningPipeline {
+    job_manager: Arc<JobManager>,
+    miner: Option<CODL3Miner>,
+    stratum: Option<StratumServer>,
+    coordinator: Arc<MergeMiningCoordinator>,
+    sealed: mpsc::Receiver<Block>,
+    /// Pauses handing out work while set, e.g. while the proof queue is full
+    backpressure: Option<watch::Receiver<bool>>,
+    /// Whether this node holds the production lease; always when unset
+    lease: Option<watch::Receiver<bool>>,
+}
+
+impl MiningPipeline {
+    /// Build the pipeline for `config`. Templates are checked under `chain`'s block limits with
+    /// `state` as their state transition, and pay their fees to `fee_recipient`.
+    pub fn new(
+        config: &MiningConfig,
+        chain: &ConsensusConfig,
+        tx_pool: Arc<RwLock<TxPool>>,
+        state: Arc<dyn StateTransition>,
+        fee_recipient: Vec<u8>,
+    ) -> Result<Self> {
+        let mut validator = BlockValidator::new(chain.block_limits(), chain.max_future_drift);
+        validator.set_state_transition(state);
+        let job_manager_config = JobManagerConfig {
+            selection: chain.selection.clone(),
+            ..config.job_manager.clone()
+        };
+        let job_manager = Arc::new(JobManager::new(job_manager_config, tx_pool, validator, fee_recipient)?);
+        let miner = match &config.miner {
+            Some(miner) => Some(CODL3Miner::with_job_manager(
+                miner.clone(),
+                job_manager.clone(),
+                &BackendRegistry::default(),
+            )?),
+            None => None,
+        };
+        let stratum = config.stratum.clone().map(StratumServer::new).transpose()?;
+        let (sealed_tx, sealed) = mpsc::channel(16);
+        let coordinator = MergeMiningCoordinator::new(config.merge_mining.clone(), job_manager.clone(), sealed_tx)?;
+        Ok(Self {
+            job_manager,
+            miner,
+            stratum,
+            coordinator: Arc::new(coordinator),
+            sealed,
+            backpressure: None,
+            lease: None,
+        })
+    }
+
+    /// Job manager building the templates everything here mines
+    pub fn job_manager(&self) -> Arc<JobManager> {
+        self.job_manager.clone()
+    }
+
+    /// Stale block tracker of the in-process miner, if it mines
+    pub fn stale_tracker(&self) -> Option<Arc<RwLock<StaleTracker>>> {
+        self.miner.as_ref().map(CODL3Miner::stale_tracker)
+    }
+
+    /// Per-worker statistics of the Stratum server, if it serves work
+    pub fn worker_stats(&self) -> Option<Arc<RwLock<HashMap<String, WorkerStats>>>> {
+        self.stratum.as_ref().map(StratumServer::worker_stats)
+    }
+
+    /// PPLNS ledger of the Stratum server, if it keeps pool accounts
+    pub fn pplns_ledger(&self) -> Option<Arc<RwLock<PplnsLedger>>> {
+        self.stratum.as_ref().and_then(StratumServer::pplns_ledger)
+    }
+
+    /// Stop handing out work while `signal` is set, once the pipeline runs
+    pub fn follow_backpressure(&mut self, signal: watch::Receiver<bool>) {
+        self.backpressure = Some(signal);
+    }
+
+    /// Build templates and import sealed blocks only while `held` is set, once the pipeline runs
+    pub fn follow_lease(&mut self, held: watch::Receiver<bool>) {
+        self.lease = Some(held);
+    }
+
+    /// Mine on the head of `consensus` until `shutdown`, importing the blocks sealed for it and
+    /// passing them to `announce` for peers
+    pub async fn run(
+        self,
+        consensus: Arc<RwLock<Consensus>>,
+        announce: mpsc::Sender<BlockProposal>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
+        let Self {
+            job_manager,
+            mut miner,
+            mut stratum,
+            coordinator,
+            mut sealed,
+            backpressure,
+            lease,
+        } = self;
+        let (_always_held, held_here) = watch::channel(true);
+        let mut lease = lease.unwrap_or(held_here);
+        job_manager.set_producing(*lease.borrow_and_update());
+        let (running_tx, running) = watch::channel(true);
+        if let Some(stratum) = &mut stratum {
+            stratum.start().await?;
+        }
+
+        // Blocks found by the miner and by Stratum workers reach the coordinator on one channel
+        let (found_tx, found_rx) = broadcast::channel(64);
+        let sources = miner
+            .iter()
+            .map(CODL3Miner::subscribe_blocks)
+            .chain(stratum.iter().map(StratumServer::subscribe_blocks));
+        let forwards: Vec<JoinHandle<()>> =
+            sources.map(|blocks| tokio::spawn(forward(blocks, found_tx.clone()))).collect();
+        let coordinator_task = tokio::spawn(coordinator.run(found_rx, running.clone()));
+        let backpressure_task = backpressure.map(|signal| tokio::spawn(job_manager.clone().follow_backpressure(signal)));
+
+        // Without local threads the job manager still has to build templates for Stratum
+        let (_done_tx, done_rx) = mpsc::channel(1);
+        let manager_task = match &mut miner {
+            Some(miner) => {
+                miner.start().await?;
+                None
+            }
+            None => Some(tokio::spawn(job_manager.clone().run(Vec::new(), done_rx, running))),
+        };
+
+        let mut generation = job_manager.subscribe_generation();
+        let mut poll = tokio::time::interval(TIP_POLL_INTERVAL);
+        let mut tip = None;
+        loop {
+            tokio::select! {
+                _ = poll.tick() => match chain_tip(&consensus).await {
+                    Ok(Some(head)) if tip != Some(head) => {
+                        tip = Some(head);
+                        match &miner {
+                            Some(miner) => {
+                                for stale in miner.notify_tip(head).await {
+                                    println!("Mined block {} went stale", stale.height);
+                                }
+                            }
+                            None => job_manager.update_tip(head),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Failed to read the chain tip to mine on: {}", e),
+                },
+                Ok(()) = generation.changed() => {
+                    if let (Some(stratum), Some(job)) = (&stratum, job_manager.current_job().await) {
+                        if let Err(e) = stratum.set_job(job).await {
+                            eprintln!("Failed to publish mining job to Stratum workers: {}", e);
+                        }
+                    }
+                }
+                Ok(()) = lease.changed() => {
+                    let held = *lease.borrow_and_update();
+                    job_manager.set_producing(held);
+                    if held {
+                        poll.reset_immediately();
+                    }
+                }
+                Some(block) = sealed.recv() => {
+                    let height = block.header.height;
+                    if !*lease.borrow() {
+                        eprintln!("Dropped mined block {}: the production lease is held elsewhere", height);
+                        continue;
+                    }
+                    match import_sealed(&consensus, block).await {
+                        Ok((proposal, outcome)) => {
+                            println!("Imported mined block {} ({})", height, hex::encode(outcome.hash));
+                            let _ = announce.try_send(proposal);
+                            // Mine on top of it straight away
+                            poll.reset_immediately();
+                        }
+                        Err(e) => eprintln!("Mined block {} was not imported: {}", height, e),
+                    }
+                }
+                _ = shutdown.cancelled() => break,
+            }
+        }
+
+        running_tx.send_replace(false);
+        if let Some(miner) = &mut miner {
+            miner.stop().await?;
+        }
+        if let Some(task) = manager_task {
+            task.await?;
+        }
+        coordinator_task.await?;
+        for task in forwards.into_iter().chain(backpressure_task) {
+            task.abort();
+        }
+        if let Some(stratum) = &mut stratum {
+            stratum.stop().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Pass blocks from `blocks` on to `found` until `blocks` closes
+async fn forward(mut blocks: broadcast::Receiver<FoundBlock>, found: broadcast::Sender<FoundBlock>) {
+    loop {
+        match blocks.recv().await {
+            Ok(block) => {
+                let _ = found.send(block);
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("Dropped {} found blocks", missed),
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Head of `consensus` as a tip to mine on
+async fn chain_tip(consensus: &RwLock<Consensus>) -> Result<Option<ChainTip>, ConsensusError> {
+    let head = consensus.read().await.head_with_difficulty().await?;
+    Ok(head.map(|(head, difficulty)| ChainTip {
+        height: head.height,
+        hash: head.hash,
+        difficulty,
+    }))
+}
+
+/// Sign `block` as this node's proposal and import it
+async fn import_sealed(
+    consensus: &RwLock<Consensus>,
+    block: Block,
+) -> Result<(BlockProposal, ImportOutcome), ConsensusError> {
+    let consensus = consensus.read().await;
+    let proposal = consensus.seal_proposal(block).await?;
+    let outcome = consensus.import_proposal(&proposal).await?;
+    Ok((proposal, outcome))
+}
@@ -0,0 +1,645 @@
+//! Webhook notifications for node operators.
+//!
+//! The notifications task polls the consensus head, the bridge and the Fuego daemon and turns
+//! what changed since the last poll into events: a block mined, a reorg, a failed proof, the
+//! Fuego daemon going down, or bridge work that stopped moving. It also follows executed
+//! blocks for payments to the deposit addresses exchanges derive, reporting each deposit
+//! when it is first seen and again once it is confirmed. Each event is POSTed as JSON
+//! to every webhook subscribed to it, retried with a doubling backoff, and signed with an
+//! HMAC-SHA256 of the body when the webhook has a secret.
+
+use anyhow::Result;
+use bridge::Bridge;
+use consensus::engine::ChainHead;
+use consensus::Consensus;
+use execution::receipt::MAX_LOG_RANGE;
+use execution::{DetectedDeposit, ExecutionError};
+use fuego_integration::FuegoDaemon;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use state_db::RocksStateDB;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::sleep_unless_shutdown;
+
+/// Header carrying `sha256=<hex HMAC of the body>` on deliveries to webhooks with a secret
+pub const SIGNATURE_HEADER: &str = "X-Codl3-Signature";
+
+/// Notification settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Endpoints events are posted to; no notifications are sent without one
+    pub webhooks: Vec<WebhookConfig>,
+    /// Attempts at each delivery, the first included
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled on each later one
+    pub retry_backoff_ms: u64,
+    /// How long one delivery attempt may take
+    pub timeout_secs: u64,
+    /// How often the node is checked for events
+    pub poll_interval_secs: u64,
+    /// Pending bridge work that makes no progress for this long is reported stuck
+    pub bridge_stuck_secs: u64,
+    /// Blocks a deposit must have, its own included, to be reported confirmed
+    pub deposit_confirmations: u64,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: Vec::new(),
+            max_attempts: 4,
+            retry_backoff_ms: 500,
+            timeout_secs: 10,
+            poll_interval_secs: 10,
+            bridge_stuck_secs: 600,
+            deposit_confirmations: 10,
+        }
+    }
+}
+
+/// One endpoint receiving events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key deliveries are signed with in the `X-Codl3-Signature` header when set
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events posted to this webhook; empty subscribes it to all of them
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+}
+
+/// Kinds of event a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    BlockMined,
+    ReorgDetected,
+    ProofFailed,
+    FuegoDaemonDown,
+    BridgeStuck,
+    DepositDetected,
+    DepositConfirmed,
+}
+
+/// Something a node operator should hear about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NodeEvent {
+    /// This node mined a block on `chain`, `c0dl3` or `fuego`
+    BlockMined { chain: String, height: u64 },
+    /// `reorgs` reorganizations of `layer`, `l3` or `l1`, the latest leaving the head at `height`
+    ReorgDetected { layer: String, height: u64, reorgs: u64 },
+    /// `failed` more proof submissions to the settlement layer failed
+    ProofFailed { failed: u64, total_failed: u64 },
+    /// The Fuego daemon stopped answering RPC
+    FuegoDaemonDown {
+        consecutive_failures: u32,
+        last_error: Option<String>,
+    },
+    /// `pending` bridge items made no progress for `stalled_secs`
+    BridgeStuck { pending: usize, stalled_secs: u64 },
+    /// A payment to a derived deposit address was executed
+    DepositDetected(DepositNotice),
+    /// A deposit reached the configured confirmations
+    DepositConfirmed(DepositNotice),
+}
+
+/// A deposit as reported to webhooks, with hex-encoded bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositNotice {
+    pub account: String,
+    pub index: u64,
+    pub block_height: u64,
+    pub tx_hash: String,
+    pub position: u32,
+    pub amount: u64,
+    pub asset: Option<String>,
+    pub private: bool,
+    pub confirmations: u64,
+}
+
+impl From<&DetectedDeposit> for DepositNotice {
+    fn from(deposit: &DetectedDeposit) -> Self {
+        Self {
+            account: hex::encode(&deposit.account),
+            index: deposit.index,
+            block_height: deposit.block_height,
+            tx_hash: hex::encode(deposit.tx_hash),
+            position: deposit.position,
+            amount: deposit.amount,
+            asset: deposit.asset.map(hex::encode),
+            private: deposit.private,
+            confirmations: deposit.confirmations,
+        }
+    }
+}
+
+impl NodeEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            NodeEvent::BlockMined { .. } => EventKind::BlockMined,
+            NodeEvent::ReorgDetected { .. } => EventKind::ReorgDetected,
+            NodeEvent::ProofFailed { .. } => EventKind::ProofFailed,
+            NodeEvent::FuegoDaemonDown { .. } => EventKind::FuegoDaemonDown,
+            NodeEvent::BridgeStuck { .. } => EventKind::BridgeStuck,
+            NodeEvent::DepositDetected(_) => EventKind::DepositDetected,
+            NodeEvent::DepositConfirmed(_) => EventKind::DepositConfirmed,
+        }
+    }
+}
+
+/// Notification delivery statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotifierStats {
+    pub events: u64,
+    pub delivered: u64,
+    /// Deliveries that failed every attempt
+    pub failed: u64,
+    pub retries: u64,
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` under `secret`
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Posts events to the configured webhooks
+pub struct Notifier {
+    config: NotificationConfig,
+    http: reqwest::Client,
+    stats: Arc<RwLock<NotifierStats>>,
+}
+
+impl Notifier {
+    pub fn new(config: NotificationConfig) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        Ok(Self {
+            config,
+            http,
+            stats: Arc::new(RwLock::new(NotifierStats::default())),
+        })
+    }
+
+    /// Post `event` to every webhook subscribed to it, returning how many accepted it
+    pub async fn notify(&self, event: &NodeEvent) -> usize {
+        let mut payload = serde_json::to_value(event).expect("events serialize to JSON");
+        payload["timestamp"] = unix_now().into();
+        let body = payload.to_string();
+        self.stats.write().await.events += 1;
+
+        let mut delivered = 0;
+        for webhook in &self.config.webhooks {
+            if !webhook.events.is_empty() && !webhook.events.contains(&event.kind()) {
+                continue;
+            }
+            match self.deliver(webhook, &body).await {
+                Ok(()) => delivered += 1,
+                Err(e) => {
+                    eprintln!("Notification to {} not delivered: {}", webhook.url, e);
+                    self.stats.write().await.failed += 1;
+                }
+            }
+        }
+        self.stats.write().await.delivered += delivered as u64;
+        delivered
+    }
+
+    async fn deliver(&self, webhook: &WebhookConfig, body: &str) -> Result<(), reqwest::Error> {
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .http
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+            if let Some(secret) = &webhook.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret.as_bytes(), body.as_bytes()));
+            }
+            match request.send().await.and_then(|response| response.error_for_status()) {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt >= self.config.max_attempts => return Err(e),
+                Err(_) => {
+                    self.stats.write().await.retries += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    pub fn config(&self) -> &NotificationConfig {
+        &self.config
+    }
+
+    pub async fn get_stats(&self) -> NotifierStats {
+        self.stats.read().await.clone()
+    }
+}
+
+/// What the notifications task read from the node in one poll
+#[derive(Debug, Clone, Default)]
+pub struct NodeObservation {
+    pub head: Option<ChainHead>,
+    pub last_l1_block: u64,
+    pub l1_reorgs: u64,
+    pub proofs_failed: u64,
+    /// Counters that move whenever the bridge gets work done
+    pub bridge_progress: [u64; 3],
+    /// Items waiting in the bridge queues
+    pub bridge_pending: usize,
+    /// Fuego daemon health as `(healthy, consecutive_failures, last_error)`, when mining
+    pub fuego_health: Option<(bool, u32, Option<String>)>,
+    /// Fuego blocks submitted and the height of the latest template, when mining
+    pub fuego_mined: Option<(u64, u64)>,
+}
+
+/// Turns successive observations into events, reporting each outage once
+pub struct EventWatcher {
+    /// Report heads this node builds as mined blocks
+    produces_blocks: bool,
+    bridge_stuck_secs: u64,
+    last: Option<NodeObservation>,
+    fuego_down: bool,
+    /// When the bridge last made progress or had nothing to do
+    bridge_moved_at: u64,
+    bridge_stuck: bool,
+}
+
+impl EventWatcher {
+    pub fn new(produces_blocks: bool, bridge_stuck_secs: u64) -> Self {
+        Self {
+            produces_blocks,
+            bridge_stuck_secs,
+            last: None,
+            fuego_down: false,
+            bridge_moved_at: 0,
+            bridge_stuck: false,
+        }
+    }
+
+    /// Events between the previous observation and `observation`, made at unix time `now`.
+    /// The first observation only sets the baseline for the counters.
+    pub fn observe(&mut self, observation: NodeObservation, now: u64) -> Vec<NodeEvent> {
+        let mut events = Vec::new();
+        if let Some((healthy, consecutive_failures, last_error)) = &observation.fuego_health {
+            if !healthy && *consecutive_failures > 0 && !self.fuego_down {
+                events.push(NodeEvent::FuegoDaemonDown {
+                    consecutive_failures: *consecutive_failures,
+                    last_error: last_error.clone(),
+                });
+            }
+            self.fuego_down = !healthy && *consecutive_failures > 0;
+        }
+
+        let progressed = self.last.as_ref().is_none_or(|last| last.bridge_progress != observation.bridge_progress);
+        if progressed || observation.bridge_pending == 0 {
+            self.bridge_moved_at = now;
+            self.bridge_stuck = false;
+        } else if now.saturating_sub(self.bridge_moved_at) >= self.bridge_stuck_secs && !self.bridge_stuck {
+            self.bridge_stuck = true;
+            events.push(NodeEvent::BridgeStuck {
+                pending: observation.bridge_pending,
+                stalled_secs: now - self.bridge_moved_at,
+            });
+        }
+
+        if let Some(last) = &self.last {
+            match (&last.head, &observation.head) {
+                (Some(before), Some(head)) if head.hash != before.hash && head.height <= before.height => {
+                    events.push(NodeEvent::ReorgDetected {
+                        layer: "l3".to_string(),
+                        height: head.height,
+                        reorgs: 1,
+                    });
+                }
+                (before, Some(head)) if self.produces_blocks && before.as_ref() != Some(head) => {
+                    events.push(NodeEvent::BlockMined {
+                        chain: "c0dl3".to_string(),
+                        height: head.height,
+                    });
+                }
+                _ => {}
+            }
+            if let (Some((before, _)), Some((submitted, height))) = (last.fuego_mined, observation.fuego_mined) {
+                if submitted > before {
+                    events.push(NodeEvent::BlockMined {
+                        chain: "fuego".to_string(),
+                        height,
+                    });
+                }
+            }
+            if observation.l1_reorgs > last.l1_reorgs {
+                events.push(NodeEvent::ReorgDetected {
+                    layer: "l1".to_string(),
+                    height: observation.last_l1_block,
+                    reorgs: observation.l1_reorgs - last.l1_reorgs,
+                });
+            }
+            if observation.proofs_failed > last.proofs_failed {
+                events.push(NodeEvent::ProofFailed {
+                    failed: observation.proofs_failed - last.proofs_failed,
+                    total_failed: observation.proofs_failed,
+                });
+            }
+        }
+        self.last = Some(observation);
+        events
+    }
+}
+
+/// Follows executed blocks for deposits to derived addresses. The first poll starts after
+/// the latest block; deposits executed while the node was down are found with
+/// `exchange_getDeposits` instead.
+pub struct DepositWatcher {
+    confirmations: u64,
+    /// Next block to scan, once the first poll has set it
+    next_block: Option<u64>,
+    /// Deposits reported detected but not yet confirmed
+    pending: Vec<DetectedDeposit>,
+}
+
+impl DepositWatcher {
+    pub fn new(confirmations: u64) -> Self {
+        Self {
+            confirmations,
+            next_block: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Deposits executed since the last poll, and those that have reached the confirmations
+    pub fn poll(&mut self, state: &RocksStateDB) -> Result<Vec<NodeEvent>, ExecutionError> {
+        let Some(latest) = state.latest_version() else {
+            return Ok(Vec::new());
+        };
+        let mut events = Vec::new();
+        let from_block = *self.next_block.get_or_insert(latest + 1);
+        if from_block <= latest {
+            let to_block = latest.min(from_block + MAX_LOG_RANGE - 1);
+            for deposit in execution::exchange::scan_deposits(state, from_block, to_block)? {
+                events.push(NodeEvent::DepositDetected((&deposit).into()));
+                self.pending.push(deposit);
+            }
+            self.next_block = Some(to_block + 1);
+        }
+        let confirmations = self.confirmations;
+        self.pending.retain_mut(|deposit| {
+            deposit.confirmations = latest.saturating_sub(deposit.block_height) + 1;
+            if deposit.confirmations < confirmations {
+                return true;
+            }
+            events.push(NodeEvent::DepositConfirmed((&*deposit).into()));
+            false
+        });
+        Ok(events)
+    }
+}
+
+/// What the notifications task polls
+#[derive(Clone)]
+pub struct EventSources {
+    pub consensus: Arc<RwLock<Consensus>>,
+    pub bridge: Arc<RwLock<Bridge>>,
+    pub fuego_daemon: Option<Arc<RwLock<FuegoDaemon>>>,
+    pub state_db: Arc<RwLock<RocksStateDB>>,
+}
+
+impl EventSources {
+    async fn observe(&self) -> NodeObservation {
+        let head = self.consensus.read().await.head().await;
+        let bridge = self.bridge.read().await;
+        let stats = bridge.get_bridge_stats().await;
+        let bridge_pending = match bridge.queue_depths().await {
+            Ok(depths) => depths.pending_proofs + depths.pending_mints + depths.pending_withdrawals,
+            Err(e) => {
+                eprintln!("Failed to read bridge queues: {}", e);
+                0
+            }
+        };
+        let (fuego_health, fuego_mined) = match &self.fuego_daemon {
+            Some(daemon) => {
+                let daemon = daemon.read().await;
+                let health = daemon.client().health().await;
+                let mined = daemon.get_stats().await;
+                (
+                    Some((health.is_healthy, health.consecutive_failures, health.last_error)),
+                    Some((mined.blocks_submitted, mined.last_template_height)),
+                )
+            }
+            None => (None, None),
+        };
+        NodeObservation {
+            head,
+            last_l1_block: stats.last_l1_block,
+            l1_reorgs: stats.l1_reorgs,
+            proofs_failed: stats.total_proofs_failed,
+            bridge_progress: [
+                stats.total_proofs_confirmed,
+                stats.total_batches_committed,
+                stats.total_deposits_ingested,
+            ],
+            bridge_pending,
+            fuego_health,
+            fuego_mined,
+        }
+    }
+}
+
+/// Poll `sources` every poll interval and post what changed until shutdown
+pub async fn run_notifications(
+    notifier: Arc<Notifier>,
+    sources: EventSources,
+    produces_blocks: bool,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let config = notifier.config();
+    let mut watcher = EventWatcher::new(produces_blocks, config.bridge_stuck_secs);
+    let mut deposits = DepositWatcher::new(config.deposit_confirmations);
+    let interval = Duration::from_secs(config.poll_interval_secs.max(1));
+    loop {
+        let mut events = watcher.observe(sources.observe().await, unix_now());
+        match deposits.poll(&*sources.state_db.read().await) {
+            Ok(found) => events.extend(found),
+            Err(e) => eprintln!("Failed to scan for deposits: {}", e),
+        }
+        for event in events {
+            notifier.notify(&event).await;
+        }
+        if !sleep_unless_shutdown(&shutdown, interval).await {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_watcher_reports_changes_and_each_outage_once() {
+        let mut watcher = EventWatcher::new(true, 60);
+        let head = |height: u64, hash: u8| Some(ChainHead { height, hash: [hash; 32] });
+        let observation = NodeObservation {
+            head: head(5, 5),
+            bridge_pending: 2,
+            fuego_health: Some((true, 0, None)),
+            ..Default::default()
+        };
+        assert!(watcher.observe(observation.clone(), 1_000).is_empty());
+
+        let mined = NodeObservation { head: head(6, 6), ..observation.clone() };
+        let events = watcher.observe(mined.clone(), 1_010);
+        assert_eq!(events, [NodeEvent::BlockMined { chain: "c0dl3".to_string(), height: 6 }]);
+
+        // A head replaced at the same height is a reorg; L1 reorgs and failed proofs are counted
+        let down = NodeObservation {
+            head: head(6, 7),
+            l1_reorgs: 2,
+            proofs_failed: 1,
+            fuego_health: Some((false, 3, Some("refused".to_string()))),
+            ..mined
+        };
+        let events = watcher.observe(down.clone(), 1_020);
+        let kinds: Vec<EventKind> = events.iter().map(NodeEvent::kind).collect();
+        assert_eq!(
+            kinds,
+            [EventKind::FuegoDaemonDown, EventKind::ReorgDetected, EventKind::ReorgDetected, EventKind::ProofFailed]
+        );
+        assert_eq!(events[2], NodeEvent::ReorgDetected { layer: "l1".to_string(), height: 0, reorgs: 2 });
+
+        // The bridge has made no progress with work pending since 1_000
+        let events = watcher.observe(down.clone(), 1_060);
+        assert_eq!(events, [NodeEvent::BridgeStuck { pending: 2, stalled_secs: 60 }]);
+        assert!(watcher.observe(down, 1_100).is_empty());
+    }
+
+    #[test]
+    fn test_deposits_are_reported_when_seen_and_when_confirmed() {
+        use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+        use execution::{BlockExecutor, ExecutionConfig};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        let mut genesis = state_db::Genesis::default();
+        genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 1_000_000 });
+        state.apply_genesis(&genesis).unwrap();
+        let deposit_address = execution::exchange::new_deposit_address(&mut state, &[0xe0; 20]).unwrap();
+        let block = |height: u64, transactions: Vec<Transaction>| Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof { proof_type: ProofType::PoW, proof_data: vec![] },
+        };
+        let deposit = Transaction {
+            hash: [0x11; 32],
+            sender: vec![0xa1],
+            nonce: 0,
+            gas_limit: 100_000,
+            data: Vec::new(),
+            inputs: vec![],
+            outputs: vec![TxOutput {
+                amount: 250,
+                address: deposit_address.address,
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 100_000,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+        };
+
+        // The first poll only sets where scanning starts
+        let mut watcher = DepositWatcher::new(2);
+        assert!(watcher.poll(&state).unwrap().is_empty());
+        let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+        executor.process_block(&mut state, &block(1, vec![deposit]), &[0xfe]).unwrap();
+        let events = watcher.poll(&state).unwrap();
+        let [NodeEvent::DepositDetected(notice)] = events.as_slice() else {
+            panic!("expected one detected deposit, got {:?}", events);
+        };
+        assert_eq!((notice.amount, notice.confirmations, notice.private), (250, 1, false));
+        assert_eq!(notice.account, "e0".repeat(20));
+
+        executor.process_block(&mut state, &block(2, vec![]), &[0xfe]).unwrap();
+        let events = watcher.poll(&state).unwrap();
+        assert_eq!(events.iter().map(NodeEvent::kind).collect::<Vec<_>>(), [EventKind::DepositConfirmed]);
+        assert!(watcher.poll(&state).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_notifier_signs_retries_and_filters_by_event() {
+        let server = MockServer::start().await;
+        let event = NodeEvent::ProofFailed { failed: 1, total_failed: 1 };
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header("content-type", "application/json"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let webhook = |url: &str, events: Vec<EventKind>| WebhookConfig {
+            url: format!("{}{}", server.uri(), url),
+            secret: Some("hook-secret".to_string()),
+            events,
+        };
+        let notifier = Notifier::new(NotificationConfig {
+            webhooks: vec![
+                webhook("/hook", vec![EventKind::ProofFailed]),
+                webhook("/blocks", vec![EventKind::BlockMined]),
+            ],
+            retry_backoff_ms: 10,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(notifier.notify(&event).await, 1);
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let delivered = &requests[1];
+        let signature = delivered.headers.get(SIGNATURE_HEADER).unwrap().to_str().unwrap();
+        assert_eq!(signature, sign(b"hook-secret", &delivered.body));
+        let payload: serde_json::Value = serde_json::from_slice(&delivered.body).unwrap();
+        assert_eq!((payload["event"].as_str(), payload["failed"].as_u64()), (Some("proof_failed"), Some(1)));
+        let stats = notifier.get_stats().await;
+        assert_eq!((stats.delivered, stats.retries, stats.failed), (1, 1, 0));
+    }
+}
@@ -0,0 +1,494 @@
+//! Offline checks and rebuilds of a data directory, for after a crash or suspected database
+//! corruption. `node verify-chain` replays every stored block from genesis into a scratch
+//! state database: each block is checked on its own, then against its parent with the
+//! checks consensus imports it under (difficulty, median time past and the proposer's
+//! signature on its seal) and for its AuxPoW and input signatures, then executed, and what
+//! the replay rebuilds is compared with what is stored: the state root, header and receipts
+//! of each height. The first height where they part is reported. The AuxPoW is the only
+//! proof a block carries and is checked with the same verifier as live import; the prover's
+//! zk proofs are not part of blocks and import never checks them, so neither does replay.
+//! `node reindex` runs the same replay and, when the chain holds up, swaps the rebuilt
+//! database in, carrying over the records kept outside the chain such as bridge, staking
+//! and finality bookkeeping. Both need the node to be stopped.
+
+use crate::blocks::stored_seal;
+use crate::chain_spec::{read_genesis_record, ChainSpec};
+use anyhow::{bail, Result};
+use block_sync::{Block, BlockHeader, Canonical};
+use consensus::engine::{header_id, header_signing_bytes, ConsensusEngine, HybridEngine, HybridEngineConfig};
+use consensus::validators::{Validator, ValidatorSet};
+use consensus::BlockValidator;
+use execution::receipt::{get_block, get_headers, get_receipt};
+use execution::BlockExecutor;
+use state_db::{RocksStateDB, StateDBConfig};
+use std::fmt;
+use std::path::Path;
+
+/// Scratch database `node verify-chain` replays into, relative to the data directory
+const VERIFY_DIR: &str = "state.verify";
+/// Database `node reindex` rebuilds before swapping it in, relative to the data directory
+const REINDEX_DIR: &str = "state.reindex";
+/// Where `node reindex` keeps the database it replaced, relative to the data directory
+const REPLACED_DIR: &str = "state.pre-reindex";
+
+/// Check of a stored block that failed during replay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayCheck {
+    /// The block's body is absent or cannot be decoded
+    Body,
+    /// The block's header on its own, its size and its transaction order
+    Syntax,
+    /// The block's parent hash against the block stored below it
+    Linkage,
+    /// The block's difficulty and timestamp against what its parent chain requires
+    Context,
+    /// The merge-mined AuxPoW
+    ProofOfWork,
+    /// The proposer's signature on the block's seal
+    Proposal,
+    /// The input signatures of the block's transactions
+    Signatures,
+    /// Executing the block on the replayed parent state
+    StateTransition,
+    /// The replayed state root against the stored one
+    StateRoot,
+    /// The stored header and receipts against the replayed ones
+    Index,
+}
+
+impl fmt::Display for ReplayCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReplayCheck::Body => "body",
+            ReplayCheck::Syntax => "syntax",
+            ReplayCheck::Linkage => "linkage",
+            ReplayCheck::Context => "context",
+            ReplayCheck::ProofOfWork => "pow",
+            ReplayCheck::Proposal => "proposal",
+            ReplayCheck::Signatures => "signatures",
+            ReplayCheck::StateTransition => "state",
+            ReplayCheck::StateRoot => "state root",
+            ReplayCheck::Index => "index",
+        })
+    }
+}
+
+/// First height where the stored chain and its replay part
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub height: u64,
+    pub check: ReplayCheck,
+    pub reason: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "block {} failed the {} check: {}", self.height, self.check, self.reason)
+    }
+}
+
+/// Outcome of replaying a data directory's blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Highest version the stored state committed
+    pub latest: Option<u64>,
+    /// Blocks that passed every check, genesis not counted
+    pub blocks_checked: u64,
+    pub divergence: Option<Divergence>,
+}
+
+/// Replay the chain stored in `data_dir` and report the first divergence, leaving the data
+/// directory as it was
+pub fn verify_chain(chain: &ChainSpec, data_dir: &Path, config: &StateDBConfig) -> Result<ReplayReport> {
+    check_genesis(chain, data_dir)?;
+    let scratch = data_dir.join(VERIFY_DIR);
+    remove_dir(&scratch)?;
+    let report = {
+        let stored = RocksStateDB::with_config(data_dir.join("state"), config.clone())?;
+        let mut replayed = RocksStateDB::with_config(&scratch, config.clone())?;
+        replay(chain, &stored, &mut replayed)?
+    };
+    remove_dir(&scratch)?;
+    Ok(report)
+}
+
+/// Rebuild the state database of `data_dir` by replaying its blocks. The stored database is
+/// only replaced if every block passes; it is kept beside the new one until the next reindex.
+pub fn reindex(chain: &ChainSpec, data_dir: &Path, config: &StateDBConfig) -> Result<ReplayReport> {
+    check_genesis(chain, data_dir)?;
+    let (live, rebuilt) = (data_dir.join("state"), data_dir.join(REINDEX_DIR));
+    remove_dir(&rebuilt)?;
+    let report = {
+        let stored = RocksStateDB::with_config(&live, config.clone())?;
+        let mut replayed = RocksStateDB::with_config(&rebuilt, config.clone())?;
+        let report = replay(chain, &stored, &mut replayed)?;
+        if report.divergence.is_none() {
+            let copied = replayed.copy_missing_from(&stored)?;
+            replayed.flush()?;
+            println!("Carried over {} records kept outside the chain", copied);
+        }
+        report
+    };
+    if report.divergence.is_some() {
+        remove_dir(&rebuilt)?;
+        return Ok(report);
+    }
+
+    let replaced = data_dir.join(REPLACED_DIR);
+    remove_dir(&replaced)?;
+    std::fs::rename(&live, &replaced)?;
+    std::fs::rename(&rebuilt, &live)?;
+    println!("Previous state database kept at {}", replaced.display());
+    Ok(report)
+}
+
+/// Fail unless `data_dir` was initialized for `chain`, if it records a genesis at all
+fn check_genesis(chain: &ChainSpec, data_dir: &Path) -> Result<()> {
+    let genesis_hash = hex::encode(chain.genesis_hash());
+    match read_genesis_record(data_dir)? {
+        Some(record) if record.genesis_hash != genesis_hash => bail!(
+            "{} was initialized for {} (genesis {}) but the node is configured for {} (genesis {})",
+            data_dir.display(),
+            record.chain,
+            record.genesis_hash,
+            chain.name,
+            genesis_hash
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn remove_dir(path: &Path) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
+
+/// Replay every block of `stored` onto the empty `replayed`, stopping at the first
+/// divergence. Errors are left for failures of the databases themselves.
+pub fn replay(chain: &ChainSpec, stored: &RocksStateDB, replayed: &mut RocksStateDB) -> Result<ReplayReport> {
+    let mut report = ReplayReport {
+        latest: stored.latest_version(),
+        ..Default::default()
+    };
+    if !chain.genesis.alloc.is_empty() {
+        replayed.apply_genesis(&chain.genesis)?;
+    }
+    let diverge = |height: u64, check: ReplayCheck, reason: String| Divergence { height, check, reason };
+    if stored.root_at(0)? != replayed.root_at(0)? {
+        let reason = "genesis state differs from the chain spec's".to_string();
+        report.divergence = Some(diverge(0, ReplayCheck::StateRoot, reason));
+        return Ok(report);
+    }
+
+    let consensus_config = chain.consensus_config();
+    let validator = BlockValidator::new(consensus_config.block_limits(), consensus_config.max_future_drift);
+    let mut executor = BlockExecutor::new(chain.execution_config())?;
+    // Holds the replayed chain, final up to its tip, for what each next block's context must be
+    let mut engine = HybridEngine::new(HybridEngineConfig::from(&consensus_config), ValidatorSet::default())?;
+    let mut parent = chain.genesis_header();
+    engine.restore_chain(std::slice::from_ref(&parent))?;
+    for height in 1..=report.latest.unwrap_or(0) {
+        match check_block(stored, replayed, &validator, &engine, &mut executor, &parent, height) {
+            Ok(header) => {
+                engine.extend_final(&header)?;
+                parent = header;
+            }
+            Err((check, reason)) => {
+                report.divergence = Some(diverge(height, check, reason));
+                break;
+            }
+        }
+        report.blocks_checked += 1;
+    }
+    Ok(report)
+}
+
+/// Replay stored block `height` on top of `parent`, returning its header
+fn check_block(
+    stored: &RocksStateDB,
+    replayed: &mut RocksStateDB,
+    validator: &BlockValidator,
+    engine: &HybridEngine,
+    executor: &mut BlockExecutor,
+    parent: &BlockHeader,
+    height: u64,
+) -> Result<BlockHeader, (ReplayCheck, String)> {
+    let (block, fee_recipient): (Block, Vec<u8>) = match get_block(stored, height) {
+        Ok(Some(stored_block)) => stored_block,
+        Ok(None) => return Err((ReplayCheck::Body, "not stored".to_string())),
+        Err(e) => return Err((ReplayCheck::Body, e.to_string())),
+    };
+    if block.header.height != height {
+        return Err((ReplayCheck::Body, format!("stored under height {} but is block {}", height, block.header.height)));
+    }
+
+    validator
+        .check_syntax(&block)
+        .map_err(|e| (ReplayCheck::Syntax, e.to_string()))?;
+    if block.header.prev_hash != header_id(parent) {
+        return Err((
+            ReplayCheck::Linkage,
+            format!("parent hash {} is not block {}", hex::encode(block.header.prev_hash), height - 1),
+        ));
+    }
+    let context = engine
+        .block_context(&block.header)
+        .map_err(|e| (ReplayCheck::Context, e.to_string()))?;
+    validator
+        .check_context(&block.header, &context)
+        .map_err(|e| (ReplayCheck::Context, e.to_string()))?;
+    validator
+        .check_work(&block)
+        .map_err(|e| (ReplayCheck::ProofOfWork, e.to_string()))?;
+    check_proposer(stored, &block.header, &fee_recipient).map_err(|reason| (ReplayCheck::Proposal, reason))?;
+    validator
+        .check_signatures(&block)
+        .map_err(|e| (ReplayCheck::Signatures, e.to_string()))?;
+
+    let execution = executor
+        .process_block(replayed, &block, &fee_recipient)
+        .map_err(|e| (ReplayCheck::StateTransition, e.to_string()))?;
+    match stored.root_at(height) {
+        Ok(Some(root)) if root == execution.state_root => {}
+        Ok(Some(root)) => {
+            return Err((
+                ReplayCheck::StateRoot,
+                format!("replayed root {} but {} is stored", hex::encode(execution.state_root), hex::encode(root)),
+            ))
+        }
+        Ok(None) => return Err((ReplayCheck::StateRoot, "no state root stored".to_string())),
+        Err(e) => return Err((ReplayCheck::StateRoot, e.to_string())),
+    }
+
+    let index_error = |e: execution::ExecutionError| (ReplayCheck::Index, e.to_string());
+    let headers = get_headers(stored, height, 1).map_err(index_error)?;
+    if headers.iter().map(Canonical::to_canonical_bytes).ne([block.header.to_canonical_bytes()]) {
+        return Err((ReplayCheck::Index, "stored header differs from the block's".to_string()));
+    }
+    for receipt in &execution.receipts {
+        if get_receipt(stored, &receipt.tx_hash).map_err(index_error)?.as_ref() != Some(receipt) {
+            return Err((
+                ReplayCheck::Index,
+                format!("receipt of transaction {} differs from the replayed one", hex::encode(receipt.tx_hash)),
+            ));
+        }
+    }
+    Ok(block.header)
+}
+
+/// Check the seal stored for the block with `header` was signed by the validator paid its fees
+fn check_proposer(stored: &RocksStateDB, header: &BlockHeader, fee_recipient: &[u8]) -> Result<(), String> {
+    let seal = stored_seal(stored, header.height)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "no seal stored".to_string())?;
+    let public_key = fee_recipient
+        .try_into()
+        .map_err(|_| format!("fee recipient {} is not a validator key", hex::encode(fee_recipient)))?;
+    // The proposer only has to have been staked when the block was imported
+    let mut proposer = ValidatorSet::default();
+    proposer
+        .insert(Validator { id: seal.proposer, public_key, stake: 1 })
+        .map_err(|e| e.to_string())?;
+    proposer
+        .verify(seal.proposer, &header_signing_bytes(header), &seal.signature)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::store_seal;
+    use block_sync::{BlockProof, ProofType, Transaction, TxOutput};
+    use consensus::ProposalSeal;
+    use ed25519_dalek::{Signer, SigningKey};
+    use execution::Network;
+    use pow::auxpow::bare_coinbase;
+    use pow::{AuxPow, MergeMinedProof, ParentBlockHeader};
+    use state_db::account::GenesisAccount;
+    use tempfile::TempDir;
+
+    fn transfer(nonce: u64) -> Transaction {
+        Transaction {
+            hash: [0u8; 32],
+            sender: vec![0xa1],
+            nonce,
+            gas_limit: 100_000,
+            data: Vec::new(),
+            inputs: vec![],
+            outputs: vec![TxOutput {
+                amount: 250,
+                address: vec![0xb2],
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 100_000,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 3,
+            fee_payer: None,
+            asset: None,
+        }
+        .with_id()
+    }
+
+    /// Devnet data directory holding two executed blocks and a record kept outside the chain
+    fn data_dir(chain: &ChainSpec) -> TempDir {
+        data_dir_with(chain, |_| {}, |_, _| {})
+    }
+
+    /// Like `data_dir`, with `tweak` applied to each block's header before it is mined and
+    /// `tamper` to the proof mined for each height afterwards
+    fn data_dir_with(
+        chain: &ChainSpec,
+        tweak: impl Fn(&mut BlockHeader),
+        tamper: impl Fn(u64, &mut MergeMinedProof),
+    ) -> TempDir {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+        chain.init_data_dir(temp_dir.path(), &mut state).unwrap();
+        let mut executor = BlockExecutor::new(chain.execution_config()).unwrap();
+        let mut parent = chain.genesis_header();
+        for height in 1..=2 {
+            let transactions = vec![transfer(height - 1)];
+            let mut header = BlockHeader {
+                height,
+                prev_hash: header_id(&parent),
+                merkle_root: consensus::merkle_root(&transactions),
+                timestamp: parent.timestamp + 2,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            };
+            tweak(&mut header);
+            let mut parent_header = ParentBlockHeader::default();
+            let coinbase = bare_coinbase(height);
+            let aux_pow = AuxPow::create(header.hash().unwrap(), coinbase, &[], &mut parent_header).unwrap();
+            let mut proof = MergeMinedProof { parent_header, aux_pow };
+            tamper(height, &mut proof);
+            let block = Block {
+                header: header.clone(),
+                transactions,
+                proof: BlockProof { proof_type: ProofType::PoW, proof_data: serde_json::to_vec(&proof).unwrap() },
+            };
+            executor.process_block(&mut state, &block, &key.verifying_key().to_bytes()).unwrap();
+            let seal = ProposalSeal {
+                proposer: 0,
+                timestamp: header.timestamp,
+                signature: key.sign(&header_signing_bytes(&header)).to_bytes().to_vec(),
+            };
+            store_seal(&mut state, height, &seal).unwrap();
+            parent = header;
+        }
+        state.write_batch_sync(&[(b"bridge/cursor".to_vec(), b"7".to_vec())]).unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_reindex_rebuilds_a_chain_that_verifies() {
+        let mut chain = ChainSpec::for_network(Network::Devnet);
+        chain.genesis.alloc.insert("a1".to_string(), GenesisAccount { balance: 1_000_000 });
+        let temp_dir = data_dir(&chain);
+        let config = StateDBConfig::default();
+
+        let report = verify_chain(&chain, temp_dir.path(), &config).unwrap();
+        assert_eq!((report.latest, report.blocks_checked, report.divergence), (Some(2), 2, None));
+        assert!(!temp_dir.path().join(VERIFY_DIR).exists());
+
+        assert_eq!(reindex(&chain, temp_dir.path(), &config).unwrap().divergence, None);
+        assert!(temp_dir.path().join(REPLACED_DIR).exists());
+        let state = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+        assert_eq!(state.latest_version(), Some(2));
+        assert_eq!(state.get_account(&[0xb2]).unwrap().balance, 500);
+        assert_eq!(state.get_sync(b"bridge/cursor").unwrap(), Some(b"7".to_vec()));
+        assert!(stored_seal(&state, 2).unwrap().is_some());
+        assert!(get_receipt(&state, &transfer(1).hash).unwrap().is_some());
+
+        // Another chain's spec is refused before anything is opened
+        assert!(verify_chain(&ChainSpec::for_network(Network::Testnet), temp_dir.path(), &config).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_index_is_reported_and_not_swapped_in() {
+        let mut chain = ChainSpec::for_network(Network::Devnet);
+        chain.genesis.alloc.insert("a1".to_string(), GenesisAccount { balance: 1_000_000 });
+        let temp_dir = data_dir(&chain);
+        {
+            let mut state = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+            let mut receipt = get_receipt(&state, &transfer(1).hash).unwrap().unwrap();
+            receipt.gas_used += 1;
+            let key = [b"receipt/".as_slice(), &transfer(1).hash].concat();
+            state.write_batch_sync(&[(key, serde_json::to_vec(&receipt).unwrap())]).unwrap();
+        }
+
+        let report = reindex(&chain, temp_dir.path(), &StateDBConfig::default()).unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!((divergence.height, divergence.check, report.blocks_checked), (2, ReplayCheck::Index, 1));
+        assert!(!temp_dir.path().join(REINDEX_DIR).exists());
+        assert!(!temp_dir.path().join(REPLACED_DIR).exists());
+
+        // A replay against another genesis allocation parts at genesis
+        chain.genesis.alloc.insert("a2".to_string(), GenesisAccount { balance: 1 });
+        let stored = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+        let replay_dir = TempDir::new().unwrap();
+        let mut replayed = RocksStateDB::new(replay_dir.path()).unwrap();
+        let divergence = replay(&chain, &stored, &mut replayed).unwrap().divergence.unwrap();
+        assert_eq!((divergence.height, divergence.check), (0, ReplayCheck::StateRoot));
+    }
+
+    #[test]
+    fn test_blocks_failing_the_consensus_checks_are_reported() {
+        let mut chain = ChainSpec::for_network(Network::Devnet);
+        chain.genesis.alloc.insert("a1".to_string(), GenesisAccount { balance: 1_000_000 });
+        let config = StateDBConfig::default();
+
+        // A difficulty the chain below does not call for
+        let temp_dir = data_dir_with(&chain, |header| header.difficulty = 2, |_, _| {});
+        let divergence = verify_chain(&chain, temp_dir.path(), &config).unwrap().divergence.unwrap();
+        assert_eq!((divergence.height, divergence.check), (1, ReplayCheck::Context));
+
+        // A timestamp not past the median of the blocks below
+        let temp_dir = data_dir_with(
+            &chain,
+            |header| {
+                if header.height == 2 {
+                    header.timestamp -= 4;
+                }
+            },
+            |_, _| {},
+        );
+        let divergence = verify_chain(&chain, temp_dir.path(), &config).unwrap().divergence.unwrap();
+        assert_eq!((divergence.height, divergence.check), (2, ReplayCheck::Context));
+
+        // An AuxPoW whose parent header no longer commits to its coinbase
+        let temp_dir = data_dir_with(
+            &chain,
+            |_| {},
+            |height, proof| {
+                if height == 2 {
+                    proof.parent_header.merkle_root[0] ^= 1;
+                }
+            },
+        );
+        let divergence = verify_chain(&chain, temp_dir.path(), &config).unwrap().divergence.unwrap();
+        assert_eq!((divergence.height, divergence.check), (2, ReplayCheck::ProofOfWork));
+
+        // A seal signed by someone other than the validator paid the block's fees
+        let temp_dir = data_dir(&chain);
+        {
+            let mut state = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+            let mut seal = stored_seal(&state, 2).unwrap().unwrap();
+            let header = get_block(&state, 2).unwrap().unwrap().0.header;
+            let outsider = SigningKey::from_bytes(&[8u8; 32]);
+            seal.signature = outsider.sign(&header_signing_bytes(&header)).to_bytes().to_vec();
+            store_seal(&mut state, 2, &seal).unwrap();
+        }
+        let report = reindex(&chain, temp_dir.path(), &config).unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!((divergence.height, divergence.check, report.blocks_checked), (2, ReplayCheck::Proposal, 1));
+        assert!(!temp_dir.path().join(REPLACED_DIR).exists());
+    }
+}
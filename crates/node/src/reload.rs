@@ -0,0 +1,341 @@
+//! Reloading the node's configuration while it runs, on SIGHUP or `admin_reloadConfig`.
+//!
+//! The reloaded config is validated as a whole, then compared with the running one.
+//! Settings in `RELOADABLE` are applied to their subsystems at once; any other change
+//! is reported as waiting for a restart and left out of the running config.
+
+use crate::config::{ConfigError, REDACTED, SECRET_SETTINGS};
+use crate::telemetry::{otlp_layer, LoggingConfig, Telemetry};
+use crate::NodeConfig;
+use async_trait::async_trait;
+use fuego_integration::FuegoDaemon;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::RwLock;
+use toml::{Table, Value};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt as log_fmt, reload, Registry};
+use txpool::fee::SimpleFeeAlgorithm;
+use txpool::TxPool;
+
+/// Settings applied without a restart, by their path in the TOML config
+pub const RELOADABLE: &[&str] = &[
+    "log_level",
+    "min_fee",
+    "max_fee",
+    "tx_pool_limits",
+    "max_peers",
+    "fuego.threads",
+    "fuego.poll_interval",
+];
+
+/// Produces the config a reload should switch to, layered the same way as at startup
+pub type ConfigLoader = Box<dyn Fn() -> Result<NodeConfig, ConfigError> + Send + Sync>;
+
+/// One setting that differs between the running and the reloaded config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChange {
+    /// Path of the setting, e.g. `fuego.poll_interval.secs`
+    pub setting: String,
+    /// Value before the reload; `None` when the setting was unset
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unset = "(unset)".to_string();
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.setting,
+            self.old.as_ref().unwrap_or(&unset),
+            self.new.as_ref().unwrap_or(&unset)
+        )
+    }
+}
+
+/// Outcome of a reload
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadReport {
+    /// Changes now in effect
+    pub applied: Vec<ConfigChange>,
+    /// Changes that only take effect once the node restarts
+    pub requires_restart: Vec<ConfigChange>,
+}
+
+impl ReloadReport {
+    /// Sort every difference between `running` and `reloaded` into applied or restart-only
+    pub fn diff(running: &NodeConfig, reloaded: &NodeConfig) -> Result<Self, ConfigError> {
+        let old = flatten(running)?;
+        let new = flatten(reloaded)?;
+        let mut settings: Vec<&String> = old.keys().chain(new.keys()).collect();
+        settings.sort();
+        settings.dedup();
+
+        let mut report = Self::default();
+        for setting in settings {
+            let change = ConfigChange {
+                setting: setting.clone(),
+                old: old.get(setting).cloned(),
+                new: new.get(setting).cloned(),
+            };
+            if change.old == change.new {
+                continue;
+            }
+            let change = if SECRET_SETTINGS.contains(&setting.as_str()) {
+                ConfigChange {
+                    old: change.old.map(|_| REDACTED.to_string()),
+                    new: change.new.map(|_| REDACTED.to_string()),
+                    ..change
+                }
+            } else {
+                change
+            };
+            // A nested setting is only live when its section exists both before and after,
+            // since adding or removing a section such as `[fuego]` starts or stops a subsystem
+            let listed = RELOADABLE
+                .iter()
+                .any(|reloadable| setting == reloadable || setting.starts_with(&format!("{}.", reloadable)));
+            let section_kept = !setting.contains('.') || (change.old.is_some() && change.new.is_some());
+            if listed && section_kept {
+                report.applied.push(change);
+            } else {
+                report.requires_restart.push(change);
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.requires_restart.is_empty()
+    }
+}
+
+impl fmt::Display for ReloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Config reloaded, no settings changed");
+        }
+        write!(f, "Config reloaded")?;
+        for change in &self.applied {
+            write!(f, "\n  applied: {}", change)?;
+        }
+        for change in &self.requires_restart {
+            write!(f, "\n  needs restart: {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten a config into `path -> value`, one entry per leaf setting
+fn flatten(config: &NodeConfig) -> Result<BTreeMap<String, String>, ConfigError> {
+    fn walk(prefix: &str, table: &Table, settings: &mut BTreeMap<String, String>) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+            match value {
+                Value::Table(table) => walk(&path, table, settings),
+                value => {
+                    settings.insert(path, value.to_string());
+                }
+            }
+        }
+    }
+    let table = Table::try_from(config).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+    let mut settings = BTreeMap::new();
+    walk("", &table, &mut settings);
+    Ok(settings)
+}
+
+pub(crate) fn parse_log_level(level: &str) -> Result<LevelFilter, ConfigError> {
+    LevelFilter::from_str(level).map_err(|_| {
+        ConfigError::ValidationError(format!(
+            "log_level \"{}\" is not one of off, error, warn, info, debug or trace",
+            level
+        ))
+    })
+}
+
+/// Fee rule for new pool transactions under `config`'s limits
+pub(crate) fn fee_algorithm(config: &NodeConfig) -> Box<SimpleFeeAlgorithm> {
+    Box::new(SimpleFeeAlgorithm::with_limits(1, config.min_fee, config.max_fee.unwrap_or(u64::MAX)))
+}
+
+/// Changes the level of the subscriber installed by `init_logging`
+#[derive(Clone)]
+pub struct LogLevelHandle(reload::Handle<LevelFilter, Registry>);
+
+impl LogLevelHandle {
+    pub fn set(&self, level: &str) -> Result<(), ConfigError> {
+        let level = parse_log_level(level)?;
+        self.0.modify(|filter| *filter = level).map_err(|e| ConfigError::ValidationError(e.to_string()))
+    }
+}
+
+/// Install the global tracing subscriber at `level`, exporting spans as `logging` sets out.
+/// Returns a handle to change the level later and the span exporter, if there is one.
+pub fn init_logging(level: &str, logging: &LoggingConfig) -> Result<(LogLevelHandle, Option<Telemetry>), ConfigError> {
+    let (filter, handle) = reload::Layer::new(parse_log_level(level)?);
+    let (otlp, telemetry) = otlp_layer(logging)?.unzip();
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(log_fmt::layer())
+        .with(otlp)
+        .try_init()
+        .map_err(|e| ConfigError::ValidationError(format!("Failed to install logger: {}", e)))?;
+    Ok((LogLevelHandle(handle), telemetry))
+}
+
+/// Applies reloaded settings to the running node's subsystems
+pub struct ConfigReloader {
+    running: RwLock<NodeConfig>,
+    tx_pool: Arc<RwLock<TxPool>>,
+    fuego_daemon: Option<Arc<RwLock<FuegoDaemon>>>,
+    loader: OnceLock<ConfigLoader>,
+    log_level: OnceLock<LogLevelHandle>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config: NodeConfig,
+        tx_pool: Arc<RwLock<TxPool>>,
+        fuego_daemon: Option<Arc<RwLock<FuegoDaemon>>>,
+    ) -> Self {
+        Self {
+            running: RwLock::new(config),
+            tx_pool,
+            fuego_daemon,
+            loader: OnceLock::new(),
+            log_level: OnceLock::new(),
+        }
+    }
+
+    /// Set where `reload` reads the new config from; only the first loader is kept
+    pub fn set_loader(&self, loader: ConfigLoader) {
+        if self.loader.set(loader).is_err() {
+            println!("Config loader already set, keeping the first one");
+        }
+    }
+
+    /// Let reloads change the level of the logger installed by `init_logging`
+    pub fn attach_log_level(&self, handle: LogLevelHandle) {
+        if self.log_level.set(handle).is_err() {
+            println!("Log level handle already attached, keeping the first one");
+        }
+    }
+
+    /// The running config, including settings changed by reloads
+    pub async fn config(&self) -> NodeConfig {
+        self.running.read().await.clone()
+    }
+
+    /// Load the config again and apply it
+    pub async fn reload(&self) -> Result<ReloadReport, ConfigError> {
+        let loader = self
+            .loader
+            .get()
+            .ok_or_else(|| ConfigError::ReadError("no config source to reload from".to_string()))?;
+        self.apply(loader()?).await
+    }
+
+    /// Apply the reloadable settings of `config`, which must be valid as a whole
+    pub async fn apply(&self, config: NodeConfig) -> Result<ReloadReport, ConfigError> {
+        config.validate()?;
+        let mut running = self.running.write().await;
+        let report = ReloadReport::diff(&running, &config)?;
+        if report.applied.is_empty() {
+            return Ok(report);
+        }
+
+        if let Some(handle) = self.log_level.get() {
+            handle.set(&config.log_level)?;
+        }
+        {
+            let mut tx_pool = self.tx_pool.write().await;
+            tx_pool.set_fee_algorithm(fee_algorithm(&config));
+            tx_pool.set_limits(config.tx_pool_limits.clone());
+        }
+        running.log_level = config.log_level;
+        running.min_fee = config.min_fee;
+        running.max_fee = config.max_fee;
+        running.tx_pool_limits = config.tx_pool_limits;
+        running.max_peers = config.max_peers;
+        if let (Some(fuego), Some(reloaded)) = (running.fuego.as_mut(), config.fuego) {
+            if let Some(daemon) = &self.fuego_daemon {
+                daemon.read().await.set_tuning(reloaded.threads, reloaded.poll_interval).await;
+            }
+            fuego.threads = reloaded.threads;
+            fuego.poll_interval = reloaded.poll_interval;
+        }
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl rpc::ConfigReload for ConfigReloader {
+    async fn reload_config(&self) -> Result<serde_json::Value, String> {
+        let report = self.reload().await.map_err(|e| e.to_string())?;
+        println!("{}", report);
+        serde_json::to_value(report).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fuego_integration::FuegoDaemonConfig;
+    use std::time::Duration;
+    use txpool::priority::SimplePriorityCalculator;
+
+    #[tokio::test]
+    async fn test_reload_applies_live_settings_and_reports_the_rest() {
+        let config = NodeConfig {
+            role: crate::NodeRole::Sequencer,
+            fuego: Some(FuegoDaemonConfig { wallet_address: "fire1".to_string(), ..Default::default() }),
+            ..Default::default()
+        };
+        let tx_pool = Arc::new(RwLock::new(TxPool::new(
+            fee_algorithm(&config),
+            Box::new(SimplePriorityCalculator::new()),
+            10,
+        )));
+        let daemon = Arc::new(RwLock::new(FuegoDaemon::new(config.fuego.clone().unwrap()).unwrap()));
+        let reloader = ConfigReloader::new(config.clone(), tx_pool.clone(), Some(daemon.clone()));
+
+        let mut reloaded = config.clone();
+        reloaded.min_fee = 10;
+        reloaded.max_fee = Some(500);
+        reloaded.max_peers = 80;
+        reloaded.p2p_port = 30400;
+        let fuego = reloaded.fuego.as_mut().unwrap();
+        fuego.threads = 4;
+        fuego.poll_interval = Duration::from_secs(2);
+        fuego.wallet_address = "fire2".to_string();
+
+        let report = reloader.apply(reloaded).await.unwrap();
+        let applied: Vec<&str> = report.applied.iter().map(|change| change.setting.as_str()).collect();
+        assert_eq!(applied, ["fuego.poll_interval.secs", "fuego.threads", "max_fee", "max_peers", "min_fee"]);
+        let restart: Vec<&str> = report.requires_restart.iter().map(|change| change.setting.as_str()).collect();
+        assert_eq!(restart, ["fuego.wallet_address", "p2p_port"]);
+        assert_eq!(report.applied[2].old, None);
+        assert_eq!(report.applied[2].new.as_deref(), Some("500"));
+
+        assert_eq!(tx_pool.read().await.fee_limits(), (10, 500));
+        let mining = daemon.read().await.config().await;
+        assert_eq!((mining.threads, mining.poll_interval), (4, Duration::from_secs(2)));
+        let running = reloader.config().await;
+        assert_eq!((running.max_peers, running.p2p_port), (80, 30303));
+        assert_eq!(running.fuego.unwrap().wallet_address, "fire1");
+
+        // An invalid reload changes nothing
+        let invalid = NodeConfig { min_fee: 900, max_fee: Some(100), ..reloader.config().await };
+        assert!(matches!(reloader.apply(invalid).await, Err(ConfigError::ValidationError(_))));
+        assert_eq!(tx_pool.read().await.fee_limits(), (10, 500));
+        assert!(reloader.reload().await.is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! What each kind of node runs, and the lease that keeps block production with one sequencer.
+//!
+//! A sequencer mines blocks from the pool, but only while it holds the production lease.
+//! It renews the lease every block time. A standby sequencer sharing the lease record
+//! takes over once the holder lets it lapse, and each new holder starts a new term.
+//! Sequencers tell each other apart by the node id each keeps in its data directory.
+
+use anyhow::Result;
+use consensus::finality::Attestation;
+use consensus::Consensus;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, RwLock};
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::sleep_unless_shutdown;
+
+/// Lease record a sequencer keeps in its data directory unless `lease_file` is set
+pub const LEASE_FILE: &str = "production_lease.json";
+
+/// Random id identifying this node's data directory, kept across restarts
+pub const NODE_ID_FILE: &str = "node.id";
+
+/// A lock on the lease record older than this was left by a crashed writer
+const STALE_LOCK: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// What a node does with the chain it follows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeRole {
+    /// Builds blocks while it holds the production lease, and settles them on L1
+    Sequencer,
+    /// Attests to the blocks sequencers build, without building any
+    Validator,
+    /// Executes every block and serves RPC
+    #[default]
+    Full,
+    /// Follows the chain without keeping a pool or settling on L1
+    Light,
+    /// Executes blocks without mining and checks the batches committed to L1 against them
+    Watchtower,
+}
+
+impl NodeRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeRole::Sequencer => "sequencer",
+            NodeRole::Validator => "validator",
+            NodeRole::Full => "full",
+            NodeRole::Light => "light",
+            NodeRole::Watchtower => "watchtower",
+        }
+    }
+
+    /// Runs the block production pipeline, including Fuego mining
+    pub fn produces_blocks(&self) -> bool {
+        matches!(self, NodeRole::Sequencer)
+    }
+
+    /// Needs a validator key to sign proposals or attestations
+    pub fn signs(&self) -> bool {
+        matches!(self, NodeRole::Sequencer | NodeRole::Validator)
+    }
+
+    /// Keeps a transaction pool of its own
+    pub fn keeps_pool(&self) -> bool {
+        !matches!(self, NodeRole::Light)
+    }
+}
+
+impl std::str::FromStr for NodeRole {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "sequencer" => Ok(NodeRole::Sequencer),
+            "validator" => Ok(NodeRole::Validator),
+            "full" => Ok(NodeRole::Full),
+            "light" => Ok(NodeRole::Light),
+            "watchtower" => Ok(NodeRole::Watchtower),
+            _ => Err(format!("Unknown role {}", name)),
+        }
+    }
+}
+
+/// Sequencer configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SequencerConfig {
+    /// Seconds a lease stays valid without being renewed; longer than the block time
+    pub lease_duration_secs: u64,
+    /// Lease record shared with standby sequencers, e.g. on a volume they all mount;
+    /// `production_lease.json` in the data directory when unset
+    pub lease_file: Option<String>,
+}
+
+impl Default for SequencerConfig {
+    fn default() -> Self {
+        Self {
+            lease_duration_secs: 30,
+            lease_file: None,
+        }
+    }
+}
+
+impl SequencerConfig {
+    /// Where the lease record of a node with `data_dir` is kept
+    pub fn lease_path(&self, data_dir: &Path) -> PathBuf {
+        match &self.lease_file {
+            Some(file) => PathBuf::from(file),
+            None => data_dir.join(LEASE_FILE),
+        }
+    }
+}
+
+/// The right to build blocks, held by one sequencer at a time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProductionLease {
+    pub holder: String,
+    /// Incremented whenever the lease changes hands
+    pub term: u64,
+    /// Unix time the lease lapses unless renewed
+    pub expires_at: u64,
+}
+
+/// Where the sequencers taking turns at block production keep the lease record
+pub trait LeaseStore: Send + Sync {
+    /// The lease as last written, whether or not it is still valid
+    fn load(&self) -> Result<Option<ProductionLease>>;
+
+    /// Write `lease` if the record still holds `expected`, returning false if another
+    /// sequencer changed it first
+    fn replace(&self, expected: Option<&ProductionLease>, lease: &ProductionLease) -> Result<bool>;
+}
+
+/// Lease record in a JSON file every sequencer can reach. Writers take turns through a
+/// lock file created next to it, so the file may sit on a shared volume.
+pub struct FileLeaseStore {
+    path: PathBuf,
+}
+
+impl FileLeaseStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.path.with_extension("lock")
+    }
+
+    /// Create the lock file, clearing one left behind by a crashed writer. Returns false
+    /// while another writer holds it.
+    fn lock(&self) -> Result<bool> {
+        let lock = self.lock_path();
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&lock) {
+                Ok(_) => return Ok(true),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let age = std::fs::metadata(&lock)?.modified()?.elapsed().unwrap_or_default();
+                    if age < STALE_LOCK {
+                        return Ok(false);
+                    }
+                    let _ = std::fs::remove_file(&lock);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl LeaseStore for FileLeaseStore {
+    fn load(&self) -> Result<Option<ProductionLease>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn replace(&self, expected: Option<&ProductionLease>, lease: &ProductionLease) -> Result<bool> {
+        if !self.lock()? {
+            return Ok(false);
+        }
+        let written = (|| {
+            if self.load()?.as_ref() != expected {
+                return Ok(false);
+            }
+            // Readers see the old record or the new one, never part of it
+            let staged = self.path.with_extension("tmp");
+            let mut file = std::fs::File::create(&staged)?;
+            file.write_all(&serde_json::to_vec(lease)?)?;
+            file.sync_all()?;
+            std::fs::rename(&staged, &self.path)?;
+            Ok(true)
+        })();
+        let _ = std::fs::remove_file(self.lock_path());
+        written
+    }
+}
+
+/// Take or renew the lease for `holder` until `now + duration`. Returns `None` while
+/// another holder's lease is still valid, or when another sequencer wrote the record first.
+pub fn acquire_lease(
+    store: &dyn LeaseStore,
+    holder: &str,
+    now: u64,
+    duration: u64,
+) -> Result<Option<ProductionLease>> {
+    let current = store.load()?;
+    let lease = match &current {
+        Some(lease) if lease.holder == holder => ProductionLease { expires_at: now + duration, ..lease.clone() },
+        Some(lease) if lease.expires_at > now => return Ok(None),
+        previous => ProductionLease {
+            holder: holder.to_string(),
+            term: previous.as_ref().map_or(1, |lease| lease.term + 1),
+            expires_at: now + duration,
+        },
+    };
+    Ok(store.replace(current.as_ref(), &lease)?.then_some(lease))
+}
+
+/// Let the lease lapse at `now` if `holder` has it, so a standby can take over at once
+pub fn release_lease(store: &dyn LeaseStore, holder: &str, now: u64) -> Result<bool> {
+    match store.load()? {
+        Some(lease) if lease.holder == holder => {
+            let released = ProductionLease { expires_at: now, ..lease.clone() };
+            store.replace(Some(&lease), &released)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Id of the node keeping its data in `data_dir`, created on first use
+pub fn node_id(data_dir: &Path) -> Result<String> {
+    let path = data_dir.join(NODE_ID_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(id) => Ok(id.trim().to_string()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let id = hex::encode(rand::random::<[u8; 16]>());
+            std::fs::write(&path, &id)?;
+            Ok(id)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Seconds since the Unix epoch, which lease expiries are given in
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Keep the production lease in `leases` for `holder`, renewing it every `interval`, and
+/// publish on `held` whether this node has it. When the task ends `held` is cleared before
+/// the lease is released on shutdown, so mining stops before a standby can take over.
+pub async fn run_production_lease(
+    config: SequencerConfig,
+    holder: String,
+    leases: Arc<dyn LeaseStore>,
+    held: Arc<watch::Sender<bool>>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let mut held_term = None;
+    loop {
+        let lease = match acquire_lease(&*leases, &holder, unix_now(), config.lease_duration_secs) {
+            Ok(lease) => lease,
+            Err(e) => {
+                held.send_replace(false);
+                return Err(e);
+            }
+        };
+        match (&lease, held_term) {
+            (Some(lease), None) => println!("Took the block production lease, term {}", lease.term),
+            (None, Some(term)) => println!("Lost the block production lease after term {}", term),
+            _ => {}
+        }
+        held_term = lease.map(|lease| lease.term);
+        held.send_replace(held_term.is_some());
+        if !sleep_unless_shutdown(&shutdown, interval).await {
+            break;
+        }
+    }
+    held.send_replace(false);
+    if held_term.is_some() {
+        release_lease(&*leases, &holder, unix_now())?;
+        println!("Released the block production lease");
+    }
+    Ok(())
+}
+
+/// Attest to each newly confirmed head with this node's validator key until shutdown, passing
+/// the attestations consensus accepts on to `publish` for peers
+pub async fn run_attestation(
+    consensus: Arc<RwLock<Consensus>>,
+    publish: mpsc::Sender<Attestation>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let mut attested = None;
+    while sleep_unless_shutdown(&shutdown, interval).await {
+        let consensus = consensus.read().await;
+        let Some(head) = consensus.head_height().await else { continue };
+        let depth = consensus.finality().read().await.confirmation_depth();
+        let Some(height) = head.checked_sub(depth).filter(|height| attested < Some(*height)) else {
+            continue;
+        };
+        let result = match consensus.create_attestation(height).await {
+            Ok(attestation) => consensus.submit_attestation(&attestation).await.map(|_| {
+                let _ = publish.try_send(attestation);
+            }),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => attested = Some(height),
+            Err(e) => eprintln!("Attesting to block {} failed: {}", height, e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lease_is_renewed_by_its_holder_and_taken_over_once_it_lapses() {
+        let temp_dir = TempDir::new().unwrap();
+        // Two sequencers reaching one lease record
+        let a = FileLeaseStore::new(temp_dir.path().join(LEASE_FILE));
+        let b = FileLeaseStore::new(temp_dir.path().join(LEASE_FILE));
+
+        let lease = acquire_lease(&a, "a", 100, 30).unwrap().unwrap();
+        assert_eq!((lease.term, lease.expires_at), (1, 130));
+        assert_eq!(acquire_lease(&b, "b", 120, 30).unwrap(), None);
+        let lease = acquire_lease(&a, "a", 125, 30).unwrap().unwrap();
+        assert_eq!((lease.term, lease.expires_at), (1, 155));
+
+        // A lapsed lease changes hands, starting a new term
+        let lease = acquire_lease(&b, "b", 155, 30).unwrap().unwrap();
+        assert_eq!((lease.holder.as_str(), lease.term), ("b", 2));
+        assert_eq!(acquire_lease(&a, "a", 160, 30).unwrap(), None);
+
+        // Releasing hands it over without waiting
+        assert!(!release_lease(&a, "a", 170).unwrap());
+        assert!(release_lease(&b, "b", 170).unwrap());
+        assert_eq!(acquire_lease(&a, "a", 170, 30).unwrap().unwrap().term, 3);
+        assert_eq!(b.load().unwrap().unwrap().holder, "a");
+        assert_eq!("validator".parse::<NodeRole>().unwrap(), NodeRole::Validator);
+        assert!("miner".parse::<NodeRole>().is_err());
+    }
+
+    #[test]
+    fn test_lease_writers_take_turns_and_node_ids_are_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FileLeaseStore::new(temp_dir.path().join(LEASE_FILE));
+
+        // While another writer holds the lock the record is left alone
+        std::fs::write(store.lock_path(), b"").unwrap();
+        assert_eq!(acquire_lease(&store, "a", 100, 30).unwrap(), None);
+        std::fs::remove_file(store.lock_path()).unwrap();
+        let lease = acquire_lease(&store, "a", 100, 30).unwrap().unwrap();
+
+        // A write is refused once the record changed since it was read
+        let taken = ProductionLease { holder: "b".to_string(), term: 2, expires_at: 200 };
+        assert!(!store.replace(None, &taken).unwrap());
+        assert!(store.replace(Some(&lease), &taken).unwrap());
+        assert!(!store.lock_path().exists());
+
+        let id = node_id(temp_dir.path()).unwrap();
+        assert_eq!(id.len(), 32);
+        assert_eq!(node_id(temp_dir.path()).unwrap(), id);
+    }
+}
@@ -0,0 +1,351 @@
+//! Supervision of the node's long-running tasks. Each task is started from a factory so it
+//! can be started again: when one returns an error or panics it is restarted according to
+//! its restart policy, after a backoff that doubles up to a ceiling, until its restart
+//! budget runs out. The state, restart count and last error of every task are kept for the
+//! readiness probe and metrics.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::FutureExt;
+use rpc::{CheckStatus, HealthCheck, TaskHealth};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// When a task that has exited is started again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    /// Run once; a failure is final
+    Never,
+    /// Restart after an error or a panic
+    OnFailure,
+    /// Restart after any exit until shutdown
+    Always,
+}
+
+/// Restart backoff and limits shared by every supervised task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Give up on a task after this many restarts; `None` restarts forever
+    pub max_restarts: Option<u32>,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 60_000,
+            max_restarts: Some(10),
+        }
+    }
+}
+
+/// Lifecycle state of a supervised task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Waiting out the backoff before the next start
+    Restarting,
+    /// Exited without a failure, or stopped by shutdown
+    Stopped,
+    /// Failed with no restart left
+    Failed,
+}
+
+/// Supervised task status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskStatus {
+    pub name: String,
+    pub policy: RestartPolicy,
+    pub state: TaskState,
+    pub restarts: u32,
+    /// Errors and panics over the task's lifetime
+    pub crashes: u32,
+    pub last_error: Option<String>,
+}
+
+/// Shared view of every supervised task's status
+#[derive(Clone, Default)]
+pub struct SupervisedTasks {
+    statuses: Arc<RwLock<BTreeMap<String, TaskStatus>>>,
+}
+
+impl SupervisedTasks {
+    /// Status of every task, by name
+    pub async fn snapshot(&self) -> Vec<TaskStatus> {
+        self.statuses.read().await.values().cloned().collect()
+    }
+
+    async fn update(&self, name: &str, update: impl FnOnce(&mut TaskStatus)) {
+        if let Some(status) = self.statuses.write().await.get_mut(name) {
+            update(status);
+        }
+    }
+}
+
+#[async_trait]
+impl TaskHealth for SupervisedTasks {
+    async fn task_checks(&self) -> Vec<HealthCheck> {
+        self.snapshot()
+            .await
+            .into_iter()
+            .map(|task| {
+                let mut detail = format!("{:?}, {} restarts", task.state, task.restarts).to_lowercase();
+                if let Some(error) = &task.last_error {
+                    detail = format!("{}, last error: {}", detail, error);
+                }
+                HealthCheck {
+                    name: format!("task_{}", task.name),
+                    status: if task.state == TaskState::Failed { CheckStatus::Fail } else { CheckStatus::Pass },
+                    detail,
+                    duration_ms: 0,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Owns the node's long-running tasks and restarts them when they fail
+pub struct TaskSupervisor {
+    config: SupervisorConfig,
+    shutdown: CancellationToken,
+    tasks: SupervisedTasks,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl TaskSupervisor {
+    pub fn new(config: SupervisorConfig) -> Self {
+        Self {
+            config,
+            shutdown: CancellationToken::new(),
+            tasks: SupervisedTasks::default(),
+            handles: Vec::new(),
+        }
+    }
+
+    pub fn tasks(&self) -> SupervisedTasks {
+        self.tasks.clone()
+    }
+
+    /// Start over with `shutdown` ending every task spawned from now on
+    pub async fn reset(&mut self, shutdown: CancellationToken) {
+        self.shutdown = shutdown;
+        self.tasks.statuses.write().await.clear();
+    }
+
+    /// Run the futures `factory` makes under `name`, restarting them as `policy` allows.
+    /// Tasks watch the shutdown token themselves; no task is restarted once it is cancelled.
+    pub async fn spawn<F, Fut>(&mut self, name: &str, policy: RestartPolicy, factory: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.tasks.statuses.write().await.insert(
+            name.to_string(),
+            TaskStatus {
+                name: name.to_string(),
+                policy,
+                state: TaskState::Running,
+                restarts: 0,
+                crashes: 0,
+                last_error: None,
+            },
+        );
+        let supervision = supervise(
+            name.to_string(),
+            policy,
+            self.config.clone(),
+            self.shutdown.clone(),
+            self.tasks.clone(),
+            factory,
+        );
+        self.handles.push(tokio::spawn(supervision));
+    }
+
+    /// Run `task` once under `name`, for tasks that own what cannot be rebuilt
+    pub async fn spawn_once<Fut>(&mut self, name: &str, task: Fut)
+    where
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let mut task = Some(task);
+        self.spawn(name, RestartPolicy::Never, move || task.take().expect("task started twice")).await;
+    }
+
+    /// Wait for every task to finish, aborting those still running at `deadline`
+    pub async fn stop(&mut self, deadline: Instant) {
+        for mut handle in self.handles.drain(..) {
+            match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Task supervision error: {:?}", e),
+                Err(_) => {
+                    eprintln!("Task did not stop in time, aborting it");
+                    handle.abort();
+                }
+            }
+        }
+        for status in self.tasks.statuses.write().await.values_mut() {
+            if matches!(status.state, TaskState::Running | TaskState::Restarting) {
+                status.state = TaskState::Stopped;
+            }
+        }
+    }
+}
+
+/// Message carried by a panic payload
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().unwrap_or(&"unknown cause").to_string(),
+    }
+}
+
+async fn supervise<F, Fut>(
+    name: String,
+    policy: RestartPolicy,
+    config: SupervisorConfig,
+    shutdown: CancellationToken,
+    tasks: SupervisedTasks,
+    mut factory: F,
+) where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let initial_backoff = Duration::from_millis(config.initial_backoff_ms);
+    let max_backoff = Duration::from_millis(config.max_backoff_ms);
+    let mut backoff = initial_backoff;
+    loop {
+        let started = Instant::now();
+        let failure = match AssertUnwindSafe(factory()).catch_unwind().await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(payload) => Some(format!("panicked: {}", panic_message(payload))),
+        };
+        if let Some(error) = &failure {
+            eprintln!("Task {} failed: {}", name, error);
+        }
+
+        let restart = match policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => failure.is_some(),
+            RestartPolicy::Always => true,
+        };
+        let mut statuses = tasks.statuses.write().await;
+        let Some(status) = statuses.get_mut(&name) else { return };
+        if let Some(error) = failure.clone() {
+            status.crashes += 1;
+            status.last_error = Some(error);
+        }
+        if shutdown.is_cancelled() || !restart {
+            let failed = failure.is_some() && !shutdown.is_cancelled();
+            status.state = if failed { TaskState::Failed } else { TaskState::Stopped };
+            return;
+        }
+        if config.max_restarts.is_some_and(|max| status.restarts >= max) {
+            eprintln!("Task {} exhausted its {} restarts, giving up", name, status.restarts);
+            status.state = TaskState::Failed;
+            return;
+        }
+        status.state = TaskState::Restarting;
+        status.restarts += 1;
+        drop(statuses);
+
+        // A task that ran for a while before exiting is not crash looping
+        if started.elapsed() > max_backoff {
+            backoff = initial_backoff;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = shutdown.cancelled() => {
+                tasks.update(&name, |status| status.state = TaskState::Stopped).await;
+                return;
+            }
+        }
+        backoff = (backoff * 2).min(max_backoff);
+        println!("Restarting task {}", name);
+        tasks.update(&name, |status| status.state = TaskState::Running).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_failed_tasks_restart_until_their_budget_runs_out() {
+        let mut supervisor = TaskSupervisor::new(SupervisorConfig {
+            initial_backoff_ms: 1,
+            max_backoff_ms: 4,
+            max_restarts: Some(3),
+        });
+        let shutdown = CancellationToken::new();
+        supervisor.reset(shutdown.clone()).await;
+
+        // Fails twice, then runs until shutdown
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = attempts.clone();
+        let task_shutdown = shutdown.clone();
+        supervisor
+            .spawn("flaky", RestartPolicy::OnFailure, move || {
+                let attempt = counter.fetch_add(1, Ordering::SeqCst);
+                let shutdown = task_shutdown.clone();
+                async move {
+                    match attempt {
+                        0 => anyhow::bail!("connection refused"),
+                        1 => panic!("poisoned"),
+                        _ => {
+                            shutdown.cancelled().await;
+                            Ok(())
+                        }
+                    }
+                }
+            })
+            .await;
+        supervisor.spawn("broken", RestartPolicy::Always, || async { anyhow::bail!("bad config") }).await;
+        supervisor.spawn("oneshot", RestartPolicy::Never, || async { Ok(()) }).await;
+
+        // Panics can take a while to report, so wait for the tasks to settle
+        let tasks = supervisor.tasks();
+        let settled = |statuses: &[TaskStatus]| {
+            statuses.iter().all(|status| status.state != TaskState::Restarting)
+                && statuses.iter().any(|status| status.name == "broken" && status.state == TaskState::Failed)
+                && statuses.iter().any(|status| status.name == "flaky" && status.restarts == 2)
+        };
+        let mut statuses = tasks.snapshot().await;
+        for _ in 0..500 {
+            if settled(&statuses) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            statuses = tasks.snapshot().await;
+        }
+        let status = |name: &str| statuses.iter().find(|status| status.name == name).unwrap().clone();
+        let flaky = status("flaky");
+        assert_eq!((flaky.state, flaky.restarts, flaky.crashes), (TaskState::Running, 2, 2));
+        assert_eq!(flaky.last_error.as_deref(), Some("panicked: poisoned"));
+        let broken = status("broken");
+        assert_eq!((broken.state, broken.restarts, broken.crashes), (TaskState::Failed, 3, 4));
+        assert_eq!(status("oneshot").state, TaskState::Stopped);
+
+        let checks = tasks.task_checks().await;
+        let failing: Vec<_> = checks.iter().filter(|check| check.status == CheckStatus::Fail).collect();
+        assert_eq!(failing.len(), 1);
+        assert_eq!(failing[0].name, "task_broken");
+
+        shutdown.cancel();
+        supervisor.stop(Instant::now() + Duration::from_secs(1)).await;
+        assert_eq!(tasks.snapshot().await.iter().filter(|s| s.state == TaskState::Stopped).count(), 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,98 @@
+//! Export of the node's tracing spans to an OpenTelemetry collector.
+//!
+//! Transaction admission, block building, proof jobs and L1 submissions each open a span,
+//! and the spans of one block nest under its production span, proof jobs included although
+//! they run on the prover's threads. With an OTLP endpoint configured, sampled traces are
+//! batched and sent over OTLP/HTTP so slow block production can be broken down by stage.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{Sampler, SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::ConfigError;
+
+/// Span export settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`; spans are only
+    /// exported when set
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` the node's spans are exported under
+    pub service_name: String,
+    /// Fraction of traces exported, from 0 to 1; traces continued from a parent follow it
+    pub sample_ratio: f64,
+    /// How long one export may take
+    pub export_timeout_secs: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "c0dl3-node".to_string(),
+            sample_ratio: 1.0,
+            export_timeout_secs: 10,
+        }
+    }
+}
+
+/// Exports spans in the background; `shutdown` sends what is still batched
+pub struct Telemetry(SdkTracerProvider);
+
+impl Telemetry {
+    pub fn shutdown(&self) {
+        if let Err(e) = self.0.shutdown() {
+            eprintln!("Failed to flush exported spans: {}", e);
+        }
+    }
+}
+
+type OtlpLayer<S> = (OpenTelemetryLayer<S, SdkTracer>, Telemetry);
+
+/// Layer exporting spans to `config`'s OTLP endpoint, or `None` when none is set
+pub(crate) fn otlp_layer<S>(config: &LoggingConfig) -> Result<Option<OtlpLayer<S>>, ConfigError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .with_timeout(Duration::from_secs(config.export_timeout_secs))
+        .build()
+        .map_err(|e| ConfigError::ValidationError(format!("logging.otlp_endpoint: {}", e)))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+        .with_resource(Resource::builder().with_service_name(config.service_name.clone()).build())
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("c0dl3-node"));
+    Ok(Some((layer, Telemetry(provider))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn test_spans_are_exported_only_with_an_endpoint() {
+        assert!(otlp_layer::<Registry>(&LoggingConfig::default()).unwrap().is_none());
+
+        let config = LoggingConfig {
+            otlp_endpoint: Some("http://127.0.0.1:4318/v1/traces".to_string()),
+            ..Default::default()
+        };
+        let (_, telemetry) = otlp_layer::<Registry>(&config).unwrap().unwrap();
+        telemetry.shutdown();
+    }
+}
@@ -0,0 +1,242 @@
+//! State transition stage of block validation, run by the block executor. A block is executed
+//! on the state of the block it builds on and nothing is committed. When its parent is on the
+//! executed chain that state is the stored version of the parent's height; on a side branch,
+//! or above the executed tip, it is the version the branch forks from with the writes of the
+//! branch's checked blocks layered on top, oldest first.
+
+use block_sync::{Block, BlockHeader};
+use consensus::engine::header_id;
+use consensus::StateTransition;
+use execution::receipt::get_headers;
+use execution::{BlockExecutor, ExecutionError, ParentState, PendingState, StateWrites};
+use state_db::account::GENESIS_VERSION;
+use state_db::error::StateDBError;
+use state_db::RocksStateDB;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// Heights below the executed tip from which checked blocks are still remembered
+const PENDING_DEPTH: u64 = 128;
+
+/// A checked block not executed on the stored chain, with what it writes
+struct PendingBlock {
+    height: u64,
+    parent: [u8; 32],
+    writes: Arc<StateWrites>,
+}
+
+/// State before genesis of a chain whose genesis allocates nothing, so commits no version
+struct EmptyState;
+
+impl ParentState for EmptyState {
+    fn get(&self, _key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
+        Ok(None)
+    }
+}
+
+/// Checks blocks by executing them on their parent state. It reads the state database from
+/// within the consensus engine, so it needs the multi-threaded runtime.
+pub struct ExecutorTransition {
+    executor: BlockExecutor,
+    state: Arc<RwLock<RocksStateDB>>,
+    genesis_id: [u8; 32],
+    pending: Mutex<HashMap<[u8; 32], PendingBlock>>,
+}
+
+impl ExecutorTransition {
+    pub fn new(executor: BlockExecutor, state: Arc<RwLock<RocksStateDB>>, genesis: &BlockHeader) -> Self {
+        Self {
+            executor,
+            state,
+            genesis_id: header_id(genesis),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Id of the executed block at `height`, if one is stored
+    fn executed_id(&self, state: &RocksStateDB, height: u64) -> Result<Option<[u8; 32]>, String> {
+        if height == GENESIS_VERSION {
+            return Ok(Some(self.genesis_id));
+        }
+        let headers = get_headers(state, height, 1).map_err(|e| e.to_string())?;
+        Ok(headers.first().map(header_id))
+    }
+
+    /// Follow `block`'s ancestry down to the executed chain, returning the height it joins at
+    /// and the writes of the checked blocks above it, oldest first
+    fn branch(
+        &self,
+        state: &RocksStateDB,
+        pending: &HashMap<[u8; 32], PendingBlock>,
+        block: &Block,
+    ) -> Result<(u64, Vec<Arc<StateWrites>>), String> {
+        let mut layers = Vec::new();
+        let (mut height, mut parent) = (block.header.height - 1, block.header.prev_hash);
+        while self.executed_id(state, height)? != Some(parent) {
+            let Some(ancestor) = pending.get(&parent).filter(|ancestor| ancestor.height == height) else {
+                return Err(format!("Parent {} at height {} is not known", hex::encode(parent), height));
+            };
+            layers.push(ancestor.writes.clone());
+            (height, parent) = (height - 1, ancestor.parent);
+        }
+        layers.reverse();
+        Ok((height, layers))
+    }
+
+    /// Run `run` on the state `block` builds on, along with the executed tip
+    fn on_parent<T>(
+        &self,
+        block: &Block,
+        run: impl FnOnce(&dyn ParentState, u64) -> Result<T, ExecutionError>,
+    ) -> Result<T, String> {
+        if block.header.height == GENESIS_VERSION {
+            return Err("Genesis state comes from the chain spec".to_string());
+        }
+        let state = tokio::task::block_in_place(|| self.state.blocking_read());
+        let (fork, layers) = {
+            let pending = self.pending.lock().expect("pending blocks lock");
+            self.branch(&state, &pending, block)?
+        };
+        let tip = state.latest_version().unwrap_or(GENESIS_VERSION);
+        let layered = |base: &dyn ParentState| {
+            let mut parent = PendingState::new(base);
+            for writes in &layers {
+                parent.apply(writes);
+            }
+            run(&parent, tip).map_err(|e| e.to_string())
+        };
+        if fork == GENESIS_VERSION && state.root_at(fork).map_err(|e| e.to_string())?.is_none() {
+            layered(&EmptyState)
+        } else {
+            layered(&state.state_at(fork).map_err(|e| e.to_string())?)
+        }
+    }
+}
+
+impl StateTransition for ExecutorTransition {
+    fn check_block(&self, block: &Block, fee_recipient: &[u8]) -> Result<(), String> {
+        let (writes, tip) = self.on_parent(block, |parent, tip| {
+            Ok((self.executor.check_block(parent, block, fee_recipient)?, tip))
+        })?;
+        let mut pending = self.pending.lock().expect("pending blocks lock");
+        pending.retain(|_, checked| checked.height + PENDING_DEPTH > tip);
+        pending.insert(
+            header_id(&block.header),
+            PendingBlock {
+                height: block.header.height,
+                parent: block.header.prev_hash,
+                writes: Arc::new(writes),
+            },
+        );
+        Ok(())
+    }
+
+    fn nullifier_root(&self, block: &Block) -> Result<[u8; 32], String> {
+        self.on_parent(block, |parent, _| self.executor.nullifier_root(parent, block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChainSpec;
+    use block_sync::{BlockProof, ProofType, Transaction, TxOutput};
+    use execution::Network;
+    use state_db::account::GenesisAccount;
+    use state_db::merkle::EMPTY_HASH;
+    use state_db::nullifier::accumulate_nullifiers;
+    use tempfile::TempDir;
+
+    fn transfer(nonce: u64, amount: u64) -> Transaction {
+        Transaction {
+            hash: [0u8; 32],
+            sender: vec![0xa1],
+            nonce,
+            gas_limit: 100_000,
+            data: Vec::new(),
+            inputs: vec![],
+            outputs: vec![TxOutput {
+                amount,
+                address: vec![0xb2],
+                commitment: [0u8; 32],
+                ephemeral_key: None,
+            }],
+            fee: 100_000,
+            timestamp: 1_000,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 3,
+            fee_payer: None,
+            asset: None,
+        }
+        .with_id()
+    }
+
+    fn child(parent: &BlockHeader, transactions: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                height: parent.height + 1,
+                prev_hash: header_id(parent),
+                merkle_root: consensus::merkle_root(&transactions),
+                timestamp: parent.timestamp + 2,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root: [0u8; 32],
+            },
+            transactions,
+            proof: BlockProof { proof_type: ProofType::PoW, proof_data: Vec::new() },
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocks_are_checked_on_their_parent_state_side_branches_included() {
+        let mut chain = ChainSpec::for_network(Network::Devnet);
+        chain.genesis.alloc.insert("a1".to_string(), GenesisAccount { balance: 1_000_000 });
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+        chain.init_data_dir(temp_dir.path(), &mut state).unwrap();
+        let state = Arc::new(RwLock::new(state));
+        let genesis = chain.genesis_header();
+        let transition = ExecutorTransition::new(
+            BlockExecutor::new(chain.execution_config()).unwrap(),
+            state.clone(),
+            &genesis,
+        );
+        let fee_recipient = [0xfe; 32];
+
+        // Blocks above the executed tip are checked on the writes of the blocks below them
+        let first = child(&genesis, vec![transfer(0, 100)]);
+        transition.check_block(&first, &fee_recipient).unwrap();
+        let second = child(&first.header, vec![transfer(1, 100)]);
+        transition.check_block(&second, &fee_recipient).unwrap();
+        assert!(transition.check_block(&child(&first.header, vec![transfer(0, 100)]), &fee_recipient).is_err());
+
+        // A side branch sees its own spending, not the main chain's
+        let fork = child(&genesis, vec![transfer(0, 800_000)]);
+        transition.check_block(&fork, &fee_recipient).unwrap();
+        let overdraw = |parent: &Block| child(&parent.header, vec![transfer(1, 100_000)]);
+        assert!(transition.check_block(&overdraw(&fork), &fee_recipient).is_err());
+        transition.check_block(&overdraw(&first), &fee_recipient).unwrap();
+
+        // Headers commit to the nullifier root their block's spending leads to on its branch
+        let spending = Transaction { nullifiers: vec![[0x4e; 32]], ..transfer(1, 1) }.with_id();
+        let spent = accumulate_nullifiers(&EMPTY_HASH, &[[0x4e; 32]]);
+        assert_eq!(transition.nullifier_root(&child(&fork.header, vec![spending])).unwrap(), spent);
+        assert_eq!(transition.nullifier_root(&second).unwrap(), EMPTY_HASH);
+
+        // Once the main chain is executed its blocks are built on from the stored state
+        let mut executor = BlockExecutor::new(chain.execution_config()).unwrap();
+        executor.process_block(&mut *state.write().await, &first, &fee_recipient).unwrap();
+        transition.check_block(&second, &fee_recipient).unwrap();
+        assert!(transition.check_block(&overdraw(&fork), &fee_recipient).is_err());
+        assert_eq!(state.read().await.latest_version(), Some(1));
+
+        // Nothing is built on unknown parents or genesis, nor without a fee recipient
+        let orphan = child(&child(&genesis, vec![transfer(0, 1)]).header, vec![transfer(1, 1)]);
+        let error = transition.check_block(&orphan, &fee_recipient).unwrap_err();
+        assert!(error.contains("is not known"), "{}", error);
+        assert!(transition.check_block(&chain.genesis_block(), &fee_recipient).is_err());
+        assert!(transition.check_block(&second, &[]).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pow::{CnParams, CryptoNight, PowHasher};
+
+fn bench_cryptonight(c: &mut Criterion) {
+    let blob = [0x5au8; 76];
+
+    let upx2 = CryptoNight::upx2();
+    c.bench_function("cn/upx2", |b| b.iter(|| upx2.hash(black_box(&blob))));
+
+    let mut scratchpad = vec![0u8; CnParams::CN_UPX2.memory];
+    c.bench_function("cn/upx2 reused scratchpad", |b| {
+        b.iter(|| upx2.digest_with_scratchpad(black_box(&blob), &mut scratchpad))
+    });
+
+    let mut group = c.benchmark_group("cn/2");
+    group.sample_size(10);
+    let cn2 = CryptoNight::new(CnParams::CN_2);
+    group.bench_function("cn/2", |b| b.iter(|| cn2.hash(black_box(&blob))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_cryptonight);
+criterion_main!(benches);
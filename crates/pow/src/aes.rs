@@ -0,0 +1,128 @@
+//! Software AES primitives as used by CryptoNight: AES-256 key expansion truncated to
+//! ten round keys, and single unkeyed-final rounds (SubBytes, ShiftRows, MixColumns, AddRoundKey).
+
+/// GF(2^8) multiplication modulo the AES polynomial x^8 + x^4 + x^3 + x + 1
+pub(crate) const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            p ^= a;
+        }
+        let hi = a & 0x80;
+        a <<= 1;
+        if hi != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    p
+}
+
+const fn build_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut x = 0usize;
+    while x < 256 {
+        // Multiplicative inverse by exhaustive search (0 maps to 0)
+        let mut inv = 0u8;
+        if x != 0 {
+            let mut y = 1usize;
+            while y < 256 {
+                if gf_mul(x as u8, y as u8) == 1 {
+                    inv = y as u8;
+                    break;
+                }
+                y += 1;
+            }
+        }
+        sbox[x] = inv
+            ^ inv.rotate_left(1)
+            ^ inv.rotate_left(2)
+            ^ inv.rotate_left(3)
+            ^ inv.rotate_left(4)
+            ^ 0x63;
+        x += 1;
+    }
+    sbox
+}
+
+/// The AES S-box
+pub(crate) static SBOX: [u8; 256] = build_sbox();
+
+/// Expand a 256-bit key into the first ten AES round keys
+pub(crate) fn expand_key(key: &[u8]) -> [[u8; 16]; 10] {
+    const RCON: [u8; 8] = [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40];
+
+    let mut words = [[0u8; 4]; 40];
+    for (i, word) in words.iter_mut().take(8).enumerate() {
+        word.copy_from_slice(&key[i * 4..i * 4 + 4]);
+    }
+    for i in 8..40 {
+        let mut temp = words[i - 1];
+        if i % 8 == 0 {
+            temp = [
+                SBOX[temp[1] as usize] ^ RCON[i / 8],
+                SBOX[temp[2] as usize],
+                SBOX[temp[3] as usize],
+                SBOX[temp[0] as usize],
+            ];
+        } else if i % 8 == 4 {
+            temp = temp.map(|b| SBOX[b as usize]);
+        }
+        for j in 0..4 {
+            words[i][j] = words[i - 8][j] ^ temp[j];
+        }
+    }
+
+    let mut round_keys = [[0u8; 16]; 10];
+    for (r, round_key) in round_keys.iter_mut().enumerate() {
+        for w in 0..4 {
+            round_key[w * 4..w * 4 + 4].copy_from_slice(&words[r * 4 + w]);
+        }
+    }
+    round_keys
+}
+
+/// One full AES encryption round on a column-major block
+#[inline]
+pub(crate) fn round(block: &mut [u8; 16], key: &[u8; 16]) {
+    let mut shifted = [0u8; 16];
+    for c in 0..4 {
+        for r in 0..4 {
+            shifted[c * 4 + r] = SBOX[block[((c + r) % 4) * 4 + r] as usize];
+        }
+    }
+
+    for c in 0..4 {
+        let a = &shifted[c * 4..c * 4 + 4];
+        let (a0, a1, a2, a3) = (a[0], a[1], a[2], a[3]);
+        block[c * 4] = gf_mul(a0, 2) ^ gf_mul(a1, 3) ^ a2 ^ a3 ^ key[c * 4];
+        block[c * 4 + 1] = a0 ^ gf_mul(a1, 2) ^ gf_mul(a2, 3) ^ a3 ^ key[c * 4 + 1];
+        block[c * 4 + 2] = a0 ^ a1 ^ gf_mul(a2, 2) ^ gf_mul(a3, 3) ^ key[c * 4 + 2];
+        block[c * 4 + 3] = gf_mul(a0, 3) ^ a1 ^ a2 ^ gf_mul(a3, 2) ^ key[c * 4 + 3];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbox_known_values() {
+        assert_eq!(SBOX[0x00], 0x63);
+        assert_eq!(SBOX[0x01], 0x7c);
+        assert_eq!(SBOX[0x53], 0xed);
+        assert_eq!(SBOX[0xff], 0x16);
+    }
+
+    #[test]
+    fn test_key_expansion_fips197() {
+        // FIPS-197 appendix A.3 AES-256 key expansion
+        let key = [
+            0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d, 0x77, 0x81,
+            0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3, 0x09, 0x14, 0xdf, 0xf4,
+        ];
+        let round_keys = expand_key(&key);
+        assert_eq!(&round_keys[0][..], &key[..16]);
+        assert_eq!(&round_keys[2][..4], &[0x9b, 0xa3, 0x54, 0x11]);
+    }
+}
@@ -0,0 +1,634 @@
+//! Auxiliary proof-of-work for merge mining C0DL3 on top of Fuego.
+//!
+//! A miner commits the root of the aux chain merkle tree to the parent (Fuego) coinbase
+//! through a CryptoNote merge mining tag. The proof carries the coinbase, the branch from
+//! the coinbase to the parent transaction root, and the branch from the aux block hash to
+//! the committed root. All trees use the CryptoNote `tree_hash` layout over `cn_fast_hash`.
+//!
+//! The aux tree has `2^depth` slots and C0DL3's slot is fixed by its chain id and the nonce
+//! committed in the tag, as in Monero merge mining, so one parent block can commit to at
+//! most one C0DL3 block. The tag is read from the coinbase's parsed `tx_extra` and must
+//! appear there exactly once.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cryptonight::cn_fast_hash;
+use crate::error::PowError;
+use crate::{check_hash, PowHasher};
+
+/// `tx_extra` tag of a CryptoNote merge mining commitment
+pub const TX_EXTRA_MERGE_MINING_TAG: u8 = 0x03;
+
+const TX_EXTRA_TAG_PADDING: u8 = 0x00;
+const TX_EXTRA_TAG_PUBKEY: u8 = 0x01;
+const TX_EXTRA_NONCE: u8 = 0x02;
+const TX_EXTRA_MESSAGE_TAG: u8 = 0x04;
+const TX_EXTRA_TTL: u8 = 0x05;
+
+const TXIN_GEN: u8 = 0xff;
+const TXOUT_TO_KEY: u8 = 0x02;
+const TXOUT_MULTISIG: u8 = 0x03;
+
+/// Name C0DL3's aux chain id is derived from
+const AUX_CHAIN_NAME: &[u8] = b"C0DL3";
+
+/// Maximum supported merkle branch depth
+pub const MAX_BRANCH_DEPTH: usize = 32;
+
+pub type Hash = [u8; 32];
+
+fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(left);
+    buf[32..].copy_from_slice(right);
+    cn_fast_hash(&buf)
+}
+
+/// Largest power of two strictly below `count` (for `count >= 3`)
+fn tree_hash_cnt(count: usize) -> usize {
+    let mut cnt = 1;
+    while cnt * 2 < count {
+        cnt *= 2;
+    }
+    cnt
+}
+
+/// CryptoNote tree hash of `hashes`
+pub fn tree_hash(hashes: &[Hash]) -> Hash {
+    match hashes.len() {
+        0 => [0u8; 32],
+        1 => hashes[0],
+        2 => hash_pair(&hashes[0], &hashes[1]),
+        count => {
+            let mut cnt = tree_hash_cnt(count);
+            let direct = 2 * cnt - count;
+            let mut ints: Vec<Hash> = hashes[..direct].to_vec();
+            for pair in hashes[direct..].chunks_exact(2) {
+                ints.push(hash_pair(&pair[0], &pair[1]));
+            }
+            while cnt > 2 {
+                cnt >>= 1;
+                ints = ints.chunks_exact(2).map(|pair| hash_pair(&pair[0], &pair[1])).collect();
+            }
+            hash_pair(&ints[0], &ints[1])
+        }
+    }
+}
+
+/// Sibling hashes from a leaf up to the root of a CryptoNote tree
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleBranch {
+    /// Siblings ordered from the leaf towards the root
+    pub hashes: Vec<Hash>,
+    /// Bit `i` is set when the running hash is the right child at step `i`
+    pub path: u32,
+}
+
+impl MerkleBranch {
+    /// Build the branch for `hashes[index]`
+    pub fn build(hashes: &[Hash], index: usize) -> Result<Self, PowError> {
+        if index >= hashes.len() {
+            return Err(PowError::InvalidMerkleBranch(format!(
+                "index {} out of range for {} leaves",
+                index,
+                hashes.len()
+            )));
+        }
+
+        let mut branch = MerkleBranch::default();
+        let count = hashes.len();
+        if count == 1 {
+            return Ok(branch);
+        }
+        if count == 2 {
+            branch.push(hashes[1 - index], index == 1);
+            return Ok(branch);
+        }
+
+        // First level: the leading leaves pass through, the rest are paired
+        let mut cnt = tree_hash_cnt(count);
+        let direct = 2 * cnt - count;
+        let mut level: Vec<Hash> = hashes[..direct].to_vec();
+        for pair in hashes[direct..].chunks_exact(2) {
+            level.push(hash_pair(&pair[0], &pair[1]));
+        }
+        let mut position = if index < direct {
+            index
+        } else {
+            let offset = index - direct;
+            branch.push(hashes[direct + (offset ^ 1)], offset & 1 == 1);
+            direct + offset / 2
+        };
+
+        while cnt > 1 {
+            branch.push(level[position ^ 1], position & 1 == 1);
+            level = level.chunks_exact(2).map(|pair| hash_pair(&pair[0], &pair[1])).collect();
+            position /= 2;
+            cnt >>= 1;
+        }
+
+        Ok(branch)
+    }
+
+    fn push(&mut self, sibling: Hash, is_right: bool) {
+        if is_right {
+            self.path |= 1 << self.hashes.len();
+        }
+        self.hashes.push(sibling);
+    }
+
+    pub fn depth(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Fold `leaf` up the branch to the tree root
+    pub fn root(&self, leaf: &Hash) -> Result<Hash, PowError> {
+        if self.hashes.len() > MAX_BRANCH_DEPTH {
+            return Err(PowError::InvalidMerkleBranch(format!("depth {} too large", self.hashes.len())));
+        }
+
+        let mut current = *leaf;
+        for (i, sibling) in self.hashes.iter().enumerate() {
+            current = if self.path & (1 << i) != 0 {
+                hash_pair(sibling, &current)
+            } else {
+                hash_pair(&current, sibling)
+            };
+        }
+        Ok(current)
+    }
+}
+
+/// Append `value` as a CryptoNote varint
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Decode a CryptoNote varint, returning the value and the number of bytes read
+pub fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Id of the C0DL3 aux chain, which fixes its slot in every aux tree
+pub fn aux_chain_id() -> Hash {
+    cn_fast_hash(AUX_CHAIN_NAME)
+}
+
+/// Slot of the chain `chain_id` in an aux tree of `2^depth` leaves under the tag `nonce`
+pub fn aux_slot(chain_id: &Hash, nonce: u32, depth: u64) -> u64 {
+    if depth == 0 {
+        return 0;
+    }
+    let mut data = chain_id.to_vec();
+    data.extend_from_slice(&nonce.to_le_bytes());
+    let hash = cn_fast_hash(&data);
+    let value = u64::from_le_bytes(hash[..8].try_into().unwrap());
+    if depth >= 64 {
+        value
+    } else {
+        value % (1u64 << depth)
+    }
+}
+
+/// Merge mining commitment placed in the parent coinbase `tx_extra`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeMiningTag {
+    /// Depth of the aux tree, which holds `2^depth` slots
+    pub depth: u64,
+    /// Nonce the aux chains' slots are derived from
+    pub nonce: u32,
+    pub merkle_root: Hash,
+}
+
+impl MergeMiningTag {
+    /// Split the tag's varint into depth, in the low byte, and nonce; a tag with a zero
+    /// nonce reads the same as a plain CryptoNote depth
+    pub fn from_packed(packed: u64, merkle_root: Hash) -> Option<Self> {
+        Some(Self {
+            depth: packed & 0xff,
+            nonce: u32::try_from(packed >> 8).ok()?,
+            merkle_root,
+        })
+    }
+
+    fn packed(&self) -> u64 {
+        (self.nonce as u64) << 8 | (self.depth & 0xff)
+    }
+
+    /// Serialize as a `tx_extra` field: tag, length, varint depth and nonce, root
+    pub fn to_extra(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        write_varint(&mut payload, self.packed());
+        payload.extend_from_slice(&self.merkle_root);
+
+        let mut out = vec![TX_EXTRA_MERGE_MINING_TAG];
+        write_varint(&mut out, payload.len() as u64);
+        out.extend_from_slice(&payload);
+        out
+    }
+
+    /// The one merge mining tag in the `tx_extra` of a serialized parent coinbase
+    pub fn from_coinbase(coinbase_tx: &[u8]) -> Result<Self, PowError> {
+        let mut tags = extra_merge_mining_tags(coinbase_extra(coinbase_tx)?);
+        match tags.len() {
+            0 => Err(PowError::MissingMergeMiningTag),
+            1 => Ok(tags.remove(0)),
+            _ => Err(PowError::DuplicateMergeMiningTag),
+        }
+    }
+}
+
+/// Reads a serialized coinbase front to back
+struct CoinbaseReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CoinbaseReader<'a> {
+    fn varint(&mut self, field: &str) -> Result<u64, PowError> {
+        let (value, read) = read_varint(self.data).ok_or_else(|| invalid_coinbase(field))?;
+        self.data = &self.data[read..];
+        Ok(value)
+    }
+
+    fn bytes(&mut self, len: u64, field: &str) -> Result<&'a [u8], PowError> {
+        let len = usize::try_from(len).ok().filter(|len| *len <= self.data.len());
+        let (bytes, rest) = self.data.split_at(len.ok_or_else(|| invalid_coinbase(field))?);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self, field: &str) -> Result<u8, PowError> {
+        Ok(self.bytes(1, field)?[0])
+    }
+}
+
+fn invalid_coinbase(field: &str) -> PowError {
+    PowError::InvalidCoinbase(format!("bad {}", field))
+}
+
+/// `tx_extra` of a serialized coinbase: one generation input, key or deposit outputs, then
+/// the extra, which ends the transaction since a coinbase carries no signatures
+fn coinbase_extra(coinbase_tx: &[u8]) -> Result<&[u8], PowError> {
+    let mut reader = CoinbaseReader { data: coinbase_tx };
+    reader.varint("version")?;
+    reader.varint("unlock time")?;
+    if reader.varint("input count")? != 1 || reader.byte("input type")? != TXIN_GEN {
+        return Err(PowError::InvalidCoinbase("not a coinbase".to_string()));
+    }
+    reader.varint("height")?;
+    for _ in 0..reader.varint("output count")? {
+        reader.varint("output amount")?;
+        match reader.byte("output type")? {
+            TXOUT_TO_KEY => {
+                reader.bytes(32, "output key")?;
+            }
+            TXOUT_MULTISIG => {
+                let keys = reader.varint("multisignature key count")?;
+                reader.bytes(keys.saturating_mul(32), "multisignature keys")?;
+                reader.varint("required signatures")?;
+                reader.varint("output term")?;
+            }
+            other => return Err(PowError::InvalidCoinbase(format!("output type {:#04x}", other))),
+        }
+    }
+    let extra_len = reader.varint("extra length")?;
+    let extra = reader.bytes(extra_len, "extra")?;
+    if !reader.data.is_empty() {
+        return Err(PowError::InvalidCoinbase(format!("{} bytes after the extra", reader.data.len())));
+    }
+    Ok(extra)
+}
+
+/// Merge mining tags among the `tx_extra` fields fuegod reads, which stop at the first field
+/// it cannot parse
+fn extra_merge_mining_tags(extra: &[u8]) -> Vec<MergeMiningTag> {
+    let mut reader = CoinbaseReader { data: extra };
+    let mut tags = Vec::new();
+    while let Ok(tag) = reader.byte("extra tag") {
+        let field = match tag {
+            TX_EXTRA_TAG_PADDING => break,
+            TX_EXTRA_TAG_PUBKEY => reader.bytes(32, "extra public key"),
+            TX_EXTRA_NONCE => reader.byte("extra nonce size").and_then(|size| reader.bytes(size as u64, "extra nonce")),
+            TX_EXTRA_MERGE_MINING_TAG | TX_EXTRA_MESSAGE_TAG | TX_EXTRA_TTL => {
+                reader.varint("extra field size").and_then(|size| reader.bytes(size, "extra field"))
+            }
+            _ => break,
+        };
+        let Ok(field) = field else { break };
+        if tag == TX_EXTRA_MERGE_MINING_TAG {
+            let mut payload = CoinbaseReader { data: field };
+            let Ok(packed) = payload.varint("merge mining depth") else { break };
+            let tag = <Hash>::try_from(payload.data)
+                .ok()
+                .and_then(|merkle_root| MergeMiningTag::from_packed(packed, merkle_root));
+            match tag {
+                Some(tag) => tags.push(tag),
+                None => break,
+            }
+        }
+    }
+    tags
+}
+
+/// Coinbase of parent block `height` paying nothing, with its extra sized for the tag
+/// `AuxPow::create` appends; for parent blocks that never reach fuegod, such as simulations
+pub fn bare_coinbase(height: u64) -> Vec<u8> {
+    let mut coinbase = Vec::new();
+    write_varint(&mut coinbase, 1);
+    write_varint(&mut coinbase, 0);
+    coinbase.extend_from_slice(&[1, TXIN_GEN]);
+    write_varint(&mut coinbase, height);
+    coinbase.push(0);
+    let tag = MergeMiningTag { depth: 0, nonce: 0, merkle_root: [0u8; 32] };
+    write_varint(&mut coinbase, tag.to_extra().len() as u64);
+    coinbase
+}
+
+/// Header of a parent (Fuego) block
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParentBlockHeader {
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub timestamp: u64,
+    pub prev_id: Hash,
+    pub nonce: u32,
+    /// Tree hash of the parent block's transactions, coinbase first
+    pub merkle_root: Hash,
+    pub tx_count: u64,
+}
+
+impl ParentBlockHeader {
+    /// CryptoNote block hashing blob: header, transaction tree root, transaction count
+    pub fn hashing_blob(&self) -> Vec<u8> {
+        let mut blob = Vec::with_capacity(80);
+        write_varint(&mut blob, self.major_version as u64);
+        write_varint(&mut blob, self.minor_version as u64);
+        write_varint(&mut blob, self.timestamp);
+        blob.extend_from_slice(&self.prev_id);
+        blob.extend_from_slice(&self.nonce.to_le_bytes());
+        blob.extend_from_slice(&self.merkle_root);
+        write_varint(&mut blob, self.tx_count);
+        blob
+    }
+
+    /// CryptoNote block id: the fast hash of the length-prefixed hashing blob
+    pub fn id(&self) -> Hash {
+        let blob = self.hashing_blob();
+        let mut data = Vec::with_capacity(blob.len() + 2);
+        write_varint(&mut data, blob.len() as u64);
+        data.extend_from_slice(&blob);
+        cn_fast_hash(&data)
+    }
+}
+
+/// Proof that a parent block's work commits to an aux chain block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuxPow {
+    /// Serialized parent coinbase transaction carrying the merge mining tag
+    pub coinbase_tx: Vec<u8>,
+    /// Branch from the coinbase hash to the parent transaction root
+    pub coinbase_branch: MerkleBranch,
+    /// Branch from the aux block hash to the root committed in the coinbase
+    pub blockchain_branch: MerkleBranch,
+}
+
+impl AuxPow {
+    /// Commit `aux_hash` as the only aux block into `coinbase_tx`, whose extra must end the
+    /// transaction and already count the tag's bytes, and build the proof for it. Fills in
+    /// `merkle_root` and `tx_count` of `parent_header`; the caller then searches the nonce.
+    pub fn create(
+        aux_hash: Hash,
+        mut coinbase_tx: Vec<u8>,
+        other_tx_hashes: &[Hash],
+        parent_header: &mut ParentBlockHeader,
+    ) -> Result<Self, PowError> {
+        let blockchain_branch = MerkleBranch::default();
+        let tag = MergeMiningTag {
+            depth: 0,
+            nonce: 0,
+            merkle_root: aux_hash,
+        };
+        coinbase_tx.extend_from_slice(&tag.to_extra());
+
+        let mut tx_hashes = Vec::with_capacity(other_tx_hashes.len() + 1);
+        tx_hashes.push(cn_fast_hash(&coinbase_tx));
+        tx_hashes.extend_from_slice(other_tx_hashes);
+
+        parent_header.merkle_root = tree_hash(&tx_hashes);
+        parent_header.tx_count = tx_hashes.len() as u64;
+
+        Ok(Self {
+            coinbase_tx,
+            coinbase_branch: MerkleBranch::build(&tx_hashes, 0)?,
+            blockchain_branch,
+        })
+    }
+
+    /// Check that `parent_header` commits to `aux_hash` through this proof, in C0DL3's slot
+    pub fn verify(&self, parent_header: &ParentBlockHeader, aux_hash: &Hash) -> Result<(), PowError> {
+        let tag = MergeMiningTag::from_coinbase(&self.coinbase_tx)?;
+        if tag.depth != self.blockchain_branch.depth() as u64 {
+            return Err(PowError::AuxRootMismatch);
+        }
+        if self.blockchain_branch.path as u64 != aux_slot(&aux_chain_id(), tag.nonce, tag.depth) {
+            return Err(PowError::WrongAuxSlot);
+        }
+        if self.blockchain_branch.root(aux_hash)? != tag.merkle_root {
+            return Err(PowError::AuxRootMismatch);
+        }
+
+        // The coinbase is always the first transaction of the parent block
+        if self.coinbase_branch.path != 0 {
+            return Err(PowError::CoinbaseNotInParent);
+        }
+        let coinbase_hash = cn_fast_hash(&self.coinbase_tx);
+        if self.coinbase_branch.root(&coinbase_hash)? != parent_header.merkle_root {
+            return Err(PowError::CoinbaseNotInParent);
+        }
+
+        Ok(())
+    }
+
+    /// Verify the commitment and that the parent block's work meets `difficulty`
+    pub fn verify_work(
+        &self,
+        parent_header: &ParentBlockHeader,
+        aux_hash: &Hash,
+        difficulty: u64,
+        hasher: &dyn PowHasher,
+    ) -> Result<(), PowError> {
+        self.verify(parent_header, aux_hash)?;
+        if !check_hash(&hasher.hash(&parent_header.hashing_blob()), difficulty) {
+            return Err(PowError::InsufficientWork);
+        }
+        Ok(())
+    }
+}
+
+/// Merge-mined block proof carried in a C0DL3 block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergeMinedProof {
+    pub parent_header: ParentBlockHeader,
+    pub aux_pow: AuxPow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: u8) -> Vec<Hash> {
+        (0..n).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn test_tree_hash_small_trees() {
+        let h = leaves(3);
+        assert_eq!(tree_hash(&h[..1]), h[0]);
+        assert_eq!(tree_hash(&h[..2]), hash_pair(&h[0], &h[1]));
+        // Three leaves: the first passes through, the last two are paired
+        assert_eq!(tree_hash(&h), hash_pair(&h[0], &hash_pair(&h[1], &h[2])));
+    }
+
+    #[test]
+    fn test_branches_reproduce_root_for_every_leaf() {
+        for n in 1..=9u8 {
+            let h = leaves(n);
+            let root = tree_hash(&h);
+            for (i, leaf) in h.iter().enumerate() {
+                let branch = MerkleBranch::build(&h, i).unwrap();
+                assert_eq!(branch.root(leaf).unwrap(), root, "n={} i={}", n, i);
+            }
+        }
+    }
+
+    /// `bare_coinbase` with `extra` as its whole extra
+    fn coinbase_with(extra: &[u8]) -> Vec<u8> {
+        let mut coinbase = bare_coinbase(7);
+        coinbase.pop();
+        write_varint(&mut coinbase, extra.len() as u64);
+        coinbase.extend_from_slice(extra);
+        coinbase
+    }
+
+    /// Proof for `aux_hashes[index]` under `tag`, with the parent header committing to it
+    fn proof(aux_hashes: &[Hash], index: usize, tag: &MergeMiningTag) -> (AuxPow, ParentBlockHeader) {
+        let coinbase_tx = coinbase_with(&tag.to_extra());
+        let parent = ParentBlockHeader {
+            merkle_root: cn_fast_hash(&coinbase_tx),
+            tx_count: 1,
+            ..Default::default()
+        };
+        let aux_pow = AuxPow {
+            coinbase_tx,
+            coinbase_branch: MerkleBranch::default(),
+            blockchain_branch: MerkleBranch::build(aux_hashes, index).unwrap(),
+        };
+        (aux_pow, parent)
+    }
+
+    #[test]
+    fn test_merge_mining_tag_roundtrip() {
+        let tag = MergeMiningTag { depth: 3, nonce: 0x1234, merkle_root: [9u8; 32] };
+        let mut fields = vec![TX_EXTRA_TAG_PUBKEY];
+        fields.extend_from_slice(&[5u8; 32]);
+        fields.extend_from_slice(&[TX_EXTRA_NONCE, 2, 0xab, 0xcd]);
+        fields.extend_from_slice(&tag.to_extra());
+        assert_eq!(MergeMiningTag::from_coinbase(&coinbase_with(&fields)), Ok(tag));
+
+        // A zero nonce serializes as a plain CryptoNote depth
+        let plain = MergeMiningTag { depth: 3, nonce: 0, merkle_root: [9u8; 32] };
+        assert_eq!(plain.to_extra()[2], 3);
+        assert_eq!(MergeMiningTag::from_packed(3, [9u8; 32]), Some(plain));
+    }
+
+    #[test]
+    fn test_merge_mining_tag_must_appear_once_in_the_extra() {
+        let tag = MergeMiningTag { depth: 0, nonce: 0, merkle_root: [9u8; 32] };
+        assert_eq!(
+            MergeMiningTag::from_coinbase(&coinbase_with(&[])),
+            Err(PowError::MissingMergeMiningTag)
+        );
+
+        let duplicated = [tag.to_extra(), tag.to_extra()].concat();
+        assert_eq!(
+            MergeMiningTag::from_coinbase(&coinbase_with(&duplicated)),
+            Err(PowError::DuplicateMergeMiningTag)
+        );
+
+        // Tag bytes hidden inside another field, or after padding, are not a tag
+        let mut nonce = vec![TX_EXTRA_NONCE, tag.to_extra().len() as u8];
+        nonce.extend_from_slice(&tag.to_extra());
+        assert_eq!(MergeMiningTag::from_coinbase(&coinbase_with(&nonce)), Err(PowError::MissingMergeMiningTag));
+        let padded = [vec![TX_EXTRA_TAG_PADDING; 4], tag.to_extra()].concat();
+        assert_eq!(MergeMiningTag::from_coinbase(&coinbase_with(&padded)), Err(PowError::MissingMergeMiningTag));
+
+        // Bytes past the end of the extra are not part of a coinbase
+        let mut trailing = bare_coinbase(7);
+        trailing.extend_from_slice(&tag.to_extra());
+        trailing.extend_from_slice(&tag.to_extra());
+        assert!(matches!(MergeMiningTag::from_coinbase(&trailing), Err(PowError::InvalidCoinbase(_))));
+        assert!(matches!(MergeMiningTag::from_coinbase(&[0xaa; 40]), Err(PowError::InvalidCoinbase(_))));
+    }
+
+    #[test]
+    fn test_aux_block_must_sit_in_the_chain_slot() {
+        let aux_hashes = leaves(4);
+        let nonce = 42;
+        let slot = aux_slot(&aux_chain_id(), nonce, 2) as usize;
+        let tag = MergeMiningTag { depth: 2, nonce, merkle_root: tree_hash(&aux_hashes) };
+
+        let (aux_pow, parent) = proof(&aux_hashes, slot, &tag);
+        assert!(aux_pow.verify(&parent, &aux_hashes[slot]).is_ok());
+
+        // Every other slot commits to the same root but is not C0DL3's
+        for other in (0..4).filter(|index| *index != slot) {
+            let (aux_pow, parent) = proof(&aux_hashes, other, &tag);
+            assert_eq!(aux_pow.verify(&parent, &aux_hashes[other]), Err(PowError::WrongAuxSlot));
+        }
+    }
+
+    #[test]
+    fn test_auxpow_create_and_verify() {
+        let aux_hash = [3u8; 32];
+        let mut parent = ParentBlockHeader {
+            major_version: 1,
+            timestamp: 1_700_000_000,
+            ..Default::default()
+        };
+        let aux_pow = AuxPow::create(aux_hash, bare_coinbase(7), &leaves(4), &mut parent).unwrap();
+        assert_eq!(parent.tx_count, 5);
+
+        assert!(aux_pow.verify(&parent, &aux_hash).is_ok());
+        assert_eq!(aux_pow.verify(&parent, &[2u8; 32]), Err(PowError::AuxRootMismatch));
+
+        // Tampering with the parent transaction set breaks the coinbase commitment
+        let mut tampered = parent.clone();
+        tampered.merkle_root = [0u8; 32];
+        assert_eq!(aux_pow.verify(&tampered, &aux_hash), Err(PowError::CoinbaseNotInParent));
+    }
+
+    #[test]
+    fn test_auxpow_verify_work() {
+        let aux_hash = [7u8; 32];
+        let mut parent = ParentBlockHeader::default();
+        let aux_pow = AuxPow::create(aux_hash, bare_coinbase(1), &[], &mut parent).unwrap();
+        let hasher = crate::CryptoNight::upx2();
+
+        assert!(aux_pow.verify_work(&parent, &aux_hash, 1, &hasher).is_ok());
+        assert_eq!(
+            aux_pow.verify_work(&parent, &aux_hash, u64::MAX, &hasher),
+            Err(PowError::InsufficientWork)
+        );
+    }
+}
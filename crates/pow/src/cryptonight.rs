@@ -0,0 +1,381 @@
+use crate::aes;
+use crate::hashes::{blake256, groestl256, jh256, skein512_256};
+use crate::PowHasher;
+
+const KECCAK_RATE: usize = 136;
+const STATE_SIZE: usize = 200;
+const INIT_SIZE: usize = 128;
+
+/// Main-loop variant of the CryptoNight family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CnVariant {
+    /// The original 2013 algorithm (cn/0)
+    Original,
+    /// Variant 2 with shuffle-add and integer division/square-root steps (cn/2)
+    V2,
+}
+
+/// Scratchpad size, loop count and variant of a CryptoNight algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CnParams {
+    pub name: &'static str,
+    pub memory: usize,
+    pub iterations: usize,
+    pub variant: CnVariant,
+    /// Swap which neighbouring chunks receive `b` and `b1` in the variant 2 shuffle
+    pub reverse_shuffle: bool,
+}
+
+impl CnParams {
+    /// cn/0: 2 MiB scratchpad, 2^19 iterations
+    pub const CN_0: CnParams = CnParams {
+        name: "cn/0",
+        memory: 2 * 1024 * 1024,
+        iterations: 0x80000,
+        variant: CnVariant::Original,
+        reverse_shuffle: false,
+    };
+
+    /// cn/2: 2 MiB scratchpad, 2^19 iterations, variant 2
+    pub const CN_2: CnParams = CnParams {
+        name: "cn/2",
+        memory: 2 * 1024 * 1024,
+        iterations: 0x80000,
+        variant: CnVariant::V2,
+        reverse_shuffle: false,
+    };
+
+    /// cn/upx2 as used by Fuego: 128 KiB scratchpad, 2^14 iterations, variant 2 with reversed shuffle
+    pub const CN_UPX2: CnParams = CnParams {
+        name: "cn/upx2",
+        memory: 128 * 1024,
+        iterations: 0x4000,
+        variant: CnVariant::V2,
+        reverse_shuffle: true,
+    };
+
+    fn mask(&self) -> usize {
+        self.memory - 16
+    }
+}
+
+/// Memory-hard CryptoNight hasher
+#[derive(Debug, Clone, Copy)]
+pub struct CryptoNight {
+    params: CnParams,
+}
+
+impl CryptoNight {
+    pub fn new(params: CnParams) -> Self {
+        Self { params }
+    }
+
+    /// The CN-UPX/2 hasher used for Fuego proof-of-work
+    pub fn upx2() -> Self {
+        Self::new(CnParams::CN_UPX2)
+    }
+
+    pub fn params(&self) -> &CnParams {
+        &self.params
+    }
+
+    /// Hash `data`, allocating a fresh scratchpad
+    pub fn digest(&self, data: &[u8]) -> [u8; 32] {
+        let mut scratchpad = vec![0u8; self.params.memory];
+        self.digest_with_scratchpad(data, &mut scratchpad)
+    }
+
+    /// Hash `data` reusing a caller-provided scratchpad of at least `memory` bytes
+    pub fn digest_with_scratchpad(&self, data: &[u8], scratchpad: &mut [u8]) -> [u8; 32] {
+        let params = &self.params;
+        assert!(scratchpad.len() >= params.memory, "scratchpad too small");
+        let scratchpad = &mut scratchpad[..params.memory];
+
+        let mut state = keccak1600(data);
+
+        // Fill the scratchpad by repeatedly encrypting bytes 64..192 of the state
+        let round_keys = aes::expand_key(&state[..32]);
+        let mut text: [u8; INIT_SIZE] = state[64..64 + INIT_SIZE].try_into().unwrap();
+        for chunk in scratchpad.chunks_exact_mut(INIT_SIZE) {
+            encrypt_blocks(&mut text, &round_keys);
+            chunk.copy_from_slice(&text);
+        }
+
+        let mut a = xor16(&state[0..16], &state[32..48]);
+        let mut b = xor16(&state[16..32], &state[48..64]);
+        let mut b1 = xor16(&state[64..80], &state[80..96]);
+        let mut division_result = u64::from_le_bytes(state[96..104].try_into().unwrap());
+        let mut sqrt_result = u64::from_le_bytes(state[104..112].try_into().unwrap());
+        let v2 = params.variant == CnVariant::V2;
+        let reverse = params.reverse_shuffle;
+        let mask = params.mask();
+
+        for _ in 0..params.iterations {
+            // First half: one AES round keyed by `a`
+            let j = (u32::from_le_bytes(a[..4].try_into().unwrap()) as usize) & mask;
+            let mut c: [u8; 16] = scratchpad[j..j + 16].try_into().unwrap();
+            aes::round(&mut c, &a);
+            if v2 {
+                shuffle_add(scratchpad, j, &a, &b, &b1, reverse);
+            }
+            scratchpad[j..j + 16].copy_from_slice(&xor16(&c, &b));
+
+            // Second half: 64x64 multiply
+            let j = (u32::from_le_bytes(c[..4].try_into().unwrap()) as usize) & mask;
+            let mut d: [u8; 16] = scratchpad[j..j + 16].try_into().unwrap();
+            if v2 {
+                let c0 = lo64(&c);
+                let c1 = hi64(&c);
+                let d0 = lo64(&d) ^ division_result ^ (sqrt_result << 32);
+                d[..8].copy_from_slice(&d0.to_le_bytes());
+
+                let divisor = (c0.wrapping_add(sqrt_result << 1) as u32 | 0x8000_0001) as u64;
+                division_result = ((c1 / divisor) & 0xffff_ffff) | ((c1 % divisor) << 32);
+                let sqrt_input = c0.wrapping_add(division_result);
+                sqrt_result = integer_sqrt(sqrt_input);
+            }
+
+            let product = (lo64(&c) as u128) * (lo64(&d) as u128);
+            let mut hi = (product >> 64) as u64;
+            let mut lo = product as u64;
+            if v2 {
+                // Mix the product with the neighbouring chunks before shuffling them
+                let o1 = j ^ 0x10;
+                let o2 = j ^ 0x20;
+                let chunk1 = xor16(&scratchpad[o1..o1 + 16], &from_u64s(hi, lo));
+                scratchpad[o1..o1 + 16].copy_from_slice(&chunk1);
+                hi ^= u64::from_le_bytes(scratchpad[o2..o2 + 8].try_into().unwrap());
+                lo ^= u64::from_le_bytes(scratchpad[o2 + 8..o2 + 16].try_into().unwrap());
+                shuffle_add(scratchpad, j, &a, &b, &b1, reverse);
+            }
+
+            let a0 = lo64(&a).wrapping_add(hi);
+            let a1 = hi64(&a).wrapping_add(lo);
+            scratchpad[j..j + 8].copy_from_slice(&a0.to_le_bytes());
+            scratchpad[j + 8..j + 16].copy_from_slice(&a1.to_le_bytes());
+            a = from_u64s(a0 ^ lo64(&d), a1 ^ hi64(&d));
+
+            b1 = b;
+            b = c;
+        }
+
+        // Fold the scratchpad back into the state
+        let round_keys = aes::expand_key(&state[32..64]);
+        let mut text: [u8; INIT_SIZE] = state[64..64 + INIT_SIZE].try_into().unwrap();
+        for chunk in scratchpad.chunks_exact(INIT_SIZE) {
+            for (t, s) in text.iter_mut().zip(chunk) {
+                *t ^= s;
+            }
+            encrypt_blocks(&mut text, &round_keys);
+        }
+        state[64..64 + INIT_SIZE].copy_from_slice(&text);
+        keccak_permute(&mut state);
+
+        match state[0] & 3 {
+            0 => blake256(&state),
+            1 => groestl256(&state),
+            2 => jh256(&state),
+            _ => skein512_256(&state),
+        }
+    }
+}
+
+impl PowHasher for CryptoNight {
+    fn algorithm(&self) -> &'static str {
+        self.params.name
+    }
+
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        self.digest(data)
+    }
+}
+
+/// CryptoNote fast hash: Keccak-256 with the original padding
+pub fn cn_fast_hash(data: &[u8]) -> [u8; 32] {
+    keccak1600(data)[..32].try_into().unwrap()
+}
+
+/// Keccak-1600 with the original padding, returning the full 200-byte state
+fn keccak1600(data: &[u8]) -> [u8; STATE_SIZE] {
+    let mut lanes = [0u64; 25];
+    let mut absorb = |block: &[u8]| {
+        for (i, lane) in block.chunks_exact(8).enumerate() {
+            lanes[i] ^= u64::from_le_bytes(lane.try_into().unwrap());
+        }
+        keccak::f1600(&mut lanes);
+    };
+
+    let mut blocks = data.chunks_exact(KECCAK_RATE);
+    for block in &mut blocks {
+        absorb(block);
+    }
+    let rest = blocks.remainder();
+    let mut last = [0u8; KECCAK_RATE];
+    last[..rest.len()].copy_from_slice(rest);
+    last[rest.len()] = 0x01;
+    last[KECCAK_RATE - 1] |= 0x80;
+    absorb(&last);
+
+    let mut state = [0u8; STATE_SIZE];
+    for (i, lane) in lanes.iter().enumerate() {
+        state[i * 8..i * 8 + 8].copy_from_slice(&lane.to_le_bytes());
+    }
+    state
+}
+
+fn keccak_permute(state: &mut [u8; STATE_SIZE]) {
+    let mut lanes = [0u64; 25];
+    for (i, lane) in lanes.iter_mut().enumerate() {
+        *lane = u64::from_le_bytes(state[i * 8..i * 8 + 8].try_into().unwrap());
+    }
+    keccak::f1600(&mut lanes);
+    for (i, lane) in lanes.iter().enumerate() {
+        state[i * 8..i * 8 + 8].copy_from_slice(&lane.to_le_bytes());
+    }
+}
+
+/// Ten AES rounds over each of the eight 16-byte blocks
+fn encrypt_blocks(text: &mut [u8; INIT_SIZE], round_keys: &[[u8; 16]; 10]) {
+    for block in text.chunks_exact_mut(16) {
+        let block: &mut [u8; 16] = block.try_into().unwrap();
+        for key in round_keys {
+            aes::round(block, key);
+        }
+    }
+}
+
+/// Variant 2 shuffle of the three neighbouring 16-byte chunks of `j`
+fn shuffle_add(scratchpad: &mut [u8], j: usize, a: &[u8; 16], b: &[u8; 16], b1: &[u8; 16], reverse: bool) {
+    let read = |pad: &[u8], offset: usize| -> [u8; 16] { pad[offset..offset + 16].try_into().unwrap() };
+    let (o1, o2, o3) = (j ^ 0x10, j ^ 0x20, j ^ 0x30);
+    let chunk1 = read(scratchpad, o1);
+    let chunk2 = read(scratchpad, o2);
+    let chunk3 = read(scratchpad, o3);
+
+    if reverse {
+        scratchpad[o1..o1 + 16].copy_from_slice(&add16(&chunk1, b1));
+        scratchpad[o2..o2 + 16].copy_from_slice(&add16(&chunk3, b));
+    } else {
+        scratchpad[o1..o1 + 16].copy_from_slice(&add16(&chunk3, b1));
+        scratchpad[o2..o2 + 16].copy_from_slice(&add16(&chunk1, b));
+    }
+    scratchpad[o3..o3 + 16].copy_from_slice(&add16(&chunk2, a));
+}
+
+/// floor(sqrt(2^64 + input) * 2 - 2^33), computed in double precision then corrected
+fn integer_sqrt(input: u64) -> u64 {
+    let mut r = ((input as f64 + 18446744073709551616.0).sqrt() * 2.0 - 8589934592.0) as u64;
+    let s = r >> 1;
+    let b = r & 1;
+    let r2 = s.wrapping_mul(s.wrapping_add(b)).wrapping_add(r << 32);
+    if r2.wrapping_add(b) > input {
+        r = r.wrapping_sub(1);
+    }
+    if r2.wrapping_add(1 << 32) < input.wrapping_sub(s) {
+        r = r.wrapping_add(1);
+    }
+    r
+}
+
+fn lo64(x: &[u8; 16]) -> u64 {
+    u64::from_le_bytes(x[..8].try_into().unwrap())
+}
+
+fn hi64(x: &[u8; 16]) -> u64 {
+    u64::from_le_bytes(x[8..].try_into().unwrap())
+}
+
+fn from_u64s(lo: u64, hi: u64) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    out[..8].copy_from_slice(&lo.to_le_bytes());
+    out[8..].copy_from_slice(&hi.to_le_bytes());
+    out
+}
+
+fn xor16(x: &[u8], y: &[u8]) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    for i in 0..16 {
+        out[i] = x[i] ^ y[i];
+    }
+    out
+}
+
+fn add16(x: &[u8; 16], y: &[u8; 16]) -> [u8; 16] {
+    from_u64s(lo64(x).wrapping_add(lo64(y)), hi64(x).wrapping_add(hi64(y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cn_fast_hash() {
+        assert_eq!(
+            hex::encode(cn_fast_hash(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn test_cn0_reference_vector() {
+        let hash = CryptoNight::new(CnParams::CN_0).digest(b"This is a test");
+        assert_eq!(
+            hex::encode(hash),
+            "a084f01d1437a09c6985401b60d43554ae105802c5f5d8a9b3253649c0be6605"
+        );
+    }
+
+    #[test]
+    fn test_cn2_reference_vector() {
+        let hash = CryptoNight::new(CnParams::CN_2).digest(b"This is a test This is a test This is a test");
+        assert_eq!(
+            hex::encode(hash),
+            "353fdc068fd47b03c04b9431e005e00b68c2168a3cc7335c8b9b308156591a4f"
+        );
+    }
+
+    /// Block hashing blob XMRig's CryptoNight self-tests hash for every variant
+    const XMRIG_TEST_INPUT: &str = "0305a0dbd6bf05cf16e503f3a66f78007cbf34144332ecbfc22ed95c8700383b\
+                                    309ace1923a0964b00000008ba939a62724c0d7581fce5761e9d8a0e6a1c3f92\
+                                    4fdd8493d1115649c05eb601";
+
+    #[test]
+    fn test_xmrig_reference_vectors() {
+        // XMRig's test_output_v2 and test_output_upx2 for its test input
+        let input = hex::decode(XMRIG_TEST_INPUT).unwrap();
+        assert_eq!(
+            hex::encode(CryptoNight::new(CnParams::CN_2).digest(&input)),
+            "97378282cf10e7ad033f7b8074c40e14d06e7f609dddda787680b58c05f43d21"
+        );
+        assert_eq!(
+            hex::encode(CryptoNight::upx2().digest(&input)),
+            "aabbb8ed14a835fa22cfb1b5dea872b0a1d6cbd846f4391c0f01f3875e3a3761"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a block hashing blob and PoW hash exported from a Fuego mainnet daemon"]
+    fn test_upx2_matches_fuego_mainnet() {
+        // FUEGO_UPX2_VECTOR=<hashing blob hex>:<pow hash hex> of a mainnet block past the UPX2 fork
+        let vector = std::env::var("FUEGO_UPX2_VECTOR").expect("FUEGO_UPX2_VECTOR is not set");
+        let (blob, pow_hash) = vector.split_once(':').expect("vector is <blob>:<hash>");
+        let hash = CryptoNight::upx2().digest(&hex::decode(blob).expect("blob is hex"));
+        assert_eq!(hex::encode(hash), pow_hash.to_lowercase());
+    }
+
+    #[test]
+    fn test_upx2_is_deterministic_and_input_sensitive() {
+        let hasher = CryptoNight::upx2();
+        let first = hasher.hash(b"fuego block hashing blob");
+        assert_eq!(first, hasher.hash(b"fuego block hashing blob"));
+        assert_ne!(first, hasher.hash(b"fuego block hashing blob!"));
+        assert_eq!(hasher.algorithm(), "cn/upx2");
+    }
+
+    #[test]
+    fn test_integer_sqrt() {
+        // sqrt(2^64 + 0) * 2 - 2^33 == 0
+        assert_eq!(integer_sqrt(0), 0);
+        assert!(integer_sqrt(1 << 40) < integer_sqrt(1 << 41));
+    }
+}
@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PowError {
+    #[error("Invalid merkle branch: {0}")]
+    InvalidMerkleBranch(String),
+
+    #[error("Merge mining tag not found in coinbase")]
+    MissingMergeMiningTag,
+
+    #[error("Coinbase carries more than one merge mining tag")]
+    DuplicateMergeMiningTag,
+
+    #[error("Invalid parent coinbase: {0}")]
+    InvalidCoinbase(String),
+
+    #[error("Aux chain merkle root mismatch")]
+    AuxRootMismatch,
+
+    #[error("Aux block is not in the C0DL3 slot of the aux tree")]
+    WrongAuxSlot,
+
+    #[error("Coinbase is not committed to the parent block")]
+    CoinbaseNotInParent,
+
+    #[error("Insufficient proof of work")]
+    InsufficientWork,
+}
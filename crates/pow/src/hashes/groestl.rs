@@ -0,0 +1,112 @@
+//! Grøstl-256 (512-bit state, 10 rounds)
+
+use crate::aes::{gf_mul, SBOX};
+
+const ROWS: usize = 8;
+const COLS: usize = 8;
+const ROUNDS: u8 = 10;
+
+/// Column-major 8x8 byte state: byte `i` sits at row `i % 8`, column `i / 8`
+type State = [u8; 64];
+
+const P_SHIFTS: [usize; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+const Q_SHIFTS: [usize; 8] = [1, 3, 5, 7, 0, 2, 4, 6];
+const MIX: [u8; 8] = [2, 2, 3, 4, 5, 3, 5, 7];
+
+fn permute(state: &mut State, is_q: bool) {
+    for r in 0..ROUNDS {
+        // AddRoundConstant
+        if is_q {
+            for byte in state.iter_mut() {
+                *byte ^= 0xff;
+            }
+            for col in 0..COLS {
+                state[col * ROWS + 7] ^= ((col as u8) << 4) ^ r;
+            }
+        } else {
+            for col in 0..COLS {
+                state[col * ROWS] ^= ((col as u8) << 4) ^ r;
+            }
+        }
+
+        // SubBytes
+        for byte in state.iter_mut() {
+            *byte = SBOX[*byte as usize];
+        }
+
+        // ShiftBytes
+        let shifts = if is_q { &Q_SHIFTS } else { &P_SHIFTS };
+        let mut shifted = [0u8; 64];
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                shifted[col * ROWS + row] = state[((col + shifts[row]) % COLS) * ROWS + row];
+            }
+        }
+
+        // MixBytes
+        for col in 0..COLS {
+            for row in 0..ROWS {
+                let mut acc = 0u8;
+                for k in 0..ROWS {
+                    acc ^= gf_mul(shifted[col * ROWS + k], MIX[(k + ROWS - row) % ROWS]);
+                }
+                state[col * ROWS + row] = acc;
+            }
+        }
+    }
+}
+
+fn compress(h: &mut State, block: &[u8]) {
+    let mut p = [0u8; 64];
+    let mut q = [0u8; 64];
+    for i in 0..64 {
+        p[i] = h[i] ^ block[i];
+        q[i] = block[i];
+    }
+    permute(&mut p, false);
+    permute(&mut q, true);
+    for i in 0..64 {
+        h[i] ^= p[i] ^ q[i];
+    }
+}
+
+/// Compute Grøstl-256 of `data`
+pub fn groestl256(data: &[u8]) -> [u8; 32] {
+    // Pad with a 1 bit, zeros and the 64-bit count of blocks
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    let blocks = (padded.len() / 64 + 1) as u64;
+    padded.extend_from_slice(&blocks.to_be_bytes());
+
+    let mut h = [0u8; 64];
+    h[62] = 0x01; // output size in bits, 256
+
+    for block in padded.chunks(64) {
+        compress(&mut h, block);
+    }
+
+    // Output transformation: truncate(P(h) ^ h)
+    let mut p = h;
+    permute(&mut p, false);
+    let mut out = [0u8; 32];
+    for i in 0..32 {
+        out[i] = p[32 + i] ^ h[32 + i];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_groestl256_vectors() {
+        assert_eq!(
+            hex::encode(groestl256(b"")),
+            "1a52d11d550039be16107f9c58db9ebcc417f16f736adb2502567119f0083467"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! JH-256 (42-round E8), following the reference nibble-oriented description
+
+const SBOXES: [[u8; 16]; 2] = [
+    [9, 0, 4, 11, 13, 12, 3, 15, 1, 10, 2, 6, 7, 5, 8, 14],
+    [3, 12, 6, 13, 5, 7, 1, 9, 15, 2, 0, 4, 11, 10, 14, 8],
+];
+
+/// Round 0 constant: the fractional part of sqrt(2), one nibble per element
+const ROUND_CONSTANT_ZERO: [u8; 64] = [
+    0x6, 0xa, 0x0, 0x9, 0xe, 0x6, 0x6, 0x7, 0xf, 0x3, 0xb, 0xc, 0xc, 0x9, 0x0, 0x8,
+    0xb, 0x2, 0xf, 0xb, 0x1, 0x3, 0x6, 0x6, 0xe, 0xa, 0x9, 0x5, 0x7, 0xd, 0x3, 0xe,
+    0x3, 0xa, 0xd, 0xe, 0xc, 0x1, 0x7, 0x5, 0x1, 0x2, 0x7, 0x7, 0x5, 0x0, 0x9, 0x9,
+    0xd, 0xa, 0x2, 0xf, 0x5, 0x9, 0x0, 0xb, 0x0, 0x6, 0x6, 0x7, 0x3, 0x2, 0x2, 0xa,
+];
+
+/// The MDS layer applied to a pair of nibbles
+#[inline]
+fn linear(a: &mut u8, b: &mut u8) {
+    *b ^= ((*a << 1) ^ (*a >> 3) ^ ((*a >> 2) & 2)) & 0xf;
+    *a ^= ((*b << 1) ^ (*b >> 3) ^ ((*b >> 2) & 2)) & 0xf;
+}
+
+/// Pi, P' and Phi permutations over `N` nibbles
+fn permute<const N: usize>(tem: &mut [u8; N], out: &mut [u8; N]) {
+    for i in (0..N).step_by(4) {
+        tem.swap(i + 2, i + 3);
+    }
+    for i in 0..N / 2 {
+        out[i] = tem[i << 1];
+        out[i + N / 2] = tem[(i << 1) + 1];
+    }
+    for i in (N / 2..N).step_by(2) {
+        out.swap(i, i + 1);
+    }
+}
+
+fn round(a: &mut [u8; 256], constant: &[u8; 64]) {
+    let mut tem = [0u8; 256];
+    for i in 0..256 {
+        let bit = (constant[i >> 2] >> (3 - (i & 3))) & 1;
+        tem[i] = SBOXES[bit as usize][a[i] as usize];
+    }
+    for i in (0..256).step_by(2) {
+        let (lo, hi) = tem.split_at_mut(i + 1);
+        linear(&mut lo[i], &mut hi[0]);
+    }
+    permute(&mut tem, a);
+}
+
+fn update_round_constant(constant: &mut [u8; 64]) {
+    let mut tem = [0u8; 64];
+    for i in 0..64 {
+        tem[i] = SBOXES[0][constant[i] as usize];
+    }
+    for i in (0..64).step_by(2) {
+        let (lo, hi) = tem.split_at_mut(i + 1);
+        linear(&mut lo[i], &mut hi[0]);
+    }
+    permute(&mut tem, constant);
+}
+
+#[inline]
+fn bit(h: &[u8; 128], i: usize) -> u8 {
+    (h[i >> 3] >> (7 - (i & 7))) & 1
+}
+
+fn e8(h: &mut [u8; 128]) {
+    // Group the bits of H into 4-bit elements
+    let mut tem = [0u8; 256];
+    for (i, nibble) in tem.iter_mut().enumerate() {
+        *nibble = (bit(h, i) << 3) | (bit(h, i + 256) << 2) | (bit(h, i + 512) << 1) | bit(h, i + 768);
+    }
+    let mut a = [0u8; 256];
+    for i in 0..128 {
+        a[i << 1] = tem[i];
+        a[(i << 1) + 1] = tem[i + 128];
+    }
+
+    let mut constant = ROUND_CONSTANT_ZERO;
+    for _ in 0..42 {
+        round(&mut a, &constant);
+        update_round_constant(&mut constant);
+    }
+
+    // Degroup back into H
+    for i in 0..128 {
+        tem[i] = a[i << 1];
+        tem[i + 128] = a[(i << 1) + 1];
+    }
+    *h = [0u8; 128];
+    for (i, nibble) in tem.iter().enumerate() {
+        let shift = 7 - (i & 7);
+        h[i >> 3] |= ((nibble >> 3) & 1) << shift;
+        h[(i + 256) >> 3] |= ((nibble >> 2) & 1) << shift;
+        h[(i + 512) >> 3] |= ((nibble >> 1) & 1) << shift;
+        h[(i + 768) >> 3] |= (nibble & 1) << shift;
+    }
+}
+
+fn f8(h: &mut [u8; 128], block: &[u8]) {
+    for i in 0..64 {
+        h[i] ^= block[i];
+    }
+    e8(h);
+    for i in 0..64 {
+        h[i + 64] ^= block[i];
+    }
+}
+
+/// Compute JH-256 of `data`
+pub fn jh256(data: &[u8]) -> [u8; 32] {
+    let mut h = [0u8; 128];
+    h[0] = 0x01; // output size in bits, 256
+    f8(&mut h, &[0u8; 64]);
+
+    // Pad with a 1 bit and at least 383 zero bits, then the 128-bit message length
+    let bit_len = (data.len() as u128) * 8;
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    let zeros = 47 + (64 - data.len() % 64) % 64;
+    padded.resize(padded.len() + zeros, 0);
+    padded.extend_from_slice(&bit_len.to_be_bytes());
+
+    for block in padded.chunks(64) {
+        f8(&mut h, block);
+    }
+
+    h[96..].try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jh256_vectors() {
+        assert_eq!(
+            hex::encode(jh256(b"")),
+            "46e64619c18bb0a92a5e87185a47eef83ca747b8fcc8e1412921357e326df434"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! The four 256-bit hashes CryptoNight selects between for its final digest
+
+mod blake256;
+mod groestl;
+mod jh;
+mod skein;
+
+pub use blake256::blake256;
+pub use groestl::groestl256;
+pub use jh::jh256;
+pub use skein::skein512_256;
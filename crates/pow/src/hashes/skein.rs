@@ -0,0 +1,126 @@
+//! Skein-512-256 (Skein 1.3, Threefish-512)
+
+const C240: u64 = 0x1bd1_1bda_a9fc_1a22;
+
+const ROTATIONS: [[u32; 4]; 8] = [
+    [46, 36, 19, 37],
+    [33, 27, 14, 42],
+    [17, 49, 36, 39],
+    [44, 9, 54, 56],
+    [39, 30, 34, 24],
+    [13, 50, 10, 17],
+    [25, 29, 39, 43],
+    [8, 35, 56, 22],
+];
+
+const PERMUTATION: [usize; 8] = [2, 1, 4, 7, 6, 5, 0, 3];
+
+const TYPE_CFG: u64 = 4;
+const TYPE_MSG: u64 = 48;
+const TYPE_OUT: u64 = 63;
+const FLAG_FIRST: u64 = 1 << 62;
+const FLAG_FINAL: u64 = 1 << 63;
+
+fn threefish512(key: &[u64; 8], tweak: [u64; 2], block: &[u64; 8]) -> [u64; 8] {
+    let mut k = [0u64; 9];
+    k[..8].copy_from_slice(key);
+    k[8] = key.iter().fold(C240, |acc, w| acc ^ w);
+    let t = [tweak[0], tweak[1], tweak[0] ^ tweak[1]];
+
+    let subkey = |s: usize| -> [u64; 8] {
+        let mut ks = [0u64; 8];
+        for (i, word) in ks.iter_mut().enumerate() {
+            *word = k[(s + i) % 9];
+        }
+        ks[5] = ks[5].wrapping_add(t[s % 3]);
+        ks[6] = ks[6].wrapping_add(t[(s + 1) % 3]);
+        ks[7] = ks[7].wrapping_add(s as u64);
+        ks
+    };
+
+    let mut x = *block;
+    for d in 0..72 {
+        if d % 4 == 0 {
+            let ks = subkey(d / 4);
+            for i in 0..8 {
+                x[i] = x[i].wrapping_add(ks[i]);
+            }
+        }
+        for j in 0..4 {
+            let (a, b) = (x[2 * j], x[2 * j + 1]);
+            let y0 = a.wrapping_add(b);
+            x[2 * j] = y0;
+            x[2 * j + 1] = b.rotate_left(ROTATIONS[d % 8][j]) ^ y0;
+        }
+        let mixed = x;
+        for i in 0..8 {
+            x[i] = mixed[PERMUTATION[i]];
+        }
+    }
+    let ks = subkey(18);
+    for i in 0..8 {
+        x[i] = x[i].wrapping_add(ks[i]);
+    }
+    x
+}
+
+/// Unique Block Iteration over `message` (zero padded to 64-byte blocks)
+fn ubi(chain: &[u64; 8], message: &[u8], block_type: u64) -> [u64; 8] {
+    let mut chain = *chain;
+    let blocks = message.len().div_ceil(64).max(1);
+    for i in 0..blocks {
+        let start = i * 64;
+        let end = (start + 64).min(message.len());
+        let mut bytes = [0u8; 64];
+        bytes[..end - start].copy_from_slice(&message[start..end]);
+
+        let mut words = [0u64; 8];
+        for (w, word) in words.iter_mut().enumerate() {
+            *word = u64::from_le_bytes(bytes[w * 8..w * 8 + 8].try_into().unwrap());
+        }
+
+        let mut t1 = block_type << 56;
+        if i == 0 {
+            t1 |= FLAG_FIRST;
+        }
+        if i == blocks - 1 {
+            t1 |= FLAG_FINAL;
+        }
+        let out = threefish512(&chain, [end as u64, t1], &words);
+        for w in 0..8 {
+            chain[w] = out[w] ^ words[w];
+        }
+    }
+    chain
+}
+
+/// Compute Skein-512-256 of `data`
+pub fn skein512_256(data: &[u8]) -> [u8; 32] {
+    let mut config = [0u8; 32];
+    config[..4].copy_from_slice(b"SHA3");
+    config[4..6].copy_from_slice(&1u16.to_le_bytes());
+    config[8..16].copy_from_slice(&256u64.to_le_bytes());
+
+    let iv = ubi(&[0u64; 8], &config, TYPE_CFG);
+    let chain = ubi(&iv, data, TYPE_MSG);
+    let out = ubi(&chain, &0u64.to_le_bytes(), TYPE_OUT);
+
+    let mut result = [0u8; 32];
+    for (i, word) in out.iter().take(4).enumerate() {
+        result[i * 8..i * 8 + 8].copy_from_slice(&word.to_le_bytes());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skein512_256_vectors() {
+        assert_eq!(
+            hex::encode(skein512_256(b"")),
+            "39ccc4554a8b31853b9de7a1fe638a24cce6b35a55f2431009e18780335d2621"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Proof-of-work hash functions.
+//!
+//! [`CryptoNight`] implements the CryptoNight family, including the CN-UPX/2 variant Fuego
+//! mines with, so merge-mined parent blocks can be checked against their real PoW hash.
+//! [`auxpow`] binds C0DL3 blocks to that work.
+
+mod aes;
+pub mod auxpow;
+pub mod cryptonight;
+pub mod error;
+pub mod hashes;
+
+pub use auxpow::{AuxPow, MergeMinedProof, ParentBlockHeader};
+pub use cryptonight::{cn_fast_hash, CnParams, CnVariant, CryptoNight};
+pub use error::PowError;
+
+/// A proof-of-work hash function
+pub trait PowHasher: Send + Sync {
+    /// Algorithm name as used by mining software, e.g. `cn/upx2`
+    fn algorithm(&self) -> &'static str;
+
+    /// Hash a block hashing blob
+    fn hash(&self, data: &[u8]) -> [u8; 32];
+
+    /// Hash `data` and check the result against a CryptoNote difficulty
+    fn verify(&self, data: &[u8], difficulty: u64) -> bool {
+        check_hash(&self.hash(data), difficulty)
+    }
+}
+
+/// CryptoNote difficulty check: the hash, read as a little-endian 256-bit integer,
+/// multiplied by `difficulty` must not overflow 256 bits
+pub fn check_hash(hash: &[u8; 32], difficulty: u64) -> bool {
+    let mut carry = 0u128;
+    for chunk in hash.chunks_exact(8) {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap()) as u128;
+        carry = (word * difficulty as u128 + carry) >> 64;
+    }
+    carry == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_hash() {
+        let mut hash = [0xffu8; 32];
+        assert!(check_hash(&hash, 1));
+        assert!(!check_hash(&hash, 2));
+
+        // 2^248 passes up to difficulty 255
+        hash = [0u8; 32];
+        hash[31] = 0x01;
+        assert!(check_hash(&hash, 255));
+        assert!(!check_hash(&hash, 256));
+    }
+}
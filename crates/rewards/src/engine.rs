@@ -0,0 +1,507 @@
+use crate::error::RewardsError;
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const ACCOUNT_PREFIX: &[u8] = b"rewards/account/";
+const EPOCH_PREFIX: &[u8] = b"rewards/epoch/";
+const STATS_KEY: &[u8] = b"rewards/stats";
+
+/// Basis points in one whole
+const BPS: u128 = 10_000;
+
+/// Who a part of the block reward is paid to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardClass {
+    Miner,
+    Validators,
+    LiquidityProviders,
+    Treasury,
+}
+
+/// Share of every block reward paid to each class, in basis points adding up to 10000
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardSplit {
+    pub miner_bps: u64,
+    /// Split among validators pro rata to stake
+    pub validators_bps: u64,
+    /// Split among liquidity providers pro rata to liquidity
+    pub liquidity_providers_bps: u64,
+    /// Also receives the share of a class with no recipients in a block
+    pub treasury_bps: u64,
+}
+
+impl Default for RewardSplit {
+    fn default() -> Self {
+        Self {
+            miner_bps: 6_000,
+            validators_bps: 2_500,
+            liquidity_providers_bps: 1_000,
+            treasury_bps: 500,
+        }
+    }
+}
+
+/// Linear release of an epoch's rewards once the epoch ends: nothing is claimable before
+/// the cliff, and everything is after `duration_epochs`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VestingSchedule {
+    pub cliff_epochs: u64,
+    pub duration_epochs: u64,
+}
+
+/// Reward distribution configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardsConfig {
+    /// Blocks per accounting epoch
+    pub epoch_length: u64,
+    pub split: RewardSplit,
+    /// Hex-encoded address credited with the treasury share
+    pub treasury_address: String,
+    /// Classes whose rewards vest; the others are claimable as soon as they are earned
+    pub vesting: BTreeMap<RewardClass, VestingSchedule>,
+}
+
+impl Default for RewardsConfig {
+    fn default() -> Self {
+        Self {
+            epoch_length: 1000,
+            split: RewardSplit::default(),
+            treasury_address: hex::encode([0x7e; 20]),
+            vesting: BTreeMap::new(),
+        }
+    }
+}
+
+impl RewardsConfig {
+    /// Check that the split adds up and every schedule and address is usable
+    pub fn validate(&self) -> Result<(), RewardsError> {
+        let split = &self.split;
+        let total = [split.miner_bps, split.validators_bps, split.liquidity_providers_bps, split.treasury_bps]
+            .iter()
+            .fold(0u128, |total, bps| total + *bps as u128);
+        if total != BPS {
+            return Err(RewardsError::ConfigError(format!("Reward split adds up to {} bps, not 10000", total)));
+        }
+        if self.epoch_length == 0 {
+            return Err(RewardsError::ConfigError("Epoch length must be positive".to_string()));
+        }
+        let cliff_too_long = self.vesting.iter().find(|(_, vesting)| vesting.cliff_epochs > vesting.duration_epochs);
+        if let Some((class, _)) = cliff_too_long {
+            return Err(RewardsError::ConfigError(format!("{:?} vesting cliff is longer than its duration", class)));
+        }
+        if !hex::decode(&self.treasury_address).is_ok_and(|address| !address.is_empty()) {
+            return Err(RewardsError::ConfigError("Treasury address is not a hex-encoded address".to_string()));
+        }
+        Ok(())
+    }
+
+    fn share_bps(&self, class: RewardClass) -> u64 {
+        match class {
+            RewardClass::Miner => self.split.miner_bps,
+            RewardClass::Validators => self.split.validators_bps,
+            RewardClass::LiquidityProviders => self.split.liquidity_providers_bps,
+            RewardClass::Treasury => self.split.treasury_bps,
+        }
+    }
+}
+
+/// Who earned a share of one block's reward
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockRecipients {
+    pub miner: Vec<u8>,
+    /// Validator addresses with their stake
+    pub validators: Vec<(Vec<u8>, u64)>,
+    /// Liquidity provider addresses with their liquidity
+    pub liquidity_providers: Vec<(Vec<u8>, u64)>,
+}
+
+/// Rewards one address earned in one class during one epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardGrant {
+    pub epoch: u64,
+    pub class: RewardClass,
+    pub amount: u64,
+    /// Height vesting starts from
+    pub granted_at: u64,
+    /// Nothing is claimable before this height
+    pub cliff_at: u64,
+    /// Everything is claimable from this height
+    pub vested_at: u64,
+}
+
+impl RewardGrant {
+    /// Part of the grant released by `height`
+    pub fn vested(&self, height: u64) -> u64 {
+        if height < self.cliff_at {
+            0
+        } else if height >= self.vested_at {
+            self.amount
+        } else {
+            let elapsed = (height - self.granted_at) as u128;
+            (self.amount as u128 * elapsed / (self.vested_at - self.granted_at) as u128) as u64
+        }
+    }
+}
+
+/// Everything an address has earned and claimed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardAccount {
+    pub address: Vec<u8>,
+    pub grants: Vec<RewardGrant>,
+    pub claimed: u64,
+}
+
+impl RewardAccount {
+    /// Every reward earned, vested or not
+    pub fn accrued(&self) -> u64 {
+        self.grants.iter().fold(0u64, |total, grant| total.saturating_add(grant.amount))
+    }
+
+    /// Rewards released by `height`
+    pub fn vested(&self, height: u64) -> u64 {
+        self.grants.iter().fold(0u64, |total, grant| total.saturating_add(grant.vested(height)))
+    }
+
+    /// Released rewards not yet claimed at `height`
+    pub fn claimable(&self, height: u64) -> u64 {
+        self.vested(height).saturating_sub(self.claimed)
+    }
+}
+
+/// Rewards paid out in one epoch, by class
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochSummary {
+    pub epoch: u64,
+    pub blocks: u64,
+    pub total: u64,
+    pub by_class: BTreeMap<RewardClass, u64>,
+}
+
+/// Reward statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RewardsStats {
+    pub total_distributed: u64,
+    pub total_claimed: u64,
+    pub blocks_rewarded: u64,
+    pub last_epoch: u64,
+}
+
+/// Splits block rewards by the configured shares and tracks what each address may claim,
+/// optionally persisted in a StateDB
+pub struct RewardsEngine {
+    config: RewardsConfig,
+    treasury: Vec<u8>,
+    accounts: BTreeMap<Vec<u8>, RewardAccount>,
+    epochs: BTreeMap<u64, EpochSummary>,
+    stats: RewardsStats,
+    db: Option<Arc<RwLock<RocksStateDB>>>,
+}
+
+impl RewardsEngine {
+    /// Create an in-memory rewards engine
+    pub fn new(config: RewardsConfig) -> Result<Self, RewardsError> {
+        config.validate()?;
+        let treasury = hex::decode(&config.treasury_address).expect("validated treasury address");
+        Ok(Self {
+            config,
+            treasury,
+            accounts: BTreeMap::new(),
+            epochs: BTreeMap::new(),
+            stats: RewardsStats::default(),
+            db: None,
+        })
+    }
+
+    /// Load reward totals from `db` and persist every change back to it. Accounts and
+    /// epochs are read when first touched.
+    pub async fn with_state_db(config: RewardsConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, RewardsError> {
+        let mut engine = Self::new(config)?;
+        if let Some(stats) = db.read().await.get_sync(STATS_KEY)? {
+            engine.stats = serde_json::from_slice(&stats)?;
+        }
+        engine.db = Some(db);
+        Ok(engine)
+    }
+
+    /// Split the `reward` of the block at `height` among its recipients. Returns each
+    /// address's credit by class.
+    pub async fn record_block(
+        &mut self,
+        height: u64,
+        reward: u64,
+        recipients: &BlockRecipients,
+    ) -> Result<Vec<(Vec<u8>, RewardClass, u64)>, RewardsError> {
+        if recipients.miner.is_empty() {
+            return Err(RewardsError::InvalidReward(format!("Block {} has no miner", height)));
+        }
+        let class_amount = |class| (reward as u128 * self.config.share_bps(class) as u128 / BPS) as u64;
+        let mut credits = Vec::new();
+        credits.push((recipients.miner.clone(), RewardClass::Miner, class_amount(RewardClass::Miner)));
+        for (class, weights) in [
+            (RewardClass::Validators, &recipients.validators),
+            (RewardClass::LiquidityProviders, &recipients.liquidity_providers),
+        ] {
+            for (address, amount) in split_by_weight(class_amount(class), weights) {
+                credits.push((address, class, amount));
+            }
+        }
+        // The treasury takes its share, any class nobody earned and the rounding remainder
+        let paid: u64 = credits.iter().map(|(_, _, amount)| amount).sum();
+        credits.push((self.treasury.clone(), RewardClass::Treasury, reward - paid));
+        credits.retain(|(_, _, amount)| *amount > 0);
+
+        let epoch = height / self.config.epoch_length;
+        for (address, class, amount) in &credits {
+            let grant = self.new_grant(epoch, *class);
+            let account = self.load_account(address).await?;
+            match account
+                .grants
+                .iter_mut()
+                .find(|existing| existing.epoch == epoch && existing.class == *class)
+            {
+                Some(existing) => existing.amount += amount,
+                None => account.grants.push(RewardGrant { amount: *amount, ..grant }),
+            }
+        }
+        let summary = self.load_epoch(epoch).await?;
+        summary.blocks += 1;
+        summary.total += reward;
+        for (_, class, amount) in &credits {
+            *summary.by_class.entry(*class).or_default() += amount;
+        }
+        self.stats.total_distributed += reward;
+        self.stats.blocks_rewarded += 1;
+        self.stats.last_epoch = epoch;
+
+        let mut touched: Vec<Vec<u8>> = credits.iter().map(|(address, _, _)| address.clone()).collect();
+        touched.sort();
+        touched.dedup();
+        self.persist(&touched, Some(epoch)).await?;
+        Ok(credits)
+    }
+
+    /// Claim what `address` has vested by `height` and not claimed yet
+    pub async fn claim(&mut self, address: &[u8], height: u64) -> Result<u64, RewardsError> {
+        let account = self.load_account(address).await?;
+        let amount = account.claimable(height);
+        account.claimed += amount;
+        self.stats.total_claimed += amount;
+        self.persist(&[address.to_vec()], None).await?;
+        Ok(amount)
+    }
+
+    /// Rewards of `address`, if it has earned any
+    pub async fn get_account(&mut self, address: &[u8]) -> Result<Option<RewardAccount>, RewardsError> {
+        let account = self.load_account(address).await?;
+        Ok((!account.grants.is_empty()).then(|| account.clone()))
+    }
+
+    /// Get reward statistics
+    pub fn get_stats(&self) -> RewardsStats {
+        self.stats.clone()
+    }
+
+    fn new_grant(&self, epoch: u64, class: RewardClass) -> RewardGrant {
+        let epoch_length = self.config.epoch_length;
+        let (granted_at, cliff_at, vested_at) = match self.config.vesting.get(&class) {
+            Some(vesting) => {
+                let epoch_end = (epoch + 1).saturating_mul(epoch_length);
+                (
+                    epoch_end,
+                    epoch_end.saturating_add(vesting.cliff_epochs.saturating_mul(epoch_length)),
+                    epoch_end.saturating_add(vesting.duration_epochs.saturating_mul(epoch_length)),
+                )
+            }
+            None => {
+                let epoch_start = epoch.saturating_mul(epoch_length);
+                (epoch_start, epoch_start, epoch_start)
+            }
+        };
+        RewardGrant {
+            epoch,
+            class,
+            amount: 0,
+            granted_at,
+            cliff_at,
+            vested_at,
+        }
+    }
+
+    async fn load_account(&mut self, address: &[u8]) -> Result<&mut RewardAccount, RewardsError> {
+        if !self.accounts.contains_key(address) {
+            let stored = match &self.db {
+                Some(db) => get_reward_account(&*db.read().await, address)?,
+                None => None,
+            };
+            let account = stored.unwrap_or_else(|| RewardAccount {
+                address: address.to_vec(),
+                grants: Vec::new(),
+                claimed: 0,
+            });
+            self.accounts.insert(address.to_vec(), account);
+        }
+        Ok(self.accounts.get_mut(address).expect("account was just loaded"))
+    }
+
+    async fn load_epoch(&mut self, epoch: u64) -> Result<&mut EpochSummary, RewardsError> {
+        if !self.epochs.contains_key(&epoch) {
+            let stored = match &self.db {
+                Some(db) => get_epoch_summary(&*db.read().await, epoch)?,
+                None => None,
+            };
+            let summary = stored.unwrap_or(EpochSummary {
+                epoch,
+                ..Default::default()
+            });
+            self.epochs.insert(epoch, summary);
+        }
+        Ok(self.epochs.get_mut(&epoch).expect("epoch was just loaded"))
+    }
+
+    /// Atomically write the given accounts, the epoch summary and the stats to the StateDB
+    async fn persist(&self, addresses: &[Vec<u8>], epoch: Option<u64>) -> Result<(), RewardsError> {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let mut entries = Vec::with_capacity(addresses.len() + 2);
+        for address in addresses {
+            if let Some(account) = self.accounts.get(address) {
+                entries.push((account_key(address), serde_json::to_vec(account)?));
+            }
+        }
+        if let Some(summary) = epoch.and_then(|epoch| self.epochs.get(&epoch)) {
+            entries.push((epoch_key(summary.epoch), serde_json::to_vec(summary)?));
+        }
+        entries.push((STATS_KEY.to_vec(), serde_json::to_vec(&self.stats)?));
+        db.write().await.write_batch_sync(&entries)?;
+        Ok(())
+    }
+}
+
+/// Split `amount` pro rata to weight. The rounding remainder goes to the heaviest
+/// recipient, the lowest address breaking ties; nothing is paid if there is no weight.
+fn split_by_weight(amount: u64, weights: &[(Vec<u8>, u64)]) -> Vec<(Vec<u8>, u64)> {
+    let total: u128 = weights.iter().map(|(_, weight)| *weight as u128).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut shares: Vec<(Vec<u8>, u64)> = weights
+        .iter()
+        .map(|(address, weight)| (address.clone(), (amount as u128 * *weight as u128 / total) as u64))
+        .collect();
+    let remainder = amount - shares.iter().map(|(_, share)| share).sum::<u64>();
+    if let Some(heaviest) = weights
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(index, _)| index)
+    {
+        shares[heaviest].1 += remainder;
+    }
+    shares
+}
+
+fn account_key(address: &[u8]) -> Vec<u8> {
+    [ACCOUNT_PREFIX, address].concat()
+}
+
+fn epoch_key(epoch: u64) -> Vec<u8> {
+    [EPOCH_PREFIX, epoch.to_be_bytes().as_slice()].concat()
+}
+
+/// Persisted rewards of `address`, if it has earned any
+pub fn get_reward_account(state: &RocksStateDB, address: &[u8]) -> Result<Option<RewardAccount>, RewardsError> {
+    match state.get_sync(&account_key(address))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// Persisted accounting of `epoch`, if any block of it was rewarded
+pub fn get_epoch_summary(state: &RocksStateDB, epoch: u64) -> Result<Option<EpochSummary>, RewardsError> {
+    match state.get_sync(&epoch_key(epoch))? {
+        Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MINER: &[u8] = &[0x11];
+
+    fn recipients() -> BlockRecipients {
+        BlockRecipients {
+            miner: MINER.to_vec(),
+            validators: vec![(vec![0x21], 300), (vec![0x22], 100)],
+            liquidity_providers: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_rewards_follow_the_split() {
+        let mut engine = RewardsEngine::new(RewardsConfig::default()).unwrap();
+        let credits = engine.record_block(5, 1_001, &recipients()).await.unwrap();
+        let treasury = hex::decode(RewardsConfig::default().treasury_address).unwrap();
+        // 60% to the miner, 25% split 3:1 among validators, and the treasury takes its own
+        // 5%, the unclaimed liquidity share and the rounding remainder
+        assert_eq!(
+            credits,
+            vec![
+                (MINER.to_vec(), RewardClass::Miner, 600),
+                (vec![0x21], RewardClass::Validators, 188),
+                (vec![0x22], RewardClass::Validators, 62),
+                (treasury, RewardClass::Treasury, 151),
+            ]
+        );
+        assert_eq!(engine.claim(MINER, 5).await.unwrap(), 600);
+        assert_eq!(engine.claim(MINER, 6).await.unwrap(), 0);
+
+        let uneven = RewardsConfig {
+            split: RewardSplit { treasury_bps: 600, ..Default::default() },
+            ..Default::default()
+        };
+        assert!(RewardsEngine::new(uneven).is_err());
+        assert!(engine.record_block(6, 10, &BlockRecipients::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_vesting_and_epochs_persist() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let config = RewardsConfig {
+            epoch_length: 10,
+            vesting: BTreeMap::from([(RewardClass::Miner, VestingSchedule { cliff_epochs: 1, duration_epochs: 4 })]),
+            ..Default::default()
+        };
+        {
+            let mut engine = RewardsEngine::with_state_db(config.clone(), db.clone()).await.unwrap();
+            engine.record_block(3, 1_000, &recipients()).await.unwrap();
+            engine.record_block(7, 1_000, &recipients()).await.unwrap();
+            // Epoch 0 ends at height 10; nothing before the cliff at 20
+            assert_eq!(engine.claim(MINER, 19).await.unwrap(), 0);
+        }
+
+        let mut engine = RewardsEngine::with_state_db(config, db.clone()).await.unwrap();
+        assert_eq!(engine.get_stats().total_distributed, 2_000);
+        let account = engine.get_account(MINER).await.unwrap().unwrap();
+        assert_eq!((account.accrued(), account.grants.len()), (1_200, 1));
+        assert_eq!(account.claimable(30), 600);
+        assert_eq!(engine.claim(MINER, 30).await.unwrap(), 600);
+        assert_eq!(engine.claim(MINER, 50).await.unwrap(), 600);
+
+        let state = db.read().await;
+        let summary = get_epoch_summary(&state, 0).unwrap().unwrap();
+        assert_eq!((summary.blocks, summary.total, summary.by_class[&RewardClass::Miner]), (2, 2_000, 1_200));
+        assert_eq!(get_reward_account(&state, MINER).unwrap().unwrap().claimed, 1_200);
+    }
+}
@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum RewardsError {
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Invalid block reward: {0}")]
+    InvalidReward(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+}
+
+impl From<state_db::error::StateDBError> for RewardsError {
+    fn from(err: state_db::error::StateDBError) -> Self {
+        RewardsError::StorageError(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for RewardsError {
+    fn from(err: serde_json::Error) -> Self {
+        RewardsError::StorageError(err.to_string())
+    }
+}
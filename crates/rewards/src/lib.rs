@@ -0,0 +1,12 @@
+//! Block reward distribution: a declarative split between the miner, validators,
+//! liquidity providers and the treasury, optional vesting per class, and per-epoch
+//! accounting persisted in the StateDB.
+
+pub mod engine;
+pub mod error;
+
+pub use engine::{
+    get_epoch_summary, get_reward_account, BlockRecipients, EpochSummary, RewardAccount, RewardClass, RewardGrant,
+    RewardSplit, RewardsConfig, RewardsEngine, RewardsStats, VestingSchedule,
+};
+pub use error::RewardsError;
@@ -0,0 +1,332 @@
+//! The `admin_` namespace: operator actions kept apart from the public RPC surface.
+//!
+//! Admin methods are off unless `AdminConfig` enables them with a token or the server
+//! has a JWT secret, and every call must present that token or a valid JWT. While the
+//! namespace is off its methods answer as if they did not exist. Failed authentication
+//! is logged and counted like any other failed request.
+
+use crate::error::RPCError;
+use crate::RPCServer;
+use async_trait::async_trait;
+use net_p2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use state_db::BackupManifest;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Access to the `admin_` namespace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    /// Bearer token admin calls may present; without it or a JWT secret the namespace stays off
+    pub token: Option<String>,
+}
+
+/// Re-reads the node's configuration and applies what can change while running
+#[async_trait]
+pub trait ConfigReload: Send + Sync {
+    /// Reload and report which settings were applied and which wait for a restart
+    async fn reload_config(&self) -> Result<serde_json::Value, String>;
+}
+
+/// Node operations the admin namespace drives
+#[async_trait]
+pub trait NodeAdmin: Send + Sync {
+    async fn start_mining(&self) -> Result<(), String>;
+
+    async fn stop_mining(&self) -> Result<(), String>;
+
+    /// Replace the validator signing key, returning the new public key
+    async fn rotate_validator_key(&self) -> Result<[u8; 32], String>;
+}
+
+/// Compare tokens in time independent of where they first differ
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+impl RPCServer {
+    /// Reject the call unless the namespace is on and `token` is the admin token or a
+    /// JWT signed with the configured secret
+    pub(crate) async fn authorize_admin(&self, method: &str, token: &str) -> Result<(), RPCError> {
+        let admin = &self.config.admin;
+        let result = if !admin.enabled || (admin.token.is_none() && self.jwt_secret.is_none()) {
+            Err(RPCError::MethodNotFound(method.to_string()))
+        } else if admin.token.as_deref().is_some_and(|expected| tokens_match(expected, token)) {
+            Ok(())
+        } else {
+            match &self.jwt_secret {
+                Some(secret) => secret.verify(token, chrono::Utc::now().timestamp() as u64),
+                None => Err(RPCError::AuthenticationError("invalid admin token".to_string())),
+            }
+        };
+        if let Err(RPCError::AuthenticationError(reason)) = &result {
+            warn!("Rejected {}: {}", method, reason);
+        }
+        if result.is_err() {
+            self.state.increment_request(false).await;
+        }
+        result
+    }
+
+    async fn admin_result(&self, result: Result<serde_json::Value, RPCError>) -> Result<serde_json::Value, RPCError> {
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    fn node_admin(&self) -> Result<&dyn NodeAdmin, RPCError> {
+        self.node_admin
+            .as_deref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("Node admin not attached".to_string()))
+    }
+
+    /// Dial a peer at `addr`
+    pub async fn admin_add_peer(&self, token: &str, addr: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_addPeer", token).await?;
+        info!("Admin: adding peer {}", addr);
+
+        let result = self.peer_command(|peers| {
+            let addr: Multiaddr = addr
+                .parse()
+                .map_err(|e| RPCError::InvalidParameters(format!("Invalid multiaddr {}: {}", addr, e)))?;
+            peers.add_peer(addr).map_err(|e| RPCError::ServiceUnavailable(e.to_string()))
+        });
+        self.admin_result(result.map(|()| serde_json::json!({ "added": addr }))).await
+    }
+
+    /// Disconnect from `peer_id`
+    pub async fn admin_remove_peer(&self, token: &str, peer_id: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_removePeer", token).await?;
+        info!("Admin: removing peer {}", peer_id);
+
+        let result = self.peer_command(|peers| {
+            peers.remove_peer(parse_peer_id(peer_id)?).map_err(|e| RPCError::ServiceUnavailable(e.to_string()))
+        });
+        self.admin_result(result.map(|()| serde_json::json!({ "removed": peer_id }))).await
+    }
+
+    /// Disconnect from `peer_id` and refuse it for `duration_secs`
+    pub async fn admin_ban_peer(
+        &self,
+        token: &str,
+        peer_id: &str,
+        duration_secs: u64,
+    ) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_banPeer", token).await?;
+        info!("Admin: banning peer {} for {}s", peer_id, duration_secs);
+
+        let result = self.peer_command(|peers| {
+            peers
+                .ban_peer(parse_peer_id(peer_id)?, Duration::from_secs(duration_secs))
+                .map_err(|e| RPCError::ServiceUnavailable(e.to_string()))
+        });
+        self.admin_result(result.map(|()| serde_json::json!({ "banned": peer_id, "durationSecs": duration_secs })))
+            .await
+    }
+
+    pub async fn admin_unban_peer(&self, token: &str, peer_id: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_unbanPeer", token).await?;
+        info!("Admin: unbanning peer {}", peer_id);
+
+        let result = self.peer_command(|peers| {
+            peers.unban_peer(parse_peer_id(peer_id)?).map_err(|e| RPCError::ServiceUnavailable(e.to_string()))
+        });
+        self.admin_result(result.map(|()| serde_json::json!({ "unbanned": peer_id }))).await
+    }
+
+    fn peer_command(
+        &self,
+        command: impl FnOnce(&net_p2p::PeerControl) -> Result<(), RPCError>,
+    ) -> Result<(), RPCError> {
+        let peers = self
+            .peer_control
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("P2P network not attached".to_string()))?;
+        command(peers)
+    }
+
+    pub async fn admin_start_mining(&self, token: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_startMining", token).await?;
+        info!("Admin: starting mining");
+
+        let result = match self.node_admin() {
+            Ok(node_admin) => node_admin.start_mining().await.map_err(RPCError::InternalError),
+            Err(e) => Err(e),
+        };
+        self.admin_result(result.map(|()| serde_json::json!({ "mining": true }))).await
+    }
+
+    pub async fn admin_stop_mining(&self, token: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_stopMining", token).await?;
+        info!("Admin: stopping mining");
+
+        let result = match self.node_admin() {
+            Ok(node_admin) => node_admin.stop_mining().await.map_err(RPCError::InternalError),
+            Err(e) => Err(e),
+        };
+        self.admin_result(result.map(|()| serde_json::json!({ "mining": false }))).await
+    }
+
+    /// Export the state at `version`, or at the finalized head, into a snapshot archive at `path`
+    pub async fn admin_export_snapshot(
+        &self,
+        token: &str,
+        path: &str,
+        version: Option<u64>,
+    ) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_exportSnapshot", token).await?;
+        info!("Admin: exporting state snapshot to {}", path);
+
+        let result = self.export_state(path, version).await;
+        self.admin_result(result).await
+    }
+
+    /// Back the state database up into a new checkpoint in the configured backup directory
+    pub async fn admin_create_backup(&self, token: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_createBackup", token).await?;
+        info!("Admin: backing up the state database");
+
+        let result = match &self.state_db {
+            Some(state_db) => state_db
+                .read()
+                .await
+                .create_backup()
+                .map(|(path, manifest)| backup_json(&path, &manifest))
+                .map_err(|e| RPCError::InternalError(e.to_string())),
+            None => Err(RPCError::ServiceUnavailable("State database not attached".to_string())),
+        };
+        self.admin_result(result).await
+    }
+
+    /// Backups in the configured backup directory, oldest first
+    pub async fn admin_list_backups(&self, token: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_listBackups", token).await?;
+
+        let result = match &self.state_db {
+            Some(state_db) => state_db::backup::list_backups(state_db.read().await.backup_dir())
+                .map(|backups| backups.iter().map(|(path, manifest)| backup_json(path, manifest)).collect())
+                .map_err(|e| RPCError::InternalError(e.to_string())),
+            None => Err(RPCError::ServiceUnavailable("State database not attached".to_string())),
+        };
+        self.admin_result(result).await
+    }
+
+    /// Switch the validator to a fresh signing key
+    pub async fn admin_rotate_validator_key(&self, token: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_rotateValidatorKey", token).await?;
+        info!("Admin: rotating validator key");
+
+        let result = match self.node_admin() {
+            Ok(node_admin) => node_admin.rotate_validator_key().await.map_err(RPCError::InternalError),
+            Err(e) => Err(e),
+        };
+        self.admin_result(result.map(|public_key| serde_json::json!({ "publicKey": hex::encode(public_key) })))
+            .await
+    }
+
+    /// Reload the node's configuration, as SIGHUP does
+    pub async fn admin_reload_config(&self, token: &str) -> Result<serde_json::Value, RPCError> {
+        self.authorize_admin("admin_reloadConfig", token).await?;
+        info!("Admin: reloading node configuration");
+
+        let result = match &self.config_reload {
+            Some(config_reload) => config_reload.reload_config().await.map_err(RPCError::ConfigError),
+            None => Err(RPCError::ServiceUnavailable("Config reload not attached".to_string())),
+        };
+        self.admin_result(result).await
+    }
+}
+
+fn backup_json(path: &Path, manifest: &BackupManifest) -> serde_json::Value {
+    serde_json::json!({
+        "path": path.display().to_string(),
+        "createdAt": manifest.created_at,
+        "version": manifest.version,
+        "root": manifest.root.map(hex::encode),
+        "files": manifest.files.len(),
+        "bytes": manifest.files.iter().map(|file| file.size).sum::<u64>(),
+    })
+}
+
+fn parse_peer_id(peer_id: &str) -> Result<PeerId, RPCError> {
+    peer_id
+        .parse()
+        .map_err(|e| RPCError::InvalidParameters(format!("Invalid peer id {}: {}", peer_id, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RPCServerConfig;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    #[derive(Default)]
+    struct StubNode {
+        mining: RwLock<bool>,
+        reloads: RwLock<u32>,
+    }
+
+    #[async_trait]
+    impl NodeAdmin for StubNode {
+        async fn start_mining(&self) -> Result<(), String> {
+            *self.mining.write().await = true;
+            Ok(())
+        }
+
+        async fn stop_mining(&self) -> Result<(), String> {
+            *self.mining.write().await = false;
+            Ok(())
+        }
+
+        async fn rotate_validator_key(&self) -> Result<[u8; 32], String> {
+            Ok([0xcd; 32])
+        }
+    }
+
+    #[async_trait]
+    impl ConfigReload for StubNode {
+        async fn reload_config(&self) -> Result<serde_json::Value, String> {
+            *self.reloads.write().await += 1;
+            Ok(serde_json::json!({ "applied": [], "requiresRestart": [] }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_methods_require_the_token() {
+        let node = Arc::new(StubNode::default());
+        let mut disabled = RPCServer::new(RPCServerConfig::default()).unwrap();
+        disabled.attach_node_admin(node.clone());
+        assert!(matches!(disabled.admin_start_mining(TOKEN).await, Err(RPCError::MethodNotFound(_))));
+
+        let config = RPCServerConfig {
+            admin: AdminConfig { enabled: true, token: Some(TOKEN.to_string()) },
+            ..Default::default()
+        };
+        let mut server = RPCServer::new(config).unwrap();
+        server.attach_node_admin(node.clone());
+        server.attach_config_reload(node.clone());
+        assert!(matches!(server.admin_start_mining("wrong").await, Err(RPCError::AuthenticationError(_))));
+        assert!(!*node.mining.read().await);
+
+        assert_eq!(server.admin_start_mining(TOKEN).await.unwrap()["mining"], true);
+        assert!(*node.mining.read().await);
+        assert_eq!(server.admin_rotate_validator_key(TOKEN).await.unwrap()["publicKey"], "cd".repeat(32));
+        assert!(server.admin_reload_config(TOKEN).await.unwrap()["requiresRestart"].as_array().unwrap().is_empty());
+        assert_eq!(*node.reloads.read().await, 1);
+
+        // Peer methods need the P2P layer, and check their parameters
+        let peer = PeerId::random().to_string();
+        assert!(matches!(server.admin_ban_peer(TOKEN, &peer, 60).await, Err(RPCError::ServiceUnavailable(_))));
+        let snapshot = server.admin_export_snapshot(TOKEN, "/tmp/x", Some(1)).await;
+        assert!(matches!(snapshot, Err(RPCError::ServiceUnavailable(_))));
+
+        let stats = server.get_stats().await;
+        assert_eq!((stats.successful_requests, stats.failed_requests), (3, 3));
+    }
+}
@@ -36,8 +36,11 @@ pub enum RPCError {
     #[error("Timeout error: {0}")]
     TimeoutError(String),
 
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
+    #[error("Rate limit exceeded, retry after {0}s")]
+    RateLimitExceeded(u64),
+
+    #[error("Request too large: {0}")]
+    RequestTooLarge(String),
 
     #[error("Authentication failed: {0}")]
     AuthenticationError(String),
@@ -79,6 +82,27 @@ impl From<tokio::time::error::Elapsed> for RPCError {
     }
 }
 
+impl RPCError {
+    /// JSON-RPC error code reported for this error
+    pub fn code(&self) -> RPCErrorCode {
+        match self {
+            RPCError::JsonRPCError(_) | RPCError::DeserializationError(_) => RPCErrorCode::ParseError,
+            RPCError::RequestTooLarge(_) => RPCErrorCode::InvalidRequest,
+            RPCError::MethodNotFound(_) => RPCErrorCode::MethodNotFound,
+            RPCError::InvalidParameters(_) => RPCErrorCode::InvalidParams,
+            RPCError::InternalError(_) | RPCError::SerializationError(_) => RPCErrorCode::InternalError,
+            RPCError::TimeoutError(_) => RPCErrorCode::Timeout,
+            RPCError::RateLimitExceeded(_) => RPCErrorCode::RateLimit,
+            RPCError::AuthenticationError(_) => RPCErrorCode::Authentication,
+            RPCError::AuthorizationError(_) => RPCErrorCode::Authorization,
+            RPCError::NotFound(_) => RPCErrorCode::NotFound,
+            RPCError::BadRequest(_) => RPCErrorCode::BadRequest,
+            RPCError::ServiceUnavailable(_) => RPCErrorCode::ServiceUnavailable,
+            _ => RPCErrorCode::ServerError,
+        }
+    }
+}
+
 /// JSON-RPC error codes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RPCErrorCode {
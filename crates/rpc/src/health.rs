@@ -0,0 +1,389 @@
+//! Liveness and readiness probes for orchestration systems.
+//!
+//! `/health/live` answers as long as the server's runtime does. `/health/ready`
+//! checks the subsystems the node depends on and reports every check, so an
+//! operator can see which dependency is holding the node back. Checks whose
+//! subsystem is not attached or configured are skipped rather than failed,
+//! except the state database, which every node needs. When the node's task supervisor is
+//! attached, each supervised task adds a check that fails once the task has died
+//! for good.
+
+use crate::error::RPCError;
+use crate::RPCServer;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head the health server reads before answering
+const MAX_REQUEST_BYTES: usize = 4096;
+
+/// Readiness thresholds and the external endpoints to probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// Fewest connected peers for the node to count as ready
+    pub min_peers: usize,
+    /// Most blocks the node may trail its sync target by
+    pub max_sync_lag: u64,
+    /// Fuego daemon RPC endpoint, probed when set
+    pub fuego_rpc_url: Option<String>,
+    /// L1 RPC endpoint, probed when set
+    pub l1_rpc_url: Option<String>,
+    /// How long each check may take before it fails
+    pub check_timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            min_peers: 1,
+            max_sync_lag: 10,
+            fuego_rpc_url: None,
+            l1_rpc_url: None,
+            check_timeout_ms: 2000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+/// Outcome of one dependency check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+/// Overall status with the checks behind it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub status: CheckStatus,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    fn from_checks(checks: Vec<HealthCheck>) -> Self {
+        let failed = checks.iter().any(|check| check.status == CheckStatus::Fail);
+        Self {
+            status: if failed { CheckStatus::Fail } else { CheckStatus::Pass },
+            checks,
+        }
+    }
+}
+
+/// Long-running node tasks whose health the readiness probe reports
+#[async_trait]
+pub trait TaskHealth: Send + Sync {
+    /// One check per task, failing for tasks that died and will not be restarted
+    async fn task_checks(&self) -> Vec<HealthCheck>;
+}
+
+/// `host:port` of an `http://` or `https://` URL
+fn endpoint_address(url: &str) -> Option<String> {
+    let (rest, default_port) = match url.split_once("://") {
+        Some(("https", rest)) => (rest, 443),
+        Some((_, rest)) => (rest, 80),
+        None => (url, 80),
+    };
+    let authority = rest.split(['/', '?']).next().filter(|authority| !authority.is_empty())?;
+    let authority = authority.rsplit('@').next()?;
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok() && !authority.ends_with(']'));
+    Some(if has_port { authority.to_string() } else { format!("{}:{}", authority, default_port) })
+}
+
+/// Run `check` under `timeout`, timing it
+async fn run_check<F>(name: &str, timeout: Duration, check: F) -> HealthCheck
+where
+    F: std::future::Future<Output = (CheckStatus, String)>,
+{
+    let started = Instant::now();
+    let (status, detail) = tokio::time::timeout(timeout, check)
+        .await
+        .unwrap_or_else(|_| (CheckStatus::Fail, format!("timed out after {:?}", timeout)));
+    HealthCheck {
+        name: name.to_string(),
+        status,
+        detail,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Check that a TCP connection to `url` can be opened
+async fn check_endpoint(url: Option<&str>) -> (CheckStatus, String) {
+    let Some(url) = url else {
+        return (CheckStatus::Skip, "not configured".to_string());
+    };
+    let Some(address) = endpoint_address(url) else {
+        return (CheckStatus::Fail, format!("cannot parse {}", url));
+    };
+    match TcpStream::connect(&address).await {
+        Ok(_) => (CheckStatus::Pass, format!("{} reachable", address)),
+        Err(e) => (CheckStatus::Fail, format!("{} unreachable: {}", address, e)),
+    }
+}
+
+impl RPCServer {
+    /// Liveness: the server is running and answering
+    pub async fn health_live(&self) -> HealthReport {
+        let uptime = self.state.stats.read().await.uptime_seconds;
+        HealthReport::from_checks(vec![HealthCheck {
+            name: "process".to_string(),
+            status: CheckStatus::Pass,
+            detail: format!("up {}s", uptime),
+            duration_ms: 0,
+        }])
+    }
+
+    /// Readiness: every attached dependency is reachable and the node is caught up
+    pub async fn health_ready(&self) -> HealthReport {
+        let config = &self.config.health;
+        let timeout = Duration::from_millis(config.check_timeout_ms);
+        let mut checks = Vec::new();
+
+        checks.push(
+            run_check("state_db", timeout, async {
+                let Some(state_db) = &self.state_db else {
+                    return (CheckStatus::Fail, "not attached".to_string());
+                };
+                let state = state_db.read().await;
+                match state.storage_stats() {
+                    Ok(stats) => (
+                        CheckStatus::Pass,
+                        format!("version {:?}, ~{} keys", state.latest_version(), stats.estimated_keys),
+                    ),
+                    Err(e) => (CheckStatus::Fail, e.to_string()),
+                }
+            })
+            .await,
+        );
+
+        let network = match &self.network_info {
+            Some(network_info) => Some(network_info.read().await.clone()),
+            None => None,
+        };
+        checks.push(
+            run_check("p2p_listening", timeout, async {
+                match &network {
+                    None => (CheckStatus::Skip, "network not attached".to_string()),
+                    Some(info) if info.listen_addrs.is_empty() => {
+                        (CheckStatus::Fail, "no listen address".to_string())
+                    }
+                    Some(info) => (CheckStatus::Pass, info.listen_addrs.join(", ")),
+                }
+            })
+            .await,
+        );
+        checks.push(
+            run_check("peers", timeout, async {
+                match &network {
+                    None => (CheckStatus::Skip, "network not attached".to_string()),
+                    Some(info) => {
+                        let status = if info.connected_peers >= config.min_peers {
+                            CheckStatus::Pass
+                        } else {
+                            CheckStatus::Fail
+                        };
+                        (status, format!("{} connected, {} required", info.connected_peers, config.min_peers))
+                    }
+                }
+            })
+            .await,
+        );
+
+        checks.push(run_check("fuego_rpc", timeout, check_endpoint(config.fuego_rpc_url.as_deref())).await);
+        checks.push(run_check("l1_rpc", timeout, check_endpoint(config.l1_rpc_url.as_deref())).await);
+
+        checks.push(
+            run_check("sync_lag", timeout, async {
+                let (Some(target), Some(state_db)) = (&self.sync_target, &self.state_db) else {
+                    return (CheckStatus::Skip, "no sync target attached".to_string());
+                };
+                let height = state_db.read().await.latest_version().unwrap_or(0);
+                let lag = target.load(Ordering::Relaxed).saturating_sub(height);
+                let status = if lag <= config.max_sync_lag { CheckStatus::Pass } else { CheckStatus::Fail };
+                (status, format!("{} blocks behind, at most {} allowed", lag, config.max_sync_lag))
+            })
+            .await,
+        );
+
+        if let Some(task_health) = &self.task_health {
+            checks.extend(task_health.task_checks().await);
+        }
+
+        HealthReport::from_checks(checks)
+    }
+}
+
+/// Serves `GET /health/live` and `GET /health/ready` over plain HTTP, answering
+/// 200 when the report passes and 503 when it fails
+pub struct HealthServer {
+    listener: TcpListener,
+    server: Arc<RPCServer>,
+}
+
+impl HealthServer {
+    pub async fn bind(addr: &str, server: Arc<RPCServer>) -> Result<Self, RPCError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| RPCError::HTTPError(format!("Failed to bind {}: {}", addr, e)))?;
+        Ok(Self { listener, server })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, RPCError> {
+        self.listener.local_addr().map_err(|e| RPCError::HTTPError(e.to_string()))
+    }
+
+    /// Answer probes until the task is dropped
+    pub async fn run(self) {
+        loop {
+            let stream = match self.listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Health server accept failed: {}", e);
+                    continue;
+                }
+            };
+            let server = self.server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, &server).await {
+                    eprintln!("Health request failed: {}", e);
+                }
+            });
+        }
+    }
+}
+
+async fn respond(mut stream: TcpStream, server: &RPCServer) -> Result<(), RPCError> {
+    let io_error = |e: std::io::Error| RPCError::HTTPError(e.to_string());
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let read = stream.read(&mut buffer).await.map_err(io_error)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request_line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+    let mut parts = request_line.split_whitespace();
+    let report = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/health/live")) => Some(server.health_live().await),
+        (Some("GET"), Some("/health/ready")) => Some(server.health_ready().await),
+        _ => None,
+    };
+    let response = match report {
+        Some(report) => {
+            let body = serde_json::to_string(&report)?;
+            let status = match report.status {
+                CheckStatus::Fail => "503 Service Unavailable",
+                _ => "200 OK",
+            };
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+        }
+        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await.map_err(io_error)?;
+    stream.shutdown().await.map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RPCServerConfig;
+    use net_p2p::NetworkInfo;
+    use state_db::RocksStateDB;
+    use tokio::sync::RwLock;
+
+    async fn get(addr: SocketAddr, path: &str) -> (String, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(format!("GET {} HTTP/1.1\r\n\r\n", path).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), serde_json::from_str(body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoints_report_each_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let l1 = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let config = RPCServerConfig {
+            health: HealthConfig {
+                min_peers: 2,
+                fuego_rpc_url: Some(format!("http://{}/json_rpc", closed)),
+                l1_rpc_url: Some(format!("http://{}", l1.local_addr().unwrap())),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut server = RPCServer::new(config).unwrap();
+        server.attach_state_db(Arc::new(RwLock::new(RocksStateDB::new(dir.path()).unwrap())));
+        let network_info: NetworkInfo = serde_json::from_value(serde_json::json!({
+            "peer_id": "12D3KooWTest",
+            "listen_addrs": ["/ip4/127.0.0.1/tcp/4001"],
+            "observed_addrs": [],
+            "external_addrs": [],
+            "nat_status": "Unknown",
+            "relay_enabled": false,
+            "hole_punching_enabled": false,
+            "direct_upgrades": 0,
+            "connected_peers": 1,
+            "discovered_peers": 0,
+            "bootstrap_attempts": 0,
+            "transport_security": "Noise",
+            "private_network": null,
+            "dandelion_enabled": true,
+            "transactions_stemmed": 0,
+            "transactions_fluffed": 0
+        }))
+        .unwrap();
+        let network_info = Arc::new(RwLock::new(network_info));
+        server.attach_network(network_info.clone());
+
+        let health = HealthServer::bind("127.0.0.1:0", Arc::new(server)).await.unwrap();
+        let addr = health.local_addr().unwrap();
+        tokio::spawn(health.run());
+
+        let (status, live) = get(addr, "/health/live").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(live["status"], "pass");
+
+        let (status, ready) = get(addr, "/health/ready").await;
+        assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+        let check = |name: &str| {
+            ready["checks"].as_array().unwrap().iter().find(|check| check["name"] == name).unwrap()["status"].clone()
+        };
+        assert_eq!(check("state_db"), "pass");
+        assert_eq!(check("p2p_listening"), "pass");
+        assert_eq!(check("peers"), "fail");
+        assert_eq!(check("fuego_rpc"), "fail");
+        assert_eq!(check("l1_rpc"), "pass");
+        assert_eq!(check("sync_lag"), "skip");
+
+        assert_eq!(endpoint_address("https://user@l1.example.org/rpc").unwrap(), "l1.example.org:443");
+        assert!(get(addr, "/health").await.0.starts_with("HTTP/1.1 404"));
+    }
+}
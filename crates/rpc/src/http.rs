@@ -0,0 +1,481 @@
+//! JSON-RPC 2.0 over HTTP POST, served over TLS when the operator provides a
+//! certificate and key.
+//!
+//! Every request passes [`RPCServer::admit_request`] before it is dispatched.
+//! Privileged methods, the `admin_`, `snapshot_` and `exchange_` namespaces, read their
+//! credential from the `Authorization: Bearer` header: either the admin token or
+//! a JWT signed with the configured shared secret. There is no WebSocket transport.
+
+use crate::error::RPCError;
+use crate::limits::rejection_response;
+use crate::RPCServer;
+use serde_json::Value;
+use std::io::BufReader;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::debug;
+
+/// Largest request line and headers read before the body
+const MAX_HEAD_BYTES: usize = 8192;
+
+/// Build a TLS acceptor from PEM files holding the certificate chain and its private key
+pub fn load_tls(cert: &Path, key: &Path) -> Result<TlsAcceptor, RPCError> {
+    let tls_error = |path: &Path, e: String| RPCError::ConfigError(format!("TLS {}: {}", path.display(), e));
+    let open = |path: &Path| {
+        std::fs::File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| tls_error(path, e.to_string()))
+    };
+
+    let certs = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| tls_error(cert, e.to_string()))?;
+    if certs.is_empty() {
+        return Err(tls_error(cert, "no certificates found".to_string()));
+    }
+    let private_key = rustls_pemfile::private_key(&mut open(key)?)
+        .map_err(|e| tls_error(key, e.to_string()))?
+        .ok_or_else(|| tls_error(key, "no private key found".to_string()))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| RPCError::ConfigError(e.to_string()))?
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(|e| tls_error(cert, e.to_string()))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serves JSON-RPC requests on `POST /`, one request per connection
+pub struct RpcHttpServer {
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    server: Arc<RPCServer>,
+}
+
+impl RpcHttpServer {
+    /// Bind `addr`, serving TLS when the server's config names a certificate and key
+    pub async fn bind(addr: &str, server: Arc<RPCServer>) -> Result<Self, RPCError> {
+        let tls = match (&server.config.tls_cert, &server.config.tls_key) {
+            (Some(cert), Some(key)) => Some(load_tls(cert, key)?),
+            (None, None) => None,
+            _ => return Err(RPCError::ConfigError("tls_cert and tls_key must be set together".to_string())),
+        };
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| RPCError::HTTPError(format!("Failed to bind {}: {}", addr, e)))?;
+        Ok(Self { listener, tls, server })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, RPCError> {
+        self.listener.local_addr().map_err(|e| RPCError::HTTPError(e.to_string()))
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Answer requests until the task is dropped
+    pub async fn run(self) {
+        loop {
+            let (stream, peer) = match self.listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("RPC server accept failed: {}", e);
+                    continue;
+                }
+            };
+            let server = self.server.clone();
+            let tls = self.tls.clone();
+            tokio::spawn(async move {
+                let result = match tls {
+                    Some(tls) => match tls.accept(stream).await {
+                        Ok(stream) => respond(stream, peer.ip(), &server).await,
+                        Err(e) => Err(RPCError::HTTPError(format!("TLS handshake failed: {}", e))),
+                    },
+                    None => respond(stream, peer.ip(), &server).await,
+                };
+                if let Err(e) = result {
+                    debug!("RPC request from {} failed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+/// Positional JSON-RPC parameters
+struct Params<'a>(&'a [Value]);
+
+impl Params<'_> {
+    fn value(&self, index: usize) -> &Value {
+        self.0.get(index).unwrap_or(&Value::Null)
+    }
+
+    fn str(&self, index: usize) -> Result<&str, RPCError> {
+        self.value(index)
+            .as_str()
+            .ok_or_else(|| RPCError::InvalidParameters(format!("Parameter {} must be a string", index)))
+    }
+
+    fn opt_str(&self, index: usize) -> Result<Option<&str>, RPCError> {
+        match self.value(index) {
+            Value::Null => Ok(None),
+            _ => self.str(index).map(Some),
+        }
+    }
+
+    fn u64(&self, index: usize) -> Result<u64, RPCError> {
+        self.value(index)
+            .as_u64()
+            .ok_or_else(|| RPCError::InvalidParameters(format!("Parameter {} must be an unsigned integer", index)))
+    }
+
+    fn bool(&self, index: usize) -> Result<bool, RPCError> {
+        self.value(index)
+            .as_bool()
+            .ok_or_else(|| RPCError::InvalidParameters(format!("Parameter {} must be a boolean", index)))
+    }
+
+    fn opt_u64(&self, index: usize) -> Result<Option<u64>, RPCError> {
+        match self.value(index) {
+            Value::Null => Ok(None),
+            _ => self.u64(index).map(Some),
+        }
+    }
+}
+
+/// Route `method` to the server, passing `bearer` as the credential of privileged methods
+async fn dispatch(server: &RPCServer, method: &str, params: Params<'_>, bearer: &str) -> Result<Value, RPCError> {
+    match method {
+        "getNodeStatus" => server.get_node_status().await,
+        "getBlockchainInfo" => server.get_blockchain_info().await,
+        "getBridgeStatus" => server.get_bridge_status().await,
+        "getNetworkInfo" => server.get_network_info().await,
+        "net_peerStats" => server.net_peer_stats().await,
+        "getConsensusStatus" => server.get_consensus_status().await,
+        "getMiningWorkers" => server.get_mining_workers().await,
+        "getMiningStaleBlocks" => server.get_mining_stale_blocks(params.opt_u64(0)?.unwrap_or(10) as usize).await,
+        "getMiningPayouts" => server.get_mining_payouts(params.opt_u64(0)?.unwrap_or(10) as usize).await,
+        "getFinalizedHead" => server.get_finalized_head().await,
+        "getNetworkTime" => server.get_network_time().await,
+        "getState" => server.get_state(params.str(0)?, params.opt_u64(1)?).await,
+        "getSupply" => server.get_supply().await,
+        "getTokenBalance" => server.get_token_balance(params.str(0)?, params.str(1)?).await,
+        "getHeadersRange" => server.get_headers_range(params.u64(0)?, params.u64(1)?).await,
+        "getStateProof" => server.get_state_proof(params.str(0)?, params.u64(1)?).await,
+        "tx_getSigningHashes" => server.tx_get_signing_hashes(params.value(0), params.opt_str(1)?).await,
+        "tx_sponsor" => server.tx_sponsor(params.value(0), params.str(1)?, params.str(2)?).await,
+        "submitBlock" => server.submit_block(params.str(0)?, params.u64(1)?, params.str(2)?).await,
+        "proof_status" => server.proof_status(params.u64(0)?).await,
+        "eth_getTransactionReceipt" => server.eth_get_transaction_receipt(params.str(0)?).await,
+        "eth_getLogs" => server.eth_get_logs(params.value(0)).await,
+        "debug_traceTransaction" => server.debug_trace_transaction(params.str(0)?).await,
+        "debug_traceBlock" => server.debug_trace_block(params.u64(0)?).await,
+        "bridge_getWithdrawalProof" => {
+            let log_index = u32::try_from(params.u64(1)?)
+                .map_err(|_| RPCError::InvalidParameters("Log index is out of range".to_string()))?;
+            server.bridge_get_withdrawal_proof(params.str(0)?, log_index).await
+        }
+        "bridge_getProofSubmission" => server.bridge_get_proof_submission(params.str(0)?).await,
+        "bridge_getProofCosts" => server.bridge_get_proof_costs().await,
+        "bridge_getReorgIncidents" => server.bridge_get_reorg_incidents().await,
+        "bridge_listTransfers" => server.bridge_list_transfers(params.opt_str(0)?, params.opt_str(1)?).await,
+        "bridge_listChallenges" => server.bridge_list_challenges(params.opt_u64(0)?, params.opt_str(1)?).await,
+        "bridge_getChallenge" => server.bridge_get_challenge(params.u64(0)?).await,
+        // Challengers sign their moves, and only authenticated clients may relay them
+        "bridge_openChallenge" => {
+            server.authorize_admin(method, bearer).await?;
+            server.bridge_open_challenge(params.u64(0)?, params.str(1)?, params.str(2)?, params.str(3)?).await
+        }
+        "bridge_chooseChallengeHalf" => {
+            server.authorize_admin(method, bearer).await?;
+            server.bridge_choose_challenge_half(params.u64(0)?, params.bool(1)?, params.str(2)?).await
+        }
+        // The node defends the batches it committed
+        "bridge_bisectChallenge" => {
+            server.authorize_admin(method, bearer).await?;
+            server.bridge_bisect_challenge(params.u64(0)?, params.str(1)?).await
+        }
+        "bridge_proveChallengeStep" => {
+            server.authorize_admin(method, bearer).await?;
+            server.bridge_prove_challenge_step(params.u64(0)?, params.str(1)?).await
+        }
+        "eldernode_list" => server.eldernode_list().await,
+        "eldernode_getNode" => server.eldernode_get_node(params.str(0)?).await,
+        "eldernode_getBlockServers" => server.eldernode_get_block_servers(params.u64(0)?).await,
+        "fees_getBaseFeeHistory" => server.fees_get_base_fee_history(params.u64(0)?, params.u64(1)?).await,
+        "rewards_getRewards" => server.rewards_get_rewards(params.str(0)?).await,
+        "rewards_getEpoch" => server.rewards_get_epoch(params.u64(0)?).await,
+        "privacy_getNoteWitness" => server.privacy_get_note_witness(params.str(0)?).await,
+        "privacy_scanOutputs" => {
+            server
+                .privacy_scan_outputs(params.str(0)?, params.str(1)?, params.u64(2)?, params.u64(3)?)
+                .await
+        }
+        "privacy_exportAuditReport" => {
+            server.privacy_export_audit_report(params.str(0)?, params.u64(1)?, params.u64(2)?).await
+        }
+        "privacy_verifyAuditReport" => server.privacy_verify_audit_report(params.value(0), params.opt_str(1)?).await,
+        // Snapshots read and write files on the node's host
+        "snapshot_export" => {
+            server.authorize_admin(method, bearer).await?;
+            server.snapshot_export(params.str(0)?, params.opt_u64(1)?).await
+        }
+        "snapshot_import" => {
+            server.authorize_admin(method, bearer).await?;
+            server.snapshot_import(params.str(0)?, params.opt_str(1)?).await
+        }
+        // Deposit tracking holds scan secrets and maps addresses to an exchange's users
+        "exchange_registerViewKey" => {
+            server.authorize_admin(method, bearer).await?;
+            server.exchange_register_view_key(params.str(0)?, params.str(1)?, params.str(2)?).await
+        }
+        "exchange_newDepositAddress" => {
+            server.authorize_admin(method, bearer).await?;
+            server.exchange_new_deposit_address(params.str(0)?).await
+        }
+        "exchange_getDeposits" => {
+            server.authorize_admin(method, bearer).await?;
+            server.exchange_get_deposits(params.u64(0)?, params.u64(1)?, params.opt_str(2)?).await
+        }
+        "admin_addPeer" => server.admin_add_peer(bearer, params.str(0)?).await,
+        "admin_removePeer" => server.admin_remove_peer(bearer, params.str(0)?).await,
+        "admin_banPeer" => server.admin_ban_peer(bearer, params.str(0)?, params.u64(1)?).await,
+        "admin_unbanPeer" => server.admin_unban_peer(bearer, params.str(0)?).await,
+        "admin_startMining" => server.admin_start_mining(bearer).await,
+        "admin_stopMining" => server.admin_stop_mining(bearer).await,
+        "admin_exportSnapshot" => server.admin_export_snapshot(bearer, params.str(0)?, params.opt_u64(1)?).await,
+        "admin_createBackup" => server.admin_create_backup(bearer).await,
+        "admin_listBackups" => server.admin_list_backups(bearer).await,
+        "admin_rotateValidatorKey" => server.admin_rotate_validator_key(bearer).await,
+        "admin_reloadConfig" => server.admin_reload_config(bearer).await,
+        _ => {
+            server.state.increment_request(false).await;
+            Err(RPCError::MethodNotFound(method.to_string()))
+        }
+    }
+}
+
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn error_body(id: &Value, error: &RPCError) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code().as_i32(), "message": error.to_string() },
+    })
+    .to_string()
+}
+
+async fn respond<S>(mut stream: S, peer: IpAddr, server: &RPCServer) -> Result<(), RPCError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let response = read_and_handle(&mut stream, peer, server).await?;
+    let io_error = |e: std::io::Error| RPCError::HTTPError(e.to_string());
+    stream.write_all(response.as_bytes()).await.map_err(io_error)?;
+    stream.shutdown().await.map_err(io_error)
+}
+
+async fn read_and_handle<S>(stream: &mut S, peer: IpAddr, server: &RPCServer) -> Result<String, RPCError>
+where
+    S: AsyncRead + Unpin,
+{
+    let io_error = |e: std::io::Error| RPCError::HTTPError(e.to_string());
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let head_end = loop {
+        if let Some(position) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if request.len() >= MAX_HEAD_BYTES {
+            return Ok(http_response("431 Request Header Fields Too Large", ""));
+        }
+        let read = stream.read(&mut buffer).await.map_err(io_error)?;
+        if read == 0 {
+            return Err(RPCError::HTTPError("connection closed before the request ended".to_string()));
+        }
+        request.extend_from_slice(&buffer[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+    let mut lines = head.lines();
+    if !lines.next().is_some_and(|line| line.starts_with("POST ")) {
+        return Ok(http_response("405 Method Not Allowed", ""));
+    }
+    let header = |name: &str| {
+        head.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+        })
+    };
+    let Some(body_len) = header("content-length").and_then(|len| len.parse::<usize>().ok()) else {
+        return Ok(http_response("411 Length Required", ""));
+    };
+    let bearer = header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer ").map(str::to_string))
+        .unwrap_or_default();
+
+    // Refuse oversized bodies before reading them; the size check spends no rate-limit tokens
+    if body_len > server.config.rate_limit.max_body_bytes {
+        if let Err(e) = server.admit_request(peer, "", body_len).await {
+            return Ok(rejection_response(&e));
+        }
+    }
+    let mut body = request.split_off(head_end);
+    if body.len() < body_len {
+        let start = body.len();
+        body.resize(body_len, 0);
+        stream.read_exact(&mut body[start..]).await.map_err(io_error)?;
+    }
+    body.truncate(body_len);
+
+    let call: Value = match serde_json::from_slice(&body) {
+        Ok(call) => call,
+        Err(e) => {
+            let error = RPCError::JsonRPCError(e.to_string());
+            return Ok(http_response("200 OK", &error_body(&Value::Null, &error)));
+        }
+    };
+    let id = call["id"].clone();
+    let Some(method) = call["method"].as_str() else {
+        let error = RPCError::BadRequest("request has no method".to_string());
+        return Ok(http_response("200 OK", &error_body(&id, &error)));
+    };
+    if let Err(e) = server.admit_request(peer, method, body_len).await {
+        return Ok(rejection_response(&e));
+    }
+
+    let params = match &call["params"] {
+        Value::Array(params) => params.as_slice(),
+        _ => &[],
+    };
+    let response = match dispatch(server, method, Params(params), &bearer).await {
+        Ok(result) => {
+            let body = serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            http_response("200 OK", &body.to_string())
+        }
+        Err(e @ RPCError::AuthenticationError(_)) => http_response("401 Unauthorized", &error_body(&id, &e)),
+        Err(e) => http_response("200 OK", &error_body(&id, &e)),
+    };
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jwt::JwtSecret;
+    use crate::{AdminConfig, RPCServerConfig};
+    use tokio::net::TcpStream;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    fn now() -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    async fn call<S>(mut stream: S, method: &str, bearer: Option<&str>) -> (String, Value)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let body = serde_json::json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": [] }).to_string();
+        let auth = bearer.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+        let request = format!("POST / HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", auth, body.len(), body);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8(response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), serde_json::from_str(body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_privileged_methods_take_the_admin_token_or_a_jwt() {
+        let dir = tempfile::tempdir().unwrap();
+        let jwt_path = dir.path().join("jwt.hex");
+        let secret = JwtSecret::new([7; 32]);
+        std::fs::write(&jwt_path, hex::encode([7u8; 32])).unwrap();
+        let config = RPCServerConfig {
+            admin: AdminConfig { enabled: true, token: Some("0123456789abcdef".to_string()) },
+            jwt_secret: Some(jwt_path),
+            ..Default::default()
+        };
+        let server = RpcHttpServer::bind("127.0.0.1:0", Arc::new(RPCServer::new(config).unwrap())).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        assert!(!server.is_tls());
+        tokio::spawn(server.run());
+        let connect = || TcpStream::connect(addr);
+
+        let (status, response) = call(connect().await.unwrap(), "getNodeStatus", None).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!((response["id"].clone(), response["result"]["status"].clone()), (7.into(), "running".into()));
+
+        let (status, response) = call(connect().await.unwrap(), "admin_reloadConfig", None).await;
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+        assert_eq!(response["error"]["code"], -32003);
+        let stale = secret.sign(now() - 120);
+        assert!(call(connect().await.unwrap(), "admin_reloadConfig", Some(&stale)).await.0.contains("401"));
+        for method in ["bridge_openChallenge", "bridge_chooseChallengeHalf"] {
+            assert_eq!(call(connect().await.unwrap(), method, None).await.0, "HTTP/1.1 401 Unauthorized");
+        }
+
+        // Authorized calls reach the method, which reports the reloader is not attached
+        for bearer in [secret.sign(now()), "0123456789abcdef".to_string()] {
+            let (status, response) = call(connect().await.unwrap(), "admin_reloadConfig", Some(&bearer)).await;
+            assert_eq!(status, "HTTP/1.1 200 OK");
+            assert_eq!(response["error"]["code"], -32007);
+        }
+        let (_, response) = call(connect().await.unwrap(), "eth_unknown", None).await;
+        assert_eq!(response["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn test_serves_over_tls() {
+        let dir = tempfile::tempdir().unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let (cert_path, key_path) = (dir.path().join("rpc.crt"), dir.path().join("rpc.key"));
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+        let config = RPCServerConfig {
+            tls_cert: Some(cert_path.clone()),
+            ..Default::default()
+        };
+        let server = Arc::new(RPCServer::new(config.clone()).unwrap());
+        assert!(matches!(RpcHttpServer::bind("127.0.0.1:0", server).await, Err(RPCError::ConfigError(_))));
+
+        let config = RPCServerConfig { tls_key: Some(key_path), ..config };
+        let server = RpcHttpServer::bind("127.0.0.1:0", Arc::new(RPCServer::new(config).unwrap())).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        assert!(server.is_tls());
+        tokio::spawn(server.run());
+
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(certified.cert.der().to_vec())).unwrap();
+        let client = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TlsConnector::from(Arc::new(client))
+            .connect(ServerName::try_from("localhost").unwrap(), TcpStream::connect(addr).await.unwrap())
+            .await
+            .unwrap();
+        let (status, response) = call(stream, "getNodeStatus", None).await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(response["result"]["status"], "running");
+    }
+}
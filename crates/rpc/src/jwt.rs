@@ -0,0 +1,106 @@
+//! Engine-API style JWT authentication: HS256 tokens signed with a 32-byte shared
+//! secret, accepted only while their `iat` claim is within a minute of the node's clock.
+
+use crate::error::RPCError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::Path;
+
+/// How far a token's `iat` may be from the node's clock
+const MAX_IAT_DRIFT_SECS: u64 = 60;
+
+/// Shared secret for signing and checking bearer tokens
+#[derive(Clone)]
+pub struct JwtSecret([u8; 32]);
+
+impl std::fmt::Debug for JwtSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("JwtSecret(..)")
+    }
+}
+
+impl JwtSecret {
+    pub fn new(secret: [u8; 32]) -> Self {
+        Self(secret)
+    }
+
+    /// Parse 32 hex-encoded bytes, with or without a `0x` prefix
+    pub fn from_hex(value: &str) -> Result<Self, RPCError> {
+        let bytes = hex::decode(value.trim().trim_start_matches("0x"))
+            .map_err(|e| RPCError::ConfigError(format!("Invalid JWT secret: {}", e)))?;
+        let secret = <[u8; 32]>::try_from(bytes)
+            .map_err(|_| RPCError::ConfigError("JWT secret must be 32 hex-encoded bytes".to_string()))?;
+        Ok(Self(secret))
+    }
+
+    /// Read a hex-encoded secret file, as shared with the consensus client
+    pub fn from_file(path: &Path) -> Result<Self, RPCError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| RPCError::ConfigError(format!("Failed to read JWT secret {}: {}", path.display(), e)))?;
+        Self::from_hex(&contents)
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts any key length")
+    }
+
+    /// HS256 token issued at `iat`, in seconds since the Unix epoch
+    pub fn sign(&self, iat: u64) -> String {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(serde_json::json!({ "iat": iat }).to_string());
+        let mut mac = self.mac();
+        mac.update(format!("{}.{}", header, claims).as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}.{}", header, claims, signature)
+    }
+
+    /// Check `token`'s signature and that it was issued within a minute of `now`
+    pub fn verify(&self, token: &str, now: u64) -> Result<(), RPCError> {
+        let invalid = |reason: &str| RPCError::AuthenticationError(format!("invalid JWT: {}", reason));
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header_segment, claims_segment, signature] = parts[..] else {
+            return Err(invalid("expected three segments"));
+        };
+
+        let decode = |segment: &str| URL_SAFE_NO_PAD.decode(segment).map_err(|_| invalid("bad base64"));
+        let header: serde_json::Value =
+            serde_json::from_slice(&decode(header_segment)?).map_err(|_| invalid("bad header"))?;
+        if header["alg"] != "HS256" {
+            return Err(invalid("algorithm must be HS256"));
+        }
+        let mut mac = self.mac();
+        mac.update(format!("{}.{}", header_segment, claims_segment).as_bytes());
+        mac.verify_slice(&decode(signature)?).map_err(|_| invalid("signature mismatch"))?;
+
+        let claims: serde_json::Value =
+            serde_json::from_slice(&decode(claims_segment)?).map_err(|_| invalid("bad claims"))?;
+        let iat = claims["iat"].as_u64().ok_or_else(|| invalid("missing iat"))?;
+        if iat.abs_diff(now) > MAX_IAT_DRIFT_SECS {
+            return Err(invalid("iat is more than 60s from the node's clock"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jwt_round_trip_and_rejections() {
+        let secret = JwtSecret::from_hex(&format!("0x{}", "ab".repeat(32))).unwrap();
+        let token = secret.sign(1_000);
+        secret.verify(&token, 1_030).unwrap();
+
+        assert!(secret.verify(&token, 1_061).is_err());
+        assert!(JwtSecret::new([1; 32]).verify(&token, 1_000).is_err());
+        let (unsigned, _) = token.rsplit_once('.').unwrap();
+        assert!(secret.verify(unsigned, 1_000).is_err());
+        let claims = unsigned.split('.').nth(1).unwrap();
+        let none_alg = format!("{}.{}.", URL_SAFE_NO_PAD.encode(br#"{"alg":"none"}"#), claims);
+        assert!(secret.verify(&none_alg, 1_000).is_err());
+        assert!(JwtSecret::from_hex("abcd").is_err());
+    }
+}
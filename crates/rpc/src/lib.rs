@@ -1,11 +1,48 @@
 use anyhow::Result;
+use block_sync::{Block, BlockHeader, Canonical, FeePayer, Transaction};
+use bridge::challenges::{Challenge, ChallengeStatus};
+use bridge::deposits::ReorgIncident;
+use bridge::error::BridgeError;
+use bridge::submission::{SubmissionCostStats, SubmissionRecord, SubmissionState};
+use bridge::transfers::{BridgeTransfer, TransferKind, TransferStatus};
+use bridge::withdrawals::WithdrawalProof;
+use commitments::note_tree::NoteWitness;
+use consensus::finality::FinalityGadget;
+use consensus::error::ConsensusError;
+use consensus::{BlockProposal, Consensus, NetworkTime};
+use execution::{
+    AuditReport, BlockExecutor, BlockFees, DepositAddress, DepositViewKey, DetectedDeposit, Eldernode, EldernodeShare,
+    ExecutionError, Log, LogFilter, Receipt, ReceiptStatus, StealthOutputRecord, TransactionTrace,
+};
+use mining::{PplnsLedger, StaleTracker, WorkerStats};
+use rewards::{EpochSummary, RewardAccount};
+use net_p2p::{BandwidthTracker, NetworkInfo, PeerControl};
 use serde::{Deserialize, Serialize};
+use state_db::error::StateDBError;
+use state_db::merkle::SparseMerkleProof;
+use state_db::snapshot::SnapshotManifest;
+use state_db::RocksStateDB;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
+use tokio::sync::RwLock;
 use tracing::{debug, info};
+use zk_proofs::{JobStatus, ProofJobInfo, ProofPriority, ProverService, ViewKey, ViewingKey};
 
+pub mod admin;
 pub mod error;
+pub mod health;
+pub mod http;
+pub mod jwt;
+pub mod limits;
 
+pub use admin::{AdminConfig, ConfigReload, NodeAdmin};
 use error::RPCError;
+pub use health::{CheckStatus, HealthCheck, HealthConfig, HealthReport, HealthServer, TaskHealth};
+pub use http::RpcHttpServer;
+pub use jwt::JwtSecret;
+pub use limits::{rejection_response, RateLimitConfig, RateLimiter};
 
 /// RPC server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +53,21 @@ pub struct RPCServerConfig {
     pub max_connections: usize,
     pub request_timeout: u64,
     pub enable_metrics: bool,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// PEM certificate chain; with `tls_key`, the HTTP endpoint serves TLS
+    #[serde(default)]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for `tls_cert`
+    #[serde(default)]
+    pub tls_key: Option<PathBuf>,
+    /// File holding a hex-encoded 32-byte secret; privileged methods then accept HS256 JWTs signed with it
+    #[serde(default)]
+    pub jwt_secret: Option<PathBuf>,
 }
 
 impl Default for RPCServerConfig {
@@ -27,6 +79,12 @@ impl Default for RPCServerConfig {
             max_connections: 1000,
             request_timeout: 30,
             enable_metrics: true,
+            health: HealthConfig::default(),
+            admin: AdminConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            tls_cert: None,
+            tls_key: None,
+            jwt_secret: None,
         }
     }
 }
@@ -84,108 +142,1841 @@ impl RPCServerState {
     }
 }
 
+/// Map state database errors to RPC errors
+fn state_error(e: StateDBError) -> RPCError {
+    match e {
+        StateDBError::VersionPruned(_) | StateDBError::InvalidVersion(_) => RPCError::NotFound(e.to_string()),
+        StateDBError::SnapshotError(_) => RPCError::BadRequest(e.to_string()),
+        e => RPCError::InternalError(e.to_string()),
+    }
+}
+
+/// Map execution errors to RPC errors
+fn execution_error(e: ExecutionError) -> RPCError {
+    match e {
+        ExecutionError::InvalidFilter(_) | ExecutionError::InvalidViewKey(_) => {
+            RPCError::InvalidParameters(e.to_string())
+        }
+        e => RPCError::InternalError(e.to_string()),
+    }
+}
+
+/// Map bridge errors to RPC errors
+fn bridge_error(e: BridgeError) -> RPCError {
+    match e {
+        BridgeError::ChallengeError(_) => RPCError::InvalidParameters(e.to_string()),
+        e => RPCError::InternalError(e.to_string()),
+    }
+}
+
+/// Decode hex bytes, with or without a `0x` prefix
+fn parse_hex(value: &str, what: &str) -> Result<Vec<u8>, RPCError> {
+    hex::decode(value.trim_start_matches("0x"))
+        .map_err(|e| RPCError::InvalidParameters(format!("Invalid {}: {}", what, e)))
+}
+
+fn parse_hash(value: &str, what: &str) -> Result<[u8; 32], RPCError> {
+    <[u8; 32]>::try_from(parse_hex(value, what)?)
+        .map_err(|_| RPCError::InvalidParameters(format!("{} must be 32 hex-encoded bytes", what)))
+}
+
+fn parse_viewing_key(value: &str) -> Result<ViewingKey, RPCError> {
+    ViewingKey::from_bytes(&parse_hex(value, "Viewing key")?).map_err(|e| RPCError::InvalidParameters(e.to_string()))
+}
+
+fn parse_transaction(value: &serde_json::Value) -> Result<Transaction, RPCError> {
+    serde_json::from_value(value.clone())
+        .map_err(|e| RPCError::InvalidParameters(format!("Invalid transaction: {}", e)))
+}
+
+fn signing_hashes(tx: &serde_json::Value, fee_payer: Option<&str>) -> Result<serde_json::Value, RPCError> {
+    let tx = parse_transaction(tx)?;
+    let fee_payer = fee_payer.map(|account| parse_hex(account, "fee payer")).transpose()?;
+    Ok(serde_json::json!({
+        "signingHash": hex::encode(tx.signing_hash()),
+        "feePayerHash": fee_payer.map(|account| hex::encode(tx.fee_payer_hash(&account))),
+    }))
+}
+
+fn sponsor_transaction(
+    tx: &serde_json::Value,
+    fee_payer: &str,
+    signature: &str,
+) -> Result<serde_json::Value, RPCError> {
+    let mut tx = parse_transaction(tx)?;
+    let account = parse_hex(fee_payer, "fee payer")?;
+    if account.is_empty() || account == tx.sender {
+        return Err(RPCError::InvalidParameters("Fee payer must be an account other than the sender".to_string()));
+    }
+    tx.fee_payer = Some(FeePayer {
+        account,
+        signature: parse_hex(signature, "fee payer signature")?,
+    });
+    block_sync::signatures::verify_fee_payer(&tx).map_err(|e| RPCError::InvalidParameters(e.to_string()))?;
+    serde_json::to_value(&tx).map_err(|e| RPCError::SerializationError(e.to_string()))
+}
+
+/// Parse a block number given as a JSON number, a hex quantity or `latest`
+fn parse_block_number(value: &serde_json::Value) -> Result<Option<u64>, RPCError> {
+    match value {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(tag) if tag == "latest" => Ok(None),
+        serde_json::Value::String(quantity) => u64::from_str_radix(quantity.trim_start_matches("0x"), 16)
+            .map(Some)
+            .map_err(|e| RPCError::InvalidParameters(format!("Invalid block number {}: {}", quantity, e))),
+        value => value
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| RPCError::InvalidParameters(format!("Invalid block number {}", value))),
+    }
+}
+
+/// Parse an `eth_getLogs` filter object
+fn parse_log_filter(filter: &serde_json::Value) -> Result<LogFilter, RPCError> {
+    let addresses = match &filter["address"] {
+        serde_json::Value::Null => Vec::new(),
+        serde_json::Value::String(address) => vec![parse_hex(address, "address")?],
+        serde_json::Value::Array(addresses) => addresses
+            .iter()
+            .map(|address| parse_hex(address.as_str().unwrap_or_default(), "address"))
+            .collect::<Result<_, _>>()?,
+        _ => return Err(RPCError::InvalidParameters("Address must be a string or an array".to_string())),
+    };
+    let topic_list = |topics: &[serde_json::Value]| -> Result<Vec<[u8; 32]>, RPCError> {
+        topics
+            .iter()
+            .map(|topic| parse_hash(topic.as_str().unwrap_or_default(), "topic"))
+            .collect()
+    };
+    let topics = match &filter["topics"] {
+        serde_json::Value::Null => Vec::new(),
+        serde_json::Value::Array(positions) => positions
+            .iter()
+            .map(|position| match position {
+                serde_json::Value::Null => Ok(None),
+                serde_json::Value::String(topic) => Ok(Some(vec![parse_hash(topic, "topic")?])),
+                serde_json::Value::Array(alternatives) => topic_list(alternatives).map(Some),
+                _ => Err(RPCError::InvalidParameters("Topic must be a string or an array".to_string())),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(RPCError::InvalidParameters("Topics must be an array".to_string())),
+    };
+
+    Ok(LogFilter {
+        from_block: parse_block_number(&filter["fromBlock"])?,
+        to_block: parse_block_number(&filter["toBlock"])?,
+        addresses,
+        topics,
+    })
+}
+
+fn log_json(log: &Log) -> serde_json::Value {
+    serde_json::json!({
+        "address": hex::encode(&log.address),
+        "topics": log.topics.iter().map(hex::encode).collect::<Vec<_>>(),
+        "data": hex::encode(&log.data),
+        "blockNumber": log.block_height,
+        "transactionHash": hex::encode(log.tx_hash),
+        "transactionIndex": log.tx_index,
+        "logIndex": log.log_index,
+    })
+}
+
+fn receipt_json(receipt: &Receipt) -> serde_json::Value {
+    serde_json::json!({
+        "transactionHash": hex::encode(receipt.tx_hash),
+        "blockNumber": receipt.block_height,
+        "transactionIndex": receipt.tx_index,
+        "status": u8::from(receipt.status == ReceiptStatus::Success),
+        "gasUsed": receipt.gas_used,
+        "cumulativeGasUsed": receipt.cumulative_gas_used,
+        "fee": receipt.fee,
+        "contractAddress": receipt.contract_address.as_ref().map(hex::encode),
+        "revertReason": receipt.revert_reason,
+        "logs": receipt.logs.iter().map(log_json).collect::<Vec<_>>(),
+    })
+}
+
+/// A receipt followed by the gas charged at each step and the balances the transaction moved
+fn trace_json(trace: &TransactionTrace) -> serde_json::Value {
+    let mut json = receipt_json(&trace.receipt);
+    json["gasSteps"] = trace
+        .gas_steps
+        .iter()
+        .map(|step| serde_json::json!({ "op": step.op, "gas": step.gas, "cumulativeGas": step.cumulative_gas }))
+        .collect();
+    json["balanceChanges"] = trace
+        .balance_changes
+        .iter()
+        .map(|change| {
+            serde_json::json!({
+                "address": hex::encode(&change.address),
+                "before": change.before,
+                "after": change.after,
+            })
+        })
+        .collect();
+    json
+}
+
+/// Header fields, its hash and canonical encoding, and the state root committed at its height
+fn header_json(header: &BlockHeader, state_root: Option<[u8; 32]>) -> Result<serde_json::Value, RPCError> {
+    let hash = header.hash().map_err(|e| RPCError::InternalError(e.to_string()))?;
+    Ok(serde_json::json!({
+        "height": header.height,
+        "hash": hex::encode(hash),
+        "prevHash": hex::encode(header.prev_hash),
+        "merkleRoot": hex::encode(header.merkle_root),
+        "timestamp": header.timestamp,
+        "nonce": header.nonce,
+        "difficulty": header.difficulty,
+        "nullifierRoot": hex::encode(header.nullifier_root),
+        "stateRoot": state_root.map(hex::encode),
+        "encoded": hex::encode(header.to_canonical_bytes()),
+    }))
+}
+
+fn state_proof_json(proof: &SparseMerkleProof) -> serde_json::Value {
+    serde_json::json!({
+        "leaf": proof.leaf.as_ref().map(|leaf| serde_json::json!({
+            "keyHash": hex::encode(leaf.key_hash),
+            "valueHash": hex::encode(leaf.value_hash),
+        })),
+        "siblings": proof.siblings.iter().map(hex::encode).collect::<Vec<_>>(),
+    })
+}
+
+fn withdrawal_proof_json(proof: &WithdrawalProof) -> serde_json::Value {
+    serde_json::json!({
+        "batchIndex": proof.batch_index,
+        "root": hex::encode(proof.root),
+        "committed": proof.committed,
+        "leaf": hex::encode(proof.leaf),
+        "leafIndex": proof.leaf_index,
+        "proof": proof.siblings.iter().map(hex::encode).collect::<Vec<_>>(),
+        "withdrawal": {
+            "id": hex::encode(proof.withdrawal.id()),
+            "sender": hex::encode(&proof.withdrawal.sender),
+            "recipient": hex::encode(&proof.withdrawal.l1_recipient),
+            "amount": proof.withdrawal.amount,
+            "token": proof.withdrawal.token.as_ref().map(hex::encode),
+            "blockNumber": proof.withdrawal.block_height,
+            "transactionHash": hex::encode(proof.withdrawal.tx_hash),
+            "logIndex": proof.withdrawal.log_index,
+        },
+    })
+}
+
+fn transfer_json(transfer: &BridgeTransfer) -> serde_json::Value {
+    let kind = match transfer.kind {
+        TransferKind::Deposit => "deposit",
+        TransferKind::XfgBurn => "xfg_burn",
+        TransferKind::Withdrawal => "withdrawal",
+        TransferKind::Proof => "proof",
+    };
+    serde_json::json!({
+        "id": hex::encode(transfer.id),
+        "kind": kind,
+        "status": transfer.status.name(),
+        "address": hex::encode(&transfer.address),
+        "amount": transfer.amount,
+        "mintNonce": transfer.mint_nonce,
+        "history": transfer.history.iter().map(|transition| serde_json::json!({
+            "status": transition.status.name(),
+            "at": transition.at,
+            "detail": transition.detail,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn challenge_json(challenge: &Challenge) -> serde_json::Value {
+    serde_json::json!({
+        "id": challenge.id,
+        "batch": challenge.batch,
+        "firstBlock": challenge.first_block,
+        "challenger": hex::encode(&challenge.challenger),
+        "claimedStateRoot": hex::encode(challenge.claimed_state_root),
+        "status": challenge.status.name(),
+        "toMove": challenge.status.to_move(),
+        "agreedStep": challenge.agreed_step,
+        "agreedState": hex::encode(challenge.agreed_state),
+        "disputedStep": challenge.disputed_step,
+        "disputedState": hex::encode(challenge.disputed_state),
+        "midpoint": challenge.midpoint(),
+        "rounds": challenge.rounds.iter().map(|round| serde_json::json!({
+            "agreedStep": round.agreed_step,
+            "disputedStep": round.disputed_step,
+            "midpointState": hex::encode(round.midpoint_state),
+            "challengerAgreed": round.challenger_agreed,
+        })).collect::<Vec<_>>(),
+        "lastMoveAt": challenge.last_move_at,
+        "resolution": challenge.resolution,
+        "settled": challenge.settled,
+    })
+}
+
+fn proof_submission_json(record: &SubmissionRecord) -> serde_json::Value {
+    let (status, block_number) = match record.state {
+        SubmissionState::InFlight => ("pending", None),
+        SubmissionState::Confirmed { block_number } => ("confirmed", Some(block_number)),
+        SubmissionState::Reverted { block_number } => ("reverted", Some(block_number)),
+    };
+    serde_json::json!({
+        "proofId": hex::encode(record.proof_id),
+        "status": status,
+        "blockNumber": block_number,
+        "nonce": record.nonce,
+        "transactionHash": record.included_tx.map(hex::encode),
+        "attempts": record.attempts.iter().map(|attempt| serde_json::json!({
+            "transactionHash": hex::encode(attempt.tx_hash),
+            "gasPrice": attempt.gas_price,
+            "sentAt": attempt.sent_at,
+        })).collect::<Vec<_>>(),
+        "gasUsed": record.gas_used,
+        // Wei costs can exceed what JSON numbers hold exactly
+        "cost": record.cost.to_string(),
+    })
+}
+
+fn reorg_incident_json(incident: &ReorgIncident) -> serde_json::Value {
+    serde_json::json!({
+        "detectedAt": incident.detected_at,
+        "l1Head": incident.l1_head,
+        "commonAncestor": incident.common_ancestor,
+        "orphanedBlock": incident.orphaned_block,
+        "rolledBackMints": incident.rolled_back_mints,
+        "irreversibleMints": incident.irreversible_mints.iter().map(hex::encode).collect::<Vec<_>>(),
+    })
+}
+
+fn proof_costs_json(stats: &SubmissionCostStats) -> serde_json::Value {
+    serde_json::json!({
+        "submitted": stats.submitted,
+        "confirmed": stats.confirmed,
+        "reverted": stats.reverted,
+        "replacements": stats.replacements,
+        "totalGasUsed": stats.total_gas_used,
+        "averageGasUsed": stats.average_gas_used(),
+        "totalCost": stats.total_cost.to_string(),
+    })
+}
+
+fn proof_job_json(job: &ProofJobInfo) -> serde_json::Value {
+    let (status, proof, error) = match &job.status {
+        JobStatus::Queued => ("queued", None, None),
+        JobStatus::Running => ("running", None, None),
+        JobStatus::Completed(proof) => ("completed", Some(proof), None),
+        JobStatus::Failed(error) => ("failed", None, Some(error)),
+    };
+    serde_json::json!({
+        "jobId": job.job_id,
+        "priority": match job.priority {
+            ProofPriority::Block => "block",
+            ProofPriority::Transaction => "transaction",
+        },
+        "status": status,
+        "submittedAt": job.submitted_at,
+        "provingTimeMs": job.proving_time_ms,
+        "proof": proof.map(|proof| serde_json::json!({
+            "circuit": proof.circuit,
+            "publicInputs": proof.public_inputs.iter().map(hex::encode).collect::<Vec<_>>(),
+            "proof": hex::encode(&proof.proof),
+        })),
+        "error": error,
+    })
+}
+
+fn note_witness_json(witness: &NoteWitness) -> serde_json::Value {
+    serde_json::json!({
+        "commitment": hex::encode(witness.commitment),
+        "index": witness.index,
+        "siblings": witness.siblings.iter().map(hex::encode).collect::<Vec<_>>(),
+        "root": hex::encode(witness.root),
+    })
+}
+
+fn stealth_output_json(output: &StealthOutputRecord) -> serde_json::Value {
+    serde_json::json!({
+        "blockNumber": output.block_height,
+        "transactionHash": hex::encode(output.tx_hash),
+        "outputIndex": output.output_index,
+        "amount": output.amount,
+        "ephemeralKey": hex::encode(output.ephemeral_key),
+        "oneTimeKey": hex::encode(&output.one_time_key),
+    })
+}
+
+fn deposit_address_json(address: &DepositAddress) -> serde_json::Value {
+    serde_json::json!({
+        "account": hex::encode(&address.account),
+        "index": address.index,
+        "address": hex::encode(&address.address),
+        "stealthAddress": address.stealth_address.as_ref().map(hex::encode),
+    })
+}
+
+fn deposit_json(deposit: &DetectedDeposit) -> serde_json::Value {
+    serde_json::json!({
+        "account": hex::encode(&deposit.account),
+        "index": deposit.index,
+        "blockNumber": deposit.block_height,
+        "transactionHash": hex::encode(deposit.tx_hash),
+        "position": deposit.position,
+        "amount": deposit.amount,
+        "asset": deposit.asset.map(hex::encode),
+        "private": deposit.private,
+        "confirmations": deposit.confirmations,
+    })
+}
+
+fn eldernode_json(node: &Eldernode) -> serde_json::Value {
+    serde_json::json!({
+        "address": hex::encode(&node.address),
+        "stake": node.stake,
+        "registeredAt": node.registered_at,
+        "lastHeartbeat": node.last_heartbeat,
+        "blocksServed": node.blocks_served,
+        "feesEarned": node.fees_earned,
+    })
+}
+
+fn eldernode_share_json(share: &EldernodeShare) -> serde_json::Value {
+    serde_json::json!({
+        "address": hex::encode(&share.address),
+        "fee": share.fee,
+    })
+}
+
+fn block_fees_json(fees: &BlockFees) -> serde_json::Value {
+    serde_json::json!({
+        "height": fees.height,
+        "baseFee": fees.base_fee,
+        "gasUsed": fees.gas_used,
+        "gasTarget": fees.gas_target,
+        "baseFees": fees.base_fees,
+        "tips": fees.tips,
+    })
+}
+
+fn reward_account_json(account: &RewardAccount, height: u64) -> serde_json::Value {
+    let grants: Vec<serde_json::Value> = account
+        .grants
+        .iter()
+        .map(|grant| {
+            serde_json::json!({
+                "epoch": grant.epoch,
+                "class": grant.class,
+                "amount": grant.amount,
+                "vested": grant.vested(height),
+                "cliffAt": grant.cliff_at,
+                "vestedAt": grant.vested_at,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "address": hex::encode(&account.address),
+        "height": height,
+        "accrued": account.accrued(),
+        "vested": account.vested(height),
+        "claimed": account.claimed,
+        "claimable": account.claimable(height),
+        "grants": grants,
+    })
+}
+
+fn epoch_summary_json(summary: &EpochSummary) -> serde_json::Value {
+    serde_json::json!({
+        "epoch": summary.epoch,
+        "blocks": summary.blocks,
+        "total": summary.total,
+        "byClass": summary.by_class,
+    })
+}
+
+fn snapshot_summary(manifest: &SnapshotManifest) -> serde_json::Value {
+    serde_json::json!({
+        "version": manifest.version,
+        "root": hex::encode(manifest.root),
+        "entries": manifest.entries,
+        "chunks": manifest.chunks.len(),
+    })
+}
+
 /// Main RPC server implementation
 pub struct RPCServer {
     config: RPCServerConfig,
     state: Arc<RPCServerState>,
+    network_info: Option<Arc<RwLock<NetworkInfo>>>,
+    bandwidth: Option<Arc<RwLock<BandwidthTracker>>>,
+    mining_workers: Option<Arc<RwLock<HashMap<String, WorkerStats>>>>,
+    stale_tracker: Option<Arc<RwLock<StaleTracker>>>,
+    pplns: Option<Arc<RwLock<PplnsLedger>>>,
+    finality: Option<Arc<RwLock<FinalityGadget>>>,
+    network_time: Option<Arc<RwLock<NetworkTime>>>,
+    consensus: Option<Arc<RwLock<Consensus>>>,
+    state_db: Option<Arc<RwLock<RocksStateDB>>>,
+    executor: Option<Arc<BlockExecutor>>,
+    prover: Option<Arc<ProverService>>,
+    sync_target: Option<Arc<AtomicU64>>,
+    config_reload: Option<Arc<dyn ConfigReload>>,
+    peer_control: Option<PeerControl>,
+    node_admin: Option<Arc<dyn NodeAdmin>>,
+    task_health: Option<Arc<dyn TaskHealth>>,
+    rate_limiter: RwLock<RateLimiter>,
+    jwt_secret: Option<JwtSecret>,
 }
 
 impl RPCServer {
     pub fn new(config: RPCServerConfig) -> Result<Self, RPCError> {
         let state = Arc::new(RPCServerState::new(config.clone()));
+        let rate_limiter = RwLock::new(RateLimiter::new(config.rate_limit.clone()));
+        let jwt_secret = config.jwt_secret.as_deref().map(JwtSecret::from_file).transpose()?;
 
         Ok(Self {
             config,
             state,
+            network_info: None,
+            bandwidth: None,
+            mining_workers: None,
+            stale_tracker: None,
+            pplns: None,
+            finality: None,
+            network_time: None,
+            consensus: None,
+            state_db: None,
+            executor: None,
+            prover: None,
+            sync_target: None,
+            config_reload: None,
+            peer_control: None,
+            node_admin: None,
+            task_health: None,
+            rate_limiter,
+            jwt_secret,
         })
     }
 
-    /// Start the RPC server
-    pub async fn start(&mut self) -> Result<(), RPCError> {
-        info!("Starting RPC server...");
-        info!("HTTP server will start on {}", self.config.http_addr);
-        info!("WebSocket server will start on {}", self.config.ws_addr);
-        info!("RPC server started successfully");
-        Ok(())
+    /// Attach the P2P layer's address discovery state
+    pub fn attach_network(&mut self, network_info: Arc<RwLock<NetworkInfo>>) {
+        self.network_info = Some(network_info);
+    }
+
+    /// Attach the P2P layer's per-peer bandwidth counters
+    pub fn attach_bandwidth(&mut self, bandwidth: Arc<RwLock<BandwidthTracker>>) {
+        self.bandwidth = Some(bandwidth);
+    }
+
+    /// Attach the Stratum server's per-worker statistics
+    pub fn attach_mining(&mut self, workers: Arc<RwLock<HashMap<String, WorkerStats>>>) {
+        self.mining_workers = Some(workers);
+    }
+
+    /// Attach the in-process miner's stale block tracker
+    pub fn attach_stale_blocks(&mut self, stale_tracker: Arc<RwLock<StaleTracker>>) {
+        self.stale_tracker = Some(stale_tracker);
+    }
+
+    /// Attach the Stratum server's PPLNS ledger
+    pub fn attach_pplns(&mut self, pplns: Arc<RwLock<PplnsLedger>>) {
+        self.pplns = Some(pplns);
+    }
+
+    /// Attach the consensus finality gadget
+    pub fn attach_finality(&mut self, finality: Arc<RwLock<FinalityGadget>>) {
+        self.finality = Some(finality);
+    }
+
+    /// Attach the consensus network-adjusted clock
+    pub fn attach_network_time(&mut self, network_time: Arc<RwLock<NetworkTime>>) {
+        self.network_time = Some(network_time);
+    }
+
+    /// Attach consensus for block submission
+    pub fn attach_consensus(&mut self, consensus: Arc<RwLock<Consensus>>) {
+        self.consensus = Some(consensus);
+    }
+
+    /// Attach the state database for state queries
+    pub fn attach_state_db(&mut self, state_db: Arc<RwLock<RocksStateDB>>) {
+        self.state_db = Some(state_db);
+    }
+
+    /// Attach an executor configured like the chain's, to re-execute blocks for traces
+    pub fn attach_executor(&mut self, executor: Arc<BlockExecutor>) {
+        self.executor = Some(executor);
+    }
+
+    /// Attach the prover service for proof job queries
+    pub fn attach_prover(&mut self, prover: Arc<ProverService>) {
+        self.prover = Some(prover);
+    }
+
+    /// Attach the best block height known from peers, used to report sync lag
+    pub fn attach_sync_target(&mut self, sync_target: Arc<AtomicU64>) {
+        self.sync_target = Some(sync_target);
+    }
+
+    /// Attach the node's config reloader for `admin_reloadConfig`
+    pub fn attach_config_reload(&mut self, config_reload: Arc<dyn ConfigReload>) {
+        self.config_reload = Some(config_reload);
+    }
+
+    /// Attach the P2P layer's peer control for the admin peer methods
+    pub fn attach_peer_control(&mut self, peer_control: PeerControl) {
+        self.peer_control = Some(peer_control);
+    }
+
+    /// Attach the node's mining and key management for the admin methods
+    pub fn attach_node_admin(&mut self, node_admin: Arc<dyn NodeAdmin>) {
+        self.node_admin = Some(node_admin);
+    }
+
+    /// Attach the node's supervised tasks, reported by the readiness probe
+    pub fn attach_task_health(&mut self, task_health: Arc<dyn TaskHealth>) {
+        self.task_health = Some(task_health);
+    }
+
+    /// Start the RPC server
+    pub async fn start(&mut self) -> Result<(), RPCError> {
+        info!("Starting RPC server...");
+        info!("HTTP server will start on {}", self.config.http_addr);
+        info!("WebSocket server will start on {}", self.config.ws_addr);
+        info!("RPC server started successfully");
+        Ok(())
+    }
+
+    /// Stop the RPC server
+    pub async fn stop(&mut self) -> Result<(), RPCError> {
+        info!("Stopping RPC server...");
+        info!("RPC server stopped");
+        Ok(())
+    }
+
+    /// Get server statistics
+    pub async fn get_stats(&self) -> RPCServerStats {
+        self.state.stats.read().await.clone()
+    }
+
+    /// Test RPC functionality
+    pub async fn test_rpc(&self) -> Result<String, RPCError> {
+        debug!("Testing RPC functionality");
+        self.state.increment_request(true).await;
+        Ok("RPC test successful".to_string())
+    }
+
+    /// Get node status
+    pub async fn get_node_status(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting node status");
+        self.state.increment_request(true).await;
+
+        let status = serde_json::json!({
+            "status": "running",
+            "uptime": self.state.stats.read().await.uptime_seconds,
+            "version": "0.1.0",
+            "peers": 5,
+            "sync_status": "synced",
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        });
+
+        Ok(status)
+    }
+
+    /// Get blockchain info
+    pub async fn get_blockchain_info(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting blockchain info");
+        self.state.increment_request(true).await;
+
+        let info = serde_json::json!({
+            "chain": "coldl3",
+            "network": "mainnet",
+            "current_height": 12345,
+            "best_block_hash": "block_hash_12345",
+            "genesis_block_hash": "genesis_block_hash",
+            "difficulty": 1000000,
+            "total_supply": 1000000000,
+            "circulating_supply": 500000000,
+            "block_time": 10,
+            "last_block_timestamp": chrono::Utc::now().timestamp(),
+            "sync_status": "synced"
+        });
+
+        Ok(info)
+    }
+
+    /// Get bridge status
+    pub async fn get_bridge_status(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting bridge status");
+        self.state.increment_request(true).await;
+
+        let status = serde_json::json!({
+            "status": "active",
+            "total_headers_verified": 1000,
+            "total_proofs_submitted": 500,
+            "total_proofs_confirmed": 450,
+            "total_proofs_failed": 50,
+            "last_header_height": 12345,
+            "last_proof_timestamp": chrono::Utc::now().timestamp(),
+            "pending_proofs": 10,
+            "arbitrum_connection": "connected",
+            "fuego_connection": "connected"
+        });
+
+        Ok(status)
+    }
+
+    /// Get P2P network info, including addresses observed by remote peers
+    pub async fn get_network_info(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting network info");
+
+        let network_info = match &self.network_info {
+            Some(network_info) => network_info,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("P2P network not attached".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        let info = network_info.read().await.clone();
+        Ok(serde_json::to_value(info)?)
+    }
+
+    /// Get the bytes exchanged with each connected peer per protocol, and whether it is throttled
+    pub async fn net_peer_stats(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting peer bandwidth stats");
+
+        let bandwidth = match &self.bandwidth {
+            Some(bandwidth) => bandwidth,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("P2P network not attached".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        let bandwidth = bandwidth.read().await;
+        let limits = bandwidth.limits();
+        let peers: Vec<serde_json::Value> = bandwidth
+            .stats()
+            .into_iter()
+            .map(|(peer_id, stats)| {
+                let mut peer = serde_json::to_value(&stats).unwrap_or_default();
+                peer["peer_id"] = peer_id.to_string().into();
+                peer["total"] = serde_json::to_value(stats.total()).unwrap_or_default();
+                peer
+            })
+            .collect();
+        Ok(serde_json::json!({
+            "upload_bytes_per_sec": limits.upload_bytes_per_sec,
+            "download_bytes_per_sec": limits.download_bytes_per_sec,
+            "window_secs": limits.window_secs,
+            "disconnected_peers": bandwidth.disconnected(),
+            "peers": peers,
+        }))
+    }
+
+    /// Get Stratum workers with their share counts and hashrate
+    pub async fn get_mining_workers(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting mining workers");
+
+        let workers = match &self.mining_workers {
+            Some(workers) => workers,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("Stratum server not attached".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        let workers = workers.read().await;
+        let total_hashrate: u64 = workers.values().map(|worker| worker.hashrate).sum();
+        let mut list: Vec<&WorkerStats> = workers.values().collect();
+        list.sort_by(|a, b| a.worker_id.cmp(&b.worker_id));
+        Ok(serde_json::json!({
+            "total_hashrate": total_hashrate,
+            "workers": list,
+        }))
+    }
+
+    /// Get the most recent locally mined blocks that lost to a competing tip
+    pub async fn get_mining_stale_blocks(&self, limit: usize) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting stale mined blocks");
+
+        let stale_tracker = match &self.stale_tracker {
+            Some(stale_tracker) => stale_tracker,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("Miner not attached".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        let tracker = stale_tracker.read().await;
+        Ok(serde_json::json!({
+            "stale_blocks": tracker.stale_blocks(),
+            "accepted_blocks": tracker.accepted_blocks(),
+            "recent": tracker.recent(limit),
+        }))
+    }
+
+    /// Get pool balances and the most recent PPLNS block payouts
+    pub async fn get_mining_payouts(&self, limit: usize) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting pool payouts");
+
+        let pplns = match &self.pplns {
+            Some(pplns) => pplns,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("Pool accounting not enabled".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        let ledger = pplns.read().await;
+        let (window_shares, window_difficulty) = ledger.window();
+        Ok(serde_json::json!({
+            "balances": ledger.balances(),
+            "pool_fees": ledger.pool_fees(),
+            "window_shares": window_shares,
+            "window_difficulty": window_difficulty,
+            "payouts": ledger.payouts(limit),
+        }))
+    }
+
+    /// Get the status of a proving job, or null if it is unknown or expired
+    pub async fn proof_status(&self, job_id: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting proof job {}", job_id);
+
+        let prover = match &self.prover {
+            Some(prover) => prover,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("Prover service not attached".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        Ok(prover.status(job_id).as_ref().map_or(serde_json::Value::Null, proof_job_json))
+    }
+
+    /// Get the highest finalized block, which bridges can treat as irreversible
+    pub async fn get_finalized_head(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting finalized head");
+
+        let finality = match &self.finality {
+            Some(finality) => finality,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("Finality gadget not attached".to_string()));
+            }
+        };
+
+        self.state.increment_request(true).await;
+        Ok(match finality.read().await.finalized_head() {
+            Some(head) => serde_json::json!({
+                "finalized": true,
+                "height": head.height,
+                "hash": hex::encode(head.hash),
+                "timestamp": head.timestamp,
+            }),
+            None => serde_json::json!({ "finalized": false }),
+        })
+    }
+
+    /// Get the local clock and the network-adjusted time block timestamps are judged against
+    pub async fn get_network_time(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting network time");
+
+        let network_time = match &self.network_time {
+            Some(network_time) => network_time,
+            None => {
+                self.state.increment_request(false).await;
+                return Err(RPCError::ServiceUnavailable("Network time not attached".to_string()));
+            }
+        };
+
+        let status = network_time.read().await.status();
+        self.state.increment_request(true).await;
+        Ok(serde_json::json!({
+            "localTime": status.local_time,
+            "adjustedTime": status.adjusted_time,
+            "offset": status.offset,
+            "samples": status.samples,
+        }))
+    }
+
+    /// Submit a canonically encoded block signed by `proposer`. Blocks failing validation are
+    /// not errors: the result names the stage and rule that rejected them. A valid block on a
+    /// branch with less work than the canonical chain is accepted but not canonical.
+    pub async fn submit_block(
+        &self,
+        block: &str,
+        proposer: u64,
+        signature: &str,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Submitting block from validator {}", proposer);
+
+        let result = self.import_block(block, proposer, signature).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn import_block(&self, block: &str, proposer: u64, signature: &str) -> Result<serde_json::Value, RPCError> {
+        let consensus = self
+            .consensus
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("Consensus not attached".to_string()))?;
+        let block = Block::from_canonical_bytes(&parse_hex(block, "block")?)
+            .map_err(|e| RPCError::InvalidParameters(format!("Invalid block: {}", e)))?;
+        let proposal = BlockProposal {
+            block,
+            proposer,
+            timestamp: consensus::time::local_time(),
+            signature: parse_hex(signature, "signature")?,
+        };
+
+        match consensus.read().await.import_proposal(&proposal).await {
+            Ok(outcome) => Ok(serde_json::json!({
+                "accepted": true,
+                "height": outcome.height,
+                "hash": hex::encode(outcome.hash),
+                "canonical": !outcome.connected.is_empty(),
+                "reorgDepth": outcome.reorg_depth,
+            })),
+            Err(ConsensusError::BlockRejected(rejection)) => Ok(serde_json::json!({
+                "accepted": false,
+                "stage": rejection.stage().to_string(),
+                "reason": rejection.code(),
+                "message": rejection.to_string(),
+            })),
+            Err(e) => Err(RPCError::InternalError(e.to_string())),
+        }
+    }
+
+    /// Get a hex-encoded state value with its Merkle proof, at `version` or the latest version
+    pub async fn get_state(&self, key: &str, version: Option<u64>) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting state for key {} at version {:?}", key, version);
+
+        let result = self.read_state(key, version).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_state(&self, key: &str, version: Option<u64>) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let key = hex::decode(key).map_err(|e| RPCError::InvalidParameters(format!("Invalid key: {}", e)))?;
+
+        let state_db = state_db.read().await;
+        let view = match version {
+            Some(version) => state_db.state_at(version),
+            None => state_db.latest_state(),
+        }
+        .map_err(state_error)?;
+        let value = view.get(&key).map_err(|e| RPCError::InternalError(e.to_string()))?;
+        let proof = view.get_proof(&key).map_err(|e| RPCError::InternalError(e.to_string()))?;
+
+        Ok(serde_json::json!({
+            "version": view.version(),
+            "root": hex::encode(view.root()),
+            "value": value.map(hex::encode),
+            "proof": proof,
+        }))
+    }
+
+    /// Headers of up to `count` consecutive blocks from `from`, for light clients following
+    /// the chain without block bodies
+    pub async fn get_headers_range(&self, from: u64, count: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting {} headers from height {}", count, from);
+
+        let result = self.read_headers_range(from, count).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_headers_range(&self, from: u64, count: u64) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+
+        let state_db = state_db.read().await;
+        let headers = execution::receipt::get_headers(&state_db, from, count).map_err(execution_error)?;
+        let headers = headers
+            .iter()
+            .map(|header| header_json(header, state_db.root_at(header.height).ok().flatten()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(serde_json::Value::Array(headers))
+    }
+
+    /// Merkle proof of `key`'s value, or its absence, under the state root at `height`,
+    /// which a light client checks against the root in that block's header entry
+    pub async fn get_state_proof(&self, key: &str, height: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting state proof for key {} at height {}", key, height);
+
+        let result = self.read_state_proof(key, height).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_state_proof(&self, key: &str, height: u64) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let key = parse_hex(key, "key")?;
+
+        let state_db = state_db.read().await;
+        let view = state_db.state_at(height).map_err(state_error)?;
+        let value = view.get(&key).map_err(state_error)?;
+        let proof = view.get_proof(&key).map_err(state_error)?;
+
+        Ok(serde_json::json!({
+            "height": height,
+            "stateRoot": hex::encode(view.root()),
+            "key": hex::encode(&key),
+            "value": value.map(hex::encode),
+            "proof": state_proof_json(&proof),
+        }))
+    }
+
+    /// HEAT supply totals of the latest state: genesis, minted, burned and bridged
+    pub async fn get_supply(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting HEAT supply");
+
+        let result = self.read_supply().await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_supply(&self) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let state_db = state_db.read().await;
+        let version = state_db
+            .latest_version()
+            .ok_or_else(|| RPCError::NotFound("No state has been committed".to_string()))?;
+        let supply = state_db.get_supply().map_err(|e| RPCError::InternalError(e.to_string()))?;
+        let circulating = supply.circulating().map_err(|e| RPCError::InternalError(e.to_string()))?;
+
+        Ok(serde_json::json!({
+            "version": version,
+            "genesis": supply.genesis,
+            "minted": supply.minted,
+            "burned": supply.burned,
+            "bridgedIn": supply.bridged_in,
+            "bridgedOut": supply.bridged_out,
+            "xfgBurnMinted": supply.xfg_burn_minted,
+            "blockRewards": supply.block_rewards,
+            "feesBurned": supply.fees_burned,
+            "circulating": circulating,
+        }))
+    }
+
+    /// Balance of the bridged token `asset` held by a hex-encoded address, with the token's
+    /// registry entry, or null for it if no deposit has registered the token yet
+    pub async fn get_token_balance(&self, address: &str, asset: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting balance of token {} held by {}", asset, address);
+
+        let result = self.read_token_balance(address, asset).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_token_balance(&self, address: &str, asset: &str) -> Result<serde_json::Value, RPCError> {
+        let address = parse_hex(address, "address")?;
+        let asset = parse_hash(asset, "Asset id")?;
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let state_db = state_db.read().await;
+        let token = execution::token::get_token(&state_db, &asset).map_err(execution_error)?;
+        let balance = execution::token::get_token_balance(&state_db, &asset, &address).map_err(execution_error)?;
+
+        Ok(serde_json::json!({
+            "address": hex::encode(&address),
+            "asset": hex::encode(asset),
+            "balance": balance,
+            "token": token.map(|token| serde_json::json!({
+                "l1Origin": hex::encode(&token.info.l1_origin),
+                "symbol": token.info.symbol,
+                "decimals": token.info.decimals,
+                "supply": token.supply,
+            })),
+        }))
+    }
+
+    /// Every registered Eldernode with its stake, last heartbeat and earnings
+    pub async fn eldernode_list(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Listing Eldernodes");
+
+        let result = self.read_eldernodes().await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_eldernodes(&self) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let nodes = execution::eldernode::get_eldernodes(&*state_db.read().await).map_err(execution_error)?;
+        Ok(serde_json::Value::Array(nodes.iter().map(eldernode_json).collect()))
+    }
+
+    /// The Eldernode registered by a hex-encoded address, or null if there is none
+    pub async fn eldernode_get_node(&self, address: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting Eldernode {}", address);
+
+        let result = self.read_eldernode(address).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_eldernode(&self, address: &str) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let address = parse_hex(address, "Address")?;
+
+        let node = execution::eldernode::get_eldernode(&*state_db.read().await, &address).map_err(execution_error)?;
+        Ok(node.as_ref().map_or(serde_json::Value::Null, eldernode_json))
+    }
+
+    /// The Eldernodes that served the block at `height` and the fees each was routed
+    pub async fn eldernode_get_block_servers(&self, height: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting Eldernodes that served block {}", height);
+
+        let result = self.read_block_servers(height).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_block_servers(&self, height: u64) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let shares = execution::eldernode::get_served(&*state_db.read().await, height).map_err(execution_error)?;
+        Ok(serde_json::json!({
+            "height": height,
+            "eldernodes": shares.iter().map(eldernode_share_json).collect::<Vec<_>>(),
+        }))
+    }
+
+    /// Base fee and fees of up to `count` blocks from `from`, and the base fee of the block
+    /// after the last of them
+    pub async fn fees_get_base_fee_history(&self, from: u64, count: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting base fees of {} blocks from height {}", count, from);
+
+        let result = self.read_base_fee_history(from, count).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_base_fee_history(&self, from: u64, count: u64) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let history = execution::fee_market::get_fee_history(&*state_db.read().await, from, count)
+            .map_err(execution_error)?;
+        Ok(serde_json::json!({
+            "blocks": history.iter().map(block_fees_json).collect::<Vec<_>>(),
+            "nextBaseFee": history.last().map(|fees| fees.next_base_fee),
+        }))
+    }
+
+    /// Rewards accrued, vested, claimed and claimable at the latest height by a hex-encoded
+    /// address, or null if it has earned none
+    pub async fn rewards_get_rewards(&self, address: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting rewards of {}", address);
+
+        let result = self.read_rewards(address).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_rewards(&self, address: &str) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let address = parse_hex(address, "Address")?;
+
+        let state_db = state_db.read().await;
+        let height = state_db.latest_version().unwrap_or(0);
+        let account = rewards::get_reward_account(&state_db, &address)
+            .map_err(|e| RPCError::InternalError(e.to_string()))?;
+        Ok(account.map_or(serde_json::Value::Null, |account| reward_account_json(&account, height)))
+    }
+
+    /// Rewards paid out during `epoch` by class, or null if none were
+    pub async fn rewards_get_epoch(&self, epoch: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting rewards of epoch {}", epoch);
+
+        let result = self.read_reward_epoch(epoch).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_reward_epoch(&self, epoch: u64) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let summary = rewards::get_epoch_summary(&*state_db.read().await, epoch)
+            .map_err(|e| RPCError::InternalError(e.to_string()))?;
+        Ok(summary.as_ref().map_or(serde_json::Value::Null, epoch_summary_json))
+    }
+
+    /// Export the state at `version`, or at the finalized head, into a snapshot archive at `path`
+    pub async fn snapshot_export(&self, path: &str, version: Option<u64>) -> Result<serde_json::Value, RPCError> {
+        info!("Exporting state snapshot to {}", path);
+
+        let result = self.export_state(path, version).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn export_state(&self, path: &str, version: Option<u64>) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let version = match (version, &self.finality) {
+            (Some(version), _) => version,
+            (None, Some(finality)) => finality
+                .read()
+                .await
+                .finalized_head()
+                .map(|head| head.height)
+                .ok_or_else(|| RPCError::NotFound("No finalized block to snapshot".to_string()))?,
+            (None, None) => return Err(RPCError::InvalidParameters("Snapshot version is required".to_string())),
+        };
+
+        let manifest = state_db
+            .read()
+            .await
+            .export_snapshot(version, path)
+            .map_err(state_error)?;
+        Ok(snapshot_summary(&manifest))
+    }
+
+    /// Import the snapshot archive at `path` into the empty state database, optionally
+    /// pinning the hex-encoded state root it must rebuild
+    pub async fn snapshot_import(&self, path: &str, root: Option<&str>) -> Result<serde_json::Value, RPCError> {
+        info!("Importing state snapshot from {}", path);
+
+        let result = self.import_state(path, root).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn import_state(&self, path: &str, root: Option<&str>) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let root = root.map(|root| parse_hash(root, "Root")).transpose()?;
+
+        let manifest = state_db
+            .write()
+            .await
+            .import_snapshot(path, root)
+            .map_err(state_error)?;
+        Ok(snapshot_summary(&manifest))
+    }
+
+    /// Get the receipt of an executed transaction by hex-encoded hash, or null if unknown
+    pub async fn eth_get_transaction_receipt(&self, tx_hash: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting receipt for transaction {}", tx_hash);
+
+        let result = self.read_receipt(tx_hash).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_receipt(&self, tx_hash: &str) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let tx_hash = parse_hash(tx_hash, "Transaction hash")?;
+
+        let receipt = execution::receipt::get_receipt(&*state_db.read().await, &tx_hash).map_err(execution_error)?;
+        Ok(receipt.as_ref().map_or(serde_json::Value::Null, receipt_json))
+    }
+
+    /// Get the logs matching an `eth_getLogs` filter with optional
+    /// `fromBlock`, `toBlock`, `address` and positional `topics`
+    pub async fn eth_get_logs(&self, filter: &serde_json::Value) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting logs for filter {}", filter);
+
+        let result = self.read_logs(filter).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_logs(&self, filter: &serde_json::Value) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let filter = parse_log_filter(filter)?;
+
+        let logs = execution::receipt::get_logs(&*state_db.read().await, &filter).map_err(execution_error)?;
+        Ok(serde_json::Value::Array(logs.iter().map(log_json).collect()))
+    }
+
+    /// Trace an executed transaction by hex-encoded hash, or null if unknown
+    pub async fn debug_trace_transaction(&self, tx_hash: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Tracing transaction {}", tx_hash);
+
+        let result = self.read_transaction_trace(tx_hash).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_transaction_trace(&self, tx_hash: &str) -> Result<serde_json::Value, RPCError> {
+        let (state_db, executor) = self.tracing()?;
+        let tx_hash = parse_hash(tx_hash, "Transaction hash")?;
+
+        let state_db = state_db.read().await;
+        let Some(receipt) = execution::receipt::get_receipt(&state_db, &tx_hash).map_err(execution_error)? else {
+            return Ok(serde_json::Value::Null);
+        };
+        let traces = executor.trace_block(&state_db, receipt.block_height).map_err(execution_error)?;
+        Ok(traces
+            .and_then(|traces| traces.into_iter().nth(receipt.tx_index as usize))
+            .map_or(serde_json::Value::Null, |trace| trace_json(&trace)))
+    }
+
+    /// Trace every transaction of an executed block
+    pub async fn debug_trace_block(&self, height: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Tracing block {}", height);
+
+        let result = self.read_block_trace(height).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_block_trace(&self, height: u64) -> Result<serde_json::Value, RPCError> {
+        let (state_db, executor) = self.tracing()?;
+
+        let traces = executor
+            .trace_block(&*state_db.read().await, height)
+            .map_err(execution_error)?
+            .ok_or_else(|| RPCError::NotFound(format!("Block {} has not been executed by this node", height)))?;
+        Ok(serde_json::Value::Array(traces.iter().map(trace_json).collect()))
+    }
+
+    fn tracing(&self) -> Result<(&Arc<RwLock<RocksStateDB>>, &BlockExecutor), RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let executor = self
+            .executor
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("Block executor not attached".to_string()))?;
+        Ok((state_db, executor))
+    }
+
+    /// Get the Merkle proof for claiming a withdrawal on the L1 bridge contract, or null until
+    /// the withdrawal logged by `tx_hash` at `log_index` has been batched
+    pub async fn bridge_get_withdrawal_proof(&self, tx_hash: &str, log_index: u32) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting withdrawal proof for {} log {}", tx_hash, log_index);
+
+        let result = self.read_withdrawal_proof(tx_hash, log_index).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_withdrawal_proof(&self, tx_hash: &str, log_index: u32) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let tx_hash = parse_hash(tx_hash, "Transaction hash")?;
+
+        let proof = bridge::withdrawals::get_withdrawal_proof(&*state_db.read().await, &tx_hash, log_index)
+            .map_err(|e| RPCError::InternalError(e.to_string()))?;
+        Ok(proof.as_ref().map_or(serde_json::Value::Null, withdrawal_proof_json))
+    }
+
+    /// Get the status and gas cost of a proof submission by hex-encoded proof id, or null if unknown
+    pub async fn bridge_get_proof_submission(&self, proof_id: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting proof submission {}", proof_id);
+
+        let result = self.read_proof_submission(proof_id).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_proof_submission(&self, proof_id: &str) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let proof_id = parse_hash(proof_id, "Proof id")?;
+
+        let record = bridge::submission::get_submission(&*state_db.read().await, &proof_id)
+            .map_err(|e| RPCError::InternalError(e.to_string()))?;
+        Ok(record.as_ref().map_or(serde_json::Value::Null, proof_submission_json))
+    }
+
+    /// Get aggregate gas costs of proof submissions
+    pub async fn bridge_get_proof_costs(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting proof submission costs");
+
+        let result = match &self.state_db {
+            Some(state_db) => bridge::submission::get_cost_stats(&*state_db.read().await)
+                .map(|stats| proof_costs_json(&stats))
+                .map_err(|e| RPCError::InternalError(e.to_string())),
+            None => Err(RPCError::ServiceUnavailable("State database not attached".to_string())),
+        };
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    /// List bridge deposits, burn mints, withdrawals and proofs, oldest first, optionally only
+    /// those in `status` or involving the hex-encoded C0DL3 `address`
+    pub async fn bridge_list_transfers(
+        &self,
+        status: Option<&str>,
+        address: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Listing bridge transfers with status {:?} and address {:?}", status, address);
+
+        let result = self.read_transfers(status, address).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_transfers(&self, status: Option<&str>, address: Option<&str>) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let status = status
+            .map(|name| {
+                TransferStatus::from_name(name)
+                    .ok_or_else(|| RPCError::InvalidParameters(format!("Unknown transfer status {}", name)))
+            })
+            .transpose()?;
+        let address = address.map(|address| parse_hex(address, "address")).transpose()?;
+
+        let transfers = bridge::transfers::list_transfers(&*state_db.read().await, status, address.as_deref())
+            .map_err(|e| RPCError::InternalError(e.to_string()))?;
+        Ok(transfers.iter().map(transfer_json).collect())
+    }
+
+    /// Get the L1 reorgs that orphaned deposits the bridge had already ingested, newest first
+    pub async fn bridge_get_reorg_incidents(&self) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting bridge reorg incidents");
+
+        let result = match &self.state_db {
+            Some(state_db) => bridge::deposits::get_reorg_incidents(&*state_db.read().await)
+                .map(|incidents| incidents.iter().rev().map(reorg_incident_json).collect())
+                .map_err(|e| RPCError::InternalError(e.to_string())),
+            None => Err(RPCError::ServiceUnavailable("State database not attached".to_string())),
+        };
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    /// List fraud-proof challenges, oldest first, optionally only those of `batch` or in `status`
+    pub async fn bridge_list_challenges(
+        &self,
+        batch: Option<u64>,
+        status: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Listing challenges of batch {:?} with status {:?}", batch, status);
+
+        let result = self.read_challenges(batch, status).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_challenges(&self, batch: Option<u64>, status: Option<&str>) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let status = status
+            .map(|name| {
+                ChallengeStatus::from_name(name)
+                    .ok_or_else(|| RPCError::InvalidParameters(format!("Unknown challenge status {}", name)))
+            })
+            .transpose()?;
+
+        let challenges =
+            bridge::challenges::list_challenges(&*state_db.read().await, batch, status).map_err(bridge_error)?;
+        Ok(challenges.iter().map(challenge_json).collect())
+    }
+
+    /// Get fraud-proof challenge `id` and its bisection rounds, or null if unknown
+    pub async fn bridge_get_challenge(&self, id: u64) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting challenge {}", id);
+
+        let result = match &self.state_db {
+            Some(state_db) => bridge::challenges::get_challenge(&*state_db.read().await, id)
+                .map(|challenge| challenge.as_ref().map_or(serde_json::Value::Null, challenge_json))
+                .map_err(bridge_error),
+            None => Err(RPCError::ServiceUnavailable("State database not attached".to_string())),
+        };
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    /// Dispute the state root committed batch `batch` ends in, claiming it should be
+    /// `state_root`, with `signature` the challenger key's signature of that claim. The
+    /// bridge opens the challenge on the settlement layer.
+    pub async fn bridge_open_challenge(
+        &self,
+        batch: u64,
+        challenger: &str,
+        state_root: &str,
+        signature: &str,
+    ) -> Result<serde_json::Value, RPCError> {
+        info!("Opening challenge of batch {} for {}", batch, challenger);
+
+        let result = async {
+            let challenger = parse_hex(challenger, "challenger")?;
+            let state_root = parse_hash(state_root, "State root")?;
+            let signature = parse_hex(signature, "signature")?;
+            self.play_challenge(|state| {
+                bridge::challenges::open_challenge(state, batch, challenger, state_root, &signature)
+            })
+            .await
+        }
+        .await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    /// Say whether the challenger agrees with the state root the defender posted at the
+    /// midpoint of challenge `id`, leaving the upper or the lower half disputed. `signature`
+    /// is the challenger's signature of the challenge id, round and choice.
+    pub async fn bridge_choose_challenge_half(
+        &self,
+        id: u64,
+        agrees: bool,
+        signature: &str,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Challenger agrees with the midpoint of challenge {}: {}", id, agrees);
+
+        let result = async {
+            let signature = parse_hex(signature, "signature")?;
+            self.play_challenge(|state| bridge::challenges::choose(state, id, agrees, &signature)).await
+        }
+        .await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    /// Post the node's state root at the midpoint of challenge `id` against one of its batches
+    pub async fn bridge_bisect_challenge(&self, id: u64, state_root: &str) -> Result<serde_json::Value, RPCError> {
+        info!("Bisecting challenge {}", id);
+
+        let result = async {
+            let state_root = parse_hash(state_root, "State root")?;
+            self.play_challenge(|state| bridge::challenges::bisect(state, id, state_root)).await
+        }
+        .await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    /// Prove the one block left disputed in challenge `id` with the hex-encoded `witness`
+    pub async fn bridge_prove_challenge_step(&self, id: u64, witness: &str) -> Result<serde_json::Value, RPCError> {
+        info!("Proving the disputed block of challenge {}", id);
+
+        let result = async {
+            let witness = parse_hex(witness, "witness")?;
+            self.play_challenge(|state| bridge::challenges::prove_step(state, id, &witness)).await
+        }
+        .await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn play_challenge(
+        &self,
+        play: impl FnOnce(&mut RocksStateDB) -> Result<Challenge, BridgeError>,
+    ) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let challenge = play(&mut *state_db.write().await).map_err(bridge_error)?;
+        Ok(challenge_json(&challenge))
+    }
+
+    /// Get the membership witness a wallet needs to spend the note with `commitment`
+    pub async fn privacy_get_note_witness(&self, commitment: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting note witness for {}", commitment);
+
+        let result = self.read_note_witness(commitment).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn read_note_witness(&self, commitment: &str) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let commitment = parse_hash(commitment, "Commitment")?;
+
+        let witness = commitments::note_tree::get_witness(&*state_db.read().await, &commitment)
+            .map_err(|e| RPCError::InternalError(e.to_string()))?;
+        Ok(witness.as_ref().map_or(serde_json::Value::Null, note_witness_json))
+    }
+
+    /// Find the stealth outputs paid to a recipient in a block range. The scan secret
+    /// lets the node recognise outputs but not spend them.
+    pub async fn privacy_scan_outputs(
+        &self,
+        scan_secret: &str,
+        spend_public: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Scanning stealth outputs in blocks {}..={}", from_block, to_block);
+
+        let result = self.scan_outputs(scan_secret, spend_public, from_block, to_block).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn scan_outputs(
+        &self,
+        scan_secret: &str,
+        spend_public: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let view_key = ViewKey::from_bytes(&parse_hash(scan_secret, "Scan secret")?, &parse_hash(spend_public, "Spend key")?)
+            .map_err(|e| RPCError::InvalidParameters(e.to_string()))?;
+
+        let outputs = execution::receipt::get_stealth_outputs(&*state_db.read().await, from_block, to_block)
+            .map_err(execution_error)?;
+        let owned: Vec<serde_json::Value> = outputs
+            .iter()
+            .filter(|output| {
+                <[u8; 32]>::try_from(output.one_time_key.as_slice())
+                    .is_ok_and(|one_time_key| view_key.owns(output.output_index, &output.ephemeral_key, &one_time_key))
+            })
+            .map(stealth_output_json)
+            .collect();
+        Ok(serde_json::Value::Array(owned))
+    }
+
+    /// Export the stealth transfers a viewing key can see in a block range as a report
+    /// signed with the account's scan key, for handing to an auditor
+    pub async fn privacy_export_audit_report(
+        &self,
+        viewing_key: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Exporting audit report for blocks {}..={}", from_block, to_block);
+
+        let result = self.export_audit_report(viewing_key, from_block, to_block).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
+
+    async fn export_audit_report(
+        &self,
+        viewing_key: &str,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let viewing_key = parse_viewing_key(viewing_key)?;
+
+        let report = AuditReport::build(&*state_db.read().await, &viewing_key, from_block, to_block)
+            .map_err(execution_error)?;
+        serde_json::to_value(report).map_err(|e| RPCError::InternalError(e.to_string()))
+    }
+
+    /// Check an audit report against this node's chain. With the account's viewing key
+    /// the check also confirms the report is complete.
+    pub async fn privacy_verify_audit_report(
+        &self,
+        report: &serde_json::Value,
+        viewing_key: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Verifying audit report");
+
+        let result = self.verify_audit_report(report, viewing_key).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
     }
 
-    /// Stop the RPC server
-    pub async fn stop(&mut self) -> Result<(), RPCError> {
-        info!("Stopping RPC server...");
-        info!("RPC server stopped");
-        Ok(())
+    async fn verify_audit_report(
+        &self,
+        report: &serde_json::Value,
+        viewing_key: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let report: AuditReport = serde_json::from_value(report.clone())
+            .map_err(|e| RPCError::InvalidParameters(format!("Invalid audit report: {}", e)))?;
+        let viewing_key = viewing_key.map(parse_viewing_key).transpose()?;
+
+        let reason = match execution::check_audit_report(&*state_db.read().await, &report, viewing_key.as_ref()) {
+            Ok(()) => None,
+            Err(ExecutionError::InvalidAuditReport(reason)) => Some(reason),
+            Err(e) => return Err(execution_error(e)),
+        };
+        Ok(serde_json::json!({
+            "valid": reason.is_none(),
+            "reason": reason,
+            "complete": viewing_key.is_some() && reason.is_none(),
+            "incoming": report.incoming.len(),
+            "outgoing": report.outgoing.len(),
+        }))
     }
 
-    /// Get server statistics
-    pub async fn get_stats(&self) -> RPCServerStats {
-        self.state.stats.read().await.clone()
+    /// Register the stealth view key a hex-encoded account scans for private deposits with.
+    /// The node keeps the scan secret to find deposits to the account's subaddresses.
+    pub async fn exchange_register_view_key(
+        &self,
+        account: &str,
+        scan_secret: &str,
+        spend_public: &str,
+    ) -> Result<serde_json::Value, RPCError> {
+        info!("Registering deposit view key of {}", account);
+
+        let result = self.register_view_key(account, scan_secret, spend_public).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
     }
 
-    /// Test RPC functionality
-    pub async fn test_rpc(&self) -> Result<String, RPCError> {
-        debug!("Testing RPC functionality");
-        self.state.increment_request(true).await;
-        Ok("RPC test successful".to_string())
+    async fn register_view_key(
+        &self,
+        account: &str,
+        scan_secret: &str,
+        spend_public: &str,
+    ) -> Result<serde_json::Value, RPCError> {
+        let account = parse_hex(account, "account")?;
+        let key = DepositViewKey {
+            scan_secret: parse_hash(scan_secret, "Scan secret")?,
+            spend_public: parse_hash(spend_public, "Spend key")?,
+        };
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        execution::exchange::register_view_key(&mut *state_db.write().await, &account, key)
+            .map_err(execution_error)?;
+        Ok(serde_json::json!({ "account": hex::encode(&account), "registered": true }))
     }
 
-    /// Get node status
-    pub async fn get_node_status(&self) -> Result<serde_json::Value, RPCError> {
-        debug!("Getting node status");
-        self.state.increment_request(true).await;
+    /// Derive the next deposit address of a hex-encoded account, with its stealth subaddress
+    /// once the account has registered a view key
+    pub async fn exchange_new_deposit_address(&self, account: &str) -> Result<serde_json::Value, RPCError> {
+        debug!("Deriving a deposit address for {}", account);
 
-        let status = serde_json::json!({
-            "status": "running",
-            "uptime": self.state.stats.read().await.uptime_seconds,
-            "version": "0.1.0",
-            "peers": 5,
-            "sync_status": "synced",
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        });
+        let result = self.new_deposit_address(account).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
 
-        Ok(status)
+    async fn new_deposit_address(&self, account: &str) -> Result<serde_json::Value, RPCError> {
+        let account = parse_hex(account, "account")?;
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let address = execution::exchange::new_deposit_address(&mut *state_db.write().await, &account)
+            .map_err(execution_error)?;
+        Ok(deposit_address_json(&address))
     }
 
-    /// Get blockchain info
-    pub async fn get_blockchain_info(&self) -> Result<serde_json::Value, RPCError> {
-        debug!("Getting blockchain info");
-        self.state.increment_request(true).await;
+    /// Deposits to derived addresses in a block range with their confirmations, only those of
+    /// one hex-encoded account when it is given
+    pub async fn exchange_get_deposits(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        account: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting deposits in blocks {}..={}", from_block, to_block);
 
-        let info = serde_json::json!({
-            "chain": "coldl3",
-            "network": "mainnet",
-            "current_height": 12345,
-            "best_block_hash": "block_hash_12345",
-            "genesis_block_hash": "genesis_block_hash",
-            "difficulty": 1000000,
-            "total_supply": 1000000000,
-            "circulating_supply": 500000000,
-            "block_time": 10,
-            "last_block_timestamp": chrono::Utc::now().timestamp(),
-            "sync_status": "synced"
-        });
+        let result = self.read_deposits(from_block, to_block, account).await;
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
 
-        Ok(info)
+    async fn read_deposits(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        account: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        let account = account.map(|account| parse_hex(account, "account")).transpose()?;
+        let state_db = self
+            .state_db
+            .as_ref()
+            .ok_or_else(|| RPCError::ServiceUnavailable("State database not attached".to_string()))?;
+        let deposits = execution::exchange::scan_deposits(&*state_db.read().await, from_block, to_block)
+            .map_err(execution_error)?;
+        Ok(serde_json::Value::Array(
+            deposits
+                .iter()
+                .filter(|deposit| account.as_ref().is_none_or(|account| deposit.account == *account))
+                .map(deposit_json)
+                .collect(),
+        ))
     }
 
-    /// Get bridge status
-    pub async fn get_bridge_status(&self) -> Result<serde_json::Value, RPCError> {
-        debug!("Getting bridge status");
-        self.state.increment_request(true).await;
+    /// Hashes the sender of `tx` signs its inputs with and, for a sponsored transaction, the
+    /// one `fee_payer` signs
+    pub async fn tx_get_signing_hashes(
+        &self,
+        tx: &serde_json::Value,
+        fee_payer: Option<&str>,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Getting transaction signing hashes");
 
-        let status = serde_json::json!({
-            "status": "active",
-            "total_headers_verified": 1000,
-            "total_proofs_submitted": 500,
-            "total_proofs_confirmed": 450,
-            "total_proofs_failed": 50,
-            "last_header_height": 12345,
-            "last_proof_timestamp": chrono::Utc::now().timestamp(),
-            "pending_proofs": 10,
-            "arbitrum_connection": "connected",
-            "fuego_connection": "connected"
-        });
+        let result = signing_hashes(tx, fee_payer);
+        self.state.increment_request(result.is_ok()).await;
+        result
+    }
 
-        Ok(status)
+    /// `tx` sponsored by `fee_payer`, whose hex-encoded key and signature of the fee payer hash
+    /// are checked before they are attached
+    pub async fn tx_sponsor(
+        &self,
+        tx: &serde_json::Value,
+        fee_payer: &str,
+        signature: &str,
+    ) -> Result<serde_json::Value, RPCError> {
+        debug!("Sponsoring transaction from {}", fee_payer);
+
+        let result = sponsor_transaction(tx, fee_payer, signature);
+        self.state.increment_request(result.is_ok()).await;
+        result
     }
 
     /// Get consensus status
@@ -290,6 +2081,1082 @@ mod tests {
         assert!(status["total_headers_verified"].is_number());
     }
 
+    #[tokio::test]
+    async fn test_get_network_info() {
+        let config = RPCServerConfig::default();
+        let mut server = RPCServer::new(config).unwrap();
+
+        // Not available until the network is attached
+        assert!(server.get_network_info().await.is_err());
+
+        let network_info: NetworkInfo = serde_json::from_value(serde_json::json!({
+            "peer_id": "12D3KooWTest",
+            "listen_addrs": ["/ip4/127.0.0.1/tcp/4001"],
+            "observed_addrs": ["/ip4/1.2.3.4/tcp/4001"],
+            "external_addrs": [],
+            "nat_status": "Private",
+            "relay_enabled": true,
+            "hole_punching_enabled": true,
+            "direct_upgrades": 0,
+            "connected_peers": 3,
+            "discovered_peers": 1,
+            "bootstrap_attempts": 1,
+            "transport_security": "Noise",
+            "private_network": null
+        })).unwrap();
+        server.attach_network(Arc::new(RwLock::new(network_info)));
+
+        let info = server.get_network_info().await.unwrap();
+        assert_eq!(info["observed_addrs"][0], "/ip4/1.2.3.4/tcp/4001");
+        assert_eq!(info["nat_status"], "Private");
+        assert_eq!(info["connected_peers"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_net_peer_stats() {
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(server.net_peer_stats().await.is_err());
+
+        let peer = net_p2p::PeerId::random();
+        let mut bandwidth = BandwidthTracker::new(net_p2p::BandwidthLimits {
+            upload_bytes_per_sec: Some(10),
+            ..Default::default()
+        });
+        bandwidth.record(peer, net_p2p::bandwidth::Protocol::Sync, net_p2p::bandwidth::Direction::Sent, 500);
+        server.attach_bandwidth(Arc::new(RwLock::new(bandwidth)));
+
+        let stats = server.net_peer_stats().await.unwrap();
+        assert_eq!(stats["upload_bytes_per_sec"], 10);
+        assert_eq!(stats["peers"][0]["peer_id"], peer.to_string());
+        assert_eq!(stats["peers"][0]["sync"]["sent"], 500);
+        assert_eq!(stats["peers"][0]["total"]["sent"], 500);
+        assert_eq!(stats["peers"][0]["throttled"], true);
+    }
+
+    #[tokio::test]
+    async fn test_get_mining_workers() {
+        let config = RPCServerConfig::default();
+        let mut server = RPCServer::new(config).unwrap();
+        assert!(server.get_mining_workers().await.is_err());
+
+        let worker: WorkerStats = serde_json::from_value(serde_json::json!({
+            "worker_id": "1",
+            "login": "fire",
+            "agent": "xmrig",
+            "address": "127.0.0.1:50000",
+            "accepted_shares": 10,
+            "rejected_shares": 0,
+            "stale_shares": 1,
+            "fuego_blocks": 0,
+            "c0dl3_blocks": 1,
+            "hashrate": 1500,
+            "connected_at": 1700000000,
+            "last_share": 1700000100
+        })).unwrap();
+        let workers = HashMap::from([("1".to_string(), worker)]);
+        server.attach_mining(Arc::new(RwLock::new(workers)));
+
+        let info = server.get_mining_workers().await.unwrap();
+        assert_eq!(info["total_hashrate"], 1500);
+        assert_eq!(info["workers"][0]["login"], "fire");
+    }
+
+    #[tokio::test]
+    async fn test_get_mining_stale_blocks() {
+        let config = RPCServerConfig::default();
+        let mut server = RPCServer::new(config).unwrap();
+        assert!(server.get_mining_stale_blocks(10).await.is_err());
+
+        let mut tracker = StaleTracker::default();
+        tracker.record_mined(5, [1u8; 32]);
+        tracker.record_mined(6, [2u8; 32]);
+        tracker.record_canonical(5, [3u8; 32]);
+        tracker.record_canonical(6, [4u8; 32]);
+        server.attach_stale_blocks(Arc::new(RwLock::new(tracker)));
+
+        let info = server.get_mining_stale_blocks(1).await.unwrap();
+        assert_eq!(info["stale_blocks"], 2);
+        assert_eq!(info["recent"].as_array().unwrap().len(), 1);
+        assert_eq!(info["recent"][0]["height"], 6);
+        assert_eq!(info["recent"][0]["competing_hash"], "04".repeat(32));
+    }
+
+    #[tokio::test]
+    async fn test_get_mining_payouts() {
+        let config = RPCServerConfig::default();
+        let mut server = RPCServer::new(config).unwrap();
+        assert!(server.get_mining_payouts(10).await.is_err());
+
+        let mut ledger = PplnsLedger::open(mining::PplnsConfig::default()).unwrap();
+        ledger.record_share("fire", 5000);
+        server.attach_pplns(Arc::new(RwLock::new(ledger)));
+
+        let info = server.get_mining_payouts(10).await.unwrap();
+        assert_eq!(info["window_shares"], 1);
+        assert_eq!(info["window_difficulty"], 5000);
+        assert_eq!(info["payouts"].as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sponsor_transaction() {
+        use block_sync::signatures::sign_fee_payer;
+        use ed25519_dalek::SigningKey;
+
+        let server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        let tx = serde_json::json!({
+            "hash": vec![7u8; 32],
+            "sender": [0xa1],
+            "inputs": [],
+            "outputs": [],
+            "fee": 21_000,
+            "timestamp": 1,
+            "chain_id": 1,
+        });
+        let unsponsored: Transaction = serde_json::from_value(tx.clone()).unwrap();
+        let hashes = server.tx_get_signing_hashes(&tx, Some("fe")).await.unwrap();
+        assert_eq!(hashes["signingHash"], hex::encode(unsponsored.signing_hash()));
+        assert_eq!(hashes["feePayerHash"], hex::encode(unsponsored.fee_payer_hash(&[0xfe])));
+
+        let signature = hex::encode(sign_fee_payer(&SigningKey::from_bytes(&[0xfe; 32]), &unsponsored, &[0xfe]));
+        let sponsored = server.tx_sponsor(&tx, "fe", &signature).await.unwrap();
+        let sponsored: Transaction = serde_json::from_value(sponsored).unwrap();
+        assert_eq!(sponsored.fee_account(), [0xfe]);
+        // The signature covers the account the fee is paid from, which cannot be the sender
+        assert!(server.tx_sponsor(&tx, "fd", &signature).await.is_err());
+        assert!(server.tx_sponsor(&tx, "a1", &signature).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_finalized_head() {
+        use consensus::engine::Checkpoint;
+        use consensus::finality::FinalityConfig;
+
+        let config = RPCServerConfig::default();
+        let mut server = RPCServer::new(config).unwrap();
+        assert!(server.get_finalized_head().await.is_err());
+
+        let finality = Arc::new(RwLock::new(FinalityGadget::new(FinalityConfig::default()).unwrap()));
+        server.attach_finality(finality.clone());
+        assert_eq!(server.get_finalized_head().await.unwrap()["finalized"], false);
+
+        let checkpoint = Checkpoint {
+            height: 42,
+            hash: [0xab; 32],
+            timestamp: 1_700_000_000,
+        };
+        finality.write().await.record_finalized(checkpoint).await.unwrap();
+        let head = server.get_finalized_head().await.unwrap();
+        assert_eq!(head["height"], 42);
+        assert_eq!(head["hash"], "ab".repeat(32));
+    }
+
+    #[tokio::test]
+    async fn test_get_state() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(server.get_state("00", None).await.is_err());
+
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+        assert!(matches!(server.get_state("00", None).await, Err(RPCError::NotFound(_))));
+
+        let root = {
+            let mut db = state_db.write().await;
+            db.put_sync(b"key", b"old").unwrap();
+            db.commit_sync(1).unwrap();
+            db.put_sync(b"key", b"new").unwrap();
+            db.commit_sync(2).unwrap();
+            db.root_at(1).unwrap().unwrap()
+        };
+
+        let state = server.get_state(&hex::encode(b"key"), Some(1)).await.unwrap();
+        assert_eq!(state["value"], hex::encode(b"old"));
+        assert_eq!(state["root"], hex::encode(root));
+        let proof: state_db::merkle::SparseMerkleProof = serde_json::from_value(state["proof"].clone()).unwrap();
+        assert!(proof.verify(&root, b"key", Some(b"old")));
+
+        let latest = server.get_state(&hex::encode(b"key"), None).await.unwrap();
+        assert_eq!(latest["version"], 2);
+        assert_eq!(latest["value"], hex::encode(b"new"));
+        assert!(server.get_state("zz", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_submit_block_reports_rejection_stage() {
+        use block_sync::{BlockProof, ProofType};
+        use consensus::ConsensusConfig;
+
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(matches!(server.submit_block("00", 1, "00").await, Err(RPCError::ServiceUnavailable(_))));
+        let consensus = Consensus::new(ConsensusConfig::default()).unwrap();
+        server.attach_consensus(Arc::new(RwLock::new(consensus)));
+        assert!(matches!(server.submit_block("zz", 1, "00").await, Err(RPCError::InvalidParameters(_))));
+
+        let block = |height: u64| Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000,
+                nonce: 0,
+                difficulty: 1000,
+                nullifier_root: [0u8; 32],
+            },
+            transactions: vec![],
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        };
+        for (height, stage, reason) in [(0, "pow", "missing_pow"), (5, "contextual", "unknown_parent")] {
+            let encoded = hex::encode(block(height).to_canonical_bytes());
+            let result = server.submit_block(&encoded, 1, "00").await.unwrap();
+            assert_eq!(result["accepted"], false);
+            assert_eq!(result["stage"], stage);
+            assert_eq!(result["reason"], reason);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_light_client_headers_and_state_proofs() {
+        use block_sync::{Block, BlockProof, ProofType};
+        use execution::{BlockExecutor, ExecutionConfig};
+        use state_db::merkle::ProofLeaf;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(matches!(server.get_headers_range(0, 10).await, Err(RPCError::ServiceUnavailable(_))));
+
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+        {
+            let mut db = state_db.write().await;
+            let mut genesis = state_db::Genesis::default();
+            genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 100 });
+            db.apply_genesis(&genesis).unwrap();
+            let block = Block {
+                header: BlockHeader {
+                    height: 1,
+                    prev_hash: [0u8; 32],
+                    merkle_root: [0u8; 32],
+                    timestamp: 1_000,
+                    nonce: 0,
+                    difficulty: 1,
+                    nullifier_root: [0u8; 32],
+                },
+                transactions: Vec::new(),
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: vec![],
+                },
+            };
+            let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+            executor.process_block(&mut db, &block, &[0xfe]).unwrap();
+        }
+
+        let headers = server.get_headers_range(0, 10).await.unwrap();
+        assert_eq!(headers.as_array().unwrap().len(), 1);
+        assert_eq!(headers[0]["height"], 1);
+        let encoded = hex::decode(headers[0]["encoded"].as_str().unwrap()).unwrap();
+        let decoded = BlockHeader::from_canonical_bytes(&encoded).unwrap();
+        assert_eq!(hex::encode(decoded.hash().unwrap()), headers[0]["hash"]);
+        assert!(server.get_headers_range(0, 100_000).await.is_err());
+
+        // The proof verifies against the state root served with the header
+        let key = state_db::account::account_key(&[0xa1]);
+        let response = server.get_state_proof(&hex::encode(&key), 1).await.unwrap();
+        assert_eq!(response["stateRoot"], headers[0]["stateRoot"]);
+        let hash = |value: &serde_json::Value| {
+            <[u8; 32]>::try_from(hex::decode(value.as_str().unwrap()).unwrap()).unwrap()
+        };
+        let proof = SparseMerkleProof {
+            leaf: response["proof"]["leaf"].as_object().map(|leaf| ProofLeaf {
+                key_hash: hash(&leaf["keyHash"]),
+                value_hash: hash(&leaf["valueHash"]),
+            }),
+            siblings: response["proof"]["siblings"].as_array().unwrap().iter().map(hash).collect(),
+        };
+        let value = hex::decode(response["value"].as_str().unwrap()).unwrap();
+        assert!(proof.verify(&hash(&response["stateRoot"]), &key, Some(&value)));
+        assert!(matches!(server.get_state_proof("00", 7).await, Err(RPCError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_supply() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(matches!(server.get_supply().await, Err(RPCError::ServiceUnavailable(_))));
+
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+        assert!(matches!(server.get_supply().await, Err(RPCError::NotFound(_))));
+
+        let genesis: state_db::Genesis = serde_json::from_value(serde_json::json!({
+            "alloc": { "a1": { "balance": 1_000 } }
+        }))
+        .unwrap();
+        state_db.write().await.apply_genesis(&genesis).unwrap();
+        let supply = server.get_supply().await.unwrap();
+        assert_eq!((supply["genesis"].as_u64(), supply["circulating"].as_u64()), (Some(1_000), Some(1_000)));
+        assert_eq!(supply["bridgedOut"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_eldernode_namespace() {
+        use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+        use execution::{BlockExecutor, EldernodeCommand, ExecutionConfig, ELDERNODE_REGISTRY_ADDRESS};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(matches!(server.eldernode_list().await, Err(RPCError::ServiceUnavailable(_))));
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+        {
+            let mut db = state_db.write().await;
+            let mut genesis = state_db::Genesis::default();
+            genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 1_000_000 });
+            db.apply_genesis(&genesis).unwrap();
+
+            let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+            let registration = Transaction {
+                hash: [0x11; 32],
+                sender: vec![0xa1],
+                nonce: 0,
+                gas_limit: 100_000,
+                data: EldernodeCommand::Register.to_data(),
+                inputs: vec![],
+                outputs: vec![TxOutput {
+                    amount: 800_000,
+                    address: ELDERNODE_REGISTRY_ADDRESS.to_vec(),
+                    commitment: [0u8; 32],
+                    ephemeral_key: None,
+                }],
+                fee: 100_000,
+                timestamp: 1_000,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
+            };
+            let block = Block {
+                header: BlockHeader {
+                    height: 1,
+                    prev_hash: [0u8; 32],
+                    merkle_root: [0u8; 32],
+                    timestamp: 1_000,
+                    nonce: 0,
+                    difficulty: 1,
+                    nullifier_root: [0u8; 32],
+                },
+                transactions: vec![registration],
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: vec![],
+                },
+            };
+            executor.process_block(&mut db, &block, &[0xfe]).unwrap();
+        }
+
+        let nodes = server.eldernode_list().await.unwrap();
+        assert_eq!((nodes[0]["address"].as_str(), nodes[0]["stake"].as_u64()), (Some("a1"), Some(800_000)));
+        assert_eq!(server.eldernode_get_node("0xa1").await.unwrap()["blocksServed"], 1);
+        assert!(server.eldernode_get_node("b0").await.unwrap().is_null());
+        let servers = server.eldernode_get_block_servers(1).await.unwrap();
+        assert_eq!(servers["eldernodes"][0]["address"], "a1");
+        assert!(server.eldernode_get_block_servers(2).await.unwrap()["eldernodes"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_deposit_addresses() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap())));
+        let account = "e0".repeat(20);
+
+        let first = server.exchange_new_deposit_address(&account).await.unwrap();
+        assert_eq!((first["index"].as_u64(), &first["stealthAddress"]), (Some(0), &serde_json::Value::Null));
+        let keys = zk_proofs::StealthKeys::from_seed(b"exchange");
+        let (scan_secret, spend_public) = (hex::encode(keys.scan_secret()), hex::encode(keys.address().spend_public));
+        assert!(server.exchange_register_view_key(&account, &scan_secret, "zz").await.is_err());
+        server.exchange_register_view_key(&account, &scan_secret, &spend_public).await.unwrap();
+        let second = server.exchange_new_deposit_address(&account).await.unwrap();
+        assert_eq!(second["index"], 1);
+        assert_eq!(second["stealthAddress"], hex::encode(keys.subaddress(1).to_bytes()));
+        assert_ne!(first["address"], second["address"]);
+    }
+
+    #[tokio::test]
+    async fn test_base_fee_history() {
+        use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+        use execution::{BlockExecutor, ExecutionConfig, FeeMarketConfig};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+        {
+            let mut db = state_db.write().await;
+            let mut genesis = state_db::Genesis::default();
+            genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 1_000_000 });
+            db.apply_genesis(&genesis).unwrap();
+
+            let fee_market = FeeMarketConfig { enabled: true, initial_base_fee: 2, ..Default::default() };
+            let mut executor = BlockExecutor::new(ExecutionConfig { fee_market, ..Default::default() }).unwrap();
+            let transfer = Transaction {
+                hash: [0x11; 32],
+                sender: vec![0xa1],
+                nonce: 0,
+                gas_limit: 100_000,
+                data: vec![],
+                inputs: vec![],
+                outputs: vec![TxOutput {
+                    amount: 100,
+                    address: vec![0xb0],
+                    commitment: [0u8; 32],
+                    ephemeral_key: None,
+                }],
+                fee: 300_000,
+                timestamp: 1_000,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
+            };
+            let block = Block {
+                header: BlockHeader {
+                    height: 1,
+                    prev_hash: [0u8; 32],
+                    merkle_root: [0u8; 32],
+                    timestamp: 1_000,
+                    nonce: 0,
+                    difficulty: 1,
+                    nullifier_root: [0u8; 32],
+                },
+                transactions: vec![transfer],
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: vec![],
+                },
+            };
+            executor.process_block(&mut db, &block, &[0xfe]).unwrap();
+        }
+
+        let history = server.fees_get_base_fee_history(0, 10).await.unwrap();
+        let fees = &history["blocks"][0];
+        assert_eq!((fees["height"].as_u64(), fees["baseFee"].as_u64()), (Some(1), Some(2)));
+        assert_eq!(fees["baseFees"].as_u64().unwrap(), 2 * fees["gasUsed"].as_u64().unwrap());
+        // A nearly empty block lowers the base fee by an eighth, rounded down to nothing here
+        assert_eq!(history["nextBaseFee"], 2);
+        assert_eq!(server.get_supply().await.unwrap()["feesBurned"], fees["baseFees"]);
+        assert!(server.fees_get_base_fee_history(0, 100_000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rewards_namespace() {
+        use rewards::{BlockRecipients, RewardsConfig, RewardsEngine};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(matches!(server.rewards_get_rewards("11").await, Err(RPCError::ServiceUnavailable(_))));
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+
+        let mut engine = RewardsEngine::with_state_db(RewardsConfig::default(), state_db.clone()).await.unwrap();
+        let recipients = BlockRecipients { miner: vec![0x11], ..Default::default() };
+        engine.record_block(1, 1_000, &recipients).await.unwrap();
+        engine.claim(&[0x11], 1).await.unwrap();
+
+        let rewards = server.rewards_get_rewards("0x11").await.unwrap();
+        assert_eq!((rewards["accrued"].as_u64(), rewards["claimable"].as_u64()), (Some(600), Some(0)));
+        assert_eq!(rewards["grants"][0]["class"], "miner");
+        assert!(server.rewards_get_rewards("22").await.unwrap().is_null());
+        let epoch = server.rewards_get_epoch(0).await.unwrap();
+        assert_eq!((epoch["total"].as_u64(), epoch["byClass"]["treasury"].as_u64()), (Some(1_000), Some(400)));
+        assert!(server.rewards_get_epoch(1).await.unwrap().is_null());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_export_import() {
+        let (source_dir, archive, target_dir) = (
+            tempfile::TempDir::new().unwrap(),
+            tempfile::TempDir::new().unwrap(),
+            tempfile::TempDir::new().unwrap(),
+        );
+        let path = archive.path().to_str().unwrap();
+
+        let mut source = RPCServer::new(RPCServerConfig::default()).unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(source_dir.path()).unwrap()));
+        source.attach_state_db(state_db.clone());
+        assert!(source.snapshot_export(path, None).await.is_err());
+        {
+            let mut db = state_db.write().await;
+            db.put_sync(b"key", b"value").unwrap();
+            db.commit_sync(7).unwrap();
+        }
+        let exported = source.snapshot_export(path, Some(7)).await.unwrap();
+        assert_eq!(exported["entries"], 1);
+
+        let mut target = RPCServer::new(RPCServerConfig::default()).unwrap();
+        target.attach_state_db(Arc::new(RwLock::new(RocksStateDB::new(target_dir.path()).unwrap())));
+        assert!(matches!(
+            target.snapshot_import(path, Some("00")).await,
+            Err(RPCError::InvalidParameters(_))
+        ));
+        let root = exported["root"].as_str().unwrap();
+        let imported = target.snapshot_import(path, Some(root)).await.unwrap();
+        assert_eq!(imported["version"], 7);
+        let state = target.get_state(&hex::encode(b"key"), None).await.unwrap();
+        assert_eq!(state["value"], hex::encode(b"value"));
+    }
+
+    #[tokio::test]
+    async fn test_receipts_and_logs() {
+        use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+        use execution::{BlockExecutor, ExecutionConfig};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(server.eth_get_transaction_receipt(&"11".repeat(32)).await.is_err());
+
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        server.attach_state_db(state_db.clone());
+        {
+            let mut db = state_db.write().await;
+            let mut genesis = state_db::Genesis::default();
+            genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 100_000 });
+            db.apply_genesis(&genesis).unwrap();
+
+            let transaction = Transaction {
+                hash: [0x11; 32],
+                sender: vec![0xa1],
+                nonce: 0,
+                gas_limit: 50_000,
+                data: Vec::new(),
+                inputs: vec![],
+                outputs: vec![TxOutput {
+                    amount: 40,
+                    address: vec![0xb0],
+                    commitment: [0u8; 32],
+                    ephemeral_key: None,
+                }],
+                fee: 50_000,
+                timestamp: 1_000,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
+            };
+            let block = Block {
+                header: BlockHeader {
+                    height: 1,
+                    prev_hash: [0u8; 32],
+                    merkle_root: [0u8; 32],
+                    timestamp: 1_000,
+                    nonce: 0,
+                    difficulty: 1,
+                    nullifier_root: [0u8; 32],
+                },
+                transactions: vec![transaction],
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: vec![],
+                },
+            };
+            let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+            executor.process_block(&mut db, &block, &[0xfe]).unwrap();
+        }
+
+        let receipt = server.eth_get_transaction_receipt(&format!("0x{}", "11".repeat(32))).await.unwrap();
+        assert_eq!(receipt["status"], 1);
+        assert_eq!(receipt["blockNumber"], 1);
+        assert_eq!(receipt["gasUsed"], receipt["fee"]);
+        assert_eq!(receipt["logs"][0]["address"], "a1");
+        assert!(server.eth_get_transaction_receipt(&"22".repeat(32)).await.unwrap().is_null());
+        assert!(matches!(
+            server.eth_get_transaction_receipt("11").await,
+            Err(RPCError::InvalidParameters(_))
+        ));
+
+        let recipient_topic = format!("{}b0", "00".repeat(31));
+        let logs = server
+            .eth_get_logs(&serde_json::json!({
+                "fromBlock": "0x0",
+                "toBlock": "latest",
+                "address": "0xa1",
+                "topics": [null, null, [recipient_topic]],
+            }))
+            .await
+            .unwrap();
+        assert_eq!(logs.as_array().unwrap().len(), 1);
+        assert_eq!(logs[0]["data"], hex::encode(40u64.to_be_bytes()));
+        let none = server.eth_get_logs(&serde_json::json!({ "address": ["b0"] })).await.unwrap();
+        assert!(none.as_array().unwrap().is_empty());
+        assert!(server.eth_get_logs(&serde_json::json!({ "topics": "zz" })).await.is_err());
+
+        // Tracing re-executes the block and agrees with its receipt
+        assert!(matches!(server.debug_trace_block(1).await, Err(RPCError::ServiceUnavailable(_))));
+        server.attach_executor(Arc::new(BlockExecutor::new(ExecutionConfig::default()).unwrap()));
+        let trace = server.debug_trace_transaction(&"11".repeat(32)).await.unwrap();
+        assert_eq!((&trace["gasUsed"], &trace["logs"]), (&receipt["gasUsed"], &receipt["logs"]));
+        assert_eq!(trace["gasSteps"][0]["op"], "intrinsic");
+        assert_eq!(trace["gasSteps"].as_array().unwrap().last().unwrap()["cumulativeGas"], receipt["gasUsed"]);
+        let changes = trace["balanceChanges"].as_array().unwrap();
+        let recipient = &changes[1];
+        assert_eq!(recipient["address"], "b0");
+        assert_eq!((&recipient["before"], &recipient["after"]), (&0.into(), &40.into()));
+        assert_eq!(server.debug_trace_block(1).await.unwrap()[0], trace);
+        assert!(server.debug_trace_transaction(&"22".repeat(32)).await.unwrap().is_null());
+        assert!(matches!(server.debug_trace_block(2).await, Err(RPCError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_privacy_scan_outputs() {
+        use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+        use execution::{BlockExecutor, ExecutionConfig};
+        use zk_proofs::StealthKeys;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(state_db.clone());
+
+        let recipient = StealthKeys::from_seed(b"recipient");
+        let other = StealthKeys::from_seed(b"other");
+        let stealth_output = |keys: &StealthKeys, output_index: u32| {
+            let output = keys.address().derive_output(output_index, &mut rand::rngs::OsRng).unwrap();
+            TxOutput {
+                amount: 10 + output_index as u64,
+                address: output.one_time_key.to_vec(),
+                commitment: [0u8; 32],
+                ephemeral_key: Some(output.ephemeral_key),
+            }
+        };
+        {
+            let mut db = state_db.write().await;
+            let mut genesis = state_db::Genesis::default();
+            genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 1_000_000 });
+            db.apply_genesis(&genesis).unwrap();
+
+            let transaction = Transaction {
+                hash: [0x11; 32],
+                sender: vec![0xa1],
+                nonce: 0,
+                gas_limit: 200_000,
+                data: Vec::new(),
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
+                inputs: vec![],
+                outputs: vec![stealth_output(&other, 0), stealth_output(&recipient, 1)],
+                fee: 200_000,
+                timestamp: 1_000,
+            };
+            let block = Block {
+                header: BlockHeader {
+                    height: 1,
+                    prev_hash: [0u8; 32],
+                    merkle_root: [0u8; 32],
+                    timestamp: 1_000,
+                    nonce: 0,
+                    difficulty: 1,
+                    nullifier_root: [0u8; 32],
+                },
+                transactions: vec![transaction],
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: vec![],
+                },
+            };
+            let mut executor = BlockExecutor::new(ExecutionConfig::default()).unwrap();
+            executor.process_block(&mut db, &block, &[0xfe]).unwrap();
+        }
+
+        let scan_secret = hex::encode(recipient.scan_secret());
+        let spend_public = hex::encode(recipient.address().spend_public);
+        let outputs = server.privacy_scan_outputs(&scan_secret, &spend_public, 0, 10).await.unwrap();
+        assert_eq!(outputs.as_array().unwrap().len(), 1);
+        assert_eq!(outputs[0]["outputIndex"], 1);
+        assert_eq!(outputs[0]["amount"], 11);
+        assert_eq!(outputs[0]["transactionHash"], "11".repeat(32));
+        assert!(server.privacy_scan_outputs(&scan_secret, "zz", 0, 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_privacy_audit_report() {
+        use block_sync::{Block, BlockHeader, BlockProof, ProofType, RingInput, Transaction, TxOutput};
+        use execution::{BlockExecutor, ExecutionConfig};
+        use state_db::nullifier::accumulate_nullifiers;
+        use zk_proofs::{RingSignature, StealthKeys};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(state_db.clone());
+
+        let company = StealthKeys::from_seed(b"company");
+        let output = company.address().derive_output(0, &mut rand::rngs::OsRng).unwrap();
+        let transaction = |hash: u8, sender: u8, outputs: Vec<TxOutput>, ring_inputs: Vec<RingInput>| Transaction {
+            hash: [hash; 32],
+            sender: vec![sender],
+            nonce: 0,
+            gas_limit: 200_000,
+            data: Vec::new(),
+            nullifiers: Vec::new(),
+            ring_inputs,
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
+            inputs: vec![],
+            outputs,
+            fee: 200_000,
+            timestamp: 1_000,
+        };
+        let block = |height: u64, nullifier_root: [u8; 32], transactions: Vec<Transaction>| Block {
+            header: BlockHeader {
+                height,
+                prev_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_000 + height,
+                nonce: 0,
+                difficulty: 1,
+                nullifier_root,
+            },
+            transactions,
+            proof: BlockProof {
+                proof_type: ProofType::PoW,
+                proof_data: vec![],
+            },
+        };
+        {
+            let mut db = state_db.write().await;
+            let mut genesis = state_db::Genesis::default();
+            genesis.alloc.insert("a1".to_string(), state_db::account::GenesisAccount { balance: 1_000_000 });
+            db.apply_genesis(&genesis).unwrap();
+            let config = ExecutionConfig { ring_size: 1, ..ExecutionConfig::default() };
+            let mut executor = BlockExecutor::new(config).unwrap();
+
+            let payment = TxOutput {
+                amount: 300_000,
+                address: output.one_time_key.to_vec(),
+                commitment: [0u8; 32],
+                ephemeral_key: Some(output.ephemeral_key),
+            };
+            let paid = block(1, [0u8; 32], vec![transaction(0x11, 0xa1, vec![payment], vec![])]);
+            executor.process_block(&mut db, &paid, &[0xfe]).unwrap();
+
+            let ring = vec![output.one_time_key];
+            let secret = company.one_time_secret(0, &output).unwrap();
+            let key_image = zk_proofs::key_image(&secret).unwrap();
+            let input = RingInput {
+                amount: 300_000,
+                ring: ring.clone(),
+                key_image,
+                signature: Vec::new(),
+                audit_tag: Some(company.audit_tag(&key_image, &output.one_time_key)),
+            };
+            let refund = TxOutput { amount: 50, address: vec![0xb0], commitment: [0u8; 32], ephemeral_key: None };
+            let mut spend = transaction(0x22, 0xc0, vec![refund], vec![input]);
+            let signature = RingSignature::sign(&spend.signing_hash(), &ring, 0, &secret, &mut rand::rngs::OsRng);
+            spend.ring_inputs[0].signature = signature.unwrap().to_bytes();
+            let root = accumulate_nullifiers(&[0u8; 32], &[key_image]);
+            let spent = block(2, root, vec![spend]);
+            executor.process_block(&mut db, &spent, &[0xfe]).unwrap();
+        }
+
+        let viewing_key = hex::encode(company.viewing_key().to_bytes());
+        let report = server.privacy_export_audit_report(&viewing_key, 0, 10).await.unwrap();
+        assert_eq!(report["toBlock"], 2);
+        assert_eq!(report["incoming"].as_array().unwrap().len(), 1);
+        assert_eq!(report["outgoing"].as_array().unwrap().len(), 1);
+        assert_eq!(report["outgoing"][0]["amount"], 300_000);
+
+        let checked = server.privacy_verify_audit_report(&report, Some(&viewing_key)).await.unwrap();
+        assert_eq!(checked["valid"], true);
+        assert_eq!(checked["complete"], true);
+
+        // Dropping the spend breaks the signature, and re-signing cannot hide it from the viewing key
+        let mut partial: AuditReport = serde_json::from_value(report.clone()).unwrap();
+        partial.outgoing.clear();
+        let unsigned = serde_json::to_value(&partial).unwrap();
+        assert_eq!(server.privacy_verify_audit_report(&unsigned, None).await.unwrap()["valid"], false);
+        partial.signature = company.viewing_key().sign(&partial.digest().unwrap()).to_vec();
+        let resigned = serde_json::to_value(&partial).unwrap();
+        assert_eq!(server.privacy_verify_audit_report(&resigned, None).await.unwrap()["valid"], true);
+        assert_eq!(server.privacy_verify_audit_report(&resigned, Some(&viewing_key)).await.unwrap()["valid"], false);
+        let other = hex::encode(StealthKeys::from_seed(b"other").viewing_key().to_bytes());
+        let checked = server.privacy_verify_audit_report(&report, Some(&other)).await.unwrap();
+        assert_eq!(checked["valid"], false);
+        assert!(server.privacy_export_audit_report("00", 0, 10).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bridge_get_withdrawal_proof() {
+        use bridge::withdrawals::{verify_merkle_proof, WithdrawalConfig, WithdrawalQueue};
+        use execution::receipt::{address_topic, withdrawal_topic};
+        use execution::WITHDRAWAL_ADDRESS;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(state_db.clone());
+
+        let withdrawal_log = |log_index: u32| Log {
+            address: WITHDRAWAL_ADDRESS.to_vec(),
+            topics: vec![withdrawal_topic(), address_topic(&[0xa1]), address_topic(&[0x1e; 20])],
+            data: 75u64.to_be_bytes().to_vec(),
+            block_height: 3,
+            tx_hash: [0x33; 32],
+            tx_index: 0,
+            log_index,
+        };
+        let receipt = Receipt {
+            tx_hash: [0x33; 32],
+            block_height: 3,
+            tx_index: 0,
+            status: ReceiptStatus::Success,
+            gas_used: 0,
+            cumulative_gas_used: 0,
+            fee: 0,
+            contract_address: None,
+            revert_reason: None,
+            logs: vec![withdrawal_log(0), withdrawal_log(1), withdrawal_log(2)],
+        };
+        let mut queue = WithdrawalQueue::with_state_db(WithdrawalConfig::default(), state_db.clone())
+            .await
+            .unwrap();
+        queue.ingest(&[receipt]).await.unwrap();
+        assert!(server.bridge_get_withdrawal_proof(&"33".repeat(32), 1).await.unwrap().is_null());
+        let batch = queue.seal_batch().await.unwrap().unwrap();
+
+        let proof = server.bridge_get_withdrawal_proof(&"33".repeat(32), 1).await.unwrap();
+        assert_eq!(proof["root"], hex::encode(batch.root));
+        assert_eq!(proof["committed"], false);
+        assert_eq!(proof["withdrawal"]["recipient"], "1e".repeat(20));
+        assert_eq!(proof["withdrawal"]["amount"], 75);
+        let siblings: Vec<[u8; 32]> = proof["proof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|sibling| parse_hash(sibling.as_str().unwrap(), "sibling").unwrap())
+            .collect();
+        let leaf = parse_hash(proof["leaf"].as_str().unwrap(), "leaf").unwrap();
+        assert!(verify_merkle_proof(&leaf, 1, &siblings, &batch.root));
+        assert!(server.bridge_get_withdrawal_proof("33", 1).await.is_err());
+
+        // Each withdrawal's lifecycle is listed by status and by its sender
+        let sender = format!("{}a1", "00".repeat(19));
+        let transfers = server.bridge_list_transfers(Some("submitted"), Some(&sender)).await.unwrap();
+        assert_eq!(transfers.as_array().unwrap().len(), 3);
+        assert_eq!(transfers[0]["kind"], "withdrawal");
+        assert_eq!(transfers[0]["history"][1]["detail"], "batch 0");
+        assert_eq!(server.bridge_list_transfers(Some("pending"), None).await.unwrap(), serde_json::json!([]));
+        assert!(server.bridge_list_transfers(Some("lost"), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bridge_get_proof_submission() {
+        use bridge::submission::{SubmissionAttempt, SubmissionCostStats, SubmissionRecord, SubmissionState};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(state_db.clone());
+
+        let record = SubmissionRecord {
+            proof_id: [0x44; 32],
+            nonce: 9,
+            attempts: vec![
+                SubmissionAttempt { tx_hash: [0x01; 32], gas_price: 100, sent_at: 1 },
+                SubmissionAttempt { tx_hash: [0x02; 32], gas_price: 112, sent_at: 61 },
+            ],
+            state: SubmissionState::Confirmed { block_number: 20 },
+            included_tx: Some([0x02; 32]),
+            gas_used: 60_000,
+            cost: 6_720_000,
+        };
+        let stats = SubmissionCostStats {
+            submitted: 1,
+            confirmed: 1,
+            reverted: 0,
+            replacements: 1,
+            total_gas_used: 60_000,
+            total_cost: 6_720_000,
+        };
+        state_db
+            .write()
+            .await
+            .write_batch_sync(&[
+                ([b"bridge/proof_submissions/record/".as_slice(), &[0x44; 32]].concat(), serde_json::to_vec(&record).unwrap()),
+                (b"bridge/proof_submissions/stats".to_vec(), serde_json::to_vec(&stats).unwrap()),
+            ])
+            .unwrap();
+
+        let submission = server.bridge_get_proof_submission(&"44".repeat(32)).await.unwrap();
+        assert_eq!(submission["status"], "confirmed");
+        assert_eq!(submission["blockNumber"], 20);
+        assert_eq!(submission["transactionHash"], "02".repeat(32));
+        assert_eq!(submission["attempts"].as_array().unwrap().len(), 2);
+        assert_eq!(submission["cost"], "6720000");
+        assert!(server.bridge_get_proof_submission(&"55".repeat(32)).await.unwrap().is_null());
+
+        let costs = server.bridge_get_proof_costs().await.unwrap();
+        assert_eq!(costs["averageGasUsed"], 60_000);
+        assert_eq!(costs["replacements"], 1);
+        assert_eq!(costs["totalCost"], "6720000");
+    }
+
+    #[tokio::test]
+    async fn test_bridge_get_reorg_incidents() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(matches!(server.bridge_get_reorg_incidents().await, Err(RPCError::ServiceUnavailable(_))));
+        server.attach_state_db(state_db.clone());
+        assert_eq!(server.bridge_get_reorg_incidents().await.unwrap(), serde_json::json!([]));
+
+        let incident = |common_ancestor| ReorgIncident {
+            detected_at: 1_700_000_000,
+            l1_head: 130,
+            common_ancestor,
+            orphaned_block: 114,
+            rolled_back_mints: 2,
+            irreversible_mints: vec![[0x0d; 32]],
+        };
+        let incidents = serde_json::to_vec(&vec![incident(99), incident(109)]).unwrap();
+        state_db.write().await.write_batch_sync(&[(b"bridge/reorg_incidents".to_vec(), incidents)]).unwrap();
+
+        let incidents = server.bridge_get_reorg_incidents().await.unwrap();
+        assert_eq!(incidents[0]["commonAncestor"], 109);
+        assert_eq!(incidents[1]["irreversibleMints"][0], "0d".repeat(32));
+    }
+
+    #[tokio::test]
+    async fn test_bridge_challenge_game() {
+        use bridge::batches::{BatchSubmission, L1Batch};
+        use bridge::challenges::{choice_message, one_step, open_message};
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(state_db.clone());
+        let first = one_step(&[0u8; 32], b"block 0");
+        let states = [[0u8; 32], first, one_step(&first, b"block 1")];
+        let batch = L1Batch {
+            number: 0,
+            first_block: 0,
+            last_block: 1,
+            blocks_hash: [0; 32],
+            state_root: states[2],
+            priority_ops_hash: [0; 32],
+            priority_ops: 0,
+            transactions: 0,
+            submission: Some(BatchSubmission { tx_hash: [1; 32], nonce: 0, gas_price: 1, submitted_at: 0 }),
+        };
+        let key = [b"bridge/batches/batch/".as_slice(), &0u64.to_be_bytes()].concat();
+        state_db.write().await.write_batch_sync(&[(key, serde_json::to_vec(&batch).unwrap())]).unwrap();
+
+        let challenger = SigningKey::from_bytes(&[0xc1; 32]);
+        let public = hex::encode(challenger.verifying_key().to_bytes());
+        let sign = |message: [u8; 32]| hex::encode(challenger.sign(&message).to_bytes());
+        let impostor = hex::encode(SigningKey::from_bytes(&[0xc2; 32]).sign(&open_message(0, &[9; 32])).to_bytes());
+        assert!(matches!(
+            server.bridge_open_challenge(0, &public, &hex::encode(states[2]), &sign(open_message(0, &states[2]))).await,
+            Err(RPCError::InvalidParameters(_))
+        ));
+        assert!(server.bridge_open_challenge(0, &public, &"09".repeat(32), &impostor).await.is_err());
+        let opened = sign(open_message(0, &[9; 32]));
+        let challenge = server.bridge_open_challenge(0, &public, &"09".repeat(32), &opened).await.unwrap();
+        assert_eq!((challenge["id"].clone(), challenge["status"].clone()), (serde_json::json!(0), "opened".into()));
+
+        // The bridge accepts it on the settlement layer, then the parties play
+        let mut accepted = bridge::challenges::get_challenge(&*state_db.read().await, 0).unwrap().unwrap();
+        accepted.accept(1).unwrap();
+        let entry = ([b"bridge/challenges/".as_slice(), &0u64.to_be_bytes()].concat(), serde_json::to_vec(&accepted));
+        state_db.write().await.write_batch_sync(&[(entry.0, entry.1.unwrap())]).unwrap();
+        let agreed = sign(choice_message(0, 0, true));
+        assert!(server.bridge_choose_challenge_half(0, true, &agreed).await.is_err());
+        let challenge = server.bridge_bisect_challenge(0, &hex::encode(states[1])).await.unwrap();
+        assert_eq!(challenge["toMove"], "challenger");
+        assert!(server.bridge_choose_challenge_half(0, false, &agreed).await.is_err());
+        let challenge = server.bridge_choose_challenge_half(0, true, &agreed).await.unwrap();
+        assert_eq!(challenge["status"], "awaiting_one_step_proof");
+        assert_eq!(challenge["rounds"][0]["challengerAgreed"], true);
+        let challenge = server.bridge_prove_challenge_step(0, &hex::encode(b"block 1")).await.unwrap();
+        assert_eq!(challenge["status"], "defender_won");
+
+        assert_eq!(server.bridge_get_challenge(0).await.unwrap()["agreedStep"], 1);
+        assert!(server.bridge_get_challenge(1).await.unwrap().is_null());
+        let won = server.bridge_list_challenges(Some(0), Some("defender_won")).await.unwrap();
+        assert_eq!(won.as_array().unwrap().len(), 1);
+        assert_eq!(server.bridge_list_challenges(Some(1), None).await.unwrap(), serde_json::json!([]));
+        assert!(server.bridge_list_challenges(None, Some("won")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_privacy_get_note_witness() {
+        use commitments::note_tree::NoteCommitmentTree;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        server.attach_state_db(state_db.clone());
+
+        let root = {
+            let mut db = state_db.write().await;
+            let mut tree = NoteCommitmentTree::load(&db, 8).unwrap();
+            tree.append_all(&mut db, &[[0x11; 32], [0x22; 32], [0x33; 32]]).unwrap();
+            tree.root()
+        };
+
+        let witness = server.privacy_get_note_witness(&"22".repeat(32)).await.unwrap();
+        assert_eq!(witness["index"], 1);
+        assert_eq!(witness["root"], hex::encode(root));
+        assert_eq!(witness["siblings"].as_array().unwrap().len(), 8);
+        assert_eq!(witness["siblings"][0], "11".repeat(32));
+        assert!(server.privacy_get_note_witness(&"44".repeat(32)).await.unwrap().is_null());
+        assert!(server.privacy_get_note_witness("zz").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proof_status() {
+        use zk_proofs::{ProverServiceConfig, ZkProof};
+
+        let mut server = RPCServer::new(RPCServerConfig::default()).unwrap();
+        assert!(server.proof_status(1).await.is_err());
+
+        let prover = Arc::new(ProverService::new(ProverServiceConfig { workers: 1, ..Default::default() }).unwrap());
+        server.attach_prover(prover.clone());
+        let job_id = prover
+            .submit(ProofPriority::Block, || {
+                Ok(ZkProof { circuit: "state_transition".to_string(), public_inputs: vec![[0x11; 32]], proof: vec![0xab] })
+            })
+            .unwrap();
+        let failed = prover
+            .submit(ProofPriority::Transaction, || Err(zk_proofs::ZkProofError::InvalidWitness("overdraft".to_string())))
+            .unwrap();
+
+        let mut status = server.proof_status(failed).await.unwrap();
+        while status["status"] != "failed" {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            status = server.proof_status(failed).await.unwrap();
+        }
+        assert_eq!(status["priority"], "transaction");
+        assert_eq!(status["error"], "Invalid witness: overdraft");
+
+        let completed = server.proof_status(job_id).await.unwrap();
+        assert_eq!(completed["status"], "completed");
+        assert_eq!(completed["priority"], "block");
+        assert_eq!(completed["proof"]["proof"], "ab");
+        assert_eq!(completed["proof"]["publicInputs"][0], "11".repeat(32));
+        assert!(server.proof_status(99).await.unwrap().is_null());
+    }
+
     #[tokio::test]
     async fn test_get_consensus_status() {
         let config = RPCServerConfig::default();
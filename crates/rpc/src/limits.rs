@@ -0,0 +1,226 @@
+//! Request admission for the public RPC server: a token bucket per client IP,
+//! method costs so expensive queries drain a bucket faster, and a cap on body size.
+//!
+//! The transport calls [`RPCServer::admit_request`] before parsing a request and
+//! answers a rejection with [`rejection_response`], which carries `Retry-After`
+//! for rate-limited clients.
+
+use crate::error::RPCError;
+use crate::RPCServer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Most client buckets kept before idle, refilled ones are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Rate and size limits applied to every public RPC request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Tokens a client IP regains per second
+    pub requests_per_second: u32,
+    /// Most tokens a client IP can bank, and so its largest burst
+    pub burst: u32,
+    /// Largest request body accepted, in bytes
+    pub max_body_bytes: usize,
+    /// Tokens a call costs for methods that cost more than one
+    pub method_costs: HashMap<String, u32>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let method_costs = [
+            ("eth_getLogs", 10),
+            ("debug_traceTransaction", 20),
+            ("debug_traceBlock", 20),
+            ("privacy_scanOutputs", 20),
+            ("privacy_exportAuditReport", 20),
+            ("privacy_verifyAuditReport", 10),
+            ("exchange_getDeposits", 20),
+            ("snapshot_export", 40),
+            ("snapshot_import", 40),
+            ("admin_createBackup", 40),
+        ];
+        Self {
+            enabled: true,
+            requests_per_second: 20,
+            burst: 40,
+            max_body_bytes: 1024 * 1024,
+            method_costs: method_costs.into_iter().map(|(method, cost)| (method.to_string(), cost)).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets keyed by client IP
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: HashMap<IpAddr, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Tokens a call to `method` takes; never more than a full bucket, so every method stays callable
+    pub fn cost(&self, method: &str) -> u32 {
+        self.config.method_costs.get(method).copied().unwrap_or(1).clamp(1, self.config.burst.max(1))
+    }
+
+    /// Take the tokens for a call to `method` from `ip`'s bucket, or return how long
+    /// until the bucket holds enough
+    pub fn check(&mut self, ip: IpAddr, method: &str, now: Instant) -> Result<(), Duration> {
+        let cost = f64::from(self.cost(method));
+        let rate = f64::from(self.config.requests_per_second.max(1));
+        let burst = f64::from(self.config.burst.max(1));
+        if self.buckets.len() >= MAX_TRACKED_CLIENTS && !self.buckets.contains_key(&ip) {
+            self.prune(now);
+        }
+
+        let bucket = self.buckets.entry(ip).or_insert(Bucket { tokens: burst, updated: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((cost - bucket.tokens) / rate))
+        }
+    }
+
+    /// Drop buckets that would be full by `now`; they hold nothing a new bucket would not
+    pub fn prune(&mut self, now: Instant) {
+        let rate = f64::from(self.config.requests_per_second.max(1));
+        let burst = f64::from(self.config.burst.max(1));
+        self.buckets.retain(|_, bucket| {
+            bucket.tokens + now.saturating_duration_since(bucket.updated).as_secs_f64() * rate < burst
+        });
+    }
+
+    /// Number of client IPs with a bucket
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+impl RPCServer {
+    /// Admit a request from `ip` calling `method` with a body of `body_len` bytes,
+    /// or reject it as too large or rate limited
+    pub async fn admit_request(&self, ip: IpAddr, method: &str, body_len: usize) -> Result<(), RPCError> {
+        let limits = &self.config.rate_limit;
+        let result = if body_len > limits.max_body_bytes {
+            Err(RPCError::RequestTooLarge(format!(
+                "{} bytes exceeds the {} byte limit",
+                body_len, limits.max_body_bytes
+            )))
+        } else if limits.enabled {
+            self.rate_limiter
+                .write()
+                .await
+                .check(ip, method, Instant::now())
+                .map_err(|retry_after| RPCError::RateLimitExceeded(retry_after.as_secs_f64().ceil().max(1.0) as u64))
+        } else {
+            Ok(())
+        };
+
+        if let Err(e) = &result {
+            debug!("Rejected {} from {}: {}", method, ip, e);
+            self.state.increment_request(false).await;
+        }
+        result
+    }
+}
+
+/// HTTP response for a request [`RPCServer::admit_request`] rejected: 429 with
+/// `Retry-After` when rate limited, 413 when too large
+pub fn rejection_response(error: &RPCError) -> String {
+    let (status, retry_after) = match error {
+        RPCError::RateLimitExceeded(secs) => ("429 Too Many Requests", Some(*secs)),
+        RPCError::RequestTooLarge(_) => ("413 Payload Too Large", None),
+        _ => ("400 Bad Request", None),
+    };
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": error.code().as_i32(), "message": error.to_string() },
+    })
+    .to_string();
+    let retry_after = retry_after.map(|secs| format!("Retry-After: {}\r\n", secs)).unwrap_or_default();
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        retry_after,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RPCServerConfig;
+
+    #[tokio::test]
+    async fn test_requests_are_rate_limited_per_ip() {
+        let config = RPCServerConfig {
+            rate_limit: RateLimitConfig {
+                requests_per_second: 2,
+                burst: 4,
+                max_body_bytes: 64,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = RPCServer::new(config).unwrap();
+        let (client, other): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+
+        for _ in 0..4 {
+            server.admit_request(client, "getNodeStatus", 10).await.unwrap();
+        }
+        let limited = server.admit_request(client, "getNodeStatus", 10).await.unwrap_err();
+        assert!(matches!(limited, RPCError::RateLimitExceeded(1)));
+        let response = rejection_response(&limited);
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\n"));
+
+        // Another client has its own bucket, and bodies are capped before any tokens are spent
+        server.admit_request(other, "getNodeStatus", 10).await.unwrap();
+        let too_large = server.admit_request(other, "getNodeStatus", 65).await.unwrap_err();
+        assert!(rejection_response(&too_large).starts_with("HTTP/1.1 413"));
+        assert_eq!(server.get_stats().await.failed_requests, 2);
+    }
+
+    #[test]
+    fn test_expensive_methods_cost_more() {
+        let mut limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_second: 10,
+            burst: 20,
+            ..Default::default()
+        });
+        let ip: IpAddr = "::1".parse().unwrap();
+        let now = Instant::now();
+        assert_eq!((limiter.cost("eth_getLogs"), limiter.cost("snapshot_export")), (10, 20));
+
+        limiter.check(ip, "eth_getLogs", now).unwrap();
+        limiter.check(ip, "eth_getLogs", now).unwrap();
+        assert_eq!(limiter.check(ip, "eth_getLogs", now), Err(Duration::from_secs(1)));
+        limiter.check(ip, "eth_getLogs", now + Duration::from_secs(1)).unwrap();
+
+        limiter.prune(now + Duration::from_secs(3));
+        assert_eq!(limiter.tracked_clients(), 0);
+    }
+}
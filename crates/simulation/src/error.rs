@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SimulationError {
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Block production failed: {0}")]
+    ProductionFailed(String),
+
+    #[error("Execution failed on node {node}: {reason}")]
+    ExecutionFailed { node: usize, reason: String },
+
+    #[error("State error: {0}")]
+    StateError(String),
+
+    #[error("Nodes diverged: {0}")]
+    Diverged(String),
+}
+
+impl From<state_db::error::StateDBError> for SimulationError {
+    fn from(err: state_db::error::StateDBError) -> Self {
+        SimulationError::StateError(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for SimulationError {
+    fn from(err: std::io::Error) -> Self {
+        SimulationError::StateError(err.to_string())
+    }
+}
@@ -0,0 +1,363 @@
+//! Deterministic simulation of a network of in-process nodes, for testing fork choice,
+//! sync and execution together.
+//!
+//! Each node runs its own consensus engine and state database. Producers build blocks of
+//! random transfers on a schedule, and the nodes exchange blocks over an in-memory network
+//! with random latency that can be partitioned and healed. Time is simulated: nothing
+//! sleeps, and a run is reproducible from its seed. `converged` then checks that every node
+//! agrees on the head and on the state root it executed to.
+
+use consensus::engine::{ChainHead, HybridEngineConfig};
+use consensus::validators::{Validator, ValidatorSet};
+use ed25519_dalek::SigningKey;
+use execution::ExecutionConfig;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use state_db::account::GenesisAccount;
+use state_db::Genesis;
+
+pub mod error;
+pub mod network;
+pub mod node;
+
+pub use error::SimulationError;
+pub use network::{Envelope, Message, NetworkStats, SimNetwork};
+pub use node::{NodeStats, SimNode};
+
+/// Simulation parameters
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub nodes: usize,
+    /// Nodes `0..producers` build blocks, each on its own schedule
+    pub producers: usize,
+    /// Seed of every random choice in a run
+    pub seed: u64,
+    /// Unix time the simulated clock starts at; block timestamps must not be ahead of the
+    /// real clock, so it lies in the past
+    pub start_time: u64,
+    /// Average seconds between blocks of one producer
+    pub block_time_secs: u64,
+    pub min_latency_ms: u64,
+    pub max_latency_ms: u64,
+    /// Funded accounts the transfers move balances between
+    pub accounts: usize,
+    /// Most transfers in one block
+    pub max_transactions: usize,
+    pub engine: HybridEngineConfig,
+    pub execution: ExecutionConfig,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            nodes: 4,
+            producers: 1,
+            seed: 0,
+            start_time: 1_600_000_000,
+            block_time_secs: 10,
+            min_latency_ms: 20,
+            max_latency_ms: 500,
+            accounts: 8,
+            max_transactions: 4,
+            // Difficulty 1 throughout, so blocks are mined with the first nonce
+            engine: HybridEngineConfig {
+                initial_difficulty: 1,
+                retarget_interval: 1_000_000,
+                ..Default::default()
+            },
+            execution: ExecutionConfig::default(),
+        }
+    }
+}
+
+/// Counts over a whole run
+#[derive(Debug, Clone, Default)]
+pub struct SimulationStats {
+    pub blocks_produced: u64,
+    pub reorgs: u64,
+    pub deepest_reorg: u64,
+    pub blocks_rejected: u64,
+    pub network: NetworkStats,
+}
+
+/// A network of simulated nodes driven by a seeded RNG and a simulated clock
+pub struct Simulation {
+    config: SimulationConfig,
+    nodes: Vec<SimNode>,
+    network: SimNetwork,
+    rng: StdRng,
+    /// Milliseconds since `start_time`
+    now_ms: u64,
+    /// When each producer builds its next block
+    next_block_ms: Vec<u64>,
+    accounts: Vec<Vec<u8>>,
+}
+
+impl Simulation {
+    /// Start `config.nodes` nodes on one genesis block and state
+    pub fn new(config: SimulationConfig) -> Result<Self, SimulationError> {
+        if config.nodes == 0 || config.producers > config.nodes {
+            return Err(SimulationError::ConfigError(
+                "Need at least one node and no more producers than nodes".to_string(),
+            ));
+        }
+        if config.accounts < 2 || config.block_time_secs == 0 || config.min_latency_ms > config.max_latency_ms {
+            return Err(SimulationError::ConfigError(
+                "Need two accounts, a positive block time and a latency range".to_string(),
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(config.seed);
+
+        let keys: Vec<SigningKey> = (0..config.nodes).map(|_| SigningKey::from_bytes(&rng.gen())).collect();
+        let mut validators = ValidatorSet::new(1);
+        for (id, key) in keys.iter().enumerate() {
+            validators
+                .insert(Validator {
+                    id: id as u64 + 1,
+                    public_key: key.verifying_key().to_bytes(),
+                    stake: 1,
+                })
+                .map_err(|e| SimulationError::ConfigError(e.to_string()))?;
+        }
+        let accounts: Vec<Vec<u8>> = (0..config.accounts)
+            .map(|account| format!("account-{}", account).into_bytes())
+            .collect();
+        let mut genesis = Genesis::default();
+        for account in &accounts {
+            genesis.alloc.insert(hex::encode(account), GenesisAccount { balance: 1_000_000_000_000 });
+        }
+
+        let mut nodes = Vec::with_capacity(config.nodes);
+        for (id, key) in keys.into_iter().enumerate() {
+            nodes.push(SimNode::new(id, key, validators.clone(), &config, genesis.clone())?);
+        }
+        let genesis_block = nodes[0].seal(0, [0u8; 32], config.start_time, Vec::new())?;
+        for node in &mut nodes {
+            node.import_genesis(&genesis_block)?;
+        }
+
+        let block_time_ms = config.block_time_secs * 1000;
+        let next_block_ms = (0..config.producers)
+            .map(|_| rng.gen_range(block_time_ms / 2..=block_time_ms * 3 / 2))
+            .collect();
+        Ok(Self {
+            network: SimNetwork::new(config.min_latency_ms, config.max_latency_ms),
+            config,
+            nodes,
+            rng,
+            now_ms: 0,
+            next_block_ms,
+            accounts,
+        })
+    }
+
+    pub fn nodes(&self) -> &[SimNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, id: usize) -> &SimNode {
+        &self.nodes[id]
+    }
+
+    /// Simulated Unix time
+    pub fn now(&self) -> u64 {
+        self.config.start_time + self.now_ms / 1000
+    }
+
+    /// Advance the clock by `secs`, producing blocks on schedule and delivering messages as
+    /// they arrive
+    pub fn run_for(&mut self, secs: u64) -> Result<(), SimulationError> {
+        let until = self.now_ms + secs * 1000;
+        loop {
+            let next_message = self.network.next_arrival().filter(|arrival| *arrival <= until);
+            let next_block = (0..self.next_block_ms.len())
+                .min_by_key(|producer| self.next_block_ms[*producer])
+                .filter(|producer| self.next_block_ms[*producer] <= until);
+            match (next_message, next_block) {
+                (Some(arrival), Some(producer)) if arrival <= self.next_block_ms[producer] => self.deliver(until)?,
+                (_, Some(producer)) => self.produce(producer)?,
+                (Some(_), None) => self.deliver(until)?,
+                (None, None) => break,
+            }
+        }
+        self.now_ms = until;
+        Ok(())
+    }
+
+    /// Deliver every message in flight without producing more blocks, advancing the clock
+    /// to the last arrival
+    pub fn settle(&mut self) -> Result<(), SimulationError> {
+        while self.network.next_arrival().is_some() {
+            self.deliver(u64::MAX)?;
+        }
+        let block_time_ms = self.config.block_time_secs * 1000;
+        for next in &mut self.next_block_ms {
+            *next = (*next).max(self.now_ms + block_time_ms / 2);
+        }
+        Ok(())
+    }
+
+    /// Cut the network into `sides`; nodes in no side are isolated
+    pub fn partition(&mut self, sides: &[&[usize]]) -> Result<(), SimulationError> {
+        if sides.iter().flat_map(|side| side.iter()).any(|node| *node >= self.nodes.len()) {
+            return Err(SimulationError::ConfigError("Partition names an unknown node".to_string()));
+        }
+        self.network.partition(self.nodes.len(), sides);
+        Ok(())
+    }
+
+    /// Reconnect every node. Each announces its head to the others, as on reconnecting, so
+    /// nodes behind a better chain fetch it.
+    pub fn heal(&mut self) {
+        self.network.heal();
+        for node in 0..self.nodes.len() {
+            let head = self.nodes[node].head();
+            let Some(proposal) = self.nodes[node].block(&head.hash) else { continue };
+            self.broadcast(node, Message::Block(proposal));
+        }
+    }
+
+    /// The head every node is on, or how they differ
+    pub fn converged(&self) -> Result<ChainHead, SimulationError> {
+        let head = self.nodes[0].head();
+        let root = self.nodes[0].state_root()?;
+        for node in &self.nodes[1..] {
+            let other = node.head();
+            if other != head {
+                return Err(SimulationError::Diverged(format!(
+                    "node {} is at {} ({}) but node 0 at {} ({})",
+                    node.id(),
+                    other.height,
+                    hex::encode(other.hash),
+                    head.height,
+                    hex::encode(head.hash)
+                )));
+            }
+            let other_root = node.state_root()?;
+            if other_root != root {
+                return Err(SimulationError::Diverged(format!(
+                    "node {} executed block {} to state root {} but node 0 to {}",
+                    node.id(),
+                    head.height,
+                    hex::encode(other_root),
+                    hex::encode(root)
+                )));
+            }
+        }
+        Ok(head)
+    }
+
+    pub fn get_stats(&self) -> SimulationStats {
+        let mut stats = SimulationStats {
+            network: self.network.get_stats(),
+            ..Default::default()
+        };
+        for node in &self.nodes {
+            let node = node.get_stats();
+            stats.blocks_produced += node.blocks_produced;
+            stats.reorgs += node.reorgs;
+            stats.deepest_reorg = stats.deepest_reorg.max(node.deepest_reorg);
+            stats.blocks_rejected += node.blocks_rejected;
+        }
+        stats
+    }
+
+    fn produce(&mut self, producer: usize) -> Result<(), SimulationError> {
+        self.now_ms = self.now_ms.max(self.next_block_ms[producer]);
+        let block_time_ms = self.config.block_time_secs * 1000;
+        self.next_block_ms[producer] = self.now_ms + self.rng.gen_range(block_time_ms / 2..=block_time_ms * 3 / 2);
+
+        let timestamp = self.now();
+        let proposal = self.nodes[producer].produce(
+            &mut self.rng,
+            timestamp,
+            &self.accounts,
+            self.config.max_transactions,
+        )?;
+        self.broadcast(producer, Message::Block(proposal));
+        Ok(())
+    }
+
+    fn deliver(&mut self, until: u64) -> Result<(), SimulationError> {
+        let Some((arrival, envelope)) = self.network.receive(until) else { return Ok(()) };
+        self.now_ms = self.now_ms.max(arrival);
+        if let Some(reply) = self.nodes[envelope.to].receive(envelope.message)? {
+            let envelope = Envelope {
+                from: envelope.to,
+                to: envelope.from,
+                message: reply,
+            };
+            self.network.send(&mut self.rng, self.now_ms, envelope);
+        }
+        Ok(())
+    }
+
+    fn broadcast(&mut self, from: usize, message: Message) {
+        for to in (0..self.nodes.len()).filter(|to| *to != from) {
+            let envelope = Envelope {
+                from,
+                to,
+                message: message.clone(),
+            };
+            self.network.send(&mut self.rng, self.now_ms, envelope);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_producers_converge_and_runs_repeat_from_their_seed() {
+        let config = SimulationConfig {
+            producers: 2,
+            seed: 7,
+            ..Default::default()
+        };
+        let run = || {
+            let mut simulation = Simulation::new(config.clone()).unwrap();
+            simulation.run_for(300).unwrap();
+            simulation.settle().unwrap();
+            let head = simulation.converged().unwrap();
+            (head, simulation.node(0).state_root().unwrap(), simulation.get_stats())
+        };
+
+        let (head, root, stats) = run();
+        assert!(head.height >= 20);
+        assert_eq!(stats.blocks_rejected, 0);
+        let (again, again_root, _) = run();
+        assert_eq!((again, again_root), (head, root));
+    }
+
+    #[test]
+    fn test_partitioned_minority_reorgs_onto_the_longer_chain_when_healed() {
+        let mut simulation = Simulation::new(SimulationConfig {
+            nodes: 5,
+            producers: 3,
+            seed: 3,
+            ..Default::default()
+        })
+        .unwrap();
+        simulation.run_for(60).unwrap();
+        simulation.settle().unwrap();
+        let before = simulation.converged().unwrap();
+
+        // Producers 0 and 1 outpace producer 2 on the other side
+        simulation.partition(&[&[0, 1, 3], &[2, 4]]).unwrap();
+        simulation.run_for(300).unwrap();
+        simulation.settle().unwrap();
+        let majority = simulation.node(0).head();
+        let minority = simulation.node(4).head();
+        assert!(majority.height > minority.height && minority.height > before.height);
+        assert!(simulation.converged().is_err());
+
+        simulation.heal();
+        simulation.settle().unwrap();
+        assert_eq!(simulation.converged().unwrap(), majority);
+        let stats = simulation.get_stats();
+        assert!(stats.deepest_reorg >= minority.height - before.height);
+        assert!(simulation.node(2).get_stats().state_rebuilds > 0);
+        assert!(stats.network.messages_dropped > 0);
+    }
+}
@@ -0,0 +1,115 @@
+//! In-memory transport between simulated nodes. Every node is linked to every other, and
+//! each message arrives after a latency drawn from the simulation's seeded RNG. Messages
+//! are delivered in order of arrival time, then of sending, so a run is reproducible from
+//! its seed. While the network is partitioned, messages crossing the cut are dropped.
+
+use consensus::BlockProposal;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::BTreeMap;
+
+/// What simulated nodes send each other
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// A block announced by its producer, or by a peer catching up after a partition
+    Block(BlockProposal),
+    /// Ask for the peer's chain above the highest of `have` it shares; `have` holds the
+    /// sender's canonical block ids by height
+    GetBlocks { have: Vec<[u8; 32]> },
+    /// Canonical blocks answering `GetBlocks`, lowest first
+    Blocks(Vec<BlockProposal>),
+}
+
+/// A message between two nodes
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub from: usize,
+    pub to: usize,
+    pub message: Message,
+}
+
+/// Message counts of a run
+#[derive(Debug, Clone, Default)]
+pub struct NetworkStats {
+    pub messages_sent: u64,
+    pub messages_delivered: u64,
+    /// Messages lost to a partition
+    pub messages_dropped: u64,
+}
+
+/// Messages in flight between simulated nodes
+pub struct SimNetwork {
+    /// Keyed by arrival time in milliseconds and send order
+    in_flight: BTreeMap<(u64, u64), Envelope>,
+    sent: u64,
+    min_latency_ms: u64,
+    max_latency_ms: u64,
+    /// Side of the partition each node is on, `None` while fully connected
+    sides: Option<Vec<usize>>,
+    stats: NetworkStats,
+}
+
+impl SimNetwork {
+    pub fn new(min_latency_ms: u64, max_latency_ms: u64) -> Self {
+        Self {
+            in_flight: BTreeMap::new(),
+            sent: 0,
+            min_latency_ms,
+            max_latency_ms,
+            sides: None,
+            stats: NetworkStats::default(),
+        }
+    }
+
+    /// Send `envelope` at `now_ms`, arriving after a random latency
+    pub fn send(&mut self, rng: &mut StdRng, now_ms: u64, envelope: Envelope) {
+        let latency = rng.gen_range(self.min_latency_ms..=self.max_latency_ms);
+        self.in_flight.insert((now_ms + latency, self.sent), envelope);
+        self.sent += 1;
+        self.stats.messages_sent += 1;
+    }
+
+    /// Arrival time of the next message, if any is in flight
+    pub fn next_arrival(&self) -> Option<u64> {
+        self.in_flight.keys().next().map(|(arrival, _)| *arrival)
+    }
+
+    /// Take the next message arriving by `until_ms` with its arrival time, dropping those
+    /// that cross a partition on the way
+    pub fn receive(&mut self, until_ms: u64) -> Option<(u64, Envelope)> {
+        while self.next_arrival().is_some_and(|arrival| arrival <= until_ms) {
+            let ((arrival, _), envelope) = self.in_flight.pop_first()?;
+            if self.connected(envelope.from, envelope.to) {
+                self.stats.messages_delivered += 1;
+                return Some((arrival, envelope));
+            }
+            self.stats.messages_dropped += 1;
+        }
+        None
+    }
+
+    /// Split the nodes into `sides`, which can no longer reach each other. Nodes left out
+    /// of every side are cut off from all others.
+    pub fn partition(&mut self, nodes: usize, sides: &[&[usize]]) {
+        let mut assignment: Vec<usize> = (0..nodes).map(|node| sides.len() + node).collect();
+        for (side, members) in sides.iter().enumerate() {
+            for &node in *members {
+                assignment[node] = side;
+            }
+        }
+        self.sides = Some(assignment);
+    }
+
+    /// Reconnect every node
+    pub fn heal(&mut self) {
+        self.sides = None;
+    }
+
+    pub fn connected(&self, a: usize, b: usize) -> bool {
+        self.sides.as_ref().is_none_or(|sides| sides[a] == sides[b])
+    }
+
+    pub fn get_stats(&self) -> NetworkStats {
+        self.stats.clone()
+    }
+}
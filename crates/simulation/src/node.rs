@@ -0,0 +1,344 @@
+//! One simulated node: a hybrid consensus engine deciding the chain, and a state database
+//! executing it. Every block a node hears of goes through the engine's import, whose fork
+//! choice decides the chain as it does on a real node, and a node fetches a peer's chain
+//! when a block builds on blocks it lacks.
+
+use block_sync::{Block, BlockHeader, BlockProof, ProofType, Transaction, TxOutput};
+use consensus::engine::{header_id, header_signing_bytes, ChainHead, ConsensusEngine, HybridEngine};
+use consensus::error::ConsensusError;
+use consensus::validators::ValidatorSet;
+use consensus::{merkle_root, BlockProposal, BlockRejection};
+use ed25519_dalek::{Signer, SigningKey};
+use execution::{BlockExecutor, ExecutionConfig};
+use pow::auxpow::bare_coinbase;
+use pow::{AuxPow, MergeMinedProof, ParentBlockHeader};
+use rand::rngs::StdRng;
+use rand::Rng;
+use state_db::{Genesis, RocksStateDB};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+use crate::network::Message;
+use crate::{SimulationConfig, SimulationError};
+
+/// Gas limit and fee of the transfers producers make up
+const TRANSFER_GAS: u64 = 100_000;
+
+/// Address the fees of blocks proposed by `validator_id` are paid to
+pub fn validator_address(validator_id: u64) -> Vec<u8> {
+    format!("validator-{}", validator_id).into_bytes()
+}
+
+/// Counts of what happened to a node's chain
+#[derive(Debug, Clone, Default)]
+pub struct NodeStats {
+    pub blocks_produced: u64,
+    pub reorgs: u64,
+    pub deepest_reorg: u64,
+    /// Blocks the engine refused, e.g. for conflicting with a checkpoint
+    pub blocks_rejected: u64,
+    /// Times the state was replayed from genesis after a reorg
+    pub state_rebuilds: u64,
+}
+
+/// A simulated node
+pub struct SimNode {
+    id: usize,
+    validator_id: u64,
+    key: SigningKey,
+    engine: HybridEngine,
+    /// Every block seen, by id
+    blocks: HashMap<[u8; 32], BlockProposal>,
+    genesis: Genesis,
+    execution: ExecutionConfig,
+    executor: BlockExecutor,
+    state: RocksStateDB,
+    /// Ids of the blocks executed into `state`, from height 1
+    executed: Vec<[u8; 32]>,
+    /// Holds `state`, so it is declared after it and dropped last
+    dir: TempDir,
+    stats: NodeStats,
+}
+
+impl SimNode {
+    pub(crate) fn new(
+        id: usize,
+        key: SigningKey,
+        validators: ValidatorSet,
+        config: &SimulationConfig,
+        genesis: Genesis,
+    ) -> Result<Self, SimulationError> {
+        let engine = HybridEngine::new(config.engine.clone(), validators)
+            .map_err(|e| SimulationError::ConfigError(e.to_string()))?;
+        let executor = BlockExecutor::new(config.execution.clone())
+            .map_err(|e| SimulationError::ConfigError(e.to_string()))?;
+        let (dir, state) = open_state(&genesis)?;
+        Ok(Self {
+            id,
+            validator_id: id as u64 + 1,
+            key,
+            engine,
+            blocks: HashMap::new(),
+            genesis,
+            execution: config.execution.clone(),
+            executor,
+            state,
+            executed: Vec::new(),
+            dir,
+            stats: NodeStats::default(),
+        })
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Best block; every node starts from the shared genesis block
+    pub fn head(&self) -> ChainHead {
+        self.engine.head().expect("simulated nodes start with the genesis block")
+    }
+
+    /// Root of the state after executing the canonical chain
+    pub fn state_root(&self) -> Result<[u8; 32], SimulationError> {
+        let version = self.state.latest_version().unwrap_or_default();
+        self.state
+            .root_at(version)?
+            .ok_or_else(|| SimulationError::StateError(format!("No root committed at version {}", version)))
+    }
+
+    /// A block this node has seen
+    pub fn block(&self, hash: &[u8; 32]) -> Option<BlockProposal> {
+        self.blocks.get(hash).cloned()
+    }
+
+    pub fn state(&self) -> &RocksStateDB {
+        &self.state
+    }
+
+    pub fn get_stats(&self) -> NodeStats {
+        self.stats.clone()
+    }
+
+    /// Canonical block ids by height
+    fn canonical(&self) -> Vec<[u8; 32]> {
+        let head = self.head();
+        (0..=head.height)
+            .filter_map(|height| self.engine.canonical_checkpoint(height))
+            .map(|block| block.hash)
+            .collect()
+    }
+
+    /// Sign and mine a block on `parent` at `timestamp`
+    pub(crate) fn seal(
+        &self,
+        height: u64,
+        prev_hash: [u8; 32],
+        timestamp: u64,
+        transactions: Vec<Transaction>,
+    ) -> Result<BlockProposal, SimulationError> {
+        let failed = |e: String| SimulationError::ProductionFailed(format!("node {}: {}", self.id, e));
+        let header = BlockHeader {
+            height,
+            prev_hash,
+            merkle_root: merkle_root(&transactions),
+            timestamp,
+            nonce: 0,
+            difficulty: self.engine.expected_difficulty(height).map_err(|e| failed(e.to_string()))?,
+            nullifier_root: self.state.nullifier_root()?,
+        };
+        let mut parent_header = ParentBlockHeader {
+            major_version: 1,
+            timestamp,
+            ..Default::default()
+        };
+        let aux_hash = header.hash().map_err(|e| failed(e.to_string()))?;
+        let aux_pow = AuxPow::create(aux_hash, bare_coinbase(height), &[], &mut parent_header)
+            .map_err(|e| failed(e.to_string()))?;
+        let proof = MergeMinedProof { parent_header, aux_pow };
+        let signature = self.key.sign(&header_signing_bytes(&header)).to_bytes().to_vec();
+        Ok(BlockProposal {
+            block: Block {
+                header,
+                transactions,
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: serde_json::to_vec(&proof).map_err(|e| failed(e.to_string()))?,
+                },
+            },
+            proposer: self.validator_id,
+            timestamp,
+            signature,
+        })
+    }
+
+    /// Build, import and return a block on the head with up to `max_transactions` random
+    /// transfers between `accounts`
+    pub(crate) fn produce(
+        &mut self,
+        rng: &mut StdRng,
+        timestamp: u64,
+        accounts: &[Vec<u8>],
+        max_transactions: usize,
+    ) -> Result<BlockProposal, SimulationError> {
+        let head = self.head();
+        let height = head.height + 1;
+        // Blocks must be later than the median of the ones below
+        let timestamp = match self.engine.median_time_past(height) {
+            Some(median) => timestamp.max(median + 1),
+            None => timestamp,
+        };
+
+        let mut nonces: HashMap<usize, u64> = HashMap::new();
+        let mut transactions = Vec::new();
+        for _ in 0..rng.gen_range(0..=max_transactions) {
+            let sender = rng.gen_range(0..accounts.len());
+            let recipient = (sender + rng.gen_range(1..accounts.len())) % accounts.len();
+            let amount = rng.gen_range(1..=1_000u64);
+            let nonce = match nonces.get(&sender) {
+                Some(nonce) => *nonce,
+                None => self.state.get_account(&accounts[sender])?.nonce,
+            };
+            nonces.insert(sender, nonce + 1);
+            let transfer = Transaction {
+                hash: [0u8; 32],
+                sender: accounts[sender].clone(),
+                nonce,
+                gas_limit: TRANSFER_GAS,
+                data: Vec::new(),
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                inputs: Vec::new(),
+                outputs: vec![TxOutput {
+                    amount,
+                    address: accounts[recipient].clone(),
+                    commitment: [0u8; 32],
+                    ephemeral_key: None,
+                }],
+                fee: TRANSFER_GAS,
+                timestamp,
+                chain_id: self.execution.chain_id,
+                fee_payer: None,
+                asset: None,
+            };
+            transactions.push(transfer.with_id());
+        }
+
+        let proposal = self.seal(height, head.hash, timestamp, transactions)?;
+        self.blocks.insert(header_id(&proposal.block.header), proposal.clone());
+        self.engine
+            .import_proposal(&proposal)
+            .map_err(|e| SimulationError::ProductionFailed(format!("node {}: {}", self.id, e)))?;
+        self.execute_canonical()?;
+        self.stats.blocks_produced += 1;
+        Ok(proposal)
+    }
+
+    /// Import the shared genesis block
+    pub(crate) fn import_genesis(&mut self, genesis: &BlockProposal) -> Result<(), SimulationError> {
+        self.blocks.insert(header_id(&genesis.block.header), genesis.clone());
+        self.engine
+            .import_proposal(genesis)
+            .map_err(|e| SimulationError::ConfigError(format!("Genesis block rejected: {}", e)))?;
+        Ok(())
+    }
+
+    /// Handle a message from a peer, returning the reply to send back
+    pub(crate) fn receive(&mut self, message: Message) -> Result<Option<Message>, SimulationError> {
+        match message {
+            Message::Block(proposal) => self.on_block(proposal),
+            Message::GetBlocks { have } => Ok(Some(Message::Blocks(self.blocks_after(&have)))),
+            Message::Blocks(proposals) => {
+                self.on_blocks(proposals)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Import a peer's block; ask the sender for its chain if the block builds on blocks we lack
+    fn on_block(&mut self, proposal: BlockProposal) -> Result<Option<Message>, SimulationError> {
+        if self.blocks.insert(header_id(&proposal.block.header), proposal.clone()).is_some() {
+            return Ok(None);
+        }
+        if self.import(&[proposal])? {
+            return Ok(Some(Message::GetBlocks { have: self.canonical() }));
+        }
+        Ok(None)
+    }
+
+    /// Import a peer's chain; the engine switches to it only if it has more work
+    fn on_blocks(&mut self, proposals: Vec<BlockProposal>) -> Result<(), SimulationError> {
+        for proposal in &proposals {
+            self.blocks.insert(header_id(&proposal.block.header), proposal.clone());
+        }
+        self.import(&proposals)?;
+        Ok(())
+    }
+
+    /// Import `proposals` in order through the engine, stopping at the first it refuses, and
+    /// execute the resulting chain. Returns whether a block's parent was unknown.
+    fn import(&mut self, proposals: &[BlockProposal]) -> Result<bool, SimulationError> {
+        let mut missing_parent = false;
+        for proposal in proposals {
+            match self.engine.import_proposal(proposal) {
+                Ok(outcome) if outcome.reorg_depth > 0 => {
+                    self.stats.reorgs += 1;
+                    self.stats.deepest_reorg = self.stats.deepest_reorg.max(outcome.reorg_depth);
+                }
+                Ok(_) | Err(ConsensusError::BlockRejected(BlockRejection::AlreadyKnown(_))) => {}
+                Err(ConsensusError::BlockRejected(BlockRejection::UnknownParent(_))) => {
+                    missing_parent = true;
+                    break;
+                }
+                Err(e) => {
+                    println!("Node {} rejected block {}: {}", self.id, proposal.block.header.height, e);
+                    self.stats.blocks_rejected += 1;
+                    break;
+                }
+            }
+        }
+        self.execute_canonical()?;
+        Ok(missing_parent)
+    }
+
+    /// Our canonical blocks above the highest block of `have` we share
+    fn blocks_after(&self, have: &[[u8; 32]]) -> Vec<BlockProposal> {
+        let canonical = self.canonical();
+        let shared = canonical.iter().zip(have).take_while(|(ours, theirs)| ours == theirs).count();
+        canonical[shared.max(1)..]
+            .iter()
+            .filter_map(|hash| self.blocks.get(hash).cloned())
+            .collect()
+    }
+
+    /// Bring the state up to the canonical head. State versions only move forward, so after
+    /// a reorg the chain is replayed from genesis into a fresh store.
+    fn execute_canonical(&mut self) -> Result<(), SimulationError> {
+        let canonical = self.canonical().split_off(1);
+        let kept = self.executed.iter().zip(&canonical).take_while(|(a, b)| a == b).count();
+        if kept < self.executed.len() {
+            let (dir, state) = open_state(&self.genesis)?;
+            self.state = state;
+            self.dir = dir;
+            self.executed.clear();
+            self.stats.state_rebuilds += 1;
+        }
+        for hash in &canonical[self.executed.len()..] {
+            let proposal = &self.blocks[hash];
+            self.executor
+                .process_block(&mut self.state, &proposal.block, &validator_address(proposal.proposer))
+                .map_err(|e| SimulationError::ExecutionFailed {
+                    node: self.id,
+                    reason: e.to_string(),
+                })?;
+            self.executed.push(*hash);
+        }
+        Ok(())
+    }
+}
+
+fn open_state(genesis: &Genesis) -> Result<(TempDir, RocksStateDB), SimulationError> {
+    let dir = TempDir::new()?;
+    let mut state = RocksStateDB::new(dir.path())?;
+    state.apply_genesis(genesis)?;
+    Ok((dir, state))
+}
@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum StakingError {
+    #[error("Validator not found: {0}")]
+    ValidatorNotFound(u64),
+
+    #[error("Insufficient stake: {0}")]
+    InsufficientStake(String),
+
+    #[error("Invalid evidence: {0}")]
+    InvalidEvidence(String),
+
+    #[error("Storage error: {0}")]
+    StorageError(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+}
+
+impl From<state_db::error::StateDBError> for StakingError {
+    fn from(err: state_db::error::StateDBError) -> Self {
+        StakingError::StorageError(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StakingError {
+    fn from(err: serde_json::Error) -> Self {
+        StakingError::StorageError(err.to_string())
+    }
+}
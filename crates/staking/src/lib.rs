@@ -0,0 +1,10 @@
+//! Validator staking: bonded stake, unbonding, reward distribution and slashing,
+//! persisted in the StateDB and wired into consensus through double-sign evidence.
+
+pub mod error;
+pub mod slashing;
+pub mod staking;
+
+pub use error::StakingError;
+pub use slashing::{run_slashing, sync_validators};
+pub use staking::{StakedValidator, StakingConfig, StakingStats, Unbonding, ValidatorStaking};
@@ -0,0 +1,139 @@
+use crate::staking::ValidatorStaking;
+use consensus::Consensus;
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tokio_util::sync::CancellationToken;
+
+/// Push the staking module's active validators into consensus
+pub async fn sync_validators(staking: &Arc<RwLock<ValidatorStaking>>, consensus: &Arc<RwLock<Consensus>>) {
+    let validators = staking.read().await.validator_set();
+    consensus.read().await.update_validators(validators).await;
+}
+
+/// Slash validators reported by consensus for double signing, then remove them from
+/// the consensus validator set. Runs until consensus is dropped or `shutdown` is cancelled.
+pub async fn run_slashing(
+    staking: Arc<RwLock<ValidatorStaking>>,
+    consensus: Arc<RwLock<Consensus>>,
+    shutdown: CancellationToken,
+) {
+    let mut evidence_rx = consensus.read().await.subscribe_evidence();
+    loop {
+        // Evidence already being applied is finished before shutting down
+        let received = tokio::select! {
+            received = evidence_rx.recv() => received,
+            _ = shutdown.cancelled() => break,
+        };
+        let evidence = match received {
+            Ok(evidence) => evidence,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                println!("Slashing hook missed {} evidence reports", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        let result = staking.write().await.apply_evidence(&evidence).await;
+        match result {
+            Ok(0) => {}
+            Ok(_) => sync_validators(&staking, &consensus).await,
+            Err(e) => println!("Ignoring double-sign evidence against {}: {}", evidence.validator_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::staking::StakingConfig;
+    use block_sync::{Block, BlockHeader, BlockProof, ProofType};
+    use consensus::engine::{header_id, header_signing_bytes, HybridEngine, HybridEngineConfig};
+    use consensus::validators::ValidatorSet;
+    use consensus::{BlockProposal, ConsensusConfig};
+    use ed25519_dalek::{Signer, SigningKey};
+    use pow::auxpow::bare_coinbase;
+    use pow::{AuxPow, MergeMinedProof, ParentBlockHeader};
+    use std::time::Duration;
+
+    fn proposal(key: &SigningKey, height: u64, prev_hash: [u8; 32], timestamp: u64) -> BlockProposal {
+        let header = BlockHeader {
+            height,
+            prev_hash,
+            merkle_root: [0u8; 32],
+            timestamp,
+            nonce: 0,
+            difficulty: 1,
+            nullifier_root: [0u8; 32],
+        };
+        let mut parent_header = ParentBlockHeader {
+            major_version: 1,
+            timestamp,
+            ..Default::default()
+        };
+        let aux_pow = AuxPow::create(header.hash().unwrap(), bare_coinbase(1), &[], &mut parent_header).unwrap();
+        let proof = MergeMinedProof { parent_header, aux_pow };
+        let signature = key.sign(&header_signing_bytes(&header)).to_bytes().to_vec();
+        BlockProposal {
+            block: Block {
+                header,
+                transactions: vec![],
+                proof: BlockProof {
+                    proof_type: ProofType::PoW,
+                    proof_data: serde_json::to_vec(&proof).unwrap(),
+                },
+            },
+            proposer: 1,
+            timestamp,
+            signature,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_double_sign_is_slashed_and_removed() {
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let mut staking = ValidatorStaking::new(StakingConfig::default()).unwrap();
+        staking.stake(1, key.verifying_key().to_bytes(), 10_000).await.unwrap();
+        let staking = Arc::new(RwLock::new(staking));
+
+        let engine_config = HybridEngineConfig {
+            initial_difficulty: 1,
+            ..Default::default()
+        };
+        let engine = HybridEngine::new(engine_config, ValidatorSet::default()).unwrap();
+        let consensus = Consensus::with_engine(ConsensusConfig::default(), Box::new(engine)).unwrap();
+        let consensus = Arc::new(RwLock::new(consensus));
+        sync_validators(&staking, &consensus).await;
+        let shutdown = CancellationToken::new();
+        let hook = tokio::spawn(run_slashing(staking.clone(), consensus.clone(), shutdown.clone()));
+        tokio::task::yield_now().await;
+
+        let genesis = proposal(&key, 0, [0u8; 32], 1_000);
+        let genesis_id = header_id(&genesis.block.header);
+        consensus.read().await.import_proposal(&genesis).await.unwrap();
+        consensus.read().await.import_proposal(&proposal(&key, 1, genesis_id, 1_001)).await.unwrap();
+        // Conflicting block at height 1 from the same validator
+        let conflicting = proposal(&key, 1, genesis_id, 1_002);
+        let conflicting_id = header_id(&conflicting.block.header);
+        consensus.read().await.import_proposal(&conflicting).await.unwrap();
+
+        for _ in 0..100 {
+            if staking.read().await.get_stats().total_slashed > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(staking.read().await.get_stats().total_slashed, 500);
+
+        // The jailed validator can no longer sign blocks
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let err = consensus
+            .read()
+            .await
+            .import_proposal(&proposal(&key, 2, conflicting_id, 1_003))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not staked"));
+        shutdown.cancel();
+        hook.await.unwrap();
+    }
+}
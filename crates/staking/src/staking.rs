@@ -0,0 +1,503 @@
+use crate::error::StakingError;
+use consensus::validators::{DoubleSignEvidence, Validator, ValidatorSet};
+use serde::{Deserialize, Serialize};
+use state_db::RocksStateDB;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const VALIDATOR_PREFIX: &str = "staking/validator/";
+const INDEX_KEY: &[u8] = b"staking/index";
+const STATS_KEY: &[u8] = b"staking/stats";
+
+/// Basis points in one whole
+const BPS: u128 = 10_000;
+
+/// Staking configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StakingConfig {
+    /// Minimum bonded stake to sign blocks
+    pub min_stake: u64,
+    /// Blocks unstaked funds stay slashable before they can be withdrawn
+    pub unbonding_period: u64,
+    /// Share of bonded and unbonding stake taken for signing two blocks at one height, in basis points
+    pub double_sign_slash_bps: u64,
+}
+
+impl Default for StakingConfig {
+    fn default() -> Self {
+        Self {
+            min_stake: 1000,
+            unbonding_period: 1000,
+            double_sign_slash_bps: 500,
+        }
+    }
+}
+
+/// Stake on its way out, still slashable until `release_height`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unbonding {
+    pub amount: u64,
+    pub release_height: u64,
+}
+
+/// A validator's staking state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StakedValidator {
+    pub id: u64,
+    pub public_key: [u8; 32],
+    /// Bonded stake
+    pub stake: u64,
+    pub unbonding: Vec<Unbonding>,
+    /// Unclaimed rewards
+    pub rewards: u64,
+    /// Jailed validators cannot sign blocks
+    pub jailed: bool,
+    pub total_slashed: u64,
+    /// Heights this validator has already been slashed for
+    pub slashed_heights: Vec<u64>,
+}
+
+impl StakedValidator {
+    /// Stake still at risk of slashing
+    pub fn slashable_stake(&self) -> u64 {
+        self.stake + self.unbonding.iter().map(|entry| entry.amount).sum::<u64>()
+    }
+}
+
+/// Staking statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StakingStats {
+    pub total_staked: u64,
+    pub total_unbonding: u64,
+    pub total_slashed: u64,
+    pub total_distributed: u64,
+    pub active_validators: usize,
+}
+
+/// Validator stake accounting: stake, unstake, slash and reward distribution,
+/// optionally persisted in a StateDB
+pub struct ValidatorStaking {
+    config: StakingConfig,
+    validators: BTreeMap<u64, StakedValidator>,
+    total_slashed: u64,
+    total_distributed: u64,
+    db: Option<Arc<RwLock<RocksStateDB>>>,
+}
+
+impl ValidatorStaking {
+    /// Create in-memory staking state
+    pub fn new(config: StakingConfig) -> Result<Self, StakingError> {
+        if config.double_sign_slash_bps > BPS as u64 {
+            return Err(StakingError::ConfigError("Slash fraction cannot exceed 10000 bps".to_string()));
+        }
+        Ok(Self {
+            config,
+            validators: BTreeMap::new(),
+            total_slashed: 0,
+            total_distributed: 0,
+            db: None,
+        })
+    }
+
+    /// Load staking state from `db` and persist every change back to it
+    pub async fn with_state_db(config: StakingConfig, db: Arc<RwLock<RocksStateDB>>) -> Result<Self, StakingError> {
+        let mut staking = Self::new(config)?;
+        {
+            let store = db.read().await;
+            if let Some(index) = store.get_sync(INDEX_KEY)? {
+                let ids: Vec<u64> = serde_json::from_slice(&index)?;
+                for id in ids {
+                    let key = Self::validator_key(id);
+                    let data = store
+                        .get_sync(&key)?
+                        .ok_or_else(|| StakingError::StorageError(format!("Missing record for validator {}", id)))?;
+                    staking.validators.insert(id, serde_json::from_slice(&data)?);
+                }
+            }
+            if let Some(stats) = store.get_sync(STATS_KEY)? {
+                let stats: StakingStats = serde_json::from_slice(&stats)?;
+                staking.total_slashed = stats.total_slashed;
+                staking.total_distributed = stats.total_distributed;
+            }
+        }
+        staking.db = Some(db);
+        Ok(staking)
+    }
+
+    /// Bond `amount` for validator `id`, registering it on first stake
+    pub async fn stake(&mut self, id: u64, public_key: [u8; 32], amount: u64) -> Result<u64, StakingError> {
+        if amount == 0 {
+            return Err(StakingError::InsufficientStake("Stake amount must be positive".to_string()));
+        }
+        let current = match self.validators.get(&id) {
+            Some(validator) if validator.public_key != public_key => {
+                return Err(StakingError::ConfigError(format!("Validator {} is registered with another key", id)));
+            }
+            Some(validator) => validator.stake,
+            None => 0,
+        };
+        if current + amount < self.config.min_stake {
+            return Err(StakingError::InsufficientStake(format!(
+                "Stake {} below minimum {}",
+                current + amount,
+                self.config.min_stake
+            )));
+        }
+
+        let validator = self.validators.entry(id).or_insert_with(|| StakedValidator {
+            id,
+            public_key,
+            stake: 0,
+            unbonding: Vec::new(),
+            rewards: 0,
+            jailed: false,
+            total_slashed: 0,
+            slashed_heights: Vec::new(),
+        });
+        validator.stake += amount;
+        let stake = validator.stake;
+        self.persist(&[id]).await?;
+        Ok(stake)
+    }
+
+    /// Start unbonding `amount`; it can be withdrawn after the unbonding period
+    pub async fn unstake(&mut self, id: u64, amount: u64, current_height: u64) -> Result<u64, StakingError> {
+        let min_stake = self.config.min_stake;
+        let validator = self.validators.get_mut(&id).ok_or(StakingError::ValidatorNotFound(id))?;
+        if amount == 0 || amount > validator.stake {
+            return Err(StakingError::InsufficientStake(format!(
+                "Cannot unstake {} of {}",
+                amount, validator.stake
+            )));
+        }
+        // Partial unstakes must leave at least the minimum bonded
+        let remaining = validator.stake - amount;
+        if remaining > 0 && remaining < min_stake {
+            return Err(StakingError::InsufficientStake(format!(
+                "Remaining stake {} below minimum {}",
+                remaining, min_stake
+            )));
+        }
+
+        let release_height = current_height + self.config.unbonding_period;
+        validator.stake = remaining;
+        validator.unbonding.push(Unbonding { amount, release_height });
+        self.persist(&[id]).await?;
+        Ok(release_height)
+    }
+
+    /// Withdraw unbonded stake whose release height has passed
+    pub async fn withdraw(&mut self, id: u64, current_height: u64) -> Result<u64, StakingError> {
+        let validator = self.validators.get_mut(&id).ok_or(StakingError::ValidatorNotFound(id))?;
+        let mut released = 0;
+        validator.unbonding.retain(|entry| {
+            if entry.release_height <= current_height {
+                released += entry.amount;
+                false
+            } else {
+                true
+            }
+        });
+        self.persist(&[id]).await?;
+        Ok(released)
+    }
+
+    /// Slash `bps` of a validator's bonded and unbonding stake and jail it. Returns the amount slashed.
+    pub async fn slash(&mut self, id: u64, bps: u64, reason: &str) -> Result<u64, StakingError> {
+        let validator = self.validators.get_mut(&id).ok_or(StakingError::ValidatorNotFound(id))?;
+        let penalty = (validator.slashable_stake() as u128 * bps.min(BPS as u64) as u128 / BPS) as u64;
+
+        // Take from bonded stake first, then from the newest unbonding entries
+        let mut remaining = penalty;
+        let from_stake = remaining.min(validator.stake);
+        validator.stake -= from_stake;
+        remaining -= from_stake;
+        for entry in validator.unbonding.iter_mut().rev() {
+            let taken = remaining.min(entry.amount);
+            entry.amount -= taken;
+            remaining -= taken;
+        }
+        validator.unbonding.retain(|entry| entry.amount > 0);
+
+        validator.jailed = true;
+        validator.total_slashed += penalty;
+        self.total_slashed += penalty;
+        println!("Slashed validator {} by {} for {}", id, penalty, reason);
+        self.persist(&[id]).await?;
+        Ok(penalty)
+    }
+
+    /// Verify double-sign evidence and slash the offender once per height
+    pub async fn apply_evidence(&mut self, evidence: &DoubleSignEvidence) -> Result<u64, StakingError> {
+        let validator = self
+            .validators
+            .get(&evidence.validator_id)
+            .ok_or(StakingError::ValidatorNotFound(evidence.validator_id))?;
+        evidence
+            .verify(&validator.public_key)
+            .map_err(|e| StakingError::InvalidEvidence(e.to_string()))?;
+        if validator.slashed_heights.contains(&evidence.height) {
+            return Ok(0);
+        }
+
+        let id = evidence.validator_id;
+        if let Some(validator) = self.validators.get_mut(&id) {
+            validator.slashed_heights.push(evidence.height);
+        }
+        let reason = format!("double signing at height {}", evidence.height);
+        self.slash(id, self.config.double_sign_slash_bps, &reason).await
+    }
+
+    /// Let a jailed validator sign again, if it still meets the minimum stake
+    pub async fn unjail(&mut self, id: u64) -> Result<(), StakingError> {
+        let min_stake = self.config.min_stake;
+        let validator = self.validators.get_mut(&id).ok_or(StakingError::ValidatorNotFound(id))?;
+        if validator.stake < min_stake {
+            return Err(StakingError::InsufficientStake(format!(
+                "Stake {} below minimum {}",
+                validator.stake, min_stake
+            )));
+        }
+        validator.jailed = false;
+        self.persist(&[id]).await
+    }
+
+    /// Split `reward` among active validators pro rata to stake. The rounding
+    /// remainder goes to the largest staker. Returns each validator's share.
+    pub async fn distribute(&mut self, reward: u64) -> Result<Vec<(u64, u64)>, StakingError> {
+        let active: Vec<u64> = self.active_ids();
+        let total: u128 = active.iter().map(|id| self.validators[id].stake as u128).sum();
+        if total == 0 || reward == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut shares: Vec<(u64, u64)> = active
+            .iter()
+            .map(|id| (*id, (reward as u128 * self.validators[id].stake as u128 / total) as u64))
+            .collect();
+        let remainder = reward - shares.iter().map(|(_, share)| share).sum::<u64>();
+        if let Some(largest) = shares
+            .iter_mut()
+            .max_by_key(|(id, _)| (self.validators[id].stake, std::cmp::Reverse(*id)))
+        {
+            largest.1 += remainder;
+        }
+
+        for (id, share) in &shares {
+            if let Some(validator) = self.validators.get_mut(id) {
+                validator.rewards += share;
+            }
+        }
+        self.total_distributed += reward;
+        self.persist(&active).await?;
+        Ok(shares)
+    }
+
+    /// Claim a validator's accumulated rewards
+    pub async fn claim_rewards(&mut self, id: u64) -> Result<u64, StakingError> {
+        let validator = self.validators.get_mut(&id).ok_or(StakingError::ValidatorNotFound(id))?;
+        let rewards = std::mem::take(&mut validator.rewards);
+        self.persist(&[id]).await?;
+        Ok(rewards)
+    }
+
+    /// Get a validator's staking state
+    pub fn get_validator(&self, id: u64) -> Option<&StakedValidator> {
+        self.validators.get(&id)
+    }
+
+    /// Validator set for consensus: unjailed validators with at least the minimum stake
+    pub fn validator_set(&self) -> ValidatorSet {
+        let mut set = ValidatorSet::new(self.config.min_stake);
+        for id in self.active_ids() {
+            let validator = &self.validators[&id];
+            let _ = set.insert(Validator {
+                id,
+                public_key: validator.public_key,
+                stake: validator.stake,
+            });
+        }
+        set
+    }
+
+    /// Get staking statistics
+    pub fn get_stats(&self) -> StakingStats {
+        StakingStats {
+            total_staked: self.validators.values().map(|validator| validator.stake).sum(),
+            total_unbonding: self
+                .validators
+                .values()
+                .flat_map(|validator| validator.unbonding.iter())
+                .map(|entry| entry.amount)
+                .sum(),
+            total_slashed: self.total_slashed,
+            total_distributed: self.total_distributed,
+            active_validators: self.active_ids().len(),
+        }
+    }
+
+    fn active_ids(&self) -> Vec<u64> {
+        self.validators
+            .values()
+            .filter(|validator| !validator.jailed && validator.stake >= self.config.min_stake)
+            .map(|validator| validator.id)
+            .collect()
+    }
+
+    fn validator_key(id: u64) -> Vec<u8> {
+        format!("{}{}", VALIDATOR_PREFIX, id).into_bytes()
+    }
+
+    /// Atomically write the given validators, the index and the stats to the StateDB
+    async fn persist(&self, ids: &[u64]) -> Result<(), StakingError> {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let mut entries = Vec::with_capacity(ids.len() + 2);
+        for id in ids {
+            if let Some(validator) = self.validators.get(id) {
+                entries.push((Self::validator_key(*id), serde_json::to_vec(validator)?));
+            }
+        }
+        let index: Vec<u64> = self.validators.keys().copied().collect();
+        entries.push((INDEX_KEY.to_vec(), serde_json::to_vec(&index)?));
+        entries.push((STATS_KEY.to_vec(), serde_json::to_vec(&self.get_stats())?));
+        db.write().await.write_batch_sync(&entries)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::engine::header_signing_bytes;
+    use block_sync::BlockHeader;
+    use ed25519_dalek::{Signer, SigningKey};
+    use tempfile::TempDir;
+
+    fn test_config() -> StakingConfig {
+        StakingConfig {
+            min_stake: 100,
+            unbonding_period: 10,
+            double_sign_slash_bps: 1000,
+        }
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn signed_header(key: &SigningKey, height: u64, timestamp: u64) -> (BlockHeader, Vec<u8>) {
+        let header = BlockHeader {
+            height,
+            prev_hash: [1u8; 32],
+            merkle_root: [0u8; 32],
+            timestamp,
+            nonce: 0,
+            difficulty: 1,
+            nullifier_root: [0u8; 32],
+        };
+        let signature = key.sign(&header_signing_bytes(&header)).to_bytes().to_vec();
+        (header, signature)
+    }
+
+    #[tokio::test]
+    async fn test_stake_unstake_withdraw() {
+        let mut staking = ValidatorStaking::new(test_config()).unwrap();
+        let public_key = key(1).verifying_key().to_bytes();
+
+        assert!(staking.stake(1, public_key, 50).await.is_err());
+        assert!(staking.get_validator(1).is_none());
+        assert_eq!(staking.stake(1, public_key, 300).await.unwrap(), 300);
+        assert!(staking.stake(1, key(2).verifying_key().to_bytes(), 300).await.is_err());
+
+        // Leaving less than the minimum bonded is not allowed
+        assert!(staking.unstake(1, 250, 5).await.is_err());
+        assert_eq!(staking.unstake(1, 100, 5).await.unwrap(), 15);
+        assert_eq!(staking.withdraw(1, 14).await.unwrap(), 0);
+        assert_eq!(staking.withdraw(1, 15).await.unwrap(), 100);
+
+        let stats = staking.get_stats();
+        assert_eq!(stats.total_staked, 200);
+        assert_eq!(stats.total_unbonding, 0);
+        assert_eq!(stats.active_validators, 1);
+    }
+
+    #[tokio::test]
+    async fn test_distribute_rewards() {
+        let mut staking = ValidatorStaking::new(test_config()).unwrap();
+        staking.stake(1, key(1).verifying_key().to_bytes(), 300).await.unwrap();
+        staking.stake(2, key(2).verifying_key().to_bytes(), 100).await.unwrap();
+
+        let shares = staking.distribute(101).await.unwrap();
+        // 75 and 25, with the rounding remainder going to the larger staker
+        assert_eq!(shares, vec![(1, 76), (2, 25)]);
+        assert_eq!(staking.claim_rewards(1).await.unwrap(), 76);
+        assert_eq!(staking.claim_rewards(1).await.unwrap(), 0);
+        assert_eq!(staking.get_stats().total_distributed, 101);
+    }
+
+    #[tokio::test]
+    async fn test_double_sign_slashing() {
+        let mut staking = ValidatorStaking::new(test_config()).unwrap();
+        let signer = key(1);
+        staking.stake(1, signer.verifying_key().to_bytes(), 1000).await.unwrap();
+        staking.unstake(1, 500, 0).await.unwrap();
+
+        let (first_header, first_signature) = signed_header(&signer, 7, 1_000);
+        let (second_header, second_signature) = signed_header(&signer, 7, 1_001);
+        let evidence = DoubleSignEvidence {
+            validator_id: 1,
+            height: 7,
+            first_header,
+            first_signature,
+            second_header,
+            second_signature,
+        };
+
+        // 10% of bonded plus unbonding stake, taken from the bond first
+        assert_eq!(staking.apply_evidence(&evidence).await.unwrap(), 100);
+        let validator = staking.get_validator(1).unwrap();
+        assert_eq!(validator.stake, 400);
+        assert_eq!(validator.unbonding[0].amount, 500);
+        assert!(validator.jailed);
+        assert!(staking.validator_set().is_empty());
+
+        // The same offence is only punished once
+        assert_eq!(staking.apply_evidence(&evidence).await.unwrap(), 0);
+
+        // Evidence that does not verify against the validator's key is rejected
+        let mut forged = evidence.clone();
+        forged.height = 8;
+        forged.first_header.height = 8;
+        forged.second_header.height = 8;
+        assert!(matches!(
+            staking.apply_evidence(&forged).await,
+            Err(StakingError::InvalidEvidence(_))
+        ));
+
+        staking.unjail(1).await.unwrap();
+        assert!(staking.validator_set().is_active(1));
+    }
+
+    #[tokio::test]
+    async fn test_state_persists() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Arc::new(RwLock::new(RocksStateDB::new(temp_dir.path()).unwrap()));
+        let public_key = key(1).verifying_key().to_bytes();
+        {
+            let mut staking = ValidatorStaking::with_state_db(test_config(), db.clone()).await.unwrap();
+            staking.stake(1, public_key, 400).await.unwrap();
+            staking.slash(1, 5000, "test").await.unwrap();
+        }
+
+        let staking = ValidatorStaking::with_state_db(test_config(), db).await.unwrap();
+        let validator = staking.get_validator(1).unwrap();
+        assert_eq!(validator.stake, 200);
+        assert!(validator.jailed);
+        assert_eq!(staking.get_stats().total_slashed, 200);
+    }
+}
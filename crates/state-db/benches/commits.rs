@@ -0,0 +1,50 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use state_db::account::Account;
+use state_db::RocksStateDB;
+use tempfile::TempDir;
+
+/// Accounts a block typically touches
+const UPDATES: u64 = 100;
+
+fn address(index: u64) -> Vec<u8> {
+    index.to_be_bytes().to_vec()
+}
+
+/// Commit latency of a block's account updates as the state grows
+fn bench_commits(c: &mut Criterion) {
+    let mut group = c.benchmark_group("state_db/commit 100 accounts");
+    group.sample_size(20);
+    for accounts in [1_000u64, 10_000, 100_000] {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        for index in 0..accounts {
+            let account = Account {
+                balance: index,
+                ..Default::default()
+            };
+            state.put_account(&address(index), &account).unwrap();
+        }
+        state.commit_sync(1).unwrap();
+
+        let mut version = 1;
+        group.bench_with_input(BenchmarkId::from_parameter(accounts), &accounts, |b, accounts| {
+            b.iter(|| {
+                version += 1;
+                for update in 0..UPDATES {
+                    let index = (version * UPDATES + update) * 7_919 % accounts;
+                    let account = Account {
+                        balance: version,
+                        nonce: version,
+                        ..Default::default()
+                    };
+                    state.put_account(&address(index), &account).unwrap();
+                }
+                state.commit_sync(version).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_commits);
+criterion_main!(benches);
@@ -0,0 +1,221 @@
+use crate::error::StateDBError;
+use crate::supply::SupplyLedger;
+use crate::{MerkleRoot, RocksStateDB};
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const ACCOUNT_PREFIX: &[u8] = b"account/";
+const CODE_PREFIX: &[u8] = b"code/";
+const STORAGE_PREFIX: &[u8] = b"storage/";
+
+/// Version holding the genesis allocation
+pub const GENESIS_VERSION: u64 = 0;
+
+/// Balance and nonce of an address
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    /// HEAT balance in atomic units
+    pub balance: u64,
+    /// Number of transactions sent from the account
+    pub nonce: u64,
+    /// Hash of the deployed code, for contract accounts
+    pub code_hash: Option<[u8; 32]>,
+    /// Root of the contract storage, for contract accounts
+    pub storage_root: Option<[u8; 32]>,
+}
+
+impl Account {
+    /// Add `amount` to the balance
+    pub fn credit(&mut self, amount: u64) -> Result<(), StateDBError> {
+        self.balance = self.balance.checked_add(amount).ok_or_else(|| {
+            StateDBError::BalanceOverflow(format!("crediting {} to balance {}", amount, self.balance))
+        })?;
+        Ok(())
+    }
+
+    /// Whether contract code is deployed at the account
+    pub fn is_contract(&self) -> bool {
+        self.code_hash.is_some()
+    }
+
+    /// Fold a contract storage write into the storage root.
+    ///
+    /// The slots themselves are committed by the state tree; the storage root is a
+    /// digest of the account's write history so a changed root reveals any write.
+    pub fn record_storage_write(&mut self, key: &[u8], value: &[u8]) {
+        let root = Hasher::new(Domain::StorageRoot).fixed(&self.storage_root.unwrap_or_default());
+        self.storage_root = Some(root.bytes(key).bytes(value).finish());
+    }
+
+    /// Remove `amount` from the balance
+    pub fn debit(&mut self, amount: u64) -> Result<(), StateDBError> {
+        self.balance = self.balance.checked_sub(amount).ok_or_else(|| {
+            StateDBError::InsufficientBalance(format!("balance {} is below {}", self.balance, amount))
+        })?;
+        Ok(())
+    }
+}
+
+/// State key of the account at `address`
+pub fn account_key(address: &[u8]) -> Vec<u8> {
+    [ACCOUNT_PREFIX, address].concat()
+}
+
+/// State key of the contract code with hash `code_hash`
+pub fn code_key(code_hash: &[u8; 32]) -> Vec<u8> {
+    [CODE_PREFIX, code_hash.as_slice()].concat()
+}
+
+/// State key of storage slot `key` of the contract at `address`
+pub fn storage_key(address: &[u8], key: &[u8]) -> Vec<u8> {
+    [STORAGE_PREFIX, &(address.len() as u32).to_be_bytes(), address, key].concat()
+}
+
+/// Initial allocation in a genesis file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    pub balance: u64,
+}
+
+/// Genesis file allocating the initial HEAT balances
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genesis {
+    /// Balances by hex-encoded address
+    pub alloc: BTreeMap<String, GenesisAccount>,
+}
+
+impl Genesis {
+    /// Load a genesis JSON file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, StateDBError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Decoded addresses with their initial accounts
+    pub fn accounts(&self) -> Result<Vec<(Vec<u8>, Account)>, StateDBError> {
+        self.alloc
+            .iter()
+            .map(|(address, allocation)| {
+                let address = hex::decode(address.trim_start_matches("0x"))
+                    .map_err(|e| StateDBError::ConfigError(format!("Invalid genesis address {}: {}", address, e)))?;
+                let account = Account {
+                    balance: allocation.balance,
+                    ..Default::default()
+                };
+                Ok((address, account))
+            })
+            .collect()
+    }
+
+    /// Sum of all allocations
+    pub fn total_supply(&self) -> Result<u64, StateDBError> {
+        self.alloc.values().try_fold(0u64, |total, allocation| {
+            total
+                .checked_add(allocation.balance)
+                .ok_or_else(|| StateDBError::BalanceOverflow("genesis allocations exceed the supply limit".to_string()))
+        })
+    }
+}
+
+impl RocksStateDB {
+    /// Get the account at `address`, or an empty account if it was never written
+    pub fn get_account(&self, address: &[u8]) -> Result<Account, StateDBError> {
+        match self.get_sync(&account_key(address))? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Account::default()),
+        }
+    }
+
+    /// Stage the account at `address` for the next commit
+    pub fn put_account(&mut self, address: &[u8], account: &Account) -> Result<(), StateDBError> {
+        self.put_sync(&account_key(address), &serde_json::to_vec(account)?)
+    }
+
+    /// Get deployed contract code by hash
+    pub fn get_code(&self, code_hash: &[u8; 32]) -> Result<Option<Vec<u8>>, StateDBError> {
+        self.get_sync(&code_key(code_hash))
+    }
+
+    /// Stage contract code for the next commit, returning its hash
+    pub fn put_code(&mut self, code: &[u8]) -> Result<[u8; 32], StateDBError> {
+        let code_hash = hashing::hash(Domain::ContractCode, code);
+        self.put_sync(&code_key(&code_hash), code)?;
+        Ok(code_hash)
+    }
+
+    /// Get storage slot `key` of the contract at `address`
+    pub fn get_storage(&self, address: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
+        self.get_sync(&storage_key(address, key))
+    }
+
+    /// Stage a contract storage write for the next commit
+    pub fn put_storage(&mut self, address: &[u8], key: &[u8], value: &[u8]) -> Result<(), StateDBError> {
+        self.put_sync(&storage_key(address, key), value)
+    }
+
+    /// Commit the genesis allocation as the first version of an empty database
+    pub fn apply_genesis(&mut self, genesis: &Genesis) -> Result<MerkleRoot, StateDBError> {
+        if self.latest_version.is_some() {
+            return Err(StateDBError::ConfigError("State is already initialized".to_string()));
+        }
+        let supply = genesis.total_supply()?;
+        for (address, account) in genesis.accounts()? {
+            self.put_account(&address, &account)?;
+        }
+        self.put_supply(&SupplyLedger {
+            genesis: supply,
+            ..Default::default()
+        })?;
+        let root = self.commit_sync(GENESIS_VERSION)?;
+        println!(
+            "Applied genesis allocation of {} HEAT to {} accounts",
+            supply,
+            genesis.alloc.len()
+        );
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::supply::MintSource;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_genesis_allocation() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("genesis.json");
+        fs::write(&path, r#"{"alloc": {"0xaabb": {"balance": 1000}, "ccdd": {"balance": 500}}}"#).unwrap();
+        let genesis = Genesis::from_file(&path).unwrap();
+        assert_eq!(genesis.total_supply().unwrap(), 1500);
+
+        let mut db = RocksStateDB::new(temp_dir.path().join("state")).unwrap();
+        let root = db.apply_genesis(&genesis).unwrap();
+        assert_eq!(db.root_at(GENESIS_VERSION).unwrap(), Some(root));
+        assert_eq!(db.get_account(&[0xaa, 0xbb]).unwrap().balance, 1000);
+        assert_eq!(db.get_account(&[0x01]).unwrap(), Account::default());
+        let mut supply = db.get_supply().unwrap();
+        assert_eq!((supply.genesis, supply.circulating().unwrap()), (1500, 1500));
+        supply.record_mint(MintSource::XfgBurn, 200).unwrap();
+        supply.record_withdrawal(1700).unwrap();
+        assert_eq!((supply.xfg_burn_minted, supply.bridged_out, supply.circulating().unwrap()), (200, 1700, 0));
+        assert!(supply.record_withdrawal(1).is_err());
+        assert!(db.apply_genesis(&genesis).is_err());
+
+        let mut account = db.get_account(&[0xcc, 0xdd]).unwrap();
+        assert!(account.debit(501).is_err());
+        account.debit(500).unwrap();
+        account.credit(u64::MAX).unwrap();
+        assert!(matches!(account.credit(1), Err(StateDBError::BalanceOverflow(_))));
+
+        // Storage writes move the storage root, and slots are namespaced by contract
+        account.record_storage_write(b"slot", b"1");
+        let root = account.storage_root;
+        account.record_storage_write(b"slot", b"2");
+        assert!(root.is_some() && account.storage_root != root);
+        assert_ne!(storage_key(&[1], &[2, 3]), storage_key(&[1, 2], &[3]));
+    }
+}
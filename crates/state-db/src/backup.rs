@@ -0,0 +1,347 @@
+//! Online backups of the state database. A backup is a RocksDB checkpoint, a consistent copy
+//! of every column family taken while the node keeps running, with a manifest listing the
+//! size and checksum of each of its files. Backups are taken on request or on a schedule and
+//! the oldest beyond the retention are deleted. A restore checks every file against the
+//! manifest, and the copy against the manifest's state root, before it replaces anything.
+
+use crate::config::StateDBConfig;
+use crate::error::StateDBError;
+use crate::{MerkleRoot, RocksStateDB};
+use hashing::{Domain, Hasher};
+use rocksdb::checkpoint::Checkpoint;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+const MANIFEST_FILE: &str = "backup.json";
+
+/// Layout version of backup manifests
+const BACKUP_FORMAT: u32 = 1;
+
+/// Bytes of a file hashed at a time
+const HASH_PIECE: usize = 1 << 20;
+
+/// One file of a backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub name: String,
+    pub size: u64,
+    /// Hash of the file's contents, taken a piece at a time
+    pub checksum: [u8; 32],
+}
+
+/// Description of a backup, stored among its files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: u32,
+    /// Unix time the backup was taken
+    pub created_at: u64,
+    /// Latest state version in the backup, `None` for an empty database
+    pub version: Option<u64>,
+    /// State root of that version
+    pub root: Option<MerkleRoot>,
+    pub files: Vec<BackupFile>,
+}
+
+impl BackupManifest {
+    /// Read the manifest of the backup in `dir`
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, StateDBError> {
+        let manifest: BackupManifest = serde_json::from_slice(&fs::read(dir.as_ref().join(MANIFEST_FILE))?)?;
+        if manifest.format != BACKUP_FORMAT {
+            return Err(StateDBError::BackupError(format!("Unsupported backup format {}", manifest.format)));
+        }
+        Ok(manifest)
+    }
+
+    /// Check every file the manifest lists is in `dir` with its size and checksum
+    pub fn verify<P: AsRef<Path>>(&self, dir: P) -> Result<(), StateDBError> {
+        for file in &self.files {
+            let path = dir.as_ref().join(&file.name);
+            let size = fs::metadata(&path)
+                .map_err(|e| StateDBError::BackupError(format!("{} is missing: {}", file.name, e)))?
+                .len();
+            if size != file.size {
+                return Err(StateDBError::BackupError(format!(
+                    "{} holds {} bytes, expected {}",
+                    file.name, size, file.size
+                )));
+            }
+            if file_checksum(&path)? != file.checksum {
+                return Err(StateDBError::BackupError(format!("{} is corrupt", file.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checksum of a file: its pieces, each behind its length
+fn file_checksum(path: &Path) -> Result<[u8; 32], StateDBError> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Hasher::new(Domain::BackupFile);
+    let mut piece = vec![0u8; HASH_PIECE];
+    loop {
+        let read = file.read(&mut piece)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher = hasher.bytes(&piece[..read]);
+    }
+}
+
+/// Size and checksum of every file in `dir`, by name
+fn list_files(dir: &Path) -> Result<Vec<BackupFile>, StateDBError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() || name == MANIFEST_FILE {
+            continue;
+        }
+        files.push(BackupFile {
+            size: entry.metadata()?.len(),
+            checksum: file_checksum(&entry.path())?,
+            name,
+        });
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(files)
+}
+
+/// Backups in `dir` with their manifests, oldest first. Directories without a readable
+/// manifest, such as a backup interrupted part way, are skipped.
+pub fn list_backups<P: AsRef<Path>>(dir: P) -> Result<Vec<(PathBuf, BackupManifest)>, StateDBError> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Ok(manifest) = BackupManifest::load(&path) {
+            backups.push((path, manifest));
+        }
+    }
+    backups.sort_by(|(a_path, a), (b_path, b)| (a.created_at, a_path).cmp(&(b.created_at, b_path)));
+    Ok(backups)
+}
+
+/// Delete the oldest backups in `dir` beyond the newest `retention`, returning how many
+pub fn prune_backups<P: AsRef<Path>>(dir: P, retention: usize) -> Result<usize, StateDBError> {
+    let backups = list_backups(dir)?;
+    let excess = backups.len().saturating_sub(retention);
+    for (path, _) in &backups[..excess] {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(excess)
+}
+
+/// Replace the database at `db_path` with the backup in `backup_dir`. The backup's files are
+/// checked, copied beside the database and opened with `config` to check the state root
+/// before the database is swapped out; the replaced database is kept beside the restored one.
+/// The database must not be open.
+pub fn restore_backup<P: AsRef<Path>, Q: AsRef<Path>>(
+    backup_dir: P,
+    db_path: Q,
+    config: StateDBConfig,
+) -> Result<BackupManifest, StateDBError> {
+    let (backup_dir, db_path) = (backup_dir.as_ref(), db_path.as_ref());
+    let manifest = BackupManifest::load(backup_dir)?;
+    manifest.verify(backup_dir)?;
+
+    let sibling = |suffix: &str| {
+        let mut name = db_path.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        db_path.with_file_name(name)
+    };
+    let (staging, replaced) = (sibling(".restore"), sibling(".pre-restore"));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    for file in &manifest.files {
+        fs::copy(backup_dir.join(&file.name), staging.join(&file.name))?;
+    }
+    let checked = manifest.verify(&staging).and_then(|()| {
+        let restored = RocksStateDB::with_config(&staging, config)?;
+        let root = restored.latest_version().map(|version| restored.root_at(version)).transpose()?.flatten();
+        if restored.latest_version() != manifest.version || root != manifest.root {
+            return Err(StateDBError::BackupError(
+                "Restored state does not match the manifest's version and root".to_string(),
+            ));
+        }
+        Ok(())
+    });
+    if let Err(e) = checked {
+        fs::remove_dir_all(&staging)?;
+        return Err(e);
+    }
+
+    if replaced.exists() {
+        fs::remove_dir_all(&replaced)?;
+    }
+    if db_path.exists() {
+        fs::rename(db_path, &replaced)?;
+    }
+    fs::rename(&staging, db_path)?;
+    println!(
+        "Restored state backup at version {} ({} files)",
+        manifest.version.map_or("none".to_string(), |version| version.to_string()),
+        manifest.files.len()
+    );
+    Ok(manifest)
+}
+
+impl RocksStateDB {
+    /// Directory backups are written to; a relative configured one is taken from the
+    /// directory holding the database
+    pub fn backup_dir(&self) -> PathBuf {
+        let dir = Path::new(&self.config.backup.dir);
+        match self.db.path().parent() {
+            Some(parent) if dir.is_relative() => parent.join(dir),
+            _ => dir.to_path_buf(),
+        }
+    }
+
+    /// Checkpoint the database into a new backup, then delete the oldest beyond the retention
+    pub fn create_backup(&self) -> Result<(PathBuf, BackupManifest), StateDBError> {
+        if !self.pending_changes.is_empty() {
+            println!("{} uncommitted state changes will not be backed up", self.pending_changes.len());
+        }
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let version = self.latest_version;
+        let root = version.map(|version| self.root_at(version)).transpose()?.flatten();
+        let backups = self.backup_dir();
+        fs::create_dir_all(&backups)?;
+        let dir = backups.join(format!("backup-{}-{}", created_at, version.unwrap_or(0)));
+        if dir.exists() {
+            return Err(StateDBError::BackupError(format!("{} already exists", dir.display())));
+        }
+
+        Checkpoint::new(&self.db)?.create_checkpoint(&dir)?;
+        let manifest = BackupManifest {
+            format: BACKUP_FORMAT,
+            created_at,
+            version,
+            root,
+            files: list_files(&dir)?,
+        };
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+        println!("Backed up state at version {} to {}", version.unwrap_or(0), dir.display());
+
+        let pruned = prune_backups(&backups, self.config.backup.retention.max(1))?;
+        if pruned > 0 {
+            println!("Deleted {} backups beyond the retention", pruned);
+        }
+        Ok((dir, manifest))
+    }
+
+    /// Back the database up on the configured interval until `shutdown`, if one is set
+    pub async fn spawn_backups(db: Arc<RwLock<RocksStateDB>>, shutdown: CancellationToken) -> Option<JoinHandle<()>> {
+        let interval = db.read().await.config.backup.interval?;
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.cancelled() => return,
+                }
+                if let Err(e) = db.read().await.create_backup() {
+                    eprintln!("State backup failed: {}", e);
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BackupConfig;
+    use tempfile::TempDir;
+
+    fn config(retention: usize) -> StateDBConfig {
+        StateDBConfig {
+            backup: BackupConfig { retention, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_backup_restores_the_state_it_was_taken_at() {
+        let data_dir = TempDir::new().unwrap();
+        let db_path = data_dir.path().join("state");
+        let (backup, manifest) = {
+            let mut db = RocksStateDB::with_config(&db_path, config(2)).unwrap();
+            db.put_sync(b"a", b"1").unwrap();
+            db.commit_sync(1).unwrap();
+            let backup = db.create_backup().unwrap();
+            db.put_sync(b"a", b"2").unwrap();
+            db.commit_sync(2).unwrap();
+            backup
+        };
+        assert_eq!(backup.parent(), Some(data_dir.path().join("backups").as_path()));
+        assert_eq!(manifest.version, Some(1));
+        assert!(!manifest.files.is_empty());
+        assert_eq!(list_backups(data_dir.path().join("backups")).unwrap(), vec![(backup.clone(), manifest.clone())]);
+
+        restore_backup(&backup, &db_path, config(2)).unwrap();
+        let db = RocksStateDB::with_config(&db_path, config(2)).unwrap();
+        assert_eq!(db.latest_version(), Some(1));
+        assert_eq!(db.get_sync(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(db.root_at(1).unwrap(), manifest.root);
+        assert!(data_dir.path().join("state.pre-restore").exists());
+    }
+
+    #[test]
+    fn test_corrupt_backup_is_not_restored() {
+        let data_dir = TempDir::new().unwrap();
+        let db_path = data_dir.path().join("state");
+        let (backup, manifest) = {
+            let mut db = RocksStateDB::with_config(&db_path, config(2)).unwrap();
+            db.put_sync(b"a", b"1").unwrap();
+            db.commit_sync(1).unwrap();
+            db.create_backup().unwrap()
+        };
+        let file = &manifest.files[0];
+        let mut bytes = fs::read(backup.join(&file.name)).unwrap();
+        bytes.push(0);
+        fs::write(backup.join(&file.name), bytes).unwrap();
+
+        assert!(matches!(
+            restore_backup(&backup, &db_path, config(2)),
+            Err(StateDBError::BackupError(_))
+        ));
+        assert!(!data_dir.path().join("state.restore").exists());
+        assert!(!data_dir.path().join("state.pre-restore").exists());
+    }
+
+    #[test]
+    fn test_old_backups_beyond_the_retention_are_deleted() {
+        let backups = TempDir::new().unwrap();
+        for (created_at, name) in [(3, "c"), (1, "a"), (2, "b")] {
+            let dir = backups.path().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            let manifest = BackupManifest {
+                format: BACKUP_FORMAT,
+                created_at,
+                version: None,
+                root: None,
+                files: vec![],
+            };
+            fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        }
+        fs::create_dir_all(backups.path().join("interrupted")).unwrap();
+
+        assert_eq!(prune_backups(backups.path(), 2).unwrap(), 1);
+        let kept: Vec<u64> = list_backups(backups.path()).unwrap().iter().map(|(_, m)| m.created_at).collect();
+        assert_eq!(kept, vec![2, 3]);
+        assert!(backups.path().join("interrupted").exists());
+    }
+}
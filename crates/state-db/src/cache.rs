@@ -0,0 +1,111 @@
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Hit and miss totals of the account read cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    /// Share of reads answered from memory
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            reads => self.hits as f64 / reads as f64,
+        }
+    }
+}
+
+/// Committed values of recently read keys, absent keys included. Writes drop the keys
+/// they touch, so a cached value is never older than the database's.
+pub(crate) struct ReadCache {
+    entries: Mutex<LruCache<Vec<u8>, Option<Vec<u8>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ReadCache {
+    /// A cache of `capacity` keys, or none when it is 0
+    pub(crate) fn new(capacity: usize) -> Option<Self> {
+        Some(Self {
+            entries: Mutex::new(LruCache::new(NonZeroUsize::new(capacity)?)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// The cached value of `key`, or the one `read` finds, which is then cached
+    pub(crate) fn get_or_read<E>(
+        &self,
+        key: &[u8],
+        read: impl FnOnce() -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Option<Vec<u8>>, E> {
+        if let Some(value) = self.entries.lock().unwrap().get(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value.clone());
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = read()?;
+        self.entries.lock().unwrap().put(key.to_vec(), value.clone());
+        Ok(value)
+    }
+
+    pub(crate) fn invalidate<'a>(&self, keys: impl IntoIterator<Item = &'a Vec<u8>>) {
+        let mut entries = self.entries.lock().unwrap();
+        for key in keys {
+            entries.pop(key);
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::account::account_key;
+    use crate::{Account, RocksStateDB};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_account_reads_are_cached_until_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+        let funded = Account { balance: 10, ..Default::default() };
+        db.put_account(b"alice", &funded).unwrap();
+        db.commit_sync(1).unwrap();
+
+        assert_eq!(db.get_account(b"alice").unwrap(), funded);
+        assert_eq!(db.get_account(b"alice").unwrap(), funded);
+        assert_eq!(db.get_account(b"bob").unwrap(), Account::default());
+        let stats = db.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+        // Commits and direct writes replace what was cached
+        let spent = Account { balance: 4, nonce: 1, ..Default::default() };
+        db.put_account(b"alice", &spent).unwrap();
+        db.commit_sync(2).unwrap();
+        assert_eq!(db.get_account(b"alice").unwrap(), spent);
+        db.write_batch_sync(&[(account_key(b"bob"), serde_json::to_vec(&funded).unwrap())]).unwrap();
+        assert_eq!(db.get_account(b"bob").unwrap(), funded);
+        assert_eq!(db.cache_stats().unwrap().misses, 4);
+
+        // Values kept outside the account column family are not cached
+        db.get_sync(b"bridge/deposit_cursor").unwrap();
+        assert_eq!(db.cache_stats().unwrap().misses, 4);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// How much state history the database keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StorageMode {
+    /// Keep only the last `retention` committed versions
+    Pruned { retention: u64 },
+    /// Keep every committed version
+    Archive,
+}
+
+impl StorageMode {
+    pub fn is_archive(&self) -> bool {
+        matches!(self, StorageMode::Archive)
+    }
+}
+
+impl Default for StorageMode {
+    fn default() -> Self {
+        StorageMode::Pruned { retention: 10_000 }
+    }
+}
+
+impl fmt::Display for StorageMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageMode::Pruned { retention } => write!(f, "pruned (retention {})", retention),
+            StorageMode::Archive => write!(f, "archive"),
+        }
+    }
+}
+
+/// Compression of a column family's SST files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl From<Compression> for rocksdb::DBCompressionType {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => rocksdb::DBCompressionType::None,
+            Compression::Snappy => rocksdb::DBCompressionType::Snappy,
+            Compression::Lz4 => rocksdb::DBCompressionType::Lz4,
+            Compression::Zstd => rocksdb::DBCompressionType::Zstd,
+        }
+    }
+}
+
+/// RocksDB memory and on-disk format settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RocksTuning {
+    /// Block cache shared by every column family, in MiB
+    pub block_cache_mb: usize,
+    /// Memtable size of each column family, in MiB
+    pub write_buffer_mb: usize,
+    /// Bloom filter bits per key; 0 disables the filters
+    pub bloom_bits_per_key: u32,
+    /// Compression of column families without an override
+    pub compression: Compression,
+    /// Compression by column family name, such as `history` or `accounts`
+    pub column_compression: BTreeMap<String, Compression>,
+}
+
+impl Default for RocksTuning {
+    fn default() -> Self {
+        Self {
+            block_cache_mb: 128,
+            write_buffer_mb: 64,
+            bloom_bits_per_key: 10,
+            compression: Compression::Lz4,
+            column_compression: BTreeMap::new(),
+        }
+    }
+}
+
+/// Online backups of the state database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Directory backups are written to; a relative one is taken from the data directory
+    pub dir: String,
+    /// How often the background task takes a backup; `None` only backs up on request
+    pub interval: Option<Duration>,
+    /// Backups kept; older ones are deleted after each new backup
+    pub retention: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: "backups".to_string(),
+            interval: None,
+            retention: 7,
+        }
+    }
+}
+
+/// State database configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateDBConfig {
+    pub mode: StorageMode,
+    /// How often the background task compacts pruned history
+    pub compaction_interval: Duration,
+    /// State entries per chunk of an exported snapshot
+    pub snapshot_chunk_entries: usize,
+    pub tuning: RocksTuning,
+    /// Account, code and contract storage reads kept in memory; 0 disables the cache
+    pub account_cache_entries: usize,
+    pub backup: BackupConfig,
+}
+
+impl Default for StateDBConfig {
+    fn default() -> Self {
+        Self {
+            mode: StorageMode::default(),
+            compaction_interval: Duration::from_secs(3600),
+            snapshot_chunk_entries: 10_000,
+            tuning: RocksTuning::default(),
+            account_cache_entries: 10_000,
+            backup: BackupConfig::default(),
+        }
+    }
+}
@@ -13,4 +13,34 @@ pub enum StateDBError {
     
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    #[error("Storage mode mismatch: {0}")]
+    ModeMismatch(String),
+
+    #[error("Version {0} has been pruned")]
+    VersionPruned(u64),
+
+    #[error("Invalid version: {0}")]
+    InvalidVersion(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Schema error: {0}")]
+    SchemaError(String),
+
+    #[error("Snapshot error: {0}")]
+    SnapshotError(String),
+
+    #[error("Backup error: {0}")]
+    BackupError(String),
+
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalance(String),
+
+    #[error("Balance overflow: {0}")]
+    BalanceOverflow(String),
+
+    #[error("Nullifier already spent: {0}")]
+    NullifierSpent(String),
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use crate::error::StateDBError;
+use crate::{RocksStateDB, CHANGES_CF, HISTORY_CF, META_CF, PRUNED_THROUGH_KEY, STALE_CF, TREE_CF};
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// History keys are the length-prefixed state key, so no key is a prefix of another
+pub(crate) fn key_prefix(key: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(4 + key.len());
+    prefix.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    prefix.extend_from_slice(key);
+    prefix
+}
+
+/// History key of `key` at `version`, ordered by version within each key
+pub(crate) fn history_key(key: &[u8], version: u64) -> Vec<u8> {
+    let mut history_key = key_prefix(key);
+    history_key.extend_from_slice(&version.to_be_bytes());
+    history_key
+}
+
+/// Split a history key into the state key and version
+pub(crate) fn split_history_key(history_key: &[u8]) -> Option<(&[u8], u64)> {
+    let len = u32::from_be_bytes(history_key.get(..4)?.try_into().ok()?) as usize;
+    if history_key.len() != 4 + len + 8 {
+        return None;
+    }
+    Some((&history_key[4..4 + len], version_suffix(history_key)))
+}
+
+/// Version stored in the last eight bytes of a key
+fn version_suffix(key: &[u8]) -> u64 {
+    let mut version = [0u8; 8];
+    version.copy_from_slice(&key[key.len() - 8..]);
+    u64::from_be_bytes(version)
+}
+
+impl RocksStateDB {
+    /// Delete history and tree nodes that no version after `horizon` can read, returning the entries removed
+    pub fn prune_through(&mut self, horizon: u64) -> Result<u64, StateDBError> {
+        if self.pruned_through.is_some_and(|pruned| horizon <= pruned) {
+            return Ok(0);
+        }
+        let start = self.pruned_through.map_or(0, |pruned| pruned + 1);
+        let history = self.cf(HISTORY_CF)?;
+        let changes = self.cf(CHANGES_CF)?;
+        let mut batch = WriteBatch::default();
+
+        // Keys changed in the pruned versions
+        let mut keys = BTreeSet::new();
+        for entry in self
+            .db
+            .iterator_cf(changes, IteratorMode::From(&start.to_be_bytes(), Direction::Forward))
+        {
+            let (version, changed) = entry?;
+            if version_suffix(&version) > horizon {
+                break;
+            }
+            keys.extend(serde_json::from_slice::<Vec<Vec<u8>>>(&changed)?);
+            batch.delete_cf(changes, version);
+        }
+
+        // Keep each key's newest value at the horizon, since later versions still read it
+        let mut removed = 0;
+        for key in keys {
+            let prefix = key_prefix(&key);
+            let seek = history_key(&key, horizon);
+            let newest = match self
+                .db
+                .iterator_cf(history, IteratorMode::From(&seek, Direction::Reverse))
+                .next()
+                .transpose()?
+            {
+                Some((entry, _)) if entry.starts_with(&prefix) => version_suffix(&entry),
+                _ => continue,
+            };
+            for entry in self.db.iterator_cf(history, IteratorMode::From(&prefix, Direction::Forward)) {
+                let (entry, _) = entry?;
+                if !entry.starts_with(&prefix) || version_suffix(&entry) >= newest {
+                    break;
+                }
+                batch.delete_cf(history, entry);
+                removed += 1;
+            }
+        }
+
+        // Tree nodes replaced at or before the horizon are only reachable from pruned versions
+        let nodes = self.cf(TREE_CF)?;
+        let stale = self.cf(STALE_CF)?;
+        for entry in self
+            .db
+            .iterator_cf(stale, IteratorMode::From(&start.to_be_bytes(), Direction::Forward))
+        {
+            let (stale_key, _) = entry?;
+            if version_suffix(&stale_key[..8]) > horizon {
+                break;
+            }
+            batch.delete_cf(nodes, &stale_key[8..]);
+            batch.delete_cf(stale, stale_key);
+            removed += 1;
+        }
+
+        batch.put_cf(self.cf(META_CF)?, PRUNED_THROUGH_KEY, horizon.to_be_bytes());
+        self.db.write(batch)?;
+        self.pruned_through = Some(horizon);
+        self.pruned_since_compaction.fetch_add(removed, Ordering::Relaxed);
+        if removed > 0 {
+            println!("Pruned {} state entries through version {}", removed, horizon);
+        }
+        Ok(removed)
+    }
+
+    /// Compact the history column families to reclaim space left by pruning
+    pub fn compact(&self) -> Result<(), StateDBError> {
+        for name in [HISTORY_CF, CHANGES_CF, TREE_CF, STALE_CF] {
+            self.db.compact_range_cf(self.cf(name)?, None::<&[u8]>, None::<&[u8]>);
+        }
+        self.pruned_since_compaction.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Periodically compact the database once pruning has deleted history, until `shutdown`
+    pub async fn spawn_compaction(db: Arc<RwLock<RocksStateDB>>, shutdown: CancellationToken) -> JoinHandle<()> {
+        let interval = db.read().await.config.compaction_interval;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.cancelled() => return,
+                }
+                let db = db.read().await;
+                let pending = db.pruned_since_compaction.load(Ordering::Relaxed);
+                if pending == 0 {
+                    continue;
+                }
+                match db.compact() {
+                    Ok(()) => println!("Compacted state history after pruning {} entries", pending),
+                    Err(e) => eprintln!("State compaction failed: {}", e),
+                }
+            }
+        })
+    }
+}
@@ -1,17 +1,72 @@
 use anyhow::Result;
-use rocksdb::{DB, Options};
+use rocksdb::{
+    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, Options, WriteBatch, DB,
+};
 use std::path::Path;
 use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
 
+pub mod account;
+pub mod backup;
+pub mod cache;
+pub mod config;
 pub mod error;
+pub mod history;
 pub mod merkle;
+pub mod nullifier;
+pub mod schema;
+pub mod snapshot;
+pub mod supply;
+pub mod view;
 
+pub use account::{Account, Genesis};
+pub use backup::{BackupFile, BackupManifest};
+pub use cache::CacheStats;
+pub use config::{BackupConfig, Compression, RocksTuning, StateDBConfig, StorageMode};
+pub use supply::{MintSource, SupplyLedger};
+pub use view::StateView;
+use cache::ReadCache;
 use error::StateDBError;
-use merkle::MerkleTrie;
+use history::history_key;
+use merkle::{key_hash, value_hash, Child, Node, NodeKey, NodeReader, SparseMerkleProof, SparseMerkleTree, EMPTY_HASH};
+use schema::{value_cf, ACCOUNTS_CF, VALUE_CFS};
+
+/// Column family holding every committed value by key and version
+const HISTORY_CF: &str = "history";
+/// Column family listing the keys changed in each version
+const CHANGES_CF: &str = "changes";
+/// Column family for database bookkeeping
+const META_CF: &str = "meta";
+/// Column family holding sparse Merkle tree nodes
+const TREE_CF: &str = "tree";
+/// Column family holding the tree root of each version
+const ROOTS_CF: &str = "roots";
+/// Column family indexing tree nodes by the version that replaced them
+const STALE_CF: &str = "stale_nodes";
+/// Column families of versioned history and database bookkeeping
+const HISTORY_CFS: [&str; 6] = [HISTORY_CF, CHANGES_CF, META_CF, TREE_CF, ROOTS_CF, STALE_CF];
+
+const MODE_KEY: &[u8] = b"storage_mode";
+const LATEST_VERSION_KEY: &[u8] = b"latest_version";
+const PRUNED_THROUGH_KEY: &[u8] = b"pruned_through";
+
+/// Entries written per batch when copying values between databases
+const COPY_BATCH: usize = 10_000;
 
 /// Merkle root type
 pub type MerkleRoot = [u8; 32];
 
+/// Keys with their values
+type KeyValues = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// RocksDB size estimates reported by `RocksStateDB::storage_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    pub estimated_keys: u64,
+    pub sst_bytes: u64,
+    pub memtable_bytes: u64,
+}
+
 /// State database trait as specified in the outline
 pub trait StateDB {
     async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
@@ -22,56 +77,372 @@ pub trait StateDB {
 /// RocksDB-based state database implementation
 pub struct RocksStateDB {
     db: DB,
-    merkle_trie: MerkleTrie,
+    config: StateDBConfig,
+    /// Tree root of the latest version
+    root: Option<Child>,
     pending_changes: HashMap<Vec<u8>, Vec<u8>>,
+    latest_version: Option<u64>,
+    /// Versions at or below this one have been pruned
+    pruned_through: Option<u64>,
+    /// History entries deleted since the last compaction
+    pruned_since_compaction: AtomicU64,
+    /// Recently read accounts, code and contract storage
+    account_cache: Option<ReadCache>,
 }
 
 impl RocksStateDB {
-    /// Create a new state database
+    /// Create a new state database with the default configuration
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, StateDBError> {
+        Self::with_config(path, StateDBConfig::default())
+    }
+
+    /// Open a state database, failing if it was created in a different storage mode
+    pub fn with_config<P: AsRef<Path>>(path: P, config: StateDBConfig) -> Result<Self, StateDBError> {
+        if config.mode == (StorageMode::Pruned { retention: 0 }) {
+            return Err(StateDBError::ConfigError("Pruning retention must be at least 1".to_string()));
+        }
+
         let mut opts = Options::default();
         opts.create_if_missing(true);
-        
-        let db = DB::open(&opts, path)?;
-        let merkle_trie = MerkleTrie::new();
-        let pending_changes = HashMap::new();
-        
-        Ok(Self {
+        opts.create_missing_column_families(true);
+
+        let tuning = &config.tuning;
+        let mut table: BlockBasedOptions = Default::default();
+        table.set_block_cache(&Cache::new_lru_cache(tuning.block_cache_mb << 20));
+        if tuning.bloom_bits_per_key > 0 {
+            table.set_bloom_filter(tuning.bloom_bits_per_key as f64, false);
+        }
+        let descriptors = HISTORY_CFS.iter().chain(&VALUE_CFS).map(|name| {
+            let mut cf_opts = Options::default();
+            cf_opts.set_block_based_table_factory(&table);
+            cf_opts.set_write_buffer_size(tuning.write_buffer_mb << 20);
+            let compression = tuning.column_compression.get(*name).unwrap_or(&tuning.compression);
+            cf_opts.set_compression_type((*compression).into());
+            ColumnFamilyDescriptor::new(*name, cf_opts)
+        });
+        let db = DB::open_cf_descriptors(&opts, path, descriptors)?;
+        schema::migrate(&db)?;
+        let mut state_db = Self {
             db,
-            merkle_trie,
-            pending_changes,
-        })
+            account_cache: ReadCache::new(config.account_cache_entries),
+            config,
+            root: None,
+            pending_changes: HashMap::new(),
+            latest_version: None,
+            pruned_through: None,
+            pruned_since_compaction: AtomicU64::new(0),
+        };
+        state_db.check_mode()?;
+        state_db.latest_version = state_db.read_version(LATEST_VERSION_KEY)?;
+        state_db.pruned_through = state_db.read_version(PRUNED_THROUGH_KEY)?;
+        if let Some(latest) = state_db.latest_version {
+            state_db.root = state_db.root_child(latest)?.flatten();
+        }
+        Ok(state_db)
+    }
+
+    /// Record the storage mode on first open and refuse to switch modes later
+    fn check_mode(&self) -> Result<(), StateDBError> {
+        let meta = self.cf(META_CF)?;
+        let mode = self.config.mode;
+        if let Some(stored) = self.db.get_cf(meta, MODE_KEY)? {
+            let stored: StorageMode = serde_json::from_slice(&stored)?;
+            if stored.is_archive() != mode.is_archive() {
+                return Err(StateDBError::ModeMismatch(format!(
+                    "database was created in {} mode but opened in {} mode",
+                    stored, mode
+                )));
+            }
+            if stored == mode {
+                return Ok(());
+            }
+            println!("State retention changed from {} to {}", stored, mode);
+        }
+        self.db.put_cf(meta, MODE_KEY, serde_json::to_vec(&mode)?)?;
+        Ok(())
+    }
+
+    fn read_version(&self, key: &[u8]) -> Result<Option<u64>, StateDBError> {
+        Ok(self
+            .db
+            .get_cf(self.cf(META_CF)?, key)?
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_be_bytes))
+    }
+
+    fn cf(&self, name: &str) -> Result<&ColumnFamily, StateDBError> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| StateDBError::ConfigError(format!("Missing column family {}", name)))
+    }
+
+    /// Storage mode the database was opened with
+    pub fn mode(&self) -> StorageMode {
+        self.config.mode
+    }
+
+    /// Highest committed version
+    pub fn latest_version(&self) -> Option<u64> {
+        self.latest_version
+    }
+
+    /// Highest version whose history has been pruned
+    pub fn pruned_through(&self) -> Option<u64> {
+        self.pruned_through
+    }
+
+    /// Write the memtables of every column family to disk. Staged changes that have
+    /// not been committed are not part of any version and are not written.
+    pub fn flush(&self) -> Result<(), StateDBError> {
+        if !self.pending_changes.is_empty() {
+            println!("{} uncommitted state changes will not be persisted", self.pending_changes.len());
+        }
+        for name in HISTORY_CFS.iter().chain(&VALUE_CFS) {
+            self.db.flush_cf(self.cf(name)?)?;
+        }
+        Ok(())
+    }
+
+    /// RocksDB size estimates summed over the column families
+    pub fn storage_stats(&self) -> Result<StorageStats, StateDBError> {
+        let mut stats = StorageStats::default();
+        for name in HISTORY_CFS.iter().chain(&VALUE_CFS) {
+            let cf = self.cf(name)?;
+            let property = |property: &str| -> Result<u64, StateDBError> {
+                Ok(self.db.property_int_value_cf(cf, property)?.unwrap_or(0))
+            };
+            stats.estimated_keys += property("rocksdb.estimate-num-keys")?;
+            stats.sst_bytes += property("rocksdb.live-sst-files-size")?;
+            stats.memtable_bytes += property("rocksdb.cur-size-all-mem-tables")?;
+        }
+        Ok(stats)
+    }
+    
+    /// Hits and misses of the account read cache, if it is enabled
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.account_cache.as_ref().map(ReadCache::stats)
     }
     
-    /// Get a value from the database
+    /// Get a value from the database, including uncommitted changes
     pub fn get_sync(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
-        self.db.get(key).map_err(StateDBError::RocksDBError)
+        if let Some(value) = self.pending_changes.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        let cf = value_cf(key);
+        let read = || Ok(self.db.get_cf(self.cf(cf)?, key)?);
+        match &self.account_cache {
+            Some(cache) if cf == ACCOUNTS_CF => cache.get_or_read(key, read),
+            _ => read(),
+        }
     }
     
-    /// Put a value into the database
+    /// Stage a value for the next commit
     pub fn put_sync(&mut self, key: &[u8], value: &[u8]) -> Result<(), StateDBError> {
-        self.db.put(key, value)?;
         self.pending_changes.insert(key.to_vec(), value.to_vec());
         Ok(())
     }
+
+    /// Atomically write entries that are kept outside the versioned state
+    pub fn write_batch_sync(&mut self, entries: &[(Vec<u8>, Vec<u8>)]) -> Result<(), StateDBError> {
+        let mut batch = WriteBatch::default();
+        for (key, value) in entries {
+            batch.put_cf(self.cf(value_cf(key))?, key, value);
+            // A direct write supersedes any staged value
+            self.pending_changes.remove(key);
+        }
+        self.db.write(batch)?;
+        if let Some(cache) = &self.account_cache {
+            cache.invalidate(entries.iter().map(|(key, _)| key));
+        }
+        Ok(())
+    }
     
-    /// Commit changes and return Merkle root
+    /// Values written directly under `prefix`, in key order; staged changes are not included
+    pub fn scan_prefix_sync(&self, prefix: &[u8]) -> Result<KeyValues, StateDBError> {
+        let mut entries = Vec::new();
+        let cf = self.cf(value_cf(prefix))?;
+        for entry in self.db.iterator_cf(cf, IteratorMode::From(prefix, Direction::Forward)) {
+            let (key, value) = entry?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    /// Copy the latest values of `source` whose keys this database does not hold, such as
+    /// records written outside the versioned state, returning how many were copied
+    pub fn copy_missing_from(&mut self, source: &RocksStateDB) -> Result<u64, StateDBError> {
+        let mut copied = 0;
+        for name in VALUE_CFS {
+            let (from, to) = (source.cf(name)?, self.cf(name)?);
+            let mut batch = WriteBatch::default();
+            for entry in source.db.iterator_cf(from, IteratorMode::Start) {
+                let (key, value) = entry?;
+                if self.db.get_pinned_cf(to, &key)?.is_none() {
+                    batch.put_cf(to, key, value);
+                    copied += 1;
+                }
+                if batch.len() >= COPY_BATCH {
+                    self.db.write(std::mem::take(&mut batch))?;
+                }
+            }
+            self.db.write(batch)?;
+        }
+        if let Some(cache) = &self.account_cache {
+            cache.clear();
+        }
+        Ok(copied)
+    }
+
+    /// Fail unless `version` was committed and has not been pruned
+    fn check_readable(&self, version: u64) -> Result<(), StateDBError> {
+        if self.pruned_through.is_some_and(|pruned| version <= pruned) {
+            return Err(StateDBError::VersionPruned(version));
+        }
+        if self.latest_version.is_none_or(|latest| version > latest) {
+            return Err(StateDBError::InvalidVersion(format!("Version {} has not been committed", version)));
+        }
+        Ok(())
+    }
+
+    /// Tree root reference stored for `version`, if it was committed
+    fn root_child(&self, version: u64) -> Result<Option<Option<Child>>, StateDBError> {
+        match self.db.get_cf(self.cf(ROOTS_CF)?, version.to_be_bytes())? {
+            Some(bytes) => Ok(Some(Child::decode(&bytes)?.0)),
+            None => Ok(None),
+        }
+    }
+
+    /// State root committed at `version`; roots outlive pruned history
+    pub fn root_at(&self, version: u64) -> Result<Option<MerkleRoot>, StateDBError> {
+        Ok(self
+            .root_child(version)?
+            .map(|root| root.map_or(EMPTY_HASH, |root| root.hash)))
+    }
+
+    /// Read-only view of the state committed at `version`
+    pub fn state_at(&self, version: u64) -> Result<StateView<'_>, StateDBError> {
+        self.check_readable(version)?;
+        StateView::new(self, version)
+    }
+
+    /// Read-only view of the latest committed state
+    pub fn latest_state(&self) -> Result<StateView<'_>, StateDBError> {
+        let version = self
+            .latest_version
+            .ok_or_else(|| StateDBError::InvalidVersion("No version has been committed".to_string()))?;
+        self.state_at(version)
+    }
+
+    /// Prove the value of `key`, or its absence, under the root of `version`
+    pub fn get_proof(&self, key: &[u8], version: u64) -> Result<SparseMerkleProof, StateDBError> {
+        self.state_at(version)?.get_proof(key)
+    }
+
+    /// Get the value `key` had when `version` was committed
+    pub fn get_at_version(&self, key: &[u8], version: u64) -> Result<Option<Vec<u8>>, StateDBError> {
+        self.state_at(version)?.get(key)
+    }
+
+    /// Atomically commit staged changes as `version` and return the Merkle root
     pub fn commit_sync(&mut self, version: u64) -> Result<MerkleRoot, StateDBError> {
-        // Update Merkle trie with pending changes
+        self.commit_inner(version, None, &[])
+    }
+
+    /// Commit staged changes as `version` together with unversioned `entries`, such as
+    /// receipts, so both land in the same atomic batch
+    pub fn commit_with_sync(&mut self, version: u64, entries: &[(Vec<u8>, Vec<u8>)]) -> Result<MerkleRoot, StateDBError> {
+        self.commit_inner(version, None, entries)
+    }
+
+    /// Commit staged changes, writing nothing unless the new root matches `expected_root`
+    fn commit_inner(
+        &mut self,
+        version: u64,
+        expected_root: Option<&MerkleRoot>,
+        entries: &[(Vec<u8>, Vec<u8>)],
+    ) -> Result<MerkleRoot, StateDBError> {
+        if self.latest_version.is_some_and(|latest| version <= latest) {
+            return Err(StateDBError::InvalidVersion(format!(
+                "Version {} is not above the latest version {}",
+                version,
+                self.latest_version.unwrap_or_default()
+            )));
+        }
+
+        // Rewrite the tree paths of the changed keys
+        let mut tree = SparseMerkleTree::new(self, self.root, version);
         for (key, value) in &self.pending_changes {
-            self.merkle_trie.insert(key, value)?;
+            tree.put(key_hash(key), value_hash(value))?;
         }
-        
-        // Get the Merkle root
-        let root = self.merkle_trie.root()?;
-        
+        let root = tree.root_hash();
+        if expected_root.is_some_and(|expected| *expected != root) {
+            return Err(StateDBError::SnapshotError(format!(
+                "Rebuilt state root for version {} does not match the expected root",
+                version
+            )));
+        }
+        let tree = tree.into_batch();
+
+        // Write the values, their history and the tree in one atomic batch
+        let mut batch = WriteBatch::default();
+        let history = self.cf(HISTORY_CF)?;
+        for (key, value) in &self.pending_changes {
+            batch.put_cf(self.cf(value_cf(key))?, key, value);
+            batch.put_cf(history, history_key(key, version), value);
+        }
+        let keys: Vec<&Vec<u8>> = self.pending_changes.keys().collect();
+        batch.put_cf(self.cf(CHANGES_CF)?, version.to_be_bytes(), serde_json::to_vec(&keys)?);
+        batch.put_cf(self.cf(META_CF)?, LATEST_VERSION_KEY, version.to_be_bytes());
+
+        let nodes = self.cf(TREE_CF)?;
+        for (node_key, node) in &tree.new_nodes {
+            batch.put_cf(nodes, node_key.encode(), node.encode());
+        }
+        let stale = self.cf(STALE_CF)?;
+        for node_key in &tree.stale_nodes {
+            let mut stale_key = version.to_be_bytes().to_vec();
+            stale_key.extend_from_slice(&node_key.encode());
+            batch.put_cf(stale, stale_key, []);
+        }
+        let mut root_bytes = Vec::new();
+        Child::encode(tree.root.as_ref(), &mut root_bytes);
+        batch.put_cf(self.cf(ROOTS_CF)?, version.to_be_bytes(), root_bytes);
+        for (key, value) in entries {
+            batch.put_cf(self.cf(value_cf(key))?, key, value);
+        }
+
+        self.db.write(batch)?;
+        self.latest_version = Some(version);
+        self.root = tree.root;
+        if let Some(cache) = &self.account_cache {
+            cache.invalidate(self.pending_changes.keys().chain(entries.iter().map(|(key, _)| key)));
+        }
+
         // Clear pending changes
         self.pending_changes.clear();
-        
+
+        if let StorageMode::Pruned { retention } = self.config.mode {
+            if let Some(horizon) = version.checked_sub(retention) {
+                self.prune_through(horizon)?;
+            }
+        }
+
         Ok(root)
     }
 }
 
+impl NodeReader for RocksStateDB {
+    fn get_node(&self, key: &NodeKey) -> Result<Option<Node>, StateDBError> {
+        match self.db.get_cf(self.cf(TREE_CF)?, key.encode())? {
+            Some(bytes) => Ok(Some(Node::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+}
+
 impl StateDB for RocksStateDB {
     async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         // For now, use sync version. In a real implementation, this would be async
@@ -158,4 +529,116 @@ mod tests {
         let root = db.commit_sync(1).unwrap();
         assert_eq!(root.len(), 32);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pruning_keeps_retention_window() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StateDBConfig {
+            mode: StorageMode::Pruned { retention: 2 },
+            ..Default::default()
+        };
+        let mut db = RocksStateDB::with_config(temp_dir.path(), config).unwrap();
+
+        db.put_sync(b"a", b"1").unwrap();
+        db.put_sync(b"b", b"1").unwrap();
+        db.commit_sync(1).unwrap();
+        db.put_sync(b"a", b"2").unwrap();
+        db.commit_sync(2).unwrap();
+        db.put_sync(b"a", b"3").unwrap();
+        db.commit_sync(3).unwrap();
+        db.put_sync(b"a", b"4").unwrap();
+        db.commit_sync(4).unwrap();
+
+        assert_eq!(db.pruned_through(), Some(2));
+        assert!(matches!(db.get_at_version(b"a", 2), Err(StateDBError::VersionPruned(2))));
+        assert_eq!(db.get_at_version(b"a", 3).unwrap(), Some(b"3".to_vec()));
+        // Values last written before the window are still readable inside it
+        assert_eq!(db.get_at_version(b"b", 3).unwrap(), Some(b"1".to_vec()));
+        assert_eq!(db.get_at_version(b"c", 4).unwrap(), None);
+        assert!(db.commit_sync(4).is_err());
+
+        // Tree nodes still used by the retained versions survive pruning
+        let root = db.root_at(3).unwrap().unwrap();
+        assert!(db.get_proof(b"b", 3).unwrap().verify(&root, b"b", Some(b"1")));
+        assert!(db.get_proof(b"a", 2).is_err());
+        db.compact().unwrap();
+    }
+
+    #[test]
+    fn test_versioned_roots_and_proofs() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+        db.put_sync(b"alice", b"10").unwrap();
+        db.put_sync(b"bob", b"20").unwrap();
+        let first = db.commit_sync(1).unwrap();
+        db.put_sync(b"alice", b"15").unwrap();
+        let second = db.commit_sync(2).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(db.root_at(1).unwrap(), Some(first));
+        assert_eq!(db.root_at(3).unwrap(), None);
+
+        let proof = db.get_proof(b"alice", 1).unwrap();
+        assert!(proof.verify(&first, b"alice", Some(b"10")));
+        assert!(!proof.verify(&second, b"alice", Some(b"10")));
+        assert!(db.get_proof(b"alice", 2).unwrap().verify(&second, b"alice", Some(b"15")));
+        assert!(db.get_proof(b"carol", 2).unwrap().verify(&second, b"carol", None));
+        drop(db);
+
+        // The tree is rebuilt from disk on reopen
+        let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+        db.put_sync(b"carol", b"5").unwrap();
+        let third = db.commit_sync(3).unwrap();
+        assert!(db.get_proof(b"bob", 3).unwrap().verify(&third, b"bob", Some(b"20")));
+    }
+
+    #[test]
+    fn test_storage_mode_is_recorded() {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = StateDBConfig {
+            mode: StorageMode::Archive,
+            ..Default::default()
+        };
+        {
+            let mut db = RocksStateDB::with_config(temp_dir.path(), archive.clone()).unwrap();
+            for version in 1..=20u64 {
+                db.put_sync(b"a", &version.to_be_bytes()).unwrap();
+                db.commit_sync(version).unwrap();
+            }
+            assert_eq!(db.get_at_version(b"a", 1).unwrap(), Some(1u64.to_be_bytes().to_vec()));
+        }
+
+        // Reopening in pruned mode would silently drop the archive
+        assert!(matches!(
+            RocksStateDB::new(temp_dir.path()),
+            Err(StateDBError::ModeMismatch(_))
+        ));
+        let db = RocksStateDB::with_config(temp_dir.path(), archive).unwrap();
+        assert_eq!(db.latest_version(), Some(20));
+        assert_eq!(db.pruned_through(), None);
+    }
+
+    #[test]
+    fn test_staged_changes_and_state_views() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+            db.put_sync(b"a", b"1").unwrap();
+            db.commit_sync(1).unwrap();
+            db.put_sync(b"a", b"2").unwrap();
+            db.write_batch_sync(&[(b"meta".to_vec(), b"x".to_vec())]).unwrap();
+
+            // Staged values are readable but not part of any committed version
+            assert_eq!(db.get_sync(b"a").unwrap(), Some(b"2".to_vec()));
+            let view = db.latest_state().unwrap();
+            assert_eq!(view.version(), 1);
+            assert_eq!(view.get(b"a").unwrap(), Some(b"1".to_vec()));
+            assert!(view.get_proof(b"a").unwrap().verify(&view.root(), b"a", Some(b"1")));
+        }
+
+        // Uncommitted changes are dropped on restart, batched writes are not
+        let db = RocksStateDB::new(temp_dir.path()).unwrap();
+        assert_eq!(db.get_sync(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(db.get_sync(b"meta").unwrap(), Some(b"x".to_vec()));
+        assert!(db.state_at(2).is_err());
+    }
+}
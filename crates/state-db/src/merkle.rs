@@ -1,51 +1,474 @@
 use crate::error::StateDBError;
+use hashing::{Domain, Hasher};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use blake2::{Blake2b, Digest};
 
-/// Simple Merkle trie implementation
-pub struct MerkleTrie {
-    nodes: HashMap<Vec<u8>, Vec<u8>>,
-    root: [u8; 32],
+/// Root of a tree with no leaves
+pub const EMPTY_HASH: [u8; 32] = [0u8; 32];
+
+/// Bits in a hashed key, the maximum depth of the tree
+const KEY_BITS: usize = 256;
+
+const LEAF_DOMAIN: u8 = 0;
+const INTERNAL_DOMAIN: u8 = 1;
+
+const NO_CHILD: u8 = 0;
+const INTERNAL_CHILD: u8 = 1;
+const LEAF_CHILD: u8 = 2;
+
+/// Position of a state key in the tree
+pub fn key_hash(key: &[u8]) -> [u8; 32] {
+    hashing::hash(Domain::StateKey, key)
+}
+
+/// Hash of a state value stored in a leaf
+pub fn value_hash(value: &[u8]) -> [u8; 32] {
+    hashing::hash(Domain::StateValue, value)
+}
+
+fn leaf_hash(key_hash: &[u8; 32], value_hash: &[u8; 32]) -> [u8; 32] {
+    Hasher::new(Domain::StateLeaf).fixed(key_hash).fixed(value_hash).finish()
+}
+
+fn internal_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Hasher::new(Domain::StateNode).fixed(left).fixed(right).finish()
+}
+
+/// Bit of `key_hash` choosing the branch at `depth`, most significant first
+fn bit(key_hash: &[u8; 32], depth: usize) -> usize {
+    ((key_hash[depth / 8] >> (7 - depth % 8)) & 1) as usize
+}
+
+/// Reference from a parent to a child node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Child {
+    pub hash: [u8; 32],
+    /// Version that wrote the child node
+    pub version: u64,
+    pub is_leaf: bool,
+}
+
+impl Child {
+    /// Encode an optional child reference
+    pub fn encode(child: Option<&Child>, out: &mut Vec<u8>) {
+        match child {
+            None => out.push(NO_CHILD),
+            Some(child) => {
+                out.push(if child.is_leaf { LEAF_CHILD } else { INTERNAL_CHILD });
+                out.extend_from_slice(&child.hash);
+                out.extend_from_slice(&child.version.to_be_bytes());
+            }
+        }
+    }
+
+    /// Decode an optional child reference, returning it and the bytes read
+    pub fn decode(bytes: &[u8]) -> Result<(Option<Child>, usize), StateDBError> {
+        let corrupt = || StateDBError::MerkleTrieError("Corrupt child reference".to_string());
+        let is_leaf = match bytes.first() {
+            Some(&NO_CHILD) => return Ok((None, 1)),
+            Some(&INTERNAL_CHILD) => false,
+            Some(&LEAF_CHILD) => true,
+            _ => return Err(corrupt()),
+        };
+        if bytes.len() < 41 {
+            return Err(corrupt());
+        }
+        let child = Child {
+            hash: bytes[1..33].try_into().map_err(|_| corrupt())?,
+            version: u64::from_be_bytes(bytes[33..41].try_into().map_err(|_| corrupt())?),
+            is_leaf,
+        };
+        Ok((Some(child), 41))
+    }
+}
+
+/// A stored tree node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Leaf { key_hash: [u8; 32], value_hash: [u8; 32] },
+    /// Children for bit 0 and bit 1; a missing child is an empty subtree
+    Internal { children: [Option<Child>; 2] },
+}
+
+impl Node {
+    pub fn hash(&self) -> [u8; 32] {
+        match self {
+            Node::Leaf { key_hash, value_hash } => leaf_hash(key_hash, value_hash),
+            Node::Internal { children } => {
+                let [left, right] = children.map(|child| child.map_or(EMPTY_HASH, |child| child.hash));
+                internal_hash(&left, &right)
+            }
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Node::Leaf { key_hash, value_hash } => {
+                let mut bytes = vec![LEAF_DOMAIN];
+                bytes.extend_from_slice(key_hash);
+                bytes.extend_from_slice(value_hash);
+                bytes
+            }
+            Node::Internal { children } => {
+                let mut bytes = vec![INTERNAL_DOMAIN];
+                for child in children {
+                    Child::encode(child.as_ref(), &mut bytes);
+                }
+                bytes
+            }
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, StateDBError> {
+        let corrupt = || StateDBError::MerkleTrieError("Corrupt tree node".to_string());
+        match bytes.first() {
+            Some(&LEAF_DOMAIN) if bytes.len() == 65 => Ok(Node::Leaf {
+                key_hash: bytes[1..33].try_into().map_err(|_| corrupt())?,
+                value_hash: bytes[33..65].try_into().map_err(|_| corrupt())?,
+            }),
+            Some(&INTERNAL_DOMAIN) => {
+                let (left, read) = Child::decode(&bytes[1..])?;
+                let (right, _) = Child::decode(&bytes[1 + read..])?;
+                Ok(Node::Internal { children: [left, right] })
+            }
+            _ => Err(corrupt()),
+        }
+    }
+}
+
+/// Storage key of a node: the version that wrote it and its position in the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeKey {
+    pub version: u64,
+    pub depth: u16,
+    /// First `depth` bits of the keys below the node, the rest zeroed
+    pub path: [u8; 32],
+}
+
+impl NodeKey {
+    /// Key of the node written at `version` on the path to `key_hash` at `depth`
+    pub fn new(version: u64, depth: usize, key_hash: &[u8; 32]) -> Self {
+        let mut path = [0u8; 32];
+        for index in 0..depth {
+            path[index / 8] |= (bit(key_hash, index) as u8) << (7 - index % 8);
+        }
+        Self {
+            version,
+            depth: depth as u16,
+            path,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(42);
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(&self.depth.to_be_bytes());
+        bytes.extend_from_slice(&self.path);
+        bytes
+    }
+}
+
+/// Source of persisted tree nodes
+pub trait NodeReader {
+    fn get_node(&self, key: &NodeKey) -> Result<Option<Node>, StateDBError>;
+}
+
+impl NodeReader for HashMap<NodeKey, Node> {
+    fn get_node(&self, key: &NodeKey) -> Result<Option<Node>, StateDBError> {
+        Ok(self.get(key).cloned())
+    }
+}
+
+fn load<R: NodeReader>(reader: &R, key: &NodeKey) -> Result<Node, StateDBError> {
+    reader.get_node(key)?.ok_or_else(|| {
+        StateDBError::MerkleTrieError(format!(
+            "Missing tree node written at version {} depth {}",
+            key.version, key.depth
+        ))
+    })
+}
+
+/// Nodes written and replaced by one version of the tree
+#[derive(Debug, Default)]
+pub struct TreeBatch {
+    pub root: Option<Child>,
+    pub new_nodes: HashMap<NodeKey, Node>,
+    /// Nodes from earlier versions that the new version no longer uses
+    pub stale_nodes: Vec<NodeKey>,
 }
 
-impl MerkleTrie {
-    pub fn new() -> Self {
+/// Sparse Merkle tree over 256-bit key hashes.
+///
+/// A subtree holding a single leaf is stored as that leaf, so only the paths
+/// touched by a version are rewritten and earlier versions stay readable.
+pub struct SparseMerkleTree<'a, R> {
+    reader: &'a R,
+    version: u64,
+    batch: TreeBatch,
+}
+
+impl<'a, R: NodeReader> SparseMerkleTree<'a, R> {
+    /// Start building `version` on top of the tree rooted at `root`
+    pub fn new(reader: &'a R, root: Option<Child>, version: u64) -> Self {
         Self {
-            nodes: HashMap::new(),
-            root: [0u8; 32],
+            reader,
+            version,
+            batch: TreeBatch {
+                root,
+                ..Default::default()
+            },
         }
     }
-    
-    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), StateDBError> {
-        self.nodes.insert(key.to_vec(), value.to_vec());
-        self.update_root();
+
+    /// Set the value hash stored under `key_hash`
+    pub fn put(&mut self, key_hash: [u8; 32], value_hash: [u8; 32]) -> Result<(), StateDBError> {
+        let root = self.batch.root;
+        self.batch.root = Some(self.insert(root, 0, &key_hash, &value_hash)?);
         Ok(())
     }
-    
-    pub fn get(&self, key: &[u8]) -> Option<&Vec<u8>> {
-        self.nodes.get(key)
+
+    /// Root hash of the tree built so far
+    pub fn root_hash(&self) -> [u8; 32] {
+        self.batch.root.map_or(EMPTY_HASH, |root| root.hash)
     }
-    
-    pub fn root(&self) -> Result<[u8; 32], StateDBError> {
-        Ok(self.root)
+
+    /// Finish the version, returning the nodes to persist
+    pub fn into_batch(self) -> TreeBatch {
+        self.batch
+    }
+
+    fn load(&self, key: &NodeKey) -> Result<Node, StateDBError> {
+        match self.batch.new_nodes.get(key) {
+            Some(node) => Ok(node.clone()),
+            None => load(self.reader, key),
+        }
     }
-    
-    fn update_root(&mut self) {
-        // Simple hash of all key-value pairs
-        // In a real implementation, this would be a proper Merkle tree
-        let mut hasher = blake2::Blake2b::new();
-        for (key, value) in &self.nodes {
-            hasher.update(key);
-            hasher.update(value);
+
+    /// Drop a node that is being replaced
+    fn retire(&mut self, key: NodeKey) {
+        if key.version == self.version {
+            self.batch.new_nodes.remove(&key);
+        } else {
+            self.batch.stale_nodes.push(key);
         }
-        
-        let result: [u8; 64] = hasher.finalize().into();
-        self.root.copy_from_slice(&result[..32]);
+    }
+
+    fn store(&mut self, depth: usize, key_hash: &[u8; 32], node: Node) -> Child {
+        let child = Child {
+            hash: node.hash(),
+            version: self.version,
+            is_leaf: matches!(node, Node::Leaf { .. }),
+        };
+        self.batch
+            .new_nodes
+            .insert(NodeKey::new(self.version, depth, key_hash), node);
+        child
+    }
+
+    fn insert(
+        &mut self,
+        current: Option<Child>,
+        depth: usize,
+        key_hash: &[u8; 32],
+        value_hash: &[u8; 32],
+    ) -> Result<Child, StateDBError> {
+        let leaf = Node::Leaf {
+            key_hash: *key_hash,
+            value_hash: *value_hash,
+        };
+        let current = match current {
+            Some(current) => current,
+            None => return Ok(self.store(depth, key_hash, leaf)),
+        };
+
+        let node_key = NodeKey::new(current.version, depth, key_hash);
+        let node = self.load(&node_key)?;
+        self.retire(node_key);
+        match node {
+            Node::Leaf {
+                key_hash: existing_key,
+                value_hash: existing_value,
+            } => {
+                if existing_key == *key_hash {
+                    Ok(self.store(depth, key_hash, leaf))
+                } else {
+                    self.split(depth, (existing_key, existing_value), (*key_hash, *value_hash))
+                }
+            }
+            Node::Internal { mut children } => {
+                let branch = bit(key_hash, depth);
+                children[branch] = Some(self.insert(children[branch], depth + 1, key_hash, value_hash)?);
+                Ok(self.store(depth, key_hash, Node::Internal { children }))
+            }
+        }
+    }
+
+    /// Push two leaves down until their key hashes diverge
+    fn split(
+        &mut self,
+        depth: usize,
+        first: ([u8; 32], [u8; 32]),
+        second: ([u8; 32], [u8; 32]),
+    ) -> Result<Child, StateDBError> {
+        if depth >= KEY_BITS {
+            return Err(StateDBError::MerkleTrieError("Key hashes collide".to_string()));
+        }
+        let mut children = [None, None];
+        let (first_bit, second_bit) = (bit(&first.0, depth), bit(&second.0, depth));
+        if first_bit == second_bit {
+            children[first_bit] = Some(self.split(depth + 1, first, second)?);
+        } else {
+            for (branch, (key_hash, value_hash)) in [(first_bit, first), (second_bit, second)] {
+                children[branch] = Some(self.store(depth + 1, &key_hash, Node::Leaf { key_hash, value_hash }));
+            }
+        }
+        Ok(self.store(depth, &second.0, Node::Internal { children }))
     }
 }
 
-impl Default for MerkleTrie {
-    fn default() -> Self {
-        Self::new()
+/// Leaf found at the end of a proof path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofLeaf {
+    pub key_hash: [u8; 32],
+    pub value_hash: [u8; 32],
+}
+
+/// Proof that a key holds a value, or holds nothing, under a state root
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleProof {
+    /// Leaf where the search for the key ended, if any
+    pub leaf: Option<ProofLeaf>,
+    /// Sibling hashes from the root down
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl SparseMerkleProof {
+    /// Build a proof for `key_hash` in the tree rooted at `root`
+    pub fn generate<R: NodeReader>(
+        reader: &R,
+        root: Option<Child>,
+        key_hash: &[u8; 32],
+    ) -> Result<Self, StateDBError> {
+        let mut siblings = Vec::new();
+        let mut current = root;
+        while let Some(child) = current {
+            let depth = siblings.len();
+            match load(reader, &NodeKey::new(child.version, depth, key_hash))? {
+                Node::Leaf { key_hash, value_hash } => {
+                    return Ok(Self {
+                        leaf: Some(ProofLeaf { key_hash, value_hash }),
+                        siblings,
+                    });
+                }
+                Node::Internal { children } => {
+                    let branch = bit(key_hash, depth);
+                    siblings.push(children[1 - branch].map_or(EMPTY_HASH, |sibling| sibling.hash));
+                    current = children[branch];
+                }
+            }
+        }
+        Ok(Self { leaf: None, siblings })
+    }
+
+    /// Check that `key` holds `value` under `root`, where `None` proves the key is absent
+    pub fn verify(&self, root: &[u8; 32], key: &[u8], value: Option<&[u8]>) -> bool {
+        let key_hash = key_hash(key);
+        let depth = self.siblings.len();
+        if depth > KEY_BITS {
+            return false;
+        }
+
+        let mut hash = match (&self.leaf, value) {
+            (Some(leaf), Some(value)) => {
+                if leaf.key_hash != key_hash || leaf.value_hash != value_hash(value) {
+                    return false;
+                }
+                leaf_hash(&leaf.key_hash, &leaf.value_hash)
+            }
+            (Some(leaf), None) => {
+                // Another key's leaf must occupy the position this key would take
+                if leaf.key_hash == key_hash || (0..depth).any(|index| bit(&leaf.key_hash, index) != bit(&key_hash, index)) {
+                    return false;
+                }
+                leaf_hash(&leaf.key_hash, &leaf.value_hash)
+            }
+            (None, Some(_)) => return false,
+            (None, None) => EMPTY_HASH,
+        };
+
+        for (index, sibling) in self.siblings.iter().enumerate().rev() {
+            hash = if bit(&key_hash, index) == 0 {
+                internal_hash(&hash, sibling)
+            } else {
+                internal_hash(sibling, &hash)
+            };
+        }
+        hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(
+        store: &mut HashMap<NodeKey, Node>,
+        root: Option<Child>,
+        version: u64,
+        entries: &[(&[u8], &[u8])],
+    ) -> TreeBatch {
+        let mut tree = SparseMerkleTree::new(&*store, root, version);
+        for (key, value) in entries {
+            tree.put(key_hash(key), value_hash(value)).unwrap();
+        }
+        let batch = tree.into_batch();
+        store.extend(batch.new_nodes.clone());
+        batch
+    }
+
+    #[test]
+    fn test_root_is_independent_of_insert_order() {
+        let entries: Vec<(&[u8], &[u8])> = vec![(b"a", b"1"), (b"b", b"2"), (b"c", b"3"), (b"d", b"4")];
+        let mut reversed = entries.clone();
+        reversed.reverse();
+
+        let forward = build(&mut HashMap::new(), None, 1, &entries);
+        let backward = build(&mut HashMap::new(), None, 1, &reversed);
+        assert_eq!(forward.root, backward.root);
+        assert_ne!(forward.root.unwrap().hash, EMPTY_HASH);
+
+        // Building across versions reaches the same root hash
+        let mut store = HashMap::new();
+        let first = build(&mut store, None, 1, &entries[..2]);
+        let second = build(&mut store, first.root, 2, &entries[2..]);
+        assert_eq!(second.root.unwrap().hash, forward.root.unwrap().hash);
+    }
+
+    #[test]
+    fn test_proofs_and_versions() {
+        let mut store = HashMap::new();
+        let first = build(&mut store, None, 1, &[(b"a", b"1"), (b"b", b"2"), (b"c", b"3")]);
+        let second = build(&mut store, first.root, 2, &[(b"a", b"9")]);
+        // Only the path to the updated leaf is rewritten
+        assert!(!second.stale_nodes.is_empty());
+        assert!(second.new_nodes.len() <= second.stale_nodes.len());
+
+        let old_root = first.root.unwrap().hash;
+        let new_root = second.root.unwrap().hash;
+        let proof = SparseMerkleProof::generate(&store, first.root, &key_hash(b"a")).unwrap();
+        assert!(proof.verify(&old_root, b"a", Some(b"1")));
+        assert!(!proof.verify(&old_root, b"a", Some(b"9")));
+        assert!(!proof.verify(&new_root, b"a", Some(b"1")));
+
+        let proof = SparseMerkleProof::generate(&store, second.root, &key_hash(b"a")).unwrap();
+        assert!(proof.verify(&new_root, b"a", Some(b"9")));
+
+        let proof = SparseMerkleProof::generate(&store, second.root, &key_hash(b"missing")).unwrap();
+        assert!(proof.verify(&new_root, b"missing", None));
+        assert!(!proof.verify(&new_root, b"missing", Some(b"1")));
+        assert!(!proof.verify(&new_root, b"a", None));
+
+        let empty = SparseMerkleProof::generate(&store, None, &key_hash(b"a")).unwrap();
+        assert!(empty.verify(&EMPTY_HASH, b"a", None));
     }
-}
\ No newline at end of file
+}
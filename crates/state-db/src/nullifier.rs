@@ -0,0 +1,110 @@
+use crate::error::StateDBError;
+use crate::merkle::EMPTY_HASH;
+use crate::{MerkleRoot, RocksStateDB};
+use hashing::{Domain, Hasher};
+use std::collections::HashSet;
+
+const NULLIFIER_PREFIX: &[u8] = b"nullifier/";
+/// State key of the spent-nullifier set root
+pub const NULLIFIER_ROOT_KEY: &[u8] = b"nullifier_root";
+
+/// State key recording that `nullifier` was spent
+pub fn nullifier_key(nullifier: &[u8; 32]) -> Vec<u8> {
+    [NULLIFIER_PREFIX, nullifier.as_slice()].concat()
+}
+
+/// Fold spent nullifiers, in block order, into the nullifier-set root
+pub fn accumulate_nullifiers(root: &MerkleRoot, nullifiers: &[[u8; 32]]) -> MerkleRoot {
+    nullifiers
+        .iter()
+        .fold(*root, |root, nullifier| Hasher::new(Domain::NullifierRoot).fixed(&root).fixed(nullifier).finish())
+}
+
+impl RocksStateDB {
+    /// Height at which `nullifier` was spent, if it was
+    pub fn nullifier_height(&self, nullifier: &[u8; 32]) -> Result<Option<u64>, StateDBError> {
+        match self.get_sync(&nullifier_key(nullifier))? {
+            Some(bytes) => {
+                let height = <[u8; 8]>::try_from(bytes.as_slice())
+                    .map_err(|_| StateDBError::MerkleTrieError("Malformed nullifier entry".to_string()))?;
+                Ok(Some(u64::from_be_bytes(height)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Root of the spent-nullifier set, including staged spends
+    pub fn nullifier_root(&self) -> Result<MerkleRoot, StateDBError> {
+        match self.get_sync(NULLIFIER_ROOT_KEY)? {
+            Some(bytes) => <[u8; 32]>::try_from(bytes.as_slice())
+                .map_err(|_| StateDBError::MerkleTrieError("Malformed nullifier root".to_string())),
+            None => Ok(EMPTY_HASH),
+        }
+    }
+
+    /// Stage the nullifiers spent at `height` for the next commit and return the new root.
+    ///
+    /// Fails without staging anything if a nullifier was already spent or repeats.
+    pub fn spend_nullifiers(&mut self, height: u64, nullifiers: &[[u8; 32]]) -> Result<MerkleRoot, StateDBError> {
+        let mut seen = HashSet::with_capacity(nullifiers.len());
+        for nullifier in nullifiers {
+            if !seen.insert(nullifier) {
+                return Err(StateDBError::NullifierSpent(format!("{} repeats", hex::encode(nullifier))));
+            }
+            if let Some(spent_at) = self.nullifier_height(nullifier)? {
+                return Err(StateDBError::NullifierSpent(format!(
+                    "{} was spent at height {}",
+                    hex::encode(nullifier),
+                    spent_at
+                )));
+            }
+        }
+
+        let root = accumulate_nullifiers(&self.nullifier_root()?, nullifiers);
+        if nullifiers.is_empty() {
+            return Ok(root);
+        }
+        for nullifier in nullifiers {
+            self.put_sync(&nullifier_key(nullifier), &height.to_be_bytes())?;
+        }
+        self.put_sync(NULLIFIER_ROOT_KEY, &root)?;
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_spent_nullifiers_persist_and_reject_reuse() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut state = RocksStateDB::new(temp_dir.path()).unwrap();
+        assert_eq!(state.nullifier_root().unwrap(), EMPTY_HASH);
+
+        let root = state.spend_nullifiers(1, &[[1u8; 32], [2u8; 32]]).unwrap();
+        assert_eq!(root, accumulate_nullifiers(&EMPTY_HASH, &[[1u8; 32], [2u8; 32]]));
+        let state_root = state.commit_sync(1).unwrap();
+        assert_eq!(state.nullifier_height(&[2u8; 32]).unwrap(), Some(1));
+        assert_eq!(state.nullifier_height(&[3u8; 32]).unwrap(), None);
+
+        // Spent nullifiers are part of the committed state
+        let proof = state.get_proof(&nullifier_key(&[1u8; 32]), 1).unwrap();
+        assert!(proof.verify(&state_root, &nullifier_key(&[1u8; 32]), Some(&1u64.to_be_bytes())));
+
+        assert!(matches!(
+            state.spend_nullifiers(2, &[[3u8; 32], [1u8; 32]]),
+            Err(StateDBError::NullifierSpent(_))
+        ));
+        assert!(matches!(
+            state.spend_nullifiers(2, &[[4u8; 32], [4u8; 32]]),
+            Err(StateDBError::NullifierSpent(_))
+        ));
+        assert_eq!(state.nullifier_height(&[3u8; 32]).unwrap(), None);
+        assert_eq!(state.nullifier_root().unwrap(), root);
+
+        // An empty block leaves the root unchanged
+        assert_eq!(state.spend_nullifiers(2, &[]).unwrap(), root);
+    }
+}
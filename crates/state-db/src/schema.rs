@@ -0,0 +1,208 @@
+//! Storage layout of the latest value of each key, and the migrations that bring older data
+//! directories up to it. Values are split into column families by the kind of record their
+//! key names; the schema version in the meta column family says which layout a directory
+//! uses, and every migration above it runs when the database is opened.
+
+use crate::error::StateDBError;
+use crate::{LATEST_VERSION_KEY, META_CF, MODE_KEY, TREE_CF};
+use rocksdb::{IteratorMode, WriteBatch, DB};
+
+/// Column family holding accounts, contract code and contract storage
+pub(crate) const ACCOUNTS_CF: &str = "accounts";
+/// Column family holding block headers and the finalized head
+pub(crate) const BLOCKS_CF: &str = "blocks";
+/// Column family holding receipts and the per-block log, output and spend indexes
+pub(crate) const RECEIPTS_CF: &str = "receipts";
+/// Column family holding the note commitment tree and stealth outputs
+pub(crate) const COMMITMENTS_CF: &str = "commitments";
+/// Column family holding spent nullifiers and their root
+pub(crate) const NULLIFIERS_CF: &str = "nullifiers";
+/// Column family for every other record: bridge, staking, rewards and supply bookkeeping
+pub(crate) const METADATA_CF: &str = "metadata";
+
+/// Column families holding latest values
+pub(crate) const VALUE_CFS: [&str; 6] =
+    [ACCOUNTS_CF, BLOCKS_CF, RECEIPTS_CF, COMMITMENTS_CF, NULLIFIERS_CF, METADATA_CF];
+
+/// Layout this build reads and writes
+pub const SCHEMA_VERSION: u32 = 3;
+
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Entries moved per write batch, so a migration interrupted part way resumes where it stopped
+const MIGRATION_BATCH: usize = 10_000;
+
+/// Key prefixes of each column family; keys matching none belong in `METADATA_CF`
+const ROUTES: &[(&[u8], &str)] = &[
+    (b"account/", ACCOUNTS_CF),
+    (b"code/", ACCOUNTS_CF),
+    (b"storage/", ACCOUNTS_CF),
+    (b"header/", BLOCKS_CF),
+    (b"finality/", BLOCKS_CF),
+    (b"receipt/", RECEIPTS_CF),
+    (b"logs/", RECEIPTS_CF),
+    (b"stealth_outputs/", RECEIPTS_CF),
+    (b"ring_spends/", RECEIPTS_CF),
+    (b"note_tree/", COMMITMENTS_CF),
+    (b"stealth_output/", COMMITMENTS_CF),
+    (b"nullifier/", NULLIFIERS_CF),
+    (b"nullifier_root", NULLIFIERS_CF),
+];
+
+/// Column family holding the latest value of `key`
+pub(crate) fn value_cf(key: &[u8]) -> &'static str {
+    ROUTES
+        .iter()
+        .find(|(prefix, _)| key.starts_with(prefix))
+        .map_or(METADATA_CF, |(_, cf)| cf)
+}
+
+/// An upgrade of the layout from schema version `from` to the next
+struct Migration {
+    from: u32,
+    description: &'static str,
+    /// Moves the data, returning how many entries changed
+    run: fn(&DB) -> Result<u64, StateDBError>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "split latest values out of the default column family",
+        run: split_default_cf,
+    },
+    Migration {
+        from: 2,
+        description: "hash state tree nodes under domain tags",
+        run: refuse_untagged_tree,
+    },
+];
+
+/// Schema version 1 kept every latest value in the default column family
+fn split_default_cf(db: &DB) -> Result<u64, StateDBError> {
+    let mut moved = 0;
+    loop {
+        let mut batch = WriteBatch::default();
+        let mut entries = 0;
+        for entry in db.iterator(IteratorMode::Start).take(MIGRATION_BATCH) {
+            let (key, value) = entry?;
+            batch.put_cf(cf(db, value_cf(&key))?, &key, value);
+            batch.delete(key);
+            entries += 1;
+        }
+        if entries == 0 {
+            return Ok(moved);
+        }
+        db.write(batch)?;
+        moved += entries;
+    }
+}
+
+/// Schema version 2 hashed tree nodes without domain tags. The nodes cannot be rehashed in
+/// place without the history of every version, so a directory holding any must be resynced.
+fn refuse_untagged_tree(db: &DB) -> Result<u64, StateDBError> {
+    if db.iterator_cf(cf(db, TREE_CF)?, IteratorMode::Start).next().is_some() {
+        return Err(StateDBError::SchemaError(
+            "State tree was hashed without domain tags; resync from genesis or a snapshot taken by this build"
+                .to_string(),
+        ));
+    }
+    Ok(0)
+}
+
+fn cf<'a>(db: &'a DB, name: &str) -> Result<&'a rocksdb::ColumnFamily, StateDBError> {
+    db.cf_handle(name)
+        .ok_or_else(|| StateDBError::ConfigError(format!("Missing column family {}", name)))
+}
+
+/// Bring the layout of `db` up to `SCHEMA_VERSION`, recording the version of a new database
+pub(crate) fn migrate(db: &DB) -> Result<(), StateDBError> {
+    let meta = cf(db, META_CF)?;
+    let stored = match db.get_cf(meta, SCHEMA_VERSION_KEY)? {
+        Some(bytes) => {
+            let bytes = bytes
+                .try_into()
+                .map_err(|_| StateDBError::SchemaError("Malformed schema version record".to_string()))?;
+            u32::from_be_bytes(bytes)
+        }
+        // Databases from before the version record have bookkeeping or values but no version
+        None if db.get_cf(meta, MODE_KEY)?.is_some()
+            || db.get_cf(meta, LATEST_VERSION_KEY)?.is_some()
+            || db.iterator(IteratorMode::Start).next().is_some() =>
+        {
+            1
+        }
+        None => SCHEMA_VERSION,
+    };
+    if stored > SCHEMA_VERSION {
+        return Err(StateDBError::SchemaError(format!(
+            "Database uses schema version {} but this build supports up to {}",
+            stored, SCHEMA_VERSION
+        )));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from >= stored) {
+        let moved = (migration.run)(db)?;
+        db.put_cf(meta, SCHEMA_VERSION_KEY, (migration.from + 1).to_be_bytes())?;
+        println!(
+            "Migrated state database schema from version {} to {}: {} ({} entries)",
+            migration.from,
+            migration.from + 1,
+            migration.description,
+            moved
+        );
+    }
+    db.put_cf(meta, SCHEMA_VERSION_KEY, SCHEMA_VERSION.to_be_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RocksStateDB;
+    use rocksdb::Options;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_version_one_directories_are_split_into_column_families() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            // Lay the directory out the way schema version 1 did
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            opts.create_missing_column_families(true);
+            let db = DB::open_cf(&opts, temp_dir.path(), [META_CF]).unwrap();
+            db.put(b"account/alice", b"10").unwrap();
+            db.put(b"receipt/01", b"r").unwrap();
+            db.put(b"bridge/deposit_cursor", b"7").unwrap();
+            db.put_cf(db.cf_handle(META_CF).unwrap(), LATEST_VERSION_KEY, 3u64.to_be_bytes()).unwrap();
+        }
+
+        let db = RocksStateDB::new(temp_dir.path()).unwrap();
+        assert_eq!(db.get_sync(b"account/alice").unwrap(), Some(b"10".to_vec()));
+        assert_eq!(db.get_sync(b"receipt/01").unwrap(), Some(b"r".to_vec()));
+        assert_eq!(db.get_sync(b"bridge/deposit_cursor").unwrap(), Some(b"7".to_vec()));
+        assert!(db.db.iterator(IteratorMode::Start).next().is_none());
+        let receipts = db.db.cf_handle(RECEIPTS_CF).unwrap();
+        assert_eq!(db.db.get_cf(receipts, b"receipt/01").unwrap(), Some(b"r".to_vec()));
+        assert_eq!(db.latest_version(), Some(3));
+
+        // A directory written by a newer build is refused rather than misread
+        let meta = db.db.cf_handle(META_CF).unwrap();
+        db.db.put_cf(meta, SCHEMA_VERSION_KEY, (SCHEMA_VERSION + 1).to_be_bytes()).unwrap();
+        drop(db);
+        assert!(matches!(RocksStateDB::new(temp_dir.path()), Err(StateDBError::SchemaError(_))));
+    }
+
+    #[test]
+    fn test_version_two_trees_must_be_resynced() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut db = RocksStateDB::new(temp_dir.path()).unwrap();
+        db.put_sync(b"account/alice", b"10").unwrap();
+        db.commit_sync(1).unwrap();
+        let meta = db.db.cf_handle(META_CF).unwrap();
+        db.db.put_cf(meta, SCHEMA_VERSION_KEY, 2u32.to_be_bytes()).unwrap();
+        drop(db);
+        assert!(matches!(RocksStateDB::new(temp_dir.path()), Err(StateDBError::SchemaError(_))));
+    }
+}
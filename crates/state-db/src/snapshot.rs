@@ -0,0 +1,265 @@
+use crate::error::StateDBError;
+use crate::{MerkleRoot, RocksStateDB};
+use hashing::Domain;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// State keys and values held by one chunk
+type ChunkEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Layout version of snapshot archives
+const SNAPSHOT_FORMAT: u32 = 2;
+
+/// One file of a snapshot archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    pub index: u32,
+    pub entries: u64,
+    /// Hash of the chunk file
+    pub checksum: [u8; 32],
+}
+
+/// Description of a snapshot archive, stored next to its chunks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub format: u32,
+    /// State version the snapshot was taken at
+    pub version: u64,
+    /// State root the imported entries must rebuild
+    pub root: MerkleRoot,
+    pub entries: u64,
+    pub chunks: Vec<SnapshotChunk>,
+}
+
+impl SnapshotManifest {
+    /// Read the manifest of the archive in `dir`
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, StateDBError> {
+        let manifest: SnapshotManifest = serde_json::from_slice(&fs::read(dir.as_ref().join(MANIFEST_FILE))?)?;
+        if manifest.format != SNAPSHOT_FORMAT {
+            return Err(StateDBError::SnapshotError(format!(
+                "Unsupported snapshot format {}",
+                manifest.format
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+fn chunk_file(index: u32) -> String {
+    format!("chunk-{:05}.bin", index)
+}
+
+/// Chunks are a sequence of length-prefixed keys and values
+fn encode_entry(chunk: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    for bytes in [key, value] {
+        chunk.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        chunk.extend_from_slice(bytes);
+    }
+}
+
+fn take_field(rest: &mut &[u8]) -> Result<Vec<u8>, StateDBError> {
+    let truncated = || StateDBError::SnapshotError("Truncated snapshot chunk".to_string());
+    if rest.len() < 4 {
+        return Err(truncated());
+    }
+    let (len, tail) = rest.split_at(4);
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    if tail.len() < len {
+        return Err(truncated());
+    }
+    let (field, tail) = tail.split_at(len);
+    *rest = tail;
+    Ok(field.to_vec())
+}
+
+fn decode_chunk(mut bytes: &[u8]) -> Result<ChunkEntries, StateDBError> {
+    let mut entries = Vec::new();
+    while !bytes.is_empty() {
+        let key = take_field(&mut bytes)?;
+        entries.push((key, take_field(&mut bytes)?));
+    }
+    Ok(entries)
+}
+
+fn write_chunk(dir: &Path, manifest: &mut SnapshotManifest, chunk: &mut Vec<u8>, entries: &mut u64) -> Result<(), StateDBError> {
+    let index = manifest.chunks.len() as u32;
+    fs::write(dir.join(chunk_file(index)), &chunk)?;
+    manifest.chunks.push(SnapshotChunk {
+        index,
+        entries: *entries,
+        checksum: hashing::hash(Domain::SnapshotChunk, chunk),
+    });
+    manifest.entries += *entries;
+    chunk.clear();
+    *entries = 0;
+    Ok(())
+}
+
+impl RocksStateDB {
+    /// Export the state committed at `version` into a chunked, checksummed archive in `dir`
+    pub fn export_snapshot<P: AsRef<Path>>(&self, version: u64, dir: P) -> Result<SnapshotManifest, StateDBError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let view = self.state_at(version)?;
+        let chunk_entries = self.config.snapshot_chunk_entries.max(1) as u64;
+
+        let mut manifest = SnapshotManifest {
+            format: SNAPSHOT_FORMAT,
+            version,
+            root: view.root(),
+            entries: 0,
+            chunks: Vec::new(),
+        };
+        let mut chunk = Vec::new();
+        let mut entries = 0;
+        view.for_each_entry(|key, value| {
+            encode_entry(&mut chunk, key, value);
+            entries += 1;
+            if entries == chunk_entries {
+                write_chunk(dir, &mut manifest, &mut chunk, &mut entries)?;
+            }
+            Ok(())
+        })?;
+        if entries > 0 {
+            write_chunk(dir, &mut manifest, &mut chunk, &mut entries)?;
+        }
+
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+        println!(
+            "Exported state snapshot at version {} ({} entries in {} chunks)",
+            version,
+            manifest.entries,
+            manifest.chunks.len()
+        );
+        Ok(manifest)
+    }
+
+    /// Import the archive in `dir` into an empty database.
+    ///
+    /// Every chunk is checked against its checksum and the rebuilt state root
+    /// against the manifest, and `expected_root` when given, before anything is written.
+    pub fn import_snapshot<P: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        expected_root: Option<MerkleRoot>,
+    ) -> Result<SnapshotManifest, StateDBError> {
+        if self.latest_version.is_some() || !self.pending_changes.is_empty() {
+            return Err(StateDBError::SnapshotError(
+                "Snapshots can only be imported into an empty database".to_string(),
+            ));
+        }
+        let dir = dir.as_ref();
+        let manifest = SnapshotManifest::load(dir)?;
+        if expected_root.is_some_and(|root| root != manifest.root) {
+            return Err(StateDBError::SnapshotError(
+                "Snapshot root does not match the trusted root".to_string(),
+            ));
+        }
+
+        let mut changes = HashMap::new();
+        for chunk in &manifest.chunks {
+            let bytes = fs::read(dir.join(chunk_file(chunk.index)))?;
+            if hashing::hash(Domain::SnapshotChunk, &bytes) != chunk.checksum {
+                return Err(StateDBError::SnapshotError(format!("Chunk {} is corrupt", chunk.index)));
+            }
+            let entries = decode_chunk(&bytes)?;
+            if entries.len() as u64 != chunk.entries {
+                return Err(StateDBError::SnapshotError(format!(
+                    "Chunk {} holds {} entries, expected {}",
+                    chunk.index,
+                    entries.len(),
+                    chunk.entries
+                )));
+            }
+            changes.extend(entries);
+        }
+        if changes.len() as u64 != manifest.entries {
+            return Err(StateDBError::SnapshotError("Snapshot entry count does not match".to_string()));
+        }
+
+        self.pending_changes = changes;
+        if let Err(e) = self.commit_inner(manifest.version, Some(&manifest.root), &[]) {
+            self.pending_changes.clear();
+            return Err(e);
+        }
+        println!(
+            "Imported state snapshot at version {} ({} entries)",
+            manifest.version, manifest.entries
+        );
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StateDBConfig;
+    use tempfile::TempDir;
+
+    fn source_db(path: &Path) -> RocksStateDB {
+        let config = StateDBConfig {
+            snapshot_chunk_entries: 2,
+            ..Default::default()
+        };
+        let mut db = RocksStateDB::with_config(path, config).unwrap();
+        for (key, value) in [(b"a", b"1"), (b"b", b"2"), (b"c", b"3")] {
+            db.put_sync(key, value).unwrap();
+        }
+        db.commit_sync(1).unwrap();
+        db.put_sync(b"a", b"4").unwrap();
+        db.put_sync(b"d", b"5").unwrap();
+        db.commit_sync(2).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let source_dir = TempDir::new().unwrap();
+        let archive = TempDir::new().unwrap();
+        let target_dir = TempDir::new().unwrap();
+        let source = source_db(source_dir.path());
+
+        let manifest = source.export_snapshot(1, archive.path()).unwrap();
+        assert_eq!(manifest.entries, 3);
+        assert_eq!(manifest.chunks.len(), 2);
+        assert_eq!(Some(manifest.root), source.root_at(1).unwrap());
+
+        let mut target = RocksStateDB::new(target_dir.path()).unwrap();
+        assert!(target.import_snapshot(archive.path(), Some([1u8; 32])).is_err());
+        target.import_snapshot(archive.path(), Some(manifest.root)).unwrap();
+        assert_eq!(target.latest_version(), Some(1));
+        assert_eq!(target.root_at(1).unwrap(), Some(manifest.root));
+        assert_eq!(target.get_sync(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(target.get_sync(b"d").unwrap(), None);
+
+        // Only empty databases accept a snapshot
+        assert!(target.import_snapshot(archive.path(), None).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_is_rejected() {
+        let source_dir = TempDir::new().unwrap();
+        let archive = TempDir::new().unwrap();
+        let target_dir = TempDir::new().unwrap();
+        let manifest = source_db(source_dir.path()).export_snapshot(2, archive.path()).unwrap();
+        assert_eq!(manifest.entries, 4);
+
+        let path = archive.path().join(chunk_file(1));
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&path, bytes).unwrap();
+
+        let mut target = RocksStateDB::new(target_dir.path()).unwrap();
+        assert!(matches!(
+            target.import_snapshot(archive.path(), None),
+            Err(StateDBError::SnapshotError(_))
+        ));
+        assert_eq!(target.latest_version(), None);
+        assert_eq!(target.get_sync(b"a").unwrap(), None);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::StateDBError;
+use crate::RocksStateDB;
+use serde::{Deserialize, Serialize};
+
+/// State key of the supply totals
+pub const SUPPLY_KEY: &[u8] = b"supply";
+const BRIDGE_MINT_PREFIX: &[u8] = b"bridge/mint/";
+const XFG_MINT_PREFIX: &[u8] = b"xfg/mint/";
+
+/// What backs newly minted HEAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MintSource {
+    /// A deposit locked in the L1 bridge contract
+    BridgeDeposit,
+    /// XFG burned on Fuego
+    XfgBurn,
+}
+
+/// Running HEAT supply totals, committed with every block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupplyLedger {
+    /// Allocated by the genesis file
+    pub genesis: u64,
+    /// Created by mints of every source
+    pub minted: u64,
+    /// Destroyed by burns of every kind
+    pub burned: u64,
+    /// Minted for deposits locked in the L1 bridge contract
+    pub bridged_in: u64,
+    /// Burned for withdrawal to L1
+    pub bridged_out: u64,
+    /// Minted against XFG burned on Fuego
+    pub xfg_burn_minted: u64,
+    /// Minted as block subsidies under the emission schedule
+    pub block_rewards: u64,
+    /// Base fees burned by the fee market
+    pub fees_burned: u64,
+}
+
+impl SupplyLedger {
+    /// HEAT held by accounts and the shielded pool: genesis plus mints minus burns
+    pub fn circulating(&self) -> Result<u64, StateDBError> {
+        self.genesis
+            .checked_add(self.minted)
+            .and_then(|total| total.checked_sub(self.burned))
+            .ok_or_else(|| StateDBError::BalanceOverflow(format!("supply ledger is inconsistent: {:?}", self)))
+    }
+
+    /// Record `amount` minted against `source`
+    pub fn record_mint(&mut self, source: MintSource, amount: u64) -> Result<(), StateDBError> {
+        self.minted = add(self.minted, amount)?;
+        match source {
+            MintSource::BridgeDeposit => self.bridged_in = add(self.bridged_in, amount)?,
+            MintSource::XfgBurn => self.xfg_burn_minted = add(self.xfg_burn_minted, amount)?,
+        }
+        self.circulating().map(drop)
+    }
+
+    /// Record `amount` minted as a block subsidy
+    pub fn record_subsidy(&mut self, amount: u64) -> Result<(), StateDBError> {
+        self.minted = add(self.minted, amount)?;
+        self.block_rewards = add(self.block_rewards, amount)?;
+        self.circulating().map(drop)
+    }
+
+    /// Record `amount` burned for withdrawal to L1; fails if more is burned than exists
+    pub fn record_withdrawal(&mut self, amount: u64) -> Result<(), StateDBError> {
+        self.burned = add(self.burned, amount)?;
+        self.bridged_out = add(self.bridged_out, amount)?;
+        self.circulating().map(drop)
+    }
+
+    /// Record `amount` of base fees burned
+    pub fn record_fee_burn(&mut self, amount: u64) -> Result<(), StateDBError> {
+        self.burned = add(self.burned, amount)?;
+        self.fees_burned = add(self.fees_burned, amount)?;
+        self.circulating().map(drop)
+    }
+}
+
+fn add(total: u64, amount: u64) -> Result<u64, StateDBError> {
+    total
+        .checked_add(amount)
+        .ok_or_else(|| StateDBError::BalanceOverflow(format!("adding {} to supply total {}", amount, total)))
+}
+
+/// Key of the `nonce`-th mint the bridge derived from a verified `source`, queued for block
+/// production; written outside the versioned state, it authorizes nothing by itself
+pub fn mint_record_key(source: MintSource, nonce: u64) -> Vec<u8> {
+    let prefix = match source {
+        MintSource::BridgeDeposit => BRIDGE_MINT_PREFIX,
+        MintSource::XfgBurn => XFG_MINT_PREFIX,
+    };
+    [prefix, nonce.to_be_bytes().as_slice()].concat()
+}
+
+/// Whether `key` is a mint record, which lives outside the versioned state
+pub fn is_mint_record_key(key: &[u8]) -> bool {
+    key.starts_with(BRIDGE_MINT_PREFIX) || key.starts_with(XFG_MINT_PREFIX)
+}
+
+impl RocksStateDB {
+    /// Supply totals, including staged changes; zero before genesis
+    pub fn get_supply(&self) -> Result<SupplyLedger, StateDBError> {
+        match self.get_sync(SUPPLY_KEY)? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(SupplyLedger::default()),
+        }
+    }
+
+    /// Stage supply totals for the next commit
+    pub fn put_supply(&mut self, supply: &SupplyLedger) -> Result<(), StateDBError> {
+        self.put_sync(SUPPLY_KEY, &serde_json::to_vec(supply)?)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::error::StateDBError;
+use crate::history::{history_key, key_prefix, split_history_key};
+use crate::merkle::{key_hash, Child, Node, NodeKey, NodeReader, SparseMerkleProof, EMPTY_HASH};
+use crate::{MerkleRoot, RocksStateDB, HISTORY_CF, ROOTS_CF, TREE_CF};
+use rocksdb::{Direction, IteratorMode, Snapshot};
+
+/// Read-only view of the state committed at one version.
+///
+/// Reads go through a RocksDB snapshot, so a view stays consistent while
+/// later versions are committed.
+pub struct StateView<'a> {
+    db: &'a RocksStateDB,
+    snapshot: Snapshot<'a>,
+    version: u64,
+    root: Option<Child>,
+}
+
+impl<'a> StateView<'a> {
+    pub(crate) fn new(db: &'a RocksStateDB, version: u64) -> Result<Self, StateDBError> {
+        let snapshot = db.db.snapshot();
+        let root = match snapshot.get_cf(db.cf(ROOTS_CF)?, version.to_be_bytes())? {
+            Some(bytes) => Child::decode(&bytes)?.0,
+            None => {
+                return Err(StateDBError::InvalidVersion(format!(
+                    "No root stored for version {}",
+                    version
+                )))
+            }
+        };
+        Ok(Self {
+            db,
+            snapshot,
+            version,
+            root,
+        })
+    }
+
+    /// Version the view is pinned to
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// State root of the version
+    pub fn root(&self) -> MerkleRoot {
+        self.root.map_or(EMPTY_HASH, |root| root.hash)
+    }
+
+    /// Get the value `key` had at this version
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, StateDBError> {
+        let prefix = key_prefix(key);
+        let seek = history_key(key, self.version);
+        let newest = self
+            .snapshot
+            .iterator_cf(self.db.cf(HISTORY_CF)?, IteratorMode::From(&seek, Direction::Reverse))
+            .next()
+            .transpose()?;
+        Ok(newest
+            .filter(|(entry, _)| entry.starts_with(&prefix))
+            .map(|(_, value)| value.to_vec()))
+    }
+
+    /// Visit every key with its value at this version, in storage order
+    pub fn for_each_entry<F>(&self, mut visit: F) -> Result<(), StateDBError>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), StateDBError>,
+    {
+        // History is ordered by key and then version, so keep the newest visible value per key
+        let mut current: Option<(Vec<u8>, Vec<u8>)> = None;
+        for entry in self.snapshot.iterator_cf(self.db.cf(HISTORY_CF)?, IteratorMode::Start) {
+            let (history_key, value) = entry?;
+            let (key, version) = split_history_key(&history_key).ok_or_else(|| {
+                StateDBError::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, "Corrupt history key"))
+            })?;
+            if version > self.version {
+                continue;
+            }
+            if let Some((current_key, current_value)) = &current {
+                if current_key.as_slice() != key {
+                    visit(current_key, current_value)?;
+                }
+            }
+            current = Some((key.to_vec(), value.to_vec()));
+        }
+        if let Some((key, value)) = current {
+            visit(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Prove the value of `key`, or its absence, under this version's root
+    pub fn get_proof(&self, key: &[u8]) -> Result<SparseMerkleProof, StateDBError> {
+        SparseMerkleProof::generate(self, self.root, &key_hash(key))
+    }
+}
+
+impl NodeReader for StateView<'_> {
+    fn get_node(&self, key: &NodeKey) -> Result<Option<Node>, StateDBError> {
+        match self.snapshot.get_cf(self.db.cf(TREE_CF)?, key.encode())? {
+            Some(bytes) => Ok(Some(Node::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use block_sync::{Transaction, TxInput, TxOutput};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use tokio::runtime::Runtime;
+use txpool::fee::SimpleFeeAlgorithm;
+use txpool::priority::SimplePriorityCalculator;
+use txpool::TxPool;
+
+const POOL_SIZE: usize = 100_000;
+
+fn transaction(index: u32) -> Transaction {
+    let mut hash = [0u8; 32];
+    hash[..4].copy_from_slice(&index.to_be_bytes());
+    Transaction {
+        hash: [0u8; 32],
+        sender: Vec::new(),
+        nonce: 0,
+        gas_limit: 21_000,
+        data: Vec::new(),
+        inputs: vec![TxInput {
+            prev_tx_hash: hash,
+            output_index: 0,
+            signature: vec![1u8; 64],
+        }],
+        outputs: vec![TxOutput {
+            amount: 100,
+            address: vec![1u8; 32],
+            commitment: [0u8; 32],
+            ephemeral_key: None,
+        }],
+        // Spread fees so selection has to order them
+        fee: 1_000_000 + (index as u64 * 7_919) % 100_000,
+        timestamp: 1_700_000_000,
+        nullifiers: Vec::new(),
+        ring_inputs: Vec::new(),
+        chain_id: 1,
+        fee_payer: None,
+        asset: None,
+    }
+    .with_id()
+}
+
+fn empty_pool() -> TxPool {
+    TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), POOL_SIZE)
+}
+
+async fn fill(mut pool: TxPool, transactions: Vec<Transaction>) -> TxPool {
+    for tx in transactions {
+        pool.add_transaction(tx).await.unwrap();
+    }
+    pool
+}
+
+fn bench_txpool(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let transactions: Vec<Transaction> = (0..POOL_SIZE as u32).map(transaction).collect();
+
+    let mut group = c.benchmark_group("txpool");
+    group.sample_size(10);
+    group.bench_function("add 100k", |b| {
+        b.iter_batched(
+            || (empty_pool(), transactions.clone()),
+            |(pool, transactions)| runtime.block_on(fill(pool, transactions)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    let pool = runtime.block_on(fill(empty_pool(), transactions));
+    group.bench_function("select 1k of 100k", |b| {
+        b.iter(|| runtime.block_on(pool.get_transactions(black_box(1_000))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_txpool);
+criterion_main!(benches);
@@ -2,18 +2,55 @@ use anyhow::Result;
 use dashmap::DashMap;
 use priority_queue::PriorityQueue;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use block_sync::Transaction;
+use block_sync::{Canonical, Transaction};
 
 pub mod error;
 pub mod fee;
 pub mod priority;
+pub mod selection;
 
 use error::TxPoolError;
 use fee::FeeAlgorithm;
 use priority::PriorityCalculator;
+use selection::{Candidate, SelectionStrategy};
+
+/// Limits protecting the pool from being flooded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolLimits {
+    /// Most pooled transactions from one sender account; those without a sender are not capped
+    pub max_per_sender: usize,
+    /// Pooled transactions older than this are dropped
+    pub expiry_secs: u64,
+    /// Pool utilization above which the relay fee starts rising
+    pub congestion_threshold: f64,
+    /// Multiple of the normal fee required once the pool is full
+    pub max_fee_multiplier: u64,
+}
+
+impl Default for PoolLimits {
+    fn default() -> Self {
+        Self {
+            max_per_sender: 64,
+            expiry_secs: 3 * 60 * 60,
+            congestion_threshold: 0.8,
+            max_fee_multiplier: 10,
+        }
+    }
+}
+
+/// Seconds since the Unix epoch
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
 
 /// Transaction pool as specified in the outline
 pub struct TxPool {
@@ -22,6 +59,26 @@ pub struct TxPool {
     fee_algorithm: Box<dyn FeeAlgorithm + Send + Sync>,
     priority_calculator: Box<dyn PriorityCalculator + Send + Sync>,
     max_size: usize,
+    /// Transactions dropped to make room for better-paying ones
+    evictions: u64,
+    /// Chain new transactions must be signed for; any chain when unset
+    chain_id: Option<u64>,
+    /// Verify the input signatures of new transactions
+    verify_signatures: bool,
+    /// Priorities of pooled transactions a block included, kept until the block is final so
+    /// a reorg can return them to the pool unchanged
+    included: HashMap<[u8; 32], u64>,
+    limits: PoolLimits,
+    /// When each transaction entered the pool
+    pooled_at: HashMap<[u8; 32], u64>,
+    /// Pooled transactions of each sender account
+    per_sender: HashMap<Vec<u8>, usize>,
+    /// Transactions refused admission
+    rejected: u64,
+    /// Transactions dropped for staying too long
+    expired: u64,
+    /// System accounts whose transactions take the priority-ops lane; empty disables the lane
+    priority_accounts: HashSet<Vec<u8>>,
 }
 
 impl TxPool {
@@ -37,29 +94,86 @@ impl TxPool {
             fee_algorithm,
             priority_calculator,
             max_size,
+            evictions: 0,
+            chain_id: None,
+            verify_signatures: false,
+            included: HashMap::new(),
+            limits: PoolLimits::default(),
+            pooled_at: HashMap::new(),
+            per_sender: HashMap::new(),
+            rejected: 0,
+            expired: 0,
+            priority_accounts: HashSet::new(),
         }
     }
     
+    /// Replace the flooding limits; pooled transactions are kept, new ones are checked against them
+    pub fn set_limits(&mut self, limits: PoolLimits) {
+        self.limits = limits;
+    }
+    
+    /// Route transactions sent by or to `accounts` through the priority-ops lane. Lane
+    /// transactions are kept out of the fee market: they are not capped per sender, do not
+    /// pay the congestion fee and are never evicted, and those sent by a system account need
+    /// no fee or inputs at all.
+    pub fn set_priority_accounts(&mut self, accounts: Vec<Vec<u8>>) {
+        self.priority_accounts = accounts.into_iter().collect();
+    }
+    
+    /// Whether `tx` belongs in the priority-ops lane
+    pub fn is_priority_op(&self, tx: &Transaction) -> bool {
+        self.priority_accounts.contains(&tx.sender)
+            || tx.outputs.iter().any(|output| self.priority_accounts.contains(&output.address))
+    }
+    
     /// Add transaction as specified in the outline
+    #[tracing::instrument(name = "txpool.add_transaction", skip_all, fields(nonce = tx.nonce, fee = tx.fee))]
     pub async fn add_transaction(&mut self, tx: Transaction) -> Result<(), TxPoolError> {
-        // Check if pool is full
-        if self.transactions.len() >= self.max_size {
-            return Err(TxPoolError::PoolFull);
+        let result = self.admit(tx).await;
+        if result.is_err() {
+            self.rejected += 1;
         }
-        
+        result
+    }
+    
+    async fn admit(&mut self, tx: Transaction) -> Result<(), TxPoolError> {
         // Validate transaction
         if !self.validate_transaction(&tx).await? {
             return Err(TxPoolError::InvalidTransaction);
         }
         
         // Calculate priority
-        let priority = self.priority_calculator.calculate_priority(&tx)?;
+        let priority = if self.is_priority_op(&tx) { 0 } else { self.priority_calculator.calculate_priority(&tx)? };
+        self.insert(tx, priority).await
+    }
+    
+    /// Pool `tx` at `priority`, evicting a lower priority transaction when full. Priority ops
+    /// go to their own lane instead of the priority queue, so they always win over it.
+    async fn insert(&mut self, tx: Transaction, priority: u64) -> Result<(), TxPoolError> {
+        // A full pool only makes room by evicting a transaction of strictly lower priority
+        if self.transactions.len() >= self.max_size {
+            let priority_queue = self.priority_queue.clone();
+            let mut queue = priority_queue.write().await;
+            let lowest = queue.iter().min_by_key(|(_, priority)| **priority).map(|(hash, priority)| (*hash, *priority));
+            match lowest {
+                Some((hash, lowest)) if lowest < priority || self.is_priority_op(&tx) => {
+                    queue.remove(&hash);
+                    self.forget(&hash);
+                    self.evictions += 1;
+                }
+                _ => return Err(TxPoolError::PoolFull),
+            }
+        }
         
         // Add to transactions map
+        self.pooled_at.insert(tx.hash, now_secs());
+        if !tx.sender.is_empty() {
+            *self.per_sender.entry(tx.sender.clone()).or_default() += 1;
+        }
         self.transactions.insert(tx.hash, tx.clone());
         
         // Add to priority queue
-        {
+        if !self.is_priority_op(&tx) {
             let mut queue = self.priority_queue.write().await;
             queue.push(tx.hash, priority);
         }
@@ -92,10 +206,52 @@ impl TxPool {
         transactions
     }
     
+    /// Up to `limit` transactions outside the priority-ops lane, chosen and ordered by `strategy`
+    pub fn select_transactions(&self, strategy: &dyn SelectionStrategy, limit: usize) -> Vec<Transaction> {
+        let now = now_secs();
+        let candidates = self
+            .transactions
+            .iter()
+            .filter(|entry| !self.is_priority_op(entry.value()))
+            .map(|entry| Candidate {
+                tx: entry.value().clone(),
+                waited_secs: now.saturating_sub(self.pooled_at.get(entry.key()).copied().unwrap_or(now)),
+            })
+            .collect();
+        strategy.select(candidates, limit)
+    }
+    
+    /// Up to `limit` transactions of the priority-ops lane, oldest first and in nonce order
+    /// per sender
+    pub fn priority_ops(&self, limit: usize) -> Vec<Transaction> {
+        let mut ops: Vec<Transaction> = self
+            .transactions
+            .iter()
+            .filter(|entry| self.is_priority_op(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        ops.sort_by_key(|tx| (self.pooled_at.get(&tx.hash).copied().unwrap_or(0), tx.sender.clone(), tx.nonce));
+        ops.truncate(limit);
+        ops
+    }
+    
+    /// Drop `tx_hash` from the map and the per-sender bookkeeping, leaving the queue to the caller
+    fn forget(&mut self, tx_hash: &[u8; 32]) -> Option<Transaction> {
+        let (_, tx) = self.transactions.remove(tx_hash)?;
+        self.pooled_at.remove(tx_hash);
+        if let Some(count) = self.per_sender.get_mut(&tx.sender) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_sender.remove(&tx.sender);
+            }
+        }
+        Some(tx)
+    }
+    
     /// Remove transaction as specified in the outline
     pub async fn remove_transaction(&mut self, tx_hash: &[u8; 32]) -> Result<(), TxPoolError> {
         // Remove from transactions map
-        if self.transactions.remove(tx_hash).is_none() {
+        if self.forget(tx_hash).is_none() {
             return Err(TxPoolError::TransactionNotFound);
         }
         
@@ -108,47 +264,231 @@ impl TxPool {
         Ok(())
     }
     
+    /// Drop the transactions a newly connected block included, remembering the priority of
+    /// those that were pooled. Returns how many left the pool.
+    pub async fn remove_included(&mut self, transactions: &[Transaction]) -> usize {
+        let priority_queue = self.priority_queue.clone();
+        let mut queue = priority_queue.write().await;
+        let mut removed = 0;
+        for tx in transactions {
+            if self.forget(&tx.hash).is_none() {
+                continue;
+            }
+            if let Some((_, priority)) = queue.remove(&tx.hash) {
+                self.included.insert(tx.hash, priority);
+            }
+            removed += 1;
+        }
+        removed
+    }
+    
+    /// Forget the priorities kept for transactions whose block is now final
+    pub fn forget_included(&mut self, transactions: &[Transaction]) {
+        for tx in transactions {
+            self.included.remove(&tx.hash);
+        }
+    }
+    
+    /// Return the transactions of disconnected blocks to the pool. They passed admission
+    /// when first pooled, so fee limits set since are not applied again, and they get back
+    /// the priority they had then. Returns how many were pooled.
+    pub async fn reinject(&mut self, transactions: Vec<Transaction>) -> usize {
+        let mut reinjected = 0;
+        for tx in transactions {
+            if self.transactions.contains_key(&tx.hash) || self.chain_id.is_some_and(|id| id != tx.chain_id) {
+                continue;
+            }
+            let priority = match self.included.remove(&tx.hash) {
+                Some(priority) => priority,
+                None if self.is_priority_op(&tx) => 0,
+                None => match self.priority_calculator.calculate_priority(&tx) {
+                    Ok(priority) => priority,
+                    Err(_) => continue,
+                },
+            };
+            if self.insert(tx, priority).await.is_ok() {
+                reinjected += 1;
+            }
+        }
+        reinjected
+    }
+    
+    /// Drop transactions pooled longer than the expiry, returning how many went
+    pub async fn expire_stale(&mut self) -> usize {
+        self.expire_before(now_secs().saturating_sub(self.limits.expiry_secs)).await
+    }
+    
+    /// Drop transactions pooled before `cutoff`, in Unix seconds
+    async fn expire_before(&mut self, cutoff: u64) -> usize {
+        let stale: Vec<[u8; 32]> = self
+            .pooled_at
+            .iter()
+            .filter(|(_, pooled_at)| **pooled_at < cutoff)
+            .map(|(hash, _)| *hash)
+            .collect();
+        let priority_queue = self.priority_queue.clone();
+        let mut queue = priority_queue.write().await;
+        for hash in &stale {
+            queue.remove(hash);
+            self.forget(hash);
+        }
+        self.expired += stale.len() as u64;
+        stale.len()
+    }
+    
+    /// Multiple of the normal fee a new transaction must pay, rising linearly from 1 at the
+    /// congestion threshold to the maximum multiplier when the pool is full
+    fn fee_multiplier(&self) -> f64 {
+        let utilization = self.transactions.len() as f64 / self.max_size.max(1) as f64;
+        let threshold = self.limits.congestion_threshold.clamp(0.0, 1.0);
+        if utilization <= threshold || threshold >= 1.0 {
+            return 1.0;
+        }
+        let congestion = ((utilization - threshold) / (1.0 - threshold)).min(1.0);
+        1.0 + (self.limits.max_fee_multiplier.max(1) - 1) as f64 * congestion
+    }
+    
+    /// Lowest fee the pool currently relays
+    pub fn min_relay_fee(&self) -> u64 {
+        (self.fee_algorithm.get_min_fee() as f64 * self.fee_multiplier()).ceil() as u64
+    }
+    
     /// Get transaction by hash
     pub fn get_transaction(&self, tx_hash: &[u8; 32]) -> Option<Transaction> {
         self.transactions.get(tx_hash).map(|tx| tx.clone())
     }
     
+    /// Every pooled transaction, in no particular order
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.transactions.iter().map(|tx| tx.value().clone()).collect()
+    }
+    
+    /// Replace the fee algorithm; pooled transactions are kept, new ones are checked against it
+    pub fn set_fee_algorithm(&mut self, fee_algorithm: Box<dyn FeeAlgorithm + Send + Sync>) {
+        self.fee_algorithm = fee_algorithm;
+    }
+
+    /// Only admit transactions signed for `chain_id` from now on
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = Some(chain_id);
+    }
+
+    /// Refuse transactions whose inputs are not signed by their owners' ed25519 keys
+    pub fn set_verify_signatures(&mut self, verify_signatures: bool) {
+        self.verify_signatures = verify_signatures;
+    }
+
+    /// Get fee limits enforced on new transactions
+    pub fn fee_limits(&self) -> (u64, u64) {
+        (self.fee_algorithm.get_min_fee(), self.fee_algorithm.get_max_fee())
+    }
+
     /// Get pool statistics
     pub fn get_stats(&self) -> PoolStats {
         PoolStats {
             total_transactions: self.transactions.len(),
             max_size: self.max_size,
             utilization: self.transactions.len() as f64 / self.max_size as f64,
+            evictions: self.evictions,
+            rejected: self.rejected,
+            expired: self.expired,
+            min_relay_fee: self.min_relay_fee(),
+            priority_ops: self.transactions.iter().filter(|entry| self.is_priority_op(entry.value())).count(),
         }
     }
     
+    /// Write the pooled transactions to `path` so they survive a restart
+    pub fn save(&self, path: &Path) -> Result<usize, TxPoolError> {
+        let transactions: Vec<Transaction> = self.transactions.iter().map(|entry| entry.value().clone()).collect();
+        std::fs::write(path, transactions.to_canonical_bytes()).map_err(|e| TxPoolError::IoError(e.to_string()))?;
+        Ok(transactions.len())
+    }
+    
+    /// Re-add transactions written by `save`, skipping any the pool now rejects
+    pub async fn load(&mut self, path: &Path) -> Result<usize, TxPoolError> {
+        let bytes = std::fs::read(path).map_err(|e| TxPoolError::IoError(e.to_string()))?;
+        let transactions = Vec::<Transaction>::from_canonical_bytes(&bytes)
+            .map_err(|e| TxPoolError::SerializationError(e.to_string()))?;
+        let mut loaded = 0;
+        for tx in transactions {
+            if self.add_transaction(tx).await.is_ok() {
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+    
     /// Clear all transactions
     pub async fn clear(&mut self) {
         self.transactions.clear();
+        self.pooled_at.clear();
+        self.per_sender.clear();
         let mut queue = self.priority_queue.write().await;
         queue.clear();
     }
     
     /// Validate transaction
     async fn validate_transaction(&self, tx: &Transaction) -> Result<bool, TxPoolError> {
-        // Basic validation
-        if tx.fee == 0 {
+        // Basic validation; system accounts pay no fee and spend no inputs
+        let priority_op = self.is_priority_op(tx);
+        let system = self.priority_accounts.contains(&tx.sender);
+        if tx.fee == 0 && !system {
             return Ok(false);
         }
         
-        if tx.inputs.is_empty() || tx.outputs.is_empty() {
+        if (tx.inputs.is_empty() && !system) || tx.outputs.is_empty() {
             return Ok(false);
         }
         
+        // Signatures commit to the chain id, so a transaction for another chain could never be included
+        if let Some(chain_id) = self.chain_id.filter(|chain_id| *chain_id != tx.chain_id) {
+            return Err(TxPoolError::ValidationError(format!(
+                "transaction is for chain {}, not {}",
+                tx.chain_id, chain_id
+            )));
+        }
+        
+        // System transactions pay no fee, so only others may be sponsored, by another account
+        if let Some(payer) = &tx.fee_payer {
+            if system || payer.account.is_empty() || payer.account == tx.sender {
+                return Err(TxPoolError::ValidationError(
+                    "fee payer must be an account other than the sender of a fee-paying transaction".to_string(),
+                ));
+            }
+        }
+        
+        // The pool is keyed by hash, so a made-up one could shadow someone else's transaction
+        if !tx.has_valid_id() {
+            return Err(TxPoolError::ValidationError("transaction hash is not its id".to_string()));
+        }
+        
         // Check for duplicate
         if self.transactions.contains_key(&tx.hash) {
             return Ok(false);
         }
         
-        // Validate fee
+        if system {
+            return Ok(true);
+        }
+        
+        if self.verify_signatures {
+            block_sync::signatures::verify_transaction(tx).map_err(|e| TxPoolError::ValidationError(e.to_string()))?;
+        }
+        
+        let pending = self.per_sender.get(&tx.sender).copied().unwrap_or(0);
+        if !tx.sender.is_empty() && !priority_op && pending >= self.limits.max_per_sender {
+            return Err(TxPoolError::ValidationError(format!(
+                "sender already has {} pending transactions",
+                self.limits.max_per_sender
+            )));
+        }
+        
+        // Validate fee, which rises as the pool fills up for all but priority ops
         let expected_fee = self.fee_algorithm.calculate_fee(tx)?;
-        if tx.fee < expected_fee {
-            return Ok(false);
+        let multiplier = if priority_op { 1.0 } else { self.fee_multiplier() };
+        let required_fee = (expected_fee as f64 * multiplier).ceil() as u64;
+        if tx.fee < required_fee {
+            return Err(TxPoolError::InsufficientFee);
         }
         
         Ok(true)
@@ -200,6 +540,15 @@ pub struct PoolStats {
     pub total_transactions: usize,
     pub max_size: usize,
     pub utilization: f64,
+    pub evictions: u64,
+    /// Transactions refused admission
+    pub rejected: u64,
+    /// Transactions dropped for staying in the pool too long
+    pub expired: u64,
+    /// Lowest fee the pool currently relays
+    pub min_relay_fee: u64,
+    /// Transactions waiting in the priority-ops lane
+    pub priority_ops: usize,
 }
 
 /// Transaction with metadata
@@ -291,7 +640,7 @@ mod tests {
         for i in 0..5 {
             let mut tx = create_test_transaction_with_index(i);
             tx.fee = (i as u64) + 10; // Ensure sufficient fee
-            pool.add_transaction(tx).await.unwrap();
+            pool.add_transaction(tx.with_id()).await.unwrap();
         }
         
         let transactions = pool.get_transactions(3).await;
@@ -303,6 +652,8 @@ mod tests {
         let fee_algorithm = Box::new(SimpleFeeAlgorithm::new(1));
         let priority_calculator = Box::new(SimplePriorityCalculator::new());
         let mut pool = TxPool::new(fee_algorithm, priority_calculator, 1);
+        // Admission of a full pool is down to priority alone here
+        pool.set_limits(PoolLimits { max_fee_multiplier: 1, ..Default::default() });
         
         let tx1 = create_test_transaction_with_index(1);
         let tx2 = create_test_transaction_with_index(2);
@@ -314,17 +665,206 @@ mod tests {
         assert_eq!(result.unwrap_err(), TxPoolError::PoolFull);
     }
     
+    #[tokio::test]
+    async fn test_full_pool_evicts_lower_priority() {
+        let fee_algorithm = Box::new(SimpleFeeAlgorithm::new(1));
+        let priority_calculator = Box::new(SimplePriorityCalculator::new());
+        let mut pool = TxPool::new(fee_algorithm, priority_calculator, 1);
+        // Admission of a full pool is down to priority alone here
+        pool.set_limits(PoolLimits { max_fee_multiplier: 1, ..Default::default() });
+        
+        let tx1 = create_test_transaction_with_index(1);
+        let mut tx2 = create_test_transaction_with_index(2);
+        tx2.fee = 20;
+        let tx2 = tx2.with_id();
+        
+        pool.add_transaction(tx1.clone()).await.unwrap();
+        pool.add_transaction(tx2.clone()).await.unwrap();
+        
+        assert!(pool.get_transaction(&tx1.hash).is_none());
+        assert!(pool.get_transaction(&tx2.hash).is_some());
+        assert_eq!(pool.get_stats().evictions, 1);
+        assert_eq!(pool.add_transaction(tx1).await.unwrap_err(), TxPoolError::PoolFull);
+    }
+    
+    #[tokio::test]
+    async fn test_reinjected_transactions_keep_their_priority() {
+        let priority_calculator = Box::new(crate::priority::TimeBasedPriorityCalculator::with_decay(1000, 0.001));
+        let mut pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), priority_calculator, 10);
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let mut pooled = create_test_transaction_with_index(1);
+        pooled.timestamp = now;
+        let pooled = pooled.with_id();
+        pool.add_transaction(pooled.clone()).await.unwrap();
+        let original = pool.priority_queue.read().await.get_priority(&pooled.hash).copied().unwrap();
+        
+        // A block includes it together with one this pool never saw, which is priced on return
+        let mut relayed = create_test_transaction_with_index(2);
+        relayed.timestamp = now + 60;
+        let relayed = relayed.with_id();
+        assert_eq!(pool.remove_included(&[pooled.clone(), relayed.clone()]).await, 1);
+        assert_eq!(pool.get_stats().total_transactions, 0);
+        
+        // The block is disconnected after fees rose above what both pay
+        pool.set_fee_algorithm(Box::new(SimpleFeeAlgorithm::new(1_000)));
+        assert_eq!(pool.reinject(vec![pooled.clone(), relayed.clone()]).await, 2);
+        let queue = pool.priority_queue.read().await;
+        assert_eq!(queue.get_priority(&pooled.hash), Some(&original));
+        assert_eq!(queue.get_priority(&relayed.hash), Some(&1000));
+    }
+    
+    #[tokio::test]
+    async fn test_sender_cap_fee_floor_and_expiry() {
+        let mut pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 10);
+        pool.set_limits(PoolLimits {
+            max_per_sender: 2,
+            congestion_threshold: 0.5,
+            max_fee_multiplier: 11,
+            ..Default::default()
+        });
+        let from = |index: u8, sender: u8, fee: u64| Transaction {
+            sender: vec![sender],
+            fee,
+            ..create_test_transaction_with_index(index)
+        }
+        .with_id();
+        
+        pool.add_transaction(from(1, 1, 10)).await.unwrap();
+        pool.add_transaction(from(2, 1, 10)).await.unwrap();
+        assert!(matches!(pool.add_transaction(from(3, 1, 10)).await, Err(TxPoolError::ValidationError(_))));
+        for index in 3..6 {
+            pool.add_transaction(from(index, index, 10)).await.unwrap();
+        }
+        assert_eq!(pool.min_relay_fee(), 1);
+        
+        // Past half full the fee rises towards eleven times the normal fee of 2
+        pool.add_transaction(from(6, 6, 10)).await.unwrap();
+        assert_eq!(pool.min_relay_fee(), 3);
+        assert_eq!(pool.add_transaction(from(7, 7, 5)).await.unwrap_err(), TxPoolError::InsufficientFee);
+        pool.add_transaction(from(7, 7, 6)).await.unwrap();
+        
+        // Removing a sender's transaction frees room for another
+        pool.remove_transaction(&from(1, 1, 10).hash).await.unwrap();
+        pool.add_transaction(from(8, 1, 10)).await.unwrap();
+        
+        assert_eq!(pool.expire_stale().await, 0);
+        assert_eq!(pool.expire_before(u64::MAX).await, 7);
+        let stats = pool.get_stats();
+        assert_eq!((stats.total_transactions, stats.rejected, stats.expired), (0, 2, 7));
+        assert!(pool.per_sender.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_priority_ops_lane() {
+        let mut pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 3);
+        pool.set_limits(PoolLimits { max_fee_multiplier: 1, ..Default::default() });
+        pool.set_priority_accounts(vec![vec![0xaa], vec![0xed]]);
+        let mint = |index: u8, nonce: u64| Transaction {
+            sender: vec![0xaa],
+            nonce,
+            fee: 0,
+            inputs: Vec::new(),
+            ..create_test_transaction_with_index(index)
+        }
+        .with_id();
+        
+        // Users fill the pool, then system mints without fees or inputs evict them
+        for index in 1..4 {
+            pool.add_transaction(create_test_transaction_with_index(index)).await.unwrap();
+        }
+        pool.add_transaction(mint(11, 1)).await.unwrap();
+        pool.add_transaction(mint(10, 0)).await.unwrap();
+        let mut registration = create_test_transaction_with_index(12);
+        registration.sender = vec![0xff];
+        registration.outputs[0].address = vec![0xed];
+        let mut registration = registration.with_id();
+        pool.add_transaction(registration.clone()).await.unwrap();
+        
+        // A full lane is never evicted, and lane transactions stay out of the fee-ordered queue
+        assert_eq!(pool.add_transaction(create_test_transaction_with_index(4)).await, Err(TxPoolError::PoolFull));
+        assert!(pool.get_transactions(10).await.is_empty());
+        let ops: Vec<_> = pool.priority_ops(10).iter().map(|tx| tx.hash).collect();
+        assert_eq!(ops, [mint(10, 0).hash, mint(11, 1).hash, registration.hash]);
+        assert_eq!(pool.get_stats().priority_ops, 3);
+        
+        // Only system senders may skip the fee
+        pool.remove_transaction(&registration.hash).await.unwrap();
+        registration.fee = 0;
+        assert_eq!(pool.add_transaction(registration.with_id()).await, Err(TxPoolError::InvalidTransaction));
+    }
+    
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("codl3-txpool-{}.bin", std::process::id()));
+        let mut pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 10);
+        pool.add_transaction(create_test_transaction_with_index(1)).await.unwrap();
+        pool.add_transaction(create_test_transaction_with_index(2)).await.unwrap();
+        assert_eq!(pool.save(&path).unwrap(), 2);
+        
+        let mut restored =
+            TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 10);
+        assert_eq!(restored.load(&path).await.unwrap(), 2);
+        assert!(restored.get_transaction(&create_test_transaction_with_index(2).hash).is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_transactions_for_another_chain_are_rejected() {
+        let mut pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 10);
+        pool.set_chain_id(2);
+        let error = pool.add_transaction(create_test_transaction()).await.unwrap_err();
+        assert!(matches!(error, TxPoolError::ValidationError(_)));
+        
+        let testnet = Transaction { chain_id: 2, ..create_test_transaction() }.with_id();
+        pool.add_transaction(testnet).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_inputs_must_be_signed_when_signatures_are_verified() {
+        let mut pool = TxPool::new(Box::new(SimpleFeeAlgorithm::new(1)), Box::new(SimplePriorityCalculator::new()), 10);
+        pool.set_verify_signatures(true);
+        let error = pool.add_transaction(create_test_transaction()).await.unwrap_err();
+        assert!(matches!(error, TxPoolError::ValidationError(_)));
+        
+        let mut signed = create_test_transaction();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+        signed.inputs[0].signature = block_sync::signatures::sign_input(&key, &signed);
+        pool.add_transaction(signed.with_id()).await.unwrap();
+        
+        // The signature covers the amounts, so one raised after signing is refused
+        let mut inflated = create_test_transaction_with_index(2);
+        inflated.inputs[0].signature = block_sync::signatures::sign_input(&key, &inflated);
+        inflated.outputs[0].amount += 1;
+        let error = pool.add_transaction(inflated.with_id()).await.unwrap_err();
+        assert!(matches!(error, TxPoolError::ValidationError(_)));
+        
+        // A sponsored transaction needs its fee payer's signature as well
+        let mut sponsored = create_test_transaction_with_index(1);
+        sponsored.inputs[0].signature = block_sync::signatures::sign_input(&key, &sponsored);
+        let payer = ed25519_dalek::SigningKey::from_bytes(&[0xfe; 32]);
+        let misdirected = block_sync::signatures::sign_fee_payer(&payer, &sponsored, &[0xfd]);
+        sponsored.fee_payer = Some(block_sync::FeePayer { account: vec![0xfe], signature: misdirected });
+        assert!(pool.add_transaction(sponsored.clone().with_id()).await.is_err());
+        let signature = block_sync::signatures::sign_fee_payer(&payer, &sponsored, &[0xfe]);
+        sponsored.fee_payer.as_mut().unwrap().signature = signature;
+        pool.add_transaction(sponsored.with_id()).await.unwrap();
+    }
+    
     fn create_test_transaction() -> Transaction {
         create_test_transaction_with_index(0)
     }
     
     fn create_test_transaction_with_index(index: u8) -> Transaction {
-        let mut hash = [0u8; 32];
-        hash[0] = index;
+        let mut prev_tx_hash = [0u8; 32];
+        prev_tx_hash[0] = index;
         Transaction {
-            hash,
+            hash: [0u8; 32],
+            sender: Vec::new(),
+            nonce: 0,
+            gas_limit: 0,
+            data: Vec::new(),
             inputs: vec![TxInput {
-                prev_tx_hash: [0u8; 32],
+                prev_tx_hash,
                 output_index: 0,
                 signature: vec![1u8; 64],
             }],
@@ -332,9 +872,16 @@ mod tests {
                 amount: 100,
                 address: vec![1u8; 32],
                 commitment: [0u8; 32],
+                ephemeral_key: None,
             }],
             fee: 10, // Higher fee to pass validation
             timestamp: 1234567890,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
         }
+        .with_id()
     }
 }
\ No newline at end of file
@@ -212,6 +212,10 @@ mod tests {
     fn create_test_transaction(fee: u64, timestamp: u64) -> Transaction {
         Transaction {
             hash: [1u8; 32],
+            sender: Vec::new(),
+            nonce: 0,
+            gas_limit: 0,
+            data: Vec::new(),
             inputs: vec![TxInput {
                 prev_tx_hash: [0u8; 32],
                 output_index: 0,
@@ -221,9 +225,15 @@ mod tests {
                 amount: 100,
                 address: vec![1u8; 32],
                 commitment: [0u8; 32],
+                ephemeral_key: None,
             }],
             fee,
             timestamp,
+            nullifiers: Vec::new(),
+            ring_inputs: Vec::new(),
+            chain_id: 1,
+            fee_payer: None,
+            asset: None,
         }
     }
     
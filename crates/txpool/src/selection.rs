@@ -0,0 +1,182 @@
+use block_sync::{Canonical, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A pooled transaction offered to a selection strategy
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub tx: Transaction,
+    /// Seconds the transaction has been waiting in the pool
+    pub waited_secs: u64,
+}
+
+/// Chooses which pooled transactions a block template takes, and in what order
+pub trait SelectionStrategy: Send + Sync {
+    /// Up to `limit` of `candidates`, best first
+    fn select(&self, candidates: Vec<Candidate>, limit: usize) -> Vec<Transaction>;
+}
+
+/// Fee per kilobyte of canonical encoding
+fn fee_rate(tx: &Transaction) -> u128 {
+    tx.fee as u128 * 1024 / tx.to_canonical_bytes().len().max(1) as u128
+}
+
+/// Sort `candidates` by descending `score`, keeping pool order among equals
+fn best_first(mut candidates: Vec<Candidate>, score: impl Fn(&Candidate) -> u128) -> Vec<Candidate> {
+    candidates.sort_by_cached_key(|candidate| std::cmp::Reverse(score(candidate)));
+    candidates
+}
+
+/// Highest fee per byte first
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyFee;
+
+impl SelectionStrategy for GreedyFee {
+    fn select(&self, candidates: Vec<Candidate>, limit: usize) -> Vec<Transaction> {
+        best_first(candidates, |candidate| fee_rate(&candidate.tx))
+            .into_iter()
+            .take(limit)
+            .map(|candidate| candidate.tx)
+            .collect()
+    }
+}
+
+/// Fee per byte plus a credit for every minute waited, so cheap transactions are not starved
+/// by a steady stream of better-paying ones
+#[derive(Debug, Clone, Copy)]
+pub struct FeeAndAge {
+    /// Fee per kilobyte credited for each minute in the pool
+    pub credit_per_minute: u64,
+}
+
+impl SelectionStrategy for FeeAndAge {
+    fn select(&self, candidates: Vec<Candidate>, limit: usize) -> Vec<Transaction> {
+        let credit = |candidate: &Candidate| (candidate.waited_secs / 60) as u128 * self.credit_per_minute as u128;
+        best_first(candidates, |candidate| fee_rate(&candidate.tx) + credit(candidate))
+            .into_iter()
+            .take(limit)
+            .map(|candidate| candidate.tx)
+            .collect()
+    }
+}
+
+/// Highest fee per byte first, with no sender taking more than a share of the block.
+/// Transactions without a sender are not capped.
+#[derive(Debug, Clone, Copy)]
+pub struct AccountFairness {
+    /// Largest share of a block's transactions one sender may fill, in percent
+    pub max_sender_percent: u64,
+}
+
+impl SelectionStrategy for AccountFairness {
+    fn select(&self, candidates: Vec<Candidate>, limit: usize) -> Vec<Transaction> {
+        let cap = (limit as u64 * self.max_sender_percent.min(100) / 100).max(1) as usize;
+        let mut per_sender: HashMap<Vec<u8>, usize> = HashMap::new();
+        best_first(candidates, |candidate| fee_rate(&candidate.tx))
+            .into_iter()
+            .filter(|candidate| {
+                if candidate.tx.sender.is_empty() {
+                    return true;
+                }
+                let count = per_sender.entry(candidate.tx.sender.clone()).or_default();
+                *count += 1;
+                *count <= cap
+            })
+            .take(limit)
+            .map(|candidate| candidate.tx)
+            .collect()
+    }
+}
+
+/// Which selection strategy block templates are built with
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SelectionConfig {
+    /// Highest fee per byte first
+    #[default]
+    GreedyFee,
+    /// Fee per byte plus a credit for each minute waited
+    FeeAge { credit_per_minute: u64 },
+    /// Highest fee per byte first, each sender capped at a share of the block
+    AccountFair { max_sender_percent: u64 },
+}
+
+impl SelectionConfig {
+    /// Build the configured strategy
+    pub fn strategy(&self) -> Box<dyn SelectionStrategy> {
+        match self {
+            SelectionConfig::GreedyFee => Box::new(GreedyFee),
+            SelectionConfig::FeeAge { credit_per_minute } => Box::new(FeeAndAge {
+                credit_per_minute: *credit_per_minute,
+            }),
+            SelectionConfig::AccountFair { max_sender_percent } => Box::new(AccountFairness {
+                max_sender_percent: *max_sender_percent,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block_sync::{TxInput, TxOutput};
+
+    fn candidate(id: u8, sender: u8, fee: u64, waited_secs: u64) -> Candidate {
+        Candidate {
+            tx: Transaction {
+                hash: [id; 32],
+                sender: vec![sender; 20],
+                nonce: id as u64,
+                gas_limit: 0,
+                data: Vec::new(),
+                inputs: vec![TxInput {
+                    prev_tx_hash: [0u8; 32],
+                    output_index: 0,
+                    signature: vec![1u8; 64],
+                }],
+                outputs: vec![TxOutput {
+                    amount: 100,
+                    address: vec![1u8; 32],
+                    commitment: [0u8; 32],
+                    ephemeral_key: None,
+                }],
+                fee,
+                timestamp: 1234567890,
+                nullifiers: Vec::new(),
+                ring_inputs: Vec::new(),
+                chain_id: 1,
+                fee_payer: None,
+                asset: None,
+            },
+            waited_secs,
+        }
+    }
+
+    fn ids(transactions: &[Transaction]) -> Vec<u8> {
+        transactions.iter().map(|tx| tx.hash[0]).collect()
+    }
+
+    #[test]
+    fn test_strategies_order_and_cap_candidates() {
+        let candidates = vec![
+            candidate(1, 0xa, 100, 0),
+            candidate(2, 0xa, 300, 0),
+            candidate(3, 0xb, 10, 3600),
+            candidate(4, 0xa, 200, 0),
+        ];
+
+        let greedy = SelectionConfig::GreedyFee.strategy();
+        assert_eq!(ids(&greedy.select(candidates.clone(), 3)), vec![2, 4, 1]);
+
+        // An hour in the pool earns the cheap transaction 60 minutes of credit
+        let aged = SelectionConfig::FeeAge { credit_per_minute: 100 }.strategy();
+        assert_eq!(ids(&aged.select(candidates.clone(), 2)), vec![3, 2]);
+
+        // Sender 0xa may fill half of a four-transaction block
+        let fair = SelectionConfig::AccountFair { max_sender_percent: 50 }.strategy();
+        assert_eq!(ids(&fair.select(candidates, 4)), vec![2, 4, 3]);
+
+        let config: SelectionConfig = serde_json::from_str(r#"{"type":"fee_age","credit_per_minute":5}"#).unwrap();
+        assert_eq!(config, SelectionConfig::FeeAge { credit_per_minute: 5 });
+    }
+}
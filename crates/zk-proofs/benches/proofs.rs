@@ -0,0 +1,67 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::rngs::OsRng;
+use zk_proofs::privacy::{note_commitment, value_commitment};
+use zk_proofs::{
+    Fr, HashDomain, LeafUpdate, Poseidon, PrivateTransferKeys, PrivateTransferProver, SpendWitness, StateTransition,
+    StateTransitionKeys, StateTransitionProver, StateTransitionVerifier, TransitionShape, ZkFriendlyHash,
+    ZkProofProver, ZkProofVerifier,
+};
+
+fn bench_hashes(c: &mut Criterion) {
+    let (left, right) = (Fr::from(0x11u64), Fr::from(0x22u64));
+    c.bench_function("poseidon/hash2", |b| {
+        b.iter(|| Poseidon::hash2(HashDomain::MerkleNode, black_box(left), black_box(right)))
+    });
+    c.bench_function("commitments/value", |b| {
+        b.iter(|| value_commitment(black_box(100), black_box(left)))
+    });
+    c.bench_function("commitments/note", |b| {
+        b.iter(|| note_commitment(black_box(100), black_box(left), black_box(right)))
+    });
+}
+
+fn bench_proofs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prove");
+    group.sample_size(10);
+
+    let prover = PrivateTransferProver::new(PrivateTransferKeys::setup(&mut OsRng).unwrap());
+    let witness = SpendWitness {
+        balance: 1_000,
+        balance_blinding: Fr::from(0xb1u64),
+        amount: 100,
+        amount_blinding: Fr::from(0xa1u64),
+        spending_key: Fr::from(0x5eu64),
+    };
+    group.bench_function("private_transfer", |b| {
+        b.iter(|| prover.build_transaction(black_box(&witness), Vec::new()).unwrap())
+    });
+
+    // One update through the default tree depth, in a circuit sized for a block's updates
+    let shape = TransitionShape::default();
+    let keys = StateTransitionKeys::setup(shape, &mut OsRng).unwrap();
+    let verifier = StateTransitionVerifier::new(keys.verifying_key());
+    let prover = StateTransitionProver::new(keys);
+    let update = LeafUpdate {
+        index: 5,
+        old_leaf: Fr::from(0u64),
+        new_leaf: Fr::from(50u64),
+        siblings: vec![Fr::from(0u64); shape.depth],
+    };
+    let statement = StateTransition {
+        old_root: update.old_root(),
+        new_root: update.new_root(),
+    };
+    let updates = vec![update];
+    group.bench_function("state_transition", |b| {
+        b.iter(|| prover.prove(black_box(&statement), &updates).unwrap())
+    });
+    group.finish();
+
+    let proof = prover.prove(&statement, &updates).unwrap();
+    c.bench_function("verify/state_transition", |b| {
+        b.iter(|| verifier.verify(black_box(&statement), &proof).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_hashes, bench_proofs);
+criterion_main!(benches);
@@ -0,0 +1,202 @@
+//! Batch verification of Groth16 proofs of one circuit.
+//!
+//! A batch is not an aggregate: it carries every inner proof and public input,
+//! so its size, and the calldata to post it, grow linearly with the number of
+//! proofs, and nothing on L1 can verify it. What it saves is verifier time: the
+//! batch is checked with one randomized pairing product, each proof's equation
+//! scaled by a fresh verifier-chosen scalar and all of them sharing a single
+//! final exponentiation, so checking N proofs costs N + 2 Miller loops instead
+//! of 3N pairings. A forged inner proof passes only if the verifier's scalars
+//! happen to cancel it out, which happens with negligible probability.
+
+use crate::cache::{VerificationCache, VerifierCache};
+use crate::error::ZkProofError;
+use crate::{field_from_bytes, PublicInputs, ZkProof, ZkProofVerifier};
+use ark_bn254::{Bn254, Fr};
+use ark_ec::pairing::Pairing;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{Field, PrimeField, UniformRand};
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Prefix of the circuit name of proof batches
+pub const BATCH_PREFIX: &str = "batch/";
+
+/// Bundle proofs of the same circuit into one batch, to be checked together
+pub fn batch_proofs(proofs: Vec<ZkProof>) -> Result<ZkProof, ZkProofError> {
+    let first = proofs
+        .first()
+        .ok_or_else(|| ZkProofError::InvalidProof("No proofs to batch".to_string()))?;
+    if first.circuit.starts_with(BATCH_PREFIX) {
+        return Err(ZkProofError::InvalidProof("Batches cannot be nested".to_string()));
+    }
+    let circuit = first.circuit.clone();
+    let input_count = first.public_inputs.len();
+
+    let mut bytes = (proofs.len() as u32).to_le_bytes().to_vec();
+    let mut public_inputs = Vec::with_capacity(proofs.len() * input_count);
+    for proof in proofs {
+        if proof.circuit != circuit || proof.public_inputs.len() != input_count {
+            return Err(ZkProofError::InvalidProof(format!(
+                "Cannot batch a {} proof with {} proofs",
+                proof.circuit, circuit
+            )));
+        }
+        // Re-encode so a malformed inner proof is refused here rather than at verification
+        let inner = Proof::<Bn254>::deserialize_compressed(&proof.proof[..])
+            .map_err(|e| ZkProofError::InvalidProof(e.to_string()))?;
+        inner.serialize_compressed(&mut bytes)?;
+        public_inputs.extend(proof.public_inputs);
+    }
+
+    Ok(ZkProof {
+        circuit: format!("{}{}", BATCH_PREFIX, circuit),
+        public_inputs,
+        proof: bytes,
+    })
+}
+
+/// Verifies batches of one circuit against the statements they cover, in order
+pub struct BatchVerifier<S> {
+    circuit: String,
+    verifying_key: PreparedVerifyingKey<Bn254>,
+    cache: VerifierCache,
+    _statement: PhantomData<S>,
+}
+
+impl<S: PublicInputs> BatchVerifier<S> {
+    /// Verifier for batches of `circuit` proofs made with `verifying_key`
+    pub fn new(circuit: &str, verifying_key: &VerifyingKey<Bn254>) -> Self {
+        Self {
+            circuit: format!("{}{}", BATCH_PREFIX, circuit),
+            verifying_key: Groth16::<Bn254>::process_vk(verifying_key).expect("processing a verifying key cannot fail"),
+            cache: VerifierCache::new(verifying_key),
+            _statement: PhantomData,
+        }
+    }
+
+    /// Share verification results through `cache`
+    pub fn with_cache(mut self, cache: Arc<VerificationCache>) -> Self {
+        self.cache.attach(cache);
+        self
+    }
+
+    fn decode(&self, proof: &ZkProof) -> Result<Vec<Proof<Bn254>>, ZkProofError> {
+        let malformed = |e: ark_serialize::SerializationError| ZkProofError::InvalidProof(e.to_string());
+        if proof.proof.len() < 4 {
+            return Err(ZkProofError::InvalidProof("Truncated batch".to_string()));
+        }
+        let count = u32::from_le_bytes(proof.proof[0..4].try_into().unwrap()) as usize;
+        let mut reader = &proof.proof[4..];
+        let proofs = (0..count)
+            .map(|_| Proof::<Bn254>::deserialize_compressed(&mut reader).map_err(malformed))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !reader.is_empty() {
+            return Err(ZkProofError::InvalidProof("Trailing bytes after batched proofs".to_string()));
+        }
+        Ok(proofs)
+    }
+
+    /// Check the randomized pairing product of the batch against each statement's inputs
+    fn verify_product(&self, inputs: &[Vec<Fr>], proof: &ZkProof) -> Result<bool, ZkProofError> {
+        let proofs = self.decode(proof)?;
+        if proofs.is_empty() || proofs.len() != inputs.len() {
+            return Ok(false);
+        }
+
+        // prod e(r_i A_i, B_i) * e(sum r_i IC_i, -gamma) * e(sum r_i C_i, -delta) == e(alpha, beta)^(sum r_i)
+        let mut rng = rand::rngs::OsRng;
+        let mut g1 = Vec::with_capacity(proofs.len() + 2);
+        let mut g2 = Vec::with_capacity(proofs.len() + 2);
+        let mut inputs_sum = <Bn254 as Pairing>::G1::default();
+        let mut c_sum = <Bn254 as Pairing>::G1::default();
+        let mut r_sum = Fr::from(0u64);
+        for (inner, inputs) in proofs.iter().zip(inputs) {
+            let r = Fr::rand(&mut rng);
+            g1.push((inner.a * r).into_affine());
+            g2.push(<Bn254 as Pairing>::G2Prepared::from(inner.b));
+            inputs_sum += Groth16::<Bn254>::prepare_inputs(&self.verifying_key, inputs)? * r;
+            c_sum += inner.c.into_group() * r;
+            r_sum += r;
+        }
+        g1.push(inputs_sum.into_affine());
+        g2.push(self.verifying_key.gamma_g2_neg_pc.clone());
+        g1.push(c_sum.into_affine());
+        g2.push(self.verifying_key.delta_g2_neg_pc.clone());
+
+        let product = Bn254::final_exponentiation(Bn254::multi_miller_loop(g1, g2))
+            .ok_or_else(|| ZkProofError::InvalidProof("Pairing check failed".to_string()))?;
+        Ok(product.0 == self.verifying_key.alpha_g1_beta_g2.pow(r_sum.into_bigint()))
+    }
+}
+
+impl<S: PublicInputs> ZkProofVerifier for BatchVerifier<S> {
+    type Statement = Vec<S>;
+
+    fn verify(&self, statements: &Vec<S>, proof: &ZkProof) -> Result<bool, ZkProofError> {
+        if proof.circuit != self.circuit {
+            return Err(ZkProofError::InvalidProof(format!("Expected a {} proof, got {}", self.circuit, proof.circuit)));
+        }
+        let inputs: Vec<Vec<Fr>> = statements.iter().map(PublicInputs::public_inputs).collect();
+        let declared = proof
+            .public_inputs
+            .iter()
+            .map(field_from_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
+        if declared != inputs.concat() {
+            return Ok(false);
+        }
+        self.cache.check(proof, || self.verify_product(&inputs, proof))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::{self, PrivateTransferKeys, PrivateTransferProver, SpendStatement, SpendWitness};
+    use crate::ZkProofProver;
+
+    fn spend(balance: u64, amount: u64) -> SpendWitness {
+        SpendWitness {
+            balance,
+            balance_blinding: Fr::from(balance + 1),
+            amount,
+            amount_blinding: Fr::from(amount + 2),
+            spending_key: Fr::from(balance * 3),
+        }
+    }
+
+    #[test]
+    fn test_batch_verifies_all_statements() {
+        let keys = PrivateTransferKeys::setup(&mut rand::rngs::OsRng).unwrap();
+        let verifier = BatchVerifier::<SpendStatement>::new(privacy::CIRCUIT_NAME, keys.verifying_key());
+        let prover = PrivateTransferProver::new(keys);
+        let witnesses = [spend(10, 3), spend(20, 20), spend(30, 1)];
+        let statements: Vec<SpendStatement> = witnesses.iter().map(SpendWitness::statement).collect();
+        let proofs: Vec<ZkProof> = witnesses
+            .iter()
+            .zip(&statements)
+            .map(|(witness, statement)| prover.prove(statement, witness).unwrap())
+            .collect();
+
+        let batch = batch_proofs(proofs.clone()).unwrap();
+        assert_eq!(batch.circuit, "batch/private_transfer");
+        assert!(verifier.verify(&statements, &batch).unwrap());
+
+        // Statements out of order or missing do not match
+        let reordered = vec![statements[1], statements[0], statements[2]];
+        assert!(!verifier.verify(&reordered, &batch).unwrap());
+        assert!(!verifier.verify(&statements[..2].to_vec(), &batch).unwrap());
+
+        // Swapping in a proof of another statement breaks the product
+        let mut mixed = batch_proofs(vec![proofs[0].clone(), proofs[1].clone(), proofs[1].clone()]).unwrap();
+        mixed.public_inputs = batch.public_inputs.clone();
+        assert!(!verifier.verify(&statements, &mixed).unwrap());
+
+        assert!(batch_proofs(Vec::new()).is_err());
+        assert!(batch_proofs(vec![batch]).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Results of proof verifications, shared by every verifier that checks proofs.
+//!
+//! The same proof is checked when it is gossiped, when its block is validated and when
+//! it is submitted over RPC; the pairing check runs once and later checks are answered
+//! from the cache. Results are kept per verifying key and proof hash, and only for the
+//! current epoch: moving to a new epoch, such as a key rotation, drops every result.
+
+use crate::error::ZkProofError;
+use crate::ZkProof;
+use ark_bn254::Bn254;
+use ark_groth16::VerifyingKey;
+use ark_serialize::CanonicalSerialize;
+use hashing::Domain;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Verification cache statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub epoch: u64,
+}
+
+struct CacheState {
+    /// Result by verifying key and proof hash
+    results: LruCache<([u8; 32], [u8; 32]), bool>,
+    stats: VerificationCacheStats,
+}
+
+/// Bounded cache of verification results
+pub struct VerificationCache {
+    state: Mutex<CacheState>,
+}
+
+impl VerificationCache {
+    /// A cache keeping the results of the `capacity` most recently checked proofs
+    pub fn new(capacity: usize) -> Result<Self, ZkProofError> {
+        let capacity = NonZeroUsize::new(capacity)
+            .ok_or_else(|| ZkProofError::ServiceError("Verification cache capacity must be positive".to_string()))?;
+        Ok(Self {
+            state: Mutex::new(CacheState {
+                results: LruCache::new(capacity),
+                stats: VerificationCacheStats::default(),
+            }),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Move to `epoch`, dropping the results of earlier ones
+    pub fn set_epoch(&self, epoch: u64) {
+        let mut state = self.lock();
+        if state.stats.epoch != epoch {
+            state.results.clear();
+            state.stats.epoch = epoch;
+        }
+    }
+
+    pub fn stats(&self) -> VerificationCacheStats {
+        let state = self.lock();
+        VerificationCacheStats {
+            entries: state.results.len(),
+            ..state.stats
+        }
+    }
+
+    /// The cached result for `proof` under `key_id`, or the one `verify` returns, which is
+    /// kept unless the epoch changed meanwhile. Errors are not cached.
+    fn get_or_verify(
+        &self,
+        key_id: &[u8; 32],
+        proof: &ZkProof,
+        verify: impl FnOnce() -> Result<bool, ZkProofError>,
+    ) -> Result<bool, ZkProofError> {
+        let key = (*key_id, proof.hash());
+        let epoch = {
+            let mut state = self.lock();
+            if let Some(valid) = state.results.get(&key).copied() {
+                state.stats.hits += 1;
+                return Ok(valid);
+            }
+            state.stats.misses += 1;
+            state.stats.epoch
+        };
+        let valid = verify()?;
+        let mut state = self.lock();
+        if state.stats.epoch == epoch {
+            state.results.put(key, valid);
+        }
+        Ok(valid)
+    }
+}
+
+/// A verifier's view of a shared cache, which keys its results by the verifying key
+pub(crate) struct VerifierCache {
+    key_id: [u8; 32],
+    cache: Option<Arc<VerificationCache>>,
+}
+
+impl VerifierCache {
+    pub(crate) fn new(verifying_key: &VerifyingKey<Bn254>) -> Self {
+        let mut bytes = Vec::new();
+        verifying_key
+            .serialize_compressed(&mut bytes)
+            .expect("serializing a verifying key cannot fail");
+        Self {
+            key_id: hashing::hash(Domain::VerifyingKey, &bytes),
+            cache: None,
+        }
+    }
+
+    pub(crate) fn attach(&mut self, cache: Arc<VerificationCache>) {
+        self.cache = Some(cache);
+    }
+
+    /// Run `verify` for `proof` unless its result is cached
+    pub(crate) fn check(
+        &self,
+        proof: &ZkProof,
+        verify: impl FnOnce() -> Result<bool, ZkProofError>,
+    ) -> Result<bool, ZkProofError> {
+        match &self.cache {
+            Some(cache) => cache.get_or_verify(&self.key_id, proof, verify),
+            None => verify(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::privacy::{PrivateTransferKeys, PrivateTransferProver, PrivateTransferVerifier, SpendWitness};
+    use crate::{verify_private_transaction, Fr};
+
+    #[test]
+    fn test_verifications_are_cached_per_key_until_the_epoch_changes() {
+        let keys = PrivateTransferKeys::setup(&mut rand::rngs::OsRng).unwrap();
+        let other_keys = PrivateTransferKeys::setup(&mut rand::rngs::OsRng).unwrap();
+        let cache = Arc::new(VerificationCache::new(2).unwrap());
+        let verifier = PrivateTransferVerifier::new(keys.verifying_key()).with_cache(cache.clone());
+        let other_verifier = PrivateTransferVerifier::new(other_keys.verifying_key()).with_cache(cache.clone());
+        let prover = PrivateTransferProver::new(keys);
+        let witness = SpendWitness {
+            balance: 10,
+            balance_blinding: Fr::from(1u64),
+            amount: 4,
+            amount_blinding: Fr::from(2u64),
+            spending_key: Fr::from(3u64),
+        };
+        let transaction = prover.build_transaction(&witness, Vec::new()).unwrap();
+
+        // Gossip, block validation and RPC submission share one pairing check
+        for _ in 0..3 {
+            assert!(verify_private_transaction(&verifier, &transaction).unwrap());
+        }
+        assert_eq!(cache.stats(), VerificationCacheStats { hits: 2, misses: 1, entries: 1, epoch: 0 });
+
+        // A verifier with another key does not trust the first one's result
+        assert!(!verify_private_transaction(&other_verifier, &transaction).unwrap());
+        assert_eq!(cache.stats().entries, 2);
+
+        // Malformed proofs fail every time and are never cached
+        let mut truncated = transaction.clone();
+        truncated.validity_proof.proof.truncate(8);
+        assert!(verify_private_transaction(&verifier, &truncated).is_err());
+        assert!(verify_private_transaction(&verifier, &truncated).is_err());
+        assert_eq!(cache.stats().entries, 2);
+
+        cache.set_epoch(1);
+        assert_eq!(cache.stats().entries, 0);
+        assert!(verify_private_transaction(&verifier, &transaction).unwrap());
+        assert_eq!(cache.stats(), VerificationCacheStats { hits: 2, misses: 5, entries: 1, epoch: 1 });
+    }
+}
@@ -0,0 +1,147 @@
+//! Viewing keys for selective disclosure to auditors.
+//!
+//! A viewing key pairs the scan secret, which recognises incoming stealth outputs,
+//! with an outgoing key derived from the spend secret. A spender tags each ring
+//! input with the real output's one-time key masked under the outgoing key, so the
+//! viewing key also reveals which owned outputs were spent. Neither half can sign a
+//! ring input, so handing out a viewing key gives up no spend capability.
+//!
+//! Disclosures are signed with the scan key, binding them to the address they describe.
+
+use crate::error::ZkProofError;
+use crate::stealth::{decompress, hash_to_scalar, scalar, ViewKey};
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::scalar::Scalar;
+use hashing::{Domain, Hasher};
+
+fn tag_mask(outgoing_key: &[u8; 32], key_image: &[u8; 32]) -> [u8; 32] {
+    Hasher::new(Domain::Disclosure).fixed(outgoing_key).fixed(key_image).finish()
+}
+
+/// Audit tag of a ring input spending `one_time_key` with `key_image`
+pub fn outgoing_tag(outgoing_key: &[u8; 32], key_image: &[u8; 32], one_time_key: &[u8; 32]) -> [u8; 32] {
+    let mask = tag_mask(outgoing_key, key_image);
+    std::array::from_fn(|i| mask[i] ^ one_time_key[i])
+}
+
+fn signature_challenge(nonce_point: &[u8; 32], scan_public: &[u8; 32], message: &[u8]) -> Scalar {
+    hash_to_scalar(Domain::Disclosure, &[b"disclosure", nonce_point, scan_public, message])
+}
+
+/// Check a disclosure signature made by the holder of the scan key `scan_public`
+pub fn verify_disclosure_signature(scan_public: &[u8; 32], message: &[u8], signature: &[u8]) -> bool {
+    let Ok(signature) = <[u8; 64]>::try_from(signature) else {
+        return false;
+    };
+    let nonce_point: [u8; 32] = signature[..32].try_into().unwrap();
+    let (Ok(nonce), Ok(public), Ok(response)) = (
+        decompress(&nonce_point, "Signature nonce"),
+        decompress(scan_public, "Scan key"),
+        scalar(&signature[32..].try_into().unwrap(), "Signature response"),
+    ) else {
+        return false;
+    };
+    let challenge = signature_challenge(&nonce_point, scan_public, message);
+    &response * ED25519_BASEPOINT_TABLE == nonce + challenge * public
+}
+
+/// Key an account hands to an auditor: sees incoming and outgoing stealth transfers, cannot spend
+#[derive(Clone)]
+pub struct ViewingKey {
+    scan_secret: Scalar,
+    spend_public: [u8; 32],
+    outgoing_key: [u8; 32],
+}
+
+impl ViewingKey {
+    pub(crate) fn new(scan_secret: Scalar, spend_public: [u8; 32], outgoing_key: [u8; 32]) -> Self {
+        Self {
+            scan_secret,
+            spend_public,
+            outgoing_key,
+        }
+    }
+
+    /// Scan secret, spend key and outgoing key
+    pub fn to_bytes(&self) -> [u8; 96] {
+        let mut bytes = [0u8; 96];
+        bytes[..32].copy_from_slice(self.scan_secret.as_bytes());
+        bytes[32..64].copy_from_slice(&self.spend_public);
+        bytes[64..].copy_from_slice(&self.outgoing_key);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZkProofError> {
+        if bytes.len() != 96 {
+            return Err(ZkProofError::InvalidKey(format!("Viewing key has {} bytes, expected 96", bytes.len())));
+        }
+        let spend_public = bytes[32..64].try_into().unwrap();
+        decompress(&spend_public, "Spend key")?;
+        Ok(Self {
+            scan_secret: scalar(&bytes[..32].try_into().unwrap(), "Scan secret")?,
+            spend_public,
+            outgoing_key: bytes[64..].try_into().unwrap(),
+        })
+    }
+
+    pub fn scan_public(&self) -> [u8; 32] {
+        (&self.scan_secret * ED25519_BASEPOINT_TABLE).compress().to_bytes()
+    }
+
+    pub fn spend_public(&self) -> [u8; 32] {
+        self.spend_public
+    }
+
+    /// Incoming half of the key
+    pub fn view_key(&self) -> ViewKey {
+        ViewKey::from_bytes(self.scan_secret.as_bytes(), &self.spend_public)
+            .expect("viewing key holds a valid spend key")
+    }
+
+    /// One-time key of the output a ring input with `key_image` and `audit_tag` spent
+    pub fn spent_output(&self, key_image: &[u8; 32], audit_tag: &[u8; 32]) -> [u8; 32] {
+        outgoing_tag(&self.outgoing_key, key_image, audit_tag)
+    }
+
+    /// Sign a disclosure with the scan key. The nonce is derived from the key and
+    /// message, so the same disclosure always gets the same signature.
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let scan_public = self.scan_public();
+        let nonce = hash_to_scalar(Domain::Disclosure, &[b"disclosure-nonce", self.scan_secret.as_bytes(), message]);
+        let nonce_point = (&nonce * ED25519_BASEPOINT_TABLE).compress().to_bytes();
+        let response = nonce + signature_challenge(&nonce_point, &scan_public, message) * self.scan_secret;
+
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&nonce_point);
+        signature[32..].copy_from_slice(response.as_bytes());
+        signature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring::key_image;
+    use crate::stealth::StealthKeys;
+
+    #[test]
+    fn test_viewing_key_reveals_spends_and_signs_disclosures() {
+        let mut rng = rand::rngs::OsRng;
+        let owner = StealthKeys::generate(&mut rng);
+        let output = owner.address().derive_output(0, &mut rng).unwrap();
+        let image = key_image(&owner.one_time_secret(0, &output).unwrap()).unwrap();
+        let tag = owner.audit_tag(&image, &output.one_time_key);
+
+        let viewing_key = ViewingKey::from_bytes(&owner.viewing_key().to_bytes()).unwrap();
+        assert!(viewing_key.view_key().owns(0, &output.ephemeral_key, &output.one_time_key));
+        assert_eq!(viewing_key.spent_output(&image, &tag), output.one_time_key);
+        assert_ne!(StealthKeys::generate(&mut rng).viewing_key().spent_output(&image, &tag), output.one_time_key);
+        assert_eq!(viewing_key.scan_public(), owner.address().scan_public);
+
+        let signature = viewing_key.sign(b"report");
+        assert!(verify_disclosure_signature(&owner.address().scan_public, b"report", &signature));
+        assert!(!verify_disclosure_signature(&owner.address().scan_public, b"other report", &signature));
+        assert!(!verify_disclosure_signature(&owner.address().spend_public, b"report", &signature));
+        assert!(ViewingKey::from_bytes(&[0u8; 95]).is_err());
+    }
+}
@@ -0,0 +1,37 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum ZkProofError {
+    #[error("Invalid witness: {0}")]
+    InvalidWitness(String),
+
+    #[error("Invalid proof: {0}")]
+    InvalidProof(String),
+
+    #[error("Circuit synthesis failed: {0}")]
+    SynthesisError(String),
+
+    #[error("Proof queue full: {0}")]
+    QueueFull(String),
+
+    #[error("Prover service error: {0}")]
+    ServiceError(String),
+
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+}
+
+impl From<ark_relations::r1cs::SynthesisError> for ZkProofError {
+    fn from(err: ark_relations::r1cs::SynthesisError) -> Self {
+        ZkProofError::SynthesisError(err.to_string())
+    }
+}
+
+impl From<ark_serialize::SerializationError> for ZkProofError {
+    fn from(err: ark_serialize::SerializationError) -> Self {
+        ZkProofError::SerializationError(err.to_string())
+    }
+}
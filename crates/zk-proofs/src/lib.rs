@@ -0,0 +1,137 @@
+//! Zero-knowledge proofs for C0DL3: Groth16 over BN254 with circuits built from
+//! the Poseidon hash, behind the `ZkProofProver`/`ZkProofVerifier` traits.
+
+pub mod batch;
+pub mod cache;
+pub mod disclosure;
+pub mod error;
+pub mod poseidon;
+pub mod privacy;
+pub mod ring;
+pub mod service;
+pub mod state_transition;
+pub mod stealth;
+
+use ark_bn254::Bn254;
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use serde::{Deserialize, Serialize};
+
+pub use batch::{batch_proofs, BatchVerifier};
+pub use ark_bn254::Fr;
+pub use cache::{VerificationCache, VerificationCacheStats};
+pub use disclosure::{verify_disclosure_signature, ViewingKey};
+pub use error::ZkProofError;
+pub use poseidon::{HashDomain, Poseidon, ZkFriendlyHash};
+pub use privacy::{
+    verify_private_transaction, PrivateTransaction, PrivateTransferKeys, PrivateTransferProver,
+    PrivateTransferVerifier, SpendStatement, SpendWitness,
+};
+pub use ring::{key_image, select_ring, RingMember, RingSignature};
+pub use service::{JobStatus, ProofJobInfo, ProofPriority, ProverService, ProverServiceConfig, ProverServiceStats};
+pub use state_transition::{
+    LeafUpdate, StateTransition, StateTransitionKeys, StateTransitionProver, StateTransitionVerifier,
+    TransitionShape,
+};
+pub use stealth::{StealthAddress, StealthKeys, StealthOutput, ViewKey};
+
+/// A serialized proof together with the public inputs it was generated for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZkProof {
+    /// Name of the circuit the proof belongs to
+    pub circuit: String,
+    /// Public inputs as little-endian field elements
+    pub public_inputs: Vec<[u8; 32]>,
+    /// Compressed Groth16 proof
+    pub proof: Vec<u8>,
+}
+
+impl ZkProof {
+    /// Hash identifying the proof
+    pub fn hash(&self) -> [u8; 32] {
+        let hasher = hashing::Hasher::new(hashing::Domain::Proof)
+            .bytes(self.circuit.as_bytes())
+            .u64(self.public_inputs.len() as u64);
+        let hasher = self.public_inputs.iter().fold(hasher, |hasher, input| hasher.fixed(input));
+        hasher.bytes(&self.proof).finish()
+    }
+}
+
+/// Statements whose public inputs feed a Groth16 verifier
+pub trait PublicInputs {
+    fn public_inputs(&self) -> Vec<Fr>;
+}
+
+/// Generates proofs of a statement from a private witness
+pub trait ZkProofProver {
+    type Statement;
+    type Witness;
+
+    fn prove(&self, statement: &Self::Statement, witness: &Self::Witness) -> Result<ZkProof, ZkProofError>;
+}
+
+/// Checks proofs of a statement; `Ok(false)` means the proof does not hold
+pub trait ZkProofVerifier {
+    type Statement;
+
+    fn verify(&self, statement: &Self::Statement, proof: &ZkProof) -> Result<bool, ZkProofError>;
+}
+
+/// Encode a field element as 32 little-endian bytes
+pub fn field_to_bytes(value: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    value
+        .serialize_compressed(&mut bytes[..])
+        .expect("field elements fit in 32 bytes");
+    bytes
+}
+
+/// Decode a field element, rejecting non-canonical encodings
+pub fn field_from_bytes(bytes: &[u8; 32]) -> Result<Fr, ZkProofError> {
+    Ok(Fr::deserialize_compressed(&bytes[..])?)
+}
+
+/// Map an arbitrary 32-byte hash into the field
+pub fn field_from_hash(hash: &[u8; 32]) -> Fr {
+    use ark_ff::PrimeField;
+    Fr::from_le_bytes_mod_order(hash)
+}
+
+/// Wrap a Groth16 proof for `circuit`
+fn encode_proof(circuit: &str, inputs: &[Fr], proof: &Proof<Bn254>) -> Result<ZkProof, ZkProofError> {
+    let mut bytes = Vec::new();
+    proof.serialize_compressed(&mut bytes)?;
+    Ok(ZkProof {
+        circuit: circuit.to_string(),
+        public_inputs: inputs.iter().map(field_to_bytes).collect(),
+        proof: bytes,
+    })
+}
+
+/// Check a Groth16 proof of `circuit` against the statement's public inputs, answering
+/// from `cache` when the proof was checked before
+fn verify_groth16(
+    verifying_key: &PreparedVerifyingKey<Bn254>,
+    cache: &cache::VerifierCache,
+    circuit: &str,
+    inputs: &[Fr],
+    proof: &ZkProof,
+) -> Result<bool, ZkProofError> {
+    if proof.circuit != circuit {
+        return Err(ZkProofError::InvalidProof(format!("Expected a {} proof, got {}", circuit, proof.circuit)));
+    }
+    let declared = proof
+        .public_inputs
+        .iter()
+        .map(field_from_bytes)
+        .collect::<Result<Vec<_>, _>>()?;
+    if declared != inputs {
+        return Ok(false);
+    }
+    cache.check(proof, || {
+        let groth16_proof = Proof::<Bn254>::deserialize_compressed(&proof.proof[..])
+            .map_err(|e| ZkProofError::InvalidProof(e.to_string()))?;
+        Ok(Groth16::<Bn254>::verify_with_processed_vk(verifying_key, inputs, &groth16_proof)?)
+    })
+}
@@ -0,0 +1,167 @@
+//! Poseidon over the BN254 scalar field, natively and as an R1CS gadget.
+//!
+//! The parameters are circomlib's (x^5 S-box, 8 full rounds), so hashes match circuits
+//! and on-chain verifiers built from it. The capacity element carries a tag made of
+//! `HASH_VERSION` and a `HashDomain`: a note commitment can never pass for a nullifier
+//! or a tree node, and hashes from a future parameter set cannot collide with today's.
+//! One permutation costs about 240 constraints for two inputs, against 364 for MiMC-7.
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_relations::r1cs::SynthesisError;
+use light_poseidon::parameters::bn254_x5;
+use light_poseidon::{PoseidonHasher, PoseidonParameters};
+use std::sync::OnceLock;
+
+/// Version of the hash parameters and domain tags; bump it when either changes
+pub const HASH_VERSION: u64 = 1;
+
+/// Most field elements one permutation absorbs
+pub const MAX_INPUTS: usize = 12;
+
+/// Bytes packed into one field element, few enough that every packing is canonical
+const BYTES_PER_ELEMENT: usize = 31;
+
+/// What a hash is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashDomain {
+    SpendKey = 1,
+    ValueCommitment = 2,
+    NoteCommitment = 3,
+    Nullifier = 4,
+    MerkleNode = 5,
+    HeatCommitment = 6,
+    YieldCommitment = 7,
+    /// Hash of the data a commitment is made over
+    AuxData = 8,
+}
+
+impl HashDomain {
+    /// Capacity element the permutation starts from
+    pub fn tag(self) -> Fr {
+        Fr::from((HASH_VERSION << 32) | self as u64)
+    }
+}
+
+/// Hash that is cheap to prove inside a circuit
+pub trait ZkFriendlyHash {
+    /// Hash 1 to `MAX_INPUTS` field elements under `domain`
+    fn hash(domain: HashDomain, inputs: &[Fr]) -> Fr;
+
+    /// In-circuit `hash`
+    fn hash_gadget(domain: HashDomain, inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError>;
+
+    /// Two-to-one hash
+    fn hash2(domain: HashDomain, left: Fr, right: Fr) -> Fr {
+        Self::hash(domain, &[left, right])
+    }
+
+    /// Hash bytes of any length, packed 31 to a field element and absorbed in a chain
+    /// that starts from their length
+    fn hash_bytes(domain: HashDomain, data: &[u8]) -> Fr {
+        let elements: Vec<Fr> = data.chunks(BYTES_PER_ELEMENT).map(Fr::from_le_bytes_mod_order).collect();
+        let mut digest = Fr::from(data.len() as u64);
+        if elements.is_empty() {
+            return Self::hash(domain, &[digest]);
+        }
+        for chunk in elements.chunks(MAX_INPUTS - 1) {
+            let inputs: Vec<Fr> = std::iter::once(digest).chain(chunk.iter().copied()).collect();
+            digest = Self::hash(domain, &inputs);
+        }
+        digest
+    }
+}
+
+/// Parameters for each state width, from 2 (one input) up
+fn parameters(width: usize) -> &'static PoseidonParameters<Fr> {
+    static PARAMETERS: OnceLock<Vec<PoseidonParameters<Fr>>> = OnceLock::new();
+    let all = PARAMETERS.get_or_init(|| {
+        (2..=MAX_INPUTS as u8 + 1)
+            .map(|width| bn254_x5::get_poseidon_parameters::<Fr>(width).expect("circom widths go up to 13"))
+            .collect()
+    });
+    &all[width - 2]
+}
+
+/// Poseidon with circomlib's BN254 parameters
+pub struct Poseidon;
+
+impl ZkFriendlyHash for Poseidon {
+    /// Panics unless `inputs` holds 1 to `MAX_INPUTS` elements
+    fn hash(domain: HashDomain, inputs: &[Fr]) -> Fr {
+        assert!((1..=MAX_INPUTS).contains(&inputs.len()), "Poseidon takes 1 to {} inputs", MAX_INPUTS);
+        light_poseidon::Poseidon::<Fr>::with_domain_tag_circom(inputs.len(), domain.tag())
+            .and_then(|mut hasher| hasher.hash(inputs))
+            .expect("input count was checked")
+    }
+
+    fn hash_gadget(domain: HashDomain, inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+        if inputs.is_empty() || inputs.len() > MAX_INPUTS {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let width = inputs.len() + 1;
+        let params = parameters(width);
+        let mut state: Vec<FpVar<Fr>> = std::iter::once(FpVar::Constant(domain.tag()))
+            .chain(inputs.iter().cloned())
+            .collect();
+
+        let half_full = params.full_rounds / 2;
+        for round in 0..params.full_rounds + params.partial_rounds {
+            for (i, element) in state.iter_mut().enumerate() {
+                *element += params.ark[round * width + i];
+            }
+            // Partial rounds only raise the first element to the fifth power
+            let full = round < half_full || round >= half_full + params.partial_rounds;
+            for element in state.iter_mut().take(if full { width } else { 1 }) {
+                let square = &*element * &*element;
+                *element = &square * &square * &*element;
+            }
+            state = params
+                .mds
+                .iter()
+                .map(|row| state.iter().zip(row).fold(FpVar::Constant(Fr::from(0u64)), |sum, (x, m)| sum + x * *m))
+                .collect();
+        }
+        Ok(state.swap_remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_r1cs_std::alloc::AllocVar;
+    use ark_r1cs_std::R1CSVar;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn test_gadget_matches_native_hash() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        for count in [1, 2, 3, MAX_INPUTS] {
+            let inputs: Vec<Fr> = (0..count as u64).map(|i| Fr::from(i * 7 + 1)).collect();
+            let vars: Vec<FpVar<Fr>> =
+                inputs.iter().map(|input| FpVar::new_witness(cs.clone(), || Ok(*input)).unwrap()).collect();
+            let hashed = Poseidon::hash_gadget(HashDomain::Nullifier, &vars).unwrap();
+            assert_eq!(hashed.value().unwrap(), Poseidon::hash(HashDomain::Nullifier, &inputs));
+        }
+        assert!(cs.is_satisfied().unwrap());
+        assert!(Poseidon::hash_gadget(HashDomain::Nullifier, &[]).is_err());
+    }
+
+    #[test]
+    fn test_domains_and_lengths_separate_hashes() {
+        let (left, right) = (Fr::from(1u64), Fr::from(2u64));
+        assert_ne!(
+            Poseidon::hash2(HashDomain::NoteCommitment, left, right),
+            Poseidon::hash2(HashDomain::Nullifier, left, right)
+        );
+
+        let data = [7u8; 400];
+        assert_eq!(Poseidon::hash_bytes(HashDomain::AuxData, &data), Poseidon::hash_bytes(HashDomain::AuxData, &data));
+        assert_ne!(
+            Poseidon::hash_bytes(HashDomain::AuxData, &data),
+            Poseidon::hash_bytes(HashDomain::AuxData, &data[..399])
+        );
+        assert_ne!(Poseidon::hash_bytes(HashDomain::AuxData, &[]), Poseidon::hash_bytes(HashDomain::AuxData, &[0]));
+    }
+}
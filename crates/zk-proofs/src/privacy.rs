@@ -0,0 +1,354 @@
+//! Private transfer validity: proves that a hidden balance covers a hidden
+//! amount and that the spend's nullifier was derived from the spent note.
+//!
+//! A note commits to its value, a blinding factor and the owner's spend public
+//! key. Spending reveals only the note commitment, a commitment to the amount
+//! and the nullifier `hash(spending_key, note_commitment)`, which is unique per
+//! note so the note cannot be spent twice without the repeat being visible.
+
+use crate::cache::{VerificationCache, VerifierCache};
+use crate::error::ZkProofError;
+use crate::poseidon::{HashDomain, Poseidon, ZkFriendlyHash};
+use crate::{
+    encode_proof, field_from_bytes, field_to_bytes, verify_groth16, PublicInputs, ZkProof, ZkProofProver,
+    ZkProofVerifier,
+};
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{Groth16, PreparedVerifyingKey, ProvingKey, VerifyingKey};
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::boolean::Boolean;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Circuit name recorded in proofs
+pub const CIRCUIT_NAME: &str = "private_transfer";
+
+/// Public key a note is locked to
+pub fn spend_public_key(spending_key: Fr) -> Fr {
+    Poseidon::hash(HashDomain::SpendKey, &[spending_key])
+}
+
+/// Commitment to a value under a blinding factor
+pub fn value_commitment(value: u64, blinding: Fr) -> Fr {
+    Poseidon::hash2(HashDomain::ValueCommitment, Fr::from(value), blinding)
+}
+
+/// Commitment to a note of `value` owned by `owner`
+pub fn note_commitment(value: u64, blinding: Fr, owner: Fr) -> Fr {
+    Poseidon::hash2(HashDomain::NoteCommitment, value_commitment(value, blinding), owner)
+}
+
+/// Nullifier published when the note is spent
+pub fn derive_nullifier(spending_key: Fr, note_commitment: Fr) -> Fr {
+    Poseidon::hash2(HashDomain::Nullifier, spending_key, note_commitment)
+}
+
+/// Public statement of a spend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpendStatement {
+    pub note_commitment: Fr,
+    pub amount_commitment: Fr,
+    pub nullifier: Fr,
+}
+
+impl PublicInputs for SpendStatement {
+    fn public_inputs(&self) -> Vec<Fr> {
+        vec![self.note_commitment, self.amount_commitment, self.nullifier]
+    }
+}
+
+/// Private data of a spend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendWitness {
+    pub balance: u64,
+    pub balance_blinding: Fr,
+    pub amount: u64,
+    pub amount_blinding: Fr,
+    pub spending_key: Fr,
+}
+
+impl SpendWitness {
+    /// Statement this witness proves
+    pub fn statement(&self) -> SpendStatement {
+        let note = note_commitment(self.balance, self.balance_blinding, spend_public_key(self.spending_key));
+        SpendStatement {
+            note_commitment: note,
+            amount_commitment: value_commitment(self.amount, self.amount_blinding),
+            nullifier: derive_nullifier(self.spending_key, note),
+        }
+    }
+}
+
+/// Allocate a witness as 64 bits so it cannot wrap around the field
+fn u64_witness(cs: ConstraintSystemRef<Fr>, value: u64) -> Result<FpVar<Fr>, SynthesisError> {
+    let bits = (0..64)
+        .map(|i| Boolean::new_witness(cs.clone(), || Ok((value >> i) & 1 == 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Boolean::le_bits_to_fp_var(&bits)
+}
+
+struct PrivateTransferCircuit {
+    statement: SpendStatement,
+    witness: SpendWitness,
+}
+
+impl PrivateTransferCircuit {
+    /// All-zero assignment used for key generation
+    fn blank() -> Self {
+        let witness = SpendWitness {
+            balance: 0,
+            balance_blinding: Fr::from(0u64),
+            amount: 0,
+            amount_blinding: Fr::from(0u64),
+            spending_key: Fr::from(0u64),
+        };
+        Self { statement: witness.statement(), witness }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for PrivateTransferCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let note = FpVar::new_input(cs.clone(), || Ok(self.statement.note_commitment))?;
+        let amount_commitment = FpVar::new_input(cs.clone(), || Ok(self.statement.amount_commitment))?;
+        let nullifier = FpVar::new_input(cs.clone(), || Ok(self.statement.nullifier))?;
+
+        let witness = self.witness;
+        let balance = u64_witness(cs.clone(), witness.balance)?;
+        let amount = u64_witness(cs.clone(), witness.amount)?;
+        let balance_blinding = FpVar::new_witness(cs.clone(), || Ok(witness.balance_blinding))?;
+        let amount_blinding = FpVar::new_witness(cs.clone(), || Ok(witness.amount_blinding))?;
+        let spending_key = FpVar::new_witness(cs.clone(), || Ok(witness.spending_key))?;
+
+        // balance - amount must itself fit in 64 bits, i.e. the balance covers the amount
+        let change = u64_witness(cs.clone(), witness.balance.saturating_sub(witness.amount))?;
+        (&balance - &amount).enforce_equal(&change)?;
+
+        let owner = Poseidon::hash_gadget(HashDomain::SpendKey, std::slice::from_ref(&spending_key))?;
+        let balance_commitment = Poseidon::hash_gadget(HashDomain::ValueCommitment, &[balance, balance_blinding])?;
+        Poseidon::hash_gadget(HashDomain::NoteCommitment, &[balance_commitment, owner])?.enforce_equal(&note)?;
+        Poseidon::hash_gadget(HashDomain::ValueCommitment, &[amount, amount_blinding])?
+            .enforce_equal(&amount_commitment)?;
+        Poseidon::hash_gadget(HashDomain::Nullifier, &[spending_key, note])?.enforce_equal(&nullifier)
+    }
+}
+
+/// Groth16 proving key of the private transfer circuit
+#[derive(Clone)]
+pub struct PrivateTransferKeys {
+    pub proving_key: ProvingKey<Bn254>,
+}
+
+impl PrivateTransferKeys {
+    /// Run the circuit-specific setup. Whoever knows `rng`'s output can forge
+    /// proofs, so production keys must come from a trusted ceremony.
+    pub fn setup<R: RngCore + CryptoRng>(rng: &mut R) -> Result<Self, ZkProofError> {
+        let (proving_key, _) = Groth16::<Bn254>::circuit_specific_setup(PrivateTransferCircuit::blank(), rng)?;
+        Ok(Self { proving_key })
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        &self.proving_key.vk
+    }
+
+    /// Serialize the compressed proving key
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ZkProofError> {
+        let mut bytes = Vec::new();
+        self.proving_key.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZkProofError> {
+        Ok(Self { proving_key: ProvingKey::deserialize_compressed(bytes)? })
+    }
+
+    /// Serialize the compressed verifying key handed to verifiers
+    pub fn verifying_key_bytes(&self) -> Result<Vec<u8>, ZkProofError> {
+        let mut bytes = Vec::new();
+        self.verifying_key().serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// A shielded transfer: public commitments, the nullifier and its validity proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateTransaction {
+    pub note_commitment: [u8; 32],
+    pub amount_commitment: [u8; 32],
+    pub nullifier: [u8; 32],
+    /// Recipient details, encrypted to the recipient
+    pub encrypted_recipient: Vec<u8>,
+    pub validity_proof: ZkProof,
+}
+
+impl PrivateTransaction {
+    /// Statement the validity proof must prove
+    pub fn statement(&self) -> Result<SpendStatement, ZkProofError> {
+        Ok(SpendStatement {
+            note_commitment: field_from_bytes(&self.note_commitment)?,
+            amount_commitment: field_from_bytes(&self.amount_commitment)?,
+            nullifier: field_from_bytes(&self.nullifier)?,
+        })
+    }
+}
+
+/// Proves private transfers with a proving key
+pub struct PrivateTransferProver {
+    keys: PrivateTransferKeys,
+}
+
+impl PrivateTransferProver {
+    pub fn new(keys: PrivateTransferKeys) -> Self {
+        Self { keys }
+    }
+
+    /// Prove `witness` and wrap it in a transaction
+    pub fn build_transaction(
+        &self,
+        witness: &SpendWitness,
+        encrypted_recipient: Vec<u8>,
+    ) -> Result<PrivateTransaction, ZkProofError> {
+        let statement = witness.statement();
+        Ok(PrivateTransaction {
+            note_commitment: field_to_bytes(&statement.note_commitment),
+            amount_commitment: field_to_bytes(&statement.amount_commitment),
+            nullifier: field_to_bytes(&statement.nullifier),
+            encrypted_recipient,
+            validity_proof: self.prove(&statement, witness)?,
+        })
+    }
+}
+
+impl ZkProofProver for PrivateTransferProver {
+    type Statement = SpendStatement;
+    type Witness = SpendWitness;
+
+    fn prove(&self, statement: &SpendStatement, witness: &SpendWitness) -> Result<ZkProof, ZkProofError> {
+        if witness.balance < witness.amount {
+            return Err(ZkProofError::InvalidWitness(format!(
+                "Balance {} does not cover amount {}",
+                witness.balance, witness.amount
+            )));
+        }
+        if witness.statement() != *statement {
+            return Err(ZkProofError::InvalidWitness("Witness does not open the statement".to_string()));
+        }
+        let circuit = PrivateTransferCircuit { statement: *statement, witness: witness.clone() };
+        let proof = Groth16::<Bn254>::prove(&self.keys.proving_key, circuit, &mut rand::rngs::OsRng)?;
+        encode_proof(CIRCUIT_NAME, &statement.public_inputs(), &proof)
+    }
+}
+
+/// Verifies private transfer proofs with a prepared verifying key
+pub struct PrivateTransferVerifier {
+    verifying_key: PreparedVerifyingKey<Bn254>,
+    cache: VerifierCache,
+}
+
+impl PrivateTransferVerifier {
+    pub fn new(verifying_key: &VerifyingKey<Bn254>) -> Self {
+        Self {
+            verifying_key: Groth16::<Bn254>::process_vk(verifying_key).expect("processing a verifying key cannot fail"),
+            cache: VerifierCache::new(verifying_key),
+        }
+    }
+
+    /// Share verification results through `cache`
+    pub fn with_cache(mut self, cache: Arc<VerificationCache>) -> Self {
+        self.cache.attach(cache);
+        self
+    }
+
+    /// Build a verifier from `PrivateTransferKeys::verifying_key_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZkProofError> {
+        Ok(Self::new(&VerifyingKey::deserialize_compressed(bytes)?))
+    }
+}
+
+impl ZkProofVerifier for PrivateTransferVerifier {
+    type Statement = SpendStatement;
+
+    fn verify(&self, statement: &SpendStatement, proof: &ZkProof) -> Result<bool, ZkProofError> {
+        verify_groth16(&self.verifying_key, &self.cache, CIRCUIT_NAME, &statement.public_inputs(), proof)
+    }
+}
+
+/// Check that a private transaction's validity proof holds for its public fields
+pub fn verify_private_transaction(
+    verifier: &PrivateTransferVerifier,
+    transaction: &PrivateTransaction,
+) -> Result<bool, ZkProofError> {
+    let statement = match transaction.statement() {
+        Ok(statement) => statement,
+        Err(_) => return Ok(false),
+    };
+    verifier.verify(&statement, &transaction.validity_proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn witness(balance: u64, amount: u64) -> SpendWitness {
+        SpendWitness {
+            balance,
+            balance_blinding: Fr::from(0xb1u64),
+            amount,
+            amount_blinding: Fr::from(0xa1u64),
+            spending_key: Fr::from(0x5eu64),
+        }
+    }
+
+    #[test]
+    fn test_private_transaction_verifies() {
+        let keys = PrivateTransferKeys::setup(&mut rand::rngs::OsRng).unwrap();
+        let verifier = PrivateTransferVerifier::from_bytes(&keys.verifying_key_bytes().unwrap()).unwrap();
+        let prover = PrivateTransferProver::new(PrivateTransferKeys::from_bytes(&keys.to_bytes().unwrap()).unwrap());
+
+        let transaction = prover.build_transaction(&witness(100, 100), vec![1, 2, 3]).unwrap();
+        assert!(verify_private_transaction(&verifier, &transaction).unwrap());
+
+        // Public fields cannot be swapped out from under the proof
+        let mut other_nullifier = transaction.clone();
+        other_nullifier.nullifier = field_to_bytes(&Fr::from(1u64));
+        assert!(!verify_private_transaction(&verifier, &other_nullifier).unwrap());
+        let mut other_amount = transaction.clone();
+        other_amount.amount_commitment = field_to_bytes(&value_commitment(1, Fr::from(0xa1u64)));
+        other_amount.validity_proof.public_inputs[1] = other_amount.amount_commitment;
+        assert!(!verify_private_transaction(&verifier, &other_amount).unwrap());
+        let mut empty_proof = transaction;
+        empty_proof.validity_proof.proof.clear();
+        assert!(verify_private_transaction(&verifier, &empty_proof).is_err());
+
+        let overdraft = prover.build_transaction(&witness(99, 100), Vec::new());
+        assert!(matches!(overdraft, Err(ZkProofError::InvalidWitness(_))));
+    }
+
+    #[test]
+    fn test_circuit_enforces_balance_and_nullifier() {
+        let satisfied = |statement: SpendStatement, witness: SpendWitness| {
+            let cs = ConstraintSystem::new_ref();
+            PrivateTransferCircuit { statement, witness }.generate_constraints(cs.clone()).unwrap();
+            cs.is_satisfied().unwrap()
+        };
+
+        let valid = witness(u64::MAX, 5);
+        assert!(satisfied(valid.statement(), valid));
+
+        // An overdraft fails the range check even with consistent commitments
+        let overdraft = witness(4, 5);
+        assert!(!satisfied(overdraft.statement(), overdraft));
+
+        // A nullifier under another key does not match the note
+        let valid = witness(10, 5);
+        let mut statement = valid.statement();
+        statement.nullifier = derive_nullifier(Fr::from(0x5fu64), statement.note_commitment);
+        assert!(!satisfied(statement, valid));
+    }
+}
@@ -0,0 +1,245 @@
+//! Linkable ring signatures (LSAG) over stealth one-time keys, and the decoy
+//! selection wallets use to build rings.
+//!
+//! A spend signs with the secret of one ring member without revealing which.
+//! The key image `x * Hp(P)` is the same whichever ring the output is spent in,
+//! so it serves as the spend's nullifier.
+
+use crate::error::ZkProofError;
+use crate::stealth::{decompress, hash_to_scalar, random_scalar, scalar};
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use hashing::{Domain, Hasher};
+use rand::{CryptoRng, Rng, RngCore};
+use std::collections::HashSet;
+
+/// Point with unknown discrete log derived from a one-time key
+fn hash_to_point(one_time_key: &[u8; 32]) -> RistrettoPoint {
+    RistrettoPoint::from_uniform_bytes(&Hasher::new(Domain::RingKeyImage).fixed(one_time_key).finish_wide())
+}
+
+/// Challenge chaining one ring position to the next
+fn challenge(prefix: &[u8; 32], left: &EdwardsPoint, right: &RistrettoPoint) -> Scalar {
+    hash_to_scalar(Domain::RingChallenge, &[prefix, left.compress().as_bytes(), right.compress().as_bytes()])
+}
+
+/// Everything the signature commits to besides the per-member points
+fn signing_prefix(message: &[u8], ring: &[[u8; 32]], key_image: &[u8; 32]) -> [u8; 32] {
+    let hasher = Hasher::new(Domain::RingPrefix).bytes(message).u64(ring.len() as u64);
+    ring.iter().fold(hasher, |hasher, member| hasher.fixed(member)).fixed(key_image).finish()
+}
+
+/// Key image of the output with one-time secret `secret`
+pub fn key_image(secret: &[u8; 32]) -> Result<[u8; 32], ZkProofError> {
+    let secret = scalar(secret, "One-time secret")?;
+    let one_time_key = (&secret * ED25519_BASEPOINT_TABLE).compress().to_bytes();
+    Ok((secret * hash_to_point(&one_time_key)).compress().to_bytes())
+}
+
+/// Signature proving knowledge of the secret of one ring member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingSignature {
+    pub key_image: [u8; 32],
+    pub challenge: [u8; 32],
+    /// One response per ring member
+    pub responses: Vec<[u8; 32]>,
+}
+
+impl RingSignature {
+    /// Sign `message` with the one-time secret of `ring[real_index]`
+    pub fn sign<R: RngCore + CryptoRng>(
+        message: &[u8],
+        ring: &[[u8; 32]],
+        real_index: usize,
+        secret: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<Self, ZkProofError> {
+        let secret_scalar = scalar(secret, "One-time secret")?;
+        if ring.get(real_index) != Some(&(&secret_scalar * ED25519_BASEPOINT_TABLE).compress().to_bytes()) {
+            return Err(ZkProofError::InvalidWitness("Secret does not match the real ring member".to_string()));
+        }
+        let members = ring
+            .iter()
+            .map(|member| decompress(member, "Ring member"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let image_point = secret_scalar * hash_to_point(&ring[real_index]);
+        let key_image = image_point.compress().to_bytes();
+        let prefix = signing_prefix(message, ring, &key_image);
+
+        let n = ring.len();
+        let mut challenges = vec![Scalar::ZERO; n];
+        let mut responses = vec![Scalar::ZERO; n];
+        let nonce = random_scalar(rng);
+        challenges[(real_index + 1) % n] = challenge(
+            &prefix,
+            &(&nonce * ED25519_BASEPOINT_TABLE),
+            &(nonce * hash_to_point(&ring[real_index])),
+        );
+        for step in 1..n {
+            let i = (real_index + step) % n;
+            responses[i] = random_scalar(rng);
+            let left = &responses[i] * ED25519_BASEPOINT_TABLE + challenges[i] * members[i];
+            let right = responses[i] * hash_to_point(&ring[i]) + challenges[i] * image_point;
+            challenges[(i + 1) % n] = challenge(&prefix, &left, &right);
+        }
+        responses[real_index] = nonce - challenges[real_index] * secret_scalar;
+
+        Ok(Self {
+            key_image,
+            challenge: challenges[0].to_bytes(),
+            responses: responses.iter().map(Scalar::to_bytes).collect(),
+        })
+    }
+
+    /// Check the signature over `message` by some member of `ring`
+    pub fn verify(&self, message: &[u8], ring: &[[u8; 32]]) -> bool {
+        if ring.is_empty() || self.responses.len() != ring.len() {
+            return false;
+        }
+        let Some(image_point) = CompressedRistretto(self.key_image).decompress() else {
+            return false;
+        };
+        if image_point.is_identity() {
+            return false;
+        }
+        let Ok(first) = scalar(&self.challenge, "Challenge") else {
+            return false;
+        };
+        let prefix = signing_prefix(message, ring, &self.key_image);
+
+        let mut current = first;
+        for (member, response) in ring.iter().zip(&self.responses) {
+            let Ok(point) = decompress(member, "Ring member") else {
+                return false;
+            };
+            let Ok(response) = scalar(response, "Response") else {
+                return false;
+            };
+            if !point.is_torsion_free() {
+                return false;
+            }
+            let left = &response * ED25519_BASEPOINT_TABLE + current * point;
+            let right = response * hash_to_point(member) + current * image_point;
+            current = challenge(&prefix, &left, &right);
+        }
+        current == first
+    }
+
+    /// Challenge followed by the responses, as carried in a transaction next to the key image
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.challenge.to_vec();
+        for response in &self.responses {
+            bytes.extend_from_slice(response);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(key_image: [u8; 32], bytes: &[u8]) -> Result<Self, ZkProofError> {
+        if bytes.len() < 64 || !bytes.len().is_multiple_of(32) {
+            return Err(ZkProofError::InvalidProof(format!("Ring signature has {} bytes", bytes.len())));
+        }
+        let mut chunks = bytes.chunks_exact(32).map(|chunk| <[u8; 32]>::try_from(chunk).unwrap());
+        Ok(Self {
+            key_image,
+            challenge: chunks.next().unwrap(),
+            responses: chunks.collect(),
+        })
+    }
+}
+
+/// An output that can appear in a ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingMember {
+    pub one_time_key: [u8; 32],
+    /// Height of the block that created the output
+    pub height: u64,
+}
+
+/// Pick `ring_size - 1` decoys for `real` from `candidates` and return the ring in
+/// canonical order with the position of the real output.
+///
+/// Decoys are drawn without replacement with weight `1 / (1 + age)`, mirroring how
+/// recently created outputs are far more likely to be the ones being spent, so the real
+/// output does not stand out as the youngest member.
+pub fn select_ring<R: RngCore>(
+    real: &RingMember,
+    candidates: &[RingMember],
+    ring_size: usize,
+    current_height: u64,
+    rng: &mut R,
+) -> Result<(Vec<[u8; 32]>, usize), ZkProofError> {
+    if ring_size == 0 {
+        return Err(ZkProofError::InvalidWitness("Ring size must be at least 1".to_string()));
+    }
+    let mut seen = HashSet::from([real.one_time_key]);
+    let mut keyed: Vec<(f64, [u8; 32])> = candidates
+        .iter()
+        .filter(|candidate| seen.insert(candidate.one_time_key))
+        .map(|candidate| {
+            let weight = 1.0 / (1.0 + current_height.saturating_sub(candidate.height) as f64);
+            // Efraimidis-Spirakis: the largest u^(1/w) form a weighted sample
+            let u: f64 = rng.gen_range(f64::MIN_POSITIVE..1.0);
+            (u.ln() / weight, candidate.one_time_key)
+        })
+        .collect();
+    if keyed.len() < ring_size - 1 {
+        return Err(ZkProofError::InvalidWitness(format!(
+            "Only {} decoys available for a ring of {}",
+            keyed.len(),
+            ring_size
+        )));
+    }
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut ring: Vec<[u8; 32]> = keyed[..ring_size - 1].iter().map(|(_, key)| *key).collect();
+    ring.push(real.one_time_key);
+    ring.sort();
+    let real_index = ring.iter().position(|key| *key == real.one_time_key).unwrap();
+    Ok((ring, real_index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stealth::StealthKeys;
+
+    #[test]
+    fn test_ring_signature_hides_signer_and_links_spends() {
+        let mut rng = rand::rngs::OsRng;
+        let owner = StealthKeys::generate(&mut rng);
+        let output = owner.address().derive_output(0, &mut rng).unwrap();
+        let secret = owner.one_time_secret(0, &output).unwrap();
+
+        let candidates: Vec<RingMember> = (0..20u64)
+            .map(|height| RingMember {
+                one_time_key: StealthKeys::generate(&mut rng).address().derive_output(0, &mut rng).unwrap().one_time_key,
+                height,
+            })
+            .collect();
+        let real = RingMember { one_time_key: output.one_time_key, height: 5 };
+        let (ring, real_index) = select_ring(&real, &candidates, 11, 20, &mut rng).unwrap();
+        assert_eq!(ring.len(), 11);
+        assert_eq!(ring.iter().collect::<HashSet<_>>().len(), 11);
+        assert!(select_ring(&real, &candidates, 22, 20, &mut rng).is_err());
+
+        let signature = RingSignature::sign(b"tx", &ring, real_index, &secret, &mut rng).unwrap();
+        let decoded = RingSignature::from_bytes(signature.key_image, &signature.to_bytes()).unwrap();
+        assert!(decoded.verify(b"tx", &ring));
+        assert!(!decoded.verify(b"other tx", &ring));
+        assert_eq!(signature.key_image, key_image(&secret).unwrap());
+
+        // Spending the same output in another ring reveals the same key image
+        let (other_ring, other_index) = select_ring(&real, &candidates, 5, 20, &mut rng).unwrap();
+        let again = RingSignature::sign(b"tx2", &other_ring, other_index, &secret, &mut rng).unwrap();
+        assert_eq!(again.key_image, signature.key_image);
+
+        // A forged key image or a signer outside the ring fails
+        let mut forged = signature.clone();
+        forged.key_image = key_image(&StealthKeys::from_seed(b"x").scan_secret()).unwrap();
+        assert!(!forged.verify(b"tx", &ring));
+        assert!(RingSignature::sign(b"tx", &ring, (real_index + 1) % 11, &secret, &mut rng).is_err());
+    }
+}
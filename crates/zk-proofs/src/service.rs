@@ -0,0 +1,449 @@
+//! Prover service: a bounded, prioritized queue of proving jobs drained by a
+//! pool of dedicated worker threads, so proof generation never blocks mining.
+//!
+//! Block proofs are taken before transaction proofs and jobs of one priority
+//! run in submission order. When the queue fills up, new jobs are rejected and
+//! the backpressure signal turns on until a worker frees a slot.
+
+use crate::error::ZkProofError;
+use crate::{ZkProof, ZkProofProver};
+use metrics::Metrics;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// Prover service configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProverServiceConfig {
+    /// Number of proving threads
+    pub workers: usize,
+    /// Jobs that may wait for a worker before submissions are rejected
+    pub queue_capacity: usize,
+    /// Finished jobs whose status stays queryable
+    pub retained_jobs: usize,
+}
+
+impl Default for ProverServiceConfig {
+    fn default() -> Self {
+        Self {
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            queue_capacity: 64,
+            retained_jobs: 1024,
+        }
+    }
+}
+
+/// Proving priority; block proofs are served first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ProofPriority {
+    Transaction,
+    Block,
+}
+
+impl ProofPriority {
+    fn label(&self) -> &'static str {
+        match self {
+            ProofPriority::Transaction => "transaction",
+            ProofPriority::Block => "block",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed(ZkProof),
+    Failed(String),
+}
+
+/// Status of one proving job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofJobInfo {
+    pub job_id: u64,
+    pub priority: ProofPriority,
+    pub status: JobStatus,
+    /// Unix time the job was submitted
+    pub submitted_at: u64,
+    /// Time spent proving, once finished
+    pub proving_time_ms: Option<u64>,
+}
+
+/// Prover service statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProverServiceStats {
+    pub queued: usize,
+    pub running: usize,
+    pub completed: u64,
+    pub failed: u64,
+    /// Submissions refused because the queue was full
+    pub rejected: u64,
+}
+
+type ProofTask = Box<dyn FnOnce() -> Result<ZkProof, ZkProofError> + Send>;
+
+struct QueuedJob {
+    priority: ProofPriority,
+    job_id: u64,
+    task: ProofTask,
+    /// Opened on submission under the submitter's span, so the job's trace continues on the
+    /// worker thread
+    span: tracing::Span,
+}
+
+impl QueuedJob {
+    fn key(&self) -> (ProofPriority, Reverse<u64>) {
+        (self.priority, Reverse(self.job_id))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: BinaryHeap<QueuedJob>,
+    jobs: HashMap<u64, ProofJobInfo>,
+    finished: VecDeque<u64>,
+    next_job_id: u64,
+    stats: ProverServiceStats,
+    stopping: bool,
+}
+
+struct Shared {
+    config: ProverServiceConfig,
+    queue: Mutex<Queue>,
+    available: Condvar,
+    backpressure: watch::Sender<bool>,
+    metrics: OnceLock<Arc<Metrics>>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update_backpressure(&self, queue: &Queue) {
+        let full = queue.pending.len() >= self.config.queue_capacity;
+        self.backpressure.send_if_modified(|current| std::mem::replace(current, full) != full);
+    }
+
+    fn finish(&self, job_id: u64, status: JobStatus, proving_time_ms: Option<u64>) {
+        let mut queue = self.lock();
+        match status {
+            JobStatus::Completed(_) => queue.stats.completed += 1,
+            _ => queue.stats.failed += 1,
+        }
+        if let Some(info) = queue.jobs.get_mut(&job_id) {
+            info.status = status;
+            info.proving_time_ms = proving_time_ms;
+        }
+        queue.finished.push_back(job_id);
+        while queue.finished.len() > self.config.retained_jobs {
+            if let Some(expired) = queue.finished.pop_front() {
+                queue.jobs.remove(&expired);
+            }
+        }
+    }
+
+    fn run_worker(&self) {
+        loop {
+            let job = {
+                let mut queue = self.lock();
+                loop {
+                    if queue.stopping {
+                        return;
+                    }
+                    if let Some(job) = queue.pending.pop() {
+                        break job;
+                    }
+                    queue = self.available.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+            };
+            {
+                let mut queue = self.lock();
+                queue.stats.queued = queue.pending.len();
+                queue.stats.running += 1;
+                if let Some(info) = queue.jobs.get_mut(&job.job_id) {
+                    info.status = JobStatus::Running;
+                }
+                self.update_backpressure(&queue);
+            }
+
+            let started = Instant::now();
+            let _proving = tracing::info_span!(parent: &job.span, "prover.prove").entered();
+            let status = match catch_unwind(AssertUnwindSafe(job.task)) {
+                Ok(Ok(proof)) => JobStatus::Completed(proof),
+                Ok(Err(e)) => JobStatus::Failed(e.to_string()),
+                Err(_) => JobStatus::Failed("Prover panicked".to_string()),
+            };
+            let elapsed = started.elapsed();
+            if let Some(metrics) = self.metrics.get() {
+                metrics.observe_proof_time(job.priority.label(), elapsed);
+            }
+            self.lock().stats.running -= 1;
+            self.finish(job.job_id, status, Some(elapsed.as_millis() as u64));
+        }
+    }
+}
+
+/// Runs proving jobs on a pool of worker threads
+pub struct ProverService {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ProverService {
+    /// Create the service and start its workers
+    pub fn new(config: ProverServiceConfig) -> Result<Self, ZkProofError> {
+        if config.workers == 0 || config.queue_capacity == 0 {
+            return Err(ZkProofError::ServiceError("Worker count and queue capacity must be positive".to_string()));
+        }
+        let shared = Arc::new(Shared {
+            config: config.clone(),
+            queue: Mutex::new(Queue::default()),
+            available: Condvar::new(),
+            backpressure: watch::Sender::new(false),
+            metrics: OnceLock::new(),
+        });
+        let workers = (0..config.workers)
+            .map(|index| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("codl3-prover-{}", index))
+                    .spawn(move || shared.run_worker())
+                    .map_err(|e| ZkProofError::ServiceError(format!("Failed to spawn prover thread: {}", e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { shared, workers })
+    }
+
+    /// Record proof generation latency in `metrics`
+    pub fn attach_metrics(&self, metrics: Arc<Metrics>) {
+        let _ = self.shared.metrics.set(metrics);
+    }
+
+    /// Queue a proving task, failing with `QueueFull` instead of waiting for room
+    pub fn submit<F>(&self, priority: ProofPriority, task: F) -> Result<u64, ZkProofError>
+    where
+        F: FnOnce() -> Result<ZkProof, ZkProofError> + Send + 'static,
+    {
+        let mut queue = self.shared.lock();
+        if queue.stopping {
+            return Err(ZkProofError::ServiceError("Prover service stopped".to_string()));
+        }
+        if queue.pending.len() >= self.shared.config.queue_capacity {
+            queue.stats.rejected += 1;
+            return Err(ZkProofError::QueueFull(format!("{} jobs waiting", queue.pending.len())));
+        }
+
+        queue.next_job_id += 1;
+        let job_id = queue.next_job_id;
+        let submitted_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        queue.jobs.insert(
+            job_id,
+            ProofJobInfo {
+                job_id,
+                priority,
+                status: JobStatus::Queued,
+                submitted_at,
+                proving_time_ms: None,
+            },
+        );
+        let span = tracing::info_span!("prover.job", job_id, priority = priority.label());
+        queue.pending.push(QueuedJob { priority, job_id, task: Box::new(task), span });
+        queue.stats.queued = queue.pending.len();
+        self.shared.update_backpressure(&queue);
+        drop(queue);
+
+        self.shared.available.notify_one();
+        Ok(job_id)
+    }
+
+    /// Queue a proof of `statement` by `prover`
+    pub fn submit_proof<P>(
+        &self,
+        priority: ProofPriority,
+        prover: Arc<P>,
+        statement: P::Statement,
+        witness: P::Witness,
+    ) -> Result<u64, ZkProofError>
+    where
+        P: ZkProofProver + Send + Sync + 'static,
+        P::Statement: Send + 'static,
+        P::Witness: Send + 'static,
+    {
+        self.submit(priority, move || prover.prove(&statement, &witness))
+    }
+
+    /// Status of a job, or `None` if it is unknown or has expired
+    pub fn status(&self, job_id: u64) -> Option<ProofJobInfo> {
+        self.shared.lock().jobs.get(&job_id).cloned()
+    }
+
+    /// Turns true while the queue is full; producers should hold off until it clears
+    pub fn subscribe_backpressure(&self) -> watch::Receiver<bool> {
+        self.shared.backpressure.subscribe()
+    }
+
+    /// Get prover service statistics
+    pub fn get_stats(&self) -> ProverServiceStats {
+        self.shared.lock().stats.clone()
+    }
+
+    /// Stop accepting jobs, fail the queued ones and wait for running jobs to finish
+    pub fn stop(&mut self) {
+        let dropped: Vec<u64> = {
+            let mut queue = self.shared.lock();
+            queue.stopping = true;
+            let dropped = queue.pending.drain().map(|job| job.job_id).collect();
+            queue.stats.queued = 0;
+            self.shared.update_backpressure(&queue);
+            dropped
+        };
+        for job_id in dropped {
+            self.shared.finish(job_id, JobStatus::Failed("Prover service stopped".to_string()), None);
+        }
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ProverService {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn proof(tag: u8) -> ZkProof {
+        ZkProof { circuit: "test".to_string(), public_inputs: vec![[tag; 32]], proof: vec![tag] }
+    }
+
+    fn wait_finished(service: &ProverService, job_id: u64) -> ProofJobInfo {
+        for _ in 0..500 {
+            let info = service.status(job_id).unwrap();
+            if matches!(info.status, JobStatus::Completed(_) | JobStatus::Failed(_)) {
+                return info;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Job {} did not finish", job_id);
+    }
+
+    #[test]
+    fn test_block_proofs_run_first() {
+        let config = ProverServiceConfig { workers: 1, queue_capacity: 8, retained_jobs: 8 };
+        let service = ProverService::new(config).unwrap();
+        let metrics = Arc::new(Metrics::new());
+        service.attach_metrics(metrics.clone());
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (order_tx, order_rx) = mpsc::channel();
+
+        // Occupy the only worker while the rest of the jobs queue up
+        let blocker = service
+            .submit(ProofPriority::Transaction, move || {
+                release_rx.recv().unwrap();
+                Ok(proof(0))
+            })
+            .unwrap();
+        while service.status(blocker).unwrap().status != JobStatus::Running {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let mut jobs = Vec::new();
+        for (tag, priority) in [(1, ProofPriority::Transaction), (2, ProofPriority::Block), (3, ProofPriority::Transaction)] {
+            let order_tx = order_tx.clone();
+            let job_id = service
+                .submit(priority, move || {
+                    order_tx.send(tag).unwrap();
+                    Ok(proof(tag))
+                })
+                .unwrap();
+            jobs.push(job_id);
+        }
+        assert_eq!(service.status(jobs[1]).unwrap().status, JobStatus::Queued);
+
+        release_tx.send(()).unwrap();
+        for job_id in &jobs {
+            wait_finished(&service, *job_id);
+        }
+        let order: Vec<u8> = order_rx.try_iter().collect();
+        assert_eq!(order, vec![2, 1, 3]);
+        assert_eq!(service.status(jobs[1]).unwrap().status, JobStatus::Completed(proof(2)));
+        assert_eq!(service.get_stats().completed, 4);
+        let exported = metrics.encode().unwrap();
+        assert!(exported.contains("codl3_proof_generation_seconds_count{priority=\"block\"} 1"));
+        assert!(exported.contains("codl3_proof_generation_seconds_count{priority=\"transaction\"} 3"));
+    }
+
+    #[test]
+    fn test_full_queue_rejects_and_signals_backpressure() {
+        let config = ProverServiceConfig { workers: 1, queue_capacity: 2, retained_jobs: 2 };
+        let mut service = ProverService::new(config).unwrap();
+        let backpressure = service.subscribe_backpressure();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        let blocker = service
+            .submit(ProofPriority::Block, move || {
+                release_rx.recv().unwrap();
+                Err(ZkProofError::InvalidWitness("bad witness".to_string()))
+            })
+            .unwrap();
+        while service.status(blocker).unwrap().status != JobStatus::Running {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let queued = service.submit(ProofPriority::Transaction, || Ok(proof(1))).unwrap();
+        let panicking = service.submit(ProofPriority::Transaction, || panic!("prover bug")).unwrap();
+        assert!(*backpressure.borrow());
+        assert!(matches!(
+            service.submit(ProofPriority::Block, || Ok(proof(2))),
+            Err(ZkProofError::QueueFull(_))
+        ));
+        assert_eq!(service.get_stats().rejected, 1);
+
+        release_tx.send(()).unwrap();
+        assert!(matches!(wait_finished(&service, blocker).status, JobStatus::Failed(_)));
+        assert_eq!(wait_finished(&service, panicking).status, JobStatus::Failed("Prover panicked".to_string()));
+        assert!(!*backpressure.borrow());
+
+        // Only the most recent finished jobs stay queryable
+        assert!(service.status(blocker).is_none());
+        assert!(service.status(queued).is_some());
+
+        service.stop();
+        assert!(service.submit(ProofPriority::Block, || Ok(proof(3))).is_err());
+    }
+}
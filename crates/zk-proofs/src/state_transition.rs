@@ -0,0 +1,372 @@
+//! State-transition circuit: proves that applying a batch of leaf updates to a
+//! Poseidon Merkle tree moves its root from `old_root` to `new_root`.
+//!
+//! Each update slot opens the current root at one leaf, replaces the leaf and
+//! carries the recomputed root into the next slot. Slots past the number of
+//! real updates are disabled and leave the root unchanged, so a single key pair
+//! serves every batch up to the shape's capacity.
+
+use crate::cache::{VerificationCache, VerifierCache};
+use crate::error::ZkProofError;
+use crate::poseidon::{HashDomain, Poseidon, ZkFriendlyHash};
+use crate::{encode_proof, verify_groth16, PublicInputs, ZkProof, ZkProofProver, ZkProofVerifier};
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{Groth16, PreparedVerifyingKey, ProvingKey, VerifyingKey};
+use ark_r1cs_std::alloc::AllocVar;
+use ark_r1cs_std::boolean::Boolean;
+use ark_r1cs_std::eq::EqGadget;
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::select::CondSelectGadget;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_snark::SNARK;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Circuit name recorded in proofs
+pub const CIRCUIT_NAME: &str = "state_transition";
+
+/// Size of the circuit; keys generated for one shape only verify that shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransitionShape {
+    /// Merkle tree depth
+    pub depth: usize,
+    /// Maximum leaf updates per proof
+    pub updates: usize,
+}
+
+impl Default for TransitionShape {
+    fn default() -> Self {
+        Self { depth: 32, updates: 8 }
+    }
+}
+
+/// Public statement: the tree root before and after the transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateTransition {
+    pub old_root: Fr,
+    pub new_root: Fr,
+}
+
+impl PublicInputs for StateTransition {
+    fn public_inputs(&self) -> Vec<Fr> {
+        vec![self.old_root, self.new_root]
+    }
+}
+
+/// One leaf replacement with its authentication path in the tree it applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafUpdate {
+    pub index: u64,
+    pub old_leaf: Fr,
+    pub new_leaf: Fr,
+    /// Sibling hashes from the leaf level up
+    pub siblings: Vec<Fr>,
+}
+
+impl LeafUpdate {
+    /// Root of the tree before the update
+    pub fn old_root(&self) -> Fr {
+        merkle_root(self.old_leaf, self.index, &self.siblings)
+    }
+
+    /// Root of the tree after the update
+    pub fn new_root(&self) -> Fr {
+        merkle_root(self.new_leaf, self.index, &self.siblings)
+    }
+}
+
+/// Root of a Poseidon Merkle tree given a leaf and its authentication path
+pub fn merkle_root(leaf: Fr, index: u64, siblings: &[Fr]) -> Fr {
+    siblings.iter().enumerate().fold(leaf, |node, (level, sibling)| {
+        if (index >> level) & 1 == 0 {
+            Poseidon::hash2(HashDomain::MerkleNode, node, *sibling)
+        } else {
+            Poseidon::hash2(HashDomain::MerkleNode, *sibling, node)
+        }
+    })
+}
+
+/// The circuit, padded to its shape
+#[derive(Clone)]
+struct StateTransitionCircuit {
+    shape: TransitionShape,
+    statement: StateTransition,
+    updates: Vec<LeafUpdate>,
+}
+
+impl StateTransitionCircuit {
+    /// All-zero assignment used for key generation
+    fn blank(shape: TransitionShape) -> Self {
+        Self {
+            shape,
+            statement: StateTransition { old_root: Fr::from(0u64), new_root: Fr::from(0u64) },
+            updates: Vec::new(),
+        }
+    }
+}
+
+fn root_gadget(leaf: &FpVar<Fr>, bits: &[Boolean<Fr>], siblings: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+    let mut node = leaf.clone();
+    for (bit, sibling) in bits.iter().zip(siblings) {
+        let left = FpVar::conditionally_select(bit, sibling, &node)?;
+        let right = FpVar::conditionally_select(bit, &node, sibling)?;
+        node = Poseidon::hash_gadget(HashDomain::MerkleNode, &[left, right])?;
+    }
+    Ok(node)
+}
+
+impl ConstraintSynthesizer<Fr> for StateTransitionCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let old_root = FpVar::new_input(cs.clone(), || Ok(self.statement.old_root))?;
+        let new_root = FpVar::new_input(cs.clone(), || Ok(self.statement.new_root))?;
+
+        let mut root = old_root;
+        for slot in 0..self.shape.updates {
+            let update = self.updates.get(slot);
+            let enabled = Boolean::new_witness(cs.clone(), || Ok(update.is_some()))?;
+            let index = update.map_or(0, |update| update.index);
+            let bits = (0..self.shape.depth)
+                .map(|level| Boolean::new_witness(cs.clone(), || Ok((index >> level) & 1 == 1)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let old_leaf = FpVar::new_witness(cs.clone(), || Ok(update.map_or(Fr::from(0u64), |u| u.old_leaf)))?;
+            let new_leaf = FpVar::new_witness(cs.clone(), || Ok(update.map_or(Fr::from(0u64), |u| u.new_leaf)))?;
+            let siblings = (0..self.shape.depth)
+                .map(|level| {
+                    FpVar::new_witness(cs.clone(), || {
+                        Ok(update.map_or(Fr::from(0u64), |u| u.siblings[level]))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // An enabled slot must open the current root; its new leaf then defines the next one
+            let opened = root_gadget(&old_leaf, &bits, &siblings)?;
+            opened.conditional_enforce_equal(&root, &enabled)?;
+            let updated = root_gadget(&new_leaf, &bits, &siblings)?;
+            root = FpVar::conditionally_select(&enabled, &updated, &root)?;
+        }
+        root.enforce_equal(&new_root)
+    }
+}
+
+/// Groth16 proving key for one circuit shape; the verifying key is part of it
+#[derive(Clone)]
+pub struct StateTransitionKeys {
+    pub shape: TransitionShape,
+    pub proving_key: ProvingKey<Bn254>,
+}
+
+impl StateTransitionKeys {
+    /// Run the circuit-specific setup. Whoever knows `rng`'s output can forge
+    /// proofs, so production keys must come from a trusted ceremony.
+    pub fn setup<R: RngCore + CryptoRng>(shape: TransitionShape, rng: &mut R) -> Result<Self, ZkProofError> {
+        let (proving_key, _) = Groth16::<Bn254>::circuit_specific_setup(StateTransitionCircuit::blank(shape), rng)?;
+        Ok(Self { shape, proving_key })
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        &self.proving_key.vk
+    }
+
+    /// Serialize the shape and the compressed proving key
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ZkProofError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.shape.depth as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.shape.updates as u32).to_le_bytes());
+        self.proving_key.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZkProofError> {
+        if bytes.len() < 8 {
+            return Err(ZkProofError::SerializationError("Truncated proving key".to_string()));
+        }
+        let shape = TransitionShape {
+            depth: u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize,
+            updates: u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+        };
+        let proving_key = ProvingKey::deserialize_compressed(&bytes[8..])?;
+        Ok(Self { shape, proving_key })
+    }
+
+    /// Serialize the compressed verifying key handed to verifiers
+    pub fn verifying_key_bytes(&self) -> Result<Vec<u8>, ZkProofError> {
+        let mut bytes = Vec::new();
+        self.verifying_key().serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Proves state transitions with a proving key
+pub struct StateTransitionProver {
+    keys: StateTransitionKeys,
+}
+
+impl StateTransitionProver {
+    pub fn new(keys: StateTransitionKeys) -> Self {
+        Self { keys }
+    }
+
+    pub fn shape(&self) -> TransitionShape {
+        self.keys.shape
+    }
+
+    /// Check that the updates chain from the old root to the new one
+    fn check_witness(&self, statement: &StateTransition, updates: &[LeafUpdate]) -> Result<(), ZkProofError> {
+        let shape = self.keys.shape;
+        if updates.len() > shape.updates {
+            return Err(ZkProofError::InvalidWitness(format!(
+                "{} updates exceed the circuit capacity of {}",
+                updates.len(),
+                shape.updates
+            )));
+        }
+        let mut root = statement.old_root;
+        for (slot, update) in updates.iter().enumerate() {
+            if update.siblings.len() != shape.depth {
+                return Err(ZkProofError::InvalidWitness(format!(
+                    "Update {} has {} siblings, expected {}",
+                    slot,
+                    update.siblings.len(),
+                    shape.depth
+                )));
+            }
+            if shape.depth < 64 && update.index >> shape.depth != 0 {
+                return Err(ZkProofError::InvalidWitness(format!("Update {} index {} is outside the tree", slot, update.index)));
+            }
+            if update.old_root() != root {
+                return Err(ZkProofError::InvalidWitness(format!("Update {} does not open the current root", slot)));
+            }
+            root = update.new_root();
+        }
+        if root != statement.new_root {
+            return Err(ZkProofError::InvalidWitness("Updates do not produce the new root".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl ZkProofProver for StateTransitionProver {
+    type Statement = StateTransition;
+    type Witness = Vec<LeafUpdate>;
+
+    fn prove(&self, statement: &StateTransition, updates: &Vec<LeafUpdate>) -> Result<ZkProof, ZkProofError> {
+        self.check_witness(statement, updates)?;
+        let circuit = StateTransitionCircuit {
+            shape: self.keys.shape,
+            statement: *statement,
+            updates: updates.clone(),
+        };
+        let proof = Groth16::<Bn254>::prove(&self.keys.proving_key, circuit, &mut rand::rngs::OsRng)?;
+        encode_proof(CIRCUIT_NAME, &statement.public_inputs(), &proof)
+    }
+}
+
+/// Verifies state-transition proofs with a prepared verifying key
+pub struct StateTransitionVerifier {
+    verifying_key: PreparedVerifyingKey<Bn254>,
+    cache: VerifierCache,
+}
+
+impl StateTransitionVerifier {
+    pub fn new(verifying_key: &VerifyingKey<Bn254>) -> Self {
+        Self {
+            verifying_key: Groth16::<Bn254>::process_vk(verifying_key).expect("processing a verifying key cannot fail"),
+            cache: VerifierCache::new(verifying_key),
+        }
+    }
+
+    /// Share verification results through `cache`
+    pub fn with_cache(mut self, cache: Arc<VerificationCache>) -> Self {
+        self.cache.attach(cache);
+        self
+    }
+
+    /// Build a verifier from `StateTransitionKeys::verifying_key_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZkProofError> {
+        Ok(Self::new(&VerifyingKey::deserialize_compressed(bytes)?))
+    }
+}
+
+impl ZkProofVerifier for StateTransitionVerifier {
+    type Statement = StateTransition;
+
+    fn verify(&self, statement: &StateTransition, proof: &ZkProof) -> Result<bool, ZkProofError> {
+        verify_groth16(&self.verifying_key, &self.cache, CIRCUIT_NAME, &statement.public_inputs(), proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_to_bytes;
+
+    const SHAPE: TransitionShape = TransitionShape { depth: 3, updates: 2 };
+
+    /// Full tree of `leaves` with the update of `index` to `new_leaf` applied
+    fn update(leaves: &mut [Fr], index: usize, new_leaf: Fr) -> LeafUpdate {
+        let mut siblings = Vec::new();
+        let mut level: Vec<Fr> = leaves.to_vec();
+        let mut position = index;
+        while level.len() > 1 {
+            siblings.push(level[position ^ 1]);
+            level = level.chunks(2).map(|pair| Poseidon::hash2(HashDomain::MerkleNode, pair[0], pair[1])).collect();
+            position /= 2;
+        }
+        let update = LeafUpdate { index: index as u64, old_leaf: leaves[index], new_leaf, siblings };
+        leaves[index] = new_leaf;
+        update
+    }
+
+    fn setup() -> (StateTransitionProver, StateTransitionVerifier) {
+        let keys = StateTransitionKeys::setup(SHAPE, &mut rand::rngs::OsRng).unwrap();
+        let verifier = StateTransitionVerifier::from_bytes(&keys.verifying_key_bytes().unwrap()).unwrap();
+        let keys = StateTransitionKeys::from_bytes(&keys.to_bytes().unwrap()).unwrap();
+        (StateTransitionProver::new(keys), verifier)
+    }
+
+    #[test]
+    fn test_prove_and_verify_transition() {
+        let (prover, verifier) = setup();
+        let mut leaves: Vec<Fr> = (0..8u64).map(Fr::from).collect();
+        let first = update(&mut leaves, 5, Fr::from(50u64));
+        let second = update(&mut leaves, 2, Fr::from(20u64));
+        let statement = StateTransition { old_root: first.old_root(), new_root: second.new_root() };
+
+        let proof = prover.prove(&statement, &vec![first.clone(), second]).unwrap();
+        assert!(verifier.verify(&statement, &proof).unwrap());
+
+        // A single update leaves the second slot disabled
+        let partial = StateTransition { old_root: first.old_root(), new_root: first.new_root() };
+        let proof = prover.prove(&partial, &vec![first]).unwrap();
+        let proof: ZkProof = serde_json::from_slice(&serde_json::to_vec(&proof).unwrap()).unwrap();
+        assert!(verifier.verify(&partial, &proof).unwrap());
+        assert!(!verifier.verify(&statement, &proof).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_tampered_proofs() {
+        let (prover, verifier) = setup();
+        let mut leaves: Vec<Fr> = (0..8u64).map(Fr::from).collect();
+        let change = update(&mut leaves, 1, Fr::from(7u64));
+        let statement = StateTransition { old_root: change.old_root(), new_root: change.new_root() };
+        let proof = prover.prove(&statement, &vec![change.clone()]).unwrap();
+
+        // A different claimed new root, even with matching declared inputs
+        let forged = StateTransition { old_root: statement.old_root, new_root: Fr::from(1u64) };
+        let mut relabeled = proof.clone();
+        relabeled.public_inputs = forged.public_inputs().iter().map(field_to_bytes).collect();
+        assert!(!verifier.verify(&forged, &relabeled).unwrap());
+
+        // Flipped proof bytes either fail to decode or fail the pairing check
+        for position in [0, 40, proof.proof.len() - 1] {
+            let mut tampered = proof.clone();
+            tampered.proof[position] ^= 1;
+            assert!(!matches!(verifier.verify(&statement, &tampered), Ok(true)));
+        }
+
+        // The prover refuses witnesses that do not chain to the claimed root
+        assert!(matches!(prover.prove(&forged, &vec![change]), Err(ZkProofError::InvalidWitness(_))));
+    }
+}
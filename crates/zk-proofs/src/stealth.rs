@@ -0,0 +1,285 @@
+//! Dual-key stealth addresses over ed25519, as in CryptoNote.
+//!
+//! A recipient publishes a scan key `A = aG` and a spend key `B = bG`. For each
+//! output the sender picks a random `r`, publishes the ephemeral key `R = rG` and
+//! pays the one-time key `P = Hs(8rA, i)G + B`. The recipient recognises the output
+//! with only the scan secret, since `8aR = 8rA`, and only the holder of `b` can
+//! derive the one-time secret `Hs(8aR, i) + b` needed to spend it.
+
+use crate::disclosure::{outgoing_tag, ViewingKey};
+use crate::error::ZkProofError;
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use hashing::{Domain, Hasher};
+use rand::{CryptoRng, RngCore};
+
+pub(crate) fn hash_to_scalar(domain: Domain, parts: &[&[u8]]) -> Scalar {
+    let hasher = parts.iter().fold(Hasher::new(domain), |hasher, part| hasher.bytes(part));
+    Scalar::from_bytes_mod_order_wide(&hasher.finish_wide())
+}
+
+pub(crate) fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+pub(crate) fn decompress(bytes: &[u8; 32], what: &str) -> Result<EdwardsPoint, ZkProofError> {
+    CompressedEdwardsY(*bytes)
+        .decompress()
+        .ok_or_else(|| ZkProofError::InvalidKey(format!("{} is not a curve point", what)))
+}
+
+pub(crate) fn scalar(bytes: &[u8; 32], what: &str) -> Result<Scalar, ZkProofError> {
+    Option::from(Scalar::from_canonical_bytes(*bytes))
+        .ok_or_else(|| ZkProofError::InvalidKey(format!("{} is not a canonical scalar", what)))
+}
+
+/// Scalar shared by sender and recipient for output `output_index`
+fn shared_scalar(shared_point: &EdwardsPoint, output_index: u32) -> Scalar {
+    hash_to_scalar(Domain::Stealth, &[shared_point.compress().as_bytes(), &output_index.to_le_bytes()])
+}
+
+/// Scalar added to the spend key for subaddress `index`; only the scan secret derives it
+fn subaddress_scalar(scan_secret: &Scalar, index: u64) -> Scalar {
+    hash_to_scalar(Domain::Stealth, &[b"subaddress", scan_secret.as_bytes(), &index.to_le_bytes()])
+}
+
+/// Public address a recipient hands out: scan key followed by spend key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StealthAddress {
+    pub scan_public: [u8; 32],
+    pub spend_public: [u8; 32],
+}
+
+impl StealthAddress {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.scan_public);
+        bytes[32..].copy_from_slice(&self.spend_public);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZkProofError> {
+        if bytes.len() != 64 {
+            return Err(ZkProofError::InvalidKey(format!("Stealth address has {} bytes, expected 64", bytes.len())));
+        }
+        let address = Self {
+            scan_public: bytes[..32].try_into().unwrap(),
+            spend_public: bytes[32..].try_into().unwrap(),
+        };
+        decompress(&address.scan_public, "Scan key")?;
+        decompress(&address.spend_public, "Spend key")?;
+        Ok(address)
+    }
+
+    /// Derive the one-time output paying this address at `output_index` of a transaction
+    pub fn derive_output<R: RngCore + CryptoRng>(
+        &self,
+        output_index: u32,
+        rng: &mut R,
+    ) -> Result<StealthOutput, ZkProofError> {
+        let scan_public = decompress(&self.scan_public, "Scan key")?;
+        let spend_public = decompress(&self.spend_public, "Spend key")?;
+        let ephemeral_secret = random_scalar(rng);
+        let shared = shared_scalar(&(ephemeral_secret * scan_public).mul_by_cofactor(), output_index);
+        Ok(StealthOutput {
+            ephemeral_key: (&ephemeral_secret * ED25519_BASEPOINT_TABLE).compress().to_bytes(),
+            one_time_key: (&shared * ED25519_BASEPOINT_TABLE + spend_public).compress().to_bytes(),
+        })
+    }
+}
+
+/// Keys published with an output: the sender's ephemeral key and the one-time key paid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StealthOutput {
+    pub ephemeral_key: [u8; 32],
+    pub one_time_key: [u8; 32],
+}
+
+/// What a wallet or node needs to find a recipient's outputs without being able to spend them
+#[derive(Clone)]
+pub struct ViewKey {
+    scan_secret: Scalar,
+    spend_public: EdwardsPoint,
+}
+
+impl ViewKey {
+    pub fn from_bytes(scan_secret: &[u8; 32], spend_public: &[u8; 32]) -> Result<Self, ZkProofError> {
+        Ok(Self {
+            scan_secret: scalar(scan_secret, "Scan secret")?,
+            spend_public: decompress(spend_public, "Spend key")?,
+        })
+    }
+
+    /// Whether the output at `output_index` with these keys pays this recipient
+    pub fn owns(&self, output_index: u32, ephemeral_key: &[u8; 32], one_time_key: &[u8; 32]) -> bool {
+        let Ok(ephemeral) = decompress(ephemeral_key, "Ephemeral key") else {
+            return false;
+        };
+        let shared = shared_scalar(&(self.scan_secret * ephemeral).mul_by_cofactor(), output_index);
+        (&shared * ED25519_BASEPOINT_TABLE + self.spend_public).compress().to_bytes() == *one_time_key
+    }
+
+    /// Subaddress `index` of this recipient. Subaddresses share the scan key and differ in
+    /// the spend key, so one scan finds the outputs paid to any of them. The shared scan key
+    /// also shows publicly that they belong to the same recipient.
+    pub fn subaddress(&self, index: u64) -> StealthAddress {
+        let spend_public = self.spend_public + &subaddress_scalar(&self.scan_secret, index) * ED25519_BASEPOINT_TABLE;
+        StealthAddress {
+            scan_public: (&self.scan_secret * ED25519_BASEPOINT_TABLE).compress().to_bytes(),
+            spend_public: spend_public.compress().to_bytes(),
+        }
+    }
+
+    /// Spend key of the address or subaddress of this recipient the output at `output_index`
+    /// pays, if it was derived with this scan key; look it up among the recipient's subaddresses
+    pub fn output_spend_key(
+        &self,
+        output_index: u32,
+        ephemeral_key: &[u8; 32],
+        one_time_key: &[u8; 32],
+    ) -> Option<[u8; 32]> {
+        let ephemeral = decompress(ephemeral_key, "Ephemeral key").ok()?;
+        let one_time = decompress(one_time_key, "One-time key").ok()?;
+        let shared = shared_scalar(&(self.scan_secret * ephemeral).mul_by_cofactor(), output_index);
+        Some((one_time - &shared * ED25519_BASEPOINT_TABLE).compress().to_bytes())
+    }
+}
+
+/// A recipient's scan and spend secrets
+#[derive(Clone)]
+pub struct StealthKeys {
+    scan_secret: Scalar,
+    spend_secret: Scalar,
+}
+
+impl StealthKeys {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        Self {
+            scan_secret: random_scalar(rng),
+            spend_secret: random_scalar(rng),
+        }
+    }
+
+    /// Deterministic keys from a wallet seed; the scan key is derived from the spend key
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let spend_secret = hash_to_scalar(Domain::Stealth, &[b"spend", seed]);
+        Self {
+            scan_secret: hash_to_scalar(Domain::Stealth, &[b"scan", spend_secret.as_bytes()]),
+            spend_secret,
+        }
+    }
+
+    pub fn address(&self) -> StealthAddress {
+        StealthAddress {
+            scan_public: (&self.scan_secret * ED25519_BASEPOINT_TABLE).compress().to_bytes(),
+            spend_public: (&self.spend_secret * ED25519_BASEPOINT_TABLE).compress().to_bytes(),
+        }
+    }
+
+    /// Scan secret, which can be shared with a node to scan on the wallet's behalf
+    pub fn scan_secret(&self) -> [u8; 32] {
+        self.scan_secret.to_bytes()
+    }
+
+    pub fn view_key(&self) -> ViewKey {
+        ViewKey {
+            scan_secret: self.scan_secret,
+            spend_public: &self.spend_secret * ED25519_BASEPOINT_TABLE,
+        }
+    }
+
+    pub fn subaddress(&self, index: u64) -> StealthAddress {
+        self.view_key().subaddress(index)
+    }
+
+    /// Secret key of an output paid to subaddress `index`, or `None` if it pays someone else
+    pub fn subaddress_secret(&self, index: u64, output_index: u32, output: &StealthOutput) -> Option<[u8; 32]> {
+        let view_key = self.view_key();
+        let spend_key = view_key.output_spend_key(output_index, &output.ephemeral_key, &output.one_time_key)?;
+        if spend_key != self.subaddress(index).spend_public {
+            return None;
+        }
+        let ephemeral = decompress(&output.ephemeral_key, "Ephemeral key").ok()?;
+        let shared = shared_scalar(&(self.scan_secret * ephemeral).mul_by_cofactor(), output_index);
+        Some((shared + self.spend_secret + subaddress_scalar(&self.scan_secret, index)).to_bytes())
+    }
+
+    /// Viewing key disclosing incoming and outgoing transfers without spend capability
+    pub fn viewing_key(&self) -> ViewingKey {
+        ViewingKey::new(self.scan_secret, self.address().spend_public, self.outgoing_key())
+    }
+
+    fn outgoing_key(&self) -> [u8; 32] {
+        hash_to_scalar(Domain::Stealth, &[b"outgoing", self.spend_secret.as_bytes()]).to_bytes()
+    }
+
+    /// Audit tag for a ring input spending the owned output `one_time_key` with `key_image`
+    pub fn audit_tag(&self, key_image: &[u8; 32], one_time_key: &[u8; 32]) -> [u8; 32] {
+        outgoing_tag(&self.outgoing_key(), key_image, one_time_key)
+    }
+
+    /// Secret key of an owned output, or `None` if the output pays someone else
+    pub fn one_time_secret(&self, output_index: u32, output: &StealthOutput) -> Option<[u8; 32]> {
+        if !self.view_key().owns(output_index, &output.ephemeral_key, &output.one_time_key) {
+            return None;
+        }
+        let ephemeral = decompress(&output.ephemeral_key, "Ephemeral key").ok()?;
+        let shared = shared_scalar(&(self.scan_secret * ephemeral).mul_by_cofactor(), output_index);
+        Some((shared + self.spend_secret).to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipient_finds_and_spends_only_own_outputs() {
+        let mut rng = rand::rngs::OsRng;
+        let alice = StealthKeys::generate(&mut rng);
+        let bob = StealthKeys::from_seed(b"bob");
+        let address = StealthAddress::from_bytes(&alice.address().to_bytes()).unwrap();
+
+        let first = address.derive_output(0, &mut rng).unwrap();
+        let second = address.derive_output(0, &mut rng).unwrap();
+        // Outputs to the same address are unlinkable
+        assert_ne!(first.one_time_key, second.one_time_key);
+        assert_ne!(first.one_time_key, address.spend_public);
+
+        let view_key = ViewKey::from_bytes(&alice.scan_secret(), &address.spend_public).unwrap();
+        assert!(view_key.owns(0, &first.ephemeral_key, &first.one_time_key));
+        assert!(!view_key.owns(1, &first.ephemeral_key, &first.one_time_key));
+        assert!(!bob.view_key().owns(0, &first.ephemeral_key, &first.one_time_key));
+
+        // The one-time secret controls the one-time key
+        let secret = Scalar::from_canonical_bytes(alice.one_time_secret(0, &first).unwrap()).unwrap();
+        assert_eq!((&secret * ED25519_BASEPOINT_TABLE).compress().to_bytes(), first.one_time_key);
+        assert!(bob.one_time_secret(0, &first).is_none());
+
+        assert_eq!(StealthKeys::from_seed(b"bob").address(), bob.address());
+        assert!(StealthAddress::from_bytes(&[0u8; 63]).is_err());
+    }
+
+    #[test]
+    fn test_one_scan_finds_outputs_to_every_subaddress() {
+        let mut rng = rand::rngs::OsRng;
+        let exchange = StealthKeys::from_seed(b"exchange");
+        let (first, second) = (exchange.subaddress(0), exchange.subaddress(1));
+        assert_eq!(first.scan_public, exchange.address().scan_public);
+        assert_ne!(first.spend_public, second.spend_public);
+        assert_ne!(first.spend_public, exchange.address().spend_public);
+
+        let output = second.derive_output(3, &mut rng).unwrap();
+        let view_key = exchange.view_key();
+        let spend_key = view_key.output_spend_key(3, &output.ephemeral_key, &output.one_time_key);
+        assert_eq!(spend_key, Some(second.spend_public));
+        assert!(!view_key.owns(3, &output.ephemeral_key, &output.one_time_key));
+
+        let secret = Scalar::from_canonical_bytes(exchange.subaddress_secret(1, 3, &output).unwrap()).unwrap();
+        assert_eq!((&secret * ED25519_BASEPOINT_TABLE).compress().to_bytes(), output.one_time_key);
+        assert!(exchange.subaddress_secret(0, 3, &output).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+#!/usr/bin/env node
+// Collect the results of `cargo bench` into one JSON report, and optionally compare them
+// with the report of an earlier release.
+//
+//   cargo bench --workspace
+//   node scripts/bench-report.js [--out bench-results/<version>.json] [--baseline <report.json>] [--threshold 10]
+//
+// Exits with status 1 when a benchmark's mean got slower than the baseline by more than
+// the threshold, in percent.
+
+const fs = require("fs");
+const path = require("path");
+const { execSync } = require("child_process");
+
+const ROOT = path.join(__dirname, "..");
+const CRITERION_DIR = path.join(ROOT, "target", "criterion");
+
+function parseArgs(argv) {
+    const args = { threshold: 10 };
+    for (let i = 0; i < argv.length; i += 2) {
+        const value = argv[i + 1];
+        switch (argv[i]) {
+            case "--out": args.out = value; break;
+            case "--baseline": args.baseline = value; break;
+            case "--threshold": args.threshold = Number(value); break;
+            default: throw new Error(`Unknown argument ${argv[i]}`);
+        }
+    }
+    return args;
+}
+
+function workspaceVersion() {
+    const manifest = fs.readFileSync(path.join(ROOT, "Cargo.toml"), "utf8");
+    const section = manifest.split("[workspace.package]")[1] || "";
+    const match = section.match(/^version\s*=\s*"([^"]+)"/m);
+    return match ? match[1] : "unknown";
+}
+
+function gitCommit() {
+    try {
+        return execSync("git rev-parse HEAD", { cwd: ROOT, stdio: ["ignore", "pipe", "ignore"] }).toString().trim();
+    } catch (e) {
+        return null;
+    }
+}
+
+// Every benchmark criterion measured last, keyed by its full id, e.g. "txpool/add 100k"
+function collect(dir, benchmarks = {}) {
+    for (const entry of fs.readdirSync(dir, { withFileTypes: true })) {
+        if (!entry.isDirectory()) continue;
+        const child = path.join(dir, entry.name);
+        const benchmarkFile = path.join(child, "new", "benchmark.json");
+        const estimatesFile = path.join(child, "new", "estimates.json");
+        if (fs.existsSync(benchmarkFile) && fs.existsSync(estimatesFile)) {
+            const benchmark = JSON.parse(fs.readFileSync(benchmarkFile, "utf8"));
+            const estimates = JSON.parse(fs.readFileSync(estimatesFile, "utf8"));
+            benchmarks[benchmark.full_id] = {
+                mean_ns: estimates.mean.point_estimate,
+                median_ns: estimates.median.point_estimate,
+                std_dev_ns: estimates.std_dev.point_estimate,
+            };
+        } else if (entry.name !== "report") {
+            collect(child, benchmarks);
+        }
+    }
+    return benchmarks;
+}
+
+// Benchmarks whose mean moved by more than `threshold` percent from `baseline`
+function compare(baseline, current, threshold) {
+    const regressions = [];
+    for (const [id, result] of Object.entries(current.benchmarks)) {
+        const before = baseline.benchmarks[id];
+        if (!before) continue;
+        const change = (result.mean_ns / before.mean_ns - 1) * 100;
+        const marker = change > threshold ? "REGRESSED" : change < -threshold ? "improved" : "";
+        console.log(`${id.padEnd(48)} ${before.mean_ns.toFixed(0).padStart(14)} ns -> ` +
+            `${result.mean_ns.toFixed(0).padStart(14)} ns ${change.toFixed(1).padStart(7)}% ${marker}`);
+        if (change > threshold) regressions.push(id);
+    }
+    return regressions;
+}
+
+function main() {
+    const args = parseArgs(process.argv.slice(2));
+    if (!fs.existsSync(CRITERION_DIR)) {
+        console.error(`No results in ${CRITERION_DIR}; run cargo bench first`);
+        process.exit(2);
+    }
+
+    const version = workspaceVersion();
+    const report = {
+        version,
+        commit: gitCommit(),
+        generated_at: new Date().toISOString(),
+        benchmarks: collect(CRITERION_DIR),
+    };
+    const out = args.out || path.join(ROOT, "bench-results", `${version}.json`);
+    fs.mkdirSync(path.dirname(out), { recursive: true });
+    fs.writeFileSync(out, JSON.stringify(report, null, 2) + "\n");
+    console.log(`Wrote ${Object.keys(report.benchmarks).length} benchmarks to ${out}`);
+
+    if (args.baseline) {
+        const baseline = JSON.parse(fs.readFileSync(args.baseline, "utf8"));
+        console.log(`\nCompared with ${baseline.version} (${baseline.commit || "unknown commit"}):`);
+        const regressions = compare(baseline, report, args.threshold);
+        if (regressions.length > 0) {
+            console.error(`\n${regressions.length} benchmarks regressed by more than ${args.threshold}%`);
+            process.exit(1);
+        }
+    }
+}
+
+main();